@@ -111,6 +111,14 @@ impl<'a> Value<'a> {
         None
     }
 
+    /// Returns an integer if the value is an integer. Otherwise returns `None`.
+    pub fn as_int(self) -> Option<i32> {
+        if let Self::Int(i) = self {
+            return Some(i);
+        }
+        None
+    }
+
     /// Returns a float if the value is a float. Otherwise returns `None`.
     pub fn as_float(self) -> Option<f32> {
         if let Self::Float(f) = self {
@@ -92,6 +92,32 @@ pub enum FcntlLeaseType {
     F_SHLCK = const_conversions::i32_from_u32(bindings::LINUX_F_SHLCK),
 }
 
+/// Lock type, as used in [`flock::l_type`] with [`FcntlCommand::F_SETLK`] and related commands.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(i16)]
+#[allow(non_camel_case_types)]
+pub enum FcntlLockType {
+    F_RDLCK = const_conversions::i16_from_u32(bindings::LINUX_F_RDLCK),
+    F_WRLCK = const_conversions::i16_from_u32(bindings::LINUX_F_WRLCK),
+    F_UNLCK = const_conversions::i16_from_u32(bindings::LINUX_F_UNLCK),
+}
+
+/// Record lock description, as used with [`FcntlCommand::F_SETLK`] and related commands.
+#[allow(non_camel_case_types)]
+pub type flock = bindings::linux_flock;
+unsafe impl shadow_pod::Pod for flock {}
+
+bitflags::bitflags! {
+    /// Operations for the `flock` syscall.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct FlockOperation: i32 {
+        const LOCK_SH = const_conversions::i32_from_u32(bindings::LINUX_LOCK_SH);
+        const LOCK_EX = const_conversions::i32_from_u32(bindings::LINUX_LOCK_EX);
+        const LOCK_NB = const_conversions::i32_from_u32(bindings::LINUX_LOCK_NB);
+        const LOCK_UN = const_conversions::i32_from_u32(bindings::LINUX_LOCK_UN);
+    }
+}
+
 /// Seal type, as used with [`FcntlCommand::F_ADD_SEALS`] and [`FcntlCommand::F_GET_SEALS`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[repr(i32)]
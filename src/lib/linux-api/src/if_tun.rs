@@ -0,0 +1,41 @@
+//! Types used by the TUN/TAP device, which is opened from `/dev/net/tun`. See the kernel's
+//! `Documentation/networking/tuntap.rst`.
+
+// Manually translated from linux/if_tun.h.
+// `linux/if_tun.h` isn't currently included in our generated bindings.
+
+/// Attach the file to a new or existing TUN/TAP interface. `_IOW('T', 202, int)`.
+pub const TUNSETIFF: u32 = 0x400454ca;
+
+/// Get the name and flags of the file's interface. `_IOR('T', 210, unsigned int)`.
+pub const TUNGETIFF: u32 = 0x800454d2;
+
+/// The length of the packet information header (`struct tun_pi`) that precedes each packet unless
+/// `IFF_NO_PI` is set.
+pub const TUN_PI_LEN: usize = 4;
+
+/// The IPv4 protocol ID, as used in the `proto` field of the packet information header. From
+/// `linux/if_ether.h`.
+pub const ETH_P_IP: u16 = 0x0800;
+
+bitflags::bitflags! {
+    /// Flags of the `ifr_flags` field of the `ifreq` passed to `TUNSETIFF`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct TunFlags: core::ffi::c_short {
+        /// A TUN (layer 3) interface.
+        const IFF_TUN = 0x0001;
+        /// A TAP (layer 2) interface.
+        const IFF_TAP = 0x0002;
+        const IFF_NAPI = 0x0010;
+        const IFF_NAPI_FRAGS = 0x0020;
+        const IFF_NO_CARRIER = 0x0040;
+        const IFF_MULTI_QUEUE = 0x0100;
+        const IFF_ATTACH_QUEUE = 0x0200;
+        const IFF_DETACH_QUEUE = 0x0400;
+        const IFF_PERSIST = 0x0800;
+        /// Packets aren't preceded by a packet information header.
+        const IFF_NO_PI = 0x1000;
+        const IFF_ONE_QUEUE = 0x2000;
+        const IFF_VNET_HDR = 0x4000;
+    }
+}
@@ -1,6 +1,33 @@
-use crate::bindings;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::{bindings, const_conversions};
 
 pub use bindings::linux_sockaddr_in;
 #[allow(non_camel_case_types)]
 pub type sockaddr_in = linux_sockaddr_in;
 unsafe impl shadow_pod::Pod for sockaddr_in {}
+
+/// `IPPROTO_IP` socket option to set the path MTU discovery mode. See [`IpPmtuDisc`].
+pub const IP_MTU_DISCOVER: i32 = const_conversions::i32_from_u32(bindings::LINUX_IP_MTU_DISCOVER);
+
+/// `IPPROTO_IP` socket option to get the path MTU of a connected socket.
+pub const IP_MTU: i32 = const_conversions::i32_from_u32(bindings::LINUX_IP_MTU);
+
+/// Path MTU discovery modes of the `IP_MTU_DISCOVER` socket option. See ip(7).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum IpPmtuDisc {
+    /// Never send packets with the "don't fragment" flag.
+    IP_PMTUDISC_DONT = const_conversions::i32_from_u32(bindings::LINUX_IP_PMTUDISC_DONT),
+    /// Use per-route settings.
+    IP_PMTUDISC_WANT = const_conversions::i32_from_u32(bindings::LINUX_IP_PMTUDISC_WANT),
+    /// Always send packets with the "don't fragment" flag.
+    IP_PMTUDISC_DO = const_conversions::i32_from_u32(bindings::LINUX_IP_PMTUDISC_DO),
+    /// Set the "don't fragment" flag but ignore the path MTU.
+    IP_PMTUDISC_PROBE = const_conversions::i32_from_u32(bindings::LINUX_IP_PMTUDISC_PROBE),
+    /// Ignore the path MTU and never set the "don't fragment" flag.
+    IP_PMTUDISC_INTERFACE = const_conversions::i32_from_u32(bindings::LINUX_IP_PMTUDISC_INTERFACE),
+    /// Like `IP_PMTUDISC_INTERFACE`, but also allow forwarded packets to be fragmented.
+    IP_PMTUDISC_OMIT = const_conversions::i32_from_u32(bindings::LINUX_IP_PMTUDISC_OMIT),
+}
@@ -155,6 +155,9 @@ pub enum IoctlRequest {
     SIOCGHWTSTAMP = bindings::LINUX_SIOCGHWTSTAMP,
     SIOCDEVPRIVATE = bindings::LINUX_SIOCDEVPRIVATE,
     SIOCPROTOPRIVATE = bindings::LINUX_SIOCPROTOPRIVATE,
+    // `linux/if_tun.h` isn't currently included in our generated bindings
+    TUNSETIFF = crate::if_tun::TUNSETIFF,
+    TUNGETIFF = crate::if_tun::TUNGETIFF,
 }
 
 impl IoctlRequest {
@@ -0,0 +1,201 @@
+//! System V IPC types and constants.
+//!
+//! `linux/ipc.h`, `linux/shm.h`, and `linux/sem.h` aren't currently included in our generated
+//! bindings, so the definitions here are manually translated. The structure layouts are the
+//! x86-64 "64-bit" versions (`ipc64_perm`, `shmid64_ds`, `semid64_ds`), which are the only
+//! versions used by the x86-64 syscalls.
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::bindings;
+
+#[allow(non_camel_case_types)]
+pub type kernel_key_t = bindings::linux___kernel_key_t;
+
+/// Key that always creates a new IPC object.
+pub const IPC_PRIVATE: kernel_key_t = 0;
+
+/// Create the object if the key doesn't exist.
+pub const IPC_CREAT: i32 = 0o1000;
+/// Fail if the key exists.
+pub const IPC_EXCL: i32 = 0o2000;
+/// Return an error instead of waiting.
+pub const IPC_NOWAIT: i32 = 0o4000;
+
+/// Attach the segment read-only (`shmat`).
+pub const SHM_RDONLY: i32 = 0o10000;
+/// Round the attach address down to `SHMLBA` (`shmat`).
+pub const SHM_RND: i32 = 0o20000;
+/// Take over the region on attach (`shmat`).
+pub const SHM_REMAP: i32 = 0o40000;
+/// Execution access (`shmat`).
+pub const SHM_EXEC: i32 = 0o100000;
+
+/// Segment will be destroyed on last detach (`shm_perm.mode`).
+pub const SHM_DEST: u32 = 0o1000;
+/// Segment will not be swapped (`shm_perm.mode`).
+pub const SHM_LOCKED: u32 = 0o2000;
+
+/// Undo the operation when the process exits (`semop`).
+pub const SEM_UNDO: i16 = 0x1000;
+
+/// Maximum number of IPC objects of each type, from `linux/ipc.h`.
+pub const IPCMNI: usize = 32768;
+/// Segment low boundary address multiple (`PAGE_SIZE` on x86-64).
+pub const SHMLBA: usize = 4096;
+/// Minimum size of a shared memory segment in bytes.
+pub const SHMMIN: usize = 1;
+/// Default maximum size of a shared memory segment in bytes (`ULONG_MAX - (1UL << 24)`).
+pub const SHMMAX: usize = usize::MAX - (1 << 24);
+/// Maximum number of shared memory segments.
+pub const SHMMNI: usize = 4096;
+/// Maximum number of semaphore sets.
+pub const SEMMNI: usize = 32000;
+/// Maximum number of semaphores per set.
+pub const SEMMSL: usize = 32000;
+/// Maximum number of operations per `semop` call.
+pub const SEMOPM: usize = 500;
+/// Maximum value of a semaphore.
+pub const SEMVMX: i32 = 32767;
+/// Maximum adjust-on-exit value.
+pub const SEMAEM: i32 = SEMVMX;
+
+/// Commands for `shmctl`, including the commands shared by all IPC object types.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum ShmCtlCmd {
+    IPC_RMID = 0,
+    IPC_SET = 1,
+    IPC_STAT = 2,
+    IPC_INFO = 3,
+    SHM_LOCK = 11,
+    SHM_UNLOCK = 12,
+    SHM_STAT = 13,
+    SHM_INFO = 14,
+    SHM_STAT_ANY = 15,
+}
+
+/// Commands for `semctl`, including the commands shared by all IPC object types.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum SemCtlCmd {
+    IPC_RMID = 0,
+    IPC_SET = 1,
+    IPC_STAT = 2,
+    IPC_INFO = 3,
+    GETPID = 11,
+    GETVAL = 12,
+    GETALL = 13,
+    GETNCNT = 14,
+    GETZCNT = 15,
+    SETVAL = 16,
+    SETALL = 17,
+    SEM_STAT = 18,
+    SEM_INFO = 19,
+    SEM_STAT_ANY = 20,
+}
+
+// Manually translated from asm-generic/ipcbuf.h.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct linux_ipc64_perm {
+    pub key: kernel_key_t,
+    pub uid: bindings::linux___kernel_uid32_t,
+    pub gid: bindings::linux___kernel_gid32_t,
+    pub cuid: bindings::linux___kernel_uid32_t,
+    pub cgid: bindings::linux___kernel_gid32_t,
+    pub mode: bindings::linux___kernel_mode_t,
+    pub seq: core::ffi::c_ushort,
+    pub l__pad2: core::ffi::c_ushort,
+    pub l__unused1: bindings::linux___kernel_ulong_t,
+    pub l__unused2: bindings::linux___kernel_ulong_t,
+}
+
+#[allow(non_camel_case_types)]
+pub type ipc_perm = linux_ipc64_perm;
+unsafe impl shadow_pod::Pod for ipc_perm {}
+
+// Manually translated from arch/x86/include/uapi/asm/shmbuf.h.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct linux_shmid64_ds {
+    /// operation perms
+    pub shm_perm: linux_ipc64_perm,
+    /// size of segment (bytes)
+    pub shm_segsz: bindings::linux___kernel_size_t,
+    /// last attach time
+    pub shm_atime: bindings::linux___kernel_long_t,
+    /// last detach time
+    pub shm_dtime: bindings::linux___kernel_long_t,
+    /// last change time
+    pub shm_ctime: bindings::linux___kernel_long_t,
+    /// pid of creator
+    pub shm_cpid: bindings::linux___kernel_pid_t,
+    /// pid of last operator
+    pub shm_lpid: bindings::linux___kernel_pid_t,
+    /// no. of current attaches
+    pub shm_nattch: bindings::linux___kernel_ulong_t,
+    pub l__unused4: bindings::linux___kernel_ulong_t,
+    pub l__unused5: bindings::linux___kernel_ulong_t,
+}
+
+#[allow(non_camel_case_types)]
+pub type shmid_ds = linux_shmid64_ds;
+unsafe impl shadow_pod::Pod for shmid_ds {}
+
+// Manually translated from arch/x86/include/uapi/asm/sembuf.h.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct linux_semid64_ds {
+    /// permissions .. see ipc.h
+    pub sem_perm: linux_ipc64_perm,
+    /// last semop time
+    pub sem_otime: bindings::linux___kernel_long_t,
+    pub l__unused1: bindings::linux___kernel_ulong_t,
+    /// last change time
+    pub sem_ctime: bindings::linux___kernel_long_t,
+    pub l__unused2: bindings::linux___kernel_ulong_t,
+    /// no. of semaphores in array
+    pub sem_nsems: bindings::linux___kernel_ulong_t,
+    pub l__unused3: bindings::linux___kernel_ulong_t,
+    pub l__unused4: bindings::linux___kernel_ulong_t,
+}
+
+#[allow(non_camel_case_types)]
+pub type semid_ds = linux_semid64_ds;
+unsafe impl shadow_pod::Pod for semid_ds {}
+
+// Manually translated from linux/sem.h.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct linux_sembuf {
+    /// semaphore index in array
+    pub sem_num: core::ffi::c_ushort,
+    /// semaphore operation
+    pub sem_op: core::ffi::c_short,
+    /// operation flags
+    pub sem_flg: core::ffi::c_short,
+}
+
+#[allow(non_camel_case_types)]
+pub type sembuf = linux_sembuf;
+unsafe impl shadow_pod::Pod for sembuf {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(core::mem::size_of::<ipc_perm>(), 48);
+        assert_eq!(core::mem::size_of::<shmid_ds>(), 112);
+        assert_eq!(core::mem::size_of::<semid_ds>(), 104);
+        assert_eq!(core::mem::size_of::<sembuf>(), 6);
+    }
+}
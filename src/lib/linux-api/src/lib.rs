@@ -57,21 +57,27 @@ pub mod errno;
 pub mod exit;
 pub mod fcntl;
 pub mod futex;
+pub mod if_tun;
 pub mod inet;
 pub mod ioctls;
+pub mod ipc;
 pub mod ldt;
 pub mod limits;
 pub mod mman;
+pub mod mqueue;
+pub mod netdevice;
 pub mod netlink;
 pub mod poll;
 pub mod posix_types;
 pub mod prctl;
+pub mod random;
 pub mod resource;
 pub mod rseq;
 pub mod rtnetlink;
 pub mod sched;
 pub mod signal;
 pub mod socket;
+pub mod stat;
 pub mod syscall;
 pub mod sysinfo;
 pub mod time;
@@ -109,6 +115,12 @@ mod const_conversions {
         val as i32
     }
 
+    pub const fn i16_from_u32(val: u32) -> i16 {
+        assert!(val <= (i16::MAX as u32));
+
+        val as i16
+    }
+
     pub const fn u16_from_u32(val: u32) -> u16 {
         assert!(val <= (u16::MAX as u32));
 
@@ -0,0 +1,51 @@
+use crate::bindings;
+
+/// Maximum message priority (exclusive), from `linux/mqueue.h`.
+pub const MQ_PRIO_MAX: u32 = 32768;
+
+/// Maximum number of bytes of messages in a queue, from `linux/mqueue.h`.
+pub const MQ_BYTES_MAX: u32 = 819200;
+
+/// Hard upper limit for `mq_maxmsg`, from `linux/ipc_namespace.h` (`HARD_MSGMAX`).
+pub const HARD_MSGMAX: u32 = 65536;
+
+/// Hard upper limit for `mq_msgsize`, from `linux/ipc_namespace.h` (`HARD_MSGSIZEMAX`).
+pub const HARD_MSGSIZEMAX: u32 = 16 * 1024 * 1024;
+
+/// Default `mq_maxmsg` when `mq_open` is called without attributes (`DFLT_MSG`).
+pub const DFLT_MSG: u32 = 10;
+
+/// Default `mq_msgsize` when `mq_open` is called without attributes (`DFLT_MSGSIZE`).
+pub const DFLT_MSGSIZE: u32 = 8192;
+
+// Manually translated from linux/mqueue.h.
+// `linux/mqueue.h` isn't currently included in our generated bindings.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct linux_mq_attr {
+    /// message queue flags
+    pub mq_flags: bindings::linux___kernel_long_t,
+    /// maximum number of messages
+    pub mq_maxmsg: bindings::linux___kernel_long_t,
+    /// maximum message size
+    pub mq_msgsize: bindings::linux___kernel_long_t,
+    /// number of messages currently queued
+    pub mq_curmsgs: bindings::linux___kernel_long_t,
+    /// ignored for input, zeroed for output
+    pub l__reserved: [bindings::linux___kernel_long_t; 4],
+}
+
+#[allow(non_camel_case_types)]
+pub type mq_attr = linux_mq_attr;
+unsafe impl shadow_pod::Pod for mq_attr {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(core::mem::size_of::<mq_attr>(), 64);
+    }
+}
@@ -0,0 +1,138 @@
+//! Types used by the network device ioctls such as `SIOCGIFADDR`. See netdevice(7).
+
+use crate::bindings;
+
+/// Size of an interface name buffer, including the terminating nul, from `linux/if.h`.
+pub const IFNAMSIZ: usize = 16;
+
+/// Ethernet hardware address type, from `linux/if_arp.h`.
+pub const ARPHRD_ETHER: u16 = 1;
+
+/// Loopback hardware address type, from `linux/if_arp.h`.
+pub const ARPHRD_LOOPBACK: u16 = 772;
+
+/// Hardware address type of interfaces without a hardware address (such as TUN interfaces), from
+/// `linux/if_arp.h`.
+pub const ARPHRD_NONE: u16 = 0xfffe;
+
+bitflags::bitflags! {
+    /// Interface flags, as used e.g. with `SIOCGIFFLAGS`. From `linux/if.h`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct IfFlags: core::ffi::c_short {
+        const IFF_UP = 1 << 0;
+        const IFF_BROADCAST = 1 << 1;
+        const IFF_DEBUG = 1 << 2;
+        const IFF_LOOPBACK = 1 << 3;
+        const IFF_POINTOPOINT = 1 << 4;
+        const IFF_NOTRAILERS = 1 << 5;
+        const IFF_RUNNING = 1 << 6;
+        const IFF_NOARP = 1 << 7;
+        const IFF_PROMISC = 1 << 8;
+        const IFF_ALLMULTI = 1 << 9;
+        const IFF_MASTER = 1 << 10;
+        const IFF_SLAVE = 1 << 11;
+        const IFF_MULTICAST = 1 << 12;
+        const IFF_PORTSEL = 1 << 13;
+        const IFF_AUTOMEDIA = 1 << 14;
+        const IFF_DYNAMIC = 1 << 15;
+    }
+}
+
+// Manually translated from linux/socket.h.
+// bindgen doesn't generate the generic `sockaddr` since the kernel headers only define it when
+// building the kernel.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct linux_sockaddr {
+    pub sa_family: bindings::linux___kernel_sa_family_t,
+    pub sa_data: [core::ffi::c_char; 14],
+}
+
+#[allow(non_camel_case_types)]
+pub type sockaddr = linux_sockaddr;
+unsafe impl shadow_pod::Pod for sockaddr {}
+
+// Manually translated from linux/if.h.
+// `linux/if.h` isn't currently included in our generated bindings.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union linux_ifreq_ifru {
+    pub ifru_addr: linux_sockaddr,
+    pub ifru_dstaddr: linux_sockaddr,
+    pub ifru_broadaddr: linux_sockaddr,
+    pub ifru_netmask: linux_sockaddr,
+    pub ifru_hwaddr: linux_sockaddr,
+    pub ifru_flags: core::ffi::c_short,
+    pub ifru_ivalue: core::ffi::c_int,
+    pub ifru_mtu: core::ffi::c_int,
+    pub ifru_slave: [core::ffi::c_char; IFNAMSIZ],
+    pub ifru_newname: [core::ffi::c_char; IFNAMSIZ],
+    pub ifru_data: *mut core::ffi::c_void,
+    /// Manually translated from `struct ifmap ifru_map`, which is the largest member of the union.
+    /// We don't currently use it.
+    pub l_ifru_map: [core::ffi::c_ulong; 3],
+}
+
+// Manually translated from linux/if.h.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct linux_ifreq {
+    pub ifr_name: [core::ffi::c_char; IFNAMSIZ],
+    pub ifr_ifru: linux_ifreq_ifru,
+}
+
+impl core::fmt::Debug for linux_ifreq {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("linux_ifreq")
+            .field("ifr_name", &self.ifr_name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[allow(non_camel_case_types)]
+pub type ifreq = linux_ifreq;
+unsafe impl shadow_pod::Pod for ifreq {}
+
+impl ifreq {
+    /// The interface name, up to (but not including) the first nul. Like Linux, the last byte of
+    /// `ifr_name` is ignored and the name is always treated as nul-terminated.
+    pub fn name(&self) -> &[u8] {
+        // c_char may be signed, so we reinterpret the name as bytes
+        let name: &[u8; IFNAMSIZ] = bytemuck::cast_ref(&self.ifr_name);
+        let name = &name[..IFNAMSIZ - 1];
+        let len = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+        &name[..len]
+    }
+
+    /// Set the interface name. Panics if the name (including a terminating nul) doesn't fit in
+    /// `ifr_name`.
+    pub fn set_name(&mut self, name: &[u8]) {
+        assert!(name.len() < IFNAMSIZ);
+        let buf: &mut [u8; IFNAMSIZ] = bytemuck::cast_mut(&mut self.ifr_name);
+        buf.fill(0);
+        buf[..name.len()].copy_from_slice(name);
+    }
+}
+
+// Manually translated from linux/if.h.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct linux_ifconf {
+    /// Size of the buffer.
+    pub ifc_len: core::ffi::c_int,
+    /// Explicit padding before the pointer.
+    pub l_pad: core::ffi::c_int,
+    /// Union of `ifcu_buf` and `ifcu_req`: a buffer of `ifreq` structs.
+    pub ifc_buf: *mut core::ffi::c_void,
+}
+
+#[allow(non_camel_case_types)]
+pub type ifconf = linux_ifconf;
+unsafe impl shadow_pod::Pod for ifconf {}
+
+static_assertions::assert_eq_size!(ifreq, [u8; 40]);
+static_assertions::assert_eq_size!(ifconf, [u8; 16]);
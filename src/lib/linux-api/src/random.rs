@@ -0,0 +1,15 @@
+// Manually translated from linux/random.h.
+// `linux/random.h` isn't currently included in our generated bindings.
+
+bitflags::bitflags! {
+    /// Flags for `getrandom`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct GrndFlags: u32 {
+        /// Don't block if no entropy is available.
+        const GRND_NONBLOCK = 0x0001;
+        /// Read from the "random" source instead of the "urandom" source.
+        const GRND_RANDOM = 0x0002;
+        /// Return non-cryptographic random bytes if the pool isn't initialized yet.
+        const GRND_INSECURE = 0x0004;
+    }
+}
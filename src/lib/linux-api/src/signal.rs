@@ -5,7 +5,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use shadow_pod::Pod;
 use vasi::VirtualAddressSpaceIndependent;
 
-use crate::bindings::{self, linux_sigval};
+use crate::bindings;
 use crate::const_conversions;
 use crate::const_conversions::i32_from_u32_allowing_wraparound;
 use crate::errno::Errno;
@@ -346,6 +346,64 @@ pub enum SigInfoCodeSys {
 #[allow(non_camel_case_types)]
 pub type linux_siginfo_t = bindings::linux_siginfo_t;
 
+#[allow(non_camel_case_types)]
+pub type linux_sigval = bindings::linux_sigval;
+
+/// Number of `int` padding fields at the end of `sigevent`.
+const SIGEV_PAD_SIZE: usize = (const_conversions::usize_from_u32(bindings::LINUX_SIGEV_MAX_SIZE)
+    - core::mem::size_of::<linux_sigval>()
+    - 2 * core::mem::size_of::<core::ffi::c_int>())
+    / core::mem::size_of::<core::ffi::c_int>();
+
+// Manually translated from asm-generic/siginfo.h.
+// bindgen doesn't generate `sigevent` since it's only used in function prototypes.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct linux_sigevent {
+    pub sigev_value: linux_sigval,
+    pub sigev_signo: core::ffi::c_int,
+    pub sigev_notify: core::ffi::c_int,
+    /// Union of `_tid`, `_sigev_thread`, and padding. `_tid` (for `SIGEV_THREAD_ID`) is the first
+    /// element.
+    pub l_sigev_un: [core::ffi::c_int; SIGEV_PAD_SIZE],
+}
+
+impl core::fmt::Debug for linux_sigevent {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("linux_sigevent")
+            .field("sigev_signo", &self.sigev_signo)
+            .field("sigev_notify", &self.sigev_notify)
+            .finish_non_exhaustive()
+    }
+}
+
+#[allow(non_camel_case_types)]
+pub type sigevent = linux_sigevent;
+unsafe impl shadow_pod::Pod for sigevent {}
+
+impl sigevent {
+    pub fn notify(&self) -> Result<SigevNotify, i32> {
+        SigevNotify::try_from(self.sigev_notify).map_err(|_| self.sigev_notify)
+    }
+
+    /// The target thread for `SIGEV_THREAD_ID`.
+    pub fn tid(&self) -> kernel_pid_t {
+        self.l_sigev_un[0]
+    }
+}
+
+/// Notification method in a [`sigevent`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum SigevNotify {
+    SIGEV_SIGNAL = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_SIGNAL),
+    SIGEV_NONE = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_NONE),
+    SIGEV_THREAD = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_THREAD),
+    SIGEV_THREAD_ID = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_THREAD_ID),
+}
+
 type SigInfoDetailsFields = bindings::linux___sifields;
 
 // The fields of `linux___sifields` in the original Linux source are anonymous
@@ -577,6 +635,17 @@ impl siginfo_t {
     }
 
     pub fn new_for_timer(signal: Signal, timer_id: i32, overrun: i32) -> Self {
+        // SAFETY: a zeroed `sigval` is a null pointer and a zero int
+        Self::new_for_posix_timer(signal, timer_id, overrun, unsafe { core::mem::zeroed() })
+    }
+
+    /// Like [`siginfo_t::new_for_timer`], but with the `sigev_value` given to `timer_create(2)`.
+    pub fn new_for_posix_timer(
+        signal: Signal,
+        timer_id: i32,
+        overrun: i32,
+        sigval: linux_sigval,
+    ) -> Self {
         // sigaction(2):
         // > Signals sent by POSIX.1b timers (since Linux 2.6) fill in si_overrun and
         // > si_timerid.  The si_timerid field is  an  internal ID  used by the kernel
@@ -593,7 +662,7 @@ impl siginfo_t {
                     l_timer: SigInfoDetailsTimer {
                         l_tid: timer_id,
                         l_overrun: overrun,
-                        l_sigval: core::mem::zeroed(),
+                        l_sigval: sigval,
                         l_sys_private: 0,
                     },
                 },
@@ -629,6 +698,22 @@ impl siginfo_t {
         }
     }
 
+    /// For a signal that the kernel sends on its own behalf (`SEND_SIG_PRIV`), such as the
+    /// `SIGURG` sent when a socket receives urgent data.
+    pub fn new_for_kernel(signal: Signal) -> Self {
+        // SAFETY: a zeroed `sigfault` is a valid (null) address
+        unsafe {
+            Self::new(
+                signal,
+                0,
+                SigInfoCodeSi::SI_KERNEL.into(),
+                SigInfoDetailsFields {
+                    l_sigfault: core::mem::zeroed(),
+                },
+            )
+        }
+    }
+
     pub fn new_for_sigchld_exited(
         exit_signal: Signal,
         child_pid: i32,
@@ -1375,6 +1460,19 @@ pub fn rt_sigprocmask(
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigevent_layout() {
+        assert_eq!(
+            core::mem::size_of::<sigevent>(),
+            const_conversions::usize_from_u32(bindings::LINUX_SIGEV_MAX_SIZE)
+        );
+    }
+}
+
 #[cfg(test)]
 mod rt_sigaction_tests {
     use core::sync::atomic::AtomicI32;
@@ -0,0 +1,114 @@
+use crate::{bindings, const_conversions};
+
+// Manually translated from linux/stat.h.
+// `linux/stat.h` isn't currently included in our generated bindings.
+
+/// File type mask of a file mode.
+pub const S_IFMT: u16 = 0o170000;
+/// Socket file type.
+pub const S_IFSOCK: u16 = 0o140000;
+/// Regular file type.
+pub const S_IFREG: u16 = 0o100000;
+/// FIFO (pipe) file type.
+pub const S_IFIFO: u16 = 0o010000;
+/// Character device file type.
+pub const S_IFCHR: u16 = 0o020000;
+
+bitflags::bitflags! {
+    /// The fields requested from and returned by `statx`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct StatxMask: u32 {
+        const STATX_TYPE = 0x00000001;
+        const STATX_MODE = 0x00000002;
+        const STATX_NLINK = 0x00000004;
+        const STATX_UID = 0x00000008;
+        const STATX_GID = 0x00000010;
+        const STATX_ATIME = 0x00000020;
+        const STATX_MTIME = 0x00000040;
+        const STATX_CTIME = 0x00000080;
+        const STATX_INO = 0x00000100;
+        const STATX_SIZE = 0x00000200;
+        const STATX_BLOCKS = 0x00000400;
+        const STATX_BASIC_STATS = 0x000007ff;
+        const STATX_BTIME = 0x00000800;
+        const STATX_MNT_ID = 0x00001000;
+        const STATX_DIOALIGN = 0x00002000;
+        /// Reserved for future expansion; `statx` fails with `EINVAL` if it's requested.
+        const STATX__RESERVED = 0x80000000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags for `statx`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct StatxFlags: i32 {
+        const AT_SYMLINK_NOFOLLOW = const_conversions::i32_from_u32(bindings::LINUX_AT_SYMLINK_NOFOLLOW);
+        const AT_NO_AUTOMOUNT = const_conversions::i32_from_u32(bindings::LINUX_AT_NO_AUTOMOUNT);
+        const AT_EMPTY_PATH = const_conversions::i32_from_u32(bindings::LINUX_AT_EMPTY_PATH);
+        const AT_STATX_FORCE_SYNC = const_conversions::i32_from_u32(bindings::LINUX_AT_STATX_FORCE_SYNC);
+        const AT_STATX_DONT_SYNC = const_conversions::i32_from_u32(bindings::LINUX_AT_STATX_DONT_SYNC);
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct statx_timestamp {
+    pub tv_sec: i64,
+    pub tv_nsec: u32,
+    pub __reserved: i32,
+}
+unsafe impl shadow_pod::Pod for statx_timestamp {}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct statx {
+    /// what results were written
+    pub stx_mask: u32,
+    /// preferred general I/O size
+    pub stx_blksize: u32,
+    /// flags conveying information about the file
+    pub stx_attributes: u64,
+    /// number of hard links
+    pub stx_nlink: u32,
+    /// user ID of owner
+    pub stx_uid: u32,
+    /// group ID of owner
+    pub stx_gid: u32,
+    /// file mode
+    pub stx_mode: u16,
+    pub __spare0: [u16; 1],
+    /// inode number
+    pub stx_ino: u64,
+    /// file size
+    pub stx_size: u64,
+    /// number of 512-byte blocks allocated
+    pub stx_blocks: u64,
+    /// mask to show what's supported in `stx_attributes`
+    pub stx_attributes_mask: u64,
+    /// last access time
+    pub stx_atime: statx_timestamp,
+    /// file creation time
+    pub stx_btime: statx_timestamp,
+    /// last attribute change time
+    pub stx_ctime: statx_timestamp,
+    /// last data modification time
+    pub stx_mtime: statx_timestamp,
+    /// device ID of special file (if the file is a device)
+    pub stx_rdev_major: u32,
+    pub stx_rdev_minor: u32,
+    /// ID of the device containing the file
+    pub stx_dev_major: u32,
+    pub stx_dev_minor: u32,
+    /// mount ID
+    pub stx_mnt_id: u64,
+    /// memory buffer alignment for direct I/O
+    pub stx_dio_mem_align: u32,
+    /// file offset alignment for direct I/O
+    pub stx_dio_offset_align: u32,
+    pub __spare3: [u64; 12],
+}
+unsafe impl shadow_pod::Pod for statx {}
+
+static_assertions::assert_eq_size!(statx, [u8; 256]);
@@ -45,6 +45,14 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// Valid flags passed to `timer_settime(2)`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct TimerSettimeFlags: i32 {
+        const TIMER_ABSTIME = const_conversions::i32_from_u32(bindings::LINUX_TIMER_ABSTIME);
+    }
+}
+
 /// Interval timers
 #[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 // getitimer takes `int`:
@@ -91,6 +99,10 @@ pub fn clock_gettime(clockid: ClockId) -> Result<timespec, Errno> {
     clock_gettime_raw(clockid.into())
 }
 
+pub use bindings::linux___kernel_timer_t;
+#[allow(non_camel_case_types)]
+pub type kernel_timer_t = linux___kernel_timer_t;
+
 pub use bindings::linux_itimerspec;
 #[allow(non_camel_case_types)]
 pub type itimerspec = linux_itimerspec;
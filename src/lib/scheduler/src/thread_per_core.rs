@@ -1,9 +1,25 @@
 //! A thread-per-core host scheduler.
+//!
+//! Each thread has a queue of hosts, and a thread that finishes its own hosts steals hosts from the
+//! other threads' queues, so that a thread doesn't sit idle while another thread still has hosts
+//! to run. The scheduler also measures how long each host takes to run, and if the threads'
+//! loads were unbalanced in a round, reassigns the hosts before the next round so that the most
+//! expensive hosts are run first and spread across the threads. Which thread runs a host doesn't
+//! affect the host's results, and hosts are ordered by their costs and then their positions in
+//! the original list of hosts, so the scheduling only affects the run time of the simulation.
+//!
+//! Threads can be divided into groups, such as the threads on each NUMA node, and each host
+//! belongs to one group. A thread steals hosts from the threads in its own group before other
+//! groups, and hosts are only ever assigned to the threads in their own group, so that a host's
+//! memory stays local to the threads that usually run it. Hosts can also be given an affinity,
+//! and hosts with the same affinity are always run together by one thread.
 
 // unsafe code should be isolated to the thread pool
 #![forbid(unsafe_code)]
 
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use crossbeam::queue::ArrayQueue;
 
@@ -13,12 +29,58 @@ use crate::CORE_AFFINITY;
 pub trait Host: Debug + Send {}
 impl<T> Host for T where T: Debug + Send {}
 
+/// Only rebalance the hosts if the busiest thread's load was more than this fraction above the
+/// best achievable load.
+const REBALANCE_THRESHOLD: f64 = 0.1;
+
+/// Only rebalance the hosts if the busiest thread's load was more than this above the best
+/// achievable load, so that short rounds with noisy timings don't cause rebalancing.
+const REBALANCE_MIN_DIFF: Duration = Duration::from_micros(50);
+
+/// How a host is assigned to threads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Placement {
+    /// The group of threads that the host is assigned to.
+    pub thread_group: usize,
+    /// Hosts with the same affinity are always run by the same thread, one after another in the
+    /// order that they were given to the scheduler.
+    pub affinity: Option<usize>,
+}
+
+/// Hosts in a thread's queue, which are always run together by one thread. This is a single host
+/// unless the hosts have an affinity.
+#[derive(Debug)]
+struct QueuedHosts<HostType> {
+    hosts: Vec<HostType>,
+    /// The position of the first host in the list of hosts given to the scheduler.
+    index: usize,
+    /// The group of threads that the hosts belong to.
+    group: usize,
+    /// The thread that ran the hosts in the last round, or that the hosts are assigned to if they
+    /// were run by a thread in another group.
+    thread: usize,
+    /// How long the hosts took to run in the last round, in nanoseconds.
+    cost: u64,
+}
+
+/// The load of a thread during a round.
+#[derive(Debug, Default)]
+struct ThreadLoad {
+    /// The total time spent running hosts, in nanoseconds.
+    total: AtomicU64,
+    /// The longest time spent running a single host (or hosts with the same affinity), in
+    /// nanoseconds.
+    max_host: AtomicU64,
+}
+
 /// A host scheduler.
 pub struct ThreadPerCoreSched<HostType: Host> {
     pool: UnboundedThreadPool,
     num_threads: usize,
-    thread_hosts: Vec<ArrayQueue<HostType>>,
-    thread_hosts_processed: Vec<ArrayQueue<HostType>>,
+    thread_groups: Vec<usize>,
+    thread_hosts: Vec<ArrayQueue<QueuedHosts<HostType>>>,
+    thread_hosts_processed: Vec<ArrayQueue<QueuedHosts<HostType>>>,
+    thread_loads: Vec<ThreadLoad>,
     hosts_need_swap: bool,
 }
 
@@ -30,10 +92,32 @@ impl<HostType: Host> ThreadPerCoreSched<HostType> {
     where
         T: IntoIterator<Item = HostType>,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
+    {
+        let thread_groups = vec![0; cpu_ids.len()];
+        let hosts = hosts.into_iter().map(|host| (host, Placement::default()));
+        Self::with_placements(cpu_ids, &thread_groups, hosts, yield_spin)
+    }
+
+    /// Like [`ThreadPerCoreSched::new`], but the threads are divided into groups, where
+    /// `thread_groups` is the group of each thread, and `hosts` are given with the placement of each
+    /// host. Hosts are only assigned to the threads in their group, but may be run by threads in
+    /// other groups if those threads have no other hosts to run. Every group that has a host must
+    /// have at least one thread. Hosts with the same affinity are assigned to the thread group of
+    /// the first of those hosts.
+    pub fn with_placements<T>(
+        cpu_ids: &[Option<u32>],
+        thread_groups: &[usize],
+        hosts: T,
+        yield_spin: bool,
+    ) -> Self
+    where
+        T: IntoIterator<Item = (HostType, Placement)>,
+        <T as IntoIterator>::IntoIter: ExactSizeIterator,
     {
         let hosts = hosts.into_iter();
 
         let num_threads = cpu_ids.len();
+        assert_eq!(thread_groups.len(), num_threads);
         let mut pool = UnboundedThreadPool::new(num_threads, "shadow-worker", yield_spin);
 
         // set the affinity of each thread
@@ -52,24 +136,56 @@ impl<HostType: Host> ThreadPerCoreSched<HostType> {
             });
         });
 
+        // combine the hosts with the same affinity
+        let mut queued: Vec<QueuedHosts<HostType>> = Vec::new();
+        let mut affinities = std::collections::HashMap::<usize, usize>::new();
+        for (index, (host, placement)) in hosts.enumerate() {
+            if let Some(affinity) = placement.affinity {
+                if let Some(&i) = affinities.get(&affinity) {
+                    queued[i].hosts.push(host);
+                    continue;
+                }
+                affinities.insert(affinity, queued.len());
+            }
+
+            queued.push(QueuedHosts {
+                hosts: vec![host],
+                index,
+                group: placement.thread_group,
+                // assigned below
+                thread: 0,
+                cost: 0,
+            });
+        }
+
         // each thread gets two fixed-sized queues with enough capacity to store every host
         let thread_hosts: Vec<_> = (0..num_threads)
-            .map(|_| ArrayQueue::new(hosts.len()))
+            .map(|_| ArrayQueue::new(queued.len()))
             .collect();
         let thread_hosts_2: Vec<_> = (0..num_threads)
-            .map(|_| ArrayQueue::new(hosts.len()))
+            .map(|_| ArrayQueue::new(queued.len()))
             .collect();
 
-        // assign hosts to threads in a round-robin manner
-        for (thread_queue, host) in thread_hosts.iter().cycle().zip(hosts) {
-            thread_queue.push(host).unwrap();
+        // assign hosts to the threads in their group in a round-robin manner
+        let mut group_counts = std::collections::HashMap::<usize, usize>::new();
+        for mut queued in queued {
+            let group = queued.group;
+            let count = group_counts.entry(group).or_default();
+            let threads = group_threads(thread_groups, group);
+            assert!(!threads.is_empty(), "No threads in group {group}");
+            queued.thread = threads[*count % threads.len()];
+            *count += 1;
+
+            thread_hosts[queued.thread].push(queued).unwrap();
         }
 
         Self {
             pool,
             num_threads,
+            thread_groups: thread_groups.to_vec(),
             thread_hosts,
             thread_hosts_processed: thread_hosts_2,
+            thread_loads: (0..num_threads).map(|_| ThreadLoad::default()).collect(),
             hosts_need_swap: false,
         }
     }
@@ -89,13 +205,24 @@ impl<HostType: Host> ThreadPerCoreSched<HostType> {
         if self.hosts_need_swap {
             debug_assert!(self.thread_hosts.iter().all(|queue| queue.is_empty()));
 
-            std::mem::swap(&mut self.thread_hosts, &mut self.thread_hosts_processed);
+            if self.is_unbalanced() {
+                self.rebalance();
+            } else {
+                std::mem::swap(&mut self.thread_hosts, &mut self.thread_hosts_processed);
+            }
             self.hosts_need_swap = false;
+
+            for load in &self.thread_loads {
+                load.total.store(0, Ordering::Relaxed);
+                load.max_host.store(0, Ordering::Relaxed);
+            }
         }
 
         // data/references that we'll pass to the scope
+        let thread_groups = &self.thread_groups;
         let thread_hosts = &self.thread_hosts;
         let thread_hosts_processed = &self.thread_hosts_processed;
+        let thread_loads = &self.thread_loads;
         let hosts_need_swap = &mut self.hosts_need_swap;
 
         // we cannot access `self` after calling `pool.scope()` since `SchedulerScope` has a
@@ -103,8 +230,10 @@ impl<HostType: Host> ThreadPerCoreSched<HostType> {
 
         self.pool.scope(move |s| {
             let sched_scope = SchedulerScope {
+                thread_groups,
                 thread_hosts,
                 thread_hosts_processed,
+                thread_loads,
                 hosts_need_swap,
                 runner: s,
             };
@@ -117,6 +246,112 @@ impl<HostType: Host> ThreadPerCoreSched<HostType> {
     pub fn join(self) {
         self.pool.join();
     }
+
+    /// Whether the busiest thread in the last round took noticeably longer than the best possible
+    /// assignment of the hosts would have, which is at least the average load and at least the
+    /// cost of the most expensive host.
+    fn is_unbalanced(&self) -> bool {
+        if self.num_threads <= 1 {
+            return false;
+        }
+
+        let loads: Vec<u64> = self
+            .thread_loads
+            .iter()
+            .map(|x| x.total.load(Ordering::Relaxed))
+            .collect();
+        let max_host = self
+            .thread_loads
+            .iter()
+            .map(|x| x.max_host.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0);
+
+        let max_load = loads.iter().copied().max().unwrap_or(0);
+        let mean_load = loads.iter().sum::<u64>() / self.num_threads as u64;
+        let best_load = std::cmp::max(mean_load, max_host);
+
+        let diff = max_load.saturating_sub(best_load);
+        diff > REBALANCE_MIN_DIFF.as_nanos() as u64
+            && diff as f64 > best_load as f64 * REBALANCE_THRESHOLD
+    }
+
+    /// Move the processed hosts to the threads' queues, reassigning them to balance the loads of
+    /// the threads in each group.
+    fn rebalance(&mut self) {
+        let mut hosts: Vec<_> = self
+            .thread_hosts_processed
+            .iter()
+            .flat_map(|queue| std::iter::from_fn(|| queue.pop()))
+            .collect();
+
+        // the most expensive hosts are assigned and run first
+        hosts.sort_unstable_by_key(|x| (std::cmp::Reverse(x.cost), x.index));
+
+        let mut groups: Vec<_> = hosts.iter().map(|x| x.group).collect();
+        groups.sort_unstable();
+        groups.dedup();
+
+        let mut host_threads = vec![0; hosts.len()];
+        for group in groups {
+            let threads = group_threads(&self.thread_groups, group);
+            let members: Vec<_> = (0..hosts.len())
+                .filter(|&i| hosts[i].group == group)
+                .collect();
+
+            // balance the hosts using the positions of the threads within the group
+            let costs: Vec<_> = members
+                .iter()
+                .map(|&i| {
+                    let host = &hosts[i];
+                    let thread = threads.iter().position(|&x| x == host.thread).unwrap();
+                    (thread, host.cost)
+                })
+                .collect();
+            let assigned = balance(&costs, threads.len());
+
+            for (i, thread) in members.into_iter().zip(assigned) {
+                host_threads[i] = threads[thread];
+            }
+        }
+
+        for (mut host, thread) in hosts.into_iter().zip(host_threads) {
+            host.thread = thread;
+            self.thread_hosts[thread].push(host).unwrap();
+        }
+    }
+}
+
+/// The threads in a group, in increasing order.
+fn group_threads(thread_groups: &[usize], group: usize) -> Vec<usize> {
+    (0..thread_groups.len())
+        .filter(|&x| thread_groups[x] == group)
+        .collect()
+}
+
+/// Assign hosts to threads so that the threads have similar loads. Each host is given as the thread
+/// that last ran it and its cost, in the order that they should be assigned (usually the most
+/// expensive first). A host stays on its previous thread unless that would give the thread more
+/// than an even share of the total cost, in which case it's assigned to the least-loaded thread.
+/// Returns the thread of each host.
+fn balance(hosts: &[(usize, u64)], num_threads: usize) -> Vec<usize> {
+    let total: u64 = hosts.iter().map(|(_, cost)| cost).sum();
+    let share = total.div_ceil(num_threads as u64);
+
+    let mut loads = vec![0; num_threads];
+    hosts
+        .iter()
+        .map(|&(previous, cost)| {
+            let thread = if loads[previous] + cost <= share {
+                previous
+            } else {
+                // the least-loaded thread, preferring the lowest index
+                (0..num_threads).min_by_key(|&x| loads[x]).unwrap()
+            };
+            loads[thread] += cost;
+            thread
+        })
+        .collect()
 }
 
 /// A wrapper around the work pool's scoped runner.
@@ -124,8 +359,10 @@ pub struct SchedulerScope<'sched, 'pool, 'scope, HostType: Host>
 where
     'sched: 'scope,
 {
-    thread_hosts: &'sched Vec<ArrayQueue<HostType>>,
-    thread_hosts_processed: &'sched Vec<ArrayQueue<HostType>>,
+    thread_groups: &'sched Vec<usize>,
+    thread_hosts: &'sched Vec<ArrayQueue<QueuedHosts<HostType>>>,
+    thread_hosts_processed: &'sched Vec<ArrayQueue<QueuedHosts<HostType>>>,
+    thread_loads: &'sched Vec<ThreadLoad>,
     hosts_need_swap: &'sched mut bool,
     runner: TaskRunner<'pool, 'scope>,
 }
@@ -143,8 +380,10 @@ impl<'sched, 'pool, 'scope, HostType: Host> SchedulerScope<'sched, 'pool, 'scope
     ) {
         self.runner.run(move |i| {
             let mut host_iter = HostIter {
+                thread_groups: self.thread_groups,
                 thread_hosts_from: self.thread_hosts,
-                thread_hosts_to: &self.thread_hosts_processed[i],
+                thread_hosts_to: self.thread_hosts_processed,
+                thread_load: &self.thread_loads[i],
                 this_thread_index: i,
             };
 
@@ -166,8 +405,10 @@ impl<'sched, 'pool, 'scope, HostType: Host> SchedulerScope<'sched, 'pool, 'scope
             let this_elem = &data[i];
 
             let mut host_iter = HostIter {
+                thread_groups: self.thread_groups,
                 thread_hosts_from: self.thread_hosts,
-                thread_hosts_to: &self.thread_hosts_processed[i],
+                thread_hosts_to: self.thread_hosts_processed,
+                thread_load: &self.thread_loads[i],
                 this_thread_index: i,
             };
 
@@ -181,12 +422,17 @@ impl<'sched, 'pool, 'scope, HostType: Host> SchedulerScope<'sched, 'pool, 'scope
 /// Supports iterating over all hosts assigned to this thread. For this thread-per-core scheduler,
 /// the iterator may steal hosts from other threads.
 pub struct HostIter<'a, HostType: Host> {
+    /// The group of each thread.
+    thread_groups: &'a [usize],
     /// Queues to take hosts from.
-    thread_hosts_from: &'a [ArrayQueue<HostType>],
-    /// The queue to add hosts to when done with them.
-    thread_hosts_to: &'a ArrayQueue<HostType>,
+    thread_hosts_from: &'a [ArrayQueue<QueuedHosts<HostType>>],
+    /// Queues to add hosts to when done with them. Hosts are added to this thread's queue, unless
+    /// they belong to another group, in which case they're returned to their assigned thread.
+    thread_hosts_to: &'a [ArrayQueue<QueuedHosts<HostType>>],
+    /// The load of this thread, which is updated with the time spent running hosts.
+    thread_load: &'a ThreadLoad,
     /// The index of this thread. This is the first queue of `thread_hosts_from` that we take hosts
-    /// from.
+    /// from, followed by the queues of the other threads in this thread's group.
     this_thread_index: usize,
 }
 
@@ -196,16 +442,41 @@ impl<'a, HostType: Host> HostIter<'a, HostType> {
     where
         F: FnMut(HostType) -> HostType,
     {
-        for from_queue in self
-            .thread_hosts_from
-            .iter()
-            .cycle()
-            // start from the current thread index
-            .skip(self.this_thread_index)
-            .take(self.thread_hosts_from.len())
-        {
-            while let Some(host) = from_queue.pop() {
-                self.thread_hosts_to.push(f(host)).unwrap();
+        let num_threads = self.thread_hosts_from.len();
+        let this_group = self.thread_groups[self.this_thread_index];
+
+        // start from the current thread index
+        let threads = (0..num_threads).map(|x| (x + self.this_thread_index) % num_threads);
+
+        // steal from threads in the same group before threads in other groups
+        let threads = threads
+            .clone()
+            .filter(|&x| self.thread_groups[x] == this_group)
+            .chain(threads.filter(|&x| self.thread_groups[x] != this_group));
+
+        for from_queue in threads.map(|x| &self.thread_hosts_from[x]) {
+            while let Some(queued) = from_queue.pop() {
+                let start = Instant::now();
+                let hosts = queued.hosts.into_iter().map(&mut f).collect();
+                let cost = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+
+                self.thread_load.total.fetch_add(cost, Ordering::Relaxed);
+                self.thread_load.max_host.fetch_max(cost, Ordering::Relaxed);
+
+                let thread = if queued.group == this_group {
+                    self.this_thread_index
+                } else {
+                    queued.thread
+                };
+
+                let queued = QueuedHosts {
+                    hosts,
+                    index: queued.index,
+                    group: queued.group,
+                    thread,
+                    cost,
+                };
+                self.thread_hosts_to[thread].push(queued).unwrap();
             }
         }
     }
@@ -299,6 +570,171 @@ mod tests {
         sched.join();
     }
 
+    #[test]
+    fn test_balance() {
+        // balanced hosts stay on their threads
+        assert_eq!(
+            balance(&[(0, 10), (1, 10), (0, 5), (1, 5)], 2),
+            [0, 1, 0, 1]
+        );
+
+        // the expensive hosts are spread across the threads
+        assert_eq!(
+            balance(&[(0, 10), (0, 10), (0, 1), (0, 1)], 2),
+            [0, 1, 0, 1]
+        );
+
+        // a host that's more expensive than an even share gets its own thread
+        assert_eq!(
+            balance(&[(1, 100), (1, 1), (1, 1), (0, 1)], 3),
+            [0, 1, 1, 2]
+        );
+
+        // hosts that didn't run have no cost and stay on their threads
+        assert_eq!(balance(&[(2, 0), (1, 0)], 3), [2, 1]);
+    }
+
+    #[test]
+    fn test_rebalance() {
+        let hosts = [(); 6].map(|_| TestHost {});
+        let mut sched: ThreadPerCoreSched<TestHost> =
+            ThreadPerCoreSched::new(&[None, None], hosts, false);
+
+        // pretend that thread 0 ran every host, and that the first two hosts were expensive
+        sched.hosts_need_swap = true;
+        for (index, queue) in sched.thread_hosts.iter().enumerate() {
+            assert_eq!(queue.len(), 3);
+            while let Some(mut host) = queue.pop() {
+                host.thread = 0;
+                host.cost = if host.index < 2 { 1_000_000 } else { 1000 };
+                sched.thread_hosts_processed[0].push(host).unwrap();
+            }
+            sched.thread_loads[index]
+                .total
+                .store(if index == 0 { 2_004_000 } else { 0 }, Ordering::Relaxed);
+        }
+        sched.thread_loads[0]
+            .max_host
+            .store(1_000_000, Ordering::Relaxed);
+
+        assert!(sched.is_unbalanced());
+        sched.rebalance();
+
+        // each thread gets one of the expensive hosts first
+        let thread_0: Vec<_> = std::iter::from_fn(|| sched.thread_hosts[0].pop()).collect();
+        let thread_1: Vec<_> = std::iter::from_fn(|| sched.thread_hosts[1].pop()).collect();
+        assert_eq!(thread_0[0].index, 0);
+        assert_eq!(thread_1[0].index, 1);
+        assert_eq!(thread_0.len() + thread_1.len(), 6);
+
+        // put the hosts back so that the scheduler can be used
+        for host in thread_0.into_iter().chain(thread_1) {
+            sched.thread_hosts[0].push(host).unwrap();
+        }
+        sched.hosts_need_swap = false;
+        sched.join();
+    }
+
+    #[test]
+    fn test_thread_groups() {
+        let hosts = [0, 1, 1, 0, 1, 1].map(|group| {
+            let placement = Placement {
+                thread_group: group,
+                affinity: None,
+            };
+            (TestHost {}, placement)
+        });
+        let mut sched: ThreadPerCoreSched<TestHost> =
+            ThreadPerCoreSched::with_placements(&[None, None, None], &[1, 0, 1], hosts, false);
+
+        // hosts are assigned to the threads in their group
+        let groups = |queue: &ArrayQueue<QueuedHosts<TestHost>>| {
+            let hosts: Vec<_> = std::iter::from_fn(|| queue.pop()).collect();
+            let groups: Vec<_> = hosts.iter().map(|x| x.group).collect();
+            for host in hosts {
+                queue.push(host).unwrap();
+            }
+            groups
+        };
+        assert_eq!(groups(&sched.thread_hosts[0]), [1, 1]);
+        assert_eq!(groups(&sched.thread_hosts[1]), [0, 0]);
+        assert_eq!(groups(&sched.thread_hosts[2]), [1, 1]);
+
+        // only thread 2 runs hosts, so it runs the hosts of both groups
+        let counter = AtomicU32::new(0);
+        sched.scope(|s| {
+            s.run_with_hosts(|i, hosts| {
+                if i == 2 {
+                    hosts.for_each(|host| {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        host
+                    });
+                }
+            });
+        });
+        assert_eq!(counter.load(Ordering::SeqCst), 6);
+
+        // the hosts of the other group are returned to their thread
+        assert!(groups(&sched.thread_hosts_processed[0]).is_empty());
+        assert_eq!(groups(&sched.thread_hosts_processed[1]), [0, 0]);
+        assert_eq!(groups(&sched.thread_hosts_processed[2]), [1, 1, 1, 1]);
+
+        sched.join();
+    }
+
+    #[test]
+    fn test_affinity() {
+        #[derive(Debug)]
+        struct NumberedHost(usize);
+
+        let affinities = [Some(0), None, Some(0), Some(0), None];
+        let hosts = affinities.into_iter().enumerate().map(|(i, affinity)| {
+            let placement = Placement {
+                thread_group: 0,
+                affinity,
+            };
+            (NumberedHost(i), placement)
+        });
+        let mut sched: ThreadPerCoreSched<NumberedHost> =
+            ThreadPerCoreSched::with_placements(&[None, None], &[0, 0], hosts, false);
+
+        let hosts = |queue: &ArrayQueue<QueuedHosts<NumberedHost>>| {
+            let queued: Vec<_> = std::iter::from_fn(|| queue.pop()).collect();
+            let hosts: Vec<Vec<_>> = queued
+                .iter()
+                .map(|x| x.hosts.iter().map(|host| host.0).collect())
+                .collect();
+            for x in queued {
+                queue.push(x).unwrap();
+            }
+            hosts
+        };
+
+        // the hosts with the same affinity are assigned together
+        assert_eq!(hosts(&sched.thread_hosts[0]), [vec![0, 2, 3], vec![4]]);
+        assert_eq!(hosts(&sched.thread_hosts[1]), [vec![1]]);
+
+        // only thread 1 runs hosts, so it steals the hosts of thread 0 and runs them in order
+        let order = std::sync::Mutex::new(Vec::new());
+        sched.scope(|s| {
+            s.run_with_hosts(|i, hosts| {
+                if i == 1 {
+                    hosts.for_each(|host| {
+                        order.lock().unwrap().push(host.0);
+                        host
+                    });
+                }
+            });
+        });
+        assert_eq!(*order.lock().unwrap(), [1, 0, 2, 3, 4]);
+        assert_eq!(
+            hosts(&sched.thread_hosts_processed[1]),
+            [vec![1], vec![0, 2, 3], vec![4]]
+        );
+
+        sched.join();
+    }
+
     #[test]
     fn test_run_with_data() {
         let hosts = [(); 5].map(|_| TestHost {});
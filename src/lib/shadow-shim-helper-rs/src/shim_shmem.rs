@@ -181,6 +181,8 @@ impl ProcessShmem {
                     pending_standard_siginfos: [siginfo_t::default();
                         Signal::STANDARD_MAX.as_i32() as usize],
                     signal_actions: [sigaction::default(); Signal::MAX.as_i32() as usize],
+                    user_cpu_time: SimulationTime::ZERO,
+                    system_cpu_time: SimulationTime::ZERO,
                 },
             ),
         }
@@ -209,6 +211,12 @@ pub struct ProcessShmemProtected {
     // outside of its original virtual address space.
     #[unsafe_assume_virtual_address_space_independent]
     signal_actions: [sigaction; Signal::MAX.as_i32() as usize],
+
+    // Modeled CPU time used by the process's threads. Syscalls handled in the shim count as user
+    // time, and syscalls handled by shadow count as system time. This only accumulates when
+    // modeling the latency of unblocked syscalls.
+    pub user_cpu_time: SimulationTime,
+    pub system_cpu_time: SimulationTime,
 }
 
 // We have several arrays indexed by signal number - 1.
@@ -300,6 +308,7 @@ impl ThreadShmem {
                     pending_standard_siginfos: [siginfo_t::default();
                         Signal::STANDARD_MAX.as_i32() as usize],
                     blocked_signals: sigset_t::EMPTY,
+                    saved_blocked_signals: FfiOption::None,
                     sigaltstack: StackWrapper(stack_t {
                         ss_sp: std::ptr::null_mut(),
                         ss_flags: libc::SS_DISABLE,
@@ -340,6 +349,11 @@ pub struct ThreadShmemProtected {
     // actually supported by the kernel.
     pub blocked_signals: sigset_t,
 
+    // The signal mask to restore after a syscall that temporarily replaced `blocked_signals`
+    // (e.g. `ppoll`). If the syscall was interrupted, it's restored after the interrupting
+    // signal is handled.
+    pub saved_blocked_signals: FfiOption<sigset_t>,
+
     // Configured alternate signal stack for this thread.
     sigaltstack: StackWrapper,
 }
@@ -588,6 +602,23 @@ pub mod export {
         unsafe { *protected.signal_action_mut(Signal::try_from(sig).unwrap()) = *action };
     }
 
+    /// Add to the process's modeled user CPU time.
+    ///
+    /// # Safety
+    ///
+    /// Pointer args must be safely dereferenceable.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn shimshmem_incrementUserCpuTime(
+        lock: *const ShimShmemHostLock,
+        process: *const ShimShmemProcess,
+        dt: CSimulationTime,
+    ) {
+        let process_mem = unsafe { process.as_ref().unwrap() };
+        let lock = unsafe { lock.as_ref().unwrap() };
+        let mut protected = process_mem.protected.borrow_mut(&lock.root);
+        protected.user_cpu_time += SimulationTime::from_c_simtime(dt).unwrap();
+    }
+
     #[no_mangle]
     pub extern "C-unwind" fn shimshmemthread_size() -> usize {
         std::mem::size_of::<ThreadShmem>()
@@ -437,6 +437,12 @@ impl From<SysCallReg> for linux_api::fcntl::OFlag {
     }
 }
 
+impl From<SysCallReg> for linux_api::fcntl::FlockOperation {
+    fn from(reg: SysCallReg) -> Self {
+        Self::from_bits_retain(reg.into())
+    }
+}
+
 impl TryFrom<SysCallReg> for nix::sys::eventfd::EfdFlags {
     type Error = ();
     fn try_from(reg: SysCallReg) -> Result<Self, Self::Error> {
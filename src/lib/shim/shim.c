@@ -106,6 +106,21 @@ static void _shim_parent_init_memory_manager() {
     shim_swapAllowNativeSyscalls(oldNativeSyscallFlag);
 }
 
+// The kernel gives each new program 16 random bytes at `AT_RANDOM`. libc has already used them by
+// the time we're loaded, but the program may read them too, so we replace them with bytes from
+// the host's deterministic random source.
+static void _shim_parent_init_at_random() {
+    unsigned char* atRandom = (unsigned char*)getauxval(AT_RANDOM);
+    if (atRandom == NULL) {
+        return;
+    }
+
+    long rv = shim_emulated_syscall(NULL, SYS_getrandom, atRandom, 16, 0);
+    if (rv != 16) {
+        warning("Could not replace the AT_RANDOM bytes: %ld", rv);
+    }
+}
+
 static void _shim_parent_init_seccomp() {
     shim_seccomp_init();
 }
@@ -128,6 +143,7 @@ void _shim_parent_init_preload() {
     _shim_init_signal_stack();
     _shim_init_death_signal();
     _shim_parent_init_memory_manager();
+    _shim_parent_init_at_random();
     _shim_parent_init_rdtsc_emu();
     _shim_parent_init_seccomp();
     _shim_parent_close_stdin();
@@ -183,8 +183,9 @@ bool shim_sys_handle_syscall_locally(long syscall_num, long* rv, va_list args) {
 
     if (shimshmem_getModelUnblockedSyscallLatency(shim_hostSharedMem())) {
         ShimShmemHostLock* host_lock = shimshmemhost_lock(shim_hostSharedMem());
-        shimshmem_incrementUnappliedCpuLatency(
-            host_lock, _shim_sys_latency_for_syscall(syscall_num));
+        CSimulationTime latency = _shim_sys_latency_for_syscall(syscall_num);
+        shimshmem_incrementUnappliedCpuLatency(host_lock, latency);
+        shimshmem_incrementUserCpuTime(host_lock, shim_processSharedMem(), latency);
         CSimulationTime unappliedCpuLatency = shimshmem_getUnappliedCpuLatency(host_lock);
         // TODO: Once ptrace mode is deprecated, we can hold this lock longer to
         // avoid having to reacquire it below. We currently can't hold the lock
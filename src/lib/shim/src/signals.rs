@@ -6,6 +6,7 @@ use linux_api::signal::{
 };
 use linux_api::ucontext::ucontext;
 use log::{trace, warn};
+use shadow_shim_helper_rs::option::FfiOption;
 use shadow_shim_helper_rs::shim_shmem;
 
 use crate::tls::ShimTlsVar;
@@ -106,12 +107,30 @@ pub unsafe fn process_signals(mut ucontext: Option<&mut ucontext>) -> bool {
 
     let mut restartable = true;
 
+    // If we're handling the signals that interrupted a syscall that temporarily replaced the
+    // signal mask (e.g. `ppoll`), then like linux the handlers run with the temporary mask, and
+    // the original mask is restored afterwards.
+    let mut saved_mask = tls_thread_shmem::with(|thread| {
+        thread
+            .protected
+            .borrow_mut(&host_lock.root)
+            .saved_blocked_signals
+            .take()
+    });
+
     loop {
         let Some((sig, siginfo)) = tls_process_shmem::with(|process| {
             tls_thread_shmem::with(|thread| {
                 shim_shmem::take_pending_unblocked_signal(&host_lock, process, thread)
             })
         }) else {
+            if let FfiOption::Some(mask) = saved_mask.take() {
+                // restoring the original mask may unblock other pending signals
+                tls_thread_shmem::with(|thread| {
+                    thread.protected.borrow_mut(&host_lock.root).blocked_signals = mask
+                });
+                continue;
+            }
             break;
         };
 
@@ -115,7 +115,12 @@ fn run_cbindgen(build_common: &ShadowBuildCommon) {
             Some(v)
         };
         config.export = cbindgen::ExportConfig {
-            include: vec!["QDiscMode".into(), "FileSignals".into(), "FileState".into()],
+            include: vec![
+                "QDiscMode".into(),
+                "OutputLimitAction".into(),
+                "FileSignals".into(),
+                "FileState".into(),
+            ],
             // Export everything except function definitions, since those are already
             // exported in the other header file, and need the C header files.
             item_types: base_config
@@ -147,8 +152,11 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .header("host/descriptor/epoll.h")
         .header("host/descriptor/regular_file.h")
         .header("host/descriptor/tcp_cong.h")
+        .header("host/descriptor/tcp_cong_bbr.h")
+        .header("host/descriptor/tcp_cong_cubic.h")
         .header("host/descriptor/tcp_cong_reno.h")
         .header("host/futex.h")
+        .header("host/network/network_queuing_disciplines.h")
         .header("host/status_listener.h")
         .header("host/syscall/handler/fcntl.h")
         .header("host/syscall/handler/file.h")
@@ -191,6 +199,7 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .allowlist_function("legacysocket_.*")
         .blocklist_function("legacysocket_init")
         .allowlist_function("networkinterface_.*")
+        .allowlist_function("drrsocketqueue_.*")
         .allowlist_function("hostc_.*")
         // used by shadow's main function
         .allowlist_function("main_.*")
@@ -246,7 +255,6 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .allowlist_var("CONFIG_MTU")
         .allowlist_var("SYSCALL_IO_BUFSIZE")
         .allowlist_var("SHADOW_SOMAXCONN")
-        .allowlist_var("TCP_CONG_RENO_NAME")
         .allowlist_var("SHADOW_FLAG_MASK")
         .allowlist_var("GLIB_MAJOR_VERSION")
         .allowlist_var("GLIB_MINOR_VERSION")
@@ -265,6 +273,8 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .opaque_type("DescriptorTable")
         .opaque_type("MemoryManager")
         .opaque_type("TaskRef")
+        // Needs GQueue
+        .opaque_type("_?DrrSocketQueue")
         .blocklist_type("Logger")
         .blocklist_type("Timer")
         .blocklist_type("Controller")
@@ -280,6 +290,7 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .opaque_type("WorkerPool")
         .blocklist_type("HashSet_String")
         .blocklist_type("QDiscMode")
+        .blocklist_type("OutputLimitAction")
         // Imported from libc crate below
         .blocklist_type("siginfo_t")
         .blocklist_type("SysCallReg")
@@ -291,7 +302,7 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .disable_header_comment()
         .raw_line("/* automatically generated by rust-bindgen */")
         .raw_line("")
-        .raw_line("use crate::core::configuration::QDiscMode;")
+        .raw_line("use crate::core::configuration::{OutputLimitAction, QDiscMode};")
         .raw_line("use crate::host::descriptor::{File, FileSignals, FileState, OpenFile};")
         .raw_line("use crate::host::descriptor::socket::inet::{InetSocket, InetSocketWeak};")
         .raw_line("use crate::host::host::Host;")
@@ -300,6 +311,7 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .raw_line("use crate::host::syscall::handler::SyscallHandler;")
         .raw_line("use crate::host::syscall::types::SyscallReturn;")
         .raw_line("use crate::host::thread::Thread;")
+        .raw_line("use crate::utility::pcap_writer::PacketCapture;")
         .raw_line("use crate::utility::legacy_callback_queue::RootedRefCell_StateEventSource;")
         .raw_line("")
         .raw_line("use shadow_shim_helper_rs::HostId;")
@@ -356,6 +368,8 @@ fn build_shadow_c(build_common: &ShadowBuildCommon) {
         "host/descriptor/socket.c",
         "host/descriptor/tcp.c",
         "host/descriptor/tcp_cong.c",
+        "host/descriptor/tcp_cong_bbr.c",
+        "host/descriptor/tcp_cong_cubic.c",
         "host/descriptor/tcp_cong_reno.c",
         "host/process.c",
         "host/futex.c",
@@ -0,0 +1,263 @@
+//! Checkpoints of a running simulation, which can be resumed with the `--resume` option to skip
+//! a long bootstrap phase that is the same in every run.
+//!
+//! The managed processes are native processes, so Shadow can't serialize their state itself.
+//! Instead the checkpoint is written by [CRIU](https://criu.org), which dumps the entire process
+//! tree of Shadow (Shadow, its worker threads, and the managed processes) at the end of a
+//! scheduling round, while the simulation is paused. CRIU doesn't save the contents of files, so
+//! the data directory and Shadow's shared memory files are copied into the checkpoint, and are
+//! copied back before the process tree is restored.
+//!
+//! A checkpoint directory contains:
+//!
+//! - `checkpoint.json`: the checkpoint's [`CheckpointInfo`].
+//! - `images/`: CRIU's images of the process tree.
+//! - `data/`: a copy of the data directory.
+//! - `shm/`: copies of Shadow's shared memory files.
+//! - `dump.log` and `restore.log`: CRIU's logs.
+
+use std::convert::Infallible;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::utility;
+
+/// The directory that contains Shadow's shared memory files.
+const SHM_DIR: &str = "/dev/shm";
+
+/// Information about a checkpoint that is needed to resume it.
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointInfo {
+    /// The absolute path of the simulation's data directory.
+    data_directory: PathBuf,
+    /// The process ID of Shadow, which is the same after the checkpoint is resumed.
+    pid: u32,
+    /// The simulated time at which the checkpoint was written, in nanoseconds.
+    sim_time_ns: u64,
+}
+
+/// Writes a checkpoint at the end of the first scheduling round that ends at or after the
+/// checkpoint time.
+#[derive(Debug)]
+pub struct Checkpointer {
+    path: PathBuf,
+    time: SimulationTime,
+    written: bool,
+}
+
+impl Checkpointer {
+    /// A checkpointer that writes a checkpoint to the directory `path` at `time`. The directory
+    /// must not already exist.
+    pub fn new(path: &Path, time: SimulationTime) -> anyhow::Result<Self> {
+        if path.exists() {
+            return Err(anyhow::anyhow!(
+                "The checkpoint directory '{}' already exists",
+                path.display()
+            ));
+        }
+
+        // fail early rather than after the simulation has run until the checkpoint time
+        let criu = std::process::Command::new("criu")
+            .arg("--version")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+        if !criu.is_ok_and(|x| x.success()) {
+            return Err(anyhow::anyhow!(
+                "Checkpoints require CRIU, but 'criu' could not be run"
+            ));
+        }
+
+        Ok(Self {
+            path: std::env::current_dir()?.join(path),
+            time,
+            written: false,
+        })
+    }
+
+    /// Called at the end of each scheduling round. Writes the checkpoint if the round ended at or
+    /// after the checkpoint time and the checkpoint hasn't been written yet. This also returns
+    /// after the checkpoint is resumed, in which case the simulation continues from this round.
+    pub fn finish_round(
+        &mut self,
+        round_end: SimulationTime,
+        data_path: &Path,
+    ) -> anyhow::Result<()> {
+        if self.written || round_end < self.time {
+            return Ok(());
+        }
+        self.written = true;
+
+        log::info!(
+            "Writing a checkpoint of the simulation at {round_end:?} to '{}'",
+            self.path.display()
+        );
+        write(&self.path, data_path, round_end)
+            .with_context(|| format!("Failed to write checkpoint '{}'", self.path.display()))?;
+        log::info!("Finished the checkpoint at {round_end:?}");
+
+        Ok(())
+    }
+}
+
+/// Write a checkpoint of this process's tree to `path`.
+fn write(path: &Path, data_path: &Path, sim_time: SimulationTime) -> anyhow::Result<()> {
+    let pid = std::process::id();
+
+    std::fs::create_dir(path)?;
+
+    let info = CheckpointInfo {
+        data_directory: data_path.to_path_buf(),
+        pid,
+        sim_time_ns: sim_time.as_nanos().try_into().unwrap(),
+    };
+    let info_file = std::fs::File::create(path.join("checkpoint.json"))?;
+    serde_json::to_writer_pretty(info_file, &info)?;
+
+    // the simulation is paused, so the files don't change until the dump is finished
+    utility::copy_dir_all(data_path, path.join("data"))
+        .context("Failed to copy the data directory")?;
+
+    std::fs::create_dir(path.join("shm"))?;
+    for shm_path in shm_files(pid)? {
+        let dst = path.join("shm").join(shm_path.file_name().unwrap());
+        std::fs::copy(&shm_path, dst)
+            .with_context(|| format!("Failed to copy '{}'", shm_path.display()))?;
+    }
+
+    std::fs::create_dir(path.join("images"))?;
+
+    // CRIU can't dump a process tree that it's a part of, so it's run in the background by a
+    // shell that exits immediately, and writes its exit code to the 'status' file when done
+    let script = r#"(criu dump --tree "$2" --images-dir "$1/images" --log-file "$1/dump.log" \
+        --leave-running --shell-job --ext-unix-sk --file-locks --tcp-established; \
+        echo $? > "$1/status.tmp"; mv "$1/status.tmp" "$1/status") \
+        < /dev/null > /dev/null 2>&1 &"#;
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .arg("sh")
+        .arg(path)
+        .arg(pid.to_string())
+        .status()
+        .context("Failed to start CRIU")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("Failed to start CRIU ({status})"));
+    }
+
+    // CRIU freezes this process while it's dumped, so once the status is written this process is
+    // either still running or was resumed from the checkpoint
+    let status_path = path.join("status");
+    let status = loop {
+        match std::fs::read_to_string(&status_path) {
+            Ok(x) => break x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                std::thread::sleep(Duration::from_millis(100))
+            }
+            Err(e) => return Err(e).context("Failed to read the CRIU status"),
+        }
+    };
+
+    if status.trim() != "0" {
+        return Err(anyhow::anyhow!(
+            "CRIU failed with exit code {}; see '{}'",
+            status.trim(),
+            path.join("dump.log").display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resume the checkpoint at `path`. On success this process is replaced by CRIU, which restores
+/// the simulation and exits with Shadow's exit code when the simulation finishes. The data
+/// directory of the checkpointed simulation must not exist.
+pub fn resume(path: &Path) -> anyhow::Result<Infallible> {
+    let info_file = std::fs::File::open(path.join("checkpoint.json"))
+        .with_context(|| format!("Failed to open checkpoint '{}'", path.display()))?;
+    let info: CheckpointInfo = serde_json::from_reader(info_file)
+        .with_context(|| format!("Failed to read checkpoint '{}'", path.display()))?;
+
+    if !path.join("status").exists() {
+        return Err(anyhow::anyhow!(
+            "The checkpoint '{}' is incomplete",
+            path.display()
+        ));
+    }
+
+    // CRIU restores the processes with their original process IDs
+    if Path::new("/proc").join(info.pid.to_string()).exists() {
+        return Err(anyhow::anyhow!(
+            "The checkpointed Shadow process ID {} is in use by another process",
+            info.pid
+        ));
+    }
+
+    // CRIU restores the files opened by the processes from their paths, so the files must be the
+    // same as when the checkpoint was written
+    if info.data_directory.exists() {
+        return Err(anyhow::anyhow!(
+            "The data directory '{}' already exists",
+            info.data_directory.display()
+        ));
+    }
+    utility::copy_dir_all(path.join("data"), &info.data_directory).with_context(|| {
+        format!(
+            "Failed to copy the data directory to '{}'",
+            info.data_directory.display()
+        )
+    })?;
+
+    for entry in std::fs::read_dir(path.join("shm"))? {
+        let entry = entry?;
+        let dst = Path::new(SHM_DIR).join(entry.file_name());
+        std::fs::copy(entry.path(), &dst)
+            .with_context(|| format!("Failed to copy '{}'", dst.display()))?;
+    }
+
+    log::info!(
+        "Resuming the simulation at {:?} from checkpoint '{}'",
+        SimulationTime::from_nanos(info.sim_time_ns),
+        path.display()
+    );
+
+    let error = std::process::Command::new("criu")
+        .arg("restore")
+        .arg("--images-dir")
+        .arg(path.join("images"))
+        .arg("--log-file")
+        .arg(path.join("restore.log"))
+        .args([
+            "--shell-job",
+            "--ext-unix-sk",
+            "--file-locks",
+            "--tcp-established",
+        ])
+        .exec();
+
+    Err(error).context("Failed to run CRIU")
+}
+
+/// The shared memory files created by the Shadow process `pid`.
+fn shm_files(pid: u32) -> std::io::Result<Vec<PathBuf>> {
+    let suffix = format!("-{pid}");
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(SHM_DIR)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with("shadow_shmemfile_") && name.ends_with(&suffix) {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(files)
+}
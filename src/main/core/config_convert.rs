@@ -0,0 +1,602 @@
+//! Converting a configuration file that uses deprecated options to the current format.
+//!
+//! The conversions are applied to the parsed configuration, and are also applied as line edits to
+//! the configuration file's text so that its comments and formatting are kept. If the edited text
+//! doesn't match the converted configuration (for example if an option was written in a
+//! flow-style `{...}` mapping, or a conversion can't be written as a line edit), the converted
+//! configuration is serialized instead, which loses the comments.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::core::config_validate::{self, OptionPath, Segment};
+
+/// Experimental options that were removed and have no replacement.
+const REMOVED_EXPERIMENTAL_OPTIONS: &[&str] = &[
+    "interpose_method",
+    "preload_spin_max",
+    "use_explicit_block_message",
+    "use_legacy_working_dir",
+    "use_o_n_waitpid_workarounds",
+    "use_seccomp",
+    "use_shim_syscall_handler",
+];
+
+/// A change that was made to the configuration, or a deprecated option that couldn't be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// The option, for example `hosts.client.processes[0].stop_time`.
+    pub option: String,
+    pub message: String,
+}
+
+/// The results of converting a configuration.
+#[derive(Debug)]
+pub struct Conversion {
+    /// The converted configuration file.
+    pub text: String,
+    pub changes: Vec<Note>,
+    /// The deprecated options that were left in the configuration.
+    pub unconvertible: Vec<Note>,
+    /// Whether the comments and formatting of the configuration file were kept.
+    pub formatting_preserved: bool,
+}
+
+/// Convert the deprecated options in the configuration file's text. Included files aren't
+/// converted.
+pub fn convert(text: &str) -> Result<Conversion, serde_yaml::Error> {
+    let mut converter = Converter {
+        value: serde_yaml::from_str(text)?,
+        text: Some(text.to_string()),
+        changes: Vec::new(),
+        unconvertible: Vec::new(),
+    };
+
+    converter.convert_config();
+
+    // only use the edited text if it has the same meaning as the converted configuration
+    let edited = converter
+        .text
+        .filter(|text| serde_yaml::from_str::<Value>(text).ok().as_ref() == Some(&converter.value));
+    let formatting_preserved = edited.is_some();
+    let text = match edited {
+        Some(text) => text,
+        None => serde_yaml::to_string(&converter.value)?,
+    };
+
+    Ok(Conversion {
+        text,
+        changes: converter.changes,
+        unconvertible: converter.unconvertible,
+        formatting_preserved,
+    })
+}
+
+struct Converter {
+    /// The converted configuration.
+    value: Value,
+    /// The edited text of the configuration file, or `None` if an edit couldn't be made to it.
+    text: Option<String>,
+    changes: Vec<Note>,
+    unconvertible: Vec<Note>,
+}
+
+impl Converter {
+    fn convert_config(&mut self) {
+        let root = OptionPath::new(&[]);
+        let general = OptionPath::new(&["general"]);
+        let experimental = OptionPath::new(&["experimental"]);
+
+        self.rename_option(&root, "host_defaults", "host_option_defaults");
+        self.convert_host_options(&OptionPath::new(&["host_option_defaults"]));
+
+        self.move_option(&experimental, &general, "model_unblocked_syscall_latency");
+        for name in REMOVED_EXPERIMENTAL_OPTIONS {
+            if self.get(&experimental.clone().key(name)).is_some() {
+                self.unconvertible(
+                    &experimental.clone().key(name),
+                    "was removed and has no replacement",
+                );
+            }
+        }
+
+        let hosts = OptionPath::new(&["hosts"]);
+        let host_names: Vec<String> = match self.get(&hosts).and_then(Value::as_mapping) {
+            Some(hosts) => hosts
+                .keys()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect(),
+            None => Vec::new(),
+        };
+        for name in host_names {
+            let host = hosts.clone().key(&name);
+            self.rename_option(&host, "options", "host_options");
+            self.convert_host_options(&host.clone().key("host_options"));
+
+            let processes = host.clone().key("processes");
+            let num_processes = self
+                .get(&processes)
+                .and_then(Value::as_sequence)
+                .map_or(0, Vec::len);
+            for i in 0..num_processes {
+                self.convert_process(&processes.clone().index(i));
+            }
+            self.replicate_processes(&processes);
+        }
+    }
+
+    fn convert_host_options(&mut self, options: &OptionPath) {
+        let path = options.clone().key("pcap_directory");
+        let Some(directory) = self.get(&path) else {
+            return;
+        };
+        let enabled = !directory.is_null();
+        if !self.rename(options, "pcap_directory", "pcap_enabled") {
+            return;
+        }
+        self.set(&options.clone().key("pcap_enabled"), enabled.into());
+
+        let mut message = format!("replaced with 'pcap_enabled: {enabled}'");
+        if enabled {
+            message += "; the pcap files are now written to the host's directory in the data \
+                directory";
+        }
+        self.change(&path, message);
+    }
+
+    fn convert_process(&mut self, process: &OptionPath) {
+        let path = process.clone().key("stop_time");
+        if self.get(&path).is_none() || !self.rename(process, "stop_time", "shutdown_time") {
+            return;
+        }
+
+        // 'stop_time' killed the process, so keep the same signal and expect the process to be
+        // killed by it
+        let mut previous = process.clone().key("shutdown_time");
+        for (key, value, text) in [
+            ("shutdown_signal", Value::from("SIGKILL"), "SIGKILL"),
+            (
+                "expected_final_state",
+                Value::Mapping(Mapping::from_iter([("signaled".into(), "SIGKILL".into())])),
+                "{signaled: SIGKILL}",
+            ),
+        ] {
+            if self.get(&process.clone().key(key)).is_some() {
+                continue;
+            }
+            self.insert(process, key, value);
+            self.edit_text(|x| insert_after(x, &previous, key, text));
+            previous = process.clone().key(key);
+        }
+        self.change(
+            &path,
+            "replaced with 'shutdown_time', with a SIGKILL 'shutdown_signal' that the process is \
+            expected to be killed by",
+        );
+    }
+
+    /// Replace the processes that have a `quantity` with that many copies of the process.
+    fn replicate_processes(&mut self, processes: &OptionPath) {
+        let Some(list) = self.get(processes).and_then(Value::as_sequence).cloned() else {
+            return;
+        };
+        if !list.iter().any(|x| x.get("quantity").is_some()) {
+            return;
+        }
+
+        let mut replicated = Vec::new();
+        for (i, mut process) in list.into_iter().enumerate() {
+            let Some(quantity) = process.get("quantity").cloned() else {
+                replicated.push(process);
+                continue;
+            };
+            let path = processes.clone().index(i).key("quantity");
+            match quantity.as_u64().filter(|x| *x > 0) {
+                Some(quantity) => {
+                    process.as_mapping_mut().unwrap().shift_remove("quantity");
+                    replicated.extend(vec![process; quantity as usize]);
+                    self.change(
+                        &path,
+                        format!("replaced with {quantity} copies of the process"),
+                    );
+                }
+                None => {
+                    self.unconvertible(&path, "must be a positive integer");
+                    replicated.push(process);
+                }
+            }
+        }
+
+        *self.get_mut(processes).unwrap() = Value::Sequence(replicated);
+        // the copies can't be made by editing single lines
+        self.text = None;
+    }
+
+    /// Move an option to another mapping.
+    fn move_option(&mut self, from: &OptionPath, to: &OptionPath, key: &str) {
+        let old = from.clone().key(key);
+        let new = to.clone().key(key);
+        let Some(value) = self.get(&old).cloned() else {
+            return;
+        };
+        if self.get(&new).is_some() {
+            self.unconvertible(
+                &old,
+                format!("can't be moved to '{new}' since it's also set"),
+            );
+            return;
+        }
+
+        self.get_mut(from)
+            .and_then(Value::as_mapping_mut)
+            .unwrap()
+            .shift_remove(key);
+        if self.get(to).is_none() {
+            self.insert(
+                &OptionPath::new(&[]),
+                &to.to_string(),
+                Value::Mapping(Mapping::new()),
+            );
+        }
+        self.insert(to, key, value);
+
+        self.edit_text(|x| {
+            let value = scalar_text(x, &old)?;
+            let x = remove_line(x, &old)?;
+            insert_child(&x, to, key, &value)
+        });
+        self.change(&old, format!("moved to '{new}'"));
+    }
+
+    fn rename_option(&mut self, parent: &OptionPath, old: &str, new: &str) {
+        if self.rename(parent, old, new) {
+            self.change(&parent.clone().key(old), format!("renamed to '{new}'"));
+        }
+    }
+
+    /// Rename a mapping entry's key, keeping its position in the mapping. Returns `false` if the
+    /// entry wasn't renamed.
+    fn rename(&mut self, parent: &OptionPath, old: &str, new: &str) -> bool {
+        let path = parent.clone().key(old);
+        let Some(mapping) = self.get_mut(parent).and_then(Value::as_mapping_mut) else {
+            return false;
+        };
+        if !mapping.contains_key(old) {
+            return false;
+        }
+        if mapping.contains_key(new) {
+            self.unconvertible(
+                &path,
+                format!("can't be renamed to '{new}' since '{new}' is also set"),
+            );
+            return false;
+        }
+
+        *mapping = std::mem::take(mapping)
+            .into_iter()
+            .map(|(k, v)| match k.as_str() {
+                Some(k) if k == old => (new.into(), v),
+                _ => (k, v),
+            })
+            .collect();
+
+        self.edit_text(|x| rename_key(x, &path, new));
+        true
+    }
+
+    fn set(&mut self, path: &OptionPath, value: Value) {
+        let text = serde_yaml::to_string(&value).unwrap();
+        *self.get_mut(path).unwrap() = value;
+        self.edit_text(|x| set_scalar(x, path, text.trim_end()));
+    }
+
+    fn insert(&mut self, parent: &OptionPath, key: &str, value: Value) {
+        self.get_mut(parent)
+            .and_then(Value::as_mapping_mut)
+            .unwrap()
+            .insert(key.into(), value);
+    }
+
+    fn get(&self, path: &OptionPath) -> Option<&Value> {
+        path.0
+            .iter()
+            .try_fold(&self.value, |value, segment| match segment {
+                Segment::Key(x) => value.get(x),
+                Segment::Index(x) => value.get(x),
+            })
+    }
+
+    fn get_mut(&mut self, path: &OptionPath) -> Option<&mut Value> {
+        path.0
+            .iter()
+            .try_fold(&mut self.value, |value, segment| match segment {
+                Segment::Key(x) => value.get_mut(x),
+                Segment::Index(x) => value.get_mut(x),
+            })
+    }
+
+    /// Apply an edit to the text, or stop editing the text if the edit can't be made.
+    fn edit_text(&mut self, edit: impl FnOnce(&str) -> Option<String>) {
+        self.text = self.text.take().and_then(|x| edit(&x));
+    }
+
+    fn change(&mut self, option: &OptionPath, message: impl Into<String>) {
+        self.changes.push(Note {
+            option: option.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn unconvertible(&mut self, option: &OptionPath, message: impl Into<String>) {
+        self.unconvertible.push(Note {
+            option: option.to_string(),
+            message: message.into(),
+        });
+    }
+}
+
+/// Find the line (starting at 0) of the mapping entry at `path` in the yaml `text`, and the byte
+/// offset of its key in the line.
+fn find_key(text: &str, path: &OptionPath) -> Option<(usize, usize)> {
+    let Some(Segment::Key(key)) = path.0.last() else {
+        return None;
+    };
+    let (line, column) = config_validate::locate(text, &path.0)?;
+    let content = &text.lines().nth(line - 1)?[column - 1..];
+    config_validate::is_key(content, key).then_some((line - 1, column - 1))
+}
+
+/// Edit the lines of the text.
+fn edit_lines(text: &str, edit: impl FnOnce(&mut Vec<String>) -> Option<()>) -> Option<String> {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    edit(&mut lines)?;
+    let mut text = lines.join("\n");
+    text.push('\n');
+    Some(text)
+}
+
+/// Split a mapping entry's line into the text up to and including the key's colon, the value,
+/// and the trailing comment. Returns `None` if the value isn't a scalar on the same line.
+fn split_entry(line: &str, offset: usize) -> Option<(&str, &str, &str)> {
+    let colon = offset + line[offset..].find(':')?;
+    let (key, rest) = line.split_at(colon + 1);
+    let (value, comment) = match rest.find(" #") {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let value = value.trim();
+    if value.is_empty() || value.starts_with(['{', '[', '|', '>', '&', '*', '!']) {
+        return None;
+    }
+    Some((key, value, comment))
+}
+
+fn rename_key(text: &str, path: &OptionPath, new: &str) -> Option<String> {
+    let (line, offset) = find_key(text, path)?;
+    edit_lines(text, |lines| {
+        let colon = offset + lines[line][offset..].find(':')?;
+        lines[line].replace_range(offset..colon, new);
+        Some(())
+    })
+}
+
+/// The text of a mapping entry's scalar value.
+fn scalar_text(text: &str, path: &OptionPath) -> Option<String> {
+    let (line, offset) = find_key(text, path)?;
+    let (_, value, _) = split_entry(text.lines().nth(line)?, offset)?;
+    Some(value.to_string())
+}
+
+fn set_scalar(text: &str, path: &OptionPath, value: &str) -> Option<String> {
+    let (line, offset) = find_key(text, path)?;
+    edit_lines(text, |lines| {
+        let (key, _, comment) = split_entry(&lines[line], offset)?;
+        lines[line] = format!("{key} {value}{comment}");
+        Some(())
+    })
+}
+
+/// Remove a mapping entry with a scalar value. Entries on the same line as a sequence item's `-`
+/// aren't removed.
+fn remove_line(text: &str, path: &OptionPath) -> Option<String> {
+    let (line, offset) = find_key(text, path)?;
+    edit_lines(text, |lines| {
+        split_entry(&lines[line], offset)?;
+        if !lines[line][..offset].trim().is_empty() {
+            return None;
+        }
+        lines.remove(line);
+        Some(())
+    })
+}
+
+/// Insert a new mapping entry after the entry at `path`, which must have a scalar value.
+fn insert_after(text: &str, path: &OptionPath, key: &str, value: &str) -> Option<String> {
+    let (line, offset) = find_key(text, path)?;
+    edit_lines(text, |lines| {
+        split_entry(&lines[line], offset)?;
+        lines.insert(line + 1, format!("{}{key}: {value}", " ".repeat(offset)));
+        Some(())
+    })
+}
+
+/// Insert a new mapping entry as the first entry of the block-style mapping at `parent`.
+fn insert_child(text: &str, parent: &OptionPath, key: &str, value: &str) -> Option<String> {
+    let (line, offset) = find_key(text, parent)?;
+    edit_lines(text, |lines| {
+        let colon = offset + lines[line][offset..].find(':')?;
+        let rest = lines[line][colon + 1..].trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return None;
+        }
+        let indent = lines[line + 1..]
+            .iter()
+            .find_map(|x| config_validate::split_line(x))
+            .map(|(indent, _)| indent)
+            .filter(|indent| *indent > offset)
+            .unwrap_or(offset + 2);
+        lines.insert(line + 1, format!("{}{key}: {value}", " ".repeat(indent)));
+        Some(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let text = "\
+# an old configuration
+general:
+  stop_time: 10 s # the end
+experimental:
+  model_unblocked_syscall_latency: true
+  use_seccomp: false
+host_defaults:
+  pcap_directory: /tmp/pcap
+hosts:
+  client:
+    network_node_id: 0
+    options:
+      pcap_directory: null
+    processes:
+    - path: /bin/sleep
+      args: 100
+      # stop it early
+      stop_time: 5 s
+    - stop_time: 5 s
+      path: /bin/sleep
+";
+        let conversion = convert(text).unwrap();
+
+        assert_eq!(
+            conversion.text,
+            "\
+# an old configuration
+general:
+  model_unblocked_syscall_latency: true
+  stop_time: 10 s # the end
+experimental:
+  use_seccomp: false
+host_option_defaults:
+  pcap_enabled: true
+hosts:
+  client:
+    network_node_id: 0
+    host_options:
+      pcap_enabled: false
+    processes:
+    - path: /bin/sleep
+      args: 100
+      # stop it early
+      shutdown_time: 5 s
+      shutdown_signal: SIGKILL
+      expected_final_state: {signaled: SIGKILL}
+    - shutdown_time: 5 s
+      shutdown_signal: SIGKILL
+      expected_final_state: {signaled: SIGKILL}
+      path: /bin/sleep
+"
+        );
+        assert!(conversion.formatting_preserved);
+
+        let options: Vec<_> = conversion.changes.iter().map(|x| &x.option[..]).collect();
+        assert_eq!(
+            options,
+            [
+                "host_defaults",
+                "host_option_defaults.pcap_directory",
+                "experimental.model_unblocked_syscall_latency",
+                "hosts.client.options",
+                "hosts.client.host_options.pcap_directory",
+                "hosts.client.processes[0].stop_time",
+                "hosts.client.processes[1].stop_time",
+            ]
+        );
+        assert_eq!(
+            conversion.unconvertible,
+            [Note {
+                option: "experimental.use_seccomp".into(),
+                message: "was removed and has no replacement".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_convert_unchanged() {
+        let text = "general: {stop_time: 10 s}\nhosts: {}\n";
+        let conversion = convert(text).unwrap();
+        assert_eq!(conversion.text, text);
+        assert!(conversion.formatting_preserved);
+        assert!(conversion.changes.is_empty());
+        assert!(conversion.unconvertible.is_empty());
+    }
+
+    #[test]
+    fn test_convert_reserialized() {
+        let yaml = |x: &str| serde_yaml::from_str::<Value>(x).unwrap();
+
+        // options in flow-style mappings can't be edited
+        let conversion = convert("{host_defaults: {log_level: info}}\n").unwrap();
+        assert!(!conversion.formatting_preserved);
+        assert_eq!(
+            yaml(&conversion.text),
+            yaml("host_option_defaults: {log_level: info}")
+        );
+
+        let conversion = convert(
+            "hosts:\n  client:\n    processes:\n    - {path: /bin/true, quantity: 2}\n    - {path: /bin/false}\n",
+        )
+        .unwrap();
+        assert!(!conversion.formatting_preserved);
+        assert_eq!(
+            yaml(&conversion.text),
+            yaml("hosts: {client: {processes: [{path: /bin/true}, {path: /bin/true}, {path: /bin/false}]}}")
+        );
+        assert_eq!(
+            conversion.changes[0].message,
+            "replaced with 2 copies of the process"
+        );
+    }
+
+    #[test]
+    fn test_convert_conflict() {
+        let text = "host_defaults: {}\nhost_option_defaults: {}\n";
+        let conversion = convert(text).unwrap();
+        assert_eq!(conversion.text, text);
+        assert!(conversion.changes.is_empty());
+        assert_eq!(conversion.unconvertible.len(), 1);
+        assert_eq!(conversion.unconvertible[0].option, "host_defaults");
+    }
+
+    #[test]
+    fn test_text_edits() {
+        let text = "a:\n  b: 1 # one\n  c:\n    d: x\n";
+        let path = |keys: &[&str]| OptionPath::new(keys);
+
+        assert_eq!(
+            rename_key(text, &path(&["a", "b"]), "e").unwrap(),
+            "a:\n  e: 1 # one\n  c:\n    d: x\n"
+        );
+        assert_eq!(
+            set_scalar(text, &path(&["a", "b"]), "2").unwrap(),
+            "a:\n  b: 2 # one\n  c:\n    d: x\n"
+        );
+        assert_eq!(
+            remove_line(text, &path(&["a", "c", "d"])).unwrap(),
+            "a:\n  b: 1 # one\n  c:\n"
+        );
+        assert_eq!(
+            insert_child(text, &path(&["a", "c"]), "e", "y").unwrap(),
+            "a:\n  b: 1 # one\n  c:\n    e: y\n    d: x\n"
+        );
+        assert_eq!(scalar_text(text, &path(&["a", "b"])).unwrap(), "1");
+
+        // the entry must exist and have a scalar value on the same line
+        assert_eq!(rename_key(text, &path(&["a", "x"]), "e"), None);
+        assert_eq!(set_scalar(text, &path(&["a", "c"]), "2"), None);
+        assert_eq!(insert_after(text, &path(&["a", "c"]), "e", "y"), None);
+        assert_eq!(insert_child(text, &path(&["a", "b"]), "e", "y"), None);
+    }
+}
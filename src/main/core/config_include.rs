@@ -0,0 +1,331 @@
+//! Loading configuration files that include other configuration files.
+//!
+//! A configuration file can list other files in its top-level `include` key. The included files are
+//! loaded first, in order, and each file is overlaid on the files before it. The including file is
+//! overlaid last, so its options take precedence over the options of all of its included files.
+//! When a file is overlaid on another, mappings are merged key by key, and all other values
+//! (including sequences) are replaced. Included files may include other files, and relative paths
+//! are relative to the directory of the including file.
+//!
+//! Configuration files can also be written in JSON or TOML, which have the same schema as YAML
+//! files. A file's format is detected from its extension, so YAML, JSON, and TOML files can include
+//! each other.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::utility::tilde_expansion;
+
+/// The top-level key that lists the files to include.
+const INCLUDE_KEY: &str = "include";
+
+/// The format of a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl ConfigFormat {
+    /// The format of a file based on its extension. Files without a ".json" or ".toml" extension
+    /// are YAML files.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("json") => Self::Json,
+            Some("toml") => Self::Toml,
+            _ => Self::Yaml,
+        }
+    }
+
+    /// Parse the text of a configuration file in this format.
+    pub fn parse(self, text: &str) -> anyhow::Result<Value> {
+        // serde's default behaviour is to silently ignore duplicate keys during deserialization so
+        // we would typically need to use serde_with's `maps_duplicate_key_is_error()` on our
+        // 'ConfigFileOptions' struct to prevent duplicate hostnames, but since we deserialize to
+        // serde_yaml's `Value` type initially we don't need to prevent duplicate keys as
+        // serde_yaml's `Mapping` does this for us: https://github.com/dtolnay/serde-yaml/pull/301
+        match self {
+            Self::Yaml => {
+                serde_yaml::from_str(text).context("Could not parse configuration file as yaml")
+            }
+            Self::Json => {
+                serde_json::from_str(text).context("Could not parse configuration file as json")
+            }
+            Self::Toml => {
+                toml::from_str(text).context("Could not parse configuration file as toml")
+            }
+        }
+    }
+}
+
+/// Load a configuration file and the files that it includes, returning the merged yaml. The file's
+/// format is detected from its extension if `format` is `None`. Merge keys (`<<`) are applied to
+/// each file separately, so anchors can't be used across files.
+pub fn load_with_includes(path: &Path, format: Option<ConfigFormat>) -> anyhow::Result<Value> {
+    load(path, format, &mut Vec::new())
+}
+
+/// Load a configuration file and its includes. `stack` contains the canonical paths of the files
+/// that are currently being loaded, which are used to detect include cycles.
+fn load(
+    path: &Path,
+    format: Option<ConfigFormat>,
+    stack: &mut Vec<PathBuf>,
+) -> anyhow::Result<Value> {
+    // stdin can't be canonicalized if it's a pipe, but it also can't be included
+    let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(anyhow::anyhow!(
+            "The config file '{}' includes itself",
+            path.display()
+        ));
+    }
+
+    let text = std::fs::read_to_string(path).context("Could not read config file")?;
+    let mut value = format
+        .unwrap_or_else(|| ConfigFormat::from_path(path))
+        .parse(&text)?;
+
+    value.apply_merge().context("Could not merge '<<' keys")?;
+
+    let includes = take_includes(&mut value)?;
+    if includes.is_empty() {
+        return Ok(value);
+    }
+
+    // the config may be read from stdin, which isn't in the same directory as the included files
+    let dir = if path == Path::new("/dev/stdin") {
+        Path::new(".")
+    } else {
+        path.parent().unwrap_or(Path::new("."))
+    };
+
+    stack.push(canonical);
+    let mut merged = Value::Mapping(Default::default());
+    for include in includes {
+        let include_path = dir.join(tilde_expansion(&include));
+        let included = load(&include_path, None, stack)
+            .with_context(|| format!("Failed to load included file '{include}'"))?;
+        overlay(&mut merged, included);
+    }
+    stack.pop();
+
+    overlay(&mut merged, value);
+    Ok(merged)
+}
+
+/// Remove the `include` key from the top-level mapping, returning the included paths. The key may
+/// be a single path or a list of paths.
+fn take_includes(value: &mut Value) -> anyhow::Result<Vec<String>> {
+    let Value::Mapping(mapping) = value else {
+        return Ok(Vec::new());
+    };
+
+    let paths = match mapping.remove(INCLUDE_KEY) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(path)) => vec![path],
+        Some(Value::Sequence(paths)) => paths
+            .into_iter()
+            .map(|x| match x {
+                Value::String(x) => Ok(x),
+                x => Err(anyhow::anyhow!("Included path {x:?} is not a string")),
+            })
+            .collect::<anyhow::Result<_>>()?,
+        Some(x) => {
+            return Err(anyhow::anyhow!(
+                "The '{INCLUDE_KEY}' key must be a path or a list of paths, not {x:?}"
+            ))
+        }
+    };
+
+    Ok(paths)
+}
+
+/// Overlay `top` on `base`. Mappings are merged recursively, and all other values in `top` replace
+/// the values in `base`.
+pub fn overlay(base: &mut Value, top: Value) {
+    match (base, top) {
+        (Value::Mapping(base), Value::Mapping(top)) => {
+            for (key, value) in top {
+                match base.get_mut(&key) {
+                    Some(x) => overlay(x, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, top) => *base = top,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut base = yaml(
+            "
+            general: {stop_time: 10 s, seed: 1}
+            hosts:
+              client: {network_node_id: 0, processes: [{path: a}, {path: b}]}
+            ",
+        );
+        overlay(
+            &mut base,
+            yaml(
+                "
+                general: {stop_time: 20 s}
+                hosts:
+                  client: {processes: [{path: c}]}
+                  server: {network_node_id: 1}
+                ",
+            ),
+        );
+
+        assert_eq!(
+            base,
+            yaml(
+                "
+                general: {stop_time: 20 s, seed: 1}
+                hosts:
+                  client: {network_node_id: 0, processes: [{path: c}]}
+                  server: {network_node_id: 1}
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("common")).unwrap();
+
+        std::fs::write(
+            dir.path().join("common/hosts.yaml"),
+            "
+            include: network.yaml
+            x-host: &host {network_node_id: 0, bandwidth_down: 1 Mbit}
+            hosts:
+              client: {<<: *host, processes: []}
+              server: {<<: *host, processes: []}
+            ",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("common/network.yaml"),
+            "
+            general: {stop_time: 10 s}
+            network: {graph: {type: 1_gbit_switch}}
+            ",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("experiment.yaml"),
+            "
+            include: [common/hosts.yaml]
+            general: {stop_time: 20 s}
+            hosts:
+              server: {bandwidth_down: 10 Mbit}
+            ",
+        )
+        .unwrap();
+
+        let value = load_with_includes(&dir.path().join("experiment.yaml"), None).unwrap();
+        assert_eq!(value["general"]["stop_time"], yaml("20 s"));
+        assert_eq!(value["network"]["graph"]["type"], yaml("1_gbit_switch"));
+        assert_eq!(value["hosts"]["client"]["bandwidth_down"], yaml("1 Mbit"));
+        assert_eq!(value["hosts"]["server"]["bandwidth_down"], yaml("10 Mbit"));
+        assert_eq!(value["hosts"]["server"]["network_node_id"], yaml("0"));
+        assert!(value.get(INCLUDE_KEY).is_none());
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.yaml"), "include: b.yaml").unwrap();
+        std::fs::write(dir.path().join("b.yaml"), "include: a.yaml").unwrap();
+        assert!(load_with_includes(&dir.path().join("a.yaml"), None).is_err());
+
+        std::fs::write(dir.path().join("c.yaml"), "include: missing.yaml").unwrap();
+        assert!(load_with_includes(&dir.path().join("c.yaml"), None).is_err());
+
+        std::fs::write(dir.path().join("d.yaml"), "include: {a: b}").unwrap();
+        assert!(load_with_includes(&dir.path().join("d.yaml"), None).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("hosts.toml"),
+            "
+            [hosts.client]
+            network_node_id = 0
+            processes = [{path = 'a', args = ['-v']}]
+            ",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("experiment.json"),
+            r#"{"include": "hosts.toml", "general": {"stop_time": "10 s", "seed": 1}}"#,
+        )
+        .unwrap();
+
+        let value = load_with_includes(&dir.path().join("experiment.json"), None).unwrap();
+        assert_eq!(
+            value,
+            yaml(
+                "
+                hosts:
+                  client: {network_node_id: 0, processes: [{path: a, args: [-v]}]}
+                general: {stop_time: 10 s, seed: 1}
+                "
+            )
+        );
+
+        // the format given explicitly takes precedence over the extension
+        assert!(load_with_includes(
+            &dir.path().join("experiment.json"),
+            Some(ConfigFormat::Toml)
+        )
+        .is_err());
+
+        // duplicate keys aren't allowed in any format
+        std::fs::write(dir.path().join("dup.json"), r#"{"a": 1, "a": 2}"#).unwrap();
+        assert!(load_with_includes(&dir.path().join("dup.json"), None).is_err());
+
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a/b.toml")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("b.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("/dev/stdin")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!("json".parse::<ConfigFormat>().unwrap(), ConfigFormat::Json);
+    }
+}
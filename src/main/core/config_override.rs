@@ -0,0 +1,236 @@
+//! Overriding configuration file options from the command line.
+//!
+//! The `--set option=value` command line option sets any option of the configuration file, where
+//! `option` is the option's path of mapping keys separated by `.`, and sequence indexes in `[...]`
+//! (for example `hosts.client.processes[0].args`). Keys that contain a `.` or `[` can be quoted with
+//! `"`. The value is parsed as yaml, so it can be a number, a string, a list, or a mapping. Missing
+//! mappings along the path are created, but sequence items must already exist.
+
+use serde_yaml::Value;
+
+/// A component of an option's path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// An option set from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+    path: Vec<Segment>,
+    value: Value,
+}
+
+/// Parse an `option=value` override from the command line.
+pub fn parse_override(s: &str) -> Result<ConfigOverride, String> {
+    let (path, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected 'option=value', not '{s}'"))?;
+    let path = parse_path(path)?;
+
+    // values on the command line have the same types that they would have in the config
+    let value = serde_yaml::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+
+    Ok(ConfigOverride { path, value })
+}
+
+/// Parse a path like `hosts.client.processes[0].args`.
+fn parse_path(s: &str) -> Result<Vec<Segment>, String> {
+    let mut path = Vec::new();
+    let mut rest = s;
+
+    loop {
+        // a key, which may be quoted
+        let (key, after) = if let Some(x) = rest.strip_prefix('"') {
+            let end = x
+                .find('"')
+                .ok_or_else(|| format!("Unterminated quote in option '{s}'"))?;
+            (&x[..end], &x[end + 1..])
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if key.is_empty() {
+            return Err(format!("Empty key in option '{s}'"));
+        }
+        path.push(Segment::Key(key.to_string()));
+        rest = after;
+
+        // any sequence indexes
+        while let Some(x) = rest.strip_prefix('[') {
+            let end = x
+                .find(']')
+                .ok_or_else(|| format!("Unterminated index in option '{s}'"))?;
+            let index = x[..end]
+                .parse()
+                .map_err(|_| format!("Invalid index '{}' in option '{s}'", &x[..end]))?;
+            path.push(Segment::Index(index));
+            rest = &x[end + 1..];
+        }
+
+        if rest.is_empty() {
+            return Ok(path);
+        }
+        rest = rest
+            .strip_prefix('.')
+            .ok_or_else(|| format!("Expected '.' before '{rest}' in option '{s}'"))?;
+    }
+}
+
+impl ConfigOverride {
+    /// Set the option in the configuration.
+    pub fn apply(&self, config: &mut Value) -> Result<(), String> {
+        let mut value = config;
+
+        for (i, segment) in self.path.iter().enumerate() {
+            let parent = display_path(&self.path[..i]);
+            value = match segment {
+                Segment::Key(key) => {
+                    if value.is_null() {
+                        *value = Value::Mapping(Default::default());
+                    }
+                    let Value::Mapping(mapping) = value else {
+                        return Err(format!("The option '{parent}' is not a mapping"));
+                    };
+                    mapping
+                        .entry(Value::String(key.clone()))
+                        .or_insert(Value::Null)
+                }
+                Segment::Index(index) => {
+                    let Value::Sequence(sequence) = value else {
+                        return Err(format!("The option '{parent}' is not a sequence"));
+                    };
+                    let len = sequence.len();
+                    sequence.get_mut(*index).ok_or_else(|| {
+                        format!("The option '{parent}' has {len} items, so it has no index {index}")
+                    })?
+                }
+            };
+        }
+
+        *value = self.value.clone();
+        Ok(())
+    }
+
+    /// The path of the option.
+    pub fn option(&self) -> String {
+        display_path(&self.path)
+    }
+}
+
+fn display_path(path: &[Segment]) -> String {
+    let mut s = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(x) => {
+                if !s.is_empty() {
+                    s.push('.');
+                }
+                if x.contains(['.', '[']) {
+                    s.push_str(&format!("\"{x}\""));
+                } else {
+                    s.push_str(x);
+                }
+            }
+            Segment::Index(x) => s.push_str(&format!("[{x}]")),
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    fn apply(config: &str, overrides: &[&str]) -> Result<Value, String> {
+        let mut config = yaml(config);
+        for x in overrides {
+            parse_override(x)?.apply(&mut config)?;
+        }
+        Ok(config)
+    }
+
+    #[test]
+    fn test_parse_path() {
+        use Segment::*;
+        let key = |x: &str| Key(x.to_string());
+
+        assert_eq!(parse_path("general"), Ok(vec![key("general")]));
+        assert_eq!(
+            parse_path("hosts.client.processes[0].args"),
+            Ok(vec![
+                key("hosts"),
+                key("client"),
+                key("processes"),
+                Index(0),
+                key("args")
+            ])
+        );
+        assert_eq!(
+            parse_path("hosts.\"a.b\".x[1][2]"),
+            Ok(vec![key("hosts"), key("a.b"), key("x"), Index(1), Index(2)])
+        );
+        assert_eq!(
+            display_path(&parse_path("hosts.\"a.b\".x[1][2]").unwrap()),
+            "hosts.\"a.b\".x[1][2]"
+        );
+
+        for invalid in [
+            "", "a.", ".a", "a..b", "a[x]", "a[1", "a[1]b", "\"a", "a.\"b\"c",
+        ] {
+            assert!(parse_path(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = apply(
+            "
+            general: {stop_time: 10 s}
+            hosts:
+              client:
+                network_node_id: 0
+                processes: [{path: a, args: -v}, {path: b}]
+            ",
+            &[
+                "general.stop_time=20 s",
+                "general.seed=5",
+                "hosts.client.network_node_id=3",
+                "hosts.client.processes[1].args=[-q, '1']",
+                "hosts.client.host_options.pcap_enabled=true",
+                "experimental.use_memory_manager=false",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            yaml(
+                "
+                general: {stop_time: 20 s, seed: 5}
+                hosts:
+                  client:
+                    network_node_id: 3
+                    processes: [{path: a, args: -v}, {path: b, args: [-q, '1']}]
+                    host_options: {pcap_enabled: true}
+                experimental: {use_memory_manager: false}
+                "
+            )
+        );
+
+        assert_eq!(apply("{a: 1}", &["a=x=y"]).unwrap(), yaml("{a: x=y}"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse_override("general.stop_time").is_err());
+        assert!(apply("{a: 1}", &["a.b=2"]).is_err());
+        assert!(apply("{a: [1]}", &["a[1]=2"]).is_err());
+        assert!(apply("{a: {b: 1}}", &["a[0]=2"]).is_err());
+    }
+}
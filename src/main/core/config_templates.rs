@@ -0,0 +1,213 @@
+//! Host templates in configuration files.
+//!
+//! Templates are defined in the top-level `host_templates` mapping of a configuration file, and
+//! contain any host options. A host (or another template) with a `template` option inherits the
+//! options of that template, and its own options override them. Mappings such as `host_options` are
+//! merged recursively, and all other values (including lists such as `processes`) replace the
+//! template's values.
+
+use std::collections::BTreeMap;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::core::config_include::overlay;
+
+/// The top-level key that defines the templates.
+const TEMPLATES_KEY: &str = "host_templates";
+
+/// The top-level key that contains the hosts.
+const HOSTS_KEY: &str = "hosts";
+
+/// The host or template option that names the template that it inherits from.
+const TEMPLATE_KEY: &str = "template";
+
+/// Remove the templates from the top-level mapping of `config`, and apply them to the hosts that
+/// use them.
+pub fn apply(config: &mut Value) -> Result<(), String> {
+    let Value::Mapping(mapping) = config else {
+        return Ok(());
+    };
+
+    let templates = match mapping.remove(TEMPLATES_KEY) {
+        None | Some(Value::Null) => Mapping::new(),
+        Some(Value::Mapping(x)) => x,
+        Some(x) => {
+            return Err(format!(
+                "The '{TEMPLATES_KEY}' key must be a mapping, not {x:?}"
+            ))
+        }
+    };
+
+    let mut templates: BTreeMap<String, Mapping> = templates
+        .into_iter()
+        .map(|(name, value)| {
+            let Value::String(name) = name else {
+                return Err(format!("Template name {name:?} is not a string"));
+            };
+            match value {
+                Value::Mapping(x) => Ok((name, x)),
+                Value::Null => Ok((name, Mapping::new())),
+                x => Err(format!("Template '{name}' must be a mapping, not {x:?}")),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    // resolve the inheritance of the templates themselves first
+    let names: Vec<String> = templates.keys().cloned().collect();
+    for name in names {
+        let resolved = resolve(&templates, &name, &mut Vec::new())?;
+        templates.insert(name, resolved);
+    }
+
+    let Some(Value::Mapping(hosts)) = mapping.get_mut(HOSTS_KEY) else {
+        return Ok(());
+    };
+
+    for (hostname, host) in hosts.iter_mut() {
+        let Value::Mapping(options) = host else {
+            continue;
+        };
+        let Some(template) = options.remove(TEMPLATE_KEY) else {
+            continue;
+        };
+        let template = template_name(&template)
+            .map_err(|e| format!("Invalid template for host {hostname:?}: {e}"))?;
+        let base = templates
+            .get(template)
+            .ok_or_else(|| format!("Host {hostname:?} uses the unknown template '{template}'"))?;
+
+        let mut resolved = Value::Mapping(base.clone());
+        overlay(&mut resolved, Value::Mapping(std::mem::take(options)));
+        *host = resolved;
+    }
+
+    Ok(())
+}
+
+/// The options of the template `name` after applying the templates that it inherits from, where
+/// `stack` is the templates that inherit from it.
+fn resolve(
+    templates: &BTreeMap<String, Mapping>,
+    name: &str,
+    stack: &mut Vec<String>,
+) -> Result<Mapping, String> {
+    if stack.iter().any(|x| x == name) {
+        stack.push(name.to_string());
+        return Err(format!(
+            "The templates inherit from each other in a cycle: {}",
+            stack.join(" -> ")
+        ));
+    }
+
+    let mut options = templates
+        .get(name)
+        .ok_or_else(|| format!("Unknown template '{name}'"))?
+        .clone();
+
+    let Some(parent) = options.remove(TEMPLATE_KEY) else {
+        return Ok(options);
+    };
+    let parent = template_name(&parent)
+        .map_err(|e| format!("Invalid template for template '{name}': {e}"))?;
+
+    stack.push(name.to_string());
+    let mut resolved = Value::Mapping(resolve(templates, parent, stack)?);
+    stack.pop();
+
+    overlay(&mut resolved, Value::Mapping(options));
+    let Value::Mapping(resolved) = resolved else {
+        unreachable!();
+    };
+    Ok(resolved)
+}
+
+fn template_name(value: &Value) -> Result<&str, String> {
+    match value {
+        Value::String(x) => Ok(x),
+        x => Err(format!("The template name must be a string, not {x:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    fn applied(s: &str) -> Result<Value, String> {
+        let mut config = yaml(s);
+        apply(&mut config)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = applied(
+            "
+            host_templates:
+              relay:
+                bandwidth_down: 100 Mbit
+                bandwidth_up: 100 Mbit
+                host_options: {pcap_enabled: true, log_level: info}
+                processes: [{path: tor}]
+              exit:
+                template: relay
+                bandwidth_up: 50 Mbit
+                host_options: {log_level: debug}
+            hosts:
+              relay1:
+                template: relay
+                network_node_id: 0
+              exit1:
+                template: exit
+                network_node_id: 1
+                processes: [{path: tor, args: --exit}]
+              client:
+                network_node_id: 2
+                processes: [{path: curl}]
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            yaml(
+                "
+                hosts:
+                  relay1:
+                    bandwidth_down: 100 Mbit
+                    bandwidth_up: 100 Mbit
+                    host_options: {pcap_enabled: true, log_level: info}
+                    processes: [{path: tor}]
+                    network_node_id: 0
+                  exit1:
+                    bandwidth_down: 100 Mbit
+                    bandwidth_up: 50 Mbit
+                    host_options: {pcap_enabled: true, log_level: debug}
+                    processes: [{path: tor, args: --exit}]
+                    network_node_id: 1
+                  client:
+                    network_node_id: 2
+                    processes: [{path: curl}]
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(applied("{host_templates: [a], hosts: {}}").is_err());
+        assert!(applied("{host_templates: {a: 1}, hosts: {}}").is_err());
+        assert!(applied("{hosts: {a: {template: b, network_node_id: 0}}}").is_err());
+        assert!(applied("{host_templates: {a: {template: c}}, hosts: {}}").is_err());
+        assert!(applied("{host_templates: {a: {}}, hosts: {b: {template: [a]}}}").is_err());
+
+        // cycles
+        assert!(applied("{host_templates: {a: {template: a}}, hosts: {}}").is_err());
+        assert!(
+            applied("{host_templates: {a: {template: b}, b: {template: a}}, hosts: {}}").is_err()
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! Validating a configuration without running the simulation.
+//!
+//! The configuration is loaded and resolved in the same way as when running a simulation. Then the
+//! references to files, binaries, and graph nodes are checked, and the simulation's configuration
+//! is built (which loads and parses the network graph and computes the routes between hosts). The
+//! problems are reported as a list of diagnostics, which include the location of the option in the
+//! configuration file when it can be found.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::core::configuration::{
+    CaidaGraphOptions, CliOptions, ConfigFileOptions, ConfigOptions, GraphOptions, GraphSource,
+    RoutingOptions,
+};
+use crate::core::sim_config::SimConfig;
+use crate::network::graph::{load_network_graph, NetworkGraph};
+use crate::utility::tilde_expansion;
+
+/// The results of validating a configuration.
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    /// Whether the configuration has no errors. It may still have warnings.
+    pub valid: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// An error or warning about the configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The option that the diagnostic refers to, for example `hosts.client.processes[0].path`.
+    pub option: Option<String>,
+    /// The line of the option in the configuration file, starting at 1.
+    pub line: Option<usize>,
+    /// The column of the option in the configuration file, starting at 1.
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A component of an option's path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The path of an option, such as `hosts.client.processes[0].path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OptionPath(pub(crate) Vec<Segment>);
+
+impl OptionPath {
+    pub(crate) fn new(keys: &[&str]) -> Self {
+        Self(keys.iter().map(|x| Segment::Key(x.to_string())).collect())
+    }
+
+    pub(crate) fn key(mut self, key: &str) -> Self {
+        self.0.push(Segment::Key(key.to_string()));
+        self
+    }
+
+    pub(crate) fn index(mut self, index: usize) -> Self {
+        self.0.push(Segment::Index(index));
+        self
+    }
+}
+
+impl std::fmt::Display for OptionPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(x) if i == 0 => write!(f, "{x}")?,
+                Segment::Key(x) => write!(f, ".{x}")?,
+                Segment::Index(x) => write!(f, "[{x}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collects the diagnostics, and finds their locations in the configuration file's text.
+struct Diagnostics<'a> {
+    text: Option<&'a str>,
+    list: Vec<Diagnostic>,
+}
+
+impl Diagnostics<'_> {
+    fn push(&mut self, severity: Severity, option: Option<OptionPath>, message: String) {
+        let location = option
+            .as_ref()
+            .zip(self.text)
+            .and_then(|(option, text)| locate(text, &option.0));
+        self.list.push(Diagnostic {
+            severity,
+            message,
+            option: option.map(|x| x.to_string()),
+            line: location.map(|x| x.0),
+            column: location.map(|x| x.1),
+        });
+    }
+
+    fn error(&mut self, option: Option<OptionPath>, message: String) {
+        self.push(Severity::Error, option, message)
+    }
+
+    fn warning(&mut self, option: Option<OptionPath>, message: String) {
+        self.push(Severity::Warning, option, message)
+    }
+
+    fn has_errors(&self) -> bool {
+        self.list.iter().any(|x| x.severity == Severity::Error)
+    }
+}
+
+/// Validate a loaded configuration file. The `config_text` is the text of the configuration file,
+/// which is used to find the locations of the options in the diagnostics.
+pub fn validate(
+    config_file: anyhow::Result<ConfigFileOptions>,
+    options: &CliOptions,
+    config_text: Option<&str>,
+) -> ValidationReport {
+    let mut diagnostics = Diagnostics {
+        text: config_text,
+        list: Vec::new(),
+    };
+
+    match config_file {
+        Ok(config_file) => {
+            let config = ConfigOptions::new(config_file, options.clone());
+            check_config(&config, &mut diagnostics);
+        }
+        Err(e) => {
+            // yaml syntax errors have a location, but errors in the options don't
+            let location = e
+                .chain()
+                .find_map(|x| x.downcast_ref::<serde_yaml::Error>())
+                .and_then(|x| x.location());
+            diagnostics.list.push(Diagnostic {
+                severity: Severity::Error,
+                message: format!("{e:#}"),
+                option: None,
+                line: location.as_ref().map(|x| x.line()),
+                column: location.as_ref().map(|x| x.column()),
+            });
+        }
+    }
+
+    ValidationReport {
+        valid: !diagnostics.has_errors(),
+        diagnostics: diagnostics.list,
+    }
+}
+
+fn check_config(config: &ConfigOptions, diagnostics: &mut Diagnostics) {
+    check_files(config, diagnostics);
+
+    // the graph can't be loaded if its file is missing
+    if !diagnostics.has_errors() {
+        check_graph_nodes(config, diagnostics);
+    }
+
+    for (name, host) in &config.hosts {
+        let host_path = OptionPath::new(&["hosts", name.as_str()]);
+
+        if host.processes.is_empty() {
+            diagnostics.warning(
+                Some(host_path.clone().key("processes")),
+                format!("Host '{name}' has no processes"),
+            );
+        }
+
+        for (i, process) in host.processes.iter().enumerate() {
+            let path = tilde_expansion(process.path.to_str().unwrap());
+            if let Err(e) = which::which(&path) {
+                diagnostics.error(
+                    Some(host_path.clone().key("processes").index(i).key("path")),
+                    format!("Could not find the binary '{}': {e}", path.display()),
+                );
+            }
+        }
+    }
+
+    // building the simulation's configuration runs all of the other checks, but stops at the first
+    // error
+    if !diagnostics.has_errors() {
+        if let Err(e) = SimConfig::new(config, &HashSet::new()) {
+            diagnostics.error(None, format!("{e:#}"));
+        }
+    }
+}
+
+/// Check that the files referenced by the configuration exist.
+fn check_files(config: &ConfigOptions, diagnostics: &mut Diagnostics) {
+    let mut files = Vec::new();
+
+    match config.network.graph.as_ref() {
+        Some(GraphOptions::Gml(GraphSource::File(source)))
+        | Some(GraphOptions::Caida(CaidaGraphOptions {
+            source: GraphSource::File(source),
+            ..
+        })) => files.push((
+            OptionPath::new(&["network", "graph", "file", "path"]),
+            &source.path,
+        )),
+        _ => {}
+    }
+
+    if let Some(RoutingOptions::Static { path }) = config.network.routing.as_ref() {
+        files.push((OptionPath::new(&["network", "routing", "path"]), path));
+    }
+
+    for (i, trace) in config.network.link_traces.iter().flatten().enumerate() {
+        files.push((
+            OptionPath::new(&["network", "link_traces"])
+                .index(i)
+                .key("path"),
+            &trace.path,
+        ));
+    }
+
+    for (name, host) in &config.hosts {
+        if let Some(trace) = &host.uplink_trace {
+            files.push((
+                OptionPath::new(&["hosts", name.as_str(), "uplink_trace"]),
+                trace,
+            ));
+        }
+    }
+
+    for (option, path) in files {
+        let expanded = tilde_expansion(path);
+        if !expanded.is_file() {
+            diagnostics.error(
+                Some(option),
+                format!("The file '{}' does not exist", expanded.display()),
+            );
+        }
+    }
+}
+
+/// Check that the hosts' graph nodes exist.
+fn check_graph_nodes(config: &ConfigOptions, diagnostics: &mut Diagnostics) {
+    let graph = load_network_graph(config.network.graph.as_ref().unwrap())
+        .and_then(|x| NetworkGraph::parse(&x));
+    let graph = match graph {
+        Ok(x) => x,
+        Err(e) => {
+            diagnostics.error(
+                Some(OptionPath::new(&["network", "graph"])),
+                format!("Failed to load the network graph: {e}"),
+            );
+            return;
+        }
+    };
+
+    for (name, host) in &config.hosts {
+        let host_path = OptionPath::new(&["hosts", name.as_str()]);
+
+        if graph.node_id_to_index(host.network_node_id).is_none() {
+            diagnostics.error(
+                Some(host_path.clone().key("network_node_id")),
+                format!(
+                    "The network node id {} for host '{name}' does not exist",
+                    host.network_node_id
+                ),
+            );
+        }
+
+        for (i, interface) in host.interfaces.iter().enumerate() {
+            if graph.node_id_to_index(interface.network_node_id).is_none() {
+                diagnostics.error(
+                    Some(
+                        host_path
+                            .clone()
+                            .key("interfaces")
+                            .index(i)
+                            .key("network_node_id"),
+                    ),
+                    format!(
+                        "The network node id {} for interface 'eth{}' of host '{name}' does not \
+                         exist",
+                        interface.network_node_id,
+                        i + 1
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Split a line into its indentation and its content, or `None` if it's blank or a comment.
+pub(crate) fn split_line(line: &str) -> Option<(usize, &str)> {
+    let content = line.trim_start_matches(' ');
+    if content.trim().is_empty() || content.starts_with('#') {
+        return None;
+    }
+    Some((line.len() - content.len(), content))
+}
+
+/// Whether the line's content is a mapping entry with the key `key`.
+pub(crate) fn is_key(content: &str, key: &str) -> bool {
+    [
+        format!("{key}:"),
+        format!("'{key}':"),
+        format!("\"{key}\":"),
+    ]
+    .iter()
+    .any(|x| content.starts_with(x.as_str()))
+}
+
+/// Find the line and column (starting at 1) of the option at `path` in the yaml `text`, or of its
+/// closest ancestor that can be found. Only block-style mappings and sequences are searched, so
+/// options in flow-style (`{...}` or `[...]`) collections and in included files aren't found.
+pub(crate) fn locate(text: &str, path: &[Segment]) -> Option<(usize, usize)> {
+    let lines: Vec<_> = text.lines().map(split_line).collect();
+
+    // the line and indentation of the option that was last found, and whether it's a sequence item
+    let mut parent: Option<(usize, usize, bool)> = None;
+    let mut location = None;
+
+    for segment in path {
+        // the lines that may contain the children of the parent, as (line, indentation, content)
+        let mut candidates = Vec::new();
+        match parent {
+            None => candidates.extend(
+                lines
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, x)| x.map(|(indent, content)| (i, indent, content))),
+            ),
+            Some((line, indent, is_item)) => {
+                if is_item {
+                    // the item's line also contains the item's first mapping entry
+                    let (_, content) = lines[line].unwrap();
+                    candidates.push((line, indent + 2, &content[2..]));
+                }
+                for (i, x) in lines.iter().enumerate().skip(line + 1) {
+                    let Some((child_indent, content)) = *x else {
+                        continue;
+                    };
+                    // a sequence can have the same indentation as the key that contains it
+                    if child_indent < indent
+                        || (child_indent == indent && (is_item || !is_sequence_item(content)))
+                    {
+                        break;
+                    }
+                    candidates.push((i, child_indent, content));
+                }
+            }
+        }
+
+        let Some(&(_, child_indent, _)) = candidates.first() else {
+            break;
+        };
+
+        let mut items = 0;
+        let found = candidates
+            .into_iter()
+            .filter(|(_, indent, _)| *indent == child_indent)
+            .find(|(_, _, content)| match segment {
+                Segment::Key(key) => is_key(content, key),
+                Segment::Index(index) => {
+                    if !is_sequence_item(content) {
+                        return false;
+                    }
+                    items += 1;
+                    items - 1 == *index
+                }
+            });
+
+        let Some((line, indent, content)) = found else {
+            break;
+        };
+        location = Some((line + 1, indent + 1));
+        parent = Some((line, indent, is_sequence_item(content)));
+    }
+
+    location
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_option_path() {
+        let path = OptionPath::new(&["hosts", "client"])
+            .key("processes")
+            .index(1)
+            .key("path");
+        assert_eq!(path.to_string(), "hosts.client.processes[1].path");
+    }
+
+    #[test]
+    fn test_locate() {
+        let text = "
+general:
+  stop_time: 10 s
+# a comment
+hosts:
+  client:
+    network_node_id: 0
+    processes:
+    - path: /bin/true
+    - args: -v
+      path: /bin/false
+  'server':
+    network_node_id: 1
+    processes: [{path: /bin/true}]
+    interfaces:
+      - network_node_id: 2
+";
+        let locate = |path: &OptionPath| locate(text, &path.0);
+        let hosts = OptionPath::new(&["hosts"]);
+        let client = hosts.clone().key("client");
+        let server = hosts.clone().key("server");
+
+        assert_eq!(
+            locate(&OptionPath::new(&["general", "stop_time"])),
+            Some((3, 3))
+        );
+        assert_eq!(locate(&client.clone().key("network_node_id")), Some((7, 5)));
+        let processes = client.clone().key("processes");
+        assert_eq!(
+            locate(&processes.clone().index(0).key("path")),
+            Some((9, 7))
+        );
+        assert_eq!(
+            locate(&processes.clone().index(1).key("path")),
+            Some((11, 7))
+        );
+        assert_eq!(
+            locate(&server.clone().key("network_node_id")),
+            Some((13, 5))
+        );
+        let interface = server.clone().key("interfaces").index(0);
+        assert_eq!(locate(&interface.key("network_node_id")), Some((16, 9)));
+
+        // the closest ancestor is found for flow-style collections and missing options
+        let processes = server.clone().key("processes");
+        assert_eq!(locate(&processes.index(0).key("path")), Some((14, 5)));
+        assert_eq!(locate(&client.key("uplink_trace")), Some((6, 3)));
+        assert_eq!(locate(&OptionPath::new(&["network"])), None);
+    }
+}
@@ -0,0 +1,892 @@
+//! Variables and expressions in configuration files.
+//!
+//! Variables are defined in the top-level `variables` mapping of a configuration file, or with the
+//! `--define name=value` command line option, which takes precedence. Environment variables of the
+//! shadow process are only available as variables if they're allowed with `--allow-env name`, so
+//! that a configuration doesn't unexpectedly depend on the environment. Strings in the configuration
+//! (both keys and values) can contain `${...}` expressions, which are replaced by their results. An
+//! expression is a variable name, a number, or arithmetic (`+`, `-`, `*`, `/`, `%`, and
+//! parentheses) on variables and numbers. A string that is only a variable name in `${...}` is
+//! replaced by the variable's value of any type, such as a number or a list. A string that is only
+//! an arithmetic expression is replaced by the resulting number. A literal `${` is written as
+//! `$${`.
+//!
+//! A host with a `quantity` option is replicated that many times, and the `hostidx` variable is the
+//! index (starting at 1) of each replica, so that the replicas can have different options.
+//!
+//! Expressions can also sample random distributions with functions such as `uniform(min, max)` and
+//! `normal(mean, stddev)`. The samples are deterministic for the simulation's seed, and each host
+//! (and each replica) samples from its own random number generator, so that adding or removing a
+//! host doesn't change the samples of the other hosts.
+
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde_yaml::{Mapping, Value};
+
+/// The top-level key that defines the variables.
+const VARIABLES_KEY: &str = "variables";
+
+/// The top-level key that contains the hosts.
+const HOSTS_KEY: &str = "hosts";
+
+/// The host option that replicates a host.
+const QUANTITY_KEY: &str = "quantity";
+
+/// The variable that is the index of a replicated host.
+const HOST_INDEX_VARIABLE: &str = "hostidx";
+
+/// The seed that is used if neither the configuration nor the command line sets one.
+const DEFAULT_SEED: u32 = 1;
+
+/// Remove the variables from the top-level mapping of `config`, and replace the expressions in
+/// `config` using those variables and the `defines`, which override the config's variables. The
+/// random samples use the `seed` from the command line, or otherwise the config's `general.seed`.
+pub fn substitute(
+    config: &mut Value,
+    defines: &[(String, String)],
+    seed: Option<u32>,
+) -> Result<(), String> {
+    let mut variables = BTreeMap::new();
+
+    let seed = seed
+        .or_else(|| {
+            let seed = config.get("general")?.get("seed")?.as_u64()?;
+            u32::try_from(seed).ok()
+        })
+        .unwrap_or(DEFAULT_SEED);
+
+    if let Value::Mapping(mapping) = config {
+        match mapping.remove(VARIABLES_KEY) {
+            None | Some(Value::Null) => {}
+            Some(Value::Mapping(x)) => {
+                for (name, value) in x {
+                    let Value::String(name) = name else {
+                        return Err(format!("Variable name {name:?} is not a string"));
+                    };
+                    variables.insert(name, value);
+                }
+            }
+            Some(x) => {
+                return Err(format!(
+                    "The '{VARIABLES_KEY}' key must be a mapping, not {x:?}"
+                ))
+            }
+        }
+    }
+
+    for (name, value) in defines {
+        // values on the command line have the same types that they would have in the config
+        let value = serde_yaml::from_str(value).unwrap_or_else(|_| Value::String(value.clone()));
+        variables.insert(name.clone(), value);
+    }
+
+    if let Some(name) = variables.keys().find(|x| !is_identifier(x)) {
+        return Err(format!("Invalid variable name '{name}'"));
+    }
+
+    if variables.contains_key(HOST_INDEX_VARIABLE) {
+        return Err(format!(
+            "The variable name '{HOST_INDEX_VARIABLE}' is reserved"
+        ));
+    }
+
+    let mut rng = scoped_rng(seed, "");
+
+    let Value::Mapping(mapping) = config else {
+        return substitute_value(config, &variables, &mut rng);
+    };
+
+    // each replica of a host has its own index, so the hosts are substituted separately
+    let hosts = mapping.remove(HOSTS_KEY);
+    substitute_value(config, &variables, &mut rng)?;
+
+    if let Some(mut hosts) = hosts {
+        match &mut hosts {
+            Value::Mapping(x) => *x = replicate_hosts(std::mem::take(x), &mut variables, seed)?,
+            x => substitute_value(x, &variables, &mut rng)?,
+        }
+        if let Value::Mapping(mapping) = config {
+            mapping.insert(HOSTS_KEY.into(), hosts);
+        }
+    }
+
+    Ok(())
+}
+
+/// Substitute the expressions in the hosts, replacing each host that has a `quantity` with its
+/// replicas. A replica's name is its host's name with the index appended, unless the host's name
+/// already contains the index.
+fn replicate_hosts(
+    hosts: Mapping,
+    variables: &mut BTreeMap<String, Value>,
+    seed: u32,
+) -> Result<Mapping, String> {
+    let mut replicated = Mapping::new();
+    let mut insert = |name: Value, host| {
+        if replicated.contains_key(&name) {
+            return Err(format!("Duplicate host name {name:?}"));
+        }
+        replicated.insert(name, host);
+        Ok(())
+    };
+
+    for (mut name, mut host) in hosts {
+        let quantity = match &mut host {
+            Value::Mapping(x) => x.remove(QUANTITY_KEY),
+            _ => None,
+        };
+
+        // the host's name as written in the config, which is the same for all of its replicas
+        let mut rng = host_rng(seed, &name);
+
+        let Some(mut quantity) = quantity else {
+            substitute_value(&mut name, variables, &mut rng)?;
+            substitute_value(&mut host, variables, &mut rng)?;
+            insert(name, host)?;
+            continue;
+        };
+
+        substitute_value(&mut quantity, variables, &mut rng)?;
+        let quantity = quantity.as_u64().filter(|x| *x > 0).ok_or_else(|| {
+            format!("The '{QUANTITY_KEY}' of host {name:?} must be a positive integer")
+        })?;
+
+        let append_index = replica_name(&name, 1, variables, &mut rng)?
+            == replica_name(&name, 2, variables, &mut rng)?;
+
+        for index in 1..=quantity {
+            let mut replica_name = replica_name(&name, index, variables, &mut rng)?;
+            if append_index {
+                let Value::String(x) = replica_name else {
+                    return Err(format!("Host name {replica_name:?} is not a string"));
+                };
+                replica_name = Value::String(format!("{x}{index}"));
+            }
+
+            // 'replica_name()' has set the index variable
+            let mut replica = host.clone();
+            let mut rng = host_rng(seed, &replica_name);
+            substitute_value(&mut replica, variables, &mut rng)?;
+            insert(replica_name, replica)?;
+        }
+
+        variables.remove(HOST_INDEX_VARIABLE);
+    }
+
+    Ok(replicated)
+}
+
+/// Set the host index variable, and substitute the expressions in the host's name.
+fn replica_name(
+    name: &Value,
+    index: u64,
+    variables: &mut BTreeMap<String, Value>,
+    rng: &mut Xoshiro256PlusPlus,
+) -> Result<Value, String> {
+    variables.insert(HOST_INDEX_VARIABLE.to_string(), index.into());
+    let mut name = name.clone();
+    substitute_value(&mut name, variables, rng)?;
+    Ok(name)
+}
+
+/// A random number generator for the expressions of a host (or of the rest of the configuration
+/// if `scope` is empty), which only depends on the seed and the host's name.
+fn scoped_rng(seed: u32, scope: &str) -> Xoshiro256PlusPlus {
+    let mut hasher = std::hash::DefaultHasher::new();
+    (seed, scope).hash(&mut hasher);
+    Xoshiro256PlusPlus::seed_from_u64(hasher.finish())
+}
+
+fn host_rng(seed: u32, name: &Value) -> Xoshiro256PlusPlus {
+    match name {
+        Value::String(x) => scoped_rng(seed, x),
+        x => scoped_rng(seed, &format!("{x:?}")),
+    }
+}
+
+/// Parse a `name=value` variable definition from the command line.
+pub fn parse_define(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected 'name=value', not '{s}'"))?;
+    if !is_identifier(name) {
+        return Err(format!("Invalid variable name '{name}'"));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Parse the name of an environment variable that the configuration is allowed to use.
+pub fn parse_env_name(s: &str) -> Result<String, String> {
+    if !is_identifier(s) {
+        return Err(format!("Invalid variable name '{s}'"));
+    }
+    Ok(s.to_string())
+}
+
+/// Get the variable definitions for the allowed environment variables that are set. They have the
+/// same format as the definitions from the command line, so they can be passed to [`substitute`].
+pub fn env_defines(names: &[String]) -> Result<Vec<(String, String)>, String> {
+    let mut defines = Vec::new();
+    for name in names {
+        match std::env::var(name) {
+            Ok(value) => defines.push((name.clone(), value)),
+            // the config can provide a default value for variables that aren't set
+            Err(std::env::VarError::NotPresent) => {}
+            Err(e) => return Err(format!("Environment variable '{name}': {e}")),
+        }
+    }
+    Ok(defines)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|x| x.is_ascii_alphabetic() || x == '_')
+        && chars.all(|x| x.is_ascii_alphanumeric() || x == '_')
+}
+
+fn substitute_value(
+    value: &mut Value,
+    variables: &BTreeMap<String, Value>,
+    rng: &mut Xoshiro256PlusPlus,
+) -> Result<(), String> {
+    match value {
+        Value::String(s) => {
+            if let Some(x) = substitute_str(s, variables, rng)? {
+                *value = x;
+            }
+        }
+        Value::Sequence(x) => {
+            for value in x {
+                substitute_value(value, variables, rng)?;
+            }
+        }
+        Value::Mapping(x) => {
+            // keys may also contain expressions, so rebuild the mapping
+            let mut mapping = Mapping::new();
+            for (mut key, mut value) in std::mem::take(x) {
+                substitute_value(&mut key, variables, rng)?;
+                substitute_value(&mut value, variables, rng)?;
+                if mapping.contains_key(&key) {
+                    return Err(format!("Duplicate key {key:?} after substitution"));
+                }
+                mapping.insert(key, value);
+            }
+            *x = mapping;
+        }
+        Value::Tagged(x) => substitute_value(&mut x.value, variables, rng)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+
+    Ok(())
+}
+
+/// Replace the expressions in a string, returning `None` if it has no expressions.
+fn substitute_str(
+    s: &str,
+    variables: &BTreeMap<String, Value>,
+    rng: &mut Xoshiro256PlusPlus,
+) -> Result<Option<Value>, String> {
+    if !s.contains("${") {
+        return Ok(None);
+    }
+
+    // a string that's a single expression keeps the type of its result
+    if let Some(expr) = s.strip_prefix("${").and_then(|x| x.strip_suffix('}')) {
+        if !expr.contains(['{', '}']) {
+            let expr = expr.trim();
+            if is_identifier(expr) {
+                let value = variables
+                    .get(expr)
+                    .ok_or_else(|| format!("Undefined variable '{expr}'"))?;
+                return Ok(Some(value.clone()));
+            }
+            return Ok(Some(evaluate(expr, variables, rng)?.into()));
+        }
+    }
+
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(x) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = x;
+        } else if let Some(x) = rest.strip_prefix("${") {
+            let end = x
+                .find('}')
+                .ok_or_else(|| format!("Unterminated expression in '{s}'"))?;
+            let expr = x[..end].trim();
+            let value = if is_identifier(expr) {
+                let value = variables
+                    .get(expr)
+                    .ok_or_else(|| format!("Undefined variable '{expr}'"))?;
+                Scalar::try_from(value).map_err(|e| format!("Variable '{expr}' {e}"))?
+            } else {
+                evaluate(expr, variables, rng)?
+            };
+            result.push_str(&value.to_string());
+            rest = &x[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(Some(Value::String(result)))
+}
+
+/// The result of an expression. Strings can be interpolated, but not used in arithmetic.
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl TryFrom<&Value> for Scalar {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(x) => Ok(match x.as_i64() {
+                Some(x) => Scalar::Int(x),
+                None => Scalar::Float(x.as_f64().unwrap()),
+            }),
+            Value::String(x) => Ok(Scalar::Str(x.clone())),
+            Value::Bool(x) => Ok(Scalar::Str(x.to_string())),
+            _ => Err("is not a number or a string".into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scalar::Int(x) => write!(f, "{x}"),
+            Scalar::Float(x) => write!(f, "{x}"),
+            Scalar::Str(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+impl From<Scalar> for Value {
+    fn from(x: Scalar) -> Self {
+        match x {
+            Scalar::Int(x) => x.into(),
+            Scalar::Float(x) => x.into(),
+            Scalar::Str(x) => x.into(),
+        }
+    }
+}
+
+/// Evaluate an arithmetic expression.
+fn evaluate(
+    expr: &str,
+    variables: &BTreeMap<String, Value>,
+    rng: &mut Xoshiro256PlusPlus,
+) -> Result<Scalar, String> {
+    let mut parser = Parser {
+        expr,
+        pos: 0,
+        variables,
+        rng,
+    };
+    let value = parser.sum()?;
+    parser.skip_whitespace();
+    if parser.pos != expr.len() {
+        return Err(format!(
+            "Unexpected '{}' in expression '{expr}'",
+            &expr[parser.pos..]
+        ));
+    }
+    Ok(value)
+}
+
+/// A recursive descent parser that evaluates an expression as it's parsed.
+struct Parser<'a> {
+    expr: &'a str,
+    pos: usize,
+    variables: &'a BTreeMap<String, Value>,
+    rng: &'a mut Xoshiro256PlusPlus,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.expr[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume the next character if it's one of `chars`.
+    fn next_op(&mut self, chars: &[char]) -> Option<char> {
+        self.skip_whitespace();
+        let c = self.expr[self.pos..].chars().next()?;
+        if !chars.contains(&c) {
+            return None;
+        }
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn sum(&mut self) -> Result<Scalar, String> {
+        let mut value = self.product()?;
+        while let Some(op) = self.next_op(&['+', '-']) {
+            value = arithmetic(op, value, self.product()?)?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<Scalar, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op(&['*', '/', '%']) {
+            value = arithmetic(op, value, self.unary()?)?;
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<Scalar, String> {
+        if self.next_op(&['-']).is_some() {
+            return arithmetic('-', Scalar::Int(0), self.unary()?);
+        }
+
+        if self.next_op(&['(']).is_some() {
+            let value = self.sum()?;
+            if self.next_op(&[')']).is_none() {
+                return Err(format!("Missing ')' in expression '{}'", self.expr));
+            }
+            return Ok(value);
+        }
+
+        self.skip_whitespace();
+        let rest = &self.expr[self.pos..];
+        let len = rest
+            .find(|x: char| !(x.is_ascii_alphanumeric() || x == '_' || x == '.'))
+            .unwrap_or(rest.len());
+        let token = &rest[..len];
+        self.pos += len;
+
+        if token.is_empty() {
+            return Err(format!("Expected a value in expression '{}'", self.expr));
+        }
+
+        if is_identifier(token) && self.next_op(&['(']).is_some() {
+            let args = self.arguments()?;
+            return call(token, &args, self.rng)
+                .map_err(|e| format!("{e} in expression '{}'", self.expr));
+        }
+
+        if is_identifier(token) {
+            let value = self
+                .variables
+                .get(token)
+                .ok_or_else(|| format!("Undefined variable '{token}'"))?;
+            return Scalar::try_from(value).map_err(|e| format!("Variable '{token}' {e}"));
+        }
+
+        if let Ok(x) = token.parse::<i64>() {
+            return Ok(Scalar::Int(x));
+        }
+
+        token
+            .parse::<f64>()
+            .map(Scalar::Float)
+            .map_err(|_| format!("Invalid number '{token}'"))
+    }
+
+    /// The comma-separated arguments of a function call, after the opening parenthesis.
+    fn arguments(&mut self) -> Result<Vec<Scalar>, String> {
+        let mut args = Vec::new();
+        if self.next_op(&[')']).is_some() {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.sum()?);
+            match self.next_op(&[',', ')']) {
+                Some(',') => {}
+                Some(_) => return Ok(args),
+                None => return Err(format!("Missing ')' in expression '{}'", self.expr)),
+            }
+        }
+    }
+}
+
+/// Call a function. The random distributions sample from `rng`.
+fn call(name: &str, args: &[Scalar], rng: &mut Xoshiro256PlusPlus) -> Result<Scalar, String> {
+    let float = |x: &Scalar| match x {
+        Scalar::Int(x) => Ok(*x as f64),
+        Scalar::Float(x) => Ok(*x),
+        Scalar::Str(x) => Err(format!("Can't use the string '{x}' as a number")),
+    };
+    let arity = |n: usize| {
+        if args.len() != n {
+            return Err(format!(
+                "Function '{name}' takes {n} arguments, not {}",
+                args.len()
+            ));
+        }
+        Ok(())
+    };
+
+    // a sample from the standard normal distribution (Box-Muller transform)
+    let standard_normal = |rng: &mut Xoshiro256PlusPlus| {
+        let u1 = 1.0 - rng.gen::<f64>();
+        let u2 = rng.gen::<f64>();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    };
+
+    let result = match name {
+        "uniform" => {
+            arity(2)?;
+            if let [Scalar::Int(min), Scalar::Int(max)] = args {
+                if min > max {
+                    return Err(format!("Invalid range {min} to {max} of '{name}'"));
+                }
+                return Ok(Scalar::Int(rng.gen_range(*min..=*max)));
+            }
+            let (min, max) = (float(&args[0])?, float(&args[1])?);
+            if min.is_nan() || max.is_nan() || min > max {
+                return Err(format!("Invalid range {min} to {max} of '{name}'"));
+            }
+            min + (max - min) * rng.gen::<f64>()
+        }
+        "normal" | "lognormal" => {
+            arity(2)?;
+            let (mean, stddev) = (float(&args[0])?, float(&args[1])?);
+            if stddev.is_nan() || stddev < 0.0 {
+                return Err(format!("Invalid standard deviation {stddev} of '{name}'"));
+            }
+            let x = mean + stddev * standard_normal(rng);
+            if name == "lognormal" {
+                x.exp()
+            } else {
+                x
+            }
+        }
+        "exponential" => {
+            arity(1)?;
+            let mean = float(&args[0])?;
+            if mean.is_nan() || mean <= 0.0 {
+                return Err(format!("Invalid mean {mean} of '{name}'"));
+            }
+            -mean * (1.0 - rng.gen::<f64>()).ln()
+        }
+        "round" => {
+            arity(1)?;
+            let x = float(&args[0])?.round();
+            // the float is saturated to the range of an i64
+            if x.is_nan() || x.abs() >= i64::MAX as f64 {
+                return Err(format!("Can't round {x} to an integer"));
+            }
+            return Ok(Scalar::Int(x as i64));
+        }
+        "min" | "max" => {
+            arity(2)?;
+            let (a, b) = (float(&args[0])?, float(&args[1])?);
+            // keep the type of the chosen argument
+            let first = if name == "min" { a <= b } else { a >= b };
+            return Ok(if first {
+                args[0].clone()
+            } else {
+                args[1].clone()
+            });
+        }
+        _ => return Err(format!("Unknown function '{name}'")),
+    };
+
+    if !result.is_finite() {
+        return Err(format!("'{name}' resulted in {result}"));
+    }
+    Ok(Scalar::Float(result))
+}
+
+/// Apply an arithmetic operator. Integer operations stay integers, and other numbers are floats.
+fn arithmetic(op: char, a: Scalar, b: Scalar) -> Result<Scalar, String> {
+    match (a, b) {
+        (Scalar::Int(a), Scalar::Int(b)) => {
+            let result = match op {
+                '+' => a.checked_add(b),
+                '-' => a.checked_sub(b),
+                '*' => a.checked_mul(b),
+                '/' => a.checked_div(b),
+                '%' => a.checked_rem(b),
+                _ => unreachable!(),
+            };
+            result
+                .map(Scalar::Int)
+                .ok_or_else(|| format!("Invalid arithmetic '{a} {op} {b}'"))
+        }
+        (Scalar::Str(x), _) | (_, Scalar::Str(x)) => {
+            Err(format!("Can't use the string '{x}' in arithmetic"))
+        }
+        (a, b) => {
+            let as_float = |x| match x {
+                Scalar::Int(x) => x as f64,
+                Scalar::Float(x) => x,
+                Scalar::Str(_) => unreachable!(),
+            };
+            let (a, b) = (as_float(a), as_float(b));
+            let result = match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' => a / b,
+                '%' => a % b,
+                _ => unreachable!(),
+            };
+            if !result.is_finite() {
+                return Err(format!("Invalid arithmetic '{a} {op} {b}'"));
+            }
+            Ok(Scalar::Float(result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    fn rng() -> Xoshiro256PlusPlus {
+        Xoshiro256PlusPlus::seed_from_u64(0)
+    }
+
+    fn substituted(s: &str, defines: &[(&str, &str)]) -> Result<Value, String> {
+        let defines: Vec<_> = defines
+            .iter()
+            .map(|(x, y)| (x.to_string(), y.to_string()))
+            .collect();
+        let mut config = yaml(s);
+        substitute(&mut config, &defines, None)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_substitute() {
+        let config = substituted(
+            "
+            variables: {node: 3, stop: 60, bw: 10, unit: Mbit, args: [-v, -q]}
+            general: {stop_time: '${stop}', heartbeat_interval: '${stop / 4} s'}
+            hosts:
+              client${node}:
+                network_node_id: ${node}
+                bandwidth_down: ${bw * 2} ${unit}
+                bandwidth_up: '${bw / 4}'
+                args: ${args}
+                path: $${HOME}/bin
+            ",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            yaml(
+                "
+                general: {stop_time: 60, heartbeat_interval: 15 s}
+                hosts:
+                  client3:
+                    network_node_id: 3
+                    bandwidth_down: 20 Mbit
+                    bandwidth_up: 2
+                    args: [-v, -q]
+                    path: ${HOME}/bin
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn test_defines() {
+        let config = substituted(
+            "{variables: {a: 1, b: x}, x: '${a}', y: '${b}', z: '${c + 1}'}",
+            &[("a", "2.5"), ("c", "4")],
+        )
+        .unwrap();
+        assert_eq!(config, yaml("{x: 2.5, y: x, z: 5}"));
+
+        assert_eq!(parse_define("a=b=c"), Ok(("a".into(), "b=c".into())));
+        assert!(parse_define("a").is_err());
+        assert!(parse_define("1a=b").is_err());
+    }
+
+    #[test]
+    fn test_env_defines() {
+        std::env::set_var("SHADOW_TEST_ENV_DEFINE", "8080");
+        let names = ["SHADOW_TEST_ENV_DEFINE", "SHADOW_TEST_ENV_UNSET"].map(String::from);
+        assert_eq!(
+            env_defines(&names),
+            Ok(vec![("SHADOW_TEST_ENV_DEFINE".into(), "8080".into())])
+        );
+
+        assert!(parse_env_name("A_1").is_ok());
+        assert!(parse_env_name("A-B").is_err());
+    }
+
+    #[test]
+    fn test_replicate() {
+        let config = substituted(
+            "
+            variables: {n: 2}
+            hosts:
+              relay:
+                quantity: ${n}
+                ip_addr: 11.0.0.${hostidx}
+                processes: [{path: relay, args: [--id, '${hostidx}'], environment: {ID: r${hostidx}}}]
+              'client${hostidx + 10}': {quantity: 2, network_node_id: '${hostidx - 1}'}
+              server: {network_node_id: '${n}'}
+            ",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            yaml(
+                "
+                hosts:
+                  relay1:
+                    ip_addr: 11.0.0.1
+                    processes: [{path: relay, args: [--id, 1], environment: {ID: r1}}]
+                  relay2:
+                    ip_addr: 11.0.0.2
+                    processes: [{path: relay, args: [--id, 2], environment: {ID: r2}}]
+                  client11: {network_node_id: 0}
+                  client12: {network_node_id: 1}
+                  server: {network_node_id: 2}
+                "
+            )
+        );
+
+        assert!(substituted("{hosts: {a: {quantity: 0}}}", &[]).is_err());
+        assert!(substituted("{hosts: {a: {quantity: x}}}", &[]).is_err());
+        assert!(substituted("{hosts: {a: {quantity: 2}, a1: {}}}", &[]).is_err());
+        assert!(substituted("{hosts: {a: {ip_addr: '${hostidx}'}}}", &[]).is_err());
+        assert!(substituted("{variables: {hostidx: 1}, x: 1}", &[]).is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let variables =
+            BTreeMap::from([("x".to_string(), yaml("10")), ("s".to_string(), yaml("a"))]);
+        let eval = |expr: &str| evaluate(expr, &variables, &mut rng());
+
+        assert_eq!(eval("1 + 2 * 3"), Ok(Scalar::Int(7)));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(Scalar::Int(9)));
+        assert_eq!(eval("x / 4"), Ok(Scalar::Int(2)));
+        assert_eq!(eval("x / 4.0"), Ok(Scalar::Float(2.5)));
+        assert_eq!(eval("-x % 3"), Ok(Scalar::Int(-1)));
+        assert_eq!(eval("x - -1"), Ok(Scalar::Int(11)));
+
+        for invalid in [
+            "", "1 +", "(1", "1)", "y", "s * 2", "x / 0", "1 $ 2", "1.2.3",
+        ] {
+            assert!(eval(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_distributions() {
+        let variables = BTreeMap::new();
+        let mut rng = rng();
+        let mut eval = |expr: &str| evaluate(expr, &variables, &mut rng);
+
+        for _ in 0..100 {
+            let Ok(Scalar::Int(x)) = eval("uniform(1, 3)") else {
+                panic!();
+            };
+            assert!((1..=3).contains(&x));
+            let Ok(Scalar::Float(x)) = eval("uniform(1, 3.0)") else {
+                panic!();
+            };
+            assert!((1.0..3.0).contains(&x));
+            let Ok(Scalar::Float(x)) = eval("exponential(2) + lognormal(0, 1)") else {
+                panic!();
+            };
+            assert!(x > 0.0);
+            let Ok(Scalar::Int(x)) = eval("max(1, round(normal(10, 5)))") else {
+                panic!();
+            };
+            assert!(x >= 1);
+        }
+
+        assert_eq!(eval("normal(7, 0)"), Ok(Scalar::Float(7.0)));
+        assert_eq!(
+            eval("round(2.5) + min(1, 2.0) + max(3, 1)"),
+            Ok(Scalar::Int(7))
+        );
+        assert_eq!(eval("uniform(4, 4)"), Ok(Scalar::Int(4)));
+
+        for invalid in [
+            "foo(1)",
+            "uniform(1)",
+            "uniform(2, 1)",
+            "normal(0, -1)",
+            "exponential(0)",
+            "round(1e300)",
+            "min(1, s)",
+            "round(1",
+            "round(1 2)",
+        ] {
+            assert!(eval(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_sample_hosts() {
+        let config = "
+            hosts:
+              client:
+                quantity: 3
+                bandwidth_down: ${round(uniform(1, 1000))} Mbit
+              server:
+                bandwidth_down: ${uniform(1, 1000000)}
+            ";
+        let sampled = |config: &str| {
+            let Value::Mapping(hosts) = substituted(config, &[]).unwrap()["hosts"].clone() else {
+                panic!();
+            };
+            hosts
+        };
+
+        // the samples are deterministic
+        let hosts = sampled(config);
+        assert_eq!(hosts, sampled(config));
+        assert_eq!(hosts.len(), 4);
+
+        // the replicas have their own samples
+        let bandwidths: Vec<_> = ["client1", "client2", "client3"]
+            .map(|x| hosts[x]["bandwidth_down"].clone())
+            .to_vec();
+        assert!(bandwidths.iter().any(|x| *x != bandwidths[0]));
+
+        // another host doesn't change the samples of the existing hosts
+        let more_hosts = sampled(&format!("{config}  other: {{x: '${{uniform(1, 10)}}'}}"));
+        assert_eq!(more_hosts["server"], hosts["server"]);
+        assert_eq!(more_hosts["client2"], hosts["client2"]);
+
+        // the seed changes the samples
+        let seeded = sampled(&format!("{config}general: {{seed: 2}}"));
+        assert_ne!(seeded["server"], hosts["server"]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(substituted("{x: '${y}'}", &[]).is_err());
+        assert!(substituted("{x: 'a ${y'}", &[]).is_err());
+        assert!(substituted("{variables: [a], x: 1}", &[]).is_err());
+        assert!(substituted("{variables: {a-b: 1}, x: 1}", &[]).is_err());
+        assert!(substituted("{variables: {a: 1}, host1: 1, 'host${a}': 2}", &[]).is_err());
+        // lists can't be interpolated into strings
+        assert!(substituted("{variables: {a: [1]}, x: 'b ${a}'}", &[]).is_err());
+    }
+}
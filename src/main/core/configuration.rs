@@ -24,6 +24,10 @@ use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
+use crate::core::config_include::ConfigFormat;
+use crate::core::config_override::{self, ConfigOverride};
+use crate::core::config_variables;
+use crate::core::sweep::{self, SweepParameter};
 use crate::cshadow as c;
 use crate::host::syscall::formatter::FmtOptions;
 use crate::utility::units::{self, Unit};
@@ -48,11 +52,23 @@ static VERSION: Lazy<String> = Lazy::new(crate::shadow::version);
 // clap only shows the possible values for bool options (unless we add support for the other
 // non-bool options in the future), which isn't very helpful
 #[clap(hide_possible_values = true)]
+// the configuration file isn't required when running a subcommand
+#[clap(subcommand_negates_reqs = true)]
 pub struct CliOptions {
     /// Path to the Shadow configuration file. Use '-' to read from stdin
-    #[clap(required_unless_present_any(&["show_build_info", "shm_cleanup"]))]
+    #[clap(required_unless_present_any(&[
+        "show_build_info",
+        "shm_cleanup",
+        "resume",
+    ]))]
     pub config: Option<String>,
 
+    /// The format of the configuration file ('yaml', 'json', or 'toml'). By default the format is
+    /// detected from the file's extension, and files without a '.json' or '.toml' extension are
+    /// read as YAML
+    #[clap(long, value_name = "format")]
+    pub config_format: Option<ConfigFormat>,
+
     /// Pause to allow gdb to attach
     #[clap(long, short = 'g')]
     pub gdb: bool,
@@ -70,10 +86,38 @@ pub struct CliOptions {
     #[clap(long, exclusive(true))]
     pub show_build_info: bool,
 
+    /// Define a variable that can be used in the configuration file, overriding the variable's
+    /// value in the file
+    #[clap(long = "define", short = 'D', value_name = "name=value")]
+    #[clap(value_parser = config_variables::parse_define)]
+    pub defines: Vec<(String, String)>,
+
+    /// Allow the configuration file to use the value of an environment variable as a variable with
+    /// the same name. Variables defined with '--define' take precedence
+    #[clap(long = "allow-env", value_name = "name")]
+    #[clap(value_parser = config_variables::parse_env_name)]
+    pub allowed_env: Vec<String>,
+
+    /// Set an option of the configuration file, where the option is a path such as
+    /// 'hosts.client.network_node_id'
+    #[clap(long = "set", value_name = "option=value")]
+    #[clap(value_parser = config_override::parse_override)]
+    pub overrides: Vec<ConfigOverride>,
+
     /// Exit after printing the final configuration
     #[clap(long)]
     pub show_config: bool,
 
+    /// Write a JSON description of the failure to this file if Shadow doesn't complete
+    /// successfully
+    #[clap(long, value_name = "path")]
+    pub failure_file: Option<std::path::PathBuf>,
+
+    /// Resume a simulation from the checkpoint in this directory, which was written because of the
+    /// 'general.checkpoint_time' option. Requires CRIU
+    #[clap(long, value_name = "path")]
+    pub resume: Option<std::path::PathBuf>,
+
     #[clap(flatten)]
     pub general: GeneralOptions,
 
@@ -85,6 +129,92 @@ pub struct CliOptions {
 
     #[clap(flatten)]
     pub experimental: ExperimentalOptions,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands that Shadow runs instead of a simulation.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    /// Check that Shadow works on this machine by running a small network simulation, and the
+    /// Shadow test programs in the given directory if any
+    Selftest {
+        /// A directory of Shadow test programs, such as 'target/debug' in Shadow's source tree
+        #[clap(value_name = "test-dir")]
+        test_dir: Option<std::path::PathBuf>,
+    },
+
+    /// Run the self-test's traffic generator (used internally by 'selftest')
+    #[clap(hide = true)]
+    SelftestTraffic { role: String },
+
+    /// Check the configuration and print its errors and warnings as JSON, without running the
+    /// simulation
+    Validate {
+        /// Path to the Shadow configuration file. Use '-' to read from stdin
+        config: String,
+    },
+
+    /// Print the configuration file as YAML, with its included files, variables, and command line
+    /// options applied
+    PrintConfig {
+        /// Print the final configuration instead, which also has the defaults and replicated hosts
+        /// resolved
+        #[clap(long)]
+        resolved: bool,
+
+        /// Path to the Shadow configuration file. Use '-' to read from stdin
+        config: String,
+    },
+
+    /// Run the simulation once for every combination of the variable values. Each run uses its
+    /// own directory in the data directory
+    Sweep {
+        /// A variable and its values, where the values are a comma-separated list ('1,5,10') or
+        /// an inclusive integer range ('1..10' or '0..100..10')
+        #[clap(
+            long = "param",
+            short = 'p',
+            value_name = "name=values",
+            required = true
+        )]
+        #[clap(value_parser = sweep::parse_parameter)]
+        parameters: Vec<SweepParameter>,
+
+        /// The maximum number of simulations to run at the same time
+        #[clap(long, short = 'j', value_name = "N", default_value_t = 1)]
+        #[clap(value_parser = clap::value_parser!(u32).range(1..))]
+        jobs: u32,
+
+        /// Path to the Shadow configuration file
+        config: String,
+    },
+
+    /// Print the configuration file with its deprecated options converted to the current format,
+    /// and report the deprecated options that couldn't be converted
+    ConvertConfig {
+        /// Path to the Shadow configuration file. Use '-' to read from stdin
+        config: String,
+    },
+
+    /// Work with network graph topologies
+    Topology {
+        #[clap(subcommand)]
+        command: TopologyCommand,
+    },
+}
+
+/// The subcommands of the 'topology' command.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum TopologyCommand {
+    /// Print a synthetic network graph in the GML format, generated from the given topology
+    /// options
+    Generate {
+        /// The topology options as YAML (for example '{type: star, nodes: 10}')
+        #[clap(value_name = "topology")]
+        topology: TopologyOptions,
+    },
 }
 
 /// Options contained in a configuration file.
@@ -206,12 +336,20 @@ pub struct GeneralOptions {
     #[serde(default = "default_some_1")]
     pub seed: Option<u32>,
 
-    /// How many parallel threads to use to run the simulation. A value of 0 will allow Shadow to
-    /// choose the number of threads.
+    /// How many parallel threads to use to run the simulation. A value of "auto" (or 0) will
+    /// choose the number of threads from the available CPU cores and the cgroup's CPU quota.
     #[clap(long, short = 'p', value_name = "cores")]
     #[clap(help = GENERAL_HELP.get("parallelism").unwrap().as_str())]
-    #[serde(default = "default_some_0")]
-    pub parallelism: Option<u32>,
+    #[serde(default = "default_some_auto")]
+    pub parallelism: Option<AutoOr<u32>>,
+
+    /// Warn when the available memory falls below this amount. A value of "auto" will choose the
+    /// amount from the system's memory and the cgroup's memory limit
+    #[clap(long, value_name = "bytes")]
+    #[clap(help = GENERAL_HELP.get("memory_warning_threshold").unwrap().as_str())]
+    #[serde(default = "default_some_nullable_auto")]
+    pub memory_warning_threshold:
+        Option<NullableOption<AutoOr<units::Bytes<units::SiPrefixUpper>>>>,
 
     /// The simulated time that ends Shadow's high network bandwidth/reliability bootstrap period
     #[clap(long, value_name = "seconds")]
@@ -226,6 +364,13 @@ pub struct GeneralOptions {
     #[serde(default = "default_some_info")]
     pub log_level: Option<LogLevel>,
 
+    /// Also log messages above the log level that match this filter expression, such as
+    /// "host=relay3 && module=tcp && time>300s"
+    #[clap(long, alias = "debug", value_name = "filter")]
+    #[clap(help = GENERAL_HELP.get("log_filter").unwrap().as_str())]
+    #[serde(default)]
+    pub log_filter: Option<NullableOption<String>>,
+
     /// Interval at which to print heartbeat messages
     #[clap(long, value_name = "seconds")]
     #[clap(help = GENERAL_HELP.get("heartbeat_interval").unwrap().as_str())]
@@ -250,14 +395,86 @@ pub struct GeneralOptions {
     #[serde(default = "default_some_false")]
     pub progress: Option<bool>,
 
+    /// Show a live dashboard of the simulation's progress, event rates, busiest hosts, and memory
+    /// usage at the bottom of the terminal
+    #[clap(long, value_name = "bool")]
+    #[clap(help = GENERAL_HELP.get("dashboard").unwrap().as_str())]
+    #[serde(default = "default_some_false")]
+    pub dashboard: Option<bool>,
+
+    /// Create a UNIX socket at this path that can pause, resume, inspect, and stop the simulation
+    /// while it's running
+    #[clap(long, value_name = "path")]
+    #[clap(help = GENERAL_HELP.get("control_socket").unwrap().as_str())]
+    #[serde(default)]
+    pub control_socket: Option<NullableOption<String>>,
+
+    /// Write a checkpoint of the simulation to `checkpoint_path` at this simulated time, which can
+    /// be resumed with the '--resume' command line option. Requires CRIU
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = GENERAL_HELP.get("checkpoint_time").unwrap().as_str())]
+    #[serde(default)]
+    pub checkpoint_time: Option<NullableOption<units::Time<units::TimePrefix>>>,
+
+    /// The directory to write the checkpoint to, which must not already exist
+    #[clap(long, value_name = "path")]
+    #[clap(help = GENERAL_HELP.get("checkpoint_path").unwrap().as_str())]
+    #[serde(default = "default_checkpoint_path")]
+    pub checkpoint_path: Option<String>,
+
+    /// The addresses ("host:port") of the managers that the hosts are partitioned across, when
+    /// the simulation is distributed across several Shadow processes that may run on different
+    /// machines. Each manager is run with the same configuration and a different `manager_index`
+    #[clap(skip)]
+    #[serde(default)]
+    pub managers: Option<Vec<String>>,
+
+    /// The index in `managers` of this Shadow process's manager
+    #[clap(long, value_name = "index")]
+    #[clap(help = GENERAL_HELP.get("manager_index").unwrap().as_str())]
+    #[serde(default = "default_some_0")]
+    pub manager_index: Option<u32>,
+
+    /// Maximum total size of the stdout, stderr, and pcap files of all hosts, after which the
+    /// simulation fails
+    #[clap(long, value_name = "bytes")]
+    #[clap(help = GENERAL_HELP.get("output_max_total_size").unwrap().as_str())]
+    #[serde(default)]
+    pub output_max_total_size: Option<NullableOption<units::Bytes<units::SiPrefixUpper>>>,
+
+    /// Generate a certificate authority and a TLS certificate for each host that are valid at the
+    /// simulated time, and trust the certificate authority in each host's processes
+    #[clap(long, value_name = "bool")]
+    #[clap(help = GENERAL_HELP.get("tls_certificates").unwrap().as_str())]
+    #[serde(default = "default_some_false")]
+    pub tls_certificates: Option<bool>,
+
     /// Model syscalls and VDSO functions that don't block as having some
     /// latency. This should have minimal effect on typical simulations, but
     /// can be helpful for programs with "busy loops" that otherwise deadlock
-    /// under Shadow.
+    /// under Shadow. The modeled latency is also the CPU time measured by the
+    /// `ITIMER_VIRTUAL` and `ITIMER_PROF` interval timers.
     #[clap(long, value_name = "bool")]
     #[clap(help = GENERAL_HELP.get("model_unblocked_syscall_latency").unwrap().as_str())]
     #[serde(default = "default_some_false")]
     pub model_unblocked_syscall_latency: Option<bool>,
+
+    /// Named phases of the simulation. Each phase lasts from its start time until the start time
+    /// of the next phase. Log messages and simulation statistics are tagged with the active phase.
+    #[clap(skip)]
+    #[serde(default)]
+    pub phases: Option<Vec<PhaseOptions>>,
+
+    /// External time-series databases that simulation metrics are streamed to while the
+    /// simulation is running
+    #[clap(skip)]
+    #[serde(default)]
+    pub stats_sinks: Option<Vec<StatsSinkOptions>>,
+
+    /// Conditions that stop the simulation before the stop time, when any of them is met
+    #[clap(skip)]
+    #[serde(default)]
+    pub stop_conditions: Option<Vec<StopCondition>>,
 }
 
 impl GeneralOptions {
@@ -284,12 +501,76 @@ pub struct NetworkOptions {
     #[clap(skip)]
     pub graph: Option<GraphOptions>,
 
+    /// Which hosts receive packets sent to a multicast group that they've joined: only hosts
+    /// attached to the same graph node as the sender ("node"), or hosts anywhere in the graph
+    /// ("graph")
+    #[serde(default = "default_some_multicast_node")]
+    #[clap(long, value_name = "scope")]
+    #[clap(help = NETWORK_HELP.get("multicast_scope").unwrap().as_str())]
+    pub multicast_scope: Option<MulticastScope>,
+
     /// When routing packets, follow the shortest path rather than following a direct
     /// edge between nodes. If false, the network graph is required to be complete.
     #[serde(default = "default_some_true")]
     #[clap(long, value_name = "bool")]
     #[clap(help = NETWORK_HELP.get("use_shortest_path").unwrap().as_str())]
     pub use_shortest_path: Option<bool>,
+
+    /// How the shortest paths between graph nodes are chosen: the lowest latency ("latency"), the
+    /// fewest edges ("hop_count"), the highest bandwidth ("bandwidth"), static routes loaded from a
+    /// file ("static"), or the routing policies of autonomous systems ("gao_rexford"). Requires
+    /// `use_shortest_path`.
+    #[serde(default = "default_some_routing_latency")]
+    #[clap(long, value_name = "routing")]
+    #[clap(help = NETWORK_HELP.get("routing").unwrap().as_str())]
+    pub routing: Option<RoutingOptions>,
+
+    /// How packets are spread over the equal-cost shortest paths between graph nodes: always
+    /// follow the same path ("off"), hash each flow onto one of the paths ("flow"), or choose a
+    /// random path for each packet ("packet")
+    #[serde(default = "default_some_ecmp_off")]
+    #[clap(long, value_name = "mode")]
+    #[clap(help = NETWORK_HELP.get("ecmp").unwrap().as_str())]
+    pub ecmp: Option<EcmpMode>,
+
+    /// The maximum number of equal-cost paths between two graph nodes that packets are spread
+    /// over when `ecmp` is enabled
+    #[serde(default = "default_some_8")]
+    #[clap(long, value_name = "N")]
+    #[clap(help = NETWORK_HELP.get("ecmp_max_paths").unwrap().as_str())]
+    pub ecmp_max_paths: Option<u32>,
+
+    /// Model the bandwidth of graph edges that have a 'bandwidth' attribute, so that packets
+    /// crossing the same edge queue behind each other
+    #[serde(default = "default_some_false")]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = NETWORK_HELP.get("use_link_contention").unwrap().as_str())]
+    pub use_link_contention: Option<bool>,
+
+    /// Compute the paths from a graph node when they're first needed, and cache the paths from at
+    /// most this many nodes. If 0, the paths between every pair of nodes are computed when the
+    /// simulation starts
+    #[serde(default = "default_some_0")]
+    #[clap(long, value_name = "N")]
+    #[clap(help = NETWORK_HELP.get("path_cache_size").unwrap().as_str())]
+    pub path_cache_size: Option<u32>,
+
+    /// A built-in authoritative DNS server that answers the queries of every host
+    #[clap(skip)]
+    #[serde(default)]
+    pub dns_server: Option<DnsServerOptions>,
+
+    /// Graph edges that go down or come back up at given simulated times. Paths are recomputed
+    /// around the edges that are down, and packets crossing an edge when it goes down are dropped.
+    #[clap(skip)]
+    #[serde(default)]
+    pub link_events: Option<Vec<LinkEventOptions>>,
+
+    /// Traces of the latency, bandwidth, and packet loss of graph edges, which change the edges'
+    /// attributes during the simulation. Paths are recomputed each time a trace changes an edge.
+    #[clap(skip)]
+    #[serde(default)]
+    pub link_traces: Option<Vec<LinkTraceOptions>>,
 }
 
 impl NetworkOptions {
@@ -371,6 +652,14 @@ pub struct ExperimentalOptions {
     #[clap(help = EXP_HELP.get("use_worker_spinning").unwrap().as_str())]
     pub use_worker_spinning: Option<bool>,
 
+    /// Assign hosts to the worker threads on each NUMA node, and allocate the hosts' memory from
+    /// the node of the threads that run them. This is ignored if not using CPU pinning and the
+    /// thread-per-core scheduler.
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = EXP_HELP.get("use_numa_placement").unwrap().as_str())]
+    pub use_numa_placement: Option<bool>,
+
     /// If set, overrides the automatically calculated minimum time workers may run ahead when sending events between nodes
     #[clap(hide_short_help = true)]
     #[clap(long, value_name = "seconds")]
@@ -483,6 +772,28 @@ pub struct ExperimentalOptions {
     #[clap(long, value_name = "bool")]
     #[clap(help = EXP_HELP.get("use_new_tcp").unwrap().as_str())]
     pub use_new_tcp: Option<bool>,
+
+    /// Offer selective acknowledgments (SACK) when opening TCP connections. SACK is only used on a
+    /// connection if both sides offer it. Only supported by the legacy (C) TCP implementation.
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = EXP_HELP.get("use_tcp_sack").unwrap().as_str())]
+    pub use_tcp_sack: Option<bool>,
+
+    /// Request explicit congestion notification (ECN) when opening TCP connections. ECN is only
+    /// used on a connection if both sides enable it. Only supported by the legacy (C) TCP
+    /// implementation.
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = EXP_HELP.get("use_tcp_ecn").unwrap().as_str())]
+    pub use_tcp_ecn: Option<bool>,
+
+    /// When a host's inbound router queue is congested, mark packets from ECN-capable transports
+    /// with "congestion experienced" instead of dropping them.
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = EXP_HELP.get("router_ecn_marking").unwrap().as_str())]
+    pub router_ecn_marking: Option<bool>,
 }
 
 impl ExperimentalOptions {
@@ -513,6 +824,7 @@ impl Default for ExperimentalOptions {
             use_memory_manager: Some(false),
             use_cpu_pinning: Some(true),
             use_worker_spinning: Some(true),
+            use_numa_placement: Some(false),
             runahead: Some(NullableOption::Value(units::Time::new(
                 1,
                 units::TimePrefix::Milli,
@@ -533,6 +845,9 @@ impl Default for ExperimentalOptions {
             scheduler: Some(Scheduler::ThreadPerCore),
             log_errors_to_tty: Some(true),
             use_new_tcp: Some(false),
+            use_tcp_sack: Some(true),
+            use_tcp_ecn: Some(false),
+            router_ecn_marking: Some(false),
         }
     }
 }
@@ -564,6 +879,82 @@ pub struct HostDefaultOptions {
     #[clap(long, value_name = "bytes")]
     #[clap(help = HOST_HELP.get("pcap_capture_size").unwrap().as_str())]
     pub pcap_capture_size: Option<units::Bytes<units::SiPrefixUpper>>,
+
+    /// Filter expression for the packets to capture if pcap logging is enabled
+    #[clap(long, value_name = "filter")]
+    #[clap(help = HOST_HELP.get("pcap_filter").unwrap().as_str())]
+    pub pcap_filter: Option<NullableOption<String>>,
+
+    /// Maximum size of each pcap file if pcap logging is enabled
+    #[clap(long, value_name = "bytes")]
+    #[clap(help = HOST_HELP.get("pcap_max_size").unwrap().as_str())]
+    pub pcap_max_size: Option<NullableOption<units::Bytes<units::SiPrefixUpper>>>,
+
+    /// Maximum size of each process's stdout and stderr files
+    #[clap(long, value_name = "bytes")]
+    #[clap(help = HOST_HELP.get("output_max_size").unwrap().as_str())]
+    pub output_max_size: Option<NullableOption<units::Bytes<units::SiPrefixUpper>>>,
+
+    /// What to do when a stdout, stderr, or pcap file reaches its maximum size
+    #[clap(long, value_name = "action")]
+    #[clap(help = HOST_HELP.get("output_limit_action").unwrap().as_str())]
+    pub output_limit_action: Option<OutputLimitAction>,
+
+    /// Minimum, initial, and maximum sizes of a TCP socket's receive buffer
+    #[clap(long, value_name = "sizes")]
+    #[clap(help = HOST_HELP.get("tcp_rmem").unwrap().as_str())]
+    pub tcp_rmem: Option<NullableOption<TcpMemLimits>>,
+
+    /// Minimum, initial, and maximum sizes of a TCP socket's send buffer
+    #[clap(long, value_name = "sizes")]
+    #[clap(help = HOST_HELP.get("tcp_wmem").unwrap().as_str())]
+    pub tcp_wmem: Option<NullableOption<TcpMemLimits>>,
+
+    /// Initial congestion window of a TCP connection, in packets
+    #[clap(long, value_name = "packets")]
+    #[clap(help = HOST_HELP.get("tcp_init_cwnd").unwrap().as_str())]
+    pub tcp_init_cwnd: Option<u32>,
+
+    /// Initial retransmission timeout of a TCP connection
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = HOST_HELP.get("tcp_rto_init").unwrap().as_str())]
+    pub tcp_rto_init: Option<units::Time<units::TimePrefix>>,
+
+    /// Minimum retransmission timeout of a TCP connection
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = HOST_HELP.get("tcp_rto_min").unwrap().as_str())]
+    pub tcp_rto_min: Option<units::Time<units::TimePrefix>>,
+
+    /// Maximum retransmission timeout of a TCP connection
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = HOST_HELP.get("tcp_rto_max").unwrap().as_str())]
+    pub tcp_rto_max: Option<units::Time<units::TimePrefix>>,
+
+    /// Maximum segment lifetime (MSL) of TCP connections, which stay in the TIME_WAIT state for
+    /// twice this time
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = HOST_HELP.get("tcp_msl").unwrap().as_str())]
+    pub tcp_msl: Option<units::Time<units::TimePrefix>>,
+
+    /// Time that a TCP connection waits before sending a delayed ACK
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = HOST_HELP.get("tcp_delayed_ack_timeout").unwrap().as_str())]
+    pub tcp_delayed_ack_timeout: Option<units::Time<units::TimePrefix>>,
+
+    /// Queuing discipline for the packets sent by the host's internet interface
+    #[clap(long, value_name = "qdisc")]
+    #[clap(help = HOST_HELP.get("egress_qdisc").unwrap().as_str())]
+    pub egress_qdisc: Option<NullableOption<EgressQdisc>>,
+
+    /// Queue for the packets received by the host's router
+    #[clap(long, value_name = "queue")]
+    #[clap(help = HOST_HELP.get("router_queue").unwrap().as_str())]
+    pub router_queue: Option<RouterQueue>,
+
+    /// Firewall rules for the packets sent and received by the host's internet interface
+    #[clap(long, value_name = "firewall")]
+    #[clap(help = HOST_HELP.get("firewall").unwrap().as_str())]
+    pub firewall: Option<NullableOption<FirewallOptions>>,
 }
 
 impl HostDefaultOptions {
@@ -575,6 +966,23 @@ impl HostDefaultOptions {
             // capture all the data available from the packet". The maximum length of an IP packet
             // (including the header) is 65535 bytes.
             pcap_capture_size: Some(units::Bytes::new(65535, units::SiPrefixUpper::Base)),
+            pcap_filter: None,
+            pcap_max_size: None,
+            output_max_size: None,
+            output_limit_action: Some(OutputLimitAction::Truncate),
+            tcp_rmem: None,
+            tcp_wmem: None,
+            // the defaults of linux's TCP_INIT_CWND, TCP_TIMEOUT_INIT, TCP_RTO_MIN, and TCP_RTO_MAX
+            tcp_init_cwnd: Some(10),
+            tcp_rto_init: Some(units::Time::new(1, units::TimePrefix::Sec)),
+            tcp_rto_min: Some(units::Time::new(200, units::TimePrefix::Milli)),
+            tcp_rto_max: Some(units::Time::new(120, units::TimePrefix::Sec)),
+            // connections stay in TIME_WAIT for 60 seconds, like linux's TCP_TIMEWAIT_LEN
+            tcp_msl: Some(units::Time::new(30, units::TimePrefix::Sec)),
+            tcp_delayed_ack_timeout: Some(units::Time::new(5, units::TimePrefix::Milli)),
+            egress_qdisc: None,
+            router_queue: Some(RouterQueue::Codel),
+            firewall: None,
         }
     }
 
@@ -597,6 +1005,21 @@ impl Default for HostDefaultOptions {
             log_level: None,
             pcap_enabled: None,
             pcap_capture_size: None,
+            pcap_filter: None,
+            pcap_max_size: None,
+            output_max_size: None,
+            output_limit_action: None,
+            tcp_rmem: None,
+            tcp_wmem: None,
+            tcp_init_cwnd: None,
+            tcp_rto_init: None,
+            tcp_rto_min: None,
+            tcp_rto_max: None,
+            tcp_msl: None,
+            tcp_delayed_ack_timeout: None,
+            egress_qdisc: None,
+            router_queue: None,
+            firewall: None,
         }
     }
 }
@@ -607,6 +1030,32 @@ pub enum RunningVal {
     Running,
 }
 
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Copy, Clone, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutoVal {
+    Auto,
+}
+
+/// An option value that is either chosen by Shadow from the resources that it detects ("auto"),
+/// or given explicitly.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum AutoOr<T> {
+    Auto(AutoVal),
+    Value(T),
+}
+
+impl<T: FromStr> FromStr for AutoOr<T> {
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Self::Auto(AutoVal::Auto));
+        }
+        s.parse().map(Self::Value)
+    }
+}
+
 /// The enum variants here have an extra level of indirection to get the
 /// serde serialization that we want.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
@@ -639,6 +1088,100 @@ impl std::fmt::Display for ProcessFinalState {
     }
 }
 
+/// When a process is restarted after it exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Restart the process if its final state isn't its expected final state
+    OnFailure,
+    /// Restart the process whenever it exits
+    Always,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RestartOptions {
+    /// When to restart the process
+    pub policy: RestartPolicy,
+
+    /// The maximum number of times to restart the process, or no maximum if null
+    #[serde(default)]
+    pub max_restarts: Option<u32>,
+
+    /// The delay before the process is first restarted
+    #[serde(default = "default_restart_delay")]
+    pub delay: units::Time<units::TimePrefix>,
+
+    /// The factor that the delay is multiplied by after each restart
+    #[serde(default = "default_restart_backoff")]
+    pub backoff: f64,
+
+    /// The maximum delay before the process is restarted, or no maximum if null
+    #[serde(default)]
+    pub max_delay: Option<units::Time<units::TimePrefix>>,
+}
+
+fn default_restart_delay() -> units::Time<units::TimePrefix> {
+    units::Time::new(1, units::TimePrefix::Sec)
+}
+
+fn default_restart_backoff() -> f64 {
+    2.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseOptions {
+    /// Name of the phase, used in log messages and simulation statistics
+    pub name: String,
+
+    /// The simulated time at which the phase begins
+    pub start_time: units::Time<units::TimePrefix>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StatsSinkOptions {
+    /// Format of the metrics sent to the sink
+    pub format: StatsSinkFormat,
+
+    /// Address of the sink, as "host:port"
+    pub address: String,
+
+    /// Transport protocol used to send metrics to the sink
+    #[serde(default = "default_stats_sink_protocol")]
+    pub protocol: StatsSinkProtocol,
+
+    /// Real (wall-clock) time between sending metrics to the sink
+    #[serde(default = "default_time_10")]
+    pub flush_interval: units::Time<units::TimePrefix>,
+
+    /// Prefix of each metric name
+    #[serde(default = "default_stats_sink_prefix")]
+    pub prefix: String,
+
+    /// Maximum number of flushes waiting to be sent to the sink. If the sink can't keep up, new
+    /// flushes are dropped until there is room
+    #[serde(default = "default_stats_sink_max_queued_flushes")]
+    pub max_queued_flushes: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatsSinkFormat {
+    /// InfluxDB line protocol
+    Influx,
+    /// Graphite plaintext protocol, with tags
+    Graphite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatsSinkProtocol {
+    Tcp,
+    Udp,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ProcessOptions {
@@ -650,12 +1193,21 @@ pub struct ProcessOptions {
 
     /// Environment variables passed when executing this process
     #[serde(default)]
-    pub environment: BTreeMap<EnvName, String>,
+    pub environment: BTreeMap<EnvName, EnvValue>,
+
+    /// Files to generate from templates in the host's data directory before the process starts
+    #[serde(default)]
+    pub files: Vec<ProcessFileOptions>,
 
     /// The simulated time at which to execute the process
     #[serde(default)]
     pub start_time: units::Time<units::TimePrefix>,
 
+    /// Conditions that must all be met before the process is executed, which are checked from
+    /// `start_time`
+    #[serde(default)]
+    pub start_after: Vec<StartCondition>,
+
     /// The simulated time at which to send a `shutdown_signal` signal to the process
     #[serde(default)]
     pub shutdown_time: Option<units::Time<units::TimePrefix>>,
@@ -664,10 +1216,69 @@ pub struct ProcessOptions {
     #[serde(default = "default_sigterm")]
     pub shutdown_signal: Signal,
 
+    /// Signals to send to the process at simulated times
+    #[serde(default)]
+    pub signals: Vec<ProcessSignalOptions>,
+
     /// The expected final state of the process. Shadow will report an error
     /// if the actual state doesn't match.
     #[serde(default)]
     pub expected_final_state: ProcessFinalState,
+
+    /// Restart the process after it exits
+    #[serde(default)]
+    pub restart: Option<RestartOptions>,
+}
+
+/// A condition for starting a process. Other processes are given by their index in the host's
+/// `processes`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub enum StartCondition {
+    /// The other process has exited and won't be restarted
+    Exited(usize),
+    /// The other process has been running for at least the duration
+    Running {
+        process: usize,
+        duration: units::Time<units::TimePrefix>,
+    },
+    /// A TCP socket on the host is listening on the port
+    Listening(u16),
+}
+
+/// A condition for stopping the simulation before the stop time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub enum StopCondition {
+    /// All processes have exited and won't be restarted, other than the processes that are
+    /// expected to be running at the end of the simulation
+    AllExited,
+    /// A host's process, given by its index in the host's `processes`, has exited and won't be
+    /// restarted
+    Exited { host: String, process: usize },
+    /// The hosts have sent at least this amount of data in total
+    BytesSent(units::Bytes<units::SiPrefixUpper>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessSignalOptions {
+    /// The simulated time at which to send the signal
+    pub time: units::Time<units::TimePrefix>,
+
+    /// The signal to send
+    pub signal: Signal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessFileOptions {
+    /// Path of the file, relative to the host's data directory
+    pub path: std::path::PathBuf,
+
+    /// Contents of the file, where "{{hostname}}" and "{{ip}}" are replaced with the host's name
+    /// and IP address
+    pub template: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -678,10 +1289,35 @@ pub struct HostOptions {
 
     pub processes: Vec<ProcessOptions>,
 
+    /// The simulated time at which the host joins the network. The host's name can't be resolved
+    /// before this time, and its processes can't start before this time.
+    #[serde(default)]
+    pub start_time: units::Time<units::TimePrefix>,
+
+    /// Seed for the host's randomness, which is used instead of `general.seed` so that the host's
+    /// randomness can be changed without changing the randomness of the other hosts
+    #[serde(default)]
+    pub seed: Option<u32>,
+
+    /// Path to a directory whose contents are copied into the host's data directory when the host
+    /// is created, before any of its processes start
+    #[serde(default)]
+    pub data_template: Option<String>,
+
+    /// Name of a group of hosts that are always run by the same worker thread, such as a client
+    /// and a server that send most of their traffic to each other
+    #[serde(default)]
+    pub affinity_group: Option<String>,
+
     /// IP address to assign to the host
     #[serde(default)]
     pub ip_addr: Option<std::net::Ipv4Addr>,
 
+    /// Subnet in CIDR notation that the host's IP address is assigned from, if the host doesn't
+    /// have an IP address
+    #[serde(default)]
+    pub ip_pool: Option<String>,
+
     /// Downstream bandwidth capacity of the host
     #[serde(default)]
     pub bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
@@ -690,6 +1326,42 @@ pub struct HostOptions {
     #[serde(default)]
     pub bandwidth_up: Option<units::BitsPerSec<units::SiPrefixUpper>>,
 
+    /// Amount of data that the host can send or receive in a burst above its bandwidth capacity
+    #[serde(default)]
+    pub bandwidth_burst: Option<units::Bytes<units::SiPrefixUpper>>,
+
+    /// Maximum bandwidth of the host's bursts
+    #[serde(default)]
+    pub bandwidth_peak: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+
+    /// Path to a trace file that changes the upstream bandwidth of the host, and adds latency and
+    /// packet loss to the packets that it sends, during the simulation
+    #[serde(default)]
+    pub uplink_trace: Option<String>,
+
+    /// Distance in meters from the host to the wireless access point of its graph node, which
+    /// determines the packet loss of the host's wireless channel
+    #[serde(default)]
+    pub wireless_distance: Option<f64>,
+
+    /// Make the host a NAT gateway for a private subnet
+    #[serde(default)]
+    pub nat: Option<NatOptions>,
+
+    /// Network interfaces of the host in addition to `eth0`, which are named `eth1`, `eth2`, and
+    /// so on
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceOptions>,
+
+    /// Routes that choose the interface that the host sends packets on by their destination
+    /// address
+    #[serde(default)]
+    pub routes: Vec<RouteOptions>,
+
+    /// Make the host a middlebox that intercepts and modifies the packets of other hosts
+    #[serde(default)]
+    pub middlebox: Option<MiddleboxOptions>,
+
     #[serde(default)]
     pub host_options: HostDefaultOptions,
 }
@@ -890,6 +1562,76 @@ impl std::fmt::Display for EnvName {
     }
 }
 
+/// The value of an environment variable. Numbers and booleans are converted to strings, so that
+/// values such as ports don't need to be quoted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct EnvValue(String);
+
+impl<'de> serde::Deserialize<'de> for EnvValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EnvValueVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for EnvValueVisitor {
+            type Value = EnvValue;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a string, number, or boolean")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EnvValue(v.to_owned()))
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EnvValue(v.to_string()))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EnvValue(v.to_string()))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EnvValue(v.to_string()))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EnvValue(v.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(EnvValueVisitor)
+    }
+}
+
+impl std::ops::Deref for EnvValue {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<EnvValue> for String {
+    fn from(value: EnvValue) -> Self {
+        value.0
+    }
+}
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum Scheduler {
@@ -909,6 +1651,10 @@ fn default_data_directory() -> Option<String> {
     Some("shadow.data".into())
 }
 
+fn default_checkpoint_path() -> Option<String> {
+    Some("shadow.checkpoint".into())
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum LogInfoFlag {
@@ -961,6 +1707,7 @@ fn parse_set_str(s: &str) -> Result<HashSet<String>, <String as FromStr>::Err> {
 pub enum QDiscMode {
     Fifo,
     RoundRobin,
+    DeficitRoundRobin,
 }
 
 impl FromStr for QDiscMode {
@@ -971,22 +1718,609 @@ impl FromStr for QDiscMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+/// What to do when an output file reaches its maximum size.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
-pub enum Compression {
-    Xz,
+#[repr(C)]
+pub enum OutputLimitAction {
+    /// Discard any further output.
+    Truncate,
+    /// Move the file to "<path>.1", replacing any previous one, and start a new file.
+    Rotate,
+    /// Fail the simulation.
+    Fail,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(deny_unknown_fields)]
-pub struct FileSource {
-    /// The path to the file
-    pub path: String,
-    /// The file's compression format
-    pub compression: Option<Compression>,
+impl FromStr for OutputLimitAction {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum MulticastScope {
+    Node,
+    Graph,
+}
+
+impl FromStr for MulticastScope {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum EcmpMode {
+    Off,
+    Flow,
+    Packet,
+}
+
+impl FromStr for EcmpMode {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// The minimum, initial, and maximum sizes of a socket buffer, like linux's `tcp_rmem` and
+/// `tcp_wmem` sysctls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TcpMemLimits(
+    pub units::Bytes<units::SiPrefixUpper>,
+    pub units::Bytes<units::SiPrefixUpper>,
+    pub units::Bytes<units::SiPrefixUpper>,
+);
+
+impl TcpMemLimits {
+    /// The minimum, initial, and maximum sizes in bytes.
+    pub fn to_bytes(&self) -> (u64, u64, u64) {
+        let bytes = |x: units::Bytes<_>| x.convert(units::SiPrefixUpper::Base).unwrap().value();
+        (bytes(self.0), bytes(self.1), bytes(self.2))
+    }
+}
+
+impl FromStr for TcpMemLimits {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// A queuing discipline for the packets sent by a network interface, like a Linux qdisc.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum EgressQdisc {
+    /// A first-in-first-out queue that drops arriving packets when it's full.
+    Pfifo {
+        /// The maximum number of packets in the queue
+        #[serde(default = "default_qdisc_limit")]
+        limit: u32,
+    },
+    /// A first-in-first-out queue that delays, drops, and rate limits packets, like Linux's netem
+    /// qdisc.
+    Netem {
+        /// The maximum number of packets in the queue
+        #[serde(default = "default_qdisc_limit")]
+        limit: u32,
+        /// The delay added to each packet
+        #[serde(default)]
+        delay: Option<units::Time<units::TimePrefix>>,
+        /// The chance that a packet is dropped
+        #[serde(default)]
+        loss: f32,
+        /// The rate at which packets leave the queue
+        #[serde(default)]
+        rate: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    },
+    /// A CoDel active queue management queue, like Linux's codel qdisc.
+    Codel {
+        /// The maximum number of packets in the queue
+        #[serde(default = "default_qdisc_limit")]
+        limit: u32,
+        /// Mark ECN-capable packets instead of dropping them
+        #[serde(default)]
+        ecn: bool,
+    },
+    /// A CoDel queue for each flow, which are scheduled using deficit round-robin, like Linux's
+    /// fq_codel qdisc.
+    FqCodel {
+        /// The maximum number of packets in all of the queues
+        #[serde(default = "default_fq_codel_limit")]
+        limit: u32,
+        /// The number of bytes that each flow can send per round
+        #[serde(default = "default_fq_codel_quantum")]
+        quantum: units::Bytes<units::SiPrefixUpper>,
+        /// Mark ECN-capable packets instead of dropping them
+        #[serde(default)]
+        ecn: bool,
+    },
+}
+
+impl FromStr for EgressQdisc {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// The default limit of Linux's pfifo, netem, and codel qdiscs.
+fn default_qdisc_limit() -> u32 {
+    1000
+}
+
+/// The default limit of Linux's fq_codel qdisc.
+fn default_fq_codel_limit() -> u32 {
+    10240
+}
+
+/// The default quantum of Linux's fq_codel qdisc, which is the size of an ethernet frame.
+fn default_fq_codel_quantum() -> units::Bytes<units::SiPrefixUpper> {
+    units::Bytes::new(1514, units::SiPrefixUpper::Base)
+}
+
+/// The queue of a host's router, which holds the packets sent to the host until the host's
+/// bandwidth allows them to be received.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RouterQueue {
+    /// A CoDel active queue management queue with no size limit.
+    Codel,
+    /// A first-in-first-out queue that drops arriving packets when it's full.
+    DropTail {
+        /// The maximum number of packets in the queue
+        #[serde(default)]
+        packets: Option<u32>,
+        /// The maximum number of bytes in the queue
+        #[serde(default)]
+        bytes: Option<units::Bytes<units::SiPrefixUpper>>,
+    },
+    /// A random early detection (RED) active queue management queue.
+    Red {
+        /// The maximum number of bytes in the queue
+        limit: units::Bytes<units::SiPrefixUpper>,
+        /// The average queue size at which packets start to be dropped
+        min: units::Bytes<units::SiPrefixUpper>,
+        /// The average queue size at which all packets are dropped
+        max: units::Bytes<units::SiPrefixUpper>,
+        /// The drop probability when the average queue size reaches `max`
+        #[serde(default = "default_red_probability")]
+        probability: f32,
+        /// The weight of the current queue size in the average queue size
+        #[serde(default = "default_red_weight")]
+        weight: f32,
+    },
+}
+
+impl FromStr for RouterQueue {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// The default maximum drop probability of a RED queue, from Linux's tc-red.
+fn default_red_probability() -> f32 {
+    0.02
+}
+
+/// The default queue size weight of a RED queue, from the RED paper.
+fn default_red_weight() -> f32 {
+    0.002
+}
+
+/// Firewall rules for the packets sent and received by a network interface, like a small subset of
+/// iptables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FirewallOptions {
+    /// The action for received packets that don't match any rule
+    #[serde(default)]
+    pub input_policy: FirewallAction,
+    /// The action for sent packets that don't match any rule
+    #[serde(default)]
+    pub output_policy: FirewallAction,
+    /// The rules, which are checked in order until one matches the packet
+    #[serde(default)]
+    pub rules: Vec<FirewallRule>,
+}
+
+impl FromStr for FirewallOptions {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// A firewall rule. A packet matches the rule if it matches all of the rule's fields that are set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FirewallRule {
+    /// The ID that's logged when the rule drops a packet
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Whether the rule matches received or sent packets
+    pub direction: FirewallDirection,
+    /// What to do with packets that match the rule
+    pub action: FirewallAction,
+    /// The transport protocol of the packet
+    #[serde(default)]
+    pub protocol: Option<FirewallProtocol>,
+    /// The remote address of the packet, as an address or a subnet in CIDR notation
+    #[serde(default)]
+    pub remote_addr: Option<String>,
+    /// The port of the packet on the host
+    #[serde(default)]
+    pub local_port: Option<u16>,
+    /// The port of the packet on the remote host
+    #[serde(default)]
+    pub remote_port: Option<u16>,
+    /// Whether the packet belongs to a connection that the firewall has already accepted packets
+    /// for
+    #[serde(default)]
+    pub state: Option<FirewallState>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallAction {
+    #[default]
+    Accept,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallDirection {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallState {
+    /// The first packet of a connection.
+    New,
+    /// A packet of a connection that the firewall has already accepted a packet for.
+    Established,
+}
+
+/// A NAT gateway that translates the addresses of packets sent between a private subnet and the
+/// rest of the network.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NatOptions {
+    /// The network address of the private subnet
+    pub subnet: std::net::Ipv4Addr,
+    /// The prefix length of the private subnet
+    #[serde(default = "default_nat_prefix_len")]
+    pub prefix_len: u8,
+    /// How the gateway maps private addresses and ports to public ports
+    #[serde(default)]
+    pub mapping: NatMapping,
+    /// How long a mapping lasts after its last packet
+    #[serde(default = "default_nat_timeout")]
+    pub timeout: units::Time<units::TimePrefix>,
+    /// Forward packets that are sent from the private subnet to the gateway's public address back
+    /// into the private subnet
+    #[serde(default)]
+    pub hairpinning: bool,
+}
+
+/// How a NAT gateway maps the private addresses and ports of its subnet to its public ports.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NatMapping {
+    /// Each private address and port is mapped to one public port, and any remote host can send
+    /// packets to that public port.
+    #[default]
+    FullCone,
+    /// Each private address and port is mapped to a different public port for each remote address
+    /// and port, and only that remote address and port can send packets to the public port.
+    Symmetric,
+}
+
+/// A network interface of a host in addition to its `eth0` interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InterfaceOptions {
+    /// Network graph node ID to attach the interface to
+    pub network_node_id: u32,
+    /// IP address of the interface
+    #[serde(default)]
+    pub ip_addr: Option<std::net::Ipv4Addr>,
+}
+
+/// A route that sends the packets to a subnet on one of the host's interfaces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RouteOptions {
+    /// The destination subnet in CIDR notation
+    pub destination: String,
+    /// The name of the interface, such as `eth1`
+    pub interface: String,
+}
+
+/// A middlebox that intercepts the packets sent to and from some subnets while they're in transit,
+/// and applies rules to them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MiddleboxOptions {
+    /// The subnets in CIDR notation whose packets are sent through the middlebox
+    pub intercept: Vec<String>,
+    /// The rules, which are checked in order until one matches the packet. Packets that don't match
+    /// any rule are forwarded unchanged.
+    #[serde(default)]
+    pub rules: Vec<MiddleboxRule>,
+}
+
+/// A middlebox rule. A packet matches the rule if it matches all of the rule's fields that are
+/// set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MiddleboxRule {
+    /// The ID that's logged when the rule applies to a packet
+    #[serde(default)]
+    pub id: Option<String>,
+    /// What to do with packets that match the rule
+    pub action: MiddleboxAction,
+    /// The transport protocol of the packet
+    #[serde(default)]
+    pub protocol: Option<FirewallProtocol>,
+    /// The source address of the packet, as an address or a subnet in CIDR notation
+    #[serde(default)]
+    pub src_addr: Option<String>,
+    /// The destination address of the packet, as an address or a subnet in CIDR notation
+    #[serde(default)]
+    pub dst_addr: Option<String>,
+    /// The source port of the packet
+    #[serde(default)]
+    pub src_port: Option<u16>,
+    /// The destination port of the packet
+    #[serde(default)]
+    pub dst_port: Option<u16>,
+    /// A string that the packet's payload contains
+    #[serde(default)]
+    pub payload: Option<String>,
+    /// The probability that the rule applies to a matching packet. Matching packets that the rule
+    /// doesn't apply to are checked against the following rules.
+    #[serde(default = "default_middlebox_probability")]
+    pub probability: f64,
+    /// How long the `delay` action holds packets before forwarding them
+    #[serde(default)]
+    pub delay: Option<units::Time<units::TimePrefix>>,
+    /// The addresses and ports that the `rewrite` action writes to packets
+    #[serde(default)]
+    pub rewrite: Option<MiddleboxRewrite>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MiddleboxAction {
+    /// Forward the packet unchanged.
+    Forward,
+    /// Drop the packet.
+    Drop,
+    /// Forward the packet after the rule's delay.
+    Delay,
+    /// Drop a TCP packet, and send TCP resets to both of its endpoints.
+    Reset,
+    /// Rewrite the packet's addresses and ports, and forward it to its new destination.
+    Rewrite,
+}
+
+/// The fields that a middlebox rule rewrites. Fields that aren't set are left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MiddleboxRewrite {
+    #[serde(default)]
+    pub src_addr: Option<std::net::Ipv4Addr>,
+    #[serde(default)]
+    pub src_port: Option<u16>,
+    #[serde(default)]
+    pub dst_addr: Option<std::net::Ipv4Addr>,
+    #[serde(default)]
+    pub dst_port: Option<u16>,
+}
+
+fn default_middlebox_probability() -> f64 {
+    1.0
+}
+
+/// The default prefix length of a NAT gateway's private subnet.
+fn default_nat_prefix_len() -> u8 {
+    24
+}
+
+/// The default timeout of a NAT gateway's mappings, from RFC 4787.
+fn default_nat_timeout() -> units::Time<units::TimePrefix> {
+    units::Time::new(2, units::TimePrefix::Min)
+}
+
+/// An authoritative DNS server that isn't a host, but answers the queries sent to its address by
+/// any host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DnsServerOptions {
+    /// The address that hosts send queries to
+    pub address: std::net::Ipv4Addr,
+    /// The time between a host sending a query and receiving the response
+    #[serde(default = "default_dns_latency")]
+    pub latency: units::Time<units::TimePrefix>,
+    /// The TTL of the hosts' A records, and of other records that don't have a TTL
+    #[serde(default = "default_dns_ttl")]
+    pub ttl: units::Time<units::TimePrefix>,
+    /// Records that are served in addition to the A records of the hosts
+    #[serde(default)]
+    pub records: Vec<DnsRecord>,
+}
+
+/// A resource record served by the DNS server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "UPPERCASE", deny_unknown_fields)]
+pub enum DnsRecord {
+    /// An IPv4 address.
+    A {
+        name: String,
+        address: std::net::Ipv4Addr,
+        #[serde(default)]
+        ttl: Option<units::Time<units::TimePrefix>>,
+    },
+    /// An IPv6 address.
+    Aaaa {
+        name: String,
+        address: std::net::Ipv6Addr,
+        #[serde(default)]
+        ttl: Option<units::Time<units::TimePrefix>>,
+    },
+    /// An alias for the canonical name `target`.
+    Cname {
+        name: String,
+        target: String,
+        #[serde(default)]
+        ttl: Option<units::Time<units::TimePrefix>>,
+    },
+    /// The location of a service, from RFC 2782.
+    Srv {
+        name: String,
+        target: String,
+        port: u16,
+        #[serde(default)]
+        priority: u16,
+        #[serde(default)]
+        weight: u16,
+        #[serde(default)]
+        ttl: Option<units::Time<units::TimePrefix>>,
+    },
+}
+
+impl DnsRecord {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::A { name, .. }
+            | Self::Aaaa { name, .. }
+            | Self::Cname { name, .. }
+            | Self::Srv { name, .. } => name,
+        }
+    }
+
+    pub fn ttl(&self) -> Option<units::Time<units::TimePrefix>> {
+        match self {
+            Self::A { ttl, .. }
+            | Self::Aaaa { ttl, .. }
+            | Self::Cname { ttl, .. }
+            | Self::Srv { ttl, .. } => *ttl,
+        }
+    }
+}
+
+/// The default latency of the DNS server.
+fn default_dns_latency() -> units::Time<units::TimePrefix> {
+    units::Time::new(1, units::TimePrefix::Milli)
+}
+
+/// The default TTL of the DNS server's records.
+fn default_dns_ttl() -> units::Time<units::TimePrefix> {
+    units::Time::new(5, units::TimePrefix::Min)
+}
+
+/// A change to the state of a graph edge at a simulated time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LinkEventOptions {
+    /// The simulated time at which the edge changes state
+    pub time: units::Time<units::TimePrefix>,
+    /// The ID of the edge's source node
+    pub source: u32,
+    /// The ID of the edge's target node
+    pub target: u32,
+    /// Whether the edge goes "down" or comes back "up"
+    pub state: LinkState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkState {
+    Up,
+    Down,
+}
+
+/// A trace file that changes the attributes of a graph edge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LinkTraceOptions {
+    /// The ID of the edge's source node
+    pub source: u32,
+    /// The ID of the edge's target node
+    pub target: u32,
+    /// The path to the trace file
+    pub path: String,
+}
+
+/// The strategy that chooses the shortest paths between graph nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RoutingOptions {
+    /// The path with the lowest latency, breaking ties by the lowest packet loss.
+    Latency,
+    /// The path that crosses the fewest edges, breaking ties by latency.
+    HopCount,
+    /// The path with the lowest sum of the edges' inverse bandwidths, breaking ties by latency.
+    Bandwidth,
+    /// Static routes loaded from a file. Pairs of nodes without a static route use the path with
+    /// the lowest latency.
+    Static {
+        /// The path to the file of routes
+        path: String,
+    },
+    /// Valley-free paths chosen by the routing policies of autonomous systems, using the
+    /// `relationship` attributes of the edges.
+    GaoRexford,
+}
+
+impl FromStr for RoutingOptions {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    Xz,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FileSource {
+    /// The path to the file
+    pub path: String,
+    /// The file's compression format
+    pub compression: Option<Compression>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum GraphSource {
     File(FileSource),
@@ -999,10 +2333,128 @@ pub enum GraphSource {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum GraphOptions {
     Gml(GraphSource),
+    /// CAIDA AS-relationship data, which is converted to a graph with a node for each autonomous
+    /// system.
+    Caida(CaidaGraphOptions),
+    /// A graph composed of sub-graphs and the edges between them.
+    Composite(CompositeGraphOptions),
     #[serde(rename = "1_gbit_switch")]
     OneGbitSwitch,
 }
 
+/// Options for a graph converted from CAIDA AS-relationship data.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CaidaGraphOptions {
+    /// The AS-relationship data, in the serial-1 or serial-2 format
+    #[serde(flatten)]
+    pub source: GraphSource,
+    /// The latency of the edges between a provider and its customers
+    #[serde(default = "default_time_10_ms")]
+    pub transit_latency: units::Time<units::TimePrefix>,
+    /// The latency of the edges between peers
+    #[serde(default = "default_time_5_ms")]
+    pub peer_latency: units::Time<units::TimePrefix>,
+    /// The latency of the self-loop of each autonomous system, which is used by paths between
+    /// hosts in the same autonomous system
+    #[serde(default = "default_time_1_ms")]
+    pub internal_latency: units::Time<units::TimePrefix>,
+    /// The bandwidth of the uplinks of hosts in each autonomous system
+    #[serde(default = "default_bandwidth_1_gbit")]
+    pub host_bandwidth: units::BitsPerSec<units::SiPrefixUpper>,
+}
+
+/// Options for a graph composed of sub-graphs.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CompositeGraphOptions {
+    /// The sub-graphs, by name
+    pub subgraphs: BTreeMap<String, SubgraphOptions>,
+    /// Edges between the nodes of the sub-graphs
+    #[serde(default)]
+    pub edges: Vec<CompositeEdgeOptions>,
+}
+
+/// A sub-graph of a composite graph.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SubgraphOptions {
+    /// The sub-graph, which may itself be a composite graph
+    pub graph: GraphOptions,
+    /// The number that's added to the ids of the sub-graph's nodes to get their ids in the
+    /// composite graph
+    #[serde(default)]
+    pub id_offset: u32,
+}
+
+/// An edge between the nodes of sub-graphs.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CompositeEdgeOptions {
+    /// The source node, as `<sub-graph name>:<node id in the sub-graph>`
+    pub source: String,
+    /// The target node, as `<sub-graph name>:<node id in the sub-graph>`
+    pub target: String,
+    /// The edge's GML attributes, such as `latency`
+    #[serde(flatten)]
+    pub attributes: BTreeMap<String, GraphAttribute>,
+}
+
+/// The value of a graph attribute.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum GraphAttribute {
+    Int(i32),
+    Float(f32),
+    Str(String),
+}
+
+/// Options for a synthetic network graph.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TopologyOptions {
+    /// The shape of the graph
+    #[serde(flatten)]
+    pub kind: TopologyKind,
+    /// The lowest latency of an edge
+    #[serde(default = "default_time_1_ms")]
+    pub min_latency: units::Time<units::TimePrefix>,
+    /// The highest latency of an edge
+    #[serde(default = "default_time_10_ms")]
+    pub max_latency: units::Time<units::TimePrefix>,
+    /// The lowest bandwidth of an edge or a host uplink
+    #[serde(default = "default_bandwidth_100_mbit")]
+    pub min_bandwidth: units::BitsPerSec<units::SiPrefixUpper>,
+    /// The highest bandwidth of an edge or a host uplink
+    #[serde(default = "default_bandwidth_1_gbit")]
+    pub max_bandwidth: units::BitsPerSec<units::SiPrefixUpper>,
+    /// The seed of the random number generator that chooses the edges, latencies, and bandwidths
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl FromStr for TopologyOptions {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// The shape of a synthetic network graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TopologyKind {
+    /// A hub node with an edge to each of the other nodes.
+    Star { nodes: u32 },
+    /// Two router nodes joined by a bottleneck edge, with the other nodes split between them.
+    Dumbbell { nodes: u32 },
+    /// A k-ary fat-tree of core, aggregation, and edge switches, with `5k²/4` nodes.
+    FatTree { k: u32 },
+    /// A scale-free graph where each new node has edges to `edges_per_node` existing nodes,
+    /// chosen in proportion to their degrees.
+    BarabasiAlbert { nodes: u32, edges_per_node: u32 },
+    /// A random graph where each pair of nodes has an edge with probability `edge_probability`.
+    ErdosRenyi { nodes: u32, edge_probability: f64 },
+}
+
 #[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(untagged)]
 pub enum ProcessArgs {
@@ -1322,6 +2774,51 @@ fn default_some_time_0() -> Option<units::Time<units::TimePrefix>> {
     Some(units::Time::new(0, units::TimePrefix::Sec))
 }
 
+/// Helper function for serde default `10 sec` values.
+fn default_time_10() -> units::Time<units::TimePrefix> {
+    units::Time::new(10, units::TimePrefix::Sec)
+}
+
+/// Helper function for serde default `1 ms` values.
+fn default_time_1_ms() -> units::Time<units::TimePrefix> {
+    units::Time::new(1, units::TimePrefix::Milli)
+}
+
+/// Helper function for serde default `5 ms` values.
+fn default_time_5_ms() -> units::Time<units::TimePrefix> {
+    units::Time::new(5, units::TimePrefix::Milli)
+}
+
+/// Helper function for serde default `10 ms` values.
+fn default_time_10_ms() -> units::Time<units::TimePrefix> {
+    units::Time::new(10, units::TimePrefix::Milli)
+}
+
+/// Helper function for serde default `100 Mbit` values.
+fn default_bandwidth_100_mbit() -> units::BitsPerSec<units::SiPrefixUpper> {
+    units::BitsPerSec::new(100, units::SiPrefixUpper::Mega)
+}
+
+/// Helper function for serde default `1 Gbit` values.
+fn default_bandwidth_1_gbit() -> units::BitsPerSec<units::SiPrefixUpper> {
+    units::BitsPerSec::new(1, units::SiPrefixUpper::Giga)
+}
+
+/// Helper function for serde default `StatsSinkProtocol::Tcp` values.
+fn default_stats_sink_protocol() -> StatsSinkProtocol {
+    StatsSinkProtocol::Tcp
+}
+
+/// Helper function for serde default `"shadow"` values.
+fn default_stats_sink_prefix() -> String {
+    "shadow".to_string()
+}
+
+/// Helper function for serde default `16` values.
+fn default_stats_sink_max_queued_flushes() -> u32 {
+    16
+}
+
 /// Helper function for serde default `Some(true)` values.
 fn default_some_true() -> Option<bool> {
     Some(true)
@@ -1337,17 +2834,47 @@ fn default_some_0() -> Option<u32> {
     Some(0)
 }
 
+/// Helper function for serde default `Some(AutoOr::Auto)` values.
+fn default_some_auto<T>() -> Option<AutoOr<T>> {
+    Some(AutoOr::Auto(AutoVal::Auto))
+}
+
+/// Helper function for serde default `Some(NullableOption::Value(AutoOr::Auto))` values.
+fn default_some_nullable_auto<T>() -> Option<NullableOption<AutoOr<T>>> {
+    Some(NullableOption::Value(AutoOr::Auto(AutoVal::Auto)))
+}
+
 /// Helper function for serde default `Some(1)` values.
 fn default_some_1() -> Option<u32> {
     Some(1)
 }
 
+/// Helper function for serde default `Some(8)` values.
+fn default_some_8() -> Option<u32> {
+    Some(8)
+}
+
 /// Helper function for serde default `Some(NullableOption::Value(1 sec))` values.
 fn default_some_nullable_time_1() -> Option<NullableOption<units::Time<units::TimePrefix>>> {
     let time = units::Time::new(1, units::TimePrefix::Sec);
     Some(NullableOption::Value(time))
 }
 
+/// Helper function for serde default `Some(MulticastScope::Node)` values.
+fn default_some_multicast_node() -> Option<MulticastScope> {
+    Some(MulticastScope::Node)
+}
+
+/// Helper function for serde default `Some(RoutingOptions::Latency)` values.
+fn default_some_routing_latency() -> Option<RoutingOptions> {
+    Some(RoutingOptions::Latency)
+}
+
+/// Helper function for serde default `Some(EcmpMode::Off)` values.
+fn default_some_ecmp_off() -> Option<EcmpMode> {
+    Some(EcmpMode::Off)
+}
+
 /// Helper function for serde default `Some(LogLevel::Info)` values.
 fn default_some_info() -> Option<LogLevel> {
     Some(LogLevel::Info)
@@ -1611,4 +3138,489 @@ mod tests {
             Some(NullableOption::Null)
         );
     }
+
+    #[test]
+    fn test_egress_qdisc() {
+        assert_eq!(
+            EgressQdisc::from_str("type: pfifo").unwrap(),
+            EgressQdisc::Pfifo { limit: 1000 }
+        );
+        assert_eq!(
+            EgressQdisc::from_str("{type: netem, delay: 10 ms, loss: 0.5}").unwrap(),
+            EgressQdisc::Netem {
+                limit: 1000,
+                delay: Some(units::Time::new(10, units::TimePrefix::Milli)),
+                loss: 0.5,
+                rate: None,
+            }
+        );
+        assert_eq!(
+            EgressQdisc::from_str("{type: fq_codel, ecn: true}").unwrap(),
+            EgressQdisc::FqCodel {
+                limit: 10240,
+                quantum: units::Bytes::new(1514, units::SiPrefixUpper::Base),
+                ecn: true,
+            }
+        );
+        assert!(EgressQdisc::from_str("type: red").is_err());
+        assert!(EgressQdisc::from_str("{type: codel, delay: 10 ms}").is_err());
+    }
+
+    #[test]
+    fn test_router_queue() {
+        assert_eq!(
+            RouterQueue::from_str("type: codel").unwrap(),
+            RouterQueue::Codel
+        );
+        assert_eq!(
+            RouterQueue::from_str("{type: drop_tail, packets: 100}").unwrap(),
+            RouterQueue::DropTail {
+                packets: Some(100),
+                bytes: None,
+            }
+        );
+        assert_eq!(
+            RouterQueue::from_str("{type: red, limit: 400 KB, min: 30 KB, max: 90 KB}").unwrap(),
+            RouterQueue::Red {
+                limit: units::Bytes::new(400, units::SiPrefixUpper::Kilo),
+                min: units::Bytes::new(30, units::SiPrefixUpper::Kilo),
+                max: units::Bytes::new(90, units::SiPrefixUpper::Kilo),
+                probability: 0.02,
+                weight: 0.002,
+            }
+        );
+        assert!(RouterQueue::from_str("{type: red, limit: 400 KB}").is_err());
+        assert!(RouterQueue::from_str("{type: drop_tail, limit: 100}").is_err());
+    }
+
+    #[test]
+    fn test_routing() {
+        assert_eq!(
+            RoutingOptions::from_str("type: hop_count").unwrap(),
+            RoutingOptions::HopCount
+        );
+        assert_eq!(
+            RoutingOptions::from_str("{type: static, path: routes.txt}").unwrap(),
+            RoutingOptions::Static {
+                path: "routes.txt".into(),
+            }
+        );
+        assert!(RoutingOptions::from_str("type: static").is_err());
+        assert!(RoutingOptions::from_str("{type: latency, path: routes.txt}").is_err());
+        assert_eq!(
+            RoutingOptions::from_str("type: gao_rexford").unwrap(),
+            RoutingOptions::GaoRexford
+        );
+    }
+
+    #[test]
+    fn test_caida_graph() {
+        let options: GraphOptions =
+            serde_yaml::from_str("{type: caida, file: {path: as-rel.txt}, peer_latency: 2 ms}")
+                .unwrap();
+        let GraphOptions::Caida(options) = options else {
+            panic!("Unexpected graph options: {options:?}");
+        };
+
+        assert!(matches!(options.source, GraphSource::File(ref x) if x.path == "as-rel.txt"));
+        assert_eq!(
+            options.peer_latency,
+            units::Time::new(2, units::TimePrefix::Milli)
+        );
+        assert_eq!(options.transit_latency, default_time_10_ms());
+        assert_eq!(options.host_bandwidth, default_bandwidth_1_gbit());
+
+        assert!(serde_yaml::from_str::<GraphOptions>("type: caida").is_err());
+    }
+
+    #[test]
+    fn test_topology_options() {
+        let options =
+            TopologyOptions::from_str("{type: fat_tree, k: 4, max_latency: 2 ms, seed: 3}")
+                .unwrap();
+        assert_eq!(options.kind, TopologyKind::FatTree { k: 4 });
+        assert_eq!(options.min_latency, default_time_1_ms());
+        assert_eq!(
+            options.max_latency,
+            units::Time::new(2, units::TimePrefix::Milli)
+        );
+        assert_eq!(options.seed, 3);
+
+        assert!(TopologyOptions::from_str("type: star").is_err());
+        assert!(TopologyOptions::from_str("{type: star, nodes: 3, k: 4}").is_err());
+    }
+
+    #[test]
+    fn test_firewall_options() {
+        let options = FirewallOptions::from_str(
+            "
+            input_policy: drop
+            rules:
+            - {direction: input, action: accept, state: established}
+            - {id: ssh, direction: input, action: accept, protocol: tcp, local_port: 22,
+               remote_addr: 10.0.0.0/8}
+            ",
+        )
+        .unwrap();
+        assert_eq!(options.input_policy, FirewallAction::Drop);
+        assert_eq!(options.output_policy, FirewallAction::Accept);
+        assert_eq!(
+            options.rules[1],
+            FirewallRule {
+                id: Some("ssh".into()),
+                direction: FirewallDirection::Input,
+                action: FirewallAction::Accept,
+                protocol: Some(FirewallProtocol::Tcp),
+                remote_addr: Some("10.0.0.0/8".into()),
+                local_port: Some(22),
+                remote_port: None,
+                state: None,
+            }
+        );
+        assert_eq!(options.rules[0].state, Some(FirewallState::Established));
+        assert!(FirewallOptions::from_str("rules: [{action: drop}]").is_err());
+        assert!(FirewallOptions::from_str("input_policy: reject").is_err());
+    }
+
+    #[test]
+    fn test_nat_options() {
+        assert_eq!(
+            serde_yaml::from_str::<NatOptions>("subnet: 192.168.1.0").unwrap(),
+            NatOptions {
+                subnet: std::net::Ipv4Addr::new(192, 168, 1, 0),
+                prefix_len: 24,
+                mapping: NatMapping::FullCone,
+                timeout: units::Time::new(2, units::TimePrefix::Min),
+                hairpinning: false,
+            }
+        );
+        assert_eq!(
+            serde_yaml::from_str::<NatOptions>(
+                "{subnet: 10.0.0.0, prefix_len: 8, mapping: symmetric, timeout: 30s, hairpinning: true}"
+            )
+            .unwrap(),
+            NatOptions {
+                subnet: std::net::Ipv4Addr::new(10, 0, 0, 0),
+                prefix_len: 8,
+                mapping: NatMapping::Symmetric,
+                timeout: units::Time::new(30, units::TimePrefix::Sec),
+                hairpinning: true,
+            }
+        );
+        assert!(serde_yaml::from_str::<NatOptions>("mapping: symmetric").is_err());
+        assert!(serde_yaml::from_str::<NatOptions>("{subnet: 10.0.0.0, mapping: cone}").is_err());
+    }
+
+    #[test]
+    fn test_middlebox_options() {
+        let options = serde_yaml::from_str::<MiddleboxOptions>(
+            "
+            intercept: [10.0.0.0/8]
+            rules:
+            - {id: censor, action: reset, protocol: tcp, dst_port: 80, payload: forbidden}
+            - {action: delay, delay: 50 ms, probability: 0.5}
+            - {action: rewrite, rewrite: {dst_addr: 11.0.0.1, dst_port: 8080}}
+            ",
+        )
+        .unwrap();
+        assert_eq!(options.intercept, ["10.0.0.0/8"]);
+        assert_eq!(
+            options.rules[0],
+            MiddleboxRule {
+                id: Some("censor".into()),
+                action: MiddleboxAction::Reset,
+                protocol: Some(FirewallProtocol::Tcp),
+                src_addr: None,
+                dst_addr: None,
+                src_port: None,
+                dst_port: Some(80),
+                payload: Some("forbidden".into()),
+                probability: 1.0,
+                delay: None,
+                rewrite: None,
+            }
+        );
+        assert_eq!(options.rules[1].probability, 0.5);
+        assert_eq!(
+            options.rules[1].delay,
+            Some(units::Time::new(50, units::TimePrefix::Milli))
+        );
+        assert_eq!(
+            options.rules[2].rewrite,
+            Some(MiddleboxRewrite {
+                src_addr: None,
+                src_port: None,
+                dst_addr: Some(std::net::Ipv4Addr::new(11, 0, 0, 1)),
+                dst_port: Some(8080),
+            })
+        );
+        assert!(serde_yaml::from_str::<MiddleboxOptions>("rules: []").is_err());
+        assert!(serde_yaml::from_str::<MiddleboxOptions>(
+            "{intercept: [], rules: [{action: inject}]}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_dns_server_options() {
+        let options = serde_yaml::from_str::<DnsServerOptions>(
+            "
+            address: 1.1.1.1
+            ttl: 60s
+            records:
+            - {type: A, name: www.example.com, address: 11.0.0.1}
+            - {type: CNAME, name: example.com, target: www.example.com, ttl: 10s}
+            - {type: SRV, name: _http._tcp.example.com, target: www.example.com, port: 80}
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(options.address, std::net::Ipv4Addr::new(1, 1, 1, 1));
+        assert_eq!(
+            options.latency,
+            units::Time::new(1, units::TimePrefix::Milli)
+        );
+        assert_eq!(options.ttl, units::Time::new(60, units::TimePrefix::Sec));
+        assert_eq!(
+            options.records[1],
+            DnsRecord::Cname {
+                name: "example.com".into(),
+                target: "www.example.com".into(),
+                ttl: Some(units::Time::new(10, units::TimePrefix::Sec)),
+            }
+        );
+        assert_eq!(
+            options.records[2],
+            DnsRecord::Srv {
+                name: "_http._tcp.example.com".into(),
+                target: "www.example.com".into(),
+                port: 80,
+                priority: 0,
+                weight: 0,
+                ttl: None,
+            }
+        );
+
+        assert!(serde_yaml::from_str::<DnsRecord>("{type: MX, name: example.com}").is_err());
+        assert!(serde_yaml::from_str::<DnsRecord>("{type: A, name: x, address: ::1}").is_err());
+    }
+
+    #[test]
+    fn test_process_environment() {
+        let options = serde_yaml::from_str::<ProcessOptions>(
+            "{path: a, environment: {NAME: x, PORT: 80, DEBUG: true, RATE: 1.5}}",
+        )
+        .unwrap();
+
+        let environment: Vec<_> = options
+            .environment
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            environment,
+            [
+                ("DEBUG", "true"),
+                ("NAME", "x"),
+                ("PORT", "80"),
+                ("RATE", "1.5")
+            ]
+        );
+
+        for invalid in ["{A: [1]}", "{A: {B: 1}}", "{A: null}"] {
+            let yaml = format!("{{path: a, environment: {invalid}}}");
+            assert!(serde_yaml::from_str::<ProcessOptions>(&yaml).is_err());
+        }
+    }
+
+    #[test]
+    fn test_restart_options() {
+        let options = serde_yaml::from_str::<ProcessOptions>(
+            "{path: a, restart: {policy: on-failure, max_restarts: 3, max_delay: 10 s}}",
+        )
+        .unwrap();
+        assert_eq!(
+            options.restart,
+            Some(RestartOptions {
+                policy: RestartPolicy::OnFailure,
+                max_restarts: Some(3),
+                delay: units::Time::new(1, units::TimePrefix::Sec),
+                backoff: 2.0,
+                max_delay: Some(units::Time::new(10, units::TimePrefix::Sec)),
+            })
+        );
+
+        let options = serde_yaml::from_str::<ProcessOptions>("{path: a}").unwrap();
+        assert_eq!(options.restart, None);
+
+        assert!(serde_yaml::from_str::<ProcessOptions>("{path: a, restart: {}}").is_err());
+        assert!(
+            serde_yaml::from_str::<ProcessOptions>("{path: a, restart: {policy: never}}").is_err()
+        );
+    }
+
+    #[test]
+    fn test_process_signals() {
+        let options = serde_yaml::from_str::<ProcessOptions>(
+            "{path: a, signals: [{time: 300 s, signal: SIGHUP}, {time: 600 s, signal: 15}]}",
+        )
+        .unwrap();
+        assert_eq!(
+            options.signals,
+            [
+                ProcessSignalOptions {
+                    time: units::Time::new(300, units::TimePrefix::Sec),
+                    signal: nix::sys::signal::Signal::SIGHUP.into(),
+                },
+                ProcessSignalOptions {
+                    time: units::Time::new(600, units::TimePrefix::Sec),
+                    signal: nix::sys::signal::Signal::SIGTERM.into(),
+                },
+            ]
+        );
+
+        assert!(serde_yaml::from_str::<ProcessOptions>("{path: a, signals: [{time: 1}]}").is_err());
+    }
+
+    #[test]
+    fn test_start_after() {
+        let options = serde_yaml::from_str::<ProcessOptions>(
+            "{path: a, start_after: [{exited: 0}, {running: {process: 1, duration: 5 s}}, {listening: 80}]}",
+        )
+        .unwrap();
+        assert_eq!(
+            options.start_after,
+            [
+                StartCondition::Exited(0),
+                StartCondition::Running {
+                    process: 1,
+                    duration: units::Time::new(5, units::TimePrefix::Sec),
+                },
+                StartCondition::Listening(80),
+            ]
+        );
+
+        assert!(
+            serde_yaml::from_str::<ProcessOptions>("{path: a, start_after: [{started: 0}]}")
+                .is_err()
+        );
+        assert!(serde_yaml::from_str::<ProcessOptions>(
+            "{path: a, start_after: [{listening: -1}]}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_selftest_command() {
+        // the configuration file isn't required when running a command
+        let cli = CliOptions::try_parse_from(["shadow", "selftest"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Selftest { test_dir: None })
+        ));
+        assert_eq!(cli.config, None);
+
+        let cli = CliOptions::try_parse_from(["shadow", "selftest", "target/debug"]).unwrap();
+        let Some(Command::Selftest { test_dir }) = cli.command else {
+            panic!("Expected the selftest command");
+        };
+        assert_eq!(test_dir, Some("target/debug".into()));
+
+        // the configuration file is still required without a command
+        assert!(CliOptions::try_parse_from(["shadow"]).is_err());
+    }
+
+    #[test]
+    fn test_topology_generate_command() {
+        let cli = CliOptions::try_parse_from([
+            "shadow",
+            "topology",
+            "generate",
+            "{type: star, nodes: 10}",
+        ])
+        .unwrap();
+        let Some(Command::Topology {
+            command: TopologyCommand::Generate { topology },
+        }) = cli.command
+        else {
+            panic!("Expected the topology generate command");
+        };
+        assert_eq!(topology.kind, TopologyKind::Star { nodes: 10 });
+
+        assert!(
+            CliOptions::try_parse_from(["shadow", "topology", "generate", "type: star"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_command() {
+        // options for the configuration are given before the command
+        let cli =
+            CliOptions::try_parse_from(["shadow", "--set", "general.seed=2", "validate", "-"])
+                .unwrap();
+        assert!(matches!(cli.command, Some(Command::Validate { ref config }) if config == "-"));
+        assert_eq!(cli.overrides.len(), 1);
+
+        assert!(CliOptions::try_parse_from(["shadow", "validate"]).is_err());
+    }
+
+    #[test]
+    fn test_print_config_command() {
+        let cli = CliOptions::try_parse_from(["shadow", "print-config", "shadow.yaml"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::PrintConfig {
+                resolved: false,
+                ..
+            })
+        ));
+
+        let cli =
+            CliOptions::try_parse_from(["shadow", "print-config", "--resolved", "shadow.yaml"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::PrintConfig { resolved: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_sweep_command() {
+        let cli = CliOptions::try_parse_from([
+            "shadow",
+            "sweep",
+            "-p",
+            "a=1,2",
+            "--param",
+            "b=1..3",
+            "-j",
+            "2",
+            "shadow.yaml",
+        ])
+        .unwrap();
+        let Some(Command::Sweep {
+            parameters,
+            jobs,
+            config,
+        }) = cli.command
+        else {
+            panic!("Expected the sweep command");
+        };
+        assert_eq!(parameters.len(), 2);
+        assert_eq!(jobs, 2);
+        assert_eq!(config, "shadow.yaml");
+
+        // a sweep needs at least one parameter
+        assert!(CliOptions::try_parse_from(["shadow", "sweep", "shadow.yaml"]).is_err());
+        assert!(CliOptions::try_parse_from([
+            "shadow",
+            "sweep",
+            "-p",
+            "a=1",
+            "-j",
+            "0",
+            "shadow.yaml"
+        ])
+        .is_err());
+    }
 }
@@ -0,0 +1,278 @@
+//! A UNIX socket for controlling a running simulation, created at the path given by the
+//! `general.control_socket` option. Clients send one command per line, and each command is
+//! answered with one line of JSON. The commands are:
+//!
+//! - `status`: get the simulation's status.
+//! - `pause`: pause the simulation at the end of the current scheduling round.
+//! - `resume`: resume a paused simulation.
+//! - `stop`: stop the simulation at the end of the current scheduling round, as if the stop time
+//!   was reached.
+//!
+//! Each command is answered with the simulation's status after the command, or an object with an
+//! `error` message if the command is invalid. The simulation is only paused and stopped between
+//! scheduling rounds, so controlling a simulation doesn't change its results other than the time
+//! at which it stops.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use anyhow::Context;
+use serde::Serialize;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+/// The status of the simulation at the end of the latest scheduling round.
+#[derive(Debug, Clone, Serialize)]
+struct Status {
+    /// The simulated time, in nanoseconds.
+    sim_time_ns: u64,
+    /// The time at which the simulation will stop if it's not stopped early, in nanoseconds.
+    stop_time_ns: u64,
+    /// The number of scheduling rounds that have been run.
+    rounds: u64,
+    /// The number of events that have been executed.
+    events: u64,
+    /// Whether the simulation is paused, or will be paused at the end of the current round.
+    paused: bool,
+    /// Whether the simulation will stop at the end of the current round.
+    stopping: bool,
+    hosts: Vec<HostStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct HostStatus {
+    name: String,
+    /// The number of events that the host has executed.
+    events: u64,
+}
+
+#[derive(Debug)]
+struct State {
+    status: Status,
+    /// Set when the socket is being closed, which stops accepting connections.
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    /// Notified when the simulation is resumed or stopped.
+    changed: Condvar,
+}
+
+impl Shared {
+    fn command(&self, command: &str) -> Result<Status, String> {
+        let mut state = self.state.lock().unwrap();
+        match command {
+            "status" => {}
+            "pause" => state.status.paused = true,
+            "resume" => state.status.paused = false,
+            "stop" => state.status.stopping = true,
+            x => return Err(format!("Unknown command '{x}'")),
+        }
+        self.changed.notify_all();
+        Ok(state.status.clone())
+    }
+}
+
+#[derive(Debug)]
+pub struct ControlSocket {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    listener_thread: Option<JoinHandle<()>>,
+}
+
+impl ControlSocket {
+    /// Create the socket at `path`, which must not already exist, for a simulation of the hosts
+    /// named `hosts` that ends at `stop_time`.
+    pub fn bind(
+        path: &Path,
+        hosts: impl IntoIterator<Item = String>,
+        stop_time: SimulationTime,
+    ) -> anyhow::Result<Self> {
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Could not create the socket '{}'", path.display()))?;
+
+        let status = Status {
+            sim_time_ns: 0,
+            stop_time_ns: stop_time.as_nanos().try_into().unwrap(),
+            rounds: 0,
+            events: 0,
+            paused: false,
+            stopping: false,
+            hosts: hosts
+                .into_iter()
+                .map(|name| HostStatus { name, events: 0 })
+                .collect(),
+        };
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                status,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let listener_thread = std::thread::Builder::new()
+            .name("control-socket".into())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || listen(listener, shared)
+            })
+            .context("Could not start the control socket thread")?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            shared,
+            listener_thread: Some(listener_thread),
+        })
+    }
+
+    /// Update the status at the end of a scheduling round, where `host_events` are the event
+    /// counts of the hosts in the order given to [`ControlSocket::bind`]. If the simulation is
+    /// paused, blocks until it's resumed or stopped. Returns true if a stop was requested.
+    pub fn finish_round(
+        &self,
+        sim_time: SimulationTime,
+        events: u64,
+        host_events: impl IntoIterator<Item = u64>,
+    ) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        let status = &mut state.status;
+        status.sim_time_ns = sim_time.as_nanos().try_into().unwrap();
+        status.rounds += 1;
+        status.events = events;
+        for (host, events) in status.hosts.iter_mut().zip(host_events) {
+            host.events = events;
+        }
+
+        if status.paused && !status.stopping {
+            log::info!("Pausing the simulation, as requested on the control socket");
+            state = self
+                .shared
+                .changed
+                .wait_while(state, |x| x.status.paused && !x.status.stopping)
+                .unwrap();
+            log::info!("Resuming the simulation");
+        }
+
+        state.status.stopping
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+
+        // wake up the listener thread so that it sees that the socket is closed
+        if UnixStream::connect(&self.path).is_ok() {
+            if let Some(thread) = self.listener_thread.take() {
+                thread.join().unwrap();
+            }
+        }
+
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!(
+                "Could not remove the control socket '{}': {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Accept connections until the socket is closed, handling each client on its own thread.
+fn listen(listener: UnixListener, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.state.lock().unwrap().closed {
+            break;
+        }
+        let stream = match stream {
+            Ok(x) => x,
+            Err(e) => {
+                log::warn!("Could not accept a control socket connection: {e}");
+                continue;
+            }
+        };
+
+        let shared = Arc::clone(&shared);
+        let result = std::thread::Builder::new()
+            .name("control-client".into())
+            .spawn(move || {
+                if let Err(e) = handle_client(stream, &shared) {
+                    log::debug!("Control socket connection failed: {e}");
+                }
+            });
+        if let Err(e) = result {
+            log::warn!("Could not start a control socket client thread: {e}");
+        }
+    }
+}
+
+fn handle_client(stream: UnixStream, shared: &Shared) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+
+        let response = match shared.command(command) {
+            Ok(status) => serde_json::to_string(&status).unwrap(),
+            Err(e) => serde_json::json!({ "error": e }).to_string(),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(stream: &mut BufReader<UnixStream>, command: &str) -> serde_json::Value {
+        writeln!(stream.get_mut(), "{command}").unwrap();
+        let mut line = String::new();
+        stream.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn test_control_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let socket = ControlSocket::bind(
+            &path,
+            ["client".to_string(), "server".to_string()],
+            SimulationTime::from_secs(10),
+        )
+        .unwrap();
+
+        assert!(!socket.finish_round(SimulationTime::from_secs(1), 30, [10, 20]));
+
+        let mut client = BufReader::new(UnixStream::connect(&path).unwrap());
+        let status = request(&mut client, "status");
+        assert_eq!(status["sim_time_ns"], 1_000_000_000);
+        assert_eq!(status["stop_time_ns"], 10_000_000_000u64);
+        assert_eq!(status["rounds"], 1);
+        assert_eq!(status["events"], 30);
+        assert_eq!(status["hosts"][1]["name"], "server");
+        assert_eq!(status["hosts"][1]["events"], 20);
+        assert_eq!(status["paused"], false);
+
+        assert!(request(&mut client, "jump")["error"].is_string());
+
+        // the round blocks while the simulation is paused, until it's stopped
+        assert_eq!(request(&mut client, "pause")["paused"], true);
+        std::thread::scope(|s| {
+            let round = s.spawn(|| socket.finish_round(SimulationTime::from_secs(2), 40, [20, 20]));
+            assert_eq!(request(&mut client, "stop")["stopping"], true);
+            assert!(round.join().unwrap());
+        });
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
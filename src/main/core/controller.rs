@@ -1,4 +1,5 @@
 use std::io::IsTerminal;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -9,7 +10,12 @@ use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::util::time::TimeParts;
 
-use crate::core::configuration::ConfigOptions;
+use crate::core::checkpoint::Checkpointer;
+use crate::core::configuration::{ConfigOptions, Flatten};
+use crate::core::control_socket::ControlSocket;
+use crate::core::dashboard::DashboardStats;
+use crate::core::distributed::{RemoteManagers, RoundEnd};
+use crate::core::failure::{FailureKind, ResultExt};
 use crate::core::manager::{Manager, ManagerConfig};
 use crate::core::sim_config::SimConfig;
 use crate::core::worker;
@@ -40,10 +46,19 @@ impl<'a> Controller<'a> {
     pub fn run(mut self) -> anyhow::Result<()> {
         let mut sim_config = self.sim_config.take().unwrap();
 
-        let status_logger = self.config.general.progress.unwrap().then(|| {
+        let stderr_is_terminal = std::io::stderr().lock().is_terminal();
+
+        let use_dashboard = self.config.general.dashboard.unwrap();
+        if use_dashboard && !stderr_is_terminal {
+            log::warn!("The dashboard requires stderr to be a terminal. Disabling the dashboard.");
+        }
+        let use_dashboard = use_dashboard && stderr_is_terminal;
+
+        let show_progress = self.config.general.progress.unwrap() || use_dashboard;
+        let status_logger = show_progress.then(|| {
             let state = ShadowStatusBarState::new(self.end_time);
 
-            if std::io::stderr().lock().is_terminal() {
+            if stderr_is_terminal {
                 let redraw_interval = Duration::from_millis(1000);
                 StatusLogger::Bar(StatusBar::new(state, redraw_interval))
             } else {
@@ -51,25 +66,121 @@ impl<'a> Controller<'a> {
             }
         });
 
+        let control_socket = self
+            .config
+            .general
+            .control_socket
+            .flatten_ref()
+            .map(|path| {
+                ControlSocket::bind(
+                    Path::new(path),
+                    sim_config.hosts.iter().map(|x| x.name.clone()),
+                    self.end_time - EmulatedTime::SIMULATION_START,
+                )
+            })
+            .transpose()
+            .context("Failed to create the control socket")?;
+
+        let checkpointer = self
+            .config
+            .general
+            .checkpoint_time
+            .flatten()
+            .map(|time| {
+                let path = self.config.general.checkpoint_path.as_ref().unwrap();
+                Checkpointer::new(Path::new(path), Duration::from(time).try_into().unwrap())
+            })
+            .transpose()
+            .context("Failed to configure the checkpoint")
+            .failure_kind(FailureKind::Config)?;
+
+        // the other managers, if the hosts are partitioned across several managers
+        let remote_managers = match self.config.general.managers.as_deref() {
+            Some(addresses) if !addresses.is_empty() => {
+                let index = usize::try_from(self.config.general.manager_index.unwrap()).unwrap();
+                if index >= addresses.len() {
+                    return Err(anyhow::anyhow!(
+                        "The manager index {index} must be less than the number of managers ({})",
+                        addresses.len()
+                    ))
+                    .failure_kind(FailureKind::Config);
+                }
+                if addresses.len() > sim_config.hosts.len() {
+                    return Err(anyhow::anyhow!(
+                        "There are more managers ({}) than hosts ({})",
+                        addresses.len(),
+                        sim_config.hosts.len()
+                    ))
+                    .failure_kind(FailureKind::Config);
+                }
+                if checkpointer.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Checkpoints aren't supported when there are several managers"
+                    ))
+                    .failure_kind(FailureKind::Config);
+                }
+
+                let remote_managers =
+                    RemoteManagers::connect(addresses, index, sim_config.hosts.len())
+                        .context("Failed to connect to the other managers")?;
+                Some(remote_managers)
+            }
+            _ => None,
+        };
+
         let manager_config = ManagerConfig {
             random: Xoshiro256PlusPlus::from_rng(&mut sim_config.random).unwrap(),
             ip_assignment: sim_config.ip_assignment,
-            routing_info: sim_config.routing_info,
+            routing: sim_config.routing,
             host_bandwidths: sim_config.host_bandwidths,
             hosts: sim_config.hosts,
+            phases: sim_config.phases,
+            use_dashboard,
+            stats_sinks: sim_config.stats_sinks,
+            stop_conditions: sim_config.stop_conditions,
+            output_limits: sim_config.output_limits,
+            control_socket,
+            checkpointer,
+            remote_managers,
         };
 
         let manager = Manager::new(manager_config, &self, self.config, self.end_time)
             .context("Failed to initialize the manager")?;
 
         log::info!("Running simulation");
-        let num_plugin_errors = manager.run(status_logger.as_ref().map(|x| x.status()))?;
+        let plugin_errors = manager.run(status_logger.as_ref().map(|x| x.status()))?;
         log::info!("Finished simulation");
 
+        if let Some(reason) = plugin_errors.checkpoint_failure {
+            return Err(anyhow::anyhow!(reason)).failure_kind(FailureKind::Checkpoint);
+        }
+
+        if let Some(reason) = plugin_errors.remote_manager_failure {
+            return Err(anyhow::anyhow!(reason)).failure_kind(FailureKind::RemoteManager);
+        }
+
+        if let Some(reason) = plugin_errors.output_limit_failure {
+            return Err(anyhow::anyhow!(reason)).failure_kind(FailureKind::OutputLimit);
+        }
+
+        let num_plugin_errors = plugin_errors.num_plugin_errors;
         if num_plugin_errors > 0 {
-            return Err(anyhow::anyhow!(
-                "{num_plugin_errors} managed processes in unexpected final state"
-            ));
+            let error =
+                anyhow::anyhow!("{num_plugin_errors} managed processes in unexpected final state");
+
+            // an unsupported syscall is a likely reason for a process to fail
+            let unsupported_syscalls = plugin_errors.unsupported_syscalls;
+            if !unsupported_syscalls.is_empty() {
+                let syscalls: Vec<String> =
+                    unsupported_syscalls.iter().map(|x| x.to_string()).collect();
+                return Err(error)
+                    .with_context(|| {
+                        format!("Unsupported syscalls were used: {}", syscalls.join(", "))
+                    })
+                    .failure_kind(FailureKind::UnsupportedSyscall);
+            }
+
+            return Err(error).failure_kind(FailureKind::ManagedProcess);
         }
 
         Ok(())
@@ -81,25 +192,43 @@ pub trait SimController {
     fn manager_finished_current_round(
         &self,
         min_next_event_time: EmulatedTime,
-    ) -> Option<(EmulatedTime, EmulatedTime)>;
+    ) -> anyhow::Result<Option<(EmulatedTime, EmulatedTime)>>;
 }
 
 impl SimController for Controller<'_> {
     fn manager_finished_current_round(
         &self,
         min_next_event_time: EmulatedTime,
-    ) -> Option<(EmulatedTime, EmulatedTime)> {
-        // TODO: once we get multiple managers, we have to block them here until they have all
-        // notified us that they are finished
-
-        let runahead = worker::WORKER_SHARED
-            .borrow()
-            .as_ref()
-            .unwrap()
-            .runahead
-            .get();
+    ) -> anyhow::Result<Option<(EmulatedTime, EmulatedTime)>> {
+        let RoundEnd {
+            next_event_time: min_next_event_time,
+            runahead,
+            stop,
+        } = {
+            let shared = worker::WORKER_SHARED.borrow();
+            let shared = shared.as_ref().unwrap();
+            let round_end = RoundEnd {
+                next_event_time: min_next_event_time,
+                runahead: shared.runahead.get(),
+                stop: shared.stop_conditions.is_met(),
+            };
+
+            // block until all managers have finished the round, and use the earliest next event
+            // and smallest runahead of all managers
+            match &shared.remote_managers {
+                Some(remote_managers) => remote_managers
+                    .finish_round(round_end, &shared.event_queues)
+                    .context("Failed to synchronize with the other managers")?,
+                None => round_end,
+            }
+        };
         assert_ne!(runahead, SimulationTime::ZERO);
 
+        // stop early if a stop condition was met during the round
+        if stop {
+            return Ok(None);
+        }
+
         let new_start = min_next_event_time;
 
         // update the new window end as one interval past the new window start, making sure we don't
@@ -108,7 +237,7 @@ impl SimController for Controller<'_> {
         let new_end = std::cmp::min(new_end, self.end_time);
 
         let continue_running = new_start < new_end;
-        continue_running.then_some((new_start, new_end))
+        Ok(continue_running.then_some((new_start, new_end)))
     }
 }
 
@@ -118,6 +247,8 @@ pub struct ShadowStatusBarState {
     pub current: EmulatedTime,
     end: EmulatedTime,
     pub num_failed_processes: u32,
+    // shown below the progress if the dashboard is enabled
+    pub dashboard: Option<DashboardStats>,
 }
 
 impl std::fmt::Display for ShadowStatusBarState {
@@ -138,7 +269,13 @@ impl std::fmt::Display for ShadowStatusBarState {
             sim_end.fmt_hr_min_sec(),
             realtime.fmt_hr_min_sec(),
             self.num_failed_processes,
-        )
+        )?;
+
+        if let Some(ref dashboard) = self.dashboard {
+            write!(f, "\n{dashboard}")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -149,6 +286,7 @@ impl ShadowStatusBarState {
             current: EmulatedTime::SIMULATION_START,
             end,
             num_failed_processes: 0,
+            dashboard: None,
         }
     }
 }
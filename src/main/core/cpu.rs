@@ -113,6 +113,72 @@ pub fn count_physical_cores() -> u32 {
     physical_cores.len().try_into().unwrap()
 }
 
+/// Get the NUMA node of a CPU from the `node{node}` entry in `/sys/devices/system/cpu/cpu{cpu}/`.
+/// Returns `None` if the kernel doesn't report the CPU's node.
+pub fn node(cpu: u32) -> Option<u32> {
+    let name = format!("/sys/devices/system/cpu/cpu{cpu}");
+    std::fs::read_dir(name)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()
+        })
+}
+
+/// Set the memory policy of the calling thread so that memory is preferably allocated from the
+/// given NUMA node, or from the node of the CPU that the thread is running on if `None`. This only
+/// affects memory that is allocated by the kernel after the call, for example when a page is first
+/// written to.
+pub fn set_preferred_node(node: Option<u32>) -> std::io::Result<()> {
+    // from linux/mempolicy.h
+    const MPOL_DEFAULT: libc::c_int = 0;
+    const MPOL_PREFERRED: libc::c_int = 1;
+
+    let bits = libc::c_ulong::BITS;
+    let (mode, mask) = match node {
+        Some(node) => {
+            let mut mask = vec![0 as libc::c_ulong; (node / bits + 1) as usize];
+            mask[(node / bits) as usize] |= 1 << (node % bits);
+            (MPOL_PREFERRED, mask)
+        }
+        None => (MPOL_DEFAULT, Vec::new()),
+    };
+
+    // the kernel ignores the last bit of `maxnode`, so add one
+    let maxnode = match mask.len() {
+        0 => 0,
+        len => len as libc::c_ulong * libc::c_ulong::from(bits) + 1,
+    };
+
+    let mask_ptr = if mask.is_empty() {
+        std::ptr::null()
+    } else {
+        mask.as_ptr()
+    };
+
+    // the mask is valid for `maxnode - 1` bits and is only read by the kernel
+    let rv = unsafe { libc::syscall(libc::SYS_set_mempolicy, mode, mask_ptr, maxnode) };
+    if rv != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Assign items to the NUMA nodes of the worker threads, where `thread_nodes` is the node of each
+/// thread. The item at position `i` is assigned to the node of thread `i % thread_nodes.len()`,
+/// so that each node gets a share of the items proportional to its number of threads.
+pub fn assign_nodes(thread_nodes: &[u32], num_items: usize) -> Vec<u32> {
+    (0..num_items)
+        .map(|i| thread_nodes[i % thread_nodes.len()])
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,4 +209,20 @@ mod tests {
         check("1-1,0,5", &[1, 0, 5]);
         check("1-0", &[]);
     }
+
+    #[test]
+    fn test_assign_nodes() {
+        assert_eq!(assign_nodes(&[0, 0, 1], 5), [0, 0, 1, 0, 0]);
+        assert_eq!(assign_nodes(&[1], 2), [1, 1]);
+        assert!(assign_nodes(&[0, 1], 0).is_empty());
+    }
+
+    #[test]
+    fn test_set_preferred_node() {
+        // memory policies may not be supported or allowed (for example in some containers)
+        if set_preferred_node(Some(0)).is_err() {
+            return;
+        }
+        set_preferred_node(None).unwrap();
+    }
 }
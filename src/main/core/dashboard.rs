@@ -0,0 +1,243 @@
+//! The live dashboard shown below the log output when `general.dashboard` is enabled.
+//!
+//! The manager periodically samples the number of events executed by each worker thread and each
+//! host, along with the system memory usage, and [`DashboardSampler`] turns these samples into
+//! the rates shown by [`DashboardStats`].
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+/// The number of busiest hosts to show.
+pub const NUM_TOP_HOSTS: usize = 5;
+
+/// Memory samples older than this are discarded when calculating the memory growth rate.
+const MEM_GROWTH_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Sample {
+    time: Instant,
+    sim_time: EmulatedTime,
+    thread_events: Vec<u64>,
+    host_events: Vec<u64>,
+}
+
+/// Calculates dashboard statistics from cumulative event counts.
+#[derive(Debug)]
+pub struct DashboardSampler {
+    host_names: Vec<String>,
+    last_sample: Option<Sample>,
+    // (time, bytes used) for the last `MEM_GROWTH_WINDOW` of samples
+    mem_samples: VecDeque<(Instant, u64)>,
+}
+
+impl DashboardSampler {
+    /// `host_names` must be indexed by the host's id.
+    pub fn new(host_names: Vec<String>) -> Self {
+        Self {
+            host_names,
+            last_sample: None,
+            mem_samples: VecDeque::new(),
+        }
+    }
+
+    /// Take a new sample. The event counts are the total number of events executed by each worker
+    /// thread and each host since the start of the simulation. Rates are calculated over the time
+    /// since the previous sample.
+    pub fn sample(
+        &mut self,
+        time: Instant,
+        sim_time: EmulatedTime,
+        thread_events: Vec<u64>,
+        host_events: Vec<u64>,
+        mem_used: Option<u64>,
+    ) -> DashboardStats {
+        let sample = Sample {
+            time,
+            sim_time,
+            thread_events,
+            host_events,
+        };
+
+        let mut stats = DashboardStats {
+            thread_event_rates: vec![0.0; sample.thread_events.len()],
+            sim_speed: None,
+            top_hosts: Vec::new(),
+            mem_used,
+            mem_growth_per_min: None,
+        };
+
+        if let Some(last) = &self.last_sample {
+            let elapsed = time.duration_since(last.time).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate = |new: u64, old: u64| new.saturating_sub(old) as f64 / elapsed;
+
+                stats.thread_event_rates = sample
+                    .thread_events
+                    .iter()
+                    .zip(last.thread_events.iter().chain(std::iter::repeat(&0)))
+                    .map(|(new, old)| rate(*new, *old))
+                    .collect();
+
+                let sim_elapsed = sim_time.saturating_duration_since(&last.sim_time);
+                stats.sim_speed = Some(Duration::from(sim_elapsed).as_secs_f64() / elapsed);
+
+                let mut host_rates: Vec<(usize, f64)> = sample
+                    .host_events
+                    .iter()
+                    .zip(last.host_events.iter().chain(std::iter::repeat(&0)))
+                    .map(|(new, old)| rate(*new, *old))
+                    .enumerate()
+                    .filter(|(_, rate)| *rate > 0.0)
+                    .collect();
+
+                // sort by decreasing rate, and by host id for equal rates
+                host_rates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                stats.top_hosts = host_rates
+                    .into_iter()
+                    .take(NUM_TOP_HOSTS)
+                    .map(|(idx, rate)| (self.host_names[idx].clone(), rate))
+                    .collect();
+            }
+        }
+
+        if let Some(mem_used) = mem_used {
+            while let Some((oldest, _)) = self.mem_samples.front() {
+                if time.duration_since(*oldest) <= MEM_GROWTH_WINDOW {
+                    break;
+                }
+                self.mem_samples.pop_front();
+            }
+
+            if let Some((oldest, oldest_mem)) = self.mem_samples.front() {
+                let elapsed = time.duration_since(*oldest).as_secs_f64();
+                if elapsed > 0.0 {
+                    let growth = mem_used as f64 - *oldest_mem as f64;
+                    stats.mem_growth_per_min = Some(growth / elapsed * 60.0);
+                }
+            }
+
+            self.mem_samples.push_back((time, mem_used));
+        }
+
+        self.last_sample = Some(sample);
+        stats
+    }
+}
+
+/// Statistics shown in the dashboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashboardStats {
+    /// Events per second executed by each worker thread.
+    pub thread_event_rates: Vec<f64>,
+    /// Simulated seconds per real second.
+    pub sim_speed: Option<f64>,
+    /// The busiest hosts and their events per second, busiest first.
+    pub top_hosts: Vec<(String, f64)>,
+    /// System memory in use, in bytes.
+    pub mem_used: Option<u64>,
+    /// Change in system memory use per minute, in bytes.
+    pub mem_growth_per_min: Option<f64>,
+}
+
+impl std::fmt::Display for DashboardStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MIB: f64 = (1024 * 1024) as f64;
+
+        let total_rate: f64 = self.thread_event_rates.iter().sum();
+        write!(f, "events/s: {total_rate:.0} total")?;
+        if let Some(speed) = self.sim_speed {
+            write!(f, ", speed: {speed:.3}x realtime")?;
+        }
+        writeln!(f)?;
+
+        write!(f, "workers:")?;
+        for (i, rate) in self.thread_event_rates.iter().enumerate() {
+            write!(f, " [{i}] {rate:.0}")?;
+        }
+        writeln!(f)?;
+
+        write!(f, "busiest hosts:")?;
+        if self.top_hosts.is_empty() {
+            write!(f, " -")?;
+        }
+        for (name, rate) in &self.top_hosts {
+            write!(f, " {name} ({rate:.0}/s)")?;
+        }
+        writeln!(f)?;
+
+        write!(f, "memory used: ")?;
+        match self.mem_used {
+            Some(used) => write!(f, "{:.0} MiB", used as f64 / MIB)?,
+            None => write!(f, "unknown")?,
+        }
+        if let Some(growth) = self.mem_growth_per_min {
+            write!(f, " ({:+.1} MiB/min)", growth / MIB)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+    use super::*;
+
+    #[test]
+    fn test_rates() {
+        let names = ["a", "b", "c"].iter().map(|x| x.to_string()).collect();
+        let mut sampler = DashboardSampler::new(names);
+        let start = Instant::now();
+        let sim_start = EmulatedTime::SIMULATION_START;
+
+        let stats = sampler.sample(start, sim_start, vec![0, 0], vec![0, 0, 0], Some(1000));
+        assert_eq!(stats.thread_event_rates, [0.0, 0.0]);
+        assert_eq!(stats.sim_speed, None);
+        assert!(stats.top_hosts.is_empty());
+        assert_eq!(stats.mem_growth_per_min, None);
+
+        let stats = sampler.sample(
+            start + Duration::from_secs(2),
+            sim_start + SimulationTime::from_secs(1),
+            vec![10, 30],
+            vec![4, 0, 36],
+            Some(1200),
+        );
+        assert_eq!(stats.thread_event_rates, [5.0, 15.0]);
+        assert_eq!(stats.sim_speed, Some(0.5));
+        assert_eq!(
+            stats.top_hosts,
+            [("c".to_string(), 18.0), ("a".to_string(), 2.0)]
+        );
+        assert_eq!(stats.mem_growth_per_min, Some(6000.0));
+    }
+
+    #[test]
+    fn test_mem_growth_window() {
+        let mut sampler = DashboardSampler::new(Vec::new());
+        let start = Instant::now();
+        let sim_start = EmulatedTime::SIMULATION_START;
+
+        sampler.sample(start, sim_start, vec![], vec![], Some(0));
+        sampler.sample(
+            start + Duration::from_secs(30),
+            sim_start,
+            vec![],
+            vec![],
+            Some(600),
+        );
+
+        // the first sample is now outside of the window
+        let stats = sampler.sample(
+            start + Duration::from_secs(90),
+            sim_start,
+            vec![],
+            vec![],
+            Some(600),
+        );
+        assert_eq!(stats.mem_growth_per_min, Some(0.0));
+    }
+}
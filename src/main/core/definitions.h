@@ -37,15 +37,12 @@ typedef struct Host Host;
 #define CONFIG_TCP_RMEM_MAX 6291456
 
 /**
- * Default initial retransmission timeout and ranges,
- * TCP_TIMEOUT_INIT=1000ms, TCP_RTO_MIN=200ms and TCP_RTO_MAX=120000ms from net/tcp.h
- *
  * HZ is about 1 second, i.e., about 1000 milliseconds
+ *
+ * The retransmission timeouts are set with the 'tcp_rto_init', 'tcp_rto_min', and 'tcp_rto_max'
+ * host options.
  */
 #define NET_TCP_HZ 1000
-#define CONFIG_TCP_RTO_INIT NET_TCP_HZ
-#define CONFIG_TCP_RTO_MIN (NET_TCP_HZ / 5)
-#define CONFIG_TCP_RTO_MAX (NET_TCP_HZ * 120)
 
 /**
  * Default delay ack times, from net/tcp.h
@@ -106,6 +103,11 @@ typedef struct Host Host;
  */
 #define CONFIG_HEADER_SIZE_TCP 20
 
+/**
+ * Default ICMP header size in bytes. This is the size of the echo request and reply headers.
+ */
+#define CONFIG_HEADER_SIZE_ICMP 8
+
 /**
  * Header size in bytes of a routable packet with UDP encapsulation; includes
  * the IP and UDP headers but excludes the ethernet header and packet payload.
@@ -118,6 +120,12 @@ typedef struct Host Host;
  */
 #define CONFIG_HEADER_SIZE_TCPIP (CONFIG_HEADER_SIZE_TCP + CONFIG_HEADER_SIZE_IP)
 
+/**
+ * Header size in bytes of a routable packet with ICMP encapsulation; includes
+ * the IP and ICMP headers but excludes the ethernet header and packet payload.
+ */
+#define CONFIG_HEADER_SIZE_ICMPIP (CONFIG_HEADER_SIZE_ICMP + CONFIG_HEADER_SIZE_IP)
+
 /**
  * Maximum size of an IP packet without fragmenting over Ethernetv2
  */
@@ -133,9 +141,4 @@ typedef struct Host Host;
  */
 #define CONFIG_DATAGRAM_MAX_SIZE 65507
 
-/**
- * Delay in nanoseconds for a TCP close timer.
- */
-#define CONFIG_TCPCLOSETIMER_DELAY (60 * SIMTIME_ONE_SECOND)
-
 #endif /* SHD_DEFINITIONS_H_ */
@@ -0,0 +1,491 @@
+//! Distributed simulations, where the hosts are partitioned across several Shadow processes
+//! ("managers") that may run on different machines.
+//!
+//! Each manager runs the hosts whose host ID modulo the number of managers is the manager's
+//! index, and is connected to every other manager over TCP. The managers run the same scheduling
+//! rounds: at the end of each round every manager sends each other manager the time of its next
+//! event, its runahead, whether a stop condition was met, and the packets that its hosts sent to
+//! the other manager's hosts during the round. The next round starts at the earliest next event
+//! of any manager, and lasts for the smallest runahead of any manager. The runahead is at most the
+//! smallest latency between any two hosts, so a packet sent during a round never arrives before
+//! the end of the round, which is when the managers exchange packets. This is the same
+//! conservative synchronization that is used between the worker threads of a single manager.
+//!
+//! The connections between managers aren't authenticated, so the managers' addresses must only be
+//! reachable by the other managers of the simulation.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::HostId;
+
+use crate::core::work::event::{Event, EventData};
+use crate::core::work::event_queue::EventQueue;
+use crate::network::packet::PacketRc;
+
+/// How long to wait for the other managers to start.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The largest message that a manager sends or accepts.
+const MAX_FRAME_LEN: u64 = 1 << 30;
+
+/// The state of a manager at the end of a scheduling round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundEnd {
+    pub next_event_time: EmulatedTime,
+    pub runahead: SimulationTime,
+    /// Whether a stop condition was met during the round.
+    pub stop: bool,
+}
+
+impl RoundEnd {
+    /// The state of all managers, given the states of two of them.
+    fn combine(self, other: Self) -> Self {
+        Self {
+            next_event_time: std::cmp::min(self.next_event_time, other.next_event_time),
+            runahead: std::cmp::min(self.runahead, other.runahead),
+            stop: self.stop || other.stop,
+        }
+    }
+}
+
+/// A packet sent to a host of another manager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RemotePacket {
+    time: EmulatedTime,
+    dst_host_id: HostId,
+    src_host_id: HostId,
+    src_host_event_id: u64,
+    /// The packet as an IPv4 packet.
+    bytes: Vec<u8>,
+}
+
+/// The connections of this manager to the other managers of a distributed simulation.
+#[derive(Debug)]
+pub struct RemoteManagers {
+    index: usize,
+    /// The connections to each manager, or `None` for this manager.
+    peers: Vec<Option<TcpStream>>,
+    /// The packets sent to each manager's hosts during the current round.
+    outgoing: Mutex<Vec<Vec<RemotePacket>>>,
+}
+
+impl RemoteManagers {
+    /// Connect to the managers at `addresses`, where this manager is the manager at `index`. Each
+    /// manager listens on its own address, and connects to the managers with smaller indexes.
+    /// All managers must be run with the same configuration, which has `num_hosts` hosts.
+    pub fn connect(addresses: &[String], index: usize, num_hosts: usize) -> anyhow::Result<Self> {
+        assert!(index < addresses.len());
+
+        let listener = TcpListener::bind(&addresses[index])
+            .with_context(|| format!("Failed to listen on '{}'", addresses[index]))?;
+
+        let mut peers: Vec<Option<TcpStream>> = addresses.iter().map(|_| None).collect();
+
+        for (i, address) in addresses.iter().enumerate().take(index) {
+            let mut stream = connect_with_retries(address)
+                .with_context(|| format!("Failed to connect to manager {i} at '{address}'"))?;
+            write_hello(&mut stream, index, num_hosts)?;
+            peers[i] = Some(stream);
+        }
+
+        for _ in index + 1..addresses.len() {
+            let (mut stream, _) = listener.accept()?;
+            let (i, peer_num_hosts) = read_hello(&mut stream)?;
+
+            if i <= index || i >= addresses.len() || peers[i].is_some() {
+                return Err(anyhow::anyhow!("Unexpected connection from manager {i}"));
+            }
+            if peer_num_hosts != num_hosts {
+                return Err(anyhow::anyhow!(
+                    "Manager {i} has {peer_num_hosts} hosts, but this manager has {num_hosts}; \
+                     all managers must use the same configuration"
+                ));
+            }
+
+            peers[i] = Some(stream);
+        }
+
+        for stream in peers.iter().flatten() {
+            stream.set_nodelay(true)?;
+        }
+
+        log::info!(
+            "Connected to {} other managers as manager {index}",
+            addresses.len() - 1
+        );
+
+        Ok(Self {
+            index,
+            outgoing: Mutex::new(addresses.iter().map(|_| Vec::new()).collect()),
+            peers,
+        })
+    }
+
+    /// The index of the manager that runs the host.
+    fn manager_of(&self, host_id: HostId) -> usize {
+        usize::try_from(u32::from(host_id)).unwrap() % self.peers.len()
+    }
+
+    /// Whether the host is run by this manager.
+    pub fn is_local(&self, host_id: HostId) -> bool {
+        self.manager_of(host_id) == self.index
+    }
+
+    /// Send a packet event to a host of another manager at the end of the round. Panics if the
+    /// destination host is run by this manager, or if the event isn't a packet event.
+    pub fn push(&self, dst_host_id: HostId, event: Event) {
+        let manager = self.manager_of(dst_host_id);
+        assert_ne!(manager, self.index);
+
+        let time = event.time();
+        let EventData::Packet(data) = event.data() else {
+            panic!("Only packet events can be sent to other managers");
+        };
+        let src_host_id = data.src_host_id();
+        let src_host_event_id = data.src_host_event_id();
+        let packet = PacketRc::from(data);
+
+        let packet = RemotePacket {
+            time,
+            dst_host_id,
+            src_host_id,
+            src_host_event_id,
+            bytes: packet.to_ipv4_bytes(),
+        };
+        self.outgoing.lock().unwrap()[manager].push(packet);
+    }
+
+    /// Exchange the state at the end of the round and the packets sent during the round with all
+    /// other managers, and push the received packets to the event queues of this manager's hosts.
+    /// Returns the state of all managers. This must be called between scheduling rounds.
+    pub fn finish_round(
+        &self,
+        round_end: RoundEnd,
+        event_queues: &HashMap<HostId, Arc<Mutex<EventQueue>>>,
+    ) -> anyhow::Result<RoundEnd> {
+        let outgoing = {
+            let mut outgoing = self.outgoing.lock().unwrap();
+            let empty = outgoing.iter().map(|_| Vec::new()).collect();
+            std::mem::replace(&mut *outgoing, empty)
+        };
+
+        std::thread::scope(|s| {
+            // send in other threads so that two managers don't block writing to each other
+            let senders: Vec<_> = self
+                .peers
+                .iter()
+                .zip(outgoing)
+                .filter_map(|(peer, packets)| {
+                    let mut peer = peer.as_ref()?;
+                    let message = encode_message(&round_end, &packets);
+                    Some(s.spawn(move || write_frame(&mut peer, &message)))
+                })
+                .collect();
+
+            let received = self.receive_round(round_end, event_queues);
+            if received.is_err() {
+                // the senders may be blocked writing to a manager that isn't reading, and must
+                // return before the thread scope ends
+                for peer in self.peers.iter().flatten() {
+                    let _ = peer.shutdown(Shutdown::Both);
+                }
+            }
+
+            // join all of the senders before returning any error
+            let sent: Vec<_> = senders.into_iter().map(|x| x.join().unwrap()).collect();
+            let combined = received?;
+            for result in sent {
+                result.context("Failed to send the round to another manager")?;
+            }
+
+            Ok(combined)
+        })
+    }
+
+    /// Receive the state at the end of the round and the packets sent during the round from all
+    /// other managers, and push the received packets to the event queues of this manager's hosts.
+    fn receive_round(
+        &self,
+        round_end: RoundEnd,
+        event_queues: &HashMap<HostId, Arc<Mutex<EventQueue>>>,
+    ) -> anyhow::Result<RoundEnd> {
+        let mut combined = round_end;
+
+        for (i, peer) in self.peers.iter().enumerate() {
+            let Some(mut peer) = peer.as_ref() else {
+                continue;
+            };
+
+            let message = read_frame(&mut peer)
+                .with_context(|| format!("Failed to receive the round from manager {i}"))?;
+            let (peer_round_end, packets) = decode_message(&message)
+                .with_context(|| format!("Invalid message from manager {i}"))?;
+
+            combined = combined.combine(peer_round_end);
+
+            for packet in packets {
+                let Some(event_queue) = event_queues.get(&packet.dst_host_id) else {
+                    return Err(anyhow::anyhow!(
+                        "Manager {i} sent a packet to host {:?}, which isn't run by this \
+                         manager",
+                        packet.dst_host_id
+                    ));
+                };
+                let event = PacketRc::from_remote_ipv4_bytes(
+                    &packet.bytes,
+                    packet.src_host_id,
+                    packet.src_host_event_id,
+                )
+                .map(|x| {
+                    Event::new_remote_packet(
+                        x,
+                        packet.time,
+                        packet.src_host_id,
+                        packet.src_host_event_id,
+                    )
+                })
+                .with_context(|| format!("Manager {i} sent an invalid packet"))?;
+                event_queue.lock().unwrap().push(event);
+            }
+        }
+
+        Ok(combined)
+    }
+}
+
+fn connect_with_retries(address: &str) -> std::io::Result<TcpStream> {
+    let start = Instant::now();
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => return Ok(stream),
+            // the other manager may not have started yet
+            Err(_) if start.elapsed() < CONNECT_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(100))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn write_hello(stream: &mut impl Write, index: usize, num_hosts: usize) -> std::io::Result<()> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&u64::try_from(index).unwrap().to_le_bytes());
+    bytes.extend_from_slice(&u64::try_from(num_hosts).unwrap().to_le_bytes());
+    stream.write_all(&bytes)
+}
+
+fn read_hello(stream: &mut impl Read) -> std::io::Result<(usize, usize)> {
+    let mut bytes = [0; 16];
+    stream.read_exact(&mut bytes)?;
+    let index = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let num_hosts = u64::from_le_bytes(bytes[8..].try_into().unwrap());
+    let invalid = |_| std::io::Error::from(std::io::ErrorKind::InvalidData);
+    Ok((
+        index.try_into().map_err(invalid)?,
+        num_hosts.try_into().map_err(invalid)?,
+    ))
+}
+
+/// Write a message, prefixed by its length. Messages larger than [`MAX_FRAME_LEN`] aren't sent.
+fn write_frame(stream: &mut impl Write, message: &[u8]) -> std::io::Result<()> {
+    let len = u64::try_from(message.len()).unwrap();
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("The message of {len} bytes is larger than the maximum of {MAX_FRAME_LEN}"),
+        ));
+    }
+    stream.write_all(&len.to_le_bytes())?;
+    stream.write_all(message)?;
+    stream.flush()
+}
+
+/// Read a message that was written by [`write_frame`]. Messages larger than [`MAX_FRAME_LEN`] are
+/// rejected before they're read.
+fn read_frame(stream: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("The message of {len} bytes is larger than the maximum of {MAX_FRAME_LEN}"),
+        ));
+    }
+    let mut message = vec![0; usize::try_from(len).unwrap()];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn encode_message(round_end: &RoundEnd, packets: &[RemotePacket]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(
+        &EmulatedTime::to_c_emutime(Some(round_end.next_event_time)).to_le_bytes(),
+    );
+    bytes.extend_from_slice(&SimulationTime::to_c_simtime(Some(round_end.runahead)).to_le_bytes());
+    bytes.push(round_end.stop.into());
+    bytes.extend_from_slice(&u64::try_from(packets.len()).unwrap().to_le_bytes());
+
+    for packet in packets {
+        bytes.extend_from_slice(&EmulatedTime::to_c_emutime(Some(packet.time)).to_le_bytes());
+        bytes.extend_from_slice(&u32::from(packet.dst_host_id).to_le_bytes());
+        bytes.extend_from_slice(&u32::from(packet.src_host_id).to_le_bytes());
+        bytes.extend_from_slice(&packet.src_host_event_id.to_le_bytes());
+        bytes.extend_from_slice(&u64::try_from(packet.bytes.len()).unwrap().to_le_bytes());
+        bytes.extend_from_slice(&packet.bytes);
+    }
+
+    bytes
+}
+
+/// Decode a message that was encoded by [`encode_message`]. Returns `None` if the message is
+/// invalid.
+fn decode_message(mut bytes: &[u8]) -> Option<(RoundEnd, Vec<RemotePacket>)> {
+    fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let (head, tail) = (bytes.get(..len)?, bytes.get(len..)?);
+        *bytes = tail;
+        Some(head)
+    }
+    fn take_u64(bytes: &mut &[u8]) -> Option<u64> {
+        Some(u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap()))
+    }
+    fn take_u32(bytes: &mut &[u8]) -> Option<u32> {
+        Some(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+    }
+
+    let round_end = RoundEnd {
+        next_event_time: EmulatedTime::from_c_emutime(take_u64(&mut bytes)?)?,
+        runahead: SimulationTime::from_c_simtime(take_u64(&mut bytes)?)?,
+        stop: take(&mut bytes, 1)?[0] != 0,
+    };
+
+    let num_packets = take_u64(&mut bytes)?;
+    let mut packets = Vec::new();
+    for _ in 0..num_packets {
+        let time = EmulatedTime::from_c_emutime(take_u64(&mut bytes)?)?;
+        let dst_host_id = HostId::from(take_u32(&mut bytes)?);
+        let src_host_id = HostId::from(take_u32(&mut bytes)?);
+        let src_host_event_id = take_u64(&mut bytes)?;
+        let len = take_u64(&mut bytes)?.try_into().ok()?;
+        let packet_bytes = take(&mut bytes, len)?.to_vec();
+        packets.push(RemotePacket {
+            time,
+            dst_host_id,
+            src_host_id,
+            src_host_event_id,
+            bytes: packet_bytes,
+        });
+    }
+
+    bytes.is_empty().then_some((round_end, packets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let round_end = RoundEnd {
+            next_event_time: EmulatedTime::SIMULATION_START + SimulationTime::from_millis(5),
+            runahead: SimulationTime::from_millis(1),
+            stop: true,
+        };
+        let packets = vec![
+            RemotePacket {
+                time: EmulatedTime::SIMULATION_START + SimulationTime::from_millis(6),
+                dst_host_id: HostId::from(3),
+                src_host_id: HostId::from(2),
+                src_host_event_id: 10,
+                bytes: vec![1, 2, 3],
+            },
+            RemotePacket {
+                time: EmulatedTime::SIMULATION_START + SimulationTime::from_millis(7),
+                dst_host_id: HostId::from(5),
+                src_host_id: HostId::from(4),
+                src_host_event_id: 11,
+                bytes: vec![],
+            },
+        ];
+
+        let message = encode_message(&round_end, &packets);
+        assert_eq!(decode_message(&message), Some((round_end, packets)));
+
+        // truncated and extended messages are invalid
+        assert_eq!(decode_message(&message[..message.len() - 1]), None);
+        let mut extended = message.clone();
+        extended.push(0);
+        assert_eq!(decode_message(&extended), None);
+
+        // a message without packets
+        let message = encode_message(&round_end, &[]);
+        assert_eq!(decode_message(&message), Some((round_end, vec![])));
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, &[1, 2, 3]).unwrap();
+        assert_eq!(read_frame(&mut stream.as_slice()).unwrap(), [1, 2, 3]);
+
+        // a length that's too large is rejected without reading the message
+        let stream = (MAX_FRAME_LEN + 1).to_le_bytes();
+        let err = read_frame(&mut stream.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let stream = u64::MAX.to_le_bytes();
+        let err = read_frame(&mut stream.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_finish_round() {
+        // find some unused ports
+        let listeners: Vec<_> = (0..3)
+            .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let addresses: Vec<String> = listeners
+            .iter()
+            .map(|x| x.local_addr().unwrap().to_string())
+            .collect();
+        drop(listeners);
+
+        let round_ends: Vec<_> = std::thread::scope(|s| {
+            let threads: Vec<_> = (0..addresses.len())
+                .map(|index| {
+                    let addresses = &addresses;
+                    s.spawn(move || {
+                        let managers = RemoteManagers::connect(addresses, index, 6).unwrap();
+
+                        let index = u32::try_from(index).unwrap();
+                        assert!(managers.is_local(HostId::from(index)));
+                        assert!(managers.is_local(HostId::from(index + 3)));
+                        assert!(!managers.is_local(HostId::from(index + 1)));
+
+                        let round_end = RoundEnd {
+                            next_event_time: EmulatedTime::SIMULATION_START
+                                + SimulationTime::from_millis((10 - index).into()),
+                            runahead: SimulationTime::from_millis((1 + index).into()),
+                            stop: index == 1,
+                        };
+                        managers.finish_round(round_end, &HashMap::new()).unwrap()
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|x| x.join().unwrap()).collect()
+        });
+
+        let expected = RoundEnd {
+            next_event_time: EmulatedTime::SIMULATION_START + SimulationTime::from_millis(8),
+            runahead: SimulationTime::from_millis(1),
+            stop: true,
+        };
+        assert!(round_ends.iter().all(|x| *x == expected));
+    }
+}
@@ -0,0 +1,226 @@
+//! Classification of the ways that a simulation can fail.
+//!
+//! When Shadow doesn't complete successfully, its exit code identifies the class of failure (see
+//! [`FailureKind`]), and if requested with `--failure-file`, a JSON description of the failure is
+//! written so that orchestration systems can decide whether to retry, fix the configuration, etc.
+//! Keep the exit codes in sync with `docs/exit_codes.md`.
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+
+/// The path that the failure JSON should be written to, if any.
+static FAILURE_FILE: OnceCell<PathBuf> = OnceCell::new();
+
+/// The class of a simulation failure. Each class has its own process exit code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// A failure that hasn't been classified.
+    Other,
+    /// Invalid command line options or configuration file, or the configured data directory
+    /// couldn't be created.
+    Config,
+    /// A managed process ended in an unexpected state after making syscalls that Shadow doesn't
+    /// support.
+    UnsupportedSyscall,
+    /// A managed process ended in an unexpected state.
+    ManagedProcess,
+    /// Shadow couldn't obtain enough resources from the system, such as file descriptors.
+    ResourceLimit,
+    /// Shadow panicked or failed an internal assertion. This is a bug in Shadow.
+    Internal,
+    /// A stdout, stderr, or pcap file reached its maximum size, or the output files reached their
+    /// maximum total size, and the simulation was configured to fail.
+    OutputLimit,
+    /// A checkpoint of the simulation couldn't be written.
+    Checkpoint,
+    /// The connection to another Shadow process of a distributed simulation failed.
+    RemoteManager,
+}
+
+impl FailureKind {
+    /// The exit code of the Shadow process for this failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Config => 2,
+            Self::UnsupportedSyscall => 3,
+            Self::ManagedProcess => 4,
+            Self::ResourceLimit => 5,
+            Self::Internal => 6,
+            Self::OutputLimit => 7,
+            Self::Checkpoint => 8,
+            Self::RemoteManager => 9,
+        }
+    }
+}
+
+impl std::fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Other => "error",
+            Self::Config => "configuration error",
+            Self::UnsupportedSyscall => "unsupported syscall",
+            Self::ManagedProcess => "managed process failure",
+            Self::ResourceLimit => "resource limit",
+            Self::Internal => "internal error",
+            Self::OutputLimit => "output limit exceeded",
+            Self::Checkpoint => "checkpoint failure",
+            Self::RemoteManager => "remote manager failure",
+        };
+        f.write_str(s)
+    }
+}
+
+/// An error that has been assigned a [`FailureKind`]. This is transparent when displayed, so
+/// classifying an error doesn't change its message or its chain of causes.
+#[derive(Debug)]
+struct ClassifiedError {
+    kind: FailureKind,
+    error: anyhow::Error,
+}
+
+impl std::fmt::Display for ClassifiedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for ClassifiedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Extension trait for assigning a [`FailureKind`] to an error.
+pub trait ResultExt<T> {
+    /// Classify the error (if any). If the error is classified again later, the outermost
+    /// classification is used.
+    fn failure_kind(self, kind: FailureKind) -> anyhow::Result<T>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn failure_kind(self, kind: FailureKind) -> anyhow::Result<T> {
+        self.map_err(|e| {
+            anyhow::Error::new(ClassifiedError {
+                kind,
+                error: e.into(),
+            })
+        })
+    }
+}
+
+/// Get the failure class of an error. Errors that haven't been classified are
+/// [`FailureKind::Other`].
+pub fn failure_kind(error: &anyhow::Error) -> FailureKind {
+    error
+        .downcast_ref::<ClassifiedError>()
+        .map(|x| x.kind)
+        .unwrap_or(FailureKind::Other)
+}
+
+/// The machine-readable description of a failure.
+#[derive(Debug, Serialize)]
+pub struct FailureReport {
+    pub kind: FailureKind,
+    pub exit_code: i32,
+    /// The top-level error message.
+    pub message: String,
+    /// The chain of errors that caused the failure, from outermost to innermost.
+    pub causes: Vec<String>,
+}
+
+impl FailureReport {
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let kind = failure_kind(error);
+        Self {
+            kind,
+            exit_code: kind.exit_code(),
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(|x| x.to_string()).collect(),
+        }
+    }
+
+    pub fn from_panic(payload: &(dyn std::any::Any + Send)) -> Self {
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "<unknown panic payload>".to_string()
+        };
+
+        let kind = FailureKind::Internal;
+        Self {
+            kind,
+            exit_code: kind.exit_code(),
+            message,
+            causes: Vec::new(),
+        }
+    }
+
+    /// Write the report to the failure file, if one was configured with [`set_failure_file`].
+    pub fn write(&self) -> anyhow::Result<()> {
+        let Some(path) = FAILURE_FILE.get() else {
+            return Ok(());
+        };
+
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create file '{}'", path.display()))?;
+        serde_json::to_writer_pretty(file, self)
+            .with_context(|| format!("Failed to write failure json to '{}'", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Set the path that the failure JSON will be written to. Can only be set once.
+pub fn set_failure_file(path: PathBuf) {
+    FAILURE_FILE
+        .set(path)
+        .expect("The failure file was already set");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let e: anyhow::Result<()> = Err(anyhow::anyhow!("root"));
+        let e = e.context("middle").failure_kind(FailureKind::Config);
+        let e = e.context("outer").unwrap_err();
+
+        assert_eq!(failure_kind(&e), FailureKind::Config);
+
+        // classifying doesn't change the error messages
+        let report = FailureReport::from_error(&e);
+        assert_eq!(report.exit_code, 2);
+        assert_eq!(report.message, "outer");
+        assert_eq!(report.causes, ["middle", "root"]);
+    }
+
+    #[test]
+    fn test_outermost_classification() {
+        let e: anyhow::Result<()> = Err(anyhow::anyhow!("root"));
+        let e = e
+            .failure_kind(FailureKind::ResourceLimit)
+            .failure_kind(FailureKind::ManagedProcess)
+            .unwrap_err();
+
+        assert_eq!(failure_kind(&e), FailureKind::ManagedProcess);
+    }
+
+    #[test]
+    fn test_unclassified() {
+        let e = anyhow::anyhow!("root");
+        assert_eq!(failure_kind(&e), FailureKind::Other);
+        assert_eq!(FailureReport::from_error(&e).exit_code, 1);
+    }
+}
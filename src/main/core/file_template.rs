@@ -0,0 +1,138 @@
+//! Templates for files that are generated in a host's data directory before its processes start,
+//! so that many hosts can run the same program with their own configuration file.
+//!
+//! A template is text containing `{{name}}` placeholders, which are replaced with properties of
+//! the host when the file is rendered. Placeholders may contain whitespace around the name (for
+//! example `{{ hostname }}`). The supported placeholders are listed in [`Placeholder`].
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A parsed file template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A value that can be substituted into a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// The host's name (`{{hostname}}`).
+    Hostname,
+    /// The host's IP address (`{{ip}}`).
+    Ip,
+}
+
+impl FromStr for Placeholder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hostname" => Ok(Self::Hostname),
+            "ip" => Ok(Self::Ip),
+            _ => Err(format!(
+                "Unknown placeholder '{{{{{s}}}}}'; expected one of {{{{hostname}}}} or {{{{ip}}}}"
+            )),
+        }
+    }
+}
+
+/// The properties of a host that are substituted into templates.
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateValues<'a> {
+    pub hostname: &'a str,
+    pub ip: IpAddr,
+}
+
+impl FileTemplate {
+    /// Render the template by replacing its placeholders with `values`.
+    pub fn render(&self, values: &TemplateValues) -> String {
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Text(x) => rendered.push_str(x),
+                Part::Placeholder(Placeholder::Hostname) => rendered.push_str(values.hostname),
+                Part::Placeholder(Placeholder::Ip) => rendered.push_str(&values.ip.to_string()),
+            }
+        }
+
+        rendered
+    }
+}
+
+impl FromStr for FileTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                return Err("Placeholder starting with '{{' is missing its closing '}}'".into());
+            };
+
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+
+            let name = rest[start + 2..][..len].trim();
+            parts.push(Part::Placeholder(name.parse()?));
+
+            rest = &rest[start + 2 + len + 2..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn render(template: &str) -> Result<String, String> {
+        let values = TemplateValues {
+            hostname: "relay3",
+            ip: IpAddr::V4(Ipv4Addr::new(11, 0, 0, 3)),
+        };
+        Ok(FileTemplate::from_str(template)?.render(&values))
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render("").unwrap(), "");
+        assert_eq!(render("no placeholders\n").unwrap(), "no placeholders\n");
+        assert_eq!(render("{{hostname}}").unwrap(), "relay3");
+        assert_eq!(
+            render("Nickname {{hostname}}\nAddress {{ ip }}\n").unwrap(),
+            "Nickname relay3\nAddress 11.0.0.3\n"
+        );
+        assert_eq!(
+            render("{{hostname}}-{{hostname}}").unwrap(),
+            "relay3-relay3"
+        );
+        // single braces are left alone
+        assert_eq!(render("{ip} {{ip}}}").unwrap(), "{ip} 11.0.0.3}");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(render("{{port}}").is_err());
+        assert!(render("{{}}").is_err());
+        assert!(render("{{hostname").is_err());
+        assert!(render("{{hostname}} {{ip").is_err());
+    }
+}
@@ -0,0 +1,506 @@
+//! A small expression language for enabling verbose log messages in specific parts of the
+//! simulation, such as `host=relay3 && module=tcp && time>300s`.
+//!
+//! An expression is made of comparisons `<field> <op> <value>` joined with `&&`, `||`, `!`, and
+//! parentheses. The fields are:
+//!
+//! - `host`: the name of the active host,
+//! - `module`: the Rust module path (any `::`-separated component matches) or the C file name
+//!   without its extension,
+//! - `phase`: the name of the active simulation phase,
+//! - `level`: the message's log level, ordered by verbosity (`error < warning < info < debug <
+//!   trace`), and
+//! - `time`: the simulated time since the start of the simulation, such as `300s` or `5min`.
+//!
+//! String fields support `=` and `!=`, and their values may contain `*` wildcards. The `level` and
+//! `time` fields also support `<`, `<=`, `>`, and `>=`.
+//!
+//! Expressions are parsed once at startup and evaluated only for messages that would otherwise be
+//! filtered out by the log level. Some fields may not be known when deciding whether a log level
+//! is enabled (for example the module of a C log message), so evaluation uses three-valued logic
+//! where an unknown field makes a comparison unknown.
+
+use std::str::FromStr;
+
+use log::Level;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::utility::units;
+
+/// A parsed log filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    expr: Expr,
+}
+
+/// The properties of a log message that a [`LogFilter`] is evaluated against. Fields that are
+/// `None` are unknown, except for `host` and `phase` where `None` means that there is no active
+/// host or phase.
+#[derive(Debug, Clone, Copy)]
+pub struct LogContext<'a> {
+    pub level: Level,
+    pub module: Option<&'a str>,
+    pub file: Option<&'a str>,
+    pub host: Option<&'a str>,
+    pub phase: Option<&'a str>,
+    pub time: Option<SimulationTime>,
+}
+
+impl LogFilter {
+    /// Evaluate the filter for a log message. Returns `None` if the result depends on a field that
+    /// isn't known.
+    pub fn eval(&self, ctx: &LogContext) -> Option<bool> {
+        self.expr.eval(ctx)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected '{token}' in log filter '{s}'"));
+        }
+
+        Ok(Self { expr })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Cmp(Cmp),
+}
+
+impl Expr {
+    fn eval(&self, ctx: &LogContext) -> Option<bool> {
+        match self {
+            Self::And(a, b) => match a.eval(ctx) {
+                Some(false) => Some(false),
+                a => match (a, b.eval(ctx)) {
+                    (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                },
+            },
+            Self::Or(a, b) => match a.eval(ctx) {
+                Some(true) => Some(true),
+                a => match (a, b.eval(ctx)) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                },
+            },
+            Self::Not(a) => a.eval(ctx).map(|x| !x),
+            Self::Cmp(cmp) => cmp.eval(ctx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn apply<T: Ord>(&self, a: T, b: T) -> bool {
+        match self {
+            Self::Eq => a == b,
+            Self::Ne => a != b,
+            Self::Lt => a < b,
+            Self::Le => a <= b,
+            Self::Gt => a > b,
+            Self::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Cmp {
+    Host(Op, String),
+    Module(Op, String),
+    Phase(Op, String),
+    Level(Op, Level),
+    Time(Op, SimulationTime),
+}
+
+impl Cmp {
+    fn eval(&self, ctx: &LogContext) -> Option<bool> {
+        match self {
+            Self::Host(op, pattern) => {
+                Some(op.apply(ctx.host.is_some_and(|x| glob_match(pattern, x)), true))
+            }
+            Self::Phase(op, pattern) => {
+                Some(op.apply(ctx.phase.is_some_and(|x| glob_match(pattern, x)), true))
+            }
+            Self::Module(op, pattern) => {
+                if ctx.module.is_none() && ctx.file.is_none() {
+                    return None;
+                }
+
+                let module_matches = ctx
+                    .module
+                    .is_some_and(|x| x.split("::").any(|x| glob_match(pattern, x)));
+                let file_matches = ctx.file.is_some_and(|x| {
+                    // the file name without its directory or extension
+                    let name = x.rsplit('/').next().unwrap();
+                    let stem = name.split_once('.').map(|x| x.0).unwrap_or(name);
+                    glob_match(pattern, stem)
+                });
+
+                Some(op.apply(module_matches || file_matches, true))
+            }
+            Self::Level(op, level) => Some(op.apply(ctx.level, *level)),
+            Self::Time(op, time) => Some(op.apply(ctx.time?, *time)),
+        }
+    }
+}
+
+/// Whether `s` matches `pattern`, where `*` in the pattern matches any sequence of characters.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == s;
+    };
+
+    let Some(mut s) = s.strip_prefix(prefix) else {
+        return false;
+    };
+
+    // the pattern is split into the parts between wildcards; the last part must match the end of
+    // the string, and the others are matched greedily from the left
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap();
+
+    for part in parts {
+        match s.find(part) {
+            Some(i) => s = &s[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    s.ends_with(last)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Word(x) => write!(f, "{x}"),
+            Self::Op(Op::Eq) => write!(f, "="),
+            Self::Op(Op::Ne) => write!(f, "!="),
+            Self::Op(Op::Lt) => write!(f, "<"),
+            Self::Op(Op::Le) => write!(f, "<="),
+            Self::Op(Op::Gt) => write!(f, ">"),
+            Self::Op(Op::Ge) => write!(f, ">="),
+            Self::And => write!(f, "&&"),
+            Self::Or => write!(f, "||"),
+            Self::Not => write!(f, "!"),
+            Self::LParen => write!(f, "("),
+            Self::RParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '=' => {
+                // allow both '=' and '=='
+                chars.next_if_eq(&'=');
+                Token::Op(Op::Eq)
+            }
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unterminated quote in log filter '{s}'")),
+                    }
+                }
+                Token::Word(word)
+            }
+            c if is_word_char(c) => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| is_word_char(*c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => return Err(format!("Unexpected character '{c}' in log filter '{s}'")),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '/' | '*')
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    Some(x) => Err(format!("Expected ')' but found '{x}'")),
+                    None => Err("Expected ')'".to_string()),
+                }
+            }
+            Some(Token::Word(field)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    Some(x) => return Err(format!("Expected an operator but found '{x}'")),
+                    None => return Err(format!("Expected an operator after '{field}'")),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value)) => value,
+                    Some(x) => return Err(format!("Expected a value but found '{x}'")),
+                    None => {
+                        return Err(format!(
+                            "Expected a value after '{field}{op}'",
+                            op = Token::Op(op)
+                        ))
+                    }
+                };
+                parse_cmp(&field, op, value).map(Expr::Cmp)
+            }
+            Some(x) => Err(format!("Expected a comparison but found '{x}'")),
+            None => Err("Expected a comparison".to_string()),
+        }
+    }
+}
+
+fn parse_cmp(field: &str, op: Op, value: String) -> Result<Cmp, String> {
+    let string_op = |op| match op {
+        Op::Eq | Op::Ne => Ok(op),
+        _ => Err(format!("The '{field}' field only supports '=' and '!='")),
+    };
+
+    Ok(match field {
+        "host" => Cmp::Host(string_op(op)?, value),
+        "module" => Cmp::Module(string_op(op)?, value),
+        "phase" => Cmp::Phase(string_op(op)?, value),
+        "level" => Cmp::Level(op, parse_level(&value)?),
+        "time" => Cmp::Time(op, parse_time(&value)?),
+        _ => {
+            return Err(format!(
+                "Unknown field '{field}'; expected one of host, module, phase, level, or time"
+            ))
+        }
+    })
+}
+
+fn parse_level(s: &str) -> Result<Level, String> {
+    if s.eq_ignore_ascii_case("warning") {
+        return Ok(Level::Warn);
+    }
+    Level::from_str(s).map_err(|_| format!("Invalid log level '{s}'"))
+}
+
+fn parse_time(s: &str) -> Result<SimulationTime, String> {
+    let time = units::Time::<units::TimePrefix>::from_str(s)
+        .map_err(|e| format!("Invalid time '{s}': {e}"))?;
+    let nanos = time.convert(units::TimePrefix::Nano).unwrap().value();
+    Ok(SimulationTime::from_nanos(nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> LogContext<'static> {
+        LogContext {
+            level: Level::Debug,
+            module: Some("shadow_rs::host::descriptor::socket::inet::tcp"),
+            file: Some("src/main/host/descriptor/socket/inet/tcp.rs"),
+            host: Some("relay3"),
+            phase: None,
+            time: Some(SimulationTime::from_secs(400)),
+        }
+    }
+
+    fn eval(filter: &str, ctx: &LogContext) -> Option<bool> {
+        LogFilter::from_str(filter).unwrap().eval(ctx)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let ctx = ctx();
+
+        assert_eq!(eval("host=relay3", &ctx), Some(true));
+        assert_eq!(eval("host==relay3", &ctx), Some(true));
+        assert_eq!(eval("host!=relay3", &ctx), Some(false));
+        assert_eq!(eval("host=relay*", &ctx), Some(true));
+        assert_eq!(eval("host=client*", &ctx), Some(false));
+        assert_eq!(eval("module=tcp", &ctx), Some(true));
+        assert_eq!(eval("module=inet", &ctx), Some(true));
+        assert_eq!(eval("module=udp", &ctx), Some(false));
+        assert_eq!(eval("phase=warmup", &ctx), Some(false));
+        assert_eq!(eval("phase!=warmup", &ctx), Some(true));
+        assert_eq!(eval("level=debug", &ctx), Some(true));
+        assert_eq!(eval("level<=debug", &ctx), Some(true));
+        assert_eq!(eval("level>info", &ctx), Some(true));
+        assert_eq!(eval("level=trace", &ctx), Some(false));
+        assert_eq!(eval("time>300s", &ctx), Some(true));
+        assert_eq!(eval("time>=400s", &ctx), Some(true));
+        assert_eq!(eval("time<5min", &ctx), Some(false));
+    }
+
+    #[test]
+    fn test_operators() {
+        let ctx = ctx();
+
+        assert_eq!(
+            eval("host=relay3 && module=tcp && time>300s", &ctx),
+            Some(true)
+        );
+        assert_eq!(eval("host=relay3 && module=udp", &ctx), Some(false));
+        assert_eq!(eval("host=relay2 || module=tcp", &ctx), Some(true));
+        assert_eq!(eval("!(host=relay3)", &ctx), Some(false));
+        assert_eq!(eval("!host=relay3 || level=debug", &ctx), Some(true));
+        // '&&' binds more tightly than '||'
+        assert_eq!(
+            eval("host=relay2 && module=tcp || level=debug", &ctx),
+            Some(true)
+        );
+        assert_eq!(
+            eval("host=relay2 && (module=tcp || level=debug)", &ctx),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_c_files() {
+        let ctx = LogContext {
+            module: Some("_tcp_processPacket"),
+            file: Some("tcp.c"),
+            ..ctx()
+        };
+
+        assert_eq!(eval("module=tcp", &ctx), Some(true));
+        assert_eq!(eval("module=udp", &ctx), Some(false));
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let ctx = LogContext {
+            module: None,
+            file: None,
+            time: None,
+            ..ctx()
+        };
+
+        assert_eq!(eval("module=tcp", &ctx), None);
+        assert_eq!(eval("!module=tcp", &ctx), None);
+        assert_eq!(eval("time>300s", &ctx), None);
+        assert_eq!(eval("host=relay3 && module=tcp", &ctx), None);
+        assert_eq!(eval("host=relay2 && module=tcp", &ctx), Some(false));
+        assert_eq!(eval("host=relay3 || module=tcp", &ctx), Some(true));
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("relay3", "relay3"));
+        assert!(!glob_match("relay3", "relay30"));
+        assert!(glob_match("relay*", "relay30"));
+        assert!(glob_match("*3", "relay3"));
+        assert!(glob_match("r*l*3", "relay3"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("r*x*3", "relay3"));
+        assert!(!glob_match("relay*3", "relay"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for filter in [
+            "",
+            "host",
+            "host=",
+            "host<relay3",
+            "colour=red",
+            "level=loud",
+            "time>soon",
+            "(host=relay3",
+            "host=relay3)",
+            "host=relay3 &&",
+            "host=relay3 & module=tcp",
+            "host=\"relay3",
+        ] {
+            assert!(LogFilter::from_str(filter).is_err(), "{filter}");
+        }
+    }
+}
@@ -1 +1,2 @@
+pub mod log_filter;
 pub mod shadow_logger;
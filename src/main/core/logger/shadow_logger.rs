@@ -11,6 +11,7 @@ use once_cell::sync::{Lazy, OnceCell};
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::util::time::TimeParts;
 
+use crate::core::logger::log_filter::{LogContext, LogFilter};
 use crate::core::worker::Worker;
 use crate::host::host::HostInfo;
 
@@ -29,10 +30,16 @@ const MIN_FLUSH_FREQUENCY: Duration = Duration::from_secs(10);
 
 static SHADOW_LOGGER: Lazy<ShadowLogger> = Lazy::new(ShadowLogger::new);
 
-/// Initialize the Shadow logger.
-pub fn init(max_log_level: LevelFilter, log_errors_to_stderr: bool) -> Result<(), SetLoggerError> {
+/// Initialize the Shadow logger. Messages above the maximum log level are still logged if they
+/// match `log_filter`.
+pub fn init(
+    max_log_level: LevelFilter,
+    log_errors_to_stderr: bool,
+    log_filter: Option<LogFilter>,
+) -> Result<(), SetLoggerError> {
     SHADOW_LOGGER.set_max_level(max_log_level);
     SHADOW_LOGGER.set_log_errors_to_stderr(log_errors_to_stderr);
+    SHADOW_LOGGER.set_log_filter(log_filter);
 
     log::set_logger(&*SHADOW_LOGGER)?;
 
@@ -97,6 +104,9 @@ pub struct ShadowLogger {
 
     // Whether to log errors to stderr in addition to stdout.
     log_errors_to_stderr: OnceCell<bool>,
+
+    // Enables messages above the maximum log level that match the filter.
+    log_filter: OnceCell<Option<LogFilter>>,
 }
 
 thread_local!(static SENDER: RefCell<Option<Sender<LoggerCommand>>> = const{ RefCell::new(None)});
@@ -142,6 +152,7 @@ impl ShadowLogger {
             buffering_enabled: RwLock::new(false),
             max_log_level: OnceCell::new(),
             log_errors_to_stderr: OnceCell::new(),
+            log_filter: OnceCell::new(),
         }
     }
 
@@ -250,6 +261,44 @@ impl ShadowLogger {
         self.log_errors_to_stderr.set(val).unwrap()
     }
 
+    /// Set the filter for messages above the maximum log level.
+    ///
+    /// Is only intended to be called from `init()`. Will panic if called more
+    /// than once.
+    fn set_log_filter(&self, filter: Option<LogFilter>) {
+        self.log_filter.set(filter).unwrap()
+    }
+
+    /// Whether the message's level is within the host's or the default maximum log level.
+    fn level_enabled(&self, metadata: &Metadata) -> bool {
+        let filter = match Worker::with_active_host(|host| host.info().log_level) {
+            Some(Some(level)) => level,
+            _ => self.max_level(),
+        };
+        metadata.level() <= filter
+    }
+
+    /// Evaluate the log filter for a message above the maximum log level. Returns `None` if the
+    /// result depends on the module, which isn't known when only `metadata` is available.
+    fn filter_matches(
+        &self,
+        filter: &LogFilter,
+        metadata: &Metadata,
+        record: Option<&Record>,
+    ) -> Option<bool> {
+        let host_info = Worker::with_active_host(|host| host.info().clone());
+        let phase = Worker::current_phase();
+
+        filter.eval(&LogContext {
+            level: metadata.level(),
+            module: record.and_then(|x| x.module_path()),
+            file: record.and_then(|x| x.file()),
+            host: host_info.as_ref().map(|x| x.name.as_str()),
+            phase: phase.as_deref(),
+            time: Worker::current_time().map(|x| x.duration_since(&EmulatedTime::SIMULATION_START)),
+        })
+    }
+
     // Send a flush command to the logger thread.
     fn flush_impl(&self, notify_done: Option<Sender<()>>) {
         self.send_command(LoggerCommand::Flush(notify_done))
@@ -295,16 +344,26 @@ impl ShadowLogger {
 
 impl Log for ShadowLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        let filter = match Worker::with_active_host(|host| host.info().log_level) {
-            Some(Some(level)) => level,
-            _ => self.max_level(),
-        };
-        metadata.level() <= filter
+        if self.level_enabled(metadata) {
+            return true;
+        }
+
+        // the message may still match the filter once its module is known
+        match self.log_filter.get() {
+            Some(Some(filter)) => self.filter_matches(filter, metadata, None) != Some(false),
+            _ => false,
+        }
     }
 
     fn log(&self, record: &Record) {
-        if !self.enabled(record.metadata()) {
-            return;
+        if !self.level_enabled(record.metadata()) {
+            let matches = match self.log_filter.get() {
+                Some(Some(filter)) => self.filter_matches(filter, record.metadata(), Some(record)),
+                _ => None,
+            };
+            if matches != Some(true) {
+                return;
+            }
         }
 
         let message = std::fmt::format(*record.args());
@@ -329,6 +388,7 @@ impl Log for ShadowLogger {
                 .try_with(|id| *id)
                 .unwrap_or_else(|_| nix::unistd::gettid()),
             host_info,
+            phase: Worker::current_phase(),
         };
 
         loop {
@@ -373,6 +433,7 @@ struct ShadowLogRecord {
     thread_name: String,
     thread_id: nix::unistd::Pid,
     host_info: Option<Arc<HostInfo>>,
+    phase: Option<Arc<str>>,
 }
 
 impl std::fmt::Display for ShadowLogRecord {
@@ -411,6 +472,9 @@ impl std::fmt::Display for ShadowLogRecord {
         } else {
             write!(f, " [n/a]",)?;
         }
+        if let Some(phase) = &self.phase {
+            write!(f, " [phase:{phase}]")?;
+        }
         write!(
             f,
             " [{file}:",
@@ -1,18 +1,19 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString, OsStr, OsString};
 use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicU32;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{self, Context};
 use atomic_refcell::AtomicRefCell;
+use linux_api::syscall::SyscallNum;
 use log::warn;
 use rand::seq::SliceRandom;
 use rand_xoshiro::Xoshiro256PlusPlus;
-use scheduler::thread_per_core::ThreadPerCoreSched;
+use scheduler::thread_per_core::{Placement, ThreadPerCoreSched};
 use scheduler::thread_per_host::ThreadPerHostSched;
 use scheduler::{HostIter, Scheduler};
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
@@ -22,20 +23,37 @@ use shadow_shim_helper_rs::util::SyncSendPointer;
 use shadow_shim_helper_rs::HostId;
 use shadow_shmem::allocator::ShMemBlock;
 
-use crate::core::configuration::{self, ConfigOptions, Flatten};
+use crate::core::checkpoint::Checkpointer;
+use crate::core::configuration::{self, AutoOr, ConfigOptions, EnvName, Flatten};
+use crate::core::control_socket::ControlSocket;
 use crate::core::controller::{Controller, ShadowStatusBarState, SimController};
 use crate::core::cpu;
-use crate::core::resource_usage;
+use crate::core::dashboard::DashboardSampler;
+use crate::core::distributed::RemoteManagers;
+use crate::core::failure::{FailureKind, ResultExt};
+use crate::core::file_template::TemplateValues;
+use crate::core::resource_usage::{self, AvailableResources};
+use crate::core::routing_dump;
 use crate::core::runahead::Runahead;
-use crate::core::sim_config::{Bandwidth, HostInfo};
+use crate::core::sim_config::{
+    Bandwidth, HostInfo, SimOutputLimits, SimPhases, SimStopConditions, StatsSinkConfig,
+};
 use crate::core::sim_stats;
+use crate::core::stats_sink::{Metric, StatsSinks};
+use crate::core::tls::CertificateAuthority;
 use crate::core::worker;
 use crate::cshadow as c;
 use crate::host::host::{Host, HostParameters};
-use crate::network::graph::{IpAssignment, RoutingInfo};
+use crate::network::dns_server::DnsServer;
+use crate::network::graph::{IpAssignment, RoutingSchedule};
+use crate::network::link_queue::LinkQueues;
+use crate::network::middlebox::{MiddleboxHost, Middleboxes};
+use crate::network::multicast::MulticastGroups;
+use crate::network::nat::{NatGateway, NatGateways, Subnet};
 use crate::utility;
 use crate::utility::childpid_watcher::ChildPidWatcher;
 use crate::utility::status_bar::Status;
+use crate::utility::units::{self, Unit};
 
 pub struct Manager<'a> {
     manager_config: Option<ManagerConfig>,
@@ -49,11 +67,17 @@ pub struct Manager<'a> {
     data_path: PathBuf,
     hosts_path: PathBuf,
 
+    /// Issues TLS certificates to hosts, if enabled.
+    tls_ca: Option<CertificateAuthority>,
+
     preload_paths: Arc<Vec<PathBuf>>,
 
     check_fd_usage: bool,
-    check_mem_usage: bool,
+    /// Warn once if the available memory falls below this many bytes.
+    mem_warning_threshold: Option<u64>,
 
+    /// The CPU cores and memory available to Shadow.
+    resources: AvailableResources,
     meminfo_file: std::fs::File,
     shmem: ShMemBlock<'static, ManagerShmem>,
 }
@@ -148,13 +172,15 @@ impl<'a> Manager<'a> {
             );
 
             // copy the template directory to the data directory path
-            utility::copy_dir_all(&template_path, &data_path).with_context(|| {
-                format!(
-                    "Failed to copy template directory '{}' to '{}'",
-                    template_path.display(),
-                    data_path.display()
-                )
-            })?;
+            utility::copy_dir_all(&template_path, &data_path)
+                .with_context(|| {
+                    format!(
+                        "Failed to copy template directory '{}' to '{}'",
+                        template_path.display(),
+                        data_path.display()
+                    )
+                })
+                .failure_kind(FailureKind::Config)?;
 
             // create the hosts directory if it doesn't exist
             let result = std::fs::create_dir(&hosts_path);
@@ -168,9 +194,11 @@ impl<'a> Manager<'a> {
             }
         } else {
             // create the data and hosts directories
-            std::fs::create_dir(&data_path).with_context(|| {
-                format!("Failed to create data directory '{}'", data_path.display())
-            })?;
+            std::fs::create_dir(&data_path)
+                .with_context(|| {
+                    format!("Failed to create data directory '{}'", data_path.display())
+                })
+                .failure_kind(FailureKind::Config)?;
             std::fs::create_dir(&hosts_path).with_context(|| {
                 format!(
                     "Failed to create hosts directory '{}'",
@@ -179,6 +207,18 @@ impl<'a> Manager<'a> {
             })?;
         }
 
+        let tls_ca = if config.general.tls_certificates.unwrap() {
+            let ca = CertificateAuthority::new(
+                data_path.join("tls"),
+                end_time,
+                config.general.seed.unwrap().into(),
+            )
+            .context("Failed to create the TLS certificate authority")?;
+            Some(ca)
+        } else {
+            None
+        };
+
         // save the processed config as yaml
         let config_out_filename = data_path.join("processed-config.yaml");
         let config_out_file = std::fs::File::create(&config_out_filename).with_context(|| {
@@ -192,9 +232,18 @@ impl<'a> Manager<'a> {
             )
         })?;
 
-        let meminfo_file =
+        let mut meminfo_file =
             std::fs::File::open("/proc/meminfo").context("Failed to open '/proc/meminfo'")?;
 
+        let resources = AvailableResources::detect(&mut meminfo_file);
+        log::debug!("Detected available resources: {resources:?}");
+
+        let mem_warning_threshold = match config.general.memory_warning_threshold.flatten() {
+            Some(AutoOr::Auto(_)) => Some(resources.memory_warning_threshold()),
+            Some(AutoOr::Value(x)) => Some(x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+            None => None,
+        };
+
         let shmem = shadow_shmem::allocator::shmalloc(ManagerShmem {
             log_start_time_micros: unsafe { c::logger_get_global_start_time_micros() },
         });
@@ -208,9 +257,11 @@ impl<'a> Manager<'a> {
             end_time,
             data_path,
             hosts_path,
+            tls_ca,
             preload_paths: Arc::new(preload_paths),
             check_fd_usage: true,
-            check_mem_usage: true,
+            mem_warning_threshold,
+            resources,
             meminfo_file,
             shmem,
         })
@@ -219,7 +270,7 @@ impl<'a> Manager<'a> {
     pub fn run(
         mut self,
         status_logger_state: Option<&Arc<Status<ShadowStatusBarState>>>,
-    ) -> anyhow::Result<u32> {
+    ) -> anyhow::Result<PluginErrors> {
         let mut manager_config = self.manager_config.take().unwrap();
 
         let min_runahead_config: Option<Duration> = self
@@ -235,41 +286,31 @@ impl<'a> Manager<'a> {
         let bootstrap_end_time: SimulationTime = bootstrap_end_time.try_into().unwrap();
         let bootstrap_end_time = EmulatedTime::SIMULATION_START + bootstrap_end_time;
 
-        let smallest_latency = SimulationTime::from_nanos(
-            manager_config
-                .routing_info
-                .get_smallest_latency_ns()
-                .unwrap(),
-        );
+        let smallest_latency =
+            SimulationTime::from_nanos(manager_config.routing.get_smallest_latency_ns().unwrap());
 
         let dns = unsafe { c::dns_new() };
         assert!(!dns.is_null());
 
         let parallelism: usize = match self.config.general.parallelism.unwrap() {
-            0 => {
-                let cores = cpu::count_physical_cores().try_into().unwrap();
-                log::info!("The parallelism option was 0, so using parallelism={cores}");
-                cores
+            AutoOr::Auto(_) | AutoOr::Value(0) => {
+                let parallelism = self.resources.parallelism().try_into().unwrap();
+                log::info!("Using parallelism={parallelism} from the available resources");
+                parallelism
             }
-            x => x.try_into().unwrap(),
+            AutoOr::Value(x) => x.try_into().unwrap(),
         };
 
-        // note: there are several return points before we add these hosts to the scheduler and we
-        // would leak memory if we return before then, but not worrying about that since the issues
-        // will go away when we move the hosts to rust, and if we don't add them to the scheduler
-        // then it means there was an error and we're going to exit anyways
-        let mut hosts: Vec<_> = manager_config
-            .hosts
-            .iter()
-            .enumerate()
-            .map(|(i, x)| {
-                self.build_host(HostId::from(u32::try_from(i).unwrap()), x, dns)
-                    .with_context(|| format!("Failed to build host '{}'", x.name))
-            })
-            .collect::<anyhow::Result<_>>()?;
+        let num_hosts = manager_config.hosts.len();
 
-        // shuffle the list of hosts to make sure that they are randomly assigned by the scheduler
-        hosts.shuffle(&mut manager_config.random);
+        // whether the host at an index in the config is run by this manager rather than by
+        // another manager
+        let is_local = |i: usize| {
+            manager_config.remote_managers.as_ref().map_or(true, |x| {
+                x.is_local(HostId::from(u32::try_from(i).unwrap()))
+            })
+        };
+        let num_local_hosts = (0..num_hosts).filter(|&i| is_local(i)).count();
 
         let use_cpu_pinning = self.config.experimental.use_cpu_pinning.unwrap();
 
@@ -284,7 +325,7 @@ impl<'a> Manager<'a> {
 
         // shadow is parallelized at the host level, so we don't need more parallelism than the
         // number of hosts
-        let parallelism = std::cmp::min(parallelism, hosts.len());
+        let parallelism = std::cmp::min(parallelism, num_local_hosts);
 
         // should have either all `Some` values, or all `None` values
         let cpus: Vec<Option<u32>> = cpu_iter.take(parallelism).collect();
@@ -297,16 +338,171 @@ impl<'a> Manager<'a> {
         }
         assert_eq!(cpus.len(), parallelism);
 
+        // the NUMA node of each worker thread, if hosts are placed on the threads' nodes
+        let thread_nodes = self.numa_thread_nodes(&cpus);
+
+        // shuffle the order of the hosts to make sure that they are randomly assigned by the
+        // scheduler, and only keep the hosts that this manager runs (the shuffle is done first so
+        // that every manager draws the same random numbers)
+        let mut host_order: Vec<usize> = (0..num_hosts).collect();
+        host_order.shuffle(&mut manager_config.random);
+        host_order.retain(|&i| is_local(i));
+
+        // the affinity of each host, in the order of the hosts in the config, where the hosts in
+        // the same affinity group have the same affinity
+        let host_affinities: Vec<Option<usize>> = {
+            let mut groups = HashMap::new();
+            manager_config
+                .hosts
+                .iter()
+                .map(|host| {
+                    let group = host.affinity_group.as_ref()?;
+                    let next = groups.len();
+                    Some(*groups.entry(group).or_insert(next))
+                })
+                .collect()
+        };
+
+        // the NUMA node of each host, in the order of the hosts in the config
+        let host_nodes = thread_nodes.as_ref().map(|thread_nodes| {
+            let mut host_nodes = vec![0; num_hosts];
+            let nodes = cpu::assign_nodes(thread_nodes, host_order.len());
+            for (&host, node) in host_order.iter().zip(nodes) {
+                host_nodes[host] = node;
+            }
+
+            // the hosts in an affinity group are run by one thread, so they use the node of the
+            // group's first host
+            let mut affinity_nodes = HashMap::new();
+            for &host in &host_order {
+                if let Some(affinity) = host_affinities[host] {
+                    host_nodes[host] = *affinity_nodes.entry(affinity).or_insert(host_nodes[host]);
+                }
+            }
+
+            host_nodes
+        });
+
+        // note: there are several return points before we add these hosts to the scheduler and we
+        // would leak memory if we return before then, but not worrying about that since the issues
+        // will go away when we move the hosts to rust, and if we don't add them to the scheduler
+        // then it means there was an error and we're going to exit anyways
+        let mut hosts: Vec<Option<_>> = manager_config
+            .hosts
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let host_id = HostId::from(u32::try_from(i).unwrap());
+
+                // hosts of other managers are only registered so that they can be resolved
+                if !is_local(i) {
+                    register_remote_host(host_id, x, dns);
+                    return Ok(None);
+                }
+
+                // allocate the host's memory from the node of the threads that will run it
+                if let Some(host_nodes) = &host_nodes {
+                    set_preferred_node(Some(host_nodes[i]));
+                }
+
+                self.build_host(host_id, x, dns)
+                    .with_context(|| format!("Failed to build host '{}'", x.name))
+                    .map(Some)
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        if host_nodes.is_some() {
+            set_preferred_node(None);
+        }
+
+        let hosts: Vec<_> = host_order.iter().filter_map(|&i| hosts[i].take()).collect();
+
+        // the private subnets of the hosts that are NAT gateways
+        let nat_gateways = manager_config
+            .hosts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, host)| {
+                let nat = host.nat.as_ref()?;
+                let std::net::IpAddr::V4(public_ip) = host.ip_addr.unwrap() else {
+                    unreachable!("IPv6 not supported");
+                };
+                Some(NatGateway {
+                    subnet: Subnet::new(nat.subnet, nat.prefix_len),
+                    host_id: HostId::from(u32::try_from(i).unwrap()),
+                    public_ip,
+                })
+            })
+            .collect();
+
+        // the intercepted subnets of the hosts that are middleboxes
+        let middleboxes = manager_config
+            .hosts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, host)| {
+                let middlebox = host.middlebox.as_ref()?;
+                let std::net::IpAddr::V4(ip) = host.ip_addr.unwrap() else {
+                    unreachable!("IPv6 not supported");
+                };
+                Some(MiddleboxHost {
+                    host_id: HostId::from(u32::try_from(i).unwrap()),
+                    ip,
+                    intercept: middlebox
+                        .intercept
+                        .iter()
+                        .map(|x| x.parse().unwrap())
+                        .collect(),
+                })
+            })
+            .collect();
+
+        // queue packets at the graph edges with a bandwidth, if there are any
+        let links = manager_config.routing.initial().links();
+        let link_queues = (!links.is_empty()).then(|| LinkQueues::new(links.len()));
+
+        // hosts of wireless access points lose packets on the access point's channel
+        let wireless_loss = manager_config
+            .hosts
+            .iter()
+            .filter(|host| host.wireless_loss > 0.0)
+            .map(|host| (host.ip_addr.unwrap(), host.wireless_loss))
+            .collect();
+
+        // the built-in DNS server has an A record for each host from the host's start time, and
+        // processes find the server through a generated resolv.conf file
+        let dns_server = self.config.network.dns_server.as_ref().map(|options| {
+            let hosts = manager_config.hosts.iter().map(|host| {
+                let std::net::IpAddr::V4(ip) = host.ip_addr.unwrap() else {
+                    unreachable!("IPv6 not supported");
+                };
+                (host.name.as_str(), ip, host.start_time)
+            });
+            DnsServer::new(options, hosts)
+        });
+
+        let resolv_conf_path = match &dns_server {
+            Some(server) => {
+                let path = self.data_path.join("resolv.conf");
+                std::fs::write(&path, format!("nameserver {}\n", server.address()))
+                    .with_context(|| format!("Failed to write file '{}'", path.display()))?;
+                Some(CString::new(path.as_os_str().as_bytes()).unwrap())
+            }
+            None => None,
+        };
+
         // set the simulation's global state
         worker::WORKER_SHARED
             .borrow_mut()
             .replace(worker::WorkerShared {
                 ip_assignment: manager_config.ip_assignment,
-                routing_info: manager_config.routing_info,
+                routing: manager_config.routing,
                 host_bandwidths: manager_config.host_bandwidths,
+                wireless_loss,
                 // safe since the DNS type has an internal mutex
                 dns: unsafe { SyncSendPointer::new(dns) },
                 num_plugin_errors: AtomicU32::new(0),
+                unsupported_syscalls: Mutex::new(HashSet::new()),
                 // allow the status logger's state to be updated from anywhere
                 status_logger_state: status_logger_state.map(Arc::clone),
                 runahead: Runahead::new(
@@ -321,8 +517,29 @@ impl<'a> Manager<'a> {
                     .collect(),
                 bootstrap_end_time,
                 sim_end_time: self.end_time,
+                phases: manager_config.phases.clone(),
+                multicast_groups: MulticastGroups::new(),
+                multicast_scope: self.config.network.multicast_scope.unwrap(),
+                ecmp: self.config.network.ecmp.unwrap(),
+                link_queues,
+                nat_gateways: NatGateways::new(nat_gateways),
+                middleboxes: Middleboxes::new(middleboxes),
+                dns_server,
+                resolv_conf_path,
+                stop_conditions: manager_config.stop_conditions,
+                output_limits: manager_config.output_limits,
+                remote_managers: manager_config.remote_managers,
             });
 
+        // the simulation ends at the end time, or earlier if a stop condition was met
+        let mut stop_time = self.end_time;
+
+        // the error if the checkpoint couldn't be written
+        let mut checkpoint_failure = None;
+
+        // the error if we couldn't synchronize with the other managers
+        let mut remote_manager_failure = None;
+
         // scope used so that the scheduler is dropped before we log the global counters below
         {
             let mut scheduler = match self.config.experimental.scheduler.unwrap() {
@@ -338,9 +555,20 @@ impl<'a> Manager<'a> {
                     ))
                 }
                 configuration::Scheduler::ThreadPerCore => {
-                    Scheduler::ThreadPerCore(ThreadPerCoreSched::new(
+                    // the threads are grouped by their NUMA nodes, and the hosts are assigned to
+                    // the nodes that their memory was allocated from
+                    let thread_groups: Vec<usize> = match &thread_nodes {
+                        Some(nodes) => nodes.iter().map(|&x| x as usize).collect(),
+                        None => vec![0; parallelism],
+                    };
+                    let placements = host_order.iter().map(|&i| Placement {
+                        thread_group: host_nodes.as_ref().map_or(0, |x| x[i] as usize),
+                        affinity: host_affinities[i],
+                    });
+                    Scheduler::ThreadPerCore(ThreadPerCoreSched::with_placements(
                         &cpus,
-                        hosts,
+                        &thread_groups,
+                        hosts.into_iter().zip(placements),
                         self.config.experimental.use_worker_spinning.unwrap(),
                     ))
                 }
@@ -349,6 +577,9 @@ impl<'a> Manager<'a> {
             // initialize the thread-local Worker
             scheduler.scope(|s| {
                 s.run(|thread_id| {
+                    if let Some(thread_nodes) = &thread_nodes {
+                        set_preferred_node(Some(thread_nodes[thread_id]));
+                    }
                     worker::Worker::new_for_this_thread(worker::WorkerThreadID(thread_id as u32))
                 });
             });
@@ -372,10 +603,36 @@ impl<'a> Manager<'a> {
                 EmulatedTime::SIMULATION_START + SimulationTime::NANOSECOND,
             ));
 
-            // the next event times for each thread; allocated here to avoid re-allocating each
-            // scheduling loop
-            let thread_next_event_times: Vec<AtomicRefCell<Option<EmulatedTime>>> =
-                vec![AtomicRefCell::new(None); scheduler.parallelism()];
+            // the next event times and event counts for each thread; allocated here to avoid
+            // re-allocating each scheduling loop
+            let thread_round_data: Vec<ThreadRoundData> = (0..scheduler.parallelism())
+                .map(|_| ThreadRoundData::default())
+                .collect();
+
+            // the external time-series databases that metrics are sent to
+            let mut stats_sinks = StatsSinks::new(&manager_config.stats_sinks);
+
+            // the number of events executed by each host (indexed by host id), only counted if
+            // the dashboard, a stats sink, or the control socket is enabled
+            let use_dashboard = manager_config.use_dashboard;
+            let count_events =
+                use_dashboard || !stats_sinks.is_empty() || manager_config.control_socket.is_some();
+            let host_event_counts: Vec<AtomicU64> = manager_config
+                .hosts
+                .iter()
+                .map(|_| AtomicU64::new(0))
+                .collect();
+            let host_event_counts = &host_event_counts;
+
+            let mut dashboard_sampler = use_dashboard.then(|| {
+                DashboardSampler::new(
+                    manager_config
+                        .hosts
+                        .iter()
+                        .map(|x| x.name.clone())
+                        .collect(),
+                )
+            });
 
             // how often to log heartbeat messages
             let heartbeat_interval = self
@@ -386,7 +643,9 @@ impl<'a> Manager<'a> {
                 .map(|x| Duration::from(x).try_into().unwrap());
 
             let mut last_heartbeat = EmulatedTime::SIMULATION_START;
+            let mut current_phase = None;
             let mut time_of_last_usage_check = std::time::Instant::now();
+            let mut time_of_last_dashboard_update = None;
 
             // the scheduling loop
             while let Some((window_start, window_end)) = window {
@@ -400,15 +659,29 @@ impl<'a> Manager<'a> {
                         state.current = display_time;
                     });
 
+                // log when we've entered a new phase
+                let phase = manager_config
+                    .phases
+                    .phase_at(window_start - EmulatedTime::SIMULATION_START);
+                if phase != current_phase {
+                    current_phase = phase;
+                    if let Some(phase) = phase {
+                        log::info!(
+                            "Entering simulation phase '{}'",
+                            manager_config.phases.name(phase)
+                        );
+                    }
+                }
+
                 // run the events
                 scheduler.scope(|s| {
                     // run the closure on each of the scheduler's threads
                     s.run_with_data(
-                        &thread_next_event_times,
+                        &thread_round_data,
                         // each call of the closure is given an abstract thread-specific host
-                        // iterator, and an element of 'thread_next_event_times'
-                        move |_, hosts, next_event_time| {
-                            let mut next_event_time = next_event_time.borrow_mut();
+                        // iterator, and an element of 'thread_round_data'
+                        move |_, hosts, round_data| {
+                            let mut next_event_time = round_data.next_event_time.borrow_mut();
 
                             worker::Worker::reset_next_event_time();
                             worker::Worker::set_round_end_time(window_end);
@@ -416,9 +689,19 @@ impl<'a> Manager<'a> {
                             for_each_host(hosts, |host| {
                                 let host_next_event_time = {
                                     host.lock_shmem();
-                                    host.execute(window_end);
+                                    let num_events = host.execute(window_end);
                                     let host_next_event_time = host.next_event_time();
                                     host.unlock_shmem();
+
+                                    if count_events {
+                                        let idx = usize::try_from(u32::from(host.id())).unwrap();
+                                        host_event_counts[idx]
+                                            .fetch_add(num_events, Ordering::Relaxed);
+                                        round_data
+                                            .num_events
+                                            .fetch_add(num_events, Ordering::Relaxed);
+                                    }
+
                                     host_next_event_time
                                 };
                                 *next_event_time = [*next_event_time, host_next_event_time]
@@ -452,14 +735,82 @@ impl<'a> Manager<'a> {
                         time_of_last_usage_check = current_time;
                         self.check_resource_usage();
                     }
+
+                    // update the dashboard every real second
+                    if let Some(sampler) = &mut dashboard_sampler {
+                        let update = time_of_last_dashboard_update
+                            .map(|x| current_time.duration_since(x) >= Duration::from_secs(1))
+                            .unwrap_or(true);
+                        if update {
+                            time_of_last_dashboard_update = Some(current_time);
+                            let stats = sampler.sample(
+                                current_time,
+                                window_start,
+                                thread_round_data
+                                    .iter()
+                                    .map(|x| x.num_events.load(Ordering::Relaxed))
+                                    .collect(),
+                                host_event_counts
+                                    .iter()
+                                    .map(|x| x.load(Ordering::Relaxed))
+                                    .collect(),
+                                resource_usage::meminfo(&mut self.meminfo_file)
+                                    .ok()
+                                    .and_then(|x| x.used()),
+                            );
+                            worker::WORKER_SHARED
+                                .borrow()
+                                .as_ref()
+                                .unwrap()
+                                .update_status_logger(|state| state.dashboard = Some(stats));
+                        }
+                    }
+
+                    // send metrics to the stats sinks at their flush intervals
+                    stats_sinks.flush(current_time, || {
+                        stats_sink_metrics(
+                            window_start,
+                            &thread_round_data,
+                            host_event_counts,
+                            &manager_config.hosts,
+                            &mut self.meminfo_file,
+                        )
+                    });
                 });
 
+                // send the packets that were sent through links with a bandwidth during the round
+                worker::WORKER_SHARED
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .flush_link_queues();
+
+                // write the paths between hosts if a dump was requested with SIGUSR1
+                if routing_dump::take_request() {
+                    let shared = worker::WORKER_SHARED.borrow();
+                    let hosts: Vec<_> = manager_config
+                        .hosts
+                        .iter()
+                        .map(|x| (x.name.as_str(), x.network_node_id))
+                        .collect();
+                    let result = routing_dump::write_dump(
+                        &self.data_path,
+                        window_end - EmulatedTime::SIMULATION_START,
+                        &hosts,
+                        &shared.as_ref().unwrap().routing,
+                    );
+                    match result {
+                        Ok(path) => log::info!("Wrote the paths between hosts to {path:?}"),
+                        Err(e) => log::warn!("Failed to write the paths between hosts: {e:?}"),
+                    }
+                }
+
                 // get the minimum next event time for all threads (also resets the next event times
                 // to None while we have them borrowed)
-                let min_next_event_time = thread_next_event_times
+                let min_next_event_time = thread_round_data
                     .iter()
                     // the take() resets it to None for the next scheduling loop
-                    .filter_map(|x| x.borrow_mut().take())
+                    .filter_map(|x| x.next_event_time.borrow_mut().take())
                     .reduce(std::cmp::min)
                     .unwrap_or(EmulatedTime::MAX);
 
@@ -470,17 +821,85 @@ impl<'a> Manager<'a> {
                     (min_next_event_time - EmulatedTime::SIMULATION_START).as_nanos(),
                 );
 
+                // update the control socket's status, and wait here while the simulation is paused
+                if let Some(control_socket) = &manager_config.control_socket {
+                    let stop = control_socket.finish_round(
+                        window_end - EmulatedTime::SIMULATION_START,
+                        thread_round_data
+                            .iter()
+                            .map(|x| x.num_events.load(Ordering::Relaxed))
+                            .sum(),
+                        host_event_counts.iter().map(|x| x.load(Ordering::Relaxed)),
+                    );
+                    if stop {
+                        worker::WORKER_SHARED
+                            .borrow()
+                            .as_ref()
+                            .unwrap()
+                            .stop_conditions
+                            .stop("a stop was requested on the control socket");
+                    }
+                }
+
+                // write the checkpoint between rounds, while the hosts and worker threads are idle
+                if let Some(checkpointer) = &mut manager_config.checkpointer {
+                    let result = checkpointer
+                        .finish_round(window_end - EmulatedTime::SIMULATION_START, &self.data_path);
+                    if let Err(e) = result {
+                        log::error!("{e:?}");
+                        checkpoint_failure = Some(format!("{e:#}"));
+                        worker::WORKER_SHARED
+                            .borrow()
+                            .as_ref()
+                            .unwrap()
+                            .stop_conditions
+                            .stop("the checkpoint could not be written");
+                    }
+                }
+
                 // notify controller that we finished this round, and the time of our next event in
                 // order to fast-forward our execute window if possible
-                window = self
+                window = match self
                     .controller
-                    .manager_finished_current_round(min_next_event_time);
+                    .manager_finished_current_round(min_next_event_time)
+                {
+                    Ok(window) => window,
+                    Err(e) => {
+                        // we can't continue without the other managers' packets and event times
+                        log::error!("{e:?}");
+                        remote_manager_failure = Some(format!("{e:#}"));
+                        stop_time = std::cmp::min(window_end, self.end_time);
+                        None
+                    }
+                };
+
+                if window.is_none()
+                    && worker::WORKER_SHARED
+                        .borrow()
+                        .as_ref()
+                        .unwrap()
+                        .stop_conditions
+                        .is_met()
+                {
+                    stop_time = std::cmp::min(window_end, self.end_time);
+                }
             }
 
+            // send the final metrics and wait for the stats sinks to finish sending
+            stats_sinks.finish(std::time::Instant::now(), || {
+                stats_sink_metrics(
+                    stop_time,
+                    &thread_round_data,
+                    host_event_counts,
+                    &manager_config.hosts,
+                    &mut self.meminfo_file,
+                )
+            });
+
             scheduler.scope(|s| {
                 s.run_with_hosts(move |_, hosts| {
                     for_each_host(hosts, |host| {
-                        worker::Worker::set_current_time(self.end_time);
+                        worker::Worker::set_current_time(stop_time);
                         host.free_all_applications();
                         host.shutdown();
                         worker::Worker::clear_current_time();
@@ -504,14 +923,29 @@ impl<'a> Manager<'a> {
             .as_ref()
             .unwrap()
             .update_status_logger(|state| {
-                state.current = self.end_time;
+                state.current = stop_time;
             });
 
-        let num_plugin_errors = worker::WORKER_SHARED
+        // log how often the paths computed on demand were cached
+        worker::WORKER_SHARED
             .borrow()
             .as_ref()
             .unwrap()
-            .plugin_error_count();
+            .routing
+            .initial()
+            .log_path_cache_stats();
+
+        let plugin_errors = {
+            let shared = worker::WORKER_SHARED.borrow();
+            let shared = shared.as_ref().unwrap();
+            PluginErrors {
+                num_plugin_errors: shared.plugin_error_count(),
+                unsupported_syscalls: shared.unsupported_syscalls(),
+                output_limit_failure: shared.output_limits.failure().map(str::to_string),
+                checkpoint_failure,
+                remote_manager_failure,
+            }
+        };
 
         // drop the simulation's global state
         // must drop before the allocation counters have been checked
@@ -520,6 +954,11 @@ impl<'a> Manager<'a> {
         // since the scheduler was dropped, all workers should have completed and the global object
         // and syscall counters should have been updated
 
+        let host_name = |id: HostId| {
+            let idx = usize::try_from(u32::from(id)).unwrap();
+            manager_config.hosts[idx].name.clone()
+        };
+
         worker::with_global_sim_stats(|stats| {
             if self.config.experimental.use_syscall_counters.unwrap() {
                 log::info!(
@@ -538,14 +977,58 @@ impl<'a> Manager<'a> {
                 } else {
                     // don't change the formatting of this line as we search for it in test cases
                     log::warn!("Memory leak detected");
+
+                    for (host, counts) in sim_stats::leaked_objects_by_host(stats, host_name) {
+                        log::warn!(
+                            "Objects still allocated at shutdown on host '{host}': {counts}"
+                        );
+                    }
                 }
             }
 
             let stats_filename = self.data_path.clone().join("sim-stats.json");
-            sim_stats::write_stats_to_file(&stats_filename, stats)
+            sim_stats::write_stats_to_file(
+                &stats_filename,
+                stats,
+                &manager_config.phases,
+                host_name,
+            )
         })?;
 
-        Ok(num_plugin_errors)
+        Ok(plugin_errors)
+    }
+
+    /// The NUMA node of each worker thread's CPU, or `None` if hosts shouldn't be placed on the
+    /// threads' nodes. Placement requires CPU pinning and the thread-per-core scheduler.
+    fn numa_thread_nodes(&self, cpus: &[Option<u32>]) -> Option<Vec<u32>> {
+        if !self.config.experimental.use_numa_placement.unwrap() {
+            return None;
+        }
+
+        if !matches!(
+            self.config.experimental.scheduler.unwrap(),
+            configuration::Scheduler::ThreadPerCore
+        ) {
+            warn!("Ignoring NUMA placement since it requires the thread-per-core scheduler");
+            return None;
+        }
+
+        let Some(cpus) = cpus.iter().copied().collect::<Option<Vec<u32>>>() else {
+            warn!("Ignoring NUMA placement since it requires CPU pinning");
+            return None;
+        };
+
+        let Some(nodes) = cpus
+            .iter()
+            .map(|&x| cpu::node(x))
+            .collect::<Option<Vec<_>>>()
+        else {
+            warn!("Ignoring NUMA placement since the CPUs' NUMA nodes are unknown");
+            return None;
+        };
+
+        log::debug!("Placing hosts on the NUMA nodes of the worker threads: {nodes:?}");
+        Some(nodes)
     }
 
     fn build_host(
@@ -556,6 +1039,34 @@ impl<'a> Manager<'a> {
     ) -> anyhow::Result<Box<Host>> {
         let hostname = CString::new(&*host_info.name).unwrap();
 
+        // copy the data template first, so that the generated files below take precedence
+        if let Some(template_path) = &host_info.data_template {
+            let host_dir = self.hosts_path.join(&host_info.name);
+            utility::copy_dir_into(template_path, &host_dir).with_context(|| {
+                format!(
+                    "Failed to copy data template '{}' to '{}'",
+                    template_path.display(),
+                    host_dir.display()
+                )
+            })?;
+        }
+
+        // the path to the CA certificate trusted by the host's processes
+        let tls_ca_cert = match &self.tls_ca {
+            Some(ca) => {
+                let std::net::IpAddr::V4(ip) = host_info.ip_addr.unwrap() else {
+                    // the config only allows ipv4 addresses, so this shouldn't happen
+                    unreachable!("IPv6 not supported");
+                };
+                let host_dir = self.hosts_path.join(&host_info.name);
+                let path = ca
+                    .issue(&host_info.name, ip, &host_dir, host_info.seed)
+                    .context("Failed to issue TLS certificate")?;
+                Some(path)
+            }
+            None => None,
+        };
+
         // scope used to enforce drop order for pointers
         let host = {
             let params = HostParameters {
@@ -571,9 +1082,13 @@ impl<'a> Manager<'a> {
                     // the config only allows ipv4 addresses, so this shouldn't happen
                     std::net::IpAddr::V6(_) => unreachable!("IPv6 not supported"),
                 },
+                start_time: host_info.start_time,
                 sim_end_time: self.end_time,
                 requested_bw_down_bits: host_info.bandwidth_down_bits.unwrap(),
                 requested_bw_up_bits: host_info.bandwidth_up_bits.unwrap(),
+                requested_bw_burst_bytes: host_info.bandwidth_burst_bytes.unwrap_or(0),
+                requested_bw_peak_bits: host_info.bandwidth_peak_bits,
+                uplink_trace: host_info.uplink_trace.clone(),
                 cpu_threshold: host_info.cpu_threshold,
                 cpu_precision: host_info.cpu_precision,
                 heartbeat_interval: host_info.heartbeat_interval,
@@ -591,12 +1106,31 @@ impl<'a> Manager<'a> {
                     .log_level
                     .map(|x| x.to_c_loglevel())
                     .unwrap_or(c::_LogLevel_LOGLEVEL_UNSET),
-                pcap_config: host_info.pcap_config,
+                pcap_config: host_info.pcap_config.clone(),
+                output_max_size: host_info.output_max_size,
+                output_limit_action: host_info.output_limit_action,
                 qdisc: host_info.qdisc,
+                egress_qdisc: host_info.egress_qdisc,
+                router_queue: host_info.router_queue,
+                nat: host_info.nat,
+                middlebox: host_info.middlebox.clone(),
+                firewall: host_info.firewall.clone(),
+                interface_addrs: host_info
+                    .interfaces
+                    .iter()
+                    .map(|x| match x.ip_addr.unwrap() {
+                        std::net::IpAddr::V4(ip) => ip,
+                        std::net::IpAddr::V6(_) => unreachable!("IPv6 not supported"),
+                    })
+                    .collect(),
+                routes: host_info.routes.clone(),
                 init_sock_recv_buf_size: host_info.recv_buf_size,
                 autotune_recv_buf: host_info.autotune_recv_buf,
                 init_sock_send_buf_size: host_info.send_buf_size,
                 autotune_send_buf: host_info.autotune_send_buf,
+                tcp_rmem: host_info.tcp_rmem,
+                tcp_wmem: host_info.tcp_wmem,
+                tcp_tunables: host_info.tcp_tunables,
                 native_tsc_frequency: self.native_tsc_frequency,
                 model_unblocked_syscall_latency: self.config.model_unblocked_syscall_latency(),
                 max_unapplied_cpu_latency: self.config.max_unapplied_cpu_latency(),
@@ -608,6 +1142,9 @@ impl<'a> Manager<'a> {
                     .unwrap_or_else(|| self.config.general.log_level.unwrap())
                     .to_c_loglevel(),
                 use_new_tcp: self.config.experimental.use_new_tcp.unwrap(),
+                use_tcp_sack: self.config.experimental.use_tcp_sack.unwrap(),
+                use_tcp_ecn: self.config.experimental.use_tcp_ecn.unwrap(),
+                router_ecn_marking: self.config.experimental.router_ecn_marking.unwrap(),
                 use_mem_mapper: self.config.experimental.use_memory_manager.unwrap(),
                 use_syscall_counters: self.config.experimental.use_syscall_counters.unwrap(),
             };
@@ -624,6 +1161,22 @@ impl<'a> Manager<'a> {
             })
         };
 
+        // generate the processes' files before any of them start
+        let template_values = TemplateValues {
+            hostname: &host_info.name,
+            ip: host_info.ip_addr.unwrap(),
+        };
+        for file in host_info.processes.iter().flat_map(|proc| &proc.files) {
+            let path = host.data_dir_path().join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create directory '{}'", parent.display())
+                })?;
+            }
+            std::fs::write(&path, file.template.render(&template_values))
+                .with_context(|| format!("Failed to write file '{}'", path.display()))?;
+        }
+
         host.lock_shmem();
 
         for proc in &host_info.processes {
@@ -638,9 +1191,15 @@ impl<'a> Manager<'a> {
                 .map(|x| CString::new(x.as_bytes()).unwrap())
                 .collect();
 
-            let envv: Vec<CString> = proc
-                .env
-                .clone()
+            let mut env = proc.env.clone();
+
+            // trust the simulation's CA unless the process was configured with its own CA file
+            if let Some(path) = &tls_ca_cert {
+                env.entry(EnvName::new("SSL_CERT_FILE").unwrap())
+                    .or_insert_with(|| path.to_str().unwrap().to_string());
+            }
+
+            let envv: Vec<CString> = env
                 .into_iter()
                 .map(|(x, y)| {
                     let mut x: OsString = String::from(x).into();
@@ -654,14 +1213,17 @@ impl<'a> Manager<'a> {
 
             host.add_application(
                 proc.start_time,
+                proc.start_after.clone(),
                 proc.shutdown_time,
                 proc.shutdown_signal,
+                proc.signals.clone(),
                 plugin_name,
                 plugin_path,
                 argv,
                 envv,
                 pause_for_debugging,
                 proc.expected_final_state,
+                proc.restart.clone(),
             );
 
             host.stop_execution_timer();
@@ -734,16 +1296,15 @@ impl<'a> Manager<'a> {
             }
         }
 
-        if self.check_mem_usage {
+        if let Some(threshold) = self.mem_warning_threshold {
             match self.memory_remaining() {
-                // if less than 500 MiB available
-                Ok(remaining) if remaining < 500 * 1024 * 1024 => {
+                Ok(remaining) if remaining < threshold => {
                     log::warn!("Only {} MiB of memory available", remaining / 1024 / 1024);
-                    self.check_mem_usage = false;
+                    self.mem_warning_threshold = None;
                 }
                 Err(e) => {
                     log::warn!("Unable to check memory usage: {e}");
-                    self.check_mem_usage = false;
+                    self.mem_warning_threshold = None;
                 }
                 Ok(_) => {}
             }
@@ -785,7 +1346,9 @@ impl<'a> Manager<'a> {
         let page_size: u64 = page_size.try_into().unwrap();
         let avl_pages: u64 = avl_pages.try_into().unwrap();
 
-        Ok(page_size * avl_pages)
+        // the cgroup's memory limit may be reached before the system runs out of memory
+        let remaining = page_size * avl_pages;
+        Ok(resource_usage::cgroup_memory_remaining().map_or(remaining, |x| x.min(remaining)))
     }
 
     pub fn shmem(&self) -> &ShMemBlock<ManagerShmem> {
@@ -800,14 +1363,98 @@ pub struct ManagerConfig {
     // map of ip addresses to graph nodes
     pub ip_assignment: IpAssignment<u32>,
 
-    // routing information for paths between graph nodes
-    pub routing_info: RoutingInfo<u32>,
+    // routing information for paths between graph nodes, which changes with link events
+    pub routing: RoutingSchedule<u32>,
 
     // bandwidths of hosts at ip addresses
     pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
 
     // a list of hosts and their processes
     pub hosts: Vec<HostInfo>,
+
+    // named phases of the simulation
+    pub phases: SimPhases,
+
+    // whether to collect the statistics shown by the dashboard
+    pub use_dashboard: bool,
+
+    // external time-series databases to send simulation metrics to
+    pub stats_sinks: Vec<StatsSinkConfig>,
+
+    // conditions that stop the simulation before its end time
+    pub stop_conditions: SimStopConditions,
+
+    // limits on the size of the hosts' output files
+    pub output_limits: SimOutputLimits,
+
+    // a socket for pausing, resuming, inspecting, and stopping the simulation while it's running
+    pub control_socket: Option<ControlSocket>,
+
+    // writes a checkpoint of the simulation at the checkpoint time
+    pub checkpointer: Option<Checkpointer>,
+
+    // the other managers, if the hosts are partitioned across several managers
+    pub remote_managers: Option<RemoteManagers>,
+}
+
+/// The state of a scheduler thread during a scheduling round.
+#[derive(Default)]
+struct ThreadRoundData {
+    // the minimum next event time of the thread's hosts
+    next_event_time: AtomicRefCell<Option<EmulatedTime>>,
+
+    // the number of events executed by the thread since the start of the simulation, only counted
+    // if the dashboard or a stats sink is enabled
+    num_events: AtomicU64,
+}
+
+/// Managed process failures that occurred during the simulation.
+pub struct PluginErrors {
+    // number of processes that ended in an unexpected state
+    pub num_plugin_errors: u32,
+
+    // unsupported syscalls that were made by any managed process
+    pub unsupported_syscalls: Vec<SyscallNum>,
+
+    // why the simulation failed, if an output file exceeded its limit
+    pub output_limit_failure: Option<String>,
+
+    // why the simulation failed, if the checkpoint couldn't be written
+    pub checkpoint_failure: Option<String>,
+
+    // why the simulation failed, if we couldn't synchronize with the other managers
+    pub remote_manager_failure: Option<String>,
+}
+
+/// Set the preferred NUMA node of the calling thread's memory allocations. Memory placement only
+/// affects performance, so the first failure is logged and failures are otherwise ignored.
+fn set_preferred_node(node: Option<u32>) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+
+    if let Err(e) = cpu::set_preferred_node(node) {
+        WARNED.call_once(|| warn!("Could not set the preferred NUMA node to {node:?}: {e}"));
+    }
+}
+
+/// Register the address of a host that is run by another manager with the DNS, so that the
+/// hosts of this manager can resolve its name.
+fn register_remote_host(host_id: HostId, host_info: &HostInfo, dns: *mut c::DNS) {
+    let std::net::IpAddr::V4(ip) = host_info.ip_addr.unwrap() else {
+        unreachable!("IPv6 not supported");
+    };
+    let name = CString::new(&*host_info.name).unwrap();
+
+    let addr = unsafe {
+        c::dns_register(
+            dns,
+            host_id,
+            name.as_ptr(),
+            u32::from(ip).to_be(),
+            SimulationTime::to_c_simtime(Some(host_info.start_time)),
+        )
+    };
+    assert!(!addr.is_null());
+    unsafe { c::address_unref(addr) };
 }
 
 /// Helper function to initialize the global [`Host`] before running the closure.
@@ -822,6 +1469,39 @@ fn for_each_host(host_iter: &mut HostIter<Box<Host>>, mut f: impl FnMut(&Host))
     });
 }
 
+/// The metrics sent to the stats sinks. `host_event_counts` and `hosts` are indexed by host id.
+fn stats_sink_metrics(
+    sim_time: EmulatedTime,
+    thread_round_data: &[ThreadRoundData],
+    host_event_counts: &[AtomicU64],
+    hosts: &[HostInfo],
+    meminfo_file: &mut std::fs::File,
+) -> Vec<Metric> {
+    let sim_time = sim_time.saturating_duration_since(&EmulatedTime::SIMULATION_START);
+    let events = thread_round_data
+        .iter()
+        .map(|x| x.num_events.load(Ordering::Relaxed))
+        .sum();
+
+    let mut metrics = vec![
+        Metric::new("sim_time_ns", sim_time.as_nanos().try_into().unwrap()),
+        Metric::new("events", events),
+    ];
+
+    if let Some(used) = resource_usage::meminfo(meminfo_file)
+        .ok()
+        .and_then(|x| x.used())
+    {
+        metrics.push(Metric::new("mem_used_bytes", used));
+    }
+
+    metrics.extend(host_event_counts.iter().zip(hosts).map(|(count, host)| {
+        Metric::new("host_events", count.load(Ordering::Relaxed)).with_tag("host", &host.name)
+    }));
+
+    metrics
+}
+
 /// Get the raw speed of the experiment machine.
 fn get_raw_cpu_frequency_hz() -> anyhow::Result<u64> {
     const CONFIG_CPU_MAX_FREQ_FILE: &str = "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq";
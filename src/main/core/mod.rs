@@ -1,13 +1,30 @@
 //! The core infrastructure needed to configure and run the simulator.
 
+pub mod checkpoint;
+pub mod config_convert;
+pub mod config_include;
+pub mod config_override;
+pub mod config_templates;
+pub mod config_validate;
+pub mod config_variables;
 pub mod configuration;
+pub mod control_socket;
 pub mod controller;
 pub mod cpu;
+pub mod dashboard;
+pub mod distributed;
+pub mod failure;
+pub mod file_template;
 pub mod logger;
 pub mod manager;
 pub mod resource_usage;
+pub mod routing_dump;
 pub mod runahead;
+pub mod selftest;
 pub mod sim_config;
 pub mod sim_stats;
+pub mod stats_sink;
+pub mod sweep;
+pub mod tls;
 pub mod work;
 pub mod worker;
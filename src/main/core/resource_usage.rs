@@ -1,10 +1,14 @@
-//! Utilities for getting system resource usage.
+//! Utilities for getting system resource usage, and for detecting the resources that are
+//! available to Shadow.
 
 use std::fs::File;
 use std::io::{Read, Seek};
+use std::path::PathBuf;
 
 use serde::Serialize;
 
+use crate::core::cpu;
+
 /// Memory usage information parsed from '/proc/meminfo'. All units are converted to bytes.
 #[derive(Copy, Clone, Debug, Default, Serialize)]
 pub struct MemInfo {
@@ -18,6 +22,16 @@ pub struct MemInfo {
     shmem: Option<u64>,
 }
 
+impl MemInfo {
+    /// Memory in use in bytes. This is calculated like the "used" column of free(1), except that
+    /// shared memory (which Shadow uses heavily) is counted as used rather than as cache.
+    pub fn used(&self) -> Option<u64> {
+        let cache = self.cached? + self.s_reclaimable?;
+        let unused = self.mem_free? + self.buffers? + cache.saturating_sub(self.shmem?);
+        Some(self.mem_total?.saturating_sub(unused))
+    }
+}
+
 /// Collects some of the fields from '/proc/meminfo'. This function will seek to the start of the
 /// file before reading.
 pub fn meminfo(file: &mut File) -> std::io::Result<MemInfo> {
@@ -77,3 +91,123 @@ fn as_base_unit(val: u64, unit: Option<&str>) -> Option<u64> {
 
     val.checked_mul(mul)
 }
+
+/// The resources available to Shadow, taking into account its CPU affinity and the limits of its
+/// cgroup.
+#[derive(Copy, Clone, Debug)]
+pub struct AvailableResources {
+    /// The number of physical CPU cores in the CPU affinity mask.
+    pub physical_cores: u32,
+    /// The CPU time that the cgroup is allowed to use, in CPUs, if limited.
+    pub cpu_quota: Option<f64>,
+    /// The system's memory, or the cgroup's memory limit if it's smaller, in bytes.
+    pub memory: Option<u64>,
+}
+
+impl AvailableResources {
+    /// The minimum available memory at which to warn if the memory isn't known.
+    const DEFAULT_MEMORY_WARNING_THRESHOLD: u64 = 500 * 1024 * 1024;
+
+    pub fn detect(meminfo_file: &mut File) -> Self {
+        let cgroup = cgroup_dir();
+        let cgroup_file = |name: &str| {
+            let path = cgroup.as_ref()?.join(name);
+            std::fs::read_to_string(path).ok()
+        };
+
+        let cpu_quota = cgroup_file("cpu.max").and_then(|x| parse_cpu_max(&x));
+        let cgroup_memory = cgroup_file("memory.max").and_then(|x| parse_memory_max(&x));
+        let system_memory = meminfo(meminfo_file).ok().and_then(|x| x.mem_total);
+
+        Self {
+            physical_cores: cpu::count_physical_cores(),
+            cpu_quota,
+            memory: match (system_memory, cgroup_memory) {
+                (Some(x), Some(y)) => Some(std::cmp::min(x, y)),
+                (x, y) => x.or(y),
+            },
+        }
+    }
+
+    /// The number of threads to run the simulation with: the number of physical cores, but no more
+    /// than the cgroup's CPU quota allows.
+    pub fn parallelism(&self) -> u32 {
+        let quota = self
+            .cpu_quota
+            .map_or(u32::MAX, |x| (x.ceil() as u32).max(1));
+        std::cmp::min(self.physical_cores, quota)
+    }
+
+    /// The amount of available memory below which Shadow should warn that it's running out of
+    /// memory: 10% of the memory, or 500 MiB if the memory isn't known.
+    pub fn memory_warning_threshold(&self) -> u64 {
+        self.memory
+            .map_or(Self::DEFAULT_MEMORY_WARNING_THRESHOLD, |x| x / 10)
+    }
+}
+
+/// The memory that Shadow's cgroup can still use before reaching its memory limit, if limited.
+pub fn cgroup_memory_remaining() -> Option<u64> {
+    let cgroup = cgroup_dir()?;
+    let read = |name| std::fs::read_to_string(cgroup.join(name)).ok();
+
+    let limit = parse_memory_max(&read("memory.max")?)?;
+    let current: u64 = read("memory.current")?.trim().parse().ok()?;
+    Some(limit.saturating_sub(current))
+}
+
+/// The directory of Shadow's cgroup, if it's in a cgroup v2 hierarchy.
+fn cgroup_dir() -> Option<PathBuf> {
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    // the cgroup v2 entry has a hierarchy ID of 0 and no controllers
+    let path = cgroups.lines().find_map(|x| x.strip_prefix("0::"))?;
+    Some(PathBuf::from("/sys/fs/cgroup").join(path.trim_start_matches('/')))
+}
+
+/// Parse the contents of a cgroup's 'cpu.max' file, which contains the quota and the period, as
+/// the number of CPUs that the quota allows. Returns `None` if the quota is "max" (unlimited).
+fn parse_cpu_max(s: &str) -> Option<f64> {
+    let (quota, period) = s.trim().split_once(' ')?;
+    let quota: u64 = quota.parse().ok()?;
+    let period: u64 = period.parse().ok()?;
+    (period > 0).then(|| quota as f64 / period as f64)
+}
+
+/// Parse the contents of a cgroup's 'memory.max' file. Returns `None` if it's "max" (unlimited).
+fn parse_memory_max(s: &str) -> Option<u64> {
+    s.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cgroup_limits() {
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max("250000 100000\n"), Some(2.5));
+        assert_eq!(parse_cpu_max("100000 0\n"), None);
+        assert_eq!(parse_memory_max("max\n"), None);
+        assert_eq!(parse_memory_max("1073741824\n"), Some(1 << 30));
+    }
+
+    #[test]
+    fn test_available_resources() {
+        let mut resources = AvailableResources {
+            physical_cores: 8,
+            cpu_quota: None,
+            memory: None,
+        };
+        assert_eq!(resources.parallelism(), 8);
+        assert_eq!(resources.memory_warning_threshold(), 500 * 1024 * 1024);
+
+        resources.cpu_quota = Some(2.5);
+        resources.memory = Some(4 << 30);
+        assert_eq!(resources.parallelism(), 3);
+        assert_eq!(resources.memory_warning_threshold(), (4 << 30) / 10);
+
+        // a small quota still gets a thread
+        resources.cpu_quota = Some(0.1);
+        assert_eq!(resources.parallelism(), 1);
+    }
+}
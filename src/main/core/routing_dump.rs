@@ -0,0 +1,198 @@
+//! A dump of the paths between every pair of hosts at the current simulation time, which can be
+//! requested while the simulation is running by sending shadow the SIGUSR1 signal. This helps to
+//! debug unexpected connectivity between hosts, such as packets that are dropped or delayed.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Context;
+use serde::Serialize;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::network::graph::RoutingSchedule;
+
+/// Set when a dump is requested, and cleared when the request is taken.
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Request a dump, which the manager writes at the end of the current scheduling round. Safe to
+/// call from the thread that handles signals.
+pub fn request_dump() {
+    DUMP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if a dump was requested since the last call.
+pub fn take_request() -> bool {
+    DUMP_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+#[derive(Serialize)]
+struct RoutingDump<'a> {
+    /// The simulation time of the paths, in nanoseconds.
+    time_ns: u64,
+    paths: Vec<HostPaths<'a>>,
+}
+
+/// The paths from one host to another.
+#[derive(Serialize)]
+struct HostPaths<'a> {
+    source: &'a str,
+    destination: &'a str,
+    source_node: u32,
+    destination_node: u32,
+    /// Each of the equal-cost paths between the hosts' graph nodes, where the first is the default
+    /// path. Empty if there's no path between them, such as while an edge is down.
+    equal_cost_paths: Vec<PathDump>,
+}
+
+/// The properties of a path between graph nodes.
+#[derive(Serialize)]
+struct PathDump {
+    /// The latency of the path in the network graph. This is static: it doesn't include jitter,
+    /// queueing at links with a bandwidth, or the hosts' uplink traces.
+    latency_ns: u64,
+    jitter_ns: u64,
+    host_jitter_ns: u64,
+    packet_loss: f32,
+    packet_corruption: f32,
+    packet_reorder: f32,
+    /// The number of routers that the path crosses.
+    routers: u32,
+    /// The address of each router that the path crosses, or `None` for routers without an
+    /// address. Only known if a graph node has a router address.
+    router_addresses: Option<Vec<Option<std::net::Ipv4Addr>>>,
+}
+
+/// Write the paths between every pair of hosts at simulation time `time` to a JSON file in `dir`,
+/// returning the path of the file. Each host is given by its name and its graph node.
+pub fn write_dump(
+    dir: &Path,
+    time: SimulationTime,
+    hosts: &[(&str, u32)],
+    routing: &RoutingSchedule<u32>,
+) -> anyhow::Result<PathBuf> {
+    let infos = routing.equal_cost_paths_at(time);
+
+    let mut paths = Vec::new();
+    for &(src, start) in hosts {
+        for &(dst, end) in hosts {
+            // a pair of nodes with `n` equal-cost paths is included in the first `n` infos
+            let equal_cost_paths = infos
+                .iter()
+                .map_while(|info| {
+                    let path = info.path(start, end)?;
+                    let router_addresses = info.path_router(start, end, 0).is_some().then(|| {
+                        (0..)
+                            .map_while(|index| info.path_router(start, end, index))
+                            .map(|router| router.address)
+                            .collect()
+                    });
+
+                    Some(PathDump {
+                        latency_ns: path.latency_ns,
+                        jitter_ns: path.jitter.scale_ns,
+                        host_jitter_ns: path.host_jitter.scale_ns,
+                        packet_loss: path.packet_loss,
+                        packet_corruption: path.packet_corruption,
+                        packet_reorder: path.packet_reorder,
+                        routers: path.routers,
+                        router_addresses,
+                    })
+                })
+                .collect();
+
+            paths.push(HostPaths {
+                source: src,
+                destination: dst,
+                source_node: start,
+                destination_node: end,
+                equal_cost_paths,
+            });
+        }
+    }
+
+    let dump = RoutingDump {
+        time_ns: time.as_nanos().try_into().unwrap(),
+        paths,
+    };
+
+    let filename = dir.join(format!("routing-{}.json", dump.time_ns));
+    let file = std::fs::File::create(&filename)
+        .with_context(|| format!("Failed to create file '{}'", filename.display()))?;
+
+    serde_json::to_writer_pretty(file, &dump).with_context(|| {
+        format!(
+            "Failed to write routing json to file '{}'",
+            filename.display()
+        )
+    })?;
+
+    Ok(filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::network::graph::{PathProperties, RoutingInfo};
+
+    fn path(latency_ns: u64) -> PathProperties {
+        PathProperties {
+            latency_ns,
+            routers: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_write_dump() {
+        // nodes 0 and 1 have two equal-cost paths, and node 2 can't reach the other nodes
+        let first = RoutingInfo::new(HashMap::from([
+            ((0, 0), path(1)),
+            ((0, 1), path(10)),
+            ((1, 0), path(10)),
+            ((1, 1), path(1)),
+            ((2, 2), path(1)),
+        ]));
+        let second = RoutingInfo::new(HashMap::from([((0, 1), path(10)), ((1, 0), path(10))]));
+        let routing = RoutingSchedule::new(vec![first, second]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let time = SimulationTime::from_secs(5);
+        let hosts = [("a", 0), ("b", 1), ("c", 2)];
+
+        let filename = write_dump(dir.path(), time, &hosts, &routing).unwrap();
+        assert_eq!(filename, dir.path().join("routing-5000000000.json"));
+
+        let file = std::fs::File::open(filename).unwrap();
+        let dump: serde_json::Value = serde_json::from_reader(file).unwrap();
+        assert_eq!(dump["time_ns"], 5_000_000_000u64);
+
+        let paths = dump["paths"].as_array().unwrap();
+        assert_eq!(paths.len(), hosts.len() * hosts.len());
+
+        let paths_between = |src: &str, dst: &str| {
+            let host_paths = paths
+                .iter()
+                .find(|x| x["source"] == src && x["destination"] == dst)
+                .unwrap();
+            host_paths["equal_cost_paths"].as_array().unwrap().clone()
+        };
+
+        let equal_cost_paths = paths_between("a", "b");
+        assert_eq!(equal_cost_paths.len(), 2);
+        for path in &equal_cost_paths {
+            assert_eq!(path["latency_ns"], 10);
+            assert_eq!(path["routers"], 1);
+            assert!(path["router_addresses"].is_null());
+        }
+
+        // a host's path to itself isn't an equal-cost path of the second routing info
+        assert_eq!(paths_between("a", "a").len(), 1);
+
+        assert!(paths_between("a", "c").is_empty());
+        assert!(paths_between("c", "b").is_empty());
+        assert_eq!(paths_between("c", "c").len(), 1);
+    }
+}
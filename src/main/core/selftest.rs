@@ -0,0 +1,329 @@
+//! A self-test that checks whether Shadow works on the current machine.
+//!
+//! The self-test runs a few short simulations, each in its own Shadow process, and summarizes
+//! which of them passed. The first is a small network of hosts that run Shadow's own traffic
+//! generator (`shadow selftest-traffic`), so it doesn't depend on any other programs being
+//! installed. If a directory containing Shadow's test programs is given, a curated subset of them
+//! is also run to check the most commonly used syscalls.
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+
+/// The port that the traffic generator's server listens on.
+const TRAFFIC_PORT: u16 = 8080;
+/// The number of bytes that each traffic generator client sends to and receives from the server.
+const TRAFFIC_BYTES: usize = 1 << 20;
+/// The number of traffic generator clients in the network simulation.
+const NUM_CLIENTS: usize = 3;
+
+/// The test programs that are run by the self-test, if found. They all accept the
+/// `--shadow-passing` argument and only require a single host.
+const TEST_PROGRAMS: &[&str] = &[
+    "test_bind",
+    "test_clone",
+    "test_dup",
+    "test_epoll_edge",
+    "test_eventfd",
+    "test_mmap",
+    "test_pipe",
+    "test_poll",
+    "test_pthreads",
+    "test_random",
+    "test_select",
+    "test_send_recv",
+    "test_socket",
+    "test_statx",
+];
+
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skip(String),
+}
+
+struct Check {
+    name: String,
+    outcome: Outcome,
+    duration: Option<Duration>,
+}
+
+/// Run the self-test and print a summary to stdout. `test_dir` is a directory containing Shadow's
+/// test programs (for example "src/target/debug" in a build tree). Returns an error if any check
+/// failed.
+pub fn run(test_dir: Option<&Path>) -> anyhow::Result<()> {
+    let shadow_exe = std::env::current_exe().context("Could not find the shadow executable")?;
+    let work_dir = tempfile::Builder::new()
+        .prefix("shadow-selftest-")
+        .tempdir()
+        .context("Could not create a temporary directory")?;
+
+    let mut checks = Vec::new();
+
+    checks.push(Check {
+        name: "supported system".into(),
+        outcome: match crate::shadow::verify_supported_system() {
+            Ok(()) => Outcome::Pass,
+            Err(e) => Outcome::Fail(format!("{e:#}")),
+        },
+        duration: None,
+    });
+
+    let config = network_config(&shadow_exe);
+    checks.push(run_simulation(
+        &shadow_exe,
+        work_dir.path(),
+        "network",
+        &format!("network simulation ({} hosts)", NUM_CLIENTS + 1),
+        &config,
+    ));
+
+    for name in TEST_PROGRAMS {
+        let Some(test_dir) = test_dir else {
+            checks.push(Check {
+                name: name.to_string(),
+                outcome: Outcome::Skip("no test directory was given".into()),
+                duration: None,
+            });
+            continue;
+        };
+
+        let path = test_dir.join(name);
+        if !path.is_file() {
+            checks.push(Check {
+                name: name.to_string(),
+                outcome: Outcome::Skip(format!("not found in {}", test_dir.display())),
+                duration: None,
+            });
+            continue;
+        }
+
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Could not canonicalize {}", path.display()))?;
+        let config = test_program_config(&path);
+        checks.push(run_simulation(
+            &shadow_exe,
+            work_dir.path(),
+            name,
+            name,
+            &config,
+        ));
+    }
+
+    let num_failed = checks
+        .iter()
+        .filter(|x| matches!(x.outcome, Outcome::Fail(_)))
+        .count();
+
+    // keep the logs of the failed simulations
+    let work_dir = if num_failed > 0 {
+        Some(work_dir.into_path())
+    } else {
+        None
+    };
+
+    print_summary(&checks, work_dir.as_deref());
+
+    if num_failed > 0 {
+        anyhow::bail!("{num_failed} of {} self-test checks failed", checks.len());
+    }
+
+    Ok(())
+}
+
+fn print_summary(checks: &[Check], work_dir: Option<&Path>) {
+    println!("Shadow {} self-test", crate::shadow::version());
+
+    for check in checks {
+        let duration = check
+            .duration
+            .map(|x| format!(" ({:.1} s)", x.as_secs_f64()))
+            .unwrap_or_default();
+        match &check.outcome {
+            Outcome::Pass => println!("  [PASS] {}{duration}", check.name),
+            Outcome::Fail(reason) => println!("  [FAIL] {}{duration}: {reason}", check.name),
+            Outcome::Skip(reason) => println!("  [SKIP] {}: {reason}", check.name),
+        }
+    }
+
+    let count = |f: fn(&Outcome) -> bool| checks.iter().filter(|x| f(&x.outcome)).count();
+    println!(
+        "{} passed, {} failed, {} skipped",
+        count(|x| matches!(x, Outcome::Pass)),
+        count(|x| matches!(x, Outcome::Fail(_))),
+        count(|x| matches!(x, Outcome::Skip(_))),
+    );
+
+    if let Some(work_dir) = work_dir {
+        println!(
+            "The logs of the failed checks are in {}",
+            work_dir.display()
+        );
+    }
+}
+
+/// Run a simulation of `config` in a new shadow process.
+fn run_simulation(shadow_exe: &Path, work_dir: &Path, id: &str, name: &str, config: &str) -> Check {
+    let start = Instant::now();
+    let outcome = match run_shadow(shadow_exe, work_dir, id, config) {
+        Ok(()) => Outcome::Pass,
+        Err(e) => Outcome::Fail(format!("{e:#}")),
+    };
+
+    Check {
+        name: name.to_string(),
+        outcome,
+        duration: Some(start.elapsed()),
+    }
+}
+
+fn run_shadow(shadow_exe: &Path, work_dir: &Path, id: &str, config: &str) -> anyhow::Result<()> {
+    let config_path = work_dir.join(format!("{id}.yaml"));
+    let data_dir = work_dir.join(format!("{id}.data"));
+    let log_path = work_dir.join(format!("{id}.log"));
+
+    std::fs::write(&config_path, config).context("Could not write the configuration file")?;
+    let log = std::fs::File::create(&log_path).context("Could not create the log file")?;
+
+    let status = Command::new(shadow_exe)
+        .arg("--data-directory")
+        .arg(&data_dir)
+        .arg(&config_path)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()
+        .context("Could not run shadow")?;
+
+    if !status.success() {
+        anyhow::bail!("shadow exited with {status}; see {}", log_path.display());
+    }
+
+    Ok(())
+}
+
+/// A configuration for a network of hosts that run the traffic generator.
+fn network_config(shadow_exe: &Path) -> String {
+    let shadow_exe = yaml_str(shadow_exe);
+
+    let mut config = format!(
+        "\
+general:
+  stop_time: 60s
+network:
+  graph:
+    type: 1_gbit_switch
+hosts:
+  server:
+    network_node_id: 0
+    processes:
+    - path: {shadow_exe}
+      args: [selftest-traffic, server]
+      start_time: 1s
+      expected_final_state: running
+"
+    );
+
+    for i in 1..=NUM_CLIENTS {
+        config.push_str(&format!(
+            "  client{i}:
+    network_node_id: 0
+    processes:
+    - path: {shadow_exe}
+      args: [selftest-traffic, client]
+      start_time: 2s
+"
+        ));
+    }
+
+    config
+}
+
+/// A configuration for a single host that runs a test program.
+fn test_program_config(path: &Path) -> String {
+    format!(
+        "\
+general:
+  stop_time: 60s
+network:
+  graph:
+    type: 1_gbit_switch
+hosts:
+  testnode:
+    network_node_id: 0
+    processes:
+    - path: {}
+      args: --shadow-passing
+      start_time: 1s
+",
+        yaml_str(path),
+    )
+}
+
+/// Quote a path so that it can be used as a yaml string.
+fn yaml_str(path: &Path) -> String {
+    // json strings are valid yaml strings
+    serde_json::to_string(&path.to_string_lossy()).unwrap()
+}
+
+/// Run the traffic generator. This runs as a managed process within the self-test's network
+/// simulation.
+pub fn run_traffic(role: &str) -> anyhow::Result<()> {
+    match role {
+        "server" => traffic_server(),
+        "client" => traffic_client(),
+        _ => anyhow::bail!("Unknown traffic generator role '{role}'"),
+    }
+}
+
+/// Echo the bytes received on each connection back to the client.
+fn traffic_server() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", TRAFFIC_PORT))?;
+
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let mut reader = stream.try_clone()?;
+
+        std::thread::spawn(move || {
+            std::io::copy(&mut reader, &mut stream)?;
+            stream.shutdown(Shutdown::Write)
+        });
+    }
+
+    Ok(())
+}
+
+/// Send bytes to the server and check that the same bytes are echoed back.
+fn traffic_client() -> anyhow::Result<()> {
+    let payload: Vec<u8> = (0..TRAFFIC_BYTES).map(|x| x as u8).collect();
+
+    let mut stream = TcpStream::connect(("server", TRAFFIC_PORT))?;
+    let mut writer = stream.try_clone()?;
+
+    // write from another thread so that the server isn't blocked on a full send buffer
+    let payload_clone = payload.clone();
+    let write_thread = std::thread::spawn(move || {
+        writer.write_all(&payload_clone)?;
+        writer.shutdown(Shutdown::Write)
+    });
+
+    let mut received = Vec::with_capacity(TRAFFIC_BYTES);
+    stream.read_to_end(&mut received)?;
+    write_thread.join().unwrap()?;
+
+    if received != payload {
+        anyhow::bail!(
+            "Received {} bytes that don't match the {} bytes sent",
+            received.len(),
+            payload.len()
+        );
+    }
+
+    println!("Echoed {} bytes", received.len());
+    Ok(())
+}
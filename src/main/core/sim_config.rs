@@ -6,21 +6,36 @@ use std::collections::hash_map::Entry;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
-use std::sync::RwLock;
+use std::path::{Component, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::Duration;
 
 use anyhow::Context;
 use once_cell::sync::Lazy;
+use petgraph::graph::EdgeIndex;
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use crate::core::configuration::{
-    parse_string_as_args, ConfigOptions, EnvName, Flatten, HostOptions, LogInfoFlag, LogLevel,
-    ProcessArgs, ProcessFinalState, ProcessOptions, QDiscMode,
+    parse_string_as_args, ConfigOptions, DnsRecord, DnsServerOptions, EcmpMode, EgressQdisc,
+    EnvName, FirewallOptions, FirewallProtocol, Flatten, HostDefaultOptions, HostOptions,
+    LinkEventOptions, LinkState, LinkTraceOptions, LogInfoFlag, LogLevel, MiddleboxAction,
+    MiddleboxOptions, NatOptions, OutputLimitAction, PhaseOptions, ProcessArgs, ProcessFileOptions,
+    ProcessFinalState, ProcessOptions, QDiscMode, RestartOptions, RestartPolicy, RouteOptions,
+    RouterQueue, RoutingOptions, StartCondition, StatsSinkFormat, StatsSinkOptions,
+    StatsSinkProtocol, StopCondition, TcpMemLimits,
 };
-use crate::network::graph::{load_network_graph, IpAssignment, NetworkGraph, RoutingInfo};
+use crate::core::file_template::FileTemplate;
+use crate::network::graph::path_cache::PathCache;
+use crate::network::graph::routing::RoutingStrategy;
+use crate::network::graph::{
+    load_network_graph, IpAssignment, NetworkGraph, RoutingInfo, RoutingSchedule,
+};
+use crate::network::link_trace::{LinkTrace, LinkTraceRow};
+use crate::network::nat::Subnet;
+use crate::network::pcap_filter::PcapFilter;
 use crate::utility::units::{self, Unit};
 use crate::utility::{tilde_expansion, verify_plugin_path};
 
@@ -32,25 +47,32 @@ pub struct SimConfig {
     // map of ip addresses to graph nodes
     pub ip_assignment: IpAssignment<u32>,
 
-    // routing information for paths between graph nodes
-    pub routing_info: RoutingInfo<u32>,
+    // routing information for paths between graph nodes, which changes with link events
+    pub routing: RoutingSchedule<u32>,
 
     // bandwidths of hosts at ip addresses
     pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
 
     // a list of hosts and their processes
     pub hosts: Vec<HostInfo>,
+
+    // named phases of the simulation
+    pub phases: SimPhases,
+
+    // external time-series databases to send simulation metrics to
+    pub stats_sinks: Vec<StatsSinkConfig>,
+
+    // conditions that stop the simulation before its end time
+    pub stop_conditions: SimStopConditions,
+
+    // limits on the size of the hosts' output files
+    pub output_limits: SimOutputLimits,
 }
 
 impl SimConfig {
     pub fn new(config: &ConfigOptions, hosts_to_debug: &HashSet<String>) -> anyhow::Result<Self> {
-        // Xoshiro256PlusPlus is not ideal when a seed with many zeros is used, but
-        // 'seed_from_u64()' uses SplitMix64 to derive the actual seed, so we are okay here
-        let seed = config.general.seed.unwrap();
-        let mut random = Xoshiro256PlusPlus::seed_from_u64(seed.into());
-
-        // this should be the same for all hosts
-        let randomness_for_seed_calc = random.gen();
+        // this should be the same for all hosts that don't have their own seed
+        let randomness_for_seed_calc = randomness_for_seed_calc(config.general.seed.unwrap());
 
         // build the host list
         let mut hosts = vec![];
@@ -75,7 +97,7 @@ impl SimConfig {
         let graph: String = load_network_graph(config.network.graph.as_ref().unwrap())
             .map_err(|e| anyhow::anyhow!(e))
             .context("Failed to load the network graph")?;
-        let graph = NetworkGraph::parse(&graph)
+        let mut graph = NetworkGraph::parse(&graph)
             .map_err(|e| anyhow::anyhow!(e))
             .context("Failed to parse the network graph")?;
 
@@ -88,6 +110,17 @@ impl SimConfig {
                     host.name
                 ));
             }
+
+            for (i, interface) in host.interfaces.iter().enumerate() {
+                if graph.node_id_to_index(interface.network_node_id).is_none() {
+                    return Err(anyhow::anyhow!(
+                        "The network node id {} for interface 'eth{}' of host '{}' does not exist",
+                        interface.network_node_id,
+                        i + 1,
+                        host.name
+                    ));
+                }
+            }
         }
 
         // assign a bandwidth to every host
@@ -118,6 +151,34 @@ impl SimConfig {
                     host.name
                 ));
             }
+
+            // the hosts of a wireless access point lose packets depending on their distance from
+            // it
+            match (&node.wireless, host.wireless_distance) {
+                (Some(ap), distance) => {
+                    host.wireless_loss = ap.packet_loss(distance.unwrap_or(0.0))
+                }
+                (None, Some(_)) => {
+                    return Err(anyhow::anyhow!(
+                        "Host '{}' has a wireless distance, but graph node {} isn't a wireless \
+                        access point",
+                        host.name,
+                        host.network_node_id
+                    ));
+                }
+                (None, None) => {}
+            }
+
+            // bursts can't be slower than the sustained bandwidth
+            if let Some(peak) = host.bandwidth_peak_bits {
+                let bw = std::cmp::max(host.bandwidth_down_bits, host.bandwidth_up_bits).unwrap();
+                if peak < bw {
+                    return Err(anyhow::anyhow!(
+                        "The peak bandwidth for host '{}' is less than its bandwidth",
+                        host.name
+                    ));
+                }
+            }
         }
 
         // check if any hosts in 'hosts_to_debug' don't exist
@@ -132,12 +193,216 @@ impl SimConfig {
         // assign IP addresses to hosts and graph nodes
         let ip_assignment = assign_ips(&mut hosts)?;
 
-        // generate routing info between every pair of in-use nodes
-        let routing_info = generate_routing_info(
-            &graph,
-            &ip_assignment.get_nodes(),
-            config.network.use_shortest_path.unwrap(),
-        )?;
+        // a NAT gateway's public address can't be in a private subnet, and a host can't be in more
+        // than one private subnet
+        let nats: Vec<_> = hosts
+            .iter()
+            .filter_map(|host| host.nat.as_ref().map(|nat| (host, nat)))
+            .collect();
+        for (i, (host, nat)) in nats.iter().enumerate() {
+            let subnet = Subnet::new(nat.subnet, nat.prefix_len);
+            let std::net::IpAddr::V4(ip) = host.ip_addr.unwrap() else {
+                unreachable!("IPv6 not supported");
+            };
+
+            if let Some((other, _)) = nats
+                .iter()
+                .find(|(_, x)| Subnet::new(x.subnet, x.prefix_len).contains(ip))
+            {
+                return Err(anyhow::anyhow!(
+                    "The address of NAT gateway '{}' is in the private subnet of '{}'",
+                    host.name,
+                    other.name
+                ));
+            }
+
+            if let Some((other, _)) = nats[..i]
+                .iter()
+                .find(|(_, x)| Subnet::new(x.subnet, x.prefix_len).overlaps(&subnet))
+            {
+                return Err(anyhow::anyhow!(
+                    "The private subnets of NAT gateways '{}' and '{}' overlap",
+                    other.name,
+                    host.name
+                ));
+            }
+        }
+
+        // a packet can only be intercepted by one middlebox, and packets in a private subnet can't
+        // be intercepted since only the subnet's gateway can send them
+        let intercepted: Vec<_> = hosts
+            .iter()
+            .filter_map(|host| host.middlebox.as_ref().map(|x| (host, x)))
+            .flat_map(|(host, x)| {
+                x.intercept
+                    .iter()
+                    .map(move |subnet| (host, subnet.parse::<Subnet>().unwrap()))
+            })
+            .collect();
+        for (i, (host, subnet)) in intercepted.iter().enumerate() {
+            if let Some((other, _)) = intercepted[..i]
+                .iter()
+                .find(|(other, x)| other.name != host.name && x.overlaps(subnet))
+            {
+                return Err(anyhow::anyhow!(
+                    "The intercepted subnets of middleboxes '{}' and '{}' overlap",
+                    other.name,
+                    host.name
+                ));
+            }
+
+            if let Some((other, _)) = nats
+                .iter()
+                .find(|(_, x)| Subnet::new(x.subnet, x.prefix_len).overlaps(subnet))
+            {
+                return Err(anyhow::anyhow!(
+                    "Middlebox '{}' intercepts the private subnet of NAT gateway '{}'",
+                    host.name,
+                    other.name
+                ));
+            }
+        }
+
+        // the DNS server isn't a host, so no host can have its address
+        if let Some(dns_server) = &config.network.dns_server {
+            check_dns_server(dns_server).context("Invalid 'network.dns_server' option")?;
+
+            let address = std::net::IpAddr::V4(dns_server.address);
+            if let Some(host) = hosts.iter().find(|x| x.ip_addr == Some(address)) {
+                return Err(anyhow::anyhow!(
+                    "The address of the DNS server is also the address of host '{}'",
+                    host.name
+                ));
+            }
+        }
+
+        // packets contend for the channel of a wireless access point like they contend for the
+        // bandwidth of an edge
+        if let Some(node_id) = graph.access_points().first() {
+            if !config.network.use_link_contention.unwrap() {
+                return Err(anyhow::anyhow!(
+                    "Graph node {node_id} is a wireless access point, which requires the \
+                    'network.use_link_contention' option"
+                ));
+            }
+        }
+
+        // routers aren't hosts, so no host can have the address of a router
+        for (node_id, address) in graph.router_addresses() {
+            let address = std::net::IpAddr::V4(address);
+            if let Some(host) = hosts.iter().find(|x| x.ip_addr == Some(address)) {
+                return Err(anyhow::anyhow!(
+                    "The router address of graph node {node_id} is also the address of host '{}'",
+                    host.name
+                ));
+            }
+        }
+
+        // how the shortest paths are chosen
+        let routing_strategy = routing_strategy(config.network.routing.as_ref().unwrap())
+            .context("Invalid 'network.routing' option")?;
+        if !config.network.use_shortest_path.unwrap()
+            && routing_strategy != RoutingStrategy::Latency
+        {
+            return Err(anyhow::anyhow!(
+                "The 'network.routing' option requires the 'network.use_shortest_path' option"
+            ));
+        }
+        graph
+            .set_routing(routing_strategy)
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Invalid 'network.routing' option")?;
+
+        // the changes to the graph during the simulation
+        let link_events = link_events(&graph, config.network.link_events.as_deref().unwrap_or(&[]))
+            .context("Invalid 'network.link_events' option")?;
+        let mut link_traces: Vec<(EdgeIndex, LinkTrace)> = Vec::new();
+        for options in config.network.link_traces.as_deref().unwrap_or(&[]) {
+            let (edge, trace) = link_trace(&graph, options).with_context(|| {
+                format!(
+                    "Invalid 'network.link_traces' option for edge {} -> {}",
+                    options.source, options.target
+                )
+            })?;
+
+            if link_traces.iter().any(|(x, _)| *x == edge) {
+                return Err(anyhow::anyhow!(
+                    "Edge {} -> {} has more than one trace in the 'network.link_traces' option",
+                    options.source,
+                    options.target
+                ));
+            }
+
+            link_traces.push((edge, trace));
+        }
+
+        // the number of equal-cost paths that packets are spread over
+        let max_equal_cost_paths = match config.network.ecmp.unwrap() {
+            EcmpMode::Off => 1,
+            EcmpMode::Flow | EcmpMode::Packet => config.network.ecmp_max_paths.unwrap(),
+        };
+        if max_equal_cost_paths == 0 {
+            return Err(anyhow::anyhow!(
+                "The 'network.ecmp_max_paths' option must be at least 1"
+            ));
+        }
+
+        let path_cache_size = config.network.path_cache_size.unwrap();
+        let routing = if path_cache_size == 0 {
+            // generate routing info between every pair of in-use nodes
+            generate_routing_schedule(
+                &mut graph,
+                &ip_assignment.get_nodes(),
+                config.network.use_shortest_path.unwrap(),
+                config.network.use_link_contention.unwrap(),
+                max_equal_cost_paths.try_into().unwrap(),
+                &link_events,
+                &link_traces,
+            )?
+        } else {
+            // the paths are computed on demand, so they can't depend on anything that's computed
+            // for every pair of nodes up front
+            let unsupported = [
+                (
+                    config.network.use_link_contention.unwrap(),
+                    "the 'network.use_link_contention' option",
+                ),
+                (max_equal_cost_paths != 1, "the 'network.ecmp' option"),
+                (
+                    !link_events.1.is_empty(),
+                    "the 'network.link_events' option",
+                ),
+                (!link_traces.is_empty(), "the 'network.link_traces' option"),
+                (
+                    matches!(config.network.routing, Some(RoutingOptions::GaoRexford)),
+                    "\"gao_rexford\" routing",
+                ),
+                (
+                    !graph.router_addresses().is_empty(),
+                    "graph nodes that have a router address",
+                ),
+            ];
+            if let Some((_, name)) = unsupported.iter().find(|(x, _)| *x) {
+                return Err(anyhow::anyhow!(
+                    "The 'network.path_cache_size' option can't be used with {name}"
+                ));
+            }
+
+            let nodes = ip_assignment
+                .get_nodes()
+                .into_iter()
+                .map(|x| (x, *graph.node_id_to_index(x).unwrap()))
+                .collect();
+            let path_cache = PathCache::new(
+                graph,
+                nodes,
+                config.network.use_shortest_path.unwrap(),
+                path_cache_size.try_into().unwrap(),
+            );
+            RoutingSchedule::new(vec![
+                RoutingInfo::new(HashMap::new()).with_path_cache(path_cache)
+            ])
+        };
 
         // get all host bandwidths
         let host_bandwidths = hosts
@@ -153,12 +418,45 @@ impl SimConfig {
             })
             .collect();
 
+        let phases = SimPhases::new(config.general.phases.as_deref().unwrap_or(&[]))
+            .context("Failed to configure the simulation phases")?;
+
+        let stats_sinks = config
+            .general
+            .stats_sinks
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .map(|sink| {
+                build_stats_sink(sink)
+                    .with_context(|| format!("Failed to configure stats sink '{}'", sink.address))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let stop_conditions = SimStopConditions::new(
+            config.general.stop_conditions.as_deref().unwrap_or(&[]),
+            &hosts,
+        )
+        .context("Failed to configure the stop conditions")?;
+
+        let output_limits = SimOutputLimits::new(
+            config
+                .general
+                .output_max_total_size
+                .flatten()
+                .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+        );
+
         Ok(Self {
             random,
             ip_assignment,
-            routing_info,
+            routing,
             host_bandwidths,
             hosts,
+            phases,
+            stats_sinks,
+            stop_conditions,
+            output_limits,
         })
     }
 }
@@ -174,9 +472,28 @@ pub struct HostInfo {
     pub cpu_precision: Option<SimulationTime>,
     pub bandwidth_down_bits: Option<u64>,
     pub bandwidth_up_bits: Option<u64>,
+    pub bandwidth_burst_bytes: Option<u64>,
+    pub bandwidth_peak_bits: Option<u64>,
+    pub uplink_trace: Option<Arc<LinkTrace>>,
+    /// The directory that is copied into the host's data directory when the host is created.
+    pub data_template: Option<PathBuf>,
+    /// Hosts with the same affinity group are run by the same worker thread.
+    pub affinity_group: Option<String>,
+    /// The distance in meters from the host to its graph node's wireless access point.
+    pub wireless_distance: Option<f64>,
+    /// The probability that a packet sent or received by the host is lost on its wireless
+    /// channel.
+    pub wireless_loss: f32,
+    pub start_time: SimulationTime,
     pub ip_addr: Option<std::net::IpAddr>,
+    /// The subnet that the host's address is assigned from, if it doesn't have an address.
+    pub ip_pool: Option<Subnet>,
     pub log_level: Option<LogLevel>,
     pub pcap_config: Option<PcapConfig>,
+    /// The maximum size of each process's stdout and stderr files, if limited.
+    pub output_max_size: Option<u64>,
+    /// What to do when a stdout, stderr, or pcap file reaches its maximum size.
+    pub output_limit_action: OutputLimitAction,
     pub heartbeat_log_level: Option<LogLevel>,
     pub heartbeat_log_info: HashSet<LogInfoFlag>,
     pub heartbeat_interval: Option<SimulationTime>,
@@ -184,18 +501,115 @@ pub struct HostInfo {
     pub recv_buf_size: u64,
     pub autotune_send_buf: bool,
     pub autotune_recv_buf: bool,
+    pub tcp_rmem: Option<(u64, u64, u64)>,
+    pub tcp_wmem: Option<(u64, u64, u64)>,
+    pub tcp_tunables: TcpTunables,
     pub qdisc: QDiscMode,
+    pub egress_qdisc: Option<EgressQdisc>,
+    pub router_queue: RouterQueue,
+    pub nat: Option<NatOptions>,
+    pub firewall: Option<FirewallOptions>,
+    /// The host's network interfaces in addition to `eth0`.
+    pub interfaces: Vec<HostInterface>,
+    /// The host's routes, and the index of each route's interface, where `eth0` is 0.
+    pub routes: Vec<(Subnet, usize)>,
+    pub middlebox: Option<MiddleboxOptions>,
+}
+
+/// A network interface of a host in addition to its `eth0` interface.
+#[derive(Debug, Clone, Copy)]
+pub struct HostInterface {
+    pub network_node_id: u32,
+    pub ip_addr: Option<std::net::IpAddr>,
+}
+
+/// The parameters of a host's TCP sockets, which are applied when the sockets are created.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpTunables {
+    /// The initial congestion window, in packets.
+    pub init_cwnd: u32,
+    pub rto_init: SimulationTime,
+    pub rto_min: SimulationTime,
+    pub rto_max: SimulationTime,
+    /// The maximum segment lifetime; connections stay in TIME_WAIT for twice this time.
+    pub msl: SimulationTime,
+    pub delayed_ack_timeout: SimulationTime,
 }
 
 #[derive(Clone)]
 pub struct ProcessInfo {
     pub plugin: PathBuf,
     pub start_time: SimulationTime,
+    pub start_after: Vec<ProcessStartCondition>,
     pub shutdown_time: Option<SimulationTime>,
     pub shutdown_signal: nix::sys::signal::Signal,
+    pub signals: Vec<ProcessSignal>,
     pub args: Vec<OsString>,
     pub env: BTreeMap<EnvName, String>,
+    pub files: Vec<ProcessFile>,
     pub expected_final_state: ProcessFinalState,
+    pub restart: Option<ProcessRestart>,
+}
+
+/// A condition for starting a process. Other processes are given by their index in the host's
+/// processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessStartCondition {
+    /// The other process has exited and won't be restarted.
+    Exited(usize),
+    /// The other process has been running for at least the duration.
+    Running(usize, SimulationTime),
+    /// A TCP socket on the host is listening on the port.
+    Listening(u16),
+}
+
+impl ProcessStartCondition {
+    /// The other process that the condition depends on.
+    fn process(&self) -> Option<usize> {
+        match self {
+            Self::Exited(x) | Self::Running(x, _) => Some(*x),
+            Self::Listening(_) => None,
+        }
+    }
+}
+
+/// A signal to send to a process at a simulated time.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessSignal {
+    pub time: SimulationTime,
+    pub signal: nix::sys::signal::Signal,
+}
+
+/// When and how often a process is restarted after it exits.
+#[derive(Debug, Clone)]
+pub struct ProcessRestart {
+    pub policy: RestartPolicy,
+    pub max_restarts: Option<u32>,
+    pub delay: SimulationTime,
+    pub backoff: f64,
+    pub max_delay: SimulationTime,
+}
+
+impl ProcessRestart {
+    /// The delay before the process is restarted after it has already been restarted `restarts`
+    /// times.
+    pub fn delay(&self, restarts: u32) -> SimulationTime {
+        let exponent = i32::try_from(restarts).unwrap_or(i32::MAX);
+        let nanos = self.delay.as_nanos_f64() * self.backoff.powi(exponent);
+        if nanos >= self.max_delay.as_nanos_f64() {
+            return self.max_delay;
+        }
+        SimulationTime::from_nanos(nanos as u64)
+    }
+}
+
+/// A file that is rendered from a template into the host's data directory before the process
+/// starts.
+#[derive(Clone)]
+pub struct ProcessFile {
+    /// Path relative to the host's data directory.
+    pub path: PathBuf,
+    pub template: FileTemplate,
 }
 
 #[derive(Debug, Clone)]
@@ -204,9 +618,249 @@ pub struct Bandwidth {
     pub down_bytes: u64,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct PcapConfig {
     pub capture_size: u64,
+    /// Only packets that match the filter are captured.
+    pub filter: Option<PcapFilter>,
+    /// The maximum size of each pcap file, if limited.
+    pub max_size: Option<u64>,
+    /// What to do when a pcap file reaches its maximum size.
+    pub limit_action: OutputLimitAction,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatsSinkConfig {
+    pub format: StatsSinkFormat,
+    pub protocol: StatsSinkProtocol,
+    pub address: std::net::SocketAddr,
+    pub flush_interval: Duration,
+    pub prefix: String,
+    pub max_queued_flushes: usize,
+}
+
+/// The named phases of the simulation, sorted by start time. Each phase lasts until the next phase
+/// begins.
+#[derive(Debug, Clone, Default)]
+pub struct SimPhases {
+    phases: Vec<(SimulationTime, Arc<str>)>,
+}
+
+impl SimPhases {
+    pub fn new(phases: &[PhaseOptions]) -> anyhow::Result<Self> {
+        let mut rv: Vec<(SimulationTime, Arc<str>)> = Vec::with_capacity(phases.len());
+
+        for phase in phases {
+            let start_time: Duration = phase.start_time.into();
+            let start_time = SimulationTime::try_from(start_time)
+                .map_err(|_| anyhow::anyhow!("Invalid start time for phase '{}'", phase.name))?;
+
+            if phase.name.is_empty() {
+                return Err(anyhow::anyhow!("Phase names must not be empty"));
+            }
+
+            if rv.iter().any(|(_, name)| **name == phase.name) {
+                return Err(anyhow::anyhow!("Duplicate phase name '{}'", phase.name));
+            }
+
+            if let Some((prev_start, prev_name)) = rv.last() {
+                if start_time <= *prev_start {
+                    return Err(anyhow::anyhow!(
+                        "Phase '{}' must start after phase '{prev_name}'",
+                        phase.name
+                    ));
+                }
+            }
+
+            rv.push((start_time, Arc::from(phase.name.as_str())));
+        }
+
+        Ok(Self { phases: rv })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.phases.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// The index of the phase that is active at `time`, or `None` if `time` is before the first
+    /// phase.
+    pub fn phase_at(&self, time: SimulationTime) -> Option<usize> {
+        // the number of phases that have started by `time`
+        let started = self.phases.partition_point(|(start, _)| *start <= time);
+        started.checked_sub(1)
+    }
+
+    pub fn name(&self, index: usize) -> &Arc<str> {
+        &self.phases[index].1
+    }
+
+    pub fn start_time(&self, index: usize) -> SimulationTime {
+        self.phases[index].0
+    }
+}
+
+/// The conditions that stop the simulation before its end time, and the progress towards them. The
+/// simulation stops at the end of the scheduling round in which any of the conditions is met.
+#[derive(Debug, Default)]
+pub struct SimStopConditions {
+    /// The number of processes that haven't finished, if the simulation stops when they've all
+    /// finished. Processes that are expected to be running at the end aren't counted.
+    num_unfinished: Option<AtomicUsize>,
+    /// The processes (by host name and process index) that stop the simulation when they finish.
+    processes: HashSet<(String, usize)>,
+    /// The number of bytes that the hosts must send to stop the simulation.
+    max_bytes_sent: Option<u64>,
+    bytes_sent: AtomicU64,
+    met: AtomicBool,
+}
+
+impl SimStopConditions {
+    pub fn new(conditions: &[StopCondition], hosts: &[HostInfo]) -> anyhow::Result<Self> {
+        let mut rv = Self::default();
+
+        for condition in conditions {
+            match condition {
+                StopCondition::AllExited => {
+                    let num_processes = hosts
+                        .iter()
+                        .flat_map(|x| &x.processes)
+                        .filter(|x| {
+                            !matches!(x.expected_final_state, ProcessFinalState::Running(_))
+                        })
+                        .count();
+                    if num_processes == 0 {
+                        return Err(anyhow::anyhow!(
+                            "The 'all-exited' condition requires a process that isn't expected \
+                            to be running at the end of the simulation"
+                        ));
+                    }
+                    rv.num_unfinished = Some(AtomicUsize::new(num_processes));
+                }
+                StopCondition::Exited { host, process } => {
+                    let Some(info) = hosts.iter().find(|x| x.name == *host) else {
+                        return Err(anyhow::anyhow!("The host '{host}' does not exist"));
+                    };
+                    if *process >= info.processes.len() {
+                        return Err(anyhow::anyhow!(
+                            "The host '{host}' does not have a process {process}"
+                        ));
+                    }
+                    rv.processes.insert((host.clone(), *process));
+                }
+                StopCondition::BytesSent(bytes) => {
+                    let bytes = bytes.convert(units::SiPrefixUpper::Base).unwrap().value();
+                    rv.max_bytes_sent = Some(rv.max_bytes_sent.map_or(bytes, |x| x.min(bytes)));
+                }
+            }
+        }
+
+        Ok(rv)
+    }
+
+    /// Whether any of the conditions has been met.
+    pub fn is_met(&self) -> bool {
+        self.met.load(Ordering::Relaxed)
+    }
+
+    /// Update the conditions after a host's process exited and won't be restarted.
+    pub fn process_finished(
+        &self,
+        host: &str,
+        index: usize,
+        expected_final_state: ProcessFinalState,
+    ) {
+        if !matches!(expected_final_state, ProcessFinalState::Running(_)) {
+            if let Some(num_unfinished) = &self.num_unfinished {
+                if num_unfinished.fetch_sub(1, Ordering::Relaxed) == 1 {
+                    self.stop("all processes exited");
+                }
+            }
+        }
+
+        if self.processes.contains(&(host.to_string(), index)) {
+            self.stop(&format!("process {index} of host '{host}' exited"));
+        }
+    }
+
+    /// Update the conditions after a host sent a packet.
+    pub fn add_bytes_sent(&self, bytes: u64) {
+        let Some(max_bytes_sent) = self.max_bytes_sent else {
+            return;
+        };
+        let previous = self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        if previous < max_bytes_sent && previous + bytes >= max_bytes_sent {
+            self.stop(&format!("the hosts sent {max_bytes_sent} bytes"));
+        }
+    }
+
+    /// Stop the simulation at the end of the current round, for example when requested on the
+    /// control socket. Only the first reason is logged.
+    pub fn stop(&self, reason: &str) {
+        if !self.met.swap(true, Ordering::Relaxed) {
+            log::info!("Stopping the simulation at the end of this round since {reason}");
+        }
+    }
+}
+
+/// The limit on the total size of the hosts' output files, and whether any output file exceeded its
+/// limit in a way that fails the simulation.
+#[derive(Debug, Default)]
+pub struct SimOutputLimits {
+    /// The maximum total size of the output files, if limited.
+    max_total_size: Option<u64>,
+    total_size: AtomicU64,
+    /// Why the simulation failed, if it did.
+    failure: OnceLock<String>,
+}
+
+impl SimOutputLimits {
+    pub fn new(max_total_size: Option<u64>) -> Self {
+        Self {
+            max_total_size,
+            ..Default::default()
+        }
+    }
+
+    /// Count `bytes` that are about to be written to an output file. Returns false, and fails the
+    /// simulation, if they would exceed the maximum total size, in which case they shouldn't be
+    /// written.
+    pub fn add(&self, bytes: u64) -> bool {
+        let Some(max_total_size) = self.max_total_size else {
+            return true;
+        };
+        let previous = self.total_size.fetch_add(bytes, Ordering::Relaxed);
+        if previous + bytes > max_total_size {
+            self.fail(format!(
+                "The output files exceeded their maximum total size of {max_total_size} bytes"
+            ));
+            return false;
+        }
+        true
+    }
+
+    /// Record that the simulation failed because of its output. Only the first reason is kept.
+    pub fn fail(&self, reason: String) {
+        if self.failure.set(reason).is_ok() {
+            log::error!("{}; stopping the simulation", self.failure().unwrap());
+        }
+    }
+
+    /// Why the simulation failed, if an output file exceeded its limit.
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.get().map(String::as_str)
+    }
+}
+
+/// The randomness that is combined with a hostname to calculate the host's seed.
+fn randomness_for_seed_calc(seed: u32) -> u64 {
+    // Xoshiro256PlusPlus is not ideal when a seed with many zeros is used, but
+    // 'seed_from_u64()' uses SplitMix64 to derive the actual seed, so we are okay here
+    let mut random = Xoshiro256PlusPlus::seed_from_u64(seed.into());
+    random.gen()
 }
 
 /// For a host entry in the configuration options, build `HostInfo` object.
@@ -237,11 +891,153 @@ fn build_host(
         })
         .collect::<anyhow::Result<_>>()?;
 
+    check_start_conditions(&processes)?;
+
+    // processes can't start before their host joins the network
+    let start_time: SimulationTime = Duration::from(host.start_time).try_into().unwrap();
+    if let Some(proc) = processes.iter().find(|x| x.start_time < start_time) {
+        return Err(anyhow::anyhow!(
+            "Process '{}' starts before the host's start time '{}'",
+            proc.plugin.display(),
+            host.start_time,
+        ));
+    }
+
+    let ip_pool = match (&host.ip_addr, &host.ip_pool) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "The 'ip_addr' and 'ip_pool' options can't both be set"
+            ));
+        }
+        (_, Some(pool)) => {
+            let pool: Subnet = pool
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid 'ip_pool' option '{pool}': {e}"))?;
+            if !pool.is_valid() {
+                return Err(anyhow::anyhow!(
+                    "The 'ip_pool' subnet '{pool}' has address bits set outside of its prefix"
+                ));
+            }
+            Some(pool)
+        }
+        _ => None,
+    };
+
+    // processes share the host's data directory, so they can't generate the same file
+    let mut file_paths = HashSet::new();
+    for file in processes.iter().flat_map(|proc| &proc.files) {
+        if !file_paths.insert(&file.path) {
+            return Err(anyhow::anyhow!(
+                "The file '{}' is generated more than once",
+                file.path.display(),
+            ));
+        }
+    }
+
+    let tcp_rmem = host
+        .host_options
+        .tcp_rmem
+        .flatten()
+        .map(|x| tcp_mem_limits(&x))
+        .transpose()
+        .context("Invalid 'tcp_rmem' host option")?;
+    let tcp_wmem = host
+        .host_options
+        .tcp_wmem
+        .flatten()
+        .map(|x| tcp_mem_limits(&x))
+        .transpose()
+        .context("Invalid 'tcp_wmem' host option")?;
+    let egress_qdisc = host
+        .host_options
+        .egress_qdisc
+        .flatten()
+        .map(|x| check_egress_qdisc(&x).map(|_| x))
+        .transpose()
+        .context("Invalid 'egress_qdisc' host option")?;
+    let firewall = host
+        .host_options
+        .firewall
+        .clone()
+        .flatten()
+        .map(|x| check_firewall(&x).map(|_| x))
+        .transpose()
+        .context("Invalid 'firewall' host option")?;
+    let pcap_filter = host
+        .host_options
+        .pcap_filter
+        .clone()
+        .flatten()
+        .map(|x| x.parse::<PcapFilter>().map_err(|e| anyhow::anyhow!(e)))
+        .transpose()
+        .context("Invalid 'pcap_filter' host option")?;
+    let tcp_tunables = tcp_tunables(&host.host_options).context("Invalid TCP host options")?;
+    let uplink_trace = host
+        .uplink_trace
+        .as_deref()
+        .map(load_link_trace)
+        .transpose()
+        .context("Invalid 'uplink_trace' option")?
+        .map(Arc::new);
+    let data_template = host
+        .data_template
+        .as_deref()
+        .map(|x| {
+            let path = tilde_expansion(x);
+            if !path.is_dir() {
+                return Err(anyhow::anyhow!(
+                    "The directory '{}' does not exist",
+                    path.display()
+                ));
+            }
+            Ok(path)
+        })
+        .transpose()
+        .context("Invalid 'data_template' option")?;
+    if let Some(distance) = host.wireless_distance {
+        if !(distance >= 0.0 && distance.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "The 'wireless_distance' option must not be negative"
+            ));
+        }
+    }
+    let interfaces: Vec<_> = host
+        .interfaces
+        .iter()
+        .map(|x| HostInterface {
+            network_node_id: x.network_node_id,
+            ip_addr: x.ip_addr.map(|x| x.into()),
+        })
+        .collect();
+    let routes = host
+        .routes
+        .iter()
+        .map(|route| route_to_interface(route, interfaces.len()))
+        .collect::<anyhow::Result<_>>()
+        .context("Invalid 'routes' option")?;
+    let router_queue = host.host_options.router_queue.unwrap();
+    check_router_queue(&router_queue).context("Invalid 'router_queue' host option")?;
+    if let Some(nat) = &host.nat {
+        check_nat(nat).context("Invalid 'nat' option")?;
+    }
+    if let Some(middlebox) = &host.middlebox {
+        if host.nat.is_some() {
+            return Err(anyhow::anyhow!(
+                "A host can't have both the 'nat' and 'middlebox' options"
+            ));
+        }
+        check_middlebox(middlebox).context("Invalid 'middlebox' option")?;
+    }
+
     Ok(HostInfo {
         name: hostname,
         processes,
 
-        seed: randomness_for_seed_calc ^ hostname_hash,
+        // a host with its own seed derives its seed in the same way, but from its own seed
+        seed: host
+            .seed
+            .map_or(randomness_for_seed_calc, self::randomness_for_seed_calc)
+            ^ hostname_hash,
         network_node_id: host.network_node_id,
         pause_for_debugging,
 
@@ -254,8 +1050,22 @@ fn build_host(
         bandwidth_up_bits: host
             .bandwidth_down
             .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+        bandwidth_burst_bytes: host
+            .bandwidth_burst
+            .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+        bandwidth_peak_bits: host
+            .bandwidth_peak
+            .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+        uplink_trace,
+        data_template,
+        affinity_group: host.affinity_group.clone(),
+        wireless_distance: host.wireless_distance,
+        // set once the host's graph node is known
+        wireless_loss: 0.0,
 
+        start_time,
         ip_addr: host.ip_addr.map(|x| x.into()),
+        ip_pool,
         log_level: host.host_options.log_level.flatten(),
         pcap_config: host
             .host_options
@@ -269,7 +1079,20 @@ fn build_host(
                     .convert(units::SiPrefixUpper::Base)
                     .unwrap()
                     .value(),
+                filter: pcap_filter,
+                max_size: host
+                    .host_options
+                    .pcap_max_size
+                    .flatten()
+                    .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+                limit_action: host.host_options.output_limit_action.unwrap(),
             }),
+        output_max_size: host
+            .host_options
+            .output_max_size
+            .flatten()
+            .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+        output_limit_action: host.host_options.output_limit_action.unwrap(),
 
         // some options come from the config options and not the host options
         heartbeat_log_level: config.experimental.host_heartbeat_log_level,
@@ -299,26 +1122,386 @@ fn build_host(
             .value(),
         autotune_send_buf: config.experimental.socket_send_autotune.unwrap(),
         autotune_recv_buf: config.experimental.socket_recv_autotune.unwrap(),
+        tcp_rmem,
+        tcp_wmem,
+        tcp_tunables,
         qdisc: config.experimental.interface_qdisc.unwrap(),
+        egress_qdisc,
+        router_queue,
+        nat: host.nat,
+        firewall,
+        interfaces,
+        routes,
+        middlebox: host.middlebox.clone(),
     })
 }
 
-/// For a process entry in the configuration options, build a `ProcessInfo` object.
-fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Result<ProcessInfo> {
-    let start_time = Duration::from(proc.start_time).try_into().unwrap();
-    let shutdown_time = proc
-        .shutdown_time
-        .map(|x| Duration::from(x).try_into().unwrap());
-    let shutdown_signal = *proc.shutdown_signal;
-    let sim_stop_time =
-        SimulationTime::try_from(Duration::from(config.general.stop_time.unwrap())).unwrap();
+/// Check that the qdisc's options are within their valid ranges.
+fn check_egress_qdisc(qdisc: &EgressQdisc) -> anyhow::Result<()> {
+    let limit = match *qdisc {
+        EgressQdisc::Pfifo { limit } => limit,
+        EgressQdisc::Netem { limit, loss, .. } => {
+            if !(0.0..=1.0).contains(&loss) {
+                return Err(anyhow::anyhow!("Loss '{loss}' must be in the range [0,1]"));
+            }
+            limit
+        }
+        EgressQdisc::Codel { limit, .. } => limit,
+        EgressQdisc::FqCodel { limit, quantum, .. } => {
+            if quantum.value() == 0 {
+                return Err(anyhow::anyhow!("Quantum must be greater than 0"));
+            }
+            limit
+        }
+    };
 
-    if start_time >= sim_stop_time {
-        return Err(anyhow::anyhow!(
-            "Process start time '{}' must be earlier than the simulation stop time '{}'",
-            proc.start_time,
-            config.general.stop_time.unwrap(),
-        ));
+    if limit == 0 {
+        return Err(anyhow::anyhow!("Limit must be greater than 0"));
+    }
+
+    Ok(())
+}
+
+/// Get the minimum, initial, and maximum sizes of a socket buffer, checking that they're in order.
+fn tcp_mem_limits(limits: &TcpMemLimits) -> anyhow::Result<(u64, u64, u64)> {
+    let (min, default, max) = limits.to_bytes();
+
+    if min == 0 || min > default || default > max {
+        return Err(anyhow::anyhow!(
+            "Sizes '{min}, {default}, {max}' must be non-zero and in the order 'min, default, max'"
+        ));
+    }
+
+    Ok((min, default, max))
+}
+
+/// Get the TCP tunables of the host, and check that they're within their valid ranges.
+fn tcp_tunables(options: &HostDefaultOptions) -> anyhow::Result<TcpTunables> {
+    let to_simtime = |x: units::Time<units::TimePrefix>| -> SimulationTime {
+        Duration::from(x).try_into().unwrap()
+    };
+
+    let tunables = TcpTunables {
+        init_cwnd: options.tcp_init_cwnd.unwrap(),
+        rto_init: to_simtime(options.tcp_rto_init.unwrap()),
+        rto_min: to_simtime(options.tcp_rto_min.unwrap()),
+        rto_max: to_simtime(options.tcp_rto_max.unwrap()),
+        msl: to_simtime(options.tcp_msl.unwrap()),
+        delayed_ack_timeout: to_simtime(options.tcp_delayed_ack_timeout.unwrap()),
+    };
+
+    if tunables.init_cwnd == 0 {
+        return Err(anyhow::anyhow!("'tcp_init_cwnd' must be greater than 0"));
+    }
+
+    // the legacy TCP stack tracks the retransmission timeout in milliseconds as an int
+    let (min, init, max) = (tunables.rto_min, tunables.rto_init, tunables.rto_max);
+    if min < SimulationTime::MILLISECOND
+        || min > init
+        || init > max
+        || max.as_millis() > i32::MAX as u64
+    {
+        return Err(anyhow::anyhow!(
+            "Timeouts '{}, {}, {}' of 'tcp_rto_min', 'tcp_rto_init', and 'tcp_rto_max' must be at \
+             least 1 ms and in the order 'min, init, max'",
+            options.tcp_rto_min.unwrap(),
+            options.tcp_rto_init.unwrap(),
+            options.tcp_rto_max.unwrap(),
+        ));
+    }
+
+    Ok(tunables)
+}
+
+/// Check that the router queue's options are within their valid ranges.
+fn check_router_queue(queue: &RouterQueue) -> anyhow::Result<()> {
+    let to_bytes = |x: units::Bytes<units::SiPrefixUpper>| {
+        x.convert(units::SiPrefixUpper::Base).unwrap().value()
+    };
+
+    match *queue {
+        RouterQueue::Codel => {}
+        RouterQueue::DropTail { packets, bytes } => {
+            if packets == Some(0) || bytes.map(to_bytes) == Some(0) {
+                return Err(anyhow::anyhow!("Limits must be greater than 0"));
+            }
+        }
+        RouterQueue::Red {
+            limit,
+            min,
+            max,
+            probability,
+            weight,
+        } => {
+            let (limit, min, max) = (to_bytes(limit), to_bytes(min), to_bytes(max));
+            if min >= max || max > limit {
+                return Err(anyhow::anyhow!(
+                    "Sizes '{min}, {max}, {limit}' must be in the order 'min, max, limit'"
+                ));
+            }
+            if !(probability > 0.0 && probability <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "Probability '{probability}' must be in the range (0,1]"
+                ));
+            }
+            if !(weight > 0.0 && weight <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "Weight '{weight}' must be in the range (0,1]"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the NAT gateway's options are within their valid ranges.
+fn check_nat(nat: &NatOptions) -> anyhow::Result<()> {
+    if nat.prefix_len == 0 || nat.prefix_len > 32 {
+        return Err(anyhow::anyhow!(
+            "Prefix length '{}' must be in the range [1,32]",
+            nat.prefix_len
+        ));
+    }
+
+    let subnet = Subnet::new(nat.subnet, nat.prefix_len);
+    if !subnet.is_valid() {
+        return Err(anyhow::anyhow!(
+            "Subnet '{subnet}' has address bits set outside of its prefix"
+        ));
+    }
+
+    if Duration::from(nat.timeout).is_zero() {
+        return Err(anyhow::anyhow!("Timeout must be greater than 0"));
+    }
+
+    Ok(())
+}
+
+/// Get the destination subnet of a host's route, and the index of the route's interface. The host
+/// has `num_interfaces` interfaces in addition to `eth0`.
+fn route_to_interface(
+    route: &RouteOptions,
+    num_interfaces: usize,
+) -> anyhow::Result<(Subnet, usize)> {
+    let destination: Subnet = route
+        .destination
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid route destination '{}': {e}", route.destination))?;
+    if !destination.is_valid() {
+        return Err(anyhow::anyhow!(
+            "The route destination '{destination}' has address bits set outside of its prefix"
+        ));
+    }
+
+    let index = (0..=num_interfaces)
+        .find(|x| route.interface == format!("eth{x}"))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "The route to '{destination}' uses the unknown interface '{}'",
+                route.interface
+            )
+        })?;
+
+    Ok((destination, index))
+}
+
+/// Check that the firewall rules' addresses are valid subnets.
+fn check_firewall(firewall: &FirewallOptions) -> anyhow::Result<()> {
+    for rule in &firewall.rules {
+        let Some(addr) = &rule.remote_addr else {
+            continue;
+        };
+
+        let subnet: Subnet = addr
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid remote address '{addr}': {e}"))?;
+        if !subnet.is_valid() {
+            return Err(anyhow::anyhow!(
+                "Subnet '{subnet}' has address bits set outside of its prefix"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse an address or a subnet in CIDR notation, checking that it has no address bits set outside
+/// of its prefix.
+fn parse_subnet(addr: &str) -> anyhow::Result<Subnet> {
+    let subnet: Subnet = addr
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid address '{addr}': {e}"))?;
+    if !subnet.is_valid() {
+        return Err(anyhow::anyhow!(
+            "Subnet '{subnet}' has address bits set outside of its prefix"
+        ));
+    }
+    Ok(subnet)
+}
+
+/// Check that the middlebox's subnets are valid, and that its rules have the options that their
+/// actions need.
+fn check_middlebox(middlebox: &MiddleboxOptions) -> anyhow::Result<()> {
+    if middlebox.intercept.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The middlebox must intercept at least one subnet"
+        ));
+    }
+    for subnet in &middlebox.intercept {
+        parse_subnet(subnet)?;
+    }
+
+    for (i, rule) in middlebox.rules.iter().enumerate() {
+        let id = rule.id.clone().unwrap_or_else(|| (i + 1).to_string());
+
+        for addr in [&rule.src_addr, &rule.dst_addr].into_iter().flatten() {
+            parse_subnet(addr).with_context(|| format!("Invalid rule '{id}'"))?;
+        }
+
+        if !(0.0..=1.0).contains(&rule.probability) {
+            return Err(anyhow::anyhow!(
+                "The probability '{}' of rule '{id}' must be in the range [0,1]",
+                rule.probability
+            ));
+        }
+
+        let needs_delay = rule.action == MiddleboxAction::Delay;
+        if needs_delay != rule.delay.is_some() {
+            return Err(anyhow::anyhow!(
+                "Rule '{id}' must have a 'delay' if and only if its action is 'delay'"
+            ));
+        }
+
+        let needs_rewrite = rule.action == MiddleboxAction::Rewrite;
+        if needs_rewrite != rule.rewrite.is_some() {
+            return Err(anyhow::anyhow!(
+                "Rule '{id}' must have a 'rewrite' if and only if its action is 'rewrite'"
+            ));
+        }
+
+        if rule.action == MiddleboxAction::Reset && rule.protocol != Some(FirewallProtocol::Tcp) {
+            return Err(anyhow::anyhow!(
+                "Rule '{id}' must have the 'tcp' protocol to use the 'reset' action"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the DNS server's address, TTLs, and record names are valid.
+fn check_dns_server(dns_server: &DnsServerOptions) -> anyhow::Result<()> {
+    let address = dns_server.address;
+    if address.is_unspecified()
+        || address.is_loopback()
+        || address.is_multicast()
+        || address.is_broadcast()
+    {
+        return Err(anyhow::anyhow!(
+            "Address '{address}' is not a unicast address"
+        ));
+    }
+
+    let check_ttl = |ttl: units::Time<units::TimePrefix>| {
+        let ttl = Duration::from(ttl);
+        if ttl.subsec_nanos() != 0 || ttl.as_secs() > u32::MAX.into() {
+            return Err(anyhow::anyhow!(
+                "TTL '{ttl:?}' must be a whole number of seconds that fits in 32 bits"
+            ));
+        }
+        Ok(())
+    };
+
+    check_ttl(dns_server.ttl)?;
+
+    for record in &dns_server.records {
+        let name = record.name();
+        if let Some(ttl) = record.ttl() {
+            check_ttl(ttl).with_context(|| format!("Invalid record for name '{name}'"))?;
+        }
+
+        let targets = match record {
+            DnsRecord::Cname { target, .. } | DnsRecord::Srv { target, .. } => Some(target),
+            DnsRecord::A { .. } | DnsRecord::Aaaa { .. } => None,
+        };
+        for name in std::iter::once(name).chain(targets.map(String::as_str)) {
+            // names have at most 253 characters and labels have 1 to 63 characters (RFC 1035)
+            let labels = name.strip_suffix('.').unwrap_or(name);
+            if labels.len() > 253 || labels.split('.').any(|x| x.is_empty() || x.len() > 63) {
+                return Err(anyhow::anyhow!("Name '{name}' is not a valid domain name"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the processes' start conditions refer to other processes of the host, and that they
+/// don't depend on each other in a cycle, in which case none of them would start.
+fn check_start_conditions(processes: &[ProcessInfo]) -> anyhow::Result<()> {
+    for (index, proc) in processes.iter().enumerate() {
+        for other in proc.start_after.iter().filter_map(|x| x.process()) {
+            if other == index || other >= processes.len() {
+                return Err(anyhow::anyhow!(
+                    "Process {index} ('{}') has a start condition for process {other}, which isn't \
+                    another of the host's processes",
+                    proc.plugin.display(),
+                ));
+            }
+        }
+    }
+
+    // a depth-first search from each process, where `path` is the processes that the current
+    // process is depended on by
+    fn visit(processes: &[ProcessInfo], index: usize, path: &mut Vec<usize>) -> Option<Vec<usize>> {
+        if let Some(start) = path.iter().position(|x| *x == index) {
+            return Some(path[start..].to_vec());
+        }
+        path.push(index);
+        for other in processes[index]
+            .start_after
+            .iter()
+            .filter_map(|x| x.process())
+        {
+            if let Some(cycle) = visit(processes, other, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    for index in 0..processes.len() {
+        if let Some(cycle) = visit(processes, index, &mut Vec::new()) {
+            let cycle: Vec<_> = cycle.iter().map(|x| x.to_string()).collect();
+            return Err(anyhow::anyhow!(
+                "The start conditions of processes {} depend on each other, so they would never \
+                start",
+                cycle.join(", "),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// For a process entry in the configuration options, build a `ProcessInfo` object.
+fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Result<ProcessInfo> {
+    let start_time = Duration::from(proc.start_time).try_into().unwrap();
+    let shutdown_time = proc
+        .shutdown_time
+        .map(|x| Duration::from(x).try_into().unwrap());
+    let shutdown_signal = *proc.shutdown_signal;
+    let sim_stop_time =
+        SimulationTime::try_from(Duration::from(config.general.stop_time.unwrap())).unwrap();
+
+    if start_time >= sim_stop_time {
+        return Err(anyhow::anyhow!(
+            "Process start time '{}' must be earlier than the simulation stop time '{}'",
+            proc.start_time,
+            config.general.stop_time.unwrap(),
+        ));
     }
 
     if let Some(shutdown_time) = shutdown_time {
@@ -338,6 +1521,41 @@ fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Resul
         }
     }
 
+    let start_after = proc
+        .start_after
+        .iter()
+        .map(|x| match x {
+            StartCondition::Exited(process) => ProcessStartCondition::Exited(*process),
+            StartCondition::Running { process, duration } => ProcessStartCondition::Running(
+                *process,
+                Duration::from(*duration).try_into().unwrap(),
+            ),
+            StartCondition::Listening(port) => ProcessStartCondition::Listening(*port),
+        })
+        .collect();
+
+    let signals = proc
+        .signals
+        .iter()
+        .map(|x| {
+            let time = Duration::from(x.time).try_into().unwrap();
+            if time <= start_time || time >= sim_stop_time {
+                return Err(anyhow::anyhow!(
+                    "Time '{}' of signal {} must be after the process start time '{}' and before \
+                    the simulation stop time '{}'",
+                    x.time,
+                    x.signal,
+                    proc.start_time,
+                    config.general.stop_time.unwrap(),
+                ));
+            }
+            Ok(ProcessSignal {
+                time,
+                signal: *x.signal,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
     let mut args = match &proc.args {
         ProcessArgs::List(x) => x.iter().map(|y| OsStr::new(y).to_os_string()).collect(),
         ProcessArgs::Str(x) => parse_string_as_args(OsStr::new(&x.trim()))
@@ -383,14 +1601,125 @@ fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Resul
     // set argv[0] as the user-provided expanded string, not the canonicalized version
     args.insert(0, expanded_path.into());
 
+    let files = proc
+        .files
+        .iter()
+        .map(|file| {
+            build_process_file(file)
+                .with_context(|| format!("Failed to configure file '{}'", file.path.display()))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
     Ok(ProcessInfo {
         plugin: canonical_path,
         start_time,
+        start_after,
         shutdown_time,
         shutdown_signal,
+        signals,
         args,
-        env: proc.environment.clone(),
+        env: proc
+            .environment
+            .iter()
+            .map(|(name, value)| (name.clone(), String::from(value.clone())))
+            .collect(),
+        files,
         expected_final_state: proc.expected_final_state,
+        restart: proc
+            .restart
+            .as_ref()
+            .map(build_process_restart)
+            .transpose()
+            .context("Invalid 'restart' option")?,
+    })
+}
+
+/// For a process's restart options, build a `ProcessRestart` object.
+fn build_process_restart(restart: &RestartOptions) -> anyhow::Result<ProcessRestart> {
+    let delay: SimulationTime = Duration::from(restart.delay).try_into().unwrap();
+    let max_delay = restart
+        .max_delay
+        .map(|x| Duration::from(x).try_into().unwrap())
+        .unwrap_or(SimulationTime::MAX);
+
+    // a process that exits immediately would otherwise be restarted forever without time advancing
+    if delay == SimulationTime::ZERO {
+        return Err(anyhow::anyhow!("The delay must be greater than 0"));
+    }
+    if !(restart.backoff >= 1.0 && restart.backoff.is_finite()) {
+        return Err(anyhow::anyhow!(
+            "The backoff '{}' must be at least 1",
+            restart.backoff
+        ));
+    }
+    if max_delay < delay {
+        return Err(anyhow::anyhow!(
+            "The maximum delay must not be less than the delay"
+        ));
+    }
+
+    Ok(ProcessRestart {
+        policy: restart.policy,
+        max_restarts: restart.max_restarts,
+        delay,
+        backoff: restart.backoff,
+        max_delay,
+    })
+}
+
+/// For a process file entry in the configuration options, build a `ProcessFile` object.
+fn build_process_file(file: &ProcessFileOptions) -> anyhow::Result<ProcessFile> {
+    // the file must stay within the host's data directory
+    let is_relative = file
+        .path
+        .components()
+        .all(|x| matches!(x, Component::Normal(_) | Component::CurDir));
+    if !is_relative || file.path.file_name().is_none() {
+        return Err(anyhow::anyhow!(
+            "The path must be a relative file path without '..' components"
+        ));
+    }
+
+    let template = file
+        .template
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid template: {e}"))?;
+
+    Ok(ProcessFile {
+        path: file.path.clone(),
+        template,
+    })
+}
+
+/// For a stats sink entry in the configuration options, build a `StatsSinkConfig` object.
+fn build_stats_sink(sink: &StatsSinkOptions) -> anyhow::Result<StatsSinkConfig> {
+    use std::net::ToSocketAddrs;
+
+    let address = sink
+        .address
+        .to_socket_addrs()
+        .context("Failed to resolve the address")?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("The address did not resolve to any socket addresses"))?;
+
+    let flush_interval = Duration::from(sink.flush_interval);
+    if flush_interval.is_zero() {
+        return Err(anyhow::anyhow!("The flush interval must be greater than 0"));
+    }
+
+    if sink.max_queued_flushes == 0 {
+        return Err(anyhow::anyhow!(
+            "The maximum number of queued flushes must be greater than 0"
+        ));
+    }
+
+    Ok(StatsSinkConfig {
+        format: sink.format,
+        protocol: sink.protocol,
+        address,
+        flush_interval,
+        prefix: sink.prefix.clone(),
+        max_queued_flushes: sink.max_queued_flushes.try_into().unwrap(),
     })
 }
 
@@ -409,6 +1738,38 @@ fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
         })?;
     }
 
+    // and the additional interfaces that have a specific IP address
+    for host in hosts.iter() {
+        for (i, interface) in host.interfaces.iter().enumerate() {
+            let Some(ip) = interface.ip_addr else {
+                continue;
+            };
+            let node_id = interface.network_node_id;
+            ip_assignment.assign_ip(node_id, ip).with_context(|| {
+                format!(
+                    "Failed to assign IP address {ip} for interface 'eth{}' of host '{}' to node \
+                    '{node_id}'",
+                    i + 1,
+                    host.name
+                )
+            })?;
+        }
+    }
+
+    // then register hosts that get an address from a pool, in order
+    for host in hosts.iter_mut().filter(|x| x.ip_pool.is_some()) {
+        let pool = host.ip_pool.unwrap();
+        let ip = ip_assignment
+            .assign_from_pool(host.network_node_id, pool)
+            .with_context(|| {
+                format!(
+                    "No unused IP addresses for host '{}' in the pool '{pool}'",
+                    host.name
+                )
+            })?;
+        host.ip_addr = Some(ip);
+    }
+
     // then register remaining hosts
     for host in hosts.iter_mut().filter(|x| x.ip_addr.is_none()) {
         let ip = ip_assignment.assign(host.network_node_id);
@@ -416,15 +1777,269 @@ fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
         host.ip_addr = Some(ip);
     }
 
+    // then register the remaining additional interfaces
+    for host in hosts.iter_mut() {
+        for interface in host.interfaces.iter_mut().filter(|x| x.ip_addr.is_none()) {
+            interface.ip_addr = Some(ip_assignment.assign(interface.network_node_id));
+        }
+    }
+
     Ok(ip_assignment)
 }
 
+/// The edges with link events, and the time, edge index, and state of each event.
+type LinkEvents = (Vec<EdgeIndex>, Vec<(SimulationTime, usize, LinkState)>);
+
+/// Get the edges with link events, and the time of each event and the index of its edge.
+fn link_events(
+    graph: &NetworkGraph,
+    link_events: &[LinkEventOptions],
+) -> anyhow::Result<LinkEvents> {
+    let mut edges = Vec::new();
+    let mut events = Vec::new();
+
+    for event in link_events {
+        let time: Duration = event.time.into();
+        let time = SimulationTime::try_from(time).map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid time for the link event of edge {} -> {}",
+                event.source,
+                event.target
+            )
+        })?;
+
+        // paths from a node to itself always use its self-loop
+        if event.source == event.target {
+            return Err(anyhow::anyhow!(
+                "The self-loop of node {} can't go down",
+                event.source
+            ));
+        }
+
+        let Some(edge) = graph.edge_between(event.source, event.target) else {
+            return Err(anyhow::anyhow!(
+                "No edge connecting node {} to {}",
+                event.source,
+                event.target
+            ));
+        };
+
+        let index = match edges.iter().position(|x| *x == edge) {
+            Some(index) => index,
+            None => {
+                edges.push(edge);
+                edges.len() - 1
+            }
+        };
+
+        events.push((time, index, event.state));
+    }
+
+    Ok((edges, events))
+}
+
+/// Load the trace of a graph edge, and check that the edge can have the trace's attributes.
+fn link_trace(
+    graph: &NetworkGraph,
+    options: &LinkTraceOptions,
+) -> anyhow::Result<(EdgeIndex, LinkTrace)> {
+    let Some(edge) = graph.edge_between(options.source, options.target) else {
+        return Err(anyhow::anyhow!(
+            "No edge connecting node {} to {}",
+            options.source,
+            options.target
+        ));
+    };
+
+    let trace = load_link_trace(&options.path)?;
+
+    if trace
+        .rows()
+        .iter()
+        .any(|row| row.latency.is_some_and(|x| x.value() == 0))
+    {
+        return Err(anyhow::anyhow!("The trace's latencies must not be 0"));
+    }
+
+    // the links with a bandwidth can't change during the simulation
+    if trace.has_bandwidth() && graph.edge_weight(edge).bandwidth.is_none() {
+        return Err(anyhow::anyhow!(
+            "The edge needs a 'bandwidth' for the trace to change its bandwidth"
+        ));
+    }
+
+    Ok((edge, trace))
+}
+
+/// Read and parse a link trace file.
+fn load_link_trace(path: &str) -> anyhow::Result<LinkTrace> {
+    let path = tilde_expansion(path);
+    let trace = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the trace file '{}'", path.display()))?;
+    trace
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Failed to parse the trace file '{}': {e}", path.display()))
+}
+
+/// Get the routing strategy of the routing options, loading static routes from their file.
+fn routing_strategy(options: &RoutingOptions) -> anyhow::Result<RoutingStrategy> {
+    Ok(match options {
+        RoutingOptions::Latency => RoutingStrategy::Latency,
+        RoutingOptions::HopCount => RoutingStrategy::HopCount,
+        RoutingOptions::Bandwidth => RoutingStrategy::Bandwidth,
+        RoutingOptions::Static { path } => {
+            let path = tilde_expansion(path);
+            let routes = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read the routes file '{}'", path.display()))?;
+            let routes = routes.parse().map_err(|e| {
+                anyhow::anyhow!("Failed to parse the routes file '{}': {e}", path.display())
+            })?;
+            RoutingStrategy::Static(routes)
+        }
+        RoutingOptions::GaoRexford => RoutingStrategy::GaoRexford,
+    })
+}
+
+/// A change to the graph at a simulated time.
+enum GraphChange<'a> {
+    /// A link event of the edge at an index of the edges with link events.
+    Link(usize, LinkState),
+    /// A row of the trace of an edge.
+    Trace(EdgeIndex, &'a LinkTraceRow),
+}
+
+/// Generate the routing info between every pair of in-use nodes before the first change to the
+/// graph, and after the link events and the rows of the link traces at each time. The routing
+/// info of up to `max_equal_cost_paths` equal-cost paths is generated for each.
+fn generate_routing_schedule(
+    graph: &mut NetworkGraph,
+    nodes: &std::collections::HashSet<u32>,
+    use_shortest_paths: bool,
+    use_link_contention: bool,
+    max_equal_cost_paths: usize,
+    (event_edges, link_events): &LinkEvents,
+    link_traces: &[(EdgeIndex, LinkTrace)],
+) -> anyhow::Result<RoutingSchedule<u32>> {
+    let mut changes: Vec<_> = link_events
+        .iter()
+        .map(|(time, index, state)| (*time, GraphChange::Link(*index, *state)))
+        .chain(link_traces.iter().flat_map(|(edge, trace)| {
+            trace
+                .rows()
+                .iter()
+                .map(move |row| (row.time, GraphChange::Trace(*edge, row)))
+        }))
+        .collect();
+
+    // a stable sort, so changes at the same time are applied in the order that they're listed
+    changes.sort_by_key(|(time, _)| *time);
+
+    let initial = generate_equal_cost_routing_info(
+        graph,
+        nodes,
+        use_shortest_paths,
+        use_link_contention,
+        max_equal_cost_paths,
+        event_edges,
+    )?;
+
+    let mut down_edges = HashSet::new();
+    let mut edge_weights = HashMap::new();
+    let mut down_times = vec![Vec::new(); event_edges.len()];
+    let mut periods = Vec::new();
+
+    for (i, (time, change)) in changes.iter().enumerate() {
+        match change {
+            GraphChange::Link(index, LinkState::Down) => {
+                if down_edges.insert(event_edges[*index]) {
+                    down_times[*index].push(*time);
+                }
+            }
+            GraphChange::Link(index, LinkState::Up) => {
+                down_edges.remove(&event_edges[*index]);
+            }
+            GraphChange::Trace(edge, row) => {
+                let weight = graph.graph().edge_weight(*edge).unwrap();
+                edge_weights.insert(*edge, weight.with_trace_row(row));
+            }
+        }
+
+        // recompute the paths after the last change at this time
+        if changes.get(i + 1).is_some_and(|(next, _)| next == time) {
+            continue;
+        }
+
+        graph.set_down_edges(down_edges.clone());
+        graph.set_edge_weights(edge_weights.clone());
+        let routing_info = generate_equal_cost_routing_info(
+            graph,
+            nodes,
+            use_shortest_paths,
+            use_link_contention,
+            max_equal_cost_paths,
+            event_edges,
+        )
+        .with_context(|| {
+            format!(
+                "Failed to compute the paths at time {:?}",
+                Duration::from(*time)
+            )
+        })?;
+        periods.push((*time, routing_info));
+    }
+
+    graph.set_down_edges(HashSet::new());
+    graph.set_edge_weights(HashMap::new());
+
+    Ok(RoutingSchedule::new(initial).with_events(periods, down_times))
+}
+
+/// Generate the routing info of each of the equal-cost paths between every pair of in-use nodes,
+/// up to `max_paths` paths. Direct paths only have a single path.
+fn generate_equal_cost_routing_info(
+    graph: &mut NetworkGraph,
+    nodes: &std::collections::HashSet<u32>,
+    use_shortest_paths: bool,
+    use_link_contention: bool,
+    max_paths: usize,
+    event_edges: &[EdgeIndex],
+) -> anyhow::Result<Vec<RoutingInfo<u32>>> {
+    let max_paths = if use_shortest_paths { max_paths } else { 1 };
+    let mut infos = Vec::new();
+
+    for index in 0..max_paths {
+        graph.set_equal_cost_path(index);
+        let info = generate_routing_info(
+            graph,
+            nodes,
+            use_shortest_paths,
+            use_link_contention,
+            event_edges,
+        )?;
+
+        // no pair of nodes has this many equal-cost paths
+        if index != 0 && info.is_empty() {
+            break;
+        }
+
+        infos.push(info);
+    }
+
+    graph.set_equal_cost_path(0);
+
+    Ok(infos)
+}
+
 /// Generate a map containing routing information (latency, packet loss, etc) for each pair of
-/// nodes.
+/// nodes. If `use_link_contention` is true, this also includes the links with a bandwidth that
+/// each path crosses, and if any node has a router address, the routers that each path crosses.
+/// The crossed edges in `event_edges` are also included.
 fn generate_routing_info(
     graph: &NetworkGraph,
     nodes: &std::collections::HashSet<u32>,
     use_shortest_paths: bool,
+    use_link_contention: bool,
+    event_edges: &[EdgeIndex],
 ) -> anyhow::Result<RoutingInfo<u32>> {
     // convert gml node IDs to petgraph indexes
     let nodes: Vec<_> = nodes
@@ -457,5 +2072,558 @@ fn generate_routing_info(
             .collect()
     };
 
-    Ok(RoutingInfo::new(paths))
+    let mut routing_info = RoutingInfo::new(paths);
+
+    // the routers only need to be known if one of them can send errors
+    if !graph.router_addresses().is_empty() {
+        let path_routers = graph
+            .compute_routers(&nodes[..], use_shortest_paths)
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Failed to get the routers of the paths between graph nodes")?
+            .into_iter()
+            .map(|((src, dst), routers)| {
+                let src = graph.node_index_to_id(src).unwrap();
+                let dst = graph.node_index_to_id(dst).unwrap();
+                ((src, dst), routers)
+            })
+            .collect();
+        routing_info = routing_info.with_routers(path_routers);
+    }
+
+    // the edges with link events only need to be known if there are any
+    if !event_edges.is_empty() {
+        let path_edges = graph
+            .compute_crossed_edges(&nodes[..], use_shortest_paths, event_edges)
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Failed to get the edges of the paths between graph nodes")?
+            .into_iter()
+            .map(|((src, dst), edges)| {
+                let src = graph.node_index_to_id(src).unwrap();
+                let dst = graph.node_index_to_id(dst).unwrap();
+                ((src, dst), edges)
+            })
+            .collect();
+        routing_info = routing_info.with_edges(path_edges);
+    }
+
+    if !use_link_contention {
+        return Ok(routing_info);
+    }
+
+    let (links, path_links) = graph
+        .compute_links(&nodes[..], use_shortest_paths)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Failed to get the links of the paths between graph nodes")?;
+    let path_links = path_links
+        .into_iter()
+        .map(|((src, dst), links)| {
+            let src = graph.node_index_to_id(src).unwrap();
+            let dst = graph.node_index_to_id(dst).unwrap();
+            ((src, dst), links)
+        })
+        .collect();
+
+    Ok(routing_info.with_links((links, path_links)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::graph::routing::StaticRoutes;
+
+    fn phase(name: &str, secs: u64) -> PhaseOptions {
+        PhaseOptions {
+            name: name.to_string(),
+            start_time: units::Time::new(secs, units::TimePrefix::Sec),
+        }
+    }
+
+    #[test]
+    fn test_phase_at() {
+        let phases = SimPhases::new(&[
+            phase("bootstrap", 10),
+            phase("steady", 20),
+            phase("churn", 30),
+        ])
+        .unwrap();
+
+        let at = |secs| phases.phase_at(SimulationTime::from_secs(secs));
+
+        assert_eq!(at(0), None);
+        assert_eq!(at(9), None);
+        assert_eq!(at(10), Some(0));
+        assert_eq!(at(19), Some(0));
+        assert_eq!(at(20), Some(1));
+        assert_eq!(at(30), Some(2));
+        assert_eq!(at(1000), Some(2));
+        assert_eq!(&**phases.name(1), "steady");
+
+        let phases = SimPhases::new(&[]).unwrap();
+        assert!(phases.is_empty());
+        assert_eq!(phases.phase_at(SimulationTime::from_secs(5)), None);
+    }
+
+    #[test]
+    fn test_invalid_phases() {
+        // out of order
+        assert!(SimPhases::new(&[phase("a", 10), phase("b", 5)]).is_err());
+        // same start time
+        assert!(SimPhases::new(&[phase("a", 10), phase("b", 10)]).is_err());
+        // duplicate name
+        assert!(SimPhases::new(&[phase("a", 10), phase("a", 20)]).is_err());
+        // empty name
+        assert!(SimPhases::new(&[phase("", 10)]).is_err());
+    }
+
+    #[test]
+    fn test_route_to_interface() {
+        let route = |destination: &str, interface: &str| {
+            let route = RouteOptions {
+                destination: destination.to_string(),
+                interface: interface.to_string(),
+            };
+            route_to_interface(&route, 2)
+        };
+
+        let (subnet, index) = route("10.0.0.0/8", "eth2").unwrap();
+        assert_eq!(subnet, "10.0.0.0/8".parse().unwrap());
+        assert_eq!(index, 2);
+        assert_eq!(route("0.0.0.0/0", "eth0").unwrap().1, 0);
+        assert_eq!(route("10.0.0.1", "eth1").unwrap().1, 1);
+
+        assert!(route("10.0.0.0/8", "eth3").is_err());
+        assert!(route("10.0.0.0/8", "eth01").is_err());
+        assert!(route("10.0.0.0/8", "lo").is_err());
+        assert!(route("10.0.0.1/8", "eth1").is_err());
+        assert!(route("10.0.0.0/33", "eth1").is_err());
+    }
+
+    #[test]
+    fn test_tcp_tunables() {
+        fn ms(x: u64) -> Option<units::Time<units::TimePrefix>> {
+            Some(units::Time::new(x, units::TimePrefix::Milli))
+        }
+
+        let tunables = tcp_tunables(&HostDefaultOptions::new_with_defaults()).unwrap();
+        assert_eq!(tunables.init_cwnd, 10);
+        assert_eq!(tunables.rto_init, SimulationTime::SECOND);
+        assert_eq!(tunables.rto_min, SimulationTime::from_millis(200));
+        assert_eq!(tunables.rto_max, SimulationTime::from_secs(120));
+        assert_eq!(tunables.msl, SimulationTime::from_secs(30));
+        assert_eq!(tunables.delayed_ack_timeout, SimulationTime::from_millis(5));
+
+        let options = |f: fn(&mut HostDefaultOptions)| {
+            let mut options = HostDefaultOptions::new_with_defaults();
+            f(&mut options);
+            options
+        };
+
+        assert!(tcp_tunables(&options(|x| x.tcp_init_cwnd = Some(0))).is_err());
+        // the minimum must be at least 1 ms
+        assert!(tcp_tunables(&options(
+            |x| x.tcp_rto_min = Some(units::Time::new(500, units::TimePrefix::Micro))
+        ))
+        .is_err());
+        // the initial timeout must be within the bounds
+        assert!(tcp_tunables(&options(|x| x.tcp_rto_init = ms(100))).is_err());
+        assert!(tcp_tunables(&options(|x| x.tcp_rto_max = ms(500))).is_err());
+
+        let tunables = tcp_tunables(&options(|x| {
+            x.tcp_rto_min = ms(1);
+            x.tcp_rto_init = ms(1);
+            x.tcp_msl = ms(0);
+        }))
+        .unwrap();
+        assert_eq!(tunables.rto_min, SimulationTime::MILLISECOND);
+        assert_eq!(tunables.msl, SimulationTime::ZERO);
+    }
+
+    /// A triangle where the path from node 0 to 2 goes through node 1.
+    const TRIANGLE_GRAPH: &str = r#"graph [
+      node [
+        id 0
+      ]
+      node [
+        id 1
+      ]
+      node [
+        id 2
+      ]
+      edge [
+        source 0
+        target 0
+        latency "1 ms"
+      ]
+      edge [
+        source 1
+        target 1
+        latency "1 ms"
+      ]
+      edge [
+        source 2
+        target 2
+        latency "1 ms"
+      ]
+      edge [
+        source 0
+        target 1
+        latency "10 ms"
+      ]
+      edge [
+        source 1
+        target 2
+        latency "10 ms"
+      ]
+      edge [
+        source 0
+        target 2
+        latency "50 ms"
+      ]
+    ]"#;
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_link_events() {
+        let mut graph = NetworkGraph::parse(TRIANGLE_GRAPH).unwrap();
+        let nodes = HashSet::from([0, 1, 2]);
+
+        let event = |secs, source, target, state| LinkEventOptions {
+            time: units::Time::new(secs, units::TimePrefix::Sec),
+            source,
+            target,
+            state,
+        };
+
+        let events = link_events(
+            &graph,
+            &[
+                event(10, 2, 1, LinkState::Down),
+                event(20, 0, 2, LinkState::Down),
+                event(30, 1, 2, LinkState::Up),
+                event(30, 0, 2, LinkState::Up),
+            ],
+        )
+        .unwrap();
+        let routing =
+            generate_routing_schedule(&mut graph, &nodes, true, false, 1, &events, &[]).unwrap();
+
+        let latency = |secs| {
+            let path = routing.at(SimulationTime::from_secs(secs)).path(0, 2)?;
+            Some(path.latency_ns / 1_000_000)
+        };
+
+        assert_eq!(latency(0), Some(20));
+        // the path is rerouted over the edge 0-2
+        assert_eq!(latency(10), Some(50));
+        // no path while both edges are down
+        assert_eq!(latency(20), None);
+        assert_eq!(latency(30), Some(20));
+
+        // a packet sent 15 ms before the edge 1-2 goes down is still crossing it
+        let before = |ms| SimulationTime::from_secs(10) - SimulationTime::from_millis(ms);
+        assert!(routing.path_goes_down(0, 2, 0, before(15)));
+        assert!(!routing.path_goes_down(0, 2, 0, before(25)));
+        assert!(!routing.path_goes_down(0, 1, 0, before(5)));
+
+        // events for edges that don't exist or are self-loops
+        for event in [
+            event(10, 0, 3, LinkState::Down),
+            event(10, 1, 1, LinkState::Down),
+        ] {
+            assert!(link_events(&graph, &[event]).is_err());
+        }
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_link_traces() {
+        let mut graph = NetworkGraph::parse(TRIANGLE_GRAPH).unwrap();
+        let nodes = HashSet::from([0, 1, 2]);
+
+        let edge = graph.edge_between(0, 1).unwrap();
+        let trace: LinkTrace = "time,latency\n10,100 ms\n20,\n30,2 ms\n".parse().unwrap();
+
+        let routing = generate_routing_schedule(
+            &mut graph,
+            &nodes,
+            true,
+            false,
+            1,
+            &(Vec::new(), Vec::new()),
+            &[(edge, trace)],
+        )
+        .unwrap();
+
+        let latency = |secs, dst| {
+            let path = routing.at(SimulationTime::from_secs(secs)).path(0, dst)?;
+            Some(path.latency_ns / 1_000_000)
+        };
+
+        assert_eq!(latency(0, 2), Some(20));
+        // the path is rerouted over the edge 0-2
+        assert_eq!(latency(10, 1), Some(60));
+        assert_eq!(latency(10, 2), Some(50));
+        assert_eq!(latency(20, 2), Some(50));
+        assert_eq!(latency(30, 2), Some(12));
+
+        // the trace is applied in both directions of the edge
+        let path = routing
+            .at(SimulationTime::from_secs(10))
+            .path(1, 0)
+            .unwrap();
+        assert_eq!(path.latency_ns / 1_000_000, 60);
+
+        // the self-loops have the smallest latency until the trace's latency is smaller
+        assert_eq!(routing.initial().get_smallest_latency_ns(), Some(1_000_000));
+        assert_eq!(routing.get_smallest_latency_ns(), Some(1_000_000));
+
+        // the graph's weights are restored
+        assert_eq!(graph.edge_weight(edge).latency.value(), 10);
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_routing_strategies() {
+        let mut graph = NetworkGraph::parse(TRIANGLE_GRAPH).unwrap();
+        let nodes = HashSet::from([0, 1, 2]);
+
+        // the edge 0-2 goes down at 10 seconds
+        let link_events = link_events(
+            &graph,
+            &[LinkEventOptions {
+                time: units::Time::new(10, units::TimePrefix::Sec),
+                source: 0,
+                target: 2,
+                state: LinkState::Down,
+            }],
+        )
+        .unwrap();
+
+        let mut latencies = |routing, secs| {
+            graph.set_routing(routing).unwrap();
+            let routing =
+                generate_routing_schedule(&mut graph, &nodes, true, false, 1, &link_events, &[])
+                    .unwrap();
+            let routing = routing.at(SimulationTime::from_secs(secs));
+            let latency = |src, dst| Some(routing.path(src, dst)?.latency_ns / 1_000_000);
+            [latency(0, 2), latency(1, 0), latency(1, 2)]
+        };
+
+        assert_eq!(
+            latencies(RoutingStrategy::Latency, 0),
+            [Some(20), Some(10), Some(10)]
+        );
+        // the edge 0-2 is a single hop
+        assert_eq!(
+            latencies(RoutingStrategy::HopCount, 0),
+            [Some(50), Some(10), Some(10)]
+        );
+
+        let routes: StaticRoutes = "0 2\n1 0 2\n".parse().unwrap();
+        assert_eq!(
+            latencies(RoutingStrategy::Static(routes), 0),
+            [Some(50), Some(10), Some(60)]
+        );
+        // a static route that crosses an edge that's down has no path, and pairs without a static
+        // route use the path with the lowest latency
+        let routes: StaticRoutes = "1 0 2\n".parse().unwrap();
+        assert_eq!(
+            latencies(RoutingStrategy::Static(routes), 10),
+            [Some(20), Some(10), None]
+        );
+
+        // static routes must follow the edges of the graph
+        let routes = "0 5\n".parse().unwrap();
+        assert!(graph.set_routing(RoutingStrategy::Static(routes)).is_err());
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_equal_cost_paths() {
+        // two paths from node 0 to node 3 with the same latency, but where only one corrupts
+        // packets
+        let mut graph = NetworkGraph::parse(
+            r#"graph [
+              node [
+                id 0
+              ]
+              node [
+                id 1
+              ]
+              node [
+                id 2
+              ]
+              node [
+                id 3
+              ]
+              edge [
+                source 0
+                target 0
+                latency "1 ms"
+              ]
+              edge [
+                source 3
+                target 3
+                latency "1 ms"
+              ]
+              edge [
+                source 0
+                target 1
+                latency "10 ms"
+                packet_corruption 0.5
+              ]
+              edge [
+                source 0
+                target 2
+                latency "10 ms"
+              ]
+              edge [
+                source 1
+                target 3
+                latency "10 ms"
+              ]
+              edge [
+                source 2
+                target 3
+                latency "10 ms"
+              ]
+            ]"#,
+        )
+        .unwrap();
+        let nodes = HashSet::from([0, 3]);
+        let no_events = (Vec::new(), Vec::new());
+
+        let routing =
+            generate_routing_schedule(&mut graph, &nodes, true, false, 8, &no_events, &[]).unwrap();
+        let time = SimulationTime::ZERO;
+
+        assert_eq!(routing.equal_cost_paths_at(time).len(), 2);
+        assert_eq!(routing.path_count(0, 3, time), 2);
+        assert_eq!(routing.path_count(3, 0, time), 2);
+        assert_eq!(routing.path_count(0, 0, time), 1);
+
+        let mut corruption: Vec<_> = routing
+            .equal_cost_paths_at(time)
+            .iter()
+            .map(|info| {
+                let path = info.path(0, 3).unwrap();
+                assert_eq!(path.latency_ns, 20_000_000);
+                path.packet_corruption
+            })
+            .collect();
+        corruption.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(corruption, [0.0, 0.5]);
+
+        // only the default path is used without ECMP
+        let routing =
+            generate_routing_schedule(&mut graph, &nodes, true, false, 1, &no_events, &[]).unwrap();
+        assert_eq!(routing.path_count(0, 3, time), 1);
+    }
+
+    #[test]
+    fn test_stop_conditions() {
+        let bytes = |x| StopCondition::BytesSent(units::Bytes::new(x, units::SiPrefixUpper::Base));
+
+        let conditions = SimStopConditions::new(&[bytes(20), bytes(10)], &[]).unwrap();
+        conditions.add_bytes_sent(6);
+        assert!(!conditions.is_met());
+        conditions.add_bytes_sent(6);
+        assert!(conditions.is_met());
+
+        // processes that aren't watched don't stop the simulation
+        let conditions = SimStopConditions::new(&[], &[]).unwrap();
+        conditions.add_bytes_sent(100);
+        conditions.process_finished("client", 0, ProcessFinalState::default());
+        assert!(!conditions.is_met());
+
+        // the processes must exist
+        let exited = StopCondition::Exited {
+            host: "client".into(),
+            process: 0,
+        };
+        assert!(SimStopConditions::new(&[exited], &[]).is_err());
+        assert!(SimStopConditions::new(&[StopCondition::AllExited], &[]).is_err());
+    }
+
+    #[test]
+    fn test_randomness_for_seed_calc() {
+        assert_eq!(randomness_for_seed_calc(1), randomness_for_seed_calc(1));
+        assert_ne!(randomness_for_seed_calc(1), randomness_for_seed_calc(2));
+    }
+
+    #[test]
+    fn test_process_restart() {
+        let secs = |x| units::Time::new(x, units::TimePrefix::Sec);
+        let options = |max_delay| RestartOptions {
+            policy: RestartPolicy::OnFailure,
+            max_restarts: None,
+            delay: secs(1),
+            backoff: 2.0,
+            max_delay,
+        };
+
+        let restart = build_process_restart(&options(Some(secs(5)))).unwrap();
+        let delays: Vec<_> = (0..5).map(|x| restart.delay(x).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+
+        let restart = build_process_restart(&options(None)).unwrap();
+        assert_eq!(restart.delay(10), SimulationTime::from_secs(1024));
+        assert_eq!(restart.delay(u32::MAX), SimulationTime::MAX);
+
+        assert!(build_process_restart(&options(Some(secs(0)))).is_err());
+        assert!(build_process_restart(&RestartOptions {
+            delay: secs(0),
+            ..options(None)
+        })
+        .is_err());
+        assert!(build_process_restart(&RestartOptions {
+            backoff: 0.5,
+            ..options(None)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_start_conditions() {
+        use ProcessStartCondition::*;
+
+        let process = |start_after: &[ProcessStartCondition]| ProcessInfo {
+            plugin: PathBuf::from("/bin/true"),
+            start_time: SimulationTime::ZERO,
+            start_after: start_after.to_vec(),
+            shutdown_time: None,
+            shutdown_signal: nix::sys::signal::Signal::SIGTERM,
+            signals: Vec::new(),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            files: Vec::new(),
+            expected_final_state: ProcessFinalState::default(),
+            restart: None,
+        };
+        let check = |processes: &[&[ProcessStartCondition]]| {
+            let processes: Vec<_> = processes.iter().map(|x| process(x)).collect();
+            check_start_conditions(&processes)
+        };
+
+        let secs = SimulationTime::from_secs;
+        assert!(check(&[
+            &[],
+            &[Exited(0)],
+            &[Running(0, secs(1)), Exited(1), Listening(80)]
+        ])
+        .is_ok());
+        assert!(check(&[&[Listening(80)]]).is_ok());
+
+        // invalid processes
+        assert!(check(&[&[Exited(0)]]).is_err());
+        assert!(check(&[&[], &[Exited(2)]]).is_err());
+
+        // cycles
+        assert!(check(&[&[Exited(1)], &[Running(0, secs(1))]]).is_err());
+        assert!(check(&[&[], &[Exited(3)], &[Exited(1)], &[Exited(2)]]).is_err());
+    }
 }
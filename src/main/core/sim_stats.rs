@@ -1,9 +1,12 @@
 use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 
 use anyhow::Context;
 use serde::Serialize;
+use shadow_shim_helper_rs::HostId;
 
+use crate::core::sim_config::SimPhases;
 use crate::utility::counter::Counter;
 
 /// Simulation statistics to be accessed by a single thread.
@@ -11,7 +14,11 @@ use crate::utility::counter::Counter;
 pub struct LocalSimStats {
     pub alloc_counts: RefCell<Counter>,
     pub dealloc_counts: RefCell<Counter>,
+    /// Allocated minus deallocated objects, by the host that was active at the time (if any).
+    pub host_alive_counts: RefCell<HashMap<Option<HostId>, Counter>>,
     pub syscall_counts: RefCell<Counter>,
+    /// Syscall counts for each simulation phase, indexed by phase.
+    pub phase_syscall_counts: RefCell<Vec<Counter>>,
 }
 
 impl LocalSimStats {
@@ -19,7 +26,9 @@ impl LocalSimStats {
         Self {
             alloc_counts: RefCell::new(Counter::new()),
             dealloc_counts: RefCell::new(Counter::new()),
+            host_alive_counts: RefCell::new(HashMap::new()),
             syscall_counts: RefCell::new(Counter::new()),
+            phase_syscall_counts: RefCell::new(Vec::new()),
         }
     }
 }
@@ -35,7 +44,11 @@ impl Default for LocalSimStats {
 pub struct SharedSimStats {
     pub alloc_counts: Mutex<Counter>,
     pub dealloc_counts: Mutex<Counter>,
+    /// Allocated minus deallocated objects, by the host that was active at the time (if any).
+    pub host_alive_counts: Mutex<HashMap<Option<HostId>, Counter>>,
     pub syscall_counts: Mutex<Counter>,
+    /// Syscall counts for each simulation phase, indexed by phase.
+    pub phase_syscall_counts: Mutex<Vec<Counter>>,
 }
 
 impl SharedSimStats {
@@ -43,7 +56,9 @@ impl SharedSimStats {
         Self {
             alloc_counts: Mutex::new(Counter::new()),
             dealloc_counts: Mutex::new(Counter::new()),
+            host_alive_counts: Mutex::new(HashMap::new()),
             syscall_counts: Mutex::new(Counter::new()),
+            phase_syscall_counts: Mutex::new(Vec::new()),
         }
     }
 
@@ -64,6 +79,28 @@ impl SharedSimStats {
         *local_alloc_counts = Counter::new();
         *local_dealloc_counts = Counter::new();
         *local_syscall_counts = Counter::new();
+
+        let mut shared_host_alive_counts = self.host_alive_counts.lock().unwrap();
+        for (host, counts) in local.host_alive_counts.borrow_mut().drain() {
+            shared_host_alive_counts
+                .entry(host)
+                .or_default()
+                .add_counter(&counts);
+        }
+
+        let mut shared_phase_syscall_counts = self.phase_syscall_counts.lock().unwrap();
+        let local_phase_syscall_counts =
+            std::mem::take(&mut *local.phase_syscall_counts.borrow_mut());
+
+        if shared_phase_syscall_counts.len() < local_phase_syscall_counts.len() {
+            shared_phase_syscall_counts.resize_with(local_phase_syscall_counts.len(), Counter::new);
+        }
+        for (shared, local) in shared_phase_syscall_counts
+            .iter_mut()
+            .zip(local_phase_syscall_counts.iter())
+        {
+            shared.add_counter(local);
+        }
     }
 }
 
@@ -78,18 +115,39 @@ impl Default for SharedSimStats {
 struct SimStatsForOutput {
     pub objects: ObjectStatsForOutput,
     pub syscalls: Counter,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<PhaseStatsForOutput>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct PhaseStatsForOutput {
+    pub name: String,
+    pub start_time_ns: u64,
+    pub syscalls: Counter,
 }
 
 #[derive(Serialize, Clone, Debug)]
 struct ObjectStatsForOutput {
     pub alloc_counts: Counter,
     pub dealloc_counts: Counter,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub leaked_counts_by_host: BTreeMap<String, Counter>,
 }
 
 impl SimStatsForOutput {
     /// Takes data from `stats` and puts it into a structure designed for output. May reset fields
     /// of `stats`.
-    pub fn new(stats: &SharedSimStats) -> Self {
+    pub fn new(
+        stats: &SharedSimStats,
+        phases: &SimPhases,
+        host_name: impl Fn(HostId) -> String,
+    ) -> Self {
+        let leaked_counts_by_host = leaked_objects_by_host(stats, host_name);
+
+        let mut phase_syscall_counts =
+            std::mem::take(&mut *stats.phase_syscall_counts.lock().unwrap());
+        phase_syscall_counts.resize_with(phases.len(), Counter::new);
+
         Self {
             objects: ObjectStatsForOutput {
                 alloc_counts: std::mem::replace(
@@ -100,18 +158,65 @@ impl SimStatsForOutput {
                     &mut stats.dealloc_counts.lock().unwrap(),
                     Counter::new(),
                 ),
+                leaked_counts_by_host,
             },
             syscalls: std::mem::replace(&mut stats.syscall_counts.lock().unwrap(), Counter::new()),
+            phases: phase_syscall_counts
+                .into_iter()
+                .enumerate()
+                .map(|(i, syscalls)| PhaseStatsForOutput {
+                    name: phases.name(i).to_string(),
+                    start_time_ns: phases.start_time(i).as_nanos().try_into().unwrap(),
+                    syscalls,
+                })
+                .collect(),
         }
     }
 }
 
+/// Objects that were never deallocated, grouped by the host that was active when they were
+/// allocated. Only object types that were leaked overall are included, since objects may be
+/// allocated and deallocated while different hosts are active (for example objects that are
+/// allocated while a host is being built, before it becomes active).
+pub fn leaked_objects_by_host(
+    stats: &SharedSimStats,
+    host_name: impl Fn(HostId) -> String,
+) -> BTreeMap<String, Counter> {
+    let leaked =
+        stats.alloc_counts.lock().unwrap().clone() - stats.dealloc_counts.lock().unwrap().clone();
+
+    let mut rv = BTreeMap::new();
+    if leaked.is_empty() {
+        return rv;
+    }
+
+    for (host, counts) in stats.host_alive_counts.lock().unwrap().iter() {
+        let mut host_leaked = Counter::new();
+        for (object, count) in counts.iter() {
+            if count > 0 && leaked.get_value(object) > 0 {
+                host_leaked.add_value(object, count);
+            }
+        }
+
+        if !host_leaked.is_empty() {
+            let name = host
+                .map(&host_name)
+                .unwrap_or_else(|| "(no host)".to_string());
+            rv.insert(name, host_leaked);
+        }
+    }
+
+    rv
+}
+
 /// May reset fields of `stats`.
 pub fn write_stats_to_file(
     filename: &std::path::Path,
     stats: &SharedSimStats,
+    phases: &SimPhases,
+    host_name: impl Fn(HostId) -> String,
 ) -> anyhow::Result<()> {
-    let stats = SimStatsForOutput::new(stats);
+    let stats = SimStatsForOutput::new(stats, phases, host_name);
 
     let file = std::fs::File::create(filename)
         .with_context(|| format!("Failed to create file '{}'", filename.display()))?;
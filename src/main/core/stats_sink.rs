@@ -0,0 +1,536 @@
+//! Streaming of simulation metrics to external time-series databases.
+//!
+//! The manager periodically collects [`Metric`]s and passes them to [`StatsSinks`], which sends
+//! them to each configured [`StatsSink`] at the sink's flush interval. Each sink runs on its own
+//! thread with a bounded queue of pending flushes, so a slow or unreachable database never blocks
+//! the simulation. If a sink's queue is full, new flushes for that sink are dropped.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crossbeam::channel::TrySendError;
+
+use crate::core::configuration::{StatsSinkFormat, StatsSinkProtocol};
+use crate::core::sim_config::StatsSinkConfig;
+
+/// Timeout for connecting to and writing to a TCP sink.
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum payload size of a UDP datagram sent to a sink. Metrics are split into multiple
+/// datagrams at line boundaries to avoid IP fragmentation.
+const MAX_DATAGRAM_SIZE: usize = 1400;
+
+/// A single measurement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metric {
+    /// Name of the metric, without the sink's prefix.
+    pub name: &'static str,
+    /// Tags identifying the source of the measurement, such as the host name.
+    pub tags: Vec<(&'static str, String)>,
+    pub value: u64,
+}
+
+impl Metric {
+    pub fn new(name: &'static str, value: u64) -> Self {
+        Self {
+            name,
+            tags: Vec::new(),
+            value,
+        }
+    }
+
+    pub fn with_tag(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.tags.push((key, value.into()));
+        self
+    }
+}
+
+/// A destination for simulation metrics.
+pub trait StatsSink: Send {
+    /// Send metrics that were collected at `time`.
+    fn send(&mut self, time: SystemTime, metrics: &[Metric]) -> std::io::Result<()>;
+}
+
+/// A sink using the [InfluxDB line
+/// protocol](https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/).
+pub struct InfluxSink {
+    prefix: String,
+    conn: Connection,
+}
+
+impl InfluxSink {
+    pub fn new(
+        prefix: impl Into<String>,
+        protocol: StatsSinkProtocol,
+        address: SocketAddr,
+    ) -> Self {
+        Self {
+            prefix: prefix.into(),
+            conn: Connection::new(protocol, address),
+        }
+    }
+
+    fn format(prefix: &str, time: SystemTime, metrics: &[Metric]) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\")
+                .replace(',', "\\,")
+                .replace('=', "\\=")
+                .replace(' ', "\\ ")
+        }
+
+        let time = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+
+        let mut out = String::new();
+        for metric in metrics {
+            if prefix.is_empty() {
+                write!(out, "{}", escape(metric.name)).unwrap();
+            } else {
+                write!(out, "{}_{}", escape(prefix), escape(metric.name)).unwrap();
+            }
+            for (key, value) in &metric.tags {
+                write!(out, ",{}={}", escape(key), escape(value)).unwrap();
+            }
+            writeln!(out, " value={}i {time}", metric.value).unwrap();
+        }
+        out
+    }
+}
+
+impl StatsSink for InfluxSink {
+    fn send(&mut self, time: SystemTime, metrics: &[Metric]) -> std::io::Result<()> {
+        self.conn.send(&Self::format(&self.prefix, time, metrics))
+    }
+}
+
+/// A sink using the [Graphite plaintext
+/// protocol](https://graphite.readthedocs.io/en/latest/feeding-carbon.html), with tags.
+pub struct GraphiteSink {
+    prefix: String,
+    conn: Connection,
+}
+
+impl GraphiteSink {
+    pub fn new(
+        prefix: impl Into<String>,
+        protocol: StatsSinkProtocol,
+        address: SocketAddr,
+    ) -> Self {
+        Self {
+            prefix: prefix.into(),
+            conn: Connection::new(protocol, address),
+        }
+    }
+
+    fn format(prefix: &str, time: SystemTime, metrics: &[Metric]) -> String {
+        // graphite doesn't support escaping, so replace any characters that have a special meaning
+        fn sanitize(s: &str) -> String {
+            s.replace([' ', ';', '~', '='], "_")
+        }
+
+        let time = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mut out = String::new();
+        for metric in metrics {
+            if prefix.is_empty() {
+                write!(out, "{}", sanitize(metric.name)).unwrap();
+            } else {
+                write!(out, "{}.{}", sanitize(prefix), sanitize(metric.name)).unwrap();
+            }
+            for (key, value) in &metric.tags {
+                write!(out, ";{}={}", sanitize(key), sanitize(value)).unwrap();
+            }
+            writeln!(out, " {} {time}", metric.value).unwrap();
+        }
+        out
+    }
+}
+
+impl StatsSink for GraphiteSink {
+    fn send(&mut self, time: SystemTime, metrics: &[Metric]) -> std::io::Result<()> {
+        self.conn.send(&Self::format(&self.prefix, time, metrics))
+    }
+}
+
+/// A connection to a sink that sends newline-delimited text.
+enum Connection {
+    /// The stream is connected lazily, and is reconnected on the next send after an error.
+    Tcp {
+        address: SocketAddr,
+        stream: Option<TcpStream>,
+    },
+    Udp {
+        address: SocketAddr,
+        socket: Option<UdpSocket>,
+    },
+}
+
+impl Connection {
+    fn new(protocol: StatsSinkProtocol, address: SocketAddr) -> Self {
+        match protocol {
+            StatsSinkProtocol::Tcp => Self::Tcp {
+                address,
+                stream: None,
+            },
+            StatsSinkProtocol::Udp => Self::Udp {
+                address,
+                socket: None,
+            },
+        }
+    }
+
+    fn send(&mut self, text: &str) -> std::io::Result<()> {
+        match self {
+            Self::Tcp { address, stream } => {
+                if stream.is_none() {
+                    let new_stream = TcpStream::connect_timeout(address, TCP_TIMEOUT)?;
+                    new_stream.set_write_timeout(Some(TCP_TIMEOUT))?;
+                    *stream = Some(new_stream);
+                }
+
+                let rv = stream.as_mut().unwrap().write_all(text.as_bytes());
+                if rv.is_err() {
+                    // reconnect on the next send
+                    *stream = None;
+                }
+                rv
+            }
+            Self::Udp { address, socket } => {
+                if socket.is_none() {
+                    let bind_address: SocketAddr = match address {
+                        SocketAddr::V4(_) => (std::net::Ipv4Addr::UNSPECIFIED, 0).into(),
+                        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+                    };
+                    *socket = Some(UdpSocket::bind(bind_address)?);
+                }
+                let socket = socket.as_ref().unwrap();
+
+                for datagram in split_lines(text, MAX_DATAGRAM_SIZE) {
+                    socket.send_to(datagram.as_bytes(), *address)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Split newline-delimited text into chunks of at most `max_len` bytes without splitting any
+/// lines. Lines longer than `max_len` are returned as their own chunk.
+fn split_lines(text: &str, max_len: usize) -> impl Iterator<Item = &str> {
+    let mut remaining = text;
+    std::iter::from_fn(move || {
+        if remaining.is_empty() {
+            return None;
+        }
+
+        let mut len = 0;
+        for line in remaining.split_inclusive('\n') {
+            if len != 0 && len + line.len() > max_len {
+                break;
+            }
+            len += line.len();
+        }
+
+        let (chunk, rest) = remaining.split_at(len);
+        remaining = rest;
+        Some(chunk)
+    })
+}
+
+/// Metrics queued for a sink.
+struct Flush {
+    time: SystemTime,
+    metrics: Arc<Vec<Metric>>,
+}
+
+/// A sink running on its own thread.
+struct SinkHandle {
+    name: String,
+    flush_interval: Duration,
+    last_flush: Option<Instant>,
+    sender: crossbeam::channel::Sender<Flush>,
+    thread: std::thread::JoinHandle<()>,
+    num_dropped: u64,
+}
+
+impl SinkHandle {
+    fn new(
+        name: String,
+        mut sink: Box<dyn StatsSink>,
+        flush_interval: Duration,
+        max_queued_flushes: usize,
+    ) -> Self {
+        let (sender, receiver) = crossbeam::channel::bounded::<Flush>(max_queued_flushes);
+
+        let thread_name = name.clone();
+        let thread = std::thread::Builder::new()
+            .name("stats-sink".to_string())
+            .spawn(move || {
+                let mut failing = false;
+                for flush in receiver {
+                    match sink.send(flush.time, &flush.metrics) {
+                        Ok(()) => {
+                            if failing {
+                                log::info!("Resumed sending metrics to stats sink {thread_name}");
+                            }
+                            failing = false;
+                        }
+                        Err(e) => {
+                            // only warn when the sink starts failing so that we don't flood the
+                            // log if the database is unreachable
+                            if !failing {
+                                log::warn!(
+                                    "Failed to send metrics to stats sink {thread_name}: {e}"
+                                );
+                            } else {
+                                log::debug!(
+                                    "Failed to send metrics to stats sink {thread_name}: {e}"
+                                );
+                            }
+                            failing = true;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        Self {
+            name,
+            flush_interval,
+            last_flush: None,
+            sender,
+            thread,
+            num_dropped: 0,
+        }
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.last_flush
+            .map(|x| now.duration_since(x) >= self.flush_interval)
+            .unwrap_or(true)
+    }
+
+    /// Queue a flush. If `block` is false and the queue is full, the flush is dropped.
+    fn queue(&mut self, now: Instant, flush: Flush, block: bool) {
+        self.last_flush = Some(now);
+
+        if block {
+            self.sender
+                .send(flush)
+                .expect("Stats sink thread exited early");
+            return;
+        }
+
+        match self.sender.try_send(flush) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                if self.num_dropped == 0 {
+                    log::warn!(
+                        "Stats sink {} can't keep up; dropping metrics until it catches up",
+                        self.name
+                    );
+                }
+                self.num_dropped += 1;
+            }
+            Err(TrySendError::Disconnected(_)) => panic!("Stats sink thread exited early"),
+        }
+    }
+}
+
+/// The configured stats sinks.
+pub struct StatsSinks {
+    sinks: Vec<SinkHandle>,
+}
+
+impl StatsSinks {
+    pub fn new(configs: &[StatsSinkConfig]) -> Self {
+        let sinks = configs
+            .iter()
+            .map(|config| {
+                let sink: Box<dyn StatsSink> = match config.format {
+                    StatsSinkFormat::Influx => Box::new(InfluxSink::new(
+                        &config.prefix,
+                        config.protocol,
+                        config.address,
+                    )),
+                    StatsSinkFormat::Graphite => Box::new(GraphiteSink::new(
+                        &config.prefix,
+                        config.protocol,
+                        config.address,
+                    )),
+                };
+                SinkHandle::new(
+                    config.address.to_string(),
+                    sink,
+                    config.flush_interval,
+                    config.max_queued_flushes,
+                )
+            })
+            .collect();
+
+        Self { sinks }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Queue metrics for each sink whose flush interval has elapsed. `metrics` is only called if
+    /// at least one sink needs to be flushed.
+    pub fn flush(&mut self, now: Instant, metrics: impl FnOnce() -> Vec<Metric>) {
+        self.flush_inner(now, false, metrics)
+    }
+
+    /// Queue metrics for all sinks regardless of their flush intervals (waiting for room in the
+    /// queues if needed), and wait for the sinks to finish sending all queued metrics.
+    pub fn finish(mut self, now: Instant, metrics: impl FnOnce() -> Vec<Metric>) {
+        self.flush_inner(now, true, metrics);
+
+        for sink in self.sinks {
+            // closing the channel stops the thread once it has sent the queued flushes
+            drop(sink.sender);
+            if sink.thread.join().is_err() {
+                log::warn!("Stats sink {} panicked", sink.name);
+            }
+            if sink.num_dropped > 0 {
+                log::warn!(
+                    "Stats sink {} dropped {} flushes because it couldn't keep up",
+                    sink.name,
+                    sink.num_dropped
+                );
+            }
+        }
+    }
+
+    fn flush_inner(&mut self, now: Instant, force: bool, metrics: impl FnOnce() -> Vec<Metric>) {
+        if !force && !self.sinks.iter().any(|x| x.is_due(now)) {
+            return;
+        }
+
+        let time = SystemTime::now();
+        let metrics = Arc::new(metrics());
+
+        for sink in self.sinks.iter_mut().filter(|x| force || x.is_due(now)) {
+            let flush = Flush {
+                time,
+                metrics: Arc::clone(&metrics),
+            };
+            sink.queue(now, flush, force);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> Vec<Metric> {
+        vec![
+            Metric::new("events", 12),
+            Metric::new("host_events", 5).with_tag("host", "server"),
+            Metric::new("host_events", 7).with_tag("host", "a b,c"),
+        ]
+    }
+
+    #[test]
+    fn test_influx_format() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789);
+        assert_eq!(
+            InfluxSink::format("shadow", time, &metrics()),
+            "shadow_events value=12i 1700000000123456789\n\
+             shadow_host_events,host=server value=5i 1700000000123456789\n\
+             shadow_host_events,host=a\\ b\\,c value=7i 1700000000123456789\n"
+        );
+    }
+
+    #[test]
+    fn test_graphite_format() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789);
+        assert_eq!(
+            GraphiteSink::format("shadow", time, &metrics()),
+            "shadow.events 12 1700000000\n\
+             shadow.host_events;host=server 5 1700000000\n\
+             shadow.host_events;host=a_b,c 7 1700000000\n"
+        );
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let text = "aaa\nbb\ncccc\nd\n";
+        assert_eq!(
+            split_lines(text, 7).collect::<Vec<_>>(),
+            ["aaa\nbb\n", "cccc\n", "d\n"]
+        );
+        // a line longer than the max length is still sent
+        assert_eq!(split_lines(text, 2).count(), 4);
+        assert_eq!(split_lines("", 7).count(), 0);
+    }
+
+    /// A sink that blocks until it's told to continue.
+    struct BlockingSink {
+        sent: crossbeam::channel::Sender<usize>,
+        resume: crossbeam::channel::Receiver<()>,
+    }
+
+    impl StatsSink for BlockingSink {
+        fn send(&mut self, _time: SystemTime, metrics: &[Metric]) -> std::io::Result<()> {
+            self.resume.recv().unwrap();
+            self.sent.send(metrics.len()).unwrap();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let (sent_send, sent_recv) = crossbeam::channel::unbounded();
+        let (resume_send, resume_recv) = crossbeam::channel::unbounded();
+        let sink = BlockingSink {
+            sent: sent_send,
+            resume: resume_recv,
+        };
+
+        let interval = Duration::from_secs(1);
+        let mut sinks = StatsSinks {
+            sinks: vec![SinkHandle::new("test".into(), Box::new(sink), interval, 1)],
+        };
+
+        let start = Instant::now();
+        let mut num_calls = 0;
+        let mut flush = |sinks: &mut StatsSinks, secs| {
+            sinks.flush(start + Duration::from_secs(secs), || {
+                num_calls += 1;
+                metrics()
+            })
+        };
+
+        flush(&mut sinks, 0);
+        // not due yet
+        flush(&mut sinks, 0);
+
+        // wait for the thread to take the first flush so that the queue is empty
+        while !sinks.sinks[0].sender.is_empty() {
+            std::thread::yield_now();
+        }
+
+        // the second flush is queued, and the third is dropped
+        flush(&mut sinks, 1);
+        flush(&mut sinks, 2);
+        assert_eq!(num_calls, 3);
+        assert_eq!(sinks.sinks[0].num_dropped, 1);
+
+        // the final flush is queued even though the flush interval hasn't elapsed
+        for _ in 0..3 {
+            resume_send.send(()).unwrap();
+        }
+        sinks.finish(start + Duration::from_secs(2), metrics);
+        assert_eq!(sent_recv.try_iter().count(), 3);
+    }
+}
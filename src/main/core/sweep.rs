@@ -0,0 +1,361 @@
+//! Run a configuration once for every combination of a set of parameter values.
+//!
+//! Each parameter is a configuration variable with a list of values. The sweep runs one Shadow
+//! process for every point in the cartesian product of the values, with the point's values given
+//! to the configuration as `--define` variables and the other options given before the `sweep`
+//! command. Each point has its own data directory within the configured data directory, and a
+//! `sweep.json` summary of the points is written when the sweep completes.
+
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::core::config_variables;
+
+/// The options that are handled by the sweep itself and aren't passed to each point's process.
+const SWEEP_OPTIONS: &[&str] = &["--data-directory", "-d"];
+
+/// A configuration variable and the values that the sweep uses for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepParameter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// Parse a `name=values` sweep parameter from the command line, where the values are either a
+/// comma-separated list ('a,b,c') or an inclusive integer range with an optional step
+/// ('1..10' or '0..100..10').
+pub fn parse_parameter(s: &str) -> Result<SweepParameter, String> {
+    let (name, values) = config_variables::parse_define(s)?;
+    let values = match parse_range(&values)? {
+        Some(range) => range,
+        None => values.split(',').map(str::to_string).collect(),
+    };
+    if values.iter().any(|x| x.is_empty()) {
+        return Err(format!("Empty value in sweep parameter '{s}'"));
+    }
+    Ok(SweepParameter { name, values })
+}
+
+fn parse_range(s: &str) -> Result<Option<Vec<String>>, String> {
+    let parts: Vec<_> = s.split("..").collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Ok(None);
+    }
+    let Ok(parts) = parts
+        .iter()
+        .map(|x| x.parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return Ok(None);
+    };
+
+    let (first, last) = (parts[0], parts[1]);
+    let step = parts.get(2).copied().unwrap_or(1);
+    if step <= 0 {
+        return Err(format!("The step of range '{s}' must be positive"));
+    }
+    if last < first {
+        return Err(format!("The range '{s}' is empty"));
+    }
+
+    Ok(Some(
+        (first..=last)
+            .step_by(step as usize)
+            .map(|x| x.to_string())
+            .collect(),
+    ))
+}
+
+/// The variable definitions for every point of the sweep, in order with the last parameter
+/// changing fastest.
+pub fn points(parameters: &[SweepParameter]) -> Vec<Vec<(String, String)>> {
+    let mut points = vec![vec![]];
+    for parameter in parameters {
+        points = points
+            .into_iter()
+            .flat_map(|point| {
+                parameter.values.iter().map(move |value| {
+                    let mut point = point.clone();
+                    point.push((parameter.name.clone(), value.clone()));
+                    point
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+/// The name of a point's data directory, such as "rate=10,size=1".
+pub fn point_name(point: &[(String, String)]) -> String {
+    point
+        .iter()
+        .map(|(name, value)| {
+            let value: String = value
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || "-_.+".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            format!("{name}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The command line options before the 'sweep' command, without the program name and the options
+/// handled by the sweep.
+fn point_args(args: &[&OsStr]) -> Vec<OsString> {
+    // the configuration file is required after the command, so the command isn't the last argument
+    // even if the configuration file is also named "sweep"
+    let end = args[..args.len().saturating_sub(1)]
+        .iter()
+        .rposition(|x| *x == "sweep")
+        .unwrap_or(args.len());
+
+    let mut point_args = Vec::new();
+    let mut args = args[..end].iter().skip(1);
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_string_lossy();
+        if SWEEP_OPTIONS.contains(&arg_str.as_ref()) {
+            // skip the option's value
+            args.next();
+            continue;
+        }
+        let attached = SWEEP_OPTIONS.iter().any(|x| {
+            arg_str
+                .strip_prefix(x)
+                .is_some_and(|rest| rest.starts_with('=') || (*x == "-d" && !rest.is_empty()))
+        });
+        if !attached {
+            point_args.push(arg.to_os_string());
+        }
+    }
+    point_args
+}
+
+#[derive(Debug, Serialize)]
+struct PointSummary {
+    parameters: std::collections::BTreeMap<String, String>,
+    data_directory: PathBuf,
+    exit_code: Option<i32>,
+}
+
+/// Run the sweep, with at most `jobs` points running at the same time. The point's process output
+/// is written to a log file next to its data directory. Returns an error if any point failed.
+pub fn run(
+    args: &[&OsStr],
+    config: &Path,
+    parameters: &[SweepParameter],
+    data_dir: &Path,
+    jobs: usize,
+) -> anyhow::Result<()> {
+    let shadow_exe = std::env::current_exe().context("Could not find the shadow executable")?;
+    let points = points(parameters);
+    let base_args = point_args(args);
+
+    // like the data directory of a single simulation, the directory must not already exist
+    std::fs::create_dir(data_dir)
+        .with_context(|| format!("Failed to create data directory '{}'", data_dir.display()))?;
+
+    let next = AtomicUsize::new(0);
+    let summaries: Mutex<Vec<Option<PointSummary>>> =
+        Mutex::new(points.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, points.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(point) = points.get(i) else {
+                    break;
+                };
+                let name = point_name(point);
+                let point_dir = data_dir.join(&name);
+                let log_path = data_dir.join(format!("{name}.log"));
+
+                eprintln!("** Starting sweep point {}/{}: {name}", i + 1, points.len());
+                let exit_code = match run_point(
+                    &shadow_exe,
+                    &base_args,
+                    config,
+                    point,
+                    &point_dir,
+                    &log_path,
+                ) {
+                    Ok(code) => code,
+                    Err(e) => {
+                        eprintln!("** Sweep point {name} could not be run: {e:#}");
+                        None
+                    }
+                };
+                if exit_code != Some(0) {
+                    eprintln!("** Sweep point {name} failed; see {}", log_path.display());
+                }
+
+                summaries.lock().unwrap()[i] = Some(PointSummary {
+                    parameters: point.iter().cloned().collect(),
+                    data_directory: point_dir,
+                    exit_code,
+                });
+            });
+        }
+    });
+
+    let summaries: Vec<_> = summaries
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect();
+    let num_failed = summaries.iter().filter(|x| x.exit_code != Some(0)).count();
+
+    let summary_path = data_dir.join("sweep.json");
+    let summary = serde_json::to_string_pretty(&summaries).unwrap();
+    std::fs::write(&summary_path, summary + "\n")
+        .with_context(|| format!("Could not write '{}'", summary_path.display()))?;
+
+    eprintln!(
+        "** Sweep finished: {} of {} points succeeded; see {}",
+        summaries.len() - num_failed,
+        summaries.len(),
+        summary_path.display(),
+    );
+
+    if num_failed > 0 {
+        anyhow::bail!("{num_failed} of {} sweep points failed", summaries.len());
+    }
+
+    Ok(())
+}
+
+fn run_point(
+    shadow_exe: &Path,
+    base_args: &[OsString],
+    config: &Path,
+    point: &[(String, String)],
+    point_dir: &Path,
+    log_path: &Path,
+) -> anyhow::Result<Option<i32>> {
+    let log = std::fs::File::create(log_path).context("Could not create the log file")?;
+
+    // the point's variables are given last so that they take precedence over other definitions
+    let mut command = Command::new(shadow_exe);
+    command
+        .args(base_args)
+        .arg("--data-directory")
+        .arg(point_dir);
+    for (name, value) in point {
+        command.arg("--define").arg(format!("{name}={value}"));
+    }
+    command.arg(config);
+
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()
+        .context("Could not run shadow")?;
+
+    Ok(status.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, values: &[&str]) -> SweepParameter {
+        SweepParameter {
+            name: name.into(),
+            values: values.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_parameter() {
+        assert_eq!(
+            parse_parameter("rate=1,5,10").unwrap(),
+            param("rate", &["1", "5", "10"])
+        );
+        assert_eq!(parse_parameter("x=a").unwrap(), param("x", &["a"]));
+        assert_eq!(
+            parse_parameter("n=1..4").unwrap(),
+            param("n", &["1", "2", "3", "4"])
+        );
+        assert_eq!(
+            parse_parameter("n=0..25..10").unwrap(),
+            param("n", &["0", "10", "20"])
+        );
+        assert_eq!(
+            parse_parameter("n=-2..-1").unwrap(),
+            param("n", &["-2", "-1"])
+        );
+        assert_eq!(
+            parse_parameter("v=1.5..2").unwrap(),
+            param("v", &["1.5..2"])
+        );
+
+        assert!(parse_parameter("rate").is_err());
+        assert!(parse_parameter("1x=1").is_err());
+        assert!(parse_parameter("x=").is_err());
+        assert!(parse_parameter("x=1,,2").is_err());
+        assert!(parse_parameter("n=5..1").is_err());
+        assert!(parse_parameter("n=1..5..0").is_err());
+    }
+
+    #[test]
+    fn test_points() {
+        let names: Vec<_> = points(&[param("a", &["1", "2"]), param("b", &["x", "y", "z"])])
+            .iter()
+            .map(|x| point_name(x))
+            .collect();
+        assert_eq!(
+            names,
+            ["a=1,b=x", "a=1,b=y", "a=1,b=z", "a=2,b=x", "a=2,b=y", "a=2,b=z"]
+        );
+
+        assert_eq!(points(&[]), vec![Vec::<(String, String)>::new()]);
+        assert_eq!(point_name(&[("p".into(), "a/b c".into())]), "p=a_b_c");
+    }
+
+    #[test]
+    fn test_point_args() {
+        let args: Vec<&OsStr> = [
+            "shadow",
+            "-d",
+            "out",
+            "-dout",
+            "--data-directory=out",
+            "--define",
+            "c=4",
+            "--seed",
+            "3",
+            "sweep",
+            "-p",
+            "a=1,2",
+            "--param=b=3",
+            "-j",
+            "2",
+            "shadow.yaml",
+        ]
+        .iter()
+        .map(OsStr::new)
+        .collect();
+        assert_eq!(point_args(&args), ["--define", "c=4", "--seed", "3"]);
+
+        // the configuration file can have the same name as the command
+        let args: Vec<&OsStr> = ["shadow", "--seed", "3", "sweep", "-p", "a=1", "sweep"]
+            .iter()
+            .map(OsStr::new)
+            .collect();
+        assert_eq!(point_args(&args), ["--seed", "3"]);
+    }
+}
@@ -0,0 +1,403 @@
+//! Generation of TLS certificates that are valid at simulated times.
+//!
+//! Simulated processes see a clock that starts at [`EmulatedTime::SIMULATION_START`] (January 1st,
+//! 2000), so certificates generated on the real system would be rejected as not yet valid. The
+//! [`CertificateAuthority`] mints a CA and per-host certificates whose validity period starts at
+//! the simulation epoch, using the system's `openssl` command. Each host's data directory receives
+//! a copy of the CA certificate along with the host's own certificate and key.
+//!
+//! The keys are derived from the simulation seed, and are not secret. When the `openssl` command
+//! supports deterministic ECDSA signatures (RFC 6979, OpenSSL 3.2 and later), the certificates are
+//! also the same in every run with the same seed.
+
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+/// How long certificates remain valid after the end of the simulation.
+const VALIDITY_AFTER_END: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Name of the directory within a host's data directory that contains its TLS files.
+pub const HOST_TLS_DIR: &str = "tls";
+/// File name of the CA certificate within a host's TLS directory.
+pub const CA_CERT_FILE: &str = "ca.crt";
+/// File name of the host's certificate within a host's TLS directory.
+pub const HOST_CERT_FILE: &str = "host.crt";
+/// File name of the host's private key within a host's TLS directory.
+pub const HOST_KEY_FILE: &str = "host.key";
+
+/// The order of the P-256 curve's base point. Private keys must be in the range `[1, n)`.
+const P256_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+
+/// A certificate authority that issues certificates to simulated hosts.
+#[derive(Debug)]
+pub struct CertificateAuthority {
+    openssl: PathBuf,
+    /// Signature options that make ECDSA signatures deterministic, if supported by `openssl`.
+    sigopts: Vec<String>,
+    dir: PathBuf,
+    not_before: String,
+    not_after: String,
+}
+
+impl CertificateAuthority {
+    /// Create a new CA in the directory `dir`, which must not already exist. Certificates issued by
+    /// the CA (including its own) will be valid from the start of the simulation until some time
+    /// after `end_time`. The CA's key is derived from `seed`.
+    pub fn new(dir: impl Into<PathBuf>, end_time: EmulatedTime, seed: u64) -> anyhow::Result<Self> {
+        let dir = dir.into();
+
+        let openssl = which::which("openssl")
+            .context("Generating TLS certificates requires the 'openssl' command")?;
+
+        let sigopts = if supports_deterministic_signatures(&openssl) {
+            vec!["-sigopt".to_string(), "nonce-type:1".to_string()]
+        } else {
+            log::warn!(
+                "The 'openssl' command doesn't support deterministic signatures (OpenSSL 3.2 or \
+                 later is required), so the TLS certificates will differ between runs"
+            );
+            Vec::new()
+        };
+
+        let to_system_time = |time: EmulatedTime| {
+            SystemTime::UNIX_EPOCH + Duration::from(time - EmulatedTime::UNIX_EPOCH)
+        };
+
+        let ca = Self {
+            openssl,
+            sigopts,
+            not_before: generalized_time(to_system_time(EmulatedTime::SIMULATION_START)),
+            not_after: generalized_time(to_system_time(end_time) + VALIDITY_AFTER_END),
+            dir,
+        };
+
+        std::fs::create_dir(&ca.dir)
+            .with_context(|| format!("Failed to create directory '{}'", ca.dir.display()))?;
+        std::fs::create_dir(ca.dir.join("certs"))?;
+
+        // the database of issued certificates used by 'openssl ca'
+        std::fs::write(ca.dir.join("index.txt"), "")?;
+        std::fs::write(ca.dir.join("serial"), "1000\n")?;
+        std::fs::write(ca.config_path(), ca_config(&ca.dir))?;
+
+        let key = ca.dir.join("ca.key");
+        let csr = ca.dir.join("ca.csr");
+
+        ca.generate_key(seed, &key)?;
+        ca.generate_csr(&key, "Shadow Simulation CA", &csr)?;
+        ca.openssl(|cmd| {
+            cmd.args([
+                "ca",
+                "-batch",
+                "-notext",
+                "-selfsign",
+                "-extensions",
+                "ca_ext",
+            ])
+            .args(&ca.sigopts)
+            .arg("-config")
+            .arg(ca.config_path())
+            .arg("-keyfile")
+            .arg(&key)
+            .arg("-in")
+            .arg(&csr)
+            .arg("-out")
+            .arg(ca.cert_path())
+            .args(["-startdate", &ca.not_before, "-enddate", &ca.not_after])
+        })?;
+
+        Ok(ca)
+    }
+
+    /// Path to the CA's certificate.
+    pub fn cert_path(&self) -> PathBuf {
+        self.dir.join("ca.crt")
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.dir.join("ca.cnf")
+    }
+
+    /// Issue a certificate for the host `hostname` with address `ip`, and write it along with the
+    /// host's key and the CA's certificate to the host's data directory `host_dir`. The host's key
+    /// is derived from `seed`. Returns the path of the CA certificate within the host's data
+    /// directory.
+    pub fn issue(
+        &self,
+        hostname: &str,
+        ip: Ipv4Addr,
+        host_dir: &Path,
+        seed: u64,
+    ) -> anyhow::Result<PathBuf> {
+        let tls_dir = host_dir.join(HOST_TLS_DIR);
+        std::fs::create_dir_all(&tls_dir)
+            .with_context(|| format!("Failed to create directory '{}'", tls_dir.display()))?;
+
+        let key = tls_dir.join(HOST_KEY_FILE);
+        let csr = self.dir.join(format!("{hostname}.csr"));
+        let ext = self.dir.join(format!("{hostname}.ext"));
+
+        // clients verify the host's name or address against the subject alternative names
+        std::fs::write(&ext, host_extensions(hostname, ip))?;
+
+        self.generate_key(seed, &key)?;
+        self.generate_csr(&key, hostname, &csr)?;
+        self.openssl(|cmd| {
+            cmd.args(["ca", "-batch", "-notext", "-extensions", "host_ext"])
+                .args(&self.sigopts)
+                .arg("-config")
+                .arg(self.config_path())
+                .arg("-extfile")
+                .arg(&ext)
+                .arg("-cert")
+                .arg(self.cert_path())
+                .arg("-keyfile")
+                .arg(self.dir.join("ca.key"))
+                .arg("-in")
+                .arg(&csr)
+                .arg("-out")
+                .arg(tls_dir.join(HOST_CERT_FILE))
+                .args(["-startdate", &self.not_before, "-enddate", &self.not_after])
+        })?;
+
+        let ca_cert = tls_dir.join(CA_CERT_FILE);
+        std::fs::copy(self.cert_path(), &ca_cert)
+            .with_context(|| format!("Failed to copy CA certificate to '{}'", ca_cert.display()))?;
+
+        Ok(ca_cert)
+    }
+
+    /// Generate a P-256 private key derived from `seed`.
+    fn generate_key(&self, seed: u64, path: &Path) -> anyhow::Result<()> {
+        // openssl computes the public key when reading the private key, and writes both
+        let der = path.with_extension("der");
+        std::fs::write(&der, ec_private_key_der(&p256_scalar(seed)))
+            .with_context(|| format!("Failed to write '{}'", der.display()))?;
+        self.openssl(|cmd| {
+            cmd.args(["ec", "-inform", "DER", "-outform", "DER"])
+                .arg("-in")
+                .arg(&der)
+                .arg("-out")
+                .arg(&der)
+        })?;
+        self.openssl(|cmd| {
+            cmd.args(["pkey", "-inform", "DER"])
+                .arg("-in")
+                .arg(&der)
+                .arg("-out")
+                .arg(path)
+        })?;
+        std::fs::remove_file(&der)?;
+        Ok(())
+    }
+
+    /// Generate a certificate signing request with common name `name`.
+    fn generate_csr(&self, key: &Path, name: &str, path: &Path) -> anyhow::Result<()> {
+        self.openssl(|cmd| {
+            cmd.args(["req", "-new", "-subj", &format!("/CN={name}")])
+                .args(&self.sigopts)
+                .arg("-config")
+                .arg(self.config_path())
+                .arg("-key")
+                .arg(key)
+                .arg("-out")
+                .arg(path)
+        })
+    }
+
+    /// Run an `openssl` command, returning an error containing its stderr if it fails.
+    fn openssl(&self, f: impl FnOnce(&mut Command) -> &mut Command) -> anyhow::Result<()> {
+        let mut cmd = Command::new(&self.openssl);
+        f(&mut cmd);
+
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to run '{}'", self.openssl.display()))?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Command {:?} failed ({}): {}",
+                cmd,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if `openssl` is OpenSSL 3.2 or later, which supports deterministic ECDSA signatures.
+fn supports_deterministic_signatures(openssl: &Path) -> bool {
+    let Ok(output) = Command::new(openssl).arg("version").output() else {
+        return false;
+    };
+
+    // for example "OpenSSL 3.2.1 30 Jan 2024 (Library: OpenSSL 3.2.1 30 Jan 2024)"
+    let output = String::from_utf8_lossy(&output.stdout);
+    let Some(version) = output
+        .strip_prefix("OpenSSL ")
+        .and_then(|x| x.split_whitespace().next())
+    else {
+        return false;
+    };
+
+    let mut parts = version.split('.').map(|x| x.parse::<u32>());
+    match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => (major, minor) >= (3, 2),
+        _ => false,
+    }
+}
+
+/// Derive a valid P-256 private key (a big-endian integer in the range `[1, n)`) from `seed`.
+fn p256_scalar(seed: u64) -> [u8; 32] {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    loop {
+        let mut scalar = [0u8; 32];
+        rng.fill_bytes(&mut scalar);
+        // comparing the big-endian byte arrays compares the integers
+        if scalar != [0u8; 32] && scalar < P256_ORDER {
+            return scalar;
+        }
+    }
+}
+
+/// The DER encoding of a P-256 private key in the SEC1 `ECPrivateKey` format, without the optional
+/// public key.
+fn ec_private_key_der(scalar: &[u8; 32]) -> Vec<u8> {
+    // the OID of the prime256v1 (P-256) curve
+    const PRIME256V1: [u8; 10] = [0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+
+    let mut der = vec![0x30, 0x31, 0x02, 0x01, 0x01, 0x04, 0x20];
+    der.extend_from_slice(scalar);
+    // the curve parameters, with context-specific tag 0
+    der.extend_from_slice(&[0xa0, PRIME256V1.len() as u8]);
+    der.extend_from_slice(&PRIME256V1);
+    der
+}
+
+/// The `openssl ca` and `openssl req` configuration for a CA in `dir`.
+fn ca_config(dir: &Path) -> String {
+    format!(
+        "\
+[ ca ]
+default_ca = shadow_ca
+
+[ shadow_ca ]
+dir = {dir}
+database = $dir/index.txt
+new_certs_dir = $dir/certs
+serial = $dir/serial
+default_md = sha256
+policy = shadow_policy
+unique_subject = no
+email_in_dn = no
+
+[ shadow_policy ]
+commonName = supplied
+
+[ req ]
+distinguished_name = shadow_dn
+
+[ shadow_dn ]
+
+[ ca_ext ]
+basicConstraints = critical, CA:true
+keyUsage = critical, keyCertSign, cRLSign
+subjectKeyIdentifier = hash
+",
+        dir = dir.display(),
+    )
+}
+
+/// The certificate extensions for a host's certificate.
+fn host_extensions(hostname: &str, ip: Ipv4Addr) -> String {
+    format!(
+        "\
+[ host_ext ]
+basicConstraints = critical, CA:false
+keyUsage = critical, digitalSignature, keyEncipherment
+extendedKeyUsage = serverAuth, clientAuth
+subjectKeyIdentifier = hash
+authorityKeyIdentifier = keyid
+subjectAltName = DNS:{hostname}, IP:{ip}
+"
+    )
+}
+
+/// Format a time as an ASN.1 GeneralizedTime string (`YYYYMMDDHHMMSSZ`), as accepted by the
+/// `-startdate` and `-enddate` options of `openssl ca`.
+fn generalized_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // convert the days since the unix epoch to a civil date; see "civil_from_days" at
+    // https://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}{:02}{:02}{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generalized_time() {
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+        assert_eq!(generalized_time(time(0)), "19700101000000Z");
+        assert_eq!(generalized_time(time(946684800)), "20000101000000Z");
+        // a leap day
+        assert_eq!(generalized_time(time(1709210096)), "20240229123456Z");
+        assert_eq!(generalized_time(time(4102444799)), "20991231235959Z");
+    }
+
+    #[test]
+    fn test_p256_scalar() {
+        assert_eq!(p256_scalar(1), p256_scalar(1));
+        assert_ne!(p256_scalar(1), p256_scalar(2));
+        assert!(p256_scalar(1) < P256_ORDER);
+    }
+
+    #[test]
+    fn test_ec_private_key_der() {
+        let der = ec_private_key_der(&[1; 32]);
+        // the outer sequence's length covers the rest of the encoding
+        assert_eq!(usize::from(der[1]), der.len() - 2);
+        assert_eq!(&der[7..39], &[1; 32]);
+    }
+
+    #[test]
+    fn test_simulation_start() {
+        let start = SystemTime::UNIX_EPOCH
+            + Duration::from(EmulatedTime::SIMULATION_START - EmulatedTime::UNIX_EPOCH);
+        assert_eq!(generalized_time(start), "20000101000000Z");
+    }
+}
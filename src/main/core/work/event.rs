@@ -30,6 +30,26 @@ impl Event {
         }
     }
 
+    /// A new packet event for a packet that was sent by a host of another manager in a distributed
+    /// simulation. The event is ordered as if it was created by the source host.
+    pub fn new_remote_packet(
+        packet: PacketRc,
+        time: EmulatedTime,
+        src_host_id: HostId,
+        src_host_event_id: u64,
+    ) -> Self {
+        Self {
+            magic: Magic::new(),
+            time,
+            data: EventData::Packet(PacketEventData {
+                packet,
+                src_host_id,
+                src_host_event_id,
+            }),
+            _counter: ObjectCounter::new("Event"),
+        }
+    }
+
     /// A new local event, which is an event that was generated locally by the host itself (timers,
     /// localhost packets, etc).
     pub fn new_local(task: TaskRef, time: EmulatedTime, host: &Host) -> Self {
@@ -122,6 +142,16 @@ pub struct LocalEventData {
     event_id: u64,
 }
 
+impl PacketEventData {
+    pub fn src_host_id(&self) -> HostId {
+        self.src_host_id
+    }
+
+    pub fn src_host_event_id(&self) -> u64 {
+        self.src_host_event_id
+    }
+}
+
 impl From<PacketEventData> for PacketRc {
     fn from(data: PacketEventData) -> Self {
         data.packet
@@ -1,9 +1,13 @@
 use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU32};
 use std::sync::{Arc, Mutex};
 
 use atomic_refcell::{AtomicRef, AtomicRefCell};
+use linux_api::syscall::SyscallNum;
 use once_cell::sync::Lazy;
 use rand::Rng;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
@@ -14,17 +18,28 @@ use shadow_shim_helper_rs::util::SyncSendPointer;
 use shadow_shim_helper_rs::HostId;
 
 use super::work::event_queue::EventQueue;
+use crate::core::configuration::{EcmpMode, MulticastScope, ProcessFinalState};
 use crate::core::controller::ShadowStatusBarState;
+use crate::core::distributed::RemoteManagers;
 use crate::core::runahead::Runahead;
-use crate::core::sim_config::Bandwidth;
+use crate::core::sim_config::{Bandwidth, SimOutputLimits, SimPhases, SimStopConditions};
 use crate::core::sim_stats::{LocalSimStats, SharedSimStats};
 use crate::core::work::event::Event;
 use crate::cshadow;
 use crate::host::host::Host;
+use crate::host::network::namespace::subnet_broadcast_address;
 use crate::host::process::{Process, ProcessId};
 use crate::host::thread::{Thread, ThreadId};
-use crate::network::graph::{IpAssignment, RoutingInfo};
-use crate::network::packet::PacketRc;
+use crate::network::dns_server::DnsServer;
+use crate::network::graph::jitter::Jitter;
+use crate::network::graph::{
+    IpAssignment, LinkProperties, PathLink, PathRouter, RoutingInfo, RoutingSchedule,
+};
+use crate::network::link_queue::LinkQueues;
+use crate::network::middlebox::Middleboxes;
+use crate::network::multicast::MulticastGroups;
+use crate::network::nat::NatGateways;
+use crate::network::packet::{IcmpHeader, PacketRc, PacketStatus, IPV4_DEFAULT_TTL};
 use crate::utility::childpid_watcher::ChildPidWatcher;
 use crate::utility::counter::Counter;
 use crate::utility::status_bar;
@@ -288,6 +303,17 @@ impl Worker {
         Worker::with(|w| w.clock.borrow().now).flatten()
     }
 
+    /// The name of the simulation phase that is active at the current time, if any.
+    pub fn current_phase() -> Option<Arc<str>> {
+        Worker::with(|w| {
+            let now = w.clock.borrow().now?;
+            let phases = &w.shared.phases;
+            let phase = phases.phase_at(now - EmulatedTime::SIMULATION_START)?;
+            Some(Arc::clone(phases.name(phase)))
+        })
+        .flatten()
+    }
+
     pub fn update_lowest_used_latency(t: SimulationTime) {
         assert!(t != SimulationTime::ZERO);
 
@@ -330,35 +356,286 @@ impl Worker {
         let round_end_time = Worker::round_end_time().unwrap();
 
         let is_completed = current_time >= Worker::with(|w| w.shared.sim_end_time).unwrap();
-        let is_bootstrapping =
-            current_time < Worker::with(|w| w.shared.bootstrap_end_time).unwrap();
 
         if is_completed {
             // the simulation is over, don't bother
             return;
         }
 
+        let total_size = unsafe { cshadow::packet_getTotalSize(packet) };
+        Worker::with(|w| w.shared.stop_conditions.add_bytes_sent(total_size)).unwrap();
+
         let src_ip = unsafe { cshadow::packet_getSourceIP(packet) };
         let dst_ip = unsafe { cshadow::packet_getDestinationIP(packet) };
-        let payload_size = unsafe { cshadow::packet_getPayloadSize(packet) };
 
         let src_ip: std::net::Ipv4Addr = u32::from_be(src_ip).into();
         let dst_ip: std::net::Ipv4Addr = u32::from_be(dst_ip).into();
 
-        let dst_host_id = Worker::with(|w| {
+        // multicast and broadcast packets are sent to every host in the group or network segment,
+        // but routers don't send ICMP errors for them
+        let members = if dst_ip.is_multicast() {
+            Some(
+                Worker::with(|w| {
+                    w.shared
+                        .multicast_destinations(src_host.id(), src_ip, dst_ip, round_end_time)
+                })
+                .unwrap(),
+            )
+        } else if dst_ip.is_broadcast() || dst_ip == subnet_broadcast_address(src_ip) {
+            Some(
+                Worker::with(|w| {
+                    w.shared
+                        .broadcast_destinations(src_host.id(), src_ip, dst_ip)
+                })
+                .unwrap(),
+            )
+        } else {
+            None
+        };
+
+        if let Some(members) = members {
+            for (dst_host_id, member_ip) in members {
+                let path = unsafe {
+                    Worker::choose_path(src_host, packet, src_ip.into(), member_ip.into())
+                };
+
+                // multicast routing isn't modeled, so the routers don't decrement the TTL
+                unsafe {
+                    Worker::route_packet(
+                        src_host,
+                        packet,
+                        dst_host_id,
+                        src_ip.into(),
+                        member_ip.into(),
+                        path,
+                        0,
+                    )
+                };
+            }
+            return;
+        }
+
+        // the built-in DNS server isn't a host, so it answers queries as soon as they're sent
+        let is_dns_server = Worker::with(|w| {
             w.shared
-                .resolve_ip_to_host_id(dst_ip)
-                .expect("No host ID for dest address {dst_ip}")
+                .dns_server
+                .as_ref()
+                .is_some_and(|x| x.address() == dst_ip)
         })
         .unwrap();
+        if is_dns_server {
+            unsafe { cshadow::packet_ref(packet) };
+            Worker::send_dns_response(src_host, PacketRc::from_raw(packet));
+            return;
+        }
+
+        let packet_dst_ip = dst_ip;
+        let Some((src_ip, dst_ip)) =
+            Worker::with(|w| w.shared.nat_path(src_host.id(), src_ip, dst_ip)).unwrap()
+        else {
+            // a private address isn't routable from outside of its subnet
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+            return;
+        };
+
+        // packets that aren't sent to a NAT gateway are sent through the middlebox that intercepts
+        // their addresses, if any
+        let (src_ip, dst_ip) = if dst_ip == packet_dst_ip {
+            Worker::with(|w| w.shared.middleboxes.path(src_host.id(), src_ip, dst_ip)).unwrap()
+        } else {
+            (src_ip, dst_ip)
+        };
+
+        let Some(dst_host_id) = Worker::with(|w| w.shared.resolve_ip_to_host_id(dst_ip)).unwrap()
+        else {
+            // no host has the destination address, so the router has no route for the packet and
+            // sends an ICMP "host unreachable" error to the source
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+            unsafe { cshadow::packet_ref(packet) };
+            Worker::send_host_unreachable(src_host, &PacketRc::from_raw(packet));
+            return;
+        };
 
         let src_ip = std::net::IpAddr::V4(src_ip);
         let dst_ip = std::net::IpAddr::V4(dst_ip);
 
+        // while graph edges are down, there may not be a path to the destination, and the router
+        // that can't forward the packet sends an ICMP "host unreachable" error to the source
+        if !Worker::with(|w| w.shared.has_path(src_ip, dst_ip, 0)).unwrap() {
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+            unsafe { cshadow::packet_ref(packet) };
+            Worker::send_host_unreachable(src_host, &PacketRc::from_raw(packet));
+            return;
+        }
+
+        let path = unsafe { Worker::choose_path(src_host, packet, src_ip, dst_ip) };
+
+        // a router on the path would drop a packet that's larger than the MTU of its next link if
+        // the packet can't be fragmented, and send an ICMP "fragmentation needed" error to the
+        // source
+        if unsafe { cshadow::packet_getDontFragment(packet) } {
+            let path_mtu = Worker::with(|w| w.shared.path_mtu(src_ip, dst_ip, path)).unwrap();
+
+            if let Some(path_mtu) = path_mtu.filter(|x| total_size > u64::from(*x)) {
+                unsafe {
+                    cshadow::packet_addDeliveryStatus(
+                        packet,
+                        cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                    )
+                };
+                unsafe { cshadow::packet_ref(packet) };
+                Worker::send_frag_needed(src_host, &PacketRc::from_raw(packet), path_mtu);
+                return;
+            }
+        }
+
+        // each router on the path decrements the packet's TTL, and the router where the TTL expires
+        // drops the packet and sends an ICMP "time exceeded" error to the source
+        let routers =
+            Worker::with(|w| w.shared.path_routers(src_ip, dst_ip, path).unwrap()).unwrap();
+        let ttl = unsafe { cshadow::packet_getTTL(packet) };
+        if u32::from(ttl) <= routers {
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+
+            // a packet with a TTL of 0 expires at the first router
+            let index = ttl.saturating_sub(1);
+            let router =
+                Worker::with(|w| w.shared.path_router(src_ip, dst_ip, path, index.into())).unwrap();
+
+            // routers without an address don't send errors
+            if let Some(router) = router.filter(|x| x.address.is_some()) {
+                unsafe { cshadow::packet_ref(packet) };
+                Worker::send_time_exceeded(src_host, &PacketRc::from_raw(packet), router, index);
+            }
+            return;
+        }
+
+        unsafe {
+            Worker::route_packet(src_host, packet, dst_host_id, src_ip, dst_ip, path, routers)
+        };
+    }
+
+    /// Choose which of the equal-cost paths between `src_ip` and `dst_ip` the packet follows. With
+    /// flow-based ECMP, the packets of a flow (with the same addresses, protocol, and ports) always
+    /// follow the same path, and with per-packet ECMP each packet follows a random path.
+    ///
+    /// # Safety
+    ///
+    /// `packet` must be valid and not accessed by another thread while this function is
+    /// running.
+    unsafe fn choose_path(
+        src_host: &Host,
+        packet: *const cshadow::Packet,
+        src_ip: std::net::IpAddr,
+        dst_ip: std::net::IpAddr,
+    ) -> usize {
+        let (ecmp, count) =
+            Worker::with(|w| (w.shared.ecmp, w.shared.path_count(src_ip, dst_ip))).unwrap();
+
+        // don't use the rng if there's only one path, so that it doesn't change the random
+        // decisions of other simulations
+        if count <= 1 {
+            return 0;
+        }
+
+        match ecmp {
+            EcmpMode::Off => 0,
+            EcmpMode::Flow => {
+                let protocol = unsafe { cshadow::packet_getProtocol(packet) };
+                let src_port = unsafe { cshadow::packet_getSourcePort(packet) };
+                let dst_port = unsafe { cshadow::packet_getDestinationPort(packet) };
+
+                let mut hasher = std::hash::DefaultHasher::new();
+                (src_ip, dst_ip, protocol, src_port, dst_port).hash(&mut hasher);
+                (hasher.finish() % u64::try_from(count).unwrap())
+                    .try_into()
+                    .unwrap()
+            }
+            EcmpMode::Packet => src_host.random_mut().gen_range(0..count),
+        }
+    }
+
+    /// Send a copy of `packet` over the equal-cost path at index `path` between `src_ip` and
+    /// `dst_ip` to the host with ID `dst_host_id`, unless the path's packet loss drops it. The copy
+    /// may be marked as corrupted by the path, in which case the receiving interface will drop it,
+    /// and may be reordered by the path, in which case it's delivered after an additional delay.
+    /// The path and the source host's uplink may also add a random jitter to the packet's latency,
+    /// and the source host's uplink trace may add latency and packet loss. The copy's TTL is
+    /// decremented by the `routers` on the path, which must be fewer than the packet's TTL.
+    ///
+    /// # Safety
+    ///
+    /// `packet` must be valid and not accessed by another thread while this function is
+    /// running.
+    unsafe fn route_packet(
+        src_host: &Host,
+        packet: *mut cshadow::Packet,
+        dst_host_id: HostId,
+        src_ip: std::net::IpAddr,
+        dst_ip: std::net::IpAddr,
+        path: usize,
+        routers: u32,
+    ) {
+        let current_time = Worker::current_time().unwrap();
+        let round_end_time = Worker::round_end_time().unwrap();
+        let is_bootstrapping =
+            current_time < Worker::with(|w| w.shared.bootstrap_end_time).unwrap();
+        let payload_size = unsafe { cshadow::packet_getPayloadSize(packet) };
+
+        // the packet is dropped if there's no path while graph edges are down (which isn't checked
+        // before this for multicast and broadcast packets), or if an edge on the path goes down
+        // before the packet has crossed it
+        let is_routable = Worker::with(|w| {
+            w.shared.has_path(src_ip, dst_ip, path)
+                && !w.shared.path_goes_down(src_ip, dst_ip, path)
+        })
+        .unwrap();
+        if !is_routable {
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+            return;
+        }
+
+        // the sending host's uplink trace may add latency and packet loss
+        let uplink = src_host.uplink_trace_row(current_time);
+        let uplink_latency = uplink
+            .and_then(|row| row.latency)
+            .map_or(SimulationTime::ZERO, |x| {
+                std::time::Duration::from(x).try_into().unwrap()
+            });
+        let uplink_loss = uplink.and_then(|row| row.loss).unwrap_or(0.0);
+
         // check if network reliability forces us to 'drop' the packet
-        let reliability: f64 = Worker::with(|w| w.shared.reliability(src_ip, dst_ip).unwrap())
-            .unwrap()
-            .into();
+        let reliability = Worker::with(|w| {
+            w.shared.reliability(src_ip, dst_ip, path).unwrap()
+                * w.shared.wireless_reliability(src_ip, dst_ip)
+        })
+        .unwrap();
+        let reliability: f64 = (reliability * (1.0 - uplink_loss)).into();
         let chance: f64 = src_host.random_mut().gen();
 
         // don't drop control packets with length 0, otherwise congestion control has problems
@@ -374,13 +651,11 @@ impl Worker {
             return;
         }
 
-        let delay = Worker::with(|w| w.shared.latency(src_ip, dst_ip).unwrap()).unwrap();
+        let delay = Worker::with(|w| w.shared.latency(src_ip, dst_ip, path).unwrap()).unwrap()
+            + uplink_latency;
 
         Worker::update_lowest_used_latency(delay);
-        Worker::with(|w| w.shared.increment_packet_count(src_ip, dst_ip)).unwrap();
-
-        // TODO: this should change for sending to remote manager (on a different machine); this is
-        // the only place where tasks are sent between separate host
+        Worker::with(|w| w.shared.increment_packet_count(src_ip, dst_ip, path)).unwrap();
 
         unsafe {
             cshadow::packet_addDeliveryStatus(
@@ -390,21 +665,241 @@ impl Worker {
         };
 
         // copy the packet
-        let packet = PacketRc::from_raw(unsafe { cshadow::packet_copy(packet) });
+        let mut packet = PacketRc::from_raw(unsafe { cshadow::packet_copy(packet) });
+        packet.set_ttl(packet.ttl() - u8::try_from(routers).unwrap());
+
+        // check if the path corrupts the packet, which the receiver will detect using the
+        // checksum; like packet loss, don't corrupt control packets (and don't use the rng if the
+        // path can't corrupt packets, so that it doesn't change the random decisions of other
+        // simulations)
+        let corruption =
+            Worker::with(|w| w.shared.corruption(src_ip, dst_ip, path).unwrap()).unwrap();
+        if !is_bootstrapping && corruption > 0.0 && payload_size > 0 {
+            let chance: f32 = src_host.random_mut().gen();
+            if chance < corruption {
+                packet.add_status(PacketStatus::InetCorrupted);
+            }
+        }
 
-        // delay the packet until the next round
         let mut deliver_time = current_time + delay;
+
+        // add the random jitter of the path and the sending host's uplink (and like corruption,
+        // don't use the rng if there's no jitter)
+        let jitters = Worker::with(|w| w.shared.jitter(src_ip, dst_ip, path).unwrap()).unwrap();
+        for jitter in jitters.iter().filter(|x| !x.is_none()) {
+            deliver_time += jitter.sample(&mut *src_host.random_mut());
+        }
+
+        // check if the path reorders the packet, which we do by delaying it so that packets sent
+        // after it can arrive before it (and like corruption, don't use the rng if the path can't
+        // reorder packets)
+        let (reorder, reorder_delay, reorder_gap) =
+            Worker::with(|w| w.shared.reordering(src_ip, dst_ip, path).unwrap()).unwrap();
+        if !is_bootstrapping && reorder > 0.0 {
+            // like netem's gap, only a packet that follows at least `reorder_gap - 1` packets that
+            // weren't reordered may be reordered
+            let since_reorder = if reorder_gap > 1 {
+                src_host.packets_since_reorder(src_ip, dst_ip)
+            } else {
+                0
+            };
+
+            let is_reordered = since_reorder + 1 >= reorder_gap && {
+                let chance: f32 = src_host.random_mut().gen();
+                chance < reorder
+            };
+
+            if is_reordered {
+                deliver_time += reorder_delay;
+                packet.add_status(PacketStatus::InetReordered);
+            }
+
+            if reorder_gap > 1 {
+                let since_reorder = if is_reordered { 0 } else { since_reorder + 1 };
+                src_host.set_packets_since_reorder(src_ip, dst_ip, since_reorder);
+            }
+        }
+
+        // delay the packet until the next round
         if deliver_time < round_end_time {
             deliver_time = round_end_time;
         }
 
         // we may have sent this packet after the destination host finished running the current
         // round and calculated its min event time, so we put this in our min event time instead
+        // (if the packet is queued at links, it will arrive at this time or later)
+        Worker::update_next_event_time(deliver_time);
+
+        Worker::with(|w| {
+            // queue the packet at the links with a bandwidth on the path (but not while
+            // bootstrapping, when bandwidth is unlimited)
+            let links = w.shared.path_links(src_ip, dst_ip, path).unwrap();
+            match &w.shared.link_queues {
+                Some(link_queues) if !is_bootstrapping && !links.is_empty() => {
+                    let size = packet.total_size().try_into().unwrap();
+                    let event = Event::new_packet(packet, deliver_time, src_host);
+                    let properties = w.shared.links();
+                    link_queues.push(event, current_time, size, dst_host_id, links, properties);
+                }
+                _ => w
+                    .shared
+                    .push_packet_to_host(packet, dst_host_id, deliver_time, src_host),
+            }
+        })
+        .unwrap();
+    }
+
+    /// Send an ICMP "host unreachable" error for `packet` back to its source host. Shadow doesn't
+    /// model the routers on a path, so the error appears to come from the packet's destination, and
+    /// it's delivered at the start of the next round.
+    fn send_host_unreachable(src_host: &Host, packet: &PacketRc) {
+        let src_ip = *packet.src_address().ip();
+        let dst_ip = *packet.dst_address().ip();
+
+        let header = IcmpHeader {
+            icmp_type: IcmpHeader::TYPE_DEST_UNREACHABLE,
+            code: IcmpHeader::CODE_HOST_UNREACHABLE,
+            identifier: 0,
+            sequence: 0,
+        };
+
+        let mut error = PacketRc::from_raw(unsafe { cshadow::packet_new(src_host) });
+        error.set_icmp(dst_ip, src_ip, &header);
+        error.set_payload(&packet.icmp_error_quote(), 0);
+        error.add_status(PacketStatus::InetSent);
+
+        let deliver_time = std::cmp::max(
+            Worker::current_time().unwrap(),
+            Worker::round_end_time().unwrap(),
+        );
+
+        Worker::update_next_event_time(deliver_time);
+
+        Worker::with(|w| {
+            w.shared
+                .push_packet_to_host(error, src_host.id(), deliver_time, src_host)
+        })
+        .unwrap();
+    }
+
+    /// Send an ICMP "fragmentation needed" error for `packet` back to its source host. Shadow
+    /// doesn't model the routers on a path, so the error appears to come from the packet's
+    /// destination and is delayed by the latency of the path.
+    fn send_frag_needed(src_host: &Host, packet: &PacketRc, path_mtu: u32) {
+        let src_ip = *packet.src_address().ip();
+        let dst_ip = *packet.dst_address().ip();
+
+        let header = IcmpHeader {
+            icmp_type: IcmpHeader::TYPE_DEST_UNREACHABLE,
+            code: IcmpHeader::CODE_FRAG_NEEDED,
+            identifier: 0,
+            // the next-hop MTU
+            sequence: path_mtu.try_into().unwrap(),
+        };
+
+        let mut error = PacketRc::from_raw(unsafe { cshadow::packet_new(src_host) });
+        error.set_icmp(dst_ip, src_ip, &header);
+        error.set_payload(&packet.icmp_error_quote(), 0);
+        error.add_status(PacketStatus::InetSent);
+
+        let delay =
+            Worker::with(|w| w.shared.latency(src_ip.into(), dst_ip.into(), 0).unwrap()).unwrap();
+        let deliver_time = std::cmp::max(
+            Worker::current_time().unwrap() + delay,
+            Worker::round_end_time().unwrap(),
+        );
+
+        Worker::update_next_event_time(deliver_time);
+
+        Worker::with(|w| {
+            w.shared
+                .push_packet_to_host(error, src_host.id(), deliver_time, src_host)
+        })
+        .unwrap();
+    }
+
+    /// Send an ICMP "time exceeded" error for `packet` back to its source host from `router`, which
+    /// is the router at `router_index` on the packet's path and must have an address. The error is
+    /// delayed by the latency from the source to the router and back.
+    fn send_time_exceeded(
+        src_host: &Host,
+        packet: &PacketRc,
+        router: PathRouter,
+        router_index: u8,
+    ) {
+        let src_ip = *packet.src_address().ip();
+
+        let header = IcmpHeader {
+            icmp_type: IcmpHeader::TYPE_TIME_EXCEEDED,
+            code: IcmpHeader::CODE_TTL_EXCEEDED,
+            identifier: 0,
+            sequence: 0,
+        };
+
+        let mut error = PacketRc::from_raw(unsafe { cshadow::packet_new(src_host) });
+        error.set_icmp(router.address.unwrap(), src_ip, &header);
+        error.set_payload(&packet.icmp_error_quote(), 0);
+        // the routers between the router and the source also decrement the error's TTL
+        error.set_ttl(IPV4_DEFAULT_TTL - router_index);
+        error.add_status(PacketStatus::InetSent);
+
+        let delay = SimulationTime::from_nanos(2 * router.offset_ns);
+        let deliver_time = std::cmp::max(
+            Worker::current_time().unwrap() + delay,
+            Worker::round_end_time().unwrap(),
+        );
+
+        Worker::update_next_event_time(deliver_time);
+
+        Worker::with(|w| {
+            w.shared
+                .push_packet_to_host(error, src_host.id(), deliver_time, src_host)
+        })
+        .unwrap();
+    }
+
+    /// Answer a packet sent to the built-in DNS server with the server's response, which is
+    /// delayed by the server's latency. Packets that the server doesn't answer are dropped.
+    fn send_dns_response(src_host: &Host, mut packet: PacketRc) {
+        let response = Worker::with(|w| {
+            let server = w.shared.dns_server.as_ref().unwrap();
+
+            if packet.protocol() != cshadow::_ProtocolType_PUDP
+                || packet.dst_address().port() != DnsServer::PORT
+            {
+                return None;
+            }
+
+            let now = Worker::current_time().unwrap() - EmulatedTime::SIMULATION_START;
+
+            let mut query = vec![0; packet.payload_size()];
+            packet.get_payload(&mut query);
+            Some((server.respond(&query, now)?, server.latency()))
+        })
+        .unwrap();
+
+        let Some((response, latency)) = response else {
+            packet.add_status(PacketStatus::InetDropped);
+            return;
+        };
+
+        packet.add_status(PacketStatus::InetSent);
+
+        let mut reply = PacketRc::from_raw(unsafe { cshadow::packet_new(src_host) });
+        reply.set_udp(packet.dst_address(), packet.src_address());
+        reply.set_payload(&response, 0);
+        reply.add_status(PacketStatus::InetSent);
+
+        let deliver_time = std::cmp::max(
+            Worker::current_time().unwrap() + latency,
+            Worker::round_end_time().unwrap(),
+        );
+
         Worker::update_next_event_time(deliver_time);
 
         Worker::with(|w| {
             w.shared
-                .push_packet_to_host(packet, dst_host_id, deliver_time, src_host)
+                .push_packet_to_host(reply, src_host.id(), deliver_time, src_host)
         })
         .unwrap();
     }
@@ -422,6 +917,15 @@ impl Worker {
             .flatten()
     }
 
+    /// The ID of the active host, if any. Unlike [`Worker::with_active_host`], this is safe to call
+    /// while the active host is being set or taken.
+    fn active_host_id(&self) -> Option<HostId> {
+        self.active_host
+            .try_borrow()
+            .ok()
+            .and_then(|host| host.as_ref().map(|host| host.id()))
+    }
+
     pub fn increment_object_alloc_counter(s: &str) {
         if !USE_OBJECT_COUNTERS.load(std::sync::atomic::Ordering::Relaxed) {
             return;
@@ -429,10 +933,23 @@ impl Worker {
 
         Worker::with(|w| {
             w.sim_stats.alloc_counts.borrow_mut().add_one(s);
+            w.sim_stats
+                .host_alive_counts
+                .borrow_mut()
+                .entry(w.active_host_id())
+                .or_default()
+                .add_one(s);
         })
         .unwrap_or_else(|| {
             // no live worker; fall back to the shared counter
             SIM_STATS.alloc_counts.lock().unwrap().add_one(s);
+            SIM_STATS
+                .host_alive_counts
+                .lock()
+                .unwrap()
+                .entry(None)
+                .or_default()
+                .add_one(s);
         });
     }
 
@@ -443,10 +960,23 @@ impl Worker {
 
         Worker::with(|w| {
             w.sim_stats.dealloc_counts.borrow_mut().add_one(s);
+            w.sim_stats
+                .host_alive_counts
+                .borrow_mut()
+                .entry(w.active_host_id())
+                .or_default()
+                .sub_one(s);
         })
         .unwrap_or_else(|| {
             // no live worker; fall back to the shared counter
             SIM_STATS.dealloc_counts.lock().unwrap().add_one(s);
+            SIM_STATS
+                .host_alive_counts
+                .lock()
+                .unwrap()
+                .entry(None)
+                .or_default()
+                .sub_one(s);
         });
     }
 
@@ -471,6 +1001,28 @@ impl Worker {
         });
     }
 
+    /// Count a syscall towards the simulation phase that is active at the current time, if any.
+    pub fn increment_phase_syscall_counter(s: &str) {
+        Worker::with(|w| {
+            let Some(now) = w.clock.borrow().now else {
+                return;
+            };
+            let Some(phase) = w
+                .shared
+                .phases
+                .phase_at(now - EmulatedTime::SIMULATION_START)
+            else {
+                return;
+            };
+
+            let mut counts = w.sim_stats.phase_syscall_counts.borrow_mut();
+            if counts.len() <= phase {
+                counts.resize_with(phase + 1, Counter::new);
+            }
+            counts[phase].add_one(s);
+        });
+    }
+
     pub fn add_to_global_sim_stats() {
         Worker::with(|w| SIM_STATS.add_from_local_stats(&w.sim_stats)).unwrap()
     }
@@ -483,6 +1035,51 @@ impl Worker {
         Worker::with(|w| w.shared.increment_plugin_error_count()).unwrap()
     }
 
+    pub fn add_unsupported_syscall(syscall: SyscallNum) {
+        Worker::with(|w| w.shared.add_unsupported_syscall(syscall)).unwrap()
+    }
+
+    /// Update the stop conditions after a host's process exited and won't be restarted.
+    pub fn process_finished(host: &str, index: usize, expected_final_state: ProcessFinalState) {
+        Worker::with(|w| {
+            w.shared
+                .stop_conditions
+                .process_finished(host, index, expected_final_state)
+        })
+        .unwrap()
+    }
+
+    /// Count `bytes` that are about to be written to a stdout, stderr, or pcap file. Returns false
+    /// if they would exceed the maximum total size of the output files, in which case they
+    /// shouldn't be written and the simulation fails.
+    pub fn add_output_bytes(bytes: u64) -> bool {
+        Worker::with(|w| {
+            let allowed = w.shared.output_limits.add(bytes);
+            if !allowed {
+                w.shared
+                    .stop_conditions
+                    .stop("the output files exceeded their maximum total size");
+            }
+            allowed
+        })
+        .unwrap()
+    }
+
+    /// Fail the simulation since the output file at `path` reached its maximum size. The
+    /// simulation stops at the end of the current round.
+    pub fn fail_output_limit(path: &Path) {
+        Worker::with(|w| {
+            w.shared.output_limits.fail(format!(
+                "The output file '{}' reached its maximum size",
+                path.display()
+            ));
+            w.shared
+                .stop_conditions
+                .stop("an output file reached its maximum size");
+        })
+        .unwrap()
+    }
+
     /// Shadow allows configuration of a "bootstrapping" interval, during which
     /// hosts' network activity does not consume bandwidth. Returns `true` if we
     /// are still within this preliminary interval, or `false` otherwise.
@@ -490,10 +1087,49 @@ impl Worker {
         Worker::with(|w| w.clock.borrow().now.unwrap() < w.shared.bootstrap_end_time).unwrap()
     }
 
+    /// Add a host to a multicast group. Other hosts will see the change starting from the next
+    /// scheduling round.
+    pub fn join_multicast_group(
+        group: std::net::Ipv4Addr,
+        host_id: HostId,
+        host_ip: std::net::Ipv4Addr,
+    ) {
+        let round_end_time = Worker::round_end_time().unwrap();
+        Worker::with(|w| {
+            w.shared
+                .multicast_groups
+                .join(group, host_id, host_ip, round_end_time)
+        })
+        .unwrap();
+    }
+
+    /// Remove a host from a multicast group. Other hosts will see the change starting from the
+    /// next scheduling round.
+    pub fn leave_multicast_group(
+        group: std::net::Ipv4Addr,
+        host_id: HostId,
+        host_ip: std::net::Ipv4Addr,
+    ) {
+        let round_end_time = Worker::round_end_time().unwrap();
+        Worker::with(|w| {
+            w.shared
+                .multicast_groups
+                .leave(group, host_id, host_ip, round_end_time)
+        })
+        .unwrap();
+    }
+
+    /// Resolve the name of a host that has started at the current time.
     pub fn resolve_name_to_ip(name: &std::ffi::CStr) -> Option<std::net::Ipv4Addr> {
+        let now = Worker::current_time().unwrap() - EmulatedTime::SIMULATION_START;
+
         Worker::with_dns(|dns| {
             let addr = unsafe {
-                cshadow::dns_resolveNameToAddress(std::ptr::from_ref(dns).cast_mut(), name.as_ptr())
+                cshadow::dns_resolveNameToAddress(
+                    std::ptr::from_ref(dns).cast_mut(),
+                    name.as_ptr(),
+                    SimulationTime::to_c_simtime(Some(now)),
+                )
             };
             if addr.is_null() {
                 return None;
@@ -507,20 +1143,48 @@ impl Worker {
 #[derive(Debug)]
 pub struct WorkerShared {
     pub ip_assignment: IpAssignment<u32>,
-    pub routing_info: RoutingInfo<u32>,
+    /// Routing information for paths between graph nodes, which changes with link events.
+    pub routing: RoutingSchedule<u32>,
     pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
+    /// The packet loss on the wireless channel of hosts at ip addresses, for hosts of wireless
+    /// access points.
+    pub wireless_loss: HashMap<std::net::IpAddr, f32>,
     pub dns: SyncSendPointer<cshadow::DNS>,
     // allows for easy updating of the status bar's state
     pub status_logger_state: Option<Arc<status_bar::Status<ShadowStatusBarState>>>,
     // number of plugins that failed with a non-zero exit code
     pub num_plugin_errors: AtomicU32,
+    // unsupported syscalls that were made by any managed process
+    pub unsupported_syscalls: Mutex<HashSet<SyscallNum>>,
     // calculates the runahead for the next simulation round
     pub runahead: Runahead,
     pub child_pid_watcher: ChildPidWatcher,
-    /// Event queues for each host. This should only be used to push packet events.
+    /// Event queues for each host of this manager. This should only be used to push packet events.
     pub event_queues: HashMap<HostId, Arc<Mutex<EventQueue>>>,
     pub bootstrap_end_time: EmulatedTime,
     pub sim_end_time: EmulatedTime,
+    pub phases: SimPhases,
+    /// The hosts that are members of each multicast group.
+    pub multicast_groups: MulticastGroups,
+    pub multicast_scope: MulticastScope,
+    /// How packets are spread over the equal-cost paths between graph nodes.
+    pub ecmp: EcmpMode,
+    /// Queues at the graph edges with a bandwidth, if link contention is enabled.
+    pub link_queues: Option<LinkQueues>,
+    /// The private subnets behind NAT gateways.
+    pub nat_gateways: NatGateways,
+    /// The middleboxes and the subnets that they intercept.
+    pub middleboxes: Middleboxes,
+    /// The built-in DNS server, if enabled.
+    pub dns_server: Option<DnsServer>,
+    /// The path of the resolv.conf file that points to the built-in DNS server, if enabled.
+    pub resolv_conf_path: Option<CString>,
+    /// The conditions that stop the simulation before its end time.
+    pub stop_conditions: SimStopConditions,
+    /// The limits on the size of the hosts' output files.
+    pub output_limits: SimOutputLimits,
+    /// The other managers, if the hosts are run by several managers.
+    pub remote_managers: Option<RemoteManagers>,
 }
 
 impl WorkerShared {
@@ -536,31 +1200,243 @@ impl WorkerShared {
         unsafe { self.dns.ptr().as_ref() }.unwrap()
     }
 
-    pub fn latency(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> Option<SimulationTime> {
+    /// The routing information at the current time of the worker thread.
+    fn routing_info(&self) -> &RoutingInfo<u32> {
+        self.routing.at(Self::routing_time())
+    }
+
+    /// The routing information of the equal-cost path at index `path` at the current time of the
+    /// worker thread.
+    fn path_info(&self, path: usize) -> Option<&RoutingInfo<u32>> {
+        self.routing
+            .equal_cost_paths_at(Self::routing_time())
+            .get(path)
+    }
+
+    /// The current time of the worker thread, or the start of the simulation if the thread isn't
+    /// running an event.
+    fn routing_time() -> SimulationTime {
+        Worker::current_time().map_or(SimulationTime::ZERO, |now| {
+            now - EmulatedTime::SIMULATION_START
+        })
+    }
+
+    /// Returns true if there's an equal-cost path at index `path` between two addresses. There may
+    /// not be a path while graph edges are down.
+    pub fn has_path(&self, src: std::net::IpAddr, dst: std::net::IpAddr, path: usize) -> bool {
+        let (Some(src), Some(dst)) = (
+            self.ip_assignment.get_node(src),
+            self.ip_assignment.get_node(dst),
+        ) else {
+            return false;
+        };
+
+        self.path_info(path)
+            .is_some_and(|info| info.path(src, dst).is_some())
+    }
+
+    /// The number of equal-cost paths between two addresses.
+    pub fn path_count(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> usize {
+        let (Some(src), Some(dst)) = (
+            self.ip_assignment.get_node(src),
+            self.ip_assignment.get_node(dst),
+        ) else {
+            return 0;
+        };
+
+        self.routing.path_count(src, dst, Self::routing_time())
+    }
+
+    /// Returns true if a graph edge on the equal-cost path at index `path` between two addresses
+    /// goes down before a packet sent now has crossed it.
+    pub fn path_goes_down(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> bool {
+        let (Some(src), Some(dst)) = (
+            self.ip_assignment.get_node(src),
+            self.ip_assignment.get_node(dst),
+        ) else {
+            return false;
+        };
+
+        self.routing
+            .path_goes_down(src, dst, path, Self::routing_time())
+    }
+
+    /// The latency of the path between two addresses before link events or link traces change
+    /// the graph.
+    pub fn initial_latency(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+    ) -> Option<SimulationTime> {
         let src = self.ip_assignment.get_node(src)?;
         let dst = self.ip_assignment.get_node(dst)?;
 
         Some(SimulationTime::from_nanos(
-            self.routing_info.path(src, dst)?.latency_ns,
+            self.routing.initial().path(src, dst)?.latency_ns,
+        ))
+    }
+
+    pub fn latency(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<SimulationTime> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        Some(SimulationTime::from_nanos(
+            self.path_info(path)?.path(src, dst)?.latency_ns,
+        ))
+    }
+
+    pub fn reliability(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<f32> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        Some(1.0 - self.path_info(path)?.path(src, dst)?.packet_loss)
+    }
+
+    /// The chance that a packet on the path between two addresses is corrupted.
+    pub fn corruption(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<f32> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        Some(self.path_info(path)?.path(src, dst)?.packet_corruption)
+    }
+
+    /// The jitter of the path between two addresses, and of the uplink at the source address.
+    pub fn jitter(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<[Jitter; 2]> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        let path = self.path_info(path)?.path(src, dst)?;
+        Some([path.jitter, path.host_jitter])
+    }
+
+    /// The chance that a packet on the path between two addresses is reordered, the additional
+    /// delay of reordered packets, and the reordering gap.
+    pub fn reordering(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<(f32, SimulationTime, u32)> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        let path = self.path_info(path)?.path(src, dst)?;
+        Some((
+            path.packet_reorder,
+            SimulationTime::from_nanos(path.reorder_delay_ns),
+            path.reorder_gap,
         ))
     }
 
-    pub fn reliability(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> Option<f32> {
+    /// The smallest MTU of the links on the path between two addresses. Returns `None` if the
+    /// links don't limit the packet size.
+    pub fn path_mtu(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<u32> {
         let src = self.ip_assignment.get_node(src)?;
         let dst = self.ip_assignment.get_node(dst)?;
 
-        Some(1.0 - self.routing_info.path(src, dst)?.packet_loss)
+        self.path_info(path)?.path(src, dst)?.mtu
+    }
+
+    /// The number of routers on the path between two addresses.
+    pub fn path_routers(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<u32> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        Some(self.path_info(path)?.path(src, dst)?.routers)
+    }
+
+    /// The router at `index` on the path between two addresses. Returns `None` if no router on
+    /// the network graph has an address, in which case the routers aren't known.
+    pub fn path_router(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+        index: usize,
+    ) -> Option<PathRouter> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        self.path_info(path)?.path_router(src, dst, index)
+    }
+
+    /// The links with a bandwidth on the path between two addresses, in the order that they're
+    /// crossed.
+    pub fn path_links(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) -> Option<&[PathLink]> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        Some(self.path_info(path)?.path_links(src, dst))
+    }
+
+    /// The current properties of the links with a bandwidth.
+    pub fn links(&self) -> &[LinkProperties] {
+        self.routing_info().links()
     }
 
     pub fn bandwidth(&self, ip: std::net::IpAddr) -> Option<&Bandwidth> {
         self.host_bandwidths.get(&ip)
     }
 
-    pub fn increment_packet_count(&self, src: std::net::IpAddr, dst: std::net::IpAddr) {
+    /// The probability that a packet isn't lost on the wireless channels of its source and
+    /// destination hosts.
+    pub fn wireless_reliability(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> f32 {
+        let loss = |ip| self.wireless_loss.get(&ip).copied().unwrap_or(0.0);
+        (1.0 - loss(src)) * (1.0 - loss(dst))
+    }
+
+    pub fn increment_packet_count(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        path: usize,
+    ) {
         let src = self.ip_assignment.get_node(src).unwrap();
         let dst = self.ip_assignment.get_node(dst).unwrap();
 
-        self.routing_info.increment_packet_count(src, dst)
+        self.path_info(path)
+            .unwrap()
+            .increment_packet_count(src, dst)
     }
 
     pub fn is_routable(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> bool {
@@ -576,6 +1452,83 @@ impl WorkerShared {
         true
     }
 
+    /// The hosts that should receive a packet sent from `src` (on the host `src_host_id`) to the
+    /// multicast `group` during the round ending at `round_end`. The sending host doesn't receive
+    /// its own packets.
+    pub fn multicast_destinations(
+        &self,
+        src_host_id: HostId,
+        src: std::net::Ipv4Addr,
+        group: std::net::Ipv4Addr,
+        round_end: EmulatedTime,
+    ) -> Vec<(HostId, std::net::Ipv4Addr)> {
+        let Some(src_node) = self.ip_assignment.get_node(src.into()) else {
+            return Vec::new();
+        };
+
+        let mut members = self.multicast_groups.members(group, round_end);
+        members.retain(|(host_id, ip)| {
+            if *host_id == src_host_id {
+                return false;
+            }
+            match self.multicast_scope {
+                MulticastScope::Node => self.ip_assignment.get_node((*ip).into()) == Some(src_node),
+                MulticastScope::Graph => self.ip_assignment.get_node((*ip).into()).is_some(),
+            }
+        });
+        members
+    }
+
+    /// The hosts that should receive a packet sent from `src` (on the host `src_host_id`) to the
+    /// broadcast address `dst`. Hosts attached to the same graph node share a network segment, and
+    /// a subnet broadcast only reaches the hosts in the subnet. The sending host doesn't receive
+    /// its own packets.
+    pub fn broadcast_destinations(
+        &self,
+        src_host_id: HostId,
+        src: std::net::Ipv4Addr,
+        dst: std::net::Ipv4Addr,
+    ) -> Vec<(HostId, std::net::Ipv4Addr)> {
+        let Some(src_node) = self.ip_assignment.get_node(src.into()) else {
+            return Vec::new();
+        };
+
+        self.ip_assignment
+            .get_node_addresses(src_node)
+            .into_iter()
+            .filter_map(|ip| match ip {
+                std::net::IpAddr::V4(ip) => Some(ip),
+                std::net::IpAddr::V6(_) => None,
+            })
+            .filter(|ip| dst.is_broadcast() || subnet_broadcast_address(*ip) == dst)
+            .filter_map(|ip| Some((self.resolve_ip_to_host_id(ip)?, ip)))
+            .filter(|(host_id, _)| *host_id != src_host_id)
+            .collect()
+    }
+
+    /// The source and destination addresses of the path taken by a packet that the host
+    /// `src_host_id` sends from `src` to `dst`. Hosts in a private subnet send packets for hosts
+    /// outside of the subnet to the subnet's NAT gateway, and only the gateway can send packets
+    /// into the subnet. Returns `None` if the destination can't be reached.
+    pub fn nat_path(
+        &self,
+        src_host_id: HostId,
+        src: std::net::Ipv4Addr,
+        dst: std::net::Ipv4Addr,
+    ) -> Option<(std::net::Ipv4Addr, std::net::Ipv4Addr)> {
+        let src_gateway = self.nat_gateways.gateway(src);
+        let dst_gateway = self.nat_gateways.gateway(dst);
+
+        match (src_gateway, dst_gateway) {
+            (None, None) => Some((src, dst)),
+            (Some(x), Some(y)) if x.host_id == y.host_id => Some((src, dst)),
+            (Some(x), _) => Some((src, x.public_ip)),
+            // the gateway forwards packets into the subnet from its public address
+            (None, Some(y)) if y.host_id == src_host_id => Some((y.public_ip, dst)),
+            (None, Some(_)) => None,
+        }
+    }
+
     pub fn resolve_ip_to_host_id(&self, ip: std::net::Ipv4Addr) -> Option<HostId> {
         let dns = self.dns.ptr();
         let ip = u32::from(ip).to_be();
@@ -603,6 +1556,23 @@ impl WorkerShared {
             .load(std::sync::atomic::Ordering::SeqCst)
     }
 
+    pub fn add_unsupported_syscall(&self, syscall: SyscallNum) {
+        self.unsupported_syscalls.lock().unwrap().insert(syscall);
+    }
+
+    /// The unsupported syscalls that were made during the simulation, sorted by syscall number.
+    pub fn unsupported_syscalls(&self) -> Vec<SyscallNum> {
+        let mut syscalls: Vec<_> = self
+            .unsupported_syscalls
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        syscalls.sort_by_key(|x| u32::from(*x));
+        syscalls
+    }
+
     /// Update the status logger. If the status logger is disabled, this will be a no-op.
     pub fn update_status_logger(&self, f: impl FnOnce(&mut ShadowStatusBarState)) {
         if let Some(ref logger_state) = self.status_logger_state {
@@ -634,8 +1604,32 @@ impl WorkerShared {
         src_host: &Host,
     ) {
         let event = Event::new_packet(packet, time, src_host);
-        let event_queue = self.event_queues.get(&dst_host_id).unwrap();
-        event_queue.lock().unwrap().push(event);
+        self.push_event_to_host(event, dst_host_id);
+    }
+
+    /// Push the packets that were queued at links during the round to their destination hosts'
+    /// event queues. This must be called between scheduling rounds.
+    pub fn flush_link_queues(&self) {
+        let Some(link_queues) = &self.link_queues else {
+            return;
+        };
+
+        for (dst_host_id, event) in link_queues.flush() {
+            self.push_event_to_host(event, dst_host_id);
+        }
+    }
+
+    /// Push a packet event to the destination host's event queue, or send it to the manager that
+    /// runs the destination host if it's run by another manager.
+    fn push_event_to_host(&self, event: Event, dst_host_id: HostId) {
+        match self.event_queues.get(&dst_host_id) {
+            Some(event_queue) => event_queue.lock().unwrap().push(event),
+            None => self
+                .remote_managers
+                .as_ref()
+                .unwrap()
+                .push(dst_host_id, event),
+        }
     }
 }
 
@@ -655,6 +1649,9 @@ pub fn with_global_sim_stats<T>(f: impl FnOnce(&SharedSimStats) -> T) -> T {
 }
 
 mod export {
+    use std::ffi::{CStr, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
     use shadow_shim_helper_rs::emulated_time::CEmulatedTime;
     use shadow_shim_helper_rs::simulation_time::CSimulationTime;
 
@@ -666,7 +1663,21 @@ mod export {
         Worker::with_dns(std::ptr::from_ref).cast_mut()
     }
 
-    /// Addresses must be provided in network byte order.
+    /// Returns the path of the resolv.conf file that points to the built-in DNS server, or NULL
+    /// if the server isn't enabled. The path is valid for the rest of the simulation.
+    #[no_mangle]
+    pub extern "C-unwind" fn worker_getResolvConfPath() -> *const libc::c_char {
+        Worker::with(|w| {
+            w.shared
+                .resolv_conf_path
+                .as_ref()
+                .map_or(std::ptr::null(), |x| x.as_ptr())
+        })
+        .unwrap()
+    }
+
+    /// The latency of the path between two addresses before link events or link traces change the
+    /// graph. Addresses must be provided in network byte order.
     #[no_mangle]
     pub extern "C-unwind" fn worker_getLatency(
         src: libc::in_addr_t,
@@ -675,7 +1686,7 @@ mod export {
         let src = std::net::IpAddr::V4(u32::from_be(src).into());
         let dst = std::net::IpAddr::V4(u32::from_be(dst).into());
 
-        let latency = Worker::with(|w| w.shared.latency(src, dst)).unwrap();
+        let latency = Worker::with(|w| w.shared.initial_latency(src, dst)).unwrap();
         SimulationTime::to_c_simtime(latency)
     }
 
@@ -758,6 +1769,23 @@ mod export {
         EmulatedTime::to_c_emutime(Worker::current_time())
     }
 
+    /// Count `bytes` that are about to be written to an output file. Returns false if they would
+    /// exceed the maximum total size of the output files, in which case they shouldn't be written.
+    #[no_mangle]
+    pub extern "C-unwind" fn worker_addOutputBytes(bytes: u64) -> bool {
+        Worker::add_output_bytes(bytes)
+    }
+
+    /// Fail the simulation since the output file at `path` reached its maximum size.
+    ///
+    /// SAFETY: `path` must be a valid nul-terminated string.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn worker_failOutputLimit(path: *const libc::c_char) {
+        assert!(!path.is_null());
+        let path = unsafe { CStr::from_ptr(path) };
+        Worker::fail_output_limit(Path::new(OsStr::from_bytes(path.to_bytes())));
+    }
+
     #[no_mangle]
     pub extern "C-unwind" fn worker_resolveIPToAddress(
         ip: libc::in_addr_t,
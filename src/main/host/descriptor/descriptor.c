@@ -189,6 +189,9 @@ static gchar* _legacyfile_statusToString(FileState ds) {
     if (ds & FileState_SOCKET_ALLOWING_CONNECT) {
         g_string_append_printf(string, "SOCKET_ALLOWING_CONNECT|");
     }
+    if (ds & FileState_SOCKET_DRAINED) {
+        g_string_append_printf(string, "SOCKET_DRAINED|");
+    }
     if (string->len == 0) {
         g_string_append_printf(string, "NONE|");
     }
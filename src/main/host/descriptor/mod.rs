@@ -11,6 +11,7 @@ use crate::core::worker;
 use crate::cshadow as c;
 use crate::host::descriptor::listener::{StateListenHandle, StateListenerFilter};
 use crate::host::descriptor::socket::{Socket, SocketRef, SocketRefMut};
+use crate::host::file_lock_table::FileLockOwner;
 use crate::host::host::Host;
 use crate::host::memory_manager::MemoryManager;
 use crate::host::syscall::io::IoVec;
@@ -22,10 +23,12 @@ pub mod descriptor_table;
 pub mod epoll;
 pub mod eventfd;
 pub mod listener;
+pub mod mqueue;
 pub mod pipe;
 pub mod shared_buf;
 pub mod socket;
 pub mod timerfd;
+pub mod tun;
 
 bitflags::bitflags! {
     /// These are flags that can potentially be changed from the plugin (analagous to the Linux
@@ -136,6 +139,9 @@ bitflags::bitflags! {
         /// A listening socket is allowing connections. Only applicable to connection-oriented unix
         /// sockets.
         const SOCKET_ALLOWING_CONNECT = 1 << 6;
+        /// A closed socket has sent all of its data and had it acknowledged, or was reset. Only
+        /// applicable to legacy tcp sockets.
+        const SOCKET_DRAINED = 1 << 7;
     }
 }
 
@@ -157,6 +163,8 @@ pub enum File {
     Socket(Socket),
     TimerFd(Arc<AtomicRefCell<timerfd::TimerFd>>),
     Epoll(Arc<AtomicRefCell<epoll::Epoll>>),
+    MessageQueue(Arc<AtomicRefCell<mqueue::MqFile>>),
+    Tun(Arc<AtomicRefCell<tun::TunFile>>),
 }
 
 // will not compile if `File` is not Send + Sync
@@ -171,6 +179,8 @@ impl File {
             Self::Socket(ref f) => FileRef::Socket(f.borrow()),
             Self::TimerFd(ref f) => FileRef::TimerFd(f.borrow()),
             Self::Epoll(ref f) => FileRef::Epoll(f.borrow()),
+            Self::MessageQueue(ref f) => FileRef::MessageQueue(f.borrow()),
+            Self::Tun(ref f) => FileRef::Tun(f.borrow()),
         }
     }
 
@@ -181,6 +191,8 @@ impl File {
             Self::Socket(ref f) => FileRef::Socket(f.try_borrow()?),
             Self::TimerFd(ref f) => FileRef::TimerFd(f.try_borrow()?),
             Self::Epoll(ref f) => FileRef::Epoll(f.try_borrow()?),
+            Self::MessageQueue(ref f) => FileRef::MessageQueue(f.try_borrow()?),
+            Self::Tun(ref f) => FileRef::Tun(f.try_borrow()?),
         })
     }
 
@@ -191,6 +203,8 @@ impl File {
             Self::Socket(ref f) => FileRefMut::Socket(f.borrow_mut()),
             Self::TimerFd(ref f) => FileRefMut::TimerFd(f.borrow_mut()),
             Self::Epoll(ref f) => FileRefMut::Epoll(f.borrow_mut()),
+            Self::MessageQueue(ref f) => FileRefMut::MessageQueue(f.borrow_mut()),
+            Self::Tun(ref f) => FileRefMut::Tun(f.borrow_mut()),
         }
     }
 
@@ -201,6 +215,8 @@ impl File {
             Self::Socket(ref f) => FileRefMut::Socket(f.try_borrow_mut()?),
             Self::TimerFd(ref f) => FileRefMut::TimerFd(f.try_borrow_mut()?),
             Self::Epoll(ref f) => FileRefMut::Epoll(f.try_borrow_mut()?),
+            Self::MessageQueue(ref f) => FileRefMut::MessageQueue(f.try_borrow_mut()?),
+            Self::Tun(ref f) => FileRefMut::Tun(f.try_borrow_mut()?),
         })
     }
 
@@ -211,6 +227,8 @@ impl File {
             Self::Socket(ref f) => f.canonical_handle(),
             Self::TimerFd(f) => Arc::as_ptr(f) as usize,
             Self::Epoll(f) => Arc::as_ptr(f) as usize,
+            Self::MessageQueue(f) => Arc::as_ptr(f) as usize,
+            Self::Tun(f) => Arc::as_ptr(f) as usize,
         }
     }
 }
@@ -223,6 +241,8 @@ impl std::fmt::Debug for File {
             Self::Socket(_) => write!(f, "Socket")?,
             Self::TimerFd(_) => write!(f, "TimerFd")?,
             Self::Epoll(_) => write!(f, "Epoll")?,
+            Self::MessageQueue(_) => write!(f, "MessageQueue")?,
+            Self::Tun(_) => write!(f, "Tun")?,
         }
 
         if let Ok(file) = self.try_borrow() {
@@ -242,6 +262,8 @@ pub enum FileRef<'a> {
     Socket(SocketRef<'a>),
     TimerFd(atomic_refcell::AtomicRef<'a, timerfd::TimerFd>),
     Epoll(atomic_refcell::AtomicRef<'a, epoll::Epoll>),
+    MessageQueue(atomic_refcell::AtomicRef<'a, mqueue::MqFile>),
+    Tun(atomic_refcell::AtomicRef<'a, tun::TunFile>),
 }
 
 /// Wraps a mutably borrowed [`File`]. Created from [`File::borrow_mut`] or
@@ -252,55 +274,57 @@ pub enum FileRefMut<'a> {
     Socket(SocketRefMut<'a>),
     TimerFd(atomic_refcell::AtomicRefMut<'a, timerfd::TimerFd>),
     Epoll(atomic_refcell::AtomicRefMut<'a, epoll::Epoll>),
+    MessageQueue(atomic_refcell::AtomicRefMut<'a, mqueue::MqFile>),
+    Tun(atomic_refcell::AtomicRefMut<'a, tun::TunFile>),
 }
 
 impl FileRef<'_> {
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn state(&self) -> FileState
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn mode(&self) -> FileMode
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn status(&self) -> FileStatus
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn has_open_file(&self) -> bool
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn supports_sa_restart(&self) -> bool
     );
 }
 
 impl FileRefMut<'_> {
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn state(&self) -> FileState
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn mode(&self) -> FileMode
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn status(&self) -> FileStatus
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn has_open_file(&self) -> bool
     );
-    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn supports_sa_restart(&self) -> bool
     );
-    enum_passthrough!(self, (val), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (val), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn set_has_open_file(&mut self, val: bool)
     );
-    enum_passthrough!(self, (cb_queue), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (cb_queue), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError>
     );
-    enum_passthrough!(self, (status), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (status), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn set_status(&mut self, status: FileStatus)
     );
-    enum_passthrough!(self, (request, arg_ptr, memory_manager), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (request, arg_ptr, memory_manager), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn ioctl(&mut self, request: IoctlRequest, arg_ptr: ForeignPtr<()>, memory_manager: &mut MemoryManager) -> SyscallResult
     );
-    enum_passthrough!(self, (monitoring_state, monitoring_signals, filter, notify_fn), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (monitoring_state, monitoring_signals, filter, notify_fn), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn add_listener(
             &mut self,
             monitoring_state: FileState,
@@ -309,17 +333,17 @@ impl FileRefMut<'_> {
             notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue) + Send + Sync + 'static,
         ) -> StateListenHandle
     );
-    enum_passthrough!(self, (ptr), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (ptr), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>)
     );
-    enum_passthrough!(self, (ptr), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (ptr), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener)
     );
-    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn readv(&mut self, iovs: &[IoVec], offset: Option<libc::off_t>, flags: libc::c_int,
                      mem: &mut MemoryManager, cb_queue: &mut CallbackQueue) -> Result<libc::ssize_t, SyscallError>
     );
-    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), Pipe, EventFd, Socket, TimerFd, Epoll;
+    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), Pipe, EventFd, Socket, TimerFd, Epoll, MessageQueue, Tun;
         pub fn writev(&mut self, iovs: &[IoVec], offset: Option<libc::off_t>, flags: libc::c_int,
                       mem: &mut MemoryManager, cb_queue: &mut CallbackQueue) -> Result<libc::ssize_t, SyscallError>
     );
@@ -333,6 +357,8 @@ impl std::fmt::Debug for FileRef<'_> {
             Self::Socket(_) => write!(f, "Socket")?,
             Self::TimerFd(_) => write!(f, "TimerFd")?,
             Self::Epoll(_) => write!(f, "Epoll")?,
+            Self::MessageQueue(_) => write!(f, "MessageQueue")?,
+            Self::Tun(_) => write!(f, "Tun")?,
         }
 
         let state = self.state();
@@ -349,6 +375,8 @@ impl std::fmt::Debug for FileRefMut<'_> {
             Self::Socket(_) => write!(f, "Socket")?,
             Self::TimerFd(_) => write!(f, "TimerFd")?,
             Self::Epoll(_) => write!(f, "Epoll")?,
+            Self::MessageQueue(_) => write!(f, "MessageQueue")?,
+            Self::Tun(_) => write!(f, "Tun")?,
         }
 
         let state = self.state();
@@ -625,6 +653,11 @@ impl LegacyFileCounter {
         // from multiple threads at the same time
         if Arc::<()>::strong_count(&self.open_count) == 1 {
             if let Some(file) = self.file.take() {
+                // OFD and flock locks are released when the open file is closed
+                if unsafe { c::legacyfile_getType(file.ptr()) } == c::_LegacyFileType_DT_FILE {
+                    host.file_lock_table_borrow_mut()
+                        .release_owner(FileLockOwner::OpenFile(file.ptr() as usize));
+                }
                 unsafe { c::legacyfile_close(file.ptr(), host) }
             }
         }
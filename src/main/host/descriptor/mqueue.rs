@@ -0,0 +1,455 @@
+//! POSIX message queues. A [`MessageQueue`] is a host-wide named queue of messages ordered by
+//! priority. Each successful `mq_open()` creates a new [`MqFile`] (the open file description) that
+//! refers to the shared queue, similar to how a [`Pipe`](super::pipe::Pipe) refers to a
+//! [`SharedBuf`](super::shared_buf::SharedBuf).
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
+use linux_api::errno::Errno;
+use linux_api::ioctls::IoctlRequest;
+use linux_api::signal::Signal;
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+
+use crate::cshadow as c;
+use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
+use crate::host::descriptor::{FileMode, FileSignals, FileState, FileStatus};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::process::ProcessId;
+use crate::host::syscall::io::IoVec;
+use crate::host::syscall::types::{SyscallError, SyscallResult};
+use crate::utility::callback_queue::CallbackQueue;
+use crate::utility::HostTreePointer;
+
+/// The queue limits given to (or defaulted by) `mq_open()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MqLimits {
+    pub max_msgs: usize,
+    pub max_msg_size: usize,
+}
+
+/// A notification request registered with `mq_notify()`.
+#[derive(Copy, Clone, Debug)]
+pub struct MqNotification {
+    /// The process that registered for the notification.
+    pub pid: ProcessId,
+    /// The signal to send, or `None` for `SIGEV_NONE`.
+    pub signal: Option<Signal>,
+    /// The raw bits of the `sigev_value` union. We don't store the union itself since it contains
+    /// a pointer and isn't `Send`.
+    pub sigval: usize,
+}
+
+pub struct MessageQueue {
+    /// Messages ordered by descending priority, and then by order of arrival.
+    messages: BTreeMap<(Reverse<u32>, u64), Vec<u8>>,
+    next_seq: u64,
+    limits: MqLimits,
+    notification: Option<MqNotification>,
+    state: FileState,
+    event_source: StateEventSource,
+}
+
+impl MessageQueue {
+    pub fn new(limits: MqLimits) -> Self {
+        assert_ne!(limits.max_msgs, 0);
+        assert_ne!(limits.max_msg_size, 0);
+        Self {
+            messages: BTreeMap::new(),
+            next_seq: 0,
+            limits,
+            notification: None,
+            state: FileState::WRITABLE,
+            event_source: StateEventSource::new(),
+        }
+    }
+
+    pub fn limits(&self) -> MqLimits {
+        self.limits
+    }
+
+    pub fn num_msgs(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn notification(&self) -> Option<&MqNotification> {
+        self.notification.as_ref()
+    }
+
+    /// Register a notification. Fails with `EBUSY` if a different process is already registered.
+    pub fn set_notification(&mut self, notification: MqNotification) -> Result<(), Errno> {
+        if let Some(existing) = &self.notification {
+            if existing.pid != notification.pid {
+                return Err(Errno::EBUSY);
+            }
+        }
+        self.notification = Some(notification);
+        Ok(())
+    }
+
+    /// Remove the notification if it was registered by process `pid`.
+    pub fn clear_notification(&mut self, pid: ProcessId) {
+        if self.notification.as_ref().map(|n| n.pid) == Some(pid) {
+            self.notification = None;
+        }
+    }
+
+    /// Add a message to the queue. If the queue was previously empty, any registered notification
+    /// is removed from the queue and returned so that the caller can deliver it.
+    ///
+    /// Linux doesn't send a notification if some thread is blocked in `mq_receive()`, since that
+    /// thread receives the message directly. We don't track blocked receivers, so the
+    /// notification is always returned.
+    pub fn send(
+        &mut self,
+        msg: Vec<u8>,
+        priority: u32,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<Option<MqNotification>, Errno> {
+        if msg.len() > self.limits.max_msg_size {
+            return Err(Errno::EMSGSIZE);
+        }
+
+        if self.messages.len() >= self.limits.max_msgs {
+            return Err(Errno::EAGAIN);
+        }
+
+        let was_empty = self.messages.is_empty();
+
+        self.messages
+            .insert((Reverse(priority), self.next_seq), msg);
+        self.next_seq += 1;
+
+        self.refresh_state(FileSignals::READ_BUFFER_GREW, cb_queue);
+
+        Ok(if was_empty {
+            self.notification.take()
+        } else {
+            None
+        })
+    }
+
+    /// Remove the oldest message with the highest priority from the queue.
+    pub fn receive(&mut self, cb_queue: &mut CallbackQueue) -> Result<(Vec<u8>, u32), Errno> {
+        let Some(((Reverse(priority), _seq), msg)) = self.messages.pop_first() else {
+            return Err(Errno::EAGAIN);
+        };
+
+        self.refresh_state(FileSignals::empty(), cb_queue);
+
+        Ok((msg, priority))
+    }
+
+    pub fn add_listener(
+        &mut self,
+        monitoring_state: FileState,
+        monitoring_signals: FileSignals,
+        notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue)
+            + Send
+            + Sync
+            + 'static,
+    ) -> StateListenHandle {
+        self.event_source.add_listener(
+            monitoring_state,
+            monitoring_signals,
+            StateListenerFilter::Always,
+            notify_fn,
+        )
+    }
+
+    pub fn state(&self) -> FileState {
+        self.state
+    }
+
+    fn refresh_state(&mut self, signals: FileSignals, cb_queue: &mut CallbackQueue) {
+        let old_state = self.state;
+
+        self.state
+            .set(FileState::READABLE, !self.messages.is_empty());
+        self.state.set(
+            FileState::WRITABLE,
+            self.messages.len() < self.limits.max_msgs,
+        );
+
+        let states_changed = self.state ^ old_state;
+
+        // if nothing changed
+        if states_changed.is_empty() && signals.is_empty() {
+            return;
+        }
+
+        self.event_source
+            .notify_listeners(self.state, states_changed, signals, cb_queue);
+    }
+}
+
+/// An open file description for a [`MessageQueue`].
+pub struct MqFile {
+    queue: Option<Arc<AtomicRefCell<MessageQueue>>>,
+    event_source: StateEventSource,
+    state: FileState,
+    mode: FileMode,
+    status: FileStatus,
+    queue_event_handle: Option<StateListenHandle>,
+    // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
+    // this file
+    has_open_file: bool,
+}
+
+impl MqFile {
+    /// Create a new [`MqFile`]. The new file must be initialized using
+    /// [`MqFile::connect_to_queue`] before any of its methods are called.
+    pub fn new(mode: FileMode, status: FileStatus) -> Self {
+        Self {
+            queue: None,
+            event_source: StateEventSource::new(),
+            state: FileState::ACTIVE,
+            mode,
+            status,
+            queue_event_handle: None,
+            has_open_file: false,
+        }
+    }
+
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: FileStatus) {
+        self.status = status;
+    }
+
+    pub fn mode(&self) -> FileMode {
+        self.mode
+    }
+
+    pub fn has_open_file(&self) -> bool {
+        self.has_open_file
+    }
+
+    pub fn supports_sa_restart(&self) -> bool {
+        true
+    }
+
+    pub fn set_has_open_file(&mut self, val: bool) {
+        self.has_open_file = val;
+    }
+
+    /// The queue that this file refers to. Panics if the file is closed.
+    pub fn queue(&self) -> &Arc<AtomicRefCell<MessageQueue>> {
+        self.queue.as_ref().unwrap()
+    }
+
+    pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
+        if self.state.contains(FileState::CLOSED) {
+            log::warn!("Attempting to close an already-closed message queue");
+        }
+
+        // drop the event listener handle so that we stop receiving new events
+        if let Some(h) = self.queue_event_handle.take() {
+            h.stop_listening()
+        }
+
+        // the queue itself lives on in the namespace until it's unlinked
+        self.queue = None;
+
+        // set the closed flag and remove the active, readable, and writable flags
+        self.update_state(
+            FileState::CLOSED | FileState::ACTIVE | FileState::READABLE | FileState::WRITABLE,
+            FileState::CLOSED,
+            FileSignals::empty(),
+            cb_queue,
+        );
+
+        Ok(())
+    }
+
+    pub fn readv(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // Linux returns a text description of the queue here, which we don't support
+        log::warn!("Reading from a message queue descriptor is not supported; use mq_receive()");
+        Err(Errno::EINVAL.into())
+    }
+
+    pub fn writev(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        log::warn!("Writing to a message queue descriptor is not supported; use mq_send()");
+        Err(Errno::EINVAL.into())
+    }
+
+    pub fn ioctl(
+        &mut self,
+        request: IoctlRequest,
+        _arg_ptr: ForeignPtr<()>,
+        _memory_manager: &mut MemoryManager,
+    ) -> SyscallResult {
+        log::warn!("We do not yet handle ioctl request {request:?} on message queues");
+        Err(Errno::EINVAL.into())
+    }
+
+    pub fn connect_to_queue(
+        arc: &Arc<AtomicRefCell<Self>>,
+        queue: Arc<AtomicRefCell<MessageQueue>>,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let weak = Arc::downgrade(arc);
+        let file = &mut *arc.borrow_mut();
+
+        file.queue = Some(queue);
+
+        let handle = file.queue().borrow_mut().add_listener(
+            FileState::READABLE | FileState::WRITABLE,
+            FileSignals::READ_BUFFER_GREW,
+            move |queue_state, _changed, signals, cb_queue| {
+                // if the file hasn't been dropped
+                if let Some(file) = weak.upgrade() {
+                    let mut file = file.borrow_mut();
+
+                    // update the file's state to align with the queue's current state
+                    file.align_state_to_queue(queue_state, signals, cb_queue);
+                }
+            },
+        );
+
+        file.queue_event_handle = Some(handle);
+
+        // update the file's initial state to align with the queue's current state
+        let queue_state = file.queue().borrow().state();
+        file.align_state_to_queue(queue_state, FileSignals::empty(), cb_queue);
+    }
+
+    pub fn add_listener(
+        &mut self,
+        monitoring_state: FileState,
+        monitoring_signals: FileSignals,
+        filter: StateListenerFilter,
+        notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue)
+            + Send
+            + Sync
+            + 'static,
+    ) -> StateListenHandle {
+        self.event_source
+            .add_listener(monitoring_state, monitoring_signals, filter, notify_fn)
+    }
+
+    pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>) {
+        self.event_source.add_legacy_listener(ptr);
+    }
+
+    pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener) {
+        self.event_source.remove_legacy_listener(ptr);
+    }
+
+    pub fn state(&self) -> FileState {
+        self.state
+    }
+
+    /// Align the file's state to the queue's state. Unlike pipes, the readable and writable states
+    /// are reported regardless of the file's access mode, matching Linux's `mqueue_poll_file()`.
+    fn align_state_to_queue(
+        &mut self,
+        queue_state: FileState,
+        queue_signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        // if the file is already closed, do nothing
+        if self.state.contains(FileState::CLOSED) {
+            return;
+        }
+
+        self.update_state(
+            FileState::READABLE | FileState::WRITABLE,
+            queue_state,
+            queue_signals,
+            cb_queue,
+        );
+    }
+
+    fn update_state(
+        &mut self,
+        mask: FileState,
+        state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let old_state = self.state;
+
+        // remove the masked flags, then copy the masked flags
+        self.state.remove(mask);
+        self.state.insert(state & mask);
+
+        self.handle_state_change(old_state, signals, cb_queue);
+    }
+
+    fn handle_state_change(
+        &mut self,
+        old_state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let states_changed = self.state ^ old_state;
+
+        // if nothing changed
+        if states_changed.is_empty() && signals.is_empty() {
+            return;
+        }
+
+        self.event_source
+            .notify_listeners(self.state, states_changed, signals, cb_queue);
+    }
+}
+
+/// The per-host namespace of message queue names (what would be `/dev/mqueue` on Linux). Names
+/// are stored without their leading `/`.
+#[derive(Default)]
+pub struct MessageQueueNamespace {
+    queues: HashMap<String, Arc<AtomicRefCell<MessageQueue>>>,
+}
+
+impl MessageQueueNamespace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Arc<AtomicRefCell<MessageQueue>>> {
+        self.queues.get(name)
+    }
+
+    /// Create a new queue. Fails with `EEXIST` if the name is already in use.
+    pub fn create(
+        &mut self,
+        name: &str,
+        limits: MqLimits,
+    ) -> Result<Arc<AtomicRefCell<MessageQueue>>, Errno> {
+        if self.queues.contains_key(name) {
+            return Err(Errno::EEXIST);
+        }
+
+        let queue = Arc::new(AtomicRefCell::new(MessageQueue::new(limits)));
+        self.queues.insert(name.to_string(), Arc::clone(&queue));
+        Ok(queue)
+    }
+
+    /// Remove the name. The queue is destroyed once all descriptors referring to it are closed.
+    pub fn unlink(&mut self, name: &str) -> Result<(), Errno> {
+        self.queues.remove(name).map(|_| ()).ok_or(Errno::ENOENT)
+    }
+
+    pub fn reclaim_memory(&mut self) {
+        crate::utility::shrink_if_sparse(&mut self.queues);
+    }
+}
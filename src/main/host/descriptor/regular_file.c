@@ -7,6 +7,8 @@
 
 #include <errno.h>
 #include <fcntl.h>
+#include <inttypes.h>
+#include <limits.h>
 #include <poll.h>
 #include <stdbool.h>
 #include <stdio.h>
@@ -32,6 +34,11 @@
 
 #define OSFILE_INVALID -1
 
+// older libc headers don't define it
+#ifndef SYS_openat2
+#define SYS_openat2 437
+#endif
+
 const int SHADOW_FLAG_MASK = O_CLOEXEC;
 
 struct _RegularFile {
@@ -63,6 +70,17 @@ struct _RegularFile {
             /* The path of the file when it was opened. */
         } inMemoryFile;
     };
+    /* Limits the size of output files such as a process's stdout. Only applies to write(). */
+    struct {
+        bool enabled;
+        /* The maximum size of the file, or 0 if it's only limited by the total output size. */
+        uint64_t maxSize;
+        OutputLimitAction action;
+        /* The number of bytes written since the file was opened or last rotated. */
+        uint64_t size;
+        /* Set once the file can't be written to anymore; any further output is discarded. */
+        bool full;
+    } outputLimit;
     MAGIC_DECLARE;
 };
 
@@ -274,8 +292,50 @@ int _regularfile_initRoInMemoryFile(RegularFile* file, int flags, mode_t mode, s
     return 0;
 }
 
-int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname, int flags,
-                       mode_t mode, const char* workingDir) {
+static void _regularfile_setOSFile(RegularFile* file, int osfd, char* abspath, int flags,
+                                   mode_t mode) {
+    /* Store the create information, which is used if we mmap the file later. */
+    file->osfile.fd = osfd;
+    file->osfile.absPathAtOpen = abspath;
+    file->osfile.flagsAtOpen = flags;
+    file->osfile.modeAtOpen = mode;
+
+    trace("RegularFile %p opened os-backed file %i at absolute path %s", file,
+          _regularfile_getOSBackedFD(file), file->osfile.absPathAtOpen);
+
+    /* The os-backed file is now ready. */
+    legacyfile_adjustStatus(&file->super, FileState_ACTIVE, TRUE, 0);
+}
+
+/* Reopen an O_PATH file descriptor with new flags. Opening its magic link in /proc reopens the
+ * file that the descriptor refers to, without resolving its path again. */
+static int _regularfile_reopenOSPathFD(int osPathFd, int flags) {
+    if (flags & O_PATH) {
+        return fcntl(osPathFd, F_DUPFD_CLOEXEC, 0);
+    }
+
+    /* The descriptor only refers to a symlink if it was opened with O_NOFOLLOW, in which case
+     * open() would have failed. */
+    struct stat statbuf;
+    if (fstat(osPathFd, &statbuf) < 0) {
+        return -1;
+    }
+    if (S_ISLNK(statbuf.st_mode)) {
+        errno = ELOOP;
+        return -1;
+    }
+
+    char procPath[64];
+    snprintf(procPath, sizeof(procPath), "/proc/self/fd/%i", osPathFd);
+
+    /* The magic link is itself a symlink, so it must be followed. */
+    return open(procPath, flags & ~O_NOFOLLOW);
+}
+
+/* Like regularfile_openat(), but if osPathFd is valid it's an O_PATH descriptor of the file at the
+ * path, which has already been resolved, and it's reopened instead of the path. */
+static int _regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname,
+                               int flags, mode_t mode, const char* workingDir, int osPathFd) {
     MAGIC_ASSERT(file);
     utility_debugAssert(file->type == FILE_TYPE_NOTSET && file->osfile.fd == OSFILE_INVALID);
 
@@ -291,16 +351,29 @@ int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname
      * an absolute path to compare for special files. */
     char* abspath = _regularfile_getAbsolutePath(dir, pathname, workingDir);
 
+    /* Special files that are backed by a different file can't use the already resolved file. */
+    bool isSubstituted = false;
+
     /* Handle special files. */
     if (utility_isRandomPath(abspath)) {
         file->type = FILE_TYPE_RANDOM;
     } else if (!strcmp("/etc/hosts", abspath)) {
         file->type = FILE_TYPE_HOSTS;
-        char* hostspath = dns_getHostsFilePath(worker_getDNS());
+        char* hostspath =
+            dns_getHostsFilePath(worker_getDNS(), worker_getCurrentSimulationTime());
         if (hostspath && abspath) {
             free(abspath);
             abspath = hostspath;
+            isSubstituted = true;
         }
+    } else if (!strcmp("/etc/resolv.conf", abspath) && worker_getResolvConfPath()) {
+        // Point the resolver at the built-in DNS server.
+        file->type = FILE_TYPE_REGULAR;
+        if (abspath) {
+            free(abspath);
+        }
+        abspath = strdup(worker_getResolvConfPath());
+        isSubstituted = true;
     } else if (!strcmp("/etc/localtime", abspath)) {
         file->type = FILE_TYPE_LOCALTIME;
         if (abspath) {
@@ -308,6 +381,7 @@ int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname
         }
         // Shadow time is in UTC.
         abspath = strdup("/usr/share/zoneinfo/Etc/UTC");
+        isSubstituted = true;
     } else if (!strcmp("/sys/devices/system/cpu/possible", abspath) ||
                !strcmp("/sys/devices/system/cpu/online", abspath)) {
         if (abspath) {
@@ -332,7 +406,9 @@ int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname
     // TODO: we should open the os-backed file in non-blocking mode even if a
     // non-block is not requested, and then properly handle the io by, e.g.,
     // epolling on all such files with a shadow support thread.
-    int osfd = open(abspath, flags, mode);
+    int osfd = (osPathFd != OSFILE_INVALID && !isSubstituted)
+                   ? _regularfile_reopenOSPathFD(osPathFd, flags)
+                   : open(abspath, flags, mode);
     int errcode = errno;
 
     if (osfd < 0) {
@@ -345,21 +421,15 @@ int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname
         return -errcode;
     }
 
-    /* Store the create information, which is used if we mmap the file later. */
-    file->osfile.fd = osfd;
-    file->osfile.absPathAtOpen = abspath;
-    file->osfile.flagsAtOpen = flags;
-    file->osfile.modeAtOpen = mode;
-
-    trace("RegularFile %p opened os-backed file %i at absolute path %s", file,
-          _regularfile_getOSBackedFD(file), file->osfile.absPathAtOpen);
-
-    /* The os-backed file is now ready. */
-    legacyfile_adjustStatus(&file->super, FileState_ACTIVE, TRUE, 0);
-
+    _regularfile_setOSFile(file, osfd, abspath, flags, mode);
     return 0;
 }
 
+int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname, int flags,
+                       mode_t mode, const char* workingDir) {
+    return _regularfile_openat(file, dir, pathname, flags, mode, workingDir, OSFILE_INVALID);
+}
+
 int regularfile_open(RegularFile* file, const char* pathname, int flags, mode_t mode,
                      const char* workingDir) {
     return regularfile_openat(file, NULL, pathname, flags, mode, workingDir);
@@ -525,6 +595,95 @@ ssize_t regularfile_preadv2(RegularFile* file, const Host* host, const struct io
 }
 #endif
 
+void regularfile_setOutputLimit(RegularFile* file, uint64_t maxSize, OutputLimitAction action) {
+    MAGIC_ASSERT(file);
+    file->outputLimit.enabled = true;
+    file->outputLimit.maxSize = maxSize;
+    file->outputLimit.action = action;
+}
+
+/* Move the file to "<path>.1" and continue writing to a new empty file at its path. Returns 0 on
+ * success, or a negative errno. */
+static int _regularfile_rotate(RegularFile* file) {
+    const char* path = file->osfile.absPathAtOpen;
+    char* rotatedPath = _regularfile_getConcatStr(path, '.', "1");
+
+    int result = 0;
+    if (rename(path, rotatedPath) < 0) {
+        result = -errno;
+    } else {
+        int flags = (file->osfile.flagsAtOpen & ~SHADOW_FLAG_MASK) | O_CREAT | O_TRUNC | O_CLOEXEC;
+        int newFd = open(path, flags, file->osfile.modeAtOpen);
+        if (newFd < 0) {
+            result = -errno;
+        } else {
+            if (dup3(newFd, file->osfile.fd, O_CLOEXEC) < 0) {
+                result = -errno;
+            }
+            close(newFd);
+        }
+    }
+
+    free(rotatedPath);
+    return result;
+}
+
+/* Returns how many of the `bufSize` bytes being written fit within the file's output limits, and
+ * rotates the file or fails the simulation if the file reached its maximum size. */
+static size_t _regularfile_limitOutput(RegularFile* file, size_t bufSize) {
+    if (!file->outputLimit.enabled || bufSize == 0) {
+        return bufSize;
+    }
+    if (file->outputLimit.full) {
+        return 0;
+    }
+
+    uint64_t maxSize = file->outputLimit.maxSize;
+    if (maxSize != 0 && file->outputLimit.size + bufSize > maxSize) {
+        const char* path = file->osfile.absPathAtOpen;
+
+        switch (file->outputLimit.action) {
+            case OUTPUT_LIMIT_ACTION_ROTATE: {
+                if (file->outputLimit.size == 0) {
+                    // a single write that's larger than the maximum size is truncated
+                    break;
+                }
+                int result = _regularfile_rotate(file);
+                if (result == 0) {
+                    trace("Rotated output file '%s'", path);
+                    file->outputLimit.size = 0;
+                    break;
+                }
+                warning("Could not rotate output file '%s': %s; discarding any further output",
+                        path, strerror(-result));
+                file->outputLimit.full = true;
+                break;
+            }
+            case OUTPUT_LIMIT_ACTION_TRUNCATE: {
+                warning("Output file '%s' reached its maximum size of %" PRIu64
+                        " bytes; discarding any further output",
+                        path, maxSize);
+                file->outputLimit.full = true;
+                break;
+            }
+            case OUTPUT_LIMIT_ACTION_FAIL: {
+                worker_failOutputLimit(path);
+                file->outputLimit.full = true;
+                break;
+            }
+        }
+
+        bufSize = MIN(bufSize, maxSize - file->outputLimit.size);
+    }
+
+    if (bufSize > 0 && !worker_addOutputBytes(bufSize)) {
+        file->outputLimit.full = true;
+        return 0;
+    }
+
+    return bufSize;
+}
+
 ssize_t regularfile_write(RegularFile* file, const void* buf, size_t bufSize) {
     MAGIC_ASSERT(file);
 
@@ -539,10 +698,21 @@ ssize_t regularfile_write(RegularFile* file, const void* buf, size_t bufSize) {
     trace("RegularFile %p will write %zu bytes to os-backed file %i at path '%s'", file, bufSize,
           _regularfile_getOSBackedFD(file), file->osfile.absPathAtOpen);
 
+    size_t writeSize = _regularfile_limitOutput(file, bufSize);
+    if (writeSize == 0 && bufSize > 0) {
+        /* The output is discarded, but report it as written so that the process doesn't retry. */
+        return bufSize;
+    }
+
     /* TODO: this may block the shadow thread until we properly handle
      * os-backed files in non-blocking mode. */
-    ssize_t result = write(_regularfile_getOSBackedFD(file), buf, bufSize);
-    return (result < 0) ? -errno : result;
+    ssize_t result = write(_regularfile_getOSBackedFD(file), buf, writeSize);
+    if (result < 0) {
+        return -errno;
+    }
+
+    file->outputLimit.size += result;
+    return ((size_t)result == writeSize) ? bufSize : result;
 }
 
 ssize_t regularfile_pwrite(RegularFile* file, const void* buf, size_t bufSize, off_t offset) {
@@ -559,10 +729,44 @@ ssize_t regularfile_pwrite(RegularFile* file, const void* buf, size_t bufSize, o
     trace("RegularFile %p will pwrite %zu bytes to os-backed file %i offset %ld at path '%s'", file,
           bufSize, _regularfile_getOSBackedFD(file), offset, file->osfile.absPathAtOpen);
 
+    size_t writeSize = _regularfile_limitOutput(file, bufSize);
+    if (writeSize == 0 && bufSize > 0) {
+        /* The output is discarded, but report it as written so that the process doesn't retry. */
+        return bufSize;
+    }
+
     /* TODO: this may block the shadow thread until we properly handle
      * os-backed files in non-blocking mode. */
-    ssize_t result = pwrite(_regularfile_getOSBackedFD(file), buf, bufSize, offset);
-    return (result < 0) ? -errno : result;
+    ssize_t result = pwrite(_regularfile_getOSBackedFD(file), buf, writeSize, offset);
+    if (result < 0) {
+        return -errno;
+    }
+
+    file->outputLimit.size += result;
+    return ((size_t)result == writeSize) ? bufSize : result;
+}
+
+/* Returns the total length of the vector's items. */
+static size_t _regularfile_iovLen(const struct iovec* iov, int iovcnt) {
+    size_t len = 0;
+    for (int i = 0; i < iovcnt; i++) {
+        len += iov[i].iov_len;
+    }
+    return len;
+}
+
+/* Copies the items of the vector that hold its first `len` bytes to `limited`, which must have
+ * room for `iovcnt` items, and returns the number of items copied. */
+static int _regularfile_limitIov(const struct iovec* iov, int iovcnt, size_t len,
+                                 struct iovec* limited) {
+    int count = 0;
+    for (int i = 0; i < iovcnt && len > 0; i++) {
+        limited[count] = iov[i];
+        limited[count].iov_len = MIN(iov[i].iov_len, len);
+        len -= limited[count].iov_len;
+        count++;
+    }
+    return count;
 }
 
 ssize_t regularfile_pwritev(RegularFile* file, const struct iovec* iov, int iovcnt, off_t offset) {
@@ -579,10 +783,31 @@ ssize_t regularfile_pwritev(RegularFile* file, const struct iovec* iov, int iovc
     trace("RegularFile %p will pwritev %d vector items from os-backed file %i at path '%s'", file,
           iovcnt, _regularfile_getOSBackedFD(file), file->osfile.absPathAtOpen);
 
-    /* TODO: this may block the shadow thread until we properly handle
-     * os-backed files in non-blocking mode. */
-    ssize_t result = pwritev(_regularfile_getOSBackedFD(file), iov, iovcnt, offset);
-    return (result < 0) ? -errno : result;
+    if (!file->outputLimit.enabled) {
+        /* TODO: this may block the shadow thread until we properly handle
+         * os-backed files in non-blocking mode. */
+        ssize_t result = pwritev(_regularfile_getOSBackedFD(file), iov, iovcnt, offset);
+        return (result < 0) ? -errno : result;
+    }
+
+    size_t bufSize = _regularfile_iovLen(iov, iovcnt);
+    size_t writeSize = _regularfile_limitOutput(file, bufSize);
+    if (writeSize == 0 && bufSize > 0) {
+        /* The output is discarded, but report it as written so that the process doesn't retry. */
+        return bufSize;
+    }
+
+    struct iovec* limited = g_new(struct iovec, MAX(iovcnt, 1));
+    int limitedCnt = _regularfile_limitIov(iov, iovcnt, writeSize, limited);
+    ssize_t result = pwritev(_regularfile_getOSBackedFD(file), limited, limitedCnt, offset);
+    int err = errno;
+    g_free(limited);
+    if (result < 0) {
+        return -err;
+    }
+
+    file->outputLimit.size += result;
+    return ((size_t)result == writeSize) ? bufSize : result;
 }
 
 #ifdef SYS_pwritev2
@@ -601,11 +826,31 @@ ssize_t regularfile_pwritev2(RegularFile* file, const struct iovec* iov, int iov
     trace("RegularFile %p will pwritev2 %d vector items from os-backed file %i at path '%s'", file,
           iovcnt, _regularfile_getOSBackedFD(file), file->osfile.absPathAtOpen);
 
-    /* TODO: this may block the shadow thread until we properly handle
-     * os-backed files in non-blocking mode. */
-    ssize_t result =
-        pwritev2(_regularfile_getOSBackedFD(file), iov, iovcnt, offset, flags);
-    return (result < 0) ? -errno : result;
+    if (!file->outputLimit.enabled) {
+        /* TODO: this may block the shadow thread until we properly handle
+         * os-backed files in non-blocking mode. */
+        ssize_t result = pwritev2(_regularfile_getOSBackedFD(file), iov, iovcnt, offset, flags);
+        return (result < 0) ? -errno : result;
+    }
+
+    size_t bufSize = _regularfile_iovLen(iov, iovcnt);
+    size_t writeSize = _regularfile_limitOutput(file, bufSize);
+    if (writeSize == 0 && bufSize > 0) {
+        /* The output is discarded, but report it as written so that the process doesn't retry. */
+        return bufSize;
+    }
+
+    struct iovec* limited = g_new(struct iovec, MAX(iovcnt, 1));
+    int limitedCnt = _regularfile_limitIov(iov, iovcnt, writeSize, limited);
+    ssize_t result = pwritev2(_regularfile_getOSBackedFD(file), limited, limitedCnt, offset, flags);
+    int err = errno;
+    g_free(limited);
+    if (result < 0) {
+        return -err;
+    }
+
+    file->outputLimit.size += result;
+    return ((size_t)result == writeSize) ? bufSize : result;
 }
 #endif
 
@@ -807,6 +1052,99 @@ ssize_t regularfile_readahead(RegularFile* file, off64_t offset, size_t count) {
     return (result < 0) ? -errno : result;
 }
 
+/* The most bytes that we copy at once when we can't use the native `copy_file_range`. The syscall
+ * is allowed to copy fewer bytes than requested. */
+#define COPY_FILE_RANGE_MAX_CHUNK (1 << 16)
+
+ssize_t regularfile_copyFileRange(RegularFile* fileIn, const Host* host, off64_t* offsetIn,
+                                  RegularFile* fileOut, off64_t* offsetOut, size_t len,
+                                  unsigned int flags) {
+    MAGIC_ASSERT(fileIn);
+    MAGIC_ASSERT(fileOut);
+
+    if (flags != 0) {
+        return -EINVAL;
+    }
+
+    /* the random file is a character device, not a regular file */
+    if (fileIn->type == FILE_TYPE_RANDOM || fileOut->type == FILE_TYPE_RANDOM) {
+        return -EINVAL;
+    }
+
+    /* in-memory files are read-only */
+    if (fileOut->type == FILE_TYPE_IN_MEMORY) {
+        return -EBADF;
+    }
+
+    if (!_fd_isValid(_regularfile_getOSBackedFD(fileOut))) {
+        return -EBADF;
+    }
+
+    if (fileIn->type != FILE_TYPE_IN_MEMORY && !_fd_isValid(_regularfile_getOSBackedFD(fileIn))) {
+        return -EBADF;
+    }
+
+    if (fileIn->type != FILE_TYPE_IN_MEMORY && !fileOut->outputLimit.enabled) {
+        trace("RegularFile %p will copy %zu bytes from os-backed file %i to os-backed file %i",
+              fileIn, len, _regularfile_getOSBackedFD(fileIn), _regularfile_getOSBackedFD(fileOut));
+
+        /* TODO: this may block the shadow thread until we properly handle
+         * os-backed files in non-blocking mode. */
+        ssize_t result = copy_file_range(_regularfile_getOSBackedFD(fileIn), offsetIn,
+                                         _regularfile_getOSBackedFD(fileOut), offsetOut, len, 0);
+        return (result < 0) ? -errno : result;
+    }
+
+    /* the native syscall can't read from an in-memory file, and the output limits must only count
+     * the bytes that are actually copied, so we copy through a buffer */
+
+    if ((offsetIn != NULL && *offsetIn < 0) || (offsetOut != NULL && *offsetOut < 0)) {
+        return -EINVAL;
+    }
+
+    if (regularfile_getFlagsAtOpen(fileOut) & O_APPEND) {
+        return -EBADF;
+    }
+
+    size_t chunkLen = MIN(len, COPY_FILE_RANGE_MAX_CHUNK);
+    if (chunkLen == 0) {
+        return 0;
+    }
+
+    trace("RegularFile %p will copy %zu bytes through a buffer to os-backed file %i", fileIn,
+          chunkLen, _regularfile_getOSBackedFD(fileOut));
+
+    char* buf = g_malloc(chunkLen);
+
+    ssize_t numRead = (offsetIn != NULL)
+                          ? regularfile_pread(fileIn, host, buf, chunkLen, *offsetIn)
+                          : regularfile_read(fileIn, host, buf, chunkLen);
+    if (numRead <= 0) {
+        g_free(buf);
+        return numRead;
+    }
+
+    ssize_t numWritten = (offsetOut != NULL)
+                             ? regularfile_pwrite(fileOut, buf, numRead, *offsetOut)
+                             : regularfile_write(fileOut, buf, numRead);
+    g_free(buf);
+
+    /* only the bytes that were written count as copied */
+    ssize_t numCopied = MAX(numWritten, 0);
+    if (offsetIn != NULL) {
+        *offsetIn += numCopied;
+    } else if (fileIn->type == FILE_TYPE_IN_MEMORY) {
+        fileIn->inMemoryFile.cursor -= numRead - numCopied;
+    } else if (numRead > numCopied) {
+        lseek(_regularfile_getOSBackedFD(fileIn), numCopied - numRead, SEEK_CUR);
+    }
+    if (offsetOut != NULL) {
+        *offsetOut += numCopied;
+    }
+
+    return numWritten;
+}
+
 off_t regularfile_lseek(RegularFile* file, off_t offset, int whence) {
     MAGIC_ASSERT(file);
 
@@ -1244,3 +1582,99 @@ int regularfile_statx(RegularFile* dir, const char* pathname, int flags, unsigne
     return (result < 0) ? -errno : result;
 }
 #endif
+
+/* Returns the path of the OS file descriptor, or NULL if it's unknown. */
+static char* _regularfile_getOSFDPath(int osfd) {
+    char procPath[64];
+    snprintf(procPath, sizeof(procPath), "/proc/self/fd/%i", osfd);
+
+    char buf[PATH_MAX];
+    ssize_t len = readlink(procPath, buf, sizeof(buf) - 1);
+    if (len < 0) {
+        return NULL;
+    }
+    buf[len] = '\0';
+
+    return strdup(buf);
+}
+
+int regularfile_openat2(RegularFile* file, RegularFile* dir, const char* pathname, int flags,
+                        mode_t mode, uint64_t resolve, const char* workingDir) {
+    MAGIC_ASSERT(file);
+    utility_debugAssert(file->type == FILE_TYPE_NOTSET && file->osfile.fd == OSFILE_INVALID);
+
+    if (resolve == 0) {
+        return regularfile_openat(file, dir, pathname, flags, mode, workingDir);
+    }
+
+    /* The kernel enforces the resolve flags, so we resolve the path natively from the same
+     * directory that the plugin would have, and then open the resolved file as usual so that
+     * special files are still handled. The resolved file is reopened from its descriptor rather
+     * than its path, which may have changed since it was resolved. */
+    int osDirFd = _regularfile_getOSDirFD(dir);
+    int cwdFd = OSFILE_INVALID;
+
+    if (osDirFd == AT_FDCWD) {
+        cwdFd = open(workingDir, O_PATH | O_DIRECTORY | O_CLOEXEC);
+        if (cwdFd < 0) {
+            return -errno;
+        }
+        osDirFd = cwdFd;
+    }
+
+    struct {
+        uint64_t flags;
+        uint64_t mode;
+        uint64_t resolve;
+    } how = {
+        .flags = O_PATH | O_CLOEXEC | (flags & (O_NOFOLLOW | O_DIRECTORY)),
+        .mode = 0,
+        .resolve = resolve,
+    };
+
+    int result = 0;
+    int osfd = syscall(SYS_openat2, osDirFd, pathname, &how, sizeof(how));
+
+    if (osfd >= 0) {
+        char* resolvedPath = _regularfile_getOSFDPath(osfd);
+
+        if (resolvedPath) {
+            trace("RegularFile %p resolved path '%s' to '%s'", file, pathname, resolvedPath);
+            result = _regularfile_openat(file, NULL, resolvedPath, flags, mode, workingDir, osfd);
+            free(resolvedPath);
+        } else {
+            result = -errno;
+        }
+
+        close(osfd);
+    } else if (errno == ENOENT && (flags & O_CREAT)) {
+        /* The file doesn't exist, so it isn't a special file and we can create it natively. */
+        file->type = FILE_TYPE_REGULAR;
+        file->shadowFlags = flags & SHADOW_FLAG_MASK;
+        int osFlags = (flags & ~SHADOW_FLAG_MASK) | O_CLOEXEC;
+
+        how.flags = osFlags;
+        how.mode = mode;
+        osfd = syscall(SYS_openat2, osDirFd, pathname, &how, sizeof(how));
+
+        char* abspath = osfd >= 0 ? _regularfile_getOSFDPath(osfd) : NULL;
+
+        if (abspath) {
+            _regularfile_setOSFile(file, osfd, abspath, osFlags, mode);
+        } else {
+            result = -errno;
+            if (osfd >= 0) {
+                close(osfd);
+            }
+            file->type = FILE_TYPE_NOTSET;
+        }
+    } else {
+        result = -errno;
+    }
+
+    if (cwdFd != OSFILE_INVALID) {
+        close(cwdFd);
+    }
+
+    return result;
+}
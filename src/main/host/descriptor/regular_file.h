@@ -8,6 +8,7 @@
 
 #include <poll.h>
 #include <stddef.h>
+#include <stdint.h>
 #include <sys/stat.h>
 #include <sys/statfs.h>
 #include <sys/syscall.h>
@@ -16,6 +17,7 @@
 #include <sys/uio.h>
 #include <unistd.h>
 
+#include "main/bindings/c/bindings-opaque.h"
 #include "main/core/definitions.h"
 #include "main/host/syscall/kernel_types.h"
 
@@ -58,6 +60,12 @@ int regularfile_open(RegularFile* file, const char* pathname, int flags, mode_t
                      const char* workingDir);
 int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname, int flags,
                        mode_t mode, const char* workingDir);
+/* Like regularfile_openat(), but the path is resolved using the openat2 `resolve` flags. */
+int regularfile_openat2(RegularFile* file, RegularFile* dir, const char* pathname, int flags,
+                        mode_t mode, uint64_t resolve, const char* workingDir);
+/* Limit the size of the output written to the file with regularfile_write(), where a `maxSize` of
+ * 0 only counts the output towards the simulation's total output size. */
+void regularfile_setOutputLimit(RegularFile* file, uint64_t maxSize, OutputLimitAction action);
 
 // ************************
 // Accessors
@@ -115,6 +123,12 @@ ssize_t regularfile_flistxattr(RegularFile* file, char* list, size_t size);
 int regularfile_fremovexattr(RegularFile* file, const char* name);
 int regularfile_sync_range(RegularFile* file, off64_t offset, off64_t nbytes, unsigned int flags);
 ssize_t regularfile_readahead(RegularFile* file, off64_t offset, size_t count);
+/* Copy up to `len` bytes from `fileIn` to `fileOut`. Each non-NULL offset is used and updated
+ * instead of the file's offset. Returns the number of bytes copied, which may be less than
+ * `len`. */
+ssize_t regularfile_copyFileRange(RegularFile* fileIn, const Host* host, off64_t* offsetIn,
+                                  RegularFile* fileOut, off64_t* offsetOut, size_t len,
+                                  unsigned int flags);
 off_t regularfile_lseek(RegularFile* file, off_t offset, int whence);
 int regularfile_getdents(RegularFile* file, struct linux_dirent* dirp, unsigned int count);
 int regularfile_getdents64(RegularFile* file, struct linux_dirent64* dirp, unsigned int count);
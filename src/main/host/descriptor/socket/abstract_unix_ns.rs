@@ -180,6 +180,13 @@ impl AbstractUnixNamespace {
         Ok(())
     }
 
+    /// Release memory that is no longer needed.
+    pub fn reclaim_memory(&mut self) {
+        for names in self.address_map.values_mut() {
+            crate::utility::shrink_if_sparse(names);
+        }
+    }
+
     /// Adds a listener to the socket which runs the callback `f` when the socket is closed.
     fn on_socket_close(
         ns: Weak<AtomicRefCell<Self>>,
@@ -0,0 +1,1290 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::{Arc, Weak};
+
+use atomic_refcell::AtomicRefCell;
+use bytes::{Bytes, BytesMut};
+use linux_api::errno::Errno;
+use linux_api::ioctls::IoctlRequest;
+use linux_api::socket::Shutdown;
+use nix::sys::socket::{MsgFlags, SockaddrIn};
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+
+use crate::core::worker::Worker;
+use crate::cshadow as c;
+use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
+use crate::host::descriptor::socket::inet::udp::MessageBuffer;
+use crate::host::descriptor::socket::inet::{self, InetSocket};
+use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, ShutdownFlags};
+use crate::host::descriptor::{
+    File, FileMode, FileSignals, FileState, FileStatus, OpenFile, Socket, SyscallResult,
+};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::network::interface::FifoPacketPriority;
+use crate::host::network::namespace::{AssociationHandle, NetworkNamespace};
+use crate::host::syscall::io::{write_partial, IoVec, IoVecReader, IoVecWriter};
+use crate::host::syscall::types::SyscallError;
+use crate::network::packet::{ipv4_header, IcmpHeader, PacketRc, PacketStatus, IPV4_HEADER_LEN};
+use crate::utility::callback_queue::CallbackQueue;
+use crate::utility::sockaddr::SockaddrStorage;
+use crate::utility::{HostTreePointer, ObjectCounter};
+
+/// Maximum size of an ICMP message we are allowed to send out over the network.
+// 65,535 (2^16 - 1) - 20 (ip header)
+const CONFIG_MESSAGE_MAX_SIZE: usize = 65515;
+
+/// The TTL of packets sent from the socket if the application hasn't set `IP_TTL`.
+const DEFAULT_TTL: u8 = 64;
+
+/// The `ICMP_FILTER` socket option of `SOL_RAW`, from `linux/icmp.h`.
+const ICMP_FILTER: libc::c_int = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IcmpSocketType {
+    /// A `SOCK_DGRAM` "ping" socket. It can only send echo requests, and the echo identifier is
+    /// the socket's local port. See "ping" sockets in icmp(7).
+    Ping,
+    /// A `SOCK_RAW` socket for `IPPROTO_ICMP`. Messages are sent as-is, and received messages
+    /// include the IP header. Unlike Linux, the socket only receives echo replies to echo requests
+    /// that it has sent.
+    Raw,
+    /// An internal socket (never given to a managed process) that is associated with a network
+    /// interface and handles ICMP in place of the kernel. It replies to the echo requests the
+    /// interface receives, replies with "port unreachable" errors to UDP datagrams that no socket
+    /// receives, reports "port unreachable" and "host unreachable" errors to UDP sockets, gives
+    /// "time exceeded" errors to the raw sockets that sent the expired echo requests, and updates
+    /// the path MTU when it receives "fragmentation needed" errors.
+    EchoResponder,
+}
+
+pub struct IcmpSocket {
+    socket_type: IcmpSocketType,
+    socket_weak: Weak<AtomicRefCell<Self>>,
+    event_source: StateEventSource,
+    status: FileStatus,
+    state: FileState,
+    shutdown_status: ShutdownFlags,
+    send_buffer: MessageBuffer<MessageSendHeader>,
+    recv_buffer: MessageBuffer<MessageRecvHeader>,
+    peer_addr: Option<Ipv4Addr>,
+    /// For ping sockets, the port is the echo identifier. Raw sockets don't use the port.
+    bound_addr: Option<SocketAddrV4>,
+    /// The association of a bound ping socket.
+    association: Option<AssociationHandle>,
+    /// The associations of a raw socket, keyed by the local address and echo identifier of the
+    /// echo requests it has sent, so that it will receive the replies.
+    echo_associations: HashMap<SocketAddrV4, AssociationHandle>,
+    /// ICMP types that a raw socket should drop (`ICMP_FILTER`).
+    icmp_filter: u32,
+    ttl: u8,
+    /// The receive time of the last packet returned to the managed process during a call to
+    /// `recvmsg()`. Used for `SIOCGSTAMP`.
+    recv_time_of_last_read_packet: Option<EmulatedTime>,
+    // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
+    // this file
+    has_open_file: bool,
+    _counter: ObjectCounter,
+}
+
+impl IcmpSocket {
+    pub fn new(
+        status: FileStatus,
+        socket_type: IcmpSocketType,
+        send_buf_size: usize,
+        recv_buf_size: usize,
+    ) -> Arc<AtomicRefCell<Self>> {
+        let rv = Arc::new_cyclic(|weak| {
+            AtomicRefCell::new(Self {
+                socket_type,
+                socket_weak: weak.clone(),
+                event_source: StateEventSource::new(),
+                status,
+                state: FileState::ACTIVE,
+                shutdown_status: ShutdownFlags::empty(),
+                send_buffer: MessageBuffer::new(send_buf_size),
+                recv_buffer: MessageBuffer::new(recv_buf_size),
+                peer_addr: None,
+                bound_addr: None,
+                association: None,
+                echo_associations: HashMap::new(),
+                icmp_filter: 0,
+                ttl: DEFAULT_TTL,
+                recv_time_of_last_read_packet: None,
+                has_open_file: false,
+                _counter: ObjectCounter::new("IcmpSocket"),
+            })
+        });
+
+        CallbackQueue::queue_and_run(|cb_queue| {
+            rv.borrow_mut()
+                .refresh_readable_writable(FileSignals::empty(), cb_queue)
+        });
+
+        rv
+    }
+
+    pub fn socket_type(&self) -> IcmpSocketType {
+        self.socket_type
+    }
+
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: FileStatus) {
+        self.status = status;
+    }
+
+    pub fn mode(&self) -> FileMode {
+        FileMode::READ | FileMode::WRITE
+    }
+
+    pub fn has_open_file(&self) -> bool {
+        self.has_open_file
+    }
+
+    pub fn supports_sa_restart(&self) -> bool {
+        true
+    }
+
+    pub fn set_has_open_file(&mut self, val: bool) {
+        self.has_open_file = val;
+    }
+
+    pub fn push_in_packet(
+        &mut self,
+        mut packet: PacketRc,
+        cb_queue: &mut CallbackQueue,
+        recv_time: EmulatedTime,
+    ) {
+        packet.add_status(PacketStatus::RcvSocketProcessed);
+
+        let src = *packet.src_address().ip();
+        let dst = *packet.dst_address().ip();
+
+        let Some(icmp) = packet.get_icmp() else {
+            // the network interface only gives us non-ICMP packets if we're a responder and the
+            // packet is a UDP datagram that no socket will receive
+            if self.socket_type == IcmpSocketType::EchoResponder {
+                self.reply_port_unreachable(packet, src, dst, cb_queue);
+            } else {
+                packet.add_status(PacketStatus::RcvSocketDropped);
+            }
+            return;
+        };
+
+        if self.socket_type == IcmpSocketType::EchoResponder {
+            match (icmp.icmp_type, icmp.code) {
+                (IcmpHeader::TYPE_DEST_UNREACHABLE, IcmpHeader::CODE_FRAG_NEEDED) => {
+                    Self::update_path_mtu(packet, icmp)
+                }
+                (IcmpHeader::TYPE_DEST_UNREACHABLE, IcmpHeader::CODE_PORT_UNREACHABLE) => {
+                    Self::report_unreachable(packet, Errno::ECONNREFUSED, cb_queue)
+                }
+                (IcmpHeader::TYPE_DEST_UNREACHABLE, IcmpHeader::CODE_HOST_UNREACHABLE) => {
+                    Self::report_unreachable(packet, Errno::EHOSTUNREACH, cb_queue)
+                }
+                (IcmpHeader::TYPE_TIME_EXCEEDED, _) => {
+                    Self::report_time_exceeded(packet, cb_queue, recv_time)
+                }
+                _ => self.reply_to_echo_request(packet, icmp, src, dst, cb_queue),
+            }
+            return;
+        }
+
+        if self.peer_addr.is_some_and(|peer| peer != src) {
+            // we have a peer, but received a packet from a different source address than that peer
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        if self.socket_type == IcmpSocketType::Raw
+            && icmp.icmp_type < 32
+            && self.icmp_filter & (1 << icmp.icmp_type) != 0
+        {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        // don't bother copying the bytes if we know the push will fail
+        if !self.recv_buffer.has_space() {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        let mut message = BytesMut::zeroed(packet.payload_size());
+        let num_bytes_copied = packet.get_payload(&mut message);
+        assert_eq!(num_bytes_copied, packet.payload_size());
+
+        let header = MessageRecvHeader {
+            src,
+            dst,
+            ttl: packet.ttl(),
+            icmp,
+            recv_time,
+        };
+
+        // push the message to the receive buffer (shouldn't fail since we checked for available
+        // space above)
+        self.recv_buffer
+            .push_message(message.freeze(), header)
+            .unwrap();
+
+        log::trace!("Added a packet to the ICMP socket's recv buffer");
+        packet.add_status(PacketStatus::RcvSocketBuffered);
+
+        self.refresh_readable_writable(FileSignals::READ_BUFFER_GREW, cb_queue);
+    }
+
+    /// Lower the host's path MTU to the destination of the packet that a "fragmentation needed"
+    /// error was sent for.
+    fn update_path_mtu(mut packet: PacketRc, icmp: IcmpHeader) {
+        // the error's payload begins with the IP header of the packet that was dropped
+        let mut quote = [0; IPV4_HEADER_LEN];
+        if packet.get_payload(&mut quote) < IPV4_HEADER_LEN {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        let dst = Ipv4Addr::new(quote[16], quote[17], quote[18], quote[19]);
+        let mtu = icmp.sequence.into();
+
+        Worker::with_active_host(|host| host.network_namespace_borrow().update_path_mtu(dst, mtu))
+            .unwrap();
+
+        packet.add_status(PacketStatus::RcvSocketDelivered);
+    }
+
+    /// Give a "port unreachable" or "host unreachable" error to the UDP socket that sent the
+    /// datagram that the error was sent for, as `error`. Like Linux without `IP_RECVERR`, only
+    /// connected sockets receive the error.
+    fn report_unreachable(mut packet: PacketRc, error: Errno, cb_queue: &mut CallbackQueue) {
+        // the error's payload begins with the IP header and UDP header of the datagram
+        let mut quote = [0; IPV4_HEADER_LEN + 8];
+        if packet.get_payload(&mut quote) < quote.len()
+            || quote[9] != u8::try_from(libc::IPPROTO_UDP).unwrap()
+        {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        let src_ip = Ipv4Addr::new(quote[12], quote[13], quote[14], quote[15]);
+        let dst_ip = Ipv4Addr::new(quote[16], quote[17], quote[18], quote[19]);
+        let src_port = u16::from_be_bytes([quote[20], quote[21]]);
+        let dst_port = u16::from_be_bytes([quote[22], quote[23]]);
+        let dst = SocketAddrV4::new(dst_ip, dst_port);
+
+        let socket = Worker::with_active_host(|host| {
+            let net_ns = host.network_namespace_borrow();
+            let interface = net_ns.interface_borrow(src_ip)?;
+            interface.lookup(c::_ProtocolType_PUDP, src_port, dst)
+        })
+        .unwrap();
+
+        let Some(InetSocket::Udp(socket)) = socket else {
+            // the socket has since been closed
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        };
+
+        socket.borrow_mut().push_icmp_error(dst, error, cb_queue);
+
+        packet.add_status(PacketStatus::RcvSocketDelivered);
+    }
+
+    /// Give a "time exceeded" error to the raw socket that sent the echo request that the error was
+    /// sent for. Raw sockets receive all ICMP messages on Linux, which is how tools like
+    /// `traceroute -I` discover the routers on a path. Like Linux without `IP_RECVERR`, ping
+    /// sockets and UDP sockets don't receive the error.
+    fn report_time_exceeded(
+        mut packet: PacketRc,
+        cb_queue: &mut CallbackQueue,
+        recv_time: EmulatedTime,
+    ) {
+        // the error's payload begins with the IP header and ICMP header of the echo request
+        let mut quote = [0; IPV4_HEADER_LEN + IcmpHeader::LEN];
+        if packet.get_payload(&mut quote) < quote.len()
+            || quote[9] != u8::try_from(libc::IPPROTO_ICMP).unwrap()
+            || quote[IPV4_HEADER_LEN] != IcmpHeader::TYPE_ECHO_REQUEST
+        {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        let src_ip = Ipv4Addr::new(quote[12], quote[13], quote[14], quote[15]);
+        let dst_ip = Ipv4Addr::new(quote[16], quote[17], quote[18], quote[19]);
+        let echo = IcmpHeader::from_bytes(&quote[IPV4_HEADER_LEN..]).unwrap();
+
+        let socket = Worker::with_active_host(|host| {
+            let net_ns = host.network_namespace_borrow();
+            let interface = net_ns.interface_borrow(src_ip)?;
+            let peer = SocketAddrV4::new(dst_ip, 0);
+            interface.lookup(c::_ProtocolType_PICMP, echo.identifier, peer)
+        })
+        .unwrap();
+
+        match socket {
+            Some(InetSocket::Icmp(socket))
+                if socket.borrow().socket_type == IcmpSocketType::Raw =>
+            {
+                socket
+                    .borrow_mut()
+                    .push_in_packet(packet, cb_queue, recv_time);
+            }
+            // the socket has since been closed, or is a ping socket
+            _ => packet.add_status(PacketStatus::RcvSocketDropped),
+        }
+    }
+
+    /// Queue a "port unreachable" error for a received UDP datagram that no socket will receive.
+    /// Unlike Linux, the errors are not rate limited.
+    fn reply_port_unreachable(
+        &mut self,
+        mut packet: PacketRc,
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        if !self.send_buffer.has_space() {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        let packet_priority =
+            Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
+
+        let header = MessageSendHeader {
+            src: dst,
+            dst: src,
+            icmp: IcmpHeader {
+                icmp_type: IcmpHeader::TYPE_DEST_UNREACHABLE,
+                code: IcmpHeader::CODE_PORT_UNREACHABLE,
+                identifier: 0,
+                sequence: 0,
+            },
+            ttl: self.ttl,
+            packet_priority,
+        };
+
+        self.send_buffer
+            .push_message(packet.icmp_error_quote().into(), header)
+            .unwrap();
+
+        packet.add_status(PacketStatus::RcvSocketDelivered);
+
+        self.notify_host_has_packets(dst, cb_queue);
+    }
+
+    /// Queue an echo reply for a received echo request, and ignore all other messages.
+    fn reply_to_echo_request(
+        &mut self,
+        mut packet: PacketRc,
+        icmp: IcmpHeader,
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        if icmp.icmp_type != IcmpHeader::TYPE_ECHO_REQUEST || icmp.code != 0 {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        if !self.send_buffer.has_space() {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        // the reply echoes the request's identifier, sequence number, and data
+        let mut message = BytesMut::zeroed(packet.payload_size());
+        let num_bytes_copied = packet.get_payload(&mut message);
+        assert_eq!(num_bytes_copied, packet.payload_size());
+
+        let packet_priority =
+            Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
+
+        let header = MessageSendHeader {
+            src: dst,
+            dst: src,
+            icmp: IcmpHeader {
+                icmp_type: IcmpHeader::TYPE_ECHO_REPLY,
+                ..icmp
+            },
+            ttl: self.ttl,
+            packet_priority,
+        };
+
+        self.send_buffer
+            .push_message(message.freeze(), header)
+            .unwrap();
+
+        packet.add_status(PacketStatus::RcvSocketDelivered);
+
+        self.notify_host_has_packets(dst, cb_queue);
+    }
+
+    pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc> {
+        // pop the message from the send buffer
+        let Some((message, header)) = self.send_buffer.pop_message() else {
+            log::debug!(
+                "Attempted to remove a message from the ICMP socket's send buffer, but none available"
+            );
+
+            return None;
+        };
+
+        log::trace!("Removed a message from the ICMP socket's send buffer");
+
+        let mut packet = PacketRc::new();
+
+        packet.set_icmp(header.src, header.dst, &header.icmp);
+        packet.set_payload(&message, header.packet_priority);
+        packet.set_ttl(header.ttl);
+        packet.add_status(PacketStatus::SndCreated);
+
+        self.refresh_readable_writable(FileSignals::empty(), cb_queue);
+
+        Some(packet)
+    }
+
+    pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority> {
+        self.send_buffer
+            .peek_message()
+            .map(|(_, header)| header.packet_priority)
+    }
+
+    pub fn has_data_to_send(&self) -> bool {
+        !self.send_buffer.is_empty()
+    }
+
+    /// Tell the host that the network interface for `interface_ip` should send our packets.
+    fn notify_host_has_packets(&self, interface_ip: Ipv4Addr, cb_queue: &mut CallbackQueue) {
+        // The upgrade could fail if this was run during a drop, which would be a bug.
+        let socket = self.socket_weak.upgrade().unwrap();
+
+        cb_queue.add(move |_cb_queue| {
+            Worker::with_active_host(|host| {
+                let socket = InetSocket::Icmp(socket);
+                host.notify_socket_has_packets(interface_ip, &socket);
+            })
+            .unwrap();
+        });
+    }
+
+    pub fn getsockname(&self) -> Result<Option<SockaddrIn>, SyscallError> {
+        let mut addr = self
+            .bound_addr
+            .unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
+        // if we are bound to INADDR_ANY, we should instead return the IP used to communicate with
+        // the connected peer (if we have one)
+        if addr.ip().is_unspecified() {
+            if let Some(peer_addr) = self.peer_addr {
+                addr.set_ip(peer_addr);
+            }
+        }
+
+        Ok(Some(addr.into()))
+    }
+
+    pub fn getpeername(&self) -> Result<Option<SockaddrIn>, SyscallError> {
+        let peer_addr = self.peer_addr.ok_or(Errno::ENOTCONN)?;
+        Ok(Some(SocketAddrV4::new(peer_addr, 0).into()))
+    }
+
+    pub fn address_family(&self) -> linux_api::socket::AddressFamily {
+        linux_api::socket::AddressFamily::AF_INET
+    }
+
+    pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
+        // drop the existing association handles to disassociate the socket
+        self.association = None;
+        self.echo_associations.clear();
+
+        self.update_state(
+            /* mask= */ FileState::all(),
+            FileState::CLOSED,
+            FileSignals::empty(),
+            cb_queue,
+        );
+        Ok(())
+    }
+
+    pub fn bind(
+        socket: &Arc<AtomicRefCell<Self>>,
+        addr: Option<&SockaddrStorage>,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+    ) -> SyscallResult {
+        // if the address pointer was NULL
+        let Some(addr) = addr else {
+            return Err(Errno::EFAULT.into());
+        };
+
+        // if not an inet socket address
+        let Some(addr) = addr.as_inet() else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let addr: SocketAddrV4 = (*addr).into();
+
+        let socket_type = {
+            let socket = socket.borrow();
+
+            // if the socket is already bound
+            if socket.bound_addr.is_some() {
+                return Err(Errno::EINVAL.into());
+            }
+
+            socket.socket_type
+        };
+
+        if socket_type == IcmpSocketType::Raw {
+            // raw sockets don't have ports, so we only need to check that the address is ours
+            if !addr.ip().is_unspecified() && net_ns.interface_borrow(*addr.ip()).is_none() {
+                return Err(Errno::EADDRNOTAVAIL.into());
+            }
+
+            socket.borrow_mut().bound_addr = Some(SocketAddrV4::new(*addr.ip(), 0));
+            return Ok(0.into());
+        }
+
+        // this will allow us to receive packets from any peer
+        let unspecified_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+
+        // associate the socket
+        let (addr, handle) = inet::associate_socket(
+            InetSocket::Icmp(Arc::clone(socket)),
+            addr,
+            unspecified_addr,
+            /* check_generic_peer= */ true,
+            net_ns,
+            rng,
+        )?;
+
+        // update the socket's local address
+        {
+            let mut socket = socket.borrow_mut();
+            socket.bound_addr = Some(addr);
+            socket.association = Some(handle);
+        }
+
+        Ok(0.into())
+    }
+
+    /// Bind a ping socket to an ephemeral identifier if it isn't already bound.
+    fn implicit_bind(
+        socket: &Arc<AtomicRefCell<Self>>,
+        socket_ref: &mut Self,
+        dst: Ipv4Addr,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+    ) -> Result<(), SyscallError> {
+        if socket_ref.bound_addr.is_some() {
+            return Ok(());
+        }
+
+        // raw sockets don't need to be bound
+        if socket_ref.socket_type == IcmpSocketType::Raw {
+            return Ok(());
+        }
+
+        // use the default interface unless the remote peer is on loopback
+        let local_addr = SocketAddrV4::new(local_ip_for_dst(dst, net_ns), 0);
+
+        // this will allow us to receive packets from any peer
+        let unspecified_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+
+        let (local_addr, handle) = inet::associate_socket(
+            InetSocket::Icmp(Arc::clone(socket)),
+            local_addr,
+            unspecified_addr,
+            /* check_generic_peer= */ true,
+            net_ns,
+            rng,
+        )?;
+
+        socket_ref.bound_addr = Some(local_addr);
+        socket_ref.association = Some(handle);
+
+        Ok(())
+    }
+
+    pub fn readv(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // the readv() syscall handler should have called IcmpSocket::recvmsg() instead
+        panic!("Called IcmpSocket::readv() on an ICMP socket");
+    }
+
+    pub fn writev(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // the writev() syscall handler should have called IcmpSocket::sendmsg() instead
+        panic!("Called IcmpSocket::writev() on an ICMP socket");
+    }
+
+    pub fn sendmsg(
+        socket: &Arc<AtomicRefCell<Self>>,
+        args: SendmsgArgs,
+        mem: &mut MemoryManager,
+        net_ns: &NetworkNamespace,
+        mut rng: impl rand::Rng,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        let mut socket_ref = socket.borrow_mut();
+
+        // if the file's writing has been shut down, return EPIPE
+        if socket_ref.shutdown_status.contains(ShutdownFlags::WRITE) {
+            return Err(Errno::EPIPE.into());
+        }
+
+        let Some(mut flags) = MsgFlags::from_bits(args.flags) else {
+            log::debug!("Unrecognized send flags: {:#b}", args.flags);
+            return Err(Errno::EINVAL.into());
+        };
+
+        let dst_addr = match args.addr {
+            Some(addr) => match addr.as_inet() {
+                // an inet socket address (the port is ignored)
+                Some(x) => *SocketAddrV4::from(*x).ip(),
+                // not an inet socket address
+                None => return Err(Errno::EAFNOSUPPORT.into()),
+            },
+            // no destination address provided
+            None => match socket_ref.peer_addr {
+                Some(x) => x,
+                None => return Err(Errno::EDESTADDRREQ.into()),
+            },
+        };
+
+        if socket_ref.status().contains(FileStatus::NONBLOCK) {
+            flags.insert(MsgFlags::MSG_DONTWAIT);
+        }
+
+        let len: libc::size_t = args.iovs.iter().map(|x| x.len).sum();
+
+        if len > CONFIG_MESSAGE_MAX_SIZE {
+            return Err(Errno::EMSGSIZE.into());
+        }
+
+        // the message must contain at least the ICMP header
+        if len < IcmpHeader::LEN {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // read the message before binding so that we don't bind if the message is invalid
+        let mut reader = IoVecReader::new(args.iovs, mem);
+        let mut message = BytesMut::zeroed(len);
+        reader
+            .read_exact(&mut message[..])
+            .map_err(|e| Errno::try_from(e).unwrap())?;
+
+        let mut icmp = IcmpHeader::from_bytes(&message).unwrap();
+
+        if socket_ref.socket_type == IcmpSocketType::Ping
+            && (icmp.icmp_type != IcmpHeader::TYPE_ECHO_REQUEST || icmp.code != 0)
+        {
+            // icmp(7): "ping" sockets can only send echo requests
+            return Err(Errno::EINVAL.into());
+        }
+
+        Self::implicit_bind(socket, &mut socket_ref, dst_addr, net_ns, &mut rng)?;
+
+        let src_addr = match socket_ref.bound_addr {
+            Some(addr) if !addr.ip().is_unspecified() => *addr.ip(),
+            // depending on the destination address, choose either localhost or the public IP
+            // address
+            _ => local_ip_for_dst(dst_addr, net_ns),
+        };
+
+        match socket_ref.socket_type {
+            IcmpSocketType::Ping => {
+                // the kernel sets the identifier to the socket's port
+                icmp.identifier = socket_ref.bound_addr.unwrap().port();
+            }
+            IcmpSocketType::Raw => {
+                if icmp.icmp_type == IcmpHeader::TYPE_ECHO_REQUEST {
+                    socket_ref.associate_echo_identifier(
+                        socket,
+                        SocketAddrV4::new(src_addr, icmp.identifier),
+                        net_ns,
+                        &mut rng,
+                    );
+                }
+            }
+            IcmpSocketType::EchoResponder => unreachable!(),
+        }
+
+        // run in a closure so that an early return doesn't skip checking if we should block
+        let result = (|| {
+            if !socket_ref.send_buffer.has_space() {
+                return Err(Errno::EWOULDBLOCK);
+            }
+
+            // get the priority that we'll assign to the eventual packet
+            let packet_priority =
+                Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
+
+            let header = MessageSendHeader {
+                src: src_addr,
+                dst: dst_addr,
+                icmp,
+                ttl: socket_ref.ttl,
+                packet_priority,
+            };
+
+            // the packet stores the ICMP header separately from the data
+            let data = message.split_off(IcmpHeader::LEN).freeze();
+
+            // push the message to the send buffer (shouldn't fail since we checked for available
+            // space above)
+            socket_ref.send_buffer.push_message(data, header).unwrap();
+
+            socket_ref.notify_host_has_packets(src_addr, cb_queue);
+
+            Ok(len)
+        })();
+
+        socket_ref.refresh_readable_writable(FileSignals::empty(), cb_queue);
+
+        // if the syscall would block and we don't have the MSG_DONTWAIT flag
+        if result == Err(Errno::EWOULDBLOCK) && !flags.contains(MsgFlags::MSG_DONTWAIT) {
+            return Err(SyscallError::new_blocked_on_file(
+                File::Socket(Socket::Inet(InetSocket::Icmp(socket.clone()))),
+                FileState::WRITABLE,
+                socket_ref.supports_sa_restart(),
+            ));
+        }
+
+        Ok(result?.try_into().unwrap())
+    }
+
+    /// Associate a raw socket with an echo identifier so that it receives the replies to its echo
+    /// requests. The association is kept until the socket is closed.
+    fn associate_echo_identifier(
+        &mut self,
+        socket: &Arc<AtomicRefCell<Self>>,
+        local_addr: SocketAddrV4,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+    ) {
+        if local_addr.port() == 0 || self.echo_associations.contains_key(&local_addr) {
+            return;
+        }
+
+        // this will allow us to receive packets from any peer
+        let unspecified_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+
+        match inet::associate_socket(
+            InetSocket::Icmp(Arc::clone(socket)),
+            local_addr,
+            unspecified_addr,
+            /* check_generic_peer= */ true,
+            net_ns,
+            rng,
+        ) {
+            Ok((_, handle)) => {
+                self.echo_associations.insert(local_addr, handle);
+            }
+            Err(e) => {
+                // another socket is already using this identifier, so it will get the replies
+                log::debug!("Could not associate raw ICMP socket with {local_addr}: {e:?}");
+            }
+        }
+    }
+
+    pub fn recvmsg(
+        socket: &Arc<AtomicRefCell<Self>>,
+        args: RecvmsgArgs,
+        mem: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<RecvmsgReturn, SyscallError> {
+        let socket_ref = &mut *socket.borrow_mut();
+
+        let Some(mut flags) = MsgFlags::from_bits(args.flags) else {
+            log::debug!("Unrecognized recv flags: {:#b}", args.flags);
+            return Err(Errno::EINVAL.into());
+        };
+
+        if socket_ref.status().contains(FileStatus::NONBLOCK) {
+            flags.insert(MsgFlags::MSG_DONTWAIT);
+        }
+
+        let len: libc::size_t = args.iovs.iter().map(|x| x.len).sum();
+
+        // run in a closure so that an early return doesn't skip checking if we should block
+        let result = (|| {
+            // a temporary location to store the message and header if we popped them
+            let message_storage;
+            let header_storage;
+
+            let (data, header) = if !flags.contains(MsgFlags::MSG_PEEK) {
+                // pop the message from the receive buffer
+                (message_storage, header_storage) = socket_ref
+                    .recv_buffer
+                    .pop_message()
+                    .ok_or(Errno::EWOULDBLOCK)?;
+                (&message_storage, &header_storage)
+            } else {
+                // peek the message from the receive buffer
+                let (data, header) = socket_ref
+                    .recv_buffer
+                    .peek_message()
+                    .ok_or(Errno::EWOULDBLOCK)?;
+                (data, header)
+            };
+
+            let message = socket_ref.socket_type.format_recv_message(header, data);
+            let src = header.src;
+
+            // truncate the message if it's larger than the user-provided buffers
+            let truncated_message = &message[..std::cmp::min(len, message.len())];
+
+            // write the truncated message to the iovs
+            let mut writer = IoVecWriter::new(args.iovs, mem);
+            writer
+                .write_all(truncated_message)
+                .map_err(|e| Errno::try_from(e).unwrap())?;
+
+            let return_val = if flags.contains(MsgFlags::MSG_TRUNC) {
+                message.len()
+            } else {
+                // the number of bytes written
+                truncated_message.len()
+            };
+
+            let mut return_flags = MsgFlags::empty();
+            return_flags.set(MsgFlags::MSG_TRUNC, truncated_message.len() < message.len());
+
+            // update the cache of the last recv time
+            socket_ref.recv_time_of_last_read_packet = Some(header.recv_time);
+
+            Ok(RecvmsgReturn {
+                return_val: return_val.try_into().unwrap(),
+                addr: Some(SocketAddrV4::new(src, 0).into()),
+                msg_flags: return_flags.bits(),
+                control_len: 0,
+                rights: Vec::new(),
+                creds: None,
+            })
+        })();
+
+        socket_ref.refresh_readable_writable(FileSignals::empty(), cb_queue);
+
+        // if the syscall would block and we don't have the MSG_DONTWAIT flag
+        if result.as_ref().err() == Some(&Errno::EWOULDBLOCK)
+            && !flags.contains(MsgFlags::MSG_DONTWAIT)
+        {
+            // if the syscall would block but the file's reading has been shut down, return EOF
+            if socket_ref.shutdown_status.contains(ShutdownFlags::READ) {
+                return Ok(RecvmsgReturn {
+                    return_val: 0,
+                    addr: None,
+                    msg_flags: 0,
+                    control_len: 0,
+                    rights: Vec::new(),
+                    creds: None,
+                });
+            }
+
+            return Err(SyscallError::new_blocked_on_file(
+                File::Socket(Socket::Inet(InetSocket::Icmp(socket.clone()))),
+                FileState::READABLE,
+                socket_ref.supports_sa_restart(),
+            ));
+        }
+
+        Ok(result?)
+    }
+
+    pub fn ioctl(
+        &mut self,
+        request: IoctlRequest,
+        arg_ptr: ForeignPtr<()>,
+        mem: &mut MemoryManager,
+    ) -> SyscallResult {
+        match request {
+            // equivalent to SIOCINQ
+            IoctlRequest::FIONREAD => {
+                let len = self
+                    .recv_buffer
+                    .peek_message()
+                    .map(|(data, header)| self.socket_type.format_recv_message(header, data).len())
+                    .unwrap_or(0)
+                    .try_into()
+                    .unwrap();
+
+                let arg_ptr = arg_ptr.cast::<libc::c_int>();
+                mem.write(arg_ptr, &len)?;
+
+                Ok(0.into())
+            }
+            // equivalent to SIOCOUTQ
+            IoctlRequest::TIOCOUTQ => {
+                let len = self.send_buffer.len_bytes().try_into().unwrap();
+
+                let arg_ptr = arg_ptr.cast::<libc::c_int>();
+                mem.write(arg_ptr, &len)?;
+
+                Ok(0.into())
+            }
+            IoctlRequest::SIOCGSTAMP => {
+                let Some(last_recv_time) = self.recv_time_of_last_read_packet else {
+                    return Err(Errno::ENOENT.into());
+                };
+
+                let last_recv_time = (last_recv_time - EmulatedTime::UNIX_EPOCH)
+                    .try_into()
+                    .unwrap();
+
+                let arg_ptr = arg_ptr.cast::<libc::timeval>();
+                mem.write(arg_ptr, &last_recv_time)?;
+
+                Ok(0.into())
+            }
+            IoctlRequest::FIONBIO => {
+                panic!("This should have been handled by the ioctl syscall handler");
+            }
+            IoctlRequest::TCGETS
+            | IoctlRequest::TCSETS
+            | IoctlRequest::TCSETSW
+            | IoctlRequest::TCSETSF
+            | IoctlRequest::TCGETA
+            | IoctlRequest::TCSETA
+            | IoctlRequest::TCSETAW
+            | IoctlRequest::TCSETAF
+            | IoctlRequest::TIOCGWINSZ
+            | IoctlRequest::TIOCSWINSZ => {
+                // not a terminal
+                Err(Errno::ENOTTY.into())
+            }
+            request => {
+                warn_once_then_debug!(
+                    "We do not yet handle ioctl request {request:?} on icmp sockets"
+                );
+                Err(Errno::EINVAL.into())
+            }
+        }
+    }
+
+    pub fn listen(
+        _socket: &Arc<AtomicRefCell<Self>>,
+        _backlog: i32,
+        _net_ns: &NetworkNamespace,
+        _rng: impl rand::Rng,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        Err(Errno::EOPNOTSUPP.into())
+    }
+
+    pub fn connect(
+        socket: &Arc<AtomicRefCell<Self>>,
+        peer_addr: &SockaddrStorage,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        // if not an inet socket address
+        let Some(peer_addr) = peer_addr.as_inet() else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let mut peer_addr = *SocketAddrV4::from(*peer_addr).ip();
+
+        if peer_addr.is_unspecified() {
+            peer_addr = Ipv4Addr::LOCALHOST;
+        }
+
+        let mut socket_ref = socket.borrow_mut();
+        Self::implicit_bind(socket, &mut socket_ref, peer_addr, net_ns, rng)?;
+        socket_ref.peer_addr = Some(peer_addr);
+
+        Ok(())
+    }
+
+    pub fn accept(
+        &mut self,
+        _net_ns: &NetworkNamespace,
+        _rng: impl rand::Rng,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<OpenFile, SyscallError> {
+        Err(Errno::EOPNOTSUPP.into())
+    }
+
+    pub fn shutdown(
+        &mut self,
+        how: Shutdown,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        if self.peer_addr.is_none() {
+            return Err(Errno::ENOTCONN.into());
+        }
+
+        if how == Shutdown::SHUT_WR || how == Shutdown::SHUT_RDWR {
+            // writing has been shut down
+            self.shutdown_status.insert(ShutdownFlags::WRITE)
+        }
+
+        if how == Shutdown::SHUT_RD || how == Shutdown::SHUT_RDWR {
+            // reading has been shut down
+            self.shutdown_status.insert(ShutdownFlags::READ)
+        }
+
+        Ok(())
+    }
+
+    pub fn getsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::socklen_t, SyscallError> {
+        let optval: libc::c_int = match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
+                self.send_buffer.soft_limit_bytes().try_into().unwrap()
+            }
+            (libc::SOL_SOCKET, libc::SO_RCVBUF) => {
+                self.recv_buffer.soft_limit_bytes().try_into().unwrap()
+            }
+            (libc::SOL_SOCKET, libc::SO_ERROR) => 0,
+            (libc::SOL_SOCKET, libc::SO_DOMAIN) => libc::AF_INET,
+            (libc::SOL_SOCKET, libc::SO_TYPE) => match self.socket_type {
+                IcmpSocketType::Ping => libc::SOCK_DGRAM,
+                IcmpSocketType::Raw | IcmpSocketType::EchoResponder => libc::SOCK_RAW,
+            },
+            (libc::SOL_SOCKET, libc::SO_PROTOCOL) => libc::IPPROTO_ICMP,
+            (libc::SOL_SOCKET, libc::SO_ACCEPTCONN) => 0,
+            (libc::IPPROTO_IP, libc::IP_TTL) => self.ttl.into(),
+            (libc::SOL_SOCKET, _) | (libc::IPPROTO_IP, _) => {
+                log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
+                return Err(Errno::ENOPROTOOPT.into());
+            }
+            _ => {
+                log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
+                return Err(Errno::EOPNOTSUPP.into());
+            }
+        };
+
+        let optval_ptr = optval_ptr.cast::<libc::c_int>();
+        let bytes_written = write_partial(mem, &optval, optval_ptr, optlen as usize)?;
+
+        Ok(bytes_written as libc::socklen_t)
+    }
+
+    pub fn setsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        mem: &MemoryManager,
+    ) -> Result<(), SyscallError> {
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
+                let val = read_int_optval(optval_ptr, optlen, mem)?;
+                let val: u64 = val.try_into().or(Err(Errno::EINVAL))?;
+
+                // linux kernel doubles this value upon setting, and we use the same limits as UDP
+                // sockets
+                let val = (val * 2).clamp(4096, 268435456);
+
+                self.send_buffer
+                    .set_soft_limit_bytes(val.try_into().unwrap());
+            }
+            (libc::SOL_SOCKET, libc::SO_RCVBUF) => {
+                let val = read_int_optval(optval_ptr, optlen, mem)?;
+                let val: u64 = val.try_into().or(Err(Errno::EINVAL))?;
+
+                // linux kernel doubles this value upon setting, and we use the same limits as UDP
+                // sockets
+                let val = (val * 2).clamp(2048, 268435456);
+
+                self.recv_buffer
+                    .set_soft_limit_bytes(val.try_into().unwrap());
+            }
+            (libc::IPPROTO_IP, libc::IP_TTL) => {
+                let val = read_int_optval(optval_ptr, optlen, mem)?;
+
+                // ip(7): "-1 means use the route default"
+                self.ttl = match val {
+                    -1 => DEFAULT_TTL,
+                    1..=255 => val.try_into().unwrap(),
+                    _ => return Err(Errno::EINVAL.into()),
+                };
+            }
+            (libc::SOL_RAW, ICMP_FILTER) if self.socket_type == IcmpSocketType::Raw => {
+                type OptType = u32;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                self.icmp_filter = mem.read(optval_ptr.cast::<OptType>())?;
+            }
+            _ => {
+                log::debug!("setsockopt called with unsupported level {level} and opt {optname}");
+                return Err(Errno::ENOPROTOOPT.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_listener(
+        &mut self,
+        monitoring_state: FileState,
+        monitoring_signals: FileSignals,
+        filter: StateListenerFilter,
+        notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue)
+            + Send
+            + Sync
+            + 'static,
+    ) -> StateListenHandle {
+        self.event_source
+            .add_listener(monitoring_state, monitoring_signals, filter, notify_fn)
+    }
+
+    pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>) {
+        self.event_source.add_legacy_listener(ptr);
+    }
+
+    pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener) {
+        self.event_source.remove_legacy_listener(ptr);
+    }
+
+    pub fn state(&self) -> FileState {
+        self.state
+    }
+
+    fn refresh_readable_writable(&mut self, signals: FileSignals, cb_queue: &mut CallbackQueue) {
+        let readable = !self.recv_buffer.is_empty();
+        let writable = self.send_buffer.has_space();
+
+        let readable = readable.then_some(FileState::READABLE).unwrap_or_default();
+        let writable = writable.then_some(FileState::WRITABLE).unwrap_or_default();
+
+        self.update_state(
+            /* mask= */ FileState::READABLE | FileState::WRITABLE,
+            readable | writable,
+            signals,
+            cb_queue,
+        );
+    }
+
+    fn update_state(
+        &mut self,
+        mask: FileState,
+        state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let old_state = self.state;
+
+        // remove the masked flags, then copy the masked flags
+        self.state.remove(mask);
+        self.state.insert(state & mask);
+
+        self.handle_state_change(old_state, signals, cb_queue);
+    }
+
+    fn handle_state_change(
+        &mut self,
+        old_state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let states_changed = self.state ^ old_state;
+
+        // if nothing changed
+        if states_changed.is_empty() && signals.is_empty() {
+            return;
+        }
+
+        self.event_source
+            .notify_listeners(self.state, states_changed, signals, cb_queue);
+    }
+}
+
+impl IcmpSocketType {
+    /// The bytes returned to the application for a received message. Ping sockets return the ICMP
+    /// message, and raw sockets also include the IP header.
+    fn format_recv_message(&self, header: &MessageRecvHeader, data: &[u8]) -> Bytes {
+        let icmp = header.icmp.to_bytes(data);
+        let icmp_len = icmp.len() + data.len();
+
+        let mut message = BytesMut::new();
+
+        if *self == Self::Raw {
+            message.extend_from_slice(&ipv4_header(
+                header.src,
+                header.dst,
+                header.ttl,
+                libc::IPPROTO_ICMP as u8,
+                icmp_len,
+            ));
+        }
+
+        message.extend_from_slice(&icmp);
+        message.extend_from_slice(data);
+        message.freeze()
+    }
+}
+
+/// The local address used to send to `dst` from a socket that isn't bound to a specific address.
+fn local_ip_for_dst(dst: Ipv4Addr, net_ns: &NetworkNamespace) -> Ipv4Addr {
+    if dst == Ipv4Addr::LOCALHOST {
+        Ipv4Addr::LOCALHOST
+    } else {
+        net_ns.source_ip(dst)
+    }
+}
+
+fn read_int_optval(
+    optval_ptr: ForeignPtr<()>,
+    optlen: libc::socklen_t,
+    mem: &MemoryManager,
+) -> Result<libc::c_int, SyscallError> {
+    if usize::try_from(optlen).unwrap() < std::mem::size_of::<libc::c_int>() {
+        return Err(Errno::EINVAL.into());
+    }
+
+    Ok(mem.read(optval_ptr.cast::<libc::c_int>())?)
+}
+
+/// Non-payload data for a message in the send buffer.
+#[derive(Debug)]
+struct MessageSendHeader {
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    icmp: IcmpHeader,
+    ttl: u8,
+    /// The priority for the packet that we'll create in the future, given to us by the host.
+    packet_priority: FifoPacketPriority,
+}
+
+/// Non-payload data for a message in the receive buffer.
+#[derive(Debug)]
+struct MessageRecvHeader {
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    /// The TTL of the received packet.
+    ttl: u8,
+    icmp: IcmpHeader,
+    /// The time when the network interface received the message.
+    recv_time: EmulatedTime,
+}
@@ -42,8 +42,16 @@ pub struct LegacyTcpSocket {
 
 impl LegacyTcpSocket {
     pub fn new(status: FileStatus, host: &Host) -> Arc<AtomicRefCell<Self>> {
-        let recv_buf_size = host.params.init_sock_recv_buf_size.try_into().unwrap();
-        let send_buf_size = host.params.init_sock_send_buf_size.try_into().unwrap();
+        let recv_buf_size = match host.params.tcp_rmem {
+            Some((_min, default, _max)) => default,
+            None => host.params.init_sock_recv_buf_size,
+        };
+        let send_buf_size = match host.params.tcp_wmem {
+            Some((_min, default, _max)) => default,
+            None => host.params.init_sock_send_buf_size,
+        };
+        let recv_buf_size = recv_buf_size.try_into().unwrap();
+        let send_buf_size = send_buf_size.try_into().unwrap();
 
         let tcp = unsafe { c::tcp_new(host, recv_buf_size, send_buf_size) };
         let tcp = unsafe { Self::new_from_legacy(tcp) };
@@ -119,6 +127,23 @@ impl LegacyTcpSocket {
         FileMode::READ | FileMode::WRITE
     }
 
+    /// The process (if positive) or process group (if negative) that receives `SIGURG` when
+    /// urgent data arrives, as set by `F_SETOWN`. 0 if there is no owner.
+    pub fn owner(&self) -> libc::pid_t {
+        unsafe { c::tcp_getOwner(self.as_legacy_tcp()) }
+    }
+
+    pub fn set_owner(&mut self, owner: libc::pid_t) {
+        unsafe { c::tcp_setOwner(self.as_legacy_tcp(), owner) };
+    }
+
+    /// The `SO_LINGER` timeout in seconds, or `None` if the option isn't enabled.
+    pub fn linger(&self) -> Option<libc::c_int> {
+        let mut seconds = 0;
+        let enabled = unsafe { c::tcp_getLinger(self.as_legacy_tcp(), &mut seconds) };
+        (enabled != 0).then_some(seconds)
+    }
+
     pub fn has_open_file(&self) -> bool {
         self.has_open_file
     }
@@ -128,6 +153,10 @@ impl LegacyTcpSocket {
         true
     }
 
+    pub fn is_listening(&self) -> bool {
+        (unsafe { c::tcp_isValidListener(self.as_legacy_tcp()) }) != 0
+    }
+
     pub fn set_has_open_file(&mut self, val: bool) {
         self.has_open_file = val;
     }
@@ -334,8 +363,8 @@ impl LegacyTcpSocket {
         socket: &Arc<AtomicRefCell<Self>>,
         args: SendmsgArgs,
         mem: &mut MemoryManager,
-        _net_ns: &NetworkNamespace,
-        _rng: impl rand::Rng,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
         _cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
         let socket_ref = socket.borrow_mut();
@@ -351,7 +380,10 @@ impl LegacyTcpSocket {
             return Err(Errno::EBADF.into());
         }
 
-        let Some(mut flags) = MsgFlags::from_bits(args.flags) else {
+        // nix doesn't know about MSG_FASTOPEN
+        let fastopen = args.flags & libc::MSG_FASTOPEN != 0;
+
+        let Some(mut flags) = MsgFlags::from_bits(args.flags & !libc::MSG_FASTOPEN) else {
             log::warn!("Unrecognized send flags: {:#b}", args.flags);
             return Err(Errno::EINVAL.into());
         };
@@ -360,11 +392,54 @@ impl LegacyTcpSocket {
             flags.insert(MsgFlags::MSG_DONTWAIT);
         }
 
+        // with MSG_FASTOPEN, an unconnected socket connects and sends data in the SYN; the flag is
+        // ignored if a connection was already started
+        if fastopen && unsafe { c::tcp_getConnectionError(tcp) } > 0 {
+            let Some(peer_addr) = args.addr else {
+                return Err(Errno::EINVAL.into());
+            };
+
+            Self::prepare_connect(socket, &socket_ref, &peer_addr, net_ns, rng)?;
+
+            let (base, len) = args
+                .iovs
+                .iter()
+                .find(|x| x.len > 0)
+                .map(|x| (x.base, x.len))
+                .unwrap_or((ForeignPtr::null(), 0));
+
+            // SAFETY: We're passing an immutable pointer to the memory manager. We should not
+            // have any other mutable references to the memory manager at this point.
+            let rv = Worker::with_active_host(|host| unsafe {
+                c::tcp_connectFastOpen(tcp, host, base.cast::<()>(), len.try_into().unwrap(), mem)
+            })
+            .unwrap();
+
+            if rv >= 0 {
+                return Ok(rv.try_into().unwrap());
+            }
+
+            let errno = Errno::try_from(-rv).unwrap();
+
+            // a blocking send without a cookie waits for the handshake below and then sends the
+            // data as usual
+            if errno != Errno::EINPROGRESS || flags.contains(MsgFlags::MSG_DONTWAIT) {
+                return Err(errno.into());
+            }
+        }
+
+        // like linux, the last byte of the message is the urgent byte
+        let urgent_iov = if flags.contains(MsgFlags::MSG_OOB) {
+            args.iovs.iter().rposition(|x| x.len > 0)
+        } else {
+            None
+        };
+
         // run in a closure so that an early return doesn't skip checking if we should block
         let result = (|| {
             let mut bytes_sent = 0;
 
-            for iov in args.iovs {
+            for (i, iov) in args.iovs.iter().enumerate() {
                 let errcode = unsafe { c::tcp_getConnectionError(tcp) };
 
                 log::trace!("Connection error state is currently {errcode}");
@@ -404,6 +479,7 @@ impl LegacyTcpSocket {
                         iov.len.try_into().unwrap(),
                         0,
                         0,
+                        urgent_iov == Some(i),
                         mem,
                     )
                 })
@@ -471,6 +547,11 @@ impl LegacyTcpSocket {
             flags.insert(MsgFlags::MSG_DONTWAIT);
         }
 
+        if flags.contains(MsgFlags::MSG_OOB) {
+            // reading urgent data never blocks
+            return Self::recv_urgent(&socket_ref, args.iovs, flags, mem);
+        }
+
         // run in a closure so that an early return doesn't skip checking if we should block
         let result = (|| {
             let mut bytes_read = 0;
@@ -539,6 +620,8 @@ impl LegacyTcpSocket {
                 addr: None,
                 msg_flags: 0,
                 control_len: 0,
+                rights: Vec::new(),
+                creds: None,
             })
         })();
 
@@ -556,6 +639,42 @@ impl LegacyTcpSocket {
         Ok(result?)
     }
 
+    /// Read the urgent byte with `MSG_OOB`.
+    fn recv_urgent(
+        &self,
+        iovs: &[IoVec],
+        flags: MsgFlags,
+        mem: &mut MemoryManager,
+    ) -> Result<RecvmsgReturn, SyscallError> {
+        let mut byte = 0u8;
+        let rv = unsafe { c::tcp_peekUrgentData(self.as_legacy_tcp(), &mut byte) };
+        if rv < 0 {
+            return Err(Errno::try_from(-rv).unwrap().into());
+        }
+
+        // like linux, the byte is consumed even if there's no room for it
+        let (return_val, msg_flags) = match iovs.iter().find(|x| x.len > 0) {
+            Some(iov) => {
+                mem.write(iov.base, &byte)?;
+                (1, 0)
+            }
+            None => (0, libc::MSG_TRUNC),
+        };
+
+        if !flags.contains(MsgFlags::MSG_PEEK) {
+            unsafe { c::tcp_consumeUrgentData(self.as_legacy_tcp()) };
+        }
+
+        Ok(RecvmsgReturn {
+            return_val,
+            addr: None,
+            msg_flags,
+            control_len: 0,
+            rights: Vec::new(),
+            creds: None,
+        })
+    }
+
     pub fn ioctl(
         &mut self,
         request: IoctlRequest,
@@ -595,6 +714,14 @@ impl LegacyTcpSocket {
 
                 Ok(0.into())
             }
+            IoctlRequest::SIOCATMARK => {
+                let at_mark = unsafe { c::tcp_isAtUrgentMark(self.as_legacy_tcp()) };
+
+                let arg_ptr = arg_ptr.cast::<libc::c_int>();
+                memory_manager.write(arg_ptr, &at_mark)?;
+
+                Ok(0.into())
+            }
             // this isn't supported by tcp
             IoctlRequest::SIOCGSTAMP => Err(Errno::ENOENT.into()),
             IoctlRequest::FIONBIO => {
@@ -697,27 +824,14 @@ impl LegacyTcpSocket {
         Ok(())
     }
 
-    pub fn connect(
+    /// Bind the socket if needed and set its peer before sending a SYN. Returns the peer address.
+    fn prepare_connect(
         socket: &Arc<AtomicRefCell<Self>>,
+        socket_ref: &Self,
         peer_addr: &SockaddrStorage,
         net_ns: &NetworkNamespace,
         rng: impl rand::Rng,
-        _cb_queue: &mut CallbackQueue,
-    ) -> Result<(), SyscallError> {
-        let mut socket_ref = socket.borrow_mut();
-
-        if let Some(tid) = socket_ref.thread_of_blocked_connect {
-            // check if there is already a blocking connect() call on another thread
-            if tid != Worker::active_thread_id().unwrap() {
-                // connect(2) says "Generally,  connection-based protocol sockets may successfully
-                // connect() only once", but the application is attempting to call connect() in two
-                // threads on a blocking socket at the same time. Let's just return an error and
-                // hope no one ever does this.
-                log::warn!("Two threads are attempting to connect() on a blocking socket");
-                return Err(Errno::EBADFD.into());
-            }
-        }
-
+    ) -> Result<SocketAddrV4, SyscallError> {
         let Some(peer_addr) = peer_addr.as_inet() else {
             return Err(Errno::EINVAL.into());
         };
@@ -729,16 +843,20 @@ impl LegacyTcpSocket {
             peer_addr.set_ip(std::net::Ipv4Addr::LOCALHOST);
         }
 
-        let host_default_ip = net_ns.default_ip;
+        // the address of the interface that the routes choose for the peer
+        let local_ip = net_ns.source_ip(*peer_addr.ip());
 
         // NOTE: it would be nice to use `Ipv4Addr::is_loopback` in this code rather than comparing
         // to `Ipv4Addr::LOCALHOST`, but the rest of Shadow probably can't handle other loopback
         // addresses (ex: 127.0.0.2) and it's probably best not to change this behaviour
 
-        // make sure we will be able to route this later
+        // make sure we will be able to route this later (addresses routed to a TUN interface
+        // aren't assigned to a node)
         // TODO: should we just send the SYN and let the connection fail normally?
-        if peer_addr.ip() != &std::net::Ipv4Addr::LOCALHOST {
-            let is_routable = Worker::is_routable(host_default_ip.into(), (*peer_addr.ip()).into());
+        if peer_addr.ip() != &std::net::Ipv4Addr::LOCALHOST
+            && net_ns.tun_route_borrow(*peer_addr.ip()).is_none()
+        {
+            let is_routable = Worker::is_routable(local_ip.into(), (*peer_addr.ip()).into());
 
             if !is_routable {
                 // can't route it - there is no node with this address
@@ -754,12 +872,12 @@ impl LegacyTcpSocket {
         if !is_bound {
             log::trace!("Implicitly binding listener socket");
 
-            // implicit bind: bind to an ephemeral port (use default interface unless the remote
+            // implicit bind: bind to an ephemeral port (use the routed interface unless the remote
             // peer is on loopback)
             let local_addr = if peer_addr.ip() == &std::net::Ipv4Addr::LOCALHOST {
                 SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)
             } else {
-                SocketAddrV4::new(host_default_ip, 0)
+                SocketAddrV4::new(local_ip, 0)
             };
 
             // associate the socket
@@ -793,6 +911,32 @@ impl LegacyTcpSocket {
             )
         };
 
+        Ok(peer_addr)
+    }
+
+    pub fn connect(
+        socket: &Arc<AtomicRefCell<Self>>,
+        peer_addr: &SockaddrStorage,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        let mut socket_ref = socket.borrow_mut();
+
+        if let Some(tid) = socket_ref.thread_of_blocked_connect {
+            // check if there is already a blocking connect() call on another thread
+            if tid != Worker::active_thread_id().unwrap() {
+                // connect(2) says "Generally,  connection-based protocol sockets may successfully
+                // connect() only once", but the application is attempting to call connect() in two
+                // threads on a blocking socket at the same time. Let's just return an error and
+                // hope no one ever does this.
+                log::warn!("Two threads are attempting to connect() on a blocking socket");
+                return Err(Errno::EBADFD.into());
+            }
+        }
+
+        let peer_addr = Self::prepare_connect(socket, &socket_ref, peer_addr, net_ns, rng)?;
+
         // now we are ready to connect
         let errcode = Worker::with_active_host(|host| unsafe {
             c::legacysocket_connectToPeer(
@@ -1024,6 +1168,16 @@ impl LegacyTcpSocket {
                 // the len value returned by linux seems to be independent from the actual string length
                 Ok(std::cmp::min(optlen as usize, CONG_NAME_MAX) as libc::socklen_t)
             }
+            (libc::SOL_TCP, libc::TCP_FASTOPEN) => {
+                let queue_len: libc::c_int =
+                    unsafe { c::tcp_getFastOpenQueueLength(self.as_legacy_tcp()) };
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &queue_len, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
                 let sndbuf_size: libc::c_int =
                     unsafe { c::legacysocket_getOutputBufferSize(self.as_legacy_socket()) }
@@ -1048,6 +1202,40 @@ impl LegacyTcpSocket {
 
                 Ok(bytes_written as libc::socklen_t)
             }
+            (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
+                let keepalive: libc::c_int = unsafe { c::tcp_getKeepAlive(self.as_legacy_tcp()) };
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &keepalive, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_OOBINLINE) => {
+                let oobinline: libc::c_int =
+                    unsafe { c::tcp_getUrgentInline(self.as_legacy_tcp()) };
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &oobinline, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_LINGER) => {
+                let mut seconds = 0;
+                let enabled = unsafe { c::tcp_getLinger(self.as_legacy_tcp(), &mut seconds) };
+
+                let linger = libc::linger {
+                    l_onoff: (enabled != 0).into(),
+                    l_linger: seconds,
+                };
+
+                let optval_ptr = optval_ptr.cast::<libc::linger>();
+                let bytes_written =
+                    write_partial(memory_manager, &linger, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, libc::SO_ERROR) => {
                 // return error for failed connect() attempts
                 let conn_err = unsafe { c::tcp_getConnectionError(self.as_legacy_tcp()) };
@@ -1162,14 +1350,38 @@ impl LegacyTcpSocket {
                     .map(|x| &name[..x])
                     .unwrap_or(name);
 
-                let reno = unsafe { CStr::from_ptr(c::TCP_CONG_RENO_NAME) }.to_bytes();
+                let found = unsafe {
+                    c::tcpcong_set(
+                        self.as_legacy_tcp(),
+                        name.as_ptr() as *const libc::c_char,
+                        name.len(),
+                    )
+                };
 
-                if name != reno {
-                    log::warn!("Shadow sockets only support '{reno:?}' for TCP_CONGESTION");
+                if !found {
+                    log::warn!(
+                        "Shadow sockets don't support the congestion control algorithm {:?}",
+                        String::from_utf8_lossy(name),
+                    );
                     return Err(Errno::ENOENT.into());
                 }
+            }
+            (libc::SOL_TCP, libc::TCP_FASTOPEN) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let queue_len = memory_manager.read(optval_ptr)?;
+
+                let errcode =
+                    unsafe { c::tcp_setFastOpenQueueLength(self.as_legacy_tcp(), queue_len) };
 
-                // shadow doesn't support other congestion types, so do nothing
+                if errcode < 0 {
+                    return Err(Errno::try_from(-errcode).unwrap().into());
+                }
             }
             (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
                 type OptType = libc::c_int;
@@ -1238,9 +1450,48 @@ impl LegacyTcpSocket {
                 log::trace!("setsockopt SO_REUSEPORT not yet implemented");
             }
             (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
-                // TODO: implement this, libevent uses it in
-                // evconnlistener_new_bind()
-                log::trace!("setsockopt SO_KEEPALIVE not yet implemented");
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = memory_manager.read(optval_ptr)? != 0;
+
+                // shadow doesn't send keepalive probes, but we store the option so that it can be
+                // read back and inherited by accepted sockets
+                unsafe { c::tcp_setKeepAlive(self.as_legacy_tcp(), enable.into()) };
+            }
+            (libc::SOL_SOCKET, libc::SO_OOBINLINE) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = memory_manager.read(optval_ptr)? != 0;
+
+                unsafe { c::tcp_setUrgentInline(self.as_legacy_tcp(), enable.into()) };
+            }
+            (libc::SOL_SOCKET, libc::SO_LINGER) => {
+                type OptType = libc::linger;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let linger = memory_manager.read(optval_ptr)?;
+
+                unsafe {
+                    c::tcp_setLinger(
+                        self.as_legacy_tcp(),
+                        (linger.l_onoff != 0).into(),
+                        linger.l_linger,
+                    )
+                };
             }
             (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
                 // TODO: implement this, pkg.go.dev/net uses it
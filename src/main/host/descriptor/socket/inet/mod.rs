@@ -24,10 +24,12 @@ use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
 use crate::utility::HostTreePointer;
 
+use self::icmp::IcmpSocket;
 use self::legacy_tcp::LegacyTcpSocket;
 use self::tcp::TcpSocket;
 use self::udp::UdpSocket;
 
+pub mod icmp;
 pub mod legacy_tcp;
 pub mod tcp;
 pub mod udp;
@@ -37,6 +39,7 @@ pub enum InetSocket {
     LegacyTcp(Arc<AtomicRefCell<LegacyTcpSocket>>),
     Tcp(Arc<AtomicRefCell<TcpSocket>>),
     Udp(Arc<AtomicRefCell<UdpSocket>>),
+    Icmp(Arc<AtomicRefCell<IcmpSocket>>),
 }
 
 impl InetSocket {
@@ -45,6 +48,7 @@ impl InetSocket {
             Self::LegacyTcp(ref f) => InetSocketRef::LegacyTcp(f.borrow()),
             Self::Tcp(ref f) => InetSocketRef::Tcp(f.borrow()),
             Self::Udp(ref f) => InetSocketRef::Udp(f.borrow()),
+            Self::Icmp(ref f) => InetSocketRef::Icmp(f.borrow()),
         }
     }
 
@@ -53,6 +57,7 @@ impl InetSocket {
             Self::LegacyTcp(ref f) => InetSocketRef::LegacyTcp(f.try_borrow()?),
             Self::Tcp(ref f) => InetSocketRef::Tcp(f.try_borrow()?),
             Self::Udp(ref f) => InetSocketRef::Udp(f.try_borrow()?),
+            Self::Icmp(ref f) => InetSocketRef::Icmp(f.try_borrow()?),
         })
     }
 
@@ -61,6 +66,7 @@ impl InetSocket {
             Self::LegacyTcp(ref f) => InetSocketRefMut::LegacyTcp(f.borrow_mut()),
             Self::Tcp(ref f) => InetSocketRefMut::Tcp(f.borrow_mut()),
             Self::Udp(ref f) => InetSocketRefMut::Udp(f.borrow_mut()),
+            Self::Icmp(ref f) => InetSocketRefMut::Icmp(f.borrow_mut()),
         }
     }
 
@@ -69,6 +75,7 @@ impl InetSocket {
             Self::LegacyTcp(ref f) => InetSocketRefMut::LegacyTcp(f.try_borrow_mut()?),
             Self::Tcp(ref f) => InetSocketRefMut::Tcp(f.try_borrow_mut()?),
             Self::Udp(ref f) => InetSocketRefMut::Udp(f.try_borrow_mut()?),
+            Self::Icmp(ref f) => InetSocketRefMut::Icmp(f.try_borrow_mut()?),
         })
     }
 
@@ -77,6 +84,7 @@ impl InetSocket {
             Self::LegacyTcp(x) => InetSocketWeak::LegacyTcp(Arc::downgrade(x)),
             Self::Tcp(x) => InetSocketWeak::Tcp(Arc::downgrade(x)),
             Self::Udp(x) => InetSocketWeak::Udp(Arc::downgrade(x)),
+            Self::Icmp(x) => InetSocketWeak::Icmp(Arc::downgrade(x)),
         }
     }
 
@@ -90,6 +98,7 @@ impl InetSocket {
             Self::LegacyTcp(f) => f.borrow().canonical_handle(),
             Self::Tcp(f) => Arc::as_ptr(f) as usize,
             Self::Udp(f) => Arc::as_ptr(f) as usize,
+            Self::Icmp(f) => Arc::as_ptr(f) as usize,
         }
     }
 
@@ -103,6 +112,7 @@ impl InetSocket {
             Self::LegacyTcp(socket) => LegacyTcpSocket::bind(socket, addr, net_ns, rng),
             Self::Tcp(socket) => TcpSocket::bind(socket, addr, net_ns, rng),
             Self::Udp(socket) => UdpSocket::bind(socket, addr, net_ns, rng),
+            Self::Icmp(socket) => IcmpSocket::bind(socket, addr, net_ns, rng),
         }
     }
 
@@ -119,6 +129,7 @@ impl InetSocket {
             }
             Self::Tcp(socket) => TcpSocket::listen(socket, backlog, net_ns, rng, cb_queue),
             Self::Udp(socket) => UdpSocket::listen(socket, backlog, net_ns, rng, cb_queue),
+            Self::Icmp(socket) => IcmpSocket::listen(socket, backlog, net_ns, rng, cb_queue),
         }
     }
 
@@ -135,6 +146,7 @@ impl InetSocket {
             }
             Self::Tcp(socket) => TcpSocket::connect(socket, addr, net_ns, rng, cb_queue),
             Self::Udp(socket) => UdpSocket::connect(socket, addr, net_ns, rng, cb_queue),
+            Self::Icmp(socket) => IcmpSocket::connect(socket, addr, net_ns, rng, cb_queue),
         }
     }
 
@@ -156,6 +168,9 @@ impl InetSocket {
             Self::Udp(socket) => {
                 UdpSocket::sendmsg(socket, args, memory_manager, net_ns, rng, cb_queue)
             }
+            Self::Icmp(socket) => {
+                IcmpSocket::sendmsg(socket, args, memory_manager, net_ns, rng, cb_queue)
+            }
         }
     }
 
@@ -171,6 +186,7 @@ impl InetSocket {
             }
             Self::Tcp(socket) => TcpSocket::recvmsg(socket, args, memory_manager, cb_queue),
             Self::Udp(socket) => UdpSocket::recvmsg(socket, args, memory_manager, cb_queue),
+            Self::Icmp(socket) => IcmpSocket::recvmsg(socket, args, memory_manager, cb_queue),
         }
     }
 }
@@ -181,6 +197,7 @@ impl std::fmt::Debug for InetSocket {
             Self::LegacyTcp(_) => write!(f, "LegacyTcp")?,
             Self::Tcp(_) => write!(f, "Tcp")?,
             Self::Udp(_) => write!(f, "Udp")?,
+            Self::Icmp(_) => write!(f, "Icmp")?,
         }
 
         if let Ok(file) = self.try_borrow() {
@@ -215,6 +232,7 @@ impl PartialEq for InetSocket {
             (Self::LegacyTcp(self_), Self::LegacyTcp(other)) => Arc::ptr_eq(self_, other),
             (Self::Tcp(self_), Self::Tcp(other)) => Arc::ptr_eq(self_, other),
             (Self::Udp(self_), Self::Udp(other)) => Arc::ptr_eq(self_, other),
+            (Self::Icmp(self_), Self::Icmp(other)) => Arc::ptr_eq(self_, other),
             _ => false,
         }
     }
@@ -238,6 +256,7 @@ impl std::hash::Hash for InetSocket {
             Self::LegacyTcp(x) => Arc::as_ptr(x).cast::<libc::c_void>(),
             Self::Tcp(x) => Arc::as_ptr(x).cast(),
             Self::Udp(x) => Arc::as_ptr(x).cast(),
+            Self::Icmp(x) => Arc::as_ptr(x).cast(),
         }
         .hash(state);
     }
@@ -247,29 +266,31 @@ pub enum InetSocketRef<'a> {
     LegacyTcp(atomic_refcell::AtomicRef<'a, LegacyTcpSocket>),
     Tcp(atomic_refcell::AtomicRef<'a, TcpSocket>),
     Udp(atomic_refcell::AtomicRef<'a, UdpSocket>),
+    Icmp(atomic_refcell::AtomicRef<'a, IcmpSocket>),
 }
 
 pub enum InetSocketRefMut<'a> {
     LegacyTcp(atomic_refcell::AtomicRefMut<'a, LegacyTcpSocket>),
     Tcp(atomic_refcell::AtomicRefMut<'a, TcpSocket>),
     Udp(atomic_refcell::AtomicRefMut<'a, UdpSocket>),
+    Icmp(atomic_refcell::AtomicRefMut<'a, IcmpSocket>),
 }
 
 // file functions
 impl InetSocketRef<'_> {
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn state(&self) -> FileState
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn mode(&self) -> FileMode
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn status(&self) -> FileStatus
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn has_open_file(&self) -> bool
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn supports_sa_restart(&self) -> bool
     );
 }
@@ -281,6 +302,7 @@ impl InetSocketRef<'_> {
             Self::LegacyTcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
             Self::Tcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
             Self::Udp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
+            Self::Icmp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
         }
     }
 
@@ -289,54 +311,64 @@ impl InetSocketRef<'_> {
             Self::LegacyTcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
             Self::Tcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
             Self::Udp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
+            Self::Icmp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
         }
     }
 
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn address_family(&self) -> linux_api::socket::AddressFamily
     );
+
+    /// Returns true if the socket is a TCP socket that is listening for connections.
+    pub fn is_listening(&self) -> bool {
+        match self {
+            Self::LegacyTcp(socket) => socket.is_listening(),
+            Self::Tcp(socket) => socket.is_listening(),
+            Self::Udp(_) | Self::Icmp(_) => false,
+        }
+    }
 }
 
 // inet socket-specific functions
 impl InetSocketRef<'_> {
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority>
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn has_data_to_send(&self) -> bool
     );
 }
 
 // file functions
 impl InetSocketRefMut<'_> {
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn state(&self) -> FileState
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn mode(&self) -> FileMode
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn status(&self) -> FileStatus
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn has_open_file(&self) -> bool
     );
-    enum_passthrough!(self, (val), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (val), LegacyTcp, Tcp, Udp, Icmp;
         pub fn set_has_open_file(&mut self, val: bool)
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn supports_sa_restart(&self) -> bool
     );
-    enum_passthrough!(self, (cb_queue), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (cb_queue), LegacyTcp, Tcp, Udp, Icmp;
         pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError>
     );
-    enum_passthrough!(self, (status), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (status), LegacyTcp, Tcp, Udp, Icmp;
         pub fn set_status(&mut self, status: FileStatus)
     );
-    enum_passthrough!(self, (request, arg_ptr, memory_manager), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (request, arg_ptr, memory_manager), LegacyTcp, Tcp, Udp, Icmp;
         pub fn ioctl(&mut self, request: IoctlRequest, arg_ptr: ForeignPtr<()>, memory_manager: &mut MemoryManager) -> SyscallResult
     );
-    enum_passthrough!(self, (monitoring_state, monitoring_signals, filter, notify_fn), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (monitoring_state, monitoring_signals, filter, notify_fn), LegacyTcp, Tcp, Udp, Icmp;
         pub fn add_listener(
             &mut self,
             monitoring_state: FileState,
@@ -345,17 +377,17 @@ impl InetSocketRefMut<'_> {
             notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue) + Send + Sync + 'static,
         ) -> StateListenHandle
     );
-    enum_passthrough!(self, (ptr), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (ptr), LegacyTcp, Tcp, Udp, Icmp;
         pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>)
     );
-    enum_passthrough!(self, (ptr), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (ptr), LegacyTcp, Tcp, Udp, Icmp;
         pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener)
     );
-    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Tcp, Udp, Icmp;
         pub fn readv(&mut self, iovs: &[IoVec], offset: Option<libc::off_t>, flags: libc::c_int,
                      mem: &mut MemoryManager, cb_queue: &mut CallbackQueue) -> Result<libc::ssize_t, SyscallError>
     );
-    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Tcp, Udp, Icmp;
         pub fn writev(&mut self, iovs: &[IoVec], offset: Option<libc::off_t>, flags: libc::c_int,
                       mem: &mut MemoryManager, cb_queue: &mut CallbackQueue) -> Result<libc::ssize_t, SyscallError>
     );
@@ -368,6 +400,7 @@ impl InetSocketRefMut<'_> {
             Self::LegacyTcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
             Self::Tcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
             Self::Udp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
+            Self::Icmp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
         }
     }
 
@@ -376,20 +409,21 @@ impl InetSocketRefMut<'_> {
             Self::LegacyTcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
             Self::Tcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
             Self::Udp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
+            Self::Icmp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
         }
     }
 
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn address_family(&self) -> linux_api::socket::AddressFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager, cb_queue), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager, cb_queue), LegacyTcp, Tcp, Udp, Icmp;
         pub fn getsockopt(&mut self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
                           optlen: libc::socklen_t, memory_manager: &mut MemoryManager, cb_queue: &mut CallbackQueue)
         -> Result<libc::socklen_t, SyscallError>
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), LegacyTcp, Tcp, Udp, Icmp;
         pub fn setsockopt(&mut self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
                           optlen: libc::socklen_t, memory_manager: &MemoryManager)
         -> Result<(), SyscallError>
@@ -405,26 +439,27 @@ impl InetSocketRefMut<'_> {
             Self::LegacyTcp(socket) => socket.accept(net_ns, rng, cb_queue),
             Self::Tcp(socket) => socket.accept(net_ns, rng, cb_queue),
             Self::Udp(socket) => socket.accept(net_ns, rng, cb_queue),
+            Self::Icmp(socket) => socket.accept(net_ns, rng, cb_queue),
         }
     }
 
-    enum_passthrough!(self, (how, cb_queue), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (how, cb_queue), LegacyTcp, Tcp, Udp, Icmp;
         pub fn shutdown(&mut self, how: Shutdown, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError>
     );
 }
 
 // inet socket-specific functions
 impl InetSocketRefMut<'_> {
-    enum_passthrough!(self, (packet, cb_queue, recv_time), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (packet, cb_queue, recv_time), LegacyTcp, Tcp, Udp, Icmp;
         pub fn push_in_packet(&mut self, packet: PacketRc, cb_queue: &mut CallbackQueue, recv_time: EmulatedTime)
     );
-    enum_passthrough!(self, (cb_queue), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (cb_queue), LegacyTcp, Tcp, Udp, Icmp;
         pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc>
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority>
     );
-    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp, Icmp;
         pub fn has_data_to_send(&self) -> bool
     );
 }
@@ -435,6 +470,7 @@ impl std::fmt::Debug for InetSocketRef<'_> {
             Self::LegacyTcp(_) => write!(f, "LegacyTcp")?,
             Self::Tcp(_) => write!(f, "Tcp")?,
             Self::Udp(_) => write!(f, "Udp")?,
+            Self::Icmp(_) => write!(f, "Icmp")?,
         }
 
         write!(
@@ -452,6 +488,7 @@ impl std::fmt::Debug for InetSocketRefMut<'_> {
             Self::LegacyTcp(_) => write!(f, "LegacyTcp")?,
             Self::Tcp(_) => write!(f, "Tcp")?,
             Self::Udp(_) => write!(f, "Udp")?,
+            Self::Icmp(_) => write!(f, "Icmp")?,
         }
 
         write!(
@@ -468,6 +505,7 @@ pub enum InetSocketWeak {
     LegacyTcp(Weak<AtomicRefCell<LegacyTcpSocket>>),
     Tcp(Weak<AtomicRefCell<TcpSocket>>),
     Udp(Weak<AtomicRefCell<UdpSocket>>),
+    Icmp(Weak<AtomicRefCell<IcmpSocket>>),
 }
 
 impl InetSocketWeak {
@@ -476,6 +514,7 @@ impl InetSocketWeak {
             Self::LegacyTcp(x) => x.upgrade().map(InetSocket::LegacyTcp),
             Self::Tcp(x) => x.upgrade().map(InetSocket::Tcp),
             Self::Udp(x) => x.upgrade().map(InetSocket::Udp),
+            Self::Icmp(x) => x.upgrade().map(InetSocket::Icmp),
         }
     }
 }
@@ -509,6 +548,7 @@ fn associate_socket(
         InetSocket::LegacyTcp(_) => c::_ProtocolType_PTCP,
         InetSocket::Tcp(_) => c::_ProtocolType_PTCP,
         InetSocket::Udp(_) => c::_ProtocolType_PUDP,
+        InetSocket::Icmp(_) => c::_ProtocolType_PICMP,
     };
 
     // get a free ephemeral port if they didn't specify one
@@ -40,6 +40,8 @@ pub struct TcpSocket {
     association: Option<AssociationHandle>,
     connect_result_is_pending: bool,
     shutdown_status: Option<Shutdown>,
+    /// The `SO_KEEPALIVE` option. We store it but don't send keepalive probes.
+    keepalive: bool,
     // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
     // this file
     has_open_file: bool,
@@ -67,6 +69,7 @@ impl TcpSocket {
                 association: None,
                 connect_result_is_pending: false,
                 shutdown_status: None,
+                keepalive: false,
                 has_open_file: false,
                 _counter: ObjectCounter::new("TcpSocket"),
             })
@@ -101,6 +104,10 @@ impl TcpSocket {
         true
     }
 
+    pub fn is_listening(&self) -> bool {
+        self.tcp_state.poll().contains(tcp::PollState::LISTENING)
+    }
+
     pub fn set_has_open_file(&mut self, val: bool) {
         self.has_open_file = val;
     }
@@ -518,6 +525,8 @@ impl TcpSocket {
                 addr: None,
                 msg_flags: MsgFlags::empty().bits(),
                 control_len: 0,
+                rights: Vec::new(),
+                creds: None,
             })
         })();
 
@@ -662,7 +671,7 @@ impl TcpSocket {
                 if peer_addr.ip() == &std::net::Ipv4Addr::LOCALHOST {
                     local_addr.set_ip(Ipv4Addr::LOCALHOST)
                 } else {
-                    local_addr.set_ip(net_ns.default_ip)
+                    local_addr.set_ip(net_ns.source_ip(*peer_addr.ip()))
                 };
             }
 
@@ -677,7 +686,7 @@ impl TcpSocket {
                 let local_addr = if peer_addr.ip() == &std::net::Ipv4Addr::LOCALHOST {
                     Ipv4Addr::LOCALHOST
                 } else {
-                    net_ns.default_ip
+                    net_ns.source_ip(*peer_addr.ip())
                 };
 
                 // add a wildcard port number
@@ -778,6 +787,7 @@ impl TcpSocket {
                 tcp_state: accepted_state,
                 socket_weak: weak.clone(),
                 event_source: StateEventSource::new(),
+                // like linux, the file status isn't inherited from the listening socket
                 status: FileStatus::empty(),
                 // the readable/writable file state shouldn't matter here since we run
                 // `with_tcp_state` below to update it, but we need ACTIVE set so that epoll works
@@ -785,6 +795,8 @@ impl TcpSocket {
                 association: None,
                 connect_result_is_pending: false,
                 shutdown_status: None,
+                // like linux, socket options are inherited from the listening socket
+                keepalive: self.keepalive,
                 has_open_file: false,
                 _counter: ObjectCounter::new("TcpSocket"),
             })
@@ -916,6 +928,14 @@ impl TcpSocket {
 
                 Ok(bytes_written as libc::socklen_t)
             }
+            (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
+                let keepalive = self.keepalive as libc::c_int;
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &keepalive, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, libc::SO_ACCEPTCONN) => {
                 let is_listener = self.tcp_state.poll().contains(tcp::PollState::LISTENING);
                 let is_listener = is_listener as libc::c_int;
@@ -936,9 +956,9 @@ impl TcpSocket {
         &mut self,
         level: libc::c_int,
         optname: libc::c_int,
-        _optval_ptr: ForeignPtr<()>,
-        _optlen: libc::socklen_t,
-        _mem: &MemoryManager,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        mem: &MemoryManager,
     ) -> Result<(), SyscallError> {
         match (level, optname) {
             (libc::SOL_SOCKET, libc::SO_REUSEADDR) => {
@@ -950,8 +970,14 @@ impl TcpSocket {
                 log::trace!("setsockopt SO_REUSEPORT not yet implemented");
             }
             (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
-                // TODO: implement this, libevent uses it in evconnlistener_new_bind()
-                log::trace!("setsockopt SO_KEEPALIVE not yet implemented");
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                self.keepalive = mem.read(optval_ptr)? != 0;
             }
             (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
                 // TODO: implement this, pkg.go.dev/net uses it
@@ -6,6 +6,7 @@ use std::sync::Arc;
 use atomic_refcell::AtomicRefCell;
 use bytes::{Bytes, BytesMut};
 use linux_api::errno::Errno;
+use linux_api::inet::{IpPmtuDisc, IP_MTU, IP_MTU_DISCOVER};
 use linux_api::ioctls::IoctlRequest;
 use linux_api::socket::Shutdown;
 use nix::sys::socket::{MsgFlags, SockaddrIn};
@@ -23,9 +24,12 @@ use crate::host::descriptor::{
 use crate::host::memory_manager::MemoryManager;
 use crate::host::network::interface::FifoPacketPriority;
 use crate::host::network::namespace::{AssociationHandle, NetworkNamespace};
-use crate::host::syscall::io::{write_partial, IoVec, IoVecReader, IoVecWriter};
+use crate::host::syscall::io::{
+    read_cmsgs, write_cmsgs, write_partial, ControlMessage, IoVec, IoVecReader, IoVecWriter,
+};
 use crate::host::syscall::types::SyscallError;
-use crate::network::packet::{PacketRc, PacketStatus};
+use crate::network::packet::{PacketRc, PacketStatus, IPV4_DEFAULT_TTL};
+use crate::network::PacketDevice;
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
 use crate::utility::{HostTreePointer, ObjectCounter};
@@ -34,6 +38,21 @@ use crate::utility::{HostTreePointer, ObjectCounter};
 // 65,535 (2^16 - 1) - 20 (ip header) - 8 (udp header)
 const CONFIG_DATAGRAM_MAX_SIZE: usize = 65507;
 
+/// Length of the IPv4 and UDP headers of a datagram.
+const HEADER_SIZE_UDPIP: usize = c::CONFIG_HEADER_SIZE_UDPIP as usize;
+
+/// Maximum number of segments that a single send can be split into with `UDP_SEGMENT`. From
+/// linux's `UDP_MAX_SEGMENTS`.
+const UDP_MAX_SEGMENTS: usize = 64;
+
+/// Maximum number of datagrams that can be coalesced into a single read with `UDP_GRO`. From
+/// linux's `UDP_GRO_CNT_MAX`.
+const UDP_GRO_CNT_MAX: usize = 64;
+
+/// Maximum number of multicast groups that a socket can join. From linux's default
+/// `net.ipv4.igmp_max_memberships`.
+const IP_MAX_MEMBERSHIPS: usize = 20;
+
 pub struct UdpSocket {
     event_source: StateEventSource,
     status: FileStatus,
@@ -44,6 +63,29 @@ pub struct UdpSocket {
     peer_addr: Option<SocketAddrV4>,
     bound_addr: Option<SocketAddrV4>,
     association: Option<AssociationHandle>,
+    /// The path MTU discovery mode (`IP_MTU_DISCOVER`).
+    pmtu_disc: IpPmtuDisc,
+    /// The segment size for generic segmentation offload (`UDP_SEGMENT`), or 0 if disabled.
+    gso_size: u16,
+    /// Whether received datagrams are coalesced with generic receive offload (`UDP_GRO`).
+    gro_enabled: bool,
+    /// Whether the socket can send broadcast datagrams (`SO_BROADCAST`).
+    broadcast: bool,
+    /// The multicast groups that the socket has joined (`IP_ADD_MEMBERSHIP`).
+    multicast_groups: Vec<Ipv4Addr>,
+    /// Whether multicast datagrams that the socket sends are also delivered to the sending host
+    /// (`IP_MULTICAST_LOOP`).
+    multicast_loop: bool,
+    /// The TTL of multicast datagrams that the socket sends (`IP_MULTICAST_TTL`). Datagrams with a
+    /// TTL of 0 don't leave the sending host.
+    multicast_ttl: u8,
+    /// The TTL of unicast datagrams that the socket sends (`IP_TTL`).
+    ttl: u8,
+    /// Whether received datagrams include their TTL as a control message (`IP_RECVTTL`).
+    recv_ttl: bool,
+    /// An error reported by an ICMP message, which is returned by the next send or receive
+    /// (`SO_ERROR`).
+    error: Option<Errno>,
     /// The receive time of the last packet returned to the managed process during a call to
     /// `recvmsg()`. Used for `SIOCGSTAMP`.
     recv_time_of_last_read_packet: Option<EmulatedTime>,
@@ -69,6 +111,17 @@ impl UdpSocket {
             peer_addr: None,
             bound_addr: None,
             association: None,
+            // linux's default unless `net.ipv4.ip_no_pmtu_disc` is set
+            pmtu_disc: IpPmtuDisc::IP_PMTUDISC_WANT,
+            gso_size: 0,
+            gro_enabled: false,
+            broadcast: false,
+            multicast_groups: Vec::new(),
+            multicast_loop: true,
+            multicast_ttl: 1,
+            ttl: IPV4_DEFAULT_TTL,
+            recv_ttl: false,
+            error: None,
             recv_time_of_last_read_packet: None,
             has_open_file: false,
             _counter: ObjectCounter::new("UdpSocket"),
@@ -105,6 +158,24 @@ impl UdpSocket {
         self.has_open_file = val;
     }
 
+    /// Handle an ICMP error for a datagram that the socket sent to `dst`. Like Linux without
+    /// `IP_RECVERR`, the error is only reported if the socket is connected to `dst`.
+    pub fn push_icmp_error(
+        &mut self,
+        dst: SocketAddrV4,
+        error: Errno,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        if self.peer_addr != Some(dst) {
+            return;
+        }
+
+        log::trace!("UDP socket connected to {dst} received error {error}");
+        self.error = Some(error);
+
+        self.refresh_readable_writable(FileSignals::empty(), cb_queue);
+    }
+
     pub fn push_in_packet(
         &mut self,
         mut packet: PacketRc,
@@ -136,6 +207,18 @@ impl UdpSocket {
 
         // TODO: also check the dst address to make sure we are the intended socket?
 
+        // the host may have left the multicast group after the datagram was sent
+        let dst_ip = *packet.dst_address().ip();
+        if dst_ip.is_multicast()
+            && !Worker::with_active_host(|host| {
+                host.network_namespace_borrow().is_multicast_member(dst_ip)
+            })
+            .unwrap()
+        {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
         // don't bother copying the bytes if we know the push will fail
         if !self.recv_buffer.has_space() {
             packet.add_status(PacketStatus::RcvSocketDropped);
@@ -149,10 +232,37 @@ impl UdpSocket {
         let num_bytes_copied = packet.get_payload(&mut message);
         assert_eq!(num_bytes_copied, packet.payload_size());
 
-        let header = MessageRecvHeader {
+        let mut header = MessageRecvHeader {
             src: packet.src_address(),
             dst: packet.dst_address(),
+            ttl: packet.ttl(),
             recv_time,
+            gro_segment_size: None,
+        };
+
+        // with GRO, coalesce the datagram with the previous datagram if they're from the same flow
+        // and the application hasn't read the previous datagram yet
+        let can_coalesce = self.gro_enabled
+            && self
+                .recv_buffer
+                .peek_last_message()
+                .is_some_and(|prev| gro_can_coalesce(prev, &header, message.len()));
+
+        let message = if can_coalesce {
+            let (prev_message, prev_header) = self.recv_buffer.pop_last_message().unwrap();
+            let segment_size = prev_header.gro_segment_size.unwrap_or(prev_message.len());
+
+            let mut coalesced = BytesMut::with_capacity(prev_message.len() + message.len());
+            coalesced.extend_from_slice(&prev_message);
+            coalesced.extend_from_slice(&message);
+
+            header = MessageRecvHeader {
+                gro_segment_size: Some(segment_size),
+                ..prev_header
+            };
+            coalesced
+        } else {
+            message
         };
 
         // push the message to the receive buffer (shouldn't fail since we checked for available
@@ -187,6 +297,8 @@ impl UdpSocket {
 
         packet.set_udp(header.src, header.dst);
         packet.set_payload(&message, priority);
+        packet.set_dont_fragment(header.dont_fragment);
+        packet.set_ttl(header.ttl);
         packet.add_status(PacketStatus::SndCreated);
 
         self.refresh_readable_writable(FileSignals::empty(), cb_queue);
@@ -230,6 +342,16 @@ impl UdpSocket {
         // drop the existing association handle to disassociate the socket
         self.association = None;
 
+        if !self.multicast_groups.is_empty() {
+            Worker::with_active_host(|host| {
+                for group in self.multicast_groups.drain(..) {
+                    host.network_namespace_borrow()
+                        .leave_multicast_group(group, host.id());
+                }
+            })
+            .unwrap();
+        }
+
         self.update_state(
             /* mask= */ FileState::all(),
             FileState::CLOSED,
@@ -344,6 +466,24 @@ impl UdpSocket {
             return Err(Errno::EINVAL.into());
         };
 
+        let mut gso_size = socket_ref.gso_size;
+
+        for cmsg in read_cmsgs(mem, args.control_ptr)? {
+            match (cmsg.level, cmsg.ty) {
+                (libc::SOL_UDP, libc::UDP_SEGMENT) => {
+                    let Ok(data) = cmsg.data.try_into() else {
+                        return Err(Errno::EINVAL.into());
+                    };
+                    gso_size = u16::from_ne_bytes(data);
+                }
+                (level, ty) => {
+                    log::debug!(
+                        "Ignoring unsupported control message with level {level} and type {ty}"
+                    );
+                }
+            }
+        }
+
         let dst_addr = match args.addr {
             Some(addr) => match addr.as_inet() {
                 // an inet socket address
@@ -358,6 +498,11 @@ impl UdpSocket {
             },
         };
 
+        // only sockets with `SO_BROADCAST` can send broadcast datagrams
+        if net_ns.is_broadcast(*dst_addr.ip()) && !socket_ref.broadcast {
+            return Err(Errno::EACCES.into());
+        }
+
         if socket_ref.status().contains(FileStatus::NONBLOCK) {
             flags.insert(MsgFlags::MSG_DONTWAIT);
         }
@@ -369,6 +514,22 @@ impl UdpSocket {
             return Err(linux_api::errno::Errno::EMSGSIZE.into());
         }
 
+        // with GSO, the message is split into datagrams of `gso_size` bytes (the last datagram may
+        // be smaller)
+        let gso_size = usize::from(gso_size);
+        let segment_size = if gso_size > 0 && len > gso_size {
+            let path_mtu = usize::try_from(net_ns.path_mtu(*dst_addr.ip())).unwrap();
+            if gso_size + HEADER_SIZE_UDPIP > path_mtu || len > gso_size * UDP_MAX_SEGMENTS {
+                return Err(Errno::EINVAL.into());
+            }
+            gso_size
+        } else {
+            len
+        };
+
+        let dont_fragment =
+            socket_ref.dont_fragment(segment_size + HEADER_SIZE_UDPIP, *dst_addr.ip(), net_ns)?;
+
         // make sure that we're bound
         if socket_ref.bound_addr.is_some() {
             // we must have an association since we're bound
@@ -378,12 +539,12 @@ impl UdpSocket {
             assert!(socket_ref.peer_addr.is_none());
             assert!(socket_ref.association.is_none());
 
-            // implicit bind (use default interface unless the remote peer is on loopback)
+            // implicit bind (use the routed interface unless the remote peer is on loopback)
             // TODO: is this correct? or should we bind to UNSPECIFIED?
             let local_addr = if dst_addr.ip() == &std::net::Ipv4Addr::LOCALHOST {
                 SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)
             } else {
-                SocketAddrV4::new(net_ns.default_ip, 0)
+                SocketAddrV4::new(net_ns.source_ip(*dst_addr.ip()), 0)
             };
 
             // this will allow us to receive packets from any peer
@@ -404,6 +565,11 @@ impl UdpSocket {
 
         // run in a closure so that an early return doesn't skip checking if we should block
         let result = (|| {
+            // return any error from a previously sent datagram
+            if let Some(error) = socket_ref.error.take() {
+                return Err(error);
+            }
+
             // don't bother copying the bytes if we know the push will fail
             if !socket_ref.send_buffer.has_space() {
                 return Err(Errno::EWOULDBLOCK);
@@ -415,36 +581,93 @@ impl UdpSocket {
             reader
                 .read_exact(&mut message[..])
                 .map_err(|e| Errno::try_from(e).unwrap())?;
-
-            // get the priority that we'll assign to the eventual packet
-            let packet_priority =
-                Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
+            let mut message = message.freeze();
 
             let src_addr = socket_ref.bound_addr.unwrap();
             let src_addr = if src_addr.ip().is_unspecified() {
-                // depending on the destination address, choose either localhost or the public IP
-                // address
+                // depending on the destination address, choose either localhost or the address of
+                // the routed interface
                 if dst_addr.ip() == &std::net::Ipv4Addr::LOCALHOST {
                     SocketAddrV4::new(Ipv4Addr::LOCALHOST, src_addr.port())
                 } else {
-                    SocketAddrV4::new(net_ns.default_ip, src_addr.port())
+                    SocketAddrV4::new(net_ns.source_ip(*dst_addr.ip()), src_addr.port())
                 }
             } else {
                 src_addr
             };
 
-            let header = MessageSendHeader {
-                src: src_addr,
-                dst: dst_addr,
-                packet_priority,
+            let ttl = if dst_addr.ip().is_multicast() {
+                socket_ref.multicast_ttl
+            } else {
+                socket_ref.ttl
+            };
+
+            // split the message into datagrams (only one unless we're using GSO)
+            let mut datagrams = Vec::new();
+            loop {
+                let datagram = message.split_to(std::cmp::min(segment_size, message.len()));
+
+                // get the priority that we'll assign to the eventual packet
+                let packet_priority =
+                    Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
+
+                let header = MessageSendHeader {
+                    src: src_addr,
+                    dst: dst_addr,
+                    packet_priority,
+                    dont_fragment,
+                    ttl,
+                };
+                datagrams.push((datagram, header));
+
+                if message.is_empty() {
+                    break;
+                }
+            }
+
+            let is_multicast = dst_addr.ip().is_multicast();
+
+            // the sending host also receives the datagrams if it's a member of the multicast group,
+            // and always receives its own broadcasts
+            let loop_back = if is_multicast {
+                socket_ref.multicast_loop && net_ns.is_multicast_member(*dst_addr.ip())
+            } else {
+                net_ns.is_broadcast(*dst_addr.ip())
             };
 
-            // push the message to the send buffer (shouldn't fail since we checked for available
+            if loop_back {
+                let packets: Vec<_> = datagrams
+                    .iter()
+                    .map(|(datagram, header)| {
+                        let mut packet = PacketRc::new();
+                        packet.set_udp(header.src, header.dst);
+                        packet.set_payload(datagram, header.packet_priority);
+                        packet.set_ttl(header.ttl);
+                        packet.add_status(PacketStatus::SndCreated);
+                        packet
+                    })
+                    .collect();
+
+                // push the packets after the socket is no longer borrowed, since this socket may
+                // receive them
+                cb_queue.add(move |_cb_queue| {
+                    Worker::with_active_host(|host| {
+                        let interface = host.get_packet_device(host.default_ip());
+                        for packet in packets {
+                            interface.push(packet);
+                        }
+                    })
+                    .unwrap();
+                });
+            }
+
+            if is_multicast && socket_ref.multicast_ttl == 0 {
+                return Ok(len);
+            }
+
+            // push the datagrams to the send buffer (shouldn't fail since we checked for available
             // space above)
-            socket_ref
-                .send_buffer
-                .push_message(message.freeze(), header)
-                .unwrap();
+            socket_ref.send_buffer.push_messages(datagrams).unwrap();
 
             // notify the host that this socket has packets to send
             let socket = Arc::clone(socket);
@@ -474,6 +697,32 @@ impl UdpSocket {
         Ok(result?.try_into().unwrap())
     }
 
+    /// Whether a packet of `packet_len` bytes sent to `dst` should have the "don't fragment" flag,
+    /// based on the socket's path MTU discovery mode. Returns `EMSGSIZE` if the mode doesn't allow
+    /// the packet to be sent.
+    fn dont_fragment(
+        &self,
+        packet_len: usize,
+        dst: Ipv4Addr,
+        net_ns: &NetworkNamespace,
+    ) -> Result<bool, Errno> {
+        let exceeds = |mtu: u32| packet_len > usize::try_from(mtu).unwrap();
+
+        match self.pmtu_disc {
+            // larger packets are fragmented locally
+            IpPmtuDisc::IP_PMTUDISC_WANT => Ok(!exceeds(net_ns.path_mtu(dst))),
+            IpPmtuDisc::IP_PMTUDISC_DO if exceeds(net_ns.path_mtu(dst)) => Err(Errno::EMSGSIZE),
+            IpPmtuDisc::IP_PMTUDISC_DO => Ok(true),
+            IpPmtuDisc::IP_PMTUDISC_PROBE if exceeds(net_ns.interface_mtu(dst)) => {
+                Err(Errno::EMSGSIZE)
+            }
+            IpPmtuDisc::IP_PMTUDISC_PROBE => Ok(true),
+            IpPmtuDisc::IP_PMTUDISC_DONT
+            | IpPmtuDisc::IP_PMTUDISC_INTERFACE
+            | IpPmtuDisc::IP_PMTUDISC_OMIT => Ok(false),
+        }
+    }
+
     pub fn recvmsg(
         socket: &Arc<AtomicRefCell<Self>>,
         args: RecvmsgArgs,
@@ -495,6 +744,11 @@ impl UdpSocket {
 
         // run in a closure so that an early return doesn't skip checking if we should block
         let result = (|| {
+            // return any error from a previously sent datagram
+            if let Some(error) = socket_ref.error.take() {
+                return Err(error);
+            }
+
             // a temporary location to store the message and header if we popped them
             let message_storage;
             let header_storage;
@@ -531,8 +785,30 @@ impl UdpSocket {
                 truncated_message.len()
             };
 
+            let mut cmsgs = Vec::new();
+
+            if socket_ref.recv_ttl {
+                cmsgs.push(ControlMessage {
+                    level: libc::IPPROTO_IP,
+                    ty: libc::IP_TTL,
+                    data: libc::c_int::from(header.ttl).to_ne_bytes().to_vec(),
+                });
+            }
+
+            // with GRO, tell the application the size of the coalesced datagrams
+            if let Some(segment_size) = header.gro_segment_size {
+                let segment_size = libc::c_int::try_from(segment_size).unwrap();
+                cmsgs.push(ControlMessage {
+                    level: libc::SOL_UDP,
+                    ty: libc::UDP_GRO,
+                    data: segment_size.to_ne_bytes().to_vec(),
+                });
+            }
+            let (control_len, control_truncated) = write_cmsgs(mem, args.control_ptr, &cmsgs)?;
+
             let mut return_flags = MsgFlags::empty();
             return_flags.set(MsgFlags::MSG_TRUNC, truncated_message.len() < message.len());
+            return_flags.set(MsgFlags::MSG_CTRUNC, control_truncated);
 
             // update the cache of the last recv time
             socket_ref.recv_time_of_last_read_packet = Some(header.recv_time);
@@ -541,7 +817,9 @@ impl UdpSocket {
                 return_val: return_val.try_into().unwrap(),
                 addr: Some(header.src.into()),
                 msg_flags: return_flags.bits(),
-                control_len: 0,
+                control_len,
+                rights: Vec::new(),
+                creds: None,
             })
         })();
 
@@ -558,6 +836,8 @@ impl UdpSocket {
                     addr: None,
                     msg_flags: 0,
                     control_len: 0,
+                    rights: Vec::new(),
+                    creds: None,
                 });
             }
 
@@ -681,21 +961,16 @@ impl UdpSocket {
         // to `Ipv4Addr::LOCALHOST`, but the rest of Shadow probably can't handle other loopback
         // addresses (ex: 127.0.0.2) and it's probably best not to change this behaviour
 
-        // make sure we will be able to route this later
-        // TODO: UDP sockets probably shouldn't return `ECONNREFUSED`
-        if peer_addr.ip() != &std::net::Ipv4Addr::LOCALHOST {
-            let is_routable =
-                Worker::is_routable(net_ns.default_ip.into(), (*peer_addr.ip()).into());
+        let is_broadcast = net_ns.is_broadcast(*peer_addr.ip());
 
-            if !is_routable {
-                // can't route it - there is no node with this address
-                log::warn!(
-                    "Attempting to connect to address '{peer_addr}' for which no host exists"
-                );
-                return Err(Errno::ECONNREFUSED.into());
-            }
+        // only sockets with `SO_BROADCAST` can send broadcast datagrams
+        if is_broadcast && !socket.borrow().broadcast {
+            return Err(Errno::EACCES.into());
         }
 
+        // like Linux, we don't check that a host has the address; datagrams sent to an address
+        // with no host are answered with an ICMP "host unreachable" error instead
+
         // make sure that we're bound
         {
             let mut socket_ref = socket.borrow_mut();
@@ -725,11 +1000,11 @@ impl UdpSocket {
                 assert!(socket_ref.peer_addr.is_none());
                 assert!(socket_ref.association.is_none());
 
-                // implicit bind (use default interface unless the remote peer is on loopback)
+                // implicit bind (use the routed interface unless the remote peer is on loopback)
                 let local_addr = if peer_addr.ip() == &std::net::Ipv4Addr::LOCALHOST {
                     SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)
                 } else {
-                    SocketAddrV4::new(net_ns.default_ip, 0)
+                    SocketAddrV4::new(net_ns.source_ip(*peer_addr.ip()), 0)
                 };
 
                 // this will allow us to receive packets from any source address, but
@@ -794,7 +1069,7 @@ impl UdpSocket {
         optval_ptr: ForeignPtr<()>,
         optlen: libc::socklen_t,
         mem: &mut MemoryManager,
-        _cb_queue: &mut CallbackQueue,
+        cb_queue: &mut CallbackQueue,
     ) -> Result<libc::socklen_t, SyscallError> {
         match (level, optname) {
             (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
@@ -814,7 +1089,9 @@ impl UdpSocket {
                 Ok(bytes_written as libc::socklen_t)
             }
             (libc::SOL_SOCKET, libc::SO_ERROR) => {
-                let error = 0;
+                // reading the error clears it
+                let error = self.error.take().map(Into::into).unwrap_or(0);
+                self.refresh_readable_writable(FileSignals::empty(), cb_queue);
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written = write_partial(mem, &error, optval_ptr, optlen as usize)?;
@@ -851,6 +1128,89 @@ impl UdpSocket {
 
                 Ok(bytes_written as libc::socklen_t)
             }
+            (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
+                let broadcast = libc::c_int::from(self.broadcast);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &broadcast, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::IPPROTO_IP, IP_MTU_DISCOVER) => {
+                let pmtu_disc: libc::c_int = self.pmtu_disc.into();
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &pmtu_disc, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::IPPROTO_IP, IP_MTU) => {
+                // only connected sockets have a path
+                let Some(peer_addr) = self.peer_addr else {
+                    return Err(Errno::ENOTCONN.into());
+                };
+
+                let mtu = Worker::with_active_host(|host| {
+                    host.network_namespace_borrow().path_mtu(*peer_addr.ip())
+                })
+                .unwrap();
+                let mtu: libc::c_int = mtu.try_into().unwrap();
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &mtu, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP) => {
+                let multicast_loop = libc::c_int::from(self.multicast_loop);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(mem, &multicast_loop, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::IPPROTO_IP, libc::IP_MULTICAST_TTL) => {
+                let multicast_ttl = libc::c_int::from(self.multicast_ttl);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(mem, &multicast_ttl, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::IPPROTO_IP, libc::IP_TTL) => {
+                let ttl = libc::c_int::from(self.ttl);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &ttl, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::IPPROTO_IP, libc::IP_RECVTTL) => {
+                let recv_ttl = libc::c_int::from(self.recv_ttl);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &recv_ttl, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_UDP, libc::UDP_SEGMENT) => {
+                let gso_size = libc::c_int::from(self.gso_size);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &gso_size, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_UDP, libc::UDP_GRO) => {
+                let gro_enabled = libc::c_int::from(self.gro_enabled);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &gro_enabled, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, _) => {
                 log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
                 Err(Errno::ENOPROTOOPT.into())
@@ -939,10 +1299,110 @@ impl UdpSocket {
                 return Err(Errno::ENOPROTOOPT.into());
             }
             (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
-                // TODO: implement this, pkg.go.dev/net uses it
-                warn_once_then_debug!(
-                    "setsockopt SO_BROADCAST not yet implemented for udp; ignoring and returning 0"
-                );
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = mem.read(optval_ptr)?;
+
+                self.broadcast = val != 0;
+            }
+            (libc::IPPROTO_IP, IP_MTU_DISCOVER) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = mem.read(optval_ptr)?;
+
+                self.pmtu_disc = IpPmtuDisc::try_from(val).or(Err(Errno::EINVAL))?;
+            }
+            (libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP) => {
+                let group = read_multicast_request(mem, optval_ptr, optlen)?;
+
+                if self.multicast_groups.contains(&group) {
+                    return Err(Errno::EADDRINUSE.into());
+                }
+                if self.multicast_groups.len() >= IP_MAX_MEMBERSHIPS {
+                    return Err(Errno::ENOBUFS.into());
+                }
+
+                Worker::with_active_host(|host| {
+                    host.network_namespace_borrow()
+                        .join_multicast_group(group, host.id());
+                })
+                .unwrap();
+                self.multicast_groups.push(group);
+            }
+            (libc::IPPROTO_IP, libc::IP_DROP_MEMBERSHIP) => {
+                let group = read_multicast_request(mem, optval_ptr, optlen)?;
+
+                let Some(index) = self.multicast_groups.iter().position(|x| *x == group) else {
+                    return Err(Errno::EADDRNOTAVAIL.into());
+                };
+
+                self.multicast_groups.remove(index);
+                Worker::with_active_host(|host| {
+                    host.network_namespace_borrow()
+                        .leave_multicast_group(group, host.id());
+                })
+                .unwrap();
+            }
+            (libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP) => {
+                let val = read_int_or_byte(mem, optval_ptr, optlen)?;
+                self.multicast_loop = val != 0;
+            }
+            (libc::IPPROTO_IP, libc::IP_MULTICAST_TTL) => {
+                let val = read_int_or_byte(mem, optval_ptr, optlen)?;
+
+                // -1 selects the default TTL
+                self.multicast_ttl = match val {
+                    -1 => 1,
+                    val => u8::try_from(val).or(Err(Errno::EINVAL))?,
+                };
+            }
+            (libc::IPPROTO_IP, libc::IP_TTL) => {
+                let val = read_int_or_byte(mem, optval_ptr, optlen)?;
+
+                // ip(7): "-1 means use the route default"
+                self.ttl = match val {
+                    -1 => IPV4_DEFAULT_TTL,
+                    1..=255 => val.try_into().unwrap(),
+                    _ => return Err(Errno::EINVAL.into()),
+                };
+            }
+            (libc::IPPROTO_IP, libc::IP_RECVTTL) => {
+                let val = read_int_or_byte(mem, optval_ptr, optlen)?;
+                self.recv_ttl = val != 0;
+            }
+            (libc::SOL_UDP, libc::UDP_SEGMENT) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = mem.read(optval_ptr)?;
+
+                self.gso_size = u16::try_from(val).or(Err(Errno::EINVAL))?;
+            }
+            (libc::SOL_UDP, libc::UDP_GRO) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = mem.read(optval_ptr)?;
+
+                self.gro_enabled = val != 0;
             }
             _ => {
                 log::debug!("setsockopt called with unsupported level {level} and opt {optname}");
@@ -980,8 +1440,9 @@ impl UdpSocket {
     }
 
     fn refresh_readable_writable(&mut self, signals: FileSignals, cb_queue: &mut CallbackQueue) {
-        let readable = !self.recv_buffer.is_empty();
-        let writable = self.send_buffer.has_space();
+        // a pending error makes the socket readable and writable so that blocked calls return it
+        let readable = !self.recv_buffer.is_empty() || self.error.is_some();
+        let writable = self.send_buffer.has_space() || self.error.is_some();
 
         let readable = readable.then_some(FileState::READABLE).unwrap_or_default();
         let writable = writable.then_some(FileState::WRITABLE).unwrap_or_default();
@@ -1038,6 +1499,9 @@ struct MessageSendHeader {
     dst: SocketAddrV4,
     /// The priority for the packet that we'll create in the future, given to us by the host.
     packet_priority: FifoPacketPriority,
+    /// Whether the packet should have the "don't fragment" flag.
+    dont_fragment: bool,
+    ttl: u8,
 }
 
 /// Non-payload data for a message in the receive buffer.
@@ -1049,13 +1513,112 @@ struct MessageRecvHeader {
     /// `IP_PKTINFO` to get the packet destination address.
     #[allow(dead_code)]
     dst: SocketAddrV4,
+    /// The TTL of the received packet.
+    ttl: u8,
     /// The time when the network interface received the message.
     recv_time: EmulatedTime,
+    /// If the message is several datagrams coalesced with GRO, the size of each datagram (the last
+    /// datagram may be smaller).
+    gro_segment_size: Option<usize>,
 }
 
-/// A buffer of UDP messages and message headers.
+/// Whether a received datagram of `len` bytes with header `header` can be coalesced with GRO into
+/// the message `prev`. Like linux, all datagrams other than the last must have the same size.
+/// Read an `ip_mreq` or `ip_mreqn` option value and return its multicast group. Shadow only
+/// routes multicast datagrams on the internet interface, so other interfaces return `ENODEV`.
+fn read_multicast_request(
+    mem: &MemoryManager,
+    optval_ptr: ForeignPtr<()>,
+    optlen: libc::socklen_t,
+) -> Result<Ipv4Addr, Errno> {
+    let optlen = usize::try_from(optlen).unwrap();
+
+    // like linux, use the `ip_mreqn` if it fits
+    let (group, interface_addr, interface_index) =
+        if optlen >= std::mem::size_of::<libc::ip_mreqn>() {
+            let mreqn = mem.read(optval_ptr.cast::<libc::ip_mreqn>())?;
+            (mreqn.imr_multiaddr, mreqn.imr_address, mreqn.imr_ifindex)
+        } else if optlen >= std::mem::size_of::<libc::ip_mreq>() {
+            let mreq = mem.read(optval_ptr.cast::<libc::ip_mreq>())?;
+            (mreq.imr_multiaddr, mreq.imr_interface, 0)
+        } else {
+            return Err(Errno::EINVAL);
+        };
+
+    let group = Ipv4Addr::from(u32::from_be(group.s_addr));
+    let interface_addr = Ipv4Addr::from(u32::from_be(interface_addr.s_addr));
+
+    if !group.is_multicast() {
+        return Err(Errno::EINVAL);
+    }
+
+    let interface = Worker::with_active_host(|host| {
+        host.network_namespace_borrow()
+            .interfaces()
+            .into_iter()
+            .find(|x| !x.is_loopback && !x.is_tun)
+            .unwrap()
+    })
+    .unwrap();
+
+    // the interface is chosen by its index if given, otherwise by its address
+    let is_internet_interface = if interface_index != 0 {
+        interface_index == interface.index
+    } else {
+        interface_addr.is_unspecified() || interface_addr == interface.address
+    };
+
+    if !is_internet_interface {
+        log::debug!(
+            "Multicast is only supported on the {} interface",
+            interface.name
+        );
+        return Err(Errno::ENODEV);
+    }
+
+    Ok(group)
+}
+
+/// Read an option value that may be either an int or a single byte, like linux does for some
+/// `IPPROTO_IP` options.
+fn read_int_or_byte(
+    mem: &MemoryManager,
+    optval_ptr: ForeignPtr<()>,
+    optlen: libc::socklen_t,
+) -> Result<libc::c_int, Errno> {
+    let optlen = usize::try_from(optlen).unwrap();
+
+    if optlen >= std::mem::size_of::<libc::c_int>() {
+        Ok(mem.read(optval_ptr.cast::<libc::c_int>())?)
+    } else if optlen >= std::mem::size_of::<u8>() {
+        Ok(mem.read(optval_ptr.cast::<u8>())?.into())
+    } else {
+        Err(Errno::EINVAL)
+    }
+}
+
+fn gro_can_coalesce(
+    prev: &(Bytes, MessageRecvHeader),
+    header: &MessageRecvHeader,
+    len: usize,
+) -> bool {
+    let (prev_message, prev_header) = prev;
+    let segment_size = prev_header.gro_segment_size.unwrap_or(prev_message.len());
+
+    prev_header.src == header.src
+        && prev_header.dst == header.dst
+        && prev_header.ttl == header.ttl
+        && len > 0
+        && len <= segment_size
+        // the previous message must not already end with a smaller datagram
+        && prev_message.len() % segment_size == 0
+        && prev_message.len() / segment_size < UDP_GRO_CNT_MAX
+        && prev_message.len() + len <= CONFIG_DATAGRAM_MAX_SIZE
+}
+
+/// A buffer of UDP or ICMP messages and message headers.
 #[derive(Debug)]
-struct MessageBuffer<Hdr> {
+pub(super) struct MessageBuffer<Hdr> {
     /// The message payloads and headers.
     // use a `LinkedList` so that socket buffers can shrink when they're empty (as opposed to
     // `VecDeque`)
@@ -1092,6 +1655,22 @@ impl<Hdr> MessageBuffer<Hdr> {
         Ok(())
     }
 
+    /// Push several messages to the buffer, such as the datagrams of a single GSO send. Like a
+    /// single message, the messages may exceed the soft limit as long as there is space when
+    /// they're pushed. Returns the messages and headers as an `Err` if there wasn't enough space.
+    pub fn push_messages(&mut self, messages: Vec<(Bytes, Hdr)>) -> Result<(), Vec<(Bytes, Hdr)>> {
+        if !self.has_space() {
+            return Err(messages);
+        }
+
+        for (message, header) in messages {
+            self.len_bytes += message.len();
+            self.buffer.push_back((message, header));
+        }
+
+        Ok(())
+    }
+
     /// Pop the next message from the buffer. Returns a tuple of the message bytes and message
     /// header.
     pub fn pop_message(&mut self) -> Option<(Bytes, Hdr)> {
@@ -1106,6 +1685,20 @@ impl<Hdr> MessageBuffer<Hdr> {
         self.buffer.front()
     }
 
+    /// Pop the most recently pushed message from the buffer. Returns a tuple of the message bytes
+    /// and message header.
+    pub fn pop_last_message(&mut self) -> Option<(Bytes, Hdr)> {
+        let (message, header) = self.buffer.pop_back()?;
+        self.len_bytes -= message.len();
+
+        Some((message, header))
+    }
+
+    /// Peek the most recently pushed message in the buffer.
+    pub fn peek_last_message(&self) -> Option<&(Bytes, Hdr)> {
+        self.buffer.back()
+    }
+
     /// The number of payload bytes contained in the buffer. A length of 0 does not mean that the
     /// buffer is empty.
     pub fn len_bytes(&self) -> usize {
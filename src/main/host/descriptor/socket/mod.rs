@@ -11,7 +11,7 @@ use unix::UnixSocket;
 use crate::cshadow as c;
 use crate::host::descriptor::listener::{StateListenHandle, StateListenerFilter};
 use crate::host::descriptor::{
-    FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
+    CompatFile, FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
 };
 use crate::host::memory_manager::MemoryManager;
 use crate::host::network::namespace::NetworkNamespace;
@@ -84,6 +84,16 @@ impl Socket {
         }
     }
 
+    /// Is this a connection-oriented byte stream socket (`SOCK_STREAM`)?
+    pub fn is_stream(&self) -> bool {
+        match self {
+            Self::Unix(f) => f.borrow().socket_type() == unix::UnixSocketType::Stream,
+            Self::Inet(InetSocket::LegacyTcp(_) | InetSocket::Tcp(_)) => true,
+            Self::Inet(_) => false,
+            Self::Netlink(_) => false,
+        }
+    }
+
     pub fn bind(
         &self,
         addr: Option<&SockaddrStorage>,
@@ -383,6 +393,13 @@ pub struct SendmsgArgs<'a> {
     pub iovs: &'a [IoVec],
     /// Buffer in plugin memory containg message control data.
     pub control_ptr: ForeignArrayPtr<u8>,
+    /// Files to pass to the receiving socket, from any `SCM_RIGHTS` control messages. Only unix
+    /// sockets support passing files, and the syscall handler reads these control messages for
+    /// them since it has access to the descriptor table.
+    pub rights: Vec<CompatFile>,
+    /// Credentials of the sending process, or from a `SCM_CREDENTIALS` control message. Only unix
+    /// sockets use these, so they're only set for unix sockets.
+    pub creds: Option<libc::ucred>,
     /// Send flags.
     pub flags: libc::c_int,
 }
@@ -408,4 +425,10 @@ pub struct RecvmsgReturn {
     pub msg_flags: libc::c_int,
     /// The number of control data bytes read.
     pub control_len: libc::size_t,
+    /// Files passed from the sending socket. The syscall handler adds these to the descriptor
+    /// table and writes their descriptors in a `SCM_RIGHTS` control message.
+    pub rights: Vec<CompatFile>,
+    /// Credentials of the sending process, if the socket has `SO_PASSCRED` enabled. The syscall
+    /// handler writes these in a `SCM_CREDENTIALS` control message.
+    pub creds: Option<libc::ucred>,
 }
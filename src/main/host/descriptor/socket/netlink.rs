@@ -5,22 +5,25 @@ use std::sync::{Arc, Weak};
 use atomic_refcell::AtomicRefCell;
 use linux_api::errno::Errno;
 use linux_api::ioctls::IoctlRequest;
+use linux_api::netdevice::ARPHRD_NONE;
 use linux_api::netlink::nlmsghdr;
-use linux_api::rtnetlink::{RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR, RTM_GETADDR, RTM_GETLINK};
+use linux_api::rtnetlink::{
+    RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR, RTM_GETADDR, RTM_GETLINK, RTM_GETROUTE,
+};
 use linux_api::socket::Shutdown;
 use neli::consts::nl::{NlmF, NlmFFlags, Nlmsg};
 use neli::consts::rtnl::{
-    Arphrd, Ifa, IfaF, IfaFFlags, Iff, IffFlags, Ifla, RtAddrFamily, RtScope, Rtm,
+    Arphrd, Ifa, IfaF, IfaFFlags, Iff, IffFlags, Ifla, RtAddrFamily, RtScope, RtTable, Rta, Rtm,
+    RtmFFlags, Rtn, Rtprot,
 };
 use neli::nl::{NlPayload, Nlmsghdr};
-use neli::rtnl::{Ifaddrmsg, Ifinfomsg, Rtattr};
+use neli::rtnl::{Ifaddrmsg, Ifinfomsg, Rtattr, Rtmsg};
 use neli::types::{Buffer, RtBuffer};
 use neli::{FromBytes, ToBytes};
 use nix::sys::socket::{MsgFlags, NetlinkAddr};
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
 use crate::core::worker::Worker;
-use crate::cshadow as c;
 use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
 use crate::host::descriptor::shared_buf::{
     BufferHandle, BufferSignals, BufferState, ReaderHandle, SharedBuf,
@@ -30,9 +33,10 @@ use crate::host::descriptor::{
     File, FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
 };
 use crate::host::memory_manager::MemoryManager;
-use crate::host::network::namespace::NetworkNamespace;
+use crate::host::network::namespace::{InterfaceInfo, NetworkNamespace};
 use crate::host::syscall::io::{IoVec, IoVecReader, IoVecWriter};
 use crate::host::syscall::types::SyscallError;
+use crate::network::nat::Subnet;
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
 use crate::utility::HostTreePointer;
@@ -58,29 +62,12 @@ impl NetlinkSocket {
             let buffer = SharedBuf::new(usize::MAX);
             let buffer = Arc::new(AtomicRefCell::new(buffer));
 
-            // Get the IP address of the host
-            let default_ip = Worker::with_active_host(|host| host.default_ip()).unwrap();
-            // All the interface configurations are the same as in the getifaddrs function handler
-            let interfaces = vec![
-                Interface {
-                    address: Ipv4Addr::LOCALHOST,
-                    label: String::from("lo"),
-                    prefix_len: 8,
-                    if_type: Arphrd::Loopback,
-                    mtu: c::CONFIG_MTU,
-                    scope: RtScope::Host,
-                    index: 1,
-                },
-                Interface {
-                    address: default_ip,
-                    label: String::from("eth0"),
-                    prefix_len: 24,
-                    if_type: Arphrd::Ether,
-                    mtu: c::CONFIG_MTU,
-                    scope: RtScope::Universe,
-                    index: 2,
-                },
-            ];
+            // Get the interfaces and routes of the host
+            let (interfaces, routes) = Worker::with_active_host(|host| {
+                let net_ns = host.network_namespace_borrow();
+                (net_ns.interfaces(), net_ns.routes())
+            })
+            .unwrap();
 
             let mut common = NetlinkSocketCommon {
                 buffer,
@@ -91,6 +78,7 @@ impl NetlinkSocket {
                 status,
                 has_open_file: false,
                 interfaces,
+                routes,
             };
             let protocol_state = ProtocolState::new(&mut common, weak);
             let mut socket = Self {
@@ -686,10 +674,11 @@ impl InitialState {
             match nlmsg_type {
                 RTM_GETLINK => self.handle_ifinfomsg(common, &packet_buffer[..]),
                 RTM_GETADDR => self.handle_ifaddrmsg(common, &packet_buffer[..]),
+                RTM_GETROUTE => self.handle_rtmsg(common, &packet_buffer[..]),
                 _ => {
                     warn_once_then_debug!(
-                        "Found unsupported nlmsg_type: {nlmsg_type} (only RTM_GETLINK
-                        and RTM_GETADDR are supported)"
+                        "Found unsupported nlmsg_type: {nlmsg_type} (only RTM_GETLINK,
+                        RTM_GETADDR, and RTM_GETROUTE are supported)"
                     );
                     self.handle_error(&packet_buffer[..])
                 }
@@ -722,6 +711,8 @@ impl InitialState {
             addr: Some(src_addr),
             msg_flags: 0,
             control_len: 0,
+            rights: Vec::new(),
+            creds: None,
         })
     }
 
@@ -779,17 +770,12 @@ impl InitialState {
         }
 
         let mut buffer = Cursor::new(Vec::new());
-        // Send the interface addresses
-        for interface in &common.interfaces {
+        // Send the interface addresses (interfaces without an address are skipped)
+        let interfaces = common.interfaces.iter();
+        for interface in interfaces.filter(|x| !x.address.is_unspecified()) {
             let address = interface.address.octets();
-            let broadcast = Ipv4Addr::from(
-                0xffff_ffff_u32
-                    .checked_shr(u32::from(interface.prefix_len))
-                    .unwrap_or(0)
-                    | u32::from(interface.address),
-            )
-            .octets();
-            let mut label = Vec::from(interface.label.as_bytes());
+            let broadcast = interface.broadcast().octets();
+            let mut label = Vec::from(interface.name.as_bytes());
             label.push(0); // Null-terminate
 
             // List of attribtes sent with the response for the current interface
@@ -807,7 +793,7 @@ impl InitialState {
                 ifa_prefixlen: interface.prefix_len,
                 // IFA_F_PERMANENT is used to indicate that the address is permanent
                 ifa_flags: IfaFFlags::new(&[IfaF::Permanent]),
-                ifa_scope: libc::c_uchar::from(interface.scope),
+                ifa_scope: libc::c_uchar::from(if_scope(interface)),
                 ifa_index: interface.index,
                 rtattrs: RtBuffer::from_iter(attrs),
             };
@@ -881,7 +867,7 @@ impl InitialState {
         let mut buffer = Cursor::new(Vec::new());
         // Send the interface addresses
         for interface in &common.interfaces {
-            let mut label = Vec::from(interface.label.as_bytes());
+            let mut label = Vec::from(interface.name.as_bytes());
             label.push(0); // Null-terminate
 
             // List of attribtes sent with the response for the current interface
@@ -897,9 +883,18 @@ impl InitialState {
                     Buffer::from(&interface.mtu.to_le_bytes()[..]),
                 )
                 .unwrap(),
-                // TODO: Add the MAC address through IFLA_ADDRESS and IFLA_BROADCAST
+                Rtattr::new(None, Ifla::Address, Buffer::from(&interface.hw_address[..])).unwrap(),
+                Rtattr::new(
+                    None,
+                    Ifla::Broadcast,
+                    Buffer::from(&interface.hw_broadcast()[..]),
+                )
+                .unwrap(),
             ];
-            let flags = if interface.if_type == Arphrd::Loopback {
+            let flags = if interface.is_tun {
+                // a TUN interface is only up and running once it has been configured
+                IffFlags::from_bitmask(u32::from(interface.flags().bits() as u16))
+            } else if interface.is_loopback {
                 IffFlags::new(&[Iff::Up, Iff::Loopback, Iff::Running])
             } else {
                 // Not sure about the IFF_MULTICAST, but it's also the one I got from `strace ip addr`
@@ -907,7 +902,7 @@ impl InitialState {
             };
             let ifinfomsg = Ifinfomsg::new(
                 RtAddrFamily::Inet,
-                interface.if_type,
+                if_type(interface),
                 interface.index,
                 flags,
                 IffFlags::from_bitmask(0xffffffff), // rtnetlink(7) recommends to set it to all 1s
@@ -944,6 +939,95 @@ impl InitialState {
 
         buffer.into_inner()
     }
+
+    fn handle_rtmsg(&self, common: &mut NetlinkSocketCommon, bytes: &[u8]) -> Vec<u8> {
+        let Ok(nlmsg) = Nlmsghdr::<Rtm, Rtmsg>::from_bytes(&mut Cursor::new(bytes)) else {
+            log::warn!("Failed to deserialize the message");
+            return self.handle_error(bytes);
+        };
+
+        let Ok(rtmsg) = nlmsg.get_payload() else {
+            log::warn!("Failed to find the payload");
+            return self.handle_error(bytes);
+        };
+
+        // The only supported route address family is AF_INET
+        if rtmsg.rtm_family != RtAddrFamily::Unspecified && rtmsg.rtm_family != RtAddrFamily::Inet {
+            warn_once_then_debug!(
+                "Unsupported rtm_family (only AF_UNSPEC and AF_INET are supported)"
+            );
+            return self.handle_error(bytes);
+        }
+
+        // Linux ignores the other fields of a dump request unless the socket has enabled strict
+        // checking, but iproute2 sets `rtm_table` to the table that it's interested in, so we use
+        // it as a filter
+        let table_filter = (rtmsg.rtm_table != RtTable::Unspec).then_some(rtmsg.rtm_table);
+
+        let mut buffer = Cursor::new(Vec::new());
+        // Send the routes
+        for route in common.routes() {
+            if table_filter.is_some_and(|x| x != route.table) {
+                continue;
+            }
+
+            let table_id = u32::from(libc::c_uchar::from(route.table));
+            let oif = u32::try_from(route.interface_index).unwrap();
+
+            // List of attributes sent with the response for the current route
+            let table = Rtattr::new(None, Rta::Table, Buffer::from(&table_id.to_le_bytes()[..]));
+            let mut attrs = vec![table.unwrap()];
+            if route.dst_len != 0 {
+                let dst = route.dst.octets();
+                attrs.push(Rtattr::new(None, Rta::Dst, Buffer::from(&dst[..])).unwrap());
+            }
+            if let Some(prefsrc) = route.prefsrc {
+                let prefsrc = prefsrc.octets();
+                attrs.push(Rtattr::new(None, Rta::Prefsrc, Buffer::from(&prefsrc[..])).unwrap());
+            }
+            attrs.push(Rtattr::new(None, Rta::Oif, Buffer::from(&oif.to_le_bytes()[..])).unwrap());
+
+            let rtmsg = Rtmsg {
+                rtm_family: RtAddrFamily::Inet,
+                rtm_dst_len: route.dst_len,
+                rtm_src_len: 0,
+                rtm_tos: 0,
+                rtm_table: route.table,
+                rtm_protocol: route.protocol,
+                rtm_scope: route.scope,
+                rtm_type: route.route_type,
+                rtm_flags: RtmFFlags::empty(),
+                rtattrs: RtBuffer::from_iter(attrs),
+            };
+            let nlmsg = {
+                let len = None;
+                let nl_type = Rtm::Newroute;
+                // The NLM_F_MULTI flag is used to indicate that we will send multiple messages
+                let flags = NlmFFlags::new(&[NlmF::Multi]);
+                // Use the same sequence number as the request
+                let seq = Some(nlmsg.nl_seq);
+                let pid = None;
+                let payload = NlPayload::Payload(rtmsg);
+                Nlmsghdr::new(len, nl_type, flags, seq, pid, payload)
+            };
+            nlmsg.to_bytes(&mut buffer).unwrap();
+        }
+        // After sending the messages with the NLM_F_MULTI flag set, we need to send the NLMSG_DONE message
+        let done_msg = {
+            let len = None;
+            let nl_type = Nlmsg::Done;
+            let flags = NlmFFlags::new(&[NlmF::Multi]);
+            // Use the same sequence number as the request
+            let seq = Some(nlmsg.nl_seq);
+            let pid = None;
+            // Linux also emits the errno of zero after the header. See `strace ip route`
+            let payload: NlPayload<Nlmsg, u32> = NlPayload::Payload(0);
+            Nlmsghdr::new(len, nl_type, flags, seq, pid, payload)
+        };
+        done_msg.to_bytes(&mut buffer).unwrap();
+
+        buffer.into_inner()
+    }
 }
 
 impl ClosedState {
@@ -1013,15 +1097,36 @@ impl ClosedState {
     }
 }
 
-// The struct used to describe the network interface
-struct Interface {
-    address: Ipv4Addr,
-    label: String,
-    prefix_len: u8,
-    if_type: Arphrd,
-    mtu: u32,
+// The type of a network interface
+fn if_type(interface: &InterfaceInfo) -> Arphrd {
+    if interface.is_tun {
+        Arphrd::UnrecognizedVariant(ARPHRD_NONE)
+    } else if interface.is_loopback {
+        Arphrd::Loopback
+    } else {
+        Arphrd::Ether
+    }
+}
+
+// The scope of a network interface's address
+fn if_scope(interface: &InterfaceInfo) -> RtScope {
+    if interface.is_loopback {
+        RtScope::Host
+    } else {
+        RtScope::Universe
+    }
+}
+
+// The struct used to describe a route
+struct Route {
+    table: RtTable,
+    route_type: Rtn,
+    protocol: Rtprot,
     scope: RtScope,
-    index: libc::c_int,
+    dst: Ipv4Addr,
+    dst_len: u8,
+    prefsrc: Option<Ipv4Addr>,
+    interface_index: libc::c_int,
 }
 
 /// Common data and functionality that is useful for all states.
@@ -1038,10 +1143,103 @@ struct NetlinkSocketCommon {
     // this file
     has_open_file: bool,
     /// Interfaces
-    interfaces: Vec<Interface>,
+    interfaces: Vec<InterfaceInfo>,
+    /// The configured routes, and the index of each route's interface
+    routes: Vec<(Subnet, libc::c_int)>,
 }
 
 impl NetlinkSocketCommon {
+    /// The routes of the host, matching the routes that Linux would create for the interfaces and
+    /// the host's configured routes. Packets that no configured route contains are sent on `eth0`,
+    /// so there's a default route through `eth0` unless a configured route replaces it. TUN
+    /// interfaces only have routes once they've been assigned an address, and never have the
+    /// default route. See `ip route show table all`.
+    fn routes(&self) -> Vec<Route> {
+        let mut main_routes = Vec::new();
+        let mut local_routes = Vec::new();
+
+        // unless a configured route replaces it, the default route is through eth0 (index 2)
+        let mut configured_routes = self.routes.clone();
+        if !configured_routes
+            .iter()
+            .any(|(subnet, _)| subnet.prefix_len() == 0)
+        {
+            configured_routes.insert(0, (Subnet::new(Ipv4Addr::UNSPECIFIED, 0), 2));
+        }
+
+        for (subnet, interface_index) in configured_routes {
+            main_routes.push(Route {
+                table: RtTable::Main,
+                route_type: Rtn::Unicast,
+                protocol: Rtprot::Boot,
+                scope: RtScope::Link,
+                dst: subnet.network(),
+                dst_len: subnet.prefix_len(),
+                prefsrc: None,
+                interface_index,
+            });
+        }
+
+        let interfaces = self.interfaces.iter();
+        for interface in interfaces.filter(|x| !x.address.is_unspecified()) {
+            let is_loopback = interface.is_loopback;
+
+            if !is_loopback {
+                main_routes.push(Route {
+                    table: RtTable::Main,
+                    route_type: Rtn::Unicast,
+                    protocol: Rtprot::Kernel,
+                    scope: RtScope::Link,
+                    dst: interface.network(),
+                    dst_len: interface.prefix_len,
+                    prefsrc: Some(interface.address),
+                    interface_index: interface.index,
+                });
+            }
+
+            if is_loopback {
+                local_routes.push(Route {
+                    table: RtTable::Local,
+                    route_type: Rtn::Local,
+                    protocol: Rtprot::Kernel,
+                    scope: RtScope::Host,
+                    dst: interface.network(),
+                    dst_len: interface.prefix_len,
+                    prefsrc: Some(interface.address),
+                    interface_index: interface.index,
+                });
+            }
+            local_routes.push(Route {
+                table: RtTable::Local,
+                route_type: Rtn::Local,
+                protocol: Rtprot::Kernel,
+                scope: RtScope::Host,
+                dst: interface.address,
+                dst_len: 32,
+                prefsrc: Some(interface.address),
+                interface_index: interface.index,
+            });
+            // point-to-point interfaces don't have a broadcast address
+            if interface.is_tun {
+                continue;
+            }
+            local_routes.push(Route {
+                table: RtTable::Local,
+                route_type: Rtn::Broadcast,
+                protocol: Rtprot::Kernel,
+                scope: RtScope::Link,
+                dst: interface.broadcast(),
+                dst_len: 32,
+                prefsrc: Some(interface.address),
+                interface_index: interface.index,
+            });
+        }
+
+        // Linux lists the main table before the local table
+        main_routes.extend(local_routes);
+        main_routes
+    }
+
     pub fn supports_sa_restart(&self) -> bool {
         true
     }
@@ -18,11 +18,11 @@ use crate::host::descriptor::shared_buf::{
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
 use crate::host::descriptor::{
-    File, FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
+    CompatFile, File, FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
 };
 use crate::host::memory_manager::MemoryManager;
 use crate::host::network::namespace::NetworkNamespace;
-use crate::host::syscall::io::{IoVec, IoVecReader, IoVecWriter};
+use crate::host::syscall::io::{self, IoVec, IoVecReader, IoVecWriter};
 use crate::host::syscall::types::SyscallError;
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::{SockaddrStorage, SockaddrUnix};
@@ -44,6 +44,7 @@ impl UnixSocket {
         status: FileStatus,
         socket_type: UnixSocketType,
         namespace: &Arc<AtomicRefCell<AbstractUnixNamespace>>,
+        creds: libc::ucred,
     ) -> Arc<AtomicRefCell<Self>> {
         Arc::new_cyclic(|weak| {
             // each socket tracks its own send limit, and we let the receiver have an unlimited recv
@@ -60,6 +61,12 @@ impl UnixSocket {
                 status,
                 socket_type,
                 namespace: Arc::clone(namespace),
+                recv_control: VecDeque::new(),
+                recv_written: 0,
+                recv_read: 0,
+                creds,
+                peer_creds: None,
+                pass_creds: false,
                 has_open_file: false,
             };
 
@@ -119,6 +126,10 @@ impl UnixSocket {
         linux_api::socket::AddressFamily::AF_UNIX
     }
 
+    pub fn socket_type(&self) -> UnixSocketType {
+        self.common.socket_type
+    }
+
     fn recv_buffer(&self) -> &Arc<AtomicRefCell<SharedBuf>> {
         &self.common.recv_buffer
     }
@@ -260,37 +271,88 @@ impl UnixSocket {
 
     pub fn getsockopt(
         &mut self,
-        _level: libc::c_int,
-        _optname: libc::c_int,
-        _optval_ptr: ForeignPtr<()>,
-        _optlen: libc::socklen_t,
-        _memory_manager: &mut MemoryManager,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &mut MemoryManager,
         _cb_queue: &mut CallbackQueue,
     ) -> Result<libc::socklen_t, SyscallError> {
-        log::warn!("getsockopt() syscall not yet supported for unix sockets; Returning ENOSYS");
-        Err(Errno::ENOSYS.into())
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+                let pass_creds = libc::c_int::from(self.common.pass_creds);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    io::write_partial(memory_manager, &pass_creds, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_PEERCRED) => {
+                // like linux, a socket without a connected peer has a pid of 0 and invalid ids
+                let peer_creds = self.common.peer_creds.unwrap_or(libc::ucred {
+                    pid: 0,
+                    uid: libc::uid_t::MAX,
+                    gid: libc::gid_t::MAX,
+                });
+
+                let optval_ptr = optval_ptr.cast::<libc::ucred>();
+                let bytes_written =
+                    io::write_partial(memory_manager, &peer_creds, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            _ => {
+                log::warn!(
+                    "getsockopt() not yet supported for unix sockets with level {level} and opt \
+                    {optname}; Returning ENOSYS"
+                );
+                Err(Errno::ENOSYS.into())
+            }
+        }
     }
 
     pub fn setsockopt(
         &mut self,
-        _level: libc::c_int,
-        _optname: libc::c_int,
-        _optval_ptr: ForeignPtr<()>,
-        _optlen: libc::socklen_t,
-        _memory_manager: &MemoryManager,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &MemoryManager,
     ) -> Result<(), SyscallError> {
-        log::warn!("setsockopt() syscall not yet supported for unix sockets; Returning ENOSYS");
-        Err(Errno::ENOSYS.into())
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = memory_manager.read(optval_ptr)?;
+
+                self.common.pass_creds = val != 0;
+                Ok(())
+            }
+            _ => {
+                log::warn!(
+                    "setsockopt() not yet supported for unix sockets with level {level} and opt \
+                    {optname}; Returning ENOSYS"
+                );
+                Err(Errno::ENOSYS.into())
+            }
+        }
     }
 
     pub fn pair(
         status: FileStatus,
         socket_type: UnixSocketType,
         namespace: &Arc<AtomicRefCell<AbstractUnixNamespace>>,
+        creds: libc::ucred,
         cb_queue: &mut CallbackQueue,
     ) -> (Arc<AtomicRefCell<Self>>, Arc<AtomicRefCell<Self>>) {
-        let socket_1 = UnixSocket::new(status, socket_type, namespace);
-        let socket_2 = UnixSocket::new(status, socket_type, namespace);
+        let socket_1 = UnixSocket::new(status, socket_type, namespace, creds);
+        let socket_2 = UnixSocket::new(status, socket_type, namespace, creds);
 
         {
             let socket_1_ref = &mut *socket_1.borrow_mut();
@@ -303,6 +365,7 @@ impl UnixSocket {
                     cb_queue,
                 )
                 .unwrap();
+            socket_1_ref.common.peer_creds = Some(creds);
         }
 
         {
@@ -316,6 +379,7 @@ impl UnixSocket {
                     cb_queue,
                 )
                 .unwrap();
+            socket_2_ref.common.peer_creds = Some(creds);
         }
 
         (socket_1, socket_2)
@@ -1153,6 +1217,7 @@ impl Protocol for ConnOrientedInitial {
         // inform the server socket of the incoming connection and get the server socket's new child
         // socket
         let server_mut = &mut *server.borrow_mut();
+        let server_creds = server_mut.common.creds;
         let peer = match server_mut.protocol_state.queue_incoming_conn(
             &mut server_mut.common,
             self.bound_addr,
@@ -1180,6 +1245,10 @@ impl Protocol for ConnOrientedInitial {
             }
         };
 
+        // like linux, each side of the connection gets the other's credentials (`SO_PEERCRED`)
+        common.peer_creds = Some(server_creds);
+        peer.borrow_mut().common.peer_creds = Some(common.creds);
+
         // our send buffer will be the peer's receive buffer
         let send_buffer = Arc::clone(peer.borrow().recv_buffer());
 
@@ -1423,6 +1492,7 @@ impl Protocol for ConnOrientedListening {
             common.status,
             common.socket_type,
             &common.namespace,
+            common.creds,
         );
 
         let child_recv_buffer = Arc::clone(&child_socket.borrow_mut().common.recv_buffer);
@@ -1568,13 +1638,17 @@ impl Protocol for ConnOrientedConnected {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for sendmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
         let recv_socket = common.resolve_destination(Some(&self.peer), args.addr)?;
-        let rv = common.sendmsg(socket, args.iovs, args.flags, &recv_socket, mem, cb_queue)?;
+        let rv = common.sendmsg(
+            socket,
+            args.iovs,
+            args.flags,
+            args.rights,
+            args.creds,
+            &recv_socket,
+            mem,
+            cb_queue,
+        )?;
 
         self.refresh_file_state(common, FileSignals::empty(), cb_queue);
 
@@ -1589,13 +1663,9 @@ impl Protocol for ConnOrientedConnected {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for recvmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let (rv, num_removed_from_buf, msg_flags) =
+        let (rv, num_removed_from_buf, msg_flags, control) =
             common.recvmsg(socket, args.iovs, args.flags, mem, cb_queue)?;
+        let (rights, creds) = control.map(|x| (x.files, x.creds)).unwrap_or_default();
         let num_removed_from_buf = u64::try_from(num_removed_from_buf).unwrap();
 
         if num_removed_from_buf > 0 {
@@ -1614,6 +1684,8 @@ impl Protocol for ConnOrientedConnected {
             addr: self.peer_addr.map(Into::into),
             msg_flags,
             control_len: 0,
+            rights,
+            creds,
         })
     }
 
@@ -1775,13 +1847,17 @@ impl Protocol for ConnLessInitial {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for sendmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
         let recv_socket = common.resolve_destination(self.peer.as_ref(), args.addr)?;
-        let rv = common.sendmsg(socket, args.iovs, args.flags, &recv_socket, mem, cb_queue)?;
+        let rv = common.sendmsg(
+            socket,
+            args.iovs,
+            args.flags,
+            args.rights,
+            args.creds,
+            &recv_socket,
+            mem,
+            cb_queue,
+        )?;
 
         let byte_data = ByteData {
             from_socket: self.this_socket.upgrade().unwrap(),
@@ -1812,13 +1888,9 @@ impl Protocol for ConnLessInitial {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for recvmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let (rv, num_removed_from_buf, msg_flags) =
+        let (rv, num_removed_from_buf, msg_flags, control) =
             common.recvmsg(socket, args.iovs, args.flags, mem, cb_queue)?;
+        let (rights, creds) = control.map(|x| (x.files, x.creds)).unwrap_or_default();
         let num_removed_from_buf = u64::try_from(num_removed_from_buf).unwrap();
 
         let byte_data = self.recv_data.pop_front().unwrap();
@@ -1839,6 +1911,8 @@ impl Protocol for ConnLessInitial {
             addr: byte_data.from_addr.map(Into::into),
             msg_flags,
             control_len: 0,
+            rights,
+            creds,
         })
     }
 
@@ -1969,6 +2043,21 @@ struct UnixSocketCommon {
     status: FileStatus,
     socket_type: UnixSocketType,
     namespace: Arc<AtomicRefCell<AbstractUnixNamespace>>,
+    /// Files and credentials passed to this socket in control messages that haven't been received
+    /// yet.
+    recv_control: VecDeque<PassedControl>,
+    /// The number of bytes (stream sockets) or messages (message-based sockets) that have been
+    /// written to the receive buffer.
+    recv_written: u64,
+    /// The number of bytes (stream sockets) or messages (message-based sockets) that have been
+    /// read from the receive buffer.
+    recv_read: u64,
+    /// The credentials of the process that created the socket.
+    creds: libc::ucred,
+    /// The credentials of the peer socket when the connection was made (`SO_PEERCRED`).
+    peer_creds: Option<libc::ucred>,
+    /// Whether received messages include the sender's credentials (`SO_PASSCRED`).
+    pass_creds: bool,
     // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
     // this file
     has_open_file: bool,
@@ -1995,6 +2084,13 @@ impl UnixSocketCommon {
             debug_panic!("When closing a unix socket, the CLOSED flag was not set");
         }
 
+        // like linux, close any passed files that were never received; closing them may close
+        // other sockets (for example our peer), so defer it until we're done with this socket
+        let control = std::mem::take(&mut self.recv_control);
+        if !control.is_empty() {
+            cb_queue.add(move |_cb_queue| drop(control));
+        }
+
         Ok(())
     }
 
@@ -2101,6 +2197,8 @@ impl UnixSocketCommon {
         socket: &Arc<AtomicRefCell<UnixSocket>>,
         iovs: &[IoVec],
         flags: libc::c_int,
+        rights: Vec<CompatFile>,
+        creds: Option<libc::ucred>,
         peer: &Arc<AtomicRefCell<UnixSocket>>,
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
@@ -2130,8 +2228,9 @@ impl UnixSocketCommon {
 
         // run in a closure so that an early return doesn't return from the syscall handler
         let result = (|| {
-            let peer_ref = peer.borrow();
-            let mut send_buffer = peer_ref.recv_buffer().borrow_mut();
+            let mut peer_ref = peer.borrow_mut();
+            let peer_common = &mut peer_ref.common;
+            let mut send_buffer = peer_common.recv_buffer.borrow_mut();
 
             // if the buffer has no readers, the destination socket is closed
             if send_buffer.num_readers() == 0 {
@@ -2175,24 +2274,41 @@ impl UnixSocketCommon {
             let reader = IoVecReader::new(iovs, mem);
             let reader = reader.take(len.try_into().unwrap());
 
-            let num_copied = match self.socket_type {
+            let (num_copied, num_written) = match self.socket_type {
                 UnixSocketType::Stream => {
-                    if len == 0 {
+                    let num_copied = if len == 0 {
                         0
                     } else {
                         send_buffer
                             .write_stream(reader, len, cb_queue)
                             .map_err(|e| Errno::try_from(e).unwrap())?
-                    }
+                    };
+                    (num_copied, u64::try_from(num_copied).unwrap())
                 }
                 UnixSocketType::Dgram | UnixSocketType::SeqPacket => {
                     send_buffer
                         .write_packet(reader, len, cb_queue)
                         .map_err(|e| Errno::try_from(e).unwrap())?;
-                    len
+                    (len, 1)
                 }
             };
 
+            // like linux, only pass our credentials if either socket has `SO_PASSCRED` enabled
+            let creds = creds.filter(|_| self.pass_creds || peer_common.pass_creds);
+
+            // the passed files and credentials are received with the data we wrote; like linux,
+            // files sent without any stream data are dropped
+            let start = peer_common.recv_written;
+            peer_common.recv_written += num_written;
+            if (!rights.is_empty() || creds.is_some()) && num_written > 0 {
+                peer_common.recv_control.push_back(PassedControl {
+                    start,
+                    end: peer_common.recv_written,
+                    files: rights,
+                    creds,
+                });
+            }
+
             // if we successfully sent bytes, update the sent count
             self.sent_len += u64::try_from(num_copied).unwrap();
 
@@ -2220,7 +2336,7 @@ impl UnixSocketCommon {
         flags: libc::c_int,
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
-    ) -> Result<(usize, usize, libc::c_int), SyscallError> {
+    ) -> Result<(usize, usize, libc::c_int, Option<PassedControl>), SyscallError> {
         let supported_flags = MsgFlags::MSG_DONTWAIT | MsgFlags::MSG_TRUNC;
 
         // if there's a flag we don't support, it's probably best to raise an error rather than do
@@ -2253,12 +2369,38 @@ impl UnixSocketCommon {
                 return Err(Errno::EWOULDBLOCK);
             }
 
-            let writer = IoVecWriter::new(iovs, mem);
+            // like linux, a stream read stops after data that was sent with files or credentials,
+            // so that they're received with the correct data
+            let iovs = match (self.socket_type, self.recv_control.front()) {
+                (UnixSocketType::Stream, Some(control)) => {
+                    let limit = usize::try_from(control.end - self.recv_read).unwrap();
+                    io::iovecs_before(iovs, limit)
+                }
+                _ => iovs.to_vec(),
+            };
+
+            let writer = IoVecWriter::new(&iovs, mem);
 
             let (num_copied, num_removed_from_buf) = recv_buffer
                 .read(writer, cb_queue)
                 .map_err(|e| Errno::try_from(e).unwrap())?;
 
+            self.recv_read += match self.socket_type {
+                UnixSocketType::Stream => u64::try_from(num_removed_from_buf).unwrap(),
+                UnixSocketType::Dgram | UnixSocketType::SeqPacket => 1,
+            };
+
+            // the files and credentials are received with the first read of any of the data they
+            // were sent with, and the credentials are only received if `SO_PASSCRED` is enabled
+            let control = match self.recv_control.front() {
+                Some(control) if control.start < self.recv_read => {
+                    let mut control = self.recv_control.pop_front().unwrap();
+                    control.creds = control.creds.filter(|_| self.pass_creds);
+                    Some(control)
+                }
+                _ => None,
+            };
+
             let mut msg_flags = 0;
 
             if flags.contains(MsgFlags::MSG_TRUNC)
@@ -2270,11 +2412,16 @@ impl UnixSocketCommon {
 
                 // we're a message-based socket and MSG_TRUNC is set, so return the total size of
                 // the message, not the number of bytes we read
-                Ok((num_removed_from_buf, num_removed_from_buf, msg_flags))
+                Ok((
+                    num_removed_from_buf,
+                    num_removed_from_buf,
+                    msg_flags,
+                    control,
+                ))
             } else {
                 // We're a stream-based socket. Unlike TCP sockets, unix stream sockets ignore the
                 // MSG_TRUNC flag.
-                Ok((num_copied, num_removed_from_buf, msg_flags))
+                Ok((num_copied, num_removed_from_buf, msg_flags, control))
             }
         })();
 
@@ -2407,6 +2554,18 @@ enum IncomingConnError {
     NotSupported,
 }
 
+/// Files (`SCM_RIGHTS`) and credentials (`SCM_CREDENTIALS`) passed to a socket. They're received
+/// with the data that they were sent with, which is identified by its position in the receive
+/// buffer.
+struct PassedControl {
+    /// The position of the first byte or message that they were sent with.
+    start: u64,
+    /// The position following the last byte or message that they were sent with.
+    end: u64,
+    files: Vec<CompatFile>,
+    creds: Option<libc::ucred>,
+}
+
 struct ByteData {
     from_socket: Arc<AtomicRefCell<UnixSocket>>,
     from_addr: Option<SockaddrUnix<libc::sockaddr_un>>,
@@ -10,6 +10,7 @@
 #include <errno.h>
 #include <math.h>
 #include <netinet/tcp.h>
+#include <signal.h>
 #include <stdarg.h>
 #include <stdbool.h>
 #include <stddef.h>
@@ -64,6 +65,7 @@ enum TCPFlags {
     TCPF_WAS_ESTABLISHED = 1 << 6,
     TCPF_CONNECT_SIGNAL_NEEDED = 1 << 7,
     TCPF_SHOULD_SEND_WR_FIN = 1 << 8,
+    TCPF_RESET_RD_SIGNALED = 1 << 9,
 };
 
 enum TCPError {
@@ -84,6 +86,16 @@ enum TCPReceiveState {
     TCPRS_LOSS = 2,
 };
 
+typedef enum TCPUrgentState TCPUrgentState;
+enum TCPUrgentState {
+    /* there is no urgent mark in the unread data */
+    TCPUS_NONE = 0,
+    /* the urgent byte can be read with MSG_OOB */
+    TCPUS_VALID = 1,
+    /* the urgent byte was already read with MSG_OOB */
+    TCPUS_READ = 2,
+};
+
 typedef struct _TCPChild TCPChild;
 struct _TCPChild {
     enum TCPChildState state;
@@ -127,6 +139,9 @@ struct _TCP {
     enum TCPFlags flags;
     enum TCPError error;
 
+    /* how long we stay in TIME_WAIT, twice the host's maximum segment lifetime */
+    CSimulationTime timeWaitDuration;
+
     /* sequence numbers we track for incoming packets */
     struct {
         /* state that the receive TCP is in (Open,Recovery,Loss) */
@@ -173,8 +188,13 @@ struct _TCP {
         guint32 numQuickACKsSent;
         gboolean delayedACKIsScheduled;
         guint32 delayedACKCounter;
+        /* how long we wait before sending a delayed ACK, after the quick ACKs */
+        CSimulationTime delayedACKTimeout;
         /* list of selective ACKs, packets received after a missing packet */
         GList* selectiveACKs;
+        /* when the next data packet may be sent if the congestion control is pacing */
+        CSimulationTime nextPacingTime;
+        gboolean pacingFlushIsScheduled;
     } send;
 
     struct {
@@ -184,6 +204,10 @@ struct _TCP {
         gsize queueLength;
         /* retransmission timeout value (rto), in milliseconds */
         gint timeout;
+        /* the initial rto and the bounds of the rto from the host's options, in milliseconds */
+        gint initTimeout;
+        gint minTimeout;
+        gint maxTimeout;
         /* when the scheduled timer events will expire; empty if no retransmit is scheduled */
         PriorityQueue* scheduledTimerExpirations;
         /* our updated expiration time, to determine if previous events are still valid */
@@ -205,12 +229,78 @@ struct _TCP {
         gsize space;
     } autotune;
 
+    /* the SO_KEEPALIVE option, which we store but don't act on */
+    gboolean keepAlive;
+
+    /* the most recent urgent (out-of-band) byte that we received */
+    struct {
+        TCPUrgentState state;
+        /* the urgent mark: the sequence of the packet with the urgent byte, and its offset */
+        guint32 sequence;
+        guint16 offset;
+        guint8 byte;
+        /* the SO_OOBINLINE option; if not set, the urgent byte is removed from the stream */
+        gboolean isInline;
+    } urgent;
+
+    /* the process (if positive) or process group (if negative) that F_SETOWN set to receive
+     * SIGURG, or 0 if none */
+    pid_t owner;
+
+    /* the SO_LINGER option; a negative timeout means that close waits indefinitely */
+    struct {
+        gboolean isEnabled;
+        gint seconds;
+    } linger;
+
+    /* selective acknowledgments (RFC 2018) */
+    struct {
+        /* before the handshake, whether we offer SACK; after, whether both sides offered it */
+        gboolean isEnabled;
+        /* a duplicate segment to report in our next ACK (RFC 2883) */
+        gboolean duplicatePending;
+        guint32 duplicateSequence;
+    } sack;
+
+    /* explicit congestion notification (RFC 3168) */
+    struct {
+        /* before the handshake, whether we request ECN; after, whether both sides agreed to it */
+        gboolean isEnabled;
+        /* we received a CE mark, so we set ECE on our packets until the peer sends CWR */
+        gboolean echoPending;
+        /* we reduced our window in response to ECE, so we set CWR on our next data packet */
+        gboolean cwrPending;
+        /* we only reduce our window once per window of data, so we ignore ECE until this packet
+         * is acknowledged */
+        guint32 recoveryPoint;
+    } ecn;
+
+    /* TCP Fast Open (RFC 7413) */
+    struct {
+        /* the TCP_FASTOPEN option of a listener; 0 if it doesn't accept SYN data */
+        gint queueLength;
+        /* whether our SYN or SYN-ACK has the Fast Open option */
+        gboolean isEnabled;
+        /* the cookie in our SYN or SYN-ACK; if a client SYN has no cookie, it requests one */
+        gboolean hasCookie;
+        guint64 cookie;
+        /* a client's SYN with data, and the sequence of the data, until the SYN is acked */
+        Packet* synData;
+        guint32 dataSequence;
+        /* a server child received data with a valid cookie before completing the handshake */
+        gboolean isSynDataAccepted;
+        /* the server acknowledged the data in our SYN */
+        gboolean isSynDataAcked;
+    } fastOpen;
+
     /* congestion object for implementing different types of congestion control (aimd, reno, cubic) */
     TCPCong cong;
 
     struct {
       gint rttSmoothed;
       gint rttVariance;
+      /* the most recent RTT sample */
+      gint rttLatest;
     } timing;
 
     /* TODO: these should probably be stamped when the network interface sends
@@ -390,6 +480,21 @@ struct TCPCong_ *tcp_cong(TCP *tcp) {
     return &tcp->cong;
 }
 
+gint tcp_getSmoothedRTT(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->timing.rttSmoothed;
+}
+
+gint tcp_getLatestRTT(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->timing.rttLatest;
+}
+
+guint32 tcp_getPacketsInFlight(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->send.next - tcp->send.unacked;
+}
+
 void tcp_clearAllChildrenIfServer(TCP* tcp) {
     MAGIC_ASSERT(tcp);
     if(tcp->server && tcp->server->children) {
@@ -440,7 +545,7 @@ static guint _tcp_calculateRTT(TCP* tcp, const Host* host) {
         if(destinationIP == htonl(INADDR_LOOPBACK)) {
             sourceIP = htonl(INADDR_LOOPBACK);
         } else {
-            sourceIP = host_getDefaultIP(host);
+            sourceIP = host_getSourceIP(host, destinationIP);
         }
     }
 
@@ -484,12 +589,24 @@ static gsize _tcp_computeRTTMEM(TCP* tcp, const Host* host, gboolean isRMEM) {
 }
 
 static gsize _tcp_computeMaxRMEM(TCP* tcp, const Host* host) {
+    guint64 min = 0, initial = 0, max = 0;
+    if (host_getTCPRecvBufLimits(host, &min, &initial, &max)) {
+        /* like linux, the configured 'tcp_rmem' maximum is a hard limit */
+        return (gsize)max;
+    }
+
     gsize mem = _tcp_computeRTTMEM(tcp, host, TRUE);
     mem = CLAMP(mem, CONFIG_TCP_RMEM_MAX, CONFIG_TCP_RMEM_MAX*10);
     return mem;
 }
 
 static gsize _tcp_computeMaxWMEM(TCP* tcp, const Host* host) {
+    guint64 min = 0, initial = 0, max = 0;
+    if (host_getTCPSendBufLimits(host, &min, &initial, &max)) {
+        /* like linux, the configured 'tcp_wmem' maximum is a hard limit */
+        return (gsize)max;
+    }
+
     gsize mem = _tcp_computeRTTMEM(tcp, host, FALSE);
     mem = CLAMP(mem, CONFIG_TCP_WMEM_MAX, CONFIG_TCP_WMEM_MAX*10);
     return mem;
@@ -510,6 +627,13 @@ static void _tcp_tuneInitialBufferSizes(TCP* tcp, const Host* host) {
      */
     tcp->autotune.didInitializeBufferSizes = TRUE;
 
+    /* the bounds of the tuned sizes, which the host may override with 'tcp_rmem' and 'tcp_wmem' */
+    guint64 receiveMin = CONFIG_RECV_BUFFER_MIN_SIZE, receiveMax = CONFIG_TCP_RMEM_MAX;
+    guint64 sendMin = CONFIG_SEND_BUFFER_MIN_SIZE, sendMax = CONFIG_TCP_WMEM_MAX;
+    guint64 initial = 0;
+    host_getTCPRecvBufLimits(host, &receiveMin, &initial, &receiveMax);
+    host_getTCPSendBufLimits(host, &sendMin, &initial, &sendMax);
+
     /* addresses are in network byte order */
     in_addr_t sourceIP = tcp_getIP(tcp);
     in_addr_t destinationIP = tcp_getPeerIP(tcp);
@@ -519,7 +643,7 @@ static void _tcp_tuneInitialBufferSizes(TCP* tcp, const Host* host) {
         if(destinationIP == htonl(INADDR_LOOPBACK)) {
             sourceIP = htonl(INADDR_LOOPBACK);
         } else {
-            sourceIP = host_getDefaultIP(host);
+            sourceIP = host_getSourceIP(host, destinationIP);
         }
     }
 
@@ -530,12 +654,12 @@ static void _tcp_tuneInitialBufferSizes(TCP* tcp, const Host* host) {
 
         /* localhost always gets adjusted unless user explicitly set a set */
         if(!tcp->autotune.userDisabledReceive) {
-            legacysocket_setInputBufferSize(&(tcp->super), (gsize) CONFIG_TCP_RMEM_MAX);
-            trace("set loopback receive buffer size to %"G_GSIZE_FORMAT, (gsize)CONFIG_TCP_RMEM_MAX);
+            legacysocket_setInputBufferSize(&(tcp->super), (gsize) receiveMax);
+            trace("set loopback receive buffer size to %"G_GSIZE_FORMAT, (gsize)receiveMax);
         }
         if(!tcp->autotune.userDisabledSend) {
-            legacysocket_setOutputBufferSize(&(tcp->super), (gsize) CONFIG_TCP_WMEM_MAX);
-            trace("set loopback send buffer size to %"G_GSIZE_FORMAT, (gsize)CONFIG_TCP_WMEM_MAX);
+            legacysocket_setOutputBufferSize(&(tcp->super), (gsize) sendMax);
+            trace("set loopback send buffer size to %"G_GSIZE_FORMAT, (gsize)sendMax);
         }
 
         tcp->info.rtt = G_MAXUINT32; // not sure why this is here
@@ -566,8 +690,8 @@ static void _tcp_tuneInitialBufferSizes(TCP* tcp, const Host* host) {
     guint64 receivebuf_size = (guint64) ((rtt_milliseconds * receive_bottleneck_bw * 1024.0f * 1.25f) / 1000.0f);
 
     /* keep minimum buffer size bounds */
-    sendbuf_size = CLAMP(sendbuf_size, CONFIG_SEND_BUFFER_MIN_SIZE, CONFIG_TCP_WMEM_MAX);
-    receivebuf_size = CLAMP(receivebuf_size, CONFIG_RECV_BUFFER_MIN_SIZE, CONFIG_TCP_RMEM_MAX);
+    sendbuf_size = CLAMP(sendbuf_size, sendMin, sendMax);
+    receivebuf_size = CLAMP(receivebuf_size, receiveMin, receiveMax);
 
     /* check to see if the node should set buffer sizes via autotuning, or
      * they were specified by configuration or parameters in XML */
@@ -653,6 +777,102 @@ void tcp_disableReceiveBufferAutotuning(TCP* tcp) {
     tcp->autotune.userDisabledReceive = TRUE;
 }
 
+void tcp_setKeepAlive(TCP* tcp, gboolean enabled) {
+    MAGIC_ASSERT(tcp);
+    tcp->keepAlive = enabled;
+}
+
+gboolean tcp_getKeepAlive(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->keepAlive;
+}
+
+void tcp_setUrgentInline(TCP* tcp, gboolean enabled) {
+    MAGIC_ASSERT(tcp);
+    tcp->urgent.isInline = enabled;
+}
+
+gboolean tcp_getUrgentInline(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->urgent.isInline;
+}
+
+void tcp_setOwner(TCP* tcp, pid_t owner) {
+    MAGIC_ASSERT(tcp);
+    tcp->owner = owner;
+}
+
+pid_t tcp_getOwner(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->owner;
+}
+
+void tcp_setLinger(TCP* tcp, gboolean enabled, gint seconds) {
+    MAGIC_ASSERT(tcp);
+    tcp->linger.isEnabled = enabled;
+    tcp->linger.seconds = seconds;
+}
+
+gboolean tcp_getLinger(TCP* tcp, gint* seconds) {
+    MAGIC_ASSERT(tcp);
+    if (seconds != NULL) {
+        *seconds = tcp->linger.seconds;
+    }
+    return tcp->linger.isEnabled;
+}
+
+gint tcp_setFastOpenQueueLength(TCP* tcp, gint queueLength) {
+    MAGIC_ASSERT(tcp);
+
+    /* like linux, the option can only be set before connecting or on a listener */
+    if (queueLength < 0 || (tcp->state != TCPS_CLOSED && tcp->state != TCPS_LISTEN)) {
+        return -EINVAL;
+    }
+
+    tcp->fastOpen.queueLength = queueLength;
+    return 0;
+}
+
+gint tcp_getFastOpenQueueLength(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->fastOpen.queueLength;
+}
+
+/* The TCP Fast Open cookie that we give to the client at `clientIP`. */
+static guint64 _tcp_getFastOpenCookie(const Host* host, in_addr_t clientIP) {
+    /* a keyed hash (splitmix64) of the client's address */
+    guint64 x = host_getTCPFastOpenKey(host) ^ (guint64)ntohl(clientIP);
+    x += 0x9e3779b97f4a7c15ULL;
+    x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9ULL;
+    x = (x ^ (x >> 27)) * 0x94d049bb133111ebULL;
+    return x ^ (x >> 31);
+}
+
+/* Like linux, a child socket starts with the socket options of the listening socket. */
+static void _tcp_inheritListenerOptions(TCP* child, TCP* listener) {
+    MAGIC_ASSERT(child);
+    MAGIC_ASSERT(listener);
+
+    /* buffer sizes that were set by the user replace the configured sizes and autotuning */
+    if (listener->autotune.userDisabledReceive) {
+        legacysocket_setInputBufferSize(
+            &child->super, legacysocket_getInputBufferSize(&listener->super));
+        child->autotune.userDisabledReceive = TRUE;
+    }
+    if (listener->autotune.userDisabledSend) {
+        legacysocket_setOutputBufferSize(
+            &child->super, legacysocket_getOutputBufferSize(&listener->super));
+        child->autotune.userDisabledSend = TRUE;
+    }
+
+    child->keepAlive = listener->keepAlive;
+    child->urgent.isInline = listener->urgent.isInline;
+    child->linger = listener->linger;
+
+    const char* congestion = tcpcong_nameStr(&listener->cong);
+    tcpcong_set(child, congestion, strlen(congestion));
+}
+
 // XXX declaration
 static void _tcp_runCloseTimerExpiredTask(const Host* host, gpointer tcp, gpointer userData);
 static void _tcp_clearRetransmit(TCP* tcp, guint sequence);
@@ -752,11 +972,11 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
             TaskRef* closeTask =
                 taskref_new_bound(host_getID(host), _tcp_runCloseTimerExpiredTask,
                                   (void*)inetSocket, NULL, inetsocket_dropVoid, NULL);
-            CSimulationTime delay = CONFIG_TCPCLOSETIMER_DELAY;
+            CSimulationTime delay = tcp->timeWaitDuration;
 
             /* if a child of a server initiated the close, close more quickly */
             if(tcp->child && tcp->child->parent) {
-                delay = SIMTIME_ONE_SECOND;
+                delay = MIN(delay, SIMTIME_ONE_SECOND);
             }
 
             host_scheduleTaskWithDelay(host, closeTask, delay);
@@ -766,6 +986,11 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
         default:
             break;
     }
+
+    /* a lingering close() waits until our FIN is acknowledged (or we were reset) */
+    gboolean isDrained = (tcp->flags & TCPF_LOCAL_CLOSED_WR) &&
+                         (state == TCPS_FINWAIT2 || state == TCPS_TIMEWAIT || state == TCPS_CLOSED);
+    legacyfile_adjustStatus((LegacyFile*)tcp, FileState_SOCKET_DRAINED, isDrained, 0);
 }
 
 static void _tcp_runCloseTimerExpiredTask(const Host* host, gpointer voidInetSocket,
@@ -902,7 +1127,7 @@ static Packet* _tcp_createPacketWithoutPayload(TCP* tcp, const Host* host,
         if(destinationIP == htonl(INADDR_LOOPBACK)) {
             sourceIP = htonl(INADDR_LOOPBACK);
         } else {
-            sourceIP = host_getDefaultIP(host);
+            sourceIP = host_getSourceIP(host, destinationIP);
         }
     }
 
@@ -917,11 +1142,26 @@ static Packet* _tcp_createPacketWithoutPayload(TCP* tcp, const Host* host,
     gboolean isFinNotAck = ((flags & PTCP_FIN) && !(flags & PTCP_ACK));
     guint sequence = !isEmpty || isFinNotAck || (flags & PTCP_SYN) ? tcp->send.next : 0;
 
+    /* offer SACK during the handshake */
+    if((flags & PTCP_SYN) && tcp->sack.isEnabled) {
+        flags |= PTCP_SACK_PERMITTED;
+    }
+
+    /* an "ECN-setup" SYN has ECE and CWR, and an "ECN-setup" SYN-ACK only has ECE */
+    if((flags & PTCP_SYN) && tcp->ecn.isEnabled) {
+        flags |= (flags & PTCP_ACK) ? PTCP_ECE : (PTCP_ECE | PTCP_CWR);
+    }
+
     /* create the TCP packet. the ack, window, and timestamps will be set in _tcp_flush */
     Packet* packet = packet_new(host);
     packet_setTCP(packet, flags, sourceIP, sourcePort, destinationIP, destinationPort, sequence);
     packet_addDeliveryStatus(packet, PDS_SND_CREATED);
 
+    /* request or send a Fast Open cookie during the handshake */
+    if((flags & PTCP_SYN) && tcp->fastOpen.isEnabled) {
+        packet_setTCPFastOpen(packet, true, tcp->fastOpen.hasCookie, tcp->fastOpen.cookie);
+    }
+
     /* update sequence number */
     if(sequence > 0) {
         tcp->send.next++;
@@ -1130,8 +1370,8 @@ static void _tcp_setRetransmitTimeout(TCP* tcp, gint newTimeout) {
     tcp->retransmit.timeout = newTimeout;
 
     /* ensure correct range */
-    tcp->retransmit.timeout = MIN(tcp->retransmit.timeout, CONFIG_TCP_RTO_MAX);
-    tcp->retransmit.timeout = MAX(tcp->retransmit.timeout, CONFIG_TCP_RTO_MIN);
+    tcp->retransmit.timeout = MIN(tcp->retransmit.timeout, tcp->retransmit.maxTimeout);
+    tcp->retransmit.timeout = MAX(tcp->retransmit.timeout, tcp->retransmit.minTimeout);
 }
 
 static void _tcp_updateRTTEstimate(TCP* tcp, const Host* host, CSimulationTime timestamp) {
@@ -1144,6 +1384,8 @@ static void _tcp_updateRTTEstimate(TCP* tcp, const Host* host, CSimulationTime t
         rtt = 1;
     }
 
+    tcp->timing.rttLatest = rtt;
+
     /* RFC 6298 (http://tools.ietf.org/html/rfc6298) */
     if(!tcp->timing.rttSmoothed) {
         /* first RTT measurement */
@@ -1240,8 +1482,14 @@ void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet*
     CSimulationTime now = worker_getCurrentSimulationTime();
 
     /* update TCP header to our current advertised window and acknowledgment and timestamps */
-    packet_updateTCP(packet, tcp->receive.next, tcp->send.selectiveACKs, tcp->receive.window, 0,
-                     false, now, tcp->receive.lastTimestamp);
+    GList* selectiveACKs = tcp->sack.isEnabled ? tcp->send.selectiveACKs : NULL;
+    packet_updateTCP(packet, tcp->receive.next, selectiveACKs, tcp->receive.window, 0, false, now,
+                     tcp->receive.lastTimestamp);
+
+    /* report a duplicate segment once */
+    packet_setTCPDuplicateSACK(
+        packet, tcp->sack.duplicatePending, tcp->sack.duplicateSequence);
+    tcp->sack.duplicatePending = FALSE;
 
     /* keep track of the last things we sent them */
     tcp->send.lastAcknowledgment = tcp->receive.next;
@@ -1250,6 +1498,24 @@ void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet*
 
     PacketTCPHeader* header = packet_getTCPHeader(packet);
 
+    if(tcp->ecn.isEnabled && !(header->flags & PTCP_SYN)) {
+        /* keep echoing congestion until the peer tells us that it reduced its window */
+        if(tcp->ecn.echoPending) {
+            header->flags |= PTCP_ECE;
+        } else {
+            header->flags &= ~PTCP_ECE;
+        }
+
+        /* only data packets are ECN-capable (RFC 3168, section 6.1.4) */
+        if(packet_getPayloadSize(packet) > 0) {
+            packet_setECN(packet, PECN_ECT_0);
+            if(tcp->ecn.cwrPending) {
+                header->flags |= PTCP_CWR;
+                tcp->ecn.cwrPending = FALSE;
+            }
+        }
+    }
+
     if(header->flags & PTCP_ACK) {
         /* we are sending an ACK already, so we may not need any delayed ACK */
         tcp->send.delayedACKCounter = 0;
@@ -1266,6 +1532,35 @@ void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet*
     }
 }
 
+static void _tcp_runPacingTask(const Host* host, gpointer voidInetSocket, gpointer data) {
+    const InetSocket* inetSocket = voidInetSocket;
+    utility_alwaysAssert(inetSocket != NULL);
+    TCP* tcp = inetsocket_asLegacyTcp(inetSocket);
+    MAGIC_ASSERT(tcp);
+
+    tcp->send.pacingFlushIsScheduled = FALSE;
+    _tcp_flush(tcp, host);
+}
+
+/* Flush again once the pacing rate allows us to send the next data packet. */
+static void _tcp_schedulePacingFlush(TCP* tcp, const Host* host, CSimulationTime now) {
+    MAGIC_ASSERT(tcp);
+
+    if(tcp->send.pacingFlushIsScheduled) {
+        return;
+    }
+
+    utility_alwaysAssert(tcp->rustSocket != NULL);
+    const InetSocket* inetSocket = inetsocketweak_upgrade(tcp->rustSocket);
+    utility_alwaysAssert(inetSocket != NULL);
+    TaskRef* pacingTask = taskref_new_bound(
+        host_getID(host), _tcp_runPacingTask, (void*)inetSocket, NULL, inetsocket_dropVoid, NULL);
+    host_scheduleTaskWithDelay(host, pacingTask, tcp->send.nextPacingTime - now);
+    taskref_drop(pacingTask);
+
+    tcp->send.pacingFlushIsScheduled = TRUE;
+}
+
 static void _tcp_flush(TCP* tcp, const Host* host) {
     MAGIC_ASSERT(tcp);
 
@@ -1332,10 +1627,21 @@ static void _tcp_flush(TCP* tcp, const Host* host) {
                 _rswlog(tcp, "Can't retransmit %d, inWindow=%d, inBuffer=%d\n", header->sequence, fitsInWindow, fitsInBuffer);
                 /* we cant send the packet yet */
                 break;
-            } else {
-                /* we will send the data packet */
-                tcp->info.lastDataSent = now;
             }
+
+            /* if the congestion control is pacing, we cant send it before its departure time */
+            double pacingRate = tcp->cong.pacing_rate;
+            if(pacingRate > 0) {
+                if(now < tcp->send.nextPacingTime) {
+                    _tcp_schedulePacingFlush(tcp, host, now);
+                    break;
+                }
+                tcp->send.nextPacingTime =
+                    now + (CSimulationTime)(SIMTIME_ONE_SECOND / pacingRate);
+            }
+
+            /* we will send the data packet */
+            tcp->info.lastDataSent = now;
         }
 
         /* packet is sendable, we removed it from out buffer */
@@ -1583,6 +1889,11 @@ gint tcp_getConnectionError(TCP* tcp) {
             return -ECONNREFUSED;
         }
 
+        /* the server can already use a connection that sent data with a valid Fast Open cookie */
+        if (tcp->state == TCPS_SYNRECEIVED && tcp->fastOpen.isSynDataAccepted) {
+            return -EISCONN;
+        }
+
         if (tcp->state == TCPS_SYNSENT || tcp->state == TCPS_SYNRECEIVED) {
             return -EALREADY;
         }
@@ -1618,7 +1929,18 @@ void tcp_getInfo(TCP* tcp, struct tcp_info *tcpinfo) {
 //  tcpinfo->tcpi_retransmits;
 //  tcpinfo->tcpi_probes;
 //  tcpinfo->tcpi_backoff;
-//  tcpinfo->tcpi_options;
+    /* options are only known once we receive the peer's SYN */
+    gboolean isNegotiated = tcp->state != TCPS_CLOSED && tcp->state != TCPS_LISTEN &&
+                            tcp->state != TCPS_SYNSENT;
+    if(isNegotiated && tcp->sack.isEnabled) {
+        tcpinfo->tcpi_options |= TCPI_OPT_SACK;
+    }
+    if(isNegotiated && tcp->ecn.isEnabled) {
+        tcpinfo->tcpi_options |= TCPI_OPT_ECN;
+    }
+    if(tcp->fastOpen.isSynDataAcked || tcp->fastOpen.isSynDataAccepted) {
+        tcpinfo->tcpi_options |= TCPI_OPT_SYN_DATA;
+    }
 //  tcpinfo->tcpi_snd_wscale;
 //  tcpinfo->tcpi_rcv_wscale;
 
@@ -1690,6 +2012,58 @@ static gint _tcp_connectToPeer(LegacySocket* socket, const Host* host, in_addr_t
     return -EINPROGRESS;
 }
 
+gssize tcp_connectFastOpen(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
+                           const MemoryManager* mem) {
+    MAGIC_ASSERT(tcp);
+
+    /* Only try to connect if we haven't already started. */
+    gint errorCode = tcp_getConnectionError(tcp);
+    if (errorCode <= 0) {
+        return errorCode;
+    }
+
+    /* listening sockets can't connect */
+    if (tcp_isValidListener(tcp)) {
+        return -EISCONN;
+    }
+
+    tcp->fastOpen.isEnabled = TRUE;
+    tcp->fastOpen.hasCookie =
+        host_getTCPFastOpenCookie(host, tcp_getPeerIP(tcp), &tcp->fastOpen.cookie);
+
+    gsize length = MIN(MIN(nBytes, CONFIG_TCP_MAX_SEGMENT_SIZE), _tcp_getBufferSpaceOut(tcp));
+
+    if (length > 0 && buffer.val == 0) {
+        return -EFAULT;
+    }
+
+    if (!tcp->fastOpen.hasCookie || length == 0) {
+        /* without a cookie, the SYN only requests one and the data is sent after the handshake */
+        _tcp_sendControlPacket(tcp, host, PTCP_SYN);
+    } else {
+        /* the SYN and the data each use a sequence number */
+        Packet* packet = _tcp_createDataPacket(tcp, host, PTCP_SYN, buffer, length, mem);
+        tcp->fastOpen.dataSequence = tcp->send.next;
+        tcp->send.next++;
+        tcp->send.end++;
+
+        _tcp_bufferPacketOut(tcp, packet);
+        _tcp_flush(tcp, host);
+
+        /* keep our ref so that we can resend the data if the server doesn't accept it */
+        tcp->fastOpen.synData = packet;
+    }
+
+    trace("%s <-> %s: user initiated fast open connection with %" G_GSIZE_FORMAT " bytes",
+          tcp->super.boundString, tcp->super.peerString, tcp->fastOpen.synData ? length : 0);
+    _tcp_setState(tcp, host, TCPS_SYNSENT);
+
+    /* We need to signal when it succeeds. */
+    tcp->flags |= TCPF_CONNECT_SIGNAL_NEEDED;
+
+    return tcp->fastOpen.synData ? (gssize)length : -EINPROGRESS;
+}
+
 void tcp_enterServerMode(TCP* tcp, const Host* host, pid_t process, gint backlog) {
     MAGIC_ASSERT(tcp);
 
@@ -1837,6 +2211,16 @@ TCPProcessFlags _tcp_dataProcessing(TCP* tcp, Packet* packet, PacketTCPHeader *h
         /* its too far ahead to accept now, but they should re-send it */
         flags |= TCP_PF_PROCESSED;
         packet_addDeliveryStatus(packet, PDS_RCV_SOCKET_DROPPED);
+    } else if(header->sequence < tcp->receive.next ||
+              g_list_find(tcp->send.selectiveACKs, GINT_TO_POINTER(header->sequence))) {
+        /* we already have it, so they probably didn't get our ACK */
+        flags |= TCP_PF_PROCESSED | TCP_PF_DATA_DUPLICATE;
+        packet_addDeliveryStatus(packet, PDS_RCV_SOCKET_DROPPED);
+
+        if(tcp->sack.isEnabled) {
+            tcp->sack.duplicatePending = TRUE;
+            tcp->sack.duplicateSequence = header->sequence;
+        }
     } else if(header->sequence >= tcp->receive.next) {
         /* its in our window, so we can accept the data */
         flags |= TCP_PF_PROCESSED;
@@ -1961,7 +2345,7 @@ TCPProcessFlags _tcp_ackProcessing(TCP* tcp, const Host* host, Packet* packet,
         if(tcp->retransmit.backoffCount > 2) {
             tcp->timing.rttSmoothed = 0;
             tcp->timing.rttVariance = 0;
-            _tcp_setRetransmitTimeout(tcp, CONFIG_TCP_RTO_INIT);
+            _tcp_setRetransmitTimeout(tcp, tcp->retransmit.initTimeout);
         }
         tcp->retransmit.backoffCount = 0;
     }
@@ -1987,6 +2371,41 @@ TCPProcessFlags _tcp_ackProcessing(TCP* tcp, const Host* host, Packet* packet,
     return flags;
 }
 
+/* Record the urgent byte of a packet that we accepted, and send SIGURG to the socket's owner. */
+static void _tcp_urgentDataReceived(TCP* tcp, const Host* host, Packet* packet,
+                                    PacketTCPHeader* header) {
+    MAGIC_ASSERT(tcp);
+
+    if(header->urgentPointer == 0 || header->urgentPointer > packet_getPayloadSize(packet)) {
+        trace("ignoring invalid urgent pointer %u", (guint)header->urgentPointer);
+        return;
+    }
+
+    if(tcp->urgent.state != TCPUS_NONE && tcp->urgent.sequence == header->sequence) {
+        /* we already have this urgent byte */
+        return;
+    }
+
+    guint16 offset = header->urgentPointer - 1;
+    guint8 byte = 0;
+    guint copied = packet_copyPayloadShadow(packet, offset, &byte, 1);
+    utility_debugAssert(copied == 1);
+
+    /* like linux, a new urgent byte replaces the previous one even if it wasn't read, and the
+     * previous byte is then left in the stream */
+    tcp->urgent.state = TCPUS_VALID;
+    tcp->urgent.sequence = header->sequence;
+    tcp->urgent.offset = offset;
+    tcp->urgent.byte = byte;
+
+    trace("%s <-> %s: received urgent byte at offset %u of packet %u", tcp->super.boundString,
+          tcp->super.peerString, (guint)offset, header->sequence);
+
+    if(tcp->owner != 0) {
+        host_signalFileOwner(host, tcp->owner, SIGURG);
+    }
+}
+
 static void _tcp_logCongestionInfo(TCP* tcp) {
     gsize outSize = legacysocket_getOutputBufferSize(&tcp->super);
     gsize outLength = legacysocket_getOutputBufferLength(&tcp->super);
@@ -2019,6 +2438,136 @@ static void _tcp_sendACKTaskCallback(const Host* host, gpointer voidInetSocket,
     }
 }
 
+/* Returns TRUE if the SYN requests ECN (RFC 3168, section 6.1.1). */
+static gboolean _tcp_isECNSetupSyn(PacketTCPHeader* header) {
+    return (header->flags & (PTCP_ECE | PTCP_CWR)) == (PTCP_ECE | PTCP_CWR);
+}
+
+/* Handle the ECN codepoint and flags of a packet on a connection that negotiated ECN. */
+static void _tcp_ecnProcessing(TCP* tcp, Packet* packet, PacketTCPHeader* header) {
+    MAGIC_ASSERT(tcp);
+
+    /* the peer reduced its window, so we can stop echoing congestion */
+    if(header->flags & PTCP_CWR) {
+        tcp->ecn.echoPending = FALSE;
+    }
+
+    /* a router on the path was congested, so we need to tell the peer */
+    if(packet_getECN(packet) == PECN_CE) {
+        trace("%s <-> %s: packet %u experienced congestion", tcp->super.boundString,
+              tcp->super.peerString, header->sequence);
+        tcp->ecn.echoPending = TRUE;
+    }
+
+    /* the peer saw congestion, so we reduce our window like we would for a lost packet */
+    if((header->flags & PTCP_ECE) && header->acknowledgment > tcp->ecn.recoveryPoint) {
+        debug("[CONG] desc %p peer echoed congestion", (LegacyFile*)tcp);
+        _tcp_logCongestionInfo(tcp);
+        tcp->cong.hooks->tcp_cong_ecn_ev(tcp);
+        tcp->ecn.recoveryPoint = tcp->send.next;
+        tcp->ecn.cwrPending = TRUE;
+    }
+}
+
+/* Whether the listener `tcp` already has as many children that accepted Fast Open data before
+ * completing the handshake as its TCP_FASTOPEN queue length allows. */
+static gboolean _tcp_isFastOpenQueueFull(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    MAGIC_ASSERT(tcp->server);
+
+    gint count = 0;
+    GHashTableIter iter;
+    gpointer value = NULL;
+    g_hash_table_iter_init(&iter, tcp->server->children);
+    while (g_hash_table_iter_next(&iter, NULL, &value)) {
+        TCP* child = value;
+        if (child->state == TCPS_SYNRECEIVED && child->fastOpen.isSynDataAccepted) {
+            count++;
+        }
+    }
+
+    return count >= tcp->fastOpen.queueLength;
+}
+
+/* A listener with TCP Fast Open enabled received a SYN with the Fast Open option, and created the
+ * child `tcp` for it. */
+static void _tcp_fastOpenSynReceived(TCP* tcp, const Host* host, Packet* packet,
+                                     PacketTCPHeader* header) {
+    MAGIC_ASSERT(tcp);
+    MAGIC_ASSERT(tcp->child);
+
+    guint64 cookie = _tcp_getFastOpenCookie(host, header->sourceIP);
+
+    if(!header->fastOpenCookieSet || header->fastOpenCookie != cookie) {
+        /* a cookie request or an invalid cookie, so we send a cookie in the SYN-ACK and the
+         * client will resend any data after the handshake */
+        tcp->fastOpen.isEnabled = TRUE;
+        tcp->fastOpen.hasCookie = TRUE;
+        tcp->fastOpen.cookie = cookie;
+        return;
+    }
+
+    if(packet_getPayloadSize(packet) == 0) {
+        return;
+    }
+
+    trace("%s <-> %s: accepting fast open data", tcp->super.boundString, tcp->super.peerString);
+
+    /* the data follows the SYN's sequence number */
+    Packet* data = packet_copy(packet);
+    PacketTCPHeader* dataHeader = packet_getTCPHeader(data);
+    dataHeader->sequence++;
+    dataHeader->flags &= ~PTCP_SYN;
+    packet_setTCPFastOpen(data, false, false, 0);
+
+    _tcp_dataProcessing(tcp, data, dataHeader);
+    packet_unref(data);
+
+    /* make the data readable and acknowledge it in the SYN-ACK */
+    _tcp_flush(tcp, host);
+
+    /* the user can accept the child before the handshake completes */
+    TCP* parent = tcp->child->parent;
+    tcp->fastOpen.isSynDataAccepted = TRUE;
+    tcp->child->state = TCPCS_PENDING;
+    g_queue_push_tail(parent->server->pending, tcp);
+    legacyfile_adjustStatus(&(parent->super.super), FileState_READABLE, TRUE, 0);
+}
+
+/* We sent a SYN with the Fast Open option and received the SYN-ACK. */
+static void _tcp_fastOpenSynAckReceived(TCP* tcp, const Host* host, PacketTCPHeader* header) {
+    MAGIC_ASSERT(tcp);
+
+    /* remember the cookie for our next connection to this server */
+    if(header->fastOpenCookieSet) {
+        host_setTCPFastOpenCookie(host, header->sourceIP, header->fastOpenCookie);
+    }
+
+    if(!tcp->fastOpen.synData) {
+        return;
+    }
+
+    tcp->fastOpen.isSynDataAcked = header->acknowledgment > tcp->fastOpen.dataSequence;
+
+    /* the server didn't accept the data in our SYN, so we send it again */
+    if(!tcp->fastOpen.isSynDataAcked) {
+        trace("%s <-> %s: server didn't accept fast open data", tcp->super.boundString,
+              tcp->super.peerString);
+
+        Packet* data = packet_copy(tcp->fastOpen.synData);
+        PacketTCPHeader* dataHeader = packet_getTCPHeader(data);
+        dataHeader->sequence = tcp->fastOpen.dataSequence;
+        dataHeader->flags = PTCP_ACK;
+        packet_setTCPFastOpen(data, false, false, 0);
+
+        _tcp_bufferPacketOut(tcp, data);
+        packet_unref(data);
+    }
+
+    packet_unref(tcp->fastOpen.synData);
+    tcp->fastOpen.synData = NULL;
+}
+
 /* return TRUE if the packet should be retransmitted */
 static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* packet) {
     TCP* tcp = _tcp_fromLegacyFile((LegacyFile*)socket);
@@ -2047,6 +2596,10 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
             /* it will send no more user data after what we have now */
             tcp->receive.end = tcp->receive.next;
+
+            /* wake up any readers so that they see the reset */
+            tcp->error |= TCPE_RECEIVE_EOF;
+            legacyfile_adjustStatus((LegacyFile*)tcp, FileState_READABLE, TRUE, 0);
         }
         return;
     }
@@ -2086,6 +2639,9 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
                 guint64 recvBufSize = host_getConfiguredRecvBufSize(host);
                 guint64 sendBufSize = host_getConfiguredSendBufSize(host);
+                guint64 min = 0, max = 0;
+                host_getTCPRecvBufLimits(host, &min, &recvBufSize, &max);
+                host_getTCPSendBufLimits(host, &min, &sendBufSize, &max);
 
                 /* We will register the child socket with whichever process called listen() on the
                  * parent socket. This is incorrect and we should register the child socket with
@@ -2109,6 +2665,7 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
                 /* we need to multiplex a new child */
                 TCP* multiplexed = tcp_new(host, recvBufSize, sendBufSize);
+                _tcp_inheritListenerOptions(multiplexed, tcp);
                 Descriptor* desc = descriptor_fromLegacyTcp(multiplexed, /* flags= */ 0);
                 int handle = thread_registerDescriptor(registerInThread, desc);
 
@@ -2126,6 +2683,8 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
                 multiplexed->receive.start = header->sequence;
                 multiplexed->receive.next = multiplexed->receive.start + 1;
+                multiplexed->sack.isEnabled &= (header->flags & PTCP_SACK_PERMITTED) ? TRUE : FALSE;
+                multiplexed->ecn.isEnabled &= _tcp_isECNSetupSyn(header) ? TRUE : FALSE;
 
                 trace("%s <-> %s: server multiplexed child socket %s <-> %s",
                         tcp->super.boundString, tcp->super.peerString,
@@ -2133,6 +2692,13 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
                 _tcp_setState(multiplexed, host, TCPS_SYNRECEIVED);
 
+                /* like linux, beyond the queue length we ignore the Fast Open option and any
+                 * data, and fall back to the normal handshake */
+                if (header->fastOpenSet && tcp->fastOpen.queueLength > 0 &&
+                    !_tcp_isFastOpenQueueFull(tcp)) {
+                    _tcp_fastOpenSynReceived(multiplexed, host, packet, header);
+                }
+
                 /* child will send response */
                 tcp = multiplexed;
                 responseFlags = PTCP_SYN|PTCP_ACK;
@@ -2148,6 +2714,13 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
                 flags |= TCP_PF_PROCESSED;
                 tcp->receive.start = header->sequence;
                 tcp->receive.next = tcp->receive.start + 1;
+                tcp->sack.isEnabled &= (header->flags & PTCP_SACK_PERMITTED) ? TRUE : FALSE;
+                tcp->ecn.isEnabled &=
+                    ((header->flags & (PTCP_ECE | PTCP_CWR)) == PTCP_ECE) ? TRUE : FALSE;
+
+                if(tcp->fastOpen.isEnabled) {
+                    _tcp_fastOpenSynAckReceived(tcp, host, header);
+                }
 
                 responseFlags |= PTCP_ACK;
                 _tcp_setState(tcp, host, TCPS_ESTABLISHED);
@@ -2157,6 +2730,8 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
                 flags |= TCP_PF_PROCESSED;
                 tcp->receive.start = header->sequence;
                 tcp->receive.next = tcp->receive.start + 1;
+                tcp->sack.isEnabled &= (header->flags & PTCP_SACK_PERMITTED) ? TRUE : FALSE;
+                tcp->ecn.isEnabled &= _tcp_isECNSetupSyn(header) ? TRUE : FALSE;
 
                 responseFlags |= PTCP_ACK;
                 _tcp_setState(tcp, host, TCPS_SYNRECEIVED);
@@ -2171,8 +2746,9 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
                 flags |= TCP_PF_PROCESSED;
                 _tcp_setState(tcp, host, TCPS_ESTABLISHED);
 
-                /* if this is a child, mark it accordingly */
-                if(tcp->child) {
+                /* if this is a child, mark it accordingly. a child that accepted Fast Open
+                 * data is already pending or accepted. */
+                if(tcp->child && tcp->child->state == TCPCS_INCOMPLETE) {
                     tcp->child->state = TCPCS_PENDING;
                     g_queue_push_tail(tcp->child->parent->server->pending, tcp);
                     /* user should accept new child from parent */
@@ -2282,9 +2858,14 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
     trace("state after switch is %s", _tcp_stateToAscii(tcp->state));
 
-    /* if TCPE_RECEIVE_EOF, we are not supposed to receive any more */
-    if(packetLength > 0 && !(tcp->error & TCPE_RECEIVE_EOF)) {
+    /* if TCPE_RECEIVE_EOF, we are not supposed to receive any more. data in a SYN is only
+     * accepted during Fast Open. */
+    if(packetLength > 0 && !(tcp->error & TCPE_RECEIVE_EOF) && !(header->flags & PTCP_SYN)) {
         flags |= _tcp_dataProcessing(tcp, packet, header);
+
+        if((flags & TCP_PF_DATA_RECEIVED) && (header->flags & PTCP_URG)) {
+            _tcp_urgentDataReceived(tcp, host, packet, header);
+        }
     }
 
     if(header->flags & PTCP_ACK) {
@@ -2300,14 +2881,27 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
         return;
     }
 
-    GList* selectiveACKs = packet_copyTCPSelectiveACKs(packet);
+    if(tcp->sack.isEnabled) {
+        GList* selectiveACKs = packet_copyTCPSelectiveACKs(packet);
+
+        if (selectiveACKs) {
+           retransmit_tally_mark_sacked(tcp->retransmit.tally, selectiveACKs);
+        }
+
+        if(selectiveACKs) {
+            g_list_free(selectiveACKs);
+        }
 
-    if (selectiveACKs) {
-       retransmit_tally_mark_sacked(tcp->retransmit.tally, selectiveACKs);
+        if(header->duplicateSACKSet) {
+            /* the duplicate is below the cumulative ack, so it doesn't affect the scoreboard */
+            trace("%s <-> %s: peer received segment %u twice", tcp->super.boundString,
+                  tcp->super.peerString, header->duplicateSACK);
+        }
     }
 
-    if(selectiveACKs) {
-        g_list_free(selectiveACKs);
+    /* the ECN flags on SYNs are only used for negotiation */
+    if(tcp->ecn.isEnabled && !(header->flags & PTCP_SYN)) {
+        _tcp_ecnProcessing(tcp, packet, header);
     }
 
     /* update the last time stamp value (RFC 1323) */
@@ -2326,9 +2920,13 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
       // TODO (rwails): Any special handling for dubious acks?
     }
 
+    /* if they resent data we already have, our ACK may have been lost, so we resend it now */
+    if(flags & TCP_PF_DATA_DUPLICATE) {
+        responseFlags |= PTCP_ACK;
+    }
     /* during fast recovery, out of order data results in a duplicate ack.
      * this ack needs to get sent now. */
-    if (header->sequence > (guint)tcp->receive.next &&
+    else if (header->sequence > (guint)tcp->receive.next &&
             (header->sequence < (guint)(tcp->receive.next + tcp->receive.window))) {
         responseFlags |= (PTCP_ACK|PTCP_DUPACK);
     }
@@ -2349,7 +2947,7 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
         _rswlog(tcp, "Sending control packet on %d\n",
                 header->sequence);
 
-        if(responseFlags != PTCP_ACK) { // includes DUPACKs
+        if(responseFlags != PTCP_ACK || (flags & TCP_PF_DATA_DUPLICATE)) { // includes DUPACKs
             /* just send the response now */
             trace("sending ACK control packet now");
             _tcp_sendControlPacket(tcp, host, responseFlags);
@@ -2373,7 +2971,7 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
                     delay = 1*SIMTIME_ONE_MILLISECOND;
                     tcp->send.numQuickACKsSent++;
                 } else {
-                    delay = 5*SIMTIME_ONE_MILLISECOND;
+                    delay = tcp->send.delayedACKTimeout;
                 }
 
                 host_scheduleTaskWithDelay(host, sendACKTask, delay);
@@ -2423,7 +3021,7 @@ static void _tcp_endOfFileSignalled(TCP* tcp, enum TCPFlags flags) {
 
 /* Address and port must be in network byte order. */
 gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
-                        in_addr_t ip, in_port_t port, const MemoryManager* mem) {
+                        in_addr_t ip, in_port_t port, bool urgent, const MemoryManager* mem) {
     MAGIC_ASSERT(tcp);
 
     /* return 0 to signal close, if necessary */
@@ -2470,6 +3068,11 @@ gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gs
         if(copyLength > 0) {
             /* we are sending more user data */
             tcp->send.end++;
+
+            if(urgent && copyLength == remaining) {
+                /* like linux, the last byte that we send is the urgent byte */
+                packet_setTCPUrgent(packet, (guint16)copyLength);
+            }
         }
 
         /* buffer the outgoing packet in TCP */
@@ -2506,6 +3109,84 @@ static void _tcp_sendWindowUpdate(const Host* host, gpointer voidInetSocket, gpo
     tcp->receive.windowUpdatePending = FALSE;
 }
 
+/* If the urgent mark is in `packet` at or after `offset`, returns the number of bytes from
+ * `offset` to the mark. Otherwise returns -1. */
+static gssize _tcp_bytesBeforeUrgentMark(TCP* tcp, const Packet* packet, gsize offset) {
+    MAGIC_ASSERT(tcp);
+
+    if(tcp->urgent.state == TCPUS_NONE) {
+        return -1;
+    }
+
+    PacketTCPHeader* header = packet_getTCPHeader(packet);
+    if(header->sequence != tcp->urgent.sequence || offset > tcp->urgent.offset) {
+        return -1;
+    }
+
+    return (gssize)(tcp->urgent.offset - offset);
+}
+
+gboolean tcp_isAtUrgentMark(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+
+    if(tcp->partialUserDataPacket) {
+        return _tcp_bytesBeforeUrgentMark(tcp, tcp->partialUserDataPacket, tcp->partialOffset) ==
+               0;
+    }
+
+    const Packet* nextPacket = legacysocket_peekNextInPacket((LegacySocket*)tcp);
+    return nextPacket && _tcp_bytesBeforeUrgentMark(tcp, nextPacket, 0) == 0;
+}
+
+/* Called when a read starts at the urgent mark. The read passes the mark, and skips over the
+ * urgent byte if it isn't inline. */
+static void _tcp_passUrgentMark(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+    utility_debugAssert(tcp_isAtUrgentMark(tcp));
+
+    tcp->urgent.state = TCPUS_NONE;
+
+    if(tcp->urgent.isInline) {
+        /* the urgent byte is read as normal data */
+        return;
+    }
+
+    if(tcp->partialUserDataPacket == NULL) {
+        tcp->partialUserDataPacket = legacysocket_removeFromInputBuffer((LegacySocket*)tcp, host);
+        tcp->partialOffset = 0;
+    }
+
+    tcp->partialOffset++;
+
+    if(tcp->partialOffset >= packet_getPayloadSize(tcp->partialUserDataPacket)) {
+        /* the urgent byte was the last byte of the packet */
+        packet_addDeliveryStatus(tcp->partialUserDataPacket, PDS_RCV_SOCKET_DELIVERED);
+        packet_unref(tcp->partialUserDataPacket);
+        tcp->partialUserDataPacket = NULL;
+        tcp->partialOffset = 0;
+    }
+}
+
+gint tcp_peekUrgentData(TCP* tcp, guint8* byte) {
+    MAGIC_ASSERT(tcp);
+
+    /* like linux, there's no out-of-band data to read if it's inline or was already read */
+    if(tcp->urgent.isInline || tcp->urgent.state != TCPUS_VALID) {
+        return -EINVAL;
+    }
+
+    *byte = tcp->urgent.byte;
+    return 0;
+}
+
+void tcp_consumeUrgentData(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    utility_debugAssert(tcp->urgent.state == TCPUS_VALID);
+
+    /* the urgent mark stays in the stream until it's read past */
+    tcp->urgent.state = TCPUS_READ;
+}
+
 /* Address and port must be in network byte order. */
 gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
                            in_addr_t* ip, in_port_t* port, MemoryManager* mem) {
@@ -2538,6 +3219,13 @@ gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer,
         return -EFAULT;
     }
 
+    /* like linux, a read stops at the urgent mark, so the next read starts at the mark */
+    if (remaining > 0 && tcp_isAtUrgentMark(tcp)) {
+        _tcp_passUrgentMark(tcp, host);
+    }
+
+    bool reachedUrgentMark = false;
+
     /* check if we have a partial packet waiting to get finished */
     if(remaining > 0 && tcp->partialUserDataPacket) {
         gsize partialLength = packet_getPayloadSize(tcp->partialUserDataPacket);
@@ -2545,6 +3233,14 @@ gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer,
         utility_debugAssert(partialBytes > 0);
 
         copyLength = MIN(partialBytes, remaining);
+
+        gssize bytesBeforeMark =
+            _tcp_bytesBeforeUrgentMark(tcp, tcp->partialUserDataPacket, tcp->partialOffset);
+        if (bytesBeforeMark >= 0 && bytesBeforeMark < (gssize)copyLength) {
+            copyLength = bytesBeforeMark;
+            reachedUrgentMark = true;
+        }
+
         gssize bytesCopied = packet_copyPayloadWithMemoryManager(
             tcp->partialUserDataPacket, tcp->partialOffset, buffer, copyLength, mem);
         if (bytesCopied < 0) {
@@ -2564,11 +3260,11 @@ gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer,
         } else {
             /* still more partial bytes left */
             tcp->partialOffset += bytesCopied;
-            utility_debugAssert(remaining == 0);
+            utility_debugAssert(remaining == 0 || reachedUrgentMark);
         }
     }
 
-    while(remaining > 0) {
+    while(remaining > 0 && !reachedUrgentMark) {
         /* if we get here, we should have read the partial packet above, or
          * broken out below */
         utility_debugAssert(tcp->partialUserDataPacket == NULL);
@@ -2584,6 +3280,15 @@ gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer,
 
         gsize packetLength = packet_getPayloadSize(nextPacket);
         copyLength = MIN(packetLength, remaining);
+
+        gssize bytesBeforeMark = _tcp_bytesBeforeUrgentMark(tcp, nextPacket, 0);
+        if (bytesBeforeMark == 0) {
+            /* stop at the urgent mark */
+            break;
+        } else if (bytesBeforeMark > 0 && bytesBeforeMark < (gssize)copyLength) {
+            copyLength = bytesBeforeMark;
+        }
+
         gssize bytesCopied = packet_copyPayloadWithMemoryManager(
             nextPacket, 0, (UntypedForeignPtr){.val = buffer.val + offset}, copyLength, mem);
         if (bytesCopied < 0) {
@@ -2633,6 +3338,11 @@ gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer,
                 /* OK, no more data and nothing just received. */
                 if(tcp->state == TCPS_CLOSED) {
                     return -ENOTCONN;
+                } else if((tcp->error & TCPE_CONNECTION_RESET) &&
+                          !(tcp->flags & TCPF_RESET_RD_SIGNALED)) {
+                    /* like linux, report the reset once, and then report EOF */
+                    tcp->flags |= TCPF_RESET_RD_SIGNALED;
+                    return -ECONNRESET;
                 } else {
                     _tcp_endOfFileSignalled(tcp, TCPF_EOF_RD_SIGNALED);
                     return 0;
@@ -2708,6 +3418,11 @@ static void _tcp_free(LegacyFile* descriptor) {
         tcp->partialOffset = 0;
     }
 
+    if (tcp->fastOpen.synData != NULL) {
+        packet_unref(tcp->fastOpen.synData);
+        tcp->fastOpen.synData = NULL;
+    }
+
     if (tcp->child) {
         _tcpchild_free(tcp->child);
         tcp->child = NULL;
@@ -2732,6 +3447,28 @@ static void _tcp_free(LegacyFile* descriptor) {
     worker_count_deallocation(TCP);
 }
 
+/* Abortively close the connection, discarding any data that we haven't sent yet. */
+static void _tcp_resetConnection(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    trace("%s <-> %s: resetting connection", tcp->super.boundString, tcp->super.peerString);
+
+    priorityqueue_clear(tcp->throttledOutput);
+    tcp->throttledOutputLength = 0;
+    _tcp_clearRetransmit(tcp, (guint)-1);
+    tcp->flags &= ~TCPF_SHOULD_SEND_WR_FIN;
+
+    /* like linux, only tell the peer if it could still be expecting data from us */
+    if (tcp->state == TCPS_SYNRECEIVED || tcp->state == TCPS_ESTABLISHED ||
+        tcp->state == TCPS_CLOSEWAIT || tcp->state == TCPS_FINWAIT1 ||
+        tcp->state == TCPS_FINWAIT2) {
+        _tcp_sendControlPacket(tcp, host, PTCP_RST);
+    }
+
+    /* skip TIME_WAIT */
+    _tcp_setState(tcp, host, TCPS_CLOSED);
+}
+
 static void _tcp_close(LegacyFile* descriptor, const Host* host) {
     TCP* tcp = _tcp_fromLegacyFile(descriptor);
     MAGIC_ASSERT(tcp);
@@ -2746,6 +3483,13 @@ static void _tcp_close(LegacyFile* descriptor, const Host* host) {
     /* the user closed the connection, so should never interact with the socket again */
     legacyfile_adjustStatus((LegacyFile*)tcp, FileState_ACTIVE, FALSE, 0);
 
+    /* with SO_LINGER and a zero timeout, reset the connection instead of shutting it down */
+    if (tcp->linger.isEnabled && tcp->linger.seconds == 0 && tcp->state != TCPS_LISTEN &&
+        tcp->state != TCPS_CLOSED) {
+        _tcp_resetConnection(tcp, host);
+        return;
+    }
+
     switch (tcp->state) {
         case TCPS_LISTEN:
         case TCPS_SYNSENT: {
@@ -2853,12 +3597,13 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
     legacysocket_init(
         &(tcp->super), host, &tcp_functions, DT_TCPSOCKET, receiveBufferSize, sendBufferSize);
 
-    guint32 initial_window = 10;
+    guint32 initial_window = host_getTCPInitCwnd(host);
     gint tcpSSThresh = 0;
 
     /* in the future we'd like to support more congestion control types
      * and allow it to be set as a host option */
     tcp_cong_reno_init(tcp);
+    tcp_cong(tcp)->cwnd = initial_window;
 
     tcp->send.window = initial_window;
     tcp->send.lastWindow = initial_window;
@@ -2889,11 +3634,21 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
 
     retransmit_tally_init(&tcp->retransmit.tally);
 
+    tcp->sack.isEnabled = host_useTCPSack(host);
+    tcp->ecn.isEnabled = host_useTCPECN(host);
+
     tcp->retransmit.scheduledTimerExpirations =
         priorityqueue_new((GCompareDataFunc)_simulationTimeCompare, NULL, g_free, NULL, NULL);
 
+    /* the host's tunables apply for the lifetime of the socket */
+    tcp->retransmit.initTimeout = (gint)(host_getTCPRTOInit(host) / SIMTIME_ONE_MILLISECOND);
+    tcp->retransmit.minTimeout = (gint)(host_getTCPRTOMin(host) / SIMTIME_ONE_MILLISECOND);
+    tcp->retransmit.maxTimeout = (gint)(host_getTCPRTOMax(host) / SIMTIME_ONE_MILLISECOND);
+    tcp->timeWaitDuration = 2 * host_getTCPMSL(host);
+    tcp->send.delayedACKTimeout = host_getTCPDelayedACKTimeout(host);
+
     /* initialize tcp retransmission timeout */
-    _tcp_setRetransmitTimeout(tcp, CONFIG_TCP_RTO_INIT);
+    _tcp_setRetransmitTimeout(tcp, tcp->retransmit.initTimeout);
 
     worker_count_allocation(TCP);
     return tcp;
@@ -10,6 +10,8 @@
 #include <glib.h>
 #include <netinet/in.h>
 #include <netinet/tcp.h>
+#include <stdbool.h>
+#include <sys/types.h>
 #include <sys/un.h>
 
 #include "main/bindings/c/bindings-opaque.h"
@@ -33,6 +35,7 @@ enum TCPProcessFlags {
     TCP_PF_DATA_SACKED = 1 << 3,
     TCP_PF_DATA_LOST = 1 << 4,
     TCP_PF_RWND_UPDATED = 1 << 5,
+    TCP_PF_DATA_DUPLICATE = 1 << 6,
 };
 
 typedef enum _TCPCongestionType TCPCongestionType;
@@ -72,6 +75,12 @@ gint tcp_acceptServerPeer(TCP* tcp, const Host* host, in_addr_t* ip, in_port_t*
                           gint* acceptedHandle);
 
 struct TCPCong_ *tcp_cong(TCP *tcp);
+/* The smoothed round-trip time in milliseconds, or 0 if it hasn't been measured yet. */
+gint tcp_getSmoothedRTT(TCP* tcp);
+/* The most recent round-trip time sample in milliseconds, or 0 if it hasn't been measured yet. */
+gint tcp_getLatestRTT(TCP* tcp);
+/* The number of packets that have been sent but not yet acknowledged. */
+guint32 tcp_getPacketsInFlight(TCP* tcp);
 
 void tcp_clearAllChildrenIfServer(TCP* tcp);
 
@@ -82,14 +91,52 @@ gsize tcp_getNotSentBytes(TCP* tcp);
 void tcp_disableSendBufferAutotuning(TCP* tcp);
 void tcp_disableReceiveBufferAutotuning(TCP* tcp);
 
+void tcp_setKeepAlive(TCP* tcp, gboolean enabled);
+gboolean tcp_getKeepAlive(TCP* tcp);
+
+void tcp_setUrgentInline(TCP* tcp, gboolean enabled);
+gboolean tcp_getUrgentInline(TCP* tcp);
+
+/* The process (if positive) or process group (if negative) that receives SIGURG when urgent data
+ * arrives, as set by F_SETOWN. 0 if there is no owner. */
+void tcp_setOwner(TCP* tcp, pid_t owner);
+pid_t tcp_getOwner(TCP* tcp);
+
+/* The SO_LINGER option. With a zero timeout, closing the socket resets the connection. Otherwise
+ * `FileState_SOCKET_DRAINED` is set once a closed socket has sent all of its data. Returns whether
+ * the option is enabled, and writes the timeout to `seconds` if it's not NULL. */
+void tcp_setLinger(TCP* tcp, gboolean enabled, gint seconds);
+gboolean tcp_getLinger(TCP* tcp, gint* seconds);
+
+/* The TCP_FASTOPEN option: the maximum number of connections that haven't completed the 3-way
+ * handshake but were already accepted with SYN data. 0 disables TCP Fast Open on a listener.
+ * Returns -EINVAL if the queue length is negative or the socket is connected. */
+gint tcp_setFastOpenQueueLength(TCP* tcp, gint queueLength);
+gint tcp_getFastOpenQueueLength(TCP* tcp);
+
 gboolean tcp_isValidListener(TCP* tcp);
 gboolean tcp_isListeningAllowed(TCP* tcp);
 
+/* Send a SYN to the peer like connect(), using TCP Fast Open (MSG_FASTOPEN). If we have a cookie
+ * from the peer, the SYN carries up to one segment of the data and the number of bytes sent is
+ * returned. Otherwise the SYN requests a cookie and -EINPROGRESS is returned. */
+gssize tcp_connectFastOpen(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
+                           const MemoryManager* mem);
+
+/* If `urgent` is set, the last byte sent is urgent data (MSG_OOB). */
 gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
-                        in_addr_t ip, in_port_t port, const MemoryManager* mem);
+                        in_addr_t ip, in_port_t port, bool urgent, const MemoryManager* mem);
 gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
                            in_addr_t* ip, in_port_t* port, MemoryManager* mem);
 
+/* Get the urgent byte that can be read with MSG_OOB without consuming it. Returns -EINVAL if
+ * there is no such byte, for example if SO_OOBINLINE is set or the byte was already read. */
+gint tcp_peekUrgentData(TCP* tcp, guint8* byte);
+/* Consume the urgent byte returned by `tcp_peekUrgentData`. */
+void tcp_consumeUrgentData(TCP* tcp);
+/* Whether the next byte to be read is at the urgent mark (SIOCATMARK). */
+gboolean tcp_isAtUrgentMark(TCP* tcp);
+
 gint tcp_shutdown(TCP* tcp, const Host* host, gint how);
 
 void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet* packet);
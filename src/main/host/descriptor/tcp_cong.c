@@ -1,5 +1,51 @@
 #include "main/host/descriptor/tcp_cong.h"
 
+#include <string.h>
+
+#include "main/host/descriptor/tcp_cong_bbr.h"
+#include "main/host/descriptor/tcp_cong_cubic.h"
+#include "main/host/descriptor/tcp_cong_reno.h"
+
+typedef void (*TCPCongInit)(TCP *tcp);
+
+typedef struct TCPCongAlgorithm_ {
+    const char **name;
+    TCPCongInit init;
+} TCPCongAlgorithm;
+
+static const TCPCongAlgorithm algorithms_[] = {
+    {&TCP_CONG_RENO_NAME, tcp_cong_reno_init},
+    {&TCP_CONG_CUBIC_NAME, tcp_cong_cubic_init},
+    {&TCP_CONG_BBR_NAME, tcp_cong_bbr_init},
+};
+
 const char* tcpcong_nameStr(const TCPCong *cong) {
     return cong->hooks->tcp_cong_name_str();
 }
+
+bool tcpcong_set(TCP *tcp, const char *name, size_t len) {
+    for (size_t i = 0; i < sizeof(algorithms_) / sizeof(algorithms_[0]); i++) {
+        const char *algorithm_name = *algorithms_[i].name;
+        if (strlen(algorithm_name) != len || strncmp(algorithm_name, name, len) != 0) {
+            continue;
+        }
+
+        TCPCong *cong = tcp_cong(tcp);
+
+        if (strcmp(tcpcong_nameStr(cong), algorithm_name) == 0) {
+            // already using this algorithm
+            return true;
+        }
+
+        // like linux, the new algorithm starts with the current window
+        guint32 cwnd = cong->cwnd;
+        cong->hooks->tcp_cong_delete(tcp);
+        cong->pacing_rate = 0;
+        algorithms_[i].init(tcp);
+        cong->cwnd = cwnd;
+
+        return true;
+    }
+
+    return false;
+}
@@ -2,6 +2,7 @@
 #define SHD_TCP_CONG_H_
 
 #include <stdbool.h>
+#include <stddef.h>
 
 #include "main/host/descriptor/tcp.h"
 
@@ -12,6 +13,8 @@ typedef void (*TCPCongDuplicateAckEv)(TCP *tcp);
 typedef bool (*TCPCongFastRecovery)(TCP *tcp);
 typedef void (*TCPCongNewAckEv)(TCP *tcp, guint32 n);
 typedef void (*TCPCongTimeoutEv)(TCP *tcp);
+// the peer echoed a congestion experienced mark (RFC 3168)
+typedef void (*TCPCongECNEv)(TCP *tcp);
 typedef guint32 (*TCPCongSSThresh)(TCP *tcp);
 typedef const char* (*TCPCongNameStr)();
 
@@ -21,16 +24,25 @@ typedef struct TCPCongHooks_ {
     TCPCongFastRecovery tcp_cong_fast_recovery;
     TCPCongNewAckEv tcp_cong_new_ack_ev;
     TCPCongTimeoutEv tcp_cong_timeout_ev;
+    TCPCongECNEv tcp_cong_ecn_ev;
     TCPCongSSThresh tcp_cong_ssthresh;
     TCPCongNameStr tcp_cong_name_str;
 } TCPCongHooks;
 
 typedef struct TCPCong_ {
     guint32 cwnd;
+    // the rate (in packets per second) that data packets are sent at, or 0 if the algorithm
+    // doesn't pace and packets are sent as soon as the window allows
+    double pacing_rate;
     const TCPCongHooks *hooks;
     void *ca;
 } TCPCong;
 
 const char* tcpcong_nameStr(const TCPCong *cong);
 
+// Replace the socket's congestion control algorithm with the algorithm named `name` (which is
+// `len` bytes and doesn't need to be nul-terminated), keeping the current congestion window.
+// Returns false if there is no algorithm with this name.
+bool tcpcong_set(TCP *tcp, const char *name, size_t len);
+
 #endif // SHD_TCP_CONG_H_
@@ -0,0 +1,419 @@
+#include "main/host/descriptor/tcp_cong_bbr.h"
+
+#include <math.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#include "lib/logger/logger.h"
+#include "lib/shadow-shim-helper-rs/shim_helper.h"
+#include "main/core/definitions.h"
+#include "main/core/worker.h"
+#include "main/host/descriptor/descriptor.h"
+#include "main/host/descriptor/tcp.h"
+#include "main/host/descriptor/tcp_cong.h"
+
+/*
+ * BBR (v1) congestion control. Rather than reacting to loss, BBR builds a model of the path from
+ * a windowed maximum of the delivery rate (the bottleneck bandwidth) and a windowed minimum of the
+ * round-trip time. It paces packets at a multiple (the pacing gain) of the bottleneck bandwidth,
+ * and limits the window to a multiple (the cwnd gain) of the bandwidth-delay product. The gains
+ * depend on the mode: STARTUP grows exponentially until the bandwidth stops increasing, DRAIN
+ * empties the queue that STARTUP created, PROBE_BW cycles the pacing gain to probe for more
+ * bandwidth, and PROBE_RTT periodically shrinks the window to re-measure the minimum RTT.
+ *
+ * Delivery rate samples are taken once per round trip, rather than per ack like in linux.
+ */
+
+const char* TCP_CONG_BBR_NAME = "bbr";
+
+// 2/ln(2), the smallest gain that doubles the delivery rate every round in startup
+#define BBR_HIGH_GAIN 2.885
+#define BBR_DRAIN_GAIN (1.0 / BBR_HIGH_GAIN)
+#define BBR_CWND_GAIN 2.0
+// the number of rounds that the bottleneck bandwidth max filter covers
+#define BBR_BW_FILTER_LEN 10
+// how long a minimum RTT measurement is valid for
+#define BBR_MIN_RTT_WINDOW (10 * SIMTIME_ONE_SECOND)
+// how long to stay in PROBE_RTT once the window has drained
+#define BBR_PROBE_RTT_DURATION (200 * SIMTIME_ONE_MILLISECOND)
+#define BBR_MIN_CWND 4
+#define BBR_INIT_CWND 10
+// extra packets allowed in the window to account for delayed acks
+#define BBR_CWND_ALLOWANCE 3
+// the pipe is full when the bandwidth doesn't grow by this factor for this many rounds
+#define BBR_FULL_BW_THRESH 1.25
+#define BBR_FULL_BW_ROUNDS 3
+
+#define BBR_CYCLE_LEN 8
+static const double pacing_gain_cycle_[BBR_CYCLE_LEN] = {1.25, 0.75, 1, 1, 1, 1, 1, 1};
+
+typedef enum {
+    BBR_STARTUP,
+    BBR_DRAIN,
+    BBR_PROBE_BW,
+    BBR_PROBE_RTT,
+} BBRMode;
+
+typedef struct CABBR_ {
+    BBRMode mode;
+    double pacing_gain;
+    double cwnd_gain;
+
+    // delivery rate samples (in packets per second), indexed by round
+    double bw_samples[BBR_BW_FILTER_LEN];
+    // the total number of packets acked
+    guint64 delivered;
+    // the number of round trips so far; a round ends when the packets that were in flight at
+    // the start of the round have been acked
+    guint64 round_count;
+    guint64 next_round_delivered;
+    guint64 round_start_delivered;
+    CSimulationTime round_start_time;
+    // whether the sender had no data waiting when the round started
+    bool round_is_app_limited;
+
+    // the minimum RTT, or 0 if not measured yet
+    CSimulationTime min_rtt;
+    CSimulationTime min_rtt_stamp;
+    // when PROBE_RTT ends, or 0 if the window hasn't drained yet
+    CSimulationTime probe_rtt_done_stamp;
+
+    // used to detect when STARTUP has filled the pipe
+    double full_bw;
+    guint32 full_bw_count;
+    bool filled_pipe;
+
+    size_t cycle_index;
+    CSimulationTime cycle_stamp;
+
+    size_t duplicate_ack_n;
+    bool in_recovery;
+    // the window before loss recovery or PROBE_RTT, which is restored once they finish
+    guint32 prior_cwnd;
+} CABBR;
+
+/* HELPERS *******************************************************/
+
+static double bbr_btl_bw_(CABBR *bbr) {
+    double bw = 0;
+    for (size_t i = 0; i < BBR_BW_FILTER_LEN; i++) {
+        bw = MAX(bw, bbr->bw_samples[i]);
+    }
+    return bw;
+}
+
+/* The bandwidth-delay product scaled by `gain`, in packets. */
+static guint32 bbr_bdp_(CABBR *bbr, double gain) {
+    double bw = bbr_btl_bw_(bbr);
+
+    if (bbr->min_rtt == 0 || bw == 0) {
+        // we don't have a model yet
+        return BBR_INIT_CWND;
+    }
+
+    double bdp = bw * ((double)bbr->min_rtt) / SIMTIME_ONE_SECOND;
+    return (guint32)ceil(gain * bdp);
+}
+
+static void bbr_save_cwnd_(TCP *tcp, CABBR *bbr) {
+    guint32 cwnd = tcp_cong(tcp)->cwnd;
+    if (!bbr->in_recovery && bbr->mode != BBR_PROBE_RTT) {
+        bbr->prior_cwnd = cwnd;
+    } else {
+        bbr->prior_cwnd = MAX(bbr->prior_cwnd, cwnd);
+    }
+}
+
+static void bbr_enter_startup_(CABBR *bbr) {
+    bbr->mode = BBR_STARTUP;
+    bbr->pacing_gain = BBR_HIGH_GAIN;
+    bbr->cwnd_gain = BBR_HIGH_GAIN;
+}
+
+static void bbr_enter_probe_bw_(TCP *tcp, CABBR *bbr, CSimulationTime now) {
+    bbr->mode = BBR_PROBE_BW;
+    bbr->cwnd_gain = BBR_CWND_GAIN;
+
+    // linux starts at a random phase other than the draining phase so that flows don't
+    // synchronize; we derive it from the round count to stay deterministic
+    bbr->cycle_index = (2 + bbr->round_count % (BBR_CYCLE_LEN - 1)) % BBR_CYCLE_LEN;
+    bbr->cycle_stamp = now;
+    bbr->pacing_gain = pacing_gain_cycle_[bbr->cycle_index];
+
+    debug("[CONG] desc %p bbr entering PROBE_BW", (LegacyFile*)tcp);
+}
+
+/* Updates the round count and takes a delivery rate sample if a round ended. Returns true if a
+ * new round started. */
+static bool bbr_update_bw_(TCP *tcp, CABBR *bbr, CSimulationTime now) {
+    if (bbr->delivered < bbr->next_round_delivered) {
+        return false;
+    }
+
+    if (bbr->round_count > 0 && now > bbr->round_start_time) {
+        double sample = ((double)(bbr->delivered - bbr->round_start_delivered)) *
+                        SIMTIME_ONE_SECOND / (now - bbr->round_start_time);
+
+        // app-limited samples underestimate the bandwidth, so only use them if they're higher
+        if (!bbr->round_is_app_limited || sample >= bbr_btl_bw_(bbr)) {
+            bbr->bw_samples[bbr->round_count % BBR_BW_FILTER_LEN] = sample;
+        }
+    }
+
+    guint32 inflight = tcp_getPacketsInFlight(tcp);
+
+    bbr->round_count++;
+    bbr->next_round_delivered = bbr->delivered + inflight;
+    bbr->round_start_delivered = bbr->delivered;
+    bbr->round_start_time = now;
+    bbr->round_is_app_limited =
+        tcp_getNotSentBytes(tcp) == 0 && inflight < tcp_cong(tcp)->cwnd;
+
+    return true;
+}
+
+static void bbr_check_full_pipe_(CABBR *bbr, bool round_start) {
+    if (bbr->filled_pipe || !round_start || bbr->round_is_app_limited) {
+        return;
+    }
+
+    double bw = bbr_btl_bw_(bbr);
+    if (bw >= bbr->full_bw * BBR_FULL_BW_THRESH) {
+        // still growing
+        bbr->full_bw = bw;
+        bbr->full_bw_count = 0;
+        return;
+    }
+
+    bbr->full_bw_count++;
+    if (bbr->full_bw_count >= BBR_FULL_BW_ROUNDS) {
+        bbr->filled_pipe = true;
+    }
+}
+
+static void bbr_check_drain_(TCP *tcp, CABBR *bbr, CSimulationTime now) {
+    if (bbr->mode == BBR_STARTUP && bbr->filled_pipe) {
+        bbr->mode = BBR_DRAIN;
+        bbr->pacing_gain = BBR_DRAIN_GAIN;
+        bbr->cwnd_gain = BBR_HIGH_GAIN;
+        debug("[CONG] desc %p bbr entering DRAIN", (LegacyFile*)tcp);
+    }
+
+    if (bbr->mode == BBR_DRAIN && tcp_getPacketsInFlight(tcp) <= bbr_bdp_(bbr, 1.0)) {
+        bbr_enter_probe_bw_(tcp, bbr, now);
+    }
+}
+
+static void bbr_update_cycle_phase_(TCP *tcp, CABBR *bbr, CSimulationTime now) {
+    if (bbr->mode != BBR_PROBE_BW) {
+        return;
+    }
+
+    bool is_full_length = now - bbr->cycle_stamp > bbr->min_rtt;
+    guint32 inflight = tcp_getPacketsInFlight(tcp);
+    bool advance;
+
+    if (bbr->pacing_gain > 1.0) {
+        // keep probing until we've put enough extra packets in flight
+        advance = is_full_length && inflight >= bbr_bdp_(bbr, bbr->pacing_gain);
+    } else if (bbr->pacing_gain < 1.0) {
+        // stop draining early once the queue we created is gone
+        advance = is_full_length || inflight <= bbr_bdp_(bbr, 1.0);
+    } else {
+        advance = is_full_length;
+    }
+
+    if (advance) {
+        bbr->cycle_index = (bbr->cycle_index + 1) % BBR_CYCLE_LEN;
+        bbr->cycle_stamp = now;
+        bbr->pacing_gain = pacing_gain_cycle_[bbr->cycle_index];
+    }
+}
+
+static void bbr_update_min_rtt_(TCP *tcp, CABBR *bbr, CSimulationTime now) {
+    bool expired = bbr->min_rtt_stamp != 0 && now > bbr->min_rtt_stamp + BBR_MIN_RTT_WINDOW;
+
+    gint rtt_ms = tcp_getLatestRTT(tcp);
+    if (rtt_ms > 0) {
+        CSimulationTime rtt = rtt_ms * SIMTIME_ONE_MILLISECOND;
+        if (bbr->min_rtt == 0 || rtt <= bbr->min_rtt || expired) {
+            bbr->min_rtt = rtt;
+            bbr->min_rtt_stamp = now;
+        }
+    }
+
+    if (expired && bbr->mode != BBR_PROBE_RTT) {
+        bbr_save_cwnd_(tcp, bbr);
+        bbr->mode = BBR_PROBE_RTT;
+        bbr->pacing_gain = 1.0;
+        bbr->cwnd_gain = 1.0;
+        bbr->probe_rtt_done_stamp = 0;
+        debug("[CONG] desc %p bbr entering PROBE_RTT", (LegacyFile*)tcp);
+    }
+
+    if (bbr->mode != BBR_PROBE_RTT) {
+        return;
+    }
+
+    if (bbr->probe_rtt_done_stamp == 0) {
+        if (tcp_getPacketsInFlight(tcp) <= BBR_MIN_CWND) {
+            bbr->probe_rtt_done_stamp = now + BBR_PROBE_RTT_DURATION;
+        }
+    } else if (now >= bbr->probe_rtt_done_stamp) {
+        bbr->min_rtt_stamp = now;
+        tcp_cong(tcp)->cwnd = MAX(tcp_cong(tcp)->cwnd, bbr->prior_cwnd);
+
+        if (bbr->filled_pipe) {
+            bbr_enter_probe_bw_(tcp, bbr, now);
+        } else {
+            bbr_enter_startup_(bbr);
+        }
+    }
+}
+
+static void bbr_set_pacing_rate_(TCP *tcp, CABBR *bbr) {
+    TCPCong *cong = tcp_cong(tcp);
+    double bw = bbr_btl_bw_(bbr);
+    double rate;
+
+    if (bw > 0) {
+        rate = bbr->pacing_gain * bw;
+    } else {
+        // no bandwidth estimate yet, so pace the initial window over the RTT (like linux, we
+        // assume a 1 ms RTT if it hasn't been measured)
+        gint srtt_ms = MAX(tcp_getSmoothedRTT(tcp), 1);
+        guint32 cwnd = MAX(cong->cwnd, BBR_INIT_CWND);
+        rate = bbr->pacing_gain * cwnd * 1000.0 / srtt_ms;
+    }
+
+    // don't slow down in startup until the pipe is full
+    if (bbr->filled_pipe || rate > cong->pacing_rate) {
+        cong->pacing_rate = rate;
+    }
+}
+
+static void bbr_set_cwnd_(TCP *tcp, CABBR *bbr, guint32 n) {
+    guint32 cwnd = tcp_cong(tcp)->cwnd;
+    guint32 target = bbr_bdp_(bbr, bbr->cwnd_gain) + BBR_CWND_ALLOWANCE;
+
+    if (bbr->filled_pipe) {
+        cwnd = MIN(cwnd + n, target);
+    } else if (cwnd < target || bbr->delivered < BBR_INIT_CWND) {
+        cwnd += n;
+    }
+
+    cwnd = MAX(cwnd, BBR_MIN_CWND);
+    if (bbr->mode == BBR_PROBE_RTT) {
+        cwnd = MIN(cwnd, BBR_MIN_CWND);
+    }
+
+    tcp_cong(tcp)->cwnd = cwnd;
+}
+
+/*******************************************************************/
+
+static void tcp_cong_bbr_delete_(TCP *tcp) {
+    free(tcp_cong(tcp)->ca);
+}
+
+static void tcp_cong_bbr_duplicate_ack_ev_(TCP *tcp) {
+    CABBR *bbr = tcp_cong(tcp)->ca;
+
+    if (bbr->in_recovery) {
+        // each duplicate ack means that a packet has left the network
+        tcp_cong(tcp)->cwnd += 1;
+        return;
+    }
+
+    bbr->duplicate_ack_n++;
+
+    if (bbr->duplicate_ack_n == 3) {
+        debug("[CONG] desc %p three duplicate acks, bbr entering recovery", (LegacyFile*)tcp);
+
+        // bbr doesn't treat loss as a congestion signal, but it does conserve packets during
+        // recovery
+        bbr_save_cwnd_(tcp, bbr);
+        bbr->in_recovery = true;
+        tcp_cong(tcp)->cwnd = MAX(tcp_getPacketsInFlight(tcp), BBR_MIN_CWND);
+    }
+}
+
+static bool tcp_cong_bbr_fast_recovery_(TCP *tcp) {
+    CABBR *bbr = tcp_cong(tcp)->ca;
+    return bbr->in_recovery;
+}
+
+static void tcp_cong_bbr_new_ack_ev_(TCP *tcp, guint32 n) {
+    CABBR *bbr = tcp_cong(tcp)->ca;
+    CSimulationTime now = worker_getCurrentSimulationTime();
+
+    bbr->delivered += n;
+    bbr->duplicate_ack_n = 0;
+
+    if (bbr->in_recovery) {
+        bbr->in_recovery = false;
+        tcp_cong(tcp)->cwnd = MAX(tcp_cong(tcp)->cwnd, bbr->prior_cwnd);
+    }
+
+    bool round_start = bbr_update_bw_(tcp, bbr, now);
+    bbr_check_full_pipe_(bbr, round_start);
+    bbr_check_drain_(tcp, bbr, now);
+    bbr_update_cycle_phase_(tcp, bbr, now);
+    bbr_update_min_rtt_(tcp, bbr, now);
+
+    bbr_set_pacing_rate_(tcp, bbr);
+    bbr_set_cwnd_(tcp, bbr, n);
+}
+
+static void tcp_cong_bbr_timeout_ev_(TCP *tcp) {
+    CABBR *bbr = tcp_cong(tcp)->ca;
+
+    bbr_save_cwnd_(tcp, bbr);
+    bbr->in_recovery = true;
+    bbr->duplicate_ack_n = 0;
+    tcp_cong(tcp)->cwnd = 1;
+
+    // the bandwidth measured after the timeout shouldn't count towards filling the pipe
+    bbr->full_bw = 0;
+    bbr->full_bw_count = 0;
+
+    debug("[CONG] desc %p bbr timeout", (LegacyFile*)tcp);
+}
+
+static void tcp_cong_bbr_ecn_ev_(TCP *tcp) {
+    // like linux's bbr (v1), we use the bandwidth and rtt model rather than reacting to ecn
+    debug("[CONG] desc %p bbr ignoring ecn", (LegacyFile*)tcp);
+}
+
+static guint32 tcp_cong_bbr_ssthresh_(TCP *tcp) {
+    // bbr doesn't use a slow start threshold
+    return INT32_MAX;
+}
+
+static const char* tcp_cong_bbr_name_str_() {
+    return TCP_CONG_BBR_NAME;
+}
+
+static const struct TCPCongHooks_ bbr_hooks_ = {
+    .tcp_cong_delete = tcp_cong_bbr_delete_,
+    .tcp_cong_duplicate_ack_ev = tcp_cong_bbr_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = tcp_cong_bbr_fast_recovery_,
+    .tcp_cong_new_ack_ev = tcp_cong_bbr_new_ack_ev_,
+    .tcp_cong_timeout_ev = tcp_cong_bbr_timeout_ev_,
+    .tcp_cong_ecn_ev = tcp_cong_bbr_ecn_ev_,
+    .tcp_cong_ssthresh = tcp_cong_bbr_ssthresh_,
+    .tcp_cong_name_str = tcp_cong_bbr_name_str_,
+};
+
+void tcp_cong_bbr_init(TCP *tcp) {
+    CABBR *bbr = calloc(1, sizeof(CABBR));
+    bbr_enter_startup_(bbr);
+
+    tcp_cong(tcp)->cwnd = 1;
+    tcp_cong(tcp)->hooks = &bbr_hooks_;
+    tcp_cong(tcp)->ca = bbr;
+
+    bbr_set_pacing_rate_(tcp, bbr);
+}
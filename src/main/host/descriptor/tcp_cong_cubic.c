@@ -0,0 +1,250 @@
+#include "main/host/descriptor/tcp_cong_cubic.h"
+
+#include <math.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#include "lib/logger/logger.h"
+#include "lib/shadow-shim-helper-rs/shim_helper.h"
+#include "main/core/definitions.h"
+#include "main/core/worker.h"
+#include "main/host/descriptor/descriptor.h"
+#include "main/host/descriptor/tcp.h"
+#include "main/host/descriptor/tcp_cong.h"
+
+/*
+ * CUBIC congestion control (RFC 8312). Slow start, fast recovery, and timeouts behave like reno,
+ * but during congestion avoidance the window grows as a cubic function of the time since the
+ * last congestion event, and it is only reduced by a factor of `CUBIC_BETA` on loss.
+ */
+
+const char* TCP_CONG_CUBIC_NAME = "cubic";
+
+// the scaling constant that determines how aggressively the window grows
+#define CUBIC_C 0.4
+// the multiplicative decrease factor
+#define CUBIC_BETA 0.7
+
+typedef enum {
+    CUBIC_SLOW_START,
+    CUBIC_FAST_RECOVERY,
+    CUBIC_CONG_AVOID,
+} CubicState;
+
+typedef struct CACubic_ {
+    CubicState state;
+
+    size_t duplicate_ack_n;
+    guint32 ssthresh;
+
+    // the window size just before the last reduction
+    double w_max;
+    // the time of the start of the current congestion avoidance epoch, or 0 if not started
+    CSimulationTime epoch_start;
+    // the time it takes the window to grow back to `w_max`
+    double k;
+    // the window size at the plateau of the cubic function
+    double origin_point;
+    // the window size that reno would have (the "tcp-friendly" region)
+    double w_est;
+    // the number of acked packets that haven't increased the window yet
+    double cong_avoid_nacked;
+} CACubic;
+
+/* HELPERS *******************************************************/
+
+static double cubic_rtt_seconds_(TCP *tcp) {
+    return ((double)tcp_getSmoothedRTT(tcp)) / 1000.0;
+}
+
+/* Reduce the window after a congestion event. */
+static void cubic_reduce_(TCP *tcp, CACubic *cubic) {
+    double cwnd = tcp_cong(tcp)->cwnd;
+
+    // fast convergence: if the window didn't recover to its previous maximum, release some
+    // bandwidth to new flows
+    if (cwnd < cubic->w_max) {
+        cubic->w_max = cwnd * (1.0 + CUBIC_BETA) / 2.0;
+    } else {
+        cubic->w_max = cwnd;
+    }
+
+    cubic->ssthresh = MAX((guint32)(cwnd * CUBIC_BETA), 2);
+    cubic->epoch_start = 0;
+    cubic->duplicate_ack_n = 0;
+}
+
+static void cubic_cong_avoid_(TCP *tcp, CACubic *cubic, guint32 n) {
+    if (n == 0) {
+        return;
+    }
+
+    double cwnd = tcp_cong(tcp)->cwnd;
+    CSimulationTime now = worker_getCurrentSimulationTime();
+
+    if (cubic->epoch_start == 0) {
+        // the start of a new congestion avoidance epoch
+        cubic->epoch_start = now;
+        if (cwnd < cubic->w_max) {
+            cubic->k = cbrt((cubic->w_max - cwnd) / CUBIC_C);
+            cubic->origin_point = cubic->w_max;
+        } else {
+            cubic->k = 0;
+            cubic->origin_point = cwnd;
+        }
+        cubic->w_est = cwnd;
+        cubic->cong_avoid_nacked = 0;
+    }
+
+    // the window we want to have one rtt from now
+    double t = ((double)(now - cubic->epoch_start)) / SIMTIME_ONE_SECOND + cubic_rtt_seconds_(tcp);
+    double target = cubic->origin_point + CUBIC_C * pow(t - cubic->k, 3);
+
+    // the number of acked packets needed to increase the window by one
+    double acks_per_increase;
+    if (target > cwnd) {
+        acks_per_increase = cwnd / (target - cwnd);
+    } else {
+        // grow very slowly when we're above the target
+        acks_per_increase = 100 * cwnd;
+    }
+
+    // make sure we're never slower than reno
+    cubic->w_est += 3.0 * (1.0 - CUBIC_BETA) / (1.0 + CUBIC_BETA) * n / cwnd;
+    if (cubic->w_est > cwnd) {
+        acks_per_increase = MIN(acks_per_increase, cwnd / (cubic->w_est - cwnd));
+    }
+
+    acks_per_increase = MAX(acks_per_increase, 1.0);
+
+    cubic->cong_avoid_nacked += n;
+    while (cubic->cong_avoid_nacked >= acks_per_increase) {
+        cubic->cong_avoid_nacked -= acks_per_increase;
+        tcp_cong(tcp)->cwnd += 1;
+    }
+}
+
+static void transition_to_cong_avoid_(TCP *tcp, CACubic *cubic, guint32 n) {
+    cubic->state = CUBIC_CONG_AVOID;
+    cubic_cong_avoid_(tcp, cubic, n);
+    debug("[CONG] desc=%p transition_to_cong_avoid", (LegacyFile*)tcp);
+}
+
+/*******************************************************************/
+
+static void tcp_cong_cubic_delete_(TCP *tcp) {
+    free(tcp_cong(tcp)->ca);
+}
+
+static void tcp_cong_cubic_duplicate_ack_ev_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    if (cubic->state == CUBIC_FAST_RECOVERY) {
+        tcp_cong(tcp)->cwnd += 1;
+        return;
+    }
+
+    cubic->duplicate_ack_n++;
+
+    if (cubic->duplicate_ack_n == 3) { // transition to fast recovery
+        debug("[CONG] desc %p three duplicate acks transition_to_fast_recovery", (LegacyFile*)tcp);
+
+        cubic_reduce_(tcp, cubic);
+        tcp_cong(tcp)->cwnd = cubic->ssthresh + 3;
+        cubic->state = CUBIC_FAST_RECOVERY;
+    }
+}
+
+static bool tcp_cong_cubic_fast_recovery_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    return cubic->state == CUBIC_FAST_RECOVERY;
+}
+
+static void tcp_cong_cubic_new_ack_ev_(TCP *tcp, guint32 n) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic->duplicate_ack_n = 0;
+
+    switch (cubic->state) {
+        case CUBIC_SLOW_START: {
+            guint32 new_cwnd = tcp_cong(tcp)->cwnd + n;
+
+            if (new_cwnd >= cubic->ssthresh) {
+                // use the leftover acks for congestion avoidance
+                guint32 nleft = new_cwnd - cubic->ssthresh;
+                tcp_cong(tcp)->cwnd = cubic->ssthresh;
+                transition_to_cong_avoid_(tcp, cubic, nleft);
+            } else {
+                tcp_cong(tcp)->cwnd = new_cwnd;
+            }
+            break;
+        }
+        case CUBIC_FAST_RECOVERY: {
+            tcp_cong(tcp)->cwnd = cubic->ssthresh;
+            transition_to_cong_avoid_(tcp, cubic, n);
+            break;
+        }
+        case CUBIC_CONG_AVOID: {
+            cubic_cong_avoid_(tcp, cubic, n);
+            break;
+        }
+    }
+}
+
+static void tcp_cong_cubic_timeout_ev_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic_reduce_(tcp, cubic);
+    tcp_cong(tcp)->cwnd = 10;
+
+    // transition to slow start
+    cubic->state = CUBIC_SLOW_START;
+    debug("[CONG] desc %p transition_to_slow_start", (LegacyFile*)tcp);
+}
+
+static void tcp_cong_cubic_ecn_ev_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    if (cubic->state == CUBIC_FAST_RECOVERY) {
+        // we already reduced the window for this loss
+        return;
+    }
+
+    // there's no lost packet to recover, so we skip fast recovery
+    cubic_reduce_(tcp, cubic);
+    tcp_cong(tcp)->cwnd = cubic->ssthresh;
+    cubic->state = CUBIC_CONG_AVOID;
+    debug("[CONG] desc %p ecn transition_to_cong_avoid", (LegacyFile*)tcp);
+}
+
+static guint32 tcp_cong_cubic_ssthresh_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    return cubic->ssthresh;
+}
+
+static const char* tcp_cong_cubic_name_str_() {
+    return TCP_CONG_CUBIC_NAME;
+}
+
+static const struct TCPCongHooks_ cubic_hooks_ = {
+    .tcp_cong_delete = tcp_cong_cubic_delete_,
+    .tcp_cong_duplicate_ack_ev = tcp_cong_cubic_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = tcp_cong_cubic_fast_recovery_,
+    .tcp_cong_new_ack_ev = tcp_cong_cubic_new_ack_ev_,
+    .tcp_cong_timeout_ev = tcp_cong_cubic_timeout_ev_,
+    .tcp_cong_ecn_ev = tcp_cong_cubic_ecn_ev_,
+    .tcp_cong_ssthresh = tcp_cong_cubic_ssthresh_,
+    .tcp_cong_name_str = tcp_cong_cubic_name_str_,
+};
+
+void tcp_cong_cubic_init(TCP *tcp) {
+    CACubic *cubic = calloc(1, sizeof(CACubic));
+    cubic->state = CUBIC_SLOW_START;
+    cubic->ssthresh = INT32_MAX;
+
+    tcp_cong(tcp)->cwnd = 1;
+    tcp_cong(tcp)->hooks = &cubic_hooks_;
+    tcp_cong(tcp)->ca = cubic;
+}
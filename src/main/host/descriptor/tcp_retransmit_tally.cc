@@ -242,6 +242,8 @@ void retransmit_tally_mark_sacked(void *p, GList *sacked) {
 
       n = g_list_next(n);
    }
+
+   rt->mark_sack_holes_lost();
 }
 
 void retransmit_tally_mark_lost(void *p, uint32_t begin, uint32_t end) {
@@ -308,6 +310,26 @@ void RetransmitTally::compute_lost() {
    lost_ = ranges_subtract(lost_, retransmitted_);
 }
 
+/* RFC 6675: a hole in the sacked ranges is lost once at least kDuplAckLostThresh segments above
+ * it have been sacked, so we can repair several holes in the same round trip. */
+void RetransmitTally::mark_sack_holes_lost() {
+   SeqNum sacked_above = 0;
+
+   for (std::size_t idx = sacked_.size(); idx > 0; --idx) {
+      const auto &range = sacked_[idx - 1];
+      sacked_above += range.second - range.first;
+
+      SeqNum hole_begin = idx > 1 ? sacked_[idx - 2].second : last_ack_;
+      if (sacked_above >= static_cast<SeqNum>(kDuplAckLostThresh)
+          && hole_begin >= 0 && hole_begin < range.first)
+      {
+         ranges_insert(&marked_lost_, {hole_begin, range.first});
+      }
+   }
+
+   compute_lost();
+}
+
 void RetransmitTally::tidy_ranges(Ranges *ranges) {
    assert(still_sorted_(*ranges));
    auto original = *ranges;
@@ -23,6 +23,7 @@ enum TCPProcessFlags_ {
     TCP_PF_DATA_SACKED_ = 1 << 3,
     TCP_PF_DATA_LOST_ = 1 << 4,
     TCP_PF_RWND_UPDATED_ = 1 << 5,
+    TCP_PF_DATA_DUPLICATE_ = 1 << 6,
 };
 
 #ifdef __cplusplus
@@ -62,6 +63,7 @@ struct RetransmitTally {
    RetransmitTally &operator=(const RetransmitTally &rhs) = delete;
 
    void compute_lost();
+   void mark_sack_holes_lost();
    void tidy_ranges(Ranges *ranges);
 
    enum : std::uint64_t { kMagicNum = 0xBEEEEEEF,
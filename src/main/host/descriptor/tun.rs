@@ -0,0 +1,337 @@
+//! The file opened from `/dev/net/tun`, which is attached to a TUN interface with `TUNSETIFF`.
+//! Packets that the host routes to the interface can be read from the file, and packets written to
+//! the file are received by the host as if they had arrived on the interface.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+use linux_api::errno::Errno;
+use linux_api::if_tun::{TunFlags, ETH_P_IP, TUN_PI_LEN};
+use linux_api::ioctls::IoctlRequest;
+use linux_api::netdevice::ifreq;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+
+use crate::core::work::task::TaskRef;
+use crate::core::worker::Worker;
+use crate::cshadow as c;
+use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
+use crate::host::descriptor::{FileMode, FileSignals, FileState, FileStatus};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::syscall::io::{IoVec, IoVecReader, IoVecWriter};
+use crate::host::syscall::types::{SyscallError, SyscallResult};
+use crate::network::packet::PacketRc;
+use crate::utility::callback_queue::CallbackQueue;
+use crate::utility::HostTreePointer;
+
+/// The path of the TUN device.
+pub const TUN_DEVICE_PATH: &str = "/dev/net/tun";
+
+/// The maximum number of packets queued for reading, from Linux's default `txqueuelen` of TUN
+/// interfaces. Packets routed to the interface when the queue is full are dropped.
+const MAX_QUEUED_PACKETS: usize = 500;
+
+pub struct TunFile {
+    /// The name and flags of the interface that the file is attached to.
+    interface: Option<(String, TunFlags)>,
+    /// Packets routed to the interface that haven't been read yet.
+    packets: VecDeque<Vec<u8>>,
+    event_source: StateEventSource,
+    state: FileState,
+    status: FileStatus,
+    // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
+    // this file
+    has_open_file: bool,
+}
+
+impl TunFile {
+    pub fn new(status: FileStatus) -> Self {
+        Self {
+            interface: None,
+            packets: VecDeque::new(),
+            event_source: StateEventSource::new(),
+            state: FileState::ACTIVE | FileState::WRITABLE,
+            status,
+            has_open_file: false,
+        }
+    }
+
+    /// The name of the interface that the file is attached to, if any.
+    pub fn interface_name(&self) -> Option<&str> {
+        self.interface.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// Attach the file to the interface `name`. The caller is responsible for creating the
+    /// interface in the host's network namespace.
+    pub fn attach(&mut self, name: String, flags: TunFlags) {
+        assert!(self.interface.is_none());
+        self.interface = Some((name, flags));
+    }
+
+    /// Queue a packet routed to the file's interface so that it can be read. Returns `false` if the
+    /// packet was dropped.
+    pub fn push_packet(&mut self, packet: Vec<u8>, cb_queue: &mut CallbackQueue) -> bool {
+        if self.state.contains(FileState::CLOSED) || self.packets.len() >= MAX_QUEUED_PACKETS {
+            return false;
+        }
+
+        self.packets.push_back(packet);
+        self.refresh_state(FileSignals::READ_BUFFER_GREW, cb_queue);
+        true
+    }
+
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: FileStatus) {
+        self.status = status;
+    }
+
+    pub fn mode(&self) -> FileMode {
+        FileMode::READ | FileMode::WRITE
+    }
+
+    pub fn has_open_file(&self) -> bool {
+        self.has_open_file
+    }
+
+    pub fn supports_sa_restart(&self) -> bool {
+        true
+    }
+
+    pub fn set_has_open_file(&mut self, val: bool) {
+        self.has_open_file = val;
+    }
+
+    pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
+        // non-persistent interfaces are removed when their file is closed
+        if let Some((name, _)) = self.interface.take() {
+            Worker::with_active_host(|host| {
+                host.network_namespace_borrow().remove_tun_interface(&name)
+            });
+        }
+
+        self.packets.clear();
+
+        // set the closed flag and remove the active, readable, and writable flags
+        self.update_state(
+            FileState::CLOSED | FileState::ACTIVE | FileState::READABLE | FileState::WRITABLE,
+            FileState::CLOSED,
+            FileSignals::empty(),
+            cb_queue,
+        );
+
+        Ok(())
+    }
+
+    pub fn readv(
+        &mut self,
+        iovs: &[IoVec],
+        offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        mem: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // the TUN device doesn't support seeking
+        if offset.is_some() {
+            return Err(Errno::ESPIPE.into());
+        }
+
+        let Some((_, flags)) = &self.interface else {
+            return Err(Errno::EBADFD.into());
+        };
+
+        let Some(packet) = self.packets.pop_front() else {
+            return Err(Errno::EWOULDBLOCK.into());
+        };
+
+        let mut bytes = Vec::with_capacity(TUN_PI_LEN + packet.len());
+        if !flags.contains(TunFlags::IFF_NO_PI) {
+            // the packet information header's flags are 0 and the protocol is IPv4
+            bytes.extend_from_slice(&[0, 0]);
+            bytes.extend_from_slice(&ETH_P_IP.to_be_bytes());
+        }
+        bytes.extend_from_slice(&packet);
+
+        // like a datagram socket, the packet is truncated if the buffer is too small
+        let len: libc::size_t = iovs.iter().map(|x| x.len).sum();
+        let len = std::cmp::min(len, bytes.len());
+
+        let mut writer = IoVecWriter::new(iovs, mem);
+        writer.write_all(&bytes[..len])?;
+
+        self.refresh_state(FileSignals::empty(), cb_queue);
+
+        Ok(len.try_into().unwrap())
+    }
+
+    pub fn writev(
+        &mut self,
+        iovs: &[IoVec],
+        offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // the TUN device doesn't support seeking
+        if offset.is_some() {
+            return Err(Errno::ESPIPE.into());
+        }
+
+        let Some((name, flags)) = &self.interface else {
+            return Err(Errno::EBADFD.into());
+        };
+
+        let len: libc::size_t = iovs.iter().map(|x| x.len).sum();
+        let mut bytes = vec![0u8; len];
+        IoVecReader::new(iovs, mem).read_exact(&mut bytes)?;
+
+        // Linux returns `EIO` when writing to an interface that is down
+        let is_up = Worker::with_active_host(|host| {
+            host.network_namespace_borrow()
+                .tun_interface_is_up(name)
+                .unwrap_or(false)
+        })
+        .unwrap();
+        if !is_up {
+            return Err(Errno::EIO.into());
+        }
+
+        let mut packet = &bytes[..];
+        if !flags.contains(TunFlags::IFF_NO_PI) {
+            if packet.len() < TUN_PI_LEN {
+                return Err(Errno::EINVAL.into());
+            }
+
+            let (pi, rest) = packet.split_at(TUN_PI_LEN);
+
+            let proto = u16::from_be_bytes([pi[2], pi[3]]);
+            if proto != ETH_P_IP {
+                log::debug!("Dropping TUN packet with unsupported protocol {proto:#06x}");
+                return Ok(len.try_into().unwrap());
+            }
+
+            packet = rest;
+        }
+
+        // like Linux, malformed packets are accepted and then dropped by the network stack
+        let Some(packet) = PacketRc::from_ipv4_bytes(packet) else {
+            log::debug!("Dropping malformed or unsupported packet written to TUN interface {name}");
+            return Ok(len.try_into().unwrap());
+        };
+
+        // the host receives the packet after the syscall has returned, so that the receiving
+        // socket isn't modified while this file is borrowed
+        let task = TaskRef::new(move |host| host.receive_tun_packet(packet));
+        Worker::with_active_host(|host| host.schedule_task_with_delay(task, SimulationTime::ZERO))
+            .unwrap();
+
+        Ok(len.try_into().unwrap())
+    }
+
+    pub fn ioctl(
+        &mut self,
+        request: IoctlRequest,
+        arg_ptr: ForeignPtr<()>,
+        memory_manager: &mut MemoryManager,
+    ) -> SyscallResult {
+        match request {
+            // `TUNSETIFF` needs access to the host's network namespace, so it's handled by the
+            // syscall handler
+            IoctlRequest::TUNGETIFF => {
+                let Some((name, flags)) = &self.interface else {
+                    return Err(Errno::EBADFD.into());
+                };
+
+                let arg_ptr = arg_ptr.cast::<ifreq>();
+                let mut req = memory_manager.read(arg_ptr)?;
+                req.set_name(name.as_bytes());
+                req.ifr_ifru.ifru_flags = flags.bits();
+                memory_manager.write(arg_ptr, &req)?;
+
+                Ok(0.into())
+            }
+            _ => {
+                log::warn!("We do not yet handle ioctl request {request:?} on TUN devices");
+                Err(Errno::EINVAL.into())
+            }
+        }
+    }
+
+    pub fn add_listener(
+        &mut self,
+        monitoring_state: FileState,
+        monitoring_signals: FileSignals,
+        filter: StateListenerFilter,
+        notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue)
+            + Send
+            + Sync
+            + 'static,
+    ) -> StateListenHandle {
+        self.event_source
+            .add_listener(monitoring_state, monitoring_signals, filter, notify_fn)
+    }
+
+    pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>) {
+        self.event_source.add_legacy_listener(ptr);
+    }
+
+    pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener) {
+        self.event_source.remove_legacy_listener(ptr);
+    }
+
+    pub fn state(&self) -> FileState {
+        self.state
+    }
+
+    fn refresh_state(&mut self, signals: FileSignals, cb_queue: &mut CallbackQueue) {
+        if self.state.contains(FileState::CLOSED) {
+            return;
+        }
+
+        // packets can always be written, and excess packets are dropped by the host
+        let mut readable_writable = FileState::WRITABLE;
+        readable_writable.set(FileState::READABLE, !self.packets.is_empty());
+
+        self.update_state(
+            FileState::READABLE | FileState::WRITABLE,
+            readable_writable,
+            signals,
+            cb_queue,
+        );
+    }
+
+    fn update_state(
+        &mut self,
+        mask: FileState,
+        state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let old_state = self.state;
+
+        // remove the masked flags, then copy the masked flags
+        self.state.remove(mask);
+        self.state.insert(state & mask);
+
+        self.handle_state_change(old_state, signals, cb_queue);
+    }
+
+    fn handle_state_change(
+        &mut self,
+        old_state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let states_changed = self.state ^ old_state;
+
+        // if nothing changed
+        if states_changed.is_empty() && signals.is_empty() {
+            return;
+        }
+
+        self.event_source
+            .notify_listeners(self.state, states_changed, signals, cb_queue);
+    }
+}
@@ -0,0 +1,651 @@
+//! Advisory file locks (`fcntl(F_SETLK)`, `flock()`) shared by all processes on a host.
+//!
+//! Linux tracks two independent kinds of advisory locks:
+//!
+//! - record locks, which cover a byte range of a file and are taken with `fcntl`. Traditional POSIX
+//!   record locks are owned by a process, while "open file description" (OFD) locks are owned by
+//!   the open file. Both kinds conflict with each other.
+//! - `flock` locks, which cover the whole file and are owned by the open file.
+//!
+//! Regular files in shadow are backed by native files, so we can't rely on the native kernel to
+//! implement these locks; a conflicting native lock would block the entire simulation. Instead we
+//! track them here, keyed by the inode of the native file.
+
+use std::collections::HashMap;
+
+use shadow_shim_helper_rs::syscall_types::ManagedPhysicalMemoryAddr;
+
+use crate::cshadow as c;
+use crate::host::descriptor::CompatFile;
+use crate::host::futex_table::FutexRef;
+use crate::host::process::ProcessId;
+use crate::utility::ObjectCounter;
+
+/// Identifies a file by the device and inode number of its backing native file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileLockKey {
+    pub dev: u64,
+    pub ino: u64,
+}
+
+impl FileLockKey {
+    /// Returns the key for a legacy file, or `None` if the file isn't a regular file backed by a
+    /// native file.
+    pub fn for_legacy_file(file: *mut c::LegacyFile) -> Option<Self> {
+        if unsafe { c::legacyfile_getType(file) } != c::_LegacyFileType_DT_FILE {
+            return None;
+        }
+
+        let native_fd = unsafe { c::regularfile_getOSBackedFD(file as *mut c::RegularFile) };
+        if native_fd < 0 {
+            return None;
+        }
+
+        let stat = nix::sys::stat::fstat(native_fd).ok()?;
+        Some(Self {
+            dev: stat.st_dev,
+            ino: stat.st_ino,
+        })
+    }
+}
+
+/// The owner of a lock.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FileLockOwner {
+    /// A traditional POSIX record lock, owned by a process.
+    Process(ProcessId),
+    /// A lock owned by an open file description (OFD record locks and `flock` locks). The value is
+    /// a canonical handle for the open file.
+    OpenFile(usize),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileLockKind {
+    /// A read (shared) lock.
+    Shared,
+    /// A write (exclusive) lock.
+    Exclusive,
+}
+
+impl FileLockKind {
+    fn conflicts_with(&self, other: &Self) -> bool {
+        *self == Self::Exclusive || *other == Self::Exclusive
+    }
+}
+
+/// A byte range `[start, end)`. An `end` of `u64::MAX` means that the range extends to the end of
+/// the file, regardless of how large the file grows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FileLockRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl FileLockRange {
+    pub const WHOLE_FILE: Self = Self {
+        start: 0,
+        end: u64::MAX,
+    };
+
+    pub fn new(start: u64, end: u64) -> Self {
+        assert!(start <= end);
+        Self { start, end }
+    }
+
+    pub fn to_eof(&self) -> bool {
+        self.end == u64::MAX
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// Returns true if the ranges overlap or are directly adjacent.
+    fn touches(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// A lock held on a byte range of a file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RecordLock {
+    pub owner: FileLockOwner,
+    pub kind: FileLockKind,
+    pub range: FileLockRange,
+}
+
+/// All locks held on a single inode.
+#[derive(Default)]
+struct InodeLocks {
+    /// Record locks, sorted by range start.
+    records: Vec<RecordLock>,
+    /// `flock` locks, in the order they were taken.
+    flocks: Vec<(usize, FileLockKind)>,
+    /// Threads blocked in `F_SETLKW` or `flock` on this inode wait on this futex. Created lazily.
+    waiters: Option<FutexRef>,
+}
+
+impl InodeLocks {
+    fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.flocks.is_empty() && self.waiters.is_none()
+    }
+
+    fn conflicting_record(
+        &self,
+        owner: FileLockOwner,
+        kind: FileLockKind,
+        range: FileLockRange,
+    ) -> Option<&RecordLock> {
+        self.records.iter().find(|lock| {
+            lock.owner != owner && lock.range.overlaps(&range) && lock.kind.conflicts_with(&kind)
+        })
+    }
+
+    /// Remove `range` from all of `owner`'s record locks, splitting locks that only partially
+    /// overlap. Returns true if any locks were modified.
+    fn remove_record_range(&mut self, owner: FileLockOwner, range: FileLockRange) -> bool {
+        let mut changed = false;
+        let mut new_records = Vec::with_capacity(self.records.len() + 1);
+
+        for lock in self.records.drain(..) {
+            if lock.owner != owner || !lock.range.overlaps(&range) {
+                new_records.push(lock);
+                continue;
+            }
+
+            changed = true;
+
+            // keep the parts of the existing lock that fall outside of the range
+            if lock.range.start < range.start {
+                new_records.push(RecordLock {
+                    range: FileLockRange::new(lock.range.start, range.start),
+                    ..lock
+                });
+            }
+            if lock.range.end > range.end {
+                new_records.push(RecordLock {
+                    range: FileLockRange::new(range.end, lock.range.end),
+                    ..lock
+                });
+            }
+        }
+
+        self.records = new_records;
+        changed
+    }
+
+    /// Add a record lock for `owner`, merging it with any of the owner's existing locks of the
+    /// same kind that it overlaps or touches. Any overlapping locks of a different kind belonging
+    /// to `owner` are replaced.
+    fn insert_record(&mut self, owner: FileLockOwner, kind: FileLockKind, range: FileLockRange) {
+        self.remove_record_range(owner, range);
+
+        let mut merged = range;
+        self.records.retain(|lock| {
+            if lock.owner == owner && lock.kind == kind && lock.range.touches(&merged) {
+                merged.start = std::cmp::min(merged.start, lock.range.start);
+                merged.end = std::cmp::max(merged.end, lock.range.end);
+                false
+            } else {
+                true
+            }
+        });
+
+        self.records.push(RecordLock {
+            owner,
+            kind,
+            range: merged,
+        });
+        self.records.sort_by_key(|lock| lock.range.start);
+    }
+}
+
+/// A per-host table of advisory file locks.
+pub struct FileLockTable {
+    inodes: HashMap<FileLockKey, InodeLocks>,
+    _counter: ObjectCounter,
+}
+
+impl FileLockTable {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            inodes: HashMap::new(),
+            _counter: ObjectCounter::new("FileLockTable"),
+        }
+    }
+
+    /// Returns a record lock that would prevent `owner` from taking a lock of type `kind` on
+    /// `range`, if any (`F_GETLK`).
+    pub fn test_record(
+        &self,
+        key: FileLockKey,
+        owner: FileLockOwner,
+        kind: FileLockKind,
+        range: FileLockRange,
+    ) -> Option<RecordLock> {
+        self.inodes
+            .get(&key)?
+            .conflicting_record(owner, kind, range)
+            .copied()
+    }
+
+    /// Take a record lock (`F_SETLK`). If another owner holds a conflicting lock, the table is not
+    /// modified and the conflicting lock is returned.
+    pub fn lock_record(
+        &mut self,
+        key: FileLockKey,
+        owner: FileLockOwner,
+        kind: FileLockKind,
+        range: FileLockRange,
+    ) -> Result<(), RecordLock> {
+        let inode = self.inodes.entry(key).or_default();
+
+        if let Some(conflict) = inode.conflicting_record(owner, kind, range).copied() {
+            self.remove_if_empty(key);
+            return Err(conflict);
+        }
+
+        let downgraded = kind == FileLockKind::Shared
+            && inode.records.iter().any(|lock| {
+                lock.owner == owner
+                    && lock.kind == FileLockKind::Exclusive
+                    && lock.range.overlaps(&range)
+            });
+
+        inode.insert_record(owner, kind, range);
+
+        // downgrading a lock may allow others to take shared locks
+        if downgraded {
+            Self::wake_waiters(inode);
+        }
+
+        Ok(())
+    }
+
+    /// Release `owner`'s record locks on `range` (`F_UNLCK`).
+    pub fn unlock_record(&mut self, key: FileLockKey, owner: FileLockOwner, range: FileLockRange) {
+        let Some(inode) = self.inodes.get_mut(&key) else {
+            return;
+        };
+
+        if inode.remove_record_range(owner, range) {
+            Self::wake_waiters(inode);
+        }
+
+        self.remove_if_empty(key);
+    }
+
+    /// Take a whole-file `flock` lock for the open file `file`, converting any existing `flock`
+    /// lock held by the open file. If another open file holds a conflicting lock, returns `Err`.
+    ///
+    /// As in Linux, converting a lock is not atomic: the existing lock is released before we check
+    /// for conflicts, so a failed conversion leaves the open file without a lock.
+    pub fn lock_flock(
+        &mut self,
+        key: FileLockKey,
+        file: usize,
+        kind: FileLockKind,
+    ) -> Result<(), ()> {
+        let inode = self.inodes.entry(key).or_default();
+
+        let existing = inode.flocks.iter().position(|(f, _)| *f == file);
+        if let Some(idx) = existing {
+            if inode.flocks[idx].1 == kind {
+                return Ok(());
+            }
+            inode.flocks.remove(idx);
+            Self::wake_waiters(inode);
+        }
+
+        if inode
+            .flocks
+            .iter()
+            .any(|(_, held)| held.conflicts_with(&kind))
+        {
+            self.remove_if_empty(key);
+            return Err(());
+        }
+
+        inode.flocks.push((file, kind));
+        Ok(())
+    }
+
+    /// Release the `flock` lock held by the open file `file` (`LOCK_UN`).
+    pub fn unlock_flock(&mut self, key: FileLockKey, file: usize) {
+        let Some(inode) = self.inodes.get_mut(&key) else {
+            return;
+        };
+
+        let len_before = inode.flocks.len();
+        inode.flocks.retain(|(f, _)| *f != file);
+        if inode.flocks.len() != len_before {
+            Self::wake_waiters(inode);
+        }
+
+        self.remove_if_empty(key);
+    }
+
+    /// Release all record locks held by `owner` on the given file. Used when a process closes any
+    /// descriptor for a file, which releases all of the process' POSIX locks on that file.
+    pub fn release_owner_on(&mut self, key: FileLockKey, owner: FileLockOwner) {
+        self.unlock_record(key, owner, FileLockRange::WHOLE_FILE);
+    }
+
+    /// Release all record and `flock` locks held by `owner` on all files. Used when a process exits
+    /// or when an open file is closed.
+    pub fn release_owner(&mut self, owner: FileLockOwner) {
+        let mut keys: Vec<FileLockKey> = self.inodes.keys().copied().collect();
+        // wake waiters in a deterministic order
+        keys.sort();
+
+        for key in keys {
+            let inode = self.inodes.get_mut(&key).unwrap();
+            let mut changed = inode.remove_record_range(owner, FileLockRange::WHOLE_FILE);
+
+            if let FileLockOwner::OpenFile(file) = owner {
+                let len_before = inode.flocks.len();
+                inode.flocks.retain(|(f, _)| *f != file);
+                changed |= inode.flocks.len() != len_before;
+            }
+
+            if changed {
+                Self::wake_waiters(inode);
+            }
+
+            self.remove_if_empty(key);
+        }
+    }
+
+    /// Release the POSIX record locks held by process `pid` on the file referenced by `file`. Linux
+    /// releases all of a process' POSIX locks on a file when the process closes any descriptor
+    /// for that file, even if other descriptors for the file remain open.
+    pub fn release_on_close(&mut self, pid: ProcessId, file: &CompatFile) {
+        let CompatFile::Legacy(file) = file else {
+            // we only support locks on regular files, which are all legacy files
+            return;
+        };
+
+        if let Some(key) = FileLockKey::for_legacy_file(file.ptr()) {
+            self.release_owner_on(key, FileLockOwner::Process(pid));
+        }
+    }
+
+    /// Returns a futex that threads waiting for locks on `key` to be released can block on. The
+    /// futex is woken whenever a lock on the file is released or downgraded. The returned pointer
+    /// is borrowed and is only valid until the table is next modified.
+    pub fn wait_queue(&mut self, key: FileLockKey) -> *mut c::Futex {
+        let inode = self.inodes.entry(key).or_default();
+        inode
+            .waiters
+            .get_or_insert_with(|| {
+                // The futex is never added to the host's futex table, so the address is only used
+                // for logging.
+                let addr = ManagedPhysicalMemoryAddr::from(key.ino);
+                unsafe { FutexRef::new(c::futex_new(addr)) }
+            })
+            .ptr()
+    }
+
+    /// Release memory that is no longer needed.
+    pub fn reclaim_memory(&mut self) {
+        crate::utility::shrink_if_sparse(&mut self.inodes);
+    }
+
+    fn wake_waiters(inode: &mut InodeLocks) {
+        // Waiters will retry taking their lock, and will wait again if it's still unavailable.
+        if let Some(waiters) = inode.waiters.take() {
+            waiters.wake(libc::c_uint::MAX);
+        }
+    }
+
+    fn remove_if_empty(&mut self, key: FileLockKey) {
+        if self.inodes.get(&key).is_some_and(|x| x.is_empty()) {
+            self.inodes.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: FileLockKey = FileLockKey { dev: 1, ino: 2 };
+
+    fn pid(x: u32) -> FileLockOwner {
+        FileLockOwner::Process(ProcessId::try_from(x).unwrap())
+    }
+
+    fn records(table: &FileLockTable) -> Vec<RecordLock> {
+        table
+            .inodes
+            .get(&KEY)
+            .map(|x| x.records.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_shared_locks_coexist() {
+        let mut table = FileLockTable::new();
+        let range = FileLockRange::new(0, 10);
+
+        table
+            .lock_record(KEY, pid(1), FileLockKind::Shared, range)
+            .unwrap();
+        table
+            .lock_record(KEY, pid(2), FileLockKind::Shared, range)
+            .unwrap();
+
+        let conflict = table
+            .lock_record(
+                KEY,
+                pid(3),
+                FileLockKind::Exclusive,
+                FileLockRange::new(5, 6),
+            )
+            .unwrap_err();
+        assert_eq!(conflict.owner, pid(1));
+        assert_eq!(
+            table.test_record(KEY, pid(3), FileLockKind::Shared, range),
+            None
+        );
+    }
+
+    #[test]
+    fn test_exclusive_lock_conflicts() {
+        let mut table = FileLockTable::new();
+
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Exclusive,
+                FileLockRange::new(10, 20),
+            )
+            .unwrap();
+
+        // non-overlapping ranges don't conflict
+        table
+            .lock_record(
+                KEY,
+                pid(2),
+                FileLockKind::Exclusive,
+                FileLockRange::new(0, 10),
+            )
+            .unwrap();
+        table
+            .lock_record(
+                KEY,
+                pid(2),
+                FileLockKind::Exclusive,
+                FileLockRange::new(20, u64::MAX),
+            )
+            .unwrap();
+
+        assert!(table
+            .lock_record(
+                KEY,
+                pid(2),
+                FileLockKind::Shared,
+                FileLockRange::new(19, 21)
+            )
+            .is_err());
+
+        // an owner never conflicts with itself
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Shared,
+                FileLockRange::new(10, 20),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_unlock_splits_range() {
+        let mut table = FileLockTable::new();
+
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Exclusive,
+                FileLockRange::new(0, 30),
+            )
+            .unwrap();
+        table.unlock_record(KEY, pid(1), FileLockRange::new(10, 20));
+
+        assert_eq!(
+            records(&table),
+            vec![
+                RecordLock {
+                    owner: pid(1),
+                    kind: FileLockKind::Exclusive,
+                    range: FileLockRange::new(0, 10),
+                },
+                RecordLock {
+                    owner: pid(1),
+                    kind: FileLockKind::Exclusive,
+                    range: FileLockRange::new(20, 30),
+                },
+            ]
+        );
+
+        table
+            .lock_record(
+                KEY,
+                pid(2),
+                FileLockKind::Exclusive,
+                FileLockRange::new(10, 20),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_adjacent_locks_merge() {
+        let mut table = FileLockTable::new();
+
+        table
+            .lock_record(KEY, pid(1), FileLockKind::Shared, FileLockRange::new(0, 10))
+            .unwrap();
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Shared,
+                FileLockRange::new(10, 20),
+            )
+            .unwrap();
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Exclusive,
+                FileLockRange::new(5, 15),
+            )
+            .unwrap();
+
+        assert_eq!(
+            records(&table)
+                .iter()
+                .map(|x| (x.kind, x.range.start, x.range.end))
+                .collect::<Vec<_>>(),
+            vec![
+                (FileLockKind::Shared, 0, 5),
+                (FileLockKind::Exclusive, 5, 15),
+                (FileLockKind::Shared, 15, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_release_owner() {
+        let mut table = FileLockTable::new();
+        let ofd = FileLockOwner::OpenFile(0x1000);
+
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Exclusive,
+                FileLockRange::new(0, 10),
+            )
+            .unwrap();
+        table
+            .lock_record(KEY, ofd, FileLockKind::Shared, FileLockRange::new(10, 20))
+            .unwrap();
+        table
+            .lock_flock(KEY, 0x1000, FileLockKind::Exclusive)
+            .unwrap();
+
+        table.release_owner(pid(1));
+        assert_eq!(records(&table).len(), 1);
+
+        table.release_owner(ofd);
+        assert!(table.inodes.is_empty());
+    }
+
+    #[test]
+    fn test_failed_lock_leaves_no_entry() {
+        let mut table = FileLockTable::new();
+        let range = FileLockRange::new(0, 10);
+
+        table
+            .lock_record(KEY, pid(1), FileLockKind::Exclusive, range)
+            .unwrap();
+        assert!(table
+            .lock_record(KEY, pid(2), FileLockKind::Shared, range)
+            .is_err());
+        table.unlock_record(KEY, pid(1), range);
+        assert!(table.inodes.is_empty());
+
+        table.lock_flock(KEY, 1, FileLockKind::Exclusive).unwrap();
+        assert!(table.lock_flock(KEY, 2, FileLockKind::Shared).is_err());
+        table.unlock_flock(KEY, 1);
+        assert!(table.inodes.is_empty());
+    }
+
+    #[test]
+    fn test_flock() {
+        let mut table = FileLockTable::new();
+
+        table.lock_flock(KEY, 1, FileLockKind::Shared).unwrap();
+        table.lock_flock(KEY, 2, FileLockKind::Shared).unwrap();
+        assert!(table.lock_flock(KEY, 3, FileLockKind::Exclusive).is_err());
+
+        // flock locks don't interact with record locks
+        table
+            .lock_record(
+                KEY,
+                pid(1),
+                FileLockKind::Exclusive,
+                FileLockRange::WHOLE_FILE,
+            )
+            .unwrap();
+
+        table.unlock_flock(KEY, 1);
+        assert!(table.lock_flock(KEY, 2, FileLockKind::Exclusive).is_ok());
+        assert!(table.lock_flock(KEY, 1, FileLockKind::Shared).is_err());
+    }
+}
@@ -45,6 +45,11 @@ impl FutexTable {
     pub fn get(&self, addr: ManagedPhysicalMemoryAddr) -> Option<&FutexRef> {
         self.futexes.get(&addr)
     }
+
+    /// Release memory that is no longer needed.
+    pub fn reclaim_memory(&mut self) {
+        crate::utility::shrink_if_sparse(&mut self.futexes);
+    }
 }
 
 /// An owned reference to a [`Futex`][c::Futex].
@@ -1,9 +1,9 @@
 //! An emulated Linux system.
 
 use std::cell::{Cell, Ref, RefCell, RefMut, UnsafeCell};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::{CStr, CString, OsString};
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
 use std::num::NonZeroU8;
 use std::ops::{Deref, DerefMut};
 use std::os::unix::prelude::OsStringExt;
@@ -15,7 +15,8 @@ use linux_api::signal::{siginfo_t, Signal};
 use log::{debug, trace};
 use logger::LogLevel;
 use once_cell::unsync::OnceCell;
-use rand::SeedableRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use rand_xoshiro::Xoshiro256PlusPlus;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::explicit_drop::ExplicitDropper;
@@ -31,26 +32,41 @@ use shadow_shmem::allocator::ShMemBlock;
 use shadow_tsc::Tsc;
 use vasi_sync::scmutex::SelfContainedMutexGuard;
 
-use crate::core::configuration::{ProcessFinalState, QDiscMode};
-use crate::core::sim_config::PcapConfig;
+use crate::core::configuration::{
+    EgressQdisc, FirewallOptions, MiddleboxOptions, NatOptions, OutputLimitAction,
+    ProcessFinalState, QDiscMode, RestartPolicy, RouterQueue,
+};
+use crate::core::sim_config::{
+    PcapConfig, ProcessRestart, ProcessSignal, ProcessStartCondition, TcpTunables,
+};
 use crate::core::work::event::{Event, EventData};
 use crate::core::work::event_queue::EventQueue;
 use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow;
+use crate::host::descriptor::mqueue::MessageQueueNamespace;
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
 use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::file_lock_table::FileLockTable;
 use crate::host::futex_table::FutexTable;
+use crate::host::network::firewall::Firewall;
 use crate::host::network::interface::{FifoPacketPriority, NetworkInterface, PcapOptions};
 use crate::host::network::namespace::NetworkNamespace;
-use crate::host::process::Process;
+use crate::host::network::qdisc::new_qdisc;
+use crate::host::process::{ExitHandler, Process};
+use crate::host::sysv_ipc::SysvIpcTable;
 use crate::host::thread::ThreadId;
+use crate::network::link_trace::{LinkTrace, LinkTraceRow};
+use crate::network::middlebox::{self, Middlebox, MiddleboxVerdict};
+use crate::network::nat::{Nat, NatAction, Subnet};
+use crate::network::packet::{PacketRc, PacketStatus};
 use crate::network::relay::{RateLimit, Relay};
-use crate::network::router::Router;
+use crate::network::router::{InboundQueue, Router};
 use crate::network::PacketDevice;
 use crate::utility;
 #[cfg(feature = "perf_timers")]
 use crate::utility::perf_timer::PerfTimer;
+use crate::utility::units::{self, Unit};
 
 pub struct HostParameters {
     pub id: HostId,
@@ -60,9 +76,18 @@ pub struct HostParameters {
     pub hostname: CString,
     pub node_id: u32,
     pub ip_addr: libc::in_addr_t,
+    /// The time at which the host joins the network, before which its name can't be resolved.
+    pub start_time: SimulationTime,
     pub sim_end_time: EmulatedTime,
     pub requested_bw_down_bits: u64,
     pub requested_bw_up_bits: u64,
+    /// The size of the bursts allowed above the requested bandwidths.
+    pub requested_bw_burst_bytes: u64,
+    /// The maximum bandwidth of bursts, if limited.
+    pub requested_bw_peak_bits: Option<u64>,
+    /// A trace that changes the upstream bandwidth, and adds latency and packet loss to the
+    /// packets sent to the network, if any.
+    pub uplink_trace: Option<Arc<LinkTrace>>,
     pub cpu_frequency: u64,
     pub cpu_threshold: Option<SimulationTime>,
     pub cpu_precision: Option<SimulationTime>,
@@ -71,11 +96,35 @@ pub struct HostParameters {
     pub heartbeat_log_info: cshadow::LogInfoFlags,
     pub log_level: LogLevel,
     pub pcap_config: Option<PcapConfig>,
+    /// The maximum size of each process's stdout and stderr files, if limited.
+    pub output_max_size: Option<u64>,
+    /// What to do when a stdout or stderr file reaches its maximum size.
+    pub output_limit_action: OutputLimitAction,
     pub qdisc: QDiscMode,
+    /// The queuing discipline of the internet interface's sent packets, if any.
+    pub egress_qdisc: Option<EgressQdisc>,
+    /// The queue of the router's received packets.
+    pub router_queue: RouterQueue,
+    /// The options of the NAT gateway that the host runs, if any.
+    pub nat: Option<NatOptions>,
+    /// The rules of the middlebox that the host runs, if any.
+    pub middlebox: Option<MiddleboxOptions>,
+    /// The firewall rules of the internet interface, if any.
+    pub firewall: Option<FirewallOptions>,
+    /// The addresses of the network interfaces in addition to `eth0`.
+    pub interface_addrs: Vec<Ipv4Addr>,
+    /// The routes, and the index of each route's interface, where `eth0` is 0.
+    pub routes: Vec<(Subnet, usize)>,
     pub init_sock_recv_buf_size: u64,
     pub autotune_recv_buf: bool,
     pub init_sock_send_buf_size: u64,
     pub autotune_send_buf: bool,
+    /// The minimum, initial, and maximum sizes of TCP receive buffers, if configured.
+    pub tcp_rmem: Option<(u64, u64, u64)>,
+    /// The minimum, initial, and maximum sizes of TCP send buffers, if configured.
+    pub tcp_wmem: Option<(u64, u64, u64)>,
+    /// The initial congestion window, timeouts, and other parameters of new TCP sockets.
+    pub tcp_tunables: TcpTunables,
     pub native_tsc_frequency: u64,
     pub model_unblocked_syscall_latency: bool,
     pub max_unapplied_cpu_latency: SimulationTime,
@@ -84,6 +133,9 @@ pub struct HostParameters {
     pub strace_logging_options: Option<FmtOptions>,
     pub shim_log_level: LogLevel,
     pub use_new_tcp: bool,
+    pub use_tcp_sack: bool,
+    pub use_tcp_ecn: bool,
+    pub router_ecn_marking: bool,
     pub use_mem_mapper: bool,
     pub use_syscall_counters: bool,
 }
@@ -92,6 +144,39 @@ use super::cpu::Cpu;
 use super::process::ProcessId;
 use super::syscall::formatter::FmtOptions;
 
+/// How often an application's start conditions are checked until they're met.
+const START_CONDITION_INTERVAL: SimulationTime =
+    SimulationTime::from_duration(std::time::Duration::from_millis(10));
+
+/// An application that the host runs, and restarts according to its restart policy.
+struct Application {
+    /// The index of the application in the host's applications.
+    index: usize,
+    start_after: Vec<ProcessStartCondition>,
+    plugin_name: CString,
+    plugin_path: CString,
+    argv: Vec<CString>,
+    envv: Vec<CString>,
+    pause_for_debugging: bool,
+    expected_final_state: ProcessFinalState,
+    shutdown_time: Option<SimulationTime>,
+    shutdown_signal: nix::sys::signal::Signal,
+    signals: Vec<ProcessSignal>,
+    restart: Option<ProcessRestart>,
+}
+
+/// What happened to an application's processes so far, for the start conditions of the host's
+/// other applications.
+#[derive(Debug, Default)]
+struct ApplicationStatus {
+    /// When the application's latest process started.
+    start_time: Option<EmulatedTime>,
+    /// When the application's latest process exited.
+    exit_time: Option<EmulatedTime>,
+    /// Whether the application's process exited and won't be restarted.
+    finished: bool,
+}
+
 /// Immutable information about the Host.
 #[derive(Debug, Clone)]
 pub struct HostInfo {
@@ -121,11 +206,34 @@ pub struct Host {
 
     random: RefCell<Xoshiro256PlusPlus>,
 
+    // The source of the random bytes that the host's processes read from `getrandom`,
+    // `/dev/urandom`, `/dev/random`, and `AT_RANDOM`. This is kept separate from `random` so that
+    // a process reading random bytes doesn't change shadow's other random decisions.
+    random_bytes: RefCell<ChaCha20Rng>,
+
+    // The secret that the host's TCP servers use to generate TCP Fast Open cookies. This is taken
+    // from a separate stream so that it doesn't change the host's other random decisions.
+    tcp_fastopen_key: u64,
+
+    // The TCP Fast Open cookies that the host's TCP clients have received, by server address.
+    tcp_fastopen_cookies: RefCell<HashMap<Ipv4Addr, u64>>,
+
+    // The number of packets that the host sent since the last packet that was reordered, by the
+    // source and destination addresses of the path. This is only tracked for paths with a
+    // reordering gap.
+    packets_since_reorder: RefCell<HashMap<(IpAddr, IpAddr), u32>>,
+
     // The upstream router that will queue packets until we can receive them.
     // This only applies to the internet interface; the localhost interface
     // does not receive packets from a router.
     router: RefCell<Router>,
 
+    // The address translation state of the host, if it's a NAT gateway.
+    nat: Option<RefCell<Nat>>,
+
+    // The rules that the host applies to the packets that it intercepts, if it's a middlebox.
+    middlebox: Option<Middlebox>,
+
     // Forwards packets out from our internet interface to the router.
     relay_inet_out: Arc<Relay>,
     // Forwards packets from the router in to our internet interface.
@@ -139,6 +247,15 @@ pub struct Host {
     // map address to futex objects
     futex_table: RefCell<FutexTable>,
 
+    // advisory file locks held by processes on this host
+    file_lock_table: RefCell<FileLockTable>,
+
+    // names of POSIX message queues on this host
+    mqueue_namespace: RefCell<MessageQueueNamespace>,
+
+    // System V shared memory segments and semaphore sets on this host
+    sysv_ipc: RefCell<SysvIpcTable>,
+
     #[cfg(feature = "perf_timers")]
     execution_timer: RefCell<PerfTimer>,
 
@@ -170,6 +287,9 @@ pub struct Host {
     // Owned pointers to processes.
     processes: RefCell<BTreeMap<ProcessId, RootedRc<RootedRefCell<Process>>>>,
 
+    // the status of each application from the configuration, in the order they were added
+    applications: RefCell<Vec<ApplicationStatus>>,
+
     tsc: Tsc,
     // Cached lock for shim_shmem. `[Host::shmem_lock]` uses unsafe code to give it
     // a 'static lifetime.
@@ -231,6 +351,13 @@ impl Host {
 
         let root = Root::new();
         let random = RefCell::new(Xoshiro256PlusPlus::seed_from_u64(params.node_seed));
+        let random_bytes = RefCell::new(ChaCha20Rng::seed_from_u64(params.node_seed));
+
+        let tcp_fastopen_key = {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(params.node_seed);
+            rng.long_jump();
+            rng.next_u64()
+        };
         let cpu = RefCell::new(Cpu::new(
             params.cpu_frequency,
             raw_cpu_freq_khz,
@@ -279,15 +406,23 @@ impl Host {
         let pcap_options = params.pcap_config.as_ref().map(|x| PcapOptions {
             path: data_dir_path.clone(),
             capture_size_bytes: x.capture_size.try_into().unwrap(),
+            filter: x.filter.clone(),
+            max_size_bytes: x.max_size,
+            limit_action: x.limit_action,
         });
 
         let net_ns = unsafe {
             NetworkNamespace::new(
                 params.id,
                 hostname,
+                params.start_time,
                 public_ip,
                 pcap_options,
                 params.qdisc,
+                params.egress_qdisc.map(|x| new_qdisc(&x, params.node_seed)),
+                params.firewall.as_ref().map(Firewall::new),
+                &params.interface_addrs,
+                params.routes.clone(),
                 dns,
             )
         };
@@ -295,13 +430,21 @@ impl Host {
         // Packets that are not for localhost or our public ip go to the router.
         // Use `Ipv4Addr::UNSPECIFIED` for the router to encode this for our
         // routing table logic inside of `Host::get_packet_device()`.
-        let router = Router::new(Ipv4Addr::UNSPECIFIED);
+        let router = Router::new(
+            Ipv4Addr::UNSPECIFIED,
+            InboundQueue::new(
+                &params.router_queue,
+                params.router_ecn_marking,
+                params.requested_bw_down_bits,
+                params.node_seed,
+            ),
+        );
         let relay_inet_out = Relay::new(
-            RateLimit::BytesPerSecond(params.requested_bw_up_bits / 8),
+            relay_rate_limit(&params, params.requested_bw_up_bits),
             net_ns.internet.borrow().get_address(),
         );
         let relay_inet_in = Relay::new(
-            RateLimit::BytesPerSecond(params.requested_bw_down_bits / 8),
+            relay_rate_limit(&params, params.requested_bw_down_bits),
             router.get_address(),
         );
         let relay_loopback = Relay::new(
@@ -309,6 +452,9 @@ impl Host {
             net_ns.localhost.borrow().get_address(),
         );
 
+        let nat = params.nat.map(|x| RefCell::new(Nat::new(public_ip, &x)));
+        let middlebox = params.middlebox.as_ref().map(Middlebox::new);
+
         let in_notify_socket_has_packets = RootedCell::new(&root, false);
 
         let res = Self {
@@ -317,12 +463,21 @@ impl Host {
             event_queue: Arc::new(Mutex::new(EventQueue::new())),
             params,
             router: RefCell::new(router),
+            nat,
+            middlebox,
             relay_inet_out: Arc::new(relay_inet_out),
             relay_inet_in: Arc::new(relay_inet_in),
             relay_loopback: Arc::new(relay_loopback),
             tracker: RefCell::new(None),
             futex_table: RefCell::new(FutexTable::new()),
+            file_lock_table: RefCell::new(FileLockTable::new()),
+            mqueue_namespace: RefCell::new(MessageQueueNamespace::new()),
+            sysv_ipc: RefCell::new(SysvIpcTable::new()),
             random,
+            random_bytes,
+            tcp_fastopen_key,
+            tcp_fastopen_cookies: RefCell::new(HashMap::new()),
+            packets_since_reorder: RefCell::new(HashMap::new()),
             shim_shmem,
             shim_shmem_lock: RefCell::new(None),
             cpu,
@@ -336,6 +491,7 @@ impl Host {
             determinism_sequence_counter,
             tsc,
             processes: RefCell::new(BTreeMap::new()),
+            applications: RefCell::new(Vec::new()),
             #[cfg(feature = "perf_timers")]
             execution_timer,
             in_notify_socket_has_packets,
@@ -392,66 +548,203 @@ impl Host {
     pub fn add_application(
         &self,
         start_time: SimulationTime,
+        start_after: Vec<ProcessStartCondition>,
         shutdown_time: Option<SimulationTime>,
         shutdown_signal: nix::sys::signal::Signal,
+        signals: Vec<ProcessSignal>,
         plugin_name: CString,
         plugin_path: CString,
         argv: Vec<CString>,
         envv: Vec<CString>,
         pause_for_debugging: bool,
         expected_final_state: ProcessFinalState,
+        restart: Option<ProcessRestart>,
     ) {
         debug_assert!(shutdown_time.is_none() || shutdown_time.unwrap() > start_time);
 
+        let index = {
+            let mut applications = self.applications.borrow_mut();
+            applications.push(ApplicationStatus::default());
+            applications.len() - 1
+        };
+
+        let app = Arc::new(Application {
+            index,
+            start_after,
+            plugin_name,
+            plugin_path,
+            argv,
+            envv,
+            pause_for_debugging,
+            expected_final_state,
+            shutdown_time,
+            shutdown_signal,
+            signals,
+            restart,
+        });
+
         // Schedule spawning the process.
-        let task = TaskRef::new(move |host| {
-            // We can't move out of these captured variables, since TaskRef takes
-            // a Fn, not a FnOnce.
-            // TODO: Add support for FnOnce?
-            let envv = envv.clone();
-            let argv = argv.clone();
-            let plugin_name = plugin_name.clone();
-
-            let process = Process::spawn(
-                host,
-                plugin_name,
-                &plugin_path,
-                argv,
-                envv,
-                pause_for_debugging,
-                host.params.strace_logging_options,
-                expected_final_state,
-            )
-            .expect("Failed to initialize application {plugin_name:?}");
-            let (process_id, thread_id) = {
-                let process = process.borrow(host.root());
-                (process.id(), process.thread_group_leader_id())
-            };
-            host.processes.borrow_mut().insert(process_id, process);
-
-            if let Some(shutdown_time) = shutdown_time {
-                let task = TaskRef::new(move |host| {
-                    let Some(process) = host.process_borrow(process_id) else {
-                        debug!("Can't send shutdown signal to process {process_id}; it no longer exists");
-                        return;
-                    };
-                    let process = process.borrow(host.root());
-                    let siginfo_t = siginfo_t::new_for_kill(
-                        Signal::try_from(shutdown_signal as i32).unwrap(),
-                        1,
-                        0,
-                    );
-                    process.signal(host, None, &siginfo_t);
-                });
-                host.schedule_task_at_emulated_time(
-                    task,
-                    EmulatedTime::SIMULATION_START + shutdown_time,
-                );
+        let task = TaskRef::new(move |host| host.start_application(&app));
+        self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + start_time);
+    }
+
+    /// Spawn the application's first process if its start conditions are met, or otherwise check
+    /// them again later.
+    fn start_application(&self, app: &Arc<Application>) {
+        if app
+            .start_after
+            .iter()
+            .all(|x| self.is_start_condition_met(x))
+        {
+            self.spawn_application(app, 0);
+            return;
+        }
+
+        // the process isn't started if the conditions aren't met before it would be shut down
+        let next_check = Worker::current_time().unwrap() + START_CONDITION_INTERVAL;
+        let end_time = match app.shutdown_time {
+            Some(x) => std::cmp::min(self.params.sim_end_time, EmulatedTime::SIMULATION_START + x),
+            None => self.params.sim_end_time,
+        };
+        if next_check >= end_time {
+            log::warn!(
+                "The start conditions of process {:?} were never met, so it didn't start",
+                app.plugin_name,
+            );
+            return;
+        }
+
+        let app = Arc::clone(app);
+        let task = TaskRef::new(move |host| host.start_application(&app));
+        self.schedule_task_with_delay(task, START_CONDITION_INTERVAL);
+    }
+
+    fn is_start_condition_met(&self, condition: &ProcessStartCondition) -> bool {
+        let applications = self.applications.borrow();
+        match *condition {
+            ProcessStartCondition::Exited(index) => applications[index].finished,
+            ProcessStartCondition::Running(index, duration) => {
+                let status = &applications[index];
+                status.start_time.is_some_and(|start| {
+                    let end = status
+                        .exit_time
+                        .unwrap_or_else(|| Worker::current_time().unwrap());
+                    end.duration_since(&start) >= duration
+                })
             }
+            ProcessStartCondition::Listening(port) => self.net_ns.is_listening(port),
+        }
+    }
 
-            host.resume(process_id, thread_id);
+    /// Spawn a process for the application, which has already been restarted `restarts` times.
+    fn spawn_application(&self, app: &Arc<Application>, restarts: u32) {
+        let exit_handler: ExitHandler = {
+            let app = Arc::clone(app);
+            Box::new(move |host: &Host, is_expected: bool| {
+                let restarting = host.restart_application(&app, restarts, is_expected);
+                let status = &mut host.applications.borrow_mut()[app.index];
+                status.exit_time = Some(Worker::current_time().unwrap());
+                status.finished = !restarting;
+                if !restarting {
+                    Worker::process_finished(host.name(), app.index, app.expected_final_state);
+                }
+                restarting
+            })
+        };
+
+        let process = Process::spawn(
+            self,
+            app.plugin_name.clone(),
+            &app.plugin_path,
+            app.argv.clone(),
+            app.envv.clone(),
+            app.pause_for_debugging,
+            self.params.strace_logging_options,
+            app.expected_final_state,
+            Some(exit_handler),
+        )
+        .expect("Failed to initialize application {plugin_name:?}");
+        let (process_id, thread_id) = {
+            let process = process.borrow(self.root());
+            (process.id(), process.thread_group_leader_id())
+        };
+        self.processes.borrow_mut().insert(process_id, process);
+
+        self.applications.borrow_mut()[app.index] = ApplicationStatus {
+            start_time: Some(Worker::current_time().unwrap()),
+            ..Default::default()
+        };
+
+        if let Some(shutdown_time) = app.shutdown_time {
+            self.schedule_signal(process_id, app.shutdown_signal, shutdown_time);
+        }
+
+        // a restarted process only receives the signals that are scheduled after it starts
+        let now = Worker::current_time().unwrap();
+        for x in &app.signals {
+            if EmulatedTime::SIMULATION_START + x.time >= now {
+                self.schedule_signal(process_id, x.signal, x.time);
+            }
+        }
+
+        self.resume(process_id, thread_id);
+    }
+
+    /// Schedule restarting the application after its process exited, if its restart policy allows
+    /// it. Returns whether the application will be restarted.
+    fn restart_application(
+        &self,
+        app: &Arc<Application>,
+        restarts: u32,
+        is_expected: bool,
+    ) -> bool {
+        let Some(restart) = &app.restart else {
+            return false;
+        };
+        if restart.policy == RestartPolicy::OnFailure && is_expected {
+            return false;
+        }
+        if restart.max_restarts.is_some_and(|x| restarts >= x) {
+            return false;
+        }
+
+        // don't restart the process if it wouldn't start before the simulation ends or before its
+        // shutdown time
+        let delay = restart.delay(restarts);
+        let restart_time = Worker::current_time().unwrap() + delay;
+        if restart_time >= self.params.sim_end_time {
+            return false;
+        }
+        if app
+            .shutdown_time
+            .is_some_and(|x| restart_time >= EmulatedTime::SIMULATION_START + x)
+        {
+            return false;
+        }
+
+        let app = Arc::clone(app);
+        let task = TaskRef::new(move |host| host.spawn_application(&app, restarts + 1));
+        self.schedule_task_with_delay(task, delay);
+        true
+    }
+
+    /// Send a signal to the process at a simulated time, if the process still exists.
+    fn schedule_signal(
+        &self,
+        process_id: ProcessId,
+        signal: nix::sys::signal::Signal,
+        time: SimulationTime,
+    ) {
+        let task = TaskRef::new(move |host| {
+            let Some(process) = host.process_borrow(process_id) else {
+                debug!("Can't send signal {signal} to process {process_id}; it no longer exists");
+                return;
+            };
+            let process = process.borrow(host.root());
+            let siginfo_t = siginfo_t::new_for_kill(Signal::try_from(signal as i32).unwrap(), 1, 0);
+            process.signal(host, None, &siginfo_t);
         });
-        self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + start_time);
+        self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + time);
     }
 
     pub fn add_and_schedule_forked_process(
@@ -536,12 +829,27 @@ impl Host {
         }
 
         // Free orphaned zombies.
-        let mut processes = self.processes.borrow_mut();
-        for pid in orphaned_zombie_pids {
-            trace!("Dropping orphan zombie process {pid:?}");
-            let processrc = processes.remove(&pid).unwrap();
-            RootedRc::explicit_drop_recursive(processrc, &self.root, self);
+        {
+            let mut processes = self.processes.borrow_mut();
+            for pid in orphaned_zombie_pids {
+                trace!("Dropping orphan zombie process {pid:?}");
+                let processrc = processes.remove(&pid).unwrap();
+                RootedRc::explicit_drop_recursive(processrc, &self.root, self);
+            }
         }
+
+        // The process' teardown is complete, so any per-host state that it was using has been
+        // released.
+        self.reclaim_memory();
+    }
+
+    /// Release memory held by per-host tables that grew larger than they currently need to be.
+    fn reclaim_memory(&self) {
+        self.futextable_borrow_mut().reclaim_memory();
+        self.file_lock_table_borrow_mut().reclaim_memory();
+        self.mqueue_namespace_borrow_mut().reclaim_memory();
+        self.sysv_ipc_borrow_mut().reclaim_memory();
+        self.abstract_unix_namespace().borrow_mut().reclaim_memory();
     }
 
     #[track_caller]
@@ -653,6 +961,23 @@ impl Host {
         self.futex_table.borrow_mut()
     }
 
+    #[track_caller]
+    pub fn file_lock_table_borrow_mut(&self) -> impl DerefMut<Target = FileLockTable> + '_ {
+        self.file_lock_table.borrow_mut()
+    }
+
+    #[track_caller]
+    pub fn mqueue_namespace_borrow_mut(
+        &self,
+    ) -> impl DerefMut<Target = MessageQueueNamespace> + '_ {
+        self.mqueue_namespace.borrow_mut()
+    }
+
+    #[track_caller]
+    pub fn sysv_ipc_borrow_mut(&self) -> impl DerefMut<Target = SysvIpcTable> + '_ {
+        self.sysv_ipc.borrow_mut()
+    }
+
     #[allow(non_snake_case)]
     pub fn bw_up_kiBps(&self) -> u64 {
         self.params.requested_bw_up_bits / (8 * 1024)
@@ -688,6 +1013,45 @@ impl Host {
         self.random.borrow_mut()
     }
 
+    /// Fills the buffer with random bytes from the host's deterministic stream of random bytes.
+    /// This is the source of all random bytes that are given to the host's processes.
+    pub fn fill_random_bytes(&self, buf: &mut [u8]) {
+        self.random_bytes.borrow_mut().fill_bytes(buf);
+    }
+
+    /// The secret that the host's TCP servers use to generate TCP Fast Open cookies.
+    pub fn tcp_fastopen_key(&self) -> u64 {
+        self.tcp_fastopen_key
+    }
+
+    /// The TCP Fast Open cookie previously received from the server at `addr`, if any.
+    pub fn tcp_fastopen_cookie(&self, addr: Ipv4Addr) -> Option<u64> {
+        self.tcp_fastopen_cookies.borrow().get(&addr).copied()
+    }
+
+    /// Store the TCP Fast Open cookie received from the server at `addr`.
+    pub fn set_tcp_fastopen_cookie(&self, addr: Ipv4Addr, cookie: u64) {
+        self.tcp_fastopen_cookies.borrow_mut().insert(addr, cookie);
+    }
+
+    /// The number of packets sent over the path from `src` to `dst` since the last packet on the
+    /// path that was reordered.
+    pub fn packets_since_reorder(&self, src: IpAddr, dst: IpAddr) -> u32 {
+        self.packets_since_reorder
+            .borrow()
+            .get(&(src, dst))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Set the number of packets sent over the path from `src` to `dst` since the last packet on
+    /// the path that was reordered.
+    pub fn set_packets_since_reorder(&self, src: IpAddr, dst: IpAddr, count: u32) {
+        self.packets_since_reorder
+            .borrow_mut()
+            .insert((src, dst), count);
+    }
+
     pub fn get_new_event_id(&self) -> u64 {
         let res = self.event_id_counter.get();
         self.event_id_counter.set(res + 1);
@@ -767,6 +1131,49 @@ impl Host {
                 .borrow_mut()
                 .replace(unsafe { SyncSendPointer::new(tracker) });
         }
+
+        let send_buf_size = self.params.init_sock_send_buf_size;
+        self.net_ns
+            .start_icmp_echo_responders(send_buf_size.try_into().unwrap());
+
+        self.schedule_uplink_bandwidth(0);
+    }
+
+    /// Schedule a task that changes the upstream bandwidth at the time of the first row of the
+    /// uplink trace, starting from the row at `index`, that changes the bandwidth. The task then
+    /// schedules the next change.
+    fn schedule_uplink_bandwidth(&self, index: usize) {
+        let Some(trace) = &self.params.uplink_trace else {
+            return;
+        };
+
+        let rows = trace.rows();
+        let Some((index, row)) = rows.iter().enumerate().skip(index).find(|(i, row)| {
+            row.bandwidth.is_some() && (*i == 0 || rows[i - 1].bandwidth != row.bandwidth)
+        }) else {
+            return;
+        };
+
+        let bw_bits = row
+            .bandwidth
+            .unwrap()
+            .convert(units::SiPrefixUpper::Base)
+            .unwrap()
+            .value();
+
+        let task = TaskRef::new(move |host| {
+            host.relay_inet_out
+                .set_rate_limit(relay_rate_limit(&host.params, bw_bits));
+            host.schedule_uplink_bandwidth(index + 1);
+        });
+        self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + row.time);
+    }
+
+    /// The row of the uplink trace at `time`, if the host has an uplink trace and its first row
+    /// has started.
+    pub fn uplink_trace_row(&self, time: EmulatedTime) -> Option<&LinkTraceRow> {
+        let trace = self.params.uplink_trace.as_ref()?;
+        trace.at(time.duration_since(&EmulatedTime::SIMULATION_START))
     }
 
     /// Shut down the host. This should be called while `Worker` has the active host set.
@@ -807,7 +1214,10 @@ impl Host {
         trace!("done freeing application for host '{}'", self.name());
     }
 
-    pub fn execute(&self, until: EmulatedTime) {
+    /// Execute the host's events that occur before `until`. Returns the number of events executed.
+    pub fn execute(&self, until: EmulatedTime) -> u64 {
+        let mut num_events = 0;
+
         loop {
             let mut event = {
                 let mut event_queue = self.event_queue.lock().unwrap();
@@ -853,15 +1263,85 @@ impl Host {
             self.continue_execution_timer();
             match event.data() {
                 EventData::Packet(data) => {
-                    self.upstream_router_borrow_mut()
-                        .route_incoming_packet(data.into());
-                    self.notify_router_has_packets();
+                    let packet = self
+                        .translate_nat(data.into())
+                        .and_then(|x| self.intercept_packet(x));
+                    if let Some(packet) = packet {
+                        self.upstream_router_borrow_mut()
+                            .route_incoming_packet(packet);
+                        self.notify_router_has_packets();
+                    }
                 }
                 EventData::Local(data) => TaskRef::from(data).execute(self),
             }
             self.stop_execution_timer();
             Worker::clear_current_time();
+            num_events += 1;
+        }
+
+        num_events
+    }
+
+    /// If the host is a NAT gateway, translate a packet that arrived from the network and forward
+    /// it if needed. Returns the packet if it's for this host.
+    fn translate_nat(&self, mut packet: PacketRc) -> Option<PacketRc> {
+        let Some(nat) = &self.nat else {
+            return Some(packet);
+        };
+
+        let action = nat
+            .borrow_mut()
+            .translate(&mut packet, Worker::current_time().unwrap());
+
+        match action {
+            NatAction::Deliver => Some(packet),
+            NatAction::Forward => {
+                unsafe { Worker::send_packet(self, packet.borrow_inner()) };
+                None
+            }
+            NatAction::Drop => {
+                packet.add_status(PacketStatus::InetDropped);
+                None
+            }
+        }
+    }
+
+    /// If the host is a middlebox, apply its rules to a packet that it intercepted, and forward the
+    /// packet if needed. Returns the packet if it's for this host.
+    fn intercept_packet(&self, mut packet: PacketRc) -> Option<PacketRc> {
+        let Some(middlebox) = &self.middlebox else {
+            return Some(packet);
+        };
+
+        if self.net_ns.is_internet_address(*packet.dst_address().ip()) {
+            return Some(packet);
         }
+
+        let verdict = middlebox.process(&mut packet, &mut *self.random_mut());
+
+        match verdict {
+            MiddleboxVerdict::Forward => unsafe {
+                Worker::send_packet(self, packet.borrow_inner())
+            },
+            MiddleboxVerdict::Delay(delay) => {
+                let task = TaskRef::new(move |host| unsafe {
+                    Worker::send_packet(host, packet.borrow_inner())
+                });
+                self.schedule_task_with_delay(task, delay);
+            }
+            MiddleboxVerdict::Drop => packet.add_status(PacketStatus::InetDropped),
+            MiddleboxVerdict::Reset => {
+                packet.add_status(PacketStatus::InetDropped);
+                for header in middlebox::reset_headers(&packet.get_tcp().unwrap()) {
+                    let mut reset = PacketRc::from_raw(unsafe { cshadow::packet_new(self) });
+                    reset.set_tcp(&header);
+                    reset.add_status(PacketStatus::InetSent);
+                    unsafe { Worker::send_packet(self, reset.borrow_inner()) };
+                }
+            }
+        }
+
+        None
     }
 
     pub fn next_event_time(&self) -> Option<EmulatedTime> {
@@ -961,23 +1441,75 @@ impl Host {
     /// could be the source device from which we forward packets, or the device
     /// that will receive and process packets with a given destination address.
     /// In the latter case, if the packet destination is not on this host, we
-    /// return the router to route it to the correct host.
+    /// return the router to route it to the correct host. Packets to the
+    /// subnet of a TUN interface are routed to that interface, and packets to
+    /// a TUN interface's own address or to the address of one of the host's
+    /// other `eth` interfaces are received by the internet interface.
     pub fn get_packet_device(&self, address: Ipv4Addr) -> Ref<dyn PacketDevice> {
         if address == Ipv4Addr::LOCALHOST {
             self.net_ns.localhost.borrow()
-        } else if address == self.default_ip() {
+        } else if self.net_ns.is_internet_address(address) || self.net_ns.is_tun_address(address) {
             self.net_ns.internet.borrow()
+        } else if let Some(tun) = self
+            .net_ns
+            .tun_route_borrow(address)
+            .filter(|_| !address.is_unspecified())
+        {
+            Ref::map(tun, |x| x as &dyn PacketDevice)
         } else {
             self.router.borrow()
         }
     }
 
+    /// Receive a packet that a managed process wrote to a TUN interface. Hosts
+    /// don't forward packets, so packets that aren't addressed to this host
+    /// are dropped.
+    pub fn receive_tun_packet(&self, mut packet: PacketRc) {
+        let dst = *packet.dst_address().ip();
+
+        if dst == Ipv4Addr::LOCALHOST {
+            self.net_ns.localhost.borrow().push(packet);
+        } else if self.net_ns.is_internet_address(dst) || self.net_ns.is_tun_address(dst) {
+            self.net_ns.internet.borrow().push(packet);
+        } else {
+            log::debug!("Dropping packet {packet:?} written to a TUN interface for {dst}");
+            packet.add_status(PacketStatus::RcvInterfaceDropped);
+        }
+    }
+
+    /// Returns the packet device that a packet received from the device with
+    /// address `src_address` should be forwarded to. This is the same as
+    /// `get_packet_device()`, except that multicast and broadcast packets
+    /// received from the router are forwarded to the internet interface.
+    pub fn get_next_packet_device(
+        &self,
+        src_address: Ipv4Addr,
+        dst_address: Ipv4Addr,
+    ) -> Ref<dyn PacketDevice> {
+        let is_group_address = dst_address.is_multicast() || self.net_ns.is_broadcast(dst_address);
+
+        if is_group_address && src_address == self.router.borrow().get_address() {
+            self.net_ns.internet.borrow()
+        } else {
+            self.get_packet_device(dst_address)
+        }
+    }
+
     /// Call to trigger the forwarding of packets from the router to the network
     /// interface.
     pub fn notify_router_has_packets(&self) {
         self.relay_inet_in.notify(self);
     }
 
+    /// Call to trigger the forwarding of packets from the interface with address `addr` when its
+    /// qdisc may have packets that can now be sent.
+    pub fn notify_interface_has_packets(&self, addr: Ipv4Addr) {
+        match addr {
+            Ipv4Addr::LOCALHOST => self.relay_loopback.notify(self),
+            _ => self.relay_inet_out.notify(self),
+        };
+    }
+
     /// Call to trigger the forwarding of packets from the network interface to
     /// the next hop (either back to the network interface for loopback, or up to
     /// the router for internet-bound packets).
@@ -1013,12 +1545,65 @@ impl Host {
         None
     }
 
+    /// Whether there is a process (if `owner` is positive) or process group (if `owner` is
+    /// negative) that can be the owner of a file, as set by `fcntl(F_SETOWN)`.
+    pub fn file_owner_exists(&self, owner: libc::pid_t) -> bool {
+        let Ok(id) = ProcessId::try_from(owner.unsigned_abs()) else {
+            return false;
+        };
+
+        if owner > 0 {
+            self.process_borrow(id).is_some()
+        } else {
+            self.process_session_id_of_group_id(id).is_some()
+        }
+    }
+
+    /// Send a signal to the owner of a file, as set by `fcntl(F_SETOWN)`. The owner is a process
+    /// if `owner` is positive, or a process group if `owner` is negative.
+    pub fn signal_file_owner(&self, owner: libc::pid_t, siginfo_t: &siginfo_t) {
+        let Ok(id) = ProcessId::try_from(owner.unsigned_abs()) else {
+            return;
+        };
+
+        let process_ids: Vec<ProcessId> = if owner > 0 {
+            vec![id]
+        } else {
+            let processes = self.processes.borrow();
+            processes
+                .iter()
+                .filter(|(_, x)| x.borrow(&self.root).group_id() == id)
+                .map(|(id, _)| *id)
+                .collect()
+        };
+
+        for id in process_ids {
+            let Some(process) = self.process_borrow(id) else {
+                debug!("Can't signal file owner {id}; it no longer exists");
+                continue;
+            };
+            process.borrow(&self.root).signal(self, None, siginfo_t);
+        }
+    }
+
     /// Paths of libraries that should be preloaded into managed processes.
     pub fn preload_paths(&self) -> &[PathBuf] {
         &self.preload_paths
     }
 }
 
+/// The rate limit of a relay with a bandwidth of `bw_bits`, which allows the host's bursts.
+fn relay_rate_limit(params: &HostParameters, bw_bits: u64) -> RateLimit {
+    match params.requested_bw_burst_bytes {
+        0 => RateLimit::BytesPerSecond(bw_bits / 8),
+        burst_bytes => RateLimit::Burst {
+            bytes_per_second: bw_bits / 8,
+            burst_bytes,
+            peak_bytes_per_second: params.requested_bw_peak_bits.map(|x| x / 8),
+        },
+    }
+}
+
 impl Drop for Host {
     fn drop(&mut self) {
         if let Some(tracker) = self.tracker.borrow_mut().take() {
@@ -1037,7 +1622,7 @@ mod export {
     use std::{ops::DerefMut, os::raw::c_char, time::Duration};
 
     use libc::{in_addr_t, in_port_t};
-    use rand::{Rng, RngCore};
+    use rand::Rng;
     use shadow_shim_helper_rs::shim_shmem;
 
     use super::*;
@@ -1109,6 +1694,17 @@ mod export {
         u32::from(ip).to_be()
     }
 
+    /// The address that packets to `dst_ip` are sent from, in network byte order.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getSourceIP(
+        hostrc: *const Host,
+        dst_ip: in_addr_t,
+    ) -> in_addr_t {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        let ip = hostrc.net_ns.source_ip(u32::from_be(dst_ip).into());
+        u32::from(ip).to_be()
+    }
+
     #[no_mangle]
     pub unsafe extern "C-unwind" fn host_getNextPacketPriority(
         hostrc: *const Host,
@@ -1129,6 +1725,54 @@ mod export {
         hostrc.params.autotune_send_buf
     }
 
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_useTCPSack(hostrc: *const Host) -> bool {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        hostrc.params.use_tcp_sack
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_useTCPECN(hostrc: *const Host) -> bool {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        hostrc.params.use_tcp_ecn
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPFastOpenKey(hostrc: *const Host) -> u64 {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        hostrc.tcp_fastopen_key()
+    }
+
+    /// Returns true and writes the cookie to `cookie` if we have a TCP Fast Open cookie for the
+    /// server at `server_ip`.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPFastOpenCookie(
+        hostrc: *const Host,
+        server_ip: in_addr_t,
+        cookie: *mut u64,
+    ) -> bool {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        let server_ip = Ipv4Addr::from(u32::from_be(server_ip));
+        match hostrc.tcp_fastopen_cookie(server_ip) {
+            Some(x) => {
+                unsafe { cookie.write(x) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_setTCPFastOpenCookie(
+        hostrc: *const Host,
+        server_ip: in_addr_t,
+        cookie: u64,
+    ) {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        let server_ip = Ipv4Addr::from(u32::from_be(server_ip));
+        hostrc.set_tcp_fastopen_cookie(server_ip, cookie);
+    }
+
     #[no_mangle]
     pub unsafe extern "C-unwind" fn host_getConfiguredRecvBufSize(hostrc: *const Host) -> u64 {
         let hostrc = unsafe { hostrc.as_ref().unwrap() };
@@ -1141,6 +1785,87 @@ mod export {
         hostrc.params.init_sock_send_buf_size
     }
 
+    /// The initial congestion window of new TCP sockets, in packets.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPInitCwnd(hostrc: *const Host) -> u32 {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        hostrc.params.tcp_tunables.init_cwnd
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPRTOInit(hostrc: *const Host) -> CSimulationTime {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        SimulationTime::to_c_simtime(Some(hostrc.params.tcp_tunables.rto_init))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPRTOMin(hostrc: *const Host) -> CSimulationTime {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        SimulationTime::to_c_simtime(Some(hostrc.params.tcp_tunables.rto_min))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPRTOMax(hostrc: *const Host) -> CSimulationTime {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        SimulationTime::to_c_simtime(Some(hostrc.params.tcp_tunables.rto_max))
+    }
+
+    /// The maximum segment lifetime of TCP connections, which stay in TIME_WAIT for twice this
+    /// time.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPMSL(hostrc: *const Host) -> CSimulationTime {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        SimulationTime::to_c_simtime(Some(hostrc.params.tcp_tunables.msl))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPDelayedACKTimeout(
+        hostrc: *const Host,
+    ) -> CSimulationTime {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        SimulationTime::to_c_simtime(Some(hostrc.params.tcp_tunables.delayed_ack_timeout))
+    }
+
+    /// Returns true and writes the configured `tcp_rmem` sizes if the host has them configured.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPRecvBufLimits(
+        hostrc: *const Host,
+        min: *mut u64,
+        default: *mut u64,
+        max: *mut u64,
+    ) -> bool {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        let Some(limits) = hostrc.params.tcp_rmem else {
+            return false;
+        };
+        unsafe {
+            min.write(limits.0);
+            default.write(limits.1);
+            max.write(limits.2);
+        }
+        true
+    }
+
+    /// Returns true and writes the configured `tcp_wmem` sizes if the host has them configured.
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_getTCPSendBufLimits(
+        hostrc: *const Host,
+        min: *mut u64,
+        default: *mut u64,
+        max: *mut u64,
+    ) -> bool {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        let Some(limits) = hostrc.params.tcp_wmem else {
+            return false;
+        };
+        unsafe {
+            min.write(limits.0);
+            default.write(limits.1);
+            max.write(limits.2);
+        }
+        true
+    }
+
     #[no_mangle]
     pub unsafe extern "C-unwind" fn host_getUpstreamRouter(hostrc: *const Host) -> *mut Router {
         let hostrc = unsafe { hostrc.as_ref().unwrap() };
@@ -1260,6 +1985,19 @@ mod export {
             .unwrap_or(std::ptr::null_mut())
     }
 
+    /// Send the signal `signo` to the owner of a file, as set by `fcntl(F_SETOWN)`. See
+    /// [`Host::signal_file_owner`].
+    #[no_mangle]
+    pub unsafe extern "C-unwind" fn host_signalFileOwner(
+        host: *const Host,
+        owner: libc::pid_t,
+        signo: libc::c_int,
+    ) {
+        let host = unsafe { host.as_ref().unwrap() };
+        let signal = Signal::try_from(signo).unwrap();
+        host.signal_file_owner(owner, &siginfo_t::new_for_kernel(signal));
+    }
+
     /// Returns the specified thread, or NULL if it doesn't exist.
     /// If you already have the thread's Process*, `process_getThread` may be more
     /// efficient.
@@ -1381,7 +2119,7 @@ mod export {
     pub extern "C-unwind" fn host_rngNextNBytes(host: *const Host, buf: *mut u8, len: usize) {
         let host = unsafe { host.as_ref().unwrap() };
         let buf = unsafe { std::slice::from_raw_parts_mut(buf, len) };
-        host.random_mut().fill_bytes(buf);
+        host.fill_random_bytes(buf);
     }
 
     #[no_mangle]
@@ -662,7 +662,7 @@ impl MemoryManager {
         }
     }
 
-    fn do_munmap(
+    pub fn do_munmap(
         &mut self,
         ctx: &ThreadContext,
         addr: ForeignPtr<u8>,
@@ -7,14 +7,17 @@
 pub mod context;
 pub mod cpu;
 pub mod descriptor;
+pub mod file_lock_table;
 pub mod futex_table;
 #[allow(clippy::module_inception)]
 pub mod host;
 pub mod managed_thread;
 pub mod memory_manager;
 pub mod network;
+pub mod posix_timer;
 pub mod process;
 pub mod status_listener;
 pub mod syscall;
+pub mod sysv_ipc;
 pub mod thread;
 pub mod timer;
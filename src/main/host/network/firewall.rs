@@ -0,0 +1,221 @@
+//! A stateful packet filter for a network interface, like a small subset of iptables.
+//!
+//! Each packet sent or received by the interface is checked against the firewall's rules in order,
+//! and the first rule that matches the packet decides whether the packet is accepted or dropped.
+//! Packets that don't match any rule are handled by the policy of their direction. The firewall
+//! tracks the connections that it has accepted packets for, so that rules can match the packets of
+//! established connections.
+
+use std::collections::HashSet;
+use std::net::SocketAddrV4;
+
+use crate::core::configuration::{
+    FirewallAction, FirewallDirection, FirewallOptions, FirewallProtocol, FirewallRule,
+    FirewallState,
+};
+use crate::cshadow as c;
+use crate::network::nat::Subnet;
+use crate::network::packet::PacketRc;
+
+pub struct Firewall {
+    input_policy: FirewallAction,
+    output_policy: FirewallAction,
+    rules: Vec<Rule>,
+    /// The connections that the firewall has accepted packets for. Connections are only tracked
+    /// if a rule matches on the connection state.
+    connections: Option<HashSet<Connection>>,
+}
+
+/// A connection as seen from the host. ICMP echo messages use their identifier as the port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Connection {
+    protocol: c::ProtocolType,
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+}
+
+struct Rule {
+    id: String,
+    direction: FirewallDirection,
+    action: FirewallAction,
+    protocol: Option<c::ProtocolType>,
+    remote_addr: Option<Subnet>,
+    local_port: Option<u16>,
+    remote_port: Option<u16>,
+    state: Option<FirewallState>,
+}
+
+impl Rule {
+    /// Build a rule from its options. Rules without an ID are identified by their position in the
+    /// list of rules, starting at 1. Panics if the remote address isn't valid.
+    fn new(options: &FirewallRule, index: usize) -> Self {
+        Self {
+            id: options
+                .id
+                .clone()
+                .unwrap_or_else(|| (index + 1).to_string()),
+            direction: options.direction,
+            action: options.action,
+            protocol: options.protocol.map(|x| match x {
+                FirewallProtocol::Tcp => c::_ProtocolType_PTCP,
+                FirewallProtocol::Udp => c::_ProtocolType_PUDP,
+                FirewallProtocol::Icmp => c::_ProtocolType_PICMP,
+            }),
+            remote_addr: options.remote_addr.as_ref().map(|x| x.parse().unwrap()),
+            local_port: options.local_port,
+            remote_port: options.remote_port,
+            state: options.state,
+        }
+    }
+
+    fn matches(
+        &self,
+        direction: FirewallDirection,
+        connection: &Connection,
+        state: FirewallState,
+    ) -> bool {
+        self.direction == direction
+            && self.protocol.map_or(true, |x| x == connection.protocol)
+            && self
+                .remote_addr
+                .map_or(true, |x| x.contains(*connection.remote.ip()))
+            && self
+                .local_port
+                .map_or(true, |x| x == connection.local.port())
+            && self
+                .remote_port
+                .map_or(true, |x| x == connection.remote.port())
+            && self.state.map_or(true, |x| x == state)
+    }
+}
+
+impl Firewall {
+    pub fn new(options: &FirewallOptions) -> Self {
+        let rules: Vec<_> = options
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, x)| Rule::new(x, i))
+            .collect();
+
+        let track_connections = rules.iter().any(|x| x.state.is_some());
+
+        Self {
+            input_policy: options.input_policy,
+            output_policy: options.output_policy,
+            rules,
+            connections: track_connections.then(HashSet::new),
+        }
+    }
+
+    /// Returns true if the firewall accepts a packet that's sent or received by the host. Dropped
+    /// packets are logged with the ID of the rule that dropped them.
+    pub fn accepts(&mut self, packet: &PacketRc, direction: FirewallDirection) -> bool {
+        let (local, remote) = match direction {
+            FirewallDirection::Input => (packet.dst_address(), packet.src_address()),
+            FirewallDirection::Output => (packet.src_address(), packet.dst_address()),
+        };
+
+        let connection = Connection {
+            protocol: packet.protocol(),
+            local,
+            remote,
+        };
+
+        let state = match &self.connections {
+            Some(connections) if connections.contains(&connection) => FirewallState::Established,
+            _ => FirewallState::New,
+        };
+
+        let rule = self
+            .rules
+            .iter()
+            .find(|x| x.matches(direction, &connection, state));
+
+        let policy = match direction {
+            FirewallDirection::Input => self.input_policy,
+            FirewallDirection::Output => self.output_policy,
+        };
+
+        match rule.map_or(policy, |x| x.action) {
+            FirewallAction::Accept => {
+                if let Some(connections) = &mut self.connections {
+                    connections.insert(connection);
+                }
+                true
+            }
+            FirewallAction::Drop => {
+                let id = rule.map_or("policy", |x| x.id.as_str());
+                log::debug!(
+                    "Firewall rule '{id}' dropped {direction:?} packet from {} to {}",
+                    packet.src_address(),
+                    packet.dst_address(),
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    use FirewallDirection::{Input, Output};
+
+    fn accepts(fw: &mut Firewall, direction: FirewallDirection, src: &str, dst: &str) -> bool {
+        let packet = PacketRc::mock_new_udp(src.parse().unwrap(), dst.parse().unwrap());
+        fw.accepts(&packet, direction)
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut fw = Firewall::new(
+            &FirewallOptions::from_str(
+                "
+                rules:
+                - {direction: input, action: accept, remote_addr: 10.0.0.1, local_port: 80}
+                - {direction: input, action: drop, remote_addr: 10.0.0.0/8}
+                - {direction: input, action: drop, protocol: tcp}
+                - {direction: output, action: drop, remote_port: 25}
+                ",
+            )
+            .unwrap(),
+        );
+
+        assert!(accepts(&mut fw, Input, "10.0.0.1:5000", "1.0.0.1:80"));
+        assert!(!accepts(&mut fw, Input, "10.0.0.1:5000", "1.0.0.1:81"));
+        assert!(!accepts(&mut fw, Input, "10.0.0.2:5000", "1.0.0.1:80"));
+        // the tcp rule doesn't match udp packets, so the input policy accepts them
+        assert!(accepts(&mut fw, Input, "11.0.0.1:5000", "1.0.0.1:80"));
+        assert!(!accepts(&mut fw, Output, "1.0.0.1:5000", "11.0.0.1:25"));
+        assert!(accepts(&mut fw, Output, "1.0.0.1:5000", "11.0.0.1:26"));
+    }
+
+    #[test]
+    fn test_established() {
+        let mut fw = Firewall::new(
+            &FirewallOptions::from_str(
+                "
+                input_policy: drop
+                rules:
+                - {direction: input, action: accept, state: established}
+                - {direction: input, action: accept, local_port: 22}
+                ",
+            )
+            .unwrap(),
+        );
+
+        // replies to the host's packets are accepted
+        assert!(!accepts(&mut fw, Input, "2.0.0.1:80", "1.0.0.1:5000"));
+        assert!(accepts(&mut fw, Output, "1.0.0.1:5000", "2.0.0.1:80"));
+        assert!(accepts(&mut fw, Input, "2.0.0.1:80", "1.0.0.1:5000"));
+        assert!(!accepts(&mut fw, Input, "2.0.0.1:81", "1.0.0.1:5000"));
+
+        // packets for port 22 are accepted, and start a connection
+        assert!(accepts(&mut fw, Input, "3.0.0.1:6000", "1.0.0.1:22"));
+        assert!(accepts(&mut fw, Output, "1.0.0.1:22", "3.0.0.1:6000"));
+    }
+}
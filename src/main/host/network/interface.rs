@@ -1,3 +1,4 @@
+use std::cell::{Cell, RefCell};
 use std::ffi::{CString, OsStr};
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::os::unix::ffi::OsStrExt;
@@ -6,13 +7,18 @@ use std::path::PathBuf;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::HostId;
 
-use crate::core::configuration::QDiscMode;
+use crate::core::configuration::{FirewallDirection, OutputLimitAction, QDiscMode};
+use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::socket::inet::InetSocket;
-use crate::network::packet::PacketRc;
+use crate::host::network::firewall::Firewall;
+use crate::host::network::qdisc::Qdisc;
+use crate::network::packet::{PacketRc, PacketStatus};
+use crate::network::pcap_filter::PcapFilter;
 use crate::network::PacketDevice;
-use crate::utility::{self, HostTreePointer};
+use crate::utility::pcap_writer::PacketCapture;
+use crate::utility::HostTreePointer;
 
 /// The priority used by the fifo qdisc to choose the next socket to send a packet from.
 pub type FifoPacketPriority = u64;
@@ -21,6 +27,12 @@ pub type FifoPacketPriority = u64;
 pub struct PcapOptions {
     pub path: PathBuf,
     pub capture_size_bytes: u32,
+    /// Only packets that match the filter are captured.
+    pub filter: Option<PcapFilter>,
+    /// The maximum size of each pcap file, if limited.
+    pub max_size_bytes: Option<u64>,
+    /// What to do when a pcap file reaches its maximum size.
+    pub limit_action: OutputLimitAction,
 }
 
 /// Represents a network device that can send and receive packets. All accesses
@@ -28,6 +40,12 @@ pub struct PcapOptions {
 pub struct NetworkInterface {
     c_ptr: HostTreePointer<c::NetworkInterface>,
     addr: Ipv4Addr,
+    /// Holds the packets popped from the sockets until the qdisc allows them to be sent.
+    egress_qdisc: Option<RefCell<Box<dyn Qdisc>>>,
+    /// The time of the scheduled task that will notify the host that the qdisc can send a packet.
+    qdisc_wakeup: Cell<Option<EmulatedTime>>,
+    /// Filters the packets sent and received by the interface.
+    firewall: Option<RefCell<Firewall>>,
 }
 
 impl NetworkInterface {
@@ -45,26 +63,37 @@ impl NetworkInterface {
         name: &OsStr,
         pcap_options: Option<PcapOptions>,
         qdisc: QDiscMode,
+        egress_qdisc: Option<Box<dyn Qdisc>>,
+        firewall: Option<Firewall>,
     ) -> NetworkInterface {
-        let maybe_pcap_dir = pcap_options
-            .as_ref()
-            .map(|x| utility::pathbuf_to_nul_term_cstring(x.path.clone()));
-        let pcap_dir_cptr = maybe_pcap_dir
-            .as_ref()
-            .map_or(std::ptr::null(), |p| p.as_ptr());
-
-        let pcap_capture_size = pcap_options
-            .as_ref()
-            .map(|x| x.capture_size_bytes)
-            .unwrap_or(0);
+        let pcap = pcap_options.and_then(|x| {
+            let mut file_name = name.to_os_string();
+            file_name.push(".pcap");
+            let path = x.path.join(file_name);
+
+            let pcap = PacketCapture::new(
+                path,
+                x.capture_size_bytes,
+                x.filter,
+                x.max_size_bytes,
+                x.limit_action,
+            );
+            match pcap {
+                Ok(pcap) => Some(Box::new(pcap)),
+                Err(e) => {
+                    log::warn!("Could not create pcap file: {}", e);
+                    None
+                }
+            }
+        });
+        // the C interface takes ownership of the pcap file
+        let pcap = pcap.map_or(std::ptr::null_mut(), Box::into_raw);
 
         let mut name = name.as_bytes().to_vec();
         name.push(0);
         let name = CString::from_vec_with_nul(name).unwrap();
 
-        let c_ptr = unsafe {
-            c::networkinterface_new(addr, name.as_ptr(), pcap_dir_cptr, pcap_capture_size, qdisc)
-        };
+        let c_ptr = unsafe { c::networkinterface_new(addr, name.as_ptr(), pcap, qdisc) };
 
         let ipv4_addr: Ipv4Addr = {
             let addr = unsafe { c::address_toNetworkIP(addr) };
@@ -74,6 +103,9 @@ impl NetworkInterface {
         NetworkInterface {
             c_ptr: HostTreePointer::new_for_host(host_id, c_ptr),
             addr: ipv4_addr,
+            egress_qdisc: egress_qdisc.map(RefCell::new),
+            qdisc_wakeup: Cell::new(None),
+            firewall: firewall.map(RefCell::new),
         }
     }
 
@@ -126,6 +158,30 @@ impl NetworkInterface {
         }) != 0
     }
 
+    /// Returns the socket that would receive a packet with the given protocol and destination port
+    /// from `peer`, or `None` if there is no such socket.
+    pub fn lookup(
+        &self,
+        protocol: c::ProtocolType,
+        port: u16,
+        peer: SocketAddrV4,
+    ) -> Option<InetSocket> {
+        let port = port.to_be();
+        let peer_ip = u32::from(*peer.ip()).to_be();
+        let peer_port = peer.port().to_be();
+
+        let socket = unsafe {
+            c::networkinterface_lookup(self.c_ptr.ptr(), protocol, port, peer_ip, peer_port)
+        };
+
+        if socket.is_null() {
+            return None;
+        }
+
+        // we own the returned reference
+        Some(*unsafe { Box::from_raw(socket.cast_mut()) })
+    }
+
     pub fn add_data_source(&self, socket: &InetSocket) {
         unsafe { c::networkinterface_wantsSend(self.c_ptr.ptr(), socket) };
     }
@@ -135,6 +191,36 @@ impl NetworkInterface {
     pub fn remove_all_sockets(&self) {
         unsafe { c::networkinterface_removeAllSockets(self.c_ptr.ptr()) };
     }
+
+    /// Pop the next packet that the firewall accepts from the sockets that have packets to send,
+    /// bypassing the qdisc.
+    fn pop_from_sockets(&self) -> Option<PacketRc> {
+        loop {
+            let packet_ptr = unsafe { c::networkinterface_pop(self.c_ptr.ptr()) };
+            if packet_ptr.is_null() {
+                return None;
+            }
+
+            let mut packet = PacketRc::from_raw(packet_ptr);
+            if self.firewall_accepts(&mut packet, FirewallDirection::Output) {
+                return Some(packet);
+            }
+        }
+    }
+
+    /// Returns true if the interface has no firewall or the firewall accepts the packet.
+    fn firewall_accepts(&self, packet: &mut PacketRc, direction: FirewallDirection) -> bool {
+        let Some(firewall) = &self.firewall else {
+            return true;
+        };
+
+        if firewall.borrow_mut().accepts(packet, direction) {
+            return true;
+        }
+
+        packet.add_status(PacketStatus::FirewallDropped);
+        false
+    }
 }
 
 impl Drop for NetworkInterface {
@@ -150,14 +236,48 @@ impl PacketDevice for NetworkInterface {
     }
 
     fn pop(&self) -> Option<PacketRc> {
-        let packet_ptr = unsafe { c::networkinterface_pop(self.c_ptr.ptr()) };
-        match packet_ptr.is_null() {
-            true => None,
-            false => Some(PacketRc::from_raw(packet_ptr)),
+        let Some(egress_qdisc) = &self.egress_qdisc else {
+            return self.pop_from_sockets();
+        };
+
+        let mut egress_qdisc = egress_qdisc.borrow_mut();
+        let now = Worker::current_time().unwrap();
+
+        while !egress_qdisc.is_full() {
+            let Some(packet) = self.pop_from_sockets() else {
+                break;
+            };
+            egress_qdisc.enqueue(packet, now);
+        }
+
+        if let Some(packet) = egress_qdisc.dequeue(now) {
+            return Some(packet);
         }
+
+        // the relay stops asking for packets until it's notified again, so we need to notify it
+        // when the qdisc's next packet can be sent
+        if self.qdisc_wakeup.get().is_some_and(|t| t <= now) {
+            self.qdisc_wakeup.set(None);
+        }
+
+        if let Some(time) = egress_qdisc.next_dequeue_time() {
+            if self.qdisc_wakeup.get().map_or(true, |t| time < t) {
+                let addr = self.addr;
+                let task = TaskRef::new(move |host| host.notify_interface_has_packets(addr));
+                Worker::with_active_host(|host| host.schedule_task_at_emulated_time(task, time))
+                    .unwrap();
+                self.qdisc_wakeup.set(Some(time));
+            }
+        }
+
+        None
     }
 
-    fn push(&self, packet: PacketRc) {
+    fn push(&self, mut packet: PacketRc) {
+        if !self.firewall_accepts(&mut packet, FirewallDirection::Input) {
+            return;
+        }
+
         let packet_ptr = packet.into_inner();
         let current_time = Worker::current_time().unwrap();
         unsafe {
@@ -170,3 +290,131 @@ impl PacketDevice for NetworkInterface {
         unsafe { c::packet_unref(packet_ptr) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+
+    use super::*;
+    use crate::host::descriptor::socket::inet::udp::UdpSocket;
+    use crate::host::descriptor::FileStatus;
+
+    /// A deficit round-robin socket queue that owns its sockets' references.
+    struct DrrQueue(c::DrrSocketQueue);
+
+    impl DrrQueue {
+        fn new() -> Self {
+            // the C code expects the queue to be zeroed before it's initialized
+            let mut queue = unsafe { MaybeUninit::<c::DrrSocketQueue>::zeroed().assume_init() };
+            unsafe { c::drrsocketqueue_init(&mut queue) };
+            Self(queue)
+        }
+
+        fn push(&mut self, socket: &InetSocket) {
+            let socket = Box::into_raw(Box::new(socket.clone()));
+            unsafe { c::drrsocketqueue_push(&mut self.0, socket) };
+        }
+
+        /// Get the socket that should send next.
+        fn next(&mut self) -> InetSocket {
+            let mut socket = std::ptr::null_mut();
+            assert!(unsafe { c::drrsocketqueue_next(&mut self.0, &mut socket) });
+            unsafe { socket.as_ref() }.unwrap().clone()
+        }
+
+        /// Charge the socket for a packet of `size` bytes.
+        fn charge(&mut self, socket: &InetSocket, size: usize) {
+            unsafe { c::drrsocketqueue_charge(&mut self.0, socket, size.try_into().unwrap()) };
+        }
+
+        /// Get the socket that should send next and charge it for a packet of `size` bytes.
+        fn send(&mut self, size: usize) -> InetSocket {
+            let socket = self.next();
+            self.charge(&socket, size);
+            socket
+        }
+
+        fn remove(&mut self, socket: &InetSocket) {
+            let socket = unsafe { c::drrsocketqueue_remove(&mut self.0, socket) };
+            assert!(!socket.is_null());
+            drop(unsafe { Box::from_raw(socket) });
+        }
+    }
+
+    impl Drop for DrrQueue {
+        fn drop(&mut self) {
+            assert!(unsafe { c::drrsocketqueue_isEmpty(&mut self.0) });
+            unsafe { c::drrsocketqueue_destroy(&mut self.0, None) };
+        }
+    }
+
+    fn mock_socket() -> InetSocket {
+        InetSocket::Udp(UdpSocket::new(FileStatus::empty(), 1000, 1000))
+    }
+
+    const QUANTUM: usize = c::CONFIG_MTU as usize;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_drr_dequeue_order() {
+        let mut queue = DrrQueue::new();
+        let (a, b, c) = (mock_socket(), mock_socket(), mock_socket());
+
+        queue.push(&a);
+        queue.push(&b);
+
+        // a sends until it has used up its quantum
+        assert_eq!(queue.send(QUANTUM / 2), a);
+        assert_eq!(queue.send(QUANTUM), a);
+        assert_eq!(queue.send(QUANTUM), b);
+
+        // a socket that starts sending is served before the sockets that used up their quantum
+        queue.push(&c);
+        assert_eq!(queue.send(QUANTUM), c);
+
+        // a sent half a quantum more than its quantum, so its next turn is half a quantum
+        assert_eq!(queue.send(QUANTUM / 2), a);
+        assert_eq!(queue.send(QUANTUM), b);
+        assert_eq!(queue.send(QUANTUM), c);
+        assert_eq!(queue.send(QUANTUM), a);
+
+        // a socket that stops sending loses its turn
+        queue.remove(&b);
+        assert_eq!(queue.send(QUANTUM), c);
+        assert_eq!(queue.send(QUANTUM), a);
+
+        queue.remove(&a);
+        queue.remove(&c);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_drr_byte_shares() {
+        let mut queue = DrrQueue::new();
+        let large = mock_socket();
+        let small = mock_socket();
+
+        queue.push(&large);
+        queue.push(&small);
+
+        let mut large_bytes = 0;
+        let mut small_bytes = 0;
+
+        for _ in 0..10_000 {
+            let socket = queue.next();
+            if socket == large {
+                queue.charge(&socket, QUANTUM);
+                large_bytes += QUANTUM;
+            } else {
+                queue.charge(&socket, 100);
+                small_bytes += 100;
+            }
+        }
+
+        // both sockets send the same number of bytes, give or take one turn
+        assert!(large_bytes.abs_diff(small_bytes) <= QUANTUM);
+
+        queue.remove(&large);
+        queue.remove(&small);
+    }
+}
@@ -1,2 +1,5 @@
+pub mod firewall;
 pub mod interface;
 pub mod namespace;
+pub mod qdisc;
+pub mod tun;
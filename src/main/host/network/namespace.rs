@@ -1,4 +1,5 @@
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
 use std::ffi::{CString, OsStr};
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::num::NonZeroU8;
@@ -6,6 +7,8 @@ use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
 use atomic_refcell::AtomicRefCell;
+use linux_api::netdevice::IfFlags;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::util::SyncSendPointer;
 use shadow_shim_helper_rs::HostId;
 
@@ -13,13 +16,34 @@ use crate::core::configuration::QDiscMode;
 use crate::core::worker::Worker;
 use crate::cshadow;
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
+use crate::host::descriptor::socket::inet::icmp::{IcmpSocket, IcmpSocketType};
 use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::descriptor::tun::TunFile;
+use crate::host::descriptor::FileStatus;
+use crate::host::network::firewall::Firewall;
 use crate::host::network::interface::{NetworkInterface, PcapOptions};
+use crate::host::network::qdisc::Qdisc;
+use crate::host::network::tun::TunInterface;
+use crate::network::nat::Subnet;
 
 // The start of our random port range in host order, used if application doesn't
 // specify the port it wants to bind to, and for client connections.
 const MIN_RANDOM_PORT: u16 = 10000;
 
+/// The smallest path MTU that will be learned from ICMP "fragmentation needed" errors, from Linux's
+/// default `net.ipv4.route.min_pmtu`.
+pub const MIN_PATH_MTU: u32 = 552;
+
+/// The prefix length of the subnet of each host's internet interface. Hosts attached to the same
+/// network graph node and in the same subnet receive each other's subnet broadcasts.
+pub const INTERNET_PREFIX_LEN: u8 = 24;
+
+/// The broadcast address of the subnet of the internet interface with address `addr`.
+pub fn subnet_broadcast_address(addr: Ipv4Addr) -> Ipv4Addr {
+    let host_mask = u32::MAX >> INTERNET_PREFIX_LEN;
+    Ipv4Addr::from(u32::from(addr) | host_mask)
+}
+
 /// Represents a network namespace. Can be thought of as roughly equivalent to a Linux `struct net`.
 /// Shadow doesn't support multiple network namespaces, but this `NetworkNamespace` allows us to
 /// consolidate the host's networking objects, and hopefully might make it easier to support
@@ -35,31 +59,118 @@ pub struct NetworkNamespace {
     pub default_address: SyncSendPointer<cshadow::Address>,
     pub default_ip: Ipv4Addr,
 
+    // the addresses of the interfaces in addition to eth0 (named eth1, eth2, and so on), which
+    // share the internet interface, and their registered DNS addresses
+    extra_addresses: Vec<(Ipv4Addr, SyncSendPointer<cshadow::Address>)>,
+
+    // the routes that choose the interface of packets by their destination, and the index of each
+    // route's interface, where eth0 is 0
+    routes: Vec<(Subnet, usize)>,
+
+    // path MTUs learned from ICMP "fragmentation needed" errors, keyed by destination address
+    path_mtus: RefCell<HashMap<Ipv4Addr, u32>>,
+
+    // the multicast groups that sockets have joined, and the number of sockets in each group
+    multicast_groups: RefCell<HashMap<Ipv4Addr, usize>>,
+
+    // TUN interfaces created by managed processes, and the index of the next one to be created
+    tun_interfaces: RefCell<Vec<TunInterface>>,
+    next_tun_index: Cell<libc::c_int>,
+
     // used for debugging to make sure we've cleaned up before being dropped
     has_run_cleanup: Cell<bool>,
 }
 
+/// The configuration of one of the namespace's network interfaces, as reported to managed
+/// processes through netlink and the network device ioctls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub index: libc::c_int,
+    /// The unspecified address (0.0.0.0) if the interface hasn't been assigned an address.
+    pub address: Ipv4Addr,
+    pub prefix_len: u8,
+    pub mtu: u32,
+    pub hw_address: [u8; 6],
+    pub is_loopback: bool,
+    pub is_tun: bool,
+    pub is_up: bool,
+}
+
+impl InterfaceInfo {
+    pub fn netmask(&self) -> Ipv4Addr {
+        let netmask = 0xffff_ffff_u32
+            .checked_shl(32 - u32::from(self.prefix_len))
+            .unwrap_or(0);
+        Ipv4Addr::from(netmask)
+    }
+
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.address) & u32::from(self.netmask()))
+    }
+
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.address) | !u32::from(self.netmask()))
+    }
+
+    pub fn flags(&self) -> IfFlags {
+        if self.is_tun {
+            let flags = IfFlags::IFF_POINTOPOINT | IfFlags::IFF_NOARP | IfFlags::IFF_MULTICAST;
+            if self.is_up {
+                flags | IfFlags::IFF_UP | IfFlags::IFF_RUNNING
+            } else {
+                flags
+            }
+        } else if self.is_loopback {
+            IfFlags::IFF_UP | IfFlags::IFF_LOOPBACK | IfFlags::IFF_RUNNING
+        } else {
+            IfFlags::IFF_UP | IfFlags::IFF_BROADCAST | IfFlags::IFF_RUNNING | IfFlags::IFF_MULTICAST
+        }
+    }
+
+    /// The hardware broadcast address of the interface.
+    pub fn hw_broadcast(&self) -> [u8; 6] {
+        if self.is_loopback || self.is_tun {
+            [0; 6]
+        } else {
+            [0xff; 6]
+        }
+    }
+}
+
 impl NetworkNamespace {
+    /// The host's name can't be resolved before `start_time`.
+    ///
     /// # Safety
     ///
     /// `dns` must be a valid pointer.
     pub unsafe fn new(
         host_id: HostId,
         hostname: Vec<NonZeroU8>,
+        start_time: SimulationTime,
         public_ip: Ipv4Addr,
         pcap: Option<PcapOptions>,
         qdisc: QDiscMode,
+        egress_qdisc: Option<Box<dyn Qdisc>>,
+        firewall: Option<Firewall>,
+        extra_ips: &[Ipv4Addr],
+        routes: Vec<(Subnet, usize)>,
         dns: *mut cshadow::DNS,
     ) -> Self {
         let (localhost, local_addr) = unsafe {
             Self::setup_net_interface(
                 OsStr::new("lo"),
-                &InterfaceOptions {
+                InterfaceOptions {
                     host_id,
                     hostname: hostname.clone(),
                     ip: Ipv4Addr::LOCALHOST,
                     pcap: pcap.clone(),
                     qdisc,
+                    // packets sent to localhost never leave the host, so they aren't shaped or
+                    // filtered
+                    egress_qdisc: None,
+                    firewall: None,
+                    start_time,
                 },
                 dns,
             )
@@ -67,15 +178,44 @@ impl NetworkNamespace {
 
         unsafe { cshadow::address_unref(local_addr) };
 
+        // the addresses of the other interfaces are registered with the interface's name appended
+        // to the hostname, so that they can be resolved by name
+        let extra_addresses = extra_ips
+            .iter()
+            .enumerate()
+            .map(|(i, ip)| {
+                let suffix = format!("-eth{}", i + 1);
+                let mut name = hostname.clone();
+                name.extend(suffix.bytes().map(|x| NonZeroU8::new(x).unwrap()));
+                let name = CString::from(name);
+
+                let addr = unsafe {
+                    cshadow::dns_register(
+                        dns,
+                        host_id,
+                        name.as_ptr(),
+                        u32::from(*ip).to_be(),
+                        SimulationTime::to_c_simtime(Some(start_time)),
+                    )
+                };
+                assert!(!addr.is_null());
+
+                (*ip, unsafe { SyncSendPointer::new(addr) })
+            })
+            .collect();
+
         let (internet, public_addr) = unsafe {
             Self::setup_net_interface(
                 OsStr::new("eth0"),
-                &InterfaceOptions {
+                InterfaceOptions {
                     host_id,
                     hostname,
                     ip: public_ip,
                     pcap,
                     qdisc,
+                    egress_qdisc,
+                    firewall,
+                    start_time,
                 },
                 dns,
             )
@@ -87,6 +227,13 @@ impl NetworkNamespace {
             internet: RefCell::new(internet),
             default_address: unsafe { SyncSendPointer::new(public_addr) },
             default_ip: public_ip,
+            extra_addresses,
+            routes,
+            path_mtus: RefCell::new(HashMap::new()),
+            multicast_groups: RefCell::new(HashMap::new()),
+            tun_interfaces: RefCell::new(Vec::new()),
+            // "lo" and the "eth" interfaces use the first indexes
+            next_tun_index: Cell::new(3 + libc::c_int::try_from(extra_ips.len()).unwrap()),
             has_run_cleanup: Cell::new(false),
         }
     }
@@ -94,7 +241,7 @@ impl NetworkNamespace {
     /// Must free the returned `*mut cshadow::Address` using [`cshadow::address_unref`].
     unsafe fn setup_net_interface(
         name: &OsStr,
-        options: &InterfaceOptions,
+        options: InterfaceOptions,
         dns: *mut cshadow::DNS,
     ) -> (NetworkInterface, *mut cshadow::Address) {
         let ip = u32::from(options.ip).to_be();
@@ -104,7 +251,15 @@ impl NetworkNamespace {
         let hostname: CString = options.hostname.clone().into();
         let hostname = hostname.as_ptr();
 
-        let addr = unsafe { cshadow::dns_register(dns, options.host_id, hostname, ip) };
+        let addr = unsafe {
+            cshadow::dns_register(
+                dns,
+                options.host_id,
+                hostname,
+                ip,
+                SimulationTime::to_c_simtime(Some(options.start_time)),
+            )
+        };
         assert!(!addr.is_null());
 
         let interface = unsafe {
@@ -112,14 +267,38 @@ impl NetworkNamespace {
                 options.host_id,
                 addr,
                 name,
-                options.pcap.clone(),
+                options.pcap,
                 options.qdisc,
+                options.egress_qdisc,
+                options.firewall,
             )
         };
 
         (interface, addr)
     }
 
+    /// Associate a socket with each interface that replies to the ICMP echo requests received by
+    /// the interface, which Linux does in the kernel. This should be called while `Worker` has the
+    /// active host set. The sockets are freed during [`Self::cleanup`].
+    pub fn start_icmp_echo_responders(&self, send_buf_size: usize) {
+        for interface in [&self.localhost, &self.internet] {
+            let responder = IcmpSocket::new(
+                FileStatus::empty(),
+                IcmpSocketType::EchoResponder,
+                send_buf_size,
+                /* recv_buf_size= */ 0,
+            );
+
+            // echo requests are sent to port 0, and the responder receives them from any peer
+            interface.borrow().associate(
+                &InetSocket::Icmp(responder),
+                cshadow::_ProtocolType_PICMP,
+                0,
+                SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
+            );
+        }
+    }
+
     /// Clean up the network namespace. This should be called while `Worker` has the active host
     /// set. The `dns` object should be the same object that was originally provided to
     /// [`Self::new`].
@@ -131,6 +310,9 @@ impl NetworkNamespace {
         unsafe {
             cshadow::dns_deregister(dns.cast_mut(), self.default_address.ptr());
         }
+        for (_, addr) in &self.extra_addresses {
+            unsafe { cshadow::dns_deregister(dns.cast_mut(), addr.ptr()) };
+        }
 
         // we need to unref all sockets and free them before we drop the host, otherwise they'll try
         // to access the global host and panic since there is no host
@@ -140,6 +322,234 @@ impl NetworkNamespace {
         self.has_run_cleanup.set(true);
     }
 
+    /// The namespace's interfaces, ordered by their interface index. These should match the
+    /// interfaces returned by the shim's `getifaddrs()`.
+    pub fn interfaces(&self) -> Vec<InterfaceInfo> {
+        let mut interfaces = vec![
+            InterfaceInfo {
+                name: "lo".to_string(),
+                index: 1,
+                address: Ipv4Addr::LOCALHOST,
+                prefix_len: 8,
+                mtu: cshadow::CONFIG_MTU,
+                hw_address: [0; 6],
+                is_loopback: true,
+                is_tun: false,
+                is_up: true,
+            },
+            InterfaceInfo {
+                name: "eth0".to_string(),
+                index: 2,
+                address: self.default_ip,
+                prefix_len: INTERNET_PREFIX_LEN,
+                mtu: cshadow::CONFIG_MTU,
+                hw_address: hw_address_for_ip(self.default_ip),
+                is_loopback: false,
+                is_tun: false,
+                is_up: true,
+            },
+        ];
+
+        interfaces.extend(
+            self.extra_addresses
+                .iter()
+                .enumerate()
+                .map(|(i, (address, _))| InterfaceInfo {
+                    name: format!("eth{}", i + 1),
+                    index: 3 + libc::c_int::try_from(i).unwrap(),
+                    address: *address,
+                    prefix_len: INTERNET_PREFIX_LEN,
+                    mtu: cshadow::CONFIG_MTU,
+                    hw_address: hw_address_for_ip(*address),
+                    is_loopback: false,
+                    is_tun: false,
+                    is_up: true,
+                }),
+        );
+
+        interfaces.extend(self.tun_interfaces.borrow().iter().map(|x| InterfaceInfo {
+            name: x.name.clone(),
+            index: x.index,
+            address: x.address.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            prefix_len: x.prefix_len,
+            mtu: x.mtu,
+            hw_address: [0; 6],
+            is_loopback: false,
+            is_tun: true,
+            is_up: x.is_up,
+        }));
+
+        interfaces
+    }
+
+    /// Add a TUN interface named `name` for the TUN `file`. Returns the new interface's index.
+    pub fn add_tun_interface(
+        &self,
+        name: String,
+        file: &Arc<AtomicRefCell<TunFile>>,
+    ) -> libc::c_int {
+        let index = self.next_tun_index.get();
+        self.next_tun_index.set(index + 1);
+
+        log::debug!("Adding TUN interface {name} with index {index}");
+        self.tun_interfaces
+            .borrow_mut()
+            .push(TunInterface::new(name, index, file));
+
+        index
+    }
+
+    /// Remove the TUN interface named `name`, if it exists.
+    pub fn remove_tun_interface(&self, name: &str) {
+        log::debug!("Removing TUN interface {name}");
+        self.tun_interfaces.borrow_mut().retain(|x| x.name != name);
+    }
+
+    /// Returns `None` if there is no TUN interface named `name`.
+    #[track_caller]
+    pub fn tun_interface_borrow_mut(&self, name: &str) -> Option<RefMut<TunInterface>> {
+        RefMut::filter_map(self.tun_interfaces.borrow_mut(), |interfaces| {
+            interfaces.iter_mut().find(|x| x.name == name)
+        })
+        .ok()
+    }
+
+    /// Whether the TUN interface named `name` is up. Returns `None` if there is no such interface.
+    pub fn tun_interface_is_up(&self, name: &str) -> Option<bool> {
+        self.tun_interfaces
+            .borrow()
+            .iter()
+            .find(|x| x.name == name)
+            .map(|x| x.is_up)
+    }
+
+    /// Returns the TUN interface that packets to `dst` are routed to, if any.
+    #[track_caller]
+    pub fn tun_route_borrow(&self, dst: Ipv4Addr) -> Option<Ref<TunInterface>> {
+        Ref::filter_map(self.tun_interfaces.borrow(), |interfaces| {
+            interfaces.iter().find(|x| x.routes(dst))
+        })
+        .ok()
+    }
+
+    /// Whether `addr` is the address of `eth0` or one of the other interfaces that share the
+    /// internet interface.
+    pub fn is_internet_address(&self, addr: Ipv4Addr) -> bool {
+        addr == self.default_ip || self.extra_addresses.iter().any(|(x, _)| *x == addr)
+    }
+
+    /// The address that packets to `dst` are sent from, which is the address of the interface of
+    /// the route with the longest prefix that contains `dst`. Packets that no route contains are
+    /// sent on `eth0`. This doesn't consider the loopback or TUN interfaces.
+    pub fn source_ip(&self, dst: Ipv4Addr) -> Ipv4Addr {
+        // of the routes with the longest prefix, use the first
+        let index = self
+            .routes
+            .iter()
+            .rev()
+            .filter(|(subnet, _)| subnet.contains(dst))
+            .max_by_key(|(subnet, _)| subnet.prefix_len())
+            .map_or(0, |(_, index)| *index);
+
+        match index {
+            0 => self.default_ip,
+            _ => self.extra_addresses[index - 1].0,
+        }
+    }
+
+    /// The routes that choose the interface of packets by their destination, and the interface
+    /// index of each route's interface (see [`Self::interfaces`]).
+    pub fn routes(&self) -> Vec<(Subnet, libc::c_int)> {
+        self.routes
+            .iter()
+            // eth0 has index 2
+            .map(|(subnet, index)| (*subnet, 2 + libc::c_int::try_from(*index).unwrap()))
+            .collect()
+    }
+
+    /// Whether `addr` is the address of one of the TUN interfaces.
+    pub fn is_tun_address(&self, addr: Ipv4Addr) -> bool {
+        self.tun_interfaces
+            .borrow()
+            .iter()
+            .any(|x| x.address == Some(addr))
+    }
+
+    /// The MTU of the interface that packets to `dst` are sent on.
+    pub fn interface_mtu(&self, dst: Ipv4Addr) -> u32 {
+        if let Some(tun) = self.tun_route_borrow(dst) {
+            return tun.mtu;
+        }
+
+        let is_loopback = dst.is_loopback();
+        self.interfaces()
+            .into_iter()
+            .find(|x| x.is_loopback == is_loopback && !x.is_tun)
+            .unwrap()
+            .mtu
+    }
+
+    /// The MTU of the path to `dst`. This is the interface's MTU unless a smaller MTU has been
+    /// learned from an ICMP "fragmentation needed" error.
+    pub fn path_mtu(&self, dst: Ipv4Addr) -> u32 {
+        let interface_mtu = self.interface_mtu(dst);
+        match self.path_mtus.borrow().get(&dst) {
+            Some(mtu) => std::cmp::min(*mtu, interface_mtu),
+            None => interface_mtu,
+        }
+    }
+
+    /// Lower the MTU of the path to `dst` after receiving an ICMP "fragmentation needed" error. Like
+    /// Linux, the path MTU is never lowered below [`MIN_PATH_MTU`]. Unlike Linux, learned path MTUs
+    /// never expire.
+    pub fn update_path_mtu(&self, dst: Ipv4Addr, mtu: u32) {
+        let mtu = std::cmp::max(mtu, MIN_PATH_MTU);
+        log::debug!("Lowering the path MTU to {dst} to {mtu}");
+        self.path_mtus
+            .borrow_mut()
+            .entry(dst)
+            .and_modify(|x| *x = std::cmp::min(*x, mtu))
+            .or_insert(mtu);
+    }
+
+    /// Add a socket to the multicast `group`. The host (`host_id`) becomes a member of the group
+    /// when the first socket joins it.
+    pub fn join_multicast_group(&self, group: Ipv4Addr, host_id: HostId) {
+        let mut groups = self.multicast_groups.borrow_mut();
+        let num_sockets = groups.entry(group).or_insert(0);
+        *num_sockets += 1;
+
+        if *num_sockets == 1 {
+            log::debug!("Joining multicast group {group}");
+            Worker::join_multicast_group(group, host_id, self.default_ip);
+        }
+    }
+
+    /// Remove a socket from the multicast `group`. The host (`host_id`) stops being a member of
+    /// the group when the last socket leaves it.
+    pub fn leave_multicast_group(&self, group: Ipv4Addr, host_id: HostId) {
+        let mut groups = self.multicast_groups.borrow_mut();
+        let num_sockets = groups.get_mut(&group).unwrap();
+        *num_sockets -= 1;
+
+        if *num_sockets == 0 {
+            log::debug!("Leaving multicast group {group}");
+            groups.remove(&group);
+            Worker::leave_multicast_group(group, host_id, self.default_ip);
+        }
+    }
+
+    /// Whether `addr` is a broadcast address of the internet interface: either the limited
+    /// broadcast address (255.255.255.255) or the subnet's broadcast address.
+    pub fn is_broadcast(&self, addr: Ipv4Addr) -> bool {
+        addr.is_broadcast() || addr == subnet_broadcast_address(self.default_ip)
+    }
+
+    /// Whether any socket is a member of the multicast `group`.
+    pub fn is_multicast_member(&self, group: Ipv4Addr) -> bool {
+        self.multicast_groups.borrow().contains_key(&group)
+    }
+
     /// Returns `None` if there is no such interface.
     #[track_caller]
     pub fn interface_borrow(
@@ -155,7 +565,7 @@ impl NetworkNamespace {
         //   instead of loopback. It's not clear if this will lead to bugs.
         if addr.is_loopback() {
             Some(self.localhost.borrow())
-        } else if addr == self.default_ip || addr.is_unspecified() {
+        } else if self.is_internet_address(addr) || addr.is_unspecified() {
             Some(self.internet.borrow())
         } else {
             None
@@ -177,7 +587,7 @@ impl NetworkNamespace {
         //   instead of loopback. It's not clear if this will lead to bugs.
         if addr.is_loopback() {
             Some(self.localhost.borrow_mut())
-        } else if addr == self.default_ip || addr.is_unspecified() {
+        } else if self.is_internet_address(addr) || addr.is_unspecified() {
             Some(self.internet.borrow_mut())
         } else {
             None
@@ -207,6 +617,20 @@ impl NetworkNamespace {
         }
     }
 
+    /// Returns true if a TCP socket is listening on the port (in host byte order) of the
+    /// localhost or internet interface.
+    pub fn is_listening(&self, port: u16) -> bool {
+        let peer = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+        [&self.localhost, &self.internet]
+            .into_iter()
+            .any(|interface| {
+                interface
+                    .borrow()
+                    .lookup(cshadow::_ProtocolType_PTCP, port, peer)
+                    .is_some_and(|socket| socket.try_borrow().is_ok_and(|x| x.is_listening()))
+            })
+    }
+
     /// Returns a random port in host byte order.
     pub fn get_random_free_port(
         &self,
@@ -336,6 +760,9 @@ impl NetworkNamespace {
 impl std::ops::Drop for NetworkNamespace {
     fn drop(&mut self) {
         unsafe { cshadow::address_unref(self.default_address.ptr()) };
+        for (_, addr) in &self.extra_addresses {
+            unsafe { cshadow::address_unref(addr.ptr()) };
+        }
 
         if !self.has_run_cleanup.get() && !std::thread::panicking() {
             debug_panic!("Dropped the network namespace before it has been cleaned up");
@@ -349,6 +776,19 @@ struct InterfaceOptions {
     pub ip: Ipv4Addr,
     pub pcap: Option<PcapOptions>,
     pub qdisc: QDiscMode,
+    pub egress_qdisc: Option<Box<dyn Qdisc>>,
+    pub firewall: Option<Firewall>,
+    /// The time from which the hostname can be resolved.
+    pub start_time: SimulationTime,
+}
+
+/// A deterministic MAC address for the interface with IP address `ip`. Since each host has a unique
+/// IP address, each host gets a unique MAC address. The address is a locally administered unicast
+/// address (the second-least-significant bit of the first octet is set, and the least-significant
+/// bit is unset), so it won't conflict with any vendor-assigned addresses.
+fn hw_address_for_ip(ip: Ipv4Addr) -> [u8; 6] {
+    let [a, b, c, d] = ip.octets();
+    [0x02, 0x00, a, b, c, d]
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
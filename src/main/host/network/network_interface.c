@@ -38,9 +38,10 @@ struct _NetworkInterface {
     /* Transports wanting to send data out. */
     RrSocketQueue rrQueue;
     FifoSocketQueue fifoQueue;
+    DrrSocketQueue drrQueue;
 
     /* To support capturing incoming and outgoing packets */
-    PcapWriter_BufWriter_File* pcap;
+    PacketCapture* pcap;
 
     MAGIC_DECLARE;
 };
@@ -124,11 +125,11 @@ static void _networkinterface_capturePacket(NetworkInterface* interface, Packet*
     guint32 ts_sec = now / SIMTIME_ONE_SECOND;
     guint32 ts_usec = (now % SIMTIME_ONE_SECOND) / SIMTIME_ONE_MICROSECOND;
 
-    int error = pcapwriter_writePacket(interface->pcap, ts_sec, ts_usec, packet);
+    int error = packetcapture_writePacket(interface->pcap, ts_sec, ts_usec, packet);
     if (error) {
         /* if there was a non-recoverable error */
         warning("Fatal pcap logging error; stopping pcap logging for current interface");
-        pcapwriter_free(interface->pcap);
+        packetcapture_free(interface->pcap);
         interface->pcap = NULL;
     }
 }
@@ -137,6 +138,34 @@ static const InetSocket* _boundsockets_lookup(GHashTable* table, gchar* key) {
     return g_hash_table_lookup(table, key);
 }
 
+const InetSocket* networkinterface_lookup(NetworkInterface* interface, ProtocolType type,
+                                          in_port_t port, in_addr_t peerIP, in_port_t peerPort) {
+    MAGIC_ASSERT(interface);
+
+    /* first check for a socket with the specific association */
+    gchar* key = _networkinterface_getAssociationKey(interface, type, port, peerIP, peerPort);
+    trace("looking for socket associated with specific key %s", key);
+
+    const InetSocket* socket = _boundsockets_lookup(interface->boundSockets, key);
+    g_free(key);
+
+    if (socket == NULL) {
+        /* then check for a socket with a wildcard association */
+        key = _networkinterface_getAssociationKey(interface, type, port, 0, 0);
+        trace("looking for socket associated with general key %s", key);
+        socket = _boundsockets_lookup(interface->boundSockets, key);
+        g_free(key);
+    }
+
+    /* the caller may cause the socket to be disassociated and freed while using it, so they need
+     * their own reference */
+    if (socket != NULL) {
+        socket = inetsocket_cloneRef(socket);
+    }
+
+    return socket;
+}
+
 void networkinterface_push(NetworkInterface* interface, Packet* packet, CEmulatedTime recvTime) {
     MAGIC_ASSERT(interface);
 
@@ -148,25 +177,38 @@ void networkinterface_push(NetworkInterface* interface, Packet* packet, CEmulate
     /* successfully received */
     packet_addDeliveryStatus(packet, PDS_RCV_INTERFACE_RECEIVED);
 
+    /* the network corrupted the packet, so its checksum is invalid and the kernel would drop it
+     * before it reaches a socket */
+    if (packet_getDeliveryStatus(packet) & PDS_INET_CORRUPTED) {
+        if (interface->pcap) {
+            _networkinterface_capturePacket(interface, packet);
+        }
+
+        if (logger_isEnabled(logger_getDefault(), LOGLEVEL_DEBUG)) {
+            gchar* packetStr = packet_toString(packet);
+            debug("Dropping corrupted packet with an invalid checksum: %s", packetStr);
+            g_free(packetStr);
+        }
+
+        packet_addDeliveryStatus(packet, PDS_RCV_INTERFACE_DROPPED);
+        return;
+    }
+
     /* hand it off to the correct socket layer */
     ProtocolType ptype = packet_getProtocol(packet);
     in_port_t bindPort = packet_getDestinationPort(packet);
     in_addr_t peerIP = packet_getSourceIP(packet);
     in_port_t peerPort = packet_getSourcePort(packet);
 
-    /* first check for a socket with the specific association */
-    gchar* key = _networkinterface_getAssociationKey(interface, ptype, bindPort, peerIP, peerPort);
-    trace("looking for socket associated with specific key %s", key);
+    const InetSocket* socket =
+        networkinterface_lookup(interface, ptype, bindPort, peerIP, peerPort);
 
-    const InetSocket* socket = _boundsockets_lookup(interface->boundSockets, key);
-    g_free(key);
-
-    if (socket == NULL) {
-        /* then check for a socket with a wildcard association */
-        key = _networkinterface_getAssociationKey(interface, ptype, bindPort, 0, 0);
-        trace("looking for socket associated with general key %s", key);
-        socket = _boundsockets_lookup(interface->boundSockets, key);
-        g_free(key);
+    if (socket == NULL && ptype == PUDP &&
+        packet_getDestinationIP(packet) == address_toNetworkIP(interface->address)) {
+        /* no socket will receive the datagram, so give it to the ICMP responder, which replies
+         * with a "port unreachable" error like the kernel would (but never for multicast or
+         * broadcast datagrams) */
+        socket = networkinterface_lookup(interface, PICMP, 0, 0, 0);
     }
 
     /* record the packet before we process it, otherwise we may send more packets before we
@@ -175,13 +217,6 @@ void networkinterface_push(NetworkInterface* interface, Packet* packet, CEmulate
         _networkinterface_capturePacket(interface, packet);
     }
 
-    /* pushing a packet to the socket may cause the socket to be disassociated and freed and cause
-     * our socket pointer to become dangling while we're using it, so we need to increase its ref
-     * count */
-    if (socket != NULL) {
-        socket = inetsocket_cloneRef(socket);
-    }
-
     /* if the socket closed, just drop the packet */
     if (socket != NULL) {
         inetsocket_pushInPacket(socket, packet, recvTime);
@@ -293,6 +328,37 @@ static Packet* _networkinterface_selectFirstInFirstOut(NetworkInterface* interfa
     return NULL;
 }
 
+/* deficit round robin queuing discipline, with the flow scheduling of fq_codel ($ man tc-fq_codel)
+ * but without the codel queue management */
+static Packet* _networkinterface_selectDeficitRoundRobin(NetworkInterface* interface,
+                                                         const InetSocket** socketOut) {
+    InetSocket* socket = NULL;
+
+    while (drrsocketqueue_next(&interface->drrQueue, &socket)) {
+        Packet* packet = inetsocket_pullOutPacket(socket);
+
+        if (packet == NULL) {
+            /* socket had no packet, unref it from the sendable queue */
+            inetsocket_drop(drrsocketqueue_remove(&interface->drrQueue, socket));
+            continue;
+        }
+
+        /* we're returning the socket, so we must ref it */
+        *socketOut = inetsocket_cloneRef(socket);
+
+        drrsocketqueue_charge(&interface->drrQueue, socket, packet_getTotalSize(packet));
+
+        if (!inetsocket_hasDataToSend(socket)) {
+            /* socket has no more packets, unref it from the sendable queue */
+            inetsocket_drop(drrsocketqueue_remove(&interface->drrQueue, socket));
+        }
+
+        return packet;
+    }
+
+    return NULL;
+}
+
 static Packet* _networkinterface_pop_next_packet_out(NetworkInterface* interface,
                                                      const InetSocket** socketOut) {
     MAGIC_ASSERT(interface);
@@ -300,6 +366,9 @@ static Packet* _networkinterface_pop_next_packet_out(NetworkInterface* interface
         case Q_DISC_MODE_ROUND_ROBIN: {
             return _networkinterface_selectRoundRobin(interface, socketOut);
         }
+        case Q_DISC_MODE_DEFICIT_ROUND_ROBIN: {
+            return _networkinterface_selectDeficitRoundRobin(interface, socketOut);
+        }
         case Q_DISC_MODE_FIFO:
         default: {
             return _networkinterface_selectFirstInFirstOut(interface, socketOut);
@@ -358,6 +427,13 @@ void networkinterface_wantsSend(NetworkInterface* interface, const InetSocket* s
             }
             break;
         }
+        case Q_DISC_MODE_DEFICIT_ROUND_ROBIN: {
+            if (!drrsocketqueue_find(&interface->drrQueue, socket)) {
+                const InetSocket* newSocketRef = inetsocket_cloneRef(socket);
+                drrsocketqueue_push(&interface->drrQueue, newSocketRef);
+            }
+            break;
+        }
         case Q_DISC_MODE_FIFO:
         default: {
             if (!fifosocketqueue_find(&interface->fifoQueue, socket)) {
@@ -374,15 +450,17 @@ void networkinterface_removeAllSockets(NetworkInterface* interface) {
 
     rrsocketqueue_destroy(&interface->rrQueue, inetsocket_drop);
     fifosocketqueue_destroy(&interface->fifoQueue, inetsocket_drop);
+    drrsocketqueue_destroy(&interface->drrQueue, inetsocket_drop);
 
     rrsocketqueue_init(&interface->rrQueue);
     fifosocketqueue_init(&interface->fifoQueue);
+    drrsocketqueue_init(&interface->drrQueue);
 
     g_hash_table_remove_all(interface->boundSockets);
 }
 
-NetworkInterface* networkinterface_new(Address* address, const char* name, const gchar* pcapDir,
-                                       guint32 pcapCaptureSize, QDiscMode qdisc) {
+NetworkInterface* networkinterface_new(Address* address, const char* name, PacketCapture* pcap,
+                                       QDiscMode qdisc) {
     NetworkInterface* interface = g_new0(NetworkInterface, 1);
     MAGIC_INIT(interface);
 
@@ -396,28 +474,25 @@ NetworkInterface* networkinterface_new(Address* address, const char* name, const
     /* sockets tell us when they want to start sending */
     rrsocketqueue_init(&interface->rrQueue);
     fifosocketqueue_init(&interface->fifoQueue);
+    drrsocketqueue_init(&interface->drrQueue);
 
     /* parse queuing discipline */
     interface->qdisc = qdisc;
 
-    if (pcapDir != NULL) {
-        GString* filename = g_string_new(NULL);
-        g_string_append(filename, pcapDir);
+    /* we take ownership of the pcap file, if any */
+    interface->pcap = pcap;
 
-        /* Append trailing slash if not present */
-        if (!g_str_has_suffix(filename->str, "/")) {
-            g_string_append(filename, "/");
-        }
-
-        g_string_append_printf(filename, "%s.pcap", name);
-
-        interface->pcap = pcapwriter_new(filename->str, pcapCaptureSize);
-        g_string_free(filename, TRUE);
+    const char* qdiscName = NULL;
+    switch (interface->qdisc) {
+        case Q_DISC_MODE_ROUND_ROBIN: qdiscName = "rr"; break;
+        case Q_DISC_MODE_DEFICIT_ROUND_ROBIN: qdiscName = "drr"; break;
+        case Q_DISC_MODE_FIFO:
+        default: qdiscName = "fifo"; break;
     }
 
     debug("bringing up network interface '%s' for host '%s' at '%s' using queuing discipline %s",
           name, address_toHostName(interface->address), address_toHostIPString(interface->address),
-          interface->qdisc == Q_DISC_MODE_ROUND_ROBIN ? "rr" : "fifo");
+          qdiscName);
 
     worker_count_allocation(NetworkInterface);
     return interface;
@@ -429,13 +504,14 @@ void networkinterface_free(NetworkInterface* interface) {
     /* unref all sockets wanting to send */
     rrsocketqueue_destroy(&interface->rrQueue, inetsocket_drop);
     fifosocketqueue_destroy(&interface->fifoQueue, inetsocket_drop);
+    drrsocketqueue_destroy(&interface->drrQueue, inetsocket_drop);
 
     g_hash_table_destroy(interface->boundSockets);
 
     address_unref(interface->address);
 
     if(interface->pcap) {
-        pcapwriter_free(interface->pcap);
+        packetcapture_free(interface->pcap);
     }
 
     MAGIC_CLEAR(interface);
@@ -19,8 +19,8 @@ typedef struct _NetworkInterface NetworkInterface;
 #include "main/routing/address.h"
 #include "main/routing/packet.minimal.h"
 
-NetworkInterface* networkinterface_new(Address* address, const char* name, const gchar* pcapDir,
-                                       guint32 pcapCaptureSize, QDiscMode qdisc);
+NetworkInterface* networkinterface_new(Address* address, const char* name, PacketCapture* pcap,
+                                       QDiscMode qdisc);
 void networkinterface_free(NetworkInterface* interface);
 
 /* The address and ports must be in network byte order. */
@@ -33,6 +33,12 @@ void networkinterface_associate(NetworkInterface* interface, const InetSocket* s
 void networkinterface_disassociate(NetworkInterface* interface, ProtocolType type, in_port_t port,
                                    in_addr_t peerIP, in_port_t peerPort);
 
+/* Returns a new reference to the socket that would receive a packet with the given protocol and
+ * destination port from the given peer, or NULL if there is no such socket. The returned socket
+ * must be freed with `inetsocket_drop`. The address and ports must be in network byte order. */
+const InetSocket* networkinterface_lookup(NetworkInterface* interface, ProtocolType type,
+                                          in_port_t port, in_addr_t peerIP, in_port_t peerPort);
+
 void networkinterface_wantsSend(NetworkInterface* interface, const InetSocket* socket);
 
 Packet* networkinterface_pop(NetworkInterface* interface);
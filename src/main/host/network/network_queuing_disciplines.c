@@ -9,6 +9,7 @@
 #include <stdbool.h>
 
 #include "main/bindings/c/bindings.h"
+#include "main/core/definitions.h"
 #include "main/host/descriptor/compat_socket.h"
 #include "main/routing/packet.h"
 #include "main/utility/priority_queue.h"
@@ -158,3 +159,132 @@ bool fifosocketqueue_find(FifoSocketQueue* self, const InetSocket* socket) {
     utility_debugAssert(self->queue != NULL);
     return priorityqueue_find(self->queue, (void*)socket);
 }
+
+/* The number of bytes that each socket may send per round. Linux uses the interface's MTU plus the
+ * link-layer header size, but we don't model link-layer headers. */
+#define DRR_QUANTUM CONFIG_MTU
+
+typedef struct _DrrFlow DrrFlow;
+struct _DrrFlow {
+    /* the queue's reference to the socket */
+    InetSocket* socket;
+    /* the number of bytes the socket can send before its turn ends */
+    gint64 deficit;
+};
+
+void drrsocketqueue_init(DrrSocketQueue* self) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows == NULL);
+    self->newFlows = g_queue_new();
+    self->oldFlows = g_queue_new();
+    self->flows = g_hash_table_new(inetsocket_hashVoid, _inetsocket_eqVoid);
+}
+
+static void _drrsocketqueue_destroyFlows(GQueue* flows,
+                                         void (*fn_processItem)(const InetSocket*)) {
+    DrrFlow* flow = NULL;
+    while ((flow = g_queue_pop_head(flows)) != NULL) {
+        if (fn_processItem != NULL) {
+            fn_processItem(flow->socket);
+        }
+        g_free(flow);
+    }
+    g_queue_free(flows);
+}
+
+void drrsocketqueue_destroy(DrrSocketQueue* self, void (*fn_processItem)(const InetSocket*)) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+
+    g_hash_table_destroy(self->flows);
+    self->flows = NULL;
+
+    _drrsocketqueue_destroyFlows(self->newFlows, fn_processItem);
+    self->newFlows = NULL;
+    _drrsocketqueue_destroyFlows(self->oldFlows, fn_processItem);
+    self->oldFlows = NULL;
+}
+
+bool drrsocketqueue_isEmpty(DrrSocketQueue* self) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+    return g_hash_table_size(self->flows) == 0;
+}
+
+bool drrsocketqueue_next(DrrSocketQueue* self, InetSocket** socket) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+
+    while (true) {
+        GQueue* flows = !g_queue_is_empty(self->newFlows) ? self->newFlows : self->oldFlows;
+        DrrFlow* flow = g_queue_peek_head(flows);
+
+        if (flow == NULL) {
+            return false;
+        }
+
+        if (flow->deficit <= 0) {
+            /* the socket's turn is over; it can send more in the next round */
+            flow->deficit += DRR_QUANTUM;
+            g_queue_pop_head(flows);
+            g_queue_push_tail(self->oldFlows, flow);
+            continue;
+        }
+
+        *socket = flow->socket;
+        return true;
+    }
+}
+
+void drrsocketqueue_charge(DrrSocketQueue* self, const InetSocket* socket, gsize size) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+
+    DrrFlow* flow = g_hash_table_lookup(self->flows, socket);
+    utility_debugAssert(flow != NULL);
+
+    if (flow != NULL) {
+        flow->deficit -= (gint64)size;
+    }
+}
+
+void drrsocketqueue_push(DrrSocketQueue* self, const InetSocket* socket) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+    utility_debugAssert(socket != NULL);
+    utility_debugAssert(!drrsocketqueue_find(self, socket));
+
+    DrrFlow* flow = g_new0(DrrFlow, 1);
+    flow->socket = (InetSocket*)socket;
+    flow->deficit = DRR_QUANTUM;
+
+    g_queue_push_tail(self->newFlows, flow);
+    g_hash_table_insert(self->flows, flow->socket, flow);
+}
+
+InetSocket* drrsocketqueue_remove(DrrSocketQueue* self, const InetSocket* socket) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+
+    DrrFlow* flow = g_hash_table_lookup(self->flows, socket);
+
+    if (flow == NULL) {
+        return NULL;
+    }
+
+    g_hash_table_remove(self->flows, socket);
+    if (!g_queue_remove(self->newFlows, flow) && !g_queue_remove(self->oldFlows, flow)) {
+        utility_panic("Socket's flow was not in either of the flow queues");
+    }
+
+    InetSocket* removedSocket = flow->socket;
+    g_free(flow);
+
+    return removedSocket;
+}
+
+bool drrsocketqueue_find(DrrSocketQueue* self, const InetSocket* socket) {
+    utility_debugAssert(self != NULL);
+    utility_debugAssert(self->flows != NULL);
+    return g_hash_table_contains(self->flows, socket);
+}
@@ -24,6 +24,19 @@ struct _FifoSocketQueue {
     PriorityQueue* queue;
 };
 
+/* A deficit round-robin socket queue. Each socket may send up to a quantum of bytes per round.
+ * Like Linux's default fq_codel qdisc, sockets that just became active are scheduled before sockets
+ * that have been sending continuously. */
+typedef struct _DrrSocketQueue DrrSocketQueue;
+struct _DrrSocketQueue {
+    /* sockets that became active during the current round */
+    GQueue* newFlows;
+    /* sockets that have used up their quantum at least once since they became active */
+    GQueue* oldFlows;
+    /* maps sockets to their `DrrFlow` in one of the above queues */
+    GHashTable* flows;
+};
+
 void rrsocketqueue_init(RrSocketQueue* self);
 void rrsocketqueue_destroy(RrSocketQueue* self, void (*fn_processItem)(const InetSocket*));
 
@@ -40,4 +53,20 @@ bool fifosocketqueue_pop(FifoSocketQueue* self, InetSocket** socket);
 void fifosocketqueue_push(FifoSocketQueue* self, const InetSocket* socket);
 bool fifosocketqueue_find(FifoSocketQueue* self, const InetSocket* socket);
 
+void drrsocketqueue_init(DrrSocketQueue* self);
+void drrsocketqueue_destroy(DrrSocketQueue* self, void (*fn_processItem)(const InetSocket*));
+
+bool drrsocketqueue_isEmpty(DrrSocketQueue* self);
+/* Get the socket that should send next, without removing it from the queue. The queue keeps its
+ * reference to the socket. */
+bool drrsocketqueue_next(DrrSocketQueue* self, InetSocket** socket);
+/* Charge a socket for a packet of `size` bytes that it sent. */
+void drrsocketqueue_charge(DrrSocketQueue* self, const InetSocket* socket, gsize size);
+/* Takes ownership of the socket reference. */
+void drrsocketqueue_push(DrrSocketQueue* self, const InetSocket* socket);
+/* Remove the socket from the queue, returning the queue's reference to it or NULL if the socket
+ * wasn't in the queue. */
+InetSocket* drrsocketqueue_remove(DrrSocketQueue* self, const InetSocket* socket);
+bool drrsocketqueue_find(DrrSocketQueue* self, const InetSocket* socket);
+
 #endif /* SRC_MAIN_HOST_NETWORK_QUEUING_DISCIPLINES_H_ */
@@ -0,0 +1,41 @@
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use super::{drop_packet, Qdisc};
+use crate::network::packet::PacketRc;
+use crate::network::router::codel_queue::CoDelQueue;
+
+/// A CoDel active queue management queue, like Linux's codel qdisc. It uses the same parameters as
+/// the queue of the upstream router, but drops arriving packets when it holds `limit` packets.
+pub struct Codel {
+    queue: CoDelQueue,
+    limit: usize,
+}
+
+impl Codel {
+    /// Create a queue that holds up to `limit` packets. If `ecn` is true, ECN-capable packets are
+    /// marked instead of dropped.
+    pub fn new(limit: usize, ecn: bool) -> Self {
+        let mut queue = CoDelQueue::new();
+        queue.set_ecn_marking(ecn);
+        Self { queue, limit }
+    }
+}
+
+impl Qdisc for Codel {
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime) {
+        if self.is_full() {
+            drop_packet(packet);
+            return;
+        }
+
+        self.queue.push(packet, now);
+    }
+
+    fn dequeue(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        self.queue.pop(now)
+    }
+
+    fn is_full(&self) -> bool {
+        self.queue.len() >= self.limit
+    }
+}
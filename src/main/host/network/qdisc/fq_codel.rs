@@ -0,0 +1,185 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddrV4;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use super::{drop_packet, Qdisc};
+use crate::network::packet::PacketRc;
+use crate::network::router::codel_queue::CoDelQueue;
+
+/// Packets are assigned to flows by their source and destination addresses.
+type FlowKey = (SocketAddrV4, SocketAddrV4);
+
+struct Flow {
+    queue: CoDelQueue,
+    /// The number of bytes that the flow can send before its turn ends.
+    deficit: i64,
+}
+
+/// A CoDel queue for each flow, which are scheduled using deficit round-robin, like Linux's
+/// fq_codel qdisc.
+/// <https://tools.ietf.org/html/rfc8290>
+///
+/// Unlike Linux, flows are identified by their exact addresses rather than by a hash, and packets
+/// that arrive when the qdisc is full are dropped rather than packets from the largest flow.
+pub struct FqCodel {
+    /// The active flows, which have packets or are in `new_flows`.
+    flows: HashMap<FlowKey, Flow>,
+    /// Flows that recently became active, which are scheduled before `old_flows`.
+    new_flows: VecDeque<FlowKey>,
+    old_flows: VecDeque<FlowKey>,
+    /// The number of packets in all of the flows.
+    len: usize,
+    limit: usize,
+    quantum: i64,
+    ecn: bool,
+}
+
+impl FqCodel {
+    /// Create a qdisc that holds up to `limit` packets, where each flow can send `quantum` bytes
+    /// per round. If `ecn` is true, ECN-capable packets are marked instead of dropped.
+    pub fn new(limit: usize, quantum: u64, ecn: bool) -> Self {
+        Self {
+            flows: HashMap::new(),
+            new_flows: VecDeque::new(),
+            old_flows: VecDeque::new(),
+            len: 0,
+            limit,
+            quantum: quantum.try_into().unwrap(),
+            ecn,
+        }
+    }
+}
+
+impl Qdisc for FqCodel {
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime) {
+        if self.is_full() {
+            drop_packet(packet);
+            return;
+        }
+
+        let key = (packet.src_address(), packet.dst_address());
+
+        let flow = self.flows.entry(key).or_insert_with(|| {
+            // a new flow starts with a full quantum and is scheduled before the old flows
+            self.new_flows.push_back(key);
+
+            let mut queue = CoDelQueue::new();
+            queue.set_ecn_marking(self.ecn);
+            Flow {
+                queue,
+                deficit: self.quantum,
+            }
+        });
+
+        flow.queue.push(packet, now);
+        self.len += 1;
+    }
+
+    fn dequeue(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        loop {
+            let (key, is_new) = match self.new_flows.front() {
+                Some(key) => (*key, true),
+                None => (*self.old_flows.front()?, false),
+            };
+
+            let list = if is_new {
+                &mut self.new_flows
+            } else {
+                &mut self.old_flows
+            };
+
+            let flow = self.flows.get_mut(&key).unwrap();
+
+            // the flow used its quantum, so it waits for its next turn
+            if flow.deficit <= 0 {
+                flow.deficit += self.quantum;
+                list.pop_front();
+                self.old_flows.push_back(key);
+                continue;
+            }
+
+            // the codel queue may drop packets before returning one
+            let len_before = flow.queue.len();
+            let packet = flow.queue.pop(now);
+            self.len -= len_before - flow.queue.len();
+
+            let Some(packet) = packet else {
+                list.pop_front();
+
+                // an empty new flow moves to the old flows so that it can't starve them by
+                // becoming a new flow again, and an empty old flow becomes inactive
+                if is_new {
+                    self.old_flows.push_back(key);
+                } else {
+                    self.flows.remove(&key);
+                }
+                continue;
+            };
+
+            flow.deficit -= i64::try_from(packet.total_size()).unwrap();
+            return Some(packet);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len >= self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    fn mock_packet(src_port: u16) -> PacketRc {
+        PacketRc::mock_new_udp(
+            SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), src_port),
+            SocketAddrV4::new(Ipv4Addr::new(5, 6, 7, 8), 80),
+        )
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_flows_are_interleaved() {
+        let now = mock_time_millis(1000);
+
+        // a quantum of 1 byte allows one packet per turn
+        let mut fq_codel = FqCodel::new(100, 1, false);
+
+        let a: Vec<_> = (0..3).map(|_| mock_packet(1)).collect();
+        let b = mock_packet(2);
+
+        for packet in &a {
+            fq_codel.enqueue(packet.clone(), now);
+        }
+        fq_codel.enqueue(b.clone(), now);
+
+        assert_eq!(fq_codel.dequeue(now).as_ref(), Some(&a[0]));
+        assert_eq!(fq_codel.dequeue(now).as_ref(), Some(&b));
+        assert_eq!(fq_codel.dequeue(now).as_ref(), Some(&a[1]));
+        assert_eq!(fq_codel.dequeue(now).as_ref(), Some(&a[2]));
+        assert!(fq_codel.dequeue(now).is_none());
+
+        assert!(fq_codel.flows.is_empty());
+        assert_eq!(fq_codel.len, 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_limit() {
+        let now = mock_time_millis(1000);
+        let mut fq_codel = FqCodel::new(2, 1514, false);
+
+        for port in 1..=3 {
+            fq_codel.enqueue(mock_packet(port), now);
+        }
+
+        assert!(fq_codel.is_full());
+        assert!(fq_codel.dequeue(now).is_some());
+        assert!(fq_codel.dequeue(now).is_some());
+        assert!(fq_codel.dequeue(now).is_none());
+    }
+}
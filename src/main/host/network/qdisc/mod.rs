@@ -0,0 +1,78 @@
+//! Queuing disciplines for the packets sent by a network interface, like Linux's qdiscs.
+//!
+//! The interface's socket queue (see `experimental.interface_qdisc`) chooses which socket sends
+//! the next packet, but packets otherwise stay in their socket's send buffer until the relay
+//! forwards them. A [`Qdisc`] adds a packet queue between the sockets and the relay. Whenever the
+//! relay asks the interface for a packet, the interface moves packets from its sockets into the
+//! qdisc until the qdisc is full, so a standing queue builds up in the qdisc when the sockets send
+//! faster than the host's bandwidth.
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use crate::core::configuration::EgressQdisc;
+use crate::network::packet::{PacketRc, PacketStatus};
+use crate::utility::units::{self, Unit};
+
+mod codel;
+mod fq_codel;
+mod netem;
+mod pfifo;
+
+pub use codel::Codel;
+pub use fq_codel::FqCodel;
+pub use netem::Netem;
+pub use pfifo::Pfifo;
+
+/// A packet queue that decides the order in which packets are sent, and which packets are dropped.
+pub trait Qdisc {
+    /// Add a packet to the queue. The qdisc may drop the packet instead.
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime);
+
+    /// Remove the next packet that should be sent. Returns `None` if there are no packets that can
+    /// be sent at time `now`.
+    fn dequeue(&mut self, now: EmulatedTime) -> Option<PacketRc>;
+
+    /// Returns true if the qdisc would drop the next enqueued packet because it has no room.
+    fn is_full(&self) -> bool;
+
+    /// If the qdisc holds packets that can't be dequeued until later, returns the earliest time
+    /// that a packet can be dequeued.
+    fn next_dequeue_time(&self) -> Option<EmulatedTime> {
+        None
+    }
+}
+
+/// Create a new qdisc from the configuration options. The `seed` is used for any random decisions
+/// made by the qdisc.
+pub fn new_qdisc(options: &EgressQdisc, seed: u64) -> Box<dyn Qdisc> {
+    match *options {
+        EgressQdisc::Pfifo { limit } => Box::new(Pfifo::new(limit as usize)),
+        EgressQdisc::Netem {
+            limit,
+            delay,
+            loss,
+            rate,
+        } => Box::new(Netem::new(
+            limit as usize,
+            delay.map(|x| std::time::Duration::from(x).try_into().unwrap()),
+            loss.into(),
+            rate.map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+            seed,
+        )),
+        EgressQdisc::Codel { limit, ecn } => Box::new(Codel::new(limit as usize, ecn)),
+        EgressQdisc::FqCodel {
+            limit,
+            quantum,
+            ecn,
+        } => Box::new(FqCodel::new(
+            limit as usize,
+            quantum.convert(units::SiPrefixUpper::Base).unwrap().value(),
+            ecn,
+        )),
+    }
+}
+
+/// Drop a packet that the qdisc won't send.
+fn drop_packet(mut packet: PacketRc) {
+    packet.add_status(PacketStatus::SndQdiscDropped);
+}
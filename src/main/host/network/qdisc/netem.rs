@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use super::{drop_packet, Qdisc};
+use crate::network::packet::PacketRc;
+
+/// A first-in-first-out queue that delays, randomly drops, and rate limits packets, like Linux's
+/// netem qdisc.
+pub struct Netem {
+    /// Packets and the times that they can be sent.
+    packets: VecDeque<(PacketRc, EmulatedTime)>,
+    limit: usize,
+    delay: SimulationTime,
+    loss: f64,
+    rate_bits_per_sec: Option<u64>,
+    /// The time that the most recently enqueued packet can be sent.
+    last_send_time: EmulatedTime,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl Netem {
+    /// Create a queue that holds up to `limit` packets. Each packet is delayed by `delay`, dropped
+    /// with probability `loss`, and if `rate_bits_per_sec` is set, packets leave the queue no
+    /// faster than that rate.
+    pub fn new(
+        limit: usize,
+        delay: Option<SimulationTime>,
+        loss: f64,
+        rate_bits_per_sec: Option<u64>,
+        seed: u64,
+    ) -> Self {
+        Self {
+            packets: VecDeque::new(),
+            limit,
+            delay: delay.unwrap_or(SimulationTime::ZERO),
+            loss,
+            rate_bits_per_sec,
+            last_send_time: EmulatedTime::SIMULATION_START,
+            rng: Xoshiro256PlusPlus::seed_from_u64(seed),
+        }
+    }
+
+    /// The time to transmit a packet at the queue's rate.
+    fn transmit_time(&self, packet: &PacketRc) -> SimulationTime {
+        let Some(rate) = self.rate_bits_per_sec else {
+            return SimulationTime::ZERO;
+        };
+
+        let bits = packet.total_size() as u128 * 8;
+        let nanos = bits * 1_000_000_000 / u128::from(rate);
+        SimulationTime::from_nanos(nanos.try_into().unwrap())
+    }
+}
+
+impl Qdisc for Netem {
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime) {
+        if self.is_full() {
+            drop_packet(packet);
+            return;
+        }
+
+        // don't use the rng if there's no packet loss
+        if self.loss > 0.0 && self.rng.gen::<f64>() < self.loss {
+            drop_packet(packet);
+            return;
+        }
+
+        // the packet is sent after its delay, and after the previous packet finishes transmitting
+        let start = std::cmp::max(now + self.delay, self.last_send_time);
+        let send_time = start + self.transmit_time(&packet);
+
+        self.last_send_time = send_time;
+        self.packets.push_back((packet, send_time));
+    }
+
+    fn dequeue(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        match self.packets.front() {
+            Some((_, send_time)) if *send_time <= now => self.packets.pop_front().map(|x| x.0),
+            _ => None,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.packets.len() >= self.limit
+    }
+
+    fn next_dequeue_time(&self) -> Option<EmulatedTime> {
+        self.packets.front().map(|(_, send_time)| *send_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_delay() {
+        let delay = SimulationTime::from_millis(10);
+        let mut netem = Netem::new(10, Some(delay), 0.0, None, 1);
+
+        let packet = PacketRc::mock_new();
+        netem.enqueue(packet.clone(), mock_time_millis(1000));
+
+        assert!(netem.dequeue(mock_time_millis(1005)).is_none());
+        assert_eq!(netem.next_dequeue_time(), Some(mock_time_millis(1010)));
+        assert_eq!(netem.dequeue(mock_time_millis(1010)), Some(packet));
+        assert_eq!(netem.next_dequeue_time(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_loss() {
+        let mut netem = Netem::new(1000, None, 0.5, None, 1);
+        let now = mock_time_millis(1000);
+
+        for _ in 0..1000 {
+            netem.enqueue(PacketRc::mock_new(), now);
+        }
+
+        let mut count = 0;
+        while netem.dequeue(now).is_some() {
+            count += 1;
+        }
+        assert!((400..600).contains(&count), "{count}");
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use super::{drop_packet, Qdisc};
+use crate::network::packet::PacketRc;
+
+/// A first-in-first-out queue that drops arriving packets when it's full, like Linux's pfifo
+/// qdisc.
+pub struct Pfifo {
+    packets: VecDeque<PacketRc>,
+    limit: usize,
+}
+
+impl Pfifo {
+    /// Create a queue that holds up to `limit` packets.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            packets: VecDeque::new(),
+            limit,
+        }
+    }
+}
+
+impl Qdisc for Pfifo {
+    fn enqueue(&mut self, packet: PacketRc, _now: EmulatedTime) {
+        if self.is_full() {
+            drop_packet(packet);
+            return;
+        }
+
+        self.packets.push_back(packet);
+    }
+
+    fn dequeue(&mut self, _now: EmulatedTime) -> Option<PacketRc> {
+        self.packets.pop_front()
+    }
+
+    fn is_full(&self) -> bool {
+        self.packets.len() >= self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_limit() {
+        let now = mock_time_millis(1000);
+        let mut pfifo = Pfifo::new(2);
+
+        let packets: Vec<_> = (0..3).map(|_| PacketRc::mock_new()).collect();
+        for packet in &packets {
+            pfifo.enqueue(packet.clone(), now);
+        }
+        assert!(pfifo.is_full());
+
+        // the last packet was dropped
+        assert_eq!(pfifo.dequeue(now).as_ref(), Some(&packets[0]));
+        assert!(!pfifo.is_full());
+        assert_eq!(pfifo.dequeue(now).as_ref(), Some(&packets[1]));
+        assert!(pfifo.dequeue(now).is_none());
+    }
+}
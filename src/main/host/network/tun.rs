@@ -0,0 +1,80 @@
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Weak};
+
+use atomic_refcell::AtomicRefCell;
+
+use crate::host::descriptor::tun::TunFile;
+use crate::network::packet::{PacketRc, PacketStatus};
+use crate::network::PacketDevice;
+use crate::utility::callback_queue::CallbackQueue;
+
+/// The default MTU of a TUN interface, from Linux.
+pub const TUN_DEFAULT_MTU: u32 = 1500;
+
+/// A TUN interface created by a managed process with `TUNSETIFF`. Packets that the host routes to
+/// the interface are queued in the interface's [`TunFile`] for the process to read. The interface
+/// is configured using the network device ioctls.
+pub struct TunInterface {
+    pub name: String,
+    pub index: libc::c_int,
+    /// The address and prefix length, if the interface has been assigned an address.
+    pub address: Option<Ipv4Addr>,
+    pub prefix_len: u8,
+    pub mtu: u32,
+    pub is_up: bool,
+    file: Weak<AtomicRefCell<TunFile>>,
+}
+
+impl TunInterface {
+    pub fn new(name: String, index: libc::c_int, file: &Arc<AtomicRefCell<TunFile>>) -> Self {
+        Self {
+            name,
+            index,
+            address: None,
+            prefix_len: 0,
+            mtu: TUN_DEFAULT_MTU,
+            is_up: false,
+            file: Arc::downgrade(file),
+        }
+    }
+
+    /// Whether packets to `dst` are routed to this interface: the interface is up and `dst` is in
+    /// its subnet, but isn't the interface's own address.
+    pub fn routes(&self, dst: Ipv4Addr) -> bool {
+        let Some(address) = self.address else {
+            return false;
+        };
+
+        let netmask = u32::MAX
+            .checked_shl(32 - u32::from(self.prefix_len))
+            .unwrap_or(0);
+
+        self.is_up && dst != address && u32::from(dst) & netmask == u32::from(address) & netmask
+    }
+}
+
+impl PacketDevice for TunInterface {
+    fn get_address(&self) -> Ipv4Addr {
+        self.address.unwrap_or(Ipv4Addr::UNSPECIFIED)
+    }
+
+    fn pop(&self) -> Option<PacketRc> {
+        // packets sent from the interface are written to its file, and the host receives them
+        // directly
+        None
+    }
+
+    fn push(&self, mut packet: PacketRc) {
+        let queued = self.file.upgrade().is_some_and(|file| {
+            let bytes = packet.to_ipv4_bytes();
+            CallbackQueue::queue_and_run(|cb_queue| file.borrow_mut().push_packet(bytes, cb_queue))
+        });
+
+        if queued {
+            packet.add_status(PacketStatus::RcvInterfaceReceived);
+        } else {
+            log::trace!("TUN interface {} dropped packet {packet:?}", self.name);
+            packet.add_status(PacketStatus::RcvInterfaceDropped);
+        }
+    }
+}
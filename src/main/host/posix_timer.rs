@@ -0,0 +1,132 @@
+//! POSIX per-process timers, as created by `timer_create(2)`.
+
+use std::collections::BTreeMap;
+
+use linux_api::signal::Signal;
+use linux_api::time::kernel_timer_t;
+
+use super::thread::ThreadId;
+use super::timer::Timer;
+
+/// How a [`PosixTimer`] notifies the process when it expires.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PosixTimerNotify {
+    /// `SIGEV_NONE`: the process isn't notified.
+    None,
+    /// `SIGEV_SIGNAL` or `SIGEV_THREAD_ID`: `signal` is sent to `thread` if set, or otherwise to
+    /// the process. `sigval` is the signal's `si_value`.
+    Signal {
+        signal: Signal,
+        sigval: usize,
+        thread: Option<ThreadId>,
+    },
+}
+
+pub struct PosixTimer {
+    timer: Timer,
+    notify: PosixTimerNotify,
+    /// The number of expirations while the timer's most recent signal was pending.
+    overrun: i32,
+    /// The overrun count of the timer's previous signal.
+    overrun_last: i32,
+}
+
+impl PosixTimer {
+    pub fn new(timer: Timer, notify: PosixTimerNotify) -> Self {
+        Self {
+            timer,
+            notify,
+            overrun: 0,
+            overrun_last: 0,
+        }
+    }
+
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    pub fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    pub fn notify(&self) -> PosixTimerNotify {
+        self.notify
+    }
+
+    /// Record that the timer expired while its most recent signal was still pending, so no new
+    /// signal is sent. Returns the new overrun count of that signal.
+    pub fn record_overrun(&mut self) -> i32 {
+        // like linux, saturate at `DELAYTIMER_MAX`
+        self.overrun = self.overrun.saturating_add(1);
+        self.overrun
+    }
+
+    /// Record that a new signal was sent for the timer.
+    pub fn record_signal(&mut self) {
+        self.overrun_last = self.overrun;
+        self.overrun = 0;
+    }
+
+    /// Reset the overrun counts, for example when the timer is re-armed.
+    pub fn reset_overrun(&mut self) {
+        self.overrun = 0;
+        self.overrun_last = 0;
+    }
+
+    /// The overrun count that `timer_getoverrun(2)` returns, which is for the most recently
+    /// delivered signal. `signal_pending` is whether the timer's most recent signal is pending.
+    pub fn overrun(&self, signal_pending: bool) -> i32 {
+        if signal_pending {
+            self.overrun_last
+        } else {
+            self.overrun
+        }
+    }
+}
+
+/// The POSIX timers of a process.
+#[derive(Default)]
+pub struct PosixTimerTable {
+    timers: BTreeMap<kernel_timer_t, PosixTimer>,
+    next_id: kernel_timer_t,
+}
+
+impl PosixTimerTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the timer returned by `f`, which is called with the new timer's ID. Returns the ID, or
+    /// `None` if all IDs are in use.
+    pub fn add(&mut self, f: impl FnOnce(kernel_timer_t) -> PosixTimer) -> Option<kernel_timer_t> {
+        // like linux, allocate IDs sequentially and wrap around to 0
+        let mut id = self.next_id;
+        while self.timers.contains_key(&id) {
+            id = id.checked_add(1).unwrap_or(0);
+            if id == self.next_id {
+                return None;
+            }
+        }
+
+        self.next_id = id.checked_add(1).unwrap_or(0);
+        self.timers.insert(id, f(id));
+        Some(id)
+    }
+
+    pub fn get(&self, id: kernel_timer_t) -> Option<&PosixTimer> {
+        self.timers.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: kernel_timer_t) -> Option<&mut PosixTimer> {
+        self.timers.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: kernel_timer_t) -> Option<PosixTimer> {
+        self.timers.remove(&id)
+    }
+
+    /// Delete all timers.
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+}
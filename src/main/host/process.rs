@@ -16,19 +16,21 @@ use std::time::Duration;
 use linux_api::errno::Errno;
 use linux_api::sched::{CloneFlags, SuidDump};
 use linux_api::signal::{
-    defaultaction, siginfo_t, sigset_t, LinuxDefaultAction, SigActionFlags, Signal,
-    SignalFromI32Error,
+    defaultaction, linux_sigval, siginfo_t, sigset_t, LinuxDefaultAction, SigActionFlags,
+    SigInfoDetails, Signal, SignalFromI32Error,
 };
+use linux_api::time::{kernel_timer_t, ITimerId};
 use log::{debug, trace, warn};
 use nix::fcntl::OFlag;
 use nix::sys::signal as nixsignal;
 use nix::sys::stat::Mode;
 use nix::unistd::Pid;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::explicit_drop::{ExplicitDrop, ExplicitDropper};
 use shadow_shim_helper_rs::rootedcell::rc::RootedRc;
 use shadow_shim_helper_rs::rootedcell::refcell::RootedRefCell;
 use shadow_shim_helper_rs::rootedcell::Root;
-use shadow_shim_helper_rs::shim_shmem::ProcessShmem;
+use shadow_shim_helper_rs::shim_shmem::{ProcessShmem, ProcessShmemProtected};
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::syscall_types::{ForeignPtr, ManagedPhysicalMemoryAddr};
 use shadow_shim_helper_rs::HostId;
@@ -39,16 +41,18 @@ use super::descriptor::listener::StateEventSource;
 use super::descriptor::{FileSignals, FileState};
 use super::host::Host;
 use super::memory_manager::{MemoryManager, ProcessMemoryRef, ProcessMemoryRefMut};
+use super::posix_timer::{PosixTimer, PosixTimerNotify, PosixTimerTable};
 use super::syscall::formatter::StraceFmtMode;
 use super::syscall::types::ForeignArrayPtr;
 use super::thread::{Thread, ThreadId};
-use super::timer::Timer;
-use crate::core::configuration::{ProcessFinalState, RunningVal};
+use super::timer::{CpuTimer, Timer};
+use crate::core::configuration::{OutputLimitAction, ProcessFinalState, RunningVal};
 use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow;
 use crate::host::context::ProcessContext;
 use crate::host::descriptor::Descriptor;
+use crate::host::file_lock_table::FileLockOwner;
 use crate::host::managed_thread::ManagedThread;
 use crate::host::syscall::formatter::FmtOptions;
 use crate::utility::callback_queue::CallbackQueue;
@@ -221,6 +225,10 @@ impl Common {
     }
 }
 
+/// Called when a process exits with whether its final state was its expected final state. Returns
+/// whether the process will be restarted.
+pub type ExitHandler = Box<dyn Fn(&Host, bool) -> bool + Send + Sync>;
+
 /// A process that is currently runnable.
 pub struct RunnableProcess {
     common: Common,
@@ -233,6 +241,10 @@ pub struct RunnableProcess {
     // parent's responsibility to reap and interpret the exit status.
     expected_final_state: Option<ProcessFinalState>,
 
+    // Called when the process exits, to update the host's status of the application and restart it
+    // if it has a restart policy. This will be None for processes created via `fork`.
+    exit_handler: Option<ExitHandler>,
+
     // Shared memory allocation for shared state with shim.
     shim_shared_mem_block: ShMemBlock<'static, ProcessShmem>,
 
@@ -260,6 +272,9 @@ pub struct RunnableProcess {
     total_run_time: Cell<Duration>,
 
     itimer_real: RefCell<Timer>,
+    itimer_virtual: RefCell<CpuTimer>,
+    itimer_prof: RefCell<CpuTimer>,
+    posix_timers: RefCell<PosixTimerTable>,
 
     // The `RootedRc` lets us hold a reference to a thread without holding a
     // reference to the thread list. e.g. this lets us implement the `clone`
@@ -531,17 +546,8 @@ impl RunnableProcess {
                 .shim_shared_mem_block
                 .protected
                 .borrow_mut(&host_shmem.root);
-            // SAFETY: We don't try to call any of the function pointers.
-            let action = unsafe { process_shmem_protected.signal_action(signal) };
-            match unsafe { action.handler() } {
-                linux_api::signal::SignalHandler::Handler(_) => (),
-                linux_api::signal::SignalHandler::Action(_) => (),
-                linux_api::signal::SignalHandler::SigIgn => return,
-                linux_api::signal::SignalHandler::SigDfl => {
-                    if defaultaction(signal) == LinuxDefaultAction::IGN {
-                        return;
-                    }
-                }
+            if signal_is_ignored(&process_shmem_protected, signal) {
+                return;
             }
 
             if process_shmem_protected.pending_signals.has(signal) {
@@ -571,6 +577,269 @@ impl RunnableProcess {
         self.interrupt_with_signal(host, signal);
     }
 
+    /// Send the signal described in `siginfo_t` to `thread`, which must belong to this process.
+    /// `current_thread` should be set if there is one, as for [`RunnableProcess::signal`].
+    ///
+    /// The signal will be processed synchronously when returning from the current syscall if
+    /// `thread` is `current_thread`. Otherwise `thread` is woken up to handle it, unless it has
+    /// the signal blocked.
+    pub fn signal_thread(
+        &self,
+        host: &Host,
+        current_thread: Option<&Thread>,
+        thread: &Thread,
+        siginfo_t: &siginfo_t,
+    ) {
+        debug_assert_eq!(thread.process_id(), self.common.id());
+
+        let signal = match siginfo_t.signal() {
+            Ok(s) => s,
+            Err(SignalFromI32Error(0)) => return,
+            Err(SignalFromI32Error(n)) => panic!("Bad signo {n}"),
+        };
+
+        // Scope for `thread_shmem_protected`
+        {
+            let host_shmem = host.shim_shmem_lock_borrow().unwrap();
+            let process_shmem_protected = self
+                .shim_shared_mem_block
+                .protected
+                .borrow(&host_shmem.root);
+            if signal_is_ignored(&process_shmem_protected, signal) {
+                return;
+            }
+
+            let thread_shmem = thread.shmem();
+            let mut thread_shmem_protected = thread_shmem.protected.borrow_mut(&host_shmem.root);
+            if thread_shmem_protected.pending_signals.has(signal) {
+                // Signal is already pending. See `signal`.
+                return;
+            }
+            thread_shmem_protected.pending_signals.add(signal);
+            thread_shmem_protected.set_pending_standard_siginfo(signal, siginfo_t);
+
+            if current_thread.is_some_and(|x| x.id() == thread.id()) {
+                // It'll be handled synchronously when the current syscall returns (if it's
+                // unblocked).
+                return;
+            }
+
+            if thread_shmem_protected.blocked_signals.has(signal) {
+                // It'll be handled when the thread unblocks the signal.
+                return;
+            }
+        }
+
+        let Some(mut cond) = thread.syscall_condition_mut() else {
+            // The thread hasn't run yet. The signal will be delivered when it runs.
+            return;
+        };
+        cond.wakeup_for_signal(host, signal);
+    }
+
+    /// The modeled CPU time of the process that's measured by the interval timer `which`: the user
+    /// time for `ITIMER_VIRTUAL`, and the user and system time for `ITIMER_PROF`.
+    ///
+    /// Panics if `which` is `ITIMER_REAL`, which measures emulated time instead.
+    pub fn itimer_cpu_time(&self, host: &Host, which: ITimerId) -> SimulationTime {
+        let host_shmem = host.shim_shmem_lock_borrow().unwrap();
+        let protected = self
+            .shim_shared_mem_block
+            .protected
+            .borrow(&host_shmem.root);
+        match which {
+            ITimerId::ITIMER_VIRTUAL => protected.user_cpu_time,
+            ITimerId::ITIMER_PROF => protected.user_cpu_time + protected.system_cpu_time,
+            ITimerId::ITIMER_REAL => panic!("ITIMER_REAL doesn't measure CPU time"),
+        }
+    }
+
+    /// Borrow the interval timer `which`, which measures CPU time.
+    ///
+    /// Panics if `which` is `ITIMER_REAL`; use [`Process::realtime_timer_borrow_mut`] instead.
+    #[track_caller]
+    pub fn cpu_timer_borrow_mut(&self, which: ITimerId) -> RefMut<CpuTimer> {
+        match which {
+            ITimerId::ITIMER_VIRTUAL => self.itimer_virtual.borrow_mut(),
+            ITimerId::ITIMER_PROF => self.itimer_prof.borrow_mut(),
+            ITimerId::ITIMER_REAL => panic!("ITIMER_REAL doesn't measure CPU time"),
+        }
+    }
+
+    /// Signal the process for the interval timers that measure CPU time and have expired since
+    /// they were last checked. Since CPU time only advances while the process is running, this
+    /// should be called after the process has run (e.g. when handling a syscall).
+    pub fn check_cpu_timers(&self, host: &Host, current_thread: Option<&Thread>) {
+        for (which, signal) in [
+            (ITimerId::ITIMER_VIRTUAL, Signal::SIGVTALRM),
+            (ITimerId::ITIMER_PROF, Signal::SIGPROF),
+        ] {
+            let cpu_time = self.itimer_cpu_time(host, which);
+            let expiration_count = self
+                .cpu_timer_borrow_mut(which)
+                .consume_expirations(cpu_time);
+            if expiration_count == 0 {
+                continue;
+            }
+            // The siginfo_t structure only has an i32. Presumably we want to just truncate in
+            // case of overflow.
+            let siginfo_t = siginfo_t::new_for_timer(signal, 0, expiration_count as i32);
+            self.signal(host, current_thread, &siginfo_t);
+        }
+    }
+
+    /// Create a new POSIX timer (see `timer_create(2)`) that notifies the process as given by
+    /// `notify`, which is called with the new timer's ID. The timer is initially disarmed. Returns
+    /// the timer's ID, or `None` if the process has no more timer IDs available.
+    pub fn create_posix_timer(
+        &self,
+        notify: impl FnOnce(kernel_timer_t) -> PosixTimerNotify,
+    ) -> Option<kernel_timer_t> {
+        let pid = self.common.id();
+        self.posix_timers.borrow_mut().add(|timer_id| {
+            let timer = Timer::new(move |host| posix_timer_expiration(host, pid, timer_id));
+            PosixTimer::new(timer, notify(timer_id))
+        })
+    }
+
+    #[track_caller]
+    pub fn posix_timers_borrow(&self) -> impl Deref<Target = PosixTimerTable> + '_ {
+        self.posix_timers.borrow()
+    }
+
+    #[track_caller]
+    pub fn posix_timers_borrow_mut(&self) -> impl DerefMut<Target = PosixTimerTable> + '_ {
+        self.posix_timers.borrow_mut()
+    }
+
+    /// The overrun count of the POSIX timer `timer_id` (see `timer_getoverrun(2)`), or `None` if
+    /// there is no such timer.
+    pub fn posix_timer_overrun(&self, host: &Host, timer_id: kernel_timer_t) -> Option<i32> {
+        let timers = self.posix_timers.borrow();
+        let timer = timers.get(timer_id)?;
+        let pending = self.posix_timer_pending_siginfo(host, timer_id, timer.notify());
+        Some(timer.overrun(pending.is_some()))
+    }
+
+    /// Returns the pending siginfo of the most recent signal sent by the POSIX timer `timer_id`,
+    /// if it's still pending.
+    fn posix_timer_pending_siginfo(
+        &self,
+        host: &Host,
+        timer_id: kernel_timer_t,
+        notify: PosixTimerNotify,
+    ) -> Option<siginfo_t> {
+        let PosixTimerNotify::Signal { signal, thread, .. } = notify else {
+            return None;
+        };
+
+        let host_shmem = host.shim_shmem_lock_borrow().unwrap();
+        let info = match thread {
+            Some(tid) => {
+                let thread = self.thread(tid)?;
+                let thread = thread.borrow(host.root());
+                let thread_shmem = thread.shmem();
+                let thread_shmem_protected = thread_shmem.protected.borrow(&host_shmem.root);
+                let info = *thread_shmem_protected.pending_standard_siginfo(signal)?;
+                info
+            }
+            None => {
+                let process_shmem_protected = self
+                    .shim_shared_mem_block
+                    .protected
+                    .borrow(&host_shmem.root);
+                let info = *process_shmem_protected.pending_standard_siginfo(signal)?;
+                info
+            }
+        };
+
+        // SAFETY: We don't dereference any pointers.
+        match unsafe { info.details() } {
+            Some(SigInfoDetails::Timer(details)) if details.l_tid == timer_id => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Replace the siginfo of the pending `signal` of `thread`, or of the process if `thread` is
+    /// `None`.
+    fn set_pending_siginfo(
+        &self,
+        host: &Host,
+        thread: Option<ThreadId>,
+        signal: Signal,
+        info: &siginfo_t,
+    ) {
+        let host_shmem = host.shim_shmem_lock_borrow().unwrap();
+        match thread {
+            Some(tid) => {
+                let thread = self.thread(tid).unwrap();
+                let thread = thread.borrow(host.root());
+                let thread_shmem = thread.shmem();
+                let mut thread_shmem_protected =
+                    thread_shmem.protected.borrow_mut(&host_shmem.root);
+                thread_shmem_protected.set_pending_standard_siginfo(signal, info);
+            }
+            None => {
+                let mut process_shmem_protected = self
+                    .shim_shared_mem_block
+                    .protected
+                    .borrow_mut(&host_shmem.root);
+                process_shmem_protected.set_pending_standard_siginfo(signal, info);
+            }
+        }
+    }
+
+    /// Notify the process that the POSIX timer `timer_id` expired.
+    fn posix_timer_expired(&self, host: &Host, timer_id: kernel_timer_t) {
+        let mut timers = self.posix_timers.borrow_mut();
+        let Some(timer) = timers.get_mut(timer_id) else {
+            // The timer was deleted.
+            return;
+        };
+
+        let PosixTimerNotify::Signal {
+            signal,
+            sigval,
+            thread,
+        } = timer.notify()
+        else {
+            return;
+        };
+
+        let sigval = linux_sigval {
+            sival_ptr: sigval as *mut std::ffi::c_void,
+        };
+
+        // `timer_create(2)`: "Only a single signal is queued to the process for a given timer at
+        // any point in time. When a timer whose signal is still pending expires, no signal is
+        // queued, and a timer overrun occurs."
+        if self
+            .posix_timer_pending_siginfo(host, timer_id, timer.notify())
+            .is_some()
+        {
+            let overrun = timer.record_overrun();
+            let info = siginfo_t::new_for_posix_timer(signal, timer_id, overrun, sigval);
+            self.set_pending_siginfo(host, thread, signal, &info);
+            return;
+        }
+
+        timer.record_signal();
+        drop(timers);
+
+        let info = siginfo_t::new_for_posix_timer(signal, timer_id, 0, sigval);
+        match thread {
+            Some(tid) => {
+                let Some(thread) = self.thread(tid) else {
+                    debug!("Target thread {tid:?} of timer {timer_id} no longer exists");
+                    return;
+                };
+                let thread = thread.borrow(host.root());
+                self.signal_thread(host, None, &thread, &info);
+            }
+            None => self.signal(host, None, &info),
+        }
+    }
+
     /// Adds a new thread to the process and schedules it to run.
     /// Intended for use by `clone`.
     pub fn add_thread(&self, host: &Host, thread: RootedRc<RootedRefCell<Thread>>) {
@@ -645,6 +914,10 @@ impl RunnableProcess {
 
         let threads = RefCell::new(BTreeMap::from([(new_tgl_tid, new_thread_group_leader)]));
 
+        // `fork(2)`:
+        //  > The child inherits copies of the parent's set of attached shared memory segments.
+        host.sysv_ipc_borrow_mut().shm_fork(self.common.id, pid);
+
         let shim_shared_mem = ProcessShmem::new(
             &host.shim_shmem_lock_borrow().unwrap().root,
             host.shim_shmem().serialize(),
@@ -658,6 +931,7 @@ impl RunnableProcess {
         let runnable_process = RunnableProcess {
             common,
             expected_final_state: None,
+            exit_handler: None,
             shim_shared_mem_block,
             strace_logging,
             dumpable: self.dumpable.clone(),
@@ -667,6 +941,9 @@ impl RunnableProcess {
             #[cfg(feature = "perf_timers")]
             total_run_time: Cell::new(Duration::ZERO),
             itimer_real,
+            itimer_virtual: RefCell::new(CpuTimer::new()),
+            itimer_prof: RefCell::new(CpuTimer::new()),
+            posix_timers: RefCell::new(PosixTimerTable::new()),
             threads,
             unsafe_borrow_mut: RefCell::new(None),
             unsafe_borrows: RefCell::new(Vec::new()),
@@ -894,6 +1171,34 @@ fn itimer_real_expiration(host: &Host, pid: ProcessId) {
     process.signal(host, None, &siginfo_t);
 }
 
+fn posix_timer_expiration(host: &Host, pid: ProcessId, timer_id: kernel_timer_t) {
+    let Some(process) = host.process_borrow(pid) else {
+        debug!("Process {:?} no longer exists", pid);
+        return;
+    };
+    let process = process.borrow(host.root());
+    let Some(runnable) = process.as_runnable() else {
+        debug!("Process {:?} no longer running", &*process.name());
+        return;
+    };
+    runnable.posix_timer_expired(host, timer_id);
+}
+
+/// Whether `signal` is ignored by the process, in which case it's discarded rather than made
+/// pending.
+fn signal_is_ignored(process_shmem_protected: &ProcessShmemProtected, signal: Signal) -> bool {
+    // SAFETY: We don't try to call any of the function pointers.
+    let action = unsafe { process_shmem_protected.signal_action(signal) };
+    match unsafe { action.handler() } {
+        linux_api::signal::SignalHandler::Handler(_) => false,
+        linux_api::signal::SignalHandler::Action(_) => false,
+        linux_api::signal::SignalHandler::SigIgn => true,
+        linux_api::signal::SignalHandler::SigDfl => {
+            defaultaction(signal) == LinuxDefaultAction::IGN
+        }
+    }
+}
+
 impl Process {
     fn common(&self) -> Ref<Common> {
         Ref::map(self.state.borrow(), |state| {
@@ -943,6 +1248,7 @@ impl Process {
         pause_for_debugging: bool,
         strace_logging_options: Option<FmtOptions>,
         expected_final_state: ProcessFinalState,
+        exit_handler: Option<ExitHandler>,
     ) -> nix::Result<RootedRc<RootedRefCell<Process>>> {
         debug!("starting process '{:?}'", plugin_name);
 
@@ -1010,14 +1316,22 @@ impl Process {
                 libc::STDIN_FILENO.try_into().unwrap(),
                 "/dev/null".into(),
                 OFlag::O_RDONLY,
+                None,
             );
 
+            // the output files count towards the total output size even if they're not limited
+            let output_limit = Some((
+                host.params.output_max_size.unwrap_or(0),
+                host.params.output_limit_action,
+            ));
+
             let name = Self::static_output_file_name(&file_basename, "stdout");
             Self::open_stdio_file_helper(
                 &mut descriptor_table,
                 libc::STDOUT_FILENO.try_into().unwrap(),
                 name,
                 OFlag::O_WRONLY,
+                output_limit,
             );
 
             let name = Self::static_output_file_name(&file_basename, "stderr");
@@ -1026,6 +1340,7 @@ impl Process {
                 libc::STDERR_FILENO.try_into().unwrap(),
                 name,
                 OFlag::O_WRONLY,
+                output_limit,
             );
         }
 
@@ -1104,9 +1419,13 @@ impl Process {
                     state: RefCell::new(Some(ProcessState::Runnable(RunnableProcess {
                         common,
                         expected_final_state: Some(expected_final_state),
+                        exit_handler,
                         shim_shared_mem_block,
                         memory_manager: Box::new(RefCell::new(memory_manager)),
                         itimer_real,
+                        itimer_virtual: RefCell::new(CpuTimer::new()),
+                        itimer_prof: RefCell::new(CpuTimer::new()),
+                        posix_timers: RefCell::new(PosixTimerTable::new()),
                         strace_logging,
                         dumpable: Cell::new(SuidDump::SUID_DUMP_USER),
                         native_pid,
@@ -1129,6 +1448,16 @@ impl Process {
         self.common().id
     }
 
+    /// The credentials that unix sockets report for this process (`SO_PEERCRED` and
+    /// `SCM_CREDENTIALS`). Shadow doesn't emulate user and group IDs, so these are Shadow's own.
+    pub fn ucred(&self) -> libc::ucred {
+        libc::ucred {
+            pid: self.id().into(),
+            uid: nix::unistd::geteuid().as_raw(),
+            gid: nix::unistd::getegid().as_raw(),
+        }
+    }
+
     pub fn parent_id(&self) -> ProcessId {
         self.common().parent_pid.get()
     }
@@ -1317,11 +1646,14 @@ impl Process {
         }
     }
 
+    /// Open a stdio file. If `output_limit` is set, the output written to the file is limited to
+    /// the given maximum size (where 0 is unlimited), and the limit action is taken when reached.
     fn open_stdio_file_helper(
         descriptor_table: &mut DescriptorTable,
         fd: DescriptorHandle,
         path: PathBuf,
         access_mode: OFlag,
+        output_limit: Option<(u64, OutputLimitAction)>,
     ) {
         let stdfile = unsafe { cshadow::regularfile_new() };
         let cwd = nix::unistd::getcwd().unwrap();
@@ -1343,6 +1675,9 @@ impl Process {
                 linux_api::errno::Errno::try_from(-errorcode).unwrap()
             );
         }
+        if let Some((max_size, action)) = output_limit {
+            unsafe { cshadow::regularfile_setOutputLimit(stdfile, max_size, action) };
+        }
         let desc = unsafe {
             Descriptor::from_legacy_file(
                 stdfile as *mut cshadow::LegacyFile,
@@ -1421,6 +1756,57 @@ impl Process {
         })
     }
 
+    /// Deprecated wrapper for `RunnableProcess::itimer_cpu_time`
+    pub fn itimer_cpu_time(&self, host: &Host, which: ITimerId) -> SimulationTime {
+        self.as_runnable().unwrap().itimer_cpu_time(host, which)
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::cpu_timer_borrow_mut`
+    #[track_caller]
+    pub fn cpu_timer_borrow_mut(&self, which: ITimerId) -> impl DerefMut<Target = CpuTimer> + '_ {
+        std_util::nested_ref::NestedRefMut::map(self.as_runnable().unwrap(), |runnable| {
+            runnable.cpu_timer_borrow_mut(which)
+        })
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::check_cpu_timers`
+    pub fn check_cpu_timers(&self, host: &Host, current_thread: Option<&Thread>) {
+        self.as_runnable()
+            .unwrap()
+            .check_cpu_timers(host, current_thread)
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::create_posix_timer`
+    pub fn create_posix_timer(
+        &self,
+        notify: impl FnOnce(kernel_timer_t) -> PosixTimerNotify,
+    ) -> Option<kernel_timer_t> {
+        self.as_runnable().unwrap().create_posix_timer(notify)
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::posix_timers_borrow`
+    #[track_caller]
+    pub fn posix_timers_borrow(&self) -> impl Deref<Target = PosixTimerTable> + '_ {
+        std_util::nested_ref::NestedRef::map(self.as_runnable().unwrap(), |runnable| {
+            runnable.posix_timers.borrow()
+        })
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::posix_timers_borrow_mut`
+    #[track_caller]
+    pub fn posix_timers_borrow_mut(&self) -> impl DerefMut<Target = PosixTimerTable> + '_ {
+        std_util::nested_ref::NestedRefMut::map(self.as_runnable().unwrap(), |runnable| {
+            runnable.posix_timers.borrow_mut()
+        })
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::posix_timer_overrun`
+    pub fn posix_timer_overrun(&self, host: &Host, timer_id: kernel_timer_t) -> Option<i32> {
+        self.as_runnable()
+            .unwrap()
+            .posix_timer_overrun(host, timer_id)
+    }
+
     /// Deprecated wrapper for `RunnableProcess::first_live_thread_borrow`
     #[track_caller]
     pub fn first_live_thread_borrow(
@@ -1480,6 +1866,16 @@ impl Process {
             }
         }
 
+        // Release any POSIX record locks held by the process.
+        host.file_lock_table_borrow_mut()
+            .release_owner(FileLockOwner::Process(self.id()));
+
+        // Detach any System V shared memory segments and undo semaphore operations.
+        host.sysv_ipc_borrow_mut().process_exit(
+            self.id(),
+            Worker::current_time().unwrap_or(EmulatedTime::SIMULATION_START),
+        );
+
         // Intentionally hold the borrow on self.state to ensure the state
         // transition is "atomic".
         let mut opt_state = self.state.borrow_mut();
@@ -1537,7 +1933,25 @@ impl Process {
                     },
                     ExitStatus::StoppedByShadow => ProcessFinalState::Running(RunningVal::Running),
                 };
-                if expected_final_state == actual_final_state {
+                let is_expected = expected_final_state == actual_final_state;
+                // processes that shadow stopped at the end of the simulation aren't restarted
+                let restarting = !killed_by_shadow
+                    && runnable
+                        .exit_handler
+                        .as_ref()
+                        .is_some_and(|f| f(host, is_expected));
+                if restarting {
+                    if !is_expected {
+                        write!(s, "; expected end state was {expected_final_state} but was {actual_final_state}").unwrap();
+                    }
+                    write!(s, "; restarting the process").unwrap();
+                    let level = if is_expected {
+                        log::Level::Info
+                    } else {
+                        log::Level::Warn
+                    };
+                    (s, level)
+                } else if is_expected {
                     (s, log::Level::Debug)
                 } else {
                     Worker::increment_plugin_error_count();
@@ -1662,6 +2076,14 @@ impl Process {
             thread.explicit_drop_recursive(host.root(), host);
         }
 
+        // `execve(2)`: "Attached System V shared memory segments are detached"
+        host.sysv_ipc_borrow_mut()
+            .shm_detach_all(self.id(), Worker::current_time().unwrap());
+
+        // `timer_create(2)`: "The timers created by timer_create() ... are disarmed and deleted
+        // during an execve(2)."
+        runnable.posix_timers.borrow_mut().clear();
+
         // Recreate the `MemoryManager`
         {
             // We can't safely replace the memory manager if there are outstanding
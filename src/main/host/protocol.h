@@ -8,7 +8,7 @@
 #define SHD_PROTOCOL_H_
 
 typedef enum _ProtocolType ProtocolType;
-enum _ProtocolType { PNONE, PTCP, PUDP, PMOCK };
+enum _ProtocolType { PNONE, PTCP, PUDP, PICMP, PMOCK };
 
 enum ProtocolUDPFlags {
     PUDP_NONE = 0,
@@ -22,6 +22,10 @@ enum ProtocolTCPFlags {
     PTCP_SACK = 1 << 4,
     PTCP_FIN =  1 << 5,
     PTCP_DUPACK =  1 << 6,
+    PTCP_URG =  1 << 7,
+    PTCP_SACK_PERMITTED = 1 << 8,
+    PTCP_ECE = 1 << 9,
+    PTCP_CWR = 1 << 10,
 };
 
 #endif /* SHD_PROTOCOL_H_ */
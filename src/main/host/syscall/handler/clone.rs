@@ -160,7 +160,8 @@ impl SyscallHandler {
         }
 
         if flags.contains(CloneFlags::CLONE_SYSVSEM) {
-            // Currently a no-op since we don't support sysv semaphores.
+            // System V semaphore adjustments (`SEM_UNDO`) are tracked per process, so they're
+            // shared between threads but never between processes.
             handled_flags.insert(CloneFlags::CLONE_SYSVSEM);
         }
 
@@ -190,7 +190,7 @@ impl SyscallHandler {
     ) -> Result<std::ffi::c_int, SyscallError> {
         // Note that timeout is given in milliseconds.
         let timeout = timeout_arg_to_maybe_simtime(timeout)?;
-        Self::epoll_wait_helper(ctx, epfd, events_ptr, max_events, timeout, None)
+        Self::epoll_wait_helper(ctx, epfd, events_ptr, max_events, timeout)
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int, /* epfd */ std::ffi::c_int,
@@ -204,19 +204,16 @@ impl SyscallHandler {
         max_events: std::ffi::c_int,
         timeout: std::ffi::c_int,
         sigmask_ptr: ForeignPtr<linux_api::signal::sigset_t>,
-        _sigsetsize: linux_api::posix_types::kernel_size_t,
+        sigsetsize: linux_api::posix_types::kernel_size_t,
     ) -> Result<std::ffi::c_int, SyscallError> {
         // epoll_wait(2): "The sigmask argument may be specified as NULL, in which case
         // epoll_pwait() is equivalent to epoll_wait()"
-        let sigmask = if sigmask_ptr.is_null() {
-            None
-        } else {
-            Some(ctx.objs.process.memory_borrow().read(sigmask_ptr)?)
-        };
+        let sigsetsize = usize::try_from(sigsetsize).unwrap();
+        Self::set_temporary_sigmask(ctx, sigmask_ptr, sigsetsize)?;
 
         // Note that timeout is given in milliseconds.
         let timeout = timeout_arg_to_maybe_simtime(timeout)?;
-        Self::epoll_wait_helper(ctx, epfd, events_ptr, max_events, timeout, sigmask)
+        Self::epoll_wait_helper(ctx, epfd, events_ptr, max_events, timeout)
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int, /* epfd */ std::ffi::c_int,
@@ -230,32 +227,23 @@ impl SyscallHandler {
         max_events: std::ffi::c_int,
         timeout_ptr: ForeignPtr<linux_api::time::timespec>,
         sigmask_ptr: ForeignPtr<linux_api::signal::sigset_t>,
-        _sigsetsize: linux_api::posix_types::kernel_size_t,
+        sigsetsize: linux_api::posix_types::kernel_size_t,
     ) -> Result<std::ffi::c_int, SyscallError> {
-        let (sigmask, timeout) = {
-            let mem = ctx.objs.process.memory_borrow();
-
-            // epoll_wait(2): "The sigmask argument may be specified as NULL, in which case
-            // epoll_pwait() is equivalent to epoll_wait()"
-            let sigmask = if sigmask_ptr.is_null() {
-                None
-            } else {
-                Some(mem.read(sigmask_ptr)?)
-            };
-
-            // epoll_wait(2): "If timeout is NULL, then epoll_pwait2() can block indefinitely"
-            let timeout = if timeout_ptr.is_null() {
-                None
-            } else {
-                let tspec = mem.read(timeout_ptr)?;
-                let sim_time = SimulationTime::try_from(tspec).map_err(|_| Errno::EINVAL)?;
-                Some(sim_time)
-            };
-
-            (sigmask, timeout)
+        // epoll_wait(2): "If timeout is NULL, then epoll_pwait2() can block indefinitely"
+        let timeout = if timeout_ptr.is_null() {
+            None
+        } else {
+            let tspec = ctx.objs.process.memory_borrow().read(timeout_ptr)?;
+            let sim_time = SimulationTime::try_from(tspec).map_err(|_| Errno::EINVAL)?;
+            Some(sim_time)
         };
 
-        Self::epoll_wait_helper(ctx, epfd, events_ptr, max_events, timeout, sigmask)
+        // epoll_wait(2): "The sigmask argument may be specified as NULL, in which case
+        // epoll_pwait() is equivalent to epoll_wait()"
+        let sigsetsize = usize::try_from(sigsetsize).unwrap();
+        Self::set_temporary_sigmask(ctx, sigmask_ptr, sigsetsize)?;
+
+        Self::epoll_wait_helper(ctx, epfd, events_ptr, max_events, timeout)
     }
 
     fn epoll_wait_helper(
@@ -264,7 +252,6 @@ impl SyscallHandler {
         events_ptr: ForeignPtr<linux_api::epoll::epoll_event>,
         max_events: std::ffi::c_int,
         timeout: Option<SimulationTime>,
-        sigmask: Option<linux_api::signal::sigset_t>,
     ) -> Result<std::ffi::c_int, SyscallError> {
         // Linux enforces a range for max_events.
         let max_events = {
@@ -281,15 +268,6 @@ impl SyscallHandler {
             u32::try_from(max_events).unwrap()
         };
 
-        // TODO: support the signal mask
-        if sigmask.is_some() {
-            warn_once_then_trace!(
-                "Epoll pwait called with non-null sigmask, \
-                which is not yet supported by shadow; returning EINVAL"
-            );
-            return Err(Errno::EINVAL.into());
-        }
-
         // Get the descriptor, or return early if it doesn't exist.
         let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
         let epoll = {
@@ -1,13 +1,16 @@
 use linux_api::errno::Errno;
-use linux_api::fcntl::{DescriptorFlags, FcntlCommand, OFlag};
+use linux_api::fcntl::{flock, DescriptorFlags, FcntlCommand, FcntlLockType, OFlag};
 use log::debug;
-use shadow_shim_helper_rs::syscall_types::SysCallReg;
+use shadow_shim_helper_rs::syscall_types::{ForeignPtr, SysCallReg};
 use syscall_logger::log_syscall;
 
 use crate::cshadow;
+use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::descriptor::socket::Socket;
 use crate::host::descriptor::{CompatFile, File, FileStatus};
+use crate::host::file_lock_table::{FileLockKey, FileLockKind, FileLockOwner, FileLockRange};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
-use crate::host::syscall::types::SyscallResult;
+use crate::host::syscall::types::{SyscallError, SyscallResult};
 
 impl SyscallHandler {
     #[log_syscall(/* rv */ std::ffi::c_int, /* fd */ std::ffi::c_int, /* cmd */ std::ffi::c_int)]
@@ -36,23 +39,32 @@ impl SyscallHandler {
         Ok(match cmd {
             FcntlCommand::F_SETLK
             | FcntlCommand::F_SETLKW
-            | FcntlCommand::F_OFD_SETLKW
             | FcntlCommand::F_GETLK
+            | FcntlCommand::F_OFD_SETLK
+            | FcntlCommand::F_OFD_SETLKW
             | FcntlCommand::F_OFD_GETLK => {
-                match desc.file() {
+                let file = match desc.file() {
                     CompatFile::New(_) => {
                         warn_once_then_debug!("fcntl({cmd:?}) unimplemented for {:?}", desc.file());
                         return Err(Errno::ENOSYS.into());
                     }
-                    CompatFile::Legacy(_) => {
-                        warn_once_then_debug!(
-                            "Using fcntl({cmd:?}) implementation that assumes no lock contention. \
-                            See https://github.com/shadow/shadow/issues/2258"
-                        );
-                        drop(desc_table);
-                        return legacy_syscall_fn(ctx);
-                    }
+                    CompatFile::Legacy(file) => file.ptr(),
                 };
+
+                if unsafe { cshadow::legacyfile_getType(file) } != cshadow::_LegacyFileType_DT_FILE
+                {
+                    warn_once_then_debug!(
+                        "Using fcntl({cmd:?}) implementation that assumes no lock contention. \
+                        See https://github.com/shadow/shadow/issues/2258"
+                    );
+                    drop(desc_table);
+                    return legacy_syscall_fn(ctx);
+                }
+
+                // the descriptor holds a reference to the file, and we don't modify the descriptor
+                // table below
+                drop(desc_table);
+                return Self::fcntl_lock(ctx, cmd, file as *mut cshadow::RegularFile, arg);
             }
             FcntlCommand::F_GETFL => {
                 let file = match desc.file() {
@@ -172,10 +184,197 @@ impl SyscallHandler {
                     return Err(Errno::EINVAL.into());
                 }
             }
+            FcntlCommand::F_GETOWN | FcntlCommand::F_SETOWN => {
+                let file = match desc.file() {
+                    CompatFile::New(d) => d,
+                    // if it's a legacy file, use the C syscall handler instead
+                    CompatFile::Legacy(_) => {
+                        drop(desc_table);
+                        return legacy_syscall_fn(ctx);
+                    }
+                };
+
+                // only tcp sockets use the owner, to send `SIGURG` when urgent data arrives
+                let File::Socket(Socket::Inet(InetSocket::LegacyTcp(tcp))) = file.inner_file()
+                else {
+                    warn_once_then_debug!("fcntl({cmd:?}) unimplemented for {:?}", desc.file());
+                    return Err(Errno::EINVAL.into());
+                };
+
+                if cmd == FcntlCommand::F_GETOWN {
+                    SysCallReg::from(tcp.borrow().owner())
+                } else {
+                    // a positive value is a process, and a negative value is a process group
+                    let owner = arg as libc::pid_t;
+                    if owner != 0 && !ctx.objs.host.file_owner_exists(owner) {
+                        return Err(Errno::ESRCH.into());
+                    }
+                    tcp.borrow_mut().set_owner(owner);
+                    SysCallReg::from(0)
+                }
+            }
             cmd => {
                 warn_once_then_debug!("Unhandled fcntl command: {cmd:?}");
                 return Err(Errno::EINVAL.into());
             }
         })
     }
+
+    /// Implements the record lock commands (`F_SETLK`, `F_OFD_GETLK`, etc) for regular files.
+    fn fcntl_lock(
+        ctx: &mut SyscallContext,
+        cmd: FcntlCommand,
+        file: *mut cshadow::RegularFile,
+        arg: std::ffi::c_ulong,
+    ) -> SyscallResult {
+        let lock_ptr = ForeignPtr::<()>::from(arg).cast::<flock>();
+        let mut lock = ctx.objs.process.memory_borrow().read(lock_ptr)?;
+
+        let is_ofd = matches!(
+            cmd,
+            FcntlCommand::F_OFD_SETLK | FcntlCommand::F_OFD_SETLKW | FcntlCommand::F_OFD_GETLK
+        );
+
+        // fcntl(2): "the l_pid field of the flock structure must be set to zero" for OFD locks
+        if is_ofd && lock.l_pid != 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let Ok(lock_type) = FcntlLockType::try_from(lock.l_type) else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let native_fd = unsafe { cshadow::regularfile_getOSBackedFD(file) };
+        if native_fd < 0 {
+            return Err(Errno::EBADF.into());
+        }
+
+        let stat =
+            nix::sys::stat::fstat(native_fd).map_err(|e| Errno::try_from(e as i32).unwrap())?;
+        let key = FileLockKey {
+            dev: stat.st_dev,
+            ino: stat.st_ino,
+        };
+        let range = Self::fcntl_lock_range(native_fd, &stat, &lock)?;
+
+        let owner = if is_ofd {
+            FileLockOwner::OpenFile(file as usize)
+        } else {
+            FileLockOwner::Process(ctx.objs.process.id())
+        };
+
+        let kind = match lock_type {
+            FcntlLockType::F_RDLCK => Some(FileLockKind::Shared),
+            FcntlLockType::F_WRLCK => Some(FileLockKind::Exclusive),
+            FcntlLockType::F_UNLCK => None,
+        };
+
+        let mut lock_table = ctx.objs.host.file_lock_table_borrow_mut();
+
+        if matches!(cmd, FcntlCommand::F_GETLK | FcntlCommand::F_OFD_GETLK) {
+            // F_UNLCK isn't a valid type to test for
+            let Some(kind) = kind else {
+                return Err(Errno::EINVAL.into());
+            };
+
+            match lock_table.test_record(key, owner, kind, range) {
+                Some(conflict) => {
+                    lock.l_type = match conflict.kind {
+                        FileLockKind::Shared => FcntlLockType::F_RDLCK,
+                        FileLockKind::Exclusive => FcntlLockType::F_WRLCK,
+                    }
+                    .into();
+                    lock.l_whence = libc::SEEK_SET as i16;
+                    lock.l_start = conflict.range.start.try_into().unwrap();
+                    lock.l_len = if conflict.range.to_eof() {
+                        0
+                    } else {
+                        (conflict.range.end - conflict.range.start)
+                            .try_into()
+                            .unwrap()
+                    };
+                    lock.l_pid = match conflict.owner {
+                        FileLockOwner::Process(pid) => pid.into(),
+                        // fcntl(2): "If the conflicting lock is an open file description lock,
+                        // then l_pid is set to -1"
+                        FileLockOwner::OpenFile(_) => -1,
+                    };
+                }
+                None => lock.l_type = FcntlLockType::F_UNLCK.into(),
+            }
+
+            drop(lock_table);
+            ctx.objs
+                .process
+                .memory_borrow_mut()
+                .write(lock_ptr, &lock)?;
+            return Ok(0.into());
+        }
+
+        let Some(kind) = kind else {
+            lock_table.unlock_record(key, owner, range);
+            return Ok(0.into());
+        };
+
+        // the file must be open for reading to take a read lock, and for writing to take a write
+        // lock
+        let access = nix::fcntl::fcntl(native_fd, nix::fcntl::FcntlArg::F_GETFL)
+            .map_err(|e| Errno::try_from(e as i32).unwrap())?
+            & libc::O_ACCMODE;
+        let allowed = match kind {
+            FileLockKind::Shared => access == libc::O_RDONLY || access == libc::O_RDWR,
+            FileLockKind::Exclusive => access == libc::O_WRONLY || access == libc::O_RDWR,
+        };
+        if !allowed {
+            return Err(Errno::EBADF.into());
+        }
+
+        match lock_table.lock_record(key, owner, kind, range) {
+            Ok(()) => Ok(0.into()),
+            Err(_conflict) => {
+                if matches!(cmd, FcntlCommand::F_SETLKW | FcntlCommand::F_OFD_SETLKW) {
+                    // wait for a lock on the file to be released, then try again
+                    let futex = lock_table.wait_queue(key);
+                    Err(SyscallError::new_blocked_on_futex(
+                        futex, /* restartable= */ true,
+                    ))
+                } else {
+                    Err(Errno::EAGAIN.into())
+                }
+            }
+        }
+    }
+
+    /// Converts the `l_whence`, `l_start`, and `l_len` fields of `lock` to an absolute range.
+    fn fcntl_lock_range(
+        native_fd: std::ffi::c_int,
+        stat: &libc::stat,
+        lock: &flock,
+    ) -> Result<FileLockRange, Errno> {
+        let base: i64 = match i32::from(lock.l_whence) {
+            libc::SEEK_SET => 0,
+            libc::SEEK_CUR => nix::unistd::lseek(native_fd, 0, nix::unistd::Whence::SeekCur)
+                .map_err(|e| Errno::try_from(e as i32).unwrap())?,
+            libc::SEEK_END => stat.st_size,
+            _ => return Err(Errno::EINVAL),
+        };
+
+        let start = base.checked_add(lock.l_start).ok_or(Errno::EOVERFLOW)?;
+
+        // fcntl(2): "If l_len is negative, the interval described by lock covers bytes
+        // l_start+l_len up to and including l_start-1"
+        let (start, end) = match lock.l_len {
+            0 => (start, None),
+            len if len > 0 => (start, Some(start.checked_add(len).ok_or(Errno::EOVERFLOW)?)),
+            len => (start.checked_add(len).ok_or(Errno::EINVAL)?, Some(start)),
+        };
+
+        let start = u64::try_from(start).or(Err(Errno::EINVAL))?;
+        let end = match end {
+            Some(end) => u64::try_from(end).or(Err(Errno::EINVAL))?,
+            None => u64::MAX,
+        };
+
+        Ok(FileLockRange::new(start, end))
+    }
 }
@@ -369,6 +369,60 @@ SyscallReturn syscallhandler_readahead(SyscallHandler* sys, const SysCallArgs* a
     return syscallreturn_makeDoneI64(regularfile_readahead(file_desc, offset, count));
 }
 
+SyscallReturn syscallhandler_copy_file_range(SyscallHandler* sys, const SysCallArgs* args) {
+    int fdIn = args->args[0].as_i64;
+    UntypedForeignPtr offsetInPtr = args->args[1].as_ptr; // off64_t*
+    int fdOut = args->args[2].as_i64;
+    UntypedForeignPtr offsetOutPtr = args->args[3].as_ptr; // off64_t*
+    size_t len = args->args[4].as_u64;
+    unsigned int flags = args->args[5].as_u64;
+
+    /* Get and validate the file descriptors. */
+    RegularFile* fileIn = NULL;
+    int errcode = _syscallhandler_validateFileHelper(sys, fdIn, &fileIn);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    RegularFile* fileOut = NULL;
+    errcode = _syscallhandler_validateFileHelper(sys, fdOut, &fileOut);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    /* A NULL offset means that the file offset is used instead. */
+    off64_t offsetIn = 0;
+    if (offsetInPtr.val && process_readPtr(rustsyscallhandler_getProcess(sys), &offsetIn,
+                                           offsetInPtr, sizeof(offsetIn))) {
+        return syscallreturn_makeDoneErrno(EFAULT);
+    }
+
+    off64_t offsetOut = 0;
+    if (offsetOutPtr.val && process_readPtr(rustsyscallhandler_getProcess(sys), &offsetOut,
+                                            offsetOutPtr, sizeof(offsetOut))) {
+        return syscallreturn_makeDoneErrno(EFAULT);
+    }
+
+    ssize_t result = regularfile_copyFileRange(
+        fileIn, rustsyscallhandler_getHost(sys), offsetInPtr.val ? &offsetIn : NULL, fileOut,
+        offsetOutPtr.val ? &offsetOut : NULL, len, flags);
+    if (result < 0) {
+        return syscallreturn_makeDoneErrno(-result);
+    }
+
+    /* Write back the updated offsets. */
+    if (offsetInPtr.val && process_writePtr(rustsyscallhandler_getProcess(sys), offsetInPtr,
+                                            &offsetIn, sizeof(offsetIn))) {
+        return syscallreturn_makeDoneErrno(EFAULT);
+    }
+    if (offsetOutPtr.val && process_writePtr(rustsyscallhandler_getProcess(sys), offsetOutPtr,
+                                             &offsetOut, sizeof(offsetOut))) {
+        return syscallreturn_makeDoneErrno(EFAULT);
+    }
+
+    return syscallreturn_makeDoneI64(result);
+}
+
 SyscallReturn syscallhandler_lseek(SyscallHandler* sys, const SysCallArgs* args) {
     int fd = args->args[0].as_i64;
     off_t offset = args->args[1].as_u64;
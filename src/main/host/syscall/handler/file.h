@@ -8,6 +8,7 @@
 
 #include "main/host/syscall/protected.h"
 
+SYSCALL_HANDLER(copy_file_range);
 SYSCALL_HANDLER(creat);
 SYSCALL_HANDLER(fadvise64);
 SYSCALL_HANDLER(fallocate);
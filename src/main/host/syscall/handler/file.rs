@@ -1,24 +1,112 @@
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
+use linux_api::errno::Errno;
+use linux_api::fcntl::{DescriptorFlags, FlockOperation, OFlag};
 use linux_api::posix_types::kernel_mode_t;
-use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+use shadow_shim_helper_rs::syscall_types::{ForeignArrayPtr, ForeignPtr};
 use syscall_logger::log_syscall;
 
 use crate::cshadow;
+use crate::host::descriptor::tun::{TunFile, TUN_DEVICE_PATH};
+use crate::host::descriptor::{CompatFile, Descriptor, File, FileStatus, OpenFile};
+use crate::host::file_lock_table::{FileLockKey, FileLockKind};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::type_formatting::SyscallStringArg;
-use crate::host::syscall::types::SyscallResult;
+use crate::host::syscall::types::{SyscallError, SyscallResult};
 
 impl SyscallHandler {
     #[log_syscall(/* rv */ std::ffi::c_int, /* pathname */ SyscallStringArg,
                   /* flags */ linux_api::fcntl::OFlag, /* mode */ nix::sys::stat::Mode)]
     pub fn open(
         ctx: &mut SyscallContext,
-        _path: ForeignPtr<()>,
-        _flags: std::ffi::c_int,
+        path: ForeignPtr<()>,
+        flags: std::ffi::c_int,
         _mode: kernel_mode_t,
     ) -> SyscallResult {
+        if let Some(fd) = Self::open_tun_device(ctx, path.cast(), flags)? {
+            return Ok(fd.into());
+        }
+
         Self::legacy_syscall(cshadow::syscallhandler_open, ctx)
     }
 
+    /// Open the TUN device if `path` is the TUN device's path, which shadow emulates rather than
+    /// opening the real device. Returns `None` for any other path.
+    pub(super) fn open_tun_device(
+        ctx: &mut SyscallContext,
+        path: ForeignPtr<u8>,
+        flags: std::ffi::c_int,
+    ) -> Result<Option<std::ffi::c_int>, SyscallError> {
+        let mut path_buf = [0u8; TUN_DEVICE_PATH.len() + 1];
+        let path_buf_capacity = path_buf.len();
+
+        // paths that are too long or can't be read aren't the TUN device, and the legacy handler
+        // will return any errors
+        let Ok(path) = ctx
+            .objs
+            .process
+            .memory_borrow()
+            .copy_str_from_ptr(&mut path_buf, ForeignArrayPtr::new(path, path_buf_capacity))
+        else {
+            return Ok(None);
+        };
+
+        if path.to_bytes() != TUN_DEVICE_PATH.as_bytes() {
+            return Ok(None);
+        }
+
+        // like Linux, ignore any unknown flags
+        let flags = OFlag::from_bits_truncate(flags);
+
+        // the only status flag that the TUN device cares about
+        let (status, _) = FileStatus::from_o_flags(flags);
+        let status = status & FileStatus::NONBLOCK;
+
+        let file = Arc::new(AtomicRefCell::new(TunFile::new(status)));
+
+        let mut desc = Descriptor::new(CompatFile::New(OpenFile::new(File::Tun(file))));
+        if flags.contains(OFlag::O_CLOEXEC) {
+            desc.set_flags(DescriptorFlags::FD_CLOEXEC);
+        }
+
+        let fd = ctx
+            .objs
+            .thread
+            .descriptor_table_borrow_mut(ctx.objs.host)
+            .register_descriptor(desc)
+            .or(Err(Errno::EMFILE))?;
+
+        log::trace!("Opened the TUN device as fd {fd}");
+
+        Ok(Some(fd.val().try_into().unwrap()))
+    }
+
+    #[log_syscall(/* rv */ isize, /* fd_in */ std::ffi::c_int, /* off_in */ *const i64,
+                  /* fd_out */ std::ffi::c_int, /* off_out */ *const i64, /* len */ usize,
+                  /* flags */ std::ffi::c_uint)]
+    pub fn copy_file_range(
+        ctx: &mut SyscallContext,
+        fd_in: std::ffi::c_int,
+        _off_in: ForeignPtr<i64>,
+        fd_out: std::ffi::c_int,
+        _off_out: ForeignPtr<i64>,
+        _len: usize,
+        _flags: std::ffi::c_uint,
+    ) -> SyscallResult {
+        let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+
+        for fd in [fd_in, fd_out] {
+            // files that aren't regular files (pipes, sockets, etc) aren't supported by the syscall
+            if let CompatFile::New(_) = Self::get_descriptor(&desc_table, fd)?.file() {
+                return Err(Errno::EINVAL.into());
+            }
+        }
+
+        drop(desc_table);
+        Self::legacy_syscall(cshadow::syscallhandler_copy_file_range, ctx)
+    }
+
     #[log_syscall(/* rv */ std::ffi::c_int)]
     pub fn creat(ctx: &mut SyscallContext) -> SyscallResult {
         Self::legacy_syscall(cshadow::syscallhandler_creat, ctx)
@@ -59,9 +147,62 @@ impl SyscallHandler {
         Self::legacy_syscall(cshadow::syscallhandler_flistxattr, ctx)
     }
 
-    #[log_syscall(/* rv */ std::ffi::c_int)]
-    pub fn flock(ctx: &mut SyscallContext) -> SyscallResult {
-        Self::legacy_syscall(cshadow::syscallhandler_flock, ctx)
+    #[log_syscall(/* rv */ std::ffi::c_int, /* fd */ std::ffi::c_int,
+                  /* operation */ linux_api::fcntl::FlockOperation)]
+    pub fn flock(
+        ctx: &mut SyscallContext,
+        fd: std::ffi::c_int,
+        operation: std::ffi::c_int,
+    ) -> SyscallResult {
+        let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+        let desc = Self::get_descriptor(&desc_table, fd)?;
+
+        let file = match desc.file() {
+            CompatFile::Legacy(file)
+                if unsafe { cshadow::legacyfile_getType(file.ptr()) }
+                    == cshadow::_LegacyFileType_DT_FILE =>
+            {
+                file.ptr()
+            }
+            _ => {
+                drop(desc_table);
+                return Self::legacy_syscall(cshadow::syscallhandler_flock, ctx);
+            }
+        };
+        drop(desc_table);
+
+        let Some(operation) = FlockOperation::from_bits(operation) else {
+            return Err(Errno::EINVAL.into());
+        };
+        let non_blocking = operation.contains(FlockOperation::LOCK_NB);
+
+        let kind = match operation - FlockOperation::LOCK_NB {
+            FlockOperation::LOCK_SH => Some(FileLockKind::Shared),
+            FlockOperation::LOCK_EX => Some(FileLockKind::Exclusive),
+            FlockOperation::LOCK_UN => None,
+            _ => return Err(Errno::EINVAL.into()),
+        };
+
+        let key = FileLockKey::for_legacy_file(file).ok_or(Errno::EBADF)?;
+
+        let mut lock_table = ctx.objs.host.file_lock_table_borrow_mut();
+
+        let Some(kind) = kind else {
+            lock_table.unlock_flock(key, file as usize);
+            return Ok(0.into());
+        };
+
+        match lock_table.lock_flock(key, file as usize, kind) {
+            Ok(()) => Ok(0.into()),
+            Err(()) if non_blocking => Err(Errno::EWOULDBLOCK.into()),
+            Err(()) => {
+                // wait for a lock on the file to be released, then try again
+                let futex = lock_table.wait_queue(key);
+                Err(SyscallError::new_blocked_on_futex(
+                    futex, /* restartable= */ true,
+                ))
+            }
+        }
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int)]
@@ -7,6 +7,9 @@
 
 #include <errno.h>
 #include <fcntl.h>
+#include <inttypes.h>
+#include <stdint.h>
+#include <string.h>
 #include <sys/syscall.h>
 #include <sys/stat.h>
 #include <sys/time.h>
@@ -20,6 +23,38 @@
 #include "main/host/process.h"
 #include "main/host/syscall/protected.h"
 
+/* The same layout as `struct open_how` from linux/openat2.h, which isn't available on all of
+ * the systems that we support. */
+typedef struct _OpenHow {
+    uint64_t flags;
+    uint64_t mode;
+    uint64_t resolve;
+} OpenHow;
+
+/* The kernel won't accept a larger struct. */
+#define OPEN_HOW_MAX_SIZE 4096
+
+#ifndef RESOLVE_NO_XDEV
+#define RESOLVE_NO_XDEV 0x01
+#define RESOLVE_NO_MAGICLINKS 0x02
+#define RESOLVE_NO_SYMLINKS 0x04
+#define RESOLVE_BENEATH 0x08
+#define RESOLVE_IN_ROOT 0x10
+#define RESOLVE_CACHED 0x20
+#endif
+
+#define OPENAT2_VALID_RESOLVE                                                                      \
+    (RESOLVE_NO_XDEV | RESOLVE_NO_MAGICLINKS | RESOLVE_NO_SYMLINKS | RESOLVE_BENEATH |             \
+     RESOLVE_IN_ROOT | RESOLVE_CACHED)
+
+/* The kernel's value of O_LARGEFILE, which libc defines as 0 on 64-bit platforms. */
+#define OPENAT2_O_LARGEFILE 0100000
+
+#define OPENAT2_VALID_FLAGS                                                                        \
+    (O_ACCMODE | O_CREAT | O_EXCL | O_NOCTTY | O_TRUNC | O_APPEND | O_NONBLOCK | O_DSYNC |        \
+     O_ASYNC | O_DIRECT | OPENAT2_O_LARGEFILE | O_DIRECTORY | O_NOFOLLOW | O_NOATIME | O_CLOEXEC | \
+     O_PATH | O_TMPFILE | O_SYNC)
+
 ///////////////////////////////////////////////////////////
 // Helpers
 ///////////////////////////////////////////////////////////
@@ -100,16 +135,9 @@ static SyscallReturn _syscallhandler_renameatHelper(SyscallHandler* sys, int old
         regularfile_renameat2(olddir_desc, oldpath, newdir_desc, newpath, flags, plugin_cwd));
 }
 
-///////////////////////////////////////////////////////////
-// System Calls
-///////////////////////////////////////////////////////////
-
-SyscallReturn syscallhandler_openat(SyscallHandler* sys, const SysCallArgs* args) {
-    int dirfd = args->args[0].as_i64;
-    UntypedForeignPtr pathnamePtr = args->args[1].as_ptr; // const char*
-    int flags = args->args[2].as_i64;
-    mode_t mode = args->args[3].as_u64;
-
+static SyscallReturn _syscallhandler_openatHelper(SyscallHandler* sys, int dirfd,
+                                                  UntypedForeignPtr pathnamePtr, int flags,
+                                                  mode_t mode, uint64_t resolve) {
     trace("Trying to openat file with path name at plugin addr %p",
           (void*)pathnamePtr.val);
 
@@ -125,8 +153,8 @@ SyscallReturn syscallhandler_openat(SyscallHandler* sys, const SysCallArgs* args
 
     /* Create and open the file. */
     RegularFile* file_desc = regularfile_new();
-    errcode = regularfile_openat(file_desc, dir_desc, pathname, flags & ~O_CLOEXEC, mode,
-                                 process_getWorkingDir(rustsyscallhandler_getProcess(sys)));
+    errcode = regularfile_openat2(file_desc, dir_desc, pathname, flags & ~O_CLOEXEC, mode, resolve,
+                                  process_getWorkingDir(rustsyscallhandler_getProcess(sys)));
 
     if (errcode < 0) {
         /* This will unref/free the RegularFile. */
@@ -141,6 +169,75 @@ SyscallReturn syscallhandler_openat(SyscallHandler* sys, const SysCallArgs* args
     return syscallreturn_makeDoneI64(handle);
 }
 
+///////////////////////////////////////////////////////////
+// System Calls
+///////////////////////////////////////////////////////////
+
+SyscallReturn syscallhandler_openat(SyscallHandler* sys, const SysCallArgs* args) {
+    int dirfd = args->args[0].as_i64;
+    UntypedForeignPtr pathnamePtr = args->args[1].as_ptr; // const char*
+    int flags = args->args[2].as_i64;
+    mode_t mode = args->args[3].as_u64;
+
+    return _syscallhandler_openatHelper(sys, dirfd, pathnamePtr, flags, mode, 0);
+}
+
+SyscallReturn syscallhandler_openat2(SyscallHandler* sys, const SysCallArgs* args) {
+    int dirfd = args->args[0].as_i64;
+    UntypedForeignPtr pathnamePtr = args->args[1].as_ptr; // const char*
+    UntypedForeignPtr howPtr = args->args[2].as_ptr;      // struct open_how*
+    size_t size = args->args[3].as_u64;
+
+    /* The struct may grow in future kernel versions, but any fields that we don't know about must
+     * be zero. */
+    if (size < sizeof(OpenHow)) {
+        return syscallreturn_makeDoneErrno(EINVAL);
+    } else if (size > OPEN_HOW_MAX_SIZE) {
+        return syscallreturn_makeDoneErrno(E2BIG);
+    }
+
+    const char* howBuf = process_getReadablePtr(rustsyscallhandler_getProcess(sys), howPtr, size);
+    if (!howBuf) {
+        return syscallreturn_makeDoneErrno(EFAULT);
+    }
+
+    for (size_t i = sizeof(OpenHow); i < size; i++) {
+        if (howBuf[i] != 0) {
+            return syscallreturn_makeDoneErrno(E2BIG);
+        }
+    }
+
+    OpenHow how;
+    memcpy(&how, howBuf, sizeof(how));
+
+    trace("openat2 with flags=%#" PRIx64 " mode=%#" PRIx64 " resolve=%#" PRIx64, how.flags,
+          how.mode, how.resolve);
+
+    /* Unlike openat, unknown flags and modes are rejected. */
+    if (how.flags & ~(uint64_t)OPENAT2_VALID_FLAGS) {
+        return syscallreturn_makeDoneErrno(EINVAL);
+    }
+
+    if (how.resolve & ~(uint64_t)OPENAT2_VALID_RESOLVE) {
+        return syscallreturn_makeDoneErrno(EINVAL);
+    }
+
+    if ((how.resolve & RESOLVE_BENEATH) && (how.resolve & RESOLVE_IN_ROOT)) {
+        return syscallreturn_makeDoneErrno(EINVAL);
+    }
+
+    if ((how.flags & O_CREAT) || (how.flags & O_TMPFILE) == O_TMPFILE) {
+        if (how.mode & ~(uint64_t)07777) {
+            return syscallreturn_makeDoneErrno(EINVAL);
+        }
+    } else if (how.mode != 0) {
+        return syscallreturn_makeDoneErrno(EINVAL);
+    }
+
+    return _syscallhandler_openatHelper(
+        sys, dirfd, pathnamePtr, (int)how.flags, (mode_t)how.mode, how.resolve);
+}
+
 SyscallReturn syscallhandler_newfstatat(SyscallHandler* sys, const SysCallArgs* args) {
     int dirfd = args->args[0].as_i64;
     UntypedForeignPtr pathnamePtr = args->args[1].as_ptr; // const char*
@@ -17,6 +17,7 @@ SYSCALL_HANDLER(mkdirat);
 SYSCALL_HANDLER(mknodat);
 SYSCALL_HANDLER(newfstatat);
 SYSCALL_HANDLER(openat);
+SYSCALL_HANDLER(openat2);
 SYSCALL_HANDLER(readlinkat);
 SYSCALL_HANDLER(renameat);
 SYSCALL_HANDLER(renameat2);
@@ -1,8 +1,11 @@
+use linux_api::errno::Errno;
 use linux_api::posix_types::kernel_mode_t;
-use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+use linux_api::stat::{StatxFlags, StatxMask};
+use shadow_shim_helper_rs::syscall_types::{ForeignArrayPtr, ForeignPtr};
 use syscall_logger::log_syscall;
 
 use crate::cshadow;
+use crate::host::descriptor::{CompatFile, File};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::type_formatting::SyscallStringArg;
 use crate::host::syscall::types::SyscallResult;
@@ -13,13 +16,30 @@ impl SyscallHandler {
     pub fn openat(
         ctx: &mut SyscallContext,
         _dir_fd: std::ffi::c_int,
-        _path: ForeignPtr<()>,
-        _flags: std::ffi::c_int,
+        path: ForeignPtr<()>,
+        flags: std::ffi::c_int,
         _mode: kernel_mode_t,
     ) -> SyscallResult {
+        // the TUN device's path is absolute, so the directory doesn't matter
+        if let Some(fd) = Self::open_tun_device(ctx, path.cast(), flags)? {
+            return Ok(fd.into());
+        }
+
         Self::legacy_syscall(cshadow::syscallhandler_openat, ctx)
     }
 
+    #[log_syscall(/* rv */ std::ffi::c_int, /* dirfd */ std::ffi::c_int, /* pathname */ SyscallStringArg,
+                  /* how */ *const std::ffi::c_void, /* size */ usize)]
+    pub fn openat2(
+        ctx: &mut SyscallContext,
+        _dir_fd: std::ffi::c_int,
+        _path: ForeignPtr<()>,
+        _how: ForeignPtr<()>,
+        _size: usize,
+    ) -> SyscallResult {
+        Self::legacy_syscall(cshadow::syscallhandler_openat2, ctx)
+    }
+
     #[log_syscall(/* rv */ std::ffi::c_int)]
     pub fn faccessat(ctx: &mut SyscallContext) -> SyscallResult {
         Self::legacy_syscall(cshadow::syscallhandler_faccessat, ctx)
@@ -75,9 +95,83 @@ impl SyscallHandler {
         Self::legacy_syscall(cshadow::syscallhandler_renameat2, ctx)
     }
 
-    #[log_syscall(/* rv */ std::ffi::c_int)]
-    pub fn statx(ctx: &mut SyscallContext) -> SyscallResult {
-        Self::legacy_syscall(cshadow::syscallhandler_statx, ctx)
+    #[log_syscall(/* rv */ std::ffi::c_int, /* dirfd */ std::ffi::c_int,
+                  /* pathname */ SyscallStringArg, /* flags */ std::ffi::c_int,
+                  /* mask */ std::ffi::c_uint, /* statxbuf */ *const std::ffi::c_void)]
+    pub fn statx(
+        ctx: &mut SyscallContext,
+        dirfd: std::ffi::c_int,
+        pathname_ptr: ForeignPtr<()>,
+        flags: std::ffi::c_int,
+        mask: std::ffi::c_uint,
+        statx_ptr: ForeignPtr<linux_api::stat::statx>,
+    ) -> SyscallResult {
+        let Some(flags) = StatxFlags::from_bits(flags) else {
+            log::debug!("Invalid statx flags: {flags:#x}");
+            return Err(Errno::EINVAL.into());
+        };
+
+        if flags.contains(StatxFlags::AT_STATX_FORCE_SYNC | StatxFlags::AT_STATX_DONT_SYNC) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // unknown mask bits are ignored, but the reserved bit is not
+        let mask = StatxMask::from_bits_retain(mask);
+        if mask.contains(StatxMask::STATX__RESERVED) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // the legacy C handler passes the syscall through to the OS-backed file or path, so we
+        // only need to handle the files that shadow emulates
+        if dirfd == libc::AT_FDCWD {
+            return Self::legacy_syscall(cshadow::syscallhandler_statx, ctx);
+        }
+
+        let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+        let file = match Self::get_descriptor(&desc_table, dirfd)?.file() {
+            CompatFile::New(file) => file.inner_file().clone(),
+            CompatFile::Legacy(_) => {
+                drop(desc_table);
+                return Self::legacy_syscall(cshadow::syscallhandler_statx, ctx);
+            }
+        };
+        drop(desc_table);
+
+        // since linux 6.11, the path may be NULL if `AT_EMPTY_PATH` is given
+        let path_is_empty = if pathname_ptr.is_null() && flags.contains(StatxFlags::AT_EMPTY_PATH) {
+            true
+        } else {
+            let mut path_buf = [0u8; linux_api::limits::PATH_MAX];
+            let path = ctx.objs.process.memory_borrow().copy_str_from_ptr(
+                &mut path_buf,
+                ForeignArrayPtr::new(pathname_ptr.cast::<u8>(), linux_api::limits::PATH_MAX),
+            )?;
+            let path = path.to_bytes();
+
+            if path.first() == Some(&b'/') {
+                // `dirfd` is ignored for absolute paths
+                return Self::legacy_syscall(cshadow::syscallhandler_statx, ctx);
+            }
+
+            path.is_empty()
+        };
+
+        if !path_is_empty {
+            // a relative path, but `dirfd` isn't a directory
+            return Err(Errno::ENOTDIR.into());
+        }
+
+        if !flags.contains(StatxFlags::AT_EMPTY_PATH) {
+            return Err(Errno::ENOENT.into());
+        }
+
+        let statx = emulated_statx(&file, mask);
+        ctx.objs
+            .process
+            .memory_borrow_mut()
+            .write(statx_ptr, &statx)?;
+
+        Ok(0.into())
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int)]
@@ -95,3 +189,54 @@ impl SyscallHandler {
         Self::legacy_syscall(cshadow::syscallhandler_utimensat, ctx)
     }
 }
+
+/// Build the `statx` result for a file that shadow emulates. Only the requested fields that shadow
+/// can provide are filled in, and `stx_mask` reports which fields those are. In particular the
+/// timestamps and inode number aren't provided.
+fn emulated_statx(file: &File, mask: StatxMask) -> linux_api::stat::statx {
+    use linux_api::stat::{S_IFCHR, S_IFIFO, S_IFREG, S_IFSOCK};
+
+    let (file_type, permissions) = match file {
+        File::Pipe(_) => (S_IFIFO, 0o600),
+        File::Socket(_) => (S_IFSOCK, 0o777),
+        File::MessageQueue(_) => (S_IFREG, 0o600),
+        File::Tun(_) => (S_IFCHR, 0o666),
+        // anonymous inodes don't have a file type
+        File::EventFd(_) | File::TimerFd(_) | File::Epoll(_) => (0, 0o600),
+    };
+
+    let supported = StatxMask::STATX_TYPE
+        | StatxMask::STATX_MODE
+        | StatxMask::STATX_NLINK
+        | StatxMask::STATX_UID
+        | StatxMask::STATX_GID
+        | StatxMask::STATX_SIZE
+        | StatxMask::STATX_BLOCKS;
+    let mask = mask & supported;
+
+    let mut statx = linux_api::stat::statx {
+        stx_mask: mask.bits(),
+        stx_blksize: 4096,
+        ..Default::default()
+    };
+
+    if mask.contains(StatxMask::STATX_TYPE) {
+        statx.stx_mode |= file_type;
+    }
+    if mask.contains(StatxMask::STATX_MODE) {
+        statx.stx_mode |= permissions;
+    }
+    if mask.contains(StatxMask::STATX_NLINK) {
+        statx.stx_nlink = 1;
+    }
+    // processes run with shadow's user and group
+    if mask.contains(StatxMask::STATX_UID) {
+        statx.stx_uid = nix::unistd::geteuid().as_raw();
+    }
+    if mask.contains(StatxMask::STATX_GID) {
+        statx.stx_gid = nix::unistd::getegid().as_raw();
+    }
+    // the size and blocks are 0 for emulated files
+
+    statx
+}
@@ -1,14 +1,25 @@
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
 use linux_api::errno::Errno;
 use linux_api::fcntl::DescriptorFlags;
+use linux_api::if_tun::TunFlags;
 use linux_api::ioctls::IoctlRequest;
+use linux_api::netdevice::{
+    ifconf, ifreq, sockaddr, IfFlags, ARPHRD_ETHER, ARPHRD_LOOPBACK, ARPHRD_NONE, IFNAMSIZ,
+};
 use log::debug;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
 use crate::cshadow as c;
-use crate::host::descriptor::{CompatFile, FileStatus};
+use crate::host::descriptor::socket::Socket;
+use crate::host::descriptor::tun::TunFile;
+use crate::host::descriptor::{CompatFile, File, FileStatus};
+use crate::host::network::namespace::InterfaceInfo;
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
-use crate::host::syscall::types::SyscallResult;
+use crate::host::syscall::types::{ForeignArrayPtr, SyscallResult};
 
 impl SyscallHandler {
     #[log_syscall(/* rv */ std::ffi::c_int, /* fd */ std::ffi::c_int, /* request */ std::ffi::c_ulong)]
@@ -69,6 +80,43 @@ impl SyscallHandler {
             file.inner_file().clone()
         };
 
+        // the network device ioctls don't depend on the socket's state
+        if let File::Socket(socket) = &file {
+            let is_inet = matches!(socket, Socket::Inet(_));
+            match request {
+                IoctlRequest::SIOCGIFCONF => return Self::ioctl_gifconf(ctx, arg_ptr.cast()),
+                IoctlRequest::SIOCGIFNAME
+                | IoctlRequest::SIOCGIFINDEX
+                | IoctlRequest::SIOCGIFFLAGS
+                | IoctlRequest::SIOCGIFMTU
+                | IoctlRequest::SIOCGIFHWADDR => {
+                    return Self::ioctl_ifreq(ctx, request, arg_ptr.cast());
+                }
+                // Linux only supports the address ioctls on inet sockets
+                IoctlRequest::SIOCGIFADDR
+                | IoctlRequest::SIOCGIFNETMASK
+                | IoctlRequest::SIOCGIFBRDADDR
+                    if is_inet =>
+                {
+                    return Self::ioctl_ifreq(ctx, request, arg_ptr.cast());
+                }
+                IoctlRequest::SIOCSIFFLAGS | IoctlRequest::SIOCSIFMTU => {
+                    return Self::ioctl_set_ifreq(ctx, request, arg_ptr.cast());
+                }
+                IoctlRequest::SIOCSIFADDR | IoctlRequest::SIOCSIFNETMASK if is_inet => {
+                    return Self::ioctl_set_ifreq(ctx, request, arg_ptr.cast());
+                }
+                _ => {}
+            }
+        }
+
+        // creating a TUN interface requires the network namespace
+        if let File::Tun(tun) = &file {
+            if request == IoctlRequest::TUNSETIFF {
+                return Self::ioctl_tunsetiff(ctx, tun, arg_ptr.cast());
+            }
+        }
+
         let mut file = file.borrow_mut();
 
         // all file types that shadow implements should support non-blocking operation
@@ -86,4 +134,293 @@ impl SyscallHandler {
         // handle file-specific ioctls
         file.ioctl(request, arg_ptr, &mut ctx.objs.process.memory_borrow_mut())
     }
+
+    /// Handle `SIOCGIFCONF`, which returns the address of each interface.
+    fn ioctl_gifconf(ctx: &mut SyscallContext, arg_ptr: ForeignPtr<ifconf>) -> SyscallResult {
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+        let mut conf = mem.read(arg_ptr)?;
+
+        let interfaces = ctx.objs.host.network_namespace_borrow().interfaces();
+        // interfaces without an address aren't included
+        let reqs: Vec<ifreq> = interfaces
+            .iter()
+            .filter(|interface| !interface.address.is_unspecified())
+            .map(|interface| {
+                let mut req: ifreq = shadow_pod::zeroed();
+                req.set_name(interface.name.as_bytes());
+                req.ifr_ifru.ifru_addr = sockaddr_from_ipv4(interface.address);
+                req
+            })
+            .collect();
+
+        let req_size = std::mem::size_of::<ifreq>();
+
+        // netdevice(7): "If the ifc_req is NULL, SIOCGIFCONF returns the necessary buffer size in
+        // bytes for receiving all available addresses in ifc_len."
+        if conf.ifc_buf.is_null() {
+            conf.ifc_len = (reqs.len() * req_size).try_into().unwrap();
+            mem.write(arg_ptr, &conf)?;
+            return Ok(0.into());
+        }
+
+        // only return as many entries as will fit in the buffer
+        let buf_len = usize::try_from(conf.ifc_len).unwrap_or(0);
+        let num_reqs = std::cmp::min(reqs.len(), buf_len / req_size);
+
+        let buf_ptr = ForeignPtr::from_raw_ptr(conf.ifc_buf.cast::<ifreq>());
+        mem.copy_to_ptr(ForeignArrayPtr::new(buf_ptr, num_reqs), &reqs[..num_reqs])?;
+
+        conf.ifc_len = (num_reqs * req_size).try_into().unwrap();
+        mem.write(arg_ptr, &conf)?;
+
+        Ok(0.into())
+    }
+
+    /// Handle the network device ioctls that take a `struct ifreq` for a single interface.
+    fn ioctl_ifreq(
+        ctx: &mut SyscallContext,
+        request: IoctlRequest,
+        arg_ptr: ForeignPtr<ifreq>,
+    ) -> SyscallResult {
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+        let mut req = mem.read(arg_ptr)?;
+
+        let interfaces = ctx.objs.host.network_namespace_borrow().interfaces();
+
+        // SIOCGIFNAME looks up the interface by index, and all others look it up by name
+        let interface: Option<&InterfaceInfo> = if request == IoctlRequest::SIOCGIFNAME {
+            // SAFETY: any bit pattern is a valid `c_int`
+            let index = unsafe { req.ifr_ifru.ifru_ivalue };
+            interfaces.iter().find(|x| x.index == index)
+        } else {
+            let name = req.name();
+            interfaces.iter().find(|x| x.name.as_bytes() == name)
+        };
+
+        let Some(interface) = interface else {
+            return Err(Errno::ENODEV.into());
+        };
+
+        let is_address_request = matches!(
+            request,
+            IoctlRequest::SIOCGIFADDR | IoctlRequest::SIOCGIFNETMASK | IoctlRequest::SIOCGIFBRDADDR
+        );
+        if is_address_request && interface.address.is_unspecified() {
+            return Err(Errno::EADDRNOTAVAIL.into());
+        }
+
+        match request {
+            IoctlRequest::SIOCGIFNAME => req.set_name(interface.name.as_bytes()),
+            IoctlRequest::SIOCGIFINDEX => req.ifr_ifru.ifru_ivalue = interface.index,
+            IoctlRequest::SIOCGIFFLAGS => req.ifr_ifru.ifru_flags = interface.flags().bits(),
+            IoctlRequest::SIOCGIFMTU => {
+                req.ifr_ifru.ifru_mtu = interface.mtu.try_into().unwrap();
+            }
+            IoctlRequest::SIOCGIFHWADDR => {
+                let mut addr: sockaddr = shadow_pod::zeroed();
+                addr.sa_family = if interface.is_tun {
+                    ARPHRD_NONE
+                } else if interface.is_loopback {
+                    ARPHRD_LOOPBACK
+                } else {
+                    ARPHRD_ETHER
+                };
+                for (dst, src) in addr.sa_data.iter_mut().zip(interface.hw_address) {
+                    *dst = src as std::ffi::c_char;
+                }
+                req.ifr_ifru.ifru_hwaddr = addr;
+            }
+            IoctlRequest::SIOCGIFADDR => {
+                req.ifr_ifru.ifru_addr = sockaddr_from_ipv4(interface.address);
+            }
+            IoctlRequest::SIOCGIFNETMASK => {
+                req.ifr_ifru.ifru_netmask = sockaddr_from_ipv4(interface.netmask());
+            }
+            IoctlRequest::SIOCGIFBRDADDR => {
+                req.ifr_ifru.ifru_broadaddr = sockaddr_from_ipv4(interface.broadcast());
+            }
+            _ => panic!("Unexpected network device ioctl {request:?}"),
+        }
+
+        mem.write(arg_ptr, &req)?;
+
+        Ok(0.into())
+    }
+
+    /// Handle the network device ioctls that configure a single interface. Only TUN interfaces can
+    /// be configured.
+    fn ioctl_set_ifreq(
+        ctx: &mut SyscallContext,
+        request: IoctlRequest,
+        arg_ptr: ForeignPtr<ifreq>,
+    ) -> SyscallResult {
+        let req = ctx.objs.process.memory_borrow().read(arg_ptr)?;
+        let net_ns = ctx.objs.host.network_namespace_borrow();
+
+        let tun = std::str::from_utf8(req.name())
+            .ok()
+            .and_then(|name| net_ns.tun_interface_borrow_mut(name));
+
+        let Some(mut tun) = tun else {
+            let name = req.name();
+            if net_ns
+                .interfaces()
+                .iter()
+                .any(|x| x.name.as_bytes() == name)
+            {
+                warn_once_then_debug!(
+                    "Shadow doesn't support configuring interface '{}' with {request:?}",
+                    String::from_utf8_lossy(name),
+                );
+                return Err(Errno::EPERM.into());
+            }
+            return Err(Errno::ENODEV.into());
+        };
+
+        match request {
+            IoctlRequest::SIOCSIFFLAGS => {
+                // SAFETY: any bit pattern is a valid `c_short`
+                let flags = IfFlags::from_bits_retain(unsafe { req.ifr_ifru.ifru_flags });
+                // only the `IFF_UP` flag can be changed
+                tun.is_up = flags.contains(IfFlags::IFF_UP);
+            }
+            IoctlRequest::SIOCSIFMTU => {
+                // SAFETY: any bit pattern is a valid `c_int`
+                let mtu = unsafe { req.ifr_ifru.ifru_mtu };
+                // the MTU limits of Linux TUN interfaces
+                tun.mtu = match u32::try_from(mtu) {
+                    Ok(mtu @ 68..=65535) => mtu,
+                    _ => return Err(Errno::EINVAL.into()),
+                };
+            }
+            IoctlRequest::SIOCSIFADDR => {
+                // SAFETY: any bit pattern is a valid `sockaddr`
+                let addr = ipv4_from_sockaddr(unsafe { &req.ifr_ifru.ifru_addr })?;
+                if tun.address.is_none() {
+                    // the default prefix length of a point-to-point interface
+                    tun.prefix_len = 32;
+                }
+                tun.address = Some(addr);
+            }
+            IoctlRequest::SIOCSIFNETMASK => {
+                if tun.address.is_none() {
+                    return Err(Errno::EADDRNOTAVAIL.into());
+                }
+
+                // SAFETY: any bit pattern is a valid `sockaddr`
+                let netmask = u32::from(ipv4_from_sockaddr(unsafe { &req.ifr_ifru.ifru_netmask })?);
+
+                // the netmask must be a contiguous prefix
+                let prefix_len = netmask.leading_ones();
+                if netmask.checked_shl(prefix_len).unwrap_or(0) != 0 {
+                    return Err(Errno::EINVAL.into());
+                }
+                tun.prefix_len = prefix_len.try_into().unwrap();
+            }
+            _ => panic!("Unexpected network device ioctl {request:?}"),
+        }
+
+        log::debug!(
+            "Configured TUN interface {} with address {:?}/{}, MTU {}, up: {}",
+            tun.name,
+            tun.address,
+            tun.prefix_len,
+            tun.mtu,
+            tun.is_up,
+        );
+
+        Ok(0.into())
+    }
+
+    /// Handle `TUNSETIFF`, which creates a TUN interface and attaches it to the TUN file.
+    fn ioctl_tunsetiff(
+        ctx: &mut SyscallContext,
+        file: &Arc<AtomicRefCell<TunFile>>,
+        arg_ptr: ForeignPtr<ifreq>,
+    ) -> SyscallResult {
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+        let mut req = mem.read(arg_ptr)?;
+
+        // SAFETY: any bit pattern is a valid `c_short`
+        let flags = TunFlags::from_bits_retain(unsafe { req.ifr_ifru.ifru_flags });
+
+        if !flags.contains(TunFlags::IFF_TUN) || flags.contains(TunFlags::IFF_TAP) {
+            warn_once_then_debug!("Shadow only supports TUN interfaces, not TAP interfaces");
+            return Err(Errno::EINVAL.into());
+        }
+
+        let unsupported =
+            flags - (TunFlags::IFF_TUN | TunFlags::IFF_NO_PI | TunFlags::IFF_ONE_QUEUE);
+        if !unsupported.is_empty() {
+            warn_once_then_debug!("Unsupported TUN interface flags {unsupported:?}");
+            return Err(Errno::EINVAL.into());
+        }
+
+        if file.borrow().interface_name().is_some() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let Ok(name) = std::str::from_utf8(req.name()) else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let net_ns = ctx.objs.host.network_namespace_borrow();
+        let interfaces = net_ns.interfaces();
+
+        // like Linux, an empty name is the same as "tun%d", and "%d" is replaced with the smallest
+        // number that gives an unused name
+        let name = if name.is_empty() { "tun%d" } else { name };
+        let name = if name.contains("%d") {
+            (0..)
+                .map(|i| name.replacen("%d", &i.to_string(), 1))
+                .take_while(|x| x.len() < IFNAMSIZ)
+                .find(|x| interfaces.iter().all(|y| &y.name != x))
+                .ok_or(Errno::ENFILE)?
+        } else {
+            name.to_string()
+        };
+
+        if let Some(interface) = interfaces.iter().find(|x| x.name == name) {
+            // Linux would attach to a persistent TUN interface, but shadow's TUN interfaces can
+            // only have one file
+            return Err(if interface.is_tun {
+                Errno::EBUSY
+            } else {
+                Errno::EINVAL
+            }
+            .into());
+        }
+
+        net_ns.add_tun_interface(name.clone(), file);
+
+        req.set_name(name.as_bytes());
+        mem.write(arg_ptr, &req)?;
+
+        file.borrow_mut()
+            .attach(name, flags & (TunFlags::IFF_TUN | TunFlags::IFF_NO_PI));
+
+        Ok(0.into())
+    }
+}
+
+/// Get the address from a generic `sockaddr` containing an `AF_INET` address.
+fn ipv4_from_sockaddr(addr: &sockaddr) -> Result<Ipv4Addr, Errno> {
+    if i32::from(addr.sa_family) != libc::AF_INET {
+        return Err(Errno::EINVAL);
+    }
+
+    // the first two bytes of `sa_data` are the port
+    let [a, b, c, d] = [2, 3, 4, 5].map(|i| addr.sa_data[i] as u8);
+    Ok(Ipv4Addr::new(a, b, c, d))
+}
+
+/// Build a generic `sockaddr` containing an `AF_INET` address with port 0.
+fn sockaddr_from_ipv4(addr: Ipv4Addr) -> sockaddr {
+    let mut rv: sockaddr = shadow_pod::zeroed();
+    rv.sa_family = libc::AF_INET.try_into().unwrap();
+    // the first two bytes of `sa_data` are the port
+    for (dst, src) in rv.sa_data[2..].iter_mut().zip(addr.octets()) {
+        *dst = src as std::ffi::c_char;
+    }
+    rv
 }
@@ -38,6 +38,7 @@ mod fileat;
 mod futex;
 mod ioctl;
 mod mman;
+mod mqueue;
 mod poll;
 mod prctl;
 mod random;
@@ -48,6 +49,7 @@ mod shadow;
 mod signal;
 mod socket;
 mod sysinfo;
+mod sysv_ipc;
 mod time;
 mod timerfd;
 mod uio;
@@ -77,6 +79,9 @@ pub struct SyscallHandler {
     /// forward. This stores the result of the completed syscall, to be returned when the caller
     /// resumes.
     pending_result: Option<SyscallResult>,
+    /// The number of bytes that a blocked `MSG_WAITALL` recv has already received. The recv
+    /// continues from here when it's re-executed.
+    recv_waitall_progress: usize,
     /// We use this epoll to service syscalls that need to block on the status of multiple
     /// descriptors, like poll.
     epoll: SendPointer<c::Epoll>,
@@ -104,6 +109,7 @@ impl SyscallHandler {
             syscall_counter: count_syscalls.then(Counter::new),
             blocked_syscall: None,
             pending_result: None,
+            recv_waitall_progress: 0,
             epoll: unsafe { SendPointer::new(c::epoll_new()) },
             #[cfg(feature = "perf_timers")]
             perf_duration_current: Duration::ZERO,
@@ -163,6 +169,7 @@ impl SyscallHandler {
         if let Some(syscall_counter) = self.syscall_counter.as_mut() {
             if !was_blocked {
                 syscall_counter.add_one(syscall_name);
+                Worker::increment_phase_syscall_counter(syscall_name);
             }
         }
 
@@ -250,6 +257,21 @@ impl SyscallHandler {
             }
         }
 
+        // Syscalls such as `ppoll` temporarily replace the signal mask. Like linux, restore the
+        // original mask once the syscall completes, unless it was interrupted. In that case the
+        // shim handles the interrupting signal with the temporary mask and restores the original
+        // mask afterwards.
+        let interrupted = matches!(
+            rv,
+            Err(SyscallError::Failed(ref failed)) if failed.errno == Errno::EINTR
+        );
+        if !interrupted && !matches!(rv, Err(SyscallError::Blocked(_))) {
+            signal::restore_temporary_sigmask(
+                ctx.thread,
+                &ctx.host.shim_shmem_lock_borrow().unwrap(),
+            );
+        }
+
         // we only use unsafe borrows from C code, and we should have only called into C syscall
         // handlers through `Self::legacy_syscall` which should have already flushed the pointers,
         // but we may as well do it again here just to be safe
@@ -275,12 +297,19 @@ impl SyscallHandler {
             // latter are part of Shadow's internal plumbing; they shouldn't necessarily "consume"
             // time
             if !is_shadow_syscall(syscall) {
-                ctx.host
-                    .shim_shmem_lock_borrow_mut()
-                    .unwrap()
-                    .unapplied_cpu_latency += ctx.host.shim_shmem().unblocked_syscall_latency;
+                let latency = ctx.host.shim_shmem().unblocked_syscall_latency;
+                let mut host_shmem = ctx.host.shim_shmem_lock_borrow_mut().unwrap();
+                host_shmem.unapplied_cpu_latency += latency;
+                ctx.process
+                    .shmem()
+                    .protected
+                    .borrow_mut(&host_shmem.root)
+                    .system_cpu_time += latency;
             }
 
+            // the process's modeled CPU time may have advanced, either here or in the shim
+            ctx.process.check_cpu_timers(ctx.host, Some(ctx.thread));
+
             let unapplied_cpu_latency = ctx
                 .host
                 .shim_shmem_lock_borrow()
@@ -366,6 +395,7 @@ impl SyscallHandler {
             SyscallNum::NR_clone3 => handle!(clone3),
             SyscallNum::NR_close => handle!(close),
             SyscallNum::NR_connect => handle!(connect),
+            SyscallNum::NR_copy_file_range => handle!(copy_file_range),
             SyscallNum::NR_creat => handle!(creat),
             SyscallNum::NR_dup => handle!(dup),
             SyscallNum::NR_dup2 => handle!(dup2),
@@ -425,12 +455,19 @@ impl SyscallHandler {
             SyscallNum::NR_mknodat => handle!(mknodat),
             SyscallNum::NR_mmap => handle!(mmap),
             SyscallNum::NR_mprotect => handle!(mprotect),
+            SyscallNum::NR_mq_getsetattr => handle!(mq_getsetattr),
+            SyscallNum::NR_mq_notify => handle!(mq_notify),
+            SyscallNum::NR_mq_open => handle!(mq_open),
+            SyscallNum::NR_mq_timedreceive => handle!(mq_timedreceive),
+            SyscallNum::NR_mq_timedsend => handle!(mq_timedsend),
+            SyscallNum::NR_mq_unlink => handle!(mq_unlink),
             SyscallNum::NR_mremap => handle!(mremap),
             SyscallNum::NR_munmap => handle!(munmap),
             SyscallNum::NR_nanosleep => handle!(nanosleep),
             SyscallNum::NR_newfstatat => handle!(newfstatat),
             SyscallNum::NR_open => handle!(open),
             SyscallNum::NR_openat => handle!(openat),
+            SyscallNum::NR_openat2 => handle!(openat2),
             SyscallNum::NR_pipe => handle!(pipe),
             SyscallNum::NR_pipe2 => handle!(pipe2),
             SyscallNum::NR_poll => handle!(poll),
@@ -458,6 +495,10 @@ impl SyscallHandler {
             SyscallNum::NR_sched_getaffinity => handle!(sched_getaffinity),
             SyscallNum::NR_sched_setaffinity => handle!(sched_setaffinity),
             SyscallNum::NR_select => handle!(select),
+            SyscallNum::NR_semctl => handle!(semctl),
+            SyscallNum::NR_semget => handle!(semget),
+            SyscallNum::NR_semop => handle!(semop),
+            SyscallNum::NR_semtimedop => handle!(semtimedop),
             SyscallNum::NR_sendmsg => handle!(sendmsg),
             SyscallNum::NR_sendto => handle!(sendto),
             SyscallNum::NR_set_robust_list => handle!(set_robust_list),
@@ -466,6 +507,10 @@ impl SyscallHandler {
             SyscallNum::NR_setpgid => handle!(setpgid),
             SyscallNum::NR_setsid => handle!(setsid),
             SyscallNum::NR_setsockopt => handle!(setsockopt),
+            SyscallNum::NR_shmat => handle!(shmat),
+            SyscallNum::NR_shmctl => handle!(shmctl),
+            SyscallNum::NR_shmdt => handle!(shmdt),
+            SyscallNum::NR_shmget => handle!(shmget),
             SyscallNum::NR_shutdown => handle!(shutdown),
             SyscallNum::NR_sigaltstack => handle!(sigaltstack),
             SyscallNum::NR_socket => handle!(socket),
@@ -476,6 +521,11 @@ impl SyscallHandler {
             SyscallNum::NR_syncfs => handle!(syncfs),
             SyscallNum::NR_sysinfo => handle!(sysinfo),
             SyscallNum::NR_tgkill => handle!(tgkill),
+            SyscallNum::NR_timer_create => handle!(timer_create),
+            SyscallNum::NR_timer_delete => handle!(timer_delete),
+            SyscallNum::NR_timer_getoverrun => handle!(timer_getoverrun),
+            SyscallNum::NR_timer_gettime => handle!(timer_gettime),
+            SyscallNum::NR_timer_settime => handle!(timer_settime),
             SyscallNum::NR_timerfd_create => handle!(timerfd_create),
             SyscallNum::NR_timerfd_gettime => handle!(timerfd_gettime),
             SyscallNum::NR_timerfd_settime => handle!(timerfd_settime),
@@ -601,6 +651,7 @@ impl SyscallHandler {
                 let level = if has_already_warned {
                     log::Level::Debug
                 } else {
+                    Worker::add_unsupported_syscall(syscall);
                     log::Level::Warn
                 };
 
@@ -0,0 +1,464 @@
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
+use linux_api::errno::Errno;
+use linux_api::fcntl::{DescriptorFlags, OFlag};
+use linux_api::mqueue::mq_attr;
+use linux_api::signal::{linux_sigval, sigevent, siginfo_t, SigevNotify, Signal};
+use linux_api::time::timespec;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::syscall_types::{ForeignArrayPtr, ForeignPtr};
+use syscall_logger::log_syscall;
+
+use crate::core::worker::Worker;
+use crate::host::descriptor::mqueue::{MqFile, MqLimits, MqNotification};
+use crate::host::descriptor::{
+    CompatFile, Descriptor, File, FileMode, FileState, FileStatus, OpenFile,
+};
+use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
+use crate::host::syscall::type_formatting::{SyscallBufferArg, SyscallStringArg};
+use crate::host::syscall::types::SyscallError;
+use crate::utility::callback_queue::CallbackQueue;
+
+impl SyscallHandler {
+    #[log_syscall(/* rv */ std::ffi::c_int, /* name */ SyscallStringArg,
+                  /* oflag */ linux_api::fcntl::OFlag, /* mode */ nix::sys::stat::Mode,
+                  /* attr */ *const linux_api::mqueue::mq_attr)]
+    pub fn mq_open(
+        ctx: &mut SyscallContext,
+        name_ptr: ForeignPtr<std::ffi::c_char>,
+        oflag: std::ffi::c_int,
+        _mode: linux_api::posix_types::kernel_mode_t,
+        attr_ptr: ForeignPtr<mq_attr>,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let name = read_mq_name(ctx, name_ptr)?;
+
+        // like Linux, ignore any unknown flags
+        let oflag = OFlag::from_bits_truncate(oflag);
+
+        let (mode, oflag) = FileMode::from_o_flags(oflag).or(Err(Errno::EINVAL))?;
+        if mode.is_empty() {
+            // O_PATH isn't valid for message queues
+            return Err(Errno::EINVAL.into());
+        }
+
+        let (status, oflag) = FileStatus::from_o_flags(oflag);
+        // the only status flag that message queues care about
+        let status = status & FileStatus::NONBLOCK;
+
+        let queue = {
+            let mut namespace = ctx.objs.host.mqueue_namespace_borrow_mut();
+
+            match namespace.lookup(&name) {
+                Some(_) if oflag.contains(OFlag::O_CREAT | OFlag::O_EXCL) => {
+                    return Err(Errno::EEXIST.into());
+                }
+                Some(queue) => Arc::clone(queue),
+                None if oflag.contains(OFlag::O_CREAT) => {
+                    let limits = if attr_ptr.is_null() {
+                        MqLimits {
+                            max_msgs: linux_api::mqueue::DFLT_MSG.try_into().unwrap(),
+                            max_msg_size: linux_api::mqueue::DFLT_MSGSIZE.try_into().unwrap(),
+                        }
+                    } else {
+                        let attr = ctx.objs.process.memory_borrow().read(attr_ptr)?;
+                        limits_from_attr(&attr)?
+                    };
+                    namespace.create(&name, limits)?
+                }
+                None => return Err(Errno::ENOENT.into()),
+            }
+        };
+
+        let file = Arc::new(AtomicRefCell::new(MqFile::new(mode, status)));
+        CallbackQueue::queue_and_run(|cb_queue| {
+            MqFile::connect_to_queue(&file, queue, cb_queue);
+        });
+
+        // mq_open(3): the close-on-exec flag is always set on message queue descriptors
+        let mut desc = Descriptor::new(CompatFile::New(OpenFile::new(File::MessageQueue(file))));
+        desc.set_flags(DescriptorFlags::FD_CLOEXEC);
+
+        let fd = ctx
+            .objs
+            .thread
+            .descriptor_table_borrow_mut(ctx.objs.host)
+            .register_descriptor(desc)
+            .or(Err(Errno::EMFILE))?;
+
+        log::trace!("mq_open() opened queue '{name}' as fd {fd}");
+
+        Ok(fd.val().try_into().unwrap())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* name */ SyscallStringArg)]
+    pub fn mq_unlink(
+        ctx: &mut SyscallContext,
+        name_ptr: ForeignPtr<std::ffi::c_char>,
+    ) -> Result<(), SyscallError> {
+        let name = read_mq_name(ctx, name_ptr)?;
+        ctx.objs.host.mqueue_namespace_borrow_mut().unlink(&name)?;
+        Ok(())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* mqdes */ std::ffi::c_int,
+                  /* msg_ptr */ SyscallBufferArg</* len */ 2>, /* msg_len */ libc::size_t,
+                  /* msg_prio */ std::ffi::c_uint,
+                  /* abs_timeout */ *const linux_api::time::timespec)]
+    pub fn mq_timedsend(
+        ctx: &mut SyscallContext,
+        mqdes: std::ffi::c_int,
+        msg_ptr: ForeignPtr<u8>,
+        msg_len: libc::size_t,
+        msg_prio: std::ffi::c_uint,
+        abs_timeout_ptr: ForeignPtr<timespec>,
+    ) -> Result<(), SyscallError> {
+        let file = get_mq_file(ctx, mqdes)?;
+
+        if !file.borrow().mode().contains(FileMode::WRITE) {
+            return Err(Errno::EBADF.into());
+        }
+
+        if msg_prio >= linux_api::mqueue::MQ_PRIO_MAX {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let queue = Arc::clone(file.borrow().queue());
+
+        if msg_len > queue.borrow().limits().max_msg_size {
+            return Err(Errno::EMSGSIZE.into());
+        }
+
+        if queue.borrow().num_msgs() >= queue.borrow().limits().max_msgs {
+            return Err(Self::mq_block(
+                ctx,
+                &file,
+                FileState::WRITABLE,
+                abs_timeout_ptr,
+            ));
+        }
+
+        let mut msg = vec![0u8; msg_len];
+        ctx.objs
+            .process
+            .memory_borrow()
+            .copy_from_ptr(&mut msg, ForeignArrayPtr::new(msg_ptr, msg_len))?;
+
+        let notification = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+            CallbackQueue::queue_and_run(|cb_queue| {
+                queue.borrow_mut().send(msg, msg_prio, cb_queue)
+            })
+        })?;
+
+        if let Some(notification) = notification {
+            Self::mq_deliver_notification(ctx, notification);
+        }
+
+        Ok(())
+    }
+
+    #[log_syscall(/* rv */ libc::ssize_t, /* mqdes */ std::ffi::c_int,
+                  /* msg_ptr */ *const std::ffi::c_void, /* msg_len */ libc::size_t,
+                  /* msg_prio */ *const std::ffi::c_uint,
+                  /* abs_timeout */ *const linux_api::time::timespec)]
+    pub fn mq_timedreceive(
+        ctx: &mut SyscallContext,
+        mqdes: std::ffi::c_int,
+        msg_ptr: ForeignPtr<u8>,
+        msg_len: libc::size_t,
+        msg_prio_ptr: ForeignPtr<std::ffi::c_uint>,
+        abs_timeout_ptr: ForeignPtr<timespec>,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        let file = get_mq_file(ctx, mqdes)?;
+
+        if !file.borrow().mode().contains(FileMode::READ) {
+            return Err(Errno::EBADF.into());
+        }
+
+        let queue = Arc::clone(file.borrow().queue());
+
+        if msg_len < queue.borrow().limits().max_msg_size {
+            return Err(Errno::EMSGSIZE.into());
+        }
+
+        if queue.borrow().num_msgs() == 0 {
+            return Err(Self::mq_block(
+                ctx,
+                &file,
+                FileState::READABLE,
+                abs_timeout_ptr,
+            ));
+        }
+
+        let (msg, priority) = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+            CallbackQueue::queue_and_run(|cb_queue| queue.borrow_mut().receive(cb_queue))
+        })?;
+
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+        mem.copy_to_ptr(ForeignArrayPtr::new(msg_ptr, msg.len()), &msg)?;
+        if !msg_prio_ptr.is_null() {
+            mem.write(msg_prio_ptr, &priority)?;
+        }
+
+        Ok(msg.len().try_into().unwrap())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* mqdes */ std::ffi::c_int,
+                  /* sevp */ *const linux_api::signal::sigevent)]
+    pub fn mq_notify(
+        ctx: &mut SyscallContext,
+        mqdes: std::ffi::c_int,
+        sevp: ForeignPtr<sigevent>,
+    ) -> Result<(), SyscallError> {
+        let file = get_mq_file(ctx, mqdes)?;
+        let queue = Arc::clone(file.borrow().queue());
+        let pid = ctx.objs.process.id();
+
+        // mq_notify(3): "If sevp is NULL, and the calling process is currently registered to
+        // receive notifications for this message queue, then the registration is removed"
+        if sevp.is_null() {
+            queue.borrow_mut().clear_notification(pid);
+            return Ok(());
+        }
+
+        let sev = ctx.objs.process.memory_borrow().read(sevp)?;
+
+        let signal = match sev.notify() {
+            Ok(SigevNotify::SIGEV_NONE) => None,
+            Ok(SigevNotify::SIGEV_SIGNAL) => {
+                let signal = Signal::try_from(sev.sigev_signo).or(Err(Errno::EINVAL))?;
+                Some(signal)
+            }
+            Ok(SigevNotify::SIGEV_THREAD) => {
+                // glibc implements this using a netlink socket, which we don't support
+                warn_once_then_debug!("mq_notify() with SIGEV_THREAD is not supported");
+                return Err(Errno::EINVAL.into());
+            }
+            Ok(SigevNotify::SIGEV_THREAD_ID) | Err(_) => {
+                log::debug!(
+                    "Invalid mq_notify() notification method {}",
+                    sev.sigev_notify
+                );
+                return Err(Errno::EINVAL.into());
+            }
+        };
+
+        // the `sigval` union is fully initialized since it was read from plugin memory
+        let sigval = unsafe { sev.sigev_value.sival_ptr } as usize;
+
+        queue.borrow_mut().set_notification(MqNotification {
+            pid,
+            signal,
+            sigval,
+        })?;
+
+        Ok(())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* mqdes */ std::ffi::c_int,
+                  /* newattr */ *const linux_api::mqueue::mq_attr,
+                  /* oldattr */ *const std::ffi::c_void)]
+    pub fn mq_getsetattr(
+        ctx: &mut SyscallContext,
+        mqdes: std::ffi::c_int,
+        newattr_ptr: ForeignPtr<mq_attr>,
+        oldattr_ptr: ForeignPtr<mq_attr>,
+    ) -> Result<(), SyscallError> {
+        let file = get_mq_file(ctx, mqdes)?;
+
+        let newattr = if newattr_ptr.is_null() {
+            None
+        } else {
+            let attr = ctx.objs.process.memory_borrow().read(newattr_ptr)?;
+            // mq_setattr(3): "The only attribute that can be modified is the setting of the
+            // O_NONBLOCK flag in mq_flags"
+            if attr.mq_flags & !i64::from(OFlag::O_NONBLOCK.bits()) != 0 {
+                return Err(Errno::EINVAL.into());
+            }
+            Some(attr)
+        };
+
+        if !oldattr_ptr.is_null() {
+            let file = file.borrow();
+            let queue = file.queue().borrow();
+            let limits = queue.limits();
+
+            let mq_flags = if file.status().contains(FileStatus::NONBLOCK) {
+                OFlag::O_NONBLOCK.bits().into()
+            } else {
+                0
+            };
+
+            let oldattr = mq_attr {
+                mq_flags,
+                mq_maxmsg: limits.max_msgs.try_into().unwrap(),
+                mq_msgsize: limits.max_msg_size.try_into().unwrap(),
+                mq_curmsgs: queue.num_msgs().try_into().unwrap(),
+                ..Default::default()
+            };
+
+            ctx.objs
+                .process
+                .memory_borrow_mut()
+                .write(oldattr_ptr, &oldattr)?;
+        }
+
+        if let Some(newattr) = newattr {
+            let mut file = file.borrow_mut();
+            let mut status = file.status();
+            status.set(
+                FileStatus::NONBLOCK,
+                newattr.mq_flags & i64::from(OFlag::O_NONBLOCK.bits()) != 0,
+            );
+            file.set_status(status);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the error for a send or receive that can't complete right now: `EAGAIN` for
+    /// non-blocking descriptors, `ETIMEDOUT` if the timeout has passed, and otherwise a blocked
+    /// error that waits for the file to reach `state`.
+    fn mq_block(
+        ctx: &mut SyscallContext,
+        file: &Arc<AtomicRefCell<MqFile>>,
+        state: FileState,
+        abs_timeout_ptr: ForeignPtr<timespec>,
+    ) -> SyscallError {
+        if file.borrow().status().contains(FileStatus::NONBLOCK) {
+            return Errno::EAGAIN.into();
+        }
+
+        // mq_timedsend(3): the timeout is an absolute time measured against CLOCK_REALTIME
+        let abs_timeout = match read_abs_timeout(ctx, abs_timeout_ptr) {
+            Ok(x) => x,
+            Err(e) => return e.into(),
+        };
+
+        if let Some(abs_timeout) = abs_timeout {
+            if Worker::current_time().unwrap() >= abs_timeout {
+                return Errno::ETIMEDOUT.into();
+            }
+        }
+
+        let supports_sa_restart = file.borrow().supports_sa_restart();
+        let mut rv = SyscallError::new_blocked_on_file(
+            File::MessageQueue(Arc::clone(file)),
+            state,
+            supports_sa_restart,
+        );
+
+        if abs_timeout.is_some() {
+            rv.blocked_condition().unwrap().set_timeout(abs_timeout);
+        }
+
+        rv
+    }
+
+    /// Send the notification registered with `mq_notify()` to its process.
+    fn mq_deliver_notification(ctx: &mut SyscallContext, notification: MqNotification) {
+        let Some(signal) = notification.signal else {
+            // SIGEV_NONE
+            return;
+        };
+
+        let sigval = linux_sigval {
+            sival_ptr: notification.sigval as *mut std::ffi::c_void,
+        };
+        let sender_pid = ctx.objs.process.id().into();
+        let siginfo = siginfo_t::new_for_mq(signal, sender_pid, 0, sigval);
+
+        if notification.pid == ctx.objs.process.id() {
+            ctx.objs
+                .process
+                .signal(ctx.objs.host, Some(ctx.objs.thread), &siginfo);
+            return;
+        }
+
+        let Some(process) = ctx.objs.host.process_borrow(notification.pid) else {
+            log::debug!(
+                "Can't send message queue notification to process {}; it no longer exists",
+                notification.pid
+            );
+            return;
+        };
+        let process = process.borrow(ctx.objs.host.root());
+        process.signal(ctx.objs.host, Some(ctx.objs.thread), &siginfo);
+    }
+}
+
+/// Read a message queue name from plugin memory. The C library strips the leading `/` before
+/// making the syscall, so the name must not contain any further slashes.
+fn read_mq_name(
+    ctx: &SyscallContext,
+    name_ptr: ForeignPtr<std::ffi::c_char>,
+) -> Result<String, Errno> {
+    let mut name_buf = [0u8; linux_api::limits::NAME_MAX + 1];
+    let name_buf_capacity = name_buf.len();
+    let name = ctx.objs.process.memory_borrow().copy_str_from_ptr(
+        &mut name_buf,
+        ForeignArrayPtr::new(name_ptr.cast::<u8>(), name_buf_capacity),
+    )?;
+
+    let name = name.to_bytes();
+
+    // these are the same checks that Linux's `lookup_one_len()` makes
+    if name.is_empty() || name == b"." || name == b".." || name.contains(&b'/') {
+        return Err(Errno::EACCES);
+    }
+
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+fn read_abs_timeout(
+    ctx: &SyscallContext,
+    abs_timeout_ptr: ForeignPtr<timespec>,
+) -> Result<Option<EmulatedTime>, Errno> {
+    if abs_timeout_ptr.is_null() {
+        return Ok(None);
+    }
+
+    let timeout = ctx.objs.process.memory_borrow().read(abs_timeout_ptr)?;
+    let timeout = SimulationTime::try_from(timeout).or(Err(Errno::EINVAL))?;
+    Ok(Some(EmulatedTime::UNIX_EPOCH + timeout))
+}
+
+/// Validate the limits requested with `mq_open(O_CREAT)`.
+fn limits_from_attr(attr: &mq_attr) -> Result<MqLimits, Errno> {
+    let max_msgs = u32::try_from(attr.mq_maxmsg).or(Err(Errno::EINVAL))?;
+    let max_msg_size = u32::try_from(attr.mq_msgsize).or(Err(Errno::EINVAL))?;
+
+    if max_msgs == 0 || max_msgs > linux_api::mqueue::HARD_MSGMAX {
+        return Err(Errno::EINVAL);
+    }
+
+    if max_msg_size == 0 || max_msg_size > linux_api::mqueue::HARD_MSGSIZEMAX {
+        return Err(Errno::EINVAL);
+    }
+
+    Ok(MqLimits {
+        max_msgs: max_msgs.try_into().unwrap(),
+        max_msg_size: max_msg_size.try_into().unwrap(),
+    })
+}
+
+fn get_mq_file(
+    ctx: &mut SyscallContext,
+    fd: std::ffi::c_int,
+) -> Result<Arc<AtomicRefCell<MqFile>>, Errno> {
+    // get the descriptor, or return error if it doesn't exist
+    let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+    let desc = SyscallHandler::get_descriptor(&desc_table, fd)?;
+
+    // message queues are always new Rust files
+    let CompatFile::New(file) = desc.file() else {
+        return Err(Errno::EBADF);
+    };
+
+    let File::MessageQueue(file) = file.inner_file() else {
+        return Err(Errno::EBADF);
+    };
+
+    Ok(Arc::clone(file))
+}
@@ -25,9 +25,10 @@ impl SyscallHandler {
         _ufds: ForeignPtr<linux_api::poll::pollfd>,
         _nfds: std::ffi::c_uint,
         _tsp: ForeignPtr<linux_api::time::kernel_timespec>,
-        _sigmask: ForeignPtr<linux_api::signal::sigset_t>,
-        _sigsetsize: libc::size_t,
+        sigmask: ForeignPtr<linux_api::signal::sigset_t>,
+        sigsetsize: libc::size_t,
     ) -> Result<std::ffi::c_int, SyscallError> {
+        Self::set_temporary_sigmask(ctx, sigmask, sigsetsize)?;
         Ok(Self::legacy_syscall(c::syscallhandler_ppoll, ctx)?.into())
     }
 }
@@ -1,12 +1,19 @@
 use linux_api::errno::Errno;
+use linux_api::random::GrndFlags;
 use log::*;
-use rand::RngCore;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::types::{ForeignArrayPtr, SyscallResult};
 
+/// The most bytes that linux returns from a single `getrandom` call (`MAX_RW_COUNT`).
+const MAX_RW_COUNT: usize = (i32::MAX as usize) & !(4096 - 1);
+
+/// The random bytes are written to the plugin in chunks of this size so that large reads don't
+/// need a large buffer.
+const CHUNK_SIZE: usize = 64 * 1024;
+
 impl SyscallHandler {
     #[log_syscall(/* rv */ isize, /* buf */ *const std::ffi::c_void, /* count */ usize,
                   /* flags */ std::ffi::c_uint)]
@@ -14,35 +21,47 @@ impl SyscallHandler {
         ctx: &mut SyscallContext,
         buf_ptr: ForeignPtr<u8>,
         count: usize,
-        _flags: std::ffi::c_uint,
+        flags: std::ffi::c_uint,
     ) -> SyscallResult {
-        // We ignore the flags arg, because we use the same random source for both
-        // random and urandom, and it never blocks anyway.
+        let Some(flags) = GrndFlags::from_bits(flags) else {
+            debug!("Invalid getrandom flags: {flags:#x}");
+            return Err(Errno::EINVAL.into());
+        };
+
+        if flags.contains(GrndFlags::GRND_RANDOM | GrndFlags::GRND_INSECURE) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // The host's random source is always initialized and never blocks, and like linux since
+        // 5.6 we use the same source for both random and urandom, so the remaining flags make no
+        // difference.
 
+        let count = std::cmp::min(count, MAX_RW_COUNT);
         trace!("Trying to read {} random bytes.", count);
 
-        // Get a native-process mem buffer where we can copy the random bytes.
-        let dst_ptr = ForeignArrayPtr::new(buf_ptr, count);
+        let buf_ptr = ForeignArrayPtr::new(buf_ptr, count);
+        let mut chunk = vec![0u8; std::cmp::min(count, CHUNK_SIZE)];
         let mut memory = ctx.objs.process.memory_borrow_mut();
-        let mut mem_ref = match memory.memory_ref_mut_uninit(dst_ptr) {
-            Ok(m) => m,
-            Err(e) => {
-                warn!("Failed to get memory ref: {:?}", e);
-                return Err(Errno::EFAULT.into());
-            }
-        };
+        let mut written = 0;
+
+        while written < count {
+            let len = std::cmp::min(count - written, chunk.len());
+            let chunk = &mut chunk[..len];
 
-        // Get random bytes using host rng to maintain determinism.
-        let mut rng = ctx.objs.host.random_mut();
-        rng.fill_bytes(&mut mem_ref);
+            // Get random bytes using the host's random source to maintain determinism.
+            ctx.objs.host.fill_random_bytes(chunk);
 
-        // We must flush the memory reference to write it back.
-        match mem_ref.flush() {
-            Ok(()) => Ok(isize::try_from(count).unwrap().into()),
-            Err(e) => {
-                warn!("Failed to flush writes: {:?}", e);
-                Err(Errno::EFAULT.into())
+            if let Err(e) = memory.copy_to_ptr(buf_ptr.slice(written..written + len), chunk) {
+                // like linux, only return an error if nothing was written
+                if written == 0 {
+                    return Err(e.into());
+                }
+                break;
             }
+
+            written += len;
         }
+
+        Ok(isize::try_from(written).unwrap().into())
     }
 }
@@ -228,7 +228,7 @@ SyscallReturn syscallhandler_pselect6(SyscallHandler* sys, const SysCallArgs* ar
     UntypedForeignPtr writefds_ptr = args->args[2].as_ptr;  // fd_set*
     UntypedForeignPtr exceptfds_ptr = args->args[3].as_ptr; // fd_set*
     UntypedForeignPtr timeout_ptr = args->args[4].as_ptr;   // const struct timespec*
-    // TODO how do we handle the sigmask arg and behavior
+    // the sigmask arg is handled by the rust syscall handler before we're called
 
     trace("select was called with nfds=%i, readfds=%p, writefds=%p, exceptfds=%p, and timeout=%p",
           nfds, (void*)readfds_ptr.val, (void*)writefds_ptr.val, (void*)exceptfds_ptr.val,
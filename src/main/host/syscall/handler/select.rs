@@ -33,8 +33,22 @@ impl SyscallHandler {
         _outp: ForeignPtr<linux_api::posix_types::kernel_fd_set>,
         _exp: ForeignPtr<linux_api::posix_types::kernel_fd_set>,
         _tsp: ForeignPtr<linux_api::time::kernel_timespec>,
-        _sig: ForeignPtr<()>,
+        sig: ForeignPtr<()>,
     ) -> Result<std::ffi::c_int, SyscallError> {
+        // linux passes the signal mask pointer and its size through a pointer to a
+        // `{ const sigset_t *ss; size_t ss_len; }` struct, since it's short of syscall arguments
+        if !sig.is_null() {
+            let [sigmask_ptr, sigsetsize] = ctx
+                .objs
+                .process
+                .memory_borrow()
+                .read(sig.cast::<[u64; 2]>())?;
+            let sigmask_ptr =
+                ForeignPtr::<()>::from(sigmask_ptr).cast::<linux_api::signal::sigset_t>();
+            let sigsetsize = usize::try_from(sigsetsize).unwrap();
+            Self::set_temporary_sigmask(ctx, sigmask_ptr, sigsetsize)?;
+        }
+
         Ok(Self::legacy_syscall(c::syscallhandler_pselect6, ctx)?.into())
     }
 }
@@ -1,9 +1,14 @@
+use linux_api::errno::Errno;
+use linux_api::signal::{sigset_t, Signal};
+use shadow_shim_helper_rs::option::FfiOption;
+use shadow_shim_helper_rs::shim_shmem::HostShmemProtected;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
 use crate::cshadow as c;
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::types::SyscallError;
+use crate::host::thread::Thread;
 
 impl SyscallHandler {
     #[log_syscall(/* rv */ std::ffi::c_int, /* pid */ linux_api::posix_types::kernel_pid_t,
@@ -84,4 +89,54 @@ impl SyscallHandler {
         assert_eq!(0, i32::from(rv));
         Ok(())
     }
+
+    /// Replace the thread's signal mask for the duration of a syscall such as `ppoll`. The
+    /// original mask is restored when the syscall completes, or like linux if the syscall is
+    /// interrupted, after the shim has handled the interrupting signal with the temporary mask.
+    /// Does nothing if `sigmask_ptr` is NULL.
+    pub(super) fn set_temporary_sigmask(
+        ctx: &mut SyscallContext,
+        sigmask_ptr: ForeignPtr<sigset_t>,
+        sigsetsize: usize,
+    ) -> Result<(), SyscallError> {
+        if sigmask_ptr.is_null() {
+            return Ok(());
+        }
+
+        if sigsetsize != std::mem::size_of::<sigset_t>() {
+            log::debug!("Bad sigsetsize {sigsetsize}");
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut sigmask = ctx.objs.process.memory_borrow().read(sigmask_ptr)?;
+
+        // these can't be blocked
+        sigmask.del(Signal::SIGKILL);
+        sigmask.del(Signal::SIGSTOP);
+
+        let shmem_lock = ctx.objs.host.shim_shmem_lock_borrow().unwrap();
+        let mut thread_shmem = ctx
+            .objs
+            .thread
+            .shmem()
+            .protected
+            .borrow_mut(&shmem_lock.root);
+
+        // if the syscall was previously blocked, we've already saved the original mask
+        if thread_shmem.saved_blocked_signals == FfiOption::None {
+            thread_shmem.saved_blocked_signals = FfiOption::Some(thread_shmem.blocked_signals);
+        }
+        thread_shmem.blocked_signals = sigmask;
+
+        Ok(())
+    }
+}
+
+/// If the thread's signal mask was temporarily replaced by
+/// [`SyscallHandler::set_temporary_sigmask`], restore the original mask.
+pub(super) fn restore_temporary_sigmask(thread: &Thread, shmem_lock: &HostShmemProtected) {
+    let mut thread_shmem = thread.shmem().protected.borrow_mut(&shmem_lock.root);
+    if let FfiOption::Some(mask) = thread_shmem.saved_blocked_signals.take() {
+        thread_shmem.blocked_signals = mask;
+    }
 }
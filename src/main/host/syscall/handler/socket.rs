@@ -6,6 +6,8 @@ use nix::sys::socket::SockFlag;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
+use crate::host::descriptor::descriptor_table::DescriptorTable;
+use crate::host::descriptor::socket::inet::icmp::{IcmpSocket, IcmpSocketType};
 use crate::host::descriptor::socket::inet::legacy_tcp::LegacyTcpSocket;
 use crate::host::descriptor::socket::inet::tcp::TcpSocket;
 use crate::host::descriptor::socket::inet::udp::UdpSocket;
@@ -14,6 +16,8 @@ use crate::host::descriptor::socket::netlink::{NetlinkFamily, NetlinkSocket, Net
 use crate::host::descriptor::socket::unix::{UnixSocket, UnixSocketType};
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
 use crate::host::descriptor::{CompatFile, Descriptor, File, FileState, FileStatus, OpenFile};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::process::Process;
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::io::{self, IoVec};
 use crate::host::syscall::type_formatting::{SyscallBufferArg, SyscallSockAddrArg};
@@ -22,6 +26,9 @@ use crate::host::syscall::types::{SyscallError, SyscallResult};
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
 
+/// The max number of files that can be passed in a single message (`SCM_MAX_FD` in linux).
+const SCM_MAX_FD: usize = 253;
+
 impl SyscallHandler {
     #[log_syscall(/* rv */ std::ffi::c_int, /* domain */ linux_api::socket::AddressFamily,
                   /* type */ std::ffi::c_int, /* protocol */ std::ffi::c_int)]
@@ -69,6 +76,7 @@ impl SyscallHandler {
                     file_flags,
                     socket_type,
                     &ctx.objs.host.abstract_unix_namespace(),
+                    ctx.objs.process.ucred(),
                 ))
             }
             libc::AF_INET => match socket_type {
@@ -88,14 +96,39 @@ impl SyscallHandler {
                     }
                 }
                 libc::SOCK_DGRAM => {
-                    if protocol != 0 && protocol != libc::IPPROTO_UDP {
-                        log::debug!("Unsupported inet dgram socket protocol {protocol}");
+                    let send_buf_size = ctx.objs.host.params.init_sock_send_buf_size;
+                    let recv_buf_size = ctx.objs.host.params.init_sock_recv_buf_size;
+
+                    if protocol == libc::IPPROTO_ICMP {
+                        // a "ping" socket
+                        Socket::Inet(InetSocket::Icmp(IcmpSocket::new(
+                            file_flags,
+                            IcmpSocketType::Ping,
+                            send_buf_size.try_into().unwrap(),
+                            recv_buf_size.try_into().unwrap(),
+                        )))
+                    } else {
+                        if protocol != 0 && protocol != libc::IPPROTO_UDP {
+                            log::debug!("Unsupported inet dgram socket protocol {protocol}");
+                            return Err(Errno::EPROTONOSUPPORT.into());
+                        }
+                        Socket::Inet(InetSocket::Udp(UdpSocket::new(
+                            file_flags,
+                            send_buf_size.try_into().unwrap(),
+                            recv_buf_size.try_into().unwrap(),
+                        )))
+                    }
+                }
+                libc::SOCK_RAW => {
+                    if protocol != libc::IPPROTO_ICMP {
+                        log::debug!("Unsupported inet raw socket protocol {protocol}");
                         return Err(Errno::EPROTONOSUPPORT.into());
                     }
                     let send_buf_size = ctx.objs.host.params.init_sock_send_buf_size;
                     let recv_buf_size = ctx.objs.host.params.init_sock_recv_buf_size;
-                    Socket::Inet(InetSocket::Udp(UdpSocket::new(
+                    Socket::Inet(InetSocket::Icmp(IcmpSocket::new(
                         file_flags,
+                        IcmpSocketType::Raw,
                         send_buf_size.try_into().unwrap(),
                         recv_buf_size.try_into().unwrap(),
                     )))
@@ -229,6 +262,8 @@ impl SyscallHandler {
             addr,
             iovs: &[iov],
             control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
+            rights: Vec::new(),
+            creds: matches!(socket, Socket::Unix(_)).then(|| ctx.objs.process.ucred()),
             flags,
         };
 
@@ -291,11 +326,30 @@ impl SyscallHandler {
         let net_ns = ctx.objs.host.network_namespace_borrow();
 
         let msg = io::read_msghdr(&mem, msg_ptr)?;
+        let control_ptr = ForeignArrayPtr::new(msg.control, msg.control_len);
+
+        // unix sockets read their control messages here, since passing files requires the
+        // descriptor table and passing credentials requires the process
+        let (control_ptr, rights, creds) = match socket {
+            Socket::Unix(_) => {
+                let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+                let (rights, creds) =
+                    Self::read_unix_cmsgs(&desc_table, ctx.objs.process, &mem, control_ptr)?;
+                (
+                    ForeignArrayPtr::new(ForeignPtr::null(), 0),
+                    rights,
+                    Some(creds),
+                )
+            }
+            _ => (control_ptr, Vec::new(), None),
+        };
 
         let args = SendmsgArgs {
             addr: io::read_sockaddr(&mem, msg.name, msg.name_len)?,
             iovs: &msg.iovs,
-            control_ptr: ForeignArrayPtr::new(msg.control, msg.control_len),
+            control_ptr,
+            rights,
+            creds,
             // note: "the msg_flags field is ignored" for sendmsg; see send(2)
             flags,
         };
@@ -357,8 +411,6 @@ impl SyscallHandler {
             return Err(Errno::ENOTSOCK.into());
         };
 
-        let mut mem = ctx.objs.process.memory_borrow_mut();
-
         log::trace!("Attempting to recv {} bytes", buf_len);
 
         let iov = IoVec {
@@ -372,12 +424,7 @@ impl SyscallHandler {
             flags,
         };
 
-        // call the socket's recvmsg(), and run any resulting events
-        let mut result = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
-            CallbackQueue::queue_and_run(|cb_queue| {
-                Socket::recvmsg(socket, args, &mut mem, cb_queue)
-            })
-        });
+        let mut result = Self::recvmsg_helper(ctx, socket, args);
 
         // if the syscall will block, keep the file open until the syscall restarts
         if let Some(err) = result.as_mut().err() {
@@ -392,6 +439,8 @@ impl SyscallHandler {
             ..
         } = result?;
 
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+
         if !addr_ptr.is_null() {
             io::write_sockaddr_and_len(&mut mem, from_addr.as_ref(), addr_ptr, addr_len_ptr)?;
         }
@@ -435,22 +484,19 @@ impl SyscallHandler {
             return Err(Errno::ENOTSOCK.into());
         };
 
-        let mut mem = ctx.objs.process.memory_borrow_mut();
+        let mut msg = io::read_msghdr(&ctx.objs.process.memory_borrow(), msg_ptr)?;
+        let control_ptr = ForeignArrayPtr::new(msg.control, msg.control_len);
 
-        let mut msg = io::read_msghdr(&mem, msg_ptr)?;
+        // this flag only applies to the descriptors we add for passed files
+        let cloexec = flags & libc::MSG_CMSG_CLOEXEC != 0;
 
         let args = RecvmsgArgs {
             iovs: &msg.iovs,
-            control_ptr: ForeignArrayPtr::new(msg.control, msg.control_len),
-            flags,
+            control_ptr,
+            flags: flags & !libc::MSG_CMSG_CLOEXEC,
         };
 
-        // call the socket's recvmsg(), and run any resulting events
-        let mut result = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
-            CallbackQueue::queue_and_run(|cb_queue| {
-                Socket::recvmsg(socket, args, &mut mem, cb_queue)
-            })
-        });
+        let mut result = Self::recvmsg_helper(ctx, socket, args);
 
         // if the syscall will block, keep the file open until the syscall restarts
         if let Some(err) = result.as_mut().err() {
@@ -461,6 +507,8 @@ impl SyscallHandler {
 
         let result = result?;
 
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+
         // write the socket address to the plugin and update the length in msg
         if !msg.name.is_null() {
             if let Some(from_addr) = result.addr.as_ref() {
@@ -474,12 +522,266 @@ impl SyscallHandler {
         msg.control_len = result.control_len;
         msg.flags = result.msg_flags;
 
+        // write any passed credentials, and add any passed files to the descriptor table (only
+        // unix sockets pass these, and they don't return any other control messages)
+        if let Some(creds) = result.creds {
+            let cmsg = io::ControlMessage {
+                level: libc::SOL_SOCKET,
+                ty: libc::SCM_CREDENTIALS,
+                data: [
+                    &creds.pid.to_ne_bytes()[..],
+                    &creds.uid.to_ne_bytes(),
+                    &creds.gid.to_ne_bytes(),
+                ]
+                .concat(),
+            };
+            let (control_len, truncated) = io::write_cmsgs(&mut mem, control_ptr, &[cmsg])?;
+
+            msg.control_len = control_len;
+            if truncated {
+                msg.flags |= libc::MSG_CTRUNC;
+            }
+        }
+
+        if !result.rights.is_empty() {
+            let desc_flags = if cloexec {
+                DescriptorFlags::FD_CLOEXEC
+            } else {
+                DescriptorFlags::empty()
+            };
+
+            // the files follow any credentials in the control buffer
+            let control_ptr = if msg.control_len > 0 {
+                control_ptr.slice(msg.control_len..)
+            } else {
+                control_ptr
+            };
+
+            let mut desc_table = ctx.objs.thread.descriptor_table_borrow_mut(ctx.objs.host);
+            let (control_len, truncated) = Self::write_scm_rights(
+                &mut desc_table,
+                &mut mem,
+                control_ptr,
+                result.rights,
+                desc_flags,
+            )?;
+
+            msg.control_len += control_len;
+            if truncated {
+                msg.flags |= libc::MSG_CTRUNC;
+            }
+        }
+
         // write msg back to the plugin
         io::update_msghdr(&mut mem, msg_ptr, msg)?;
 
         Ok(result.return_val)
     }
 
+    /// Call the socket's recvmsg() and run any resulting events. If `MSG_WAITALL` is set for a
+    /// stream socket, keep receiving until the buffers are full, the peer closes the connection,
+    /// or an error occurs. Like linux, if a signal interrupts the blocked recv after some data was
+    /// received, that data is returned. `MSG_WAITALL` is ignored for other types of sockets.
+    fn recvmsg_helper(
+        ctx: &mut SyscallContext,
+        socket: &Socket,
+        mut args: RecvmsgArgs,
+    ) -> Result<RecvmsgReturn, SyscallError> {
+        let waitall = args.flags & libc::MSG_WAITALL != 0;
+        args.flags &= !libc::MSG_WAITALL;
+
+        let recv = |args: RecvmsgArgs| {
+            let mut mem = ctx.objs.process.memory_borrow_mut();
+            crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+                CallbackQueue::queue_and_run(|cb_queue| {
+                    Socket::recvmsg(socket, args, &mut mem, cb_queue)
+                })
+            })
+        };
+
+        let len: usize = args.iovs.iter().map(|x| x.len).sum();
+
+        // a peek doesn't consume the data, so linux doesn't wait for all of it
+        if !waitall || len == 0 || args.flags & libc::MSG_PEEK != 0 || !socket.is_stream() {
+            return recv(args);
+        }
+
+        // if we previously blocked, continue after the bytes we already received
+        let mut received = if ctx.handler.is_blocked() {
+            ctx.handler.recv_waitall_progress
+        } else {
+            0
+        };
+        ctx.handler.recv_waitall_progress = 0;
+
+        let mut rv = RecvmsgReturn {
+            return_val: 0,
+            addr: None,
+            msg_flags: 0,
+            control_len: 0,
+            rights: Vec::new(),
+            creds: None,
+        };
+
+        while received < len {
+            let iovs = io::iovecs_after(args.iovs, received);
+
+            // control messages are only received with the first bytes
+            let control_ptr = if received == 0 {
+                args.control_ptr
+            } else {
+                ForeignArrayPtr::new(ForeignPtr::null(), 0)
+            };
+
+            let result = recv(RecvmsgArgs {
+                iovs: &iovs,
+                control_ptr,
+                flags: args.flags,
+            });
+
+            match result {
+                Ok(x) => {
+                    let num = usize::try_from(x.return_val).unwrap();
+                    let has_control =
+                        x.control_len > 0 || !x.rights.is_empty() || x.creds.is_some();
+                    received += num;
+                    rv.addr = x.addr.or(rv.addr);
+                    rv.msg_flags |= x.msg_flags;
+                    rv.control_len += x.control_len;
+                    rv.rights.extend(x.rights);
+                    rv.creds = x.creds.or(rv.creds);
+
+                    // stop at the end of the stream, and like linux, after receiving control
+                    // messages such as passed file descriptors
+                    if num == 0 || has_control {
+                        break;
+                    }
+                }
+                // nothing was received, so return the error (or block) as usual
+                Err(e) if received == 0 => return Err(e),
+                Err(SyscallError::Blocked(blocked)) => {
+                    let signal_pending = ctx.objs.thread.unblocked_signal_pending(
+                        ctx.objs.process,
+                        &ctx.objs.host.shim_shmem_lock_borrow().unwrap(),
+                    );
+                    if signal_pending {
+                        break;
+                    }
+
+                    ctx.handler.recv_waitall_progress = received;
+                    return Err(SyscallError::Blocked(blocked));
+                }
+                // return the bytes that were received before the error
+                Err(_) => break,
+            }
+        }
+
+        rv.return_val = received.try_into().unwrap();
+        Ok(rv)
+    }
+
+    /// Read the `SCM_RIGHTS` and `SCM_CREDENTIALS` control messages for a unix socket. Returns the
+    /// files for the passed descriptors, and the credentials to send (the process' credentials if
+    /// none were given). Like linux, control messages that aren't at the `SOL_SOCKET` level are
+    /// ignored.
+    fn read_unix_cmsgs(
+        desc_table: &DescriptorTable,
+        process: &Process,
+        mem: &MemoryManager,
+        control_ptr: ForeignArrayPtr<u8>,
+    ) -> Result<(Vec<CompatFile>, libc::ucred), SyscallError> {
+        let process_creds = process.ucred();
+
+        let mut rights = Vec::new();
+        let mut creds = process_creds;
+
+        for cmsg in io::read_cmsgs(mem, control_ptr)? {
+            if cmsg.level != libc::SOL_SOCKET {
+                continue;
+            }
+
+            match cmsg.ty {
+                libc::SCM_RIGHTS => {
+                    for fd in cmsg.data.chunks_exact(std::mem::size_of::<libc::c_int>()) {
+                        let fd = libc::c_int::from_ne_bytes(fd.try_into().unwrap());
+                        rights.push(Self::get_descriptor(desc_table, fd)?.file().clone());
+                    }
+                }
+                libc::SCM_CREDENTIALS => {
+                    let data: [u8; std::mem::size_of::<libc::ucred>()] =
+                        cmsg.data[..].try_into().or(Err(Errno::EINVAL))?;
+                    creds = shadow_pod::from_array(&data);
+
+                    // we don't emulate capabilities, so like an unprivileged process in linux,
+                    // the process can only send its own credentials
+                    if creds.pid != process_creds.pid
+                        || creds.uid != process_creds.uid
+                        || creds.gid != process_creds.gid
+                    {
+                        log::debug!("Process can't send credentials of another process or user");
+                        return Err(Errno::EPERM.into());
+                    }
+                }
+                ty => {
+                    log::debug!("Unsupported unix socket control message type {ty}");
+                    return Err(Errno::EINVAL.into());
+                }
+            }
+        }
+
+        if rights.len() > SCM_MAX_FD {
+            return Err(Errno::EINVAL.into());
+        }
+
+        Ok((rights, creds))
+    }
+
+    /// Add the passed files to the descriptor table, and write their descriptors to the control
+    /// buffer in a `SCM_RIGHTS` control message. Like linux, files that don't fit in the control
+    /// buffer or the descriptor table are closed. Returns the number of control bytes written, and
+    /// whether any files were closed (`MSG_CTRUNC`).
+    fn write_scm_rights(
+        desc_table: &mut DescriptorTable,
+        mem: &mut MemoryManager,
+        control_ptr: ForeignArrayPtr<u8>,
+        rights: Vec<CompatFile>,
+        desc_flags: DescriptorFlags,
+    ) -> Result<(libc::size_t, bool), SyscallError> {
+        let num_rights = rights.len();
+
+        let capacity = if control_ptr.is_null() {
+            0
+        } else {
+            control_ptr.len()
+        };
+        let max_fds = capacity.saturating_sub(std::mem::size_of::<libc::cmsghdr>())
+            / std::mem::size_of::<libc::c_int>();
+
+        let mut fds = Vec::new();
+        for file in rights.into_iter().take(max_fds) {
+            let mut desc = Descriptor::new(file);
+            desc.set_flags(desc_flags);
+
+            let Ok(fd) = desc_table.register_descriptor(desc) else {
+                break;
+            };
+            fds.push(libc::c_int::from(fd));
+        }
+
+        if fds.is_empty() {
+            return Ok((0, num_rights > 0));
+        }
+
+        let cmsg = io::ControlMessage {
+            level: libc::SOL_SOCKET,
+            ty: libc::SCM_RIGHTS,
+            data: fds.iter().flat_map(|fd| fd.to_ne_bytes()).collect(),
+        };
+        let (control_len, _) = io::write_cmsgs(mem, control_ptr, &[cmsg])?;
+
+        Ok((control_len, fds.len() < num_rights))
+    }
+
     #[log_syscall(/* rv */ std::ffi::c_int, /* sockfd */ std::ffi::c_int, /* addr */ *const libc::sockaddr,
                   /* addrlen */ *const libc::socklen_t)]
     pub fn getsockname(
@@ -696,14 +998,10 @@ impl SyscallHandler {
             return Err(Errno::ENOTSOCK.into());
         };
 
-        // get the accept flags
-        let flags = match SockFlag::from_bits(flags) {
-            Some(x) => x,
-            None => {
-                // linux doesn't return an error if there are unexpected flags
-                warn!("Invalid recvfrom flags: {}", flags);
-                SockFlag::from_bits_truncate(flags)
-            }
+        // get the accept flags; unlike most syscalls, linux returns an error for unexpected flags
+        let Some(flags) = SockFlag::from_bits(flags) else {
+            debug!("Invalid accept4 flags: {flags}");
+            return Err(Errno::EINVAL.into());
         };
 
         let mut rng = ctx.objs.host.random_mut();
@@ -737,6 +1035,23 @@ impl SyscallHandler {
             new_socket.borrow().getpeername().unwrap()
         };
 
+        // Apply the flags before the socket is visible to the plugin. Like linux, the accepted
+        // socket's file status is set only from the accept flags and never inherited from the
+        // listening socket, but its socket options are inherited (the socket handles this when
+        // accepting).
+        let status = if flags.contains(SockFlag::SOCK_NONBLOCK) {
+            FileStatus::NONBLOCK
+        } else {
+            FileStatus::empty()
+        };
+        new_socket.inner_file().borrow_mut().set_status(status);
+
+        let mut new_desc = Descriptor::new(CompatFile::New(new_socket));
+
+        if flags.contains(SockFlag::SOCK_CLOEXEC) {
+            new_desc.set_flags(DescriptorFlags::FD_CLOEXEC);
+        }
+
         if !addr_ptr.is_null() {
             io::write_sockaddr_and_len(
                 &mut ctx.objs.process.memory_borrow_mut(),
@@ -746,19 +1061,6 @@ impl SyscallHandler {
             )?;
         }
 
-        if flags.contains(SockFlag::SOCK_NONBLOCK) {
-            new_socket
-                .inner_file()
-                .borrow_mut()
-                .set_status(FileStatus::NONBLOCK);
-        }
-
-        let mut new_desc = Descriptor::new(CompatFile::New(new_socket));
-
-        if flags.contains(SockFlag::SOCK_CLOEXEC) {
-            new_desc.set_flags(DescriptorFlags::FD_CLOEXEC);
-        }
-
         let new_fd = ctx
             .objs
             .thread
@@ -909,6 +1211,7 @@ impl SyscallHandler {
                 file_flags,
                 socket_type,
                 &ctx.objs.host.abstract_unix_namespace(),
+                ctx.objs.process.ucred(),
                 cb_queue,
             )
         });
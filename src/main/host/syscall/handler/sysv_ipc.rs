@@ -0,0 +1,549 @@
+use std::os::fd::AsRawFd;
+
+use linux_api::errno::Errno;
+use linux_api::ipc::{
+    ipc_perm, kernel_key_t, sembuf, semid_ds, shmid_ds, SemCtlCmd, ShmCtlCmd, SHMLBA, SHM_EXEC,
+    SHM_RDONLY, SHM_REMAP, SHM_RND,
+};
+use linux_api::time::timespec;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::syscall_types::{ForeignArrayPtr, ForeignPtr};
+use syscall_logger::log_syscall;
+
+use crate::core::worker::Worker;
+use crate::host::memory_manager::AllocdMem;
+use crate::host::syscall::handler::{SyscallContext, SyscallHandler, ThreadContext};
+use crate::host::syscall::types::{Failed, SyscallError};
+use crate::host::sysv_ipc::{IpcPerm, SemopError};
+
+impl SyscallHandler {
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/shm.c#L822>
+    // ```
+    // SYSCALL_DEFINE3(shmget, key_t, key, size_t, size, int, shmflg)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* key */ kernel_key_t, /* size */ usize,
+                  /* shmflg */ std::ffi::c_int)]
+    pub fn shmget(
+        ctx: &mut SyscallContext,
+        key: kernel_key_t,
+        size: usize,
+        shmflg: std::ffi::c_int,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let perm = new_ipc_perm(key, shmflg);
+        let now = Worker::current_time().unwrap();
+
+        Ok(ctx.objs.host.sysv_ipc_borrow_mut().shm_get(
+            perm,
+            size,
+            shmflg,
+            ctx.objs.process.id(),
+            now,
+        )?)
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/shm.c#L1690>
+    // ```
+    // SYSCALL_DEFINE3(shmat, int, shmid, char __user *, shmaddr, int, shmflg)
+    // ```
+    #[log_syscall(/* rv */ *const std::ffi::c_void, /* shmid */ std::ffi::c_int,
+                  /* shmaddr */ *const std::ffi::c_void, /* shmflg */ std::ffi::c_int)]
+    pub fn shmat(
+        ctx: &mut SyscallContext,
+        shmid: std::ffi::c_int,
+        shmaddr: ForeignPtr<u8>,
+        shmflg: std::ffi::c_int,
+    ) -> Result<ForeignPtr<u8>, SyscallError> {
+        let (native_fd, len) = {
+            let table = ctx.objs.host.sysv_ipc_borrow_mut();
+            let segment = table.shm_segment(shmid)?;
+            (segment.file().as_raw_fd(), segment.mapped_size())
+        };
+
+        let mut addr = usize::from(shmaddr);
+        if addr % SHMLBA != 0 {
+            if shmflg & SHM_RND == 0 {
+                return Err(Errno::EINVAL.into());
+            }
+            addr -= addr % SHMLBA;
+        }
+
+        // shmat(2): "EINVAL ... SHM_REMAP was specified and shmaddr was NULL"
+        if addr == 0 && shmflg & SHM_REMAP != 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut flags = libc::MAP_SHARED;
+        if addr != 0 {
+            // without SHM_REMAP, linux fails if the segment would overlap an existing mapping
+            flags |= if shmflg & SHM_REMAP != 0 {
+                libc::MAP_FIXED
+            } else {
+                libc::MAP_FIXED_NOREPLACE
+            };
+        }
+
+        let (mut prot, oflag) = if shmflg & SHM_RDONLY != 0 {
+            (libc::PROT_READ, libc::O_RDONLY)
+        } else {
+            (libc::PROT_READ | libc::PROT_WRITE, libc::O_RDWR)
+        };
+        if shmflg & SHM_EXEC != 0 {
+            prot |= libc::PROT_EXEC;
+        }
+
+        // this fd exists in the plugin and not shadow; make sure to close this before returning
+        let plugin_fd = Self::open_plugin_shm_file(ctx.objs, native_fd, oflag)?;
+
+        let mmap_result = ctx.objs.process.memory_borrow_mut().do_mmap(
+            ctx.objs,
+            ForeignPtr::<()>::from(addr).cast::<u8>(),
+            len,
+            prot,
+            flags,
+            plugin_fd,
+            0,
+        );
+
+        {
+            let (process_ctx, thread) = ctx.objs.split_thread();
+            if let Err(e) = thread.native_close(&process_ctx, plugin_fd) {
+                log::trace!("Failed to close shm file at fd {plugin_fd} in plugin, error {e}");
+            }
+        }
+
+        let mapped_addr = match mmap_result {
+            Err(SyscallError::Failed(Failed {
+                errno: Errno::EEXIST,
+                ..
+            })) => return Err(Errno::EINVAL.into()),
+            x => x?,
+        };
+
+        let now = Worker::current_time().unwrap();
+        ctx.objs.host.sysv_ipc_borrow_mut().shm_attach(
+            shmid,
+            ctx.objs.process.id(),
+            usize::from(mapped_addr),
+            len,
+            now,
+        )?;
+
+        Ok(mapped_addr)
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/shm.c#L1812>
+    // ```
+    // SYSCALL_DEFINE1(shmdt, char __user *, shmaddr)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* shmaddr */ *const std::ffi::c_void)]
+    pub fn shmdt(ctx: &mut SyscallContext, shmaddr: ForeignPtr<u8>) -> Result<(), SyscallError> {
+        let addr = usize::from(shmaddr);
+        if addr % SHMLBA != 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let now = Worker::current_time().unwrap();
+        let len = ctx
+            .objs
+            .host
+            .sysv_ipc_borrow_mut()
+            .shm_detach(ctx.objs.process.id(), addr, now)
+            .ok_or(Errno::EINVAL)?;
+
+        ctx.objs
+            .process
+            .memory_borrow_mut()
+            .do_munmap(ctx.objs, shmaddr, len)?;
+
+        Ok(())
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/shm.c#L1296>
+    // ```
+    // SYSCALL_DEFINE3(shmctl, int, shmid, int, cmd, struct shmid_ds __user *, buf)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* shmid */ std::ffi::c_int,
+                  /* cmd */ std::ffi::c_int, /* buf */ *const std::ffi::c_void)]
+    pub fn shmctl(
+        ctx: &mut SyscallContext,
+        shmid: std::ffi::c_int,
+        cmd: std::ffi::c_int,
+        buf: ForeignPtr<shmid_ds>,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let Ok(cmd) = ShmCtlCmd::try_from(cmd) else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let mut table = ctx.objs.host.sysv_ipc_borrow_mut();
+
+        match cmd {
+            ShmCtlCmd::IPC_STAT => {
+                let segment = table.shm_segment(shmid)?;
+                let ds = shmid_ds {
+                    shm_perm: to_linux_ipc_perm(&segment.perm),
+                    shm_segsz: segment.size.try_into().unwrap(),
+                    shm_atime: to_linux_time(segment.atime),
+                    shm_dtime: to_linux_time(segment.dtime),
+                    shm_ctime: to_linux_time(Some(segment.ctime)),
+                    shm_cpid: segment.cpid.into(),
+                    shm_lpid: segment.lpid.map(Into::into).unwrap_or(0),
+                    shm_nattch: segment.nattch.try_into().unwrap(),
+                    ..Default::default()
+                };
+                ctx.objs.process.memory_borrow_mut().write(buf, &ds)?;
+            }
+            ShmCtlCmd::IPC_SET => {
+                let ds = ctx.objs.process.memory_borrow().read(buf)?;
+                let segment = table.shm_segment_mut(shmid)?;
+                set_ipc_perm(&mut segment.perm, &ds.shm_perm);
+                segment.ctime = Worker::current_time().unwrap();
+            }
+            ShmCtlCmd::IPC_RMID => table.shm_remove(shmid)?,
+            ShmCtlCmd::SHM_LOCK => {
+                // we don't emulate swapping, so only the flag has any effect
+                table.shm_segment_mut(shmid)?.perm.mode |= linux_api::ipc::SHM_LOCKED;
+            }
+            ShmCtlCmd::SHM_UNLOCK => {
+                table.shm_segment_mut(shmid)?.perm.mode &= !linux_api::ipc::SHM_LOCKED;
+            }
+            ShmCtlCmd::IPC_INFO
+            | ShmCtlCmd::SHM_STAT
+            | ShmCtlCmd::SHM_INFO
+            | ShmCtlCmd::SHM_STAT_ANY => {
+                warn_once_then_debug!("shmctl command {cmd:?} is not supported");
+                return Err(Errno::EINVAL.into());
+            }
+        }
+
+        Ok(0)
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/sem.c#L616>
+    // ```
+    // SYSCALL_DEFINE3(semget, key_t, key, int, nsems, int, semflg)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* key */ kernel_key_t, /* nsems */ std::ffi::c_int,
+                  /* semflg */ std::ffi::c_int)]
+    pub fn semget(
+        ctx: &mut SyscallContext,
+        key: kernel_key_t,
+        nsems: std::ffi::c_int,
+        semflg: std::ffi::c_int,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let nsems = usize::try_from(nsems).or(Err(Errno::EINVAL))?;
+        let perm = new_ipc_perm(key, semflg);
+        let now = Worker::current_time().unwrap();
+
+        Ok(ctx
+            .objs
+            .host
+            .sysv_ipc_borrow_mut()
+            .sem_get(perm, nsems, semflg, now)?)
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/sem.c#L2279>
+    // ```
+    // SYSCALL_DEFINE3(semop, int, semid, struct sembuf __user *, tsops, unsigned, nsops)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* semid */ std::ffi::c_int,
+                  /* sops */ *const std::ffi::c_void, /* nsops */ std::ffi::c_uint)]
+    pub fn semop(
+        ctx: &mut SyscallContext,
+        semid: std::ffi::c_int,
+        sops: ForeignPtr<sembuf>,
+        nsops: std::ffi::c_uint,
+    ) -> Result<(), SyscallError> {
+        Self::semtimedop_helper(ctx, semid, sops, nsops, None)
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/sem.c#L2254>
+    // ```
+    // SYSCALL_DEFINE4(semtimedop, int, semid, struct sembuf __user *, tsops,
+    //                 unsigned int, nsops, const struct __kernel_timespec __user *, timeout)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* semid */ std::ffi::c_int,
+                  /* sops */ *const std::ffi::c_void, /* nsops */ std::ffi::c_uint,
+                  /* timeout */ *const linux_api::time::timespec)]
+    pub fn semtimedop(
+        ctx: &mut SyscallContext,
+        semid: std::ffi::c_int,
+        sops: ForeignPtr<sembuf>,
+        nsops: std::ffi::c_uint,
+        timeout: ForeignPtr<timespec>,
+    ) -> Result<(), SyscallError> {
+        let timeout = if timeout.is_null() {
+            None
+        } else {
+            let timeout = ctx.objs.process.memory_borrow().read(timeout)?;
+            Some(SimulationTime::try_from(timeout).or(Err(Errno::EINVAL))?)
+        };
+
+        Self::semtimedop_helper(ctx, semid, sops, nsops, timeout)
+    }
+
+    fn semtimedop_helper(
+        ctx: &mut SyscallContext,
+        semid: std::ffi::c_int,
+        sops_ptr: ForeignPtr<sembuf>,
+        nsops: std::ffi::c_uint,
+        timeout: Option<SimulationTime>,
+    ) -> Result<(), SyscallError> {
+        let nsops = usize::try_from(nsops).unwrap();
+
+        if nsops == 0 || semid < 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        if nsops > linux_api::ipc::SEMOPM {
+            return Err(Errno::E2BIG.into());
+        }
+
+        let mut sops = vec![sembuf::default(); nsops];
+        ctx.objs
+            .process
+            .memory_borrow()
+            .copy_from_ptr(&mut sops, ForeignArrayPtr::new(sops_ptr, nsops))?;
+
+        let tid = ctx.objs.thread.id();
+        let pid = ctx.objs.process.id();
+        let now = Worker::current_time().unwrap();
+
+        // the condition will only exist after a wakeup
+        let cond = ctx.objs.thread.syscall_condition();
+        let was_blocked = cond.is_some();
+        let prev_timeout = cond.and_then(|x| x.timeout());
+
+        let mut table = ctx.objs.host.sysv_ipc_borrow_mut();
+        table.sem_stop_waiting(tid);
+
+        if table.sem_set(semid).is_err() {
+            // semop(2): "EIDRM The semaphore set was removed."
+            return Err(if was_blocked {
+                Errno::EIDRM
+            } else {
+                Errno::EINVAL
+            }
+            .into());
+        }
+
+        let blocked_on = match table.sem_op(semid, &sops, pid, now) {
+            Ok(()) => return Ok(()),
+            Err(SemopError::Errno(e)) => return Err(e.into()),
+            Err(e @ SemopError::WouldBlock { .. }) => e,
+        };
+
+        // keep the original timeout if we were already blocked
+        let abs_timeout = match prev_timeout {
+            Some(x) => Some(x),
+            None => timeout
+                .map(|x| now.checked_add(x).ok_or(Errno::EINVAL))
+                .transpose()?,
+        };
+
+        // semtimedop(2): "EAGAIN ... the time limit specified in timeout expired"
+        if abs_timeout.is_some_and(|x| now >= x) {
+            return Err(Errno::EAGAIN.into());
+        }
+
+        let futex = table.sem_wait_queue(semid, tid, pid, blocked_on)?;
+
+        // semop is never restarted after being interrupted by a signal handler
+        let mut rv = SyscallError::new_blocked_on_futex(futex, /* restartable= */ false);
+        if abs_timeout.is_some() {
+            rv.blocked_condition().unwrap().set_timeout(abs_timeout);
+        }
+
+        Err(rv)
+    }
+
+    // <https://github.com/torvalds/linux/tree/v6.3/ipc/sem.c#L1693>
+    // ```
+    // SYSCALL_DEFINE4(semctl, int, semid, int, semnum, int, cmd, unsigned long, arg)
+    // ```
+    #[log_syscall(/* rv */ std::ffi::c_int, /* semid */ std::ffi::c_int,
+                  /* semnum */ std::ffi::c_int, /* cmd */ std::ffi::c_int,
+                  /* arg */ std::ffi::c_ulong)]
+    pub fn semctl(
+        ctx: &mut SyscallContext,
+        semid: std::ffi::c_int,
+        semnum: std::ffi::c_int,
+        cmd: std::ffi::c_int,
+        arg: std::ffi::c_ulong,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let Ok(cmd) = SemCtlCmd::try_from(cmd) else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        // `arg` is a `union semun`; it's an int for SETVAL, and a pointer otherwise
+        let arg_ptr = ForeignPtr::<()>::from(usize::try_from(arg).unwrap());
+
+        let pid = ctx.objs.process.id();
+        let now = Worker::current_time().unwrap();
+        let mut table = ctx.objs.host.sysv_ipc_borrow_mut();
+
+        match cmd {
+            SemCtlCmd::IPC_STAT => {
+                let set = table.sem_set(semid)?;
+                let ds = semid_ds {
+                    sem_perm: to_linux_ipc_perm(&set.perm),
+                    sem_otime: to_linux_time(set.otime),
+                    sem_ctime: to_linux_time(Some(set.ctime)),
+                    sem_nsems: set.sems.len().try_into().unwrap(),
+                    ..Default::default()
+                };
+                ctx.objs
+                    .process
+                    .memory_borrow_mut()
+                    .write(arg_ptr.cast::<semid_ds>(), &ds)?;
+                Ok(0)
+            }
+            SemCtlCmd::IPC_SET => {
+                let ds = ctx
+                    .objs
+                    .process
+                    .memory_borrow()
+                    .read(arg_ptr.cast::<semid_ds>())?;
+                let set = table.sem_set_mut(semid)?;
+                set_ipc_perm(&mut set.perm, &ds.sem_perm);
+                set.ctime = now;
+                Ok(0)
+            }
+            SemCtlCmd::IPC_RMID => {
+                table.sem_remove(semid)?;
+                Ok(0)
+            }
+            SemCtlCmd::GETVAL | SemCtlCmd::GETPID | SemCtlCmd::GETNCNT | SemCtlCmd::GETZCNT => {
+                let set = table.sem_set(semid)?;
+                let semnum = usize::try_from(semnum)
+                    .ok()
+                    .filter(|x| *x < set.sems.len())
+                    .ok_or(Errno::EINVAL)?;
+                let sem = set.sems[semnum];
+
+                Ok(match cmd {
+                    SemCtlCmd::GETVAL => sem.val,
+                    SemCtlCmd::GETPID => sem.pid.map(Into::into).unwrap_or(0),
+                    SemCtlCmd::GETNCNT => table
+                        .sem_wait_count(semid, semnum, /* zero= */ false)
+                        .try_into()
+                        .unwrap(),
+                    SemCtlCmd::GETZCNT => table
+                        .sem_wait_count(semid, semnum, /* zero= */ true)
+                        .try_into()
+                        .unwrap(),
+                    _ => unreachable!(),
+                })
+            }
+            SemCtlCmd::GETALL => {
+                let vals: Vec<u16> = table
+                    .sem_set(semid)?
+                    .sems
+                    .iter()
+                    .map(|x| x.val.try_into().unwrap())
+                    .collect();
+                ctx.objs.process.memory_borrow_mut().copy_to_ptr(
+                    ForeignArrayPtr::new(arg_ptr.cast::<u16>(), vals.len()),
+                    &vals,
+                )?;
+                Ok(0)
+            }
+            SemCtlCmd::SETVAL => {
+                let semnum = usize::try_from(semnum).or(Err(Errno::EINVAL))?;
+                // the value is the `int` member of the union
+                let val = arg as u32 as i32;
+                table.sem_set_vals(semid, Some(semnum), &[val], pid, now)?;
+                Ok(0)
+            }
+            SemCtlCmd::SETALL => {
+                let nsems = table.sem_set(semid)?.sems.len();
+                let mut vals = vec![0u16; nsems];
+                ctx.objs.process.memory_borrow().copy_from_ptr(
+                    &mut vals,
+                    ForeignArrayPtr::new(arg_ptr.cast::<u16>(), nsems),
+                )?;
+                let vals: Vec<i32> = vals.into_iter().map(i32::from).collect();
+                table.sem_set_vals(semid, None, &vals, pid, now)?;
+                Ok(0)
+            }
+            SemCtlCmd::IPC_INFO
+            | SemCtlCmd::SEM_STAT
+            | SemCtlCmd::SEM_INFO
+            | SemCtlCmd::SEM_STAT_ANY => {
+                warn_once_then_debug!("semctl command {cmd:?} is not supported");
+                Err(Errno::EINVAL.into())
+            }
+        }
+    }
+
+    /// Open the shadow file `native_fd` in the plugin, returning the plugin's fd. Like with
+    /// `mmap()`, the plugin opens the file through shadow's `/proc/<pid>/fd` directory.
+    fn open_plugin_shm_file(
+        ctx: &ThreadContext,
+        native_fd: std::ffi::c_int,
+        oflag: std::ffi::c_int,
+    ) -> Result<std::ffi::c_int, Errno> {
+        let path = format!("/proc/{}/fd/{native_fd}\0", std::process::id());
+
+        // must free this, but will panic if borrowing the memory manager
+        let plugin_buffer = AllocdMem::<u8>::new(ctx, path.len());
+
+        let rv = ctx
+            .process
+            .memory_borrow_mut()
+            .copy_to_ptr(plugin_buffer.ptr(), path.as_bytes());
+        if let Err(e) = rv {
+            log::warn!("Unable to write shm path to allocated buffer: {e}");
+            plugin_buffer.free(ctx);
+            return Err(e);
+        }
+
+        let (process_ctx, thread) = ctx.split_thread();
+        let rv = thread.native_open(
+            &process_ctx,
+            plugin_buffer.ptr().ptr(),
+            oflag | libc::O_CLOEXEC,
+            0,
+        );
+
+        plugin_buffer.free(ctx);
+
+        rv
+    }
+}
+
+fn new_ipc_perm(key: kernel_key_t, flags: std::ffi::c_int) -> IpcPerm {
+    IpcPerm::new(
+        key,
+        nix::unistd::geteuid().as_raw(),
+        nix::unistd::getegid().as_raw(),
+        flags as u32,
+    )
+}
+
+fn to_linux_ipc_perm(perm: &IpcPerm) -> ipc_perm {
+    ipc_perm {
+        key: perm.key,
+        uid: perm.uid,
+        gid: perm.gid,
+        cuid: perm.cuid,
+        cgid: perm.cgid,
+        mode: perm.mode,
+        ..Default::default()
+    }
+}
+
+/// Apply the fields that `IPC_SET` can change. Only the permission bits of the mode can be changed.
+fn set_ipc_perm(perm: &mut IpcPerm, new: &ipc_perm) {
+    perm.uid = new.uid;
+    perm.gid = new.gid;
+    perm.mode = (perm.mode & !0o777) | (new.mode & 0o777);
+}
+
+/// Convert to seconds since the epoch, or 0 if the time isn't set.
+fn to_linux_time(time: Option<EmulatedTime>) -> std::ffi::c_long {
+    time.map(|x| x.duration_since(&EmulatedTime::UNIX_EPOCH).as_secs())
+        .unwrap_or(0)
+        .try_into()
+        .unwrap()
+}
@@ -1,5 +1,8 @@
 use linux_api::errno::Errno;
-use linux_api::time::{ClockId, ClockNanosleepFlags, ITimerId};
+use linux_api::signal::{sigevent, SigevNotify, Signal};
+use linux_api::time::{
+    itimerspec, kernel_timer_t, ClockId, ClockNanosleepFlags, ITimerId, TimerSettimeFlags,
+};
 use log::*;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
@@ -7,12 +10,18 @@ use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
 use crate::core::worker::Worker;
+use crate::host::posix_timer::PosixTimerNotify;
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::types::{SyscallError, SyscallResult};
+use crate::host::thread::ThreadId;
 use crate::host::timer::Timer;
 
 fn itimerval_from_timer(timer: &Timer) -> linux_api::time::itimerval {
-    linux_api::time::itimerval {
+    itimerval_new(timer.remaining_time(), timer.expire_interval())
+}
+
+fn itimerspec_from_timer(timer: &Timer) -> itimerspec {
+    itimerspec {
         it_interval: timer
             .expire_interval()
             .unwrap_or(SimulationTime::ZERO)
@@ -26,6 +35,67 @@ fn itimerval_from_timer(timer: &Timer) -> linux_api::time::itimerval {
     }
 }
 
+/// The notification method of the `sigevent` given to `timer_create`.
+fn posix_timer_notify(ctx: &SyscallContext, sev: &sigevent) -> Result<PosixTimerNotify, Errno> {
+    let thread = match sev.notify() {
+        Ok(SigevNotify::SIGEV_NONE) => return Ok(PosixTimerNotify::None),
+        // Like linux, handle `SIGEV_THREAD` like `SIGEV_SIGNAL`. libc implements `SIGEV_THREAD`
+        // itself using `SIGEV_THREAD_ID` and a helper thread.
+        Ok(SigevNotify::SIGEV_SIGNAL | SigevNotify::SIGEV_THREAD) => None,
+        Ok(SigevNotify::SIGEV_THREAD_ID) => {
+            let tid = ThreadId::try_from(sev.tid()).or(Err(Errno::EINVAL))?;
+            // the thread must be in the calling process
+            if ctx.objs.process.thread_borrow(tid).is_none() {
+                debug!("Thread {tid:?} isn't in the calling process");
+                return Err(Errno::EINVAL);
+            }
+            Some(tid)
+        }
+        Err(notify) => {
+            debug!("Invalid timer_create notification method {notify}");
+            return Err(Errno::EINVAL);
+        }
+    };
+
+    let signal = Signal::try_from(sev.sigev_signo).or(Err(Errno::EINVAL))?;
+    if signal.is_realtime() {
+        warn_once_then_debug!("Unimplemented signal {signal:?}");
+        return Err(Errno::ENOSYS);
+    }
+
+    // the `sigval` union is fully initialized since it was read from plugin memory
+    let sigval = unsafe { sev.sigev_value.sival_ptr } as usize;
+
+    Ok(PosixTimerNotify::Signal {
+        signal,
+        sigval,
+        thread,
+    })
+}
+
+fn itimerval_new(
+    value: Option<SimulationTime>,
+    interval: Option<SimulationTime>,
+) -> linux_api::time::itimerval {
+    linux_api::time::itimerval {
+        it_interval: interval.unwrap_or(SimulationTime::ZERO).try_into().unwrap(),
+        it_value: value.unwrap_or(SimulationTime::ZERO).try_into().unwrap(),
+    }
+}
+
+/// Get the current value of the interval timer `which`.
+fn itimerval_get(ctx: &SyscallContext, which: ITimerId) -> linux_api::time::itimerval {
+    let process = ctx.objs.process;
+    match which {
+        ITimerId::ITIMER_REAL => itimerval_from_timer(&process.realtime_timer_borrow()),
+        ITimerId::ITIMER_VIRTUAL | ITimerId::ITIMER_PROF => {
+            let cpu_time = process.itimer_cpu_time(ctx.objs.host, which);
+            let timer = process.cpu_timer_borrow_mut(which);
+            itimerval_new(timer.remaining_time(cpu_time), timer.expire_interval())
+        }
+    }
+}
+
 impl SyscallHandler {
     #[log_syscall(/* rv */ std::ffi::c_int, /* which */ linux_api::time::ITimerId, /*curr_value*/ *const std::ffi::c_void)]
     pub fn getitimer(
@@ -38,12 +108,7 @@ impl SyscallHandler {
             return Err(Errno::EINVAL.into());
         };
 
-        if which != ITimerId::ITIMER_REAL {
-            warn_once_then_debug!("Timer type {which:?} unsupported");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let itimerval = itimerval_from_timer(&ctx.objs.process.realtime_timer_borrow());
+        let itimerval = itimerval_get(ctx, which);
         ctx.objs
             .process
             .memory_borrow_mut()
@@ -65,13 +130,8 @@ impl SyscallHandler {
             return Err(Errno::EINVAL.into());
         };
 
-        if which != ITimerId::ITIMER_REAL {
-            warn_once_then_debug!("Timer type {which:?} unsupported");
-            return Err(Errno::EINVAL.into());
-        }
-
         if !old_value_ptr.is_null() {
-            let itimerval = itimerval_from_timer(&ctx.objs.process.realtime_timer_borrow());
+            let itimerval = itimerval_get(ctx, which);
             ctx.objs
                 .process
                 .memory_borrow_mut()
@@ -83,20 +143,226 @@ impl SyscallHandler {
             SimulationTime::try_from(new_value.it_value).map_err(|_| Errno::EINVAL)?;
         let new_value_interval =
             SimulationTime::try_from(new_value.it_interval).map_err(|_| Errno::EINVAL)?;
+        let new_value_interval = new_value_interval
+            .is_positive()
+            .then_some(new_value_interval);
+
+        match which {
+            ITimerId::ITIMER_REAL => {
+                let mut timer = ctx.objs.process.realtime_timer_borrow_mut();
+                if new_value_value == SimulationTime::ZERO {
+                    timer.disarm();
+                } else {
+                    timer.arm(
+                        ctx.objs.host,
+                        Worker::current_time().unwrap() + new_value_value,
+                        new_value_interval,
+                    );
+                }
+            }
+            // These timers measure the process's modeled CPU time, which only advances when
+            // modeling the latency of unblocked syscalls. Expirations are noticed when the process
+            // makes a syscall that's handled by shadow.
+            ITimerId::ITIMER_VIRTUAL | ITimerId::ITIMER_PROF => {
+                let cpu_time = ctx.objs.process.itimer_cpu_time(ctx.objs.host, which);
+                let mut timer = ctx.objs.process.cpu_timer_borrow_mut(which);
+                if new_value_value == SimulationTime::ZERO {
+                    timer.disarm();
+                } else {
+                    timer.arm(cpu_time, new_value_value, new_value_interval);
+                }
+            }
+        }
+
+        Ok(0.into())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* clockid */ linux_api::time::ClockId,
+                  /* sevp */ *const linux_api::signal::sigevent,
+                  /* timerid */ *const kernel_timer_t)]
+    pub fn timer_create(
+        ctx: &mut SyscallContext,
+        clockid: linux_api::time::linux___kernel_clockid_t,
+        sevp: ForeignPtr<sigevent>,
+        timerid_ptr: ForeignPtr<kernel_timer_t>,
+    ) -> Result<(), SyscallError> {
+        let Ok(clockid) = ClockId::try_from(clockid) else {
+            debug!("Invalid clockid: {clockid}");
+            return Err(Errno::EINVAL.into());
+        };
+
+        match clockid {
+            // All of these are emulated time in Shadow.
+            ClockId::CLOCK_REALTIME
+            | ClockId::CLOCK_MONOTONIC
+            | ClockId::CLOCK_BOOTTIME
+            | ClockId::CLOCK_TAI
+            | ClockId::CLOCK_REALTIME_ALARM
+            | ClockId::CLOCK_BOOTTIME_ALARM => (),
+            ClockId::CLOCK_MONOTONIC_RAW
+            | ClockId::CLOCK_REALTIME_COARSE
+            | ClockId::CLOCK_MONOTONIC_COARSE => {
+                // Not supported in Linux.
+                debug!("Clock id {clockid:?} unsupported for timer_create.");
+                return Err(Errno::ENOTSUP.into());
+            }
+            ClockId::CLOCK_PROCESS_CPUTIME_ID | ClockId::CLOCK_THREAD_CPUTIME_ID => {
+                // Supported in Linux, not in Shadow.
+                warn_once_then_debug!("Clock id {clockid:?} unsupported in Shadow.");
+                return Err(Errno::ENOTSUP.into());
+            }
+            ClockId::CLOCK_SGI_CYCLE => {
+                debug!("Unknown clock id {clockid:?}.");
+                return Err(Errno::EINVAL.into());
+            }
+        }
+
+        let sev = if sevp.is_null() {
+            None
+        } else {
+            Some(ctx.objs.process.memory_borrow().read(sevp)?)
+        };
+
+        // timer_create(2): "Specifying sevp as NULL is equivalent to specifying a pointer to a
+        // sigevent structure in which sigev_notify is SIGEV_SIGNAL, sigev_signo is SIGALRM, and
+        // sigev_value.sival_int is the timer ID."
+        let notify = match sev {
+            Some(sev) => Some(posix_timer_notify(ctx, &sev)?),
+            None => None,
+        };
 
-        if new_value_value == SimulationTime::ZERO {
-            ctx.objs.process.realtime_timer_borrow_mut().disarm();
+        let timer_id = ctx
+            .objs
+            .process
+            .create_posix_timer(|timer_id| {
+                notify.unwrap_or(PosixTimerNotify::Signal {
+                    signal: Signal::SIGALRM,
+                    sigval: timer_id as usize,
+                    thread: None,
+                })
+            })
+            .ok_or(Errno::EAGAIN)?;
+
+        if let Err(e) = ctx
+            .objs
+            .process
+            .memory_borrow_mut()
+            .write(timerid_ptr, &timer_id)
+        {
+            ctx.objs.process.posix_timers_borrow_mut().remove(timer_id);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* timerid */ kernel_timer_t,
+                  /* flags */ linux_api::time::TimerSettimeFlags,
+                  /* new_value */ *const std::ffi::c_void, /* old_value */ *const std::ffi::c_void)]
+    pub fn timer_settime(
+        ctx: &mut SyscallContext,
+        timerid: kernel_timer_t,
+        flags: std::ffi::c_int,
+        new_value_ptr: ForeignPtr<itimerspec>,
+        old_value_ptr: ForeignPtr<itimerspec>,
+    ) -> Result<(), SyscallError> {
+        // like linux, ignore unknown flags
+        let flags = TimerSettimeFlags::from_bits_truncate(flags);
+
+        if new_value_ptr.is_null() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let new_value = ctx.objs.process.memory_borrow().read(new_value_ptr)?;
+        let value = SimulationTime::try_from(new_value.it_value).or(Err(Errno::EINVAL))?;
+        let interval = SimulationTime::try_from(new_value.it_interval).or(Err(Errno::EINVAL))?;
+
+        let old_value = {
+            let timers = ctx.objs.process.posix_timers_borrow();
+            let timer = timers.get(timerid).ok_or(Errno::EINVAL)?;
+            itimerspec_from_timer(timer.timer())
+        };
+
+        if !old_value_ptr.is_null() {
+            ctx.objs
+                .process
+                .memory_borrow_mut()
+                .write(old_value_ptr, &old_value)?;
+        }
+
+        let mut timers = ctx.objs.process.posix_timers_borrow_mut();
+        let timer = timers.get_mut(timerid).unwrap();
+        timer.reset_overrun();
+
+        if value.is_zero() {
+            // A value of 0 disarms the timer; it_interval is ignored.
+            timer.timer_mut().disarm();
         } else {
-            ctx.objs.process.realtime_timer_borrow_mut().arm(
+            let now = Worker::current_time().unwrap();
+            let base = if flags.contains(TimerSettimeFlags::TIMER_ABSTIME) {
+                EmulatedTime::UNIX_EPOCH
+            } else {
+                now
+            };
+            // An absolute time in the past expires immediately.
+            let expire_time = EmulatedTime::max(base + value, now);
+            timer.timer_mut().arm(
                 ctx.objs.host,
-                Worker::current_time().unwrap() + new_value_value,
-                new_value_interval
-                    .is_positive()
-                    .then_some(new_value_interval),
+                expire_time,
+                interval.is_positive().then_some(interval),
             );
         }
 
-        Ok(0.into())
+        Ok(())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* timerid */ kernel_timer_t,
+                  /* curr_value */ *const std::ffi::c_void)]
+    pub fn timer_gettime(
+        ctx: &mut SyscallContext,
+        timerid: kernel_timer_t,
+        curr_value_ptr: ForeignPtr<itimerspec>,
+    ) -> Result<(), SyscallError> {
+        let curr_value = {
+            let timers = ctx.objs.process.posix_timers_borrow();
+            let timer = timers.get(timerid).ok_or(Errno::EINVAL)?;
+            itimerspec_from_timer(timer.timer())
+        };
+
+        ctx.objs
+            .process
+            .memory_borrow_mut()
+            .write(curr_value_ptr, &curr_value)?;
+
+        Ok(())
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* timerid */ kernel_timer_t)]
+    pub fn timer_getoverrun(
+        ctx: &mut SyscallContext,
+        timerid: kernel_timer_t,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let overrun = ctx
+            .objs
+            .process
+            .posix_timer_overrun(ctx.objs.host, timerid)
+            .ok_or(Errno::EINVAL)?;
+        Ok(overrun)
+    }
+
+    #[log_syscall(/* rv */ std::ffi::c_int, /* timerid */ kernel_timer_t)]
+    pub fn timer_delete(
+        ctx: &mut SyscallContext,
+        timerid: kernel_timer_t,
+    ) -> Result<(), SyscallError> {
+        // Dropping the timer disarms it. Like linux, a signal that it sent that's still pending
+        // will still be delivered.
+        ctx.objs
+            .process
+            .posix_timers_borrow_mut()
+            .remove(timerid)
+            .ok_or(Errno::EINVAL)?;
+        Ok(())
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int, /* clock_id */ linux_api::time::ClockId,
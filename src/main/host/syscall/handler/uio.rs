@@ -493,6 +493,8 @@ impl SyscallHandler {
                 addr: None,
                 iovs,
                 control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
+                rights: Vec::new(),
+                creds: matches!(socket, Socket::Unix(_)).then(|| ctx.objs.process.ucred()),
                 flags: 0,
             };
 
@@ -17,7 +17,11 @@ use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::pipe;
 use crate::host::descriptor::shared_buf::SharedBuf;
-use crate::host::descriptor::{CompatFile, Descriptor, File, FileMode, FileStatus, OpenFile};
+use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::descriptor::socket::Socket;
+use crate::host::descriptor::{
+    CompatFile, Descriptor, File, FileMode, FileState, FileStatus, OpenFile,
+};
 use crate::host::process::{Process, ProcessId};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::io::{read_cstring_vec, IoVec};
@@ -31,6 +35,12 @@ impl SyscallHandler {
     pub fn close(ctx: &mut SyscallContext, fd: std::ffi::c_int) -> SyscallResult {
         trace!("Trying to close fd {}", fd);
 
+        // we only block after the descriptor was closed (see below), so if we were blocked the
+        // close has already completed
+        if ctx.objs.thread.syscall_condition().is_some() {
+            return Ok(0.into());
+        }
+
         let fd = fd.try_into().or(Err(linux_api::errno::Errno::EBADF))?;
 
         // according to "man 2 close", in Linux any errors that may occur will happen after the fd is
@@ -43,13 +53,61 @@ impl SyscallHandler {
             .deregister_descriptor(fd)
             .ok_or(linux_api::errno::Errno::EBADF)?;
 
+        ctx.objs
+            .host
+            .file_lock_table_borrow_mut()
+            .release_on_close(ctx.objs.process.id(), desc.file());
+
+        let file = match desc.file() {
+            CompatFile::New(file) => Some(file.inner_file().clone()),
+            CompatFile::Legacy(_) => None,
+        };
+
         // if there are still valid descriptors to the open file, close() will do nothing
         // and return None
-        crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+        let Some(rv) = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
             CallbackQueue::queue_and_run(|cb_queue| desc.close(ctx.objs.host, cb_queue))
-                .unwrap_or(Ok(()))
-                .map(|()| 0.into())
-        })
+        }) else {
+            return Ok(0.into());
+        };
+        rv?;
+
+        // like linux, closing a tcp socket with a non-zero linger timeout blocks until its
+        // remaining data has been sent and acknowledged, or until the timeout expires
+        if let Some(file @ File::Socket(Socket::Inet(InetSocket::LegacyTcp(tcp)))) = &file {
+            let tcp = tcp.borrow();
+            if let Some(linger) = tcp.linger().filter(|x| *x != 0) {
+                if !tcp.state().contains(FileState::SOCKET_DRAINED) {
+                    // the descriptor has already been closed, so if there's a signal pending we
+                    // return success rather than letting the syscall handler turn the block into
+                    // an `EINTR`
+                    let is_unblocked_signal_pending = ctx.objs.thread.unblocked_signal_pending(
+                        ctx.objs.process,
+                        &ctx.objs.host.shim_shmem_lock_borrow().unwrap(),
+                    );
+                    if is_unblocked_signal_pending {
+                        return Ok(0.into());
+                    }
+
+                    let mut err = SyscallError::new_blocked_on_file(
+                        file.clone(),
+                        FileState::SOCKET_DRAINED,
+                        /* restartable= */ false,
+                    );
+
+                    // a negative timeout waits indefinitely
+                    if let Ok(linger) = u64::try_from(linger) {
+                        let timeout =
+                            Worker::current_time().unwrap() + SimulationTime::from_secs(linger);
+                        err.blocked_condition().unwrap().set_timeout(Some(timeout));
+                    }
+
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(0.into())
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int, /* oldfd */ std::ffi::c_int)]
@@ -92,6 +150,11 @@ impl SyscallHandler {
 
         // close the replaced descriptor
         if let Some(replaced_desc) = replaced_desc {
+            ctx.objs
+                .host
+                .file_lock_table_borrow_mut()
+                .release_on_close(ctx.objs.process.id(), replaced_desc.file());
+
             // from 'man 2 dup2': "If newfd was open, any errors that would have been reported at
             // close(2) time are lost"
             CallbackQueue::queue_and_run(|cb_queue| replaced_desc.close(ctx.objs.host, cb_queue));
@@ -147,6 +210,11 @@ impl SyscallHandler {
 
         // close the replaced descriptor
         if let Some(replaced_desc) = replaced_desc {
+            ctx.objs
+                .host
+                .file_lock_table_borrow_mut()
+                .release_on_close(ctx.objs.process.id(), replaced_desc.file());
+
             // from 'man 2 dup3': "If newfd was open, any errors that would have been reported at
             // close(2) time are lost"
             CallbackQueue::queue_and_run(|cb_queue| replaced_desc.close(ctx.objs.host, cb_queue));
@@ -190,6 +190,46 @@ impl From<ForeignArrayPtr<u8>> for IoVec {
     }
 }
 
+/// Returns the [`IoVec`] buffers that remain after skipping the first `offset` bytes of `iovs`.
+pub fn iovecs_after(iovs: &[IoVec], mut offset: usize) -> Vec<IoVec> {
+    let mut remaining = Vec::with_capacity(iovs.len());
+
+    for iov in iovs {
+        if offset >= iov.len {
+            offset -= iov.len;
+            continue;
+        }
+
+        remaining.push(IoVec {
+            base: iov.base.add(offset),
+            len: iov.len - offset,
+        });
+        offset = 0;
+    }
+
+    remaining
+}
+
+/// Returns the [`IoVec`] buffers that contain the first `len` bytes of `iovs`.
+pub fn iovecs_before(iovs: &[IoVec], mut len: usize) -> Vec<IoVec> {
+    let mut remaining = Vec::with_capacity(iovs.len());
+
+    for iov in iovs {
+        if len == 0 {
+            break;
+        }
+
+        let iov_len = std::cmp::min(iov.len, len);
+        remaining.push(IoVec {
+            base: iov.base,
+            len: iov_len,
+        });
+        len -= iov_len;
+    }
+
+    remaining
+}
+
 /// A reader which reads data from [`IoVec`] buffers of plugin memory. If an error occurs while
 /// reading (for example if an `IoVec` points to an invalid memory address), the error will be
 /// returned only if no bytes have yet been read. If an error occurs after some bytes have already
@@ -418,6 +458,109 @@ fn msghdr_to_rust(msg: &libc::msghdr, mem: &MemoryManager) -> Result<MsgHdr, Err
     })
 }
 
+/// Analogous to a [`libc::cmsghdr`] and its data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlMessage {
+    pub level: libc::c_int,
+    pub ty: libc::c_int,
+    pub data: Vec<u8>,
+}
+
+// we read and write the header fields individually
+static_assertions::assert_eq_size!(libc::cmsghdr, (libc::size_t, libc::c_int, libc::c_int));
+
+/// The length of a [`libc::cmsghdr`].
+const CMSG_HDR_LEN: usize = std::mem::size_of::<libc::cmsghdr>();
+
+/// Analogous to `CMSG_ALIGN`.
+fn cmsg_align(len: usize) -> usize {
+    let align = std::mem::size_of::<libc::c_long>();
+    len.div_ceil(align) * align
+}
+
+/// Read the control messages from a plugin's control buffer (the `msg_control` and
+/// `msg_controllen` fields of a [`libc::msghdr`]). Like Linux, trailing bytes that are too short
+/// for a header are ignored.
+pub fn read_cmsgs(
+    mem: &MemoryManager,
+    control: ForeignArrayPtr<u8>,
+) -> Result<Vec<ControlMessage>, Errno> {
+    if control.is_null() || control.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut buf = vec![0u8; control.len()];
+    mem.copy_from_ptr(&mut buf, control)?;
+
+    let mut cmsgs = Vec::new();
+    let mut remaining = &buf[..];
+
+    while remaining.len() >= CMSG_HDR_LEN {
+        let (len, rest) = remaining.split_at(std::mem::size_of::<libc::size_t>());
+        let (level, rest) = rest.split_at(std::mem::size_of::<libc::c_int>());
+        let (ty, _) = rest.split_at(std::mem::size_of::<libc::c_int>());
+
+        let len = libc::size_t::from_ne_bytes(len.try_into().unwrap());
+        let level = libc::c_int::from_ne_bytes(level.try_into().unwrap());
+        let ty = libc::c_int::from_ne_bytes(ty.try_into().unwrap());
+
+        if len < CMSG_HDR_LEN || len > remaining.len() {
+            return Err(Errno::EINVAL);
+        }
+
+        cmsgs.push(ControlMessage {
+            level,
+            ty,
+            data: remaining[CMSG_HDR_LEN..len].to_vec(),
+        });
+
+        remaining = &remaining[std::cmp::min(cmsg_align(len), remaining.len())..];
+    }
+
+    Ok(cmsgs)
+}
+
+/// Write control messages to a plugin's control buffer (the `msg_control` and `msg_controllen`
+/// fields of a [`libc::msghdr`]). Returns the number of bytes written, and whether any control
+/// messages didn't fit in the buffer (`MSG_CTRUNC`).
+pub fn write_cmsgs(
+    mem: &mut MemoryManager,
+    control: ForeignArrayPtr<u8>,
+    cmsgs: &[ControlMessage],
+) -> Result<(libc::size_t, bool), Errno> {
+    let capacity = if control.is_null() { 0 } else { control.len() };
+
+    let mut buf = Vec::new();
+    let mut truncated = false;
+
+    for cmsg in cmsgs {
+        let len = CMSG_HDR_LEN + cmsg.data.len();
+
+        // unlike linux we don't write partial control messages
+        if buf.len() + len > capacity {
+            truncated = true;
+            break;
+        }
+
+        buf.extend_from_slice(&libc::size_t::to_ne_bytes(len));
+        buf.extend_from_slice(&cmsg.level.to_ne_bytes());
+        buf.extend_from_slice(&cmsg.ty.to_ne_bytes());
+        buf.extend_from_slice(&cmsg.data);
+
+        // pad the control message, unless it's the last one that fits
+        buf.resize(
+            std::cmp::min(buf.len() - len + cmsg_align(len), capacity),
+            0,
+        );
+    }
+
+    if !buf.is_empty() {
+        mem.copy_to_ptr(control.slice(..buf.len()), &buf)?;
+    }
+
+    Ok((buf.len(), truncated))
+}
+
 /// Read an array of strings, each of which with max length
 /// `linux_api::limits::ARG_MAX`.  e.g. suitable for `execve`'s argument and
 /// environment string lists.
@@ -42,6 +42,18 @@ impl Trigger {
         })
     }
 
+    /// A trigger for a wakeup on `futex`. The syscall condition takes its own reference to the
+    /// futex, so `futex` only needs to be valid for the duration of this call.
+    pub fn from_futex(futex: *mut c::Futex) -> Self {
+        assert!(!futex.is_null());
+
+        Self(c::Trigger {
+            type_: c::_TriggerType_TRIGGER_FUTEX,
+            object: c::TriggerObject { as_futex: futex },
+            state: FileState::FUTEX_WAKEUP,
+        })
+    }
+
     pub fn child() -> Self {
         Self(c::Trigger {
             type_: c::_TriggerType_TRIGGER_CHILD,
@@ -196,6 +196,8 @@ simple_display_impl!(u8, u16, u32, u64, usize);
 
 deref_pointer_impl!(i8, i16, i32, i64, isize);
 deref_pointer_impl!(u8, u16, u32, u64, usize);
+deref_pointer_impl!(linux_api::mqueue::mq_attr);
+deref_pointer_impl!(linux_api::signal::sigevent);
 deref_pointer_impl!(linux_api::sched::clone_args);
 deref_pointer_impl!(linux_api::time::timespec);
 deref_pointer_impl!(linux_api::time::kernel_timespec);
@@ -221,10 +223,12 @@ simple_display_impl!(linux_api::prctl::PrctlOp);
 simple_display_impl!(linux_api::socket::AddressFamily);
 
 bitflags_impl!(linux_api::fcntl::OFlag);
+bitflags_impl!(linux_api::fcntl::FlockOperation);
 bitflags_impl!(linux_api::mman::ProtFlags);
 bitflags_impl!(linux_api::mman::MapFlags);
 bitflags_impl!(linux_api::mman::MRemapFlags);
 bitflags_impl!(linux_api::time::ClockNanosleepFlags);
+bitflags_impl!(linux_api::time::TimerSettimeFlags);
 
 fn fmt_buffer(
     f: &mut std::fmt::Formatter<'_>,
@@ -222,6 +222,13 @@ impl SyscallError {
         })
     }
 
+    pub fn new_blocked_on_futex(futex: *mut c::Futex, restartable: bool) -> Self {
+        Self::Blocked(Blocked {
+            condition: SysCallCondition::new(Trigger::from_futex(futex)),
+            restartable,
+        })
+    }
+
     pub fn new_blocked_on_child(restartable: bool) -> Self {
         Self::Blocked(Blocked {
             condition: SysCallCondition::new(Trigger::child()),
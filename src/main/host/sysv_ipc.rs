@@ -0,0 +1,881 @@
+//! System V IPC objects (`shmget()`, `semget()`) shared by all processes on a host.
+//!
+//! Each host has its own IPC namespace. Objects are identified by an id that encodes a slot index
+//! and a sequence number, as in Linux, so that a stale id for a removed object is unlikely to
+//! refer to a newer object that reused its slot.
+//!
+//! Shared memory segments are backed by a memfd owned by shadow. Managed processes map the memfd
+//! through `/proc/<shadow-pid>/fd/<fd>` when they attach a segment, so all processes that attach
+//! the segment share the same memory.
+//!
+//! We don't emulate IPC permission checks; all managed processes on a host run as the same user.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CString;
+use std::fs::File;
+
+use linux_api::errno::Errno;
+use linux_api::ipc::{kernel_key_t, sembuf, IPC_NOWAIT, IPC_PRIVATE, SEM_UNDO};
+use nix::sys::memfd::MemFdCreateFlag;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::syscall_types::ManagedPhysicalMemoryAddr;
+
+use crate::cshadow as c;
+use crate::host::futex_table::FutexRef;
+use crate::host::process::ProcessId;
+use crate::host::thread::ThreadId;
+use crate::utility::ObjectCounter;
+
+/// Ownership and permissions of an IPC object (`struct ipc_perm`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IpcPerm {
+    pub key: kernel_key_t,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    /// The lower 9 bits are the access permissions. Shared memory segments also store the
+    /// `SHM_DEST` and `SHM_LOCKED` flags here.
+    pub mode: u32,
+}
+
+impl IpcPerm {
+    pub fn new(key: kernel_key_t, uid: u32, gid: u32, mode: u32) -> Self {
+        Self {
+            key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode: mode & 0o777,
+        }
+    }
+}
+
+struct IpcEntry<T> {
+    seq: u32,
+    object: T,
+}
+
+/// The objects of one IPC type (like Linux's `struct ipc_ids`).
+struct IpcIds<T> {
+    /// Objects indexed by their slot.
+    entries: BTreeMap<usize, IpcEntry<T>>,
+    /// Ids of the objects that can be found by key. Doesn't contain `IPC_PRIVATE` objects or
+    /// removed shared memory segments that are still attached.
+    keys: HashMap<kernel_key_t, i32>,
+    next_seq: u32,
+    max_objects: usize,
+}
+
+impl<T> IpcIds<T> {
+    /// Maximum sequence number (`IPCMNI_SEQ_MAX`).
+    const SEQ_MAX: u32 = (i32::MAX as u32) / (linux_api::ipc::IPCMNI as u32);
+
+    fn new(max_objects: usize) -> Self {
+        assert!(max_objects <= linux_api::ipc::IPCMNI);
+        Self {
+            entries: BTreeMap::new(),
+            keys: HashMap::new(),
+            next_seq: 0,
+            max_objects,
+        }
+    }
+
+    fn slot_and_seq(id: i32) -> Option<(usize, u32)> {
+        let id = u32::try_from(id).ok()?;
+        let ipcmni = linux_api::ipc::IPCMNI as u32;
+        Some(((id % ipcmni) as usize, id / ipcmni))
+    }
+
+    fn get(&self, id: i32) -> Option<&T> {
+        let (slot, seq) = Self::slot_and_seq(id)?;
+        let entry = self.entries.get(&slot)?;
+        (entry.seq == seq).then_some(&entry.object)
+    }
+
+    fn get_mut(&mut self, id: i32) -> Option<&mut T> {
+        let (slot, seq) = Self::slot_and_seq(id)?;
+        let entry = self.entries.get_mut(&slot)?;
+        (entry.seq == seq).then_some(&mut entry.object)
+    }
+
+    fn find_key(&self, key: kernel_key_t) -> Option<i32> {
+        self.keys.get(&key).copied()
+    }
+
+    /// Add a new object, returning its id.
+    fn insert(&mut self, key: kernel_key_t, object: T) -> Result<i32, Errno> {
+        if self.entries.len() >= self.max_objects {
+            return Err(Errno::ENOSPC);
+        }
+
+        // use the lowest free slot, like linux
+        let slot = (0..).find(|slot| !self.entries.contains_key(slot)).unwrap();
+
+        let seq = self.next_seq;
+        self.next_seq = if seq >= Self::SEQ_MAX { 0 } else { seq + 1 };
+
+        let id = seq as usize * linux_api::ipc::IPCMNI + slot;
+        let id = i32::try_from(id).unwrap();
+
+        self.entries.insert(slot, IpcEntry { seq, object });
+        if key != IPC_PRIVATE {
+            self.keys.insert(key, id);
+        }
+
+        Ok(id)
+    }
+
+    /// Stop the object from being found by its key.
+    fn forget_key(&mut self, id: i32, key: kernel_key_t) {
+        if self.keys.get(&key) == Some(&id) {
+            self.keys.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, id: i32, key: kernel_key_t) -> Option<T> {
+        let (slot, seq) = Self::slot_and_seq(id)?;
+        if self.entries.get(&slot)?.seq != seq {
+            return None;
+        }
+        self.forget_key(id, key);
+        self.entries.remove(&slot).map(|x| x.object)
+    }
+
+    fn ids(&self) -> Vec<i32> {
+        self.entries
+            .iter()
+            .map(|(slot, entry)| {
+                i32::try_from(entry.seq as usize * linux_api::ipc::IPCMNI + slot).unwrap()
+            })
+            .collect()
+    }
+
+    fn reclaim_memory(&mut self) {
+        crate::utility::shrink_if_sparse(&mut self.keys);
+    }
+}
+
+/// A System V shared memory segment.
+pub struct ShmSegment {
+    pub perm: IpcPerm,
+    /// The size requested when the segment was created.
+    pub size: usize,
+    /// The number of current attachments.
+    pub nattch: usize,
+    pub cpid: ProcessId,
+    /// The last process to attach or detach the segment.
+    pub lpid: Option<ProcessId>,
+    pub atime: Option<EmulatedTime>,
+    pub dtime: Option<EmulatedTime>,
+    pub ctime: EmulatedTime,
+    file: File,
+}
+
+impl ShmSegment {
+    /// The memory file that backs the segment.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// The number of bytes that are mapped when the segment is attached.
+    pub fn mapped_size(&self) -> usize {
+        round_up_to_page(self.size)
+    }
+
+    /// Whether the segment has been marked for destruction with `IPC_RMID`.
+    pub fn is_removed(&self) -> bool {
+        self.perm.mode & linux_api::ipc::SHM_DEST != 0
+    }
+}
+
+fn round_up_to_page(size: usize) -> usize {
+    // can't overflow since segment sizes are at most SHMMAX
+    let page_size = linux_api::ipc::SHMLBA;
+    (size + page_size - 1) / page_size * page_size
+}
+
+/// A segment attached by a process.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct ShmAttachment {
+    pid: ProcessId,
+    shmid: i32,
+    addr: usize,
+    len: usize,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Semaphore {
+    pub val: i32,
+    /// The last process to operate on the semaphore.
+    pub pid: Option<ProcessId>,
+}
+
+/// A System V semaphore set.
+pub struct SemSet {
+    pub perm: IpcPerm,
+    pub sems: Vec<Semaphore>,
+    pub otime: Option<EmulatedTime>,
+    pub ctime: EmulatedTime,
+    /// Adjustments to apply when each process exits (`SEM_UNDO`).
+    undo: BTreeMap<ProcessId, Vec<i32>>,
+    /// Threads blocked in `semop` on this set wait on this futex. Created lazily.
+    waiters: Option<FutexRef>,
+}
+
+impl SemSet {
+    fn wake_waiters(&mut self) {
+        // Waiters will retry their operations, and will wait again if they still can't proceed.
+        if let Some(waiters) = self.waiters.take() {
+            waiters.wake(libc::c_uint::MAX);
+        }
+    }
+
+    /// Clear the `SEM_UNDO` adjustments of all processes for the semaphore `sem_num`, or for all
+    /// semaphores if `None`.
+    fn clear_undo(&mut self, sem_num: Option<usize>) {
+        for adj in self.undo.values_mut() {
+            match sem_num {
+                Some(sem_num) => adj[sem_num] = 0,
+                None => adj.fill(0),
+            }
+        }
+    }
+}
+
+/// The reason that a `semop` could not complete.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SemopError {
+    Errno(Errno),
+    /// The operation must wait for the semaphore `sem_num` to increase, or if `zero` is true, to
+    /// become zero.
+    WouldBlock {
+        sem_num: usize,
+        zero: bool,
+    },
+}
+
+impl From<Errno> for SemopError {
+    fn from(e: Errno) -> Self {
+        Self::Errno(e)
+    }
+}
+
+/// A thread blocked in `semop`, used for `GETNCNT` and `GETZCNT`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct SemWaiter {
+    pid: ProcessId,
+    semid: i32,
+    sem_num: usize,
+    zero: bool,
+}
+
+/// A per-host table of System V shared memory segments and semaphore sets.
+pub struct SysvIpcTable {
+    shm: IpcIds<ShmSegment>,
+    shm_attachments: Vec<ShmAttachment>,
+    sem: IpcIds<SemSet>,
+    sem_waiters: HashMap<ThreadId, SemWaiter>,
+    _counter: ObjectCounter,
+}
+
+impl SysvIpcTable {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            shm: IpcIds::new(linux_api::ipc::SHMMNI),
+            shm_attachments: Vec::new(),
+            sem: IpcIds::new(linux_api::ipc::SEMMNI),
+            sem_waiters: HashMap::new(),
+            _counter: ObjectCounter::new("SysvIpcTable"),
+        }
+    }
+
+    /// Find or create a shared memory segment (`shmget`).
+    pub fn shm_get(
+        &mut self,
+        perm: IpcPerm,
+        size: usize,
+        flags: i32,
+        pid: ProcessId,
+        now: EmulatedTime,
+    ) -> Result<i32, Errno> {
+        if perm.key != IPC_PRIVATE {
+            if let Some(id) = self.shm.find_key(perm.key) {
+                if flags & linux_api::ipc::IPC_CREAT != 0 && flags & linux_api::ipc::IPC_EXCL != 0 {
+                    return Err(Errno::EEXIST);
+                }
+                if size > self.shm.get(id).unwrap().size {
+                    return Err(Errno::EINVAL);
+                }
+                return Ok(id);
+            }
+
+            if flags & linux_api::ipc::IPC_CREAT == 0 {
+                return Err(Errno::ENOENT);
+            }
+        }
+
+        if !(linux_api::ipc::SHMMIN..=linux_api::ipc::SHMMAX).contains(&size) {
+            return Err(Errno::EINVAL);
+        }
+
+        let name = CString::new(format!("shadow_sysv_shm_{}", perm.key)).unwrap();
+        let file = nix::sys::memfd::memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC)
+            .map_err(|e| Errno::try_from(e as i32).unwrap())?;
+        let file = File::from(file);
+
+        file.set_len(round_up_to_page(size).try_into().or(Err(Errno::ENOMEM))?)
+            .or(Err(Errno::ENOMEM))?;
+
+        let segment = ShmSegment {
+            perm,
+            size,
+            nattch: 0,
+            cpid: pid,
+            lpid: None,
+            atime: None,
+            dtime: None,
+            ctime: now,
+            file,
+        };
+
+        self.shm.insert(perm.key, segment)
+    }
+
+    pub fn shm_segment(&self, shmid: i32) -> Result<&ShmSegment, Errno> {
+        self.shm.get(shmid).ok_or(Errno::EINVAL)
+    }
+
+    pub fn shm_segment_mut(&mut self, shmid: i32) -> Result<&mut ShmSegment, Errno> {
+        self.shm.get_mut(shmid).ok_or(Errno::EINVAL)
+    }
+
+    /// Record that process `pid` mapped the segment at `addr` (`shmat`).
+    pub fn shm_attach(
+        &mut self,
+        shmid: i32,
+        pid: ProcessId,
+        addr: usize,
+        len: usize,
+        now: EmulatedTime,
+    ) -> Result<(), Errno> {
+        let segment = self.shm.get_mut(shmid).ok_or(Errno::EINVAL)?;
+        segment.nattch += 1;
+        segment.lpid = Some(pid);
+        segment.atime = Some(now);
+
+        // the mapping replaced any attachment that was previously at this address (SHM_REMAP)
+        self.shm_detach(pid, addr, now);
+
+        self.shm_attachments.push(ShmAttachment {
+            pid,
+            shmid,
+            addr,
+            len,
+        });
+
+        Ok(())
+    }
+
+    /// Remove the attachment of process `pid` at `addr` (`shmdt`). Returns the length of the
+    /// attachment's mapping, or `None` if there is no attachment at `addr`.
+    pub fn shm_detach(&mut self, pid: ProcessId, addr: usize, now: EmulatedTime) -> Option<usize> {
+        let idx = self
+            .shm_attachments
+            .iter()
+            .position(|x| x.pid == pid && x.addr == addr)?;
+        let attachment = self.shm_attachments.remove(idx);
+        self.shm_release_attachment(attachment, now);
+        Some(attachment.len)
+    }
+
+    /// Mark a segment for destruction (`IPC_RMID`). The segment is destroyed after it's detached by
+    /// all processes.
+    pub fn shm_remove(&mut self, shmid: i32) -> Result<(), Errno> {
+        let segment = self.shm.get_mut(shmid).ok_or(Errno::EINVAL)?;
+        let key = segment.perm.key;
+
+        // linux changes the key of removed segments to IPC_PRIVATE
+        segment.perm.mode |= linux_api::ipc::SHM_DEST;
+        segment.perm.key = IPC_PRIVATE;
+        let destroy = segment.nattch == 0;
+
+        self.shm.forget_key(shmid, key);
+        if destroy {
+            self.shm.remove(shmid, IPC_PRIVATE);
+        }
+
+        Ok(())
+    }
+
+    /// Copy the attachments of process `parent` to its new child process `child`. `fork(2)`: "The
+    /// child inherits copies of the parent's set of attached shared memory segments."
+    pub fn shm_fork(&mut self, parent: ProcessId, child: ProcessId) {
+        let inherited: Vec<ShmAttachment> = self
+            .shm_attachments
+            .iter()
+            .filter(|x| x.pid == parent)
+            .map(|x| ShmAttachment { pid: child, ..*x })
+            .collect();
+
+        for attachment in inherited {
+            if let Some(segment) = self.shm.get_mut(attachment.shmid) {
+                segment.nattch += 1;
+            }
+            self.shm_attachments.push(attachment);
+        }
+    }
+
+    /// Detach all segments attached by `pid`, whose address space no longer exists (for example
+    /// after an `exec`).
+    pub fn shm_detach_all(&mut self, pid: ProcessId, now: EmulatedTime) {
+        let (detached, remaining) = std::mem::take(&mut self.shm_attachments)
+            .into_iter()
+            .partition(|x| x.pid == pid);
+        self.shm_attachments = remaining;
+
+        for attachment in detached {
+            self.shm_release_attachment(attachment, now);
+        }
+    }
+
+    fn shm_release_attachment(&mut self, attachment: ShmAttachment, now: EmulatedTime) {
+        let Some(segment) = self.shm.get_mut(attachment.shmid) else {
+            return;
+        };
+
+        segment.nattch -= 1;
+        segment.lpid = Some(attachment.pid);
+        segment.dtime = Some(now);
+
+        if segment.nattch == 0 && segment.is_removed() {
+            self.shm.remove(attachment.shmid, IPC_PRIVATE);
+        }
+    }
+
+    /// Find or create a semaphore set (`semget`).
+    pub fn sem_get(
+        &mut self,
+        perm: IpcPerm,
+        nsems: usize,
+        flags: i32,
+        now: EmulatedTime,
+    ) -> Result<i32, Errno> {
+        if nsems > linux_api::ipc::SEMMSL {
+            return Err(Errno::EINVAL);
+        }
+
+        if perm.key != IPC_PRIVATE {
+            if let Some(id) = self.sem.find_key(perm.key) {
+                if flags & linux_api::ipc::IPC_CREAT != 0 && flags & linux_api::ipc::IPC_EXCL != 0 {
+                    return Err(Errno::EEXIST);
+                }
+                if nsems > self.sem.get(id).unwrap().sems.len() {
+                    return Err(Errno::EINVAL);
+                }
+                return Ok(id);
+            }
+
+            if flags & linux_api::ipc::IPC_CREAT == 0 {
+                return Err(Errno::ENOENT);
+            }
+        }
+
+        if nsems == 0 {
+            return Err(Errno::EINVAL);
+        }
+
+        let set = SemSet {
+            perm,
+            sems: vec![Semaphore::default(); nsems],
+            otime: None,
+            ctime: now,
+            undo: BTreeMap::new(),
+            waiters: None,
+        };
+
+        self.sem.insert(perm.key, set)
+    }
+
+    pub fn sem_set(&self, semid: i32) -> Result<&SemSet, Errno> {
+        self.sem.get(semid).ok_or(Errno::EINVAL)
+    }
+
+    pub fn sem_set_mut(&mut self, semid: i32) -> Result<&mut SemSet, Errno> {
+        self.sem.get_mut(semid).ok_or(Errno::EINVAL)
+    }
+
+    /// Atomically perform all of the operations in `ops` (`semop`). If any of the operations can't
+    /// be performed, none of them are.
+    pub fn sem_op(
+        &mut self,
+        semid: i32,
+        ops: &[sembuf],
+        pid: ProcessId,
+        now: EmulatedTime,
+    ) -> Result<(), SemopError> {
+        let set = self.sem.get_mut(semid).ok_or(Errno::EINVAL)?;
+        let nsems = set.sems.len();
+
+        if ops.iter().any(|op| usize::from(op.sem_num) >= nsems) {
+            return Err(Errno::EFBIG.into());
+        }
+
+        let uses_undo = ops.iter().any(|op| op.sem_flg & SEM_UNDO != 0);
+
+        // apply the operations to copies of the values and adjustments, then commit them if they
+        // all succeed
+        let mut vals: Vec<i32> = set.sems.iter().map(|x| x.val).collect();
+        let mut undo = uses_undo.then(|| {
+            set.undo
+                .get(&pid)
+                .cloned()
+                .unwrap_or_else(|| vec![0; nsems])
+        });
+
+        for op in ops {
+            let sem_num = usize::from(op.sem_num);
+            let val = vals[sem_num];
+
+            let would_block = |zero| {
+                if i32::from(op.sem_flg) & IPC_NOWAIT != 0 {
+                    SemopError::Errno(Errno::EAGAIN)
+                } else {
+                    SemopError::WouldBlock { sem_num, zero }
+                }
+            };
+
+            if op.sem_op == 0 {
+                if val != 0 {
+                    return Err(would_block(true));
+                }
+                continue;
+            }
+
+            let new_val = val + i32::from(op.sem_op);
+            if new_val < 0 {
+                return Err(would_block(false));
+            }
+            if new_val > linux_api::ipc::SEMVMX {
+                return Err(Errno::ERANGE.into());
+            }
+
+            if op.sem_flg & SEM_UNDO != 0 {
+                let adj = undo.as_mut().unwrap();
+                let new_adj = adj[sem_num] - i32::from(op.sem_op);
+                if !(-linux_api::ipc::SEMAEM - 1..=linux_api::ipc::SEMAEM).contains(&new_adj) {
+                    return Err(Errno::ERANGE.into());
+                }
+                adj[sem_num] = new_adj;
+            }
+
+            vals[sem_num] = new_val;
+        }
+
+        for op in ops {
+            let sem = &mut set.sems[usize::from(op.sem_num)];
+            sem.val = vals[usize::from(op.sem_num)];
+            sem.pid = Some(pid);
+        }
+
+        if let Some(undo) = undo {
+            set.undo.insert(pid, undo);
+        }
+
+        set.otime = Some(now);
+
+        if ops.iter().any(|op| op.sem_op != 0) {
+            set.wake_waiters();
+        }
+
+        Ok(())
+    }
+
+    /// Set the value of semaphore `sem_num`, or of all semaphores if `sem_num` is `None` (`SETVAL`
+    /// and `SETALL`). Clears the `SEM_UNDO` adjustments of all processes for those semaphores.
+    pub fn sem_set_vals(
+        &mut self,
+        semid: i32,
+        sem_num: Option<usize>,
+        vals: &[i32],
+        pid: ProcessId,
+        now: EmulatedTime,
+    ) -> Result<(), Errno> {
+        let set = self.sem.get_mut(semid).ok_or(Errno::EINVAL)?;
+
+        let range = match sem_num {
+            Some(sem_num) if sem_num >= set.sems.len() => return Err(Errno::EINVAL),
+            Some(sem_num) => sem_num..(sem_num + 1),
+            None => 0..set.sems.len(),
+        };
+        assert_eq!(range.len(), vals.len());
+
+        if vals
+            .iter()
+            .any(|val| !(0..=linux_api::ipc::SEMVMX).contains(val))
+        {
+            return Err(Errno::ERANGE);
+        }
+
+        for (sem, val) in set.sems[range].iter_mut().zip(vals) {
+            sem.val = *val;
+            sem.pid = Some(pid);
+        }
+
+        set.clear_undo(sem_num);
+        set.ctime = now;
+        set.wake_waiters();
+
+        Ok(())
+    }
+
+    /// Remove a semaphore set immediately (`IPC_RMID`). Threads waiting on the set are woken.
+    pub fn sem_remove(&mut self, semid: i32) -> Result<(), Errno> {
+        let key = self.sem_set(semid)?.perm.key;
+        let mut set = self.sem.remove(semid, key).unwrap();
+        set.wake_waiters();
+        self.sem_waiters.retain(|_, waiter| waiter.semid != semid);
+        Ok(())
+    }
+
+    /// Returns a futex that `tid` can block on until the semaphores in `semid` change, and records
+    /// the reason that it's waiting. The returned pointer is borrowed and is only valid until the
+    /// table is next modified.
+    pub fn sem_wait_queue(
+        &mut self,
+        semid: i32,
+        tid: ThreadId,
+        pid: ProcessId,
+        blocked_on: SemopError,
+    ) -> Result<*mut c::Futex, Errno> {
+        let SemopError::WouldBlock { sem_num, zero } = blocked_on else {
+            panic!("Waiting for a semop that didn't block: {blocked_on:?}");
+        };
+
+        let set = self.sem.get_mut(semid).ok_or(Errno::EINVAL)?;
+        let futex = set
+            .waiters
+            .get_or_insert_with(|| {
+                // The futex is never added to the host's futex table, so the address is only used
+                // for logging.
+                let addr = ManagedPhysicalMemoryAddr::from(semid as usize);
+                unsafe { FutexRef::new(c::futex_new(addr)) }
+            })
+            .ptr();
+
+        self.sem_waiters.insert(
+            tid,
+            SemWaiter {
+                pid,
+                semid,
+                sem_num,
+                zero,
+            },
+        );
+
+        Ok(futex)
+    }
+
+    /// Forget that `tid` was waiting in `semop`. Called whenever a thread (re)starts a `semop`.
+    pub fn sem_stop_waiting(&mut self, tid: ThreadId) {
+        self.sem_waiters.remove(&tid);
+    }
+
+    /// The number of threads waiting for the semaphore to increase (`GETNCNT`), or to become zero
+    /// if `zero` is true (`GETZCNT`).
+    pub fn sem_wait_count(&self, semid: i32, sem_num: usize, zero: bool) -> usize {
+        self.sem_waiters
+            .values()
+            .filter(|x| x.semid == semid && x.sem_num == sem_num && x.zero == zero)
+            .count()
+    }
+
+    /// Release the IPC resources held by an exiting process: detach its shared memory segments and
+    /// apply its semaphore adjustments (`SEM_UNDO`).
+    pub fn process_exit(&mut self, pid: ProcessId, now: EmulatedTime) {
+        self.shm_detach_all(pid, now);
+        self.sem_waiters.retain(|_, waiter| waiter.pid != pid);
+
+        // apply adjustments in a deterministic order
+        for semid in self.sem.ids() {
+            let set = self.sem.get_mut(semid).unwrap();
+            let Some(adj) = set.undo.remove(&pid) else {
+                continue;
+            };
+
+            let mut changed = false;
+            for (sem, adj) in set.sems.iter_mut().zip(adj) {
+                if adj == 0 {
+                    continue;
+                }
+                // linux clamps the adjusted value rather than failing
+                sem.val = (sem.val + adj).clamp(0, linux_api::ipc::SEMVMX);
+                sem.pid = Some(pid);
+                changed = true;
+            }
+
+            if changed {
+                set.wake_waiters();
+            }
+        }
+    }
+
+    /// Release memory that is no longer needed.
+    pub fn reclaim_memory(&mut self) {
+        self.shm.reclaim_memory();
+        self.sem.reclaim_memory();
+        crate::utility::shrink_if_sparse(&mut self.sem_waiters);
+        self.shm_attachments.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linux_api::ipc::{IPC_CREAT, IPC_EXCL};
+
+    use super::*;
+
+    const NOW: EmulatedTime = EmulatedTime::UNIX_EPOCH;
+
+    fn pid(x: u32) -> ProcessId {
+        ProcessId::try_from(x).unwrap()
+    }
+
+    fn perm(key: kernel_key_t) -> IpcPerm {
+        IpcPerm::new(key, 0, 0, 0o600)
+    }
+
+    fn op(sem_num: u16, sem_op: i16, sem_flg: i16) -> sembuf {
+        sembuf {
+            sem_num,
+            sem_op,
+            sem_flg,
+        }
+    }
+
+    fn vals(table: &SysvIpcTable, semid: i32) -> Vec<i32> {
+        table
+            .sem_set(semid)
+            .unwrap()
+            .sems
+            .iter()
+            .map(|x| x.val)
+            .collect()
+    }
+
+    #[test]
+    fn test_get_by_key() {
+        let mut table = SysvIpcTable::new();
+
+        assert_eq!(
+            table.sem_get(perm(5), 2, 0, NOW),
+            Err(Errno::ENOENT),
+            "key doesn't exist"
+        );
+
+        let id = table.sem_get(perm(5), 2, IPC_CREAT, NOW).unwrap();
+        assert_eq!(table.sem_get(perm(5), 0, 0, NOW), Ok(id));
+        assert_eq!(table.sem_get(perm(5), 3, 0, NOW), Err(Errno::EINVAL));
+        assert_eq!(
+            table.sem_get(perm(5), 2, IPC_CREAT | IPC_EXCL, NOW),
+            Err(Errno::EEXIST)
+        );
+
+        // private sets are always new
+        let private = table.sem_get(perm(IPC_PRIVATE), 1, 0, NOW).unwrap();
+        assert_ne!(private, id);
+
+        table.sem_remove(id).unwrap();
+        assert_eq!(table.sem_get(perm(5), 2, 0, NOW), Err(Errno::ENOENT));
+
+        // the slot is reused with a new sequence number
+        let new_id = table.sem_get(perm(5), 2, IPC_CREAT, NOW).unwrap();
+        assert_ne!(new_id, id);
+        assert_eq!(table.sem_set(id).err(), Some(Errno::EINVAL));
+    }
+
+    #[test]
+    fn test_semop_is_atomic() {
+        let mut table = SysvIpcTable::new();
+        let id = table.sem_get(perm(IPC_PRIVATE), 2, 0, NOW).unwrap();
+
+        table.sem_op(id, &[op(0, 2, 0)], pid(1), NOW).unwrap();
+
+        // the second operation blocks, so the first must not be applied
+        assert_eq!(
+            table.sem_op(id, &[op(0, -1, 0), op(1, -1, 0)], pid(1), NOW),
+            Err(SemopError::WouldBlock {
+                sem_num: 1,
+                zero: false
+            })
+        );
+        assert_eq!(vals(&table, id), [2, 0]);
+
+        assert_eq!(
+            table.sem_op(id, &[op(0, 0, IPC_NOWAIT as i16)], pid(1), NOW),
+            Err(SemopError::Errno(Errno::EAGAIN))
+        );
+        assert_eq!(
+            table.sem_op(id, &[op(2, 1, 0)], pid(1), NOW),
+            Err(SemopError::Errno(Errno::EFBIG))
+        );
+
+        table
+            .sem_op(id, &[op(0, -2, 0), op(0, 0, 0), op(1, 1, 0)], pid(2), NOW)
+            .unwrap();
+        assert_eq!(vals(&table, id), [0, 1]);
+        assert_eq!(table.sem_set(id).unwrap().sems[0].pid, Some(pid(2)));
+    }
+
+    #[test]
+    fn test_undo_on_exit() {
+        let mut table = SysvIpcTable::new();
+        let id = table.sem_get(perm(IPC_PRIVATE), 1, 0, NOW).unwrap();
+
+        table.sem_set_vals(id, Some(0), &[3], pid(1), NOW).unwrap();
+        table
+            .sem_op(id, &[op(0, -2, SEM_UNDO)], pid(2), NOW)
+            .unwrap();
+        table.sem_op(id, &[op(0, -1, 0)], pid(3), NOW).unwrap();
+        assert_eq!(vals(&table, id), [0]);
+
+        table.process_exit(pid(2), NOW);
+        assert_eq!(vals(&table, id), [2]);
+
+        // SETVAL clears adjustments
+        table
+            .sem_op(id, &[op(0, 1, SEM_UNDO)], pid(4), NOW)
+            .unwrap();
+        table.sem_set_vals(id, Some(0), &[0], pid(1), NOW).unwrap();
+        table.process_exit(pid(4), NOW);
+        assert_eq!(vals(&table, id), [0]);
+    }
+
+    #[test]
+    fn test_shm_remove_after_detach() {
+        let mut table = SysvIpcTable::new();
+        let id = table.shm_get(perm(7), 100, IPC_CREAT, pid(1), NOW).unwrap();
+        assert_eq!(table.shm_segment(id).unwrap().mapped_size(), 4096);
+
+        table.shm_attach(id, pid(1), 0x1000, 4096, NOW).unwrap();
+        table.shm_fork(pid(1), pid(2));
+        assert_eq!(table.shm_segment(id).unwrap().nattch, 2);
+
+        // removing an attached segment hides its key but keeps it alive
+        table.shm_remove(id).unwrap();
+        assert_eq!(
+            table.shm_get(perm(7), 100, 0, pid(1), NOW),
+            Err(Errno::ENOENT)
+        );
+        assert!(table.shm_segment(id).unwrap().is_removed());
+
+        assert_eq!(table.shm_detach(pid(1), 0x1000, NOW), Some(4096));
+        assert_eq!(table.shm_detach(pid(1), 0x1000, NOW), None);
+        assert!(table.shm_segment(id).is_ok());
+
+        table.process_exit(pid(2), NOW);
+        assert_eq!(table.shm_segment(id).err(), Some(Errno::EINVAL));
+    }
+}
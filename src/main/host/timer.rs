@@ -202,6 +202,87 @@ impl Timer {
     }
 }
 
+/// A timer that measures a process's modeled CPU time rather than emulated time, used for the
+/// `ITIMER_VIRTUAL` and `ITIMER_PROF` interval timers. Since CPU time only advances while the
+/// process runs, the timer doesn't schedule any events; instead the owner must periodically call
+/// [`CpuTimer::consume_expirations()`] with the current CPU time.
+#[derive(Debug, Default)]
+pub struct CpuTimer {
+    /// The CPU time at which the timer next expires.
+    next_expire_time: Option<SimulationTime>,
+    expire_interval: Option<SimulationTime>,
+}
+
+impl CpuTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the currently configured timer expiration interval if this timer is configured to
+    /// periodically expire, or None if the timer is configured for a one-shot expiration.
+    pub fn expire_interval(&self) -> Option<SimulationTime> {
+        self.expire_interval
+    }
+
+    /// Returns the remaining CPU time until the next expiration if the timer is armed, or None
+    /// otherwise.
+    pub fn remaining_time(&self, cpu_time: SimulationTime) -> Option<SimulationTime> {
+        let t = self.next_expire_time?;
+        Some(t.saturating_sub(cpu_time))
+    }
+
+    /// Deactivate the timer.
+    pub fn disarm(&mut self) {
+        self.next_expire_time = None;
+        self.expire_interval = None;
+    }
+
+    /// Activate the timer so that it expires after `value` more CPU time has been used, and then
+    /// every `expire_interval` of CPU time if `expire_interval` is `Some`.
+    ///
+    /// Panics if `value` or `expire_interval` is `Some` but not positive.
+    pub fn arm(
+        &mut self,
+        cpu_time: SimulationTime,
+        value: SimulationTime,
+        expire_interval: Option<SimulationTime>,
+    ) {
+        debug_assert!(value.is_positive());
+
+        // None is a valid expire interval, but zero is not.
+        if let Some(interval) = expire_interval {
+            debug_assert!(interval.is_positive());
+        }
+
+        self.next_expire_time = Some(cpu_time + value);
+        self.expire_interval = expire_interval;
+    }
+
+    /// Returns the number of times that the timer has expired by `cpu_time`, and advances or
+    /// disarms the timer accordingly.
+    pub fn consume_expirations(&mut self, cpu_time: SimulationTime) -> u64 {
+        let Some(next_expire_time) = self.next_expire_time else {
+            return 0;
+        };
+
+        if next_expire_time > cpu_time {
+            return 0;
+        }
+
+        let Some(interval) = self.expire_interval else {
+            // a one-shot timer
+            self.disarm();
+            return 1;
+        };
+
+        let overrun = cpu_time - next_expire_time;
+        let count = overrun.as_nanos() / interval.as_nanos() + 1;
+        let count = u64::try_from(count).unwrap();
+        self.next_expire_time = Some(next_expire_time + interval.checked_mul(count).unwrap());
+        count
+    }
+}
+
 pub mod export {
     use shadow_shim_helper_rs::emulated_time::CEmulatedTime;
     use shadow_shim_helper_rs::simulation_time::CSimulationTime;
@@ -65,6 +65,9 @@ struct _Tracker {
     gsize numDelayedLastInterval;
     CSimulationTime delayTimeLastInterval;
 
+    /* received packets that were reordered by the network */
+    gsize numReorderedLastInterval;
+
     IFaceCounters local;
     IFaceCounters remote;
 
@@ -237,6 +240,10 @@ void tracker_addInputBytes(Tracker* tracker, Packet* packet, const CompatSocket*
         } else {
             _tracker_updateCounters(&tracker->remote.inCounters, header, payload, status);
         }
+
+        if(status & PDS_INET_REORDERED) {
+            (tracker->numReorderedLastInterval)++;
+        }
     }
 
     if(tracker->loginfo & LOG_INFO_FLAGS_SOCKET) {
@@ -451,7 +458,7 @@ static void _tracker_logNode(Tracker* tracker, LogLevel level, CSimulationTime i
                    "interval-seconds,recv-bytes,send-bytes,cpu-percent,"
                    "delayed-count,avgdelay-milliseconds;"
                    "inbound-localhost-counters;outbound-localhost-counters;"
-                   "inbound-remote-counters;outbound-remote-counters "
+                   "inbound-remote-counters;outbound-remote-counters;reordered-count "
                    "where counters are: %s",
                    // clang-format on
                    _tracker_getCounterHeaderString());
@@ -470,6 +477,7 @@ static void _tracker_logNode(Tracker* tracker, LogLevel level, CSimulationTime i
     g_string_append_printf(buffer, "%u,%"G_GSIZE_FORMAT",%"G_GSIZE_FORMAT",%f,%"G_GSIZE_FORMAT",%f;",
             seconds, totalRecvBytes, totalSendBytes, cpuutil, tracker->numDelayedLastInterval, avgdelayms);
     g_string_append_printf(buffer, "%s;%s;%s;%s", inLocal, outLocal, inRemote, outRemote);
+    g_string_append_printf(buffer, ";%"G_GSIZE_FORMAT, tracker->numReorderedLastInterval);
 
     logger_log(logger_getDefault(), level, __FILE__, __FUNCTION__, __LINE__,
                "%s", buffer->str);
@@ -601,6 +609,7 @@ void tracker_heartbeat(Tracker* tracker, const Host* host) {
     tracker->processingTimeLastIntervalNanos = 0;
     tracker->delayTimeLastInterval = 0;
     tracker->numDelayedLastInterval = 0;
+    tracker->numReorderedLastInterval = 0;
     tracker->allocatedBytesLastInterval = 0;
     tracker->deallocatedBytesLastInterval = 0;
 
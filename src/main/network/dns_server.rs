@@ -0,0 +1,543 @@
+//! A built-in authoritative DNS server.
+//!
+//! The server isn't a host and isn't part of the network graph. Instead, UDP queries sent to the
+//! server's address are answered as soon as they're sent, and the response is delivered to the
+//! querying host after the server's latency. The server answers standard queries for A, AAAA,
+//! CNAME, and SRV records (and "ANY" queries) using the wire format from RFC 1035. It follows
+//! CNAME chains within its own records, returns NXDOMAIN for names that it has no records for, and
+//! never recurses.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::core::configuration::{DnsRecord, DnsServerOptions};
+use crate::utility::units;
+
+const HEADER_LEN: usize = 12;
+/// The maximum size of a UDP message without EDNS, from RFC 1035.
+const MAX_UDP_LEN: usize = 512;
+/// The maximum number of CNAME records that are followed when answering a query.
+const MAX_CNAME_CHAIN: usize = 8;
+
+const FLAG_QR: u16 = 1 << 15;
+const FLAG_AA: u16 = 1 << 10;
+const FLAG_TC: u16 = 1 << 9;
+const FLAG_RD: u16 = 1 << 8;
+
+const CLASS_IN: u16 = 1;
+const CLASS_ANY: u16 = 255;
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+enum Rcode {
+    NoError = 0,
+    FormErr = 1,
+    ServFail = 2,
+    NxDomain = 3,
+    NotImp = 4,
+    Refused = 5,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+}
+
+impl RecordData {
+    fn record_type(&self) -> u16 {
+        match self {
+            Self::A(_) => TYPE_A,
+            Self::Aaaa(_) => TYPE_AAAA,
+            Self::Cname(_) => TYPE_CNAME,
+            Self::Srv { .. } => TYPE_SRV,
+        }
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::A(addr) => buf.extend(addr.octets()),
+            Self::Aaaa(addr) => buf.extend(addr.octets()),
+            Self::Cname(target) => write_name(buf, target),
+            Self::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                buf.extend(priority.to_be_bytes());
+                buf.extend(weight.to_be_bytes());
+                buf.extend(port.to_be_bytes());
+                write_name(buf, target);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    ttl: u32,
+    /// The record isn't visible to queries before this time.
+    start_time: SimulationTime,
+    data: RecordData,
+}
+
+#[derive(Debug)]
+pub struct DnsServer {
+    address: Ipv4Addr,
+    latency: SimulationTime,
+    /// The records of each name. Names are lowercase and don't have a trailing dot.
+    records: HashMap<String, Vec<Record>>,
+}
+
+impl DnsServer {
+    /// The port that the server receives queries on.
+    pub const PORT: u16 = 53;
+
+    /// Create a server with the records from its options, and an A record for each of the
+    /// `hosts` that is visible from the host's start time.
+    pub fn new<'a>(
+        options: &DnsServerOptions,
+        hosts: impl IntoIterator<Item = (&'a str, Ipv4Addr, SimulationTime)>,
+    ) -> Self {
+        let default_ttl = ttl_secs(options.ttl);
+
+        let mut server = Self {
+            address: options.address,
+            latency: Duration::from(options.latency).try_into().unwrap(),
+            records: HashMap::new(),
+        };
+
+        for (name, addr, start_time) in hosts {
+            server.add_record(name, default_ttl, start_time, RecordData::A(addr));
+        }
+
+        for record in &options.records {
+            let ttl = record.ttl().map(ttl_secs).unwrap_or(default_ttl);
+            let data = match record {
+                DnsRecord::A { address, .. } => RecordData::A(*address),
+                DnsRecord::Aaaa { address, .. } => RecordData::Aaaa(*address),
+                DnsRecord::Cname { target, .. } => RecordData::Cname(normalize_name(target)),
+                DnsRecord::Srv {
+                    target,
+                    port,
+                    priority,
+                    weight,
+                    ..
+                } => RecordData::Srv {
+                    priority: *priority,
+                    weight: *weight,
+                    port: *port,
+                    target: normalize_name(target),
+                },
+            };
+            server.add_record(record.name(), ttl, SimulationTime::ZERO, data);
+        }
+
+        server
+    }
+
+    /// Add a record that is visible to queries from `start_time`.
+    pub fn add_record(
+        &mut self,
+        name: &str,
+        ttl: u32,
+        start_time: SimulationTime,
+        data: RecordData,
+    ) {
+        self.records
+            .entry(normalize_name(name))
+            .or_default()
+            .push(Record {
+                ttl,
+                start_time,
+                data,
+            });
+    }
+
+    pub fn address(&self) -> Ipv4Addr {
+        self.address
+    }
+
+    pub fn latency(&self) -> SimulationTime {
+        self.latency
+    }
+
+    /// Returns the response to a DNS message received at time `now`. Returns `None` if the message
+    /// should be ignored, because it's too short to have a header or is itself a response.
+    pub fn respond(&self, query: &[u8], now: SimulationTime) -> Option<Vec<u8>> {
+        let header = query.get(..HEADER_LEN)?;
+        let flags = u16::from_be_bytes([header[2], header[3]]);
+
+        if flags & FLAG_QR != 0 {
+            return None;
+        }
+
+        // the response keeps the query's ID, opcode, and "recursion desired" flag
+        let mut response = header[..2].to_vec();
+        let flags = FLAG_QR | FLAG_AA | (flags & (0xf << 11)) | (flags & FLAG_RD);
+        let opcode = (flags >> 11) & 0xf;
+        let qdcount = u16::from_be_bytes([header[4], header[5]]);
+
+        let question = match (opcode, qdcount) {
+            (0, 1) => parse_question(query),
+            (0, _) => None,
+            _ => return Some(error_response(response, flags, Rcode::NotImp)),
+        };
+
+        let Some((name, qtype, qclass, question)) = question else {
+            return Some(error_response(response, flags, Rcode::FormErr));
+        };
+
+        if qclass != CLASS_IN && qclass != CLASS_ANY {
+            return Some(error_response(response, flags, Rcode::Refused));
+        }
+
+        let (rcode, answers) = self.answers(name, qtype, now);
+
+        response.extend((flags | rcode as u16).to_be_bytes());
+        // the question, answer, authority, and additional counts
+        response.extend(1u16.to_be_bytes());
+        response.extend(u16::try_from(answers.len()).unwrap().to_be_bytes());
+        response.extend([0; 4]);
+        response.extend(question);
+
+        for (name, record) in answers {
+            write_name(&mut response, name);
+            response.extend(record.data.record_type().to_be_bytes());
+            response.extend(CLASS_IN.to_be_bytes());
+            response.extend(record.ttl.to_be_bytes());
+
+            let mut rdata = vec![];
+            record.data.write(&mut rdata);
+            response.extend(u16::try_from(rdata.len()).unwrap().to_be_bytes());
+            response.extend(rdata);
+        }
+
+        // without EDNS, a response that doesn't fit in a UDP message is truncated to its header
+        // and question, and the client should retry over TCP
+        if response.len() > MAX_UDP_LEN {
+            response.truncate(HEADER_LEN + question.len());
+            response[2..4].copy_from_slice(&(flags | FLAG_TC | rcode as u16).to_be_bytes());
+            response[6..8].copy_from_slice(&0u16.to_be_bytes());
+        }
+
+        Some(response)
+    }
+
+    /// Returns the name and the records of a name that are visible at time `now`. Returns `None`
+    /// if the name has no visible records.
+    fn lookup(&self, name: &str, now: SimulationTime) -> Option<(&str, Vec<&Record>)> {
+        let (name, records) = self.records.get_key_value(name)?;
+        let records: Vec<_> = records.iter().filter(|x| x.start_time <= now).collect();
+        (!records.is_empty()).then_some((name.as_str(), records))
+    }
+
+    /// Returns the response code and answers for a query at time `now`, following CNAME records.
+    fn answers(
+        &self,
+        name: String,
+        qtype: u16,
+        now: SimulationTime,
+    ) -> (Rcode, Vec<(&str, &Record)>) {
+        let mut answers = vec![];
+
+        let Some((mut name, mut records)) = self.lookup(&name, now) else {
+            return (Rcode::NxDomain, answers);
+        };
+
+        for _ in 0..MAX_CNAME_CHAIN {
+            let len = answers.len();
+            answers.extend(
+                records
+                    .iter()
+                    .filter(|x| qtype == TYPE_ANY || x.data.record_type() == qtype)
+                    .map(|x| (name, *x)),
+            );
+
+            if answers.len() > len || qtype == TYPE_CNAME {
+                return (Rcode::NoError, answers);
+            }
+
+            let Some(cname) = records.iter().find(|x| x.data.record_type() == TYPE_CNAME) else {
+                // the name exists, but has no records of this type
+                return (Rcode::NoError, answers);
+            };
+            answers.push((name, cname));
+
+            let RecordData::Cname(target) = &cname.data else {
+                unreachable!();
+            };
+
+            // like other authoritative servers, the rcode describes the last name in the chain
+            (name, records) = match self.lookup(target, now) {
+                Some(x) => x,
+                None => return (Rcode::NxDomain, answers),
+            };
+        }
+
+        // the CNAME chain is too long or has a loop
+        (Rcode::ServFail, vec![])
+    }
+}
+
+/// Returns a response with no question or answers.
+fn error_response(mut response: Vec<u8>, flags: u16, rcode: Rcode) -> Vec<u8> {
+    response.extend((flags | rcode as u16).to_be_bytes());
+    response.extend([0; 8]);
+    response
+}
+
+/// Parse the question of a query. Returns the lowercase name, the type, the class, and the bytes
+/// of the question section. Compressed names aren't supported in questions.
+fn parse_question(query: &[u8]) -> Option<(String, u16, u16, &[u8])> {
+    let mut labels = vec![];
+    let mut pos = HEADER_LEN;
+
+    loop {
+        let len = usize::from(*query.get(pos)?);
+        pos += 1;
+
+        if len == 0 {
+            break;
+        }
+
+        // the top two bits are set for compression pointers
+        if len > 63 {
+            return None;
+        }
+
+        let label = query.get(pos..(pos + len))?;
+        labels.push(std::str::from_utf8(label).ok()?.to_ascii_lowercase());
+        pos += len;
+    }
+
+    let fields = query.get(pos..(pos + 4))?;
+    let qtype = u16::from_be_bytes([fields[0], fields[1]]);
+    let qclass = u16::from_be_bytes([fields[2], fields[3]]);
+
+    Some((
+        labels.join("."),
+        qtype,
+        qclass,
+        &query[HEADER_LEN..(pos + 4)],
+    ))
+}
+
+/// Write a name without compression.
+fn write_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|x| !x.is_empty()) {
+        buf.push(label.len().try_into().unwrap());
+        buf.extend(label.as_bytes());
+    }
+    buf.push(0);
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn ttl_secs(ttl: units::Time<units::TimePrefix>) -> u32 {
+    Duration::from(ttl).as_secs().try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        write_name(&mut query, name);
+        query.extend(qtype.to_be_bytes());
+        query.extend(CLASS_IN.to_be_bytes());
+        query
+    }
+
+    /// Returns the response code and the type, TTL, and rdata of each answer.
+    fn parse_response(response: &[u8]) -> (u16, Vec<(u16, u32, Vec<u8>)>) {
+        let flags = u16::from_be_bytes([response[2], response[3]]);
+        let ancount = u16::from_be_bytes([response[6], response[7]]);
+        let (_, _, _, question) = parse_question(response).unwrap();
+
+        let mut pos = HEADER_LEN + question.len();
+        let mut answers = vec![];
+
+        for _ in 0..ancount {
+            while response[pos] != 0 {
+                pos += usize::from(response[pos]) + 1;
+            }
+            let fields = &response[(pos + 1)..(pos + 11)];
+            let rtype = u16::from_be_bytes([fields[0], fields[1]]);
+            let ttl = u32::from_be_bytes(fields[4..8].try_into().unwrap());
+            let len = usize::from(u16::from_be_bytes([fields[8], fields[9]]));
+            pos += 11;
+            answers.push((rtype, ttl, response[pos..(pos + len)].to_vec()));
+            pos += len;
+        }
+
+        (flags & 0xf, answers)
+    }
+
+    fn server() -> DnsServer {
+        let options: DnsServerOptions = serde_yaml::from_str(
+            "
+            address: 1.1.1.1
+            ttl: 60s
+            records:
+            - {type: AAAA, name: server, address: '::1'}
+            - {type: CNAME, name: www.Example.com., target: server, ttl: 10s}
+            - {type: CNAME, name: missing.example.com, target: nowhere}
+            - {type: CNAME, name: loop, target: loop}
+            - {type: SRV, name: _http._tcp.example.com, target: server, port: 80, weight: 5}
+            ",
+        )
+        .unwrap();
+
+        DnsServer::new(
+            &options,
+            [
+                ("server", Ipv4Addr::new(11, 0, 0, 1), SimulationTime::ZERO),
+                ("late", Ipv4Addr::new(11, 0, 0, 2), SimulationTime::SECOND),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_records() {
+        let server = server();
+
+        let response = server
+            .respond(&query("SERVER", TYPE_A), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(&response[..2], [0x12, 0x34]);
+        assert_eq!(
+            parse_response(&response),
+            (0, vec![(TYPE_A, 60, vec![11, 0, 0, 1])])
+        );
+
+        let response = server
+            .respond(&query("server", TYPE_AAAA), SimulationTime::ZERO)
+            .unwrap();
+        let (rcode, answers) = parse_response(&response);
+        assert_eq!(rcode, 0);
+        assert_eq!(answers[0].2, Ipv6Addr::LOCALHOST.octets());
+
+        let response = server
+            .respond(&query("server", TYPE_ANY), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(parse_response(&response).1.len(), 2);
+
+        let response = server
+            .respond(
+                &query("_http._tcp.example.com", TYPE_SRV),
+                SimulationTime::ZERO,
+            )
+            .unwrap();
+        let mut rdata = vec![0, 0, 0, 5, 0, 80];
+        write_name(&mut rdata, "server");
+        assert_eq!(parse_response(&response), (0, vec![(TYPE_SRV, 60, rdata)]));
+    }
+
+    #[test]
+    fn test_cname() {
+        let server = server();
+
+        let mut cname = vec![];
+        write_name(&mut cname, "server");
+
+        let response = server
+            .respond(&query("www.example.com", TYPE_A), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(
+            parse_response(&response),
+            (
+                0,
+                vec![
+                    (TYPE_CNAME, 10, cname.clone()),
+                    (TYPE_A, 60, vec![11, 0, 0, 1])
+                ]
+            )
+        );
+
+        let response = server
+            .respond(&query("www.example.com", TYPE_CNAME), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(
+            parse_response(&response),
+            (0, vec![(TYPE_CNAME, 10, cname)])
+        );
+
+        let response = server
+            .respond(&query("missing.example.com", TYPE_A), SimulationTime::ZERO)
+            .unwrap();
+        let (rcode, answers) = parse_response(&response);
+        assert_eq!(rcode, Rcode::NxDomain as u16);
+        assert_eq!(answers.len(), 1);
+
+        let response = server
+            .respond(&query("loop", TYPE_A), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(parse_response(&response), (Rcode::ServFail as u16, vec![]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let server = server();
+
+        // unknown names
+        let response = server
+            .respond(&query("other", TYPE_A), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(parse_response(&response), (Rcode::NxDomain as u16, vec![]));
+
+        // known names without records of the type
+        let response = server
+            .respond(&query("server", TYPE_SRV), SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(parse_response(&response), (0, vec![]));
+
+        // a truncated question
+        let query = query("server", TYPE_A);
+        let response = server
+            .respond(&query[..(query.len() - 1)], SimulationTime::ZERO)
+            .unwrap();
+        assert_eq!(u16::from_be_bytes([response[2], response[3]]) & 0xf, 1);
+
+        // responses and short messages are ignored
+        let mut response = server.respond(&query, SimulationTime::ZERO).unwrap();
+        assert!(server.respond(&response, SimulationTime::ZERO).is_none());
+        response.truncate(HEADER_LEN - 1);
+        assert!(server.respond(&response, SimulationTime::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_start_time() {
+        let server = server();
+
+        // a host's name doesn't exist before the host's start time
+        let query = query("late", TYPE_A);
+        let response = server.respond(&query, SimulationTime::ZERO).unwrap();
+        assert_eq!(parse_response(&response), (Rcode::NxDomain as u16, vec![]));
+
+        let response = server.respond(&query, SimulationTime::SECOND).unwrap();
+        assert_eq!(
+            parse_response(&response),
+            (0, vec![(TYPE_A, 60, vec![11, 0, 0, 2])])
+        );
+    }
+}
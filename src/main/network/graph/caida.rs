@@ -0,0 +1,184 @@
+//! Conversion of CAIDA AS-relationship data to a network graph of autonomous systems (ASes).
+//!
+//! The data has one relationship per line, in the CAIDA serial-1 or serial-2 format:
+//!
+//! ```text
+//! # AS 1 is a provider of AS 2
+//! 1|2|-1
+//! # AS 2 and AS 3 are peers
+//! 2|3|0
+//! ```
+//!
+//! Serial-2 data has a fourth field with the source of each relationship, which is ignored. Blank
+//! lines and lines starting with `#` are ignored.
+//!
+//! The graph has a node for each AS, where the node id is the AS number, and an undirected edge
+//! for each relationship. Each edge has a `relationship` attribute for policy routing, and a
+//! latency that's inferred from the type of the relationship.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use crate::core::configuration::CaidaGraphOptions;
+use crate::network::graph::routing::Relationship;
+
+/// Convert CAIDA AS-relationship data to a GML graph, using the latencies and host bandwidth of
+/// the options.
+pub fn to_gml(data: &str, options: &CaidaGraphOptions) -> Result<String, String> {
+    let mut ases = BTreeSet::new();
+    let mut pairs = HashSet::new();
+    let mut edges = Vec::new();
+
+    for (line_num, line) in data
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+    {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<_> = line.split('|').collect();
+        if !(3..=4).contains(&fields.len()) {
+            return Err(format!(
+                "Line {line_num} doesn't have 3 or 4 fields separated by '|'"
+            ));
+        }
+
+        let asn = |x: &str| {
+            x.parse::<u32>()
+                .map_err(|e| format!("Line {line_num} has an invalid AS number '{x}': {e}"))
+        };
+        let a = asn(fields[0])?;
+        let b = asn(fields[1])?;
+
+        // the relationship of AS `b` to AS `a`
+        let relationship = match fields[2] {
+            "-1" => Relationship::Customer,
+            "0" => Relationship::Peer,
+            x => return Err(format!("Line {line_num} has an invalid relationship '{x}'")),
+        };
+
+        if a == b {
+            return Err(format!(
+                "Line {line_num} has a relationship between AS {a} and itself"
+            ));
+        }
+
+        if !pairs.insert((a.min(b), a.max(b))) {
+            return Err(format!(
+                "Line {line_num} has a second relationship between AS {a} and AS {b}"
+            ));
+        }
+
+        ases.insert(a);
+        ases.insert(b);
+        edges.push((a, b, relationship));
+    }
+
+    if ases.is_empty() {
+        return Err("The data doesn't have any relationships".to_string());
+    }
+
+    let mut gml = String::from("graph [\n  directed 0\n");
+
+    let bandwidth = options.host_bandwidth;
+    for asn in &ases {
+        writeln!(gml, "  node [").unwrap();
+        writeln!(gml, "    id {asn}").unwrap();
+        writeln!(gml, "    host_bandwidth_up \"{bandwidth}\"").unwrap();
+        writeln!(gml, "    host_bandwidth_down \"{bandwidth}\"").unwrap();
+        writeln!(gml, "  ]").unwrap();
+
+        // the self-loop is used by paths between hosts in the same AS
+        writeln!(gml, "  edge [").unwrap();
+        writeln!(gml, "    source {asn}").unwrap();
+        writeln!(gml, "    target {asn}").unwrap();
+        writeln!(gml, "    latency \"{}\"", options.internal_latency).unwrap();
+        writeln!(gml, "  ]").unwrap();
+    }
+
+    for (a, b, relationship) in edges {
+        let latency = match relationship {
+            Relationship::Peer => options.peer_latency,
+            _ => options.transit_latency,
+        };
+
+        writeln!(gml, "  edge [").unwrap();
+        writeln!(gml, "    source {a}").unwrap();
+        writeln!(gml, "    target {b}").unwrap();
+        writeln!(gml, "    latency \"{latency}\"").unwrap();
+        writeln!(gml, "    relationship \"{relationship}\"").unwrap();
+        writeln!(gml, "  ]").unwrap();
+    }
+
+    gml.push_str("]\n");
+
+    Ok(gml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::configuration::GraphSource;
+    use crate::network::graph::{NetworkGraph, ShadowEdge};
+
+    fn options() -> CaidaGraphOptions {
+        serde_yaml::from_str("inline: ''").unwrap()
+    }
+
+    #[test]
+    fn test_caida_to_gml() {
+        let data = "
+            # source:topology|BGP
+            1|2|-1
+            2|3|0|bgp
+            1|4|-1
+        ";
+        let options = options();
+        assert!(matches!(options.source, GraphSource::Inline(_)));
+
+        let graph = NetworkGraph::parse(&to_gml(data, &options).unwrap()).unwrap();
+
+        for asn in [1, 2, 3, 4] {
+            assert!(graph.node_id_to_index(asn).is_some());
+        }
+
+        let edge = |a, b| -> ShadowEdge {
+            let edge = graph.edge_between(a, b).unwrap();
+            graph.edge_weight(edge).clone()
+        };
+
+        assert_eq!(
+            edge(1, 2).relationship_from(1),
+            Some(Relationship::Customer)
+        );
+        assert_eq!(
+            edge(1, 2).relationship_from(2),
+            Some(Relationship::Provider)
+        );
+        assert_eq!(edge(2, 3).relationship_from(3), Some(Relationship::Peer));
+        assert_eq!(edge(1, 2).latency, options.transit_latency);
+        assert_eq!(edge(2, 3).latency, options.peer_latency);
+        assert_eq!(edge(4, 4).latency, options.internal_latency);
+        assert_eq!(edge(4, 4).relationship, None);
+    }
+
+    #[test]
+    fn test_invalid_caida() {
+        let invalid = [
+            "",
+            "# only a comment",
+            "1|2",
+            "1|2|-1|bgp|x",
+            "1|x|-1",
+            "1|2|1",
+            "1|1|0",
+            "1|2|-1\n2|1|0",
+        ];
+
+        for data in invalid {
+            assert!(to_gml(data, &options()).is_err(), "{data:?}");
+        }
+    }
+}
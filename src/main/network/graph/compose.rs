@@ -0,0 +1,265 @@
+//! Composition of a network graph from sub-graphs, such as a sub-graph for each site of an
+//! experiment.
+//!
+//! The nodes of each sub-graph are added to the composite graph with the sub-graph's id offset
+//! added to their ids, so the same sub-graph can be included more than once with different
+//! offsets. Edges between sub-graphs refer to their nodes by the name of the sub-graph and the id
+//! of the node within the sub-graph, such as `site-a:3`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use gml_parser::gml::{Edge, Gml, Node, Value};
+
+use super::{load_network_graph, parse_gml, NetGraphError};
+use crate::core::configuration::{CompositeGraphOptions, GraphAttribute};
+
+/// Compose a graph from its sub-graphs, returning it in the GML format.
+pub fn compose(options: &CompositeGraphOptions) -> Result<String, NetGraphError> {
+    let mut directed = None;
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    // the name of the sub-graph of each node in the composite graph
+    let mut node_subgraphs: HashMap<u32, &str> = HashMap::new();
+
+    for (name, subgraph) in &options.subgraphs {
+        let text = load_network_graph(&subgraph.graph)
+            .map_err(|e| format!("Failed to load sub-graph '{name}': {e}"))?;
+        let graph = parse_gml(&text)
+            .map_err(|e| format!("Failed to parse sub-graph '{name}': {e}"))?
+            .upgrade_to_owned();
+
+        if *directed.get_or_insert(graph.directed) != graph.directed {
+            return Err(format!(
+                "Sub-graph '{name}' doesn't have the same 'directed' as the others"
+            )
+            .into());
+        }
+
+        let offset = |id: u32| {
+            id.checked_add(subgraph.id_offset).ok_or(format!(
+                "Node id {id} of sub-graph '{name}' is too large for its offset"
+            ))
+        };
+
+        for node in graph.nodes {
+            let id = node
+                .id
+                .ok_or(format!("A node of sub-graph '{name}' doesn't have an 'id'"))?;
+            let id = offset(id)?;
+
+            if let Some(other) = node_subgraphs.insert(id, name) {
+                return Err(format!(
+                    "Node id {id} of sub-graph '{name}' is also a node id of sub-graph '{other}'"
+                )
+                .into());
+            }
+
+            nodes.push(Node {
+                id: Some(id),
+                other: node.other,
+            });
+        }
+
+        for edge in graph.edges {
+            edges.push(Edge {
+                source: offset(edge.source)?,
+                target: offset(edge.target)?,
+                other: edge.other,
+            });
+        }
+    }
+
+    let Some(directed) = directed else {
+        return Err("The composite graph doesn't have any sub-graphs".into());
+    };
+
+    // get the id of a node in the composite graph from its `<sub-graph>:<id>` reference
+    let node_id = |reference: &str| -> Result<u32, String> {
+        let (name, id) = reference.rsplit_once(':').ok_or(format!(
+            "Edge node '{reference}' is not in the format '<sub-graph>:<node id>'"
+        ))?;
+        let subgraph = options.subgraphs.get(name).ok_or(format!(
+            "Edge node '{reference}' refers to an unknown sub-graph"
+        ))?;
+        let id = id
+            .parse::<u32>()
+            .ok()
+            .and_then(|x| x.checked_add(subgraph.id_offset))
+            .filter(|x| node_subgraphs.get(x) == Some(&name))
+            .ok_or(format!("Edge node '{reference}' doesn't exist"))?;
+        Ok(id)
+    };
+
+    for edge in &options.edges {
+        let other = edge
+            .attributes
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    GraphAttribute::Int(x) => Value::Int(*x),
+                    GraphAttribute::Float(x) => Value::Float(*x),
+                    GraphAttribute::Str(x) => Value::Str(Cow::Owned(x.clone())),
+                };
+                (Cow::Owned(key.clone()), value)
+            })
+            .collect();
+
+        edges.push(Edge {
+            source: node_id(&edge.source)?,
+            target: node_id(&edge.target)?,
+            other,
+        });
+    }
+
+    Ok(write_gml(&Gml {
+        directed,
+        nodes,
+        edges,
+        other: HashMap::new(),
+    }))
+}
+
+/// Write a graph in the GML format.
+fn write_gml(graph: &Gml) -> String {
+    let mut gml = String::from("graph [\n");
+    writeln!(gml, "  directed {}", i32::from(graph.directed)).unwrap();
+
+    for node in &graph.nodes {
+        writeln!(gml, "  node [").unwrap();
+        writeln!(gml, "    id {}", node.id.unwrap()).unwrap();
+        write_values(&mut gml, &node.other);
+        writeln!(gml, "  ]").unwrap();
+    }
+
+    for edge in &graph.edges {
+        writeln!(gml, "  edge [").unwrap();
+        writeln!(gml, "    source {}", edge.source).unwrap();
+        writeln!(gml, "    target {}", edge.target).unwrap();
+        write_values(&mut gml, &edge.other);
+        writeln!(gml, "  ]").unwrap();
+    }
+
+    gml.push_str("]\n");
+    gml
+}
+
+/// Write the key-value pairs of a node or edge, sorted by their keys.
+fn write_values(gml: &mut String, values: &HashMap<Cow<str>, Value>) {
+    let mut values: Vec<_> = values.iter().collect();
+    values.sort_by(|a, b| a.0.cmp(b.0));
+
+    for (key, value) in values {
+        match value {
+            Value::Int(x) => writeln!(gml, "    {key} {x}"),
+            // the debug format always has a decimal point or an exponent, so it's read as a float
+            Value::Float(x) => writeln!(gml, "    {key} {x:?}"),
+            Value::Str(x) => writeln!(gml, "    {key} \"{x}\""),
+        }
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::core::configuration::{
+        CompositeEdgeOptions, GraphOptions, GraphSource, SubgraphOptions,
+    };
+    use crate::network::graph::NetworkGraph;
+    use crate::utility::units::{self, Unit};
+
+    const SITE: &str = r#"graph [
+      node [
+        id 0
+        host_bandwidth_up "1 Gbit"
+        host_bandwidth_down "1 Gbit"
+      ]
+      node [
+        id 1
+      ]
+      edge [
+        source 0
+        target 1
+        latency "1 ms"
+        packet_loss 0.0
+      ]
+    ]"#;
+
+    fn subgraph(graph: GraphOptions, id_offset: u32) -> SubgraphOptions {
+        SubgraphOptions { graph, id_offset }
+    }
+
+    fn composite(subgraphs: &[(&str, u32)], edges: &[&str]) -> CompositeGraphOptions {
+        let site = GraphOptions::Gml(GraphSource::Inline(SITE.to_string()));
+        CompositeGraphOptions {
+            subgraphs: subgraphs
+                .iter()
+                .map(|(name, offset)| (name.to_string(), subgraph(site.clone(), *offset)))
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|x| serde_yaml::from_str(x).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let mut options = composite(
+            &[("a", 0), ("b", 100)],
+            &["source: a:1\ntarget: b:0\nlatency: 20 ms\npacket_loss: 0.5"],
+        );
+
+        // sub-graphs can also be composite graphs
+        let nested = GraphOptions::Composite(composite(&[("a", 0)], &[]));
+        options
+            .subgraphs
+            .insert("c".to_string(), subgraph(nested, 200));
+        options.edges.push(CompositeEdgeOptions {
+            source: "c:1".to_string(),
+            target: "a:0".to_string(),
+            attributes: BTreeMap::from([(
+                "latency".to_string(),
+                GraphAttribute::Str("3 ms".to_string()),
+            )]),
+        });
+
+        let graph = NetworkGraph::parse(&compose(&options).unwrap()).unwrap();
+        let latency_ms = |a, b| {
+            let edge = graph.edge_between(a, b).unwrap();
+            let latency = graph.edge_weight(edge).latency;
+            latency.convert(units::TimePrefix::Milli).unwrap().value()
+        };
+
+        assert_eq!(latency_ms(0, 1), 1);
+        assert_eq!(latency_ms(100, 101), 1);
+        assert_eq!(latency_ms(200, 201), 1);
+        assert_eq!(latency_ms(1, 100), 20);
+        assert_eq!(latency_ms(201, 0), 3);
+        assert!(graph.edge_between(0, 100).is_none());
+
+        let edge = graph.edge_between(1, 100).unwrap();
+        assert_eq!(graph.edge_weight(edge).packet_loss, 0.5);
+    }
+
+    #[test]
+    fn test_invalid_compose() {
+        let invalid = [
+            composite(&[], &[]),
+            // the node ids overlap
+            composite(&[("a", 0), ("b", 1)], &[]),
+            composite(&[("a", u32::MAX)], &[]),
+            composite(&[("a", 0)], &["{source: a0, target: a1, latency: 1 ms}"]),
+            composite(&[("a", 0)], &["source: b:0\ntarget: a:1\nlatency: 1 ms"]),
+            composite(&[("a", 0)], &["source: a:2\ntarget: a:1\nlatency: 1 ms"]),
+        ];
+
+        for options in invalid {
+            assert!(compose(&options).is_err(), "{options:?}");
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Generation of synthetic network graphs, such as stars, dumbbells, fat-trees, and random graphs.
+//!
+//! Each node has a self-loop with the lowest latency, which is used by paths between hosts attached
+//! to the same node. The latency and bandwidth of every other edge, and the host bandwidth of every
+//! node, are chosen uniformly at random from the ranges of the options.
+
+use std::fmt::Write;
+
+use petgraph::unionfind::UnionFind;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::core::configuration::{TopologyKind, TopologyOptions};
+use crate::utility::units::{self, Unit};
+
+/// The nodes and edges of a synthetic graph.
+#[derive(Debug)]
+struct Topology {
+    /// The label of each node, if it has one. The node ids are the indexes.
+    labels: Vec<Option<&'static str>>,
+    edges: Vec<(u32, u32)>,
+    /// The index of an edge that has the lowest bandwidth.
+    bottleneck: Option<usize>,
+}
+
+/// Generate a synthetic graph in the GML format.
+pub fn to_gml(options: &TopologyOptions) -> Result<String, String> {
+    let min_latency_ns = options
+        .min_latency
+        .convert(units::TimePrefix::Nano)?
+        .value();
+    let max_latency_ns = options
+        .max_latency
+        .convert(units::TimePrefix::Nano)?
+        .value();
+    let min_bits = options
+        .min_bandwidth
+        .convert(units::SiPrefixUpper::Base)?
+        .value();
+    let max_bits = options
+        .max_bandwidth
+        .convert(units::SiPrefixUpper::Base)?
+        .value();
+
+    if min_latency_ns == 0 {
+        return Err("The minimum latency must not be 0".to_string());
+    }
+    if min_latency_ns > max_latency_ns {
+        return Err("The minimum latency is higher than the maximum latency".to_string());
+    }
+    if min_bits == 0 {
+        return Err("The minimum bandwidth must not be 0".to_string());
+    }
+    if min_bits > max_bits {
+        return Err("The minimum bandwidth is higher than the maximum bandwidth".to_string());
+    }
+
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(options.seed);
+
+    let topology = match options.kind {
+        TopologyKind::Star { nodes } => star(nodes)?,
+        TopologyKind::Dumbbell { nodes } => dumbbell(nodes)?,
+        TopologyKind::FatTree { k } => fat_tree(k)?,
+        TopologyKind::BarabasiAlbert {
+            nodes,
+            edges_per_node,
+        } => barabasi_albert(nodes, edges_per_node, &mut rng)?,
+        TopologyKind::ErdosRenyi {
+            nodes,
+            edge_probability,
+        } => erdos_renyi(nodes, edge_probability, &mut rng)?,
+    };
+
+    // shadow requires a connected graph
+    let mut components = UnionFind::new(topology.labels.len());
+    for (a, b) in &topology.edges {
+        components.union(*a as usize, *b as usize);
+    }
+    if (0..topology.labels.len()).any(|x| !components.equiv(0, x)) {
+        return Err("The generated graph isn't connected".to_string());
+    }
+
+    let mut gml = String::from("graph [\n  directed 0\n");
+
+    for (id, label) in topology.labels.iter().enumerate() {
+        let bandwidth = rng.gen_range(min_bits..=max_bits);
+
+        writeln!(gml, "  node [").unwrap();
+        writeln!(gml, "    id {id}").unwrap();
+        if let Some(label) = label {
+            writeln!(gml, "    label \"{label}\"").unwrap();
+        }
+        writeln!(gml, "    host_bandwidth_up \"{bandwidth} bit\"").unwrap();
+        writeln!(gml, "    host_bandwidth_down \"{bandwidth} bit\"").unwrap();
+        writeln!(gml, "  ]").unwrap();
+
+        writeln!(gml, "  edge [").unwrap();
+        writeln!(gml, "    source {id}").unwrap();
+        writeln!(gml, "    target {id}").unwrap();
+        writeln!(gml, "    latency \"{min_latency_ns} ns\"").unwrap();
+        writeln!(gml, "  ]").unwrap();
+    }
+
+    for (i, (a, b)) in topology.edges.iter().enumerate() {
+        let latency_ns = rng.gen_range(min_latency_ns..=max_latency_ns);
+        let bandwidth = if topology.bottleneck == Some(i) {
+            min_bits
+        } else {
+            rng.gen_range(min_bits..=max_bits)
+        };
+
+        writeln!(gml, "  edge [").unwrap();
+        writeln!(gml, "    source {a}").unwrap();
+        writeln!(gml, "    target {b}").unwrap();
+        writeln!(gml, "    latency \"{latency_ns} ns\"").unwrap();
+        writeln!(gml, "    bandwidth \"{bandwidth} bit\"").unwrap();
+        writeln!(gml, "  ]").unwrap();
+    }
+
+    gml.push_str("]\n");
+
+    Ok(gml)
+}
+
+/// A hub node 0 with an edge to each of the other nodes.
+fn star(nodes: u32) -> Result<Topology, String> {
+    if nodes < 2 {
+        return Err("A star needs at least 2 nodes".to_string());
+    }
+
+    let mut labels = vec![None; nodes as usize];
+    labels[0] = Some("hub");
+
+    Ok(Topology {
+        labels,
+        edges: (1..nodes).map(|x| (0, x)).collect(),
+        bottleneck: None,
+    })
+}
+
+/// Router nodes 0 and 1 joined by a bottleneck edge, with the other nodes alternating between the
+/// two routers.
+fn dumbbell(nodes: u32) -> Result<Topology, String> {
+    if nodes < 4 {
+        return Err("A dumbbell needs at least 4 nodes".to_string());
+    }
+
+    let mut labels = vec![None; nodes as usize];
+    labels[0] = Some("router");
+    labels[1] = Some("router");
+
+    let mut edges = vec![(0, 1)];
+    edges.extend((2..nodes).map(|x| (x % 2, x)));
+
+    Ok(Topology {
+        labels,
+        edges,
+        bottleneck: Some(0),
+    })
+}
+
+/// A k-ary fat-tree. The `(k/2)²` core switches come first, followed by each of the `k` pods,
+/// which have `k/2` aggregation switches and then `k/2` edge switches. Each core switch has an
+/// edge to one aggregation switch in every pod, and each pod's aggregation and edge switches are
+/// fully connected.
+fn fat_tree(k: u32) -> Result<Topology, String> {
+    if k < 2 || k % 2 != 0 {
+        return Err("A fat-tree needs an even k of at least 2".to_string());
+    }
+
+    let half = k / 2;
+    let cores = half * half;
+
+    let mut labels = vec![Some("core"); cores as usize];
+    let mut edges = Vec::new();
+
+    for pod in 0..k {
+        let first = cores + pod * k;
+        labels.extend((0..half).map(|_| Some("aggregation")));
+        labels.extend((0..half).map(|_| Some("edge")));
+
+        for aggregation in 0..half {
+            for core in 0..half {
+                edges.push((aggregation * half + core, first + aggregation));
+            }
+            for edge in 0..half {
+                edges.push((first + aggregation, first + half + edge));
+            }
+        }
+    }
+
+    Ok(Topology {
+        labels,
+        edges,
+        bottleneck: None,
+    })
+}
+
+/// A Barabási–Albert graph, which starts as a complete graph of `edges_per_node + 1` nodes. Each
+/// new node then has edges to `edges_per_node` different existing nodes, which are chosen with a
+/// probability in proportion to their degrees.
+fn barabasi_albert(
+    nodes: u32,
+    edges_per_node: u32,
+    rng: &mut impl Rng,
+) -> Result<Topology, String> {
+    if edges_per_node == 0 {
+        return Err("A Barabási–Albert graph needs at least 1 edge per node".to_string());
+    }
+    if nodes <= edges_per_node {
+        return Err("A Barabási–Albert graph needs more nodes than edges per node".to_string());
+    }
+
+    let mut edges = Vec::new();
+    for a in 0..=edges_per_node {
+        for b in (a + 1)..=edges_per_node {
+            edges.push((a, b));
+        }
+    }
+
+    // each node appears once for each of its edges
+    let mut endpoints: Vec<u32> = edges.iter().flat_map(|(a, b)| [*a, *b]).collect();
+
+    for node in (edges_per_node + 1)..nodes {
+        let mut targets = Vec::new();
+        while targets.len() < edges_per_node as usize {
+            let target = endpoints[rng.gen_range(0..endpoints.len())];
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+
+        for target in targets {
+            edges.push((target, node));
+            endpoints.extend([target, node]);
+        }
+    }
+
+    Ok(Topology {
+        labels: vec![None; nodes as usize],
+        edges,
+        bottleneck: None,
+    })
+}
+
+/// An Erdős–Rényi graph, where each pair of nodes has an edge with probability `edge_probability`.
+fn erdos_renyi(nodes: u32, edge_probability: f64, rng: &mut impl Rng) -> Result<Topology, String> {
+    if nodes == 0 {
+        return Err("An Erdős–Rényi graph needs at least 1 node".to_string());
+    }
+    if !(0.0..=1.0).contains(&edge_probability) {
+        return Err("The edge probability is not in the range [0,1]".to_string());
+    }
+
+    let mut edges = Vec::new();
+    for a in 0..nodes {
+        for b in (a + 1)..nodes {
+            if rng.gen_bool(edge_probability) {
+                edges.push((a, b));
+            }
+        }
+    }
+
+    Ok(Topology {
+        labels: vec![None; nodes as usize],
+        edges,
+        bottleneck: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::network::graph::NetworkGraph;
+
+    fn generate(options: &str) -> Result<String, String> {
+        to_gml(&TopologyOptions::from_str(options).unwrap())
+    }
+
+    fn node_and_edge_counts(gml: &str) -> (usize, usize) {
+        let graph = NetworkGraph::parse(gml).unwrap();
+        // the node ids are contiguous from 0
+        let nodes = (0..)
+            .take_while(|x| graph.node_id_to_index(*x).is_some())
+            .count();
+        (nodes, graph.graph().edge_indices().count())
+    }
+
+    #[test]
+    fn test_generate_topologies() {
+        // the edge counts include a self-loop for each node
+        let topologies = [
+            ("{type: star, nodes: 5}", (5, 5 + 4)),
+            ("{type: dumbbell, nodes: 6}", (6, 6 + 5)),
+            ("{type: fat_tree, k: 4}", (20, 20 + 32)),
+            (
+                "{type: barabasi_albert, nodes: 10, edges_per_node: 2}",
+                (10, 10 + 3 + 7 * 2),
+            ),
+            (
+                "{type: erdos_renyi, nodes: 8, edge_probability: 1}",
+                (8, 8 + 28),
+            ),
+        ];
+
+        for (options, counts) in topologies {
+            let gml = generate(options).unwrap();
+            assert_eq!(node_and_edge_counts(&gml), counts, "{options}");
+        }
+    }
+
+    #[test]
+    fn test_generate_seed() {
+        let options = "{type: barabasi_albert, nodes: 20, edges_per_node: 1, seed: 4}";
+        assert_eq!(generate(options), generate(options));
+
+        let other = "{type: barabasi_albert, nodes: 20, edges_per_node: 1, seed: 5}";
+        assert_ne!(generate(options), generate(other));
+    }
+
+    #[test]
+    fn test_generate_latency_range() {
+        let options = "{type: star, nodes: 30, min_latency: 2 ms, max_latency: 3 ms}";
+        let graph = NetworkGraph::parse(&generate(options).unwrap()).unwrap();
+
+        for edge in graph.graph().edge_indices() {
+            let latency = graph.edge_weight(edge).latency;
+            let latency = latency.convert(units::TimePrefix::Nano).unwrap().value();
+            assert!((2_000_000..=3_000_000).contains(&latency));
+        }
+    }
+
+    #[test]
+    fn test_invalid_topologies() {
+        let invalid = [
+            "{type: star, nodes: 1}",
+            "{type: dumbbell, nodes: 3}",
+            "{type: fat_tree, k: 3}",
+            "{type: barabasi_albert, nodes: 2, edges_per_node: 2}",
+            "{type: erdos_renyi, nodes: 5, edge_probability: 1.5}",
+            // disconnected
+            "{type: erdos_renyi, nodes: 5, edge_probability: 0}",
+            "{type: star, nodes: 3, min_latency: 0 ms}",
+            "{type: star, nodes: 3, min_latency: 5 ms, max_latency: 4 ms}",
+            "{type: star, nodes: 3, min_bandwidth: 2 Gbit, max_bandwidth: 1 Gbit}",
+        ];
+
+        for options in invalid {
+            assert!(generate(options).is_err(), "{options}");
+        }
+    }
+}
@@ -0,0 +1,464 @@
+//! Parsing of network graphs in the GraphML format.
+//!
+//! Node and edge attributes are given by `<data>` elements, whose `<key>` has the attribute's name
+//! (`attr.name`) and type (`attr.type`). The attribute names are the same as in the GML format. A
+//! key's `<default>` value applies to each node or edge that doesn't have the attribute. Node ids
+//! must be integers, optionally with an `n` prefix (for example `n3`), and the graph's
+//! `edgedefault` sets whether it's directed.
+//!
+//! Only the subset of XML that GraphML files use is supported: elements, attributes, text, CDATA
+//! sections, character and entity references, comments, processing instructions, and a doctype
+//! declaration without an internal subset.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use gml_parser::gml::{Edge, Gml, Node, Value};
+
+/// An XML element, where the names of the element and its attributes don't have a namespace
+/// prefix.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Content>,
+}
+
+#[derive(Debug)]
+enum Content {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|x| x.as_str())
+    }
+
+    /// The child elements with the name `name`.
+    fn elements<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter_map(move |x| match x {
+            Content::Element(e) if e.name == name => Some(e),
+            _ => None,
+        })
+    }
+
+    /// The text of the element, not including the text of its child elements.
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|x| match x {
+                Content::Text(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// The name, type, and default value of a GraphML attribute.
+#[derive(Debug)]
+struct Key {
+    name: String,
+    kind: String,
+    /// The kind of element that the attribute applies to, such as "node", "edge", or "all".
+    domain: String,
+    default: Option<Value<'static>>,
+}
+
+/// Parse a GraphML graph.
+pub fn parse(text: &str) -> Result<Gml<'static>, String> {
+    let root = parse_xml(text)?;
+    if root.name != "graphml" {
+        return Err(format!(
+            "The root element is '{}' rather than 'graphml'",
+            root.name
+        ));
+    }
+
+    let mut keys = HashMap::new();
+    for key in root.elements("key") {
+        let id = key
+            .attribute("id")
+            .ok_or("GraphML key doesn't have an 'id'")?;
+        let kind = key.attribute("attr.type").unwrap_or("string");
+        let default = key
+            .elements("default")
+            .next()
+            .map(|x| parse_value(&x.text(), kind))
+            .transpose()?;
+
+        keys.insert(
+            id.to_string(),
+            Key {
+                name: key.attribute("attr.name").unwrap_or(id).to_string(),
+                kind: kind.to_string(),
+                domain: key.attribute("for").unwrap_or("all").to_string(),
+                default,
+            },
+        );
+    }
+
+    let mut graphs = root.elements("graph");
+    let graph = graphs.next().ok_or("GraphML doesn't have a graph")?;
+    if graphs.next().is_some() {
+        return Err("GraphML has more than one graph".to_string());
+    }
+
+    let directed = match graph.attribute("edgedefault") {
+        Some("directed") => true,
+        Some("undirected") | None => false,
+        Some(x) => return Err(format!("GraphML 'edgedefault' '{x}' is not valid")),
+    };
+
+    let mut nodes = Vec::new();
+    for node in graph.elements("node") {
+        if node.elements("graph").next().is_some() {
+            return Err("GraphML nested graphs are not supported".to_string());
+        }
+
+        let id = node
+            .attribute("id")
+            .ok_or("GraphML node doesn't have an 'id'")?;
+        nodes.push(Node {
+            id: Some(node_id(id)?),
+            other: attributes(node, "node", &keys)?,
+        });
+    }
+
+    let mut edges = Vec::new();
+    for edge in graph.elements("edge") {
+        if edge
+            .attribute("directed")
+            .is_some_and(|x| (x == "true") != directed)
+        {
+            return Err(
+                "GraphML graphs with both directed and undirected edges are not supported"
+                    .to_string(),
+            );
+        }
+
+        let source = edge
+            .attribute("source")
+            .ok_or("GraphML edge doesn't have a 'source'")?;
+        let target = edge
+            .attribute("target")
+            .ok_or("GraphML edge doesn't have a 'target'")?;
+        edges.push(Edge {
+            source: node_id(source)?,
+            target: node_id(target)?,
+            other: attributes(edge, "edge", &keys)?,
+        });
+    }
+
+    Ok(Gml {
+        directed,
+        nodes,
+        edges,
+        other: HashMap::new(),
+    })
+}
+
+/// Parse a GraphML node id, which is an integer with an optional `n` prefix.
+fn node_id(id: &str) -> Result<u32, String> {
+    id.strip_prefix('n')
+        .unwrap_or(id)
+        .parse()
+        .map_err(|_| format!("GraphML node id '{id}' is not an integer"))
+}
+
+/// Get the attributes of a node or edge element, including the default values of the keys for the
+/// `domain` of the element.
+fn attributes(
+    element: &Element,
+    domain: &str,
+    keys: &HashMap<String, Key>,
+) -> Result<HashMap<Cow<'static, str>, Value<'static>>, String> {
+    let mut other = HashMap::new();
+
+    for key in keys.values() {
+        if key.domain != domain && key.domain != "all" {
+            continue;
+        }
+        if let Some(default) = &key.default {
+            other.insert(Cow::Owned(key.name.clone()), default.clone());
+        }
+    }
+
+    for data in element.elements("data") {
+        let id = data
+            .attribute("key")
+            .ok_or("GraphML data doesn't have a 'key'")?;
+        let key = keys
+            .get(id)
+            .ok_or(format!("GraphML data refers to the unknown key '{id}'"))?;
+        other.insert(
+            Cow::Owned(key.name.clone()),
+            parse_value(&data.text(), &key.kind)?,
+        );
+    }
+
+    Ok(other)
+}
+
+/// Parse the text of a GraphML value with the type `kind`. Booleans are converted to the integers
+/// 0 and 1.
+fn parse_value(text: &str, kind: &str) -> Result<Value<'static>, String> {
+    let text = text.trim();
+    let invalid = || format!("GraphML value '{text}' is not a valid {kind}");
+
+    Ok(match kind {
+        "boolean" => match text {
+            "true" => Value::Int(1),
+            "false" => Value::Int(0),
+            _ => return Err(invalid()),
+        },
+        "int" | "long" => Value::Int(text.parse().map_err(|_| invalid())?),
+        "float" | "double" => Value::Float(text.parse().map_err(|_| invalid())?),
+        "string" => Value::Str(Cow::Owned(text.to_string())),
+        _ => return Err(format!("GraphML attribute type '{kind}' is not supported")),
+    })
+}
+
+/// Parse an XML document into its root element.
+fn parse_xml(text: &str) -> Result<Element, String> {
+    // the bottom of the stack holds the top-level contents of the document
+    let mut stack = vec![Element::default()];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if let Some(x) = rest.strip_prefix("<!--") {
+            rest = skip_past(x, "-->")?;
+        } else if let Some(x) = rest.strip_prefix("<![CDATA[") {
+            let end = x.find("]]>").ok_or("XML CDATA section is not terminated")?;
+            let parent = stack.last_mut().unwrap();
+            parent.children.push(Content::Text(x[..end].to_string()));
+            rest = &x[end + 3..];
+        } else if let Some(x) = rest.strip_prefix("<?") {
+            rest = skip_past(x, "?>")?;
+        } else if let Some(x) = rest.strip_prefix("<!") {
+            rest = skip_past(x, ">")?;
+        } else if let Some(x) = rest.strip_prefix("</") {
+            let end = x.find('>').ok_or("XML end tag is not terminated")?;
+            let name = local_name(x[..end].trim());
+            if stack.len() == 1 || stack.last().unwrap().name != name {
+                return Err(format!("XML end tag '{name}' doesn't match a start tag"));
+            }
+            let element = stack.pop().unwrap();
+            let parent = stack.last_mut().unwrap();
+            parent.children.push(Content::Element(element));
+            rest = &x[end + 1..];
+        } else if let Some(x) = rest.strip_prefix('<') {
+            let (element, is_empty, x) = parse_start_tag(x)?;
+            if is_empty {
+                let parent = stack.last_mut().unwrap();
+                parent.children.push(Content::Element(element));
+            } else {
+                stack.push(element);
+            }
+            rest = x;
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let parent = stack.last_mut().unwrap();
+            parent.children.push(Content::Text(unescape(&rest[..end])?));
+            rest = &rest[end..];
+        }
+    }
+
+    if stack.len() != 1 {
+        let name = &stack.last().unwrap().name;
+        return Err(format!("XML element '{name}' is not closed"));
+    }
+
+    stack
+        .pop()
+        .unwrap()
+        .children
+        .into_iter()
+        .find_map(|x| match x {
+            Content::Element(e) => Some(e),
+            _ => None,
+        })
+        .ok_or("XML document doesn't have a root element".to_string())
+}
+
+/// Parse a start tag following its `<`. Returns the element, whether it's an empty element tag
+/// (ending with `/>`), and the text after the tag.
+fn parse_start_tag(text: &str) -> Result<(Element, bool, &str), String> {
+    let name_end = text
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .ok_or("XML start tag is not terminated")?;
+    let mut element = Element {
+        name: local_name(&text[..name_end]).to_string(),
+        ..Default::default()
+    };
+
+    let mut rest = &text[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(x) = rest.strip_prefix("/>") {
+            return Ok((element, true, x));
+        }
+        if let Some(x) = rest.strip_prefix('>') {
+            return Ok((element, false, x));
+        }
+
+        let invalid = || format!("XML start tag '{}' has an invalid attribute", element.name);
+        let equals = rest.find('=').ok_or_else(invalid)?;
+        let name = local_name(rest[..equals].trim()).to_string();
+        let value = rest[equals + 1..].trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|x| *x == '"' || *x == '\'')
+            .ok_or_else(invalid)?;
+        let value = &value[1..];
+        let end = value.find(quote).ok_or_else(invalid)?;
+
+        element.attributes.insert(name, unescape(&value[..end])?);
+        rest = &value[end + 1..];
+    }
+}
+
+/// The part of a name after its namespace prefix.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap()
+}
+
+/// Get the text following the first `end` in `text`.
+fn skip_past<'a>(text: &'a str, end: &str) -> Result<&'a str, String> {
+    let index = text
+        .find(end)
+        .ok_or(format!("XML markup is missing its closing '{end}'"))?;
+    Ok(&text[index + end.len()..])
+}
+
+/// Replace the character and entity references in XML text.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        let end = start
+            + rest[start..]
+                .find(';')
+                .ok_or("XML reference is not terminated")?;
+        let reference = &rest[start + 1..end];
+
+        let c = match reference {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = reference.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(decimal) = reference.strip_prefix('#') {
+                    decimal.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(char::from_u32)
+                    .ok_or(format!("XML reference '&{reference};' is not valid"))?
+            }
+        };
+
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+    }
+
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_graphml() {
+        let graphml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+              <!-- a comment with a <tag> -->
+              <key id="d0" for="node" attr.name="host_bandwidth_up" attr.type="string">
+                <default>10 Mbit</default>
+              </key>
+              <key id="d1" for="node" attr.name="label" attr.type="string"/>
+              <key id="d2" for="edge" attr.name="latency" attr.type="string"/>
+              <key id="d3" for="edge" attr.name="packet_loss" attr.type="double"/>
+              <key id="d4" for="edge" attr.name="mtu" attr.type="int"/>
+              <graph id="G" edgedefault="undirected">
+                <node id="n0">
+                  <data key="d1">a &amp; b &#x41;</data>
+                </node>
+                <node id="1">
+                  <data key="d0"><![CDATA[100 Mbit]]></data>
+                </node>
+                <edge source="n0" target="1">
+                  <data key="d2">5 ms</data>
+                  <data key='d3'>0.5</data>
+                  <data key="d4">1400</data>
+                </edge>
+              </graph>
+            </graphml>"#;
+
+        let gml = r#"graph [
+              directed 0
+              node [
+                id 0
+                label "a & b A"
+                host_bandwidth_up "10 Mbit"
+              ]
+              node [
+                id 1
+                host_bandwidth_up "100 Mbit"
+              ]
+              edge [
+                source 0
+                target 1
+                latency "5 ms"
+                packet_loss 0.5
+                mtu 1400
+              ]
+            ]"#;
+
+        assert_eq!(
+            parse(graphml).unwrap(),
+            gml_parser::parse(gml).unwrap().upgrade_to_owned()
+        );
+    }
+
+    #[test]
+    fn test_directed_graphml() {
+        let graphml = r#"<graphml><graph edgedefault="directed">
+            <node id="0"/><node id="1"/><edge source="0" target="1"/>
+            </graph></graphml>"#;
+        let graph = parse(graphml).unwrap();
+        assert!(graph.directed);
+        assert_eq!(graph.edges.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_graphml() {
+        let invalid = [
+            "",
+            "<graph/>",
+            "<graphml><graph>",
+            "<graphml></graph>",
+            "<graphml/>",
+            r#"<graphml><graph><node id="x"/></graph></graphml>"#,
+            r#"<graphml><graph><node id="0"><data key="d0">1</data></node></graph></graphml>"#,
+            r#"<graphml><key id="d0" attr.type="int"/><graph><node id="0">
+               <data key="d0">1.5</data></node></graph></graphml>"#,
+            r#"<graphml><graph edgedefault="undirected"><node id="0"/>
+               <edge source="0" target="0" directed="true"/></graph></graphml>"#,
+            r#"<graphml><graph><node id="0"><graph/></node></graph></graphml>"#,
+            "<graphml><graph>&bad;</graph></graphml>",
+        ];
+
+        for graphml in invalid {
+            assert!(parse(graphml).is_err(), "{graphml:?}");
+        }
+    }
+}
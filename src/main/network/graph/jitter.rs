@@ -0,0 +1,251 @@
+//! Random variation ("jitter") in the latency of network graph edges and host uplinks.
+
+use rand::Rng;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::utility::tilde_expansion;
+
+/// A random latency that's added to each packet. Samples are always non-negative so that packets
+/// are never delivered earlier than the latency of their path allows.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Jitter {
+    /// The scale of the samples in nanoseconds. A packet's jitter is a sample of the distribution
+    /// multiplied by this scale.
+    pub scale_ns: u64,
+    pub distribution: JitterDistribution,
+}
+
+impl Jitter {
+    /// Returns true if this jitter never delays packets.
+    pub fn is_none(&self) -> bool {
+        self.scale_ns == 0
+    }
+
+    /// Sample the jitter for a single packet.
+    pub fn sample(&self, rng: &mut impl Rng) -> SimulationTime {
+        let x = match self.distribution {
+            JitterDistribution::Normal => standard_normal(rng).abs(),
+            JitterDistribution::LogNormal => standard_normal(rng).exp(),
+            // a pareto distribution shifted to start at 0 (a "lomax" distribution) with shape 2
+            JitterDistribution::Pareto => unit_interval(rng).powf(-0.5) - 1.0,
+            JitterDistribution::Empirical(cdf) => cdf.inverse(rng.gen()),
+        };
+
+        // the float-to-int cast saturates, so very large samples are clamped
+        let ns = (x * self.scale_ns as f64).round() as u64;
+        SimulationTime::try_from_nanos(ns).unwrap_or(SimulationTime::MAX)
+    }
+}
+
+/// The distribution of an edge's or uplink's jitter.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum JitterDistribution {
+    /// The absolute value of a standard normal distribution.
+    #[default]
+    Normal,
+    /// A pareto distribution with shape 2, shifted to start at 0.
+    Pareto,
+    /// A log-normal distribution whose logarithm is a standard normal distribution.
+    LogNormal,
+    /// An empirical distribution loaded from a file.
+    Empirical(&'static EmpiricalCdf),
+}
+
+impl std::str::FromStr for JitterDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "normal" => Self::Normal,
+            "pareto" => Self::Pareto,
+            "lognormal" => Self::LogNormal,
+            _ => {
+                let Some(path) = s.strip_prefix("empirical:") else {
+                    return Err(format!(
+                        "'{s}' is not one of 'normal', 'pareto', 'lognormal', or 'empirical:<path>'"
+                    ));
+                };
+
+                let text = std::fs::read_to_string(tilde_expansion(path))
+                    .map_err(|e| format!("Failed to read CDF file '{path}': {e}"))?;
+                let cdf = EmpiricalCdf::parse(&text)
+                    .map_err(|e| format!("Failed to parse CDF file '{path}': {e}"))?;
+
+                // the graph is loaded once and its edges are copied into the paths between
+                // nodes, so we leak the CDF rather than reference count it
+                Self::Empirical(Box::leak(Box::new(cdf)))
+            }
+        })
+    }
+}
+
+/// A cumulative distribution function given by a list of points, which is linearly interpolated
+/// between the points.
+#[derive(Debug, PartialEq)]
+pub struct EmpiricalCdf {
+    /// Pairs of (value, cumulative probability), in ascending order of both.
+    points: Vec<(f64, f64)>,
+}
+
+impl EmpiricalCdf {
+    /// Parse a CDF where each line contains a value and its cumulative probability separated by
+    /// whitespace. Empty lines and lines starting with '#' are ignored. The values must be
+    /// non-negative, both columns must be non-decreasing, and the last probability must be 1.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut points: Vec<(f64, f64)> = Vec::new();
+
+        for (line_num, line) in text.lines().enumerate().map(|(i, x)| (i + 1, x.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse = |x: Option<&str>| -> Result<f64, String> {
+                x.ok_or(format!("Line {line_num} does not have two columns"))?
+                    .parse()
+                    .map_err(|e| format!("Line {line_num} is not a valid number: {e}"))
+            };
+
+            let mut columns = line.split_whitespace();
+            let value = parse(columns.next())?;
+            let probability = parse(columns.next())?;
+
+            if columns.next().is_some() {
+                return Err(format!("Line {line_num} has more than two columns"));
+            }
+
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!(
+                    "Line {line_num} has a negative or non-finite value"
+                ));
+            }
+
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(format!("Line {line_num} has a probability not in [0,1]"));
+            }
+
+            if let Some((last_value, last_probability)) = points.last() {
+                if value < *last_value || probability < *last_probability {
+                    return Err(format!("Line {line_num} is not in ascending order"));
+                }
+            }
+
+            points.push((value, probability));
+        }
+
+        if points.last().map(|(_, p)| *p) != Some(1.0) {
+            return Err("The last probability is not 1".into());
+        }
+
+        Ok(Self { points })
+    }
+
+    /// The value at cumulative probability `p`, in the range [0,1].
+    pub fn inverse(&self, p: f64) -> f64 {
+        // the first point whose probability is at least `p`
+        let i = self.points.partition_point(|(_, x)| *x < p);
+        let (value, probability) = self.points[std::cmp::min(i, self.points.len() - 1)];
+
+        if i == 0 || probability == p {
+            return value;
+        }
+
+        let (prev_value, prev_probability) = self.points[i - 1];
+        let fraction = (p - prev_probability) / (probability - prev_probability);
+        prev_value + fraction * (value - prev_value)
+    }
+}
+
+/// A sample in the range (0,1].
+fn unit_interval(rng: &mut impl Rng) -> f64 {
+    1.0 - rng.gen::<f64>()
+}
+
+/// A sample of the standard normal distribution using the Box-Muller transform.
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1 = unit_interval(rng);
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_cdf() {
+        let cdf = EmpiricalCdf::parse("# comment\n0 0.0\n1 0.5\n\n3 1\n").unwrap();
+        assert_eq!(cdf.points, [(0.0, 0.0), (1.0, 0.5), (3.0, 1.0)]);
+
+        assert!(EmpiricalCdf::parse("").is_err());
+        assert!(EmpiricalCdf::parse("1 0.5").is_err());
+        assert!(EmpiricalCdf::parse("1").is_err());
+        assert!(EmpiricalCdf::parse("1 1 1").is_err());
+        assert!(EmpiricalCdf::parse("-1 1").is_err());
+        assert!(EmpiricalCdf::parse("1 1.5").is_err());
+        assert!(EmpiricalCdf::parse("2 0.5\n1 1").is_err());
+        assert!(EmpiricalCdf::parse("1 0.5\n2 0.4\n3 1").is_err());
+    }
+
+    #[test]
+    fn test_cdf_inverse() {
+        let cdf = EmpiricalCdf::parse("1 0.2\n2 0.6\n4 1").unwrap();
+        assert_eq!(cdf.inverse(0.0), 1.0);
+        assert_eq!(cdf.inverse(0.2), 1.0);
+        assert!((cdf.inverse(0.4) - 1.5).abs() < 1e-9);
+        assert_eq!(cdf.inverse(0.6), 2.0);
+        assert!((cdf.inverse(0.7) - 2.5).abs() < 1e-9);
+        assert_eq!(cdf.inverse(1.0), 4.0);
+    }
+
+    #[test]
+    fn test_parse_distribution() {
+        assert_eq!("normal".parse(), Ok(JitterDistribution::Normal));
+        assert_eq!("pareto".parse(), Ok(JitterDistribution::Pareto));
+        assert_eq!("lognormal".parse(), Ok(JitterDistribution::LogNormal));
+        assert!("uniform".parse::<JitterDistribution>().is_err());
+        assert!("empirical:/nonexistent/file"
+            .parse::<JitterDistribution>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let cdf = Box::leak(Box::new(EmpiricalCdf::parse("1 0.5\n2 1").unwrap()));
+
+        for distribution in [
+            JitterDistribution::Normal,
+            JitterDistribution::Pareto,
+            JitterDistribution::LogNormal,
+            JitterDistribution::Empirical(cdf),
+        ] {
+            let jitter = Jitter {
+                scale_ns: 1000,
+                distribution,
+            };
+
+            // the same seed gives the same samples
+            let mut rng_1 = Xoshiro256PlusPlus::seed_from_u64(1);
+            let mut rng_2 = Xoshiro256PlusPlus::seed_from_u64(1);
+            let samples: Vec<_> = (0..1000).map(|_| jitter.sample(&mut rng_1)).collect();
+            let samples_2: Vec<_> = (0..1000).map(|_| jitter.sample(&mut rng_2)).collect();
+            assert_eq!(samples, samples_2);
+
+            // the samples vary
+            assert!(samples.iter().any(|x| *x != samples[0]));
+
+            if let JitterDistribution::Empirical(_) = distribution {
+                let range = SimulationTime::from_nanos(1000)..=SimulationTime::from_nanos(2000);
+                assert!(samples.iter().all(|x| range.contains(x)));
+            }
+        }
+
+        // no scale means no jitter
+        let jitter = Jitter::default();
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        assert!(jitter.is_none());
+        assert_eq!(jitter.sample(&mut rng), SimulationTime::ZERO);
+    }
+}
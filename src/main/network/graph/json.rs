@@ -0,0 +1,194 @@
+//! Parsing of network graphs in a JSON format.
+//!
+//! The graph is an object with an optional `directed` boolean, a `nodes` array, and an `edges`
+//! array (or a `links` array, as written by networkx). Each node is an object with an integer `id`
+//! and each edge is an object with integer `source` and `target` ids. The other fields of nodes
+//! and edges are their attributes, which have the same names as in the GML format:
+//!
+//! ```json
+//! {
+//!   "directed": false,
+//!   "nodes": [{"id": 0, "host_bandwidth_up": "1 Gbit", "host_bandwidth_down": "1 Gbit"}],
+//!   "edges": [{"source": 0, "target": 0, "latency": "1 ms", "packet_loss": 0.0}]
+//! }
+//! ```
+//!
+//! Attributes are strings, integers, floats, or booleans, which are converted to the integers 0 and
+//! 1. Other fields of the graph are ignored.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use gml_parser::gml::{Edge, Gml, Node, Value};
+
+/// Parse a JSON graph.
+pub fn parse(text: &str) -> Result<Gml<'static>, String> {
+    let graph: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| format!("Invalid JSON graph: {e}"))?;
+    let graph = graph.as_object().ok_or("JSON graph is not an object")?;
+
+    let directed = match graph.get("directed") {
+        Some(x) => x
+            .as_bool()
+            .ok_or("JSON graph 'directed' is not a boolean")?,
+        None => false,
+    };
+
+    let nodes = graph
+        .get("nodes")
+        .ok_or("JSON graph doesn't have 'nodes'")?
+        .as_array()
+        .ok_or("JSON graph 'nodes' is not an array")?
+        .iter()
+        .map(|node| {
+            let mut other = attributes(node, "node")?;
+            Ok(Node {
+                id: Some(remove_id(&mut other, "node", "id")?),
+                other: convert(other)?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    let edges = graph
+        .get("edges")
+        .or_else(|| graph.get("links"))
+        .ok_or("JSON graph doesn't have 'edges'")?
+        .as_array()
+        .ok_or("JSON graph 'edges' is not an array")?
+        .iter()
+        .map(|edge| {
+            let mut other = attributes(edge, "edge")?;
+            Ok(Edge {
+                source: remove_id(&mut other, "edge", "source")?,
+                target: remove_id(&mut other, "edge", "target")?,
+                other: convert(other)?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(Gml {
+        directed,
+        nodes,
+        edges,
+        other: HashMap::new(),
+    })
+}
+
+/// Get the fields of a node or edge object.
+fn attributes<'a>(
+    object: &'a serde_json::Value,
+    kind: &str,
+) -> Result<HashMap<&'a str, &'a serde_json::Value>, String> {
+    Ok(object
+        .as_object()
+        .ok_or(format!("JSON {kind} is not an object"))?
+        .iter()
+        .map(|(k, v)| (k.as_str(), v))
+        .collect())
+}
+
+/// Remove the node id field `name` from the fields of a node or edge object.
+fn remove_id(
+    fields: &mut HashMap<&str, &serde_json::Value>,
+    kind: &str,
+    name: &str,
+) -> Result<u32, String> {
+    fields
+        .remove(name)
+        .ok_or(format!("JSON {kind} doesn't have a '{name}'"))?
+        .as_u64()
+        .and_then(|x| u32::try_from(x).ok())
+        .ok_or(format!("JSON {kind} '{name}' is not a valid node id"))
+}
+
+/// Convert the fields of a node or edge object to GML values.
+fn convert(
+    fields: HashMap<&str, &serde_json::Value>,
+) -> Result<HashMap<Cow<'static, str>, Value<'static>>, String> {
+    fields
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::Bool(x) => Value::Int(i32::from(*x)),
+                serde_json::Value::Number(x) => match x.as_i64() {
+                    Some(x) => Value::Int(
+                        i32::try_from(x)
+                            .map_err(|_| format!("JSON attribute '{name}' is out of range"))?,
+                    ),
+                    None => Value::Float(x.as_f64().unwrap() as f32),
+                },
+                serde_json::Value::String(x) => Value::Str(Cow::Owned(x.clone())),
+                _ => {
+                    return Err(format!(
+                        "JSON attribute '{name}' is not a string, number, or boolean"
+                    ))
+                }
+            };
+            Ok((Cow::Owned(name.to_string()), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let json = r#"{
+            "directed": true,
+            "multigraph": false,
+            "nodes": [
+                {"id": 0, "label": "a", "host_bandwidth_up": "10 Mbit"},
+                {"id": 1}
+            ],
+            "links": [
+                {"source": 0, "target": 1, "latency": "5 ms", "packet_loss": 0.5, "mtu": 1400}
+            ]
+        }"#;
+
+        let gml = r#"graph [
+              directed 1
+              node [
+                id 0
+                label "a"
+                host_bandwidth_up "10 Mbit"
+              ]
+              node [
+                id 1
+              ]
+              edge [
+                source 0
+                target 1
+                latency "5 ms"
+                packet_loss 0.5
+                mtu 1400
+              ]
+            ]"#;
+
+        assert_eq!(
+            parse(json).unwrap(),
+            gml_parser::parse(gml).unwrap().upgrade_to_owned()
+        );
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let invalid = [
+            "",
+            "[]",
+            r#"{"edges": []}"#,
+            r#"{"nodes": []}"#,
+            r#"{"directed": 0, "nodes": [], "edges": []}"#,
+            r#"{"nodes": [{"label": "a"}], "edges": []}"#,
+            r#"{"nodes": [{"id": -1}], "edges": []}"#,
+            r#"{"nodes": [{"id": 0}], "edges": [{"source": 0}]}"#,
+            r#"{"nodes": [{"id": 0, "label": ["a"]}], "edges": []}"#,
+            r#"{"nodes": [{"id": 0, "mtu": 5000000000}], "edges": []}"#,
+        ];
+
+        for json in invalid {
+            assert!(parse(json).is_err(), "{json:?}");
+        }
+    }
+}
@@ -1,34 +1,90 @@
+pub mod caida;
+mod compose;
+pub mod generate;
+mod graphml;
+pub mod jitter;
+mod json;
+pub mod path_cache;
 mod petgraph_wrapper;
+pub mod routing;
+pub mod wireless;
 
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::hash::Hash;
 
 use anyhow::Context;
+use gml_parser::gml::Value;
 use log::*;
-use petgraph::graph::NodeIndex;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::{EdgeFiltered, EdgeRef};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use crate::core::configuration::{self, Compression, FileSource, GraphOptions, GraphSource};
+use crate::network::graph::jitter::{Jitter, JitterDistribution};
+use crate::network::graph::path_cache::PathCache;
 use crate::network::graph::petgraph_wrapper::GraphWrapper;
+use crate::network::graph::routing::{Relationship, RouteCost, RoutingStrategy};
+use crate::network::graph::wireless::AccessPoint;
+use crate::network::link_trace::LinkTraceRow;
+use crate::network::nat::Subnet;
 use crate::utility::tilde_expansion;
 use crate::utility::units::{self, Unit};
 
 type NetGraphError = Box<dyn Error + Send + Sync + 'static>;
 
 /// A graph node.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct ShadowNode {
     pub id: u32,
     pub bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
     pub bandwidth_up: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    pub jitter: units::Time<units::TimePrefix>,
+    pub jitter_distribution: JitterDistribution,
+    /// The address of the node's router, which is the source of the ICMP "time exceeded" errors
+    /// that the router sends.
+    pub router_address: Option<std::net::Ipv4Addr>,
+    /// The latitude and longitude of the node in degrees. Edges between nodes with a location
+    /// don't need a latency, since it's computed from the distance between the nodes.
+    pub location: Option<(f64, f64)>,
+    /// The node's wireless access point, if its hosts share a radio channel.
+    pub wireless: Option<AccessPoint>,
 }
 
 impl TryFrom<gml_parser::gml::Node<'_>> for ShadowNode {
     type Error = String;
 
     fn try_from(mut gml_node: gml_parser::gml::Node) -> Result<Self, Self::Error> {
+        let mut degrees = |name: &str| {
+            gml_node
+                .other
+                .remove(name)
+                .map(|x| match x {
+                    Value::Int(x) => Ok(f64::from(x)),
+                    Value::Float(x) => Ok(f64::from(x)),
+                    Value::Str(_) => Err(format!("Node '{name}' is not a number")),
+                })
+                .transpose()
+        };
+
+        let location = match (degrees("latitude")?, degrees("longitude")?) {
+            (Some(latitude), Some(longitude)) => {
+                if !(-90.0..=90.0).contains(&latitude) {
+                    return Err("Node 'latitude' is not in the range [-90,90]".into());
+                }
+                if !(-180.0..=180.0).contains(&longitude) {
+                    return Err("Node 'longitude' is not in the range [-180,180]".into());
+                }
+                Some((latitude, longitude))
+            }
+            (None, None) => None,
+            _ => return Err("Node 'latitude' and 'longitude' must be provided together".into()),
+        };
+
+        let wireless = AccessPoint::from_node(&mut gml_node)?;
+
         Ok(Self {
             id: gml_node.id.ok_or("Node 'id' was not provided")?,
             bandwidth_down: gml_node
@@ -55,18 +111,127 @@ impl TryFrom<gml_parser::gml::Node<'_>> for ShadowNode {
                         .map_err(|e| format!("Node 'host_bandwidth_up' is not a valid unit: {}", e))
                 })
                 .transpose()?,
+            jitter: match gml_node.other.remove("host_jitter") {
+                Some(x) => x
+                    .as_str()
+                    .ok_or("Node 'host_jitter' is not a string")?
+                    .parse()
+                    .map_err(|e| format!("Node 'host_jitter' is not a valid unit: {}", e))?,
+                None => units::Time::new(0, units::TimePrefix::Milli),
+            },
+            jitter_distribution: match gml_node.other.remove("host_jitter_distribution") {
+                Some(x) => x
+                    .as_str()
+                    .ok_or("Node 'host_jitter_distribution' is not a string")?
+                    .parse()
+                    .map_err(|e| format!("Node 'host_jitter_distribution' is not valid: {}", e))?,
+                None => JitterDistribution::default(),
+            },
+            router_address: gml_node
+                .other
+                .remove("router_address")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Node 'router_address' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Node 'router_address' is not valid: {}", e))
+                })
+                .transpose()?,
+            location,
+            wireless,
         })
     }
 }
 
 /// A graph edge.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ShadowEdge {
     pub source: u32,
     pub target: u32,
     pub latency: units::Time<units::TimePrefix>,
     pub jitter: units::Time<units::TimePrefix>,
+    pub jitter_distribution: JitterDistribution,
     pub packet_loss: f32,
+    pub packet_corruption: f32,
+    pub packet_reorder: f32,
+    pub reorder_delay: Option<units::Time<units::TimePrefix>>,
+    pub reorder_gap: u32,
+    pub mtu: Option<u32>,
+    pub bandwidth: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    pub buffer_size: Option<units::Bytes<units::SiPrefixUpper>>,
+    /// The latency from the target to the source of an undirected edge, if it's different from
+    /// the latency from the source to the target.
+    pub reverse_latency: Option<units::Time<units::TimePrefix>>,
+    /// The packet loss from the target to the source of an undirected edge, if it's different.
+    pub reverse_packet_loss: Option<f32>,
+    /// The bandwidth from the target to the source of an undirected edge, if it's different.
+    pub reverse_bandwidth: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    /// The business relationship of the target to the source, which is used by policy routing.
+    pub relationship: Option<Relationship>,
+}
+
+impl ShadowEdge {
+    /// Whether the edge has any attributes that only apply from its target to its source.
+    pub fn is_asymmetric(&self) -> bool {
+        self.reverse_latency.is_some()
+            || self.reverse_packet_loss.is_some()
+            || self.reverse_bandwidth.is_some()
+    }
+
+    /// The properties of the edge when it's traversed from the node with id `from`.
+    pub fn properties_from(&self, from: u32) -> PathProperties {
+        if from == self.source {
+            return self.into();
+        }
+
+        // attributes without a reverse value are the same in both directions
+        let reversed = Self {
+            latency: self.reverse_latency.unwrap_or(self.latency),
+            packet_loss: self.reverse_packet_loss.unwrap_or(self.packet_loss),
+            ..*self
+        };
+        (&reversed).into()
+    }
+
+    /// The bandwidth of the edge when it's traversed from the node with id `from`.
+    pub fn bandwidth_from(&self, from: u32) -> Option<units::BitsPerSec<units::SiPrefixUpper>> {
+        if from == self.source {
+            return self.bandwidth;
+        }
+
+        self.reverse_bandwidth.or(self.bandwidth)
+    }
+
+    /// The business relationship of the node at the other end of the edge to the node with id
+    /// `from`.
+    pub fn relationship_from(&self, from: u32) -> Option<Relationship> {
+        if from == self.source {
+            return self.relationship;
+        }
+
+        self.relationship.map(Relationship::inverse)
+    }
+
+    /// The edge with the attributes that are set by a row of a link trace. The trace's attributes
+    /// apply in both directions of the edge.
+    pub fn with_trace_row(&self, row: &LinkTraceRow) -> Self {
+        let mut edge = self.clone();
+
+        if let Some(latency) = row.latency {
+            edge.latency = latency;
+            edge.reverse_latency = None;
+        }
+        if let Some(bandwidth) = row.bandwidth {
+            edge.bandwidth = Some(bandwidth);
+            edge.reverse_bandwidth = None;
+        }
+        if let Some(loss) = row.loss {
+            edge.packet_loss = loss;
+            edge.reverse_packet_loss = None;
+        }
+
+        edge
+    }
 }
 
 impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
@@ -92,20 +257,166 @@ impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
                     .map_err(|e| format!("Edge 'jitter' is not a valid unit: {}", e))?,
                 None => units::Time::new(0, units::TimePrefix::Milli),
             },
+            jitter_distribution: match gml_edge.other.remove("jitter_distribution") {
+                Some(x) => x
+                    .as_str()
+                    .ok_or("Edge 'jitter_distribution' is not a string")?
+                    .parse()
+                    .map_err(|e| format!("Edge 'jitter_distribution' is not valid: {}", e))?,
+                None => JitterDistribution::default(),
+            },
             packet_loss: match gml_edge.other.remove("packet_loss") {
                 Some(x) => x.as_float().ok_or("Edge 'packet_loss' is not a float")?,
                 None => 0.0,
             },
+            packet_corruption: match gml_edge.other.remove("packet_corruption") {
+                Some(x) => x
+                    .as_float()
+                    .ok_or("Edge 'packet_corruption' is not a float")?,
+                None => 0.0,
+            },
+            packet_reorder: match gml_edge.other.remove("packet_reorder") {
+                Some(x) => x.as_float().ok_or("Edge 'packet_reorder' is not a float")?,
+                None => 0.0,
+            },
+            reorder_delay: gml_edge
+                .other
+                .remove("reorder_delay")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Edge 'reorder_delay' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'reorder_delay' is not a valid unit: {}", e))
+                })
+                .transpose()?,
+            reorder_gap: gml_edge
+                .other
+                .remove("reorder_gap")
+                .map(|x| x.as_int().ok_or("Edge 'reorder_gap' is not an integer"))
+                .transpose()?
+                .map(|x| u32::try_from(x).or(Err("Edge 'reorder_gap' is negative")))
+                .transpose()?
+                .unwrap_or(0),
+            mtu: gml_edge
+                .other
+                .remove("mtu")
+                .map(|x| x.as_int().ok_or("Edge 'mtu' is not an integer"))
+                .transpose()?
+                .map(|x| u32::try_from(x).or(Err("Edge 'mtu' is negative")))
+                .transpose()?,
+            bandwidth: gml_edge
+                .other
+                .remove("bandwidth")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Edge 'bandwidth' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'bandwidth' is not a valid unit: {}", e))
+                })
+                .transpose()?,
+            buffer_size: gml_edge
+                .other
+                .remove("buffer_size")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Edge 'buffer_size' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'buffer_size' is not a valid unit: {}", e))
+                })
+                .transpose()?,
+            reverse_latency: gml_edge
+                .other
+                .remove("reverse_latency")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Edge 'reverse_latency' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'reverse_latency' is not a valid unit: {}", e))
+                })
+                .transpose()?,
+            reverse_packet_loss: gml_edge
+                .other
+                .remove("reverse_packet_loss")
+                .map(|x| {
+                    x.as_float()
+                        .ok_or("Edge 'reverse_packet_loss' is not a float")
+                })
+                .transpose()?,
+            reverse_bandwidth: gml_edge
+                .other
+                .remove("reverse_bandwidth")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Edge 'reverse_bandwidth' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'reverse_bandwidth' is not a valid unit: {}", e))
+                })
+                .transpose()?,
+            relationship: gml_edge
+                .other
+                .remove("relationship")
+                .map(|x| {
+                    x.as_str()
+                        .ok_or("Edge 'relationship' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'relationship' is not valid: {}", e))
+                })
+                .transpose()?,
         };
 
         if rv.packet_loss < 0f32 || rv.packet_loss > 1f32 {
             return Err("Edge 'packet_loss' is not in the range [0,1]".into());
         }
 
+        if rv
+            .reverse_packet_loss
+            .is_some_and(|x| !(0f32..=1f32).contains(&x))
+        {
+            return Err("Edge 'reverse_packet_loss' is not in the range [0,1]".into());
+        }
+
+        if rv.packet_corruption < 0f32 || rv.packet_corruption > 1f32 {
+            return Err("Edge 'packet_corruption' is not in the range [0,1]".into());
+        }
+
+        if rv.packet_reorder < 0f32 || rv.packet_reorder > 1f32 {
+            return Err("Edge 'packet_reorder' is not in the range [0,1]".into());
+        }
+
+        // the minimum MTU of an IPv4 link
+        if rv.mtu.is_some_and(|x| !(68..=65535).contains(&x)) {
+            return Err("Edge 'mtu' is not in the range [68,65535]".into());
+        }
+
         if rv.latency.value() == 0 {
             return Err("Edge 'latency' must not be 0".into());
         }
 
+        if rv.bandwidth.is_some_and(|x| x.value() == 0) {
+            return Err("Edge 'bandwidth' must not be 0".into());
+        }
+
+        if rv.reverse_latency.is_some_and(|x| x.value() == 0) {
+            return Err("Edge 'reverse_latency' must not be 0".into());
+        }
+
+        if rv.reverse_bandwidth.is_some_and(|x| x.value() == 0) {
+            return Err("Edge 'reverse_bandwidth' must not be 0".into());
+        }
+
+        if rv.buffer_size.is_some() && rv.bandwidth.is_none() && rv.reverse_bandwidth.is_none() {
+            return Err("Edge 'buffer_size' requires a 'bandwidth'".into());
+        }
+
+        // a self-loop is only ever traversed in one direction
+        if rv.source == rv.target && rv.is_asymmetric() {
+            return Err("Edge 'reverse_*' attributes are not valid for self-loops".into());
+        }
+
+        if rv.source == rv.target && rv.relationship.is_some() {
+            return Err("Edge 'relationship' is not valid for self-loops".into());
+        }
+
         Ok(rv)
     }
 }
@@ -116,6 +427,16 @@ impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
 pub struct NetworkGraph {
     graph: GraphWrapper<ShadowNode, ShadowEdge, u32>,
     node_id_to_index_map: HashMap<u32, NodeIndex>,
+    /// Edges that are down, which paths don't cross.
+    down_edges: HashSet<EdgeIndex>,
+    /// Weights that replace the weights of edges in the graph, such as weights from link traces.
+    edge_weights: HashMap<EdgeIndex, ShadowEdge>,
+    /// How the shortest paths are chosen.
+    routing: RoutingStrategy,
+    /// The edges of each static route, and the nodes they're traversed from.
+    static_routes: HashMap<(NodeIndex, NodeIndex), Vec<(EdgeIndex, NodeIndex)>>,
+    /// Which of the equal-cost shortest paths between each pair of nodes is computed.
+    path_index: usize,
 }
 
 impl NetworkGraph {
@@ -131,8 +452,46 @@ impl NetworkGraph {
         self.graph.node_weight(index).map(|w| w.id)
     }
 
+    /// Get the ids and router addresses of the nodes that have a router address, ordered by id.
+    pub fn router_addresses(&self) -> Vec<(u32, std::net::Ipv4Addr)> {
+        let mut addrs: Vec<_> = self
+            .node_id_to_index_map
+            .iter()
+            .filter_map(|(id, index)| {
+                Some((*id, self.graph.node_weight(*index).unwrap().router_address?))
+            })
+            .collect();
+        addrs.sort();
+        addrs
+    }
+
+    /// Get the ids of the nodes that have a wireless access point, ordered by id.
+    pub fn access_points(&self) -> Vec<u32> {
+        let mut ids: Vec<_> = self
+            .node_id_to_index_map
+            .iter()
+            .filter(|(_, index)| self.graph.node_weight(**index).unwrap().wireless.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Parse a graph in the GML, GraphML, or JSON format. The format is detected from the first
+    /// character of the graph that isn't whitespace.
     pub fn parse(graph_text: &str) -> Result<Self, NetGraphError> {
-        let gml_graph = gml_parser::parse(graph_text)?;
+        let mut gml_graph = parse_gml(graph_text)?;
+
+        // the factor that the latencies computed from the locations of nodes are multiplied by
+        let latency_overhead = match gml_graph.other.remove("latency_overhead") {
+            Some(Value::Int(x)) => f64::from(x),
+            Some(Value::Float(x)) => f64::from(x),
+            Some(Value::Str(_)) => return Err("Graph 'latency_overhead' is not a number".into()),
+            None => 1.0,
+        };
+        if latency_overhead <= 0.0 {
+            return Err("Graph 'latency_overhead' must be greater than 0".into());
+        }
 
         let mut g = match gml_graph.directed {
             true => GraphWrapper::Directed(
@@ -153,17 +512,42 @@ impl NetworkGraph {
 
         // map from GML id to petgraph id
         let mut id_map = HashMap::new();
+        // map from GML id to the node's location
+        let mut locations = HashMap::new();
 
         for x in gml_graph.nodes.into_iter() {
             let x: ShadowNode = x.try_into()?;
             let gml_id = x.id;
+            if let Some(location) = x.location {
+                locations.insert(gml_id, location);
+            }
             let petgraph_id = g.add_node(x);
             id_map.insert(gml_id, petgraph_id);
         }
 
-        for x in gml_graph.edges.into_iter() {
+        for mut x in gml_graph.edges.into_iter() {
+            // an edge between two nodes with a location has a latency from the distance between them
+            if x.source != x.target && !x.other.contains_key("latency") {
+                if let (Some(a), Some(b)) = (locations.get(&x.source), locations.get(&x.target)) {
+                    let latency_ns = geographic_latency_ns(*a, *b, latency_overhead);
+                    x.other.insert(
+                        "latency".into(),
+                        Value::Str(format!("{latency_ns} ns").into()),
+                    );
+                }
+            }
+
             let x: ShadowEdge = x.try_into()?;
 
+            // each direction of a directed graph is already a separate edge
+            if gml_graph.directed && x.is_asymmetric() {
+                return Err(format!(
+                    "Edge {} -> {} has 'reverse_*' attributes in a directed graph",
+                    x.source, x.target
+                )
+                .into());
+            }
+
             let source = *id_map
                 .get(&x.source)
                 .ok_or(format!("Edge source {} doesn't exist", x.source))?;
@@ -177,46 +561,151 @@ impl NetworkGraph {
         Ok(Self {
             graph: g,
             node_id_to_index_map: id_map,
+            down_edges: HashSet::new(),
+            edge_weights: HashMap::new(),
+            routing: RoutingStrategy::default(),
+            static_routes: HashMap::new(),
+            path_index: 0,
         })
     }
 
+    /// Get the edge between the nodes with ids `source` and `target`. An undirected edge can be
+    /// found from either of its nodes.
+    pub fn edge_between(&self, source: u32, target: u32) -> Option<EdgeIndex> {
+        let source = *self.node_id_to_index(source)?;
+        let target = *self.node_id_to_index(target)?;
+        self.graph.find_edge(source, target)
+    }
+
+    /// Set the edges that are down. Paths don't cross edges that are down, so some nodes may not
+    /// have a path between them.
+    pub fn set_down_edges(&mut self, edges: HashSet<EdgeIndex>) {
+        self.down_edges = edges;
+    }
+
+    /// Set the weights that replace the weights of edges in the graph. Edges without a weight in
+    /// `weights` use their weight from the graph.
+    pub fn set_edge_weights(&mut self, weights: HashMap<EdgeIndex, ShadowEdge>) {
+        self.edge_weights = weights;
+    }
+
+    /// Get the current weight of an edge.
+    pub fn edge_weight(&self, edge: EdgeIndex) -> &ShadowEdge {
+        self.edge_weights
+            .get(&edge)
+            .unwrap_or_else(|| self.graph.edge_weight(edge).unwrap())
+    }
+
+    /// Set how the shortest paths are chosen. Returns an error if a static route visits a node
+    /// that doesn't exist, or crosses between two nodes that don't have an edge between them, or if
+    /// policy routing is used and an edge between two different nodes doesn't have a relationship.
+    pub fn set_routing(&mut self, routing: RoutingStrategy) -> Result<(), NetGraphError> {
+        let mut static_routes = HashMap::new();
+
+        if routing == RoutingStrategy::GaoRexford {
+            for edge in self.graph.edge_indices() {
+                let weight = self.graph.edge_weight(edge).unwrap();
+                if weight.source != weight.target && weight.relationship.is_none() {
+                    return Err(format!(
+                        "Edge connecting node {} to {} doesn't have a 'relationship'",
+                        weight.source, weight.target
+                    )
+                    .into());
+                }
+            }
+        }
+
+        if let RoutingStrategy::Static(routes) = &routing {
+            for route in routes.iter() {
+                let nodes = route
+                    .iter()
+                    .map(|id| {
+                        self.node_id_to_index(*id)
+                            .copied()
+                            .ok_or(format!("Static route node {id} doesn't exist"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let edges = nodes
+                    .windows(2)
+                    .map(|pair| Ok((self.find_edge(pair[0], pair[1])?, pair[0])))
+                    .collect::<Result<Vec<_>, NetGraphError>>()?;
+
+                static_routes.insert((nodes[0], *nodes.last().unwrap()), edges);
+            }
+        }
+
+        self.routing = routing;
+        self.static_routes = static_routes;
+
+        Ok(())
+    }
+
+    /// Set which of the equal-cost shortest paths between each pair of nodes is computed, in a
+    /// fixed order where the first path (index 0) is the default path. Pairs of nodes with `index`
+    /// or fewer equal-cost paths are left out of the computed paths when `index` isn't 0. Direct
+    /// paths, static routes, policy routes, and paths from a node to itself only have a single
+    /// path.
+    pub fn set_equal_cost_path(&mut self, index: usize) {
+        self.path_index = index;
+    }
+
+    fn is_up(&self, edge: EdgeIndex) -> bool {
+        !self.down_edges.contains(&edge)
+    }
+
+    /// Get the edges of the static route from `src` to `dst`. Returns `None` if there's no static
+    /// route between them, and `Some(None)` if the route crosses an edge that's down, in which case
+    /// there's no path between them.
+    fn static_route(
+        &self,
+        src: NodeIndex,
+        dst: NodeIndex,
+    ) -> Option<Option<&[(EdgeIndex, NodeIndex)]>> {
+        let edges = self.static_routes.get(&(src, dst))?;
+        Some(
+            edges
+                .iter()
+                .all(|(edge, _)| self.is_up(*edge))
+                .then_some(edges.as_slice()),
+        )
+    }
+
     pub fn compute_shortest_paths(
         &self,
         nodes: &[NodeIndex],
     ) -> Result<HashMap<(NodeIndex, NodeIndex), PathProperties>, NetGraphError> {
         let start = std::time::Instant::now();
 
+        // the other equal-cost paths are found by walking their edges, and policy routes are found
+        // by following the preferred next hops
+        if self.path_index != 0 || self.routing == RoutingStrategy::GaoRexford {
+            let mut paths = self
+                .path_edges(nodes, true)?
+                .into_iter()
+                .map(|(key, edges)| (key, self.path_properties(&edges)))
+                .collect();
+
+            self.add_host_jitter(&mut paths);
+            Self::add_source_routers(&mut paths);
+
+            return Ok(paths);
+        }
+
         // calculate shortest paths
         let mut paths: HashMap<(_, _), PathProperties> = nodes
             .into_par_iter()
-            .flat_map(|src| {
-                match &self.graph {
-                    GraphWrapper::Directed(graph) => {
-                        petgraph::algo::dijkstra(&graph, *src, None, |e| e.weight().into())
-                    }
-                    GraphWrapper::Undirected(graph) => {
-                        petgraph::algo::dijkstra(&graph, *src, None, |e| e.weight().into())
-                    }
-                }
-                .into_iter()
-                // ignore nodes that aren't in use
-                .filter(|(dst, _)| nodes.contains(dst))
-                // include the src node
-                .map(|(dst, path)| ((*src, dst), path))
-                .collect::<HashMap<(_, _), _>>()
-            })
+            .map(|src| self.shortest_paths_from(*src, nodes))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
             .collect();
 
-        // use the self-loop for paths from a node to itself
-        for node in nodes {
-            // the dijkstra shortest path from node -> node will always be 0
-            assert_eq!(paths[&(*node, *node)], PathProperties::default());
+        // without edges that are down, the graph is required to be connected
+        assert!(!self.down_edges.is_empty() || paths.len() == nodes.len().pow(2));
 
-            // there must be a single self-loop for each node
-            paths.insert((*node, *node), self.get_edge_weight(node, node)?.into());
-        }
-
-        assert_eq!(paths.len(), nodes.len().pow(2));
+        self.add_host_jitter(&mut paths);
+        Self::add_source_routers(&mut paths);
 
         debug!(
             "Finished computing shortest paths: {} seconds, {} entries",
@@ -227,20 +716,112 @@ impl NetworkGraph {
         Ok(paths)
     }
 
+    /// Compute the shortest paths from `src` to each of the `nodes`, which are replaced by the
+    /// static routes from `src`. The path from `src` to itself uses its self-loop.
+    fn shortest_paths_from(
+        &self,
+        src: NodeIndex,
+        nodes: &[NodeIndex],
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), PathProperties>, NetGraphError> {
+        let mut paths: HashMap<(_, _), PathProperties> = self
+            .dijkstra(src)
+            .into_iter()
+            // ignore nodes that aren't in use
+            .filter(|(dst, _)| nodes.contains(dst))
+            // include the src node
+            .map(|(dst, cost)| ((src, dst), cost.properties))
+            .collect();
+
+        // static routes replace the shortest paths
+        for dst in nodes {
+            match self.static_route(src, *dst) {
+                Some(Some(edges)) => {
+                    paths.insert((src, *dst), self.path_properties(edges));
+                }
+                Some(None) => {
+                    paths.remove(&(src, *dst));
+                }
+                None => {}
+            }
+        }
+
+        // the dijkstra shortest path from src -> src will always be 0
+        assert_eq!(paths[&(src, src)], PathProperties::default());
+
+        // there must be a single self-loop for each node
+        paths.insert((src, src), self.get_edge_weight(&src, &src)?.into());
+
+        Ok(paths)
+    }
+
+    /// Compute the paths from `src` to each of the `nodes`, which are the same as the paths from
+    /// `src` of [`Self::compute_shortest_paths`] or [`Self::get_direct_paths`]. This is used to
+    /// compute paths on demand, so it only supports the default equal-cost paths, and doesn't
+    /// support policy routing, which computes the paths to each destination instead.
+    pub fn compute_paths_from(
+        &self,
+        src: NodeIndex,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), PathProperties>, NetGraphError> {
+        if self.path_index != 0 || self.routing == RoutingStrategy::GaoRexford {
+            return Err("Only the default paths can be computed from a single node".into());
+        }
+
+        let mut paths = if use_shortest_paths {
+            self.shortest_paths_from(src, nodes)?
+        } else {
+            nodes
+                .iter()
+                .filter(|dst| self.direct_edge_is_up(src, **dst))
+                .map(|dst| {
+                    let weight = self.get_edge_weight(&src, dst)?;
+                    Ok(((src, *dst), self.edge_properties(weight, src)))
+                })
+                .collect::<Result<_, NetGraphError>>()?
+        };
+
+        self.add_host_jitter(&mut paths);
+        Self::add_source_routers(&mut paths);
+
+        Ok(paths)
+    }
+
+    /// The lowest latency of any edge in either direction, which no path's latency is lower than.
+    pub fn smallest_edge_latency_ns(&self) -> Option<u64> {
+        self.graph
+            .edge_indices()
+            .flat_map(|edge| {
+                let weight = self.edge_weight(edge);
+                [Some(weight.latency), weight.reverse_latency]
+            })
+            .flatten()
+            .map(|x| x.convert(units::TimePrefix::Nano).unwrap().value())
+            .min()
+    }
+
     pub fn get_direct_paths(
         &self,
         nodes: &[NodeIndex],
     ) -> Result<HashMap<(NodeIndex, NodeIndex), PathProperties>, NetGraphError> {
         let start = std::time::Instant::now();
 
-        let paths: HashMap<_, _> = nodes
+        let mut paths: HashMap<_, _> = nodes
             .iter()
             .flat_map(|src| nodes.iter().map(move |dst| (*src, *dst)))
+            // nodes don't have a path between them while their edge is down
+            .filter(|(src, dst)| self.direct_edge_is_up(*src, *dst))
             // we require the graph to be connected with exactly one edge between any two nodes
-            .map(|(src, dst)| Ok(((src, dst), self.get_edge_weight(&src, &dst)?.into())))
+            .map(|(src, dst)| {
+                let weight = self.get_edge_weight(&src, &dst)?;
+                Ok(((src, dst), self.edge_properties(weight, src)))
+            })
             .collect::<Result<_, NetGraphError>>()?;
 
-        assert_eq!(paths.len(), nodes.len().pow(2));
+        assert!(!self.down_edges.is_empty() || paths.len() == nodes.len().pow(2));
+
+        self.add_host_jitter(&mut paths);
+        Self::add_source_routers(&mut paths);
 
         debug!(
             "Finished computing direct paths: {} seconds, {} entries",
@@ -251,6 +832,404 @@ impl NetworkGraph {
         Ok(paths)
     }
 
+    /// Get the links with a bandwidth, and the links crossed by the path between each pair of
+    /// nodes. Each direction of an undirected edge is a separate link. The links of all edges are
+    /// included, even edges that are down or that no path crosses, so that the links are the same
+    /// for any set of down edges. The paths are the same as those of
+    /// [`Self::compute_shortest_paths`] or [`Self::get_direct_paths`].
+    pub fn compute_links(
+        &self,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+    ) -> Result<Links<NodeIndex>, NetGraphError> {
+        let edge_paths = self.path_edges(nodes, use_shortest_paths)?;
+
+        let mut links = Vec::new();
+        let mut link_indexes = HashMap::new();
+        let mut path_links = HashMap::new();
+
+        for edge in self.graph.edge_indices() {
+            let weight = self.edge_weight(edge);
+            let (a, b) = self.graph.edge_endpoints(edge).unwrap();
+
+            // a directed edge or a self-loop is only traversed from its source node
+            let directions = match &self.graph {
+                GraphWrapper::Undirected(_) if a != b => vec![a, b],
+                _ => vec![a],
+            };
+
+            for from in directions {
+                let from_id = self.node_index_to_id(from).unwrap();
+                if let Some(bandwidth) = weight.bandwidth_from(from_id) {
+                    links.push(LinkProperties {
+                        bits_per_sec: bandwidth
+                            .convert(units::SiPrefixUpper::Base)
+                            .unwrap()
+                            .value(),
+                        buffer_bytes: weight
+                            .buffer_size
+                            .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+                        overhead_ns: 0,
+                    });
+                    link_indexes.insert((edge, from), links.len() - 1);
+                }
+            }
+        }
+
+        // the radio channel of each wireless access point is a link that's shared by the packets
+        // sent and received by the node's hosts
+        let mut channel_indexes = HashMap::new();
+        for node in self.graph.node_indices() {
+            if let Some(ap) = &self.graph.node_weight(node).unwrap().wireless {
+                links.push(LinkProperties {
+                    bits_per_sec: ap
+                        .bandwidth
+                        .convert(units::SiPrefixUpper::Base)
+                        .unwrap()
+                        .value(),
+                    buffer_bytes: None,
+                    overhead_ns: ap.overhead_ns(),
+                });
+                channel_indexes.insert(node, links.len() - 1);
+            }
+        }
+
+        for (key, edges) in edge_paths {
+            let mut offset_ns = 0;
+            let mut crossed = Vec::new();
+
+            if let Some(link) = channel_indexes.get(&key.0) {
+                crossed.push(PathLink {
+                    link: *link,
+                    offset_ns,
+                });
+            }
+
+            for (edge, from) in edges {
+                let weight = self.edge_weight(edge);
+                let from_id = self.node_index_to_id(from).unwrap();
+
+                if let Some(link) = link_indexes.get(&(edge, from)) {
+                    crossed.push(PathLink {
+                        link: *link,
+                        offset_ns,
+                    });
+                }
+
+                offset_ns += weight.properties_from(from_id).latency_ns;
+            }
+
+            if let Some(link) = channel_indexes.get(&key.1) {
+                crossed.push(PathLink {
+                    link: *link,
+                    offset_ns,
+                });
+            }
+
+            path_links.insert(key, crossed);
+        }
+
+        Ok((links, path_links))
+    }
+
+    /// Get the edges in `edges` that are crossed by the path between each pair of nodes, in the
+    /// order that they're crossed. The paths are the same as those of
+    /// [`Self::compute_shortest_paths`] or [`Self::get_direct_paths`].
+    pub fn compute_crossed_edges(
+        &self,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+        edges: &[EdgeIndex],
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), Vec<PathEdge>>, NetGraphError> {
+        let edge_paths = self.path_edges(nodes, use_shortest_paths)?;
+
+        Ok(edge_paths
+            .into_iter()
+            .map(|(key, path)| {
+                let mut end_offset_ns = 0;
+                let mut crossed = Vec::new();
+
+                for (edge, from) in path {
+                    let weight = self.edge_weight(edge);
+                    end_offset_ns += self.edge_properties(weight, from).latency_ns;
+
+                    if let Some(index) = edges.iter().position(|x| *x == edge) {
+                        crossed.push(PathEdge {
+                            edge: index,
+                            end_offset_ns,
+                        });
+                    }
+                }
+
+                (key, crossed)
+            })
+            .collect())
+    }
+
+    /// Get the routers crossed by the path between each pair of nodes, in the order that they're
+    /// crossed. Each node on a path is a router, except when the path is from a node to itself,
+    /// which doesn't cross a router. The paths are the same as those of
+    /// [`Self::compute_shortest_paths`] or [`Self::get_direct_paths`].
+    pub fn compute_routers(
+        &self,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), Vec<PathRouter>>, NetGraphError> {
+        let edge_paths = self.path_edges(nodes, use_shortest_paths)?;
+
+        let router = |node: NodeIndex, offset_ns| PathRouter {
+            address: self.graph.node_weight(node).unwrap().router_address,
+            offset_ns,
+        };
+
+        Ok(edge_paths
+            .into_iter()
+            .map(|((src, dst), edges)| {
+                if src == dst {
+                    return ((src, dst), Vec::new());
+                }
+
+                let mut offset_ns = 0;
+                let mut routers = Vec::new();
+
+                for (edge, from) in edges {
+                    routers.push(router(from, offset_ns));
+                    let weight = self.edge_weight(edge);
+                    offset_ns += self.edge_properties(weight, from).latency_ns;
+                }
+                routers.push(router(dst, offset_ns));
+
+                ((src, dst), routers)
+            })
+            .collect())
+    }
+
+    /// Get the edges of the path between each pair of nodes, and the node that each edge is
+    /// traversed from.
+    fn path_edges(
+        &self,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), Vec<(EdgeIndex, NodeIndex)>>, NetGraphError> {
+        let mut edge_paths: HashMap<(NodeIndex, NodeIndex), Vec<(EdgeIndex, NodeIndex)>> =
+            if use_shortest_paths && self.routing == RoutingStrategy::GaoRexford {
+                if self.path_index == 0 {
+                    self.gao_rexford_path_edges(nodes)
+                } else {
+                    HashMap::new()
+                }
+            } else if use_shortest_paths {
+                nodes
+                    .into_par_iter()
+                    .flat_map(|src| {
+                        let distances = self.dijkstra(*src);
+
+                        nodes
+                            .iter()
+                            .filter(|dst| *dst != src)
+                            .filter_map(|dst| {
+                                let edges = match self.static_route(*src, *dst) {
+                                    Some(edges) if self.path_index == 0 => edges?.to_vec(),
+                                    Some(_) => return None,
+                                    // nodes may be unreachable while edges are down
+                                    None if !distances.contains_key(dst) => return None,
+                                    None => {
+                                        self.shortest_path_edges(&distances, *dst, self.path_index)?
+                                    }
+                                };
+                                Some(((*src, *dst), edges))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            } else if self.path_index != 0 {
+                HashMap::new()
+            } else {
+                nodes
+                    .iter()
+                    .flat_map(|src| nodes.iter().map(move |dst| (*src, *dst)))
+                    .filter(|(src, dst)| src != dst && self.direct_edge_is_up(*src, *dst))
+                    .map(|(src, dst)| Ok(((src, dst), vec![(self.find_edge(src, dst)?, src)])))
+                    .collect::<Result<_, NetGraphError>>()?
+            };
+
+        let self_loops = nodes
+            .iter()
+            .map(|node| Ok(((*node, *node), vec![(self.find_edge(*node, *node)?, *node)])))
+            .collect::<Result<Vec<_>, NetGraphError>>()?;
+
+        if self.path_index == 0 {
+            edge_paths.extend(self_loops);
+        }
+
+        Ok(edge_paths)
+    }
+
+    /// Get the edges of the equal-cost shortest path at `index` to `dst`, given the shortest path
+    /// `distances` from the source node computed by dijkstra's algorithm. Returns `None` if there
+    /// are `index` or fewer equal-cost paths.
+    fn shortest_path_edges(
+        &self,
+        distances: &HashMap<NodeIndex, RouteCost>,
+        dst: NodeIndex,
+        index: usize,
+    ) -> Option<Vec<(EdgeIndex, NodeIndex)>> {
+        let mut edges = Vec::new();
+        let mut skip = index;
+
+        if !self.walk_shortest_paths(distances, dst, &mut skip, &mut edges) {
+            return None;
+        }
+
+        edges.reverse();
+        Some(edges)
+    }
+
+    /// Walk backwards from `node` to the source node along each of the shortest paths in turn,
+    /// skipping the first `skip` paths. Returns true when a path isn't skipped, in which case
+    /// `edges` holds its edges in reverse order.
+    fn walk_shortest_paths(
+        &self,
+        distances: &HashMap<NodeIndex, RouteCost>,
+        node: NodeIndex,
+        skip: &mut usize,
+        edges: &mut Vec<(EdgeIndex, NodeIndex)>,
+    ) -> bool {
+        // only the source node has a latency of 0, since edge latencies are never 0
+        if distances[&node].properties.latency_ns == 0 {
+            if *skip == 0 {
+                return true;
+            }
+            *skip -= 1;
+            return false;
+        }
+
+        // an edge is on a shortest path if the distance of the node it's traversed from plus the
+        // edge's weight is the distance of the current node
+        for (edge, prev) in self.incoming_edges(node) {
+            let is_shortest = prev != node
+                && distances
+                    .get(&prev)
+                    .is_some_and(|x| *x + self.edge_cost(edge, prev) == distances[&node]);
+            if !is_shortest {
+                continue;
+            }
+
+            edges.push((edge, prev));
+            if self.walk_shortest_paths(distances, prev, skip, edges) {
+                return true;
+            }
+            edges.pop();
+        }
+
+        false
+    }
+
+    /// Get the edges that are up and can be traversed to reach `node`, and the nodes they're
+    /// traversed from.
+    fn incoming_edges(&self, node: NodeIndex) -> Vec<(EdgeIndex, NodeIndex)> {
+        match &self.graph {
+            GraphWrapper::Directed(graph) => graph
+                .edges_directed(node, petgraph::Direction::Incoming)
+                .filter(|e| self.is_up(e.id()))
+                .map(|e| (e.id(), e.source()))
+                .collect(),
+            GraphWrapper::Undirected(graph) => graph
+                .edges(node)
+                .filter(|e| self.is_up(e.id()))
+                .map(|e| {
+                    let other = if e.source() == node {
+                        e.target()
+                    } else {
+                        e.source()
+                    };
+                    (e.id(), other)
+                })
+                .collect(),
+        }
+    }
+
+    /// Compute the shortest paths from `src` to each node that it can reach without crossing an
+    /// edge that's down.
+    fn dijkstra(&self, src: NodeIndex) -> HashMap<NodeIndex, RouteCost> {
+        match &self.graph {
+            GraphWrapper::Directed(graph) => {
+                let graph = EdgeFiltered::from_fn(graph, |e| self.is_up(e.id()));
+                petgraph::algo::dijkstra(&graph, src, None, |e| self.edge_cost(e.id(), e.source()))
+            }
+            GraphWrapper::Undirected(graph) => {
+                let graph = EdgeFiltered::from_fn(graph, |e| self.is_up(e.id()));
+                petgraph::algo::dijkstra(&graph, src, None, |e| self.edge_cost(e.id(), e.source()))
+            }
+        }
+    }
+
+    /// Get the cost of an edge when it's traversed from the node `from`, using the routing
+    /// strategy.
+    fn edge_cost(&self, edge: EdgeIndex, from: NodeIndex) -> RouteCost {
+        let weight = self.edge_weight(edge);
+        let from_id = self.node_index_to_id(from).unwrap();
+        let bits_per_sec = weight
+            .bandwidth_from(from_id)
+            .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value());
+        self.routing
+            .edge_cost(weight.properties_from(from_id), bits_per_sec)
+    }
+
+    /// Returns false if the edge between two nodes is down. Nodes without an edge between them
+    /// are handled by the callers, which return an error.
+    fn direct_edge_is_up(&self, src: NodeIndex, dst: NodeIndex) -> bool {
+        self.graph
+            .find_edge(src, dst)
+            .map_or(true, |e| self.is_up(e))
+    }
+
+    /// Get the properties of a path with the given edges, and the nodes they're traversed from.
+    fn path_properties(&self, edges: &[(EdgeIndex, NodeIndex)]) -> PathProperties {
+        edges
+            .iter()
+            .fold(PathProperties::default(), |path, (edge, from)| {
+                path + self.edge_properties(self.edge_weight(*edge), *from)
+            })
+    }
+
+    /// Get the properties of an edge when it's traversed from the node `from`.
+    fn edge_properties(&self, edge: &ShadowEdge, from: NodeIndex) -> PathProperties {
+        edge.properties_from(self.node_index_to_id(from).unwrap())
+    }
+
+    /// Get the edge between two nodes. Returns an error if there is no edge between them.
+    fn find_edge(&self, src: NodeIndex, dst: NodeIndex) -> Result<EdgeIndex, NetGraphError> {
+        self.graph.find_edge(src, dst).ok_or_else(|| {
+            let src_id = self.node_index_to_id(src).unwrap();
+            let dst_id = self.node_index_to_id(dst).unwrap();
+            format!("No edge connecting node {} to {}", src_id, dst_id).into()
+        })
+    }
+
+    /// Count the router of each path's source node, which the edges of the path don't include. A
+    /// path from a node to itself doesn't leave the node's network, so it doesn't cross a router.
+    fn add_source_routers(paths: &mut HashMap<(NodeIndex, NodeIndex), PathProperties>) {
+        for ((src, dst), path) in paths.iter_mut() {
+            path.routers = if src == dst { 0 } else { path.routers + 1 };
+        }
+    }
+
+    /// Set the jitter of the host uplinks at each path's source node.
+    fn add_host_jitter(&self, paths: &mut HashMap<(NodeIndex, NodeIndex), PathProperties>) {
+        for ((src, _dst), path) in paths.iter_mut() {
+            let node = self.graph.node_weight(*src).unwrap();
+            path.host_jitter = Jitter {
+                scale_ns: node
+                    .jitter
+                    .convert(units::TimePrefix::Nano)
+                    .unwrap()
+                    .value(),
+                distribution: node.jitter_distribution,
+            };
+        }
+    }
+
     /// Get the weight for the edge between two nodes. Returns an error if there
     /// is not exactly one edge between them.
     fn get_edge_weight(
@@ -273,7 +1252,7 @@ impl NetworkGraph {
                     )
                     .into());
                 }
-                Ok(edge.weight())
+                Ok(self.edge_weight(edge.id()))
             }
             GraphWrapper::Undirected(graph) => {
                 let mut edges = graph.edges_connecting(*src, *dst);
@@ -287,7 +1266,7 @@ impl NetworkGraph {
                     )
                     .into());
                 }
-                Ok(edge.weight())
+                Ok(self.edge_weight(edge.id()))
             }
         }
     }
@@ -300,8 +1279,67 @@ pub struct PathProperties {
     pub latency_ns: u64,
     /// Packet loss as fraction.
     pub packet_loss: f32,
+    /// Packet corruption as fraction.
+    pub packet_corruption: f32,
+    /// Packet reordering as fraction.
+    pub packet_reorder: f32,
+    /// Additional latency in nanoseconds of reordered packets.
+    pub reorder_delay_ns: u64,
+    /// The minimum number of packets between reordered packets, or 0 if there's no minimum.
+    pub reorder_gap: u32,
+    /// Random latency of the edges on the path. If the edges have different jitter, the path uses
+    /// the jitter with the largest scale.
+    pub jitter: Jitter,
+    /// Random latency of the host uplinks at the source node.
+    pub host_jitter: Jitter,
+    /// The smallest MTU of the edges on the path, or `None` if they don't limit the packet size.
+    pub mtu: Option<u32>,
+    /// The number of routers on the path, which each decrement the TTL of the packets on the path.
+    pub routers: u32,
+}
+
+/// A router that's crossed by a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathRouter {
+    /// The address that the router sends ICMP errors from, or `None` if it doesn't send errors.
+    pub address: Option<std::net::Ipv4Addr>,
+    /// The latency in nanoseconds from the start of the path to the router.
+    pub offset_ns: u64,
+}
+
+/// The capacity of one direction of a graph edge that has a bandwidth, or of a wireless access
+/// point's channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkProperties {
+    pub bits_per_sec: u64,
+    /// The maximum number of bytes queued at the link, or `None` if it's unlimited.
+    pub buffer_bytes: Option<u64>,
+    /// The time in nanoseconds that each packet occupies the link in addition to its transmission
+    /// time, such as the contention overhead of a wireless channel.
+    pub overhead_ns: u64,
+}
+
+/// A link with a bandwidth that's crossed by a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathLink {
+    /// The index of the link's [`LinkProperties`].
+    pub link: usize,
+    /// The latency in nanoseconds from the start of the path to the link.
+    pub offset_ns: u64,
 }
 
+/// An edge with link events that's crossed by a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathEdge {
+    /// The index of the edge in the list of edges with link events.
+    pub edge: usize,
+    /// The latency in nanoseconds from the start of the path to the end of the edge.
+    pub end_offset_ns: u64,
+}
+
+/// The links with a bandwidth, and the links crossed by the path between each pair of nodes.
+pub type Links<T> = (Vec<LinkProperties>, HashMap<(T, T), Vec<PathLink>>);
+
 impl PartialOrd for PathProperties {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         // order by lowest latency first, then by lowest packet loss
@@ -326,15 +1364,53 @@ impl core::ops::Add for PathProperties {
         Self {
             latency_ns: self.latency_ns + other.latency_ns,
             packet_loss: 1f32 - (1f32 - self.packet_loss) * (1f32 - other.packet_loss),
+            packet_corruption: 1f32
+                - (1f32 - self.packet_corruption) * (1f32 - other.packet_corruption),
+            packet_reorder: 1f32 - (1f32 - self.packet_reorder) * (1f32 - other.packet_reorder),
+            reorder_delay_ns: std::cmp::max(self.reorder_delay_ns, other.reorder_delay_ns),
+            reorder_gap: std::cmp::max(self.reorder_gap, other.reorder_gap),
+            jitter: std::cmp::max_by_key(self.jitter, other.jitter, |x| x.scale_ns),
+            host_jitter: std::cmp::max_by_key(self.host_jitter, other.host_jitter, |x| x.scale_ns),
+            mtu: match (self.mtu, other.mtu) {
+                (Some(x), Some(y)) => Some(std::cmp::min(x, y)),
+                (x, y) => x.or(y),
+            },
+            routers: self.routers + other.routers,
         }
     }
 }
 
 impl std::convert::From<&ShadowEdge> for PathProperties {
     fn from(e: &ShadowEdge) -> Self {
+        let latency_ns = e.latency.convert(units::TimePrefix::Nano).unwrap().value();
+
+        // reordered packets are delayed by an extra latency of the edge by default
+        let reorder_delay_ns = match e.reorder_delay {
+            _ if e.packet_reorder == 0f32 => 0,
+            Some(x) => x.convert(units::TimePrefix::Nano).unwrap().value(),
+            None => latency_ns,
+        };
+        let reorder_gap = if e.packet_reorder == 0f32 {
+            0
+        } else {
+            e.reorder_gap
+        };
+
         Self {
-            latency_ns: e.latency.convert(units::TimePrefix::Nano).unwrap().value(),
+            latency_ns,
             packet_loss: e.packet_loss,
+            packet_corruption: e.packet_corruption,
+            packet_reorder: e.packet_reorder,
+            reorder_delay_ns,
+            reorder_gap,
+            jitter: Jitter {
+                scale_ns: e.jitter.convert(units::TimePrefix::Nano).unwrap().value(),
+                distribution: e.jitter_distribution,
+            },
+            host_jitter: Jitter::default(),
+            mtu: e.mtu,
+            // each edge leads to the router of the node at its end
+            routers: 1,
         }
     }
 }
@@ -356,6 +1432,9 @@ pub struct IpAssignment<T: Copy + Eq + Hash + std::fmt::Display> {
     map: HashMap<std::net::IpAddr, T>,
     /// The last dynamically assigned address.
     last_assigned_addr: std::net::IpAddr,
+    /// The number of addresses of each pool that have already been checked, so that each address
+    /// in a pool is only checked once.
+    pool_offsets: HashMap<Subnet, usize>,
 }
 
 impl<T: Copy + Eq + Hash + std::fmt::Display> IpAssignment<T> {
@@ -363,7 +1442,28 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> IpAssignment<T> {
         Self {
             map: HashMap::new(),
             last_assigned_addr: std::net::IpAddr::V4(std::net::Ipv4Addr::new(11, 0, 0, 0)),
+            pool_offsets: HashMap::new(),
+        }
+    }
+
+    /// Get the lowest unused address in a pool and assign it to a node. Like [`Self::assign`],
+    /// addresses ending in ".0" or ".255" are never assigned. Returns `None` if every address in
+    /// the pool has been assigned.
+    pub fn assign_from_pool(&mut self, node_id: T, pool: Subnet) -> Option<std::net::IpAddr> {
+        let offset = self.pool_offsets.entry(pool).or_default();
+
+        for ip_addr in pool.host_addresses().skip(*offset) {
+            *offset += 1;
+            if matches!(ip_addr.octets()[3], 0 | 255) {
+                continue;
+            }
+            if let Entry::Vacant(e) = self.map.entry(ip_addr.into()) {
+                e.insert(node_id);
+                return Some(ip_addr.into());
+            }
         }
+
+        None
     }
 
     /// Get an unused address and assign it to a node.
@@ -403,6 +1503,19 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> IpAssignment<T> {
         self.map.values().copied().collect()
     }
 
+    /// Get the addresses assigned to a node, in ascending order. The hosts with these addresses
+    /// share the node's network segment.
+    pub fn get_node_addresses(&self, node_id: T) -> Vec<std::net::IpAddr> {
+        let mut addrs: Vec<_> = self
+            .map
+            .iter()
+            .filter(|(_, node)| **node == node_id)
+            .map(|(addr, _)| *addr)
+            .collect();
+        addrs.sort();
+        addrs
+    }
+
     fn increment_address(addr: &std::net::IpAddr) -> std::net::IpAddr {
         match addr {
             std::net::IpAddr::V4(mut x) => loop {
@@ -430,6 +1543,12 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> Default for IpAssignment<T> {
 pub struct RoutingInfo<T: Eq + Hash + std::fmt::Display + Clone + Copy> {
     paths: HashMap<(T, T), PathProperties>,
     packet_counters: std::sync::RwLock<HashMap<(T, T), u64>>,
+    links: Vec<LinkProperties>,
+    path_links: HashMap<(T, T), Vec<PathLink>>,
+    path_routers: HashMap<(T, T), Vec<PathRouter>>,
+    path_edges: HashMap<(T, T), Vec<PathEdge>>,
+    /// Paths that are computed on demand, for pairs of nodes without a path in `paths`.
+    path_cache: Option<PathCache<T>>,
 }
 
 impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
@@ -437,12 +1556,79 @@ impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
         Self {
             paths,
             packet_counters: std::sync::RwLock::new(HashMap::new()),
+            links: Vec::new(),
+            path_links: HashMap::new(),
+            path_routers: HashMap::new(),
+            path_edges: HashMap::new(),
+            path_cache: None,
         }
     }
 
+    /// Compute the paths on demand using the cache, rather than computing them up front.
+    pub fn with_path_cache(mut self, path_cache: PathCache<T>) -> Self {
+        self.path_cache = Some(path_cache);
+        self
+    }
+
+    /// Add the links with a bandwidth that are crossed by the paths.
+    pub fn with_links(mut self, (links, path_links): Links<T>) -> Self {
+        self.links = links;
+        self.path_links = path_links;
+        self
+    }
+
+    /// Add the routers that are crossed by the paths.
+    pub fn with_routers(mut self, path_routers: HashMap<(T, T), Vec<PathRouter>>) -> Self {
+        self.path_routers = path_routers;
+        self
+    }
+
+    /// Add the edges with link events that are crossed by the paths.
+    pub fn with_edges(mut self, path_edges: HashMap<(T, T), Vec<PathEdge>>) -> Self {
+        self.path_edges = path_edges;
+        self
+    }
+
+    /// Get the links with a bandwidth.
+    pub fn links(&self) -> &[LinkProperties] {
+        &self.links
+    }
+
+    /// Get the links with a bandwidth on the path from one node to another, in the order that
+    /// they're crossed.
+    pub fn path_links(&self, start: T, end: T) -> &[PathLink] {
+        self.path_links
+            .get(&(start, end))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Get the router at `index` on the path from one node to another. Returns `None` if the
+    /// routers weren't added.
+    pub fn path_router(&self, start: T, end: T, index: usize) -> Option<PathRouter> {
+        self.path_routers.get(&(start, end))?.get(index).copied()
+    }
+
+    /// Get the edges with link events on the path from one node to another, in the order that
+    /// they're crossed.
+    pub fn path_edges(&self, start: T, end: T) -> &[PathEdge] {
+        self.path_edges
+            .get(&(start, end))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
     /// Get properties for the path from one node to another.
     pub fn path(&self, start: T, end: T) -> Option<PathProperties> {
-        self.paths.get(&(start, end)).copied()
+        match self.paths.get(&(start, end)) {
+            Some(path) => Some(*path),
+            None => self.path_cache.as_ref()?.path(start, end),
+        }
+    }
+
+    /// Returns true if there are no paths.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.path_cache.is_none()
     }
 
     /// Increment the number of packets sent from one node to another.
@@ -459,20 +1645,141 @@ impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
     pub fn log_packet_counts(&self) {
         // only logs paths that have transmitted at least one packet
         for ((start, end), count) in self.packet_counters.read().unwrap().iter() {
-            let path = self.paths.get(&(*start, *end)).unwrap();
+            let path = self.path(*start, *end).unwrap();
             log::debug!(
-                "Found path {}->{}: latency={}ns, packet_loss={}, packet_count={}",
+                "Found path {}->{}: latency={}ns, packet_loss={}, packet_corruption={}, \
+                packet_reorder={}, reorder_delay={}ns, reorder_gap={}, jitter={}ns, host_jitter={}ns, packet_count={}",
                 start,
                 end,
                 path.latency_ns,
                 path.packet_loss,
+                path.packet_corruption,
+                path.packet_reorder,
+                path.reorder_delay_ns,
+                path.reorder_gap,
+                path.jitter.scale_ns,
+                path.host_jitter.scale_ns,
                 count,
             );
         }
     }
 
     pub fn get_smallest_latency_ns(&self) -> Option<u64> {
-        self.paths.values().map(|x| x.latency_ns).min()
+        let cached = self
+            .path_cache
+            .as_ref()
+            .and_then(|x| x.smallest_latency_ns());
+        self.paths
+            .values()
+            .map(|x| x.latency_ns)
+            .chain(cached)
+            .min()
+    }
+
+    /// Log how often the paths computed on demand were found in the cache.
+    pub fn log_path_cache_stats(&self) {
+        if let Some(path_cache) = &self.path_cache {
+            path_cache.log_stats();
+        }
+    }
+}
+
+/// Routing information for paths between nodes, which changes when graph edges go down or come
+/// back up, or when link traces change the attributes of edges. The paths are recomputed for each
+/// period between these changes. Each period has the routing information of each of the
+/// equal-cost paths between nodes, where the first is the default path and a pair of nodes with
+/// `n` equal-cost paths is included in the first `n`.
+#[derive(Debug)]
+pub struct RoutingSchedule<T: Eq + Hash + std::fmt::Display + Clone + Copy> {
+    /// Routing information when all edges are up, which is used before the first change.
+    initial: Vec<RoutingInfo<T>>,
+    /// Routing information from the time of each change, sorted by time.
+    periods: Vec<(SimulationTime, Vec<RoutingInfo<T>>)>,
+    /// The times at which each edge with link events goes down, sorted by time.
+    down_times: Vec<Vec<SimulationTime>>,
+}
+
+impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingSchedule<T> {
+    /// Routing information that doesn't change, with the routing information of each equal-cost
+    /// path.
+    pub fn new(initial: Vec<RoutingInfo<T>>) -> Self {
+        assert!(!initial.is_empty());
+        Self {
+            initial,
+            periods: Vec::new(),
+            down_times: Vec::new(),
+        }
+    }
+
+    /// Add the routing information of each period between changes, and the times at which each
+    /// edge with link events goes down.
+    pub fn with_events(
+        mut self,
+        periods: Vec<(SimulationTime, Vec<RoutingInfo<T>>)>,
+        down_times: Vec<Vec<SimulationTime>>,
+    ) -> Self {
+        assert!(periods.windows(2).all(|x| x[0].0 < x[1].0));
+        assert!(periods.iter().all(|(_, x)| !x.is_empty()));
+        assert!(down_times
+            .iter()
+            .all(|x| x.windows(2).all(|x| x[0] <= x[1])));
+        self.periods = periods;
+        self.down_times = down_times;
+        self
+    }
+
+    /// Get the routing information of the default paths before the first change. The links with
+    /// a bandwidth are the same in every period, but their bandwidths may change.
+    pub fn initial(&self) -> &RoutingInfo<T> {
+        &self.initial[0]
+    }
+
+    /// Get the smallest latency of any path in any period.
+    pub fn get_smallest_latency_ns(&self) -> Option<u64> {
+        std::iter::once(&self.initial)
+            .chain(self.periods.iter().map(|(_, infos)| infos))
+            .flatten()
+            .filter_map(|info| info.get_smallest_latency_ns())
+            .min()
+    }
+
+    /// Get the routing information of the default paths at `time`.
+    pub fn at(&self, time: SimulationTime) -> &RoutingInfo<T> {
+        &self.equal_cost_paths_at(time)[0]
+    }
+
+    /// Get the routing information of each of the equal-cost paths at `time`.
+    pub fn equal_cost_paths_at(&self, time: SimulationTime) -> &[RoutingInfo<T>] {
+        // the number of periods that have started by `time`
+        let started = self.periods.partition_point(|(start, _)| *start <= time);
+        match started.checked_sub(1) {
+            Some(index) => &self.periods[index].1,
+            None => &self.initial,
+        }
+    }
+
+    /// Get the number of equal-cost paths from one node to another at `time`.
+    pub fn path_count(&self, start: T, end: T, time: SimulationTime) -> usize {
+        self.equal_cost_paths_at(time)
+            .iter()
+            .take_while(|info| info.path(start, end).is_some())
+            .count()
+    }
+
+    /// Returns true if an edge on the equal-cost path at `index` from one node to another at
+    /// `time` goes down before a packet sent at `time` has crossed it.
+    pub fn path_goes_down(&self, start: T, end: T, index: usize, time: SimulationTime) -> bool {
+        let Some(info) = self.equal_cost_paths_at(time).get(index) else {
+            return false;
+        };
+
+        info.path_edges(start, end).iter().any(|edge| {
+            let down_times = &self.down_times[edge.edge];
+            let end_time = time + SimulationTime::from_nanos(edge.end_offset_ns);
+            // the first time the edge goes down after `time`
+            let next = down_times.partition_point(|x| *x <= time);
+            down_times.get(next).is_some_and(|x| *x <= end_time)
+        })
     }
 }
 
@@ -494,17 +1801,62 @@ fn read_xz<P: AsRef<std::path::Path>>(path: P) -> Result<String, NetGraphError>
 /// Get the network graph as a string.
 pub fn load_network_graph(graph_options: &GraphOptions) -> Result<String, NetGraphError> {
     Ok(match graph_options {
-        GraphOptions::Gml(GraphSource::File(FileSource {
+        GraphOptions::Gml(source) => read_graph_source(source)?,
+        GraphOptions::Caida(options) => {
+            let data = read_graph_source(&options.source)?;
+            caida::to_gml(&data, options)
+                .map_err(|e| format!("Failed to convert the CAIDA data: {e}"))?
+        }
+        GraphOptions::Composite(options) => compose::compose(options)?,
+        GraphOptions::OneGbitSwitch => configuration::ONE_GBIT_SWITCH_GRAPH.to_string(),
+    })
+}
+
+/// The speed of light in optical fiber in kilometers per second, which is about 2/3 of the speed of
+/// light in a vacuum.
+const FIBER_KM_PER_SEC: f64 = 200_000.0;
+
+/// The mean radius of the Earth in kilometers.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// The time in nanoseconds that light in fiber takes to cross the great-circle distance between two
+/// locations, multiplied by `overhead`. The locations are latitudes and longitudes in degrees. The
+/// latency is always at least 1 ns.
+fn geographic_latency_ns(a: (f64, f64), b: (f64, f64), overhead: f64) -> u64 {
+    let (lat_a, lon_a) = (a.0.to_radians(), a.1.to_radians());
+    let (lat_b, lon_b) = (b.0.to_radians(), b.1.to_radians());
+
+    // the haversine formula
+    let h = ((lat_b - lat_a) / 2.0).sin().powi(2)
+        + lat_a.cos() * lat_b.cos() * ((lon_b - lon_a) / 2.0).sin().powi(2);
+    let distance_km = 2.0 * EARTH_RADIUS_KM * h.sqrt().min(1.0).asin();
+
+    let latency_ns = distance_km / FIBER_KM_PER_SEC * overhead * 1e9;
+    (latency_ns.ceil() as u64).max(1)
+}
+
+/// Parse a graph in the GML, GraphML, or JSON format, detecting the format from the first character
+/// of the graph that isn't whitespace.
+fn parse_gml(graph_text: &str) -> Result<gml_parser::gml::Gml, NetGraphError> {
+    Ok(match graph_text.trim_start().chars().next() {
+        Some('<') => graphml::parse(graph_text)?,
+        Some('{') => json::parse(graph_text)?,
+        _ => gml_parser::parse(graph_text)?,
+    })
+}
+
+fn read_graph_source(source: &GraphSource) -> Result<String, NetGraphError> {
+    Ok(match source {
+        GraphSource::File(FileSource {
             compression: None,
             path: f,
-        })) => std::fs::read_to_string(tilde_expansion(f))
+        }) => std::fs::read_to_string(tilde_expansion(f))
             .with_context(|| format!("Failed to read file: {f}"))?,
-        GraphOptions::Gml(GraphSource::File(FileSource {
+        GraphSource::File(FileSource {
             compression: Some(Compression::Xz),
             path: f,
-        })) => read_xz(tilde_expansion(f))?,
-        GraphOptions::Gml(GraphSource::Inline(s)) => s.clone(),
-        GraphOptions::OneGbitSwitch => configuration::ONE_GBIT_SWITCH_GRAPH.to_string(),
+        }) => read_xz(tilde_expansion(f))?,
+        GraphSource::Inline(s) => s.clone(),
     })
 }
 
@@ -517,15 +1869,89 @@ mod tests {
         let p1 = PathProperties {
             latency_ns: 23,
             packet_loss: 0.35,
+            packet_corruption: 0.5,
+            packet_reorder: 0.0,
+            reorder_delay_ns: 0,
+            reorder_gap: 0,
+            jitter: Jitter::default(),
+            host_jitter: Jitter::default(),
+            mtu: None,
+            routers: 1,
         };
         let p2 = PathProperties {
             latency_ns: 11,
             packet_loss: 0.85,
+            packet_corruption: 0.0,
+            packet_reorder: 0.5,
+            reorder_delay_ns: 7,
+            reorder_gap: 5,
+            jitter: Jitter {
+                scale_ns: 2,
+                distribution: JitterDistribution::Pareto,
+            },
+            host_jitter: Jitter::default(),
+            mtu: Some(1400),
+            routers: 1,
+        };
+        let p3 = PathProperties {
+            latency_ns: 5,
+            packet_loss: 0.0,
+            packet_corruption: 0.5,
+            packet_reorder: 0.5,
+            reorder_delay_ns: 3,
+            reorder_gap: 0,
+            jitter: Jitter {
+                scale_ns: 1,
+                distribution: JitterDistribution::LogNormal,
+            },
+            host_jitter: Jitter::default(),
+            mtu: Some(1280),
+            routers: 1,
         };
 
-        let p3 = p1 + p2;
-        assert_eq!(p3.latency_ns, 34);
-        assert!((p3.packet_loss - 0.9025).abs() < 0.01);
+        let p4 = p1 + p2;
+        assert_eq!(p4.latency_ns, 34);
+        assert!((p4.packet_loss - 0.9025).abs() < 0.01);
+        assert!((p4.packet_corruption - 0.5).abs() < 0.01);
+        assert!(((p4 + p3).packet_corruption - 0.75).abs() < 0.01);
+        assert!((p4.packet_reorder - 0.5).abs() < 0.01);
+        assert!(((p4 + p3).packet_reorder - 0.75).abs() < 0.01);
+        assert_eq!((p4 + p3).reorder_delay_ns, 7);
+        assert_eq!((p4 + p3).reorder_gap, 5);
+        assert_eq!((p4 + p3).jitter, p2.jitter);
+        assert!((p1 + p1).jitter.is_none());
+        assert_eq!(p4.mtu, Some(1400));
+
+        assert_eq!((p4 + p3).mtu, Some(1280));
+        assert_eq!((p1 + p1).mtu, None);
+        assert_eq!((p4 + p3).routers, 3);
+    }
+
+    #[test]
+    fn test_node_addresses() {
+        let mut ip_assignment = IpAssignment::new();
+        let a = ip_assignment.assign(1);
+        let b = ip_assignment.assign(2);
+        let c = ip_assignment.assign(1);
+
+        assert_eq!(ip_assignment.get_node_addresses(1), [a, c]);
+        assert_eq!(ip_assignment.get_node_addresses(2), [b]);
+        assert!(ip_assignment.get_node_addresses(3).is_empty());
+    }
+
+    #[test]
+    fn test_pool() {
+        let pool = Subnet::new(std::net::Ipv4Addr::new(10, 1, 0, 0), 29);
+        let addr = |x| std::net::IpAddr::V4(std::net::Ipv4Addr::new(10, 1, 0, x));
+
+        let mut ip_assignment = IpAssignment::new();
+        ip_assignment.assign_ip(1, addr(2)).unwrap();
+
+        // addresses that were assigned explicitly are skipped
+        let assigned: Vec<_> =
+            std::iter::from_fn(|| ip_assignment.assign_from_pool(1, pool)).collect();
+        assert_eq!(assigned, [addr(1), addr(3), addr(4), addr(5), addr(6)]);
+        assert_eq!(ip_assignment.assign_from_pool(1, pool), None);
     }
 
     #[test]
@@ -645,4 +2071,413 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_jitter() {
+        let graph = r#"graph [
+          node [
+            id 0
+            host_jitter "2 ms"
+            host_jitter_distribution "pareto"
+          ]
+          node [
+            id 1
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ms"
+          ]
+          edge [
+            source 1
+            target 1
+            latency "1 ms"
+          ]
+          edge [
+            source 0
+            target 1
+            latency "1 ms"
+            jitter "5 us"
+            jitter_distribution "lognormal"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let node_0 = *graph.node_id_to_index(0).unwrap();
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+
+        let paths = graph.get_direct_paths(&[node_0, node_1]).unwrap();
+
+        let edge_jitter = Jitter {
+            scale_ns: 5_000,
+            distribution: JitterDistribution::LogNormal,
+        };
+        let host_jitter = Jitter {
+            scale_ns: 2_000_000,
+            distribution: JitterDistribution::Pareto,
+        };
+
+        assert!(paths[&(node_0, node_0)].jitter.is_none());
+        assert_eq!(paths[&(node_0, node_0)].host_jitter, host_jitter);
+        assert_eq!(paths[&(node_0, node_1)].jitter, edge_jitter);
+        assert_eq!(paths[&(node_0, node_1)].host_jitter, host_jitter);
+        assert_eq!(paths[&(node_1, node_0)].jitter, edge_jitter);
+        assert!(paths[&(node_1, node_0)].host_jitter.is_none());
+
+        let graph = r#"graph [
+          node [
+            id 0
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ms"
+            jitter_distribution "uniform"
+          ]
+        ]"#;
+        NetworkGraph::parse(graph).unwrap_err();
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_links() {
+        let graph = r#"graph [
+          node [
+            id 0
+          ]
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ms"
+          ]
+          edge [
+            source 1
+            target 1
+            latency "1 ms"
+            bandwidth "10 Mbit"
+          ]
+          edge [
+            source 2
+            target 2
+            latency "1 ms"
+          ]
+          edge [
+            source 0
+            target 1
+            latency "3 ms"
+            bandwidth "100 Mbit"
+            buffer_size "64 KB"
+          ]
+          edge [
+            source 1
+            target 2
+            latency "5 ms"
+            bandwidth "1 Gbit"
+          ]
+          edge [
+            source 0
+            target 2
+            latency "20 ms"
+            bandwidth "1 Gbit"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let node_0 = *graph.node_id_to_index(0).unwrap();
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let node_2 = *graph.node_id_to_index(2).unwrap();
+        let nodes = [node_0, node_1, node_2];
+
+        let (links, path_links) = graph.compute_links(&nodes, true).unwrap();
+        let link = |src, dst| &links[path_links[&(src, dst)][0].link];
+        let offsets = |src, dst| {
+            path_links[&(src, dst)]
+                .iter()
+                .map(|x| x.offset_ns)
+                .collect::<Vec<_>>()
+        };
+
+        // the shortest path from 0 to 2 crosses the edges 0-1 and 1-2
+        assert_eq!(offsets(node_0, node_2), [0, 3_000_000]);
+        assert_eq!(
+            *link(node_0, node_2),
+            LinkProperties {
+                bits_per_sec: 100_000_000,
+                buffer_bytes: Some(64_000),
+                overhead_ns: 0,
+            }
+        );
+
+        // each direction of an edge is a different link
+        assert_eq!(
+            path_links[&(node_0, node_1)][0],
+            path_links[&(node_0, node_2)][0]
+        );
+        assert_ne!(
+            path_links[&(node_0, node_1)][0],
+            path_links[&(node_1, node_0)][0]
+        );
+
+        // edges without a bandwidth aren't links
+        assert!(path_links[&(node_0, node_0)].is_empty());
+        assert_eq!(link(node_1, node_1).bits_per_sec, 10_000_000);
+
+        // the direct path from 0 to 2 crosses only the edge 0-2
+        let (_links, path_links) = graph.compute_links(&nodes, false).unwrap();
+        assert_eq!(path_links[&(node_0, node_2)].len(), 1);
+
+        let graph = r#"graph [
+          node [
+            id 0
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ms"
+            buffer_size "64 KB"
+          ]
+        ]"#;
+        NetworkGraph::parse(graph).unwrap_err();
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_wireless_links() {
+        let graph = r#"graph [
+          node [
+            id 0
+            wireless_bandwidth "54 Mbit"
+            wireless_overhead "100 us"
+          ]
+          node [
+            id 1
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ms"
+          ]
+          edge [
+            source 1
+            target 1
+            latency "1 ms"
+          ]
+          edge [
+            source 0
+            target 1
+            latency "3 ms"
+            bandwidth "1 Gbit"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let node_0 = *graph.node_id_to_index(0).unwrap();
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let nodes = [node_0, node_1];
+
+        let (links, path_links) = graph.compute_links(&nodes, true).unwrap();
+        let crossed = |src, dst| {
+            path_links[&(src, dst)]
+                .iter()
+                .map(|x| (links[x.link].bits_per_sec, x.offset_ns))
+                .collect::<Vec<_>>()
+        };
+
+        // packets sent or received by the hosts of the access point cross its channel
+        assert_eq!(
+            crossed(node_0, node_1),
+            [(54_000_000, 0), (1_000_000_000, 0)]
+        );
+        assert_eq!(
+            crossed(node_1, node_0),
+            [(1_000_000_000, 0), (54_000_000, 3_000_000)]
+        );
+        assert_eq!(
+            crossed(node_0, node_0),
+            [(54_000_000, 0), (54_000_000, 1_000_000)]
+        );
+        assert!(crossed(node_1, node_1).is_empty());
+
+        let channel = path_links[&(node_0, node_1)][0].link;
+        assert_eq!(links[channel].overhead_ns, 100_000);
+        assert_eq!(links[channel].buffer_bytes, None);
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_asymmetric_edges() {
+        let graph = r#"graph [
+          node [
+            id 0
+          ]
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ns"
+          ]
+          edge [
+            source 1
+            target 1
+            latency "1 ns"
+          ]
+          edge [
+            source 2
+            target 2
+            latency "1 ns"
+          ]
+          edge [
+            source 0
+            target 1
+            latency "3 ns"
+            reverse_latency "30 ns"
+            packet_loss 0.1
+            reverse_packet_loss 0.2
+            bandwidth "10 Mbit"
+            reverse_bandwidth "100 Mbit"
+          ]
+          edge [
+            source 0
+            target 2
+            latency "7 ns"
+          ]
+          edge [
+            source 2
+            target 1
+            latency "11 ns"
+            reverse_bandwidth "1 Gbit"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let node_0 = *graph.node_id_to_index(0).unwrap();
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let node_2 = *graph.node_id_to_index(2).unwrap();
+        let nodes = [node_0, node_1, node_2];
+
+        let paths = graph.get_direct_paths(&nodes).unwrap();
+        assert_eq!(paths[&(node_0, node_1)].latency_ns, 3);
+        assert_eq!(paths[&(node_0, node_1)].packet_loss, 0.1);
+        assert_eq!(paths[&(node_1, node_0)].latency_ns, 30);
+        assert_eq!(paths[&(node_1, node_0)].packet_loss, 0.2);
+
+        // the shortest path from 1 to 0 avoids the slow direction of the edge 0-1
+        let paths = graph.compute_shortest_paths(&nodes).unwrap();
+        assert_eq!(paths[&(node_0, node_1)].latency_ns, 3);
+        assert_eq!(paths[&(node_1, node_0)].latency_ns, 18);
+        assert_eq!(paths[&(node_1, node_2)].latency_ns, 11);
+
+        let (links, path_links) = graph.compute_links(&nodes, true).unwrap();
+        let bandwidths = |src, dst| {
+            path_links[&(src, dst)]
+                .iter()
+                .map(|x| links[x.link].bits_per_sec)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bandwidths(node_0, node_1), [10_000_000]);
+        assert_eq!(bandwidths(node_1, node_0), [1_000_000_000]);
+        assert!(bandwidths(node_2, node_1).is_empty());
+
+        let (links, path_links) = graph.compute_links(&nodes, false).unwrap();
+        let link = path_links[&(node_1, node_0)][0];
+        assert_eq!(links[link.link].bits_per_sec, 100_000_000);
+
+        // reverse attributes aren't valid in directed graphs or for self-loops
+        let graph = r#"graph [
+          directed 1
+          node [
+            id 0
+          ]
+          node [
+            id 1
+          ]
+          edge [
+            source 0
+            target 1
+            latency "1 ms"
+            reverse_latency "2 ms"
+          ]
+        ]"#;
+        NetworkGraph::parse(graph).unwrap_err();
+
+        let graph = r#"graph [
+          node [
+            id 0
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ms"
+            reverse_packet_loss 0.5
+          ]
+        ]"#;
+        NetworkGraph::parse(graph).unwrap_err();
+    }
+
+    #[test]
+    fn test_geographic_latency() {
+        let graph = |overhead: &str, edge: &str| {
+            format!(
+                r#"graph [
+                  {overhead}
+                  node [
+                    id 0
+                    label "new york"
+                    latitude 40.7128
+                    longitude -74.0060
+                  ]
+                  node [
+                    id 1
+                    label "london"
+                    latitude 51.5074
+                    longitude -0.1278
+                  ]
+                  node [
+                    id 2
+                  ]
+                  edge [
+                    source 0
+                    target 1
+                    {edge}
+                  ]
+                  edge [
+                    source 1
+                    target 2
+                    latency "2 ms"
+                  ]
+                ]"#
+            )
+        };
+        let latency_ns = |graph: &str| {
+            let graph = NetworkGraph::parse(graph).unwrap();
+            let latency = graph.edge_weight(graph.edge_between(0, 1).unwrap()).latency;
+            latency.convert(units::TimePrefix::Nano).unwrap().value()
+        };
+
+        // the great-circle distance is about 5570 km
+        let latency = latency_ns(&graph("", ""));
+        assert!((27_500_000..28_200_000).contains(&latency), "{latency}");
+
+        let doubled = latency_ns(&graph("latency_overhead 2", ""));
+        assert!(doubled.abs_diff(2 * latency) <= 1);
+
+        // an explicit latency isn't replaced
+        assert_eq!(latency_ns(&graph("", "latency \"5 ms\"")), 5_000_000);
+
+        // the edge's latency can't be computed without the location of both nodes
+        NetworkGraph::parse(&graph("", "").replace("latitude 51.5074", "")).unwrap_err();
+        NetworkGraph::parse(&graph("", "").replace("source 0", "source 2")).unwrap_err();
+        NetworkGraph::parse(&graph("latency_overhead 0.0", "")).unwrap_err();
+        NetworkGraph::parse(&graph("", "").replace("51.5074", "91.0")).unwrap_err();
+    }
 }
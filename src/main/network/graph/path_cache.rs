@@ -0,0 +1,249 @@
+//! Paths that are computed on demand from each source node, rather than between every pair of
+//! nodes when the simulation starts.
+//!
+//! The tables of paths between every pair of nodes grow with the square of the number of nodes in
+//! use, which doesn't fit in memory for very large graphs. The cache instead computes all of the
+//! paths from a source node the first time that a path from the node is needed, and keeps the paths
+//! of the most recently used source nodes. The cache is shared by all of the worker threads.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use petgraph::graph::NodeIndex;
+
+use super::{NetworkGraph, PathProperties};
+
+/// A cache of the paths from the most recently used source nodes.
+#[derive(Debug)]
+pub struct PathCache<T> {
+    graph: NetworkGraph,
+    /// The nodes in use, and their petgraph indexes.
+    nodes: HashMap<T, NodeIndex>,
+    /// The node of each petgraph index in use.
+    ids: HashMap<NodeIndex, T>,
+    use_shortest_paths: bool,
+    /// The maximum number of source nodes whose paths are cached.
+    capacity: usize,
+    entries: Mutex<Entries<T>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// The lowest latency of any edge, which no path's latency is lower than.
+    smallest_latency_ns: Option<u64>,
+}
+
+/// The cached paths of each source node.
+#[derive(Debug)]
+struct Entries<T> {
+    /// The paths from each source node to each destination node, and the time that they were last
+    /// used.
+    paths: HashMap<T, (Arc<HashMap<T, PathProperties>>, u64)>,
+    /// Incremented each time the cache is used.
+    clock: u64,
+}
+
+impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> PathCache<T> {
+    /// Cache the paths between the `nodes` in use, keeping the paths from at most `capacity`
+    /// source nodes.
+    pub fn new(
+        graph: NetworkGraph,
+        nodes: HashMap<T, NodeIndex>,
+        use_shortest_paths: bool,
+        capacity: usize,
+    ) -> Self {
+        assert!(capacity > 0);
+        let ids = nodes.iter().map(|(id, index)| (*index, *id)).collect();
+        let smallest_latency_ns = graph.smallest_edge_latency_ns();
+
+        Self {
+            graph,
+            nodes,
+            ids,
+            use_shortest_paths,
+            capacity,
+            entries: Mutex::new(Entries {
+                paths: HashMap::new(),
+                clock: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            smallest_latency_ns,
+        }
+    }
+
+    /// Get properties for the path from one node to another, computing the paths from `start` if
+    /// they aren't cached.
+    pub fn path(&self, start: T, end: T) -> Option<PathProperties> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.clock += 1;
+            let clock = entries.clock;
+
+            if let Some((paths, last_used)) = entries.paths.get_mut(&start) {
+                *last_used = clock;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return paths.get(&end).copied();
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // compute the paths without holding the lock, so that other threads can use the cache
+        let paths = Arc::new(self.compute_paths_from(start)?);
+        let path = paths.get(&end).copied();
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        // evict the least recently used source node
+        if entries.paths.len() >= self.capacity && !entries.paths.contains_key(&start) {
+            let oldest = entries
+                .paths
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(id, _)| *id)
+                .unwrap();
+            entries.paths.remove(&oldest);
+        }
+
+        entries.paths.insert(start, (paths, clock));
+
+        path
+    }
+
+    /// Compute the paths from `start` to each of the nodes in use. Returns `None` if `start`
+    /// isn't in use.
+    fn compute_paths_from(&self, start: T) -> Option<HashMap<T, PathProperties>> {
+        let src = *self.nodes.get(&start)?;
+        let nodes: Vec<_> = self.nodes.values().copied().collect();
+
+        let paths = self
+            .graph
+            .compute_paths_from(src, &nodes, self.use_shortest_paths)
+            .unwrap_or_else(|e| panic!("Failed to compute the paths from node {start}: {e}"));
+
+        Some(
+            paths
+                .into_iter()
+                .map(|((_src, dst), path)| (self.ids[&dst], path))
+                .collect(),
+        )
+    }
+
+    /// The lowest latency that any path can have.
+    pub fn smallest_latency_ns(&self) -> Option<u64> {
+        self.smallest_latency_ns
+    }
+
+    /// Log the number of times that the cached paths were used, and the number of times that
+    /// paths had to be computed.
+    pub fn log_stats(&self) {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let total = hits + misses;
+        let hit_rate = if total == 0 {
+            0.0
+        } else {
+            hits as f64 / total as f64 * 100.0
+        };
+
+        log::info!(
+            "Path cache: {hits} hits, {misses} misses ({hit_rate:.1}% hit rate), \
+            paths from {} of at most {} source nodes cached",
+            self.entries.lock().unwrap().paths.len(),
+            self.capacity,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(capacity: usize) -> PathCache<u32> {
+        let graph = r#"graph [
+          node [
+            id 0
+          ]
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          edge [
+            source 0
+            target 0
+            latency "1 ns"
+          ]
+          edge [
+            source 1
+            target 1
+            latency "2 ns"
+          ]
+          edge [
+            source 2
+            target 2
+            latency "3 ns"
+          ]
+          edge [
+            source 0
+            target 1
+            latency "5 ns"
+          ]
+          edge [
+            source 1
+            target 2
+            latency "7 ns"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let nodes = [0, 1, 2]
+            .into_iter()
+            .map(|id| (id, *graph.node_id_to_index(id).unwrap()))
+            .collect();
+        PathCache::new(graph, nodes, true, capacity)
+    }
+
+    #[test]
+    fn test_path_cache() {
+        let cache = cache(2);
+        let latency = |src, dst| cache.path(src, dst).unwrap().latency_ns;
+
+        assert_eq!(latency(0, 2), 12);
+        assert_eq!(latency(0, 0), 1);
+        assert_eq!(latency(2, 1), 7);
+        assert_eq!(latency(1, 1), 2);
+        // the paths from 0 were evicted when the paths from 1 were computed
+        assert_eq!(latency(0, 1), 5);
+        assert_eq!(latency(1, 0), 5);
+        assert!(cache.path(0, 3).is_none());
+        assert!(cache.path(3, 0).is_none());
+
+        assert_eq!(cache.hits.load(Ordering::Relaxed), 3);
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 5);
+        assert_eq!(cache.entries.lock().unwrap().paths.len(), 2);
+
+        assert_eq!(cache.smallest_latency_ns(), Some(1));
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_path_cache_matches_shortest_paths() {
+        let cache = cache(1);
+        let graph = &cache.graph;
+        let nodes: Vec<_> = cache.nodes.values().copied().collect();
+        let paths = graph.compute_shortest_paths(&nodes).unwrap();
+
+        for ((src, dst), path) in paths {
+            let src = graph.node_index_to_id(src).unwrap();
+            let dst = graph.node_index_to_id(dst).unwrap();
+            let cached = cache.path(src, dst).unwrap();
+            assert_eq!(cached, path);
+            assert_eq!(cached.routers, path.routers);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use petgraph::graph::{EdgeIndex, Graph, IndexType, NodeIndex};
+use petgraph::graph::{EdgeIndex, EdgeIndices, Graph, IndexType, NodeIndex, NodeIndices};
 use petgraph::{Directed, Undirected};
 
 #[derive(Debug)]
@@ -23,6 +23,15 @@ impl<N, E, Ix: IndexType> GraphWrapper<N, E, Ix> {
     enum_passthrough!(self, (edge), Directed, Undirected;
         pub fn edge_weight(&self, edge: EdgeIndex<Ix>) -> Option<&E>
     );
+    enum_passthrough!(self, (), Directed, Undirected;
+        pub fn node_indices(&self) -> NodeIndices<Ix>
+    );
+    enum_passthrough!(self, (), Directed, Undirected;
+        pub fn edge_indices(&self) -> EdgeIndices<Ix>
+    );
+    enum_passthrough!(self, (edge), Directed, Undirected;
+        pub fn edge_endpoints(&self, edge: EdgeIndex<Ix>) -> Option<(NodeIndex<Ix>, NodeIndex<Ix>)>
+    );
     enum_passthrough!(self, (a, b), Directed, Undirected;
         pub fn find_edge(&self, a: NodeIndex<Ix>, b: NodeIndex<Ix>) -> Option<EdgeIndex<Ix>>
     );
@@ -0,0 +1,469 @@
+//! Strategies for choosing the paths between the nodes of a network graph.
+//!
+//! The Gao-Rexford strategy models the routing policies of autonomous systems (ASes) in BGP. Each
+//! edge between two ASes has a business relationship: one AS is the other's provider, or the ASes
+//! are peers. Paths are "valley-free": they climb from customers to providers, cross at most one
+//! peer edge, and then descend from providers to customers. Each AS prefers routes through its
+//! customers over routes through its peers, and routes through its peers over routes through its
+//! providers, and then prefers routes with fewer hops and a lower latency.
+//!
+//! Static routes are loaded from a file with one route per line. A route is the list of node ids
+//! that it visits, from the source node to the destination node, separated by whitespace:
+//!
+//! ```text
+//! # from node 0 to node 3 through nodes 1 and 2
+//! 0 1 2 3
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{NetworkGraph, PathProperties};
+
+/// The bandwidth in bits per second of an edge whose cost is 1 with the
+/// [`RoutingStrategy::Bandwidth`] strategy. Edges with a lower bandwidth cost proportionally more.
+const REFERENCE_BITS_PER_SEC: u64 = 1_000_000_000_000;
+
+/// How the path between each pair of nodes is chosen.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum RoutingStrategy {
+    /// The path with the lowest latency, breaking ties by the lowest packet loss.
+    #[default]
+    Latency,
+    /// The path that crosses the fewest edges, breaking ties by latency.
+    HopCount,
+    /// The path with the lowest sum of the edges' inverse bandwidths, breaking ties by latency.
+    /// Edges without a bandwidth cost the same as the fastest edges.
+    Bandwidth,
+    /// Paths given by static routes. Pairs of nodes without a static route use the path with the
+    /// lowest latency.
+    Static(StaticRoutes),
+    /// Valley-free paths chosen by the routing policies of ASes, using the relationships of the
+    /// edges.
+    GaoRexford,
+}
+
+impl RoutingStrategy {
+    /// Get the cost of crossing an edge with the given properties and bandwidth.
+    pub(super) fn edge_cost(
+        &self,
+        properties: PathProperties,
+        bits_per_sec: Option<u64>,
+    ) -> RouteCost {
+        let metric = match self {
+            Self::Latency | Self::Static(_) | Self::GaoRexford => 0,
+            Self::HopCount => 1,
+            Self::Bandwidth => match bits_per_sec {
+                Some(bits_per_sec) => std::cmp::max(REFERENCE_BITS_PER_SEC / bits_per_sec, 1),
+                None => 1,
+            },
+        };
+
+        RouteCost { metric, properties }
+    }
+}
+
+/// The cost of a path that's minimized by a [`RoutingStrategy`]. Paths are ordered by their
+/// metric first, then by their properties.
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct RouteCost {
+    metric: u64,
+    pub properties: PathProperties,
+}
+
+impl PartialOrd for RouteCost {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match self.metric.cmp(&other.metric) {
+            std::cmp::Ordering::Equal => self.properties.partial_cmp(&other.properties),
+            x => Some(x),
+        }
+    }
+}
+
+impl PartialEq for RouteCost {
+    fn eq(&self, other: &Self) -> bool {
+        // PartialEq must be consistent with PartialOrd
+        self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+    }
+}
+
+impl core::ops::Add for RouteCost {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            metric: self.metric.saturating_add(other.metric),
+            properties: self.properties + other.properties,
+        }
+    }
+}
+
+/// The business relationship of a node to a neighboring node, as used by the
+/// [`RoutingStrategy::GaoRexford`] strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relationship {
+    /// The node is a customer of its neighbor.
+    Customer,
+    /// The node is a provider of its neighbor.
+    Provider,
+    /// The node and its neighbor are peers.
+    Peer,
+}
+
+impl Relationship {
+    /// The relationship in the other direction.
+    pub fn inverse(self) -> Self {
+        match self {
+            Self::Customer => Self::Provider,
+            Self::Provider => Self::Customer,
+            Self::Peer => Self::Peer,
+        }
+    }
+}
+
+impl std::str::FromStr for Relationship {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "customer" => Self::Customer,
+            "provider" => Self::Provider,
+            "peer" => Self::Peer,
+            _ => return Err(format!("'{s}' is not one of customer, provider, peer")),
+        })
+    }
+}
+
+impl std::fmt::Display for Relationship {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Customer => write!(f, "customer"),
+            Self::Provider => write!(f, "provider"),
+            Self::Peer => write!(f, "peer"),
+        }
+    }
+}
+
+/// A route to a destination node, in the order that the [`RoutingStrategy::GaoRexford`] strategy
+/// prefers routes: by the relationship of the next hop, then by the number of hops, then by
+/// latency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct PolicyRoute {
+    /// The class of the route: 0 for the destination itself or a route through a customer, 1 for a
+    /// route through a peer, and 2 for a route through a provider.
+    class: u8,
+    hops: u32,
+    latency_ns: u64,
+}
+
+impl NetworkGraph {
+    /// Get the edges of the Gao-Rexford path between each pair of different nodes, and the nodes
+    /// they're traversed from. Pairs of nodes without a valley-free path are left out.
+    pub(super) fn gao_rexford_path_edges(
+        &self,
+        nodes: &[NodeIndex],
+    ) -> HashMap<(NodeIndex, NodeIndex), Vec<(EdgeIndex, NodeIndex)>> {
+        nodes
+            .into_par_iter()
+            .flat_map(|dst| {
+                let next_hops = self.gao_rexford_next_hops(*dst);
+
+                nodes
+                    .iter()
+                    .filter(|src| *src != dst && next_hops.contains_key(src))
+                    .map(|src| {
+                        // follow the next hops, which always have fewer hops to the destination
+                        let mut edges = Vec::new();
+                        let mut node = *src;
+                        while node != *dst {
+                            let (edge, next) = next_hops[&node];
+                            edges.push((edge, node));
+                            node = next;
+                        }
+                        ((*src, *dst), edges)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Get the edge that each node sends packets to `dst` over, and the node at the other end of
+    /// the edge, for the nodes that have a valley-free route to `dst`.
+    fn gao_rexford_next_hops(&self, dst: NodeIndex) -> HashMap<NodeIndex, (EdgeIndex, NodeIndex)> {
+        let mut routes: HashMap<NodeIndex, PolicyRoute> = HashMap::new();
+        let mut next_hops = HashMap::new();
+
+        let dst_route = PolicyRoute {
+            class: 0,
+            hops: 0,
+            latency_ns: 0,
+        };
+        routes.insert(dst, dst_route);
+
+        // the route of `node` through the edge `edge` to `next`, which has the route `route`
+        let extend = |edge: EdgeIndex, node: NodeIndex, route: PolicyRoute, class| {
+            let weight = self.edge_weight(edge);
+            let latency_ns = self.edge_properties(weight, node).latency_ns;
+            PolicyRoute {
+                class,
+                hops: route.hops + 1,
+                latency_ns: route.latency_ns + latency_ns,
+            }
+        };
+
+        // the relationship of `next` to `node`, which sends packets to `next` over `edge`
+        let relationship = |edge: EdgeIndex, node: NodeIndex| {
+            let weight = self.edge_weight(edge);
+            weight.relationship_from(self.node_index_to_id(node).unwrap())
+        };
+
+        // customer routes: the route is announced up from customers to their providers
+        let mut queue = BinaryHeap::from([Reverse((dst_route, dst))]);
+        while let Some(Reverse((route, next))) = queue.pop() {
+            if routes[&next] != route {
+                continue;
+            }
+            for (edge, node) in self.incoming_edges(next) {
+                if node == next || relationship(edge, node) != Some(Relationship::Customer) {
+                    continue;
+                }
+                let new = extend(edge, node, route, 0);
+                if routes.get(&node).map_or(true, |x| new < *x) {
+                    routes.insert(node, new);
+                    next_hops.insert(node, (edge, next));
+                    queue.push(Reverse((new, node)));
+                }
+            }
+        }
+
+        // peer routes: nodes with a customer route announce it to their peers
+        let customer_routes: Vec<_> = routes.iter().map(|(x, route)| (*x, *route)).collect();
+        let mut peer_routes = HashMap::new();
+        for (next, route) in customer_routes {
+            for (edge, node) in self.incoming_edges(next) {
+                if node == next
+                    || routes.contains_key(&node)
+                    || relationship(edge, node) != Some(Relationship::Peer)
+                {
+                    continue;
+                }
+                let new = extend(edge, node, route, 1);
+                let best: Option<&(PolicyRoute, _, _)> = peer_routes.get(&node);
+                if best.map_or(true, |x| new < x.0) {
+                    peer_routes.insert(node, (new, edge, next));
+                }
+            }
+        }
+        for (node, (route, edge, next)) in peer_routes {
+            routes.insert(node, route);
+            next_hops.insert(node, (edge, next));
+        }
+
+        // provider routes: every route is announced down from providers to their customers
+        let mut queue: BinaryHeap<_> = routes
+            .iter()
+            .map(|(x, route)| Reverse((*route, *x)))
+            .collect();
+        while let Some(Reverse((route, next))) = queue.pop() {
+            if routes[&next] != route {
+                continue;
+            }
+            for (edge, node) in self.incoming_edges(next) {
+                if node == next || relationship(edge, node) != Some(Relationship::Provider) {
+                    continue;
+                }
+                let new = extend(edge, node, route, 2);
+                if routes.get(&node).map_or(true, |x| new < *x) {
+                    routes.insert(node, new);
+                    next_hops.insert(node, (edge, next));
+                    queue.push(Reverse((new, node)));
+                }
+            }
+        }
+
+        next_hops
+    }
+}
+
+/// Routes between pairs of nodes, given as the ids of the nodes that each route visits.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StaticRoutes {
+    routes: HashMap<(u32, u32), Vec<u32>>,
+}
+
+impl StaticRoutes {
+    /// Iterate over the routes. Each route includes its source and destination nodes.
+    pub fn iter(&self) -> impl Iterator<Item = &[u32]> {
+        self.routes.values().map(|x| x.as_slice())
+    }
+}
+
+impl std::str::FromStr for StaticRoutes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut routes = HashMap::new();
+
+        for (line_num, line) in s.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let route = line
+                .split_whitespace()
+                .map(|x| {
+                    x.parse::<u32>()
+                        .map_err(|e| format!("Line {line_num} has an invalid node id '{x}': {e}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if route.len() < 2 {
+                return Err(format!(
+                    "Line {line_num} has a route with fewer than two nodes"
+                ));
+            }
+
+            if let Some(node) = route
+                .iter()
+                .enumerate()
+                .find_map(|(i, x)| route[..i].contains(x).then_some(x))
+            {
+                return Err(format!(
+                    "Line {line_num} has a route that visits node {node} more than once"
+                ));
+            }
+
+            let key = (route[0], *route.last().unwrap());
+            if routes.insert(key, route).is_some() {
+                return Err(format!(
+                    "Line {line_num} has a second route from node {} to node {}",
+                    key.0, key.1
+                ));
+            }
+        }
+
+        Ok(Self { routes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_static_routes() {
+        let routes: StaticRoutes = "
+            # a comment
+            0 1 2
+
+            2\t1 0
+            5 7
+        "
+        .parse()
+        .unwrap();
+
+        let mut routes: Vec<_> = routes.iter().collect();
+        routes.sort();
+        assert_eq!(routes, [&[0, 1, 2][..], &[2, 1, 0], &[5, 7]]);
+    }
+
+    #[test]
+    fn test_invalid_static_routes() {
+        let invalid = ["0", "0 x 2", "0 -1", "0 1 0", "0 1 2\n0 3 2"];
+
+        for routes in invalid {
+            assert!(routes.parse::<StaticRoutes>().is_err(), "{routes:?}");
+        }
+    }
+
+    #[test]
+    fn test_gao_rexford() {
+        // (source, target, relationship of the target to the source, latency in ns)
+        let edges = [
+            (1, 2, "customer", 10),
+            (1, 3, "customer", 10),
+            (2, 3, "peer", 50),
+            (2, 4, "customer", 10),
+            (3, 5, "customer", 10),
+            (4, 5, "peer", 1000),
+            (4, 6, "customer", 10),
+            (6, 8, "peer", 10),
+        ];
+
+        let mut gml = "graph [\n  directed 0\n".to_string();
+        for node in [1, 2, 3, 4, 5, 6, 8] {
+            gml += &format!("  node [\n    id {node}\n  ]\n");
+            gml += &format!("  edge [\n    source {node}\n    target {node}\n");
+            gml += "    latency \"1 ns\"\n  ]\n";
+        }
+        for (source, target, relationship, latency) in edges {
+            gml += &format!("  edge [\n    source {source}\n    target {target}\n");
+            gml += &format!("    latency \"{latency} ns\"\n");
+            gml += &format!("    relationship \"{relationship}\"\n  ]\n");
+        }
+        gml += "]";
+
+        let mut graph = NetworkGraph::parse(&gml).unwrap();
+        graph.set_routing(RoutingStrategy::GaoRexford).unwrap();
+
+        let ids = [1, 2, 3, 4, 5, 6, 8];
+        let nodes: Vec<_> = ids
+            .iter()
+            .map(|x| *graph.node_id_to_index(*x).unwrap())
+            .collect();
+        let paths = graph.compute_shortest_paths(&nodes).unwrap();
+
+        let latency = |a: u32, b: u32| {
+            let a = *graph.node_id_to_index(a).unwrap();
+            let b = *graph.node_id_to_index(b).unwrap();
+            paths.get(&(a, b)).map(|x| x.latency_ns)
+        };
+
+        // routes through customers are preferred
+        assert_eq!(latency(1, 6), Some(30));
+        // routes through peers are preferred over shorter routes through providers
+        assert_eq!(latency(4, 5), Some(1000));
+        assert_eq!(latency(2, 5), Some(60));
+        // paths don't cross a peer edge after descending to a customer
+        assert_eq!(latency(6, 3), Some(70));
+        assert_eq!(latency(6, 5), Some(1010));
+        // routes through peers aren't announced to providers or other peers
+        assert_eq!(latency(6, 8), Some(10));
+        assert_eq!(latency(5, 8), None);
+        assert_eq!(latency(8, 4), None);
+        assert_eq!(latency(8, 8), Some(1));
+
+        // every edge between two different nodes needs a relationship
+        let gml = gml.replace("    relationship \"peer\"\n", "");
+        let mut graph = NetworkGraph::parse(&gml).unwrap();
+        assert!(graph.set_routing(RoutingStrategy::GaoRexford).is_err());
+    }
+
+    #[test]
+    fn test_route_cost_order() {
+        let latency = |latency_ns| PathProperties {
+            latency_ns,
+            ..Default::default()
+        };
+
+        let hops = RoutingStrategy::HopCount;
+        let one_hop = hops.edge_cost(latency(30), None);
+        let two_hops = hops.edge_cost(latency(10), None) + hops.edge_cost(latency(10), None);
+        assert!(one_hop < two_hops);
+
+        let bandwidth = RoutingStrategy::Bandwidth;
+        let fast = bandwidth.edge_cost(latency(30), Some(1_000_000_000));
+        let slow = bandwidth.edge_cost(latency(10), Some(10_000_000));
+        assert!(fast < slow);
+
+        // ties are broken by latency
+        let a = bandwidth.edge_cost(latency(30), None);
+        let b = bandwidth.edge_cost(latency(10), None);
+        assert!(b < a);
+    }
+}
@@ -0,0 +1,181 @@
+//! Wireless access points, which connect the hosts at a graph node over a shared radio channel.
+//!
+//! All packets sent or received by the hosts of an access point's node are transmitted over the
+//! access point's channel, one at a time, so the hosts contend for airtime. Each packet also
+//! occupies the channel for a fixed overhead, which models the inter-frame spacing, the mean
+//! random backoff, and the acknowledgement of a CSMA/CA channel. A host's packets are lost with a
+//! probability that grows with the host's distance from the access point, using a log-distance
+//! path loss model.
+
+use gml_parser::gml::Value;
+
+use crate::utility::units::{self, Unit};
+
+/// The default distance in meters at which half of the packets are lost.
+const DEFAULT_RANGE_M: f64 = 100.0;
+
+/// The default path loss exponent, which is typical of indoor environments.
+const DEFAULT_PATH_LOSS_EXPONENT: f64 = 3.0;
+
+/// The default airtime overhead of each packet, which is an 802.11 DIFS (34 us), the mean backoff
+/// of a contention window of 15 slots (67.5 us), a SIFS (16 us), and an acknowledgement (24 us).
+const DEFAULT_OVERHEAD: &str = "142 us";
+
+/// The radio channel of a wireless access point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessPoint {
+    /// The bandwidth of the channel, which is shared by all of the access point's hosts in both
+    /// directions.
+    pub bandwidth: units::BitsPerSec<units::SiPrefixUpper>,
+    /// The distance in meters at which half of the packets are lost.
+    pub range_m: f64,
+    /// How quickly the signal weakens with distance, where 2 is free space.
+    pub path_loss_exponent: f64,
+    /// The time that each packet occupies the channel in addition to its transmission time.
+    pub overhead: units::Time<units::TimePrefix>,
+}
+
+impl AccessPoint {
+    /// Remove the wireless attributes from a graph node, returning the node's access point if it
+    /// has a `wireless_bandwidth`.
+    pub fn from_node(node: &mut gml_parser::gml::Node) -> Result<Option<Self>, String> {
+        let mut number = |name: &str| {
+            node.other
+                .remove(name)
+                .map(|x| match x {
+                    Value::Int(x) => Ok(f64::from(x)),
+                    Value::Float(x) => Ok(f64::from(x)),
+                    Value::Str(_) => Err(format!("Node '{name}' is not a number")),
+                })
+                .transpose()
+        };
+        let range_m = number("wireless_range")?;
+        let path_loss_exponent = number("wireless_path_loss_exponent")?;
+        let overhead = node.other.remove("wireless_overhead");
+
+        let Some(bandwidth) = node.other.remove("wireless_bandwidth") else {
+            if range_m.is_some() || path_loss_exponent.is_some() || overhead.is_some() {
+                return Err("Node wireless attributes require a 'wireless_bandwidth'".into());
+            }
+            return Ok(None);
+        };
+
+        let bandwidth: units::BitsPerSec<units::SiPrefixUpper> = bandwidth
+            .as_str()
+            .ok_or("Node 'wireless_bandwidth' is not a string")?
+            .parse()
+            .map_err(|e| format!("Node 'wireless_bandwidth' is not a valid unit: {}", e))?;
+        if bandwidth.value() == 0 {
+            return Err("Node 'wireless_bandwidth' must be greater than 0".into());
+        }
+
+        let range_m = range_m.unwrap_or(DEFAULT_RANGE_M);
+        if !(range_m > 0.0 && range_m.is_finite()) {
+            return Err("Node 'wireless_range' must be greater than 0".into());
+        }
+
+        let path_loss_exponent = path_loss_exponent.unwrap_or(DEFAULT_PATH_LOSS_EXPONENT);
+        if !(path_loss_exponent > 0.0 && path_loss_exponent.is_finite()) {
+            return Err("Node 'wireless_path_loss_exponent' must be greater than 0".into());
+        }
+
+        let overhead = match overhead {
+            Some(x) => x
+                .as_str()
+                .ok_or("Node 'wireless_overhead' is not a string")?
+                .parse()
+                .map_err(|e| format!("Node 'wireless_overhead' is not a valid unit: {}", e))?,
+            None => DEFAULT_OVERHEAD.parse().unwrap(),
+        };
+
+        Ok(Some(Self {
+            bandwidth,
+            range_m,
+            path_loss_exponent,
+            overhead,
+        }))
+    }
+
+    /// The probability that a packet sent to or from a host at `distance_m` meters from the access
+    /// point is lost. The signal's margin above the receiver's threshold is `10 n log10(range/d)`
+    /// decibels for a path loss exponent `n`, and the loss falls off as a logistic function of the
+    /// margin, so that half of the packets are lost at the access point's range.
+    pub fn packet_loss(&self, distance_m: f64) -> f32 {
+        assert!(distance_m >= 0.0);
+        if distance_m == 0.0 {
+            return 0.0;
+        }
+
+        let margin_db = 10.0 * self.path_loss_exponent * (self.range_m / distance_m).log10();
+        (1.0 / (1.0 + margin_db.exp())) as f32
+    }
+
+    /// The channel's airtime overhead of each packet in nanoseconds.
+    pub fn overhead_ns(&self) -> u64 {
+        self.overhead
+            .convert(units::TimePrefix::Nano)
+            .unwrap()
+            .value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access_point(attributes: &str) -> Result<Option<AccessPoint>, String> {
+        let graph = format!("graph [ node [ id 0 {attributes} ] ]");
+        let mut graph = gml_parser::parse(&graph).unwrap();
+        AccessPoint::from_node(&mut graph.nodes[0])
+    }
+
+    #[test]
+    fn test_access_point() {
+        assert_eq!(access_point("").unwrap(), None);
+
+        let ap = access_point("wireless_bandwidth \"54 Mbit\"")
+            .unwrap()
+            .unwrap();
+        assert_eq!(ap.range_m, DEFAULT_RANGE_M);
+        assert_eq!(ap.path_loss_exponent, DEFAULT_PATH_LOSS_EXPONENT);
+        assert_eq!(ap.overhead_ns(), 142_000);
+
+        let ap = access_point(
+            "wireless_bandwidth \"54 Mbit\" wireless_range 30 \
+            wireless_path_loss_exponent 2.5 wireless_overhead \"50 us\"",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(ap.range_m, 30.0);
+        assert_eq!(ap.path_loss_exponent, 2.5);
+        assert_eq!(ap.overhead_ns(), 50_000);
+
+        for invalid in [
+            "wireless_range 30",
+            "wireless_bandwidth 54",
+            "wireless_bandwidth \"0 Mbit\"",
+            "wireless_bandwidth \"54 Mbit\" wireless_range 0",
+            "wireless_bandwidth \"54 Mbit\" wireless_range \"30 m\"",
+            "wireless_bandwidth \"54 Mbit\" wireless_path_loss_exponent -2",
+            "wireless_bandwidth \"54 Mbit\" wireless_overhead 50",
+        ] {
+            assert!(access_point(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_packet_loss() {
+        let ap = access_point("wireless_bandwidth \"54 Mbit\" wireless_range 50")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(ap.packet_loss(0.0), 0.0);
+        assert!(ap.packet_loss(5.0) < 1e-6);
+        assert!((ap.packet_loss(50.0) - 0.5).abs() < 1e-6);
+        assert!(ap.packet_loss(60.0) > 0.9);
+
+        // the loss increases with distance
+        let losses: Vec<_> = (1..200).map(|x| ap.packet_loss(f64::from(x))).collect();
+        assert!(losses.windows(2).all(|x| x[0] <= x[1]));
+    }
+}
@@ -0,0 +1,163 @@
+//! Queues at the graph edges that have a bandwidth, so that packets crossing the same edge
+//! contend for its bandwidth. The channel of a wireless access point is also a link, which each
+//! packet occupies for an overhead in addition to its transmission time.
+//!
+//! Hosts send packets in parallel, so to keep the simulation deterministic, the packets sent
+//! during a scheduling round are collected and then sent through the links in a deterministic
+//! order between rounds. Each link is a first-in-first-out queue that transmits packets at the
+//! link's bandwidth when the packet was sent, which may change during the simulation. Packets are processed in the order that they were sent rather than the order
+//! that they arrive at each link, so a packet never overtakes an earlier packet at a later link on
+//! its path.
+
+use std::sync::Mutex;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::HostId;
+
+use crate::core::work::event::{Event, EventData};
+use crate::network::graph::{LinkProperties, PathLink};
+use crate::network::packet::{PacketRc, PacketStatus};
+
+pub struct LinkQueues {
+    links: Mutex<Vec<Link>>,
+    /// Packets sent during the current round that haven't been queued at their links yet.
+    pending: Mutex<Vec<PendingPacket>>,
+}
+
+struct Link {
+    /// The time that the link finishes transmitting the packets queued at it.
+    busy_until: EmulatedTime,
+}
+
+struct PendingPacket {
+    /// The packet's event, with the time that it would arrive if the links were idle.
+    event: Event,
+    send_time: EmulatedTime,
+    size: u64,
+    dst_host_id: HostId,
+    /// The links that the packet will cross, and their properties when the packet was sent.
+    links: Vec<(PathLink, LinkProperties)>,
+}
+
+impl LinkQueues {
+    pub fn new(num_links: usize) -> Self {
+        let links = (0..num_links)
+            .map(|_| Link {
+                busy_until: EmulatedTime::SIMULATION_START,
+            })
+            .collect();
+
+        Self {
+            links: Mutex::new(links),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add a packet event that was sent at `send_time` and will cross `links`. The event's time
+    /// should be the time that the packet would arrive if the links were idle. The packet's `size`
+    /// is its total size in bytes, and `properties` are the properties of all links at
+    /// `send_time`.
+    pub fn push(
+        &self,
+        event: Event,
+        send_time: EmulatedTime,
+        size: u64,
+        dst_host_id: HostId,
+        links: &[PathLink],
+        properties: &[LinkProperties],
+    ) {
+        self.pending.lock().unwrap().push(PendingPacket {
+            event,
+            send_time,
+            size,
+            dst_host_id,
+            links: links.iter().map(|x| (*x, properties[x.link])).collect(),
+        });
+    }
+
+    /// Queue the packets sent since the last flush at their links, and return their events with
+    /// their arrival times delayed by the time they spent queued and being transmitted. Packets
+    /// that don't fit in a link's buffer are dropped.
+    pub fn flush(&self) -> Vec<(HostId, Event)> {
+        let mut pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut links = self.links.lock().unwrap();
+
+        // the events are ordered by their source host and event ID when their times are equal
+        pending.sort_by(|a, b| {
+            a.send_time
+                .cmp(&b.send_time)
+                .then_with(|| a.event.partial_cmp(&b.event).unwrap())
+        });
+
+        let mut events = Vec::with_capacity(pending.len());
+
+        'packets: for mut packet in pending {
+            let mut delay = SimulationTime::ZERO;
+
+            for (path_link, properties) in &packet.links {
+                let link = &mut links[path_link.link];
+                let arrival =
+                    packet.send_time + SimulationTime::from_nanos(path_link.offset_ns) + delay;
+                let start = std::cmp::max(arrival, link.busy_until);
+                let wait = start.duration_since(&arrival);
+
+                if let Some(buffer_bytes) = properties.buffer_bytes {
+                    let queued_bytes = transmit_bytes(wait, properties.bits_per_sec);
+                    if queued_bytes + packet.size > buffer_bytes {
+                        drop_packet(packet.event);
+                        continue 'packets;
+                    }
+                }
+
+                let transmit_time = transmit_time(packet.size, properties.bits_per_sec)
+                    + SimulationTime::from_nanos(properties.overhead_ns);
+                link.busy_until = start + transmit_time;
+                delay += wait + transmit_time;
+            }
+
+            packet.event.set_time(packet.event.time() + delay);
+            events.push((packet.dst_host_id, packet.event));
+        }
+
+        events
+    }
+}
+
+/// The time to transmit `bytes` at a bandwidth of `bits_per_sec`.
+fn transmit_time(bytes: u64, bits_per_sec: u64) -> SimulationTime {
+    let nanos = u128::from(bytes) * 8 * 1_000_000_000 / u128::from(bits_per_sec);
+    SimulationTime::from_nanos(nanos.try_into().unwrap())
+}
+
+/// The number of bytes transmitted in `time` at a bandwidth of `bits_per_sec`.
+fn transmit_bytes(time: SimulationTime, bits_per_sec: u64) -> u64 {
+    let bytes = time.as_nanos() * u128::from(bits_per_sec) / 8 / 1_000_000_000;
+    bytes.try_into().unwrap()
+}
+
+fn drop_packet(event: Event) {
+    let EventData::Packet(data) = event.data() else {
+        panic!("Expected a packet event");
+    };
+    let mut packet = PacketRc::from(data);
+    packet.add_status(PacketStatus::InetDropped);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transmit_time() {
+        assert_eq!(
+            transmit_time(1500, 1_000_000_000),
+            SimulationTime::from_nanos(12_000)
+        );
+        assert_eq!(
+            transmit_bytes(SimulationTime::from_nanos(12_000), 1_000_000_000),
+            1500
+        );
+        assert_eq!(transmit_bytes(SimulationTime::ZERO, 1_000_000_000), 0);
+    }
+}
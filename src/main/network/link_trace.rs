@@ -0,0 +1,275 @@
+//! Traces of link attributes that change during the simulation, such as the latency and bandwidth
+//! of a mobile or satellite link.
+//!
+//! A trace is a CSV file whose first line is a header naming its columns. The `time` column is
+//! required, and the `latency`, `bandwidth`, and `loss` columns are optional:
+//!
+//! ```text
+//! time, latency, bandwidth, loss
+//! 0 s, 20 ms, 10 Mbit, 0.0
+//! 5 s, 80 ms, 2 Mbit, 0.01
+//! 9 s, , 5 Mbit,
+//! ```
+//!
+//! Each row sets the link's attributes from its time until the time of the next row. An empty
+//! field keeps the value of the previous row, or the link's configured value if no earlier row has
+//! a value. Blank lines and lines starting with `#` are ignored.
+
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::utility::units::{self, Unit};
+
+/// A parsed link trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkTrace {
+    /// The rows of the trace, sorted by time.
+    rows: Vec<LinkTraceRow>,
+}
+
+/// The attributes of a link from a time in a trace. Attributes that are `None` haven't been set by
+/// the trace, and keep their configured values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkTraceRow {
+    pub time: SimulationTime,
+    pub latency: Option<units::Time<units::TimePrefix>>,
+    pub bandwidth: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    /// Packet loss as fraction.
+    pub loss: Option<f32>,
+}
+
+/// A column of a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Time,
+    Latency,
+    Bandwidth,
+    Loss,
+}
+
+impl Column {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Time => "time",
+            Self::Latency => "latency",
+            Self::Bandwidth => "bandwidth",
+            Self::Loss => "loss",
+        }
+    }
+}
+
+impl LinkTrace {
+    /// The rows of the trace, sorted by time.
+    pub fn rows(&self) -> &[LinkTraceRow] {
+        &self.rows
+    }
+
+    /// The row that's in effect at `time`, or `None` if `time` is before the first row.
+    pub fn at(&self, time: SimulationTime) -> Option<&LinkTraceRow> {
+        // the number of rows that have started by `time`
+        let started = self.rows.partition_point(|row| row.time <= time);
+        started.checked_sub(1).map(|index| &self.rows[index])
+    }
+
+    /// Returns true if any row of the trace sets the bandwidth.
+    pub fn has_bandwidth(&self) -> bool {
+        self.rows.iter().any(|row| row.bandwidth.is_some())
+    }
+}
+
+impl std::str::FromStr for LinkTrace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (_, header) = lines.next().ok_or("The trace has no header")?;
+        let columns = header
+            .split(',')
+            .map(|name| match name.trim() {
+                "time" => Ok(Column::Time),
+                "latency" => Ok(Column::Latency),
+                "bandwidth" => Ok(Column::Bandwidth),
+                "loss" => Ok(Column::Loss),
+                name => Err(format!(
+                    "Unknown column '{name}' (expected one of time, latency, bandwidth, loss)"
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some((_, column)) = columns
+            .iter()
+            .enumerate()
+            .find(|(i, x)| columns[..*i].contains(x))
+        {
+            return Err(format!("Column '{}' appears more than once", column.name()));
+        }
+        if !columns.contains(&Column::Time) {
+            return Err("The trace has no 'time' column".into());
+        }
+
+        let mut rows: Vec<LinkTraceRow> = Vec::new();
+
+        for (line_num, line) in lines {
+            let fields: Vec<_> = line.split(',').map(str::trim).collect();
+            if fields.len() != columns.len() {
+                return Err(format!(
+                    "Line {line_num} has {} fields, but the header has {} columns",
+                    fields.len(),
+                    columns.len()
+                ));
+            }
+
+            // empty fields keep the values of the previous row
+            let mut row = rows.last().copied().unwrap_or(LinkTraceRow {
+                time: SimulationTime::ZERO,
+                latency: None,
+                bandwidth: None,
+                loss: None,
+            });
+            let mut time = None;
+
+            for (column, field) in columns.iter().zip(fields) {
+                if field.is_empty() {
+                    continue;
+                }
+
+                let invalid = |e: &dyn std::fmt::Display| {
+                    format!(
+                        "Line {line_num} has an invalid {} '{field}': {e}",
+                        column.name()
+                    )
+                };
+
+                match column {
+                    Column::Time => {
+                        let value: units::Time<units::TimePrefix> =
+                            field.parse().map_err(|e| invalid(&e))?;
+                        let value = std::time::Duration::from(value);
+                        time = Some(
+                            SimulationTime::try_from(value)
+                                .map_err(|_| invalid(&"time is too large"))?,
+                        );
+                    }
+                    Column::Latency => {
+                        row.latency = Some(field.parse().map_err(|e| invalid(&e))?);
+                    }
+                    Column::Bandwidth => {
+                        let value: units::BitsPerSec<units::SiPrefixUpper> =
+                            field.parse().map_err(|e| invalid(&e))?;
+                        if value.value() == 0 {
+                            return Err(invalid(&"bandwidth must not be 0"));
+                        }
+                        row.bandwidth = Some(value);
+                    }
+                    Column::Loss => {
+                        let value: f32 = field.parse().map_err(|e| invalid(&e))?;
+                        if !(0.0..=1.0).contains(&value) {
+                            return Err(invalid(&"loss is not in the range [0,1]"));
+                        }
+                        row.loss = Some(value);
+                    }
+                }
+            }
+
+            row.time = time.ok_or(format!("Line {line_num} has no time"))?;
+
+            if rows.last().is_some_and(|prev| prev.time >= row.time) {
+                return Err(format!(
+                    "Line {line_num} has a time that isn't after the time of the previous row"
+                ));
+            }
+
+            rows.push(row);
+        }
+
+        if rows.is_empty() {
+            return Err("The trace has no rows".into());
+        }
+
+        Ok(Self { rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(x: u64) -> units::Time<units::TimePrefix> {
+        units::Time::new(x, units::TimePrefix::Milli)
+    }
+
+    #[test]
+    fn test_parse() {
+        let trace: LinkTrace = "
+            # a comment
+            time, latency, bandwidth, loss
+            0 s, 20 ms, 10 Mbit, 0.0
+
+            5 s, 80 ms, , 0.01
+            9000 ms, , 5 Mbit,
+        "
+        .parse()
+        .unwrap();
+
+        let rows = trace.rows();
+        assert_eq!(rows.len(), 3);
+
+        assert_eq!(rows[0].time, SimulationTime::ZERO);
+        assert_eq!(rows[0].latency, Some(ms(20)));
+        assert_eq!(rows[0].bandwidth.unwrap().value(), 10);
+        assert_eq!(rows[0].loss, Some(0.0));
+
+        // empty fields keep the values of the previous row
+        assert_eq!(rows[1].time, SimulationTime::from_secs(5));
+        assert_eq!(rows[1].latency, Some(ms(80)));
+        assert_eq!(rows[1].bandwidth, rows[0].bandwidth);
+        assert_eq!(rows[1].loss, Some(0.01));
+
+        assert_eq!(rows[2].time, SimulationTime::from_secs(9));
+        assert_eq!(rows[2].latency, Some(ms(80)));
+        assert_eq!(rows[2].bandwidth.unwrap().value(), 5);
+        assert_eq!(rows[2].loss, Some(0.01));
+
+        assert_eq!(trace.at(SimulationTime::from_secs(4)), Some(&rows[0]));
+        assert_eq!(trace.at(SimulationTime::from_secs(5)), Some(&rows[1]));
+        assert_eq!(trace.at(SimulationTime::from_secs(100)), Some(&rows[2]));
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let trace: LinkTrace = "bandwidth,time\n10 Mbit,1\n".parse().unwrap();
+        assert_eq!(trace.rows()[0].time, SimulationTime::from_secs(1));
+        assert_eq!(trace.rows()[0].latency, None);
+        assert_eq!(trace.rows()[0].loss, None);
+        assert!(trace.has_bandwidth());
+
+        // attributes that haven't been set yet are unchanged
+        assert_eq!(trace.at(SimulationTime::ZERO), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = [
+            "",
+            "latency\n10 ms\n",
+            "time,time\n1,2\n",
+            "time,speed\n1,2\n",
+            "time,latency\n1\n",
+            "time,latency\n,10 ms\n",
+            "time,latency\n1,10 parsecs\n",
+            "time,bandwidth\n1,0 Mbit\n",
+            "time,loss\n1,1.5\n",
+            "time,loss\n2,0.1\n1,0.2\n",
+            "time,loss\n1,0.1\n1,0.2\n",
+            "time,loss\n",
+        ];
+
+        for trace in invalid {
+            assert!(trace.parse::<LinkTrace>().is_err(), "{trace:?}");
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Middleboxes, which intercept the packets sent to and from some subnets while they're in transit,
+//! like transparent proxies and censorship devices.
+//!
+//! Packets sent to or from an address in a middlebox's intercepted subnets are sent to the
+//! middlebox host instead of their destination. The middlebox checks each packet against its rules
+//! in order, and the first rule that matches the packet decides whether the packet is forwarded,
+//! delayed, dropped, reset, or rewritten. The middlebox forwards packets to their destination from
+//! its own address, so a packet passes through at most one middlebox. The middlebox's own packets
+//! aren't intercepted.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::HostId;
+
+use crate::core::configuration::{
+    FirewallProtocol, MiddleboxAction, MiddleboxOptions, MiddleboxRewrite, MiddleboxRule,
+};
+use crate::cshadow as c;
+use crate::network::nat::Subnet;
+use crate::network::packet::PacketRc;
+
+/// A middlebox host and the subnets that it intercepts.
+#[derive(Debug, Clone)]
+pub struct MiddleboxHost {
+    pub host_id: HostId,
+    pub ip: Ipv4Addr,
+    pub intercept: Vec<Subnet>,
+}
+
+/// The middleboxes of the simulation.
+#[derive(Debug, Default)]
+pub struct Middleboxes {
+    middleboxes: Vec<MiddleboxHost>,
+}
+
+impl Middleboxes {
+    pub fn new(middleboxes: Vec<MiddleboxHost>) -> Self {
+        Self { middleboxes }
+    }
+
+    /// The source and destination addresses of the path taken by a packet that the host
+    /// `src_host_id` sends from `src` to `dst`. Packets from or to an intercepted subnet are sent
+    /// to the subnet's middlebox, and a middlebox forwards the packets that it intercepted from its
+    /// own address.
+    pub fn path(&self, src_host_id: HostId, src: Ipv4Addr, dst: Ipv4Addr) -> (Ipv4Addr, Ipv4Addr) {
+        if let Some(middlebox) = self.middleboxes.iter().find(|x| x.host_id == src_host_id) {
+            return (middlebox.ip, dst);
+        }
+
+        let middlebox = self.middleboxes.iter().find(|x| {
+            x.intercept
+                .iter()
+                .any(|y| y.contains(src) || y.contains(dst))
+        });
+
+        match middlebox {
+            Some(middlebox) => (src, middlebox.ip),
+            None => (src, dst),
+        }
+    }
+}
+
+/// What a middlebox does with a packet after applying its rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiddleboxVerdict {
+    /// Send the packet to its (possibly rewritten) destination.
+    Forward,
+    /// Send the packet to its destination after a delay.
+    Delay(SimulationTime),
+    /// Drop the packet.
+    Drop,
+    /// Drop the packet, and send TCP resets to both of its endpoints.
+    Reset,
+}
+
+struct Rule {
+    id: String,
+    action: MiddleboxAction,
+    protocol: Option<c::ProtocolType>,
+    src_addr: Option<Subnet>,
+    dst_addr: Option<Subnet>,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+    payload: Option<Vec<u8>>,
+    probability: f64,
+    delay: SimulationTime,
+    rewrite: Option<MiddleboxRewrite>,
+}
+
+impl Rule {
+    /// Build a rule from its options. Rules without an ID are identified by their position in the
+    /// list of rules, starting at 1. Panics if an address isn't valid.
+    fn new(options: &MiddleboxRule, index: usize) -> Self {
+        Self {
+            id: options
+                .id
+                .clone()
+                .unwrap_or_else(|| (index + 1).to_string()),
+            action: options.action,
+            protocol: options.protocol.map(|x| match x {
+                FirewallProtocol::Tcp => c::_ProtocolType_PTCP,
+                FirewallProtocol::Udp => c::_ProtocolType_PUDP,
+                FirewallProtocol::Icmp => c::_ProtocolType_PICMP,
+            }),
+            src_addr: options.src_addr.as_ref().map(|x| x.parse().unwrap()),
+            dst_addr: options.dst_addr.as_ref().map(|x| x.parse().unwrap()),
+            src_port: options.src_port,
+            dst_port: options.dst_port,
+            payload: options.payload.as_ref().map(|x| x.as_bytes().to_vec()),
+            probability: options.probability,
+            delay: options
+                .delay
+                .map(|x| std::time::Duration::from(x).try_into().unwrap())
+                .unwrap_or(SimulationTime::ZERO),
+            rewrite: options.rewrite,
+        }
+    }
+
+    fn matches(&self, packet: &PacketRc, payload: &[u8]) -> bool {
+        let src = packet.src_address();
+        let dst = packet.dst_address();
+
+        self.protocol.map_or(true, |x| x == packet.protocol())
+            && self.src_addr.map_or(true, |x| x.contains(*src.ip()))
+            && self.dst_addr.map_or(true, |x| x.contains(*dst.ip()))
+            && self.src_port.map_or(true, |x| x == src.port())
+            && self.dst_port.map_or(true, |x| x == dst.port())
+            && self.payload.as_ref().map_or(true, |x| {
+                x.is_empty() || payload.windows(x.len()).any(|y| y == x)
+            })
+    }
+}
+
+/// The rules of a middlebox host.
+pub struct Middlebox {
+    rules: Vec<Rule>,
+}
+
+impl Middlebox {
+    pub fn new(options: &MiddleboxOptions) -> Self {
+        Self {
+            rules: options
+                .rules
+                .iter()
+                .enumerate()
+                .map(|(i, x)| Rule::new(x, i))
+                .collect(),
+        }
+    }
+
+    /// Apply the first rule that matches an intercepted packet, rewriting the packet if needed.
+    /// Rules with a probability less than 1 use `rng` to decide whether they apply.
+    pub fn process(&self, packet: &mut PacketRc, rng: &mut impl rand::Rng) -> MiddleboxVerdict {
+        // only copy the payload if a rule inspects it
+        let mut payload = Vec::new();
+        if self.rules.iter().any(|x| x.payload.is_some()) {
+            payload.resize(packet.payload_size(), 0);
+            packet.get_payload(&mut payload);
+        }
+
+        let rule = self.rules.iter().find(|x| {
+            x.matches(packet, &payload) && (x.probability >= 1.0 || rng.gen_bool(x.probability))
+        });
+
+        let Some(rule) = rule else {
+            return MiddleboxVerdict::Forward;
+        };
+
+        log::debug!(
+            "Middlebox rule '{}' applied action {:?} to packet from {} to {}",
+            rule.id,
+            rule.action,
+            packet.src_address(),
+            packet.dst_address(),
+        );
+
+        match rule.action {
+            MiddleboxAction::Forward => MiddleboxVerdict::Forward,
+            MiddleboxAction::Drop => MiddleboxVerdict::Drop,
+            MiddleboxAction::Delay => MiddleboxVerdict::Delay(rule.delay),
+            MiddleboxAction::Reset => MiddleboxVerdict::Reset,
+            MiddleboxAction::Rewrite => {
+                let rewrite = rule.rewrite.unwrap();
+                let src = packet.src_address();
+                let dst = packet.dst_address();
+
+                packet.set_src_address(SocketAddrV4::new(
+                    rewrite.src_addr.unwrap_or(*src.ip()),
+                    rewrite.src_port.unwrap_or(src.port()),
+                ));
+                packet.set_dst_address(SocketAddrV4::new(
+                    rewrite.dst_addr.unwrap_or(*dst.ip()),
+                    rewrite.dst_port.unwrap_or(dst.port()),
+                ));
+                MiddleboxVerdict::Forward
+            }
+        }
+    }
+}
+
+/// The headers of the TCP resets that a middlebox sends in place of a TCP packet. The first reset
+/// is sent to the packet's destination as if it were from the packet's source, and the second is
+/// sent to the packet's source as if it were from the packet's destination. The resets use the
+/// sequence numbers that their receivers expect.
+pub fn reset_headers(header: &tcp::TcpHeader) -> [tcp::TcpHeader; 2] {
+    let reset = |src: SocketAddrV4, dst: SocketAddrV4, seq| tcp::TcpHeader {
+        ip: tcp::Ipv4Header {
+            src: *src.ip(),
+            dst: *dst.ip(),
+        },
+        flags: tcp::TcpFlags::RST,
+        src_port: src.port(),
+        dst_port: dst.port(),
+        seq,
+        ack: 0,
+        window_size: 0,
+        selective_acks: None,
+        window_scale: None,
+        timestamp: None,
+        timestamp_echo: None,
+    };
+
+    [
+        reset(header.src(), header.dst(), header.seq),
+        reset(header.dst(), header.src(), header.ack),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddrV4 {
+        s.parse().unwrap()
+    }
+
+    fn new_middlebox(rules: &str) -> Middlebox {
+        let options: MiddleboxOptions =
+            serde_yaml::from_str(&format!("{{intercept: [1.0.0.0/8], rules: {rules}}}")).unwrap();
+        Middlebox::new(&options)
+    }
+
+    /// Process a UDP packet, returning the verdict and the packet's new source and destination.
+    fn process(
+        middlebox: &Middlebox,
+        src: &str,
+        dst: &str,
+        payload: &[u8],
+    ) -> (MiddleboxVerdict, SocketAddrV4, SocketAddrV4) {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+        let mut packet = PacketRc::mock_new_udp(addr(src), addr(dst));
+        if !payload.is_empty() {
+            packet.set_payload(payload, 0);
+        }
+        let verdict = middlebox.process(&mut packet, &mut rng);
+        (verdict, packet.src_address(), packet.dst_address())
+    }
+
+    #[test]
+    fn test_path() {
+        let middleboxes = Middleboxes::new(vec![MiddleboxHost {
+            host_id: HostId::from(0),
+            ip: Ipv4Addr::new(9, 0, 0, 1),
+            intercept: vec!["1.0.0.0/8".parse().unwrap()],
+        }]);
+
+        let src = Ipv4Addr::new(1, 0, 0, 1);
+        let dst = Ipv4Addr::new(2, 0, 0, 1);
+        let middlebox = Ipv4Addr::new(9, 0, 0, 1);
+
+        // packets from and to the intercepted subnet go through the middlebox
+        assert_eq!(
+            middleboxes.path(HostId::from(1), src, dst),
+            (src, middlebox)
+        );
+        assert_eq!(
+            middleboxes.path(HostId::from(2), dst, src),
+            (dst, middlebox)
+        );
+        assert_eq!(middleboxes.path(HostId::from(2), dst, dst), (dst, dst));
+
+        // the middlebox forwards packets from its own address
+        assert_eq!(
+            middleboxes.path(HostId::from(0), src, dst),
+            (middlebox, dst)
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let middlebox = new_middlebox(
+            "[
+                {action: forward, src_addr: 1.0.0.1},
+                {action: drop, protocol: udp, dst_port: 53},
+                {action: delay, delay: 20 ms, dst_addr: 2.0.0.0/8},
+                {action: rewrite, dst_port: 80, rewrite: {dst_addr: 3.0.0.2, dst_port: 8080}},
+                {action: drop, payload: forbidden},
+            ]",
+        );
+
+        let (verdict, _, _) = process(&middlebox, "1.0.0.1:1000", "2.0.0.1:53", b"");
+        assert_eq!(verdict, MiddleboxVerdict::Forward);
+        let (verdict, _, _) = process(&middlebox, "1.0.0.2:1000", "2.0.0.1:53", b"");
+        assert_eq!(verdict, MiddleboxVerdict::Drop);
+        let (verdict, _, _) = process(&middlebox, "1.0.0.2:1000", "2.0.0.1:54", b"");
+        assert_eq!(
+            verdict,
+            MiddleboxVerdict::Delay(SimulationTime::from_millis(20))
+        );
+
+        let (verdict, src, dst) = process(&middlebox, "1.0.0.2:1000", "3.0.0.1:80", b"");
+        assert_eq!(verdict, MiddleboxVerdict::Forward);
+        assert_eq!(src, addr("1.0.0.2:1000"));
+        assert_eq!(dst, addr("3.0.0.2:8080"));
+
+        let (verdict, _, _) = process(&middlebox, "1.0.0.2:1000", "3.0.0.1:81", b"a forbidden b");
+        assert_eq!(verdict, MiddleboxVerdict::Drop);
+        let (verdict, _, dst) = process(&middlebox, "1.0.0.2:1000", "3.0.0.1:81", b"allowed");
+        assert_eq!(verdict, MiddleboxVerdict::Forward);
+        assert_eq!(dst, addr("3.0.0.1:81"));
+    }
+
+    #[test]
+    fn test_probability() {
+        let middlebox = new_middlebox("[{action: drop, probability: 0.5}]");
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        let dropped = (0..1000)
+            .filter(|_| {
+                let mut packet = PacketRc::mock_new_udp(addr("1.0.0.1:1"), addr("2.0.0.1:2"));
+                middlebox.process(&mut packet, &mut rng) == MiddleboxVerdict::Drop
+            })
+            .count();
+        assert!((400..600).contains(&dropped), "{dropped}");
+    }
+
+    #[test]
+    fn test_reset_headers() {
+        let header = tcp::TcpHeader {
+            ip: tcp::Ipv4Header {
+                src: Ipv4Addr::new(1, 0, 0, 1),
+                dst: Ipv4Addr::new(2, 0, 0, 1),
+            },
+            flags: tcp::TcpFlags::ACK | tcp::TcpFlags::PSH,
+            src_port: 1000,
+            dst_port: 80,
+            seq: 100,
+            ack: 200,
+            window_size: 1000,
+            selective_acks: None,
+            window_scale: None,
+            timestamp: None,
+            timestamp_echo: None,
+        };
+
+        let [to_dst, to_src] = reset_headers(&header);
+        assert_eq!(to_dst.src(), addr("1.0.0.1:1000"));
+        assert_eq!(to_dst.dst(), addr("2.0.0.1:80"));
+        assert_eq!(to_dst.seq, 100);
+        assert_eq!(to_dst.flags, tcp::TcpFlags::RST);
+        assert_eq!(to_src.src(), addr("2.0.0.1:80"));
+        assert_eq!(to_src.dst(), addr("1.0.0.1:1000"));
+        assert_eq!(to_src.seq, 200);
+    }
+}
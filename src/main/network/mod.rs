@@ -7,8 +7,15 @@ use std::net::Ipv4Addr;
 
 use crate::network::packet::PacketRc;
 
+pub mod dns_server;
 pub mod graph;
+pub mod link_queue;
+pub mod link_trace;
+pub mod middlebox;
+pub mod multicast;
+pub mod nat;
 pub mod packet;
+pub mod pcap_filter;
 pub mod relay;
 pub mod router;
 
@@ -0,0 +1,177 @@
+//! The hosts that are members of each multicast group.
+//!
+//! Hosts join and leave groups while running in parallel, so a host sending a multicast packet
+//! can't see membership changes that other hosts make during the same scheduling round without the
+//! result depending on the order that the hosts were run in. Instead, a change made during a round
+//! is only seen by senders starting from the next round.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+use std::sync::RwLock;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::HostId;
+
+#[derive(Debug, Default)]
+pub struct MulticastGroups {
+    /// The hosts that have ever been members of each group. The members are ordered by host ID so
+    /// that packets are sent to them in a deterministic order.
+    groups: RwLock<HashMap<Ipv4Addr, BTreeMap<HostId, Membership>>>,
+}
+
+impl MulticastGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the host with address `host_ip` to `group` during the round ending at `round_end`.
+    pub fn join(
+        &self,
+        group: Ipv4Addr,
+        host_id: HostId,
+        host_ip: Ipv4Addr,
+        round_end: EmulatedTime,
+    ) {
+        self.update(group, host_id, host_ip, true, round_end);
+    }
+
+    /// Remove the host with address `host_ip` from `group` during the round ending at
+    /// `round_end`.
+    pub fn leave(
+        &self,
+        group: Ipv4Addr,
+        host_id: HostId,
+        host_ip: Ipv4Addr,
+        round_end: EmulatedTime,
+    ) {
+        self.update(group, host_id, host_ip, false, round_end);
+    }
+
+    fn update(
+        &self,
+        group: Ipv4Addr,
+        host_id: HostId,
+        host_ip: Ipv4Addr,
+        joined: bool,
+        round_end: EmulatedTime,
+    ) {
+        let mut groups = self.groups.write().unwrap();
+        let membership = groups
+            .entry(group)
+            .or_default()
+            .entry(host_id)
+            .or_insert(Membership {
+                ip: host_ip,
+                joined: false,
+                pending: None,
+            });
+
+        membership.joined = membership.is_joined(round_end);
+        membership.pending = Some((round_end, joined));
+    }
+
+    /// The hosts that are members of `group` as seen during the round ending at `round_end`,
+    /// ordered by host ID.
+    pub fn members(&self, group: Ipv4Addr, round_end: EmulatedTime) -> Vec<(HostId, Ipv4Addr)> {
+        let groups = self.groups.read().unwrap();
+        let Some(members) = groups.get(&group) else {
+            return Vec::new();
+        };
+
+        members
+            .iter()
+            .filter(|(_, membership)| membership.is_joined(round_end))
+            .map(|(host_id, membership)| (*host_id, membership.ip))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Membership {
+    ip: Ipv4Addr,
+    /// Whether the host was a member at the end of the last round before `pending`.
+    joined: bool,
+    /// The last change to the membership, and the end time of the round it was made in.
+    pending: Option<(EmulatedTime, bool)>,
+}
+
+impl Membership {
+    /// Whether the host is a member as seen during the round ending at `round_end`.
+    fn is_joined(&self, round_end: EmulatedTime) -> bool {
+        match self.pending {
+            Some((changed, joined)) if changed < round_end => joined,
+            _ => self.joined,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    const GROUP: Ipv4Addr = Ipv4Addr::new(239, 1, 2, 3);
+    const HOST_IP: Ipv4Addr = Ipv4Addr::new(11, 0, 0, 1);
+
+    #[test]
+    fn join_visible_next_round() {
+        let groups = MulticastGroups::new();
+        let host_id = HostId::from(1);
+
+        groups.join(GROUP, host_id, HOST_IP, mock_time_millis(10));
+        assert!(groups.members(GROUP, mock_time_millis(10)).is_empty());
+        assert_eq!(
+            groups.members(GROUP, mock_time_millis(20)),
+            [(host_id, HOST_IP)]
+        );
+    }
+
+    #[test]
+    fn leave_visible_next_round() {
+        let groups = MulticastGroups::new();
+        let host_id = HostId::from(1);
+
+        groups.join(GROUP, host_id, HOST_IP, mock_time_millis(10));
+        groups.leave(GROUP, host_id, HOST_IP, mock_time_millis(20));
+        assert_eq!(
+            groups.members(GROUP, mock_time_millis(20)),
+            [(host_id, HOST_IP)]
+        );
+        assert!(groups.members(GROUP, mock_time_millis(30)).is_empty());
+    }
+
+    #[test]
+    fn join_and_leave_same_round() {
+        let groups = MulticastGroups::new();
+        let host_id = HostId::from(1);
+
+        groups.join(GROUP, host_id, HOST_IP, mock_time_millis(10));
+        groups.leave(GROUP, host_id, HOST_IP, mock_time_millis(10));
+        assert!(groups.members(GROUP, mock_time_millis(20)).is_empty());
+    }
+
+    #[test]
+    fn members_ordered_by_host_id() {
+        let groups = MulticastGroups::new();
+        let round_end = mock_time_millis(10);
+
+        for id in [3, 1, 2] {
+            groups.join(
+                GROUP,
+                HostId::from(id),
+                Ipv4Addr::new(11, 0, 0, id as u8),
+                round_end,
+            );
+        }
+
+        let members: Vec<_> = groups
+            .members(GROUP, mock_time_millis(20))
+            .into_iter()
+            .map(|(host_id, _)| u32::from(host_id))
+            .collect();
+        assert_eq!(members, [1, 2, 3]);
+        assert!(groups
+            .members(Ipv4Addr::new(239, 1, 2, 4), mock_time_millis(20))
+            .is_empty());
+    }
+}
@@ -0,0 +1,482 @@
+//! NAT gateways, which translate the addresses and ports of packets sent between a private subnet
+//! and the rest of the network.
+//!
+//! Hosts in a private subnet send their packets for hosts outside of the subnet to the subnet's
+//! gateway host. The gateway rewrites the source of each packet to its public address and a mapped
+//! port, and sends the packet on. Packets sent to a mapped port of the gateway's public address are
+//! rewritten to the mapping's private address and port, and sent into the subnet. A gateway's
+//! mappings are only used and changed by the gateway host as it processes its packet events in
+//! order, so the mappings are deterministic.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::HostId;
+
+use crate::core::configuration::{NatMapping, NatOptions};
+use crate::network::packet::PacketRc;
+
+/// The lowest public port that a gateway maps.
+const MIN_PORT: u16 = 1024;
+
+/// An IPv4 subnet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subnet {
+    network: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Subnet {
+    pub fn new(network: Ipv4Addr, prefix_len: u8) -> Self {
+        assert!(prefix_len <= 32);
+        Self {
+            network,
+            prefix_len,
+        }
+    }
+
+    pub fn network(&self) -> Ipv4Addr {
+        self.network
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    fn mask(&self) -> u32 {
+        u32::MAX
+            .checked_shl(32 - u32::from(self.prefix_len))
+            .unwrap_or(0)
+    }
+
+    /// Returns true if the subnet's network address has no bits set outside of its prefix.
+    pub fn is_valid(&self) -> bool {
+        u32::from(self.network) & !self.mask() == 0
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & self.mask() == u32::from(self.network) & self.mask()
+    }
+
+    pub fn overlaps(&self, other: &Subnet) -> bool {
+        self.contains(other.network) || other.contains(self.network)
+    }
+
+    /// The addresses in the subnet that can be assigned to hosts, in ascending order. This excludes
+    /// the network and broadcast addresses, unless the prefix is longer than 30 bits.
+    pub fn host_addresses(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let first = u32::from(self.network) & self.mask();
+        let last = first | !self.mask();
+
+        let range = if self.prefix_len <= 30 {
+            (first + 1)..=(last - 1)
+        } else {
+            first..=last
+        };
+
+        range.map(Ipv4Addr::from)
+    }
+}
+
+impl std::fmt::Display for Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+impl std::str::FromStr for Subnet {
+    type Err = String;
+
+    /// Parse a subnet in CIDR notation (for example "10.0.0.0/8"). An address without a prefix
+    /// length is a subnet containing only that address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, prefix_len) = match s.split_once('/') {
+            Some((network, prefix_len)) => (
+                network,
+                prefix_len
+                    .parse::<u8>()
+                    .map_err(|e| format!("Invalid prefix length '{prefix_len}': {e}"))?,
+            ),
+            None => (s, 32),
+        };
+
+        let network = network
+            .parse::<Ipv4Addr>()
+            .map_err(|e| format!("Invalid address '{network}': {e}"))?;
+
+        if prefix_len > 32 {
+            return Err(format!("Prefix length '{prefix_len}' is greater than 32"));
+        }
+
+        Ok(Self::new(network, prefix_len))
+    }
+}
+
+/// The gateway host of a private subnet.
+#[derive(Debug, Clone, Copy)]
+pub struct NatGateway {
+    pub subnet: Subnet,
+    pub host_id: HostId,
+    pub public_ip: Ipv4Addr,
+}
+
+/// The private subnets of the simulation and their gateways.
+#[derive(Debug, Default)]
+pub struct NatGateways {
+    gateways: Vec<NatGateway>,
+}
+
+impl NatGateways {
+    pub fn new(gateways: Vec<NatGateway>) -> Self {
+        Self { gateways }
+    }
+
+    /// The gateway of the private subnet that contains `ip`, if any.
+    pub fn gateway(&self, ip: Ipv4Addr) -> Option<&NatGateway> {
+        self.gateways.iter().find(|x| x.subnet.contains(ip))
+    }
+}
+
+/// What a gateway does with a packet after translating it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatAction {
+    /// Send the packet to its (possibly rewritten) destination.
+    Forward,
+    /// The packet wasn't translated, and is for the gateway host itself.
+    Deliver,
+    /// The gateway has no public ports left to map, so drops the packet.
+    Drop,
+}
+
+/// The private and remote endpoints of a mapping. The remote endpoint is only used by symmetric
+/// mappings.
+type MappingKey = (SocketAddrV4, Option<SocketAddrV4>);
+
+struct Mapping {
+    key: MappingKey,
+    expires: EmulatedTime,
+}
+
+/// The address translation state of a gateway host.
+pub struct Nat {
+    public_ip: Ipv4Addr,
+    subnet: Subnet,
+    mapping: NatMapping,
+    timeout: SimulationTime,
+    hairpinning: bool,
+    /// Mappings keyed by their public port.
+    mappings: HashMap<u16, Mapping>,
+    /// Public ports keyed by their mapping's endpoints.
+    ports: HashMap<MappingKey, u16>,
+    /// The next public port to try to map.
+    next_port: u16,
+}
+
+impl Nat {
+    pub fn new(public_ip: Ipv4Addr, options: &NatOptions) -> Self {
+        Self {
+            public_ip,
+            subnet: Subnet::new(options.subnet, options.prefix_len),
+            mapping: options.mapping,
+            timeout: std::time::Duration::from(options.timeout)
+                .try_into()
+                .unwrap(),
+            hairpinning: options.hairpinning,
+            mappings: HashMap::new(),
+            ports: HashMap::new(),
+            next_port: MIN_PORT,
+        }
+    }
+
+    /// Translate a packet that arrived at the gateway, rewriting its addresses and ports.
+    pub fn translate(&mut self, packet: &mut PacketRc, now: EmulatedTime) -> NatAction {
+        let src = packet.src_address();
+        let dst = packet.dst_address();
+        let from_subnet = self.subnet.contains(*src.ip());
+
+        if from_subnet && *dst.ip() != self.public_ip {
+            let Some(port) = self.map_outbound(src, dst, now) else {
+                return NatAction::Drop;
+            };
+            packet.set_src_address(SocketAddrV4::new(self.public_ip, port));
+            return NatAction::Forward;
+        }
+
+        if *dst.ip() != self.public_ip {
+            return NatAction::Deliver;
+        }
+
+        // without hairpinning, packets from the subnet to the public address are only seen by the
+        // gateway itself
+        if from_subnet && !self.hairpinning {
+            return NatAction::Deliver;
+        }
+
+        // a hairpinned packet appears to come from its source's public address and port, like a
+        // packet from any other remote host
+        let remote = if from_subnet {
+            let Some(port) = self.map_outbound(src, dst, now) else {
+                return NatAction::Drop;
+            };
+            SocketAddrV4::new(self.public_ip, port)
+        } else {
+            src
+        };
+
+        let Some(private) = self.map_inbound(dst.port(), remote, now) else {
+            return NatAction::Deliver;
+        };
+
+        packet.set_src_address(remote);
+        packet.set_dst_address(private);
+        NatAction::Forward
+    }
+
+    /// The public port mapped to a private endpoint for packets sent to `remote`, creating the
+    /// mapping if needed. Returns `None` if there are no public ports left.
+    fn map_outbound(
+        &mut self,
+        private: SocketAddrV4,
+        remote: SocketAddrV4,
+        now: EmulatedTime,
+    ) -> Option<u16> {
+        let key = match self.mapping {
+            NatMapping::FullCone => (private, None),
+            NatMapping::Symmetric => (private, Some(remote)),
+        };
+
+        if let Some(port) = self.ports.get(&key).copied() {
+            let mapping = self.mappings.get_mut(&port).unwrap();
+            if mapping.expires > now {
+                mapping.expires = now + self.timeout;
+                return Some(port);
+            }
+            self.ports.remove(&key);
+            self.mappings.remove(&port);
+        }
+
+        let port = self.allocate_port(now)?;
+        self.ports.insert(key, port);
+        self.mappings.insert(
+            port,
+            Mapping {
+                key,
+                expires: now + self.timeout,
+            },
+        );
+        Some(port)
+    }
+
+    /// The private endpoint mapped to a public port, if `remote` is allowed to send to it.
+    fn map_inbound(
+        &mut self,
+        port: u16,
+        remote: SocketAddrV4,
+        now: EmulatedTime,
+    ) -> Option<SocketAddrV4> {
+        let mapping = self.mappings.get_mut(&port)?;
+
+        if mapping.expires <= now || mapping.key.1.is_some_and(|x| x != remote) {
+            return None;
+        }
+
+        mapping.expires = now + self.timeout;
+        Some(mapping.key.0)
+    }
+
+    /// Find an unused public port, reusing the ports of expired mappings.
+    fn allocate_port(&mut self, now: EmulatedTime) -> Option<u16> {
+        for _ in MIN_PORT..=u16::MAX {
+            let port = self.next_port;
+            self.next_port = port.checked_add(1).unwrap_or(MIN_PORT);
+
+            match self.mappings.get(&port) {
+                Some(mapping) if mapping.expires > now => continue,
+                Some(mapping) => {
+                    self.ports.remove(&mapping.key);
+                    self.mappings.remove(&port);
+                }
+                None => {}
+            }
+
+            return Some(port);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+    use crate::utility::units;
+
+    fn addr(s: &str) -> SocketAddrV4 {
+        s.parse().unwrap()
+    }
+
+    fn new_nat(mapping: NatMapping, hairpinning: bool) -> Nat {
+        let options = NatOptions {
+            subnet: Ipv4Addr::new(192, 168, 0, 0),
+            prefix_len: 24,
+            mapping,
+            timeout: units::Time::new(10, units::TimePrefix::Sec),
+            hairpinning,
+        };
+        Nat::new(Ipv4Addr::new(1, 0, 0, 1), &options)
+    }
+
+    /// Translate a packet, returning the action and the packet's new source and destination.
+    fn translate(
+        nat: &mut Nat,
+        src: &str,
+        dst: &str,
+        millis: u64,
+    ) -> (NatAction, SocketAddrV4, SocketAddrV4) {
+        let mut packet = PacketRc::mock_new_udp(addr(src), addr(dst));
+        let action = nat.translate(&mut packet, mock_time_millis(millis));
+        (action, packet.src_address(), packet.dst_address())
+    }
+
+    #[test]
+    fn test_subnet() {
+        let subnet = Subnet::new(Ipv4Addr::new(10, 1, 0, 0), 16);
+        assert!(subnet.is_valid());
+        assert!(subnet.contains(Ipv4Addr::new(10, 1, 2, 3)));
+        assert!(!subnet.contains(Ipv4Addr::new(10, 2, 0, 0)));
+        assert!(subnet.overlaps(&Subnet::new(Ipv4Addr::new(10, 1, 5, 0), 24)));
+        assert!(subnet.overlaps(&Subnet::new(Ipv4Addr::new(10, 0, 0, 0), 8)));
+        assert!(!subnet.overlaps(&Subnet::new(Ipv4Addr::new(10, 2, 0, 0), 16)));
+        assert!(!Subnet::new(Ipv4Addr::new(10, 1, 0, 1), 16).is_valid());
+        assert!(Subnet::new(Ipv4Addr::new(0, 0, 0, 0), 0).contains(Ipv4Addr::new(1, 2, 3, 4)));
+    }
+
+    #[test]
+    fn test_parse_subnet() {
+        assert_eq!(
+            "10.1.0.0/16".parse(),
+            Ok(Subnet::new(Ipv4Addr::new(10, 1, 0, 0), 16))
+        );
+        assert_eq!(
+            "10.1.2.3".parse(),
+            Ok(Subnet::new(Ipv4Addr::new(10, 1, 2, 3), 32))
+        );
+        assert!("10.1.0.0/33".parse::<Subnet>().is_err());
+        assert!("10.1.0.0/".parse::<Subnet>().is_err());
+        assert!("10.1.0/16".parse::<Subnet>().is_err());
+    }
+
+    #[test]
+    fn test_host_addresses() {
+        let subnet = Subnet::new(Ipv4Addr::new(10, 1, 0, 0), 30);
+        assert_eq!(
+            subnet.host_addresses().collect::<Vec<_>>(),
+            [Ipv4Addr::new(10, 1, 0, 1), Ipv4Addr::new(10, 1, 0, 2)]
+        );
+
+        let subnet = Subnet::new(Ipv4Addr::new(10, 1, 0, 5), 32);
+        assert_eq!(
+            subnet.host_addresses().collect::<Vec<_>>(),
+            [Ipv4Addr::new(10, 1, 0, 5)]
+        );
+
+        let subnet = Subnet::new(Ipv4Addr::new(10, 0, 0, 0), 8);
+        assert_eq!(subnet.host_addresses().count(), (1 << 24) - 2);
+    }
+
+    #[test]
+    fn test_full_cone() {
+        let mut nat = new_nat(NatMapping::FullCone, false);
+
+        // the source is rewritten to a public port
+        let (action, src, dst) = translate(&mut nat, "192.168.0.2:80", "2.0.0.1:90", 0);
+        assert_eq!(action, NatAction::Forward);
+        assert_eq!(src, addr("1.0.0.1:1024"));
+        assert_eq!(dst, addr("2.0.0.1:90"));
+
+        // the same private endpoint uses the same public port for every remote host
+        let (_, src, _) = translate(&mut nat, "192.168.0.2:80", "3.0.0.1:90", 0);
+        assert_eq!(src, addr("1.0.0.1:1024"));
+        let (_, src, _) = translate(&mut nat, "192.168.0.3:80", "2.0.0.1:90", 0);
+        assert_eq!(src, addr("1.0.0.1:1025"));
+
+        // any remote host can send to the public port
+        let (action, src, dst) = translate(&mut nat, "4.0.0.1:90", "1.0.0.1:1024", 0);
+        assert_eq!(action, NatAction::Forward);
+        assert_eq!(src, addr("4.0.0.1:90"));
+        assert_eq!(dst, addr("192.168.0.2:80"));
+
+        // unmapped ports are for the gateway itself
+        let (action, _, dst) = translate(&mut nat, "4.0.0.1:90", "1.0.0.1:2000", 0);
+        assert_eq!(action, NatAction::Deliver);
+        assert_eq!(dst, addr("1.0.0.1:2000"));
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let mut nat = new_nat(NatMapping::Symmetric, false);
+
+        // each remote host gets its own public port
+        let (_, src, _) = translate(&mut nat, "192.168.0.2:80", "2.0.0.1:90", 0);
+        assert_eq!(src, addr("1.0.0.1:1024"));
+        let (_, src, _) = translate(&mut nat, "192.168.0.2:80", "2.0.0.1:91", 0);
+        assert_eq!(src, addr("1.0.0.1:1025"));
+        let (_, src, _) = translate(&mut nat, "192.168.0.2:80", "2.0.0.1:90", 0);
+        assert_eq!(src, addr("1.0.0.1:1024"));
+
+        // only the remote host of the mapping can send to the public port
+        let (action, _, dst) = translate(&mut nat, "2.0.0.1:90", "1.0.0.1:1024", 0);
+        assert_eq!(action, NatAction::Forward);
+        assert_eq!(dst, addr("192.168.0.2:80"));
+        let (action, _, _) = translate(&mut nat, "2.0.0.1:91", "1.0.0.1:1024", 0);
+        assert_eq!(action, NatAction::Deliver);
+        let (action, _, _) = translate(&mut nat, "3.0.0.1:90", "1.0.0.1:1024", 0);
+        assert_eq!(action, NatAction::Deliver);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut nat = new_nat(NatMapping::FullCone, false);
+
+        translate(&mut nat, "192.168.0.2:80", "2.0.0.1:90", 0);
+
+        // inbound packets keep the mapping alive
+        let (action, _, _) = translate(&mut nat, "2.0.0.1:90", "1.0.0.1:1024", 9_000);
+        assert_eq!(action, NatAction::Forward);
+        let (action, _, _) = translate(&mut nat, "2.0.0.1:90", "1.0.0.1:1024", 18_000);
+        assert_eq!(action, NatAction::Forward);
+
+        // the mapping expires 10 seconds after its last packet
+        let (action, _, _) = translate(&mut nat, "2.0.0.1:90", "1.0.0.1:1024", 28_000);
+        assert_eq!(action, NatAction::Deliver);
+
+        // a new mapping uses a new port
+        let (_, src, _) = translate(&mut nat, "192.168.0.2:80", "2.0.0.1:90", 28_000);
+        assert_eq!(src, addr("1.0.0.1:1025"));
+    }
+
+    #[test]
+    fn test_hairpinning() {
+        for hairpinning in [false, true] {
+            let mut nat = new_nat(NatMapping::FullCone, hairpinning);
+
+            translate(&mut nat, "192.168.0.2:80", "2.0.0.1:90", 0);
+
+            // another host in the subnet sends to the first host's public address
+            let (action, src, dst) = translate(&mut nat, "192.168.0.3:80", "1.0.0.1:1024", 0);
+
+            if hairpinning {
+                assert_eq!(action, NatAction::Forward);
+                assert_eq!(src, addr("1.0.0.1:1025"));
+                assert_eq!(dst, addr("192.168.0.2:80"));
+            } else {
+                assert_eq!(action, NatAction::Deliver);
+                assert_eq!(src, addr("192.168.0.3:80"));
+                assert_eq!(dst, addr("1.0.0.1:1024"));
+            }
+        }
+    }
+}
@@ -11,6 +11,7 @@ use crate::utility::pcap_writer::PacketDisplay;
 use linux_api::errno::Errno;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::util::SyncSendPointer;
+use shadow_shim_helper_rs::HostId;
 
 #[repr(i32)]
 pub enum PacketStatus {
@@ -36,6 +37,135 @@ pub enum PacketStatus {
     Destroyed = c::_PacketDeliveryStatusFlags_PDS_DESTROYED,
     RelayCached = c::_PacketDeliveryStatusFlags_PDS_RELAY_CACHED,
     RelayForwarded = c::_PacketDeliveryStatusFlags_PDS_RELAY_FORWARDED,
+    RouterMarked = c::_PacketDeliveryStatusFlags_PDS_ROUTER_MARKED,
+    InetCorrupted = c::_PacketDeliveryStatusFlags_PDS_INET_CORRUPTED,
+    InetReordered = c::_PacketDeliveryStatusFlags_PDS_INET_REORDERED,
+    SndQdiscDropped = c::_PacketDeliveryStatusFlags_PDS_SND_QDISC_DROPPED,
+    FirewallDropped = c::_PacketDeliveryStatusFlags_PDS_FIREWALL_DROPPED,
+}
+
+/// Length of an IPv4 header without options.
+pub const IPV4_HEADER_LEN: usize = 20;
+
+/// The IPv4 protocol numbers of the transport protocols that Shadow models.
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// The TTL of the packets that hosts send, unless the sending socket sets a different TTL. This
+/// must match `PACKET_DEFAULT_TTL` in `packet.h`.
+pub const IPV4_DEFAULT_TTL: u8 = 64;
+
+/// The fields of an ICMP header that Shadow models. Echo messages and "port unreachable", "host
+/// unreachable", "fragmentation needed", and "time exceeded" errors are currently supported. For
+/// errors, the `sequence` field holds the last 16 bits of the header (the next-hop MTU for
+/// "fragmentation needed" errors).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IcmpHeader {
+    pub icmp_type: u8,
+    pub code: u8,
+    pub identifier: u16,
+    pub sequence: u16,
+}
+
+impl IcmpHeader {
+    /// Length of the ICMP header in bytes.
+    pub const LEN: usize = 8;
+
+    pub const TYPE_ECHO_REPLY: u8 = 0;
+    pub const TYPE_DEST_UNREACHABLE: u8 = 3;
+    pub const TYPE_ECHO_REQUEST: u8 = 8;
+    pub const TYPE_TIME_EXCEEDED: u8 = 11;
+
+    /// The `TYPE_DEST_UNREACHABLE` code for a packet that couldn't be forwarded because there was
+    /// no route to its destination.
+    pub const CODE_HOST_UNREACHABLE: u8 = 1;
+    /// The `TYPE_DEST_UNREACHABLE` code for a packet sent to a port that no socket is bound to.
+    pub const CODE_PORT_UNREACHABLE: u8 = 3;
+    /// The `TYPE_DEST_UNREACHABLE` code for a packet that was too large to be forwarded without
+    /// fragmenting it, but had the "don't fragment" flag set.
+    pub const CODE_FRAG_NEEDED: u8 = 4;
+    /// The `TYPE_TIME_EXCEEDED` code for a packet whose TTL expired at a router.
+    pub const CODE_TTL_EXCEEDED: u8 = 0;
+
+    /// Parse the header from the start of an ICMP message. The checksum is ignored. Returns `None`
+    /// if the message is too short.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..Self::LEN)?;
+        Some(Self {
+            icmp_type: bytes[0],
+            code: bytes[1],
+            identifier: u16::from_be_bytes([bytes[4], bytes[5]]),
+            sequence: u16::from_be_bytes([bytes[6], bytes[7]]),
+        })
+    }
+
+    /// Serialize the header, including a checksum computed over the header and `payload`.
+    pub fn to_bytes(&self, payload: &[u8]) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[0] = self.icmp_type;
+        bytes[1] = self.code;
+        bytes[4..6].copy_from_slice(&self.identifier.to_be_bytes());
+        bytes[6..8].copy_from_slice(&self.sequence.to_be_bytes());
+
+        let checksum = internet_checksum([&bytes[..], payload]);
+        bytes[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// An IPv4 header (without options) for a packet with the given TTL, transport protocol, and
+/// payload length.
+pub fn ipv4_header(
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    ttl: u8,
+    protocol: u8,
+    payload_len: usize,
+) -> [u8; IPV4_HEADER_LEN] {
+    let total_len = u16::try_from(IPV4_HEADER_LEN + payload_len).unwrap();
+
+    let mut header = [0; IPV4_HEADER_LEN];
+    // version and header length
+    header[0] = 0x45;
+    header[2..4].copy_from_slice(&total_len.to_be_bytes());
+    // flags (don't fragment)
+    header[6] = 0x40;
+    header[8] = ttl;
+    header[9] = protocol;
+    header[12..16].copy_from_slice(&src.octets());
+    header[16..20].copy_from_slice(&dst.octets());
+
+    let checksum = internet_checksum([&header[..]]);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    header
+}
+
+/// The internet checksum (RFC 1071) of the concatenation of `bufs`.
+pub fn internet_checksum<'a>(bufs: impl IntoIterator<Item = &'a [u8]>) -> u16 {
+    let mut sum: u32 = 0;
+    let mut odd_byte: Option<u8> = None;
+
+    for byte in bufs.into_iter().flatten() {
+        match odd_byte.take() {
+            Some(high) => sum += u32::from(u16::from_be_bytes([high, *byte])),
+            None => odd_byte = Some(*byte),
+        }
+        // fold early so that we never overflow
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    if let Some(high) = odd_byte {
+        sum += u32::from(u16::from_be_bytes([high, 0]));
+    }
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
 }
 
 pub struct PacketRc {
@@ -81,6 +211,14 @@ impl PacketRc {
         PacketRc::from_raw(c_ptr)
     }
 
+    #[cfg(test)]
+    /// Creates a UDP packet with no payload for unit tests.
+    pub fn mock_new_udp(src: SocketAddrV4, dst: SocketAddrV4) -> PacketRc {
+        let mut packet = PacketRc::from_raw(unsafe { c::packet_new_inner(1, 1) });
+        packet.set_udp(src, dst);
+        packet
+    }
+
     /// Set TCP headers for this packet. Will panic if the packet already has a header.
     pub fn set_tcp(&mut self, header: &tcp::TcpHeader) {
         let selective_acks = header
@@ -222,6 +360,38 @@ impl PacketRc {
         };
     }
 
+    /// Set ICMP headers for this packet. Will panic if the packet already has a header.
+    pub fn set_icmp(&mut self, src: Ipv4Addr, dst: Ipv4Addr, header: &IcmpHeader) {
+        unsafe {
+            c::packet_setICMP(
+                self.c_ptr.ptr(),
+                u32::from(src).to_be(),
+                u32::from(dst).to_be(),
+                header.icmp_type,
+                header.code,
+                header.identifier,
+                header.sequence,
+            )
+        };
+    }
+
+    /// Returns `None` if this isn't an ICMP packet.
+    pub fn get_icmp(&self) -> Option<IcmpHeader> {
+        if unsafe { c::packet_getProtocol(self.c_ptr.ptr()) } != c::_ProtocolType_PICMP {
+            return None;
+        }
+
+        let header = unsafe { c::packet_getICMPHeader(self.c_ptr.ptr()) };
+        let header = unsafe { header.as_ref() }.unwrap();
+
+        Some(IcmpHeader {
+            icmp_type: header.type_,
+            code: header.code,
+            identifier: header.identifier,
+            sequence: header.sequence,
+        })
+    }
+
     /// Set the packet payload. Will panic if the packet already has a payload.
     pub fn set_payload(&mut self, payload: &[u8], priority: FifoPacketPriority) {
         unsafe {
@@ -328,10 +498,224 @@ impl PacketRc {
         SocketAddrV4::new(ip, port)
     }
 
+    /// Rewrite the source address and port, like a NAT.
+    pub fn set_src_address(&mut self, addr: SocketAddrV4) {
+        unsafe {
+            c::packet_setSource(
+                self.c_ptr.ptr(),
+                u32::from(*addr.ip()).to_be(),
+                addr.port().to_be(),
+            )
+        };
+    }
+
+    /// Rewrite the destination address and port, like a NAT.
+    pub fn set_dst_address(&mut self, addr: SocketAddrV4) {
+        unsafe {
+            c::packet_setDestination(
+                self.c_ptr.ptr(),
+                u32::from(*addr.ip()).to_be(),
+                addr.port().to_be(),
+            )
+        };
+    }
+
+    pub fn protocol(&self) -> c::ProtocolType {
+        unsafe { c::packet_getProtocol(self.c_ptr.ptr()) }
+    }
+
     pub fn priority(&self) -> FifoPacketPriority {
         unsafe { c::packet_getPriority(self.c_ptr.ptr()) }
     }
 
+    /// Set whether routers must drop the packet instead of fragmenting it if it's larger than the
+    /// MTU of a link on its path (the IPv4 "don't fragment" flag).
+    pub fn set_dont_fragment(&mut self, dont_fragment: bool) {
+        unsafe { c::packet_setDontFragment(self.c_ptr.ptr(), dont_fragment) };
+    }
+
+    pub fn dont_fragment(&self) -> bool {
+        unsafe { c::packet_getDontFragment(self.c_ptr.ptr()) }
+    }
+
+    /// Set the TTL of the packet's IPv4 header.
+    pub fn set_ttl(&mut self, ttl: u8) {
+        unsafe { c::packet_setTTL(self.c_ptr.ptr(), ttl) };
+    }
+
+    /// The TTL of the packet's IPv4 header. Each router on the packet's path decrements it, and
+    /// drops the packet if it expires.
+    pub fn ttl(&self) -> u8 {
+        unsafe { c::packet_getTTL(self.c_ptr.ptr()) }
+    }
+
+    /// If the packet was sent by an ECN-capable transport, set its ECN codepoint to "congestion
+    /// experienced" (RFC 3168) and return true. Otherwise returns false and leaves the packet
+    /// unchanged.
+    pub fn mark_congestion_experienced(&mut self) -> bool {
+        let ecn = unsafe { c::packet_getECN(self.c_ptr.ptr()) };
+        if ecn == c::_PacketECN_PECN_NOT_ECT {
+            return false;
+        }
+        unsafe { c::packet_setECN(self.c_ptr.ptr(), c::_PacketECN_PECN_CE) };
+        true
+    }
+
+    /// The IPv4 header and first 8 bytes of the transport header of this packet, as included in
+    /// the payload of an ICMP error message about this packet.
+    pub fn icmp_error_quote(&self) -> Vec<u8> {
+        let src = self.src_address();
+        let dst = self.dst_address();
+
+        let protocol = unsafe { c::packet_getProtocol(self.c_ptr.ptr()) };
+        let (protocol, transport) = match protocol {
+            c::_ProtocolType_PUDP => {
+                let len = u16::try_from(self.total_size() - IPV4_HEADER_LEN).unwrap();
+                let mut header = [0; 8];
+                header[0..2].copy_from_slice(&src.port().to_be_bytes());
+                header[2..4].copy_from_slice(&dst.port().to_be_bytes());
+                header[4..6].copy_from_slice(&len.to_be_bytes());
+                (libc::IPPROTO_UDP, header)
+            }
+            c::_ProtocolType_PTCP => {
+                let tcp = self.get_tcp().unwrap();
+                let mut header = [0; 8];
+                header[0..2].copy_from_slice(&src.port().to_be_bytes());
+                header[2..4].copy_from_slice(&dst.port().to_be_bytes());
+                header[4..8].copy_from_slice(&tcp.seq.to_be_bytes());
+                (libc::IPPROTO_TCP, header)
+            }
+            c::_ProtocolType_PICMP => {
+                let icmp = self.get_icmp().unwrap();
+                (libc::IPPROTO_ICMP, icmp.to_bytes(&[]))
+            }
+            x => panic!("Unexpected packet protocol {x}"),
+        };
+
+        let mut quote = ipv4_header(
+            *src.ip(),
+            *dst.ip(),
+            self.ttl(),
+            protocol.try_into().unwrap(),
+            self.total_size() - IPV4_HEADER_LEN,
+        )
+        .to_vec();
+        quote.extend_from_slice(&transport);
+        quote
+    }
+
+    /// The bytes of the packet as an IPv4 packet, with valid checksums. This is the packet as a
+    /// process would read it from a TUN interface.
+    pub fn to_ipv4_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.total_size());
+        self.display_bytes(&mut bytes).unwrap();
+
+        // the pcap format doesn't need valid checksums, so `display_bytes()` leaves them as 0, and
+        // the C packet's header size doesn't include the TCP options
+        let total_len = u16::try_from(bytes.len()).unwrap();
+        bytes[2..4].copy_from_slice(&total_len.to_be_bytes());
+        if !self.dont_fragment() {
+            bytes[6] &= !0x40;
+        }
+        let checksum = internet_checksum([&bytes[..IPV4_HEADER_LEN]]);
+        bytes[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        let (header, transport) = bytes.split_at_mut(IPV4_HEADER_LEN);
+        let checksum_offset = match header[9] {
+            IPPROTO_TCP => 16,
+            IPPROTO_UDP => 6,
+            _ => 2,
+        };
+
+        // TCP and UDP checksums include a pseudo-header with the addresses, protocol, and length
+        let transport_len = u16::try_from(transport.len()).unwrap().to_be_bytes();
+        let pseudo_header = [&header[12..20], &[0, header[9]][..], &transport_len[..]].concat();
+        let checksum = match header[9] {
+            IPPROTO_ICMP => internet_checksum([&transport[..]]),
+            // a UDP checksum of 0 means "no checksum"
+            IPPROTO_UDP => match internet_checksum([&pseudo_header[..], &transport[..]]) {
+                0 => 0xffff,
+                x => x,
+            },
+            _ => internet_checksum([&pseudo_header[..], &transport[..]]),
+        };
+        transport[checksum_offset..][..2].copy_from_slice(&checksum.to_be_bytes());
+
+        bytes
+    }
+
+    /// Build a packet from the bytes of an IPv4 packet, such as a packet that a process wrote to a
+    /// TUN interface. Checksums and IPv4 options are ignored. Returns `None` if the packet isn't a
+    /// well-formed and unfragmented TCP, UDP, or ICMP packet.
+    pub fn from_ipv4_bytes(bytes: &[u8]) -> Option<Self> {
+        Self::parse_ipv4_bytes(bytes, Self::new)
+    }
+
+    /// Build a packet from the bytes of an IPv4 packet that was sent by the host `host_id` of
+    /// another manager in a distributed simulation. Unlike [`Self::from_ipv4_bytes`], this doesn't
+    /// require an active host.
+    pub fn from_remote_ipv4_bytes(bytes: &[u8], host_id: HostId, packet_id: u64) -> Option<Self> {
+        Self::parse_ipv4_bytes(bytes, || {
+            Self::from_raw(unsafe { c::packet_new_inner(host_id.into(), packet_id) })
+        })
+    }
+
+    fn parse_ipv4_bytes(bytes: &[u8], new_packet: impl FnOnce() -> Self) -> Option<Self> {
+        let header_len = usize::from(bytes.first()? & 0xf) * 4;
+        if bytes[0] >> 4 != 4 || header_len < IPV4_HEADER_LEN {
+            return None;
+        }
+
+        let header = bytes.get(..header_len)?;
+        let total_len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        let transport = bytes.get(header_len..total_len)?;
+
+        // the "more fragments" flag and the fragment offset
+        if u16::from_be_bytes([header[6], header[7]]) & 0x3fff != 0 {
+            return None;
+        }
+
+        let dont_fragment = header[6] & 0x40 != 0;
+        let src = Ipv4Addr::new(header[12], header[13], header[14], header[15]);
+        let dst = Ipv4Addr::new(header[16], header[17], header[18], header[19]);
+
+        let mut packet = new_packet();
+
+        let payload = match header[9] {
+            IPPROTO_TCP => {
+                let (tcp, payload) = parse_tcp_header(src, dst, transport)?;
+                packet.set_tcp(&tcp);
+                payload
+            }
+            IPPROTO_UDP => {
+                let udp = transport.get(..8)?;
+                let src_port = u16::from_be_bytes([udp[0], udp[1]]);
+                let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+                let udp_len = usize::from(u16::from_be_bytes([udp[4], udp[5]]));
+                packet.set_udp(
+                    SocketAddrV4::new(src, src_port),
+                    SocketAddrV4::new(dst, dst_port),
+                );
+                transport.get(8..udp_len)?
+            }
+            IPPROTO_ICMP => {
+                let icmp = IcmpHeader::from_bytes(transport)?;
+                packet.set_icmp(src, dst, &icmp);
+                &transport[IcmpHeader::LEN..]
+            }
+            _ => return None,
+        };
+
+        if !payload.is_empty() {
+            packet.set_payload(payload, 0);
+        }
+        packet.set_dont_fragment(dont_fragment);
+        packet.set_ttl(header[8]);
+        packet.add_status(PacketStatus::SndCreated);
+
+        Some(packet)
+    }
+
     /// Transfers ownership of the given c_ptr reference into a new rust packet
     /// object.
     pub fn from_raw(c_ptr: *mut c::Packet) -> Self {
@@ -386,14 +770,16 @@ impl PacketDisplay for *const c::Packet {
         // write the IP header
 
         let version_and_header_length: u8 = 0x45;
-        let fields: u8 = 0x0;
+        // DSCP is always 0, so this is just the ECN codepoint
+        let fields: u8 = unsafe { c::packet_getECN(*self) }.try_into().unwrap();
         let total_length: u16 = header_len + payload_len;
         let identification: u16 = 0x0;
         let flags_and_fragment: u16 = 0x4000;
-        let time_to_live: u8 = 64;
+        let time_to_live: u8 = unsafe { c::packet_getTTL(*self) };
         let iana_protocol: u8 = match protocol {
             c::_ProtocolType_PTCP => 6,
             c::_ProtocolType_PUDP => 17,
+            c::_ProtocolType_PICMP => 1,
             _ => panic!("Unexpected packet protocol"),
         };
         let header_checksum: u16 = 0x0;
@@ -426,6 +812,7 @@ impl PacketDisplay for *const c::Packet {
         match protocol {
             c::_ProtocolType_PTCP => display_tcp_bytes(*self, &mut writer)?,
             c::_ProtocolType_PUDP => display_udp_bytes(*self, &mut writer)?,
+            c::_ProtocolType_PICMP => display_icmp_bytes(*self, &mut writer)?,
             _ => panic!("Unexpected packet protocol"),
         }
 
@@ -456,6 +843,74 @@ impl PacketDisplay for *const c::Packet {
     }
 }
 
+/// Parse a TCP header and its options, and return the header and the TCP payload. Shadow's TCP
+/// implementation only models the FIN, SYN, RST, and ACK flags, so other flags are ignored.
+fn parse_tcp_header(src: Ipv4Addr, dst: Ipv4Addr, bytes: &[u8]) -> Option<(tcp::TcpHeader, &[u8])> {
+    let header_len = usize::from(bytes.get(12)? >> 4) * 4;
+    let header = bytes.get(..header_len)?;
+    if header_len < c::CONFIG_HEADER_SIZE_TCP as usize {
+        return None;
+    }
+
+    let flags = tcp::TcpFlags::from_bits_truncate(header[13])
+        & (tcp::TcpFlags::FIN | tcp::TcpFlags::SYN | tcp::TcpFlags::RST | tcp::TcpFlags::ACK);
+
+    let mut tcp = tcp::TcpHeader {
+        ip: tcp::Ipv4Header { src, dst },
+        flags,
+        src_port: u16::from_be_bytes([header[0], header[1]]),
+        dst_port: u16::from_be_bytes([header[2], header[3]]),
+        seq: u32::from_be_bytes(header[4..8].try_into().unwrap()),
+        ack: u32::from_be_bytes(header[8..12].try_into().unwrap()),
+        window_size: u16::from_be_bytes([header[14], header[15]]),
+        selective_acks: None,
+        window_scale: None,
+        timestamp: None,
+        timestamp_echo: None,
+    };
+
+    let mut options = &header[20..];
+    while let Some(&kind) = options.first() {
+        // the "end of option list" and "no-operation" options don't have a length
+        match kind {
+            0 => break,
+            1 => {
+                options = &options[1..];
+                continue;
+            }
+            _ => {}
+        }
+
+        let len = usize::from(*options.get(1)?);
+        let data = options.get(2..len)?;
+
+        match (kind, data.len()) {
+            (3, 1) => tcp.window_scale = Some(data[0]),
+            (5, _) => {
+                let sacks: Vec<_> = data
+                    .chunks_exact(8)
+                    .map(|x| {
+                        let begin = u32::from_be_bytes(x[..4].try_into().unwrap());
+                        let end = u32::from_be_bytes(x[4..].try_into().unwrap());
+                        (begin, end)
+                    })
+                    .collect();
+                tcp.selective_acks = tcp::util::SmallArrayBackedSlice::new(&sacks);
+            }
+            (8, 8) => {
+                tcp.timestamp = Some(u32::from_be_bytes(data[..4].try_into().unwrap()));
+                tcp.timestamp_echo = Some(u32::from_be_bytes(data[4..].try_into().unwrap()));
+            }
+            // other options such as the maximum segment size aren't modelled
+            _ => {}
+        }
+
+        options = &options[len..];
+    }
+
+    Some((tcp, &bytes[header_len..]))
+}
+
 /// Helper for writing the tcp bytes of the packet.
 fn display_tcp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::io::Result<()> {
     assert_eq!(
@@ -485,6 +940,23 @@ fn display_tcp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::i
         options_len += 3;
     }
 
+    if tcp_header.flags & c::ProtocolTCPFlags_PTCP_SACK_PERMITTED != 0 {
+        // option-kind = 4, option-len = 2
+        options[options_len..][..2].copy_from_slice(&[4, 2]);
+        options_len += 2;
+    }
+
+    if tcp_header.fastOpenCookieSet {
+        // option-kind = 34, option-len = 10, option-data = cookie
+        options[options_len..][..2].copy_from_slice(&[34, 10]);
+        options[options_len..][2..10].copy_from_slice(&tcp_header.fastOpenCookie.to_be_bytes());
+        options_len += 10;
+    } else if tcp_header.fastOpenSet {
+        // option-kind = 34, option-len = 2 (a cookie request)
+        options[options_len..][..2].copy_from_slice(&[34, 2]);
+        options_len += 2;
+    }
+
     if options_len % 4 != 0 {
         // need to add padding (our options array was already initialized with zeroes)
         let padding = 4 - (options_len % 4);
@@ -527,9 +999,22 @@ fn display_tcp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::i
     if tcp_header.flags & c::ProtocolTCPFlags_PTCP_FIN != 0 {
         tcp_flags |= 0x01;
     }
+    if tcp_header.flags & c::ProtocolTCPFlags_PTCP_URG != 0 {
+        tcp_flags |= 0x20;
+    }
+    if tcp_header.flags & c::ProtocolTCPFlags_PTCP_ECE != 0 {
+        tcp_flags |= 0x40;
+    }
+    if tcp_header.flags & c::ProtocolTCPFlags_PTCP_CWR != 0 {
+        tcp_flags |= 0x80;
+    }
     let window: [u8; 2] = u16::try_from(tcp_header.window).unwrap().to_be_bytes();
     let checksum: u16 = 0x0;
-    let urgent_pointer: u16 = 0x0;
+    let urgent_pointer: u16 = if tcp_header.flags & c::ProtocolTCPFlags_PTCP_URG != 0 {
+        tcp_header.urgentPointer
+    } else {
+        0x0
+    };
 
     // source port: 2 bytes
     writer.write_all(&source_port)?;
@@ -546,7 +1031,7 @@ fn display_tcp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::i
     writer.write_all(&window)?;
     // checksum: 2 bytes
     writer.write_all(&checksum.to_be_bytes())?;
-
+    // urgent pointer: 2 bytes
     writer.write_all(&urgent_pointer.to_be_bytes())?;
 
     writer.write_all(options)?;
@@ -585,6 +1070,34 @@ fn display_udp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::i
     Ok(())
 }
 
+/// Helper for writing the icmp bytes of the packet.
+fn display_icmp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::io::Result<()> {
+    assert_eq!(
+        unsafe { c::packet_getProtocol(packet) },
+        c::_ProtocolType_PICMP
+    );
+
+    let icmp_header = unsafe { c::packet_getICMPHeader(packet) };
+    assert!(!icmp_header.is_null());
+    let icmp_header = unsafe { icmp_header.as_ref() }.unwrap();
+
+    // write the ICMP header
+
+    let checksum: u16 = 0x0;
+
+    // type: 1 byte
+    // code: 1 byte
+    writer.write_all(&[icmp_header.type_, icmp_header.code])?;
+    // checksum: 2 bytes
+    writer.write_all(&checksum.to_be_bytes())?;
+    // identifier: 2 bytes
+    writer.write_all(&icmp_header.identifier.to_be_bytes())?;
+    // sequence number: 2 bytes
+    writer.write_all(&icmp_header.sequence.to_be_bytes())?;
+
+    Ok(())
+}
+
 pub fn to_legacy_tcp_flags(flags: tcp::TcpFlags) -> c::ProtocolTCPFlags {
     let mut new_flags = c::ProtocolTCPFlags_PTCP_NONE;
 
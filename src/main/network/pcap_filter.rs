@@ -0,0 +1,391 @@
+//! Capture filters for pcap files, which are a small subset of the pcap-filter(7) language used by
+//! tcpdump.
+//!
+//! A filter is made of primitives like `tcp`, `src host 10.0.0.1`, `net 10.0.0.0/8`, `dst port 80`,
+//! and `udp portrange 5000-6000`, which can be combined with `and` (`&&`), `or` (`||`), `not`
+//! (`!`), and parentheses. Like pcap-filter, `not` has the highest precedence, and `and` and `or`
+//! have the same precedence and are evaluated from left to right.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::network::nat::Subnet;
+
+/// A transport protocol of a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+/// The fields of a packet that a filter can match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterPacket {
+    /// The transport protocol, or `None` if the packet isn't a TCP, UDP, or ICMP packet.
+    pub protocol: Option<FilterProtocol>,
+    pub src: SocketAddrV4,
+    pub dst: SocketAddrV4,
+}
+
+/// A parsed capture filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapFilter {
+    expr: Expr,
+}
+
+impl PcapFilter {
+    /// Returns true if the packet matches the filter.
+    pub fn matches(&self, packet: &FilterPacket) -> bool {
+        self.expr.matches(packet)
+    }
+}
+
+impl std::str::FromStr for PcapFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s);
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+
+        let expr = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected '{token}'"));
+        }
+
+        Ok(Self { expr })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Primitive(Primitive),
+}
+
+impl Expr {
+    fn matches(&self, packet: &FilterPacket) -> bool {
+        match self {
+            Self::Not(x) => !x.matches(packet),
+            Self::And(x, y) => x.matches(packet) && y.matches(packet),
+            Self::Or(x, y) => x.matches(packet) || y.matches(packet),
+            Self::Primitive(x) => x.matches(packet),
+        }
+    }
+}
+
+/// Which of the packet's addresses a primitive applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Src,
+    Dst,
+    SrcOrDst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Id {
+    /// Only the protocol qualifier (or `ip`, which matches all packets).
+    None,
+    Host(Ipv4Addr),
+    Net(Subnet),
+    PortRange(u16, u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Primitive {
+    protocol: Option<FilterProtocol>,
+    direction: Direction,
+    id: Id,
+}
+
+impl Primitive {
+    fn matches(&self, packet: &FilterPacket) -> bool {
+        if self.protocol.is_some() && self.protocol != packet.protocol {
+            return false;
+        }
+
+        let matches_addr = |f: &dyn Fn(SocketAddrV4) -> bool| match self.direction {
+            Direction::Src => f(packet.src),
+            Direction::Dst => f(packet.dst),
+            Direction::SrcOrDst => f(packet.src) || f(packet.dst),
+        };
+
+        match self.id {
+            Id::None => true,
+            Id::Host(ip) => matches_addr(&|x| *x.ip() == ip),
+            Id::Net(subnet) => matches_addr(&|x| subnet.contains(*x.ip())),
+            Id::PortRange(lo, hi) => {
+                // only TCP and UDP packets have ports
+                matches!(
+                    packet.protocol,
+                    Some(FilterProtocol::Tcp | FilterProtocol::Udp)
+                ) && matches_addr(&|x| (lo..=hi).contains(&x.port()))
+            }
+        }
+    }
+}
+
+/// Split the filter into words, parentheses, and the `!`, `&&`, and `||` operators.
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let op = match c {
+            '(' | ')' | '!' => Some(c.to_string()),
+            '&' | '|' if chars.peek() == Some(&c) => {
+                chars.next();
+                Some(format!("{c}{c}"))
+            }
+            _ => None,
+        };
+
+        if op.is_some() || c.is_whitespace() {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+        } else {
+            word.push(c);
+        }
+
+        tokens.extend(op);
+    }
+
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Consume the next token if it's one of `options`.
+    fn next_if(&mut self, options: &[&str]) -> Option<&'a str> {
+        let token = self.peek().filter(|x| options.contains(x));
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+
+        while let Some(op) = self.next_if(&["and", "&&", "or", "||"]) {
+            let rhs = Box::new(self.unary()?);
+            expr = match op {
+                "and" | "&&" => Expr::And(Box::new(expr), rhs),
+                _ => Expr::Or(Box::new(expr), rhs),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.next_if(&["not", "!"]).is_some() {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        if self.next_if(&["("]).is_some() {
+            let expr = self.expr()?;
+            if self.next_if(&[")"]).is_none() {
+                return Err("Expected ')'".to_string());
+            }
+            return Ok(expr);
+        }
+
+        self.primitive().map(Expr::Primitive)
+    }
+
+    fn primitive(&mut self) -> Result<Primitive, String> {
+        let protocol = match self.next_if(&["ip", "tcp", "udp", "icmp"]) {
+            Some("tcp") => Some(FilterProtocol::Tcp),
+            Some("udp") => Some(FilterProtocol::Udp),
+            Some("icmp") => Some(FilterProtocol::Icmp),
+            Some(_) => {
+                // only IPv4 packets are captured, so 'ip' matches all packets
+                return Ok(Primitive {
+                    protocol: None,
+                    direction: Direction::SrcOrDst,
+                    id: Id::None,
+                });
+            }
+            None => None,
+        };
+
+        let direction = match self.next_if(&["src", "dst"]) {
+            Some("src") => Some(Direction::Src),
+            Some(_) => Some(Direction::Dst),
+            None => None,
+        };
+
+        let Some(kind) = self.next_if(&["host", "net", "port", "portrange"]) else {
+            if protocol.is_some() && direction.is_none() {
+                return Ok(Primitive {
+                    protocol,
+                    direction: Direction::SrcOrDst,
+                    id: Id::None,
+                });
+            }
+            return Err(match self.peek() {
+                Some(token) => format!("Expected a primitive, but found '{token}'"),
+                None => "Expected a primitive".to_string(),
+            });
+        };
+
+        let Some(value) = self.next() else {
+            return Err(format!("Expected a value after '{kind}'"));
+        };
+
+        let id = match kind {
+            "host" => Id::Host(
+                value
+                    .parse()
+                    .map_err(|e| format!("Invalid host '{value}': {e}"))?,
+            ),
+            "net" => {
+                let subnet: Subnet = value.parse()?;
+                if !subnet.is_valid() {
+                    return Err(format!(
+                        "Net '{value}' has address bits set outside of its prefix"
+                    ));
+                }
+                Id::Net(subnet)
+            }
+            "port" => {
+                let port = parse_port(value)?;
+                Id::PortRange(port, port)
+            }
+            _ => {
+                let Some((lo, hi)) = value.split_once('-') else {
+                    return Err(format!("Invalid port range '{value}'"));
+                };
+                let (lo, hi) = (parse_port(lo)?, parse_port(hi)?);
+                if lo > hi {
+                    return Err(format!("Invalid port range '{value}'"));
+                }
+                Id::PortRange(lo, hi)
+            }
+        };
+
+        Ok(Primitive {
+            protocol,
+            direction: direction.unwrap_or(Direction::SrcOrDst),
+            id,
+        })
+    }
+}
+
+fn parse_port(s: &str) -> Result<u16, String> {
+    s.parse().map_err(|e| format!("Invalid port '{s}': {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(protocol: FilterProtocol, src: &str, dst: &str) -> FilterPacket {
+        FilterPacket {
+            protocol: Some(protocol),
+            src: src.parse().unwrap(),
+            dst: dst.parse().unwrap(),
+        }
+    }
+
+    fn matches(filter: &str, packet: &FilterPacket) -> bool {
+        filter.parse::<PcapFilter>().unwrap().matches(packet)
+    }
+
+    #[test]
+    fn test_primitives() {
+        let tcp = packet(FilterProtocol::Tcp, "10.0.0.1:1234", "11.0.0.2:80");
+        let udp = packet(FilterProtocol::Udp, "10.0.0.1:1234", "11.0.0.2:53");
+        let icmp = packet(FilterProtocol::Icmp, "10.0.0.1:0", "11.0.0.2:0");
+
+        assert!(matches("ip", &tcp));
+        assert!(matches("tcp", &tcp));
+        assert!(!matches("tcp", &udp));
+        assert!(matches("icmp", &icmp));
+
+        assert!(matches("host 10.0.0.1", &tcp));
+        assert!(matches("host 11.0.0.2", &tcp));
+        assert!(matches("src host 10.0.0.1", &tcp));
+        assert!(!matches("dst host 10.0.0.1", &tcp));
+        assert!(!matches("host 10.0.0.2", &tcp));
+
+        assert!(matches("net 10.0.0.0/8", &tcp));
+        assert!(matches("dst net 11.0.0.0/24", &tcp));
+        assert!(!matches("src net 11.0.0.0/8", &tcp));
+
+        assert!(matches("port 80", &tcp));
+        assert!(matches("tcp dst port 80", &tcp));
+        assert!(!matches("udp port 80", &tcp));
+        assert!(!matches("src port 80", &tcp));
+        assert!(matches("udp portrange 50-60", &udp));
+        assert!(!matches("portrange 81-1233", &tcp));
+        // ICMP packets don't have ports
+        assert!(!matches("port 0", &icmp));
+    }
+
+    #[test]
+    fn test_operators() {
+        let tcp = packet(FilterProtocol::Tcp, "10.0.0.1:1234", "11.0.0.2:80");
+
+        assert!(matches("tcp and port 80", &tcp));
+        assert!(matches("tcp && port 80", &tcp));
+        assert!(!matches("tcp and not port 80", &tcp));
+        assert!(matches("udp or port 80", &tcp));
+        assert!(matches("!udp||icmp", &tcp));
+        assert!(matches("not (udp or icmp)", &tcp));
+
+        // 'and' and 'or' have the same precedence and are evaluated from left to right
+        assert!(!matches("tcp or udp and icmp", &tcp));
+        assert!(matches("tcp or (udp and icmp)", &tcp));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for filter in [
+            "",
+            "tcp and",
+            "(tcp",
+            "tcp)",
+            "src tcp",
+            "src",
+            "host",
+            "host example.com",
+            "net 10.0.0.1/8",
+            "net 10.0.0.0/33",
+            "port 65536",
+            "portrange 10",
+            "portrange 20-10",
+            "tcp port 80 443",
+            "ether host 10.0.0.1",
+        ] {
+            assert!(filter.parse::<PcapFilter>().is_err(), "{filter}");
+        }
+    }
+}
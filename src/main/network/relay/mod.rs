@@ -56,6 +56,8 @@ pub struct Relay {
 struct RelayInternal {
     _counter: ObjectCounter,
     rate_limiter: Option<TokenBucket>,
+    /// Limits the rate of bursts allowed by `rate_limiter`.
+    peak_rate_limiter: Option<TokenBucket>,
     src_dev_address: Ipv4Addr,
     state: RelayState,
     next_packet: Option<PacketRc>,
@@ -78,6 +80,14 @@ enum RelayState {
 /// Specifies a throughput limit the relay should enforce when forwarding packets.
 pub enum RateLimit {
     BytesPerSecond(u64),
+    /// Like `BytesPerSecond`, but allows bursts of up to `burst_bytes` above
+    /// the sustained rate. If set, bursts are forwarded no faster than
+    /// `peak_bytes_per_second`.
+    Burst {
+        bytes_per_second: u64,
+        burst_bytes: u64,
+        peak_bytes_per_second: Option<u64>,
+    },
     Unlimited,
 }
 
@@ -88,15 +98,13 @@ impl Relay {
     /// internally schedules tasks as needed to ensure packets continue to be
     /// forwarded over time without exceeding the configured `RateLimit`.
     pub fn new(rate: RateLimit, src_dev_address: Ipv4Addr) -> Self {
-        let rate_limiter = match rate {
-            RateLimit::BytesPerSecond(bytes) => Some(create_token_bucket(bytes)),
-            RateLimit::Unlimited => None,
-        };
+        let (rate_limiter, peak_rate_limiter) = create_token_buckets(rate);
 
         Self {
             internal: AtomicRefCell::new(RelayInternal {
                 _counter: ObjectCounter::new("Relay"),
                 rate_limiter,
+                peak_rate_limiter,
                 src_dev_address,
                 state: RelayState::Idle,
                 next_packet: None,
@@ -104,6 +112,16 @@ impl Relay {
         }
     }
 
+    /// Replace the `RateLimit` that the relay enforces, such as when a trace changes the
+    /// bandwidth of the host. Packets forwarded from now on follow the new `RateLimit`.
+    pub fn set_rate_limit(&self, rate: RateLimit) {
+        let (rate_limiter, peak_rate_limiter) = create_token_buckets(rate);
+
+        let mut internal = self.internal.borrow_mut();
+        internal.rate_limiter = rate_limiter;
+        internal.peak_rate_limiter = peak_rate_limiter;
+    }
+
     /// Notify the relay that its packet source now has packets available for
     /// relaying to the packet sink. This must be called when the source changes
     /// state from empty to non-empty to signal the relay to resume forwarding.
@@ -228,6 +246,33 @@ impl Relay {
             // limits do not apply during bootstrapping, or if the source and
             // destination are the same device.
             if !is_bootstrapping && !is_local {
+                // The packet must conform to the peak rate before we remove
+                // any tokens for the sustained rate, so that a packet blocked
+                // by the peak rate doesn't use the sustained rate's tokens.
+                if let Some(tb) = internal.peak_rate_limiter.as_mut() {
+                    let blocking_dur = tb.conforming_duration(packet.total_size() as u64);
+                    if !blocking_dur.is_zero() {
+                        // Too few tokens, need to block.
+                        log::trace!(
+                            "Relay src={} dst={} exceeded peak rate limit for packet of size {}, \
+                            blocking for {:?}",
+                            src.get_address(),
+                            packet.dst_address().ip(),
+                            packet.total_size(),
+                            blocking_dur
+                        );
+
+                        // Cache the packet until we can forward it later.
+                        packet.add_status(PacketStatus::RelayCached);
+                        assert!(internal.next_packet.is_none());
+                        internal.next_packet = Some(packet);
+                        internal.state = RelayState::Idle;
+
+                        // Call Relay::forward_later() after dropping the mutable borrow.
+                        return Some(blocking_dur);
+                    }
+                }
+
                 // Rate limit applies only if we have a token bucket.
                 if let Some(tb) = internal.rate_limiter.as_mut() {
                     // Try to remove tokens for this packet.
@@ -255,6 +300,12 @@ impl Relay {
                         return Some(blocking_dur);
                     }
                 }
+
+                // The packet conforms to both rates, so also remove its tokens
+                // for the peak rate.
+                if let Some(tb) = internal.peak_rate_limiter.as_mut() {
+                    tb.comforming_remove(packet.total_size() as u64).unwrap();
+                }
             }
 
             // Forward the packet to the destination device now.
@@ -265,23 +316,42 @@ impl Relay {
                 src.push(packet);
             } else {
                 // The source and destination are different.
-                let dst = host.get_packet_device(*packet.dst_address().ip());
+                let dst =
+                    host.get_next_packet_device(src.get_address(), *packet.dst_address().ip());
                 dst.push(packet);
             }
         }
     }
 }
 
+/// Configures the token buckets for the sustained and peak rates of the given
+/// `RateLimit`.
+fn create_token_buckets(rate: RateLimit) -> (Option<TokenBucket>, Option<TokenBucket>) {
+    match rate {
+        RateLimit::BytesPerSecond(bytes) => (Some(create_token_bucket(bytes, 0)), None),
+        RateLimit::Burst {
+            bytes_per_second,
+            burst_bytes,
+            peak_bytes_per_second,
+        } => (
+            Some(create_token_bucket(bytes_per_second, burst_bytes)),
+            peak_bytes_per_second.map(|bytes| create_token_bucket(bytes, 0)),
+        ),
+        RateLimit::Unlimited => (None, None),
+    }
+}
+
 /// Configures a token bucket according the the given bytes_per_second rate
-/// limit. We always refill at least 1 byte per millisecond.
-fn create_token_bucket(bytes_per_second: u64) -> TokenBucket {
+/// limit, which allows bursts of `burst_bytes` above the rate limit. We always
+/// refill at least 1 byte per millisecond.
+fn create_token_bucket(bytes_per_second: u64, burst_bytes: u64) -> TokenBucket {
     let refill_interval = SimulationTime::from_millis(1);
     let refill_size = std::cmp::max(1, bytes_per_second / 1000);
 
     // Only the `capacity` of the bucket is increased by the burst allowance,
     // not the `refill_size`. Therefore, the long term rate limit enforced by
     // the token bucket (configured by `refill_size`) is not affected much.
-    let capacity = refill_size + get_burst_allowance();
+    let capacity = refill_size + get_burst_allowance() + burst_bytes;
 
     TokenBucket::new(capacity, refill_size, refill_interval).unwrap()
 }
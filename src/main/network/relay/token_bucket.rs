@@ -85,6 +85,21 @@ impl TokenBucket {
         Ok(self.balance)
     }
 
+    /// Returns the duration until the bucket would contain at least `decrement`
+    /// tokens, without removing any tokens. Returns a zero duration if a
+    /// `comforming_remove()` of `decrement` tokens would currently succeed.
+    pub fn conforming_duration(&mut self, decrement: u64) -> SimulationTime {
+        let now = Worker::current_time().unwrap();
+        self.conforming_duration_inner(decrement, &now)
+    }
+
+    /// Implements the functionality of `conforming_duration()` without calling
+    /// into the `Worker` module. Useful for testing.
+    fn conforming_duration_inner(&mut self, decrement: u64, now: &EmulatedTime) -> SimulationTime {
+        let next_refill_span = self.lazy_refill(now);
+        self.compute_conforming_duration(decrement, next_refill_span)
+    }
+
     /// Computes the duration required to refill enough tokens such that our
     /// balance can be decremented by the given `decrement`. Returned durations
     /// always align with this `TokenBucket`'s discrete refill interval
@@ -183,6 +198,29 @@ mod tests {
         assert_eq!(tb.refill_interval, SimulationTime::from_secs(1));
     }
 
+    #[test]
+    fn test_conforming_duration() {
+        let interval = SimulationTime::from_millis(10);
+        let capacity = 100;
+        let increment = 10;
+        let now = mock_time_millis(1000);
+
+        let mut tb = TokenBucket::new_inner(capacity, increment, interval, now).unwrap();
+
+        // Checking doesn't remove tokens
+        assert_eq!(
+            tb.conforming_duration_inner(capacity, &now),
+            SimulationTime::ZERO
+        );
+        assert_eq!(tb.balance, capacity);
+
+        assert!(tb.conforming_remove_inner(capacity - 5, &now).is_ok());
+        assert_eq!(tb.conforming_duration_inner(5, &now), SimulationTime::ZERO);
+        assert_eq!(tb.conforming_duration_inner(6, &now), interval);
+        assert_eq!(tb.conforming_duration_inner(16, &now), interval * 2);
+        assert_eq!(tb.balance, 5);
+    }
+
     #[test]
     fn test_refill_after_one_interval() {
         let interval = SimulationTime::from_millis(10);
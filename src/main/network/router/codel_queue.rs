@@ -1,6 +1,11 @@
 //! An active queue management (AQM) algorithm implementing CoDel.
 //! <https://tools.ietf.org/html/rfc8289>
 //!
+//!  If ECN marking is enabled, packets from ECN-capable transports are marked
+//!  as having experienced congestion instead of being dropped, like the `ecn`
+//!  option of the Linux codel qdisc.
+//!  <https://tools.ietf.org/html/rfc3168>
+//!
 //!  The "Flow Queue" variant is not implemented.
 //!  <https://tools.ietf.org/html/rfc8290>
 //!
@@ -74,10 +79,13 @@ pub struct CoDelQueue {
     interval_end: Option<EmulatedTime>,
     /// If Some, the next time we should drop a packet.
     drop_next: Option<EmulatedTime>,
-    /// The number of packets dropped since entering drop mode.
+    /// The number of packets dropped (or marked) since entering drop mode.
     current_drop_count: usize,
-    /// The number of packets dropped the last time we were in drop mode.
+    /// The number of packets dropped (or marked) the last time we were in drop
+    /// mode.
     previous_drop_count: usize,
+    /// If true, we mark ECN-capable packets instead of dropping them.
+    ecn_marking: bool,
 }
 
 impl CoDelQueue {
@@ -91,17 +99,22 @@ impl CoDelQueue {
             drop_next: None,
             current_drop_count: 0,
             previous_drop_count: 0,
+            ecn_marking: false,
         }
     }
 
+    /// Set whether packets from ECN-capable transports should be marked as
+    /// having experienced congestion rather than dropped.
+    pub fn set_ecn_marking(&mut self, enabled: bool) {
+        self.ecn_marking = enabled;
+    }
+
     /// Returns the total number of packets stored in the queue.
-    #[cfg(test)]
     pub fn len(&self) -> usize {
         self.elements.len()
     }
 
     /// Returns true if the queue is holding zero packets, false otherwise.
-    #[cfg(test)]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -151,9 +164,14 @@ impl CoDelQueue {
     fn drop_from_store_mode(&mut self, now: &EmulatedTime, packet: PacketRc) -> Option<PacketRc> {
         debug_assert_eq!(self.mode, CoDelMode::Store);
 
-        // Drop one packet and move to drop mode.
-        self.drop_packet(packet);
-        let next_item = self.codel_pop(now);
+        // Drop (or mark) one packet and move to drop mode.
+        let next_packet = match self.mark_packet(packet) {
+            Ok(packet) => Some(packet),
+            Err(packet) => {
+                self.drop_packet(packet);
+                self.codel_pop(now).map(|x| x.packet)
+            }
+        };
         self.mode = CoDelMode::Drop;
 
         // Reset to the drop rate that was known to control the queue.
@@ -167,7 +185,7 @@ impl CoDelQueue {
         self.drop_next = Some(CoDelQueue::apply_control_law(now, self.current_drop_count));
         self.previous_drop_count = self.current_drop_count;
 
-        next_item.map(|x| x.packet)
+        next_packet
     }
 
     fn drop_from_drop_mode(&mut self, now: &EmulatedTime, packet: PacketRc) -> Option<PacketRc> {
@@ -180,9 +198,22 @@ impl CoDelQueue {
 
         // Drop as many packets as the control law dictates.
         while item.is_some() && self.mode == CoDelMode::Drop && self.should_drop(now) {
-            self.drop_packet(item.unwrap().packet);
             self.current_drop_count += 1;
 
+            let packet = match self.mark_packet(item.unwrap().packet) {
+                Ok(packet) => {
+                    // A marked packet is forwarded, so we're done until the
+                    // next drop time.
+                    self.drop_next = Some(CoDelQueue::apply_control_law(
+                        &self.drop_next.unwrap(),
+                        self.current_drop_count,
+                    ));
+                    return Some(packet);
+                }
+                Err(packet) => packet,
+            };
+            self.drop_packet(packet);
+
             item = self.codel_pop(now);
 
             match item.as_ref().map_or(false, |x| x.ok_to_drop) {
@@ -321,6 +352,18 @@ impl CoDelQueue {
     fn drop_packet(&self, mut packet: PacketRc) {
         packet.add_status(PacketStatus::RouterDropped);
     }
+
+    /// Marks the packet as having experienced congestion if ECN marking is
+    /// enabled and the packet is ECN-capable. Otherwise the unmodified packet
+    /// is returned as an error and should be dropped instead.
+    fn mark_packet(&self, mut packet: PacketRc) -> Result<PacketRc, PacketRc> {
+        if self.ecn_marking && packet.mark_congestion_experienced() {
+            packet.add_status(PacketStatus::RouterMarked);
+            Ok(packet)
+        } else {
+            Err(packet)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -531,4 +574,60 @@ mod tests {
         assert_eq!(cdq.current_drop_count, N - 4);
         assert_eq!(cdq.mode, CoDelMode::Store);
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn mark_ecn() {
+        let start = mock_time_millis(1000);
+        let end = mock_time_millis(1000000);
+
+        let ecn_capable_packet = || {
+            let packet = PacketRc::mock_new();
+            unsafe { c::packet_setECN(packet.borrow_inner(), c::_PacketECN_PECN_ECT_0) };
+            packet
+        };
+        let ecn = |packet: &PacketRc| unsafe { c::packet_getECN(packet.borrow_inner()) };
+
+        let mut cdq = CoDelQueue::new();
+        cdq.set_ecn_marking(true);
+        const N: usize = 20;
+        for _ in 0..N {
+            cdq.push(ecn_capable_packet(), start);
+        }
+        // One packet that isn't ECN-capable, followed by one that is.
+        cdq.push(PacketRc::mock_new(), start);
+        cdq.push(ecn_capable_packet(), start);
+        assert_eq!(cdq.len(), N + 2);
+
+        // Sets the interval.
+        cdq.pop(start + TARGET);
+        assert_eq!(cdq.len(), N + 1);
+        assert_eq!(cdq.mode, CoDelMode::Store);
+
+        // Enters Drop mode, but marks the packet instead of dropping it.
+        let packet = cdq.pop(start + TARGET + INTERVAL).unwrap();
+        assert_eq!(ecn(&packet), c::_PacketECN_PECN_CE);
+        assert_eq!(cdq.len(), N);
+        assert_eq!(cdq.current_drop_count, 1);
+        assert_eq!(cdq.mode, CoDelMode::Drop);
+
+        // In Drop mode, each pop marks one packet and no packets are dropped.
+        for i in 2..N {
+            assert!(cdq.should_drop(&end));
+            let packet = cdq.pop(end).unwrap();
+            assert_eq!(ecn(&packet), c::_PacketECN_PECN_CE);
+            assert_eq!(cdq.len(), N - i + 1);
+            assert_eq!(cdq.current_drop_count, i);
+            assert_eq!(cdq.mode, CoDelMode::Drop);
+        }
+
+        // The packet that isn't ECN-capable is dropped, and then the queue is
+        // too small to stay in Drop mode.
+        assert_eq!(cdq.len(), 2);
+        let packet = cdq.pop(end).unwrap();
+        assert_eq!(ecn(&packet), c::_PacketECN_PECN_ECT_0);
+        assert_eq!(cdq.current_drop_count, N);
+        assert_eq!(cdq.mode, CoDelMode::Store);
+        assert!(cdq.is_empty());
+    }
 }
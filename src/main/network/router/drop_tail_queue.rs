@@ -0,0 +1,110 @@
+//! A first-in-first-out queue that drops arriving packets when it's full, like the buffer of a
+//! router without active queue management. The queue can be limited by the number of packets, the
+//! number of bytes, or both.
+
+use std::collections::VecDeque;
+
+use crate::network::packet::{PacketRc, PacketStatus};
+
+pub struct DropTailQueue {
+    elements: VecDeque<PacketRc>,
+    /// The maximum number of packets stored, if limited.
+    packet_limit: Option<usize>,
+    /// The maximum number of bytes stored, if limited.
+    byte_limit: Option<usize>,
+    /// The number of bytes stored.
+    total_bytes_stored: usize,
+}
+
+impl DropTailQueue {
+    pub fn new(packet_limit: Option<usize>, byte_limit: Option<usize>) -> DropTailQueue {
+        DropTailQueue {
+            elements: VecDeque::new(),
+            packet_limit,
+            byte_limit,
+            total_bytes_stored: 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the packet at the front of the queue, or None if the queue is empty.
+    #[cfg(test)]
+    pub fn peek(&self) -> Option<&PacketRc> {
+        self.elements.front()
+    }
+
+    /// Append a packet to the end of the queue, or drop it if the queue doesn't have room for it.
+    pub fn push(&mut self, mut packet: PacketRc) {
+        let size = packet.total_size();
+
+        let has_room = self.packet_limit.map_or(true, |x| self.elements.len() < x)
+            && self
+                .byte_limit
+                .map_or(true, |x| self.total_bytes_stored + size <= x);
+
+        if !has_room {
+            packet.add_status(PacketStatus::RouterDropped);
+            return;
+        }
+
+        packet.add_status(PacketStatus::RouterEnqueued);
+        self.total_bytes_stored += size;
+        self.elements.push_back(packet);
+    }
+
+    /// Remove the packet at the front of the queue.
+    pub fn pop(&mut self) -> Option<PacketRc> {
+        let mut packet = self.elements.pop_front()?;
+        self.total_bytes_stored -= packet.total_size();
+        packet.add_status(PacketStatus::RouterDequeued);
+        Some(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn packet_limit() {
+        let mut queue = DropTailQueue::new(Some(3), None);
+
+        for _ in 0..5 {
+            queue.push(PacketRc::mock_new());
+        }
+        assert_eq!(queue.len(), 3);
+
+        for _ in 0..3 {
+            assert!(queue.pop().is_some());
+        }
+        assert!(queue.is_empty());
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn byte_limit() {
+        let size = PacketRc::mock_new().total_size();
+        let mut queue = DropTailQueue::new(None, Some(2 * size + 1));
+
+        for _ in 0..5 {
+            queue.push(PacketRc::mock_new());
+        }
+        assert_eq!(queue.len(), 2);
+
+        // there's room again after a packet leaves
+        assert!(queue.pop().is_some());
+        queue.push(PacketRc::mock_new());
+        assert_eq!(queue.len(), 2);
+    }
+}
@@ -2,14 +2,21 @@ use std::cell::RefCell;
 use std::net::Ipv4Addr;
 
 use self::codel_queue::CoDelQueue;
+use self::drop_tail_queue::DropTailQueue;
+use self::red_queue::{RedParams, RedQueue};
+use crate::core::configuration::RouterQueue;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::network::packet::PacketRc;
 use crate::network::PacketDevice;
+use crate::utility::units::{self, Unit};
 use crate::utility::{Magic, ObjectCounter};
-mod codel_queue;
+pub mod codel_queue;
+pub mod drop_tail_queue;
+pub mod red_queue;
 
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 /// A router assists with moving packets between hosts across the simulated
 /// network.
@@ -18,19 +25,19 @@ pub struct Router {
     _counter: ObjectCounter,
     address: Ipv4Addr,
     /// Packets inbound to the host from the simulated network.
-    inbound_packets: RefCell<CoDelQueue>,
+    inbound_packets: RefCell<InboundQueue>,
 }
 
 impl Router {
     /// Create a new router for a host that will help route packets between it
     /// and other hosts. The `address` must uniquely identify this router to the
     /// host that owns it.
-    pub fn new(address: Ipv4Addr) -> Router {
+    pub fn new(address: Ipv4Addr, inbound_packets: InboundQueue) -> Router {
         Router {
             magic: Magic::new(),
             address,
             _counter: ObjectCounter::new("Router"),
-            inbound_packets: RefCell::new(CoDelQueue::new()),
+            inbound_packets: RefCell::new(inbound_packets),
         }
     }
 
@@ -77,6 +84,93 @@ impl PacketDevice for Router {
     }
 }
 
+/// The queue that holds packets inbound to the host until the host's bandwidth allows them to be
+/// received.
+pub enum InboundQueue {
+    CoDel(CoDelQueue),
+    DropTail(DropTailQueue),
+    Red(RedQueue),
+}
+
+impl InboundQueue {
+    /// Create a queue from the configuration options. If `ecn_marking` is true, an AQM queue marks
+    /// ECN-capable packets instead of dropping them when congested. The `bw_down_bits` is the
+    /// host's download bandwidth, and the `seed` is used for any random decisions made by the
+    /// queue.
+    pub fn new(options: &RouterQueue, ecn_marking: bool, bw_down_bits: u64, seed: u64) -> Self {
+        let to_bytes = |x: units::Bytes<units::SiPrefixUpper>| -> usize {
+            x.convert(units::SiPrefixUpper::Base)
+                .unwrap()
+                .value()
+                .try_into()
+                .unwrap()
+        };
+
+        match *options {
+            RouterQueue::Codel => {
+                let mut queue = CoDelQueue::new();
+                queue.set_ecn_marking(ecn_marking);
+                Self::CoDel(queue)
+            }
+            RouterQueue::DropTail { packets, bytes } => Self::DropTail(DropTailQueue::new(
+                packets.map(|x| x.try_into().unwrap()),
+                bytes.map(to_bytes),
+            )),
+            RouterQueue::Red {
+                limit,
+                min,
+                max,
+                probability,
+                weight,
+            } => {
+                let params = RedParams {
+                    limit: to_bytes(limit),
+                    min_threshold: to_bytes(min),
+                    max_threshold: to_bytes(max),
+                    max_probability: probability.into(),
+                    weight: weight.into(),
+                };
+
+                // the time to receive an MTU-sized packet at the host's bandwidth
+                let mtu_bits = u64::from(c::CONFIG_MTU) * 8;
+                let packet_time = match bw_down_bits {
+                    0 => SimulationTime::ZERO,
+                    bw => SimulationTime::from_nanos(mtu_bits * 1_000_000_000 / bw),
+                };
+
+                let mut queue = RedQueue::new(params, packet_time, seed);
+                queue.set_ecn_marking(ecn_marking);
+                Self::Red(queue)
+            }
+        }
+    }
+
+    fn push(&mut self, packet: PacketRc, now: EmulatedTime) {
+        match self {
+            Self::CoDel(queue) => queue.push(packet, now),
+            Self::DropTail(queue) => queue.push(packet),
+            Self::Red(queue) => queue.push(packet, now),
+        }
+    }
+
+    fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        match self {
+            Self::CoDel(queue) => queue.pop(now),
+            Self::DropTail(queue) => queue.pop(),
+            Self::Red(queue) => queue.pop(now),
+        }
+    }
+
+    #[cfg(test)]
+    fn peek(&self) -> Option<&PacketRc> {
+        match self {
+            Self::CoDel(queue) => queue.peek(),
+            Self::DropTail(queue) => queue.peek(),
+            Self::Red(queue) => queue.peek(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,7 +179,10 @@ mod tests {
     #[test]
     fn empty() {
         let now = mock_time_millis(1000);
-        let router = Router::new(Ipv4Addr::UNSPECIFIED);
+        let router = Router::new(
+            Ipv4Addr::UNSPECIFIED,
+            InboundQueue::CoDel(CoDelQueue::new()),
+        );
         assert!(router.inbound_packets.borrow().peek().is_none());
         assert!(router.pop_inner(now).is_none());
     }
@@ -95,7 +192,10 @@ mod tests {
     #[cfg_attr(miri, ignore)]
     fn push_pop_simple() {
         let now = mock_time_millis(1000);
-        let router = Router::new(Ipv4Addr::UNSPECIFIED);
+        let router = Router::new(
+            Ipv4Addr::UNSPECIFIED,
+            InboundQueue::CoDel(CoDelQueue::new()),
+        );
 
         const N: usize = 10;
 
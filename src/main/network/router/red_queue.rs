@@ -0,0 +1,294 @@
+//! An active queue management (AQM) algorithm implementing Random Early Detection (RED).
+//! <https://www.icir.org/floyd/papers/red/red.html>
+//!
+//! RED tracks an exponentially weighted moving average of the queue size in bytes. When the
+//! average is between the minimum and maximum thresholds, arriving packets are dropped (or marked,
+//! if ECN marking is enabled) with a probability that increases with the average. When the average
+//! is above the maximum threshold, all arriving packets are dropped or marked.
+//!
+//!  More info:
+//!   - <https://man7.org/linux/man-pages/man8/tc-red.8.html>
+//!   - <https://tools.ietf.org/html/rfc2309>
+
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::network::packet::{PacketRc, PacketStatus};
+
+/// The configuration of a RED queue.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedParams {
+    /// The maximum number of bytes stored. Packets that arrive when the queue is full are always
+    /// dropped.
+    pub limit: usize,
+    /// The average queue size in bytes at which packets start to be dropped.
+    pub min_threshold: usize,
+    /// The average queue size in bytes at which all packets are dropped.
+    pub max_threshold: usize,
+    /// The drop probability when the average queue size reaches the maximum threshold.
+    pub max_probability: f64,
+    /// The weight of the current queue size in the moving average.
+    pub weight: f64,
+}
+
+pub struct RedQueue {
+    params: RedParams,
+    elements: VecDeque<PacketRc>,
+    /// The number of bytes stored.
+    total_bytes_stored: usize,
+    /// The moving average of the number of bytes stored.
+    average: f64,
+    /// The number of packets enqueued since the last drop while the average was between the
+    /// thresholds, or `None` if the average was below the minimum threshold.
+    count: Option<u64>,
+    /// The time that the queue became empty, if it's empty.
+    idle_since: Option<EmulatedTime>,
+    /// The time to send a typical packet, used to decay the average while the queue is idle.
+    packet_time: SimulationTime,
+    /// If true, we mark ECN-capable packets instead of dropping them.
+    ecn_marking: bool,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl RedQueue {
+    /// Create a new RED queue. The `packet_time` is the time to send a typical packet, which
+    /// should be the time to send an MTU-sized packet at the host's bandwidth. The `seed` is used
+    /// for the random drop decisions.
+    pub fn new(params: RedParams, packet_time: SimulationTime, seed: u64) -> RedQueue {
+        RedQueue {
+            params,
+            elements: VecDeque::new(),
+            total_bytes_stored: 0,
+            average: 0.0,
+            count: None,
+            idle_since: Some(EmulatedTime::SIMULATION_START),
+            packet_time,
+            ecn_marking: false,
+            rng: Xoshiro256PlusPlus::seed_from_u64(seed),
+        }
+    }
+
+    /// If enabled, ECN-capable packets will be marked as having experienced congestion rather
+    /// than dropped.
+    pub fn set_ecn_marking(&mut self, enabled: bool) {
+        self.ecn_marking = enabled;
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the packet at the front of the queue, or None if the queue is empty.
+    #[cfg(test)]
+    pub fn peek(&self) -> Option<&PacketRc> {
+        self.elements.front()
+    }
+
+    /// Append a packet to the end of the queue, unless RED decides to drop it.
+    /// Requires the current time as an argument to avoid calling into the
+    /// worker module internally.
+    pub fn push(&mut self, packet: PacketRc, now: EmulatedTime) {
+        self.update_average(now);
+
+        let size = packet.total_size();
+        if self.total_bytes_stored + size > self.params.limit {
+            self.drop_packet(packet);
+            return;
+        }
+
+        let packet = if self.should_drop() {
+            match self.mark_packet(packet) {
+                Ok(packet) => packet,
+                Err(packet) => {
+                    self.drop_packet(packet);
+                    return;
+                }
+            }
+        } else {
+            packet
+        };
+
+        self.enqueue(packet);
+    }
+
+    /// Remove the packet at the front of the queue.
+    /// Requires the current time as an argument to avoid calling into the
+    /// worker module internally.
+    pub fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        let mut packet = self.elements.pop_front()?;
+        self.total_bytes_stored -= packet.total_size();
+
+        if self.elements.is_empty() {
+            self.idle_since = Some(now);
+        }
+
+        packet.add_status(PacketStatus::RouterDequeued);
+        Some(packet)
+    }
+
+    fn enqueue(&mut self, mut packet: PacketRc) {
+        packet.add_status(PacketStatus::RouterEnqueued);
+        self.total_bytes_stored += packet.total_size();
+        self.elements.push_back(packet);
+        self.idle_since = None;
+    }
+
+    /// Update the moving average of the queue size for a packet arriving at time `now`.
+    fn update_average(&mut self, now: EmulatedTime) {
+        let weight = self.params.weight;
+
+        match self.idle_since {
+            Some(idle_since) => {
+                // while idle, the average decays as if `m` empty-queue samples were taken, where
+                // `m` is the number of packets that could have been sent while idle
+                let idle_time = now.saturating_duration_since(&idle_since);
+                let m = if self.packet_time.is_zero() {
+                    0.0
+                } else {
+                    idle_time.as_nanos_f64() / self.packet_time.as_nanos_f64()
+                };
+                self.average *= (1.0 - weight).powf(m);
+            }
+            None => {
+                self.average =
+                    (1.0 - weight) * self.average + weight * self.total_bytes_stored as f64;
+            }
+        }
+    }
+
+    /// Decide whether an arriving packet should be dropped (or marked) based on the average queue
+    /// size.
+    fn should_drop(&mut self) -> bool {
+        let min = self.params.min_threshold as f64;
+        let max = self.params.max_threshold as f64;
+
+        if self.average < min {
+            self.count = None;
+            return false;
+        }
+
+        if self.average >= max {
+            self.count = Some(0);
+            return true;
+        }
+
+        let count = self.count.map_or(0, |x| x + 1);
+
+        // the probability increases linearly between the thresholds, and increases with the
+        // number of packets since the last drop so that drops are spaced out evenly
+        let pb = self.params.max_probability * (self.average - min) / (max - min);
+        let pa = match 1.0 - count as f64 * pb {
+            x if x <= 0.0 => 1.0,
+            x => pb / x,
+        };
+
+        if self.rng.gen::<f64>() < pa {
+            self.count = Some(0);
+            true
+        } else {
+            self.count = Some(count);
+            false
+        }
+    }
+
+    fn drop_packet(&self, mut packet: PacketRc) {
+        packet.add_status(PacketStatus::RouterDropped);
+    }
+
+    /// Marks the packet as having experienced congestion if ECN marking is
+    /// enabled and the packet is ECN-capable. Otherwise the unmodified packet
+    /// is returned as an error and should be dropped instead.
+    fn mark_packet(&self, mut packet: PacketRc) -> Result<PacketRc, PacketRc> {
+        if self.ecn_marking && packet.mark_congestion_experienced() {
+            packet.add_status(PacketStatus::RouterMarked);
+            Ok(packet)
+        } else {
+            Err(packet)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    fn params(packet_size: usize) -> RedParams {
+        RedParams {
+            limit: 100 * packet_size,
+            min_threshold: 5 * packet_size,
+            max_threshold: 15 * packet_size,
+            max_probability: 0.1,
+            weight: 0.5,
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn no_drops_below_min_threshold() {
+        let now = mock_time_millis(1000);
+        let size = PacketRc::mock_new().total_size();
+        let mut queue = RedQueue::new(params(size), SimulationTime::from_millis(1), 1);
+
+        // the average stays below the queue size, so never reaches the minimum threshold
+        for _ in 0..5 {
+            queue.push(PacketRc::mock_new(), now);
+        }
+        assert_eq!(queue.len(), 5);
+
+        for _ in 0..5 {
+            assert!(queue.pop(now).is_some());
+        }
+        assert!(queue.is_empty());
+        assert!(queue.pop(now).is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drops_above_max_threshold() {
+        let now = mock_time_millis(1000);
+        let size = PacketRc::mock_new().total_size();
+        let mut queue = RedQueue::new(params(size), SimulationTime::from_millis(1), 1);
+
+        for _ in 0..100 {
+            queue.push(PacketRc::mock_new(), now);
+        }
+
+        // the average can't grow much above the maximum threshold, since all packets are dropped
+        // when it's above the threshold
+        assert!(queue.len() > 5);
+        assert!(queue.len() < 30);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn average_decays_when_idle() {
+        let size = PacketRc::mock_new().total_size();
+        let mut queue = RedQueue::new(params(size), SimulationTime::from_millis(1), 1);
+
+        let now = mock_time_millis(1000);
+        for _ in 0..20 {
+            queue.push(PacketRc::mock_new(), now);
+        }
+        while queue.pop(now).is_some() {}
+        assert!(queue.average > 0.0);
+
+        // after a long idle period, the average is close to 0 and packets aren't dropped
+        let now = mock_time_millis(2000);
+        for _ in 0..5 {
+            queue.push(PacketRc::mock_new(), now);
+        }
+        assert!(queue.average < 5.0 * size as f64);
+        assert_eq!(queue.len(), 5);
+    }
+}
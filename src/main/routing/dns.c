@@ -30,8 +30,11 @@ struct _DNS {
     /* address mappings */
     GHashTable* addressByIP;
     GHashTable* addressByName;
+    /* the time from which each name can be resolved */
+    GHashTable* startTimeByName;
 
-    int hosts_file_fd;
+    /* hosts files keyed by the start time of the last host that they contain */
+    GHashTable* hostsFileByStartTime;
 
     MAGIC_DECLARE;
 };
@@ -82,17 +85,15 @@ static gboolean _dns_isIPInRange(const in_addr_t netIP, const gchar* cidrStr) {
 
 /* Address must be in network byte order. */
 static gboolean _dns_isRestricted(DNS* dns, in_addr_t netIP) {
-    /* http://en.wikipedia.org/wiki/Reserved_IP_addresses#Reserved_IPv4_addresses */
+    /* http://en.wikipedia.org/wiki/Reserved_IP_addresses#Reserved_IPv4_addresses
+     * Private and shared address ranges aren't restricted, since hosts behind NAT gateways use
+     * them. */
     if(_dns_isIPInRange(netIP, "0.0.0.0/8") ||
-            _dns_isIPInRange(netIP, "10.0.0.0/8") ||
-            _dns_isIPInRange(netIP, "100.64.0.0/10") ||
             _dns_isIPInRange(netIP, "127.0.0.0/8") ||
             _dns_isIPInRange(netIP, "169.254.0.0/16") ||
-            _dns_isIPInRange(netIP, "172.16.0.0/12") ||
             _dns_isIPInRange(netIP, "192.0.0.0/29") ||
             _dns_isIPInRange(netIP, "192.0.2.0/24") ||
             _dns_isIPInRange(netIP, "192.88.99.0/24") ||
-            _dns_isIPInRange(netIP, "192.168.0.0/16") ||
             _dns_isIPInRange(netIP, "198.18.0.0/15") ||
             _dns_isIPInRange(netIP, "198.51.100.0/24") ||
             _dns_isIPInRange(netIP, "203.0.113.0/24") ||
@@ -111,8 +112,21 @@ static gboolean _dns_isIPUnique(DNS* dns, in_addr_t ip) {
     return exists ? FALSE : TRUE;
 }
 
+/* Close the hosts files, which are (lazily) recreated when they're next needed. The caller must
+ * hold the lock. */
+static void _dns_invalidateHostsFiles(DNS* dns) {
+    GHashTableIter iter;
+    gpointer fd = NULL;
+    g_hash_table_iter_init(&iter, dns->hostsFileByStartTime);
+    while (g_hash_table_iter_next(&iter, NULL, &fd)) {
+        close(GPOINTER_TO_INT(fd));
+    }
+    g_hash_table_remove_all(dns->hostsFileByStartTime);
+}
+
 /* Address must be in network byte order. */
-Address* dns_register(DNS* dns, HostId id, const gchar* name, in_addr_t requestedIP) {
+Address* dns_register(DNS* dns, HostId id, const gchar* name, in_addr_t requestedIP,
+                      CSimulationTime startTime) {
     MAGIC_ASSERT(dns);
     utility_debugAssert(name);
 
@@ -144,14 +158,15 @@ Address* dns_register(DNS* dns, HostId id, const gchar* name, in_addr_t requeste
         /* cast the const pointer to non-const */
         g_hash_table_replace(dns->addressByName, (gchar*)address_toHostName(address), address);
         address_ref(address);
-    }
 
-    /* Any existing hosts file needs to be (lazily) updated. */
-    if (dns->hosts_file_fd >= 0) {
-        close(dns->hosts_file_fd);
-        dns->hosts_file_fd = -1;
+        CSimulationTime* start = g_new(CSimulationTime, 1);
+        *start = startTime;
+        g_hash_table_replace(dns->startTimeByName, g_strdup(name), start);
     }
 
+    /* Any existing hosts files need to be (lazily) updated. */
+    _dns_invalidateHostsFiles(dns);
+
     g_mutex_unlock(&dns->lock);
 
     return address;
@@ -165,12 +180,10 @@ void dns_deregister(DNS* dns, Address* address) {
         /* these remove functions will call address_unref as necessary */
         g_hash_table_remove(dns->addressByIP, GUINT_TO_POINTER(address_toNetworkIP(address)));
         g_hash_table_remove(dns->addressByName, address_toHostName(address));
+        g_hash_table_remove(dns->startTimeByName, address_toHostName(address));
 
-        /* Any existing hosts file needs to be (lazily) updated. */
-        if (dns->hosts_file_fd >= 0) {
-            close(dns->hosts_file_fd);
-            dns->hosts_file_fd = -1;
-        }
+        /* Any existing hosts files need to be (lazily) updated. */
+        _dns_invalidateHostsFiles(dns);
 
         g_mutex_unlock(&dns->lock);
     }
@@ -188,69 +201,131 @@ Address* dns_resolveIPToAddress(DNS* dns, in_addr_t ip) {
     return result;
 }
 
-Address* dns_resolveNameToAddress(DNS* dns, const gchar* name) {
+/* Returns the time from which the name can be resolved. The caller must hold the lock. */
+static CSimulationTime _dns_getStartTime(DNS* dns, const gchar* name) {
+    const CSimulationTime* start = g_hash_table_lookup(dns->startTimeByName, name);
+    return start ? *start : 0;
+}
+
+Address* dns_resolveNameToAddress(DNS* dns, const gchar* name, CSimulationTime now) {
     MAGIC_ASSERT(dns);
     Address* result = g_hash_table_lookup(dns->addressByName, name);
     if(!result) {
         warning("unable to find address from name '%s'", name);
+        return NULL;
+    }
+
+    g_mutex_lock(&dns->lock);
+    CSimulationTime start = _dns_getStartTime(dns, name);
+    g_mutex_unlock(&dns->lock);
+
+    if (now < start) {
+        debug("name '%s' can't be resolved until its host starts", name);
+        return NULL;
     }
+
     return result;
 }
 
+typedef struct _HostsFileData {
+    DNS* dns;
+    GString* buf;
+    CSimulationTime startTime;
+} HostsFileData;
+
 static void _dns_writeHostLine(gpointer key, gpointer value, gpointer data) {
     const gchar* name = key;
     const Address* address = value;
-    GString* buf = data;
-    g_string_append_printf(buf, "%s %s\n", address_toHostIPString(address), name);
+    HostsFileData* fileData = data;
+
+    /* only hosts that have started are in the file */
+    if (_dns_getStartTime(fileData->dns, name) <= fileData->startTime) {
+        g_string_append_printf(fileData->buf, "%s %s\n", address_toHostIPString(address), name);
+    }
 }
 
-static bool _dns_writeNewHostsFile(DNS* dns) {
+/* Returns the start time of the last host that has started at time `now`, or 0 if no hosts have
+ * started. Hosts files are shared by every host that opens them at a time with the same start time,
+ * so that the file's contents don't depend on which host created it. The caller must hold the
+ * lock. */
+static CSimulationTime _dns_getLastStartTime(DNS* dns, CSimulationTime now) {
+    CSimulationTime last = 0;
+
+    GHashTableIter iter;
+    gpointer value = NULL;
+    g_hash_table_iter_init(&iter, dns->startTimeByName);
+    while (g_hash_table_iter_next(&iter, NULL, &value)) {
+        CSimulationTime start = *(CSimulationTime*)value;
+        if (start <= now && start > last) {
+            last = start;
+        }
+    }
+
+    return last;
+}
+
+/* Returns the fd of a new hosts file containing the hosts that have started at `startTime`, or -1
+ * on error. The caller must hold the lock. */
+static int _dns_writeNewHostsFile(DNS* dns, CSimulationTime startTime) {
     MAGIC_ASSERT(dns);
-    utility_debugAssert(dns->hosts_file_fd < 0);
 
-    dns->hosts_file_fd = memfd_create("shadow hosts file", MFD_CLOEXEC);
-    if (dns->hosts_file_fd < 0) {
+    int fd = memfd_create("shadow hosts file", MFD_CLOEXEC);
+    if (fd < 0) {
         warning(
             "Unable create temp hosts file, memfd_create() error %i: %s", errno, strerror(errno));
-        return false;
+        return -1;
     }
 
-    GString* buf = g_string_new("127.0.0.1 localhost\n");
-    g_hash_table_foreach(dns->addressByName, _dns_writeHostLine, buf);
+    HostsFileData data = {
+        .dns = dns,
+        .buf = g_string_new("127.0.0.1 localhost\n"),
+        .startTime = startTime,
+    };
+    g_hash_table_foreach(dns->addressByName, _dns_writeHostLine, &data);
+    GString* buf = data.buf;
 
     trace("Hosts file string buffer is %zu bytes.", buf->len);
 
     size_t amt = 0;
     while(amt < buf->len) {
-        ssize_t ret = write(dns->hosts_file_fd, &buf->str[amt], buf->len-amt);
+        ssize_t ret = write(fd, &buf->str[amt], buf->len-amt);
         if(ret < 0 && errno != EAGAIN) {
             warning("Unable to write to temp hosts file, write() error %i: %s", errno, strerror(errno));
             g_string_free(buf, TRUE);
-            close(dns->hosts_file_fd);
-            dns->hosts_file_fd = -1;
-            return false;
+            close(fd);
+            return -1;
         } else if(ret >= 0) {
             amt += (size_t)ret;
         }
     }
 
     g_string_free(buf, TRUE);
-    return true;
+    return fd;
 }
 
-gchar* dns_getHostsFilePath(DNS* dns) {
+gchar* dns_getHostsFilePath(DNS* dns, CSimulationTime now) {
     MAGIC_ASSERT(dns);
 
     g_mutex_lock(&dns->lock);
 
-    if(dns->hosts_file_fd < 0) {
-        if(!_dns_writeNewHostsFile(dns)) {
+    CSimulationTime startTime = _dns_getLastStartTime(dns, now);
+
+    gpointer value = NULL;
+    int fd = -1;
+    if (g_hash_table_lookup_extended(dns->hostsFileByStartTime, &startTime, NULL, &value)) {
+        fd = GPOINTER_TO_INT(value);
+    } else {
+        fd = _dns_writeNewHostsFile(dns, startTime);
+        if (fd < 0) {
             warning("Unable to create hosts file; expect networking errors.");
+            g_mutex_unlock(&dns->lock);
             return NULL;
         }
-    }
 
-    int fd = dns->hosts_file_fd;
+        gint64* key = g_new(gint64, 1);
+        *key = (gint64)startTime;
+        g_hash_table_insert(dns->hostsFileByStartTime, key, GINT_TO_POINTER(fd));
+    }
 
     g_mutex_unlock(&dns->lock);
 
@@ -273,25 +348,24 @@ DNS* dns_new() {
 
     dns->addressByIP = g_hash_table_new_full(g_direct_hash, g_direct_equal, NULL, (GDestroyNotify) address_unref);
     dns->addressByName = g_hash_table_new_full(g_str_hash, g_str_equal, NULL, (GDestroyNotify) address_unref);
+    dns->startTimeByName = g_hash_table_new_full(g_str_hash, g_str_equal, g_free, g_free);
+    dns->hostsFileByStartTime = g_hash_table_new_full(g_int64_hash, g_int64_equal, g_free, NULL);
 
     /* 11.0.0.0 -- 100.0.0.0 is the longest available unrestricted range */
     dns->ipAddressCounter = ntohl(address_stringToIP("11.0.0.0"));
 
-    dns->hosts_file_fd = -1;
-
     return dns;
 }
 
 void dns_free(DNS* dns) {
     MAGIC_ASSERT(dns);
 
-    if (dns->hosts_file_fd >= 0) {
-        close(dns->hosts_file_fd);
-        dns->hosts_file_fd = -1;
-    }
+    _dns_invalidateHostsFiles(dns);
 
     g_hash_table_destroy(dns->addressByIP);
     g_hash_table_destroy(dns->addressByName);
+    g_hash_table_destroy(dns->startTimeByName);
+    g_hash_table_destroy(dns->hostsFileByStartTime);
 
     g_mutex_clear(&(dns->lock));
 
@@ -18,22 +18,25 @@ typedef struct _DNS DNS;
 DNS* dns_new();
 void dns_free(DNS* dns);
 
-/* Address must be in network byte order. */
-Address* dns_register(DNS* dns, HostId id, const gchar* name, in_addr_t requestedIP);
+/* Address must be in network byte order. The name can't be resolved before `startTime`. */
+Address* dns_register(DNS* dns, HostId id, const gchar* name, in_addr_t requestedIP,
+                      CSimulationTime startTime);
 void dns_deregister(DNS* dns, Address* address);
 
 /* Address must be in network byte order. */
 Address* dns_resolveIPToAddress(DNS* dns, in_addr_t ip);
-Address* dns_resolveNameToAddress(DNS* dns, const gchar* name);
+/* Returns NULL if the name isn't registered, or can't be resolved at time `now`. */
+Address* dns_resolveNameToAddress(DNS* dns, const gchar* name, CSimulationTime now);
 
 /* Returns a string path to a file containing (ip,name) information for all
- * currently registered pairs. The format of the file follows the format
- * used in /etc/hosts (see `man 5 hosts`). The returned path is a new string
- * that is owned and should be freed by the caller.
+ * currently registered pairs that can be resolved at time `now`. The format of
+ * the file follows the format used in /etc/hosts (see `man 5 hosts`). The
+ * returned path is a new string that is owned and should be freed by the
+ * caller.
  *
  * The file is created lazily when this function is called and becomes invalid
  * if dns_register() is called after this function returns; once it becomes
  * invalid, a new file is created upon a subsequent call to this function. */
-gchar* dns_getHostsFilePath(DNS* dns);
+gchar* dns_getHostsFilePath(DNS* dns, CSimulationTime now);
 
 #endif /* SHD_DNS_H_ */
@@ -6,6 +6,7 @@
 
 #include <assert.h>
 #include <netinet/in.h>
+#include <netinet/ip_icmp.h>
 #include <stddef.h>
 #include <stdint.h>
 
@@ -78,6 +79,16 @@ struct _Packet {
      */
     uint64_t priority;
 
+    /* whether routers must drop the packet instead of fragmenting it if it's
+     * larger than the MTU of a link (the IPv4 "don't fragment" flag) */
+    bool dontFragment;
+
+    /* the ECN codepoint of the IP header */
+    PacketECN ecn;
+
+    /* the time-to-live of the IP header, which is decremented by each router on the path */
+    uint8_t ttl;
+
     PacketDeliveryStatusFlags allStatus;
     GQueue* orderedStatus;
 
@@ -88,6 +99,7 @@ const gchar* protocol_toString(ProtocolType type) {
     switch (type) {
         case PUDP: return "UDP";
         case PTCP: return "TCP";
+        case PICMP: return "ICMP";
         case PMOCK: return "MOCK";
         default: return "UNKNOWN";
     }
@@ -103,6 +115,8 @@ Packet* packet_new_inner(guint hostID, guint64 packetID) {
     packet->hostID = hostID;
     packet->packetID = packetID;
 
+    packet->ttl = PACKET_DEFAULT_TTL;
+
     packet->orderedStatus = g_queue_new();
 
     return packet;
@@ -182,6 +196,9 @@ Packet* packet_copy(Packet* packet) {
         copy->priority = packet->priority;
     }
 
+    copy->dontFragment = packet->dontFragment;
+    copy->ecn = packet->ecn;
+    copy->ttl = packet->ttl;
     copy->allStatus = packet->allStatus;
 
     if(packet->orderedStatus) {
@@ -197,6 +214,11 @@ Packet* packet_copy(Packet* packet) {
                 break;
             }
 
+            case PICMP: {
+                copy->header = memdup(packet->header, sizeof(PacketICMPHeader));
+                break;
+            }
+
             case PTCP: {
                 copy->header = memdup(packet->header, sizeof(PacketTCPHeader));
 
@@ -267,6 +289,36 @@ void packet_setPriority(Packet *packet, uint64_t value) {
    packet->priority = value;
 }
 
+void packet_setDontFragment(Packet* packet, bool dontFragment) {
+    MAGIC_ASSERT(packet);
+    packet->dontFragment = dontFragment;
+}
+
+bool packet_getDontFragment(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->dontFragment;
+}
+
+void packet_setECN(Packet* packet, PacketECN ecn) {
+    MAGIC_ASSERT(packet);
+    packet->ecn = ecn;
+}
+
+PacketECN packet_getECN(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->ecn;
+}
+
+void packet_setTTL(Packet* packet, uint8_t ttl) {
+    MAGIC_ASSERT(packet);
+    packet->ttl = ttl;
+}
+
+uint8_t packet_getTTL(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->ttl;
+}
+
 gint packet_compareTCPSequence(Packet* packet1, Packet* packet2, gpointer user_data) {
     MAGIC_ASSERT(packet1);
     MAGIC_ASSERT(packet2);
@@ -331,6 +383,26 @@ void packet_setTCP(Packet* packet, enum ProtocolTCPFlags flags,
     packet->protocol = PTCP;
 }
 
+// The addresses must be in network byte order.
+void packet_setICMP(Packet* packet, in_addr_t sourceIP, in_addr_t destinationIP, guint8 type,
+                    guint8 code, guint16 identifier, guint16 sequence) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(!(packet->header) && packet->protocol == PNONE);
+    utility_debugAssert(sourceIP && destinationIP);
+
+    PacketICMPHeader* header = g_new0(PacketICMPHeader, 1);
+
+    header->sourceIP = sourceIP;
+    header->destinationIP = destinationIP;
+    header->type = type;
+    header->code = code;
+    header->identifier = identifier;
+    header->sequence = sequence;
+
+    packet->header = header;
+    packet->protocol = PICMP;
+}
+
 void packet_updateTCP(Packet* packet, guint acknowledgement, GList* selectiveACKs, guint window,
                       unsigned char windowScale, bool windowScaleSet,
                       CSimulationTime timestampValue, CSimulationTime timestampEcho) {
@@ -359,6 +431,38 @@ void packet_updateTCP(Packet* packet, guint acknowledgement, GList* selectiveACK
     header->timestampEcho = timestampEcho;
 }
 
+void packet_setTCPUrgent(Packet* packet, guint16 urgentPointer) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(packet->header && (packet->protocol == PTCP));
+    utility_debugAssert(urgentPointer > 0 && urgentPointer <= packet_getPayloadSize(packet));
+
+    PacketTCPHeader* header = (PacketTCPHeader*) packet->header;
+
+    header->flags |= PTCP_URG;
+    header->urgentPointer = urgentPointer;
+}
+
+void packet_setTCPDuplicateSACK(Packet* packet, bool isSet, guint sequence) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(packet->header && (packet->protocol == PTCP));
+
+    PacketTCPHeader* header = (PacketTCPHeader*) packet->header;
+
+    header->duplicateSACKSet = isSet;
+    header->duplicateSACK = isSet ? sequence : 0;
+}
+
+void packet_setTCPFastOpen(Packet* packet, bool isSet, bool hasCookie, guint64 cookie) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(packet->header && (packet->protocol == PTCP));
+
+    PacketTCPHeader* header = (PacketTCPHeader*) packet->header;
+
+    header->fastOpenSet = isSet;
+    header->fastOpenCookieSet = isSet && hasCookie;
+    header->fastOpenCookie = (isSet && hasCookie) ? cookie : 0;
+}
+
 gsize packet_getTotalSize(const Packet* packet) {
     MAGIC_ASSERT(packet);
     return packet_getPayloadSize(packet) + packet_getHeaderSize(packet);
@@ -380,6 +484,8 @@ gsize packet_getHeaderSize(const Packet* packet) {
 
     if (packet->protocol == PUDP) {
         return CONFIG_HEADER_SIZE_UDPIP;
+    } else if (packet->protocol == PICMP) {
+        return CONFIG_HEADER_SIZE_ICMPIP;
     } else if (packet->protocol == PTCP) {
         gsize size = CONFIG_HEADER_SIZE_TCPIP;
 
@@ -419,6 +525,12 @@ in_addr_t packet_getDestinationIP(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            ip = header->destinationIP;
+            break;
+        }
+
         case PTCP: {
             PacketTCPHeader* header = packet->header;
             ip = header->destinationIP;
@@ -447,6 +559,14 @@ in_port_t packet_getDestinationPort(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            // ICMP doesn't have ports, so we use the echo identifier as the port of the socket
+            // that sent the echo request, which lets the reply be delivered to that socket
+            PacketICMPHeader* header = packet->header;
+            port = header->type == ICMP_ECHOREPLY ? htons(header->identifier) : 0;
+            break;
+        }
+
         case PTCP: {
             PacketTCPHeader* header = packet->header;
             port = header->destinationPort;
@@ -475,6 +595,12 @@ in_addr_t packet_getSourceIP(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            ip = header->sourceIP;
+            break;
+        }
+
         case PTCP: {
             PacketTCPHeader* header = packet->header;
             ip = header->sourceIP;
@@ -503,6 +629,14 @@ in_port_t packet_getSourcePort(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            // ICMP doesn't have ports, so we use the echo identifier as the port of the socket
+            // that sent the echo request (see `packet_getDestinationPort`)
+            PacketICMPHeader* header = packet->header;
+            port = header->type == ICMP_ECHO ? htons(header->identifier) : 0;
+            break;
+        }
+
         case PTCP: {
             PacketTCPHeader* header = packet->header;
             port = header->sourcePort;
@@ -518,6 +652,76 @@ in_port_t packet_getSourcePort(const Packet* packet) {
     return port;
 }
 
+void packet_setSource(Packet* packet, in_addr_t ip, in_port_t port) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(ip);
+
+    switch (packet->protocol) {
+        case PUDP: {
+            PacketUDPHeader* header = packet->header;
+            header->sourceIP = ip;
+            header->sourcePort = port;
+            break;
+        }
+
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            header->sourceIP = ip;
+            if (header->type == ICMP_ECHO) {
+                header->identifier = ntohs(port);
+            }
+            break;
+        }
+
+        case PTCP: {
+            PacketTCPHeader* header = packet->header;
+            header->sourceIP = ip;
+            header->sourcePort = port;
+            break;
+        }
+
+        default: {
+            utility_panic("unrecognized protocol");
+            break;
+        }
+    }
+}
+
+void packet_setDestination(Packet* packet, in_addr_t ip, in_port_t port) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(ip);
+
+    switch (packet->protocol) {
+        case PUDP: {
+            PacketUDPHeader* header = packet->header;
+            header->destinationIP = ip;
+            header->destinationPort = port;
+            break;
+        }
+
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            header->destinationIP = ip;
+            if (header->type == ICMP_ECHOREPLY) {
+                header->identifier = ntohs(port);
+            }
+            break;
+        }
+
+        case PTCP: {
+            PacketTCPHeader* header = packet->header;
+            header->destinationIP = ip;
+            header->destinationPort = port;
+            break;
+        }
+
+        default: {
+            utility_panic("unrecognized protocol");
+            break;
+        }
+    }
+}
+
 ProtocolType packet_getProtocol(const Packet* packet) {
     MAGIC_ASSERT(packet);
     return packet->protocol;
@@ -585,6 +789,12 @@ PacketTCPHeader* packet_getTCPHeader(const Packet* packet) {
     return (PacketTCPHeader*)packet->header;
 }
 
+PacketICMPHeader* packet_getICMPHeader(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    utility_alwaysAssert(packet->protocol == PICMP);
+    return (PacketICMPHeader*)packet->header;
+}
+
 static const gchar* _packet_deliveryStatusToAscii(PacketDeliveryStatusFlags status) {
     switch (status) {
         case PDS_NONE: return "NONE";
@@ -610,6 +820,11 @@ static const gchar* _packet_deliveryStatusToAscii(PacketDeliveryStatusFlags stat
         case PDS_DESTROYED: return "PDS_DESTROYED";
         case PDS_RELAY_CACHED: return "RELAY_CACHED";
         case PDS_RELAY_FORWARDED: return "RELAY_FORWARDED";
+        case PDS_ROUTER_MARKED: return "ROUTER_MARKED";
+        case PDS_INET_CORRUPTED: return "INET_CORRUPTED";
+        case PDS_INET_REORDERED: return "INET_REORDERED";
+        case PDS_SND_QDISC_DROPPED: return "SND_QDISC_DROPPED";
+        case PDS_FIREWALL_DROPPED: return "FIREWALL_DROPPED";
         default: return "UKNOWN";
     }
 }
@@ -676,6 +891,17 @@ gchar* packet_toString(Packet* packet) {
                 g_string_append_printf(packetString, "NA");
             }
 
+            if(header->duplicateSACKSet) {
+                g_string_append_printf(packetString, " dsack=%u", header->duplicateSACK);
+            }
+
+            if(header->fastOpenCookieSet) {
+                g_string_append_printf(packetString, " tfo=%016" G_GINT64_MODIFIER "x",
+                                       header->fastOpenCookie);
+            } else if(header->fastOpenSet) {
+                g_string_append_printf(packetString, " tfo=request");
+            }
+
             g_string_append_printf(packetString, " window=%u bytes=%u", header->window, payloadLength);
 
             if(!(header->flags & PTCP_NONE)) {
@@ -695,6 +921,18 @@ gchar* packet_toString(Packet* packet) {
                 if(header->flags & PTCP_DUPACK) {
                     g_string_append_printf(packetString, "DUPACK");
                 }
+                if(header->flags & PTCP_URG) {
+                    g_string_append_printf(packetString, "URG");
+                }
+                if(header->flags & PTCP_SACK_PERMITTED) {
+                    g_string_append_printf(packetString, "SACKOK");
+                }
+                if(header->flags & PTCP_ECE) {
+                    g_string_append_printf(packetString, "ECE");
+                }
+                if(header->flags & PTCP_CWR) {
+                    g_string_append_printf(packetString, "CWR");
+                }
             }
 
             g_string_append_printf(packetString, " tsval=%"G_GUINT64_FORMAT" tsechoreply=%"G_GUINT64_FORMAT,
@@ -705,6 +943,20 @@ gchar* packet_toString(Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            gchar* sourceIPString = address_ipToNewString(header->sourceIP);
+            gchar* destinationIPString = address_ipToNewString(header->destinationIP);
+
+            g_string_append_printf(packetString, "%s -> %s type=%u code=%u id=%u seq=%u bytes=%u",
+                                   sourceIPString, destinationIPString, header->type, header->code,
+                                   header->identifier, header->sequence, payloadLength);
+
+            g_free(sourceIPString);
+            g_free(destinationIPString);
+            break;
+        }
+
         case PMOCK: {
             // TODO: We should panic here if this isn't a test.  We don't have a
             // good way to check whether this is being run inside a test in C.
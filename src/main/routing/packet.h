@@ -32,7 +32,16 @@ struct _PacketTCPHeader {
 
     guint sequence;
     guint acknowledgment;
+    // only valid if the URG flag is set; the offset in the payload just past the urgent byte
+    guint16 urgentPointer;
     GList* selectiveACKs;
+    // only valid if duplicateSACKSet is set; a segment that the receiver got twice (RFC 2883)
+    guint duplicateSACK;
+    bool duplicateSACKSet;
+    // the TCP Fast Open option (RFC 7413); a cookie request if fastOpenCookieSet is not set
+    bool fastOpenSet;
+    bool fastOpenCookieSet;
+    guint64 fastOpenCookie;
     guint window;
     unsigned char windowScale;
     bool windowScaleSet;
@@ -40,6 +49,34 @@ struct _PacketTCPHeader {
     CSimulationTime timestampEcho;
 };
 
+// The ECN field of the IP header (RFC 3168).
+typedef enum _PacketECN PacketECN;
+enum _PacketECN {
+    // not an ECN-capable transport
+    PECN_NOT_ECT = 0,
+    PECN_ECT_1 = 1,
+    PECN_ECT_0 = 2,
+    // congestion experienced
+    PECN_CE = 3,
+};
+
+// The TTL of the packets that hosts send, unless the sending socket sets a different TTL.
+#define PACKET_DEFAULT_TTL 64
+
+typedef struct _PacketICMPHeader PacketICMPHeader;
+struct _PacketICMPHeader {
+    // address is in network byte order
+    in_addr_t sourceIP;
+    // address is in network byte order
+    in_addr_t destinationIP;
+
+    guint8 type;
+    guint8 code;
+    // identifier and sequence are in host byte order
+    guint16 identifier;
+    guint16 sequence;
+};
+
 const gchar* protocol_toString(ProtocolType type);
 
 Packet* packet_new(const Host* host);
@@ -64,6 +101,15 @@ static inline void packet_unrefTaskFreeFunc(gpointer packet) { packet_unref(pack
 void packet_setPriority(Packet *packet, uint64_t value);
 uint64_t packet_getPriority(const Packet* packet);
 
+void packet_setDontFragment(Packet* packet, bool dontFragment);
+bool packet_getDontFragment(const Packet* packet);
+
+void packet_setECN(Packet* packet, PacketECN ecn);
+PacketECN packet_getECN(const Packet* packet);
+
+void packet_setTTL(Packet* packet, uint8_t ttl);
+uint8_t packet_getTTL(const Packet* packet);
+
 // The addresses and ports must be in network byte order.
 void packet_setUDP(Packet* packet, enum ProtocolUDPFlags flags,
         in_addr_t sourceIP, in_port_t sourcePort,
@@ -74,9 +120,19 @@ void packet_setTCP(Packet* packet, enum ProtocolTCPFlags flags,
         in_addr_t sourceIP, in_port_t sourcePort,
         in_addr_t destinationIP, in_port_t destinationPort, guint sequence);
 
+// The addresses must be in network byte order.
+void packet_setICMP(Packet* packet, in_addr_t sourceIP, in_addr_t destinationIP, guint8 type,
+                    guint8 code, guint16 identifier, guint16 sequence);
+
 void packet_updateTCP(Packet* packet, guint acknowledgement, GList* selectiveACKs, guint window,
                       unsigned char windowScale, bool windowScaleSet,
                       CSimulationTime timestampValue, CSimulationTime timestampEcho);
+// Set the URG flag. `urgentPointer` is the offset in the payload just past the urgent byte.
+void packet_setTCPUrgent(Packet* packet, guint16 urgentPointer);
+// Set or clear the duplicate SACK (RFC 2883) reporting the segment `sequence`.
+void packet_setTCPDuplicateSACK(Packet* packet, bool isSet, guint sequence);
+// Set or clear the TCP Fast Open option. Without a cookie, the option is a cookie request.
+void packet_setTCPFastOpen(Packet* packet, bool isSet, bool hasCookie, guint64 cookie);
 
 gsize packet_getTotalSize(const Packet* packet);
 gsize packet_getPayloadSize(const Packet* packet);
@@ -92,6 +148,12 @@ in_addr_t packet_getSourceIP(const Packet* packet);
 // The returned port will be in network byte order.
 in_port_t packet_getSourcePort(const Packet* packet);
 
+// Rewrite the source or destination address and port, like a NAT. For ICMP echo messages, the
+// port is the echo identifier (see `packet_getSourcePort`). The address and port must be in
+// network byte order.
+void packet_setSource(Packet* packet, in_addr_t ip, in_port_t port);
+void packet_setDestination(Packet* packet, in_addr_t ip, in_port_t port);
+
 ProtocolType packet_getProtocol(const Packet* packet);
 
 gssize packet_copyPayload(const Packet* packet, const Thread* thread, gsize payloadOffset,
@@ -103,6 +165,7 @@ guint packet_copyPayloadShadow(const Packet* packet, gsize payloadOffset, void*
                                gsize bufferLength);
 GList* packet_copyTCPSelectiveACKs(Packet* packet);
 PacketTCPHeader* packet_getTCPHeader(const Packet* packet);
+PacketICMPHeader* packet_getICMPHeader(const Packet* packet);
 gint packet_compareTCPSequence(Packet* packet1, Packet* packet2, gpointer user_data);
 
 void packet_addDeliveryStatus(Packet* packet, PacketDeliveryStatusFlags status);
@@ -34,6 +34,11 @@ enum _PacketDeliveryStatusFlags {
     PDS_DESTROYED = 1 << 20,
     PDS_RELAY_CACHED = 1 << 21,
     PDS_RELAY_FORWARDED = 1 << 22,
+    PDS_ROUTER_MARKED = 1 << 23,
+    PDS_INET_CORRUPTED = 1 << 24,
+    PDS_INET_REORDERED = 1 << 25,
+    PDS_SND_QDISC_DROPPED = 1 << 26,
+    PDS_FIREWALL_DROPPED = 1 << 27,
 };
 
 typedef struct _PacketTCPHeader PacketTCPHeader;
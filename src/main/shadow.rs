@@ -14,12 +14,26 @@ use clap::Parser;
 use nix::sys::{personality, resource, signal};
 use signal_hook::{consts, iterator::Signals};
 
-use crate::core::configuration::{CliOptions, ConfigFileOptions, ConfigOptions};
+use crate::core::checkpoint;
+use crate::core::config_convert;
+use crate::core::config_include::{self, ConfigFormat};
+use crate::core::config_override::ConfigOverride;
+use crate::core::config_templates;
+use crate::core::config_validate;
+use crate::core::config_variables;
+use crate::core::configuration::{
+    CliOptions, Command, ConfigFileOptions, ConfigOptions, Flatten, TopologyCommand,
+};
 use crate::core::controller::Controller;
+use crate::core::failure::{self, FailureKind, FailureReport, ResultExt};
+use crate::core::logger::log_filter::LogFilter;
 use crate::core::logger::shadow_logger;
+use crate::core::selftest;
 use crate::core::sim_config::SimConfig;
+use crate::core::sweep;
 use crate::core::worker;
 use crate::cshadow as c;
+use crate::network::graph::generate;
 use crate::utility::shm_cleanup;
 
 use shadow_build_info::{BUILD_TIMESTAMP, GIT_BRANCH, GIT_COMMIT_INFO, GIT_DATE};
@@ -35,11 +49,21 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
 
     verify_glib_version().context("Unsupported GLib version")?;
 
-    let mut signals_list = Signals::new([consts::signal::SIGINT, consts::signal::SIGTERM])?;
+    let mut signals_list = Signals::new([
+        consts::signal::SIGINT,
+        consts::signal::SIGTERM,
+        consts::signal::SIGUSR1,
+    ])?;
     thread::spawn(move || {
-        // `next()` should block until we've received a signal, or `signals_list` is closed and
-        // `None` is returned
-        if let Some(signal) = signals_list.forever().next() {
+        // `forever()` should block until we've received a signal, or `signals_list` is closed and
+        // the iterator ends
+        for signal in signals_list.forever() {
+            if signal == consts::signal::SIGUSR1 {
+                log::info!("Received signal {signal}. Dumping the paths between hosts");
+                crate::core::routing_dump::request_dump();
+                continue;
+            }
+
             log::info!("Received signal {}. Flushing log and exiting", signal);
             log::logger().flush();
             std::process::exit(1);
@@ -63,7 +87,7 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
             e.print().unwrap();
             if e.use_stderr() {
                 // the `clap::Error` represents an error (ex: invalid flag)
-                std::process::exit(1);
+                std::process::exit(FailureKind::Config.exit_code());
             } else {
                 // the `clap::Error` represents a non-error, but we'll want to exit anyways (ex:
                 // '--help')
@@ -72,6 +96,10 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
         }
     };
 
+    if let Some(ref failure_file) = options.failure_file {
+        failure::set_failure_file(failure_file.clone());
+    }
+
     if options.show_build_info {
         write_build_info(std::io::stderr()).unwrap();
         std::process::exit(0);
@@ -84,16 +112,174 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
         std::process::exit(0);
     }
 
+    match options.command {
+        Some(Command::Selftest { ref test_dir }) => {
+            selftest::run(test_dir.as_deref())?;
+            std::process::exit(0);
+        }
+        Some(Command::SelftestTraffic { ref role }) => {
+            selftest::run_traffic(role)?;
+            std::process::exit(0);
+        }
+        Some(Command::Topology {
+            command: TopologyCommand::Generate { ref topology },
+        }) => {
+            let graph = generate::to_gml(topology)
+                .map_err(|e| anyhow::anyhow!(e))
+                .context("Failed to generate the network graph")?;
+            print!("{graph}");
+            std::process::exit(0);
+        }
+        // the other commands need the configuration file
+        _ => {}
+    }
+
+    if let Some(ref path) = options.resume {
+        // only returns if the checkpoint couldn't be resumed
+        checkpoint::resume(path)?;
+    }
+
+    let config_filename = match options.command {
+        Some(Command::Validate { ref config }) => config,
+        Some(Command::PrintConfig { ref config, .. }) => config,
+        Some(Command::Sweep { ref config, .. }) => config,
+        Some(Command::ConvertConfig { ref config }) => config,
+        _ => options.config.as_ref().unwrap(),
+    };
+
     // read from stdin if the config filename is given as '-'
-    let config_filename: String = match options.config.as_ref().unwrap().as_str() {
+    let config_filename: String = match config_filename.as_str() {
         "-" => "/dev/stdin",
         x => x,
     }
     .into();
 
+    if let Some(Command::ConvertConfig { .. }) = options.command {
+        let text = std::fs::read_to_string(&config_filename)
+            .with_context(|| format!("Failed to read configuration file {}", config_filename))
+            .failure_kind(FailureKind::Config)?;
+        let conversion = config_convert::convert(&text)
+            .with_context(|| format!("Failed to parse configuration file {}", config_filename))
+            .failure_kind(FailureKind::Config)?;
+
+        for note in &conversion.changes {
+            eprintln!("Converted '{}': {}", note.option, note.message);
+        }
+        for note in &conversion.unconvertible {
+            eprintln!("Could not convert '{}': {}", note.option, note.message);
+        }
+        if !conversion.formatting_preserved {
+            eprintln!("The comments and formatting of the configuration file were not preserved");
+        }
+        print!("{}", conversion.text);
+
+        let exit_code = if conversion.unconvertible.is_empty() {
+            0
+        } else {
+            FailureKind::Config.exit_code()
+        };
+        std::process::exit(exit_code);
+    }
+
+    // the variables defined on the command line take precedence over the environment variables
+    let defines: Vec<_> = config_variables::env_defines(&options.allowed_env)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Could not read the allowed environment variables")
+        .failure_kind(FailureKind::Config)?
+        .into_iter()
+        .chain(options.defines.iter().cloned())
+        .collect();
+
+    if let Some(Command::Validate { .. }) = options.command {
+        let config_file = load_config_file(
+            &config_filename,
+            options.config_format,
+            true,
+            &defines,
+            options.general.seed,
+            &options.overrides,
+        );
+        // stdin can't be read again, so the diagnostics won't have locations, and the locations
+        // are only found in YAML files
+        let format = options
+            .config_format
+            .unwrap_or_else(|| ConfigFormat::from_path(std::path::Path::new(&config_filename)));
+        let config_text = (config_filename != "/dev/stdin" && format == ConfigFormat::Yaml)
+            .then(|| std::fs::read_to_string(&config_filename).ok())
+            .flatten();
+        let report = config_validate::validate(config_file, &options, config_text.as_deref());
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        let exit_code = if report.valid {
+            0
+        } else {
+            FailureKind::Config.exit_code()
+        };
+        std::process::exit(exit_code);
+    }
+
+    if let Some(Command::Sweep {
+        ref parameters,
+        jobs,
+        ..
+    }) = options.command
+    {
+        // each point's process needs to read the configuration file again
+        if config_filename == "/dev/stdin" {
+            return Err(anyhow::anyhow!(
+                "The configuration can't be read from stdin when running a sweep"
+            ))
+            .failure_kind(FailureKind::Config);
+        }
+
+        // the configuration may require the sweep's variables, so the data directory is found
+        // using the variables of the first point
+        let first_point = sweep::points(parameters).swap_remove(0);
+        let point_defines: Vec<_> = defines.iter().cloned().chain(first_point).collect();
+        let config_file = load_config_file(
+            &config_filename,
+            options.config_format,
+            true,
+            &point_defines,
+            options.general.seed,
+            &options.overrides,
+        )
+        .with_context(|| format!("Failed to load configuration file {}", config_filename))
+        .failure_kind(FailureKind::Config)?;
+        let shadow_config = ConfigOptions::new(config_file, options.clone());
+        let data_dir = shadow_config.general.data_directory.unwrap();
+
+        sweep::run(
+            &args,
+            std::path::Path::new(&config_filename),
+            parameters,
+            std::path::Path::new(&data_dir),
+            jobs as usize,
+        )?;
+        std::process::exit(0);
+    }
+
     // load the configuration yaml
-    let config_file = load_config_file(&config_filename, true)
-        .with_context(|| format!("Failed to load configuration file {}", config_filename))?;
+    let config_file = load_config_file(
+        &config_filename,
+        options.config_format,
+        true,
+        &defines,
+        options.general.seed,
+        &options.overrides,
+    )
+    .with_context(|| format!("Failed to load configuration file {}", config_filename))
+    .failure_kind(FailureKind::Config)?;
+
+    if let Some(Command::PrintConfig { resolved, .. }) = options.command {
+        let yaml = if resolved {
+            serde_yaml::to_string(&ConfigOptions::new(config_file, options.clone()))
+        } else {
+            serde_yaml::to_string(&config_file)
+        }
+        .context("Failed to serialize the configuration")?;
+        print!("{yaml}");
+        return Ok(());
+    }
 
     // generate the final shadow configuration from the config file and cli options
     let shadow_config = ConfigOptions::new(config_file, options.clone());
@@ -116,7 +302,21 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
     let log_errors_to_stderr = shadow_config.experimental.log_errors_to_tty.unwrap()
         && !std::io::stdout().lock().is_terminal()
         && std::io::stderr().lock().is_terminal();
-    shadow_logger::init(log_level.to_level_filter(), log_errors_to_stderr).unwrap();
+    let log_filter = shadow_config
+        .general
+        .log_filter
+        .flatten_ref()
+        .map(|x| x.parse::<LogFilter>())
+        .transpose()
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse the log filter")
+        .failure_kind(FailureKind::Config)?;
+    shadow_logger::init(
+        log_level.to_level_filter(),
+        log_errors_to_stderr,
+        log_filter,
+    )
+    .unwrap();
 
     // disable log buffering during startup so that we see every message immediately in the terminal
     shadow_logger::set_buffering_enabled(false);
@@ -162,10 +362,14 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
     }
 
     // raise fd soft limit to hard limit
-    raise_rlimit(resource::Resource::RLIMIT_NOFILE).context("Could not raise fd limit")?;
+    raise_rlimit(resource::Resource::RLIMIT_NOFILE)
+        .context("Could not raise fd limit")
+        .failure_kind(FailureKind::ResourceLimit)?;
 
     // raise number of processes/threads soft limit to hard limit
-    raise_rlimit(resource::Resource::RLIMIT_NPROC).context("Could not raise proc limit")?;
+    raise_rlimit(resource::Resource::RLIMIT_NPROC)
+        .context("Could not raise proc limit")
+        .failure_kind(FailureKind::ResourceLimit)?;
 
     if shadow_config.experimental.use_sched_fifo.unwrap() {
         set_sched_fifo().context("Could not set real-time scheduler mode to SCHED_FIFO")?;
@@ -210,7 +414,8 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
     }
 
     let sim_config = SimConfig::new(&shadow_config, &options.debug_hosts.unwrap_or_default())
-        .context("Failed to initialize the simulation")?;
+        .context("Failed to initialize the simulation")
+        .failure_kind(FailureKind::Config)?;
 
     // allocate and initialize our main simulation driver
     let controller = Controller::new(sim_config, &shadow_config);
@@ -266,7 +471,7 @@ fn write_build_info(mut w: impl std::io::Write) -> std::io::Result<()> {
     Ok(())
 }
 
-fn verify_supported_system() -> anyhow::Result<()> {
+pub(crate) fn verify_supported_system() -> anyhow::Result<()> {
     let uts_name = nix::sys::utsname::uname()?;
     let sysname = uts_name
         .sysname()
@@ -350,25 +555,23 @@ fn verify_glib_version() -> anyhow::Result<()> {
 
 fn load_config_file(
     filename: impl AsRef<std::path::Path>,
+    format: Option<ConfigFormat>,
     extended_yaml: bool,
+    defines: &[(String, String)],
+    seed: Option<u32>,
+    overrides: &[ConfigOverride],
 ) -> anyhow::Result<ConfigFileOptions> {
-    let file = std::fs::File::open(filename).context("Could not open config file")?;
-
-    // serde's default behaviour is to silently ignore duplicate keys during deserialization so we
-    // would typically need to use serde_with's `maps_duplicate_key_is_error()` on our
-    // 'ConfigFileOptions' struct to prevent duplicate hostnames, but since we deserialize to
-    // serde_yaml's `Value` type initially we don't need to prevent duplicate keys as serde_yaml
-    // does this for us: https://github.com/dtolnay/serde-yaml/pull/301
-
-    let mut config_file: serde_yaml::Value =
-        serde_yaml::from_reader(file).context("Could not parse configuration file as yaml")?;
+    let mut config_file: serde_yaml::Value = if extended_yaml {
+        // merges the '<<' keys of each file before merging the included files
+        config_include::load_with_includes(filename.as_ref(), format)?
+    } else {
+        let text = std::fs::read_to_string(&filename).context("Could not read config file")?;
+        format
+            .unwrap_or_else(|| ConfigFormat::from_path(filename.as_ref()))
+            .parse(&text)?
+    };
 
     if extended_yaml {
-        // apply the merge before removing extension fields
-        config_file
-            .apply_merge()
-            .context("Could not merge '<<' keys")?;
-
         // remove top-level extension fields
         if let serde_yaml::Value::Mapping(ref mut mapping) = &mut config_file {
             // remove entries having a key beginning with "x-" (follows docker's convention:
@@ -382,6 +585,25 @@ fn load_config_file(
                 true
             });
         }
+
+        // the templates are applied before the variables are substituted, so that templates can
+        // use variables such as the index of a replicated host
+        config_templates::apply(&mut config_file)
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Could not apply host templates")?;
+
+        // the variables are substituted after the included files are merged, so that they can be
+        // defined in any file
+        // the command line's seed takes precedence over the config's, as it does for the simulation
+        config_variables::substitute(&mut config_file, defines, seed)
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Could not substitute variables")?;
+    }
+
+    for x in overrides {
+        x.apply(&mut config_file)
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("Could not set option '{}'", x.option()))?;
     }
 
     serde_yaml::from_value(config_file).context("Could not parse configuration file")
@@ -470,10 +692,28 @@ mod export {
         let args = (0..argc).map(|x| unsafe { CStr::from_ptr(*argv.add(x as usize)) });
         let args = args.map(|x| OsStr::from_bytes(x.to_bytes()));
 
-        let result = run_shadow(args.collect());
+        let args: Vec<&OsStr> = args.collect();
+
+        // a panic is an internal error, so catch it to report it with the corresponding exit code
+        // (the panic message has already been printed by the panic hook)
+        let result = std::panic::catch_unwind(move || run_shadow(args));
         log::logger().flush();
 
+        let result = match result {
+            Ok(x) => x,
+            Err(payload) => {
+                let report = FailureReport::from_panic(&*payload);
+                if let Err(e) = report.write() {
+                    eprintln!("** Could not write the failure file: {e:?}");
+                }
+                eprintln!("** Shadow did not complete successfully ({})", report.kind);
+                return report.exit_code;
+            }
+        };
+
         if let Err(e) = result {
+            let report = FailureReport::from_error(&e);
+
             // log the full error, its context, and its backtrace if enabled
             if log::log_enabled!(log::Level::Error) {
                 for line in format!("{:?}", e).split('\n') {
@@ -482,7 +722,10 @@ mod export {
                 log::logger().flush();
 
                 // print the short error
-                eprintln!("** Shadow did not complete successfully: {}", e);
+                eprintln!(
+                    "** Shadow did not complete successfully ({}): {}",
+                    report.kind, e
+                );
                 eprintln!("**   {}", e.root_cause());
                 eprintln!("** See the log for details");
             } else {
@@ -490,7 +733,11 @@ mod export {
                 eprintln!("{:?}", e);
             }
 
-            return 1;
+            if let Err(e) = report.write() {
+                eprintln!("** Could not write the failure file: {e:?}");
+            }
+
+            return report.exit_code;
         }
 
         eprintln!("** Shadow completed successfully");
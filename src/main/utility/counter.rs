@@ -81,6 +81,17 @@ impl Counter {
         }
     }
 
+    /// Returns true if all keys have a value of 0.
+    pub fn is_empty(&self) -> bool {
+        // keys are removed when their value reaches 0
+        self.items.is_empty()
+    }
+
+    /// Get an iterator over all keys with a non-zero value, in an arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.items.iter().map(|(k, v)| (k.as_str(), *v))
+    }
+
     /// Add all values for all keys in `other` to this counter.
     pub fn add_counter(&mut self, other: &Counter) {
         for (key, val) in other.items.iter() {
@@ -561,4 +572,27 @@ mod tests {
             String::from("{close:1, read:1, write:1}")
         );
     }
+
+    #[test]
+    fn test_is_empty() {
+        let mut counter = Counter::new();
+        assert!(counter.is_empty());
+        counter.add_one("read");
+        assert!(!counter.is_empty());
+        counter.sub_one("read");
+        assert!(counter.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut counter = Counter::new();
+        counter.add_value("read", 2);
+        counter.sub_one("write");
+        counter.add_one("close");
+        counter.sub_one("close");
+
+        let mut items: Vec<_> = counter.iter().collect();
+        items.sort();
+        assert_eq!(items, vec![("read", 2), ("write", -1)]);
+    }
 }
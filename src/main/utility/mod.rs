@@ -244,6 +244,16 @@ pub fn tilde_expansion(path: &str) -> std::path::PathBuf {
 /// Copy the contents of the `src` directory to a new directory named `dst`. Permissions will be
 /// preserved.
 pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
+    copy_dir(src.as_ref(), dst.as_ref(), false)
+}
+
+/// Copy the contents of the `src` directory into the `dst` directory, which is created if it
+/// doesn't exist. Files that already exist in `dst` are replaced. Permissions will be preserved.
+pub fn copy_dir_into(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
+    copy_dir(src.as_ref(), dst.as_ref(), true)
+}
+
+fn copy_dir(src: &Path, dst: &Path, merge: bool) -> std::io::Result<()> {
     // a directory to copy
     struct DirCopyTask {
         src: PathBuf,
@@ -255,14 +265,17 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Re
     let mut stack: Vec<DirCopyTask> = vec![];
 
     stack.push(DirCopyTask {
-        src: src.as_ref().to_path_buf(),
-        dst: dst.as_ref().to_path_buf(),
-        mode: src.as_ref().metadata()?.mode(),
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        mode: src.metadata()?.mode(),
     });
 
     while let Some(DirCopyTask { src, dst, mode }) = stack.pop() {
-        // create the directory with the same permissions
-        create_dir_with_mode(&dst, mode)?;
+        // create the directory with the same permissions, unless merging into an existing directory
+        match create_dir_with_mode(&dst, mode) {
+            Err(e) if merge && e.kind() == std::io::ErrorKind::AlreadyExists && dst.is_dir() => {}
+            x => x?,
+        }
 
         // copy directory contents
         for entry in std::fs::read_dir(src)? {
@@ -322,6 +335,18 @@ pub fn i8_to_u8_slice(s: &[i8]) -> &[u8] {
     unsafe { std::slice::from_raw_parts(s.as_ptr() as *const u8, s.len()) }
 }
 
+/// Release excess capacity from `map` if it's using less than a quarter of its capacity. Maps
+/// that grow during a burst of activity (for example many short-lived sockets) otherwise keep
+/// their peak capacity for the rest of the simulation.
+pub fn shrink_if_sparse<K: std::hash::Hash + Eq, V>(map: &mut std::collections::HashMap<K, V>) {
+    // don't bother with small maps
+    const MIN_CAPACITY: usize = 64;
+
+    if map.capacity() > MIN_CAPACITY && map.len() < map.capacity() / 4 {
+        map.shrink_to(std::cmp::max(map.len() * 2, MIN_CAPACITY));
+    }
+}
+
 /// Returns `true` if [`eq_ignore_ascii_case`](u8::eq_ignore_ascii_case) returns `true` on all `u8`
 /// ascii pairs. Should only be used for ascii byte strings.
 pub fn case_insensitive_eq(a: &[u8], b: &[u8]) -> bool {
@@ -538,6 +563,22 @@ pub fn debug_assert_cloexec(file: &(impl std::os::fd::AsRawFd + std::fmt::Debug)
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_shrink_if_sparse() {
+        let mut map: std::collections::HashMap<u32, u32> = (0..1000).map(|x| (x, x)).collect();
+        let peak_capacity = map.capacity();
+
+        // a full map isn't shrunk
+        shrink_if_sparse(&mut map);
+        assert_eq!(map.capacity(), peak_capacity);
+
+        map.retain(|k, _| *k < 10);
+        shrink_if_sparse(&mut map);
+        assert!(map.capacity() < peak_capacity);
+        assert!(map.capacity() >= 64);
+        assert_eq!(map.len(), 10);
+    }
+
     #[test]
     fn test_tilde_expansion() {
         if let Ok(ref home) = std::env::var("HOME") {
@@ -575,6 +616,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_copy_dir_into() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        std::fs::create_dir_all(src.join("keys")).unwrap();
+        std::fs::write(src.join("keys/key"), "new").unwrap();
+        std::fs::write(src.join("config"), "config").unwrap();
+        std::fs::create_dir_all(dst.join("keys")).unwrap();
+        std::fs::write(dst.join("keys/key"), "old").unwrap();
+        std::fs::write(dst.join("other"), "other").unwrap();
+
+        // copying to an existing directory fails unless merging
+        assert!(copy_dir_all(&src, &dst).is_err());
+
+        copy_dir_into(&src, &dst).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst.join("keys/key")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dst.join("config")).unwrap(),
+            "config"
+        );
+        assert_eq!(std::fs::read_to_string(dst.join("other")).unwrap(), "other");
+
+        copy_dir_into(&src, dir.path().join("new")).unwrap();
+        assert!(dir.path().join("new/keys/key").exists());
+    }
+
     #[test]
     fn test_inject_preloads() {
         // Base case
@@ -1,6 +1,12 @@
-use std::io::{Seek, SeekFrom, Write};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
 
+use crate::core::configuration::OutputLimitAction;
+use crate::core::worker::Worker;
 use crate::cshadow as c;
+use crate::network::pcap_filter::{FilterPacket, FilterProtocol, PcapFilter};
 use crate::utility::give::Give;
 
 pub struct PcapWriter<W: Write> {
@@ -147,48 +153,169 @@ pub trait PacketDisplay {
     fn display_bytes(&self, writer: impl Write) -> std::io::Result<()>;
 }
 
-mod export {
-    use std::ffi::{CStr, OsStr};
-    use std::fs::File;
-    use std::io::BufWriter;
-    use std::os::unix::ffi::OsStrExt;
+/// The pcap file of a network interface, which only captures the packets that match its filter and
+/// stops capturing packets when the file reaches its maximum size.
+pub struct PacketCapture {
+    writer: PcapWriter<BufWriter<File>>,
+    path: PathBuf,
+    filter: Option<PcapFilter>,
+    max_size: Option<u64>,
+    /// What to do when the file reaches its maximum size.
+    limit_action: OutputLimitAction,
+    /// The size of the file, including the bytes that are still buffered.
+    size: u64,
+    /// Whether the file reached its maximum size and no more packets are captured.
+    full: bool,
+}
 
-    use super::*;
+impl PacketCapture {
+    /// The size of the pcap file header.
+    const FILE_HEADER_LEN: u64 = 24;
+    /// The size of the header of each packet record.
+    const RECORD_HEADER_LEN: u64 = 16;
 
-    /// A new packet capture writer. Each packet (header and payload) captured will be truncated to
+    /// Create a pcap file at `path`. Each packet (header and payload) captured will be truncated to
     /// a length `capture_len`.
-    #[no_mangle]
-    pub extern "C-unwind" fn pcapwriter_new(
-        path: *const libc::c_char,
+    pub fn new(
+        path: PathBuf,
         capture_len: u32,
-    ) -> *mut PcapWriter<BufWriter<File>> {
-        assert!(!path.is_null());
-        let path = OsStr::from_bytes(unsafe { CStr::from_ptr(path) }.to_bytes());
-
-        let file = match File::create(path) {
-            Ok(f) => f,
-            Err(e) => {
-                log::warn!("Could not create pcap file: {}", e);
-                return std::ptr::null_mut();
+        filter: Option<PcapFilter>,
+        max_size: Option<u64>,
+        limit_action: OutputLimitAction,
+    ) -> std::io::Result<Self> {
+        let file = BufWriter::new(File::create(&path)?);
+        Ok(Self {
+            writer: PcapWriter::new(file, capture_len)?,
+            path,
+            filter,
+            max_size,
+            limit_action,
+            size: Self::FILE_HEADER_LEN,
+            full: false,
+        })
+    }
+
+    /// Move the file to "<path>.1" and continue capturing to a new file at its path.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.writer.writer.flush()?;
+
+        let mut rotated_path = self.path.clone().into_os_string();
+        rotated_path.push(".1");
+        std::fs::rename(&self.path, rotated_path)?;
+
+        let file = BufWriter::new(File::create(&self.path)?);
+        self.writer = PcapWriter::new(file, self.writer.capture_len)?;
+        self.size = Self::FILE_HEADER_LEN;
+
+        Ok(())
+    }
+
+    /// Write the packet if it matches the filter and there's room for it in the file.
+    fn write_packet(
+        &mut self,
+        ts_sec: u32,
+        ts_usec: u32,
+        packet: *const c::Packet,
+    ) -> std::io::Result<()> {
+        if self.full {
+            return Ok(());
+        }
+
+        if let Some(filter) = &self.filter {
+            if !filter.matches(&filter_packet(packet)) {
+                return Ok(());
             }
-        };
-        let file = BufWriter::new(file);
-        Box::into_raw(Box::new(PcapWriter::new(file, capture_len).unwrap()))
+        }
+
+        let packet_len: u32 = u32::try_from(unsafe { c::packet_getTotalSize(packet) }).unwrap();
+        let record_len =
+            Self::RECORD_HEADER_LEN + u64::from(std::cmp::min(packet_len, self.writer.capture_len));
+
+        if let Some(max) = self.max_size.filter(|max| self.size + record_len > *max) {
+            match self.limit_action {
+                // a packet that doesn't fit in an empty file is skipped
+                OutputLimitAction::Rotate if Self::FILE_HEADER_LEN + record_len > max => {
+                    return Ok(());
+                }
+                OutputLimitAction::Rotate => {
+                    if let Err(e) = self.rotate() {
+                        self.full = true;
+                        return Err(e);
+                    }
+                }
+                OutputLimitAction::Truncate => {
+                    log::info!(
+                        "Pcap file '{}' reached its maximum size; no longer capturing packets",
+                        self.path.display(),
+                    );
+                    self.full = true;
+                    return Ok(());
+                }
+                OutputLimitAction::Fail => {
+                    Worker::fail_output_limit(&self.path);
+                    self.full = true;
+                    return Ok(());
+                }
+            }
+        }
+
+        if !Worker::add_output_bytes(record_len) {
+            self.full = true;
+            return Ok(());
+        }
+
+        self.writer
+            .write_packet_fmt(ts_sec, ts_usec, packet_len, |writer| {
+                packet.display_bytes(writer)
+            })?;
+        self.size += record_len;
+
+        Ok(())
     }
+}
+
+/// The fields of the packet that capture filters match.
+fn filter_packet(packet: *const c::Packet) -> FilterPacket {
+    let addr = |ip: libc::in_addr_t, port: libc::in_port_t| {
+        SocketAddrV4::new(Ipv4Addr::from(u32::from_be(ip)), u16::from_be(port))
+    };
+
+    unsafe {
+        FilterPacket {
+            protocol: match c::packet_getProtocol(packet) {
+                c::_ProtocolType_PTCP => Some(FilterProtocol::Tcp),
+                c::_ProtocolType_PUDP => Some(FilterProtocol::Udp),
+                c::_ProtocolType_PICMP => Some(FilterProtocol::Icmp),
+                _ => None,
+            },
+            src: addr(
+                c::packet_getSourceIP(packet),
+                c::packet_getSourcePort(packet),
+            ),
+            dst: addr(
+                c::packet_getDestinationIP(packet),
+                c::packet_getDestinationPort(packet),
+            ),
+        }
+    }
+}
+
+mod export {
+    use super::*;
 
     #[no_mangle]
-    pub extern "C-unwind" fn pcapwriter_free(pcap: *mut PcapWriter<BufWriter<File>>) {
+    pub extern "C-unwind" fn packetcapture_free(pcap: *mut PacketCapture) {
         if pcap.is_null() {
             return;
         }
         drop(unsafe { Box::from_raw(pcap) });
     }
 
-    /// If there's an error, returns 1. Otherwise returns 0. If there's an error, the pcap file is
-    /// likely to be corrupt.
+    /// Write the packet if it matches the capture's filter. If there's an error, returns 1.
+    /// Otherwise returns 0. If there's an error, the pcap file is likely to be corrupt.
     #[no_mangle]
-    pub extern "C-unwind" fn pcapwriter_writePacket(
-        pcap: *mut PcapWriter<BufWriter<File>>,
+    pub extern "C-unwind" fn packetcapture_writePacket(
+        pcap: *mut PacketCapture,
         ts_sec: u32,
         ts_usec: u32,
         packet: *const c::Packet,
@@ -198,11 +325,7 @@ mod export {
 
         let pcap = unsafe { pcap.as_mut() }.unwrap();
 
-        let packet_len: u32 = u32::try_from(unsafe { c::packet_getTotalSize(packet) }).unwrap();
-
-        if let Err(e) = pcap.write_packet_fmt(ts_sec, ts_usec, packet_len, |writer| {
-            packet.display_bytes(writer)
-        }) {
+        if let Err(e) = pcap.write_packet(ts_sec, ts_usec, packet) {
             log::warn!("Unable to write packet to pcap output: {}", e);
             return 1;
         }
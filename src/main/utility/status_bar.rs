@@ -5,7 +5,6 @@ use std::time::Duration;
 
 const SAVE_CURSOR: &str = "\u{1B}[s";
 const RESTORE_CURSOR: &str = "\u{1B}[u";
-const NEXT_LINE: &str = "\u{1B}[1E";
 const PREV_LINE: &str = "\u{1B}[1F";
 const CLEAR: &str = "\u{1B}[K";
 const RESTORE_SCROLL_REGION: &str = "\u{1B}[r";
@@ -36,6 +35,10 @@ impl<T: 'static + StatusBarState> StatusBar<T> {
     }
 
     fn redraw_loop(state: Arc<Status<T>>, stop_flag: Arc<AtomicBool>, redraw_interval: Duration) {
+        // the number of rows reserved at the bottom of the terminal; this never shrinks so that
+        // we don't leave stale lines behind if the status gets shorter
+        let mut num_lines: u16 = 1;
+
         // we re-draw the status bar every interval, even if the state hasn't changed, since the
         // terminal might have been resized and the scroll region might have been reset
         while !stop_flag.load(std::sync::atomic::Ordering::Acquire) {
@@ -48,24 +51,43 @@ impl<T: 'static + StatusBarState> StatusBar<T> {
                 }
             };
 
-            if rows > 1 {
+            let status = format!("{}", *state.inner.read().unwrap());
+            let lines: Vec<&str> = status.split('\n').collect();
+            num_lines = std::cmp::max(num_lines, lines.len().try_into().unwrap_or(u16::MAX));
+
+            // always leave at least one row for the scroll region
+            let num_lines_shown = std::cmp::min(num_lines, rows.saturating_sub(1));
+
+            if num_lines_shown > 0 {
+                // the first row of the status bar
+                let first_row = rows - num_lines_shown + 1;
+
+                // draw each line of the status, clearing any unused rows
+                let status_lines: String = (0..num_lines_shown)
+                    .map(|i| {
+                        let line = lines.get(usize::from(i)).unwrap_or(&"");
+                        format!("\u{1B}[{};1H{line}{CLEAR}", first_row + i)
+                    })
+                    .collect();
+
                 #[rustfmt::skip]
                 let to_print = [
                     // Restore the scroll region since some terminals handle scroll regions
                     // differently. For example, when using '{next_line}' some terminals will
                     // allow the cursor to move outside of the scroll region, and others don't.
                     SAVE_CURSOR, RESTORE_SCROLL_REGION, RESTORE_CURSOR,
-                    // This will scroll the buffer up only if the cursor is on the last row.
-                    SAVE_CURSOR, "\n", RESTORE_CURSOR,
-                    // This will move the cursor up only if the cursor is on the last row (to
-                    // match the previous scroll behaviour).
-                    NEXT_LINE, PREV_LINE,
+                    // This will scroll the buffer up only if the cursor is within the rows used
+                    // by the status bar.
+                    SAVE_CURSOR, &"\n".repeat(num_lines_shown.into()), RESTORE_CURSOR,
+                    // This will move the cursor up only if the cursor is within the rows used by
+                    // the status bar (to match the previous scroll behaviour).
+                    &format!("\u{1B}[{num_lines_shown}E"), &format!("\u{1B}[{num_lines_shown}F"),
                     // The cursor is currently at the correct location, so save it for later.
                     SAVE_CURSOR,
-                    // Set the scroll region to include all rows but the last.
-                    &format!("\u{1B}[1;{}r", rows - 1),
-                    // Move to the last row and write the message.
-                    LAST_LINE, &format!("{}", *state.inner.read().unwrap()), CLEAR,
+                    // Set the scroll region to include all rows but the status bar's rows.
+                    &format!("\u{1B}[1;{}r", first_row - 1),
+                    // Write the status in the rows below the scroll region.
+                    &status_lines,
                     // Restore the cursor position.
                     RESTORE_CURSOR,
                 ]
@@ -80,11 +102,20 @@ impl<T: 'static + StatusBarState> StatusBar<T> {
             std::thread::sleep(redraw_interval);
         }
 
+        // clear the status bar's rows, starting from the last row
+        let clear_lines: String = (0..num_lines)
+            .map(|i| {
+                if i == 0 {
+                    format!("{LAST_LINE}{CLEAR}")
+                } else {
+                    format!("{PREV_LINE}{CLEAR}")
+                }
+            })
+            .collect();
+
         let to_print = format!(
-            "{save_cursor}{last_line}{clear}{restore_scroll_region}{restore_cursor}",
+            "{save_cursor}{clear_lines}{restore_scroll_region}{restore_cursor}",
             save_cursor = SAVE_CURSOR,
-            last_line = LAST_LINE,
-            clear = CLEAR,
             restore_scroll_region = RESTORE_SCROLL_REGION,
             restore_cursor = RESTORE_CURSOR,
         );
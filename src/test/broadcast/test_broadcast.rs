@@ -0,0 +1,106 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests broadcast datagrams for UDP sockets. The "sender" and "peer" hosts are attached to the
+//! same graph node (network segment), and the "remote" host is attached to a different node.
+
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+const PORT: u16 = 8000;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("sender") => sender(),
+        Some("peer") => peer(),
+        Some("remote") => remote(),
+        _ => anyhow::bail!("Expected 'sender', 'peer', or 'remote' argument"),
+    }
+}
+
+/// Bind to the port, and return the socket after the sender has sent its datagrams.
+fn bind_and_wait() -> anyhow::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.set_nonblocking(true)?;
+
+    // the sender starts one second after us
+    std::thread::sleep(Duration::from_millis(2500));
+
+    Ok(socket)
+}
+
+/// Is on the same network segment, so receives the broadcasts.
+fn peer() -> anyhow::Result<()> {
+    let socket = bind_and_wait()?;
+
+    assert_eq!(recv(&socket)?, b"limited");
+    assert_eq!(recv(&socket)?, b"subnet");
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Is on a different network segment, so receives nothing.
+fn remote() -> anyhow::Result<()> {
+    let socket = bind_and_wait()?;
+
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    println!("Success.");
+    Ok(())
+}
+
+fn sender() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.set_nonblocking(true)?;
+
+    // broadcasts require `SO_BROADCAST`
+    assert!(!socket.broadcast()?);
+    assert_eq!(
+        errno(socket.send_to(b"denied", (Ipv4Addr::BROADCAST, PORT))),
+        Err(Errno::EACCES)
+    );
+    assert_eq!(
+        errno(socket.connect((Ipv4Addr::BROADCAST, PORT))),
+        Err(Errno::EACCES)
+    );
+
+    socket.set_broadcast(true)?;
+    assert!(socket.broadcast()?);
+
+    // the sending host also receives its own broadcasts
+    socket.send_to(b"limited", (Ipv4Addr::BROADCAST, PORT))?;
+    assert_eq!(recv(&socket)?, b"limited");
+
+    let SocketAddr::V4(addr) = ("sender", PORT).to_socket_addrs()?.next().unwrap() else {
+        anyhow::bail!("Expected an IPv4 address");
+    };
+    let subnet_broadcast = Ipv4Addr::from(u32::from(*addr.ip()) | 0xff);
+
+    socket.send_to(b"subnet", (subnet_broadcast, PORT))?;
+    assert_eq!(recv(&socket)?, b"subnet");
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Receive a datagram after giving it time to arrive.
+fn recv(socket: &UdpSocket) -> Result<Vec<u8>, Errno> {
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut buf = vec![0u8; 1024];
+    let (len, src) = errno(socket.recv_from(&mut buf))?;
+    assert_eq!(src.port(), PORT);
+
+    buf.truncate(len);
+    Ok(buf)
+}
+
+fn errno<T>(result: std::io::Result<T>) -> Result<T, Errno> {
+    result.map_err(|e| Errno::from_i32(e.raw_os_error().unwrap()))
+}
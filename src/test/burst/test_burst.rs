@@ -0,0 +1,67 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the bandwidth burst host options. The "sender" host has a bandwidth of 1 Mbit with a
+//! 20 KB burst at up to 10 Mbit, so the first batch of datagrams is sent as a fast burst and the
+//! second batch is limited to the sustained bandwidth.
+
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+const PORT: u16 = 8000;
+const BATCH_LEN: usize = 20;
+const DATAGRAM_LEN: usize = 1000;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("sender") => sender(),
+        Some("receiver") => receiver(),
+        _ => anyhow::bail!("Expected 'sender' or 'receiver' argument"),
+    }
+}
+
+fn receiver() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    // the first batch fits in the burst, so is sent at the 10 Mbit peak rate (about 16 ms)
+    let duration = recv_batch(&socket)?;
+    assert!(duration > Duration::from_millis(10), "{duration:?}");
+    assert!(duration < Duration::from_millis(50), "{duration:?}");
+
+    // the burst was used up, so the second batch is sent at the 1 Mbit rate (about 160 ms)
+    let duration = recv_batch(&socket)?;
+    assert!(duration > Duration::from_millis(100), "{duration:?}");
+
+    println!("Success.");
+    Ok(())
+}
+
+fn sender() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+
+    for _ in 0..(2 * BATCH_LEN) {
+        assert_eq!(
+            socket.send_to(&[0u8; DATAGRAM_LEN], ("receiver", PORT))?,
+            DATAGRAM_LEN
+        );
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Receive a batch of datagrams, and return the time between the first and last datagrams.
+fn recv_batch(socket: &UdpSocket) -> anyhow::Result<Duration> {
+    let mut buf = vec![0u8; DATAGRAM_LEN];
+    let mut start = None;
+
+    for _ in 0..BATCH_LEN {
+        assert_eq!(socket.recv(&mut buf)?, DATAGRAM_LEN);
+        start.get_or_insert_with(Instant::now);
+    }
+
+    Ok(start.unwrap().elapsed())
+}
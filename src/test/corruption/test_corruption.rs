@@ -0,0 +1,85 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests packet corruption on network graph edges. The edge between the "sender" and "noisy" hosts
+//! corrupts every packet, and the edge between the "sender" and "clean" hosts corrupts none.
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+const PORT: u16 = 8000;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("sender") => sender(),
+        Some("clean") => clean(),
+        Some("noisy") => noisy(),
+        _ => anyhow::bail!("Expected 'sender', 'clean', or 'noisy' argument"),
+    }
+}
+
+/// Bind to the port, and return the socket after the sender has sent its datagrams.
+fn bind_and_wait() -> anyhow::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.set_nonblocking(true)?;
+
+    // the sender starts one second after us
+    std::thread::sleep(Duration::from_millis(2500));
+
+    Ok(socket)
+}
+
+/// Receives the datagrams.
+fn clean() -> anyhow::Result<()> {
+    let socket = bind_and_wait()?;
+
+    for _ in 0..10 {
+        assert_eq!(recv(&socket)?, b"hello");
+    }
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Every datagram is corrupted and dropped, so receives nothing.
+fn noisy() -> anyhow::Result<()> {
+    let socket = bind_and_wait()?;
+
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    println!("Success.");
+    Ok(())
+}
+
+fn sender() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+
+    for _ in 0..10 {
+        // the sender doesn't know that the datagrams will be corrupted
+        assert_eq!(socket.send_to(b"hello", ("clean", PORT))?, 5);
+        assert_eq!(socket.send_to(b"hello", ("noisy", PORT))?, 5);
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Receive a datagram after giving it time to arrive.
+fn recv(socket: &UdpSocket) -> Result<Vec<u8>, Errno> {
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut buf = vec![0u8; 1024];
+    let (len, _src) = errno(socket.recv_from(&mut buf))?;
+
+    buf.truncate(len);
+    Ok(buf)
+}
+
+fn errno<T>(result: std::io::Result<T>) -> Result<T, Errno> {
+    result.map_err(|e| Errno::from_i32(e.raw_os_error().unwrap()))
+}
@@ -13,6 +13,7 @@
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <sys/auxv.h>
 #include <sys/types.h>
 #include <syscall.h>
 #include <unistd.h>
@@ -86,6 +87,38 @@ static int _test_open() {
     return EXIT_SUCCESS;
 }
 
+static int _test_getrandom() {
+    /* this should draw from the same deterministic source as the random devices */
+    unsigned char buf[1];
+    buf[0] = 0;
+
+    // getrandom() was only added in glibc 2.25
+    long sz = syscall(SYS_getrandom, buf, 1, 0);
+    if(sz != 1) {
+        return EXIT_FAILURE;
+    }
+
+    fprintf(stdout, "getrandom\t: %X\n", *buf);
+
+    return EXIT_SUCCESS;
+}
+
+static int _test_atRandom() {
+    /* shadow replaces the random bytes that the kernel gives to each new program */
+    const unsigned char* buf = (const unsigned char*)getauxval(AT_RANDOM);
+    if(buf == NULL) {
+        return EXIT_FAILURE;
+    }
+
+    fprintf(stdout, "AT_RANDOM\t: ");
+    for(int i = 0; i < 16; i++) {
+        fprintf(stdout, "%02X", buf[i]);
+    }
+    fprintf(stdout, "\n");
+
+    return EXIT_SUCCESS;
+}
+
 typedef struct _ThreadPIDs ThreadPIDs;
 struct _ThreadPIDs {
     int pid;
@@ -223,6 +256,20 @@ int main(int argc, char* argv[]) {
     }
     fprintf(stdout, "_test_fopen() passed\n");
 
+    fprintf(stdout, "starting _test_getrandom()\n");
+    if (_test_getrandom() != EXIT_SUCCESS) {
+        fprintf(stdout, "########## _test_getrandom() failed\n");
+        return EXIT_FAILURE;
+    }
+    fprintf(stdout, "_test_getrandom() passed\n");
+
+    fprintf(stdout, "starting _test_atRandom()\n");
+    if (_test_atRandom() != EXIT_SUCCESS) {
+        fprintf(stdout, "########## _test_atRandom() failed\n");
+        return EXIT_FAILURE;
+    }
+    fprintf(stdout, "_test_atRandom() passed\n");
+
     fprintf(stdout, "starting _test_getPID()\n");
     if (_test_getPID() < 0) {
         fprintf(stdout, "########## _test_getPID() failed\n");
@@ -0,0 +1,105 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the built-in DNS server. The server has an A record for the "server" host, a CNAME record
+//! that points to it, and an SRV record.
+
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(11, 0, 0, 10);
+
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_SRV: u16 = 33;
+
+fn main() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    socket.connect(("10.53.0.1", 53))?;
+
+    // the CNAME record is followed to the host's A record, and each record has its own TTL
+    let (rcode, answers) = query(&socket, "www.example.com", TYPE_A)?;
+    assert_eq!(rcode, 0);
+    assert_eq!(
+        answers,
+        [
+            (TYPE_CNAME, 10, encode_name("server")),
+            (TYPE_A, 60, SERVER_IP.octets().to_vec()),
+        ]
+    );
+
+    let (rcode, answers) = query(&socket, "_http._tcp.example.com", TYPE_SRV)?;
+    assert_eq!(rcode, 0);
+    let mut srv = vec![0, 0, 0, 0, 0, 80];
+    srv.extend(encode_name("server"));
+    assert_eq!(answers, [(TYPE_SRV, 60, srv)]);
+
+    // NXDOMAIN
+    let (rcode, answers) = query(&socket, "missing.example.com", TYPE_A)?;
+    assert_eq!(rcode, 3);
+    assert!(answers.is_empty());
+
+    // names that aren't in /etc/hosts are resolved by libc using the server
+    let addrs: Vec<_> = ("www.example.com", 80).to_socket_addrs()?.collect();
+    assert_eq!(addrs, [SocketAddr::from((SERVER_IP, 80))]);
+    assert!(("missing.example.com", 80).to_socket_addrs().is_err());
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Send a query, and return the response code and the type, TTL, and data of each answer.
+fn query(
+    socket: &UdpSocket,
+    name: &str,
+    qtype: u16,
+) -> anyhow::Result<(u16, Vec<(u16, u32, Vec<u8>)>)> {
+    let mut query = vec![0xab, 0xcd, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    query.extend(encode_name(name));
+    query.extend(qtype.to_be_bytes());
+    query.extend(1u16.to_be_bytes());
+    socket.send(&query)?;
+
+    let mut buf = [0u8; 512];
+    let len = socket.recv(&mut buf)?;
+    let response = &buf[..len];
+
+    assert_eq!(response[..2], query[..2]);
+    // the response must have the question, and have the "response" and "authoritative" flags
+    assert_eq!(response[12..query.len()], query[12..]);
+    assert_eq!(response[2] & 0x84, 0x84);
+
+    let rcode = u16::from(response[3] & 0xf);
+    let ancount = u16::from_be_bytes([response[6], response[7]]);
+
+    let mut pos = query.len();
+    let mut answers = vec![];
+    for _ in 0..ancount {
+        // skip the uncompressed name
+        while response[pos] != 0 {
+            pos += usize::from(response[pos]) + 1;
+        }
+        let fields = &response[(pos + 1)..(pos + 11)];
+        let rtype = u16::from_be_bytes([fields[0], fields[1]]);
+        let ttl = u32::from_be_bytes(fields[4..8].try_into()?);
+        let len = usize::from(u16::from_be_bytes([fields[8], fields[9]]));
+        pos += 11;
+        answers.push((rtype, ttl, response[pos..(pos + len)].to_vec()));
+        pos += len;
+    }
+
+    Ok((rcode, answers))
+}
+
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut buf = vec![];
+    for label in name.split('.') {
+        buf.push(label.len().try_into().unwrap());
+        buf.extend(label.as_bytes());
+    }
+    buf.push(0);
+    buf
+}
@@ -12,9 +12,11 @@
 #include <libgen.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <stdint.h>
 #include <string.h>
 #include <sys/ioctl.h>
 #include <sys/stat.h>
+#include <sys/syscall.h>
 #include <sys/types.h>
 #include <sys/uio.h>
 #include <termios.h>
@@ -100,6 +102,85 @@ static void _test_openat() {
     close(fd); // not testing close yet so don't assert here
 }
 
+// The same layout as `struct open_how`, which isn't defined by older headers.
+typedef struct {
+    uint64_t flags;
+    uint64_t mode;
+    uint64_t resolve;
+} OpenHow;
+
+#ifndef SYS_openat2
+#define SYS_openat2 437
+#endif
+#ifndef RESOLVE_NO_SYMLINKS
+#define RESOLVE_NO_SYMLINKS 0x04
+#define RESOLVE_BENEATH 0x08
+#define RESOLVE_IN_ROOT 0x10
+#endif
+
+static int _openat2(int dirfd, const char* pathname, uint64_t flags, uint64_t mode,
+                    uint64_t resolve) {
+    OpenHow how = {.flags = flags, .mode = mode, .resolve = resolve};
+    return syscall(SYS_openat2, dirfd, pathname, &how, sizeof(how));
+}
+
+static void _test_openat2() {
+    g_auto(AutoDeleteFile) adf = _create_auto_dir();
+    const char wbuf[] = "openat2";
+    char rbuf[sizeof(wbuf)] = {0};
+    int fd;
+
+    // create a file beneath the directory
+    assert_nonneg_errno(
+        fd = _openat2(adf.fd, "file", O_RDWR | O_CREAT | O_EXCL, 0600, RESOLVE_BENEATH));
+    assert_nonneg_errno(write(fd, wbuf, sizeof(wbuf)));
+    assert_nonneg_errno(close(fd));
+
+    assert_nonneg_errno(
+        fd = _openat2(adf.fd, "file", O_RDONLY, 0, RESOLVE_BENEATH | RESOLVE_NO_SYMLINKS));
+    assert_nonneg_errno(read(fd, rbuf, sizeof(rbuf)));
+    g_assert_cmpstr(rbuf, ==, wbuf);
+    assert_nonneg_errno(close(fd));
+
+    // the path can't escape the directory
+    g_assert_cmpint(_openat2(adf.fd, "..", O_RDONLY | O_DIRECTORY, 0, RESOLVE_BENEATH), ==, -1);
+    assert_errno_is(EXDEV);
+    g_assert_cmpint(_openat2(adf.fd, "/etc/hosts", O_RDONLY, 0, RESOLVE_BENEATH), ==, -1);
+    assert_errno_is(EXDEV);
+
+    // symlinks are only followed if allowed
+    assert_nonneg_errno(symlinkat("file", adf.fd, "link"));
+    g_assert_cmpint(_openat2(adf.fd, "link", O_RDONLY, 0, RESOLVE_NO_SYMLINKS), ==, -1);
+    assert_errno_is(ELOOP);
+    assert_nonneg_errno(fd = _openat2(adf.fd, "link", O_RDONLY, 0, RESOLVE_BENEATH));
+    assert_nonneg_errno(close(fd));
+
+    // a mode is only allowed when creating a file
+    g_assert_cmpint(_openat2(adf.fd, "file", O_RDONLY, 0600, 0), ==, -1);
+    assert_errno_is(EINVAL);
+
+    // incompatible and unknown resolve flags
+    g_assert_cmpint(
+        _openat2(adf.fd, "file", O_RDONLY, 0, RESOLVE_BENEATH | RESOLVE_IN_ROOT), ==, -1);
+    assert_errno_is(EINVAL);
+    g_assert_cmpint(_openat2(adf.fd, "file", O_RDONLY, 0, 1ull << 40), ==, -1);
+    assert_errno_is(EINVAL);
+
+    // the struct can't be smaller than the original version, and can only be larger if the
+    // unknown fields are zero
+    uint64_t how[4] = {O_RDONLY, 0, 0, 0};
+    g_assert_cmpint(syscall(SYS_openat2, adf.fd, "file", how, sizeof(OpenHow) - 1), ==, -1);
+    assert_errno_is(EINVAL);
+    assert_nonneg_errno(fd = syscall(SYS_openat2, adf.fd, "file", how, sizeof(how)));
+    assert_nonneg_errno(close(fd));
+    how[3] = 1;
+    g_assert_cmpint(syscall(SYS_openat2, adf.fd, "file", how, sizeof(how)), ==, -1);
+    assert_errno_is(E2BIG);
+
+    assert_nonneg_errno(unlinkat(adf.fd, "link", 0));
+    assert_nonneg_errno(unlinkat(adf.fd, "file", 0));
+}
+
 static void _test_close() {
     g_auto(AutoDeleteFile) adf = _create_auto_file();
     int fd;
@@ -521,6 +602,77 @@ static void _test_lseek() {
     assert_nonneg_errno(close(fd));
 }
 
+static void _test_copy_file_range() {
+    g_auto(AutoDeleteFile) adf_in = _create_auto_file();
+    g_auto(AutoDeleteFile) adf_out = _create_auto_file();
+    const char wbuf[] = "0123456789";
+    char rbuf[sizeof(wbuf)] = {0};
+    int fd_in, fd_out;
+    ssize_t rv;
+    _set_contents(&adf_in, wbuf, sizeof(wbuf));
+    assert_nonneg_errno(fd_in = open(adf_in.name, O_RDONLY));
+    assert_nonneg_errno(fd_out = open(adf_out.name, O_RDWR));
+
+    // Copy using and updating the file offsets
+    assert_nonneg_errno(rv = copy_file_range(fd_in, NULL, fd_out, NULL, 4, 0));
+    g_assert_cmpint(rv, ==, 4);
+    assert_nonneg_errno(rv = lseek(fd_in, 0, SEEK_CUR));
+    g_assert_cmpint(rv, ==, 4);
+    assert_nonneg_errno(rv = lseek(fd_out, 0, SEEK_CUR));
+    g_assert_cmpint(rv, ==, 4);
+
+    // Copy using explicit offsets, which are updated instead of the file offsets
+    off64_t off_in = 6;
+    off64_t off_out = 4;
+    assert_nonneg_errno(rv = copy_file_range(fd_in, &off_in, fd_out, &off_out, 2, 0));
+    g_assert_cmpint(rv, ==, 2);
+    g_assert_cmpint(off_in, ==, 8);
+    g_assert_cmpint(off_out, ==, 6);
+    assert_nonneg_errno(rv = lseek(fd_in, 0, SEEK_CUR));
+    g_assert_cmpint(rv, ==, 4);
+    assert_nonneg_errno(rv = lseek(fd_out, 0, SEEK_CUR));
+    g_assert_cmpint(rv, ==, 4);
+
+    // A copy past the end of the input is short
+    off_in = 8;
+    off_out = 6;
+    assert_nonneg_errno(rv = copy_file_range(fd_in, &off_in, fd_out, &off_out, 100, 0));
+    g_assert_cmpint(rv, ==, sizeof(wbuf) - 8);
+    g_assert_cmpint(off_in, ==, sizeof(wbuf));
+
+    // Nothing is copied at the end of the input
+    assert_nonneg_errno(rv = copy_file_range(fd_in, &off_in, fd_out, &off_out, 100, 0));
+    g_assert_cmpint(rv, ==, 0);
+
+    assert_nonneg_errno(rv = pread(fd_out, rbuf, sizeof(rbuf), 0));
+    g_assert_cmpint(rv, ==, 9);
+    g_assert_cmpstr(rbuf, ==, "01236789");
+
+    // Flags must be 0
+    g_assert_cmpint(copy_file_range(fd_in, NULL, fd_out, NULL, 1, 1), ==, -1);
+    assert_errno_is(EINVAL);
+
+    // Negative offsets are invalid
+    off_in = -1;
+    g_assert_cmpint(copy_file_range(fd_in, &off_in, fd_out, NULL, 1, 0), ==, -1);
+    assert_errno_is(EINVAL);
+
+    // The output must be writable
+    g_assert_cmpint(copy_file_range(fd_out, NULL, fd_in, NULL, 1, 0), ==, -1);
+    assert_errno_is(EBADF);
+
+    // Pipes aren't regular files
+    int pipefds[2];
+    assert_nonneg_errno(pipe(pipefds));
+    g_assert_cmpint(copy_file_range(fd_in, NULL, pipefds[1], NULL, 1, 0), ==, -1);
+    assert_errno_is(EINVAL);
+
+    assert_nonneg_errno(close(pipefds[0]));
+    assert_nonneg_errno(close(pipefds[1]));
+    assert_nonneg_errno(close(fd_in));
+    assert_nonneg_errno(close(fd_out));
+}
+
 static void _test_fopen() {
     g_auto(AutoDeleteFile) adf = _create_auto_file();
     FILE* file;
@@ -970,6 +1122,7 @@ int main(int argc, char* argv[]) {
     g_test_add_func("/file/open", _test_open);
     g_test_add_func("/file/open_returns_lowest", _test_open_returns_lowest);
     g_test_add_func("/file/openat", _test_openat);
+    g_test_add_func("/file/openat2", _test_openat2);
     g_test_add_func("/file/close", _test_close);
     g_test_add_func("/file/close_nonexistent", _test_close_nonexistent);
     g_test_add_func("/file/write", _test_write);
@@ -983,6 +1136,7 @@ int main(int argc, char* argv[]) {
     g_test_add_func("/file/preadv", _test_preadv);
     g_test_add_func("/file/preadv2", _test_preadv2);
     g_test_add_func("/file/lseek", _test_lseek);
+    g_test_add_func("/file/copy_file_range", _test_copy_file_range);
     g_test_add_func("/file/fopen", _test_fopen);
     g_test_add_func("/file/fclose", _test_fclose);
     g_test_add_func("/file/fileno", _test_fileno);
@@ -0,0 +1,393 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::sys::stat::Mode;
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_ofd_lock_conflict",
+            test_ofd_lock_conflict,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ofd_lock_ranges",
+            test_ofd_lock_ranges,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ofd_lock_wait",
+            test_ofd_lock_wait,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_posix_lock_released_on_close",
+            test_posix_lock_released_on_close,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_posix_lock_bad_access_mode",
+            test_posix_lock_bad_access_mode,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_flock_conflict",
+            test_flock_conflict,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_flock_released_on_close",
+            test_flock_released_on_close,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// A temporary file that is removed when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new() -> anyhow::Result<Self> {
+        let (fd, path) = nix::unistd::mkstemp("test_file_lockXXXXXX")?;
+        nix::unistd::close(fd)?;
+        Ok(Self { path })
+    }
+
+    fn open(&self, flags: OFlag) -> anyhow::Result<RawFd> {
+        Ok(nix::fcntl::open(&self.path, flags, Mode::empty())?)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = nix::unistd::unlink(&self.path);
+    }
+}
+
+fn new_flock(l_type: i32, l_start: i64, l_len: i64) -> libc::flock {
+    libc::flock {
+        l_type: l_type as i16,
+        l_whence: libc::SEEK_SET as i16,
+        l_start,
+        l_len,
+        l_pid: 0,
+    }
+}
+
+fn fcntl_lock(fd: RawFd, cmd: i32, lock: &mut libc::flock) -> Result<(), Errno> {
+    Errno::result(unsafe { libc::fcntl(fd, cmd, lock as *mut libc::flock) }).map(|_| ())
+}
+
+fn flock(fd: RawFd, operation: i32) -> Result<(), Errno> {
+    Errno::result(unsafe { libc::flock(fd, operation) }).map(|_| ())
+}
+
+fn test_ofd_lock_conflict() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_1 = file.open(OFlag::O_RDWR)?;
+    let fd_2 = file.open(OFlag::O_RDWR)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        // two read locks can coexist
+        fcntl_lock(
+            fd_1,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_RDLCK, 0, 10),
+        )?;
+        fcntl_lock(
+            fd_2,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_RDLCK, 0, 10),
+        )?;
+
+        // but a write lock conflicts with the other read lock
+        assert_eq!(
+            fcntl_lock(
+                fd_1,
+                libc::F_OFD_SETLK,
+                &mut new_flock(libc::F_WRLCK, 5, 10)
+            ),
+            Err(Errno::EAGAIN)
+        );
+
+        // a write lock that doesn't overlap is fine
+        fcntl_lock(
+            fd_1,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_WRLCK, 10, 10),
+        )?;
+
+        // F_OFD_GETLK reports the conflicting lock
+        let mut lock = new_flock(libc::F_RDLCK, 0, 0);
+        fcntl_lock(fd_2, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_WRLCK);
+        assert_eq!(i32::from(lock.l_whence), libc::SEEK_SET);
+        assert_eq!(lock.l_start, 10);
+        assert_eq!(lock.l_len, 10);
+        assert_eq!(lock.l_pid, -1);
+
+        // after unlocking, there's no conflict
+        fcntl_lock(fd_1, libc::F_OFD_SETLK, &mut new_flock(libc::F_UNLCK, 0, 0))?;
+        let mut lock = new_flock(libc::F_WRLCK, 0, 0);
+        fcntl_lock(fd_1, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_WRLCK);
+        fcntl_lock(fd_2, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_UNLCK);
+
+        // OFD locks require l_pid to be 0
+        let mut lock = new_flock(libc::F_RDLCK, 0, 0);
+        lock.l_pid = 1;
+        assert_eq!(
+            fcntl_lock(fd_1, libc::F_OFD_SETLK, &mut lock),
+            Err(Errno::EINVAL)
+        );
+
+        Ok(())
+    })
+}
+
+fn test_ofd_lock_ranges() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_1 = file.open(OFlag::O_RDWR)?;
+    let fd_2 = file.open(OFlag::O_RDWR)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        // lock [0, 100) then unlock the middle [40, 60)
+        fcntl_lock(
+            fd_1,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_WRLCK, 0, 100),
+        )?;
+        fcntl_lock(
+            fd_1,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_UNLCK, 40, 20),
+        )?;
+
+        fcntl_lock(
+            fd_2,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_WRLCK, 40, 20),
+        )?;
+        assert_eq!(
+            fcntl_lock(
+                fd_2,
+                libc::F_OFD_SETLK,
+                &mut new_flock(libc::F_WRLCK, 39, 1)
+            ),
+            Err(Errno::EAGAIN)
+        );
+        assert_eq!(
+            fcntl_lock(
+                fd_2,
+                libc::F_OFD_SETLK,
+                &mut new_flock(libc::F_WRLCK, 60, 1)
+            ),
+            Err(Errno::EAGAIN)
+        );
+
+        // a negative length covers the bytes before l_start
+        let mut lock = new_flock(libc::F_RDLCK, 40, -10);
+        fcntl_lock(fd_2, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_WRLCK);
+        assert_eq!(lock.l_start, 0);
+        assert_eq!(lock.l_len, 40);
+
+        // a length of 0 extends to the end of the file
+        fcntl_lock(fd_2, libc::F_OFD_SETLK, &mut new_flock(libc::F_UNLCK, 0, 0))?;
+        fcntl_lock(
+            fd_2,
+            libc::F_OFD_SETLK,
+            &mut new_flock(libc::F_WRLCK, 1000, 0),
+        )?;
+        let mut lock = new_flock(libc::F_RDLCK, 5000, 1);
+        fcntl_lock(fd_1, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_WRLCK);
+        assert_eq!(lock.l_start, 1000);
+        assert_eq!(lock.l_len, 0);
+
+        // a range starting before the beginning of the file is invalid
+        assert_eq!(
+            fcntl_lock(
+                fd_1,
+                libc::F_OFD_SETLK,
+                &mut new_flock(libc::F_WRLCK, 5, -10)
+            ),
+            Err(Errno::EINVAL)
+        );
+
+        Ok(())
+    })
+}
+
+fn test_ofd_lock_wait() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_1 = file.open(OFlag::O_RDWR)?;
+    let fd_2 = file.open(OFlag::O_RDWR)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        fcntl_lock(fd_1, libc::F_OFD_SETLK, &mut new_flock(libc::F_WRLCK, 0, 0))?;
+
+        let (time_before, time_after) = std::thread::scope(|s| {
+            let waiter = s.spawn(move || {
+                let time_before = std::time::Instant::now();
+                fcntl_lock(
+                    fd_2,
+                    libc::F_OFD_SETLKW,
+                    &mut new_flock(libc::F_WRLCK, 0, 0),
+                )
+                .unwrap();
+                (time_before, std::time::Instant::now())
+            });
+
+            std::thread::sleep(Duration::from_millis(100));
+            fcntl_lock(fd_1, libc::F_OFD_SETLK, &mut new_flock(libc::F_UNLCK, 0, 0)).unwrap();
+
+            waiter.join().unwrap()
+        });
+
+        // the waiter should only have acquired the lock after it was released
+        ensure_ord!(time_after - time_before, >=, Duration::from_millis(90));
+
+        Ok(())
+    })
+}
+
+fn test_posix_lock_released_on_close() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_1 = file.open(OFlag::O_RDWR)?;
+    let fd_2 = file.open(OFlag::O_RDONLY)?;
+    let fd_3 = file.open(OFlag::O_RDWR)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_3], || {
+        fcntl_lock(fd_1, libc::F_SETLK, &mut new_flock(libc::F_WRLCK, 0, 0))?;
+
+        // POSIX locks conflict with OFD locks, even within the same process
+        let mut lock = new_flock(libc::F_WRLCK, 0, 0);
+        fcntl_lock(fd_3, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_WRLCK);
+        assert_eq!(lock.l_pid, nix::unistd::getpid().as_raw());
+
+        // closing any descriptor for the file releases the process' POSIX locks
+        nix::unistd::close(fd_2)?;
+
+        let mut lock = new_flock(libc::F_WRLCK, 0, 0);
+        fcntl_lock(fd_3, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_UNLCK);
+
+        Ok(())
+    })
+}
+
+fn test_posix_lock_bad_access_mode() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_ro = file.open(OFlag::O_RDONLY)?;
+    let fd_wo = file.open(OFlag::O_WRONLY)?;
+
+    test_utils::run_and_close_fds(&[fd_ro, fd_wo], || {
+        assert_eq!(
+            fcntl_lock(fd_ro, libc::F_SETLK, &mut new_flock(libc::F_WRLCK, 0, 0)),
+            Err(Errno::EBADF)
+        );
+        assert_eq!(
+            fcntl_lock(fd_wo, libc::F_SETLK, &mut new_flock(libc::F_RDLCK, 0, 0)),
+            Err(Errno::EBADF)
+        );
+        Ok(())
+    })
+}
+
+fn test_flock_conflict() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_1 = file.open(OFlag::O_RDONLY)?;
+    let fd_2 = file.open(OFlag::O_RDONLY)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        // shared locks can coexist
+        flock(fd_1, libc::LOCK_SH)?;
+        flock(fd_2, libc::LOCK_SH | libc::LOCK_NB)?;
+
+        // an exclusive lock conflicts with the other shared lock
+        assert_eq!(
+            flock(fd_1, libc::LOCK_EX | libc::LOCK_NB),
+            Err(Errno::EWOULDBLOCK)
+        );
+
+        flock(fd_2, libc::LOCK_UN)?;
+        flock(fd_1, libc::LOCK_EX | libc::LOCK_NB)?;
+
+        assert_eq!(
+            flock(fd_2, libc::LOCK_SH | libc::LOCK_NB),
+            Err(Errno::EWOULDBLOCK)
+        );
+
+        // flock locks are independent of fcntl locks
+        let mut lock = new_flock(libc::F_RDLCK, 0, 0);
+        fcntl_lock(fd_2, libc::F_OFD_GETLK, &mut lock)?;
+        assert_eq!(i32::from(lock.l_type), libc::F_UNLCK);
+
+        // invalid operations
+        assert_eq!(flock(fd_1, 0), Err(Errno::EINVAL));
+        assert_eq!(
+            flock(fd_1, libc::LOCK_SH | libc::LOCK_EX),
+            Err(Errno::EINVAL)
+        );
+
+        Ok(())
+    })
+}
+
+fn test_flock_released_on_close() -> anyhow::Result<()> {
+    let file = TempFile::new()?;
+    let fd_1 = file.open(OFlag::O_RDONLY)?;
+    let fd_1_dup = nix::unistd::dup(fd_1)?;
+    let fd_2 = file.open(OFlag::O_RDONLY)?;
+
+    test_utils::run_and_close_fds(&[fd_1_dup, fd_2], || {
+        flock(fd_1, libc::LOCK_EX)?;
+
+        // the lock belongs to the open file, so it remains while any descriptor is open
+        nix::unistd::close(fd_1)?;
+        assert_eq!(
+            flock(fd_2, libc::LOCK_EX | libc::LOCK_NB),
+            Err(Errno::EWOULDBLOCK)
+        );
+
+        nix::unistd::close(fd_1_dup)?;
+        flock(fd_2, libc::LOCK_EX | libc::LOCK_NB)?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,85 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the firewall host option. The "server" host only accepts datagrams for its first port,
+//! and the "client" host only accepts datagrams from connections that it started.
+
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+const PORTS: [u16; 2] = [8000, 8001];
+const OTHER_PORT: u16 = 8002;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Reply to each datagram, and send an unsolicited "other" datagram to the source from another
+/// port.
+fn server() -> anyhow::Result<()> {
+    let other = UdpSocket::bind(("0.0.0.0", OTHER_PORT))?;
+
+    let threads: Vec<_> = PORTS
+        .into_iter()
+        .map(|port| {
+            let socket = UdpSocket::bind(("0.0.0.0", port))?;
+            let other = other.try_clone()?;
+            Ok(std::thread::spawn(move || -> anyhow::Result<()> {
+                let mut buf = [0u8; 100];
+                loop {
+                    let (len, src) = socket.recv_from(&mut buf)?;
+                    socket.send_to(&buf[..len], src)?;
+                    other.send_to(b"other", src)?;
+                }
+            }))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+
+    Ok(())
+}
+
+fn client() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 5000))?;
+    socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+
+    // the server accepts the datagram and replies, but the client drops the "other" datagram
+    // since it isn't part of a connection that the client started
+    socket.send_to(b"hello", ("server", PORTS[0]))?;
+    assert_eq!(recv_all(&socket)?, [("hello".to_string(), PORTS[0])]);
+
+    // the server drops datagrams for its second port
+    socket.send_to(b"hello", ("server", PORTS[1]))?;
+    assert!(recv_all(&socket)?.is_empty());
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Receive datagrams until none arrive before the socket's read timeout.
+fn recv_all(socket: &UdpSocket) -> anyhow::Result<Vec<(String, u16)>> {
+    let mut datagrams = vec![];
+    let mut buf = [0u8; 100];
+
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((len, src)) => {
+                datagrams.push((String::from_utf8(buf[..len].to_vec())?, src.port()));
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(datagrams);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
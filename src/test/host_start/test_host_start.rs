@@ -0,0 +1,29 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests host start times and address pools. The "late" host joins the network at 5 seconds, and
+//! both it and the "pooled" host are assigned addresses from the same pool in the order of their
+//! names.
+
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs};
+use std::time::Duration;
+
+fn main() -> anyhow::Result<()> {
+    assert_eq!(resolve("pooled"), Some(Ipv4Addr::new(10, 2, 0, 2).into()));
+
+    // the late host's name can't be resolved before it joins the network
+    assert_eq!(resolve("late"), None);
+
+    std::thread::sleep(Duration::from_secs(5));
+    assert_eq!(resolve("late"), Some(Ipv4Addr::new(10, 2, 0, 1).into()));
+    assert_eq!(resolve("pooled"), Some(Ipv4Addr::new(10, 2, 0, 2).into()));
+
+    println!("Success.");
+    Ok(())
+}
+
+fn resolve(name: &str) -> Option<IpAddr> {
+    Some((name, 0).to_socket_addrs().ok()?.next()?.ip())
+}
@@ -0,0 +1,441 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use nix::errno::Errno;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIMESTAMP: u8 = 13;
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    // ping sockets depend on the system's `net.ipv4.ping_group_range` and raw sockets require
+    // CAP_NET_RAW, so we only run these tests in shadow
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_ping_localhost",
+            test_ping_localhost,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new("test_ping_eth0", test_ping_eth0, set![TestEnv::Shadow]),
+        test_utils::ShadowTest::new(
+            "test_ping_connected",
+            test_ping_connected,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_not_echo",
+            test_ping_not_echo,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_too_short",
+            test_ping_too_short,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_sockopts",
+            test_ping_sockopts,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_raw_localhost",
+            test_raw_localhost,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_raw_unsupported_protocol",
+            test_raw_unsupported_protocol,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_port_unreachable",
+            test_udp_port_unreachable,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        // linux would only return an error if a router on the path replied with an ICMP error
+        test_utils::ShadowTest::new(
+            "test_udp_host_unreachable",
+            test_udp_host_unreachable,
+            set![TestEnv::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// A socket that is closed when dropped.
+struct Socket(libc::c_int);
+
+impl Socket {
+    fn new(ty: libc::c_int) -> Result<Self, Errno> {
+        let fd = unsafe { libc::socket(libc::AF_INET, ty, libc::IPPROTO_ICMP) };
+        Errno::result(fd).map(Self)
+    }
+
+    fn ping() -> Self {
+        Self::new(libc::SOCK_DGRAM).unwrap()
+    }
+
+    fn raw() -> Self {
+        Self::new(libc::SOCK_RAW).unwrap()
+    }
+
+    fn send_to(&self, buf: &[u8], addr: Ipv4Addr) -> Result<usize, Errno> {
+        let addr = sockaddr_in(SocketAddrV4::new(addr, 0));
+        let rv = unsafe {
+            libc::sendto(
+                self.0,
+                buf.as_ptr().cast(),
+                buf.len(),
+                0,
+                std::ptr::from_ref(&addr).cast(),
+                std::mem::size_of_val(&addr) as libc::socklen_t,
+            )
+        };
+        Errno::result(rv).map(|x| x as usize)
+    }
+
+    fn send(&self, buf: &[u8]) -> Result<usize, Errno> {
+        let rv = unsafe { libc::send(self.0, buf.as_ptr().cast(), buf.len(), 0) };
+        Errno::result(rv).map(|x| x as usize)
+    }
+
+    /// Receive a message, returning the message and the source address.
+    fn recv_from(&self) -> Result<(Vec<u8>, Ipv4Addr), Errno> {
+        let mut buf = vec![0u8; 1024];
+        let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+        let rv = unsafe {
+            libc::recvfrom(
+                self.0,
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                0,
+                std::ptr::from_mut(&mut addr).cast(),
+                &mut addr_len,
+            )
+        };
+        let len = Errno::result(rv)? as usize;
+        buf.truncate(len);
+
+        assert_eq!(addr.sin_family, libc::AF_INET as libc::sa_family_t);
+        assert_eq!(addr.sin_port, 0);
+        Ok((buf, Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))))
+    }
+
+    fn local_addr(&self) -> SocketAddrV4 {
+        let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+        let rv = unsafe {
+            libc::getsockname(self.0, std::ptr::from_mut(&mut addr).cast(), &mut addr_len)
+        };
+        Errno::result(rv).unwrap();
+
+        SocketAddrV4::new(
+            Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+            u16::from_be(addr.sin_port),
+        )
+    }
+
+    fn int_sockopt(&self, level: libc::c_int, optname: libc::c_int) -> Result<libc::c_int, Errno> {
+        let mut val: libc::c_int = 0;
+        let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+        let rv = unsafe {
+            libc::getsockopt(
+                self.0,
+                level,
+                optname,
+                std::ptr::from_mut(&mut val).cast(),
+                &mut len,
+            )
+        };
+        Errno::result(rv)?;
+        assert_eq!(len as usize, std::mem::size_of_val(&val));
+        Ok(val)
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+fn sockaddr_in(addr: SocketAddrV4) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as libc::sa_family_t,
+        sin_port: addr.port().to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(*addr.ip()).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+/// The internet checksum (RFC 1071) of `buf`.
+fn checksum(buf: &[u8]) -> u16 {
+    let mut sum: u32 = buf
+        .chunks(2)
+        .map(|x| u32::from(u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// An ICMP message with a valid checksum.
+fn icmp_message(icmp_type: u8, identifier: u16, sequence: u16, data: &[u8]) -> Vec<u8> {
+    let mut message = vec![icmp_type, 0, 0, 0];
+    message.extend_from_slice(&identifier.to_be_bytes());
+    message.extend_from_slice(&sequence.to_be_bytes());
+    message.extend_from_slice(data);
+
+    let checksum = checksum(&message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+    message
+}
+
+/// Check that `message` is a valid echo reply with the given fields.
+fn check_echo_reply(message: &[u8], identifier: u16, sequence: u16, data: &[u8]) {
+    assert_eq!(message.len(), 8 + data.len());
+    assert_eq!(message[0], ICMP_ECHO_REPLY);
+    assert_eq!(message[1], 0);
+    assert_eq!(checksum(message), 0);
+    assert_eq!(u16::from_be_bytes([message[4], message[5]]), identifier);
+    assert_eq!(u16::from_be_bytes([message[6], message[7]]), sequence);
+    assert_eq!(&message[8..], data);
+}
+
+/// The host's IP address on eth0.
+fn eth0_addr() -> Ipv4Addr {
+    nix::ifaddrs::getifaddrs()
+        .unwrap()
+        .filter(|x| x.interface_name == "eth0")
+        .find_map(|x| x.address?.as_sockaddr_in().copied())
+        .map(|x| *SocketAddrV4::from(x).ip())
+        .unwrap()
+}
+
+fn test_ping_localhost() -> anyhow::Result<()> {
+    let socket = Socket::ping();
+    let data = b"hello";
+
+    for sequence in 1..=3 {
+        // the kernel replaces the identifier and checksum of ping sockets
+        let request = icmp_message(ICMP_ECHO_REQUEST, 0, sequence, data);
+        assert_eq!(
+            socket.send_to(&request, Ipv4Addr::LOCALHOST)?,
+            request.len()
+        );
+
+        // the identifier is the port of the implicitly-bound socket
+        let local_addr = socket.local_addr();
+        assert_eq!(*local_addr.ip(), Ipv4Addr::LOCALHOST);
+        assert_ne!(local_addr.port(), 0);
+
+        let (reply, src) = socket.recv_from()?;
+        assert_eq!(src, Ipv4Addr::LOCALHOST);
+        check_echo_reply(&reply, local_addr.port(), sequence, data);
+    }
+
+    Ok(())
+}
+
+fn test_ping_eth0() -> anyhow::Result<()> {
+    let socket = Socket::ping();
+    let addr = eth0_addr();
+    let data = [0xab; 56];
+
+    let request = icmp_message(ICMP_ECHO_REQUEST, 0, 1, &data);
+    assert_eq!(socket.send_to(&request, addr)?, request.len());
+
+    let (reply, src) = socket.recv_from()?;
+    assert_eq!(src, addr);
+    check_echo_reply(&reply, socket.local_addr().port(), 1, &data);
+
+    Ok(())
+}
+
+fn test_ping_connected() -> anyhow::Result<()> {
+    let socket = Socket::ping();
+
+    let addr = sockaddr_in(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+    let rv = unsafe {
+        libc::connect(
+            socket.0,
+            std::ptr::from_ref(&addr).cast(),
+            std::mem::size_of_val(&addr) as libc::socklen_t,
+        )
+    };
+    Errno::result(rv)?;
+
+    let request = icmp_message(ICMP_ECHO_REQUEST, 0, 7, &[]);
+    assert_eq!(socket.send(&request)?, request.len());
+
+    let (reply, src) = socket.recv_from()?;
+    assert_eq!(src, Ipv4Addr::LOCALHOST);
+    check_echo_reply(&reply, socket.local_addr().port(), 7, &[]);
+
+    Ok(())
+}
+
+fn test_ping_not_echo() -> anyhow::Result<()> {
+    let socket = Socket::ping();
+
+    // ping sockets can only send echo requests
+    let request = icmp_message(ICMP_TIMESTAMP, 0, 1, &[0; 12]);
+    assert_eq!(
+        socket.send_to(&request, Ipv4Addr::LOCALHOST),
+        Err(Errno::EINVAL)
+    );
+
+    Ok(())
+}
+
+fn test_ping_too_short() -> anyhow::Result<()> {
+    let socket = Socket::ping();
+
+    // the message must contain the full ICMP header
+    let request = icmp_message(ICMP_ECHO_REQUEST, 0, 1, &[]);
+    assert_eq!(
+        socket.send_to(&request[..4], Ipv4Addr::LOCALHOST),
+        Err(Errno::EINVAL)
+    );
+
+    Ok(())
+}
+
+fn test_ping_sockopts() -> anyhow::Result<()> {
+    let socket = Socket::ping();
+
+    assert_eq!(
+        socket.int_sockopt(libc::SOL_SOCKET, libc::SO_TYPE)?,
+        libc::SOCK_DGRAM
+    );
+    assert_eq!(
+        socket.int_sockopt(libc::SOL_SOCKET, libc::SO_PROTOCOL)?,
+        libc::IPPROTO_ICMP
+    );
+    assert_eq!(
+        socket.int_sockopt(libc::SOL_SOCKET, libc::SO_DOMAIN)?,
+        libc::AF_INET
+    );
+    assert_eq!(socket.int_sockopt(libc::IPPROTO_IP, libc::IP_TTL)?, 64);
+
+    let socket = Socket::raw();
+
+    assert_eq!(
+        socket.int_sockopt(libc::SOL_SOCKET, libc::SO_TYPE)?,
+        libc::SOCK_RAW
+    );
+    assert_eq!(
+        socket.int_sockopt(libc::SOL_SOCKET, libc::SO_PROTOCOL)?,
+        libc::IPPROTO_ICMP
+    );
+
+    Ok(())
+}
+
+fn test_raw_localhost() -> anyhow::Result<()> {
+    let socket = Socket::raw();
+    let identifier = 0x1234;
+    let data = b"raw";
+
+    let request = icmp_message(ICMP_ECHO_REQUEST, identifier, 1, data);
+    assert_eq!(
+        socket.send_to(&request, Ipv4Addr::LOCALHOST)?,
+        request.len()
+    );
+
+    let (reply, src) = socket.recv_from()?;
+    assert_eq!(src, Ipv4Addr::LOCALHOST);
+
+    // raw sockets receive the IP header
+    let (ip_header, reply) = reply.split_at(20);
+    assert_eq!(ip_header[0], 0x45);
+    assert_eq!(
+        usize::from(u16::from_be_bytes([ip_header[2], ip_header[3]])),
+        20 + reply.len()
+    );
+    assert_eq!(ip_header[9], libc::IPPROTO_ICMP as u8);
+    assert_eq!(checksum(ip_header), 0);
+    assert_eq!(ip_header[12..16], Ipv4Addr::LOCALHOST.octets());
+    assert_eq!(ip_header[16..20], Ipv4Addr::LOCALHOST.octets());
+
+    // the identifier is unchanged
+    check_echo_reply(reply, identifier, 1, data);
+
+    Ok(())
+}
+
+fn test_raw_unsupported_protocol() -> anyhow::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_UDP) };
+    assert_eq!(Errno::result(fd), Err(Errno::EPROTONOSUPPORT));
+
+    Ok(())
+}
+
+/// Send a datagram from a UDP socket connected to `addr`, and return the error of the next receive.
+fn udp_error_after_send(addr: SocketAddrV4) -> Result<Errno, Errno> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+    let socket = Socket(Errno::result(fd)?);
+
+    let addr = sockaddr_in(addr);
+    let rv = unsafe {
+        libc::connect(
+            socket.0,
+            std::ptr::from_ref(&addr).cast(),
+            std::mem::size_of_val(&addr) as libc::socklen_t,
+        )
+    };
+    Errno::result(rv)?;
+
+    assert_eq!(socket.send(b"hello")?, 5);
+
+    // the ICMP error for the datagram is returned by the next receive
+    let mut buf = [0u8; 16];
+    let rv = unsafe { libc::recv(socket.0, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    Ok(Errno::result(rv).unwrap_err())
+}
+
+fn test_udp_port_unreachable() -> anyhow::Result<()> {
+    // this port should not be in use
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 11111);
+    assert_eq!(udp_error_after_send(addr)?, Errno::ECONNREFUSED);
+
+    Ok(())
+}
+
+fn test_udp_host_unreachable() -> anyhow::Result<()> {
+    // no host has this address (TEST-NET-1)
+    let addr = SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 1), 11111);
+    assert_eq!(udp_error_after_send(addr)?, Errno::EHOSTUNREACH);
+
+    Ok(())
+}
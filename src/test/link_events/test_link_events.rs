@@ -0,0 +1,110 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the link events of the network. The client sends datagrams to the server while the edges
+//! of the shortest path between them go down and come back up, and checks that the datagrams are
+//! rerouted over the backup path, lost while crossing an edge that goes down, or rejected with an
+//! ICMP "host unreachable" error while there's no path.
+
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+const SERVER_PORT: u16 = 8000;
+
+/// What happens to a datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// The datagram is echoed with this round-trip time.
+    Echoed(Duration),
+    /// The datagram is lost.
+    Lost,
+    /// The socket receives a "host unreachable" error.
+    HostUnreachable,
+}
+
+/// The time after the client starts at which it sends each datagram, and the expected outcome.
+/// The client starts at 2 seconds, the edge 1-2 goes down at 4 seconds, the edge 0-2 goes down at
+/// 5 seconds, and both come back up at 6 seconds.
+const PROBES: [(Duration, Outcome); 6] = [
+    // the shortest path over the edges 0-1 and 1-2
+    (ms(0), Outcome::Echoed(ms(40))),
+    (ms(500), Outcome::Echoed(ms(40))),
+    // the datagram is still crossing the edge 0-1 when the edge 1-2 goes down
+    (ms(1_990), Outcome::Lost),
+    // the backup path over the edge 0-2
+    (ms(2_500), Outcome::Echoed(ms(100))),
+    // there's no path while both edges are down
+    (ms(3_500), Outcome::HostUnreachable),
+    // the shortest path again
+    (ms(4_500), Outcome::Echoed(ms(40))),
+];
+
+const fn ms(x: u64) -> Duration {
+    Duration::from_millis(x)
+}
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Echo each datagram back to its sender.
+fn server() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", SERVER_PORT))?;
+    let mut buf = [0u8; 64];
+
+    loop {
+        let (len, src) = socket.recv_from(&mut buf)?;
+        socket.send_to(&buf[..len], src)?;
+    }
+}
+
+fn client() -> anyhow::Result<()> {
+    let start = Instant::now();
+
+    let server_addr = ("server", SERVER_PORT).to_socket_addrs()?.next().unwrap();
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(server_addr)?;
+
+    for (i, (send_time, expected)) in PROBES.iter().enumerate() {
+        std::thread::sleep((start + *send_time).saturating_duration_since(Instant::now()));
+
+        let sent = Instant::now();
+        socket.send(&[i as u8])?;
+
+        // a lost datagram would have been echoed long before the timeout
+        let outcome = if test_utils::is_readable(socket.as_raw_fd(), 300)? {
+            let rtt = sent.elapsed();
+            let mut buf = [0u8; 64];
+            match socket.recv(&mut buf) {
+                Ok(len) => {
+                    assert_eq!(&buf[..len], &[i as u8]);
+                    Outcome::Echoed(rtt)
+                }
+                Err(e) if e.raw_os_error() == Some(libc::EHOSTUNREACH) => Outcome::HostUnreachable,
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            Outcome::Lost
+        };
+
+        println!("Datagram {i} had the outcome {outcome:?}");
+
+        match (outcome, expected) {
+            (Outcome::Echoed(rtt), Outcome::Echoed(expected)) => {
+                // the hosts may add a small delay
+                assert!(rtt >= *expected && rtt < *expected + ms(10));
+            }
+            (outcome, expected) => assert_eq!(outcome, *expected),
+        }
+    }
+
+    println!("Success.");
+    Ok(())
+}
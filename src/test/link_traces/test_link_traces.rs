@@ -0,0 +1,90 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the link traces of the network. The client sends datagrams to the server while the trace
+//! of the edge between them changes its latency, and while the trace of the client's uplink adds
+//! latency and packet loss, and checks the round-trip time of each datagram.
+
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+const SERVER_PORT: u16 = 8000;
+
+/// The time after the client starts at which it sends each datagram, and the expected round-trip
+/// time of the datagram, or `None` if it's lost. The client starts at 2 seconds, the edge's latency
+/// is 40 ms from 4 to 6 seconds, and the client's uplink adds 5 ms of latency from 7 to 8 seconds
+/// and drops every packet from 8 to 9 seconds.
+const PROBES: [(Duration, Option<Duration>); 6] = [
+    (ms(0), Some(ms(20))),
+    (ms(2_500), Some(ms(80))),
+    (ms(4_500), Some(ms(20))),
+    // only the datagram from the client is delayed
+    (ms(5_500), Some(ms(25))),
+    (ms(6_500), None),
+    (ms(7_500), Some(ms(20))),
+];
+
+const fn ms(x: u64) -> Duration {
+    Duration::from_millis(x)
+}
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Echo each datagram back to its sender.
+fn server() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", SERVER_PORT))?;
+    let mut buf = [0u8; 64];
+
+    loop {
+        let (len, src) = socket.recv_from(&mut buf)?;
+        socket.send_to(&buf[..len], src)?;
+    }
+}
+
+fn client() -> anyhow::Result<()> {
+    let start = Instant::now();
+
+    let server_addr = ("server", SERVER_PORT).to_socket_addrs()?.next().unwrap();
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(server_addr)?;
+
+    for (i, (send_time, expected_rtt)) in PROBES.iter().enumerate() {
+        std::thread::sleep((start + *send_time).saturating_duration_since(Instant::now()));
+
+        let sent = Instant::now();
+        socket.send(&[i as u8])?;
+
+        // a lost datagram would have been echoed long before the timeout
+        let rtt = if test_utils::is_readable(socket.as_raw_fd(), 300)? {
+            let rtt = sent.elapsed();
+            let mut buf = [0u8; 64];
+            let len = socket.recv(&mut buf)?;
+            assert_eq!(&buf[..len], &[i as u8]);
+            Some(rtt)
+        } else {
+            None
+        };
+
+        println!("Datagram {i} had a round-trip time of {rtt:?}");
+
+        match (rtt, expected_rtt) {
+            (Some(rtt), Some(expected)) => {
+                // the hosts may add a small delay
+                assert!(rtt >= *expected && rtt < *expected + ms(10));
+            }
+            (rtt, expected) => assert_eq!(rtt, *expected),
+        }
+    }
+
+    println!("Success.");
+    Ok(())
+}
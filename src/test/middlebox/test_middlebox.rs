@@ -0,0 +1,125 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests a middlebox that intercepts the packets to and from the server. The client and server are
+//! 1 ms apart, but the middlebox is 20 ms from both of them, so intercepted packets take 40 ms to
+//! arrive. The middlebox's rules drop, delay, rewrite, and reset some of the packets.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// The UDP ports that the server replies on, and the TCP port that it echoes on.
+const UDP_PORTS: [u16; 3] = [9000, 9001, 9003];
+const TCP_PORT: u16 = 80;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Reply to each datagram with the same datagram, and echo the data of each connection.
+fn server() -> anyhow::Result<()> {
+    for port in UDP_PORTS {
+        let udp = UdpSocket::bind(("0.0.0.0", port))?;
+        std::thread::spawn(move || -> anyhow::Result<()> {
+            let mut buf = [0u8; 100];
+            loop {
+                let (len, src) = udp.recv_from(&mut buf)?;
+                udp.send_to(&buf[..len], src)?;
+            }
+        });
+    }
+
+    let tcp = TcpListener::bind(("0.0.0.0", TCP_PORT))?;
+    for stream in tcp.incoming() {
+        let mut stream = stream?;
+        let mut buf = [0u8; 100];
+        // the middlebox resets the connection, so the errors are expected
+        loop {
+            let len = match stream.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(len) => len,
+            };
+            if stream.write_all(&buf[..len]).is_err() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn client() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+    // packets that don't match a rule are forwarded through the middlebox
+    let src = check_udp(&socket, 9000, b"hello", Some(80))?;
+    assert_eq!(src.unwrap().port(), 9000);
+
+    // the "drop" rule drops datagrams that contain "drop"
+    check_udp(&socket, 9000, b"please drop this", None)?;
+
+    // the "delay" rule delays the datagrams to port 9001, but not their replies
+    check_udp(&socket, 9001, b"hello", Some(180))?;
+
+    // the "rewrite" rule sends the datagrams for port 9002 to port 9003
+    let src = check_udp(&socket, 9002, b"hello", Some(80))?;
+    assert_eq!(src.unwrap().port(), 9003);
+
+    // the "censor" rule resets connections that send "forbidden"
+    let mut stream = TcpStream::connect(("server", TCP_PORT))?;
+    stream.write_all(b"hello")?;
+    let mut buf = [0u8; 100];
+    let len = stream.read(&mut buf)?;
+    assert_eq!(&buf[..len], b"hello");
+
+    stream.write_all(b"something forbidden")?;
+    let err = stream.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Send a datagram to the server's `port`, and check that the reply takes `rtt_ms` to arrive, or
+/// that there's no reply if `rtt_ms` is `None`. Returns the source address of the reply, if any.
+fn check_udp(
+    socket: &UdpSocket,
+    port: u16,
+    data: &[u8],
+    rtt_ms: Option<u64>,
+) -> anyhow::Result<Option<SocketAddr>> {
+    let start = Instant::now();
+    socket.send_to(data, ("server", port))?;
+
+    let mut buf = [0u8; 100];
+    let result = socket.recv_from(&mut buf);
+    let elapsed = start.elapsed();
+
+    let Some(rtt_ms) = rtt_ms else {
+        let err = result.unwrap_err();
+        assert!(
+            matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+            "{err:?}"
+        );
+        return Ok(None);
+    };
+
+    let (len, src) = result?;
+    assert_eq!(&buf[..len], data);
+    // the hosts may add a small delay
+    let expected = Duration::from_millis(rtt_ms);
+    assert!(
+        elapsed >= expected && elapsed < expected + Duration::from_millis(10),
+        "round trip of {elapsed:?} to port {port}"
+    );
+
+    Ok(Some(src))
+}
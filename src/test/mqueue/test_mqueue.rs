@@ -0,0 +1,447 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::ffi::CString;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_open_errors",
+            test_open_errors,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_priority_order",
+            test_priority_order,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_nonblocking",
+            test_nonblocking,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_msgsize",
+            test_msgsize,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_access_mode",
+            test_access_mode,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_blocking_receive",
+            test_blocking_receive,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_timedreceive_timeout",
+            test_timedreceive_timeout,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new("test_poll", test_poll, set![TestEnv::Libc, TestEnv::Shadow]),
+        test_utils::ShadowTest::new(
+            "test_notify_signal",
+            test_notify_signal,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// A message queue that is closed and unlinked when dropped.
+struct MessageQueue {
+    name: CString,
+    mqd: libc::mqd_t,
+}
+
+impl MessageQueue {
+    /// Create a new queue with a name that is unique to this process.
+    fn create(
+        suffix: &str,
+        flags: libc::c_int,
+        max_msgs: i64,
+        msg_size: i64,
+    ) -> anyhow::Result<Self> {
+        let name = CString::new(format!(
+            "/shadow_test_mqueue_{}_{suffix}",
+            nix::unistd::getpid()
+        ))?;
+        let mqd = mq_open(
+            &name,
+            libc::O_CREAT | libc::O_EXCL | flags,
+            max_msgs,
+            msg_size,
+        )?;
+        Ok(Self { name, mqd })
+    }
+}
+
+impl Drop for MessageQueue {
+    fn drop(&mut self) {
+        unsafe { libc::mq_close(self.mqd) };
+        unsafe { libc::mq_unlink(self.name.as_ptr()) };
+    }
+}
+
+fn mq_open(
+    name: &CString,
+    flags: libc::c_int,
+    max_msgs: i64,
+    msg_size: i64,
+) -> Result<libc::mqd_t, Errno> {
+    let mut attr: libc::mq_attr = unsafe { std::mem::zeroed() };
+    attr.mq_maxmsg = max_msgs;
+    attr.mq_msgsize = msg_size;
+    Errno::result(unsafe {
+        libc::mq_open(
+            name.as_ptr(),
+            flags,
+            0o600 as libc::mode_t,
+            &mut attr as *mut libc::mq_attr,
+        )
+    })
+}
+
+fn mq_send(mqd: libc::mqd_t, msg: &[u8], prio: u32) -> Result<(), Errno> {
+    Errno::result(unsafe {
+        libc::mq_send(mqd, msg.as_ptr() as *const libc::c_char, msg.len(), prio)
+    })
+    .map(|_| ())
+}
+
+fn mq_receive(mqd: libc::mqd_t, buf: &mut [u8]) -> Result<(usize, u32), Errno> {
+    let mut prio = 0;
+    let rv = Errno::result(unsafe {
+        libc::mq_receive(
+            mqd,
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            &mut prio,
+        )
+    })?;
+    Ok((rv.try_into().unwrap(), prio))
+}
+
+fn mq_getattr(mqd: libc::mqd_t) -> Result<libc::mq_attr, Errno> {
+    let mut attr: libc::mq_attr = unsafe { std::mem::zeroed() };
+    Errno::result(unsafe { libc::mq_getattr(mqd, &mut attr) })?;
+    Ok(attr)
+}
+
+fn test_open_errors() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("open_errors", libc::O_RDWR, 4, 64)?;
+
+    // the queue already exists
+    assert_eq!(
+        mq_open(&mq.name, libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 4, 64),
+        Err(Errno::EEXIST)
+    );
+
+    // opening an existing queue without O_CREAT ignores the attributes
+    let mqd = mq_open(&mq.name, libc::O_RDONLY, 0, 0)?;
+    let attr = mq_getattr(mqd)?;
+    assert_eq!(attr.mq_maxmsg, 4);
+    assert_eq!(attr.mq_msgsize, 64);
+    unsafe { libc::mq_close(mqd) };
+
+    // invalid attributes
+    let name = CString::new(format!(
+        "/shadow_test_mqueue_{}_invalid",
+        nix::unistd::getpid()
+    ))?;
+    assert_eq!(
+        mq_open(&name, libc::O_CREAT | libc::O_RDWR, 0, 64),
+        Err(Errno::EINVAL)
+    );
+    assert_eq!(
+        mq_open(&name, libc::O_CREAT | libc::O_RDWR, 4, -1),
+        Err(Errno::EINVAL)
+    );
+
+    // after unlinking, the name no longer exists but the open queue is still usable
+    Errno::result(unsafe { libc::mq_unlink(mq.name.as_ptr()) })?;
+    assert_eq!(mq_open(&mq.name, libc::O_RDWR, 0, 0), Err(Errno::ENOENT));
+    assert_eq!(
+        Errno::result(unsafe { libc::mq_unlink(mq.name.as_ptr()) }),
+        Err(Errno::ENOENT)
+    );
+    mq_send(mq.mqd, b"hello", 0)?;
+
+    Ok(())
+}
+
+fn test_priority_order() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("priority_order", libc::O_RDWR, 4, 64)?;
+
+    mq_send(mq.mqd, b"low", 1)?;
+    mq_send(mq.mqd, b"high 1", 5)?;
+    mq_send(mq.mqd, b"high 2", 5)?;
+    mq_send(mq.mqd, b"none", 0)?;
+
+    assert_eq!(mq_getattr(mq.mqd)?.mq_curmsgs, 4);
+
+    // higher priorities first, and FIFO within a priority
+    let mut buf = [0u8; 64];
+    for (expected_msg, expected_prio) in [
+        (&b"high 1"[..], 5),
+        (&b"high 2"[..], 5),
+        (&b"low"[..], 1),
+        (&b"none"[..], 0),
+    ] {
+        let (len, prio) = mq_receive(mq.mqd, &mut buf)?;
+        assert_eq!(&buf[..len], expected_msg);
+        assert_eq!(prio, expected_prio);
+    }
+
+    assert_eq!(mq_getattr(mq.mqd)?.mq_curmsgs, 0);
+
+    Ok(())
+}
+
+fn test_nonblocking() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("nonblocking", libc::O_RDWR | libc::O_NONBLOCK, 2, 16)?;
+    let mut buf = [0u8; 16];
+
+    assert_eq!(mq_getattr(mq.mqd)?.mq_flags, i64::from(libc::O_NONBLOCK));
+
+    // the queue is empty
+    assert_eq!(mq_receive(mq.mqd, &mut buf), Err(Errno::EAGAIN));
+
+    // the queue is full
+    mq_send(mq.mqd, b"1", 0)?;
+    mq_send(mq.mqd, b"2", 0)?;
+    assert_eq!(mq_send(mq.mqd, b"3", 0), Err(Errno::EAGAIN));
+
+    // the only attribute that can be changed is O_NONBLOCK
+    let mut new_attr: libc::mq_attr = unsafe { std::mem::zeroed() };
+    new_attr.mq_flags = i64::from(libc::O_NONBLOCK | libc::O_RDWR);
+    assert_eq!(
+        Errno::result(unsafe { libc::mq_setattr(mq.mqd, &new_attr, std::ptr::null_mut()) }),
+        Err(Errno::EINVAL)
+    );
+
+    let mut old_attr: libc::mq_attr = unsafe { std::mem::zeroed() };
+    new_attr.mq_flags = 0;
+    Errno::result(unsafe { libc::mq_setattr(mq.mqd, &new_attr, &mut old_attr) })?;
+    assert_eq!(old_attr.mq_flags, i64::from(libc::O_NONBLOCK));
+    assert_eq!(old_attr.mq_maxmsg, 2);
+    assert_eq!(old_attr.mq_msgsize, 16);
+    assert_eq!(old_attr.mq_curmsgs, 2);
+    assert_eq!(mq_getattr(mq.mqd)?.mq_flags, 0);
+
+    Ok(())
+}
+
+fn test_msgsize() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("msgsize", libc::O_RDWR | libc::O_NONBLOCK, 2, 8)?;
+
+    // the message is larger than the queue's message size
+    assert_eq!(mq_send(mq.mqd, b"123456789", 0), Err(Errno::EMSGSIZE));
+
+    // the priority must be less than MQ_PRIO_MAX
+    assert_eq!(mq_send(mq.mqd, b"1", 32768), Err(Errno::EINVAL));
+
+    // the receive buffer must be at least as large as the queue's message size
+    mq_send(mq.mqd, b"12345678", 0)?;
+    let mut buf = [0u8; 7];
+    assert_eq!(mq_receive(mq.mqd, &mut buf), Err(Errno::EMSGSIZE));
+
+    let mut buf = [0u8; 8];
+    assert_eq!(mq_receive(mq.mqd, &mut buf), Ok((8, 0)));
+
+    Ok(())
+}
+
+fn test_access_mode() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("access_mode", libc::O_WRONLY | libc::O_NONBLOCK, 2, 8)?;
+    let reader = mq_open(&mq.name, libc::O_RDONLY | libc::O_NONBLOCK, 0, 0)?;
+
+    let mut buf = [0u8; 8];
+    assert_eq!(mq_receive(mq.mqd, &mut buf), Err(Errno::EBADF));
+    assert_eq!(mq_send(reader, b"1", 0), Err(Errno::EBADF));
+
+    // both descriptors refer to the same queue
+    mq_send(mq.mqd, b"1", 0)?;
+    assert_eq!(mq_receive(reader, &mut buf), Ok((1, 0)));
+
+    unsafe { libc::mq_close(reader) };
+
+    Ok(())
+}
+
+fn test_blocking_receive() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("blocking_receive", libc::O_RDWR, 2, 8)?;
+    let mqd = mq.mqd;
+
+    let (time_before, time_after, msg) = std::thread::scope(|s| {
+        let receiver = s.spawn(move || {
+            let time_before = std::time::Instant::now();
+            let mut buf = [0u8; 8];
+            let (len, _prio) = mq_receive(mqd, &mut buf).unwrap();
+            (time_before, std::time::Instant::now(), buf[..len].to_vec())
+        });
+
+        std::thread::sleep(Duration::from_millis(100));
+        mq_send(mqd, b"wakeup", 0).unwrap();
+
+        receiver.join().unwrap()
+    });
+
+    // the receiver should only have returned after the message was sent
+    ensure_ord!(time_after - time_before, >=, Duration::from_millis(90));
+    assert_eq!(msg, b"wakeup");
+
+    Ok(())
+}
+
+fn test_timedreceive_timeout() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("timedreceive_timeout", libc::O_RDWR, 2, 8)?;
+
+    let mut now: libc::timespec = unsafe { std::mem::zeroed() };
+    Errno::result(unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut now) })?;
+
+    // 100 ms from now
+    let mut timeout = now;
+    timeout.tv_nsec += 100_000_000;
+    if timeout.tv_nsec >= 1_000_000_000 {
+        timeout.tv_sec += 1;
+        timeout.tv_nsec -= 1_000_000_000;
+    }
+
+    let time_before = std::time::Instant::now();
+    let mut buf = [0u8; 8];
+    let rv = Errno::result(unsafe {
+        libc::mq_timedreceive(
+            mq.mqd,
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            std::ptr::null_mut(),
+            &timeout,
+        )
+    });
+    let time_after = std::time::Instant::now();
+
+    assert_eq!(rv, Err(Errno::ETIMEDOUT));
+    ensure_ord!(time_after - time_before, >=, Duration::from_millis(90));
+
+    // a timeout in the past doesn't block if the queue isn't full
+    mq_send(mq.mqd, b"1", 0)?;
+    assert_eq!(
+        Errno::result(unsafe {
+            libc::mq_timedreceive(
+                mq.mqd,
+                buf.as_mut_ptr() as *mut libc::c_char,
+                buf.len(),
+                std::ptr::null_mut(),
+                &now,
+            )
+        }),
+        Ok(1)
+    );
+
+    Ok(())
+}
+
+fn test_poll() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("poll", libc::O_RDWR | libc::O_NONBLOCK, 1, 8)?;
+
+    // empty queue: writable but not readable
+    assert!(!test_utils::is_readable(mq.mqd, 0)?);
+    assert!(test_utils::is_writable(mq.mqd, 0)?);
+
+    // full queue: readable but not writable
+    mq_send(mq.mqd, b"1", 0)?;
+    assert!(test_utils::is_readable(mq.mqd, 0)?);
+    assert!(!test_utils::is_writable(mq.mqd, 0)?);
+
+    Ok(())
+}
+
+static NOTIFY_SIGNAL_COUNT: AtomicI32 = AtomicI32::new(0);
+static NOTIFY_SIGNAL_VALUE: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn notify_handler(
+    _signo: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _ctx: *mut libc::c_void,
+) {
+    let value = unsafe { (*info).si_value().sival_ptr } as i32;
+    NOTIFY_SIGNAL_VALUE.store(value, Ordering::SeqCst);
+    NOTIFY_SIGNAL_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+fn test_notify_signal() -> anyhow::Result<()> {
+    let mq = MessageQueue::create("notify_signal", libc::O_RDWR | libc::O_NONBLOCK, 4, 8)?;
+
+    let action = SigAction::new(
+        SigHandler::SigAction(notify_handler),
+        SaFlags::SA_SIGINFO,
+        SigSet::empty(),
+    );
+    let old_action = unsafe { signal::sigaction(Signal::SIGUSR1, &action) }?;
+
+    NOTIFY_SIGNAL_COUNT.store(0, Ordering::SeqCst);
+
+    let mut sev: libc::sigevent = unsafe { std::mem::zeroed() };
+    sev.sigev_notify = libc::SIGEV_SIGNAL;
+    sev.sigev_signo = libc::SIGUSR1;
+    sev.sigev_value.sival_ptr = 1234 as *mut libc::c_void;
+    Errno::result(unsafe { libc::mq_notify(mq.mqd, &sev) })?;
+
+    // the first message on an empty queue sends the notification
+    mq_send(mq.mqd, b"1", 0)?;
+    assert_eq!(NOTIFY_SIGNAL_COUNT.load(Ordering::SeqCst), 1);
+    assert_eq!(NOTIFY_SIGNAL_VALUE.load(Ordering::SeqCst), 1234);
+
+    // the registration was removed after the notification
+    let mut buf = [0u8; 8];
+    mq_receive(mq.mqd, &mut buf)?;
+    mq_send(mq.mqd, b"2", 0)?;
+    assert_eq!(NOTIFY_SIGNAL_COUNT.load(Ordering::SeqCst), 1);
+
+    // messages sent to a non-empty queue don't send a notification
+    Errno::result(unsafe { libc::mq_notify(mq.mqd, &sev) })?;
+    mq_send(mq.mqd, b"3", 0)?;
+    assert_eq!(NOTIFY_SIGNAL_COUNT.load(Ordering::SeqCst), 1);
+
+    // unregister, then empty the queue; no further notifications
+    Errno::result(unsafe { libc::mq_notify(mq.mqd, std::ptr::null()) })?;
+    mq_receive(mq.mqd, &mut buf)?;
+    mq_receive(mq.mqd, &mut buf)?;
+    mq_send(mq.mqd, b"4", 0)?;
+    assert_eq!(NOTIFY_SIGNAL_COUNT.load(Ordering::SeqCst), 1);
+
+    unsafe { signal::sigaction(Signal::SIGUSR1, &old_action) }?;
+
+    Ok(())
+}
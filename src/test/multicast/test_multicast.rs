@@ -0,0 +1,121 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests IP multicast for UDP sockets. The "sender" and "receiver" hosts are attached to the same
+//! graph node, and the "remote" host is attached to a different node, which is outside of the
+//! default multicast scope.
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+const GROUP: Ipv4Addr = Ipv4Addr::new(239, 1, 2, 3);
+const PORT: u16 = 8000;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("sender") => sender(),
+        Some("receiver") => receiver(),
+        Some("remote") => remote(),
+        _ => anyhow::bail!("Expected 'sender', 'receiver', or 'remote' argument"),
+    }
+}
+
+/// Join the group, and return the socket after the sender has sent its datagrams.
+fn join_and_wait() -> anyhow::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_nonblocking(true)?;
+
+    // the sender starts one second after us
+    std::thread::sleep(Duration::from_millis(2500));
+
+    Ok(socket)
+}
+
+/// Receives the datagrams sent with a TTL of at least 1.
+fn receiver() -> anyhow::Result<()> {
+    let socket = join_and_wait()?;
+
+    assert_eq!(recv(&socket)?, b"hello");
+    assert_eq!(recv(&socket)?, b"no loop");
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Is outside of the multicast scope, so receives nothing.
+fn remote() -> anyhow::Result<()> {
+    let socket = join_and_wait()?;
+
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    println!("Success.");
+    Ok(())
+}
+
+fn sender() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.set_nonblocking(true)?;
+
+    assert!(socket.multicast_loop_v4()?);
+    assert_eq!(socket.multicast_ttl_v4()?, 1);
+
+    socket.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)?;
+
+    // can't join a group twice, leave a group that we haven't joined, or join a unicast address
+    assert_eq!(
+        errno(socket.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)),
+        Err(Errno::EADDRINUSE)
+    );
+    assert_eq!(
+        errno(socket.leave_multicast_v4(&Ipv4Addr::new(239, 1, 2, 4), &Ipv4Addr::UNSPECIFIED)),
+        Err(Errno::EADDRNOTAVAIL)
+    );
+    assert_eq!(
+        errno(socket.join_multicast_v4(&Ipv4Addr::new(10, 0, 0, 1), &Ipv4Addr::UNSPECIFIED)),
+        Err(Errno::EINVAL)
+    );
+
+    // we're a member of the group, so we receive our own datagram
+    socket.send_to(b"hello", (GROUP, PORT))?;
+    assert_eq!(recv(&socket)?, b"hello");
+
+    // a TTL of 0 keeps the datagram on this host
+    socket.set_multicast_ttl_v4(0)?;
+    assert_eq!(socket.multicast_ttl_v4()?, 0);
+    socket.send_to(b"local", (GROUP, PORT))?;
+    assert_eq!(recv(&socket)?, b"local");
+
+    // without loopback, only the other members receive the datagram
+    socket.set_multicast_ttl_v4(1)?;
+    socket.set_multicast_loop_v4(false)?;
+    assert!(!socket.multicast_loop_v4()?);
+    socket.send_to(b"no loop", (GROUP, PORT))?;
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    socket.leave_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Receive a datagram after giving it time to arrive.
+fn recv(socket: &UdpSocket) -> Result<Vec<u8>, Errno> {
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut buf = vec![0u8; 1024];
+    let (len, src) = errno(socket.recv_from(&mut buf))?;
+    assert_eq!(src.port(), PORT);
+
+    buf.truncate(len);
+    Ok(buf)
+}
+
+fn errno<T>(result: std::io::Result<T>) -> Result<T, Errno> {
+    result.map_err(|e| Errno::from_i32(e.raw_os_error().unwrap()))
+}
@@ -0,0 +1,114 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the additional network interfaces and routes of a host. The client's `eth0` interface
+//! (11.0.0.1) is 10 ms from the servers, and its `eth1` interface (12.0.0.1) is 50 ms from the
+//! servers. The client's routes send packets to "serverb" on `eth1`, and the servers reply to each
+//! datagram and connection with the source address that they see.
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+const PORT: u16 = 8080;
+const ETH0: Ipv4Addr = Ipv4Addr::new(11, 0, 0, 1);
+const ETH1: Ipv4Addr = Ipv4Addr::new(12, 0, 0, 1);
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Reply to each datagram and connection with its source address.
+fn server() -> anyhow::Result<()> {
+    let udp = UdpSocket::bind(("0.0.0.0", PORT))?;
+    let tcp = TcpListener::bind(("0.0.0.0", PORT))?;
+
+    let udp_thread = std::thread::spawn(move || -> anyhow::Result<()> {
+        let mut buf = [0u8; 100];
+        loop {
+            let (_len, src) = udp.recv_from(&mut buf)?;
+            udp.send_to(src.ip().to_string().as_bytes(), src)?;
+        }
+    });
+
+    for stream in tcp.incoming() {
+        let mut stream = stream?;
+        let peer = stream.peer_addr()?;
+        stream.write_all(peer.ip().to_string().as_bytes())?;
+    }
+
+    udp_thread.join().unwrap()
+}
+
+fn client() -> anyhow::Result<()> {
+    // the hostname resolves to the address of eth0, and each other interface's address is
+    // registered with the interface's name appended to the hostname
+    assert_eq!(resolve("client")?, ETH0);
+    assert_eq!(resolve("client-eth1")?, ETH1);
+
+    // the routes choose the interface of an unbound socket
+    let socket = UdpSocket::bind(("0.0.0.0", 5000))?;
+    check_udp(&socket, "servera", ETH0, 20)?;
+    check_udp(&socket, "serverb", ETH1, 100)?;
+
+    // a socket bound to an interface's address sends on that interface
+    let socket = UdpSocket::bind((ETH1, 5001))?;
+    check_udp(&socket, "servera", ETH1, 100)?;
+
+    // connected sockets are implicitly bound to the address of the routed interface
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(("serverb", PORT))?;
+    assert_eq!(socket.local_addr()?.ip(), ETH1);
+
+    for (server, interface) in [("servera", ETH0), ("serverb", ETH1)] {
+        let mut stream = TcpStream::connect((server, PORT))?;
+        assert_eq!(stream.local_addr()?.ip(), interface);
+
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        assert_eq!(reply.parse::<Ipv4Addr>()?, interface);
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+fn resolve(name: &str) -> anyhow::Result<Ipv4Addr> {
+    match (name, 0).to_socket_addrs()?.next() {
+        Some(SocketAddr::V4(addr)) => Ok(*addr.ip()),
+        x => anyhow::bail!("Unexpected address for '{name}': {x:?}"),
+    }
+}
+
+/// Send a datagram to `server`, and check that it arrives from `interface` and that the reply
+/// takes `rtt_ms` to arrive.
+fn check_udp(
+    socket: &UdpSocket,
+    server: &str,
+    interface: Ipv4Addr,
+    rtt_ms: u64,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    socket.send_to(b"hello", (server, PORT))?;
+
+    let mut buf = [0u8; 100];
+    let len = socket.recv(&mut buf)?;
+    let elapsed = start.elapsed();
+
+    let src: Ipv4Addr = std::str::from_utf8(&buf[..len])?.parse()?;
+    assert_eq!(src, interface, "source address of the datagram to {server}");
+    // the hosts may add a small delay
+    let expected = Duration::from_millis(rtt_ms);
+    assert!(
+        elapsed >= expected && elapsed < expected + Duration::from_millis(10),
+        "round trip of {elapsed:?} to {server}"
+    );
+
+    Ok(())
+}
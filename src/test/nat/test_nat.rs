@@ -0,0 +1,135 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the NAT gateway host option. The "server" host replies to each datagram with the
+//! datagram's source address as seen by the server, like a STUN server. The peers are in the
+//! private subnets of a full cone gateway (with hairpinning) and a symmetric gateway.
+
+use std::io::ErrorKind;
+use std::net::{SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::Duration;
+
+const PORTS: [u16; 2] = [3478, 3479];
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("full_cone") => full_cone(),
+        Some("symmetric") => symmetric(),
+        _ => anyhow::bail!("Expected 'server', 'full_cone', or 'symmetric' argument"),
+    }
+}
+
+/// Reply to each datagram with its source address, and send an "other" datagram to the source
+/// from the server's other port.
+fn server() -> anyhow::Result<()> {
+    let sockets = [
+        UdpSocket::bind(("0.0.0.0", PORTS[0]))?,
+        UdpSocket::bind(("0.0.0.0", PORTS[1]))?,
+    ];
+
+    let threads: Vec<_> = (0..2)
+        .map(|i| {
+            let socket = sockets[i].try_clone()?;
+            let other = sockets[1 - i].try_clone()?;
+            Ok(std::thread::spawn(move || -> anyhow::Result<()> {
+                let mut buf = [0u8; 100];
+                loop {
+                    let (_len, src) = socket.recv_from(&mut buf)?;
+                    socket.send_to(src.to_string().as_bytes(), src)?;
+                    other.send_to(b"other", src)?;
+                }
+            }))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+
+    Ok(())
+}
+
+fn full_cone() -> anyhow::Result<()> {
+    let socket = bind()?;
+
+    // the gateway maps our address to one public port for every remote port
+    let mapped = query(&socket, PORTS[0])?;
+    assert_eq!(mapped.ip().octets(), [100, 0, 0, 1]);
+    assert_eq!(query(&socket, PORTS[1])?, mapped);
+
+    // hairpinning forwards datagrams sent to our own public address back to us, and they appear
+    // to come from our public address
+    socket.send_to(b"hello", mapped)?;
+    let datagrams = recv_all(&socket)?;
+    assert_eq!(datagrams, [("hello".to_string(), SocketAddr::V4(mapped))]);
+
+    println!("Success.");
+    Ok(())
+}
+
+fn symmetric() -> anyhow::Result<()> {
+    let socket = bind()?;
+
+    // the gateway maps our address to a different public port for each remote port
+    let mapped_1 = query(&socket, PORTS[0])?;
+    let mapped_2 = query(&socket, PORTS[1])?;
+    assert_eq!(mapped_1.ip().octets(), [100, 0, 0, 2]);
+    assert_eq!(mapped_2.ip(), mapped_1.ip());
+    assert_ne!(mapped_2.port(), mapped_1.port());
+
+    println!("Success.");
+    Ok(())
+}
+
+fn bind() -> anyhow::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", 5000))?;
+    socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+    Ok(socket)
+}
+
+/// Ask the server for our address as seen from the server's `port`. Checks that the "other"
+/// datagram from the server's other port is only received through a full cone gateway.
+fn query(socket: &UdpSocket, port: u16) -> anyhow::Result<SocketAddrV4> {
+    socket.send_to(b"query", ("server", port))?;
+
+    let mut datagrams = recv_all(socket)?;
+    datagrams.sort_by_key(|(_, src)| src.port() != port);
+
+    let other_port = PORTS.into_iter().find(|x| *x != port).unwrap();
+    let is_full_cone = std::env::args().nth(1).unwrap() == "full_cone";
+
+    match &datagrams[..] {
+        [(reply, src)] if !is_full_cone => {
+            assert_eq!(src.port(), port);
+            Ok(reply.parse()?)
+        }
+        [(reply, src), (other, other_src)] if is_full_cone => {
+            assert_eq!(src.port(), port);
+            assert_eq!(other, "other");
+            assert_eq!(other_src.port(), other_port);
+            Ok(reply.parse()?)
+        }
+        _ => anyhow::bail!("Unexpected datagrams: {datagrams:?}"),
+    }
+}
+
+/// Receive datagrams until none arrive before the socket's read timeout.
+fn recv_all(socket: &UdpSocket) -> anyhow::Result<Vec<(String, SocketAddr)>> {
+    let mut datagrams = vec![];
+    let mut buf = [0u8; 100];
+
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((len, src)) => {
+                datagrams.push((String::from_utf8(buf[..len].to_vec())?, src));
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(datagrams);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::net::Ipv4Addr;
+
+use nix::errno::Errno;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_gifconf",
+            test_gifconf,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_loopback_addrs",
+            test_loopback_addrs,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_loopback_link",
+            test_loopback_link,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_index_and_name",
+            test_index_and_name,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_unknown_interface",
+            test_unknown_interface,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_unix_socket",
+            test_unix_socket,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        // the test machine's interfaces are unknown
+        test_utils::ShadowTest::new("test_eth0", test_eth0, set![TestEnv::Shadow]),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// A `struct ifreq`. The union following the name is kept as raw bytes.
+#[repr(C, align(8))]
+#[derive(Copy, Clone)]
+struct IfReq {
+    name: [u8; libc::IFNAMSIZ],
+    data: [u8; 24],
+}
+
+impl IfReq {
+    fn new(name: &str) -> Self {
+        let mut req = Self {
+            name: [0; libc::IFNAMSIZ],
+            data: [0; 24],
+        };
+        req.name[..name.len()].copy_from_slice(name.as_bytes());
+        req
+    }
+
+    fn name(&self) -> &str {
+        let len = self.name.iter().position(|x| *x == 0).unwrap();
+        std::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    fn int(&self) -> libc::c_int {
+        libc::c_int::from_ne_bytes(self.data[..4].try_into().unwrap())
+    }
+
+    fn family(&self) -> libc::sa_family_t {
+        libc::sa_family_t::from_ne_bytes(self.data[..2].try_into().unwrap())
+    }
+
+    /// The IPv4 address of a `struct sockaddr_in`.
+    fn ipv4(&self) -> Ipv4Addr {
+        assert_eq!(self.family(), libc::AF_INET as libc::sa_family_t);
+        Ipv4Addr::from(<[u8; 4]>::try_from(&self.data[4..8]).unwrap())
+    }
+
+    /// The hardware address of a `struct sockaddr`.
+    fn hw_addr(&self) -> [u8; 6] {
+        self.data[2..8].try_into().unwrap()
+    }
+}
+
+/// A `struct ifconf`.
+#[repr(C)]
+struct IfConf {
+    len: libc::c_int,
+    buf: *mut IfReq,
+}
+
+/// A socket that is closed when dropped.
+struct Socket(libc::c_int);
+
+impl Socket {
+    fn new(domain: libc::c_int, ty: libc::c_int) -> Self {
+        let fd = unsafe { libc::socket(domain, ty, 0) };
+        assert!(fd >= 0);
+        Self(fd)
+    }
+
+    fn inet() -> Self {
+        Self::new(libc::AF_INET, libc::SOCK_DGRAM)
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+fn ioctl<T>(socket: &Socket, request: libc::c_ulong, arg: &mut T) -> Result<(), Errno> {
+    let rv = unsafe { libc::ioctl(socket.0, request, std::ptr::from_mut(arg)) };
+    Errno::result(rv).map(|_| ())
+}
+
+fn ifreq_ioctl(socket: &Socket, request: libc::c_ulong, name: &str) -> Result<IfReq, Errno> {
+    let mut req = IfReq::new(name);
+    ioctl(socket, request, &mut req)?;
+    Ok(req)
+}
+
+fn test_gifconf() -> anyhow::Result<()> {
+    let socket = Socket::inet();
+    let req_size = std::mem::size_of::<IfReq>();
+
+    // a NULL buffer returns the required size
+    let mut conf = IfConf {
+        len: 0,
+        buf: std::ptr::null_mut(),
+    };
+    ioctl(&socket, libc::SIOCGIFCONF, &mut conf)?;
+    let len = usize::try_from(conf.len).unwrap();
+    assert!(len >= req_size);
+    assert_eq!(len % req_size, 0);
+
+    let mut reqs = vec![IfReq::new(""); len / req_size];
+    let mut conf = IfConf {
+        len: conf.len,
+        buf: reqs.as_mut_ptr(),
+    };
+    ioctl(&socket, libc::SIOCGIFCONF, &mut conf)?;
+    assert_eq!(usize::try_from(conf.len).unwrap(), len);
+
+    let lo = reqs.iter().find(|x| x.name() == "lo").unwrap();
+    assert_eq!(lo.ipv4(), Ipv4Addr::LOCALHOST);
+
+    // only as many entries as fit in the buffer are returned
+    let mut conf = IfConf {
+        len: libc::c_int::try_from(req_size + req_size / 2).unwrap(),
+        buf: reqs.as_mut_ptr(),
+    };
+    ioctl(&socket, libc::SIOCGIFCONF, &mut conf)?;
+    assert_eq!(usize::try_from(conf.len).unwrap(), req_size);
+
+    Ok(())
+}
+
+fn test_loopback_addrs() -> anyhow::Result<()> {
+    let socket = Socket::inet();
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFADDR, "lo")?;
+    assert_eq!(req.ipv4(), Ipv4Addr::LOCALHOST);
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFNETMASK, "lo")?;
+    assert_eq!(req.ipv4(), Ipv4Addr::new(255, 0, 0, 0));
+
+    Ok(())
+}
+
+fn test_loopback_link() -> anyhow::Result<()> {
+    let socket = Socket::inet();
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFMTU, "lo")?;
+    assert!(req.int() > 0);
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFHWADDR, "lo")?;
+    assert_eq!(req.family(), libc::ARPHRD_LOOPBACK);
+    assert_eq!(req.hw_addr(), [0; 6]);
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFFLAGS, "lo")?;
+    let flags = req.int() & 0xffff;
+    assert_ne!(flags & libc::IFF_UP, 0);
+    assert_ne!(flags & libc::IFF_LOOPBACK, 0);
+
+    Ok(())
+}
+
+fn test_index_and_name() -> anyhow::Result<()> {
+    let socket = Socket::inet();
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFINDEX, "lo")?;
+    let index = req.int();
+    assert!(index > 0);
+
+    let mut req = IfReq::new("");
+    req.data[..4].copy_from_slice(&index.to_ne_bytes());
+    ioctl(&socket, libc::SIOCGIFNAME, &mut req)?;
+    assert_eq!(req.name(), "lo");
+
+    Ok(())
+}
+
+fn test_unknown_interface() -> anyhow::Result<()> {
+    let socket = Socket::inet();
+
+    for request in [libc::SIOCGIFADDR, libc::SIOCGIFMTU, libc::SIOCGIFHWADDR] {
+        let rv = ifreq_ioctl(&socket, request, "shadowtest0").map(|_| ());
+        assert_eq!(rv, Err(Errno::ENODEV));
+    }
+
+    Ok(())
+}
+
+fn test_unix_socket() -> anyhow::Result<()> {
+    // the link-layer ioctls work on any socket
+    let socket = Socket::new(libc::AF_UNIX, libc::SOCK_STREAM);
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFINDEX, "lo")?;
+    assert!(req.int() > 0);
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFMTU, "lo")?;
+    assert!(req.int() > 0);
+
+    Ok(())
+}
+
+fn test_eth0() -> anyhow::Result<()> {
+    let socket = Socket::inet();
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFADDR, "eth0")?;
+    let addr = req.ipv4();
+    assert!(!addr.is_loopback());
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFNETMASK, "eth0")?;
+    assert_eq!(req.ipv4(), Ipv4Addr::new(255, 255, 255, 0));
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFBRDADDR, "eth0")?;
+    let [a, b, c, _] = addr.octets();
+    assert_eq!(req.ipv4(), Ipv4Addr::new(a, b, c, 255));
+
+    // the MAC address is derived from the host's IP address
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFHWADDR, "eth0")?;
+    assert_eq!(req.family(), libc::ARPHRD_ETHER);
+    let [a, b, c, d] = addr.octets();
+    assert_eq!(req.hw_addr(), [0x02, 0x00, a, b, c, d]);
+
+    let req = ifreq_ioctl(&socket, libc::SIOCGIFFLAGS, "eth0")?;
+    let flags = req.int() & 0xffff;
+    assert_ne!(flags & libc::IFF_UP, 0);
+    assert_ne!(flags & libc::IFF_BROADCAST, 0);
+    assert_eq!(flags & libc::IFF_LOOPBACK, 0);
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::io::Cursor;
+use std::net::Ipv4Addr;
+
+use neli::consts::nl::{NlmF, NlmFFlags};
+use neli::consts::rtnl::{RtAddrFamily, RtScope, RtTable, Rta, Rtm, RtmFFlags, Rtn, Rtprot};
+use neli::nl::{NlPayload, Nlmsghdr};
+use neli::rtnl::Rtmsg;
+use neli::types::RtBuffer;
+use neli::{FromBytes, ToBytes};
+
+use test_utils::{set, ShadowTest, TestEnvironment};
+
+/// A route returned by a `RTM_GETROUTE` dump.
+#[derive(Debug)]
+struct Route {
+    table: RtTable,
+    route_type: Rtn,
+    dst: Option<Ipv4Addr>,
+    dst_len: u8,
+}
+
+/// Send a `RTM_GETROUTE` dump request and return the routes from the response.
+fn dump_routes() -> anyhow::Result<Vec<Route>> {
+    let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW, libc::NETLINK_ROUTE) };
+    assert!(fd >= 0);
+
+    let rtmsg = Rtmsg {
+        rtm_family: RtAddrFamily::Inet,
+        rtm_dst_len: 0,
+        rtm_src_len: 0,
+        rtm_tos: 0,
+        rtm_table: RtTable::Unspec,
+        rtm_protocol: Rtprot::Unspec,
+        rtm_scope: RtScope::Universe,
+        rtm_type: Rtn::Unspec,
+        rtm_flags: RtmFFlags::empty(),
+        rtattrs: RtBuffer::new(),
+    };
+    let nlmsg = {
+        let len = None;
+        let nl_type = Rtm::Getroute;
+        let flags = NlmFFlags::new(&[NlmF::Request, NlmF::Dump]);
+        let seq = Some(0x4d2c81f0); // Random number
+        let pid = None;
+        let payload = NlPayload::Payload(rtmsg);
+        Nlmsghdr::new(len, nl_type, flags, seq, pid, payload)
+    };
+
+    let mut buffer = Cursor::new(Vec::new());
+    nlmsg.to_bytes(&mut buffer).unwrap();
+    let buffer = buffer.into_inner();
+
+    let rv = unsafe {
+        libc::send(
+            fd,
+            buffer.as_ptr() as *const core::ffi::c_void,
+            buffer.len(),
+            0,
+        )
+    };
+    assert_eq!(rv, buffer.len() as isize);
+
+    let mut routes = Vec::new();
+    let mut buffer = vec![0u8; 32768];
+
+    'recv: loop {
+        let len = unsafe {
+            libc::recv(
+                fd,
+                buffer.as_mut_ptr() as *mut core::ffi::c_void,
+                buffer.len(),
+                0,
+            )
+        };
+        assert!(len > 0);
+        let mut bytes = &buffer[..len as usize];
+
+        while bytes.len() >= std::mem::size_of::<libc::nlmsghdr>() {
+            let msg_len = u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as usize;
+            let msg_type = u16::from_ne_bytes(bytes[4..6].try_into().unwrap());
+            let msg = &bytes[..msg_len];
+
+            match msg_type {
+                x if x == libc::NLMSG_DONE as u16 => break 'recv,
+                x if x == libc::NLMSG_ERROR as u16 => anyhow::bail!("Received NLMSG_ERROR"),
+                x if x == libc::RTM_NEWROUTE => {
+                    let nlmsg = Nlmsghdr::<Rtm, Rtmsg>::from_bytes(&mut Cursor::new(msg))?;
+                    let rtmsg = nlmsg.get_payload()?;
+                    assert_eq!(rtmsg.rtm_family, RtAddrFamily::Inet);
+
+                    let dst = rtmsg
+                        .rtattrs
+                        .iter()
+                        .find(|x| x.rta_type == Rta::Dst)
+                        .map(|x| <[u8; 4]>::try_from(x.rta_payload.as_ref()).unwrap().into());
+
+                    routes.push(Route {
+                        table: rtmsg.rtm_table,
+                        route_type: rtmsg.rtm_type,
+                        dst,
+                        dst_len: rtmsg.rtm_dst_len,
+                    });
+                }
+                x => anyhow::bail!("Unexpected message type {x}"),
+            }
+
+            // messages are aligned to 4 bytes
+            let aligned_len = std::cmp::min((msg_len + 3) & !3, bytes.len());
+            bytes = &bytes[aligned_len..];
+        }
+    }
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+    Ok(routes)
+}
+
+fn test_loopback_route() -> anyhow::Result<()> {
+    let routes = dump_routes()?;
+
+    // the local table should contain a local route to the loopback address
+    assert!(routes.iter().any(|x| x.table == RtTable::Local
+        && x.route_type == Rtn::Local
+        && x.dst == Some(Ipv4Addr::LOCALHOST)
+        && x.dst_len == 32));
+
+    Ok(())
+}
+
+fn test_default_route() -> anyhow::Result<()> {
+    let routes = dump_routes()?;
+
+    // the main table should contain a default route, which has no destination
+    let default = routes
+        .iter()
+        .find(|x| x.table == RtTable::Main && x.dst_len == 0)
+        .unwrap();
+    assert_eq!(default.route_type, Rtn::Unicast);
+    assert_eq!(default.dst, None);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let all_envs = set![TestEnvironment::Libc, TestEnvironment::Shadow];
+
+    let mut tests: Vec<test_utils::ShadowTest<(), anyhow::Error>> = vec![
+        ShadowTest::new("loopback-route", test_loopback_route, all_envs.clone()),
+        ShadowTest::new(
+            "default-route",
+            test_default_route,
+            // The test machine isn't guaranteed to have a default route
+            set![TestEnvironment::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
@@ -0,0 +1,143 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests path MTU discovery for UDP sockets. The path between the client and server hosts has an
+//! MTU of 1000 bytes, which is smaller than the MTU of the hosts' interfaces (1500 bytes).
+
+use std::net::UdpSocket;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+use linux_api::inet::{IpPmtuDisc, IP_MTU, IP_MTU_DISCOVER};
+use nix::errno::Errno;
+
+const SERVER_PORT: u16 = 8000;
+const PATH_MTU: libc::c_int = 1000;
+const INTERFACE_MTU: libc::c_int = 1500;
+
+/// The size of the IPv4 and UDP headers.
+const HEADER_LEN: usize = 28;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Receive datagrams forever.
+fn server() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", SERVER_PORT))?;
+    let mut buf = vec![0u8; 65536];
+
+    loop {
+        let (len, src) = socket.recv_from(&mut buf)?;
+        println!("Received {len} bytes from {src}");
+    }
+}
+
+fn client() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+
+    assert_eq!(
+        int_sockopt(&socket, IP_MTU_DISCOVER)?,
+        i32::from(IpPmtuDisc::IP_PMTUDISC_WANT)
+    );
+
+    // only connected sockets have a path MTU
+    assert_eq!(int_sockopt(&socket, IP_MTU), Err(Errno::ENOTCONN));
+
+    socket.connect(("server", SERVER_PORT))?;
+    assert_eq!(int_sockopt(&socket, IP_MTU)?, INTERFACE_MTU);
+
+    // invalid modes are rejected
+    assert_eq!(
+        set_int_sockopt(&socket, IP_MTU_DISCOVER, 42),
+        Err(Errno::EINVAL)
+    );
+
+    set_int_sockopt(&socket, IP_MTU_DISCOVER, IpPmtuDisc::IP_PMTUDISC_DO.into())?;
+    assert_eq!(
+        int_sockopt(&socket, IP_MTU_DISCOVER)?,
+        i32::from(IpPmtuDisc::IP_PMTUDISC_DO)
+    );
+
+    // the path MTU isn't known yet, so this is sent but dropped by the network
+    let large = datagram(1400);
+    assert_eq!(socket.send(&large)?, large.len());
+
+    // wait for the "fragmentation needed" error
+    std::thread::sleep(Duration::from_secs(1));
+    assert_eq!(int_sockopt(&socket, IP_MTU)?, PATH_MTU);
+
+    assert_eq!(errno(socket.send(&large)), Err(Errno::EMSGSIZE));
+
+    let largest = datagram(PATH_MTU as usize);
+    assert_eq!(socket.send(&largest)?, largest.len());
+    let too_large = datagram(PATH_MTU as usize + 1);
+    assert_eq!(errno(socket.send(&too_large)), Err(Errno::EMSGSIZE));
+
+    // probing ignores the path MTU, but not the interface MTU
+    set_int_sockopt(
+        &socket,
+        IP_MTU_DISCOVER,
+        IpPmtuDisc::IP_PMTUDISC_PROBE.into(),
+    )?;
+    assert_eq!(socket.send(&large)?, large.len());
+    let huge = datagram(INTERFACE_MTU as usize + 1);
+    assert_eq!(errno(socket.send(&huge)), Err(Errno::EMSGSIZE));
+
+    // large datagrams can be sent without the "don't fragment" flag
+    for mode in [IpPmtuDisc::IP_PMTUDISC_WANT, IpPmtuDisc::IP_PMTUDISC_DONT] {
+        set_int_sockopt(&socket, IP_MTU_DISCOVER, mode.into())?;
+        assert_eq!(socket.send(&huge)?, huge.len());
+    }
+
+    // the path MTU is unchanged
+    assert_eq!(int_sockopt(&socket, IP_MTU)?, PATH_MTU);
+
+    println!("Success.");
+    Ok(())
+}
+
+/// A UDP payload that results in an IP packet of `packet_len` bytes.
+fn datagram(packet_len: usize) -> Vec<u8> {
+    vec![0u8; packet_len - HEADER_LEN]
+}
+
+fn errno<T>(result: std::io::Result<T>) -> Result<T, Errno> {
+    result.map_err(|e| Errno::from_i32(e.raw_os_error().unwrap()))
+}
+
+fn int_sockopt(socket: &UdpSocket, opt: libc::c_int) -> Result<libc::c_int, Errno> {
+    let mut val: libc::c_int = 0;
+    let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+    let rv = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            opt,
+            std::ptr::from_mut(&mut val).cast(),
+            &mut len,
+        )
+    };
+    Errno::result(rv)?;
+    assert_eq!(len as usize, std::mem::size_of_val(&val));
+    Ok(val)
+}
+
+fn set_int_sockopt(socket: &UdpSocket, opt: libc::c_int, val: libc::c_int) -> Result<(), Errno> {
+    let rv = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            opt,
+            std::ptr::from_ref(&val).cast(),
+            std::mem::size_of_val(&val) as libc::socklen_t,
+        )
+    };
+    Errno::result(rv).map(|_| ())
+}
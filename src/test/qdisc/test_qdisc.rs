@@ -0,0 +1,69 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the egress qdisc host option. The "netem" host's qdisc delays its sent packets by 200 ms,
+//! and the "fqcodel" host's qdisc doesn't delay packets when there's no standing queue.
+
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+const PORT: u16 = 8000;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("netem") => netem(),
+        Some("fq_codel") => fq_codel(),
+        _ => anyhow::bail!("Expected 'server', 'netem', or 'fq_codel' argument"),
+    }
+}
+
+/// Echoes datagrams back to their sender.
+fn server() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    let mut buf = vec![0u8; 1024];
+
+    loop {
+        let (len, src) = socket.recv_from(&mut buf)?;
+        assert_eq!(socket.send_to(&buf[..len], src)?, len);
+    }
+}
+
+fn netem() -> anyhow::Result<()> {
+    // the qdisc delays the request but not the server's reply
+    for _ in 0..5 {
+        let rtt = round_trip()?;
+        assert!(rtt >= Duration::from_millis(200), "{rtt:?}");
+        assert!(rtt < Duration::from_millis(250), "{rtt:?}");
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+fn fq_codel() -> anyhow::Result<()> {
+    for _ in 0..5 {
+        let rtt = round_trip()?;
+        assert!(rtt < Duration::from_millis(50), "{rtt:?}");
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Send a datagram to the server and return the time until the reply is received.
+fn round_trip() -> anyhow::Result<Duration> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let start = Instant::now();
+    assert_eq!(socket.send_to(b"hello", ("server", PORT))?, 5);
+
+    let mut buf = vec![0u8; 1024];
+    assert_eq!(socket.recv(&mut buf)?, 5);
+    assert_eq!(&buf[..5], b"hello");
+
+    Ok(start.elapsed())
+}
@@ -32,6 +32,21 @@ fn main() -> Result<(), String> {
             test_getrandom,
             set![TestEnv::Libc, TestEnv::Shadow],
         ),
+        test_utils::ShadowTest::new(
+            "test_getrandom_flags",
+            test_getrandom_flags,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_getrandom_large",
+            test_getrandom_large,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_at_random",
+            test_at_random,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
     ];
     if filter_shadow_passing {
         tests.retain(|x| x.passing(TestEnv::Shadow))
@@ -143,3 +158,72 @@ fn test_getrandom() -> Result<(), String> {
 
     check_randomness(&values)
 }
+
+fn getrandom(buf: &mut [u8], flags: libc::c_uint) -> Result<usize, nix::errno::Errno> {
+    let rv = unsafe {
+        libc::syscall(
+            libc::SYS_getrandom,
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            flags,
+        )
+    };
+    nix::errno::Errno::result(rv).map(|x| x as usize)
+}
+
+fn test_getrandom_flags() -> Result<(), String> {
+    let mut buf = [0_u8; 64];
+
+    for flags in [
+        0,
+        libc::GRND_NONBLOCK,
+        libc::GRND_RANDOM,
+        libc::GRND_NONBLOCK | libc::GRND_RANDOM,
+    ] {
+        test_utils::result_assert_eq(getrandom(&mut buf, flags), Ok(buf.len()), "")?;
+    }
+
+    // the random and insecure (GRND_INSECURE) sources can't both be used
+    test_utils::result_assert_eq(
+        getrandom(&mut buf, libc::GRND_RANDOM | 0x4),
+        Err(nix::errno::Errno::EINVAL),
+        "",
+    )?;
+
+    // unknown flags aren't allowed
+    test_utils::result_assert_eq(
+        getrandom(&mut buf, 0x100),
+        Err(nix::errno::Errno::EINVAL),
+        "",
+    )?;
+
+    Ok(())
+}
+
+fn test_getrandom_large() -> Result<(), String> {
+    // larger than the chunks that shadow copies at a time
+    let mut buf = vec![0_u8; 1 << 20];
+
+    test_utils::result_assert_eq(getrandom(&mut buf, 0), Ok(buf.len()), "")?;
+
+    // the end of the buffer should have been written too
+    test_utils::result_assert(
+        buf[buf.len() - 1024..].iter().any(|&x| x != 0),
+        "end of the buffer is all zeros",
+    )?;
+
+    Ok(())
+}
+
+fn test_at_random() -> Result<(), String> {
+    let ptr = unsafe { libc::getauxval(libc::AT_RANDOM) } as *const u8;
+    test_utils::result_assert(!ptr.is_null(), "AT_RANDOM isn't set")?;
+
+    let bytes = unsafe { std::slice::from_raw_parts(ptr, 16) };
+    test_utils::result_assert(
+        bytes.iter().any(|&x| x != 0),
+        "AT_RANDOM bytes are all zeros",
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests packet reordering on network graph edges. The edge between the "sender" and "receiver"
+//! hosts reorders every 5th packet by delaying it for longer than it takes to send the next
+//! packets.
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+const PORT: u16 = 8000;
+const COUNT: u8 = 20;
+const GAP: u8 = 5;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("sender") => sender(),
+        Some("receiver") => receiver(),
+        _ => anyhow::bail!("Expected 'sender' or 'receiver' argument"),
+    }
+}
+
+/// Receives the datagrams, with every 5th datagram after the others.
+fn receiver() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    socket.set_nonblocking(true)?;
+
+    // the sender starts one second after us, and all datagrams arrive within 30 ms
+    std::thread::sleep(Duration::from_millis(1500));
+
+    let (reordered, in_order): (Vec<u8>, Vec<u8>) = (0..COUNT).partition(|x| x % GAP == GAP - 1);
+    let expected: Vec<u8> = in_order.into_iter().chain(reordered).collect();
+
+    let mut received = Vec::new();
+    for _ in 0..COUNT {
+        received.push(recv(&socket)?);
+    }
+    assert_eq!(recv(&socket), Err(Errno::EWOULDBLOCK));
+
+    assert_eq!(received, expected);
+
+    println!("Success.");
+    Ok(())
+}
+
+fn sender() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+
+    for i in 0..COUNT {
+        assert_eq!(socket.send_to(&[i], ("receiver", PORT))?, 1);
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Receive a one-byte datagram.
+fn recv(socket: &UdpSocket) -> Result<u8, Errno> {
+    let mut buf = [0u8; 1];
+    let (len, _src) = errno(socket.recv_from(&mut buf))?;
+
+    assert_eq!(len, 1);
+    Ok(buf[0])
+}
+
+fn errno<T>(result: std::io::Result<T>) -> Result<T, Errno> {
+    result.map_err(|e| Errno::from_i32(e.raw_os_error().unwrap()))
+}
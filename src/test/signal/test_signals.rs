@@ -1231,6 +1231,218 @@ fn test_validate_context() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// A syscall that waits for a file descriptor to become readable while temporarily replacing the
+/// thread's signal mask.
+#[derive(Debug, Copy, Clone)]
+enum SigmaskWaitFn {
+    Ppoll,
+    Pselect,
+    EpollPwait,
+}
+
+/// Wait up to 1 second for `fd` to become readable with the temporary signal mask `mask`.
+/// Returns the number of ready file descriptors.
+fn wait_with_sigmask(
+    wait_fn: SigmaskWaitFn,
+    fd: RawFd,
+    mask: &signal::SigSet,
+) -> Result<libc::c_int, Errno> {
+    let timeout = libc::timespec {
+        tv_sec: 1,
+        tv_nsec: 0,
+    };
+
+    match wait_fn {
+        SigmaskWaitFn::Ppoll => {
+            let mut fds = [libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            }];
+            Errno::result(unsafe { libc::ppoll(fds.as_mut_ptr(), 1, &timeout, mask.as_ref()) })
+        }
+        SigmaskWaitFn::Pselect => {
+            let mut readfds: libc::fd_set = unsafe { std::mem::zeroed() };
+            unsafe { libc::FD_SET(fd, &mut readfds) };
+            Errno::result(unsafe {
+                libc::pselect(
+                    fd + 1,
+                    &mut readfds,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    &timeout,
+                    mask.as_ref(),
+                )
+            })
+        }
+        SigmaskWaitFn::EpollPwait => {
+            let epfd = Errno::result(unsafe { libc::epoll_create1(0) })?;
+            let mut event = libc::epoll_event {
+                events: libc::EPOLLIN as u32,
+                u64: 0,
+            };
+            Errno::result(unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fd, &mut event) })
+                .unwrap();
+            let mut events = [libc::epoll_event { events: 0, u64: 0 }];
+            let timeout_ms = (timeout.tv_sec * 1000) as libc::c_int;
+            let rv = Errno::result(unsafe {
+                libc::epoll_pwait(epfd, events.as_mut_ptr(), 1, timeout_ms, mask.as_ref())
+            });
+            unistd::close(epfd).unwrap();
+            rv
+        }
+    }
+}
+
+fn set_handler(signal: Signal, handler: signal::SigHandler) {
+    unsafe {
+        signal::sigaction(
+            signal,
+            &signal::SigAction::new(handler, signal::SaFlags::empty(), signal::SigSet::empty()),
+        )
+        .unwrap()
+    };
+}
+
+fn blocked_signals() -> signal::SigSet {
+    let mut mask = signal::SigSet::empty();
+    signal::sigprocmask(signal::SigmaskHow::SIG_BLOCK, None, Some(&mut mask)).unwrap();
+    mask
+}
+
+// Tests that pending signals unblocked by the temporary mask interrupt the wait, and that the
+// original mask is restored after they're handled.
+fn test_sigmask_wait_interrupted(wait_fn: SigmaskWaitFn) -> Result<(), Box<dyn Error>> {
+    let signals = [Signal::SIGUSR1, Signal::SIGUSR2];
+    let mut sigset = signal::SigSet::empty();
+    for signal in signals {
+        sigset.add(signal);
+    }
+
+    for signal in signals {
+        set_handler(signal, signal::SigHandler::Handler(signal_handler));
+    }
+
+    let mut orig_mask = signal::SigSet::empty();
+    signal::sigprocmask(
+        signal::SigmaskHow::SIG_BLOCK,
+        Some(&sigset),
+        Some(&mut orig_mask),
+    )?;
+
+    for signal in signals {
+        signal::raise(signal)?;
+    }
+    assert_eq!(signal_channel().recv(), None);
+
+    let (read_fd, write_fd) = unistd::pipe()?;
+
+    assert_eq!(
+        wait_with_sigmask(wait_fn, read_fd, &signal::SigSet::empty()),
+        Err(Errno::EINTR)
+    );
+
+    // Both signals were handled.
+    let mut received: Vec<i32> = std::iter::from_fn(|| signal_channel().recv())
+        .map(|record| record.signal)
+        .collect();
+    received.sort();
+    assert_eq!(received, signals.map(|s| s as i32));
+
+    // The original mask was restored.
+    let mask = blocked_signals();
+    assert!(signals.iter().all(|s| mask.contains(*s)));
+
+    unistd::close(read_fd)?;
+    unistd::close(write_fd)?;
+    signal::sigprocmask(signal::SigmaskHow::SIG_SETMASK, Some(&orig_mask), None)?;
+    for signal in signals {
+        set_handler(signal, signal::SigHandler::SigDfl);
+    }
+
+    Ok(())
+}
+
+// Tests that if the wait completes without being interrupted, the original mask is restored
+// before pending signals are handled.
+fn test_sigmask_wait_ready(wait_fn: SigmaskWaitFn) -> Result<(), Box<dyn Error>> {
+    let signal = Signal::SIGUSR1;
+    let mut sigset = signal::SigSet::empty();
+    sigset.add(signal);
+
+    set_handler(signal, signal::SigHandler::Handler(signal_handler));
+
+    let mut orig_mask = signal::SigSet::empty();
+    signal::sigprocmask(
+        signal::SigmaskHow::SIG_BLOCK,
+        Some(&sigset),
+        Some(&mut orig_mask),
+    )?;
+    signal::raise(signal)?;
+
+    let (read_fd, write_fd) = unistd::pipe()?;
+    unistd::write(write_fd, &[0])?;
+
+    assert_eq!(
+        wait_with_sigmask(wait_fn, read_fd, &signal::SigSet::empty()),
+        Ok(1)
+    );
+
+    // The signal is still pending and blocked.
+    assert_eq!(signal_channel().recv(), None);
+    assert!(blocked_signals().contains(signal));
+
+    // Unblocking delivers it.
+    signal::sigprocmask(signal::SigmaskHow::SIG_UNBLOCK, Some(&sigset), None)?;
+    assert_eq!(
+        Signal::try_from(signal_channel().recv().unwrap().signal)?,
+        signal
+    );
+
+    unistd::close(read_fd)?;
+    unistd::close(write_fd)?;
+    signal::sigprocmask(signal::SigmaskHow::SIG_SETMASK, Some(&orig_mask), None)?;
+    set_handler(signal, signal::SigHandler::SigDfl);
+
+    Ok(())
+}
+
+// Tests that signals blocked by the temporary mask don't interrupt the wait, and are delivered
+// once the original mask is restored.
+fn test_sigmask_wait_blocks(wait_fn: SigmaskWaitFn) -> Result<(), Box<dyn Error>> {
+    let signal = Signal::SIGUSR1;
+    let mut sigset = signal::SigSet::empty();
+    sigset.add(signal);
+
+    set_handler(signal, signal::SigHandler::Handler(signal_handler));
+
+    let (read_fd, write_fd) = unistd::pipe()?;
+
+    let pid = unistd::getpid();
+    let tid = unistd::gettid();
+    let sender = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        tgkill(pid, tid, signal).unwrap();
+    });
+
+    // The wait times out.
+    assert_eq!(wait_with_sigmask(wait_fn, read_fd, &sigset), Ok(0));
+    sender.join().unwrap();
+
+    // The signal was delivered after the wait returned.
+    assert_eq!(
+        Signal::try_from(signal_channel().recv().unwrap().signal)?,
+        signal
+    );
+    assert!(!blocked_signals().contains(signal));
+
+    unistd::close(read_fd)?;
+    unistd::close(write_fd)?;
+    set_handler(signal, signal::SigHandler::SigDfl);
+
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     // should we restrict the tests we run?
     let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
@@ -1356,9 +1568,33 @@ fn main() -> Result<(), Box<dyn Error>> {
             test_hardware_error_signals,
             all_envs.clone(),
         ),
-        ShadowTest::new("validate context", test_validate_context, all_envs),
+        ShadowTest::new("validate context", test_validate_context, all_envs.clone()),
     ];
 
+    for wait_fn in [
+        SigmaskWaitFn::Ppoll,
+        SigmaskWaitFn::Pselect,
+        SigmaskWaitFn::EpollPwait,
+    ] {
+        tests.extend([
+            ShadowTest::new(
+                &format!("{wait_fn:?} sigmask interrupted"),
+                move || test_sigmask_wait_interrupted(wait_fn),
+                all_envs.clone(),
+            ),
+            ShadowTest::new(
+                &format!("{wait_fn:?} sigmask ready"),
+                move || test_sigmask_wait_ready(wait_fn),
+                all_envs.clone(),
+            ),
+            ShadowTest::new(
+                &format!("{wait_fn:?} sigmask blocks"),
+                move || test_sigmask_wait_blocks(wait_fn),
+                all_envs.clone(),
+            ),
+        ]);
+    }
+
     if filter_shadow_passing {
         tests.retain(|x| x.passing(TestEnv::Shadow));
     }
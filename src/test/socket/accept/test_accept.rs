@@ -71,6 +71,14 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
             ),
         ]);
 
+        if accept_fn == AcceptFn::Accept4 {
+            tests.extend(vec![test_utils::ShadowTest::new(
+                &append_args("test_invalid_flags"),
+                move || test_invalid_flags(accept_fn),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            )]);
+        }
+
         let accept_flags = [
             0,
             libc::SOCK_NONBLOCK,
@@ -306,6 +314,32 @@ fn test_invalid_sock_type(accept_fn: AcceptFn) -> Result<(), String> {
     })
 }
 
+/// Test accept4 using flags other than SOCK_NONBLOCK and SOCK_CLOEXEC.
+fn test_invalid_flags(accept_fn: AcceptFn) -> Result<(), String> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_NONBLOCK, 0) };
+    assert!(fd >= 0);
+
+    socket_utils::autobind_helper(fd, libc::AF_INET);
+    let rv = unsafe { libc::listen(fd, 10) };
+    assert_eq!(rv, 0);
+
+    let mut args = AcceptArguments {
+        fd,
+        addr: None,
+        addr_len: None,
+        flags: libc::SOCK_NONBLOCK | libc::O_APPEND,
+    };
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let fd = check_accept_call(&mut args, accept_fn, Some(libc::EINVAL))?;
+        if let Some(fd) = fd {
+            let rv = unsafe { libc::close(fd) };
+            assert_eq!(rv, 0, "Could not close the fd");
+        }
+        Ok(())
+    })
+}
+
 /// Test accept using a non-listening socket.
 fn test_non_listening_fd(
     accept_fn: AcceptFn,
@@ -1057,6 +1091,27 @@ fn check_accept_call(
                     test_utils::get_errno_message(errno)
                 ));
             }
+
+            // the new socket's flags are set only from the accept flags, and never inherited
+            // from the listening socket
+            let status = unsafe { libc::fcntl(rv, libc::F_GETFL) };
+            assert!(status >= 0);
+            if (status & libc::O_NONBLOCK != 0) != (args.flags & libc::SOCK_NONBLOCK != 0) {
+                return Err(format!(
+                    "Unexpected O_NONBLOCK status {:#o} for accept flags {:#o}",
+                    status, args.flags
+                ));
+            }
+
+            let fd_flags = unsafe { libc::fcntl(rv, libc::F_GETFD) };
+            assert!(fd_flags >= 0);
+            if (fd_flags & libc::FD_CLOEXEC != 0) != (args.flags & libc::SOCK_CLOEXEC != 0) {
+                return Err(format!(
+                    "Unexpected FD_CLOEXEC flag {:#o} for accept flags {:#o}",
+                    fd_flags, args.flags
+                ));
+            }
+
             Some(rv)
         }
     };
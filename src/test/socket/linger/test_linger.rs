@@ -0,0 +1,222 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::time::Duration;
+
+use test_utils::set;
+use test_utils::socket_utils::{socket_init_helper, SocketInitMethod};
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    vec![
+        test_utils::ShadowTest::new(
+            "test_linger_option",
+            test_linger_option,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_zero_linger_reset",
+            test_zero_linger_reset,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_linger_close",
+            test_linger_close,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ]
+}
+
+/// Get a connected pair of tcp sockets.
+fn tcp_pair() -> (libc::c_int, libc::c_int) {
+    socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        0,
+        /* bind_client = */ false,
+    )
+}
+
+fn get_linger(fd: libc::c_int) -> Result<libc::linger, String> {
+    let mut linger = libc::linger {
+        l_onoff: -1,
+        l_linger: -1,
+    };
+    let mut len = std::mem::size_of_val(&linger) as libc::socklen_t;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_LINGER,
+                std::ptr::from_mut(&mut linger) as *mut libc::c_void,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    test_utils::result_assert_eq(
+        len as usize,
+        std::mem::size_of_val(&linger),
+        "Unexpected option length",
+    )?;
+    Ok(linger)
+}
+
+fn set_linger(fd: libc::c_int, onoff: libc::c_int, seconds: libc::c_int) -> Result<(), String> {
+    let linger = libc::linger {
+        l_onoff: onoff,
+        l_linger: seconds,
+    };
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_LINGER,
+                std::ptr::from_ref(&linger) as *const libc::c_void,
+                std::mem::size_of_val(&linger) as libc::socklen_t,
+            )
+        },
+        &[],
+    )?;
+    Ok(())
+}
+
+fn recv(fd: libc::c_int, len: usize) -> Result<Vec<u8>, String> {
+    let mut buf = vec![0u8; len];
+    let rv = test_utils::check_system_call!(
+        || unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) },
+        &[],
+    )?;
+    buf.truncate(rv as usize);
+    Ok(buf)
+}
+
+/// Test getting and setting `SO_LINGER`.
+fn test_linger_option() -> Result<(), String> {
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        let linger = get_linger(fd_client)?;
+        test_utils::result_assert_eq(linger.l_onoff, 0, "Linger is enabled by default")?;
+        test_utils::result_assert_eq(linger.l_linger, 0, "Unexpected default linger time")?;
+
+        set_linger(fd_client, 1, 5)?;
+        let linger = get_linger(fd_client)?;
+        test_utils::result_assert_eq(linger.l_onoff, 1, "Linger isn't enabled")?;
+        test_utils::result_assert_eq(linger.l_linger, 5, "Unexpected linger time")?;
+
+        set_linger(fd_client, 0, 5)?;
+        let linger = get_linger(fd_client)?;
+        test_utils::result_assert_eq(linger.l_onoff, 0, "Linger isn't disabled")?;
+
+        // the option value must be a full `struct linger`
+        let onoff: libc::c_int = 1;
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::setsockopt(
+                    fd_client,
+                    libc::SOL_SOCKET,
+                    libc::SO_LINGER,
+                    std::ptr::from_ref(&onoff) as *const libc::c_void,
+                    std::mem::size_of_val(&onoff) as libc::socklen_t,
+                )
+            },
+            &[libc::EINVAL],
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that closing a socket with a zero linger time resets the connection.
+fn test_zero_linger_reset() -> Result<(), String> {
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        set_linger(fd_client, 1, 0)?;
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_client) }, &[])?;
+
+        // wait for the reset to arrive
+        std::thread::sleep(Duration::from_millis(100));
+
+        // the peer sees the reset once, and then sees EOF
+        let mut buf = [0u8; 10];
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::recv(
+                    fd_server,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    0,
+                )
+            },
+            &[libc::ECONNRESET],
+        )?;
+        test_utils::result_assert_eq(recv(fd_server, 10)?, vec![], "Expected EOF")?;
+
+        Ok(())
+    })
+}
+
+/// Test that closing a socket with a non-zero linger time still delivers the sent data.
+fn test_linger_close() -> Result<(), String> {
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        set_linger(fd_client, 1, 5)?;
+
+        let data = vec![7u8; 10_000];
+        let rv = test_utils::check_system_call!(
+            || unsafe {
+                libc::send(
+                    fd_client,
+                    data.as_ptr() as *const libc::c_void,
+                    data.len(),
+                    0,
+                )
+            },
+            &[],
+        )?;
+        test_utils::result_assert_eq(rv, data.len() as isize, "Not all bytes were sent")?;
+
+        // blocks until the data has been acknowledged
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_client) }, &[])?;
+
+        let mut received = Vec::new();
+        loop {
+            let buf = recv(fd_server, 4096)?;
+            if buf.is_empty() {
+                break;
+            }
+            received.extend(buf);
+        }
+        test_utils::result_assert(received == data, "Unexpected data received")?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,305 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use nix::sys::signal;
+use test_utils::set;
+use test_utils::socket_utils::{socket_init_helper, SocketInitMethod};
+use test_utils::TestEnvironment as TestEnv;
+
+static SIGURG_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn sigurg_handler(_signo: libc::c_int) {
+    SIGURG_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    vec![
+        test_utils::ShadowTest::new(
+            "test_recv_oob",
+            test_recv_oob,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_urgent_mark",
+            test_urgent_mark,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_oobinline",
+            test_oobinline,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_sigurg",
+            test_sigurg,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ]
+}
+
+/// Get a connected pair of tcp sockets.
+fn tcp_pair() -> (libc::c_int, libc::c_int) {
+    socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        0,
+        /* bind_client = */ false,
+    )
+}
+
+fn send(fd: libc::c_int, buf: &[u8], flags: libc::c_int) -> Result<(), String> {
+    let rv = test_utils::check_system_call!(
+        || unsafe { libc::send(fd, buf.as_ptr() as *const libc::c_void, buf.len(), flags) },
+        &[],
+    )?;
+    test_utils::result_assert_eq(rv, buf.len() as isize, "Not all bytes were sent")
+}
+
+fn recv(fd: libc::c_int, len: usize, flags: libc::c_int) -> Result<Vec<u8>, String> {
+    let mut buf = vec![0u8; len];
+    let rv = test_utils::check_system_call!(
+        || unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), flags) },
+        &[],
+    )?;
+    buf.truncate(rv as usize);
+    Ok(buf)
+}
+
+/// Check that `recv()` fails with `errno`.
+fn check_recv_error(fd: libc::c_int, flags: libc::c_int, errno: libc::c_int) -> Result<(), String> {
+    let mut buf = [0u8; 1];
+    test_utils::check_system_call!(
+        || unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), flags) },
+        &[errno],
+    )?;
+    Ok(())
+}
+
+fn at_mark(fd: libc::c_int) -> Result<libc::c_int, String> {
+    let mut at_mark: libc::c_int = -1;
+    test_utils::check_system_call!(
+        || unsafe { libc::ioctl(fd, libc::SIOCATMARK, &mut at_mark) },
+        &[],
+    )?;
+    Ok(at_mark)
+}
+
+/// Wait for the data that was sent to arrive at the peer.
+fn wait_for_data() {
+    std::thread::sleep(Duration::from_millis(100));
+}
+
+/// Test reading the urgent byte with `MSG_OOB`.
+fn test_recv_oob() -> Result<(), String> {
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        // there's no urgent data yet
+        check_recv_error(fd_server, libc::MSG_OOB, libc::EINVAL)?;
+
+        send(fd_client, b"abc", 0)?;
+        send(fd_client, b"xy!", libc::MSG_OOB)?;
+        wait_for_data();
+
+        // only the last byte is urgent, and peeking doesn't consume it
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, libc::MSG_OOB | libc::MSG_PEEK)?,
+            b"!".to_vec(),
+            "Unexpected peeked urgent data",
+        )?;
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, libc::MSG_OOB)?,
+            b"!".to_vec(),
+            "Unexpected urgent data",
+        )?;
+
+        // the urgent byte can only be read once
+        check_recv_error(fd_server, libc::MSG_OOB, libc::EINVAL)?;
+
+        // the urgent byte isn't part of the normal data
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, 0)?,
+            b"abcxy".to_vec(),
+            "Unexpected normal data",
+        )?;
+        check_recv_error(fd_server, libc::MSG_DONTWAIT, libc::EAGAIN)?;
+
+        Ok(())
+    })
+}
+
+/// Test that reads stop at the urgent mark, and that `SIOCATMARK` reports the mark.
+fn test_urgent_mark() -> Result<(), String> {
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        send(fd_client, b"abc", 0)?;
+        send(fd_client, b"!", libc::MSG_OOB)?;
+        send(fd_client, b"def", 0)?;
+        wait_for_data();
+
+        test_utils::result_assert_eq(at_mark(fd_server)?, 0, "Unexpectedly at the mark")?;
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, 0)?,
+            b"abc".to_vec(),
+            "The read didn't stop at the urgent mark",
+        )?;
+        test_utils::result_assert_eq(at_mark(fd_server)?, 1, "Not at the mark")?;
+
+        // the read skips over the urgent byte
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, 0)?,
+            b"def".to_vec(),
+            "Unexpected data after the urgent mark",
+        )?;
+        test_utils::result_assert_eq(at_mark(fd_server)?, 0, "Unexpectedly at the mark")?;
+
+        // the urgent byte is gone once the read passes the mark
+        check_recv_error(fd_server, libc::MSG_OOB, libc::EINVAL)?;
+
+        Ok(())
+    })
+}
+
+/// Test that with `SO_OOBINLINE` the urgent byte stays in the normal data.
+fn test_oobinline() -> Result<(), String> {
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        let mut enabled: libc::c_int = -1;
+        let mut len = std::mem::size_of_val(&enabled) as libc::socklen_t;
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::getsockopt(
+                    fd_server,
+                    libc::SOL_SOCKET,
+                    libc::SO_OOBINLINE,
+                    std::ptr::from_mut(&mut enabled) as *mut libc::c_void,
+                    &mut len,
+                )
+            },
+            &[],
+        )?;
+        test_utils::result_assert_eq(enabled, 0, "SO_OOBINLINE is enabled by default")?;
+
+        let enable: libc::c_int = 1;
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::setsockopt(
+                    fd_server,
+                    libc::SOL_SOCKET,
+                    libc::SO_OOBINLINE,
+                    std::ptr::from_ref(&enable) as *const libc::c_void,
+                    std::mem::size_of_val(&enable) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        send(fd_client, b"abc", 0)?;
+        send(fd_client, b"!", libc::MSG_OOB)?;
+        send(fd_client, b"def", 0)?;
+        wait_for_data();
+
+        // the urgent byte can't be read out-of-band
+        check_recv_error(fd_server, libc::MSG_OOB, libc::EINVAL)?;
+
+        // reads still stop at the mark
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, 0)?,
+            b"abc".to_vec(),
+            "The read didn't stop at the urgent mark",
+        )?;
+        test_utils::result_assert_eq(at_mark(fd_server)?, 1, "Not at the mark")?;
+        test_utils::result_assert_eq(
+            recv(fd_server, 10, 0)?,
+            b"!def".to_vec(),
+            "The urgent byte isn't inline",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that the owner set with `F_SETOWN` receives `SIGURG` when urgent data arrives.
+fn test_sigurg() -> Result<(), String> {
+    let action = signal::SigAction::new(
+        signal::SigHandler::Handler(sigurg_handler),
+        signal::SaFlags::SA_RESTART,
+        signal::SigSet::empty(),
+    );
+    unsafe { signal::sigaction(signal::Signal::SIGURG, &action) }.unwrap();
+    SIGURG_RECEIVED.store(false, Ordering::SeqCst);
+
+    let (fd_client, fd_server) = tcp_pair();
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        let pid = unsafe { libc::getpid() };
+
+        // there's no owner by default
+        let owner = test_utils::check_system_call!(
+            || unsafe { libc::fcntl(fd_server, libc::F_GETOWN) },
+            &[]
+        )?;
+        test_utils::result_assert_eq(owner, 0, "Unexpected default owner")?;
+
+        // the owner must exist
+        test_utils::check_system_call!(
+            || unsafe { libc::fcntl(fd_server, libc::F_SETOWN, i32::MAX) },
+            &[libc::ESRCH],
+        )?;
+
+        test_utils::check_system_call!(
+            || unsafe { libc::fcntl(fd_server, libc::F_SETOWN, pid) },
+            &[],
+        )?;
+        let owner = test_utils::check_system_call!(
+            || unsafe { libc::fcntl(fd_server, libc::F_GETOWN) },
+            &[]
+        )?;
+        test_utils::result_assert_eq(owner, pid, "Unexpected owner")?;
+
+        // normal data doesn't send a signal
+        send(fd_client, b"abc", 0)?;
+        wait_for_data();
+        test_utils::result_assert(
+            !SIGURG_RECEIVED.load(Ordering::SeqCst),
+            "Received SIGURG for normal data",
+        )?;
+
+        send(fd_client, b"!", libc::MSG_OOB)?;
+        wait_for_data();
+        test_utils::result_assert(
+            SIGURG_RECEIVED.load(Ordering::SeqCst),
+            "Didn't receive SIGURG for urgent data",
+        )?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,391 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![];
+
+    for sock_type in [libc::SOCK_STREAM, libc::SOCK_DGRAM, libc::SOCK_SEQPACKET] {
+        let append_args = |s| format!("{s} <type={sock_type}>");
+
+        tests.extend(vec![
+            test_utils::ShadowTest::new(
+                &append_args("test_socketpair_peercred"),
+                move || test_socketpair_peercred(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_unconnected_peercred"),
+                move || test_unconnected_peercred(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_passcred_option"),
+                move || test_passcred_option(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_recv_creds"),
+                move || test_recv_creds(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_send_creds"),
+                move || test_send_creds(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+        ]);
+    }
+
+    tests.push(test_utils::ShadowTest::new(
+        "test_connect_peercred",
+        test_connect_peercred,
+        set![TestEnv::Libc, TestEnv::Shadow],
+    ));
+
+    tests
+}
+
+fn new_socketpair(sock_type: libc::c_int) -> Result<(libc::c_int, libc::c_int), String> {
+    let mut fds = [-1; 2];
+    test_utils::check_system_call!(
+        || unsafe { libc::socketpair(libc::AF_UNIX, sock_type, 0, fds.as_mut_ptr()) },
+        &[],
+    )?;
+    Ok((fds[0], fds[1]))
+}
+
+/// The credentials of this process.
+fn own_creds() -> libc::ucred {
+    libc::ucred {
+        pid: unsafe { libc::getpid() },
+        uid: unsafe { libc::geteuid() },
+        gid: unsafe { libc::getegid() },
+    }
+}
+
+fn assert_creds_eq(a: &libc::ucred, b: &libc::ucred, msg: &str) -> Result<(), String> {
+    test_utils::result_assert_eq((a.pid, a.uid, a.gid), (b.pid, b.uid, b.gid), msg)
+}
+
+fn get_peercred(fd: libc::c_int) -> Result<libc::ucred, String> {
+    let mut creds = libc::ucred {
+        pid: -1,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of_val(&creds) as libc::socklen_t;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                std::ptr::from_mut(&mut creds) as *mut libc::c_void,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    test_utils::result_assert_eq(len as usize, std::mem::size_of_val(&creds), "Wrong length")?;
+    Ok(creds)
+}
+
+fn set_passcred(fd: libc::c_int, val: libc::c_int) -> Result<(), String> {
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PASSCRED,
+                std::ptr::from_ref(&val) as *const libc::c_void,
+                std::mem::size_of_val(&val) as libc::socklen_t,
+            )
+        },
+        &[],
+    )?;
+    Ok(())
+}
+
+fn get_passcred(fd: libc::c_int) -> Result<libc::c_int, String> {
+    let mut val: libc::c_int = -1;
+    let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PASSCRED,
+                std::ptr::from_mut(&mut val) as *mut libc::c_void,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    Ok(val)
+}
+
+/// Send `data`, with `creds` in a `SCM_CREDENTIALS` control message if given.
+fn send_creds(
+    fd: libc::c_int,
+    data: &[u8],
+    creds: Option<libc::ucred>,
+) -> Result<isize, libc::c_int> {
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    let creds_len = std::mem::size_of::<libc::ucred>() as u32;
+    // a u64 array so that it's aligned for a cmsghdr
+    let mut control = vec![0u64; unsafe { libc::CMSG_SPACE(creds_len) } as usize / 8];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    if let Some(creds) = creds {
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = std::mem::size_of_val(&control[..]);
+
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_CREDENTIALS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(creds_len) as usize;
+            (libc::CMSG_DATA(cmsg) as *mut libc::ucred).write_unaligned(creds);
+        }
+    }
+
+    let rv = unsafe { libc::sendmsg(fd, &msg, 0) };
+    if rv < 0 {
+        return Err(test_utils::get_errno());
+    }
+    Ok(rv)
+}
+
+/// Receive a message. Returns the data and the credentials from any `SCM_CREDENTIALS` control
+/// message.
+fn recv_creds(fd: libc::c_int) -> Result<(Vec<u8>, Option<libc::ucred>), String> {
+    let mut buf = vec![0u8; 16];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // a u64 array so that it's aligned for a cmsghdr
+    let mut control = [0u64; 8];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control);
+
+    let rv = test_utils::check_system_call!(|| unsafe { libc::recvmsg(fd, &mut msg, 0) }, &[])?;
+    buf.truncate(rv.try_into().unwrap());
+
+    let mut creds = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        if hdr.cmsg_level == libc::SOL_SOCKET && hdr.cmsg_type == libc::SCM_CREDENTIALS {
+            let data = unsafe { libc::CMSG_DATA(cmsg) } as *const libc::ucred;
+            creds = Some(unsafe { data.read_unaligned() });
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    Ok((buf, creds))
+}
+
+/// Test that both sockets of a socket pair have the credentials of the process as their peer's
+/// credentials.
+fn test_socketpair_peercred(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd1, fd2) = new_socketpair(sock_type)?;
+
+    test_utils::run_and_close_fds(&[fd1, fd2], || {
+        assert_creds_eq(
+            &get_peercred(fd1)?,
+            &own_creds(),
+            "Unexpected peer credentials",
+        )?;
+        assert_creds_eq(
+            &get_peercred(fd2)?,
+            &own_creds(),
+            "Unexpected peer credentials",
+        )
+    })
+}
+
+/// Test the peer credentials of a socket without a peer.
+fn test_unconnected_peercred(sock_type: libc::c_int) -> Result<(), String> {
+    let fd = test_utils::check_system_call!(
+        || unsafe { libc::socket(libc::AF_UNIX, sock_type, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let expected = libc::ucred {
+            pid: 0,
+            uid: libc::uid_t::MAX,
+            gid: libc::gid_t::MAX,
+        };
+        assert_creds_eq(&get_peercred(fd)?, &expected, "Unexpected peer credentials")
+    })
+}
+
+/// Test that the connecting and accepted sockets both have peer credentials.
+fn test_connect_peercred() -> Result<(), String> {
+    let fd_server = test_utils::check_system_call!(
+        || unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) },
+        &[],
+    )?;
+    let fd_client = test_utils::check_system_call!(
+        || unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) },
+        &[],
+    )?;
+
+    // an abstract address
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as u16;
+    for (dst, src) in addr.sun_path[1..].iter_mut().zip(b"peercred") {
+        *dst = *src as libc::c_char;
+    }
+    let addr_len = (std::mem::size_of::<libc::sa_family_t>() + 1 + b"peercred".len()) as u32;
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::bind(
+                    fd_server,
+                    std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                    addr_len,
+                )
+            },
+            &[],
+        )?;
+        test_utils::check_system_call!(|| unsafe { libc::listen(fd_server, 10) }, &[])?;
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::connect(
+                    fd_client,
+                    std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                    addr_len,
+                )
+            },
+            &[],
+        )?;
+
+        let fd_accepted = test_utils::check_system_call!(
+            || unsafe { libc::accept(fd_server, std::ptr::null_mut(), std::ptr::null_mut()) },
+            &[],
+        )?;
+
+        test_utils::run_and_close_fds(&[fd_accepted], || {
+            assert_creds_eq(
+                &get_peercred(fd_client)?,
+                &own_creds(),
+                "Unexpected peer credentials",
+            )?;
+            assert_creds_eq(
+                &get_peercred(fd_accepted)?,
+                &own_creds(),
+                "Unexpected peer credentials",
+            )
+        })
+    })
+}
+
+/// Test getsockopt() and setsockopt() using the SO_PASSCRED option.
+fn test_passcred_option(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd1, fd2) = new_socketpair(sock_type)?;
+
+    test_utils::run_and_close_fds(&[fd1, fd2], || {
+        test_utils::result_assert_eq(get_passcred(fd1)?, 0, "SO_PASSCRED is enabled")?;
+
+        set_passcred(fd1, 1)?;
+        test_utils::result_assert_eq(get_passcred(fd1)?, 1, "SO_PASSCRED is disabled")?;
+
+        set_passcred(fd1, 0)?;
+        test_utils::result_assert_eq(get_passcred(fd1)?, 0, "SO_PASSCRED is enabled")
+    })
+}
+
+/// Test that credentials are only received by a socket with the SO_PASSCRED option.
+fn test_recv_creds(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(sock_type)?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver], || {
+        send_creds(fd_sender, b"a", None).map_err(|e| e.to_string())?;
+        let (data, creds) = recv_creds(fd_receiver)?;
+        test_utils::result_assert_eq(&data[..], b"a", "Unexpected data received")?;
+        test_utils::result_assert(creds.is_none(), "Unexpected credentials")?;
+
+        set_passcred(fd_receiver, 1)?;
+
+        send_creds(fd_sender, b"b", None).map_err(|e| e.to_string())?;
+        let (data, creds) = recv_creds(fd_receiver)?;
+        test_utils::result_assert_eq(&data[..], b"b", "Unexpected data received")?;
+        let Some(creds) = creds else {
+            return Err("No credentials received".to_string());
+        };
+        assert_creds_eq(&creds, &own_creds(), "Unexpected credentials")
+    })
+}
+
+/// Test sending explicit credentials, and that an unprivileged process can't send another
+/// process' credentials.
+fn test_send_creds(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(sock_type)?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver], || {
+        set_passcred(fd_receiver, 1)?;
+
+        let rv = send_creds(fd_sender, b"a", Some(own_creds())).map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(rv, 1, "Not all data was sent")?;
+
+        let (data, creds) = recv_creds(fd_receiver)?;
+        test_utils::result_assert_eq(&data[..], b"a", "Unexpected data received")?;
+        let Some(creds) = creds else {
+            return Err("No credentials received".to_string());
+        };
+        assert_creds_eq(&creds, &own_creds(), "Unexpected credentials")?;
+
+        // a privileged process can send other credentials
+        if unsafe { libc::geteuid() } == 0 {
+            return Ok(());
+        }
+
+        let other = libc::ucred {
+            pid: own_creds().pid + 1,
+            ..own_creds()
+        };
+        let rv = send_creds(fd_sender, b"b", Some(other));
+        test_utils::result_assert_eq(rv, Err(libc::EPERM), "Unexpected result")
+    })
+}
@@ -0,0 +1,282 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![];
+
+    for sock_type in [libc::SOCK_STREAM, libc::SOCK_DGRAM, libc::SOCK_SEQPACKET] {
+        let append_args = |s| format!("{s} <type={sock_type}>");
+
+        tests.extend(vec![
+            test_utils::ShadowTest::new(
+                &append_args("test_pass_fd"),
+                move || test_pass_fd(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_ctrunc"),
+                move || test_ctrunc(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_cmsg_cloexec"),
+                move || test_cmsg_cloexec(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_bad_fd"),
+                move || test_bad_fd(sock_type),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+        ]);
+    }
+
+    tests.push(test_utils::ShadowTest::new(
+        "test_stream_boundary",
+        test_stream_boundary,
+        set![TestEnv::Libc, TestEnv::Shadow],
+    ));
+
+    tests
+}
+
+fn new_socketpair(sock_type: libc::c_int) -> Result<(libc::c_int, libc::c_int), String> {
+    let mut fds = [-1; 2];
+    test_utils::check_system_call!(
+        || unsafe { libc::socketpair(libc::AF_UNIX, sock_type, 0, fds.as_mut_ptr()) },
+        &[],
+    )?;
+    Ok((fds[0], fds[1]))
+}
+
+fn new_pipe() -> Result<(libc::c_int, libc::c_int), String> {
+    let mut fds = [-1; 2];
+    test_utils::check_system_call!(|| unsafe { libc::pipe(fds.as_mut_ptr()) }, &[])?;
+    Ok((fds[0], fds[1]))
+}
+
+/// Send `data` with the descriptors `fds` in a `SCM_RIGHTS` control message.
+fn send_fds(fd: libc::c_int, data: &[u8], fds: &[libc::c_int]) -> Result<isize, libc::c_int> {
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    let fds_len = std::mem::size_of_val(fds) as u32;
+    // a u64 array so that it's aligned for a cmsghdr
+    let mut control = vec![0u64; unsafe { libc::CMSG_SPACE(fds_len) } as usize / 8];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control[..]);
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as usize;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut _, fds.len());
+    }
+
+    let rv = unsafe { libc::sendmsg(fd, &msg, 0) };
+    if rv < 0 {
+        return Err(test_utils::get_errno());
+    }
+    Ok(rv)
+}
+
+/// Receive up to `buf_len` bytes with a control buffer of `control_len` bytes. Returns the data,
+/// the descriptors from any `SCM_RIGHTS` control message, and the returned message flags.
+fn recv_fds(
+    fd: libc::c_int,
+    buf_len: usize,
+    control_len: usize,
+    flags: libc::c_int,
+) -> Result<(Vec<u8>, Vec<libc::c_int>, libc::c_int), String> {
+    let mut buf = vec![0u8; buf_len];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // a u64 array so that it's aligned for a cmsghdr
+    let mut control = vec![0u64; control_len.div_ceil(8)];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if control_len > 0 {
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control_len;
+    }
+
+    let rv = test_utils::check_system_call!(|| unsafe { libc::recvmsg(fd, &mut msg, flags) }, &[])?;
+    buf.truncate(rv.try_into().unwrap());
+
+    let mut fds = vec![];
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        if hdr.cmsg_level == libc::SOL_SOCKET && hdr.cmsg_type == libc::SCM_RIGHTS {
+            let data = unsafe { libc::CMSG_DATA(cmsg) } as *const libc::c_int;
+            let num = (hdr.cmsg_len - unsafe { libc::CMSG_LEN(0) } as usize)
+                / std::mem::size_of::<libc::c_int>();
+            for i in 0..num {
+                fds.push(unsafe { data.add(i).read_unaligned() });
+            }
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    Ok((buf, fds, msg.msg_flags))
+}
+
+/// Check that a byte written to `write_fd` can be read from `read_fd`.
+fn check_pipe(read_fd: libc::c_int, write_fd: libc::c_int) -> Result<(), String> {
+    test_utils::check_system_call!(
+        || unsafe { libc::write(write_fd, [7u8].as_ptr() as *const libc::c_void, 1) },
+        &[],
+    )?;
+
+    let mut buf = [0u8; 1];
+    test_utils::check_system_call!(
+        || unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut libc::c_void, 1) },
+        &[],
+    )?;
+    test_utils::result_assert_eq(buf[0], 7, "Unexpected byte read from the pipe")
+}
+
+/// Test that a passed descriptor refers to the same open file, even after the sender closes its
+/// own descriptor.
+fn test_pass_fd(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(sock_type)?;
+    let (fd_read, fd_write) = new_pipe()?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver, fd_read], || {
+        let rv = send_fds(fd_sender, b"hello", &[fd_write]).map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(rv, 5, "Not all data was sent")?;
+
+        // the passed file stays open
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_write) }, &[])?;
+
+        let (data, fds, flags) = recv_fds(fd_receiver, 16, 64, 0)?;
+        test_utils::result_assert_eq(&data[..], b"hello", "Unexpected data received")?;
+        test_utils::result_assert_eq(fds.len(), 1, "Unexpected number of descriptors")?;
+        test_utils::result_assert_eq(flags & libc::MSG_CTRUNC, 0, "MSG_CTRUNC was set")?;
+
+        test_utils::run_and_close_fds(&fds, || check_pipe(fd_read, fds[0]))
+    })
+}
+
+/// Test that descriptors that don't fit in the control buffer are closed, and that `MSG_CTRUNC`
+/// is set.
+fn test_ctrunc(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(sock_type)?;
+    let (fd_read, fd_write) = new_pipe()?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver, fd_read, fd_write], || {
+        send_fds(fd_sender, b"a", &[fd_write, fd_write]).map_err(|e| e.to_string())?;
+        send_fds(fd_sender, b"b", &[fd_write]).map_err(|e| e.to_string())?;
+
+        // only room for a single descriptor
+        let control_len = unsafe { libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) };
+        let (data, fds, flags) = recv_fds(fd_receiver, 16, control_len as usize, 0)?;
+        test_utils::result_assert_eq(&data[..], b"a", "Unexpected data received")?;
+        test_utils::result_assert_eq(fds.len(), 1, "Unexpected number of descriptors")?;
+        test_utils::result_assert(flags & libc::MSG_CTRUNC != 0, "MSG_CTRUNC was not set")?;
+        test_utils::run_and_close_fds(&fds, || check_pipe(fd_read, fds[0]))?;
+
+        // no control buffer
+        let (data, fds, flags) = recv_fds(fd_receiver, 16, 0, 0)?;
+        test_utils::result_assert_eq(&data[..], b"b", "Unexpected data received")?;
+        test_utils::result_assert_eq(fds.len(), 0, "Unexpected number of descriptors")?;
+        test_utils::result_assert(flags & libc::MSG_CTRUNC != 0, "MSG_CTRUNC was not set")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `MSG_CMSG_CLOEXEC` sets the close-on-exec flag of the received descriptors.
+fn test_cmsg_cloexec(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(sock_type)?;
+    let (fd_read, fd_write) = new_pipe()?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver, fd_read, fd_write], || {
+        for (flags, expected) in [(0, 0), (libc::MSG_CMSG_CLOEXEC, libc::FD_CLOEXEC)] {
+            send_fds(fd_sender, b"a", &[fd_write]).map_err(|e| e.to_string())?;
+
+            let (_data, fds, _flags) = recv_fds(fd_receiver, 16, 64, flags)?;
+            test_utils::result_assert_eq(fds.len(), 1, "Unexpected number of descriptors")?;
+
+            test_utils::run_and_close_fds(&fds, || {
+                let fd_flags = test_utils::check_system_call!(
+                    || unsafe { libc::fcntl(fds[0], libc::F_GETFD) },
+                    &[],
+                )?;
+                test_utils::result_assert_eq(fd_flags, expected, "Unexpected descriptor flags")
+            })?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Test that passing an invalid descriptor fails.
+fn test_bad_fd(sock_type: libc::c_int) -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(sock_type)?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver], || {
+        let rv = send_fds(fd_sender, b"a", &[-1]);
+        test_utils::result_assert_eq(rv, Err(libc::EBADF), "Unexpected result")
+    })
+}
+
+/// Test that a stream read stops after the data that the descriptors were sent with.
+fn test_stream_boundary() -> Result<(), String> {
+    let (fd_sender, fd_receiver) = new_socketpair(libc::SOCK_STREAM)?;
+    let (fd_read, fd_write) = new_pipe()?;
+
+    test_utils::run_and_close_fds(&[fd_sender, fd_receiver, fd_read, fd_write], || {
+        send_fds(fd_sender, b"ab", &[]).map_err(|e| e.to_string())?;
+        send_fds(fd_sender, b"cd", &[fd_write]).map_err(|e| e.to_string())?;
+        send_fds(fd_sender, b"ef", &[]).map_err(|e| e.to_string())?;
+
+        let (data, fds, _flags) = recv_fds(fd_receiver, 16, 64, 0)?;
+        test_utils::result_assert_eq(&data[..], b"abcd", "Unexpected data received")?;
+        test_utils::result_assert_eq(fds.len(), 1, "Unexpected number of descriptors")?;
+        test_utils::run_and_close_fds(&fds, || Ok(()))?;
+
+        let (data, fds, _flags) = recv_fds(fd_receiver, 16, 64, 0)?;
+        test_utils::result_assert_eq(&data[..], b"ef", "Unexpected data received")?;
+        test_utils::result_assert_eq(fds.len(), 0, "Unexpected number of descriptors")?;
+
+        Ok(())
+    })
+}
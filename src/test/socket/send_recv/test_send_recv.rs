@@ -217,14 +217,26 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
                         move || test_blocking(sys_method, init_method, sock_type),
                         set![TestEnv::Libc, TestEnv::Shadow],
                     ),
+                    test_utils::ShadowTest::new(
+                        &append_args("test_flag_waitall"),
+                        move || test_flag_waitall(sys_method, init_method, sock_type),
+                        set![TestEnv::Libc, TestEnv::Shadow],
+                    ),
                 ]);
             }
 
-            tests.extend(vec![test_utils::ShadowTest::new(
-                &append_args("test_nonblocking_stream"),
-                move || test_nonblocking_stream(sys_method, init_method),
-                set![TestEnv::Libc, TestEnv::Shadow],
-            )]);
+            tests.extend(vec![
+                test_utils::ShadowTest::new(
+                    &append_args("test_nonblocking_stream"),
+                    move || test_nonblocking_stream(sys_method, init_method),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_flag_waitall_peer_close"),
+                    move || test_flag_waitall_peer_close(sys_method, init_method),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+            ]);
         }
 
         let flags = [0, libc::SOCK_NONBLOCK, libc::SOCK_CLOEXEC];
@@ -341,11 +353,7 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
                                         flag,
                                     )
                                 },
-                                match init_method.domain() {
-                                    // TODO: enable if shadow ever supports ICMP
-                                    libc::AF_INET => set![TestEnv::Libc],
-                                    _ => set![TestEnv::Libc, TestEnv::Shadow],
-                                },
+                                set![TestEnv::Libc, TestEnv::Shadow],
                             ),
                         ]);
                     }
@@ -404,11 +412,18 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
             )]);
         }
 
-        tests.extend(vec![test_utils::ShadowTest::new(
-            &append_args("test_large_buf_udp"),
-            move || test_large_buf_udp(sys_method),
-            set![TestEnv::Libc, TestEnv::Shadow],
-        )]);
+        tests.extend(vec![
+            test_utils::ShadowTest::new(
+                &append_args("test_large_buf_udp"),
+                move || test_large_buf_udp(sys_method),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_econnrefused_udp"),
+                move || test_econnrefused_udp(sys_method),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+        ]);
     }
 
     let init_methods = [
@@ -1118,6 +1133,121 @@ fn test_blocking(
     })
 }
 
+/// Test recvfrom() using the `MSG_WAITALL` flag when the data arrives in multiple parts.
+fn test_flag_waitall(
+    sys_method: SendRecvMethod,
+    init_method: SocketInitMethod,
+    sock_type: libc::c_int,
+) -> Result<(), String> {
+    let (fd_client, fd_server) =
+        socket_init_helper(init_method, sock_type, 0, /* bind_client = */ false);
+
+    let outbuf_5_bytes: Vec<u8> = vec![1u8; 5];
+    let mut inbuf_10_bytes: Vec<u8> = vec![0u8; 10];
+
+    let sendto_args = SendtoArguments {
+        fd: fd_client,
+        len: outbuf_5_bytes.len(),
+        buf: Some(&outbuf_5_bytes),
+        ..Default::default()
+    };
+
+    let mut recvfrom_args = RecvfromArguments {
+        fd: fd_server,
+        len: inbuf_10_bytes.len(),
+        buf: Some(&mut inbuf_10_bytes),
+        flags: libc::MSG_WAITALL,
+        ..Default::default()
+    };
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        std::thread::scope(|scope| {
+            // send 5 bytes, wait 100 ms, and then send 5 more bytes
+            let handle = scope.spawn(move || {
+                check_send_call(&sendto_args, sys_method, &[], true)?;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                check_send_call(&sendto_args, sys_method, &[], true)
+            });
+
+            let time_start = std::time::Instant::now();
+
+            if sock_type == libc::SOCK_STREAM {
+                // should wait for all 10 bytes
+                check_recv_call(&mut recvfrom_args, sys_method, &[], true)?;
+                assert!(time_start.elapsed() > std::time::Duration::from_millis(70));
+            } else {
+                // message-based sockets ignore the flag and return the first message
+                let (rv, _) = check_recv_call(&mut recvfrom_args, sys_method, &[], false)?;
+                assert_eq!(rv, 5);
+                assert!(time_start.elapsed() < std::time::Duration::from_millis(70));
+            }
+
+            handle.join().unwrap()?;
+
+            Ok(())
+        })
+    })
+}
+
+/// Test recvfrom() using the `MSG_WAITALL` flag on a stream socket when the peer closes before
+/// sending all of the requested data.
+fn test_flag_waitall_peer_close(
+    sys_method: SendRecvMethod,
+    init_method: SocketInitMethod,
+) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_STREAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    let outbuf_5_bytes: Vec<u8> = vec![1u8; 5];
+    let mut inbuf_10_bytes: Vec<u8> = vec![0u8; 10];
+
+    let sendto_args = SendtoArguments {
+        fd: fd_client,
+        len: outbuf_5_bytes.len(),
+        buf: Some(&outbuf_5_bytes),
+        ..Default::default()
+    };
+
+    let mut recvfrom_args = RecvfromArguments {
+        fd: fd_server,
+        len: inbuf_10_bytes.len(),
+        buf: Some(&mut inbuf_10_bytes),
+        flags: libc::MSG_WAITALL,
+        ..Default::default()
+    };
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        std::thread::scope(|scope| {
+            // send 5 bytes, wait 100 ms, and then close the socket
+            let handle = scope.spawn(move || {
+                check_send_call(&sendto_args, sys_method, &[], true)?;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                nix::unistd::close(fd_client).unwrap();
+                Ok::<_, String>(())
+            });
+
+            // should return the 5 bytes that were sent before the close
+            let time_start = std::time::Instant::now();
+            let (rv, _) = check_recv_call(&mut recvfrom_args, sys_method, &[], false)?;
+            assert_eq!(rv, 5);
+            assert!(time_start.elapsed() > std::time::Duration::from_millis(70));
+
+            handle.join().unwrap()?;
+
+            // the next recv returns EOF
+            recvfrom_args.flags = 0;
+            let (rv, _) = check_recv_call(&mut recvfrom_args, sys_method, &[], false)?;
+            assert_eq!(rv, 0);
+
+            Ok(())
+        })
+    })
+}
+
 /// Test sendto() and recvfrom() using a non-blocking stream socket.
 fn test_nonblocking_stream(
     sys_method: SendRecvMethod,
@@ -1888,6 +2018,9 @@ fn test_recvfrom_econnrefused_after_sendto(
         // send 3 bytes; unix sockets will return an error
         simple_sendto_helper(sys_method, fd_client, &[1u8, 2, 3], expected_errnos, true)?;
 
+        // shadow needs to run events (inet sockets receive an ICMP "port unreachable" error)
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
         let expected_errnos = match (init_method.domain(), sock_type) {
             // connectionless unix sockets
             (libc::AF_UNIX, libc::SOCK_DGRAM) => &[libc::EAGAIN][..],
@@ -1915,6 +2048,66 @@ fn test_recvfrom_econnrefused_after_sendto(
     })
 }
 
+/// Test that a connected UDP socket reports ECONNREFUSED after sending to a port that no socket is
+/// bound to, and that the error is cleared once it's returned.
+fn test_econnrefused_udp(sys_method: SendRecvMethod) -> Result<(), String> {
+    let fd_client =
+        unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_NONBLOCK, 0) };
+    let fd_peer = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_NONBLOCK, 0) };
+    assert!(fd_client >= 0);
+    assert!(fd_peer >= 0);
+
+    // bind the peer socket to some unused address
+    let (peer_addr, peer_addr_len) = autobind_helper(fd_peer, libc::AF_INET);
+    // connect the client to the peer
+    dgram_connect_helper(fd_client, peer_addr, peer_addr_len);
+
+    // close the peer so that its port is unreachable
+    nix::unistd::close(fd_peer).unwrap();
+
+    let so_error =
+        || nix::sys::socket::getsockopt(fd_client, nix::sys::socket::sockopt::SocketError).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_client], || {
+        // the send succeeds, but the peer's host replies with an ICMP "port unreachable" error
+        simple_sendto_helper(sys_method, fd_client, &[1u8, 2, 3], &[], true)?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // the next send returns the error, which clears it
+        simple_sendto_helper(
+            sys_method,
+            fd_client,
+            &[1u8, 2, 3],
+            &[libc::ECONNREFUSED],
+            true,
+        )?;
+        test_utils::result_assert_eq(so_error(), 0, "Error was not cleared by send")?;
+
+        // the datagram wasn't sent above, so send another to cause another error
+        simple_sendto_helper(sys_method, fd_client, &[1u8, 2, 3], &[], true)?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // reading SO_ERROR returns the error and clears it
+        test_utils::result_assert_eq(so_error(), libc::ECONNREFUSED, "Unexpected SO_ERROR")?;
+        test_utils::result_assert_eq(so_error(), 0, "Error was not cleared by SO_ERROR")?;
+
+        // there's no error to return
+        simple_recvfrom_helper(
+            sys_method,
+            fd_client,
+            &mut [0u8; 3],
+            &[libc::EWOULDBLOCK],
+            false,
+        )?;
+
+        Ok(())
+    })
+}
+
 /// Test recvfrom() using a dgram socket with a buffer that is too small to contain all of
 /// received the data.
 fn test_short_recv_buf_dgram(
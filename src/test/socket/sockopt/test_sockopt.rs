@@ -4,6 +4,7 @@
  */
 
 use test_utils::set;
+use test_utils::socket_utils::{socket_init_helper, SocketInitMethod};
 use test_utils::AsMutPtr;
 use test_utils::TestEnvironment as TestEnv;
 
@@ -139,6 +140,16 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
             test_invalid_level,
             set![TestEnv::Libc, TestEnv::Shadow],
         ),
+        test_utils::ShadowTest::new(
+            "test_accept_inherits_options",
+            test_accept_inherits_options,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_tcp_info_sack",
+            test_tcp_info_sack,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
     ];
 
     let domains = [libc::AF_INET];
@@ -913,6 +924,27 @@ fn test_tcp_congestion(domain: libc::c_int, sock_type: libc::c_int) -> Result<()
         };
         check_setsockopt_call(&mut set_args_1, &expected_errnos)?;
 
+        if sock_type == libc::SOCK_STREAM {
+            // switch to a different algorithm and back
+            for name in ["cubic", "reno"] {
+                let mut set_args = SetsockoptArguments::new(fd, level, optname, Some(name.into()));
+                check_setsockopt_call(&mut set_args, &[])?;
+
+                let mut get_args =
+                    GetsockoptArguments::new(fd, level, optname, Some(vec![0u8; 16]));
+                check_getsockopt_call(&mut get_args, &[])?;
+
+                let returned_str = get_args.optval.unwrap();
+                let returned_str =
+                    &returned_str[..returned_str.iter().position(|&c| c == b'\0').unwrap()];
+                test_utils::result_assert_eq(
+                    returned_str,
+                    name.as_bytes(),
+                    "Unexpected value for TCP_CONGESTION",
+                )?;
+            }
+        }
+
         // try setting an invalid name
         let expected_errnos = if sock_type == libc::SOCK_STREAM {
             vec![libc::ENOENT]
@@ -925,6 +957,105 @@ fn test_tcp_congestion(domain: libc::c_int, sock_type: libc::c_int) -> Result<()
     })
 }
 
+/// Test that a socket returned by accept() has the socket options of the listening socket.
+fn test_accept_inherits_options() -> Result<(), String> {
+    let fd_server = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    let fd_client = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(fd_server >= 0);
+    assert!(fd_client >= 0);
+
+    let options = [
+        (libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1),
+        (libc::SOL_SOCKET, libc::SO_RCVBUF, 40_000),
+        (libc::SOL_SOCKET, libc::SO_SNDBUF, 50_000),
+        (libc::SOL_TCP, libc::TCP_NODELAY, 1),
+    ];
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        for (level, optname, optval) in options {
+            let mut args = SetsockoptArguments::new(
+                fd_server,
+                level,
+                optname,
+                Some(optval.to_ne_bytes().into()),
+            );
+            check_setsockopt_call(&mut args, &[])?;
+        }
+
+        let mut addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as u16,
+            sin_port: 0u16.to_be(),
+            sin_addr: libc::in_addr {
+                s_addr: libc::INADDR_LOOPBACK.to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+        let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+        let addr_ptr = std::ptr::from_mut(&mut addr) as *mut libc::sockaddr;
+        assert_eq!(unsafe { libc::bind(fd_server, addr_ptr, addr_len) }, 0);
+        assert_eq!(unsafe { libc::listen(fd_server, 10) }, 0);
+        assert_eq!(
+            unsafe { libc::getsockname(fd_server, addr_ptr, &mut addr_len) },
+            0
+        );
+        assert_eq!(unsafe { libc::connect(fd_client, addr_ptr, addr_len) }, 0);
+
+        let fd_accepted =
+            unsafe { libc::accept(fd_server, std::ptr::null_mut(), std::ptr::null_mut()) };
+        assert!(fd_accepted >= 0);
+
+        test_utils::run_and_close_fds(&[fd_accepted], || {
+            for (level, optname, _) in options {
+                test_utils::result_assert_eq(
+                    get_int_sockopt(fd_accepted, level, optname)?,
+                    get_int_sockopt(fd_server, level, optname)?,
+                    &format!("Option {optname} at level {level} was not inherited"),
+                )?;
+            }
+
+            Ok(())
+        })
+    })
+}
+
+/// Test that TCP_INFO reports that both sides of a connection negotiated SACK.
+fn test_tcp_info_sack() -> Result<(), String> {
+    // from linux's 'include/uapi/linux/tcp.h'
+    const TCPI_OPT_SACK: u8 = 2;
+
+    let (fd_client, fd_server) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        for fd in [fd_client, fd_server] {
+            let mut args =
+                GetsockoptArguments::new(fd, libc::SOL_TCP, libc::TCP_INFO, Some(vec![0u8; 20]));
+            check_getsockopt_call(&mut args, &[])?;
+
+            // 'tcpi_options' is the 6th byte of 'struct tcp_info'
+            let options = args.optval.unwrap()[5];
+            test_utils::result_assert(options & TCPI_OPT_SACK != 0, "SACK was not negotiated")?;
+        }
+
+        Ok(())
+    })
+}
+
+fn get_int_sockopt(
+    fd: libc::c_int,
+    level: libc::c_int,
+    optname: libc::c_int,
+) -> Result<i32, String> {
+    let mut args = GetsockoptArguments::new(fd, level, optname, Some(vec![0u8; 4]));
+    check_getsockopt_call(&mut args, &[])?;
+    Ok(i32::from_ne_bytes(args.optval.unwrap().try_into().unwrap()))
+}
+
 fn check_getsockopt_call(
     args: &mut GetsockoptArguments,
     expected_errnos: &[libc::c_int],
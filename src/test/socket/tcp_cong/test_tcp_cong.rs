@@ -0,0 +1,131 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use test_utils::set;
+use test_utils::socket_utils::{socket_init_helper, SocketInitMethod};
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    // unprivileged processes on linux can't use bbr unless it's in
+    // 'net.ipv4.tcp_allowed_congestion_control'
+    let algorithms = [
+        ("reno", set![TestEnv::Libc, TestEnv::Shadow]),
+        ("cubic", set![TestEnv::Libc, TestEnv::Shadow]),
+        ("bbr", set![TestEnv::Shadow]),
+    ];
+
+    algorithms
+        .into_iter()
+        .map(|(name, passing)| {
+            test_utils::ShadowTest::new(
+                &format!("test_bulk_transfer <algorithm={name}>"),
+                move || test_bulk_transfer(name),
+                passing,
+            )
+        })
+        .collect()
+}
+
+fn set_congestion(fd: libc::c_int, name: &str) -> Result<(), String> {
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_TCP,
+                libc::TCP_CONGESTION,
+                name.as_ptr() as *const libc::c_void,
+                name.len() as libc::socklen_t,
+            )
+        },
+        &[],
+    )?;
+    Ok(())
+}
+
+/// Test that a large transfer completes with the given congestion control algorithm.
+fn test_bulk_transfer(name: &str) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        set_congestion(fd_client, name)?;
+
+        let data: Vec<u8> = (0..2_000_000u32).map(|x| x as u8).collect();
+        let data_clone = data.clone();
+
+        let sender = std::thread::spawn(move || -> Result<(), String> {
+            test_utils::run_and_close_fds(&[fd_client], || {
+                let mut sent = 0;
+                while sent < data_clone.len() {
+                    let remaining = &data_clone[sent..];
+                    let rv = test_utils::check_system_call!(
+                        || unsafe {
+                            libc::send(
+                                fd_client,
+                                remaining.as_ptr() as *const libc::c_void,
+                                remaining.len(),
+                                0,
+                            )
+                        },
+                        &[],
+                    )?;
+                    sent += rv as usize;
+                }
+                Ok(())
+            })
+        });
+
+        let mut received = Vec::new();
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let rv = test_utils::check_system_call!(
+                || unsafe {
+                    libc::recv(
+                        fd_server,
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                        0,
+                    )
+                },
+                &[],
+            )?;
+            if rv == 0 {
+                break;
+            }
+            received.extend_from_slice(&buf[..rv as usize]);
+        }
+
+        sender.join().unwrap()?;
+
+        test_utils::result_assert(received == data, "Unexpected data received")?;
+
+        Ok(())
+    })
+}
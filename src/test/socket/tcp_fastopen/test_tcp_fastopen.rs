@@ -0,0 +1,365 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+// from linux's 'include/uapi/linux/tcp.h' and 'include/linux/socket.h'
+const TCP_FASTOPEN: libc::c_int = 23;
+const MSG_FASTOPEN: libc::c_int = 0x20000000;
+const TCPI_OPT_SYN_DATA: u8 = 32;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    vec![
+        test_utils::ShadowTest::new(
+            "test_fastopen_option",
+            test_fastopen_option,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_fastopen_transfer",
+            test_fastopen_transfer,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        // linux only accepts SYN data if the server bit of 'net.ipv4.tcp_fastopen' is set
+        test_utils::ShadowTest::new(
+            "test_fastopen_syn_data",
+            test_fastopen_syn_data,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_fastopen_queue_full",
+            test_fastopen_queue_full,
+            set![TestEnv::Shadow],
+        ),
+    ]
+}
+
+fn new_tcp_socket() -> Result<libc::c_int, String> {
+    test_utils::check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) },
+        &[],
+    )
+}
+
+fn set_fastopen(fd: libc::c_int, queue_len: libc::c_int) -> Result<(), String> {
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_TCP,
+                TCP_FASTOPEN,
+                std::ptr::from_ref(&queue_len) as *const libc::c_void,
+                std::mem::size_of_val(&queue_len) as libc::socklen_t,
+            )
+        },
+        &[],
+    )?;
+    Ok(())
+}
+
+fn get_fastopen(fd: libc::c_int) -> Result<libc::c_int, String> {
+    let mut queue_len: libc::c_int = -1;
+    let mut len = std::mem::size_of_val(&queue_len) as libc::socklen_t;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_TCP,
+                TCP_FASTOPEN,
+                std::ptr::from_mut(&mut queue_len) as *mut libc::c_void,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    Ok(queue_len)
+}
+
+/// Get the 'tcpi_options' field of the socket's TCP_INFO.
+fn get_tcpi_options(fd: libc::c_int) -> Result<u8, String> {
+    let mut info = [0u8; 20];
+    let mut len = info.len() as libc::socklen_t;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_TCP,
+                libc::TCP_INFO,
+                info.as_mut_ptr() as *mut libc::c_void,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    // 'tcpi_options' is the 6th byte of 'struct tcp_info'
+    Ok(info[5])
+}
+
+/// Create a listening socket with TCP Fast Open enabled, and return it with its address.
+fn fastopen_listener(queue_len: libc::c_int) -> Result<(libc::c_int, libc::sockaddr_in), String> {
+    let fd = new_tcp_socket()?;
+
+    let mut addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: 0u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: libc::INADDR_LOOPBACK.to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::bind(
+                fd,
+                std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                addr_len,
+            )
+        },
+        &[],
+    )?;
+    set_fastopen(fd, queue_len)?;
+    test_utils::check_system_call!(|| unsafe { libc::listen(fd, 10) }, &[])?;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockname(
+                fd,
+                std::ptr::from_mut(&mut addr) as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        },
+        &[],
+    )?;
+
+    Ok((fd, addr))
+}
+
+/// Connect to `addr` with a blocking `sendto(MSG_FASTOPEN)`, accept the connection on `fd_server`,
+/// and check that the data arrives. Returns the connected sockets.
+fn fastopen_connect(
+    fd_server: libc::c_int,
+    addr: &libc::sockaddr_in,
+) -> Result<(libc::c_int, libc::c_int), String> {
+    let data = b"hello";
+    let fd_client = fastopen_send(addr, data)?;
+
+    let fd_peer = test_utils::check_system_call!(
+        || unsafe { libc::accept(fd_server, std::ptr::null_mut(), std::ptr::null_mut()) },
+        &[],
+    )?;
+
+    let mut buf = [0u8; 16];
+    let rv = test_utils::check_system_call!(
+        || unsafe { libc::recv(fd_peer, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) },
+        &[],
+    )?;
+    test_utils::result_assert_eq(&buf[..rv as usize], &data[..], "Unexpected data received")?;
+
+    Ok((fd_client, fd_peer))
+}
+
+/// Connect a new socket to `addr` with a blocking `sendto(MSG_FASTOPEN)` of `data`, and return it.
+fn fastopen_send(addr: &libc::sockaddr_in, data: &[u8]) -> Result<libc::c_int, String> {
+    let fd_client = new_tcp_socket()?;
+
+    let rv = test_utils::check_system_call!(
+        || unsafe {
+            libc::sendto(
+                fd_client,
+                data.as_ptr() as *const libc::c_void,
+                data.len(),
+                MSG_FASTOPEN,
+                std::ptr::from_ref(addr) as *const libc::sockaddr,
+                std::mem::size_of_val(addr) as libc::socklen_t,
+            )
+        },
+        &[],
+    )?;
+    test_utils::result_assert_eq(rv, data.len() as isize, "Not all data was sent")?;
+
+    Ok(fd_client)
+}
+
+/// Send a reply from the server to the client. The client receiving it means that the client
+/// has also completed the handshake.
+fn send_reply(fd_client: libc::c_int, fd_peer: libc::c_int) -> Result<(), String> {
+    let reply = b"world";
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::send(
+                fd_peer,
+                reply.as_ptr() as *const libc::c_void,
+                reply.len(),
+                0,
+            )
+        },
+        &[],
+    )?;
+
+    let mut buf = [0u8; 16];
+    let rv = test_utils::check_system_call!(
+        || unsafe {
+            libc::recv(
+                fd_client,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        },
+        &[],
+    )?;
+    test_utils::result_assert_eq(&buf[..rv as usize], &reply[..], "Unexpected reply received")
+}
+
+/// Test getsockopt() and setsockopt() using the TCP_FASTOPEN option.
+fn test_fastopen_option() -> Result<(), String> {
+    let fd = new_tcp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        test_utils::result_assert_eq(get_fastopen(fd)?, 0, "Fast Open is enabled by default")?;
+
+        set_fastopen(fd, 5)?;
+        test_utils::result_assert_eq(get_fastopen(fd)?, 5, "Unexpected queue length")?;
+
+        let queue_len: libc::c_int = -1;
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_TCP,
+                    TCP_FASTOPEN,
+                    std::ptr::from_ref(&queue_len) as *const libc::c_void,
+                    std::mem::size_of_val(&queue_len) as libc::socklen_t,
+                )
+            },
+            &[libc::EINVAL],
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that data sent with MSG_FASTOPEN arrives, whether or not the client has a cookie, and that
+/// the connection can then be used in both directions.
+fn test_fastopen_transfer() -> Result<(), String> {
+    let (fd_server, addr) = fastopen_listener(5)?;
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        // the first connection gets a cookie, and the second connection may use it
+        for _ in 0..2 {
+            let (fd_client, fd_peer) = fastopen_connect(fd_server, &addr)?;
+
+            test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+                send_reply(fd_client, fd_peer)
+            })?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Test that the data is only sent in the SYN once the client has a cookie.
+fn test_fastopen_syn_data() -> Result<(), String> {
+    let (fd_server, addr) = fastopen_listener(5)?;
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        // the server address may have given us a cookie in an earlier test
+        let (fd_client, fd_peer) = fastopen_connect(fd_server, &addr)?;
+        test_utils::run_and_close_fds(&[fd_client, fd_peer], || Ok(()))?;
+
+        // we have a cookie now
+        let (fd_client, fd_peer) = fastopen_connect(fd_server, &addr)?;
+        test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+            send_reply(fd_client, fd_peer)?;
+
+            for fd in [fd_client, fd_peer] {
+                let options = get_tcpi_options(fd)?;
+                test_utils::result_assert(
+                    options & TCPI_OPT_SYN_DATA != 0,
+                    "The SYN data was not acknowledged",
+                )?;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Test that a listener only accepts SYN data from as many connections as its queue length at a
+/// time, and that later connections fall back to the normal handshake.
+fn test_fastopen_queue_full() -> Result<(), String> {
+    let (fd_server, addr) = fastopen_listener(1)?;
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        // get a cookie
+        let (fd_client, fd_peer) = fastopen_connect(fd_server, &addr)?;
+        test_utils::run_and_close_fds(&[fd_client, fd_peer], || Ok(()))?;
+
+        // both SYNs are sent before either handshake completes, so the first connection fills
+        // the queue
+        let fd_client_1 = fastopen_send(&addr, b"first")?;
+        let fd_client_2 = fastopen_send(&addr, b"second")?;
+
+        test_utils::run_and_close_fds(&[fd_client_1, fd_client_2], || {
+            for _ in 0..2 {
+                let fd_peer = test_utils::check_system_call!(
+                    || unsafe {
+                        libc::accept(fd_server, std::ptr::null_mut(), std::ptr::null_mut())
+                    },
+                    &[],
+                )?;
+
+                test_utils::run_and_close_fds(&[fd_peer], || {
+                    let mut buf = [0u8; 16];
+                    let rv = test_utils::check_system_call!(
+                        || unsafe {
+                            libc::recv(fd_peer, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+                        },
+                        &[],
+                    )?;
+                    let fd_client = match &buf[..rv as usize] {
+                        b"first" => fd_client_1,
+                        b"second" => fd_client_2,
+                        x => return Err(format!("Unexpected data received: {x:?}")),
+                    };
+                    send_reply(fd_client, fd_peer)
+                })?;
+            }
+
+            let options = get_tcpi_options(fd_client_1)?;
+            test_utils::result_assert(
+                options & TCPI_OPT_SYN_DATA != 0,
+                "The first SYN data was not acknowledged",
+            )?;
+            let options = get_tcpi_options(fd_client_2)?;
+            test_utils::result_assert(
+                options & TCPI_OPT_SYN_DATA == 0,
+                "The second SYN data was acknowledged beyond the queue length",
+            )
+        })
+    })
+}
@@ -0,0 +1,289 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+// from linux's 'include/uapi/linux/udp.h'
+const SOL_UDP: libc::c_int = 17;
+const UDP_SEGMENT: libc::c_int = 103;
+const UDP_GRO: libc::c_int = 104;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    vec![
+        test_utils::ShadowTest::new(
+            "test_udp_options",
+            test_udp_options,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_gso_send",
+            test_gso_send,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_gso_cmsg_gro_recv",
+            test_gso_cmsg_gro_recv,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ]
+}
+
+fn new_udp_socket() -> Result<libc::c_int, String> {
+    test_utils::check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )
+}
+
+fn set_udp_option(fd: libc::c_int, opt: libc::c_int, val: libc::c_int) -> Result<(), String> {
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::setsockopt(
+                fd,
+                SOL_UDP,
+                opt,
+                std::ptr::from_ref(&val) as *const libc::c_void,
+                std::mem::size_of_val(&val) as libc::socklen_t,
+            )
+        },
+        &[],
+    )?;
+    Ok(())
+}
+
+fn get_udp_option(fd: libc::c_int, opt: libc::c_int) -> Result<libc::c_int, String> {
+    let mut val: libc::c_int = -1;
+    let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                SOL_UDP,
+                opt,
+                std::ptr::from_mut(&mut val) as *mut libc::c_void,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    Ok(val)
+}
+
+/// Create a UDP socket bound to a loopback address, and return it with its address.
+fn bound_udp_socket() -> Result<(libc::c_int, libc::sockaddr_in), String> {
+    let fd = new_udp_socket()?;
+
+    let mut addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: 0u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: libc::INADDR_LOOPBACK.to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::bind(
+                fd,
+                std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                addr_len,
+            )
+        },
+        &[],
+    )?;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::getsockname(
+                fd,
+                std::ptr::from_mut(&mut addr) as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        },
+        &[],
+    )?;
+
+    Ok((fd, addr))
+}
+
+/// Receive a datagram without blocking, and return its length and the segment size from any
+/// `UDP_GRO` control message.
+fn recv_with_gro_size(fd: libc::c_int) -> Result<(usize, Option<libc::c_int>), String> {
+    let mut buf = vec![0u8; 65536];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // use a u64 array so that the buffer is aligned for a cmsghdr
+    let mut control = [0u64; 8];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control);
+
+    let rv = test_utils::check_system_call!(
+        || unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_DONTWAIT) },
+        &[],
+    )?;
+
+    let mut gro_size = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        if hdr.cmsg_level == SOL_UDP && hdr.cmsg_type == UDP_GRO {
+            let data = unsafe { libc::CMSG_DATA(cmsg) } as *const libc::c_int;
+            gro_size = Some(unsafe { data.read_unaligned() });
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    Ok((rv.try_into().unwrap(), gro_size))
+}
+
+/// Give the sent datagrams time to arrive.
+fn wait_for_datagrams() {
+    std::thread::sleep(std::time::Duration::from_millis(100));
+}
+
+/// Test getsockopt() and setsockopt() using the UDP_SEGMENT and UDP_GRO options.
+fn test_udp_options() -> Result<(), String> {
+    let fd = new_udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        test_utils::result_assert_eq(get_udp_option(fd, UDP_SEGMENT)?, 0, "GSO is enabled")?;
+        test_utils::result_assert_eq(get_udp_option(fd, UDP_GRO)?, 0, "GRO is enabled")?;
+
+        set_udp_option(fd, UDP_SEGMENT, 1000)?;
+        set_udp_option(fd, UDP_GRO, 1)?;
+
+        test_utils::result_assert_eq(get_udp_option(fd, UDP_SEGMENT)?, 1000, "Wrong GSO size")?;
+        test_utils::result_assert_eq(get_udp_option(fd, UDP_GRO)?, 1, "GRO is disabled")?;
+
+        let val: libc::c_int = -1;
+        test_utils::check_system_call!(
+            || unsafe {
+                libc::setsockopt(
+                    fd,
+                    SOL_UDP,
+                    UDP_SEGMENT,
+                    std::ptr::from_ref(&val) as *const libc::c_void,
+                    std::mem::size_of_val(&val) as libc::socklen_t,
+                )
+            },
+            &[libc::EINVAL],
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that a send on a socket with the UDP_SEGMENT option is received as separate datagrams.
+fn test_gso_send() -> Result<(), String> {
+    let (fd_server, addr) = bound_udp_socket()?;
+    let fd_client = new_udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        set_udp_option(fd_client, UDP_SEGMENT, 1000)?;
+
+        let data = vec![1u8; 2500];
+        let rv = test_utils::check_system_call!(
+            || unsafe {
+                libc::sendto(
+                    fd_client,
+                    data.as_ptr() as *const libc::c_void,
+                    data.len(),
+                    0,
+                    std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+        test_utils::result_assert_eq(rv, data.len() as isize, "Not all data was sent")?;
+
+        wait_for_datagrams();
+
+        for expected in [1000, 1000, 500] {
+            let (len, gro_size) = recv_with_gro_size(fd_server)?;
+            test_utils::result_assert_eq(len, expected, "Unexpected datagram length")?;
+            test_utils::result_assert_eq(gro_size, None, "Unexpected GRO control message")?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Test that a send with a UDP_SEGMENT control message is coalesced by a socket with the UDP_GRO
+/// option.
+fn test_gso_cmsg_gro_recv() -> Result<(), String> {
+    let (fd_server, addr) = bound_udp_socket()?;
+    let fd_client = new_udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        set_udp_option(fd_server, UDP_GRO, 1)?;
+
+        let mut data = vec![1u8; 2500];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+
+        // room for a single cmsghdr with a u16 (a u64 array so that it's aligned for a cmsghdr)
+        let mut control = [0u64; 3];
+
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_name = std::ptr::from_ref(&addr) as *mut libc::c_void;
+        msg.msg_namelen = std::mem::size_of_val(&addr) as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = std::mem::size_of_val(&control);
+
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = SOL_UDP;
+            (*cmsg).cmsg_type = UDP_SEGMENT;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<u16>() as u32) as usize;
+            (libc::CMSG_DATA(cmsg) as *mut u16).write_unaligned(1000);
+        }
+
+        let rv =
+            test_utils::check_system_call!(|| unsafe { libc::sendmsg(fd_client, &msg, 0) }, &[],)?;
+        test_utils::result_assert_eq(rv, data.len() as isize, "Not all data was sent")?;
+
+        wait_for_datagrams();
+
+        let (len, gro_size) = recv_with_gro_size(fd_server)?;
+        test_utils::result_assert_eq(len, data.len(), "Datagrams were not coalesced")?;
+        test_utils::result_assert_eq(gro_size, Some(1000), "Unexpected GRO segment size")?;
+
+        Ok(())
+    })
+}
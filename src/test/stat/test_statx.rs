@@ -0,0 +1,219 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use nix::errno::Errno;
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_regular_file",
+            test_regular_file,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_empty_path",
+            test_empty_path,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new("test_pipe", test_pipe, set![TestEnv::Libc, TestEnv::Shadow]),
+        test_utils::ShadowTest::new(
+            "test_socket",
+            test_socket,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_eventfd",
+            test_eventfd,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_invalid_args",
+            test_invalid_args,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn statx(
+    dirfd: libc::c_int,
+    path: &str,
+    flags: libc::c_int,
+    mask: libc::c_uint,
+) -> Result<libc::statx, Errno> {
+    let path = CString::new(path).unwrap();
+    let mut buf: libc::statx = unsafe { std::mem::zeroed() };
+    let rv = unsafe { libc::statx(dirfd, path.as_ptr(), flags, mask, &mut buf) };
+    Errno::result(rv)?;
+    Ok(buf)
+}
+
+/// Check the fields that shadow emulates for files without a backing OS file.
+fn check_emulated(fd: &OwnedFd, expected_mode: u16) -> anyhow::Result<()> {
+    let requested = libc::STATX_TYPE | libc::STATX_MODE | libc::STATX_NLINK | libc::STATX_UID;
+    let buf = statx(fd.as_raw_fd(), "", libc::AT_EMPTY_PATH, requested)?;
+
+    ensure_ord!(buf.stx_mask & requested, ==, requested);
+    ensure_ord!(buf.stx_mode, ==, expected_mode);
+    ensure_ord!(buf.stx_nlink, ==, 1);
+    ensure_ord!(buf.stx_uid, ==, nix::unistd::geteuid().as_raw());
+
+    // a relative path isn't valid since the file isn't a directory
+    ensure_ord!(
+        statx(fd.as_raw_fd(), "foo", 0, requested).err(),
+        ==,
+        Some(Errno::ENOTDIR)
+    );
+
+    Ok(())
+}
+
+fn test_regular_file() -> anyhow::Result<()> {
+    let path = "test_statx_regular_file";
+    let path_c = CString::new(path).unwrap();
+    let fd = unsafe { libc::open(path_c.as_ptr(), libc::O_CREAT | libc::O_WRONLY, 0o600) };
+    Errno::result(fd)?;
+    let contents = [0u8; 100];
+    let rv = unsafe { libc::write(fd, contents.as_ptr().cast(), contents.len()) };
+    let result = Errno::result(rv)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| {
+            let buf = statx(libc::AT_FDCWD, path, 0, libc::STATX_BASIC_STATS)?;
+            ensure_ord!(
+                buf.stx_mask & libc::STATX_BASIC_STATS,
+                ==,
+                libc::STATX_BASIC_STATS
+            );
+            ensure_ord!(u32::from(buf.stx_mode) & libc::S_IFMT, ==, libc::S_IFREG);
+            ensure_ord!(buf.stx_mode & 0o777, ==, 0o600);
+            ensure_ord!(buf.stx_size, ==, 100);
+
+            // the same file through its descriptor
+            let buf = statx(fd, "", libc::AT_EMPTY_PATH, libc::STATX_SIZE)?;
+            ensure_ord!(buf.stx_mask & libc::STATX_SIZE, ==, libc::STATX_SIZE);
+            ensure_ord!(buf.stx_size, ==, 100);
+
+            Ok(())
+        });
+
+    unsafe { libc::close(fd) };
+    unsafe { libc::unlink(path_c.as_ptr()) };
+    result
+}
+
+fn test_empty_path() -> anyhow::Result<()> {
+    let fd = unsafe { libc::eventfd(0, 0) };
+    Errno::result(fd)?;
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // an empty path requires `AT_EMPTY_PATH`
+    ensure_ord!(
+        statx(fd.as_raw_fd(), "", 0, libc::STATX_BASIC_STATS).err(),
+        ==,
+        Some(Errno::ENOENT)
+    );
+    ensure_ord!(
+        statx(libc::AT_FDCWD, "", 0, libc::STATX_BASIC_STATS).err(),
+        ==,
+        Some(Errno::ENOENT)
+    );
+
+    Ok(())
+}
+
+fn test_pipe() -> anyhow::Result<()> {
+    let (read_fd, write_fd) = nix::unistd::pipe()?;
+    let read_fd = unsafe { OwnedFd::from_raw_fd(read_fd) };
+    let write_fd = unsafe { OwnedFd::from_raw_fd(write_fd) };
+
+    check_emulated(&read_fd, libc::S_IFIFO as u16 | 0o600)?;
+    check_emulated(&write_fd, libc::S_IFIFO as u16 | 0o600)?;
+
+    Ok(())
+}
+
+fn test_socket() -> anyhow::Result<()> {
+    for (domain, sock_type) in [
+        (libc::AF_INET, libc::SOCK_STREAM),
+        (libc::AF_INET, libc::SOCK_DGRAM),
+        (libc::AF_UNIX, libc::SOCK_STREAM),
+    ] {
+        let fd = unsafe { libc::socket(domain, sock_type, 0) };
+        Errno::result(fd)?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        check_emulated(&fd, libc::S_IFSOCK as u16 | 0o777)?;
+    }
+
+    Ok(())
+}
+
+fn test_eventfd() -> anyhow::Result<()> {
+    let fd = unsafe { libc::eventfd(0, 0) };
+    Errno::result(fd)?;
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // anonymous inodes don't have a file type
+    check_emulated(&fd, 0o600)?;
+
+    // fields that weren't requested aren't returned
+    let buf = statx(fd.as_raw_fd(), "", libc::AT_EMPTY_PATH, libc::STATX_NLINK)?;
+    ensure_ord!(buf.stx_mask & libc::STATX_NLINK, ==, libc::STATX_NLINK);
+    ensure_ord!(buf.stx_nlink, ==, 1);
+
+    Ok(())
+}
+
+fn test_invalid_args() -> anyhow::Result<()> {
+    let fd = unsafe { libc::eventfd(0, 0) };
+    Errno::result(fd)?;
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // the reserved mask bit
+    ensure_ord!(
+        statx(fd.as_raw_fd(), "", libc::AT_EMPTY_PATH, libc::STATX__RESERVED).err(),
+        ==,
+        Some(Errno::EINVAL)
+    );
+
+    // conflicting sync flags
+    ensure_ord!(
+        statx(
+            fd.as_raw_fd(),
+            "",
+            libc::AT_EMPTY_PATH | libc::AT_STATX_FORCE_SYNC | libc::AT_STATX_DONT_SYNC,
+            libc::STATX_BASIC_STATS,
+        )
+        .err(),
+        ==,
+        Some(Errno::EINVAL)
+    );
+
+    Ok(())
+}
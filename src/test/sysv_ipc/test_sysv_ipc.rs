@@ -0,0 +1,439 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::time::Duration;
+
+use nix::errno::Errno;
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_shmget_errors",
+            test_shmget_errors,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_shm_shared_mapping",
+            test_shm_shared_mapping,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_shm_fork",
+            test_shm_fork,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_shm_rmid_while_attached",
+            test_shm_rmid_while_attached,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_semctl_values",
+            test_semctl_values,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_semop_atomic",
+            test_semop_atomic,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_semop_blocking",
+            test_semop_blocking,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_semtimedop_timeout",
+            test_semtimedop_timeout,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_sem_rmid_wakes_waiters",
+            test_sem_rmid_wakes_waiters,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_sem_undo",
+            test_sem_undo,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// A key that is unlikely to be used by other processes on the system.
+fn unique_key(n: i32) -> libc::key_t {
+    0x5ad0_0000 | ((std::process::id() as i32 & 0xfff) << 4) | n
+}
+
+/// A shared memory segment that is removed when dropped.
+struct Segment {
+    id: libc::c_int,
+}
+
+impl Segment {
+    fn create(key: libc::key_t, size: usize, flags: libc::c_int) -> Result<Self, Errno> {
+        Ok(Self {
+            id: shmget(key, size, flags)?,
+        })
+    }
+
+    fn attach(&self, flags: libc::c_int) -> Result<*mut u8, Errno> {
+        let addr = unsafe { libc::shmat(self.id, std::ptr::null(), flags) };
+        if addr as isize == -1 {
+            return Err(Errno::last());
+        }
+        Ok(addr as *mut u8)
+    }
+
+    fn stat(&self) -> Result<libc::shmid_ds, Errno> {
+        let mut ds: libc::shmid_ds = unsafe { std::mem::zeroed() };
+        Errno::result(unsafe { libc::shmctl(self.id, libc::IPC_STAT, &mut ds) })?;
+        Ok(ds)
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        unsafe { libc::shmctl(self.id, libc::IPC_RMID, std::ptr::null_mut()) };
+    }
+}
+
+fn shmget(key: libc::key_t, size: usize, flags: libc::c_int) -> Result<libc::c_int, Errno> {
+    Errno::result(unsafe { libc::shmget(key, size, flags | 0o600) })
+}
+
+fn shmdt(addr: *mut u8) -> Result<(), Errno> {
+    Errno::result(unsafe { libc::shmdt(addr as *const libc::c_void) })?;
+    Ok(())
+}
+
+/// A semaphore set that is removed when dropped.
+struct SemSet {
+    id: libc::c_int,
+}
+
+impl SemSet {
+    fn create(nsems: libc::c_int) -> Result<Self, Errno> {
+        let id = Errno::result(unsafe { libc::semget(libc::IPC_PRIVATE, nsems, 0o600) })?;
+        Ok(Self { id })
+    }
+
+    fn get_val(&self, semnum: libc::c_int) -> Result<libc::c_int, Errno> {
+        Errno::result(unsafe { libc::semctl(self.id, semnum, libc::GETVAL) })
+    }
+
+    fn set_val(&self, semnum: libc::c_int, val: libc::c_int) -> Result<(), Errno> {
+        Errno::result(unsafe { libc::semctl(self.id, semnum, libc::SETVAL, val) })?;
+        Ok(())
+    }
+
+    fn op(&self, ops: &[(u16, i16, i16)]) -> Result<(), Errno> {
+        let mut ops = to_sembufs(ops);
+        Errno::result(unsafe { libc::semop(self.id, ops.as_mut_ptr(), ops.len()) })?;
+        Ok(())
+    }
+
+    fn timed_op(&self, ops: &[(u16, i16, i16)], timeout: Duration) -> Result<(), Errno> {
+        let mut ops = to_sembufs(ops);
+        let timeout = libc::timespec {
+            tv_sec: timeout.as_secs().try_into().unwrap(),
+            tv_nsec: timeout.subsec_nanos().into(),
+        };
+        Errno::result(unsafe {
+            libc::syscall(
+                libc::SYS_semtimedop,
+                self.id,
+                ops.as_mut_ptr(),
+                ops.len(),
+                &timeout,
+            )
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for SemSet {
+    fn drop(&mut self) {
+        unsafe { libc::semctl(self.id, 0, libc::IPC_RMID) };
+    }
+}
+
+fn to_sembufs(ops: &[(u16, i16, i16)]) -> Vec<libc::sembuf> {
+    ops.iter()
+        .map(|(sem_num, sem_op, sem_flg)| libc::sembuf {
+            sem_num: *sem_num,
+            sem_op: *sem_op,
+            sem_flg: *sem_flg,
+        })
+        .collect()
+}
+
+/// Wait for the child process `pid` and ensure it exited successfully.
+fn wait_for_child(pid: libc::pid_t) -> anyhow::Result<()> {
+    let mut status = 0;
+    Errno::result(unsafe { libc::waitpid(pid, &mut status, 0) })?;
+    assert!(libc::WIFEXITED(status));
+    assert_eq!(libc::WEXITSTATUS(status), 0);
+    Ok(())
+}
+
+fn test_shmget_errors() -> anyhow::Result<()> {
+    let key = unique_key(1);
+
+    assert_eq!(shmget(key, 4096, 0), Err(Errno::ENOENT));
+    assert_eq!(shmget(libc::IPC_PRIVATE, 0, 0), Err(Errno::EINVAL));
+
+    let segment = Segment::create(key, 4096, libc::IPC_CREAT)?;
+
+    // looking up an existing key returns the same segment
+    assert_eq!(shmget(key, 100, 0), Ok(segment.id));
+    assert_eq!(shmget(key, 0, 0), Ok(segment.id));
+
+    assert_eq!(
+        shmget(key, 4096, libc::IPC_CREAT | libc::IPC_EXCL),
+        Err(Errno::EEXIST)
+    );
+    assert_eq!(shmget(key, 8192, 0), Err(Errno::EINVAL));
+
+    // IPC_PRIVATE always creates a new segment
+    let private = Segment::create(libc::IPC_PRIVATE, 4096, 0)?;
+    assert_ne!(private.id, segment.id);
+
+    assert_eq!(shmdt(std::ptr::null_mut()), Err(Errno::EINVAL));
+
+    Ok(())
+}
+
+fn test_shm_shared_mapping() -> anyhow::Result<()> {
+    let segment = Segment::create(libc::IPC_PRIVATE, 100, 0)?;
+    assert_eq!(segment.stat()?.shm_segsz, 100);
+    assert_eq!(segment.stat()?.shm_nattch, 0);
+
+    let a = segment.attach(0)?;
+    let b = segment.attach(libc::SHM_RDONLY)?;
+    assert_ne!(a, b);
+    assert_eq!(segment.stat()?.shm_nattch, 2);
+    assert_eq!(segment.stat()?.shm_lpid, unsafe { libc::getpid() });
+
+    // new segments are zeroed, and both attachments share the same memory
+    assert_eq!(unsafe { b.add(10).read_volatile() }, 0);
+    unsafe { a.add(10).write_volatile(42) };
+    assert_eq!(unsafe { b.add(10).read_volatile() }, 42);
+
+    shmdt(a)?;
+    assert_eq!(segment.stat()?.shm_nattch, 1);
+    assert_eq!(shmdt(a), Err(Errno::EINVAL));
+
+    shmdt(b)?;
+    assert_eq!(segment.stat()?.shm_nattch, 0);
+
+    Ok(())
+}
+
+fn test_shm_fork() -> anyhow::Result<()> {
+    let segment = Segment::create(libc::IPC_PRIVATE, 4096, 0)?;
+    let addr = segment.attach(0)?;
+
+    let pid = Errno::result(unsafe { libc::fork() })?;
+    if pid == 0 {
+        // the child inherits the attachment
+        unsafe { addr.write_volatile(7) };
+        unsafe { libc::_exit(0) };
+    }
+
+    wait_for_child(pid)?;
+    assert_eq!(unsafe { addr.read_volatile() }, 7);
+
+    // the child's attachment was released when it exited
+    assert_eq!(segment.stat()?.shm_nattch, 1);
+
+    shmdt(addr)?;
+    Ok(())
+}
+
+fn test_shm_rmid_while_attached() -> anyhow::Result<()> {
+    let key = unique_key(2);
+    let segment = Segment::create(key, 4096, libc::IPC_CREAT | libc::IPC_EXCL)?;
+    let addr = segment.attach(0)?;
+
+    Errno::result(unsafe { libc::shmctl(segment.id, libc::IPC_RMID, std::ptr::null_mut()) })?;
+
+    // the segment stays alive while it's attached, but can no longer be found by its key
+    assert_eq!(shmget(key, 4096, 0), Err(Errno::ENOENT));
+    let ds = segment.stat()?;
+    assert_eq!(ds.shm_perm.__key, libc::IPC_PRIVATE);
+    assert_eq!(ds.shm_perm.mode & 0o1000, 0o1000);
+    unsafe { addr.write_volatile(1) };
+
+    // the last detach destroys it
+    shmdt(addr)?;
+    assert_eq!(segment.stat().err(), Some(Errno::EINVAL));
+
+    Ok(())
+}
+
+fn test_semctl_values() -> anyhow::Result<()> {
+    assert_eq!(SemSet::create(0).err(), Some(Errno::EINVAL));
+
+    let set = SemSet::create(3)?;
+    assert_eq!(set.get_val(0), Ok(0));
+
+    set.set_val(1, 5)?;
+    assert_eq!(set.get_val(1), Ok(5));
+    assert_eq!(
+        Errno::result(unsafe { libc::semctl(set.id, 1, libc::GETPID) }),
+        Ok(unsafe { libc::getpid() })
+    );
+
+    assert_eq!(set.set_val(3, 1), Err(Errno::EINVAL));
+    assert_eq!(set.set_val(0, -1), Err(Errno::ERANGE));
+    assert_eq!(set.set_val(0, 32768), Err(Errno::ERANGE));
+
+    let vals: [libc::c_ushort; 3] = [1, 2, 3];
+    Errno::result(unsafe { libc::semctl(set.id, 0, libc::SETALL, vals.as_ptr()) })?;
+
+    let mut vals: [libc::c_ushort; 3] = [0; 3];
+    Errno::result(unsafe { libc::semctl(set.id, 0, libc::GETALL, vals.as_mut_ptr()) })?;
+    assert_eq!(vals, [1, 2, 3]);
+
+    let mut ds: libc::semid_ds = unsafe { std::mem::zeroed() };
+    Errno::result(unsafe { libc::semctl(set.id, 0, libc::IPC_STAT, &mut ds) })?;
+    assert_eq!(ds.sem_nsems, 3);
+    assert_eq!(ds.sem_perm.mode & 0o777, 0o600);
+
+    Ok(())
+}
+
+fn test_semop_atomic() -> anyhow::Result<()> {
+    let set = SemSet::create(2)?;
+    set.set_val(0, 1)?;
+
+    let nowait = libc::IPC_NOWAIT as i16;
+
+    // the second operation can't proceed, so neither operation is applied
+    assert_eq!(
+        set.op(&[(0, -1, nowait), (1, -1, nowait)]),
+        Err(Errno::EAGAIN)
+    );
+    assert_eq!(set.get_val(0), Ok(1));
+
+    // waiting for zero
+    assert_eq!(set.op(&[(0, 0, nowait)]), Err(Errno::EAGAIN));
+    set.op(&[(1, 0, nowait)])?;
+
+    set.op(&[(0, -1, 0), (1, 2, 0)])?;
+    assert_eq!(set.get_val(0), Ok(0));
+    assert_eq!(set.get_val(1), Ok(2));
+
+    assert_eq!(set.op(&[(2, 1, 0)]), Err(Errno::EFBIG));
+    assert_eq!(set.op(&[]), Err(Errno::EINVAL));
+    assert_eq!(set.op(&[(1, 32767, 0)]), Err(Errno::ERANGE));
+
+    Ok(())
+}
+
+fn test_semop_blocking() -> anyhow::Result<()> {
+    let set = SemSet::create(1)?;
+
+    let (time_before, time_after) = std::thread::scope(|s| {
+        let waiter = s.spawn(|| {
+            let time_before = std::time::Instant::now();
+            set.op(&[(0, -1, 0)]).unwrap();
+            (time_before, std::time::Instant::now())
+        });
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(
+            Errno::result(unsafe { libc::semctl(set.id, 0, libc::GETNCNT) }),
+            Ok(1)
+        );
+        set.op(&[(0, 1, 0)]).unwrap();
+
+        waiter.join().unwrap()
+    });
+
+    // the waiter should only have returned after the semaphore was incremented
+    ensure_ord!(time_after - time_before, >=, Duration::from_millis(90));
+    assert_eq!(set.get_val(0), Ok(0));
+
+    Ok(())
+}
+
+fn test_semtimedop_timeout() -> anyhow::Result<()> {
+    let set = SemSet::create(1)?;
+
+    let time_before = std::time::Instant::now();
+    let rv = set.timed_op(&[(0, -1, 0)], Duration::from_millis(100));
+    let time_after = std::time::Instant::now();
+
+    assert_eq!(rv, Err(Errno::EAGAIN));
+    ensure_ord!(time_after - time_before, >=, Duration::from_millis(90));
+
+    Ok(())
+}
+
+fn test_sem_rmid_wakes_waiters() -> anyhow::Result<()> {
+    let set = SemSet::create(1)?;
+    let id = set.id;
+
+    let rv = std::thread::scope(|s| {
+        let waiter = s.spawn(|| set.op(&[(0, -1, 0)]));
+
+        std::thread::sleep(Duration::from_millis(100));
+        Errno::result(unsafe { libc::semctl(id, 0, libc::IPC_RMID) }).unwrap();
+
+        waiter.join().unwrap()
+    });
+
+    assert_eq!(rv, Err(Errno::EIDRM));
+    assert_eq!(set.get_val(0), Err(Errno::EINVAL));
+
+    Ok(())
+}
+
+fn test_sem_undo() -> anyhow::Result<()> {
+    let set = SemSet::create(1)?;
+    set.set_val(0, 2)?;
+
+    let pid = Errno::result(unsafe { libc::fork() })?;
+    if pid == 0 {
+        let undo = libc::SEM_UNDO as i16;
+        let rv = set.op(&[(0, -2, undo)]);
+        unsafe { libc::_exit(if rv.is_ok() { 0 } else { 1 }) };
+    }
+
+    wait_for_child(pid)?;
+
+    // the child's operation was undone when it exited
+    assert_eq!(set.get_val(0), Ok(2));
+    assert_eq!(
+        Errno::result(unsafe { libc::semctl(set.id, 0, libc::GETPID) }),
+        Ok(pid)
+    );
+
+    Ok(())
+}
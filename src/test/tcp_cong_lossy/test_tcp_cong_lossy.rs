@@ -0,0 +1,153 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the congestion control algorithms over a lossy path with a large bandwidth-delay product.
+//! Each client host uses a different algorithm to send a bulk transfer to the server.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+const PORT: u16 = 8000;
+
+const ALGORITHMS: [&str; 3] = ["reno", "cubic", "bbr"];
+
+/// The number of bytes that each client sends.
+const TRANSFER_LEN: usize = 2 * 1024 * 1024;
+const CHUNK_LEN: usize = 16 * 1024;
+
+/// The initial congestion window of the hosts, in packets.
+const INIT_CWND: u32 = 10;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some(name) if ALGORITHMS.contains(&name) => client(name),
+        _ => anyhow::bail!("Expected 'server' or an algorithm name argument: {ALGORITHMS:?}"),
+    }
+}
+
+/// Get a `u32` field of the socket's TCP_INFO at the given byte offset of 'struct tcp_info'.
+fn tcp_info_u32(fd: RawFd, offset: usize) -> anyhow::Result<u32> {
+    // the libc package doesn't expose 'struct tcp_info', and we only need the fields that glibc has
+    let mut info = [0u8; 104];
+    let mut len = info.len() as libc::socklen_t;
+    let rv = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_TCP,
+            libc::TCP_INFO,
+            info.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rv != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(u32::from_ne_bytes(info[offset..][..4].try_into().unwrap()))
+}
+
+/// The 'tcpi_snd_cwnd' field.
+fn snd_cwnd(fd: RawFd) -> anyhow::Result<u32> {
+    tcp_info_u32(fd, 80)
+}
+
+/// The 'tcpi_total_retrans' field.
+fn total_retrans(fd: RawFd) -> anyhow::Result<u32> {
+    tcp_info_u32(fd, 100)
+}
+
+fn set_congestion(fd: RawFd, name: &str) -> anyhow::Result<()> {
+    let rv = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_TCP,
+            libc::TCP_CONGESTION,
+            name.as_ptr() as *const libc::c_void,
+            name.len() as libc::socklen_t,
+        )
+    };
+    if rv != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Receive a transfer from a client, and return the client's algorithm and how long the transfer
+/// took.
+fn receive(stream: TcpStream) -> anyhow::Result<(String, Duration)> {
+    let start = Instant::now();
+    let mut reader = BufReader::new(stream);
+
+    // the client sends its algorithm name first
+    let mut name = String::new();
+    reader.read_line(&mut name)?;
+    let name = name.trim_end().to_string();
+
+    let mut len = 0;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+    }
+    assert_eq!(len, TRANSFER_LEN, "{name}");
+
+    Ok((name, start.elapsed()))
+}
+
+/// Receive a transfer from each client at the same time.
+fn server() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", PORT))?;
+
+    let mut handles = Vec::new();
+    for _ in 0..ALGORITHMS.len() {
+        let (stream, _addr) = listener.accept()?;
+        handles.push(std::thread::spawn(move || receive(stream)));
+    }
+
+    let mut elapsed = HashMap::new();
+    for handle in handles {
+        let (name, duration) = handle.join().unwrap()?;
+        println!("{name}: {duration:?}");
+        elapsed.insert(name, duration);
+    }
+
+    // bbr doesn't treat random loss as congestion, so it keeps sending at the bottleneck bandwidth
+    // while the loss-based algorithms keep shrinking their windows
+    assert!(elapsed["bbr"] * 2 < elapsed["reno"]);
+    assert!(elapsed["bbr"] * 2 < elapsed["cubic"]);
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Send a transfer to the server using the congestion control algorithm `name`.
+fn client(name: &str) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect(("server", PORT))?;
+    let fd = stream.as_raw_fd();
+    set_congestion(fd, name)?;
+
+    stream.write_all(format!("{name}\n").as_bytes())?;
+
+    let chunk = vec![0u8; CHUNK_LEN];
+    let mut max_cwnd = 0;
+    for _ in 0..(TRANSFER_LEN / CHUNK_LEN) {
+        stream.write_all(&chunk)?;
+        max_cwnd = std::cmp::max(max_cwnd, snd_cwnd(fd)?);
+    }
+
+    // the window grew despite the losses, which were repaired by retransmissions
+    println!("max cwnd: {max_cwnd}");
+    assert!(max_cwnd > INIT_CWND);
+    assert!(total_retrans(fd)? > 0);
+
+    println!("Success.");
+    Ok(())
+}
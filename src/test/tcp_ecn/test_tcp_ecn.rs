@@ -0,0 +1,136 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests ECN over a bottleneck link. The client sends a bulk transfer to the server, whose
+//! downstream bandwidth is much lower than the client's upstream bandwidth, so the server's router
+//! queue builds up and marks the client's packets instead of dropping them. After the simulation,
+//! the `check` mode counts the ECN codepoints and flags in the hosts' pcap files.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const PORT: u16 = 8000;
+
+/// The number of bytes that the client sends, which takes several seconds over the bottleneck.
+const TRANSFER_LEN: usize = 4 * 1024 * 1024;
+
+const IPPROTO_TCP: u8 = 6;
+const ECN_CE: u8 = 0x3;
+const TCP_SYN: u8 = 0x02;
+const TCP_ECE: u8 = 0x40;
+const TCP_CWR: u8 = 0x80;
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("server") => server(),
+        Some("client") => client(),
+        Some("check") if args.len() == 4 => check(&args[2], &args[3]),
+        _ => anyhow::bail!("Expected 'server', 'client', or 'check <client pcap> <server pcap>'"),
+    }
+}
+
+/// Accept a connection and read all of its data.
+fn server() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", PORT))?;
+    let (mut stream, _addr) = listener.accept()?;
+
+    let mut len = 0;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+    }
+    assert_eq!(len, TRANSFER_LEN);
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Connect to the server and send all of the data.
+fn client() -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect(("server", PORT))?;
+    stream.write_all(&vec![0u8; TRANSFER_LEN])?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// ECN counters of the packets in a pcap file.
+#[derive(Debug, Default)]
+struct EcnCounters {
+    /// Packets with the "congestion experienced" codepoint.
+    ce: usize,
+    /// Non-SYN packets with the ECE flag.
+    ece: usize,
+    /// Non-SYN packets with the CWR flag.
+    cwr: usize,
+}
+
+/// Count the ECN codepoints and flags of the TCP packets in a pcap file with raw IPv4 packets, as
+/// written by shadow.
+fn count(path: &str) -> anyhow::Result<EcnCounters> {
+    let data = std::fs::read(path)?;
+    let u32_at = |offset: usize| u32::from_ne_bytes(data[offset..][..4].try_into().unwrap());
+
+    // the global header is 24 bytes
+    let mut offset = 24;
+    let mut counters = EcnCounters::default();
+
+    while offset < data.len() {
+        // the record header is 16 bytes, and the third field is the captured length
+        let captured_len = usize::try_from(u32_at(offset + 8))?;
+        let packet = &data[offset + 16..][..captured_len];
+        offset += 16 + captured_len;
+
+        let ip_header_len = usize::from(packet[0] & 0xf) * 4;
+        if packet[9] != IPPROTO_TCP {
+            continue;
+        }
+
+        if packet[1] & 0x3 == ECN_CE {
+            counters.ce += 1;
+        }
+
+        let tcp_flags = packet[ip_header_len + 13];
+        if tcp_flags & TCP_SYN != 0 {
+            // the flags on SYNs are only used to negotiate ECN
+            continue;
+        }
+        if tcp_flags & TCP_ECE != 0 {
+            counters.ece += 1;
+        }
+        if tcp_flags & TCP_CWR != 0 {
+            counters.cwr += 1;
+        }
+    }
+
+    Ok(counters)
+}
+
+/// Check that the router marked the client's packets, the server echoed the congestion, and the
+/// client reduced its window in response.
+fn check(client_pcap: &str, server_pcap: &str) -> anyhow::Result<()> {
+    let client = count(client_pcap)?;
+    let server = count(server_pcap)?;
+    println!("client: {client:?}, server: {server:?}");
+
+    // packets are marked at the server's router, so the client never sees a marked packet
+    assert_eq!(client.ce, 0);
+    assert!(server.ce > 0);
+
+    // the server's ACKs echo the congestion until the client sends CWR
+    assert!(server.ece > 0);
+    assert_eq!(client.ece, server.ece);
+
+    // the client sets CWR after reducing its window
+    assert!(client.cwr > 0);
+
+    println!("Success.");
+    Ok(())
+}
@@ -0,0 +1,119 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the `tcp_rmem` and `tcp_wmem` host options. New sockets start with the initial sizes, and
+//! the sizes are tuned to the path's bandwidth-delay product within the minimum and maximum sizes.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsRawFd, RawFd};
+
+const PORT: u16 = 8000;
+
+const RMEM: (usize, usize, usize) = (8 * 1024, 64 * 1024, 256 * 1024);
+const WMEM: (usize, usize, usize) = (8 * 1024, 32 * 1024, 128 * 1024);
+
+/// The number of bytes that each client sends, which is enough for the buffers to try to grow.
+const TRANSFER_LEN: usize = 1024 * 1024;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("fast") => client(RMEM.2, WMEM.2),
+        Some("slow") => client(RMEM.0, WMEM.0),
+        _ => anyhow::bail!("Expected 'server', 'fast', or 'slow' argument"),
+    }
+}
+
+/// Get the receive and send buffer sizes of the socket.
+fn buf_sizes(fd: RawFd) -> anyhow::Result<(usize, usize)> {
+    let get = |opt| -> anyhow::Result<usize> {
+        let mut val: libc::c_int = 0;
+        let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+        let rv = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                opt,
+                std::ptr::from_mut(&mut val) as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rv != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(val.try_into()?)
+    };
+
+    Ok((get(libc::SO_RCVBUF)?, get(libc::SO_SNDBUF)?))
+}
+
+/// Check that the socket's buffers are within the minimum and maximum sizes.
+fn assert_within_limits(fd: RawFd) -> anyhow::Result<()> {
+    let (rcvbuf, sndbuf) = buf_sizes(fd)?;
+    assert!((RMEM.0..=RMEM.2).contains(&rcvbuf), "SO_RCVBUF {rcvbuf}");
+    assert!((WMEM.0..=WMEM.2).contains(&sndbuf), "SO_SNDBUF {sndbuf}");
+    Ok(())
+}
+
+/// Accept a connection from each client, echo its first byte, and then read the rest of its data.
+fn server() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", PORT))?;
+
+    for _ in 0..2 {
+        let (mut stream, _addr) = listener.accept()?;
+
+        let mut buf = [0u8; 1];
+        stream.read_exact(&mut buf)?;
+        stream.write_all(&buf)?;
+
+        let mut len = 0;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+        }
+        assert_eq!(len, TRANSFER_LEN);
+
+        // the receive buffer grew while reading, but not beyond the maximum
+        assert_within_limits(stream.as_raw_fd())?;
+    }
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Connect to the server and check that the buffers are tuned to the expected sizes.
+fn client(expected_rcvbuf: usize, expected_sndbuf: usize) -> anyhow::Result<()> {
+    // sockets start with the initial sizes
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(fd >= 0);
+    assert_eq!(buf_sizes(fd)?, (RMEM.1, WMEM.1));
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+
+    let mut stream = TcpStream::connect(("server", PORT))?;
+
+    // the buffers are tuned once there's a round-trip time estimate
+    let mut buf = [1u8; 1];
+    stream.write_all(&buf)?;
+    stream.read_exact(&mut buf)?;
+    assert_eq!(
+        buf_sizes(stream.as_raw_fd())?,
+        (expected_rcvbuf, expected_sndbuf)
+    );
+
+    // the send buffer grows while sending, but not beyond the maximum
+    stream.write_all(&vec![0u8; TRANSFER_LEN])?;
+    assert_within_limits(stream.as_raw_fd())?;
+    if expected_sndbuf == WMEM.2 {
+        assert_eq!(buf_sizes(stream.as_raw_fd())?.1, WMEM.2);
+    }
+
+    println!("Success.");
+    Ok(())
+}
@@ -0,0 +1,150 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests SACK loss recovery over a lossy path. The client sends a bulk transfer to the server, and
+//! after the simulation the `check` mode reads the client's pcap file to check that the client
+//! repaired several holes in the same window within one round trip, rather than one hole per round
+//! trip like it would with only cumulative acknowledgments.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+const PORT: u16 = 8000;
+
+/// The number of bytes that the client sends.
+const TRANSFER_LEN: usize = 2 * 1024 * 1024;
+
+/// The round-trip time of the path between the client and the server.
+const RTT: Duration = Duration::from_millis(100);
+
+const IPPROTO_TCP: u8 = 6;
+const TCPI_OPT_SACK: u8 = 2;
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("server") => server(),
+        Some("client") => client(),
+        Some("check") if args.len() == 3 => check(&args[2]),
+        _ => anyhow::bail!("Expected 'server', 'client', or 'check <client pcap>'"),
+    }
+}
+
+/// Accept a connection and read all of its data.
+fn server() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", PORT))?;
+    let (mut stream, _addr) = listener.accept()?;
+
+    let mut len = 0;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+    }
+    assert_eq!(len, TRANSFER_LEN);
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Connect to the server and send all of the data.
+fn client() -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect(("server", PORT))?;
+
+    // the 'tcpi_options' field is the 6th byte of 'struct tcp_info'
+    let mut info = [0u8; 8];
+    let mut len = info.len() as libc::socklen_t;
+    let rv = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_TCP,
+            libc::TCP_INFO,
+            info.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    assert_eq!(rv, 0);
+    assert_ne!(info[5] & TCPI_OPT_SACK, 0, "SACK was not negotiated");
+
+    stream.write_all(&vec![0u8; TRANSFER_LEN])?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Check that two holes that weren't adjacent were repaired within half a round trip of each
+/// other, which isn't possible if each hole is only found after the previous hole was repaired.
+fn check(client_pcap: &str) -> anyhow::Result<()> {
+    let data = std::fs::read(client_pcap)?;
+    let u32_at = |offset: usize| u32::from_ne_bytes(data[offset..][..4].try_into().unwrap());
+
+    let mut sent = HashSet::new();
+    // the time of the first retransmission of each sequence number
+    let mut retransmitted = BTreeMap::new();
+
+    // the global header is 24 bytes
+    let mut offset = 24;
+    while offset < data.len() {
+        // the record header is 16 bytes: the seconds, microseconds, captured length, and length
+        let time = Duration::from_secs(u32_at(offset).into())
+            + Duration::from_micros(u32_at(offset + 4).into());
+        let captured_len = usize::try_from(u32_at(offset + 8))?;
+        let packet = &data[offset + 16..][..captured_len];
+        offset += 16 + captured_len;
+
+        if packet[9] != IPPROTO_TCP {
+            continue;
+        }
+
+        let ip_header_len = usize::from(packet[0] & 0xf) * 4;
+        let ip_total_len = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+        let tcp = &packet[ip_header_len..];
+        let tcp_header_len = usize::from(tcp[12] >> 4) * 4;
+
+        // only the client's data packets
+        let dst_port = u16::from_be_bytes([tcp[2], tcp[3]]);
+        if dst_port != PORT || ip_total_len == ip_header_len + tcp_header_len {
+            continue;
+        }
+
+        let sequence = u32::from_be_bytes(tcp[4..8].try_into().unwrap());
+        if !sent.insert(sequence) {
+            retransmitted.entry(sequence).or_insert(time);
+        }
+    }
+
+    println!("{} retransmitted packets", retransmitted.len());
+
+    let mut retransmissions: Vec<(u32, Duration)> = retransmitted
+        .iter()
+        .map(|(seq, time)| (*seq, *time))
+        .collect();
+    retransmissions.sort_by_key(|(_, time)| *time);
+
+    let repaired_together = retransmissions.windows(2).any(|pair| {
+        let [(seq_a, time_a), (seq_b, time_b)] = pair else {
+            unreachable!()
+        };
+        let (low, high) = (*seq_a.min(seq_b), *seq_a.max(seq_b));
+
+        // a packet between the holes was delivered, so they're separate holes
+        let is_separate = (low + 1..high).any(|seq| !retransmitted.contains_key(&seq));
+
+        is_separate && time_b.saturating_sub(*time_a) < RTT / 2
+    });
+    assert!(
+        repaired_together,
+        "No holes were repaired in the same round trip"
+    );
+
+    println!("Success.");
+    Ok(())
+}
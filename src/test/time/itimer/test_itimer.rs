@@ -18,6 +18,15 @@ extern "C" fn sigalrm_handler(sig: i32) {
     SIGNAL_CTR.fetch_add(1, Ordering::Relaxed);
 }
 
+// Counts how many times the SIGPROF handler ran.
+static SIGPROF_CTR: AtomicU64 = AtomicU64::new(0);
+
+// SIGPROF handler.
+extern "C" fn sigprof_handler(sig: i32) {
+    assert_eq!(sig, libc::SIGPROF);
+    SIGPROF_CTR.fetch_add(1, Ordering::Relaxed);
+}
+
 // Reset timer and signal count.
 fn reset() -> anyhow::Result<()> {
     setitimer(
@@ -274,6 +283,88 @@ fn test_interval_zero() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn test_cpu_timer_set_then_get(which: i32) -> anyhow::Result<()> {
+    let it_value = libc::timeval {
+        tv_sec: 10,
+        tv_usec: 0,
+    };
+    let it_interval = libc::timeval {
+        tv_sec: 3,
+        tv_usec: 4,
+    };
+    let val = setitimer(
+        which,
+        &libc::itimerval {
+            it_value,
+            it_interval,
+        },
+    )?;
+    ensure_ord!(val, ==, ITimer{value: TimeVal::zero(), interval: TimeVal::zero()});
+
+    let val = getitimer(which)?;
+    // Interval should be exactly as was set.
+    ensure_ord!(val.interval, ==, TimeVal::from(it_interval));
+    // Very little CPU time should have been used since setting the timer.
+    let diff = TimeVal::from(it_value).sub(val.value);
+    ensure_ord!(diff, >=, TimeVal::zero());
+    ensure_ord!(diff, <, TimeVal::milliseconds(100));
+
+    // A zero value disarms the timer.
+    let zero = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    let val = setitimer(
+        which,
+        &libc::itimerval {
+            it_value: zero,
+            it_interval: zero,
+        },
+    )?;
+    ensure_ord!(val.interval, ==, TimeVal::from(it_interval));
+    ensure_ord!(getitimer(which)?, ==, ITimer{value: TimeVal::zero(), interval: TimeVal::zero()});
+
+    Ok(())
+}
+
+fn test_prof_oneshot() -> anyhow::Result<()> {
+    SIGPROF_CTR.store(0, Ordering::Relaxed);
+
+    // 10 ms of CPU time
+    let it_value = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 10_000,
+    };
+    let it_interval = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    setitimer(
+        libc::ITIMER_PROF,
+        &libc::itimerval {
+            it_value,
+            it_interval,
+        },
+    )?;
+
+    // Use CPU time by making syscalls until the timer expires. Under shadow the CPU time of a
+    // syscall is its modeled latency.
+    for _ in 0..100_000_000 {
+        if SIGPROF_CTR.load(Ordering::Relaxed) > 0 {
+            break;
+        }
+        unsafe { libc::getppid() };
+    }
+
+    // Should have fired exactly once.
+    ensure_ord!(SIGPROF_CTR.load(Ordering::Relaxed), ==, 1);
+
+    // Timer should no longer be enabled.
+    ensure_ord!(getitimer(libc::ITIMER_PROF)?, ==, ITimer{value: TimeVal::zero(), interval: TimeVal::zero()});
+
+    Ok(())
+}
+
 fn test_leave_running() -> anyhow::Result<()> {
     reset()?;
 
@@ -309,8 +400,21 @@ fn main() -> anyhow::Result<()> {
         )
         .unwrap()
     };
+    // Likewise for SIGPROF.
+    unsafe {
+        nix::sys::signal::sigaction(
+            Signal::SIGPROF,
+            &SigAction::new(
+                SigHandler::Handler(sigprof_handler),
+                SaFlags::empty(),
+                SigSet::empty(),
+            ),
+        )
+        .unwrap()
+    };
     let mut sigset = SigSet::empty();
     sigset.add(Signal::SIGALRM);
+    sigset.add(Signal::SIGPROF);
     nix::sys::signal::sigprocmask(
         nix::sys::signal::SigmaskHow::SIG_UNBLOCK,
         Some(&sigset),
@@ -337,6 +441,17 @@ fn main() -> anyhow::Result<()> {
         ShadowTest::new("set_oneshot", test_oneshot, all_envs.clone()),
         ShadowTest::new("set_interval", test_interval, all_envs.clone()),
         ShadowTest::new("set_interval_zero", test_interval_zero, all_envs.clone()),
+        ShadowTest::new(
+            "virtual_set_then_get",
+            || test_cpu_timer_set_then_get(libc::ITIMER_VIRTUAL),
+            all_envs.clone(),
+        ),
+        ShadowTest::new(
+            "prof_set_then_get",
+            || test_cpu_timer_set_then_get(libc::ITIMER_PROF),
+            all_envs.clone(),
+        ),
+        ShadowTest::new("prof_oneshot", test_prof_oneshot, all_envs.clone()),
         // Must be last.
         // Validate proper cleanup for a timer that's still running when the
         // process exits.
@@ -0,0 +1,423 @@
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal};
+use nix::sys::time::{TimeSpec, TimeValLike};
+use test_utils::{ensure_ord, set, ShadowTest, TestEnvironment};
+
+// Counts how many times the signal handler ran.
+static SIGNAL_CTR: AtomicU64 = AtomicU64::new(0);
+// The signal number, `si_code`, `si_value`, and handling thread of the most recent signal.
+static LAST_SIGNO: AtomicI32 = AtomicI32::new(0);
+static LAST_CODE: AtomicI32 = AtomicI32::new(0);
+static LAST_VALUE: AtomicI32 = AtomicI32::new(0);
+static LAST_TID: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handler(sig: i32, info: *mut libc::siginfo_t, _ctx: *mut std::ffi::c_void) {
+    let info = unsafe { info.as_ref() }.unwrap();
+    LAST_SIGNO.store(sig, Ordering::Relaxed);
+    LAST_CODE.store(info.si_code, Ordering::Relaxed);
+    LAST_VALUE.store(
+        unsafe { info.si_value() }.sival_ptr as usize as i32,
+        Ordering::Relaxed,
+    );
+    LAST_TID.store(nix::unistd::gettid().as_raw(), Ordering::Relaxed);
+    SIGNAL_CTR.fetch_add(1, Ordering::Relaxed);
+}
+
+fn reset() {
+    SIGNAL_CTR.store(0, Ordering::Relaxed);
+    LAST_SIGNO.store(0, Ordering::Relaxed);
+    LAST_CODE.store(0, Ordering::Relaxed);
+    LAST_VALUE.store(0, Ordering::Relaxed);
+    LAST_TID.store(0, Ordering::Relaxed);
+}
+
+// We use the syscalls directly rather than libc's wrappers since libc's `timer_t` isn't the
+// kernel's timer ID.
+
+fn timer_create(clockid: libc::clockid_t, sevp: Option<&libc::sigevent>) -> nix::Result<i32> {
+    let sevp = sevp.map_or(std::ptr::null(), std::ptr::from_ref);
+    let mut id: i32 = -1;
+    let rv = unsafe { libc::syscall(libc::SYS_timer_create, clockid, sevp, &mut id) };
+    nix::errno::Errno::result(rv)?;
+    Ok(id)
+}
+
+fn timer_settime(id: i32, flags: i32, new_value: &libc::itimerspec) -> nix::Result<ITimerSpec> {
+    let mut old_value: libc::itimerspec = unsafe { std::mem::zeroed() };
+    let rv = unsafe {
+        libc::syscall(
+            libc::SYS_timer_settime,
+            id,
+            flags,
+            new_value,
+            &mut old_value,
+        )
+    };
+    nix::errno::Errno::result(rv)?;
+    Ok(old_value.into())
+}
+
+fn timer_gettime(id: i32) -> nix::Result<ITimerSpec> {
+    let mut value: libc::itimerspec = unsafe { std::mem::zeroed() };
+    let rv = unsafe { libc::syscall(libc::SYS_timer_gettime, id, &mut value) };
+    nix::errno::Errno::result(rv)?;
+    Ok(value.into())
+}
+
+fn timer_getoverrun(id: i32) -> nix::Result<i32> {
+    let rv = unsafe { libc::syscall(libc::SYS_timer_getoverrun, id) };
+    Ok(nix::errno::Errno::result(rv)?.try_into().unwrap())
+}
+
+fn timer_delete(id: i32) -> nix::Result<()> {
+    let rv = unsafe { libc::syscall(libc::SYS_timer_delete, id) };
+    nix::errno::Errno::result(rv)?;
+    Ok(())
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct ITimerSpec {
+    interval: TimeSpec,
+    value: TimeSpec,
+}
+
+impl From<libc::itimerspec> for ITimerSpec {
+    fn from(val: libc::itimerspec) -> Self {
+        Self {
+            interval: TimeSpec::from(val.it_interval),
+            value: TimeSpec::from(val.it_value),
+        }
+    }
+}
+
+fn itimerspec(value: Duration, interval: Duration) -> libc::itimerspec {
+    libc::itimerspec {
+        it_value: *TimeSpec::from_duration(value).as_ref(),
+        it_interval: *TimeSpec::from_duration(interval).as_ref(),
+    }
+}
+
+fn sigevent(notify: i32, signal: Signal, value: i32, tid: i32) -> libc::sigevent {
+    let mut sev: libc::sigevent = unsafe { std::mem::zeroed() };
+    sev.sigev_notify = notify;
+    sev.sigev_signo = signal as i32;
+    sev.sigev_value.sival_ptr = value as usize as *mut std::ffi::c_void;
+    sev.sigev_notify_thread_id = tid;
+    sev
+}
+
+const ZERO: ITimerSpec = ITimerSpec {
+    interval: TimeSpec::new(0, 0),
+    value: TimeSpec::new(0, 0),
+};
+
+fn test_create_then_delete() -> anyhow::Result<()> {
+    let id = timer_create(libc::CLOCK_MONOTONIC, None)?;
+
+    // Should initially be disarmed.
+    ensure_ord!(timer_gettime(id)?, ==, ZERO);
+    ensure_ord!(timer_getoverrun(id)?, ==, 0);
+
+    timer_delete(id)?;
+
+    // The timer no longer exists.
+    ensure_ord!(timer_gettime(id), ==, Err(nix::errno::Errno::EINVAL));
+    ensure_ord!(timer_delete(id), ==, Err(nix::errno::Errno::EINVAL));
+
+    Ok(())
+}
+
+fn test_invalid_args() -> anyhow::Result<()> {
+    use nix::errno::Errno;
+
+    // Invalid clock.
+    ensure_ord!(timer_create(1000, None), ==, Err(Errno::EINVAL));
+
+    // Invalid notification type.
+    let sev = sigevent(1000, Signal::SIGUSR1, 0, 0);
+    ensure_ord!(timer_create(libc::CLOCK_MONOTONIC, Some(&sev)), ==, Err(Errno::EINVAL));
+
+    // Invalid signal.
+    let mut sev = sigevent(libc::SIGEV_SIGNAL, Signal::SIGUSR1, 0, 0);
+    sev.sigev_signo = 0;
+    ensure_ord!(timer_create(libc::CLOCK_MONOTONIC, Some(&sev)), ==, Err(Errno::EINVAL));
+
+    // A thread that isn't in this process.
+    let sev = sigevent(libc::SIGEV_THREAD_ID, Signal::SIGUSR1, 0, i32::MAX);
+    ensure_ord!(timer_create(libc::CLOCK_MONOTONIC, Some(&sev)), ==, Err(Errno::EINVAL));
+
+    // A timer that doesn't exist.
+    let spec = itimerspec(Duration::from_secs(1), Duration::ZERO);
+    ensure_ord!(timer_settime(1000, 0, &spec), ==, Err(Errno::EINVAL));
+    ensure_ord!(timer_getoverrun(1000), ==, Err(Errno::EINVAL));
+
+    Ok(())
+}
+
+fn test_set_then_get() -> anyhow::Result<()> {
+    let id = timer_create(libc::CLOCK_MONOTONIC, None)?;
+
+    let value = Duration::new(1, 2);
+    let interval = Duration::new(3, 4);
+    let old = timer_settime(id, 0, &itimerspec(value, interval))?;
+    ensure_ord!(old, ==, ZERO);
+
+    let val = timer_gettime(id)?;
+    // Interval should be exactly as was set.
+    ensure_ord!(val.interval, ==, TimeSpec::from_duration(interval));
+    // Time remaining should be equal to or slightly less than what was set.
+    let diff = TimeSpec::from_duration(value) - val.value;
+    ensure_ord!(diff, >=, TimeSpec::zero());
+    ensure_ord!(diff, <, TimeSpec::milliseconds(1));
+
+    // A zero value disarms the timer and returns the old value.
+    let old = timer_settime(id, 0, &itimerspec(Duration::ZERO, interval))?;
+    ensure_ord!(old.interval, ==, TimeSpec::from_duration(interval));
+    ensure_ord!(timer_gettime(id)?, ==, ZERO);
+
+    timer_delete(id)?;
+    Ok(())
+}
+
+fn test_default_notification() -> anyhow::Result<()> {
+    reset();
+
+    // With no sigevent, the timer sends SIGALRM with the timer ID as the value.
+    let id = timer_create(libc::CLOCK_MONOTONIC, None)?;
+    timer_settime(
+        id,
+        0,
+        &itimerspec(Duration::from_millis(50), Duration::ZERO),
+    )?;
+
+    std::thread::sleep(Duration::from_millis(100));
+
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    ensure_ord!(LAST_SIGNO.load(Ordering::Relaxed), ==, libc::SIGALRM);
+    ensure_ord!(LAST_CODE.load(Ordering::Relaxed), ==, libc::SI_TIMER);
+    ensure_ord!(LAST_VALUE.load(Ordering::Relaxed), ==, id);
+
+    // A oneshot timer is disarmed after it expires.
+    ensure_ord!(timer_gettime(id)?, ==, ZERO);
+
+    timer_delete(id)?;
+    Ok(())
+}
+
+fn test_interval() -> anyhow::Result<()> {
+    reset();
+
+    let sev = sigevent(libc::SIGEV_SIGNAL, Signal::SIGUSR1, 1234, 0);
+    let id = timer_create(libc::CLOCK_MONOTONIC, Some(&sev))?;
+    timer_settime(
+        id,
+        0,
+        &itimerspec(Duration::from_millis(100), Duration::from_millis(100)),
+    )?;
+
+    // Sleep for 150 ms.
+    std::thread::sleep(Duration::from_millis(150));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    ensure_ord!(LAST_SIGNO.load(Ordering::Relaxed), ==, libc::SIGUSR1);
+    ensure_ord!(LAST_VALUE.load(Ordering::Relaxed), ==, 1234);
+
+    // Sleep another 100 ms, which should put us at about 250ms since setting the timer.
+    std::thread::sleep(Duration::from_millis(100));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 2);
+
+    // Each signal was delivered, so there were no overruns.
+    ensure_ord!(timer_getoverrun(id)?, ==, 0);
+
+    // No more signals after the timer is deleted.
+    timer_delete(id)?;
+    std::thread::sleep(Duration::from_millis(200));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 2);
+
+    Ok(())
+}
+
+fn test_abstime() -> anyhow::Result<()> {
+    reset();
+
+    let sev = sigevent(libc::SIGEV_SIGNAL, Signal::SIGUSR1, 0, 0);
+    let id = timer_create(libc::CLOCK_MONOTONIC, Some(&sev))?;
+
+    let mut now: libc::timespec = unsafe { std::mem::zeroed() };
+    nix::errno::Errno::result(unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) })?;
+    let expire = TimeSpec::from(now) + TimeSpec::milliseconds(100);
+    let spec = libc::itimerspec {
+        it_value: *expire.as_ref(),
+        it_interval: *TimeSpec::zero().as_ref(),
+    };
+    timer_settime(id, libc::TIMER_ABSTIME, &spec)?;
+
+    // The remaining time is relative.
+    let val = timer_gettime(id)?;
+    ensure_ord!(val.value, >, TimeSpec::zero());
+    ensure_ord!(val.value, <=, TimeSpec::milliseconds(100));
+
+    std::thread::sleep(Duration::from_millis(50));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 0);
+    std::thread::sleep(Duration::from_millis(100));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+
+    timer_delete(id)?;
+    Ok(())
+}
+
+fn test_overrun() -> anyhow::Result<()> {
+    reset();
+
+    let mut sigset = SigSet::empty();
+    sigset.add(Signal::SIGUSR2);
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_BLOCK, Some(&sigset), None)?;
+
+    let sev = sigevent(libc::SIGEV_SIGNAL, Signal::SIGUSR2, 0, 0);
+    let id = timer_create(libc::CLOCK_MONOTONIC, Some(&sev))?;
+    timer_settime(
+        id,
+        0,
+        &itimerspec(Duration::from_millis(10), Duration::from_millis(10)),
+    )?;
+
+    // The timer expires about 10 times while its signal is blocked.
+    std::thread::sleep(Duration::from_millis(105));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 0);
+
+    // Deliver the signal. Re-arming the timer would reset the overrun count, so we leave it
+    // running.
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_UNBLOCK, Some(&sigset), None)?;
+
+    // Only one signal was delivered, and the other expirations were counted as overruns.
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    ensure_ord!(LAST_SIGNO.load(Ordering::Relaxed), ==, libc::SIGUSR2);
+    let overrun = timer_getoverrun(id)?;
+    ensure_ord!(overrun, >=, 5);
+    ensure_ord!(overrun, <=, 15);
+
+    timer_delete(id)?;
+    Ok(())
+}
+
+fn test_thread_id() -> anyhow::Result<()> {
+    reset();
+
+    let (tid_sender, tid_receiver) = std::sync::mpsc::channel();
+    let thread = std::thread::spawn(move || {
+        tid_sender.send(nix::unistd::gettid().as_raw()).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+    });
+    let tid = tid_receiver.recv()?;
+
+    let sev = sigevent(libc::SIGEV_THREAD_ID, Signal::SIGUSR1, 0, tid);
+    let id = timer_create(libc::CLOCK_MONOTONIC, Some(&sev))?;
+    timer_settime(
+        id,
+        0,
+        &itimerspec(Duration::from_millis(50), Duration::ZERO),
+    )?;
+
+    thread.join().unwrap();
+
+    // The signal was handled by the target thread.
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    ensure_ord!(LAST_TID.load(Ordering::Relaxed), ==, tid);
+
+    timer_delete(id)?;
+    Ok(())
+}
+
+fn test_sigev_none() -> anyhow::Result<()> {
+    reset();
+
+    let sev = sigevent(libc::SIGEV_NONE, Signal::SIGUSR1, 0, 0);
+    let id = timer_create(libc::CLOCK_MONOTONIC, Some(&sev))?;
+    timer_settime(
+        id,
+        0,
+        &itimerspec(Duration::from_millis(10), Duration::from_millis(10)),
+    )?;
+
+    std::thread::sleep(Duration::from_millis(55));
+
+    // No signals, but the timer keeps running.
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 0);
+    let val = timer_gettime(id)?;
+    ensure_ord!(val.value, >, TimeSpec::zero());
+    ensure_ord!(val.value, <=, TimeSpec::milliseconds(10));
+    ensure_ord!(val.interval, ==, TimeSpec::milliseconds(10));
+
+    timer_delete(id)?;
+    Ok(())
+}
+
+fn test_leave_running() -> anyhow::Result<()> {
+    let id = timer_create(libc::CLOCK_MONOTONIC, None)?;
+    timer_settime(
+        id,
+        0,
+        &itimerspec(Duration::from_secs(1), Duration::from_secs(1)),
+    )?;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    // Install a handler that records the signals it receives.
+    for signal in [Signal::SIGALRM, Signal::SIGUSR1, Signal::SIGUSR2] {
+        unsafe {
+            nix::sys::signal::sigaction(
+                signal,
+                &SigAction::new(
+                    SigHandler::SigAction(handler),
+                    SaFlags::SA_SIGINFO | SaFlags::SA_RESTART,
+                    SigSet::empty(),
+                ),
+            )
+            .unwrap()
+        };
+    }
+
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let all_envs = set![TestEnvironment::Libc, TestEnvironment::Shadow];
+    let mut tests: Vec<test_utils::ShadowTest<(), anyhow::Error>> = vec![
+        ShadowTest::new(
+            "create_then_delete",
+            test_create_then_delete,
+            all_envs.clone(),
+        ),
+        ShadowTest::new("invalid_args", test_invalid_args, all_envs.clone()),
+        ShadowTest::new("set_then_get", test_set_then_get, all_envs.clone()),
+        ShadowTest::new(
+            "default_notification",
+            test_default_notification,
+            all_envs.clone(),
+        ),
+        ShadowTest::new("interval", test_interval, all_envs.clone()),
+        ShadowTest::new("abstime", test_abstime, all_envs.clone()),
+        ShadowTest::new("overrun", test_overrun, all_envs.clone()),
+        ShadowTest::new("thread_id", test_thread_id, all_envs.clone()),
+        ShadowTest::new("sigev_none", test_sigev_none, all_envs.clone()),
+        // Must be last.
+        // Validate proper cleanup for a timer that's still running when the
+        // process exits.
+        ShadowTest::new("leave_running", test_leave_running, all_envs),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    Ok(())
+}
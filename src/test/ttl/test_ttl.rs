@@ -0,0 +1,323 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the TTL handling of the network. The path between the client and server hosts crosses
+//! three routers (one for each node of the network graph), which each decrement the TTL.
+
+use std::net::{Ipv4Addr, SocketAddrV4, ToSocketAddrs, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+const SERVER_PORT: u16 = 8000;
+const DEFAULT_TTL: libc::c_int = 64;
+
+/// The addresses of the routers on the path from the client to the server.
+const ROUTERS: [Ipv4Addr; 3] = [
+    Ipv4Addr::new(192, 0, 2, 1),
+    Ipv4Addr::new(192, 0, 2, 2),
+    Ipv4Addr::new(192, 0, 2, 3),
+];
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+
+/// The size of the IPv4 header.
+const IP_HEADER_LEN: usize = 20;
+
+fn main() -> anyhow::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("server") => server(),
+        Some("client") => client(),
+        _ => anyhow::bail!("Expected 'server' or 'client' argument"),
+    }
+}
+
+/// Reply to each datagram with the TTL that it was received with.
+fn server() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", SERVER_PORT))?;
+    set_int_sockopt(socket.as_raw_fd(), libc::IP_RECVTTL, 1)?;
+    assert_eq!(int_sockopt(socket.as_raw_fd(), libc::IP_RECVTTL)?, 1);
+
+    loop {
+        let (ttl, src) = recv_with_ttl(socket.as_raw_fd())?;
+        println!("Received a datagram with TTL {ttl} from {src}");
+        socket.send_to(&ttl.to_ne_bytes(), src)?;
+    }
+}
+
+fn client() -> anyhow::Result<()> {
+    let server_addr = ("server", SERVER_PORT).to_socket_addrs()?.next().unwrap();
+    let std::net::SocketAddr::V4(server_addr) = server_addr else {
+        anyhow::bail!("Expected an IPv4 address for the server");
+    };
+
+    test_udp(server_addr)?;
+    test_traceroute(*server_addr.ip())?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn test_udp(server_addr: SocketAddrV4) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(server_addr)?;
+    socket.set_nonblocking(true)?;
+    let fd = socket.as_raw_fd();
+
+    assert_eq!(int_sockopt(fd, libc::IP_TTL)?, DEFAULT_TTL);
+    assert_eq!(int_sockopt(fd, libc::IP_RECVTTL)?, 0);
+
+    // invalid TTLs are rejected
+    for ttl in [0, 256, -2] {
+        assert_eq!(set_int_sockopt(fd, libc::IP_TTL, ttl), Err(Errno::EINVAL));
+    }
+
+    // each of the routers decrements the TTL
+    let routers = ROUTERS.len() as libc::c_int;
+    assert_eq!(udp_round_trip(&socket)?, Some(DEFAULT_TTL - routers));
+
+    set_int_sockopt(fd, libc::IP_TTL, routers + 1)?;
+    assert_eq!(int_sockopt(fd, libc::IP_TTL)?, routers + 1);
+    assert_eq!(udp_round_trip(&socket)?, Some(1));
+
+    // the last router drops the datagram
+    set_int_sockopt(fd, libc::IP_TTL, routers)?;
+    assert_eq!(udp_round_trip(&socket)?, None);
+
+    // -1 restores the default
+    set_int_sockopt(fd, libc::IP_TTL, -1)?;
+    assert_eq!(int_sockopt(fd, libc::IP_TTL)?, DEFAULT_TTL);
+    assert_eq!(udp_round_trip(&socket)?, Some(DEFAULT_TTL - routers));
+
+    Ok(())
+}
+
+/// Send a datagram to the server and return the TTL that the server received it with, or `None`
+/// if the server didn't reply.
+fn udp_round_trip(socket: &UdpSocket) -> anyhow::Result<Option<libc::c_int>> {
+    socket.send(&[0u8; 10])?;
+    std::thread::sleep(Duration::from_secs(1));
+
+    let mut buf = [0u8; 4];
+    match socket.recv(&mut buf) {
+        Ok(len) => {
+            assert_eq!(len, buf.len());
+            Ok(Some(libc::c_int::from_ne_bytes(buf)))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Send echo requests with increasing TTLs, and check that each router on the path replies with a
+/// "time exceeded" error before the server replies with an echo reply.
+fn test_traceroute(server_ip: Ipv4Addr) -> anyhow::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_ICMP) };
+    let fd = Errno::result(fd)?;
+
+    let identifier = 1234;
+
+    for (index, router) in ROUTERS.iter().enumerate() {
+        let ttl = index as libc::c_int + 1;
+        set_int_sockopt(fd, libc::IP_TTL, ttl)?;
+
+        let request = icmp_echo_request(identifier, ttl as u16);
+        send_to(fd, &request, server_ip)?;
+
+        let (packet, src) = recv_from(fd)?;
+        assert_eq!(src, *router);
+
+        let (ip_header, icmp) = packet.split_at(IP_HEADER_LEN);
+        assert_eq!(ip_header[9], libc::IPPROTO_ICMP as u8);
+        assert_eq!(
+            Ipv4Addr::from([ip_header[12], ip_header[13], ip_header[14], ip_header[15]]),
+            *router
+        );
+        // the error crosses the routers between the router and the client
+        assert_eq!(
+            libc::c_int::from(ip_header[8]),
+            DEFAULT_TTL - index as libc::c_int
+        );
+
+        assert_eq!(icmp[0], ICMP_TIME_EXCEEDED);
+        assert_eq!(icmp[1], 0);
+        assert_eq!(checksum(icmp), 0);
+
+        // the error quotes the IP header and the header of the echo request
+        let quote = &icmp[8..];
+        assert_eq!(quote.len(), IP_HEADER_LEN + 8);
+        assert_eq!(quote[9], libc::IPPROTO_ICMP as u8);
+        assert_eq!(
+            Ipv4Addr::from([quote[16], quote[17], quote[18], quote[19]]),
+            server_ip
+        );
+        let quoted_icmp = &quote[IP_HEADER_LEN..];
+        assert_eq!(quoted_icmp[0], ICMP_ECHO_REQUEST);
+        assert_eq!(&quoted_icmp[4..8], &request[4..8]);
+    }
+
+    // the echo request reaches the server
+    let ttl = ROUTERS.len() as libc::c_int + 1;
+    set_int_sockopt(fd, libc::IP_TTL, ttl)?;
+    let request = icmp_echo_request(identifier, ttl as u16);
+    send_to(fd, &request, server_ip)?;
+
+    let (packet, src) = recv_from(fd)?;
+    assert_eq!(src, server_ip);
+
+    let (ip_header, icmp) = packet.split_at(IP_HEADER_LEN);
+    assert_eq!(
+        libc::c_int::from(ip_header[8]),
+        DEFAULT_TTL - ROUTERS.len() as libc::c_int
+    );
+    assert_eq!(icmp[0], ICMP_ECHO_REPLY);
+    assert_eq!(checksum(icmp), 0);
+    assert_eq!(&icmp[4..], &request[4..]);
+
+    unsafe { libc::close(fd) };
+    Ok(())
+}
+
+/// Receive a datagram, and return the TTL from its `IP_TTL` control message and its source
+/// address.
+fn recv_with_ttl(fd: libc::c_int) -> anyhow::Result<(libc::c_int, SocketAddrV4)> {
+    let mut buf = vec![0u8; 65536];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // use a u64 array so that the buffer is aligned for a cmsghdr
+    let mut control = [0u64; 8];
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_name = std::ptr::from_mut(&mut addr).cast();
+    msg.msg_namelen = std::mem::size_of_val(&addr) as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control);
+
+    Errno::result(unsafe { libc::recvmsg(fd, &mut msg, 0) })?;
+
+    let mut ttl = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        if hdr.cmsg_level == libc::IPPROTO_IP && hdr.cmsg_type == libc::IP_TTL {
+            let data = unsafe { libc::CMSG_DATA(cmsg) } as *const libc::c_int;
+            ttl = Some(unsafe { data.read_unaligned() });
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    let src = SocketAddrV4::new(
+        Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+        u16::from_be(addr.sin_port),
+    );
+    Ok((ttl.expect("Missing IP_TTL control message"), src))
+}
+
+fn send_to(fd: libc::c_int, buf: &[u8], addr: Ipv4Addr) -> Result<(), Errno> {
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as libc::sa_family_t,
+        sin_port: 0,
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(addr).to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let rv = unsafe {
+        libc::sendto(
+            fd,
+            buf.as_ptr().cast(),
+            buf.len(),
+            0,
+            std::ptr::from_ref(&addr).cast(),
+            std::mem::size_of_val(&addr) as libc::socklen_t,
+        )
+    };
+    assert_eq!(Errno::result(rv)? as usize, buf.len());
+    Ok(())
+}
+
+/// Receive a packet, returning the packet and the source address.
+fn recv_from(fd: libc::c_int) -> Result<(Vec<u8>, Ipv4Addr), Errno> {
+    let mut buf = vec![0u8; 1024];
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    let rv = unsafe {
+        libc::recvfrom(
+            fd,
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+            0,
+            std::ptr::from_mut(&mut addr).cast(),
+            &mut addr_len,
+        )
+    };
+    let len = Errno::result(rv)? as usize;
+    buf.truncate(len);
+
+    Ok((buf, Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))))
+}
+
+/// The internet checksum (RFC 1071) of `buf`.
+fn checksum(buf: &[u8]) -> u16 {
+    let mut sum: u32 = buf
+        .chunks(2)
+        .map(|x| u32::from(u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// An echo request with a valid checksum.
+fn icmp_echo_request(identifier: u16, sequence: u16) -> Vec<u8> {
+    let mut message = vec![ICMP_ECHO_REQUEST, 0, 0, 0];
+    message.extend_from_slice(&identifier.to_be_bytes());
+    message.extend_from_slice(&sequence.to_be_bytes());
+    message.extend_from_slice(b"traceroute");
+
+    let checksum = checksum(&message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+    message
+}
+
+fn int_sockopt(fd: libc::c_int, opt: libc::c_int) -> Result<libc::c_int, Errno> {
+    let mut val: libc::c_int = 0;
+    let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+    let rv = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            opt,
+            std::ptr::from_mut(&mut val).cast(),
+            &mut len,
+        )
+    };
+    Errno::result(rv)?;
+    assert_eq!(len as usize, std::mem::size_of_val(&val));
+    Ok(val)
+}
+
+fn set_int_sockopt(fd: libc::c_int, opt: libc::c_int, val: libc::c_int) -> Result<(), Errno> {
+    let rv = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            opt,
+            std::ptr::from_ref(&val).cast(),
+            std::mem::size_of_val(&val) as libc::socklen_t,
+        )
+    };
+    Errno::result(rv).map(|_| ())
+}
@@ -0,0 +1,204 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+//! Tests the emulated TUN device. A UDP datagram sent to the TUN interface's subnet is read from
+//! the TUN file, and a reply written to the TUN file is received by the UDP socket.
+
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+/// `_IOW('T', 202, int)`, from `linux/if_tun.h`.
+const TUNSETIFF: libc::c_ulong = 0x400454ca;
+
+const TUN_ADDR: Ipv4Addr = Ipv4Addr::new(10, 99, 0, 1);
+const PEER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 99, 0, 2);
+const PEER_PORT: u16 = 5000;
+
+/// A `struct ifreq`. The union following the name is kept as raw bytes.
+#[repr(C, align(8))]
+#[derive(Copy, Clone)]
+struct IfReq {
+    name: [u8; libc::IFNAMSIZ],
+    data: [u8; 24],
+}
+
+impl IfReq {
+    fn new(name: &str) -> Self {
+        let mut req = Self {
+            name: [0; libc::IFNAMSIZ],
+            data: [0; 24],
+        };
+        req.name[..name.len()].copy_from_slice(name.as_bytes());
+        req
+    }
+
+    fn with_short(name: &str, val: libc::c_short) -> Self {
+        let mut req = Self::new(name);
+        req.data[..2].copy_from_slice(&val.to_ne_bytes());
+        req
+    }
+
+    /// A `struct sockaddr_in` with port 0.
+    fn with_ipv4(name: &str, addr: Ipv4Addr) -> Self {
+        let mut req = Self::new(name);
+        req.data[..2].copy_from_slice(&(libc::AF_INET as libc::sa_family_t).to_ne_bytes());
+        req.data[4..8].copy_from_slice(&addr.octets());
+        req
+    }
+
+    fn name(&self) -> &str {
+        let len = self.name.iter().position(|x| *x == 0).unwrap();
+        std::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    fn short(&self) -> libc::c_short {
+        libc::c_short::from_ne_bytes(self.data[..2].try_into().unwrap())
+    }
+}
+
+fn ioctl(fd: libc::c_int, request: libc::c_ulong, req: &mut IfReq) -> std::io::Result<()> {
+    let rv = unsafe { libc::ioctl(fd, request, std::ptr::from_mut(req)) };
+    if rv < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = std::ffi::CString::new("/dev/net/tun").unwrap();
+    let tun_fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_NONBLOCK) };
+    assert!(tun_fd >= 0);
+
+    // create the interface, letting the kernel choose its name
+    let flags = (libc::IFF_TUN | libc::IFF_NO_PI) as libc::c_short;
+    let mut req = IfReq::with_short("tun%d", flags);
+    ioctl(tun_fd, TUNSETIFF, &mut req)?;
+    assert_eq!(req.name(), "tun0");
+
+    // the file can only be attached to one interface
+    let mut req = IfReq::with_short("tun1", flags);
+    assert!(ioctl(tun_fd, TUNSETIFF, &mut req).is_err());
+
+    // configure the interface
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let fd = socket.as_raw_fd();
+    ioctl(
+        fd,
+        libc::SIOCSIFADDR,
+        &mut IfReq::with_ipv4("tun0", TUN_ADDR),
+    )?;
+    let netmask = Ipv4Addr::new(255, 255, 255, 0);
+    ioctl(
+        fd,
+        libc::SIOCSIFNETMASK,
+        &mut IfReq::with_ipv4("tun0", netmask),
+    )?;
+    let up = libc::IFF_UP as libc::c_short;
+    ioctl(fd, libc::SIOCSIFFLAGS, &mut IfReq::with_short("tun0", up))?;
+
+    let mut req = IfReq::new("tun0");
+    ioctl(fd, libc::SIOCGIFFLAGS, &mut req)?;
+    assert_ne!(req.short() & up, 0);
+    assert_ne!(req.short() & libc::IFF_POINTOPOINT as libc::c_short, 0);
+
+    // shadow doesn't allow reconfiguring the internet interface
+    let mut req = IfReq::with_short("eth0", up);
+    assert!(ioctl(fd, libc::SIOCSIFFLAGS, &mut req).is_err());
+
+    // a datagram sent to the interface's subnet is routed to the interface
+    socket.send_to(b"hello", SocketAddrV4::new(PEER_ADDR, PEER_PORT))?;
+    let local_port = socket.local_addr()?.port();
+
+    let packet = read_packet(tun_fd)?;
+    assert_eq!(packet[0], 0x45);
+    assert_eq!(
+        usize::from(u16::from_be_bytes([packet[2], packet[3]])),
+        packet.len()
+    );
+    assert_eq!(packet[9], libc::IPPROTO_UDP as u8);
+    assert_eq!(internet_checksum(&packet[..20]), 0);
+    assert_eq!(packet[16..20], PEER_ADDR.octets());
+
+    let src = Ipv4Addr::from(<[u8; 4]>::try_from(&packet[12..16]).unwrap());
+    let udp = &packet[20..];
+    assert_eq!(u16::from_be_bytes([udp[0], udp[1]]), local_port);
+    assert_eq!(u16::from_be_bytes([udp[2], udp[3]]), PEER_PORT);
+    assert_eq!(&udp[8..], b"hello");
+
+    // a reply written to the interface is received by the socket
+    let reply = udp_packet(
+        SocketAddrV4::new(PEER_ADDR, PEER_PORT),
+        SocketAddrV4::new(src, local_port),
+        b"world",
+    );
+    let rv = unsafe { libc::write(tun_fd, reply.as_ptr().cast(), reply.len()) };
+    assert_eq!(rv, reply.len() as isize);
+
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut buf = [0u8; 100];
+    let (len, from) = socket.recv_from(&mut buf)?;
+    assert_eq!(&buf[..len], b"world");
+    assert_eq!(from, SocketAddrV4::new(PEER_ADDR, PEER_PORT).into());
+
+    unsafe { libc::close(tun_fd) };
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Read a packet from the non-blocking TUN file, waiting up to a second.
+fn read_packet(fd: libc::c_int) -> std::io::Result<Vec<u8>> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let rv = unsafe { libc::poll(&mut pollfd, 1, 1000) };
+    assert_eq!(rv, 1);
+
+    let mut buf = vec![0u8; 2000];
+    let rv = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+    if rv < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    buf.truncate(rv as usize);
+    Ok(buf)
+}
+
+/// An IPv4 UDP packet without a UDP checksum.
+fn udp_packet(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    let total_len = u16::try_from(20 + 8 + payload.len()).unwrap();
+    let udp_len = total_len - 20;
+
+    let mut packet = vec![0x45, 0];
+    packet.extend(total_len.to_be_bytes());
+    packet.extend([0, 0, 0x40, 0, 64, libc::IPPROTO_UDP as u8, 0, 0]);
+    packet.extend(src.ip().octets());
+    packet.extend(dst.ip().octets());
+
+    let checksum = internet_checksum(&packet);
+    packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    packet.extend(src.port().to_be_bytes());
+    packet.extend(dst.port().to_be_bytes());
+    packet.extend(udp_len.to_be_bytes());
+    packet.extend([0, 0]);
+    packet.extend(payload);
+
+    packet
+}
+
+/// The internet checksum (RFC 1071) of an even number of bytes.
+fn internet_checksum(bytes: &[u8]) -> u16 {
+    let mut sum: u32 = bytes
+        .chunks(2)
+        .map(|x| u32::from(u16::from_be_bytes([x[0], x[1]])))
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}